@@ -28,6 +28,9 @@ enum Commands {
         /// Base URL for download links in index (e.g., <http://localhost:8680>)
         #[arg(long, value_name = "URL")]
         base_url: String,
+        /// Search endpoint to declare in the index for `sps2 search --remote`
+        #[arg(long, value_name = "URL")]
+        search_url: Option<String>,
         /// Minisign secret key path
         #[arg(long, value_name = "PATH")]
         key: PathBuf,
@@ -44,6 +47,9 @@ enum Commands {
         /// Base URL for download links in index
         #[arg(long, value_name = "URL")]
         base_url: String,
+        /// Search endpoint to declare in the index for `sps2 search --remote`
+        #[arg(long, value_name = "URL")]
+        search_url: Option<String>,
         /// Minisign secret key path
         #[arg(long, value_name = "PATH")]
         key: PathBuf,
@@ -84,15 +90,17 @@ async fn main() -> Result<(), Error> {
             package,
             repo_dir,
             base_url,
+            search_url,
             key,
             pass,
-        } => publish_one(package, repo_dir, base_url, key, pass).await?,
+        } => publish_one(package, repo_dir, base_url, search_url, key, pass).await?,
         Commands::UpdateIndices {
             repo_dir,
             base_url,
+            search_url,
             key,
             pass,
-        } => update_indices(repo_dir, base_url, key, pass).await?,
+        } => update_indices(repo_dir, base_url, search_url, key, pass).await?,
         Commands::RepoInit {
             repo_dir,
             pubkey,
@@ -127,6 +135,7 @@ async fn publish_one(
     package: PathBuf,
     repo_dir: PathBuf,
     base_url: String,
+    search_url: Option<String>,
     key: PathBuf,
     pass: Option<String>,
 ) -> Result<(), Error> {
@@ -161,12 +170,13 @@ async fn publish_one(
     }
 
     // Rebuild and sign index
-    update_indices(repo_dir, base_url, key, pass_final).await
+    update_indices(repo_dir, base_url, search_url, key, pass_final).await
 }
 
 async fn update_indices(
     repo_dir: PathBuf,
     base_url: String,
+    search_url: Option<String>,
     key: PathBuf,
     pass: Option<String>,
 ) -> Result<(), Error> {
@@ -179,7 +189,7 @@ async fn update_indices(
         pass
     };
     let store = LocalStore::new(&repo_dir);
-    let publisher = Publisher::new(store, base_url);
+    let publisher = Publisher::new(store, base_url).with_search_url(search_url);
     let artifacts = publisher.scan_packages_local_dir(&repo_dir).await?;
     let index = publisher.build_index(&artifacts);
     publisher
@@ -4,8 +4,8 @@
 //! A simple ls-like tool to explore the content-addressed store
 
 use clap::Parser;
-use sps2_config::fixed_paths;
 use sps2_state::create_pool;
+use sps2_types::{collate, ColorChoice};
 use sqlx::Acquire;
 
 use std::collections::HashMap;
@@ -32,11 +32,11 @@ struct Cli {
     #[arg(short = 'R', long)]
     recursive: bool,
 
-    /// Store path (defaults to /opt/pm/store)
+    /// Store path (defaults to the configured store, usually /opt/pm/store)
     #[arg(long)]
     store: Option<PathBuf>,
 
-    /// Database path (defaults to /opt/pm/state.sqlite)
+    /// Database path (defaults to the configured one, usually /opt/pm/state.sqlite)
     #[arg(long)]
     db: Option<PathBuf>,
 
@@ -44,7 +44,7 @@ struct Cli {
     #[arg(short, long)]
     all: bool,
 
-    /// Disable colored output
+    /// Disable colored output (colors also follow NO_COLOR and CLICOLOR_FORCE)
     #[arg(long)]
     no_color: bool,
 
@@ -64,13 +64,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let cli = Cli::parse();
 
-    let store_path = cli
-        .store
-        .unwrap_or_else(|| PathBuf::from(fixed_paths::STORE_DIR));
-    let db_path = cli
-        .db
-        .unwrap_or_else(|| PathBuf::from(fixed_paths::DB_PATH));
-    let use_color = !cli.no_color && std::io::stdout().is_terminal();
+    let config = sps2_config::Config::load().await.unwrap_or_default();
+    let store_path = cli.store.unwrap_or_else(|| config.store_path());
+    let db_path = cli.db.unwrap_or_else(|| config.db_path());
+    let color = if cli.no_color {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Auto
+    };
+    let use_color = color.enabled(std::io::stdout().is_terminal());
 
     if cli.packages {
         // List packages instead of objects
@@ -187,7 +189,7 @@ async fn list_store(
             }
         }
 
-        dirs.sort();
+        dirs.sort_by(|a, b| collate::natural_cmp(a, b));
         for dir in dirs {
             println!("{}/", style_blue(&dir, use_color));
         }
@@ -213,8 +215,12 @@ async fn list_recursive(
         items.push(entry);
     }
 
-    // Sort entries
-    items.sort_by_key(|e| e.file_name());
+    items.sort_by(|a, b| {
+        collate::natural_cmp(
+            &a.file_name().to_string_lossy(),
+            &b.file_name().to_string_lossy(),
+        )
+    });
 
     for entry in items {
         let metadata = entry.metadata().await?;
@@ -323,7 +329,7 @@ async fn list_specific(
                         dirs.push(e.file_name().to_string_lossy().to_string());
                     }
                 }
-                dirs.sort();
+                dirs.sort_by(|a, b| collate::natural_cmp(a, b));
                 if recursive {
                     for d in dirs {
                         println!("{}/", style_blue(&d, use_color));
@@ -358,7 +364,7 @@ async fn list_specific(
                 if dirs.is_empty() {
                     eprintln!("No objects found with prefix '{path_or_hash}'");
                 } else {
-                    dirs.sort();
+                    dirs.sort_by(|a, b| collate::natural_cmp(a, b));
                     if recursive {
                         for d in dirs {
                             println!("{}/", style_blue(&d, use_color));
@@ -609,8 +615,12 @@ async fn list_packages(
         }
     }
 
-    // Sort by name
-    packages.sort_by_key(|e| e.file_name());
+    packages.sort_by(|a, b| {
+        collate::natural_cmp(
+            &a.file_name().to_string_lossy(),
+            &b.file_name().to_string_lossy(),
+        )
+    });
 
     for entry in packages {
         let hash = entry.file_name().to_string_lossy().to_string();
@@ -644,7 +654,7 @@ async fn list_packages(
                     files.push(pkg_entry.file_name().to_string_lossy().to_string());
                 }
 
-                files.sort();
+                files.sort_by(|a, b| collate::natural_cmp(a, b));
                 for file in files {
                     println!("  {}", style_green(&file, use_color));
                 }
@@ -725,7 +735,7 @@ async fn list_specific_package(
                         files.push(pkg_entry.file_name().to_string_lossy().to_string());
                     }
 
-                    files.sort();
+                    files.sort_by(|a, b| collate::natural_cmp(a, b));
                     for file in files {
                         println!("  {}", style_green(&file, use_color));
                     }
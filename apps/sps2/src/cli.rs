@@ -1,7 +1,8 @@
 //! Command line interface definition
 
 use clap::{Parser, Subcommand};
-use sps2_types::ColorChoice;
+use sps2_ops::DependentsPolicy;
+use sps2_types::{CacheKind, ColorChoice, ConflictPolicy, SbomFormat, Shell};
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -34,6 +35,10 @@ pub struct GlobalArgs {
     #[arg(long, global = true, value_enum)]
     pub color: Option<ColorChoice>,
 
+    /// Show table cells in full instead of shortening them to the terminal width
+    #[arg(long, global = true)]
+    pub wide: bool,
+
     /// Use alternate config file
     #[arg(long, global = true, value_name = "PATH")]
     pub config: Option<PathBuf>,
@@ -42,14 +47,29 @@ pub struct GlobalArgs {
     #[arg(long, global = true, value_name = "PATH")]
     pub builder_config: Option<PathBuf>,
 
+    /// Operate on the installation under an alternate root (also `SPS2_ROOT`)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub root: Option<PathBuf>,
+
     /// Show what would be done without executing (like ansible --check)
     #[arg(long, global = true)]
     pub check: bool,
+
+    /// Use only the local index cache and store; never touch the network
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Apply changes without asking for confirmation
+    #[arg(short = 'y', long, global = true)]
+    pub yes: bool,
 }
 
 /// Available commands
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Set up a fresh installation, or check and repair an existing one
+    Init,
+
     /// Install packages from repository or local files
     #[command(alias = "i")]
     Install {
@@ -59,6 +79,31 @@ pub enum Commands {
         /// Force re-download even if package exists in cache
         #[arg(long)]
         force_download: bool,
+
+        /// Only install artifacts whose blake3 hash is pinned in FILE
+        /// (lines of `name blake3:<hex>`)
+        #[arg(long, value_name = "FILE")]
+        require_hashes: Option<PathBuf>,
+
+        /// Install from the repository copied to DIR (index.json signed by a
+        /// trusted key, with the .sp and .minisig files) instead of the
+        /// configured repositories
+        #[arg(long, value_name = "DIR")]
+        from_dir: Option<PathBuf>,
+
+        /// Leave out the packages they recommend
+        #[arg(long)]
+        no_recommends: bool,
+
+        /// What to do when packages would install different files at the
+        /// same path (overrides `conflict_policy` in config.toml)
+        #[arg(long, value_enum, value_name = "POLICY")]
+        conflict_policy: Option<ConflictPolicy>,
+
+        /// Report the exact plan (downloads, state changes, disk usage)
+        /// without changing anything
+        #[arg(long)]
+        dry_run: bool,
     },
 
     /// Update packages to newer compatible versions
@@ -66,6 +111,16 @@ pub enum Commands {
     Update {
         /// Specific packages to update (empty = all packages)
         packages: Vec<String>,
+
+        /// Report the exact plan (downloads, state changes, disk usage)
+        /// without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Update even if installed packages would be left with a version
+        /// of a dependency they do not accept
+        #[arg(long)]
+        force: bool,
     },
 
     /// Upgrade packages to latest versions (ignore upper bounds)
@@ -73,6 +128,21 @@ pub enum Commands {
     Upgrade {
         /// Specific packages to upgrade (empty = all packages)
         packages: Vec<String>,
+
+        /// Report the exact plan (downloads, state changes, disk usage)
+        /// without changing anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Upgrade even if installed packages would be left with a version
+        /// of a dependency they do not accept
+        #[arg(long)]
+        force: bool,
+
+        /// Afterwards, remove packages installed only as dependencies or
+        /// recommendations that nothing installed needs any more
+        #[arg(long)]
+        autoremove: bool,
     },
 
     /// Uninstall packages
@@ -80,6 +150,82 @@ pub enum Commands {
     Uninstall {
         /// Package names to uninstall
         packages: Vec<String>,
+
+        /// Also remove the installed packages that depend on them
+        #[arg(long, conflicts_with = "force")]
+        cascade: bool,
+
+        /// Remove them even if installed packages depend on them
+        #[arg(long)]
+        force: bool,
+
+        /// Also remove packages installed only as dependencies or
+        /// recommendations that nothing left installed needs or recommends
+        #[arg(long)]
+        autoremove: bool,
+
+        /// Report the exact plan (state changes, disk usage) without
+        /// changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Remove packages installed only as dependencies or recommendations
+    /// that nothing installed needs or recommends any more
+    Autoremove {
+        /// Report the exact plan (state changes, disk usage) without
+        /// changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Install, remove and upgrade packages in one state transition
+    ///
+    /// The change set is a TOML file listing `install` specs, `remove` and
+    /// `upgrade` names, or `upgrade_all = true`. It is resolved as a whole
+    /// and either applied completely or not at all.
+    Apply {
+        /// Change set file
+        file: PathBuf,
+
+        /// Apply even if installed packages would be left with a version
+        /// of a dependency they do not accept, or without one they need
+        #[arg(long)]
+        force: bool,
+
+        /// Report the exact plan (state changes, disk usage) without
+        /// changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Make the installed packages match a manifest in one state transition
+    ///
+    /// The manifest is a TOML file listing `packages` specs. Missing ones
+    /// are installed, and every other installed package that they do not
+    /// need is removed. With `--check`, the command exits with an error if
+    /// anything would change. Named apart from `sync`, which is short for
+    /// `reposync`.
+    #[command(name = "sync-manifest")]
+    Sync {
+        /// Manifest file
+        manifest: PathBuf,
+
+        /// Report the exact plan (state changes, disk usage) without
+        /// changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Reinstall packages at their installed versions
+    ///
+    /// Stages the packages again from the store, downloading them first if
+    /// the stored copy is missing or corrupt. Changed config files under
+    /// `etc/` are kept.
+    Reinstall {
+        /// Package names to reinstall
+        #[arg(required = true)]
+        packages: Vec<String>,
     },
 
     /// Build package from YAML recipe
@@ -98,6 +244,27 @@ pub enum Commands {
         /// Number of parallel build jobs (0=auto)
         #[arg(short, long)]
         jobs: Option<usize>,
+
+        /// First build and install unbuilt dependencies that have recipes,
+        /// removing them again afterwards
+        #[arg(long)]
+        recursive: bool,
+
+        /// Directory to look up dependency recipes in (default: the recipe's)
+        #[arg(long, requires = "recursive")]
+        recipe_dir: Option<PathBuf>,
+
+        /// Build even if a package from identical inputs is cached
+        #[arg(long)]
+        no_cache: bool,
+
+        /// Build on this SSH host instead of locally (overrides builder config)
+        #[arg(long, value_name = "HOST", conflicts_with = "recursive")]
+        remote: Option<String>,
+
+        /// Build for a remote client: stream events as JSON lines, never install
+        #[arg(long, hide = true, conflicts_with_all = ["remote", "recursive"])]
+        worker: bool,
         // Compression-related flags are removed until fully supported
     },
 
@@ -142,30 +309,168 @@ pub enum Commands {
         package: String,
     },
 
+    /// List the files an installed package provides
+    Files {
+        /// Package name
+        package: String,
+
+        /// Show the files as a directory tree
+        #[arg(long)]
+        tree: bool,
+    },
+
+    /// Show which installed package owns a file
+    Owns {
+        /// File, directory or glob under the live prefix (absolute, or
+        /// relative to the prefix)
+        #[arg(required_unless_present = "orphans")]
+        path: Option<String>,
+
+        /// List files that no package owns instead, optionally limited to
+        /// the path
+        #[arg(long)]
+        orphans: bool,
+    },
+
+    /// Show which installed package provides a command
+    Which {
+        /// Command name, as looked up in the live bin directory
+        command: String,
+
+        /// List every executable of that name on PATH, not just those
+        /// shadowing the live one
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Print shell statements that put the live prefix on the search paths
+    ///
+    /// Use as `eval "$(sps2 env)"` in a shell startup file.
+    Env {
+        /// Only set the variables this installed package needs
+        package: Option<String>,
+
+        /// Shell to write statements for; defaults to the one in $SHELL
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+    },
+
+    /// Start a shell in the environment of the project in the current
+    /// directory
+    ///
+    /// The project's packages, listed in sps2.toml or .sps2 in the nearest
+    /// directory up from here, are installed into an environment of their
+    /// own, apart from the live prefix.
+    Shell {
+        /// Run this command in the environment instead of a shell
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+
     /// Search for packages
     #[command(alias = "find")]
     Search {
         /// Search query
         query: String,
+
+        /// Query repository search endpoints instead of only the local index
+        #[arg(long)]
+        remote: bool,
     },
 
     /// Sync repository index
+    ///
+    /// Changes to the repository's signing keys are listed and need
+    /// confirmation; accepted changes are recorded in the audit trail of the
+    /// active state.
     #[command(alias = "sync")]
     Reposync {
-        /// Automatically trust new keys
+        /// Accept changes to the repository's signing keys without asking
         #[clap(long)]
-        yes: bool,
+        accept_keys: bool,
+    },
+
+    /// Copy a repository into a directory for `install --from-dir`
+    ///
+    /// Downloads the signed index and the package files, checking each
+    /// against the index hash and the trusted keys. Packages already in the
+    /// directory are kept, so an interrupted mirror can be run again.
+    Mirror {
+        /// Configured repository: fast, slow, stable or an added name
+        repo: String,
+
+        /// Directory to copy the repository into
+        dest: PathBuf,
+
+        /// Only copy these packages, with their dependencies and
+        /// recommendations (empty = whole repository)
+        packages: Vec<String>,
+    },
+
+    /// Refresh the repository index in the background
+    ///
+    /// Each refresh is recorded so that other commands can report the index
+    /// age and available upgrades without a network call.
+    Daemon {
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 21_600)]
+        interval: u64,
+
+        /// Refresh once and exit
+        #[arg(long)]
+        once: bool,
+
+        /// Print a launchd agent running `sps2 daemon --once` every interval
+        #[arg(long, conflicts_with = "once")]
+        launchd_plist: bool,
     },
 
     /// Clean up orphaned packages and old states
-    Cleanup,
+    #[command(group(
+        clap::ArgGroup::new("cleanup_target").args(&["quarantine", "downloads"]),
+    ))]
+    Cleanup {
+        /// List downloads quarantined after failing hash verification instead
+        #[arg(long)]
+        quarantine: bool,
+
+        /// Prune cached package downloads beyond the retention policy instead
+        #[arg(long)]
+        downloads: bool,
+
+        /// Delete all quarantined or cached downloads
+        #[arg(long, requires = "cleanup_target")]
+        purge: bool,
+    },
+
+    /// Clean individual caches
+    #[command(subcommand)]
+    Clean(CleanCommands),
 
     /// Rollback to previous state
     Rollback {
-        /// Target state ID (empty = previous state)
-        state_id: Option<Uuid>,
+        /// Target state ID or snapshot name (empty = previous state)
+        target: Option<String>,
+
+        /// Show how to restore the local APFS snapshot taken before the
+        /// target state was committed (empty = the latest one) instead of
+        /// rolling back
+        #[arg(long)]
+        os_snapshot: bool,
     },
 
+    /// Name states to roll back to later
+    #[command(subcommand)]
+    Snapshot(SnapshotCommands),
+
+    /// Manage the launchd services installed packages provide
+    #[command(subcommand)]
+    Services(ServicesCommands),
+
+    /// Inspect or move the content-addressed store
+    #[command(subcommand)]
+    Store(StoreCommands),
+
     /// Show state history
     History {
         /// Show all states (no availability filtering)
@@ -179,6 +484,11 @@ pub enum Commands {
         /// Limit number of states for --verify (overrides config)
         #[arg(long, requires = "verify")]
         limit: Option<usize>,
+
+        /// Show one state (ID or snapshot name) with the audit trail of who
+        /// changed it, what was asked for and what it resolved to
+        #[arg(long, value_name = "STATE", conflicts_with_all = ["all", "verify"])]
+        detail: Option<String>,
     },
 
     /// Check system health
@@ -219,16 +529,27 @@ pub enum Commands {
         force: bool,
     },
 
+    /// Manage the sps2 installation itself
+    #[command(name = "self", subcommand)]
+    SelfCommand(SelfCommands),
+
     /// Verify and optionally heal the current state
     Verify {
         /// Automatically heal any discrepancies found
         #[arg(long)]
         heal: bool,
 
-        /// Verification level (quick, standard, full)
+        /// Verification level: quick (re-hash files changed since they last
+        /// verified), standard (quick, and re-hash managed directory copies),
+        /// full (re-hash every file)
         #[arg(long, default_value = "standard")]
         level: String,
 
+        /// Re-hash every file, even if unchanged since it last verified
+        /// (same as --level full)
+        #[arg(long)]
+        force_hash: bool,
+
         /// Verification scope (live, store, all)
         #[arg(long, default_value = "live")]
         scope: String,
@@ -237,6 +558,32 @@ pub enum Commands {
         /// (one-off maintenance; does not change persistent config)
         #[arg(long)]
         sync_refcounts: bool,
+
+        /// Show recorded verification runs and recurring discrepancies instead
+        /// of verifying
+        #[arg(long, conflicts_with_all = ["heal", "force_hash", "sync_refcounts"])]
+        history: bool,
+
+        /// Number of recent runs to show with --history
+        #[arg(long, requires = "history", default_value_t = 20)]
+        limit: usize,
+
+        /// Package to compare with the store (with --diff)
+        #[arg(long, value_name = "NAME", requires = "diff")]
+        package: Option<String>,
+
+        /// Report which files of --package differ from the store (hash
+        /// mismatch, mode change, missing, extra) instead of verifying
+        #[arg(
+            long,
+            requires = "package",
+            conflicts_with_all = ["heal", "force_hash", "sync_refcounts", "history"]
+        )]
+        diff: bool,
+
+        /// Show a unified diff for changed text files (with --diff)
+        #[arg(long, requires = "diff")]
+        unified: bool,
     },
 
     /// Manage repositories
@@ -246,6 +593,124 @@ pub enum Commands {
     /// Manage trusted signing keys
     #[command(subcommand)]
     Keys(KeysCommands),
+
+    /// Software bill of materials for the active state
+    #[command(subcommand)]
+    Sbom(SbomCommands),
+
+    /// JSON Schemas of the reports printed with --json, for tool authors
+    #[command(subcommand)]
+    Schema(SchemaCommands),
+}
+
+/// Cache cleaning subcommands
+#[derive(Subcommand)]
+pub enum CleanCommands {
+    /// List caches with their size, or clear the given ones
+    Cache {
+        /// Caches to clear; without any, caches are only listed
+        #[arg(value_enum)]
+        kinds: Vec<CacheKind>,
+
+        /// Clear every cache
+        #[arg(long, conflicts_with = "kinds")]
+        all: bool,
+    },
+}
+
+/// Subcommands acting on the installation itself
+#[derive(Subcommand)]
+pub enum SelfCommands {
+    /// Remove the installation: live tree, states, database, store, build
+    /// caches, the launchd agent and the PATH line in shell startup files
+    Destruct {
+        /// Leave the store in place so a new installation can reuse it
+        #[arg(long)]
+        keep_store: bool,
+    },
+}
+
+/// State snapshot subcommands
+#[derive(Subcommand)]
+pub enum SnapshotCommands {
+    /// Name the current state, or the one given with --state
+    Create {
+        /// Snapshot name (letters, digits, `.`, `_` and `-`)
+        name: String,
+        /// State ID to name instead of the current state
+        #[arg(long, value_name = "ID")]
+        state: Option<Uuid>,
+    },
+
+    /// List snapshots
+    List,
+
+    /// Delete a snapshot name; the state itself is kept
+    Delete {
+        /// Snapshot name
+        name: String,
+    },
+}
+
+/// Launchd service subcommands
+#[derive(Subcommand)]
+pub enum ServicesCommands {
+    /// List services with whether they are enabled and running
+    List,
+
+    /// Start a service for this session, without enabling it
+    Start {
+        /// Service label
+        label: String,
+    },
+
+    /// Stop a service
+    Stop {
+        /// Service label
+        label: String,
+    },
+
+    /// Restart a service, or start it if it is not running
+    Restart {
+        /// Service label
+        label: String,
+    },
+
+    /// Start a service now and at every login (or boot, as root)
+    Enable {
+        /// Service label
+        label: String,
+    },
+
+    /// Stop a service and keep it from starting at login or boot
+    Disable {
+        /// Service label
+        label: String,
+    },
+}
+
+/// Store subcommands
+#[derive(Subcommand)]
+pub enum StoreCommands {
+    /// Show deduplication statistics and the largest packages
+    Stats {
+        /// Number of packages to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+
+    /// Move the store to another directory or volume
+    Relocate {
+        /// Absolute path of the new store; must not exist or be empty
+        new_path: PathBuf,
+
+        /// Leave the old store in place after the move
+        #[arg(long)]
+        keep_old: bool,
+    },
+
+    /// Remove objects stored twice under different hash algorithms
+    Compact,
 }
 
 /// Repository management subcommands
@@ -293,4 +758,51 @@ pub enum KeysCommands {
     },
 }
 
+/// SBOM subcommands
+#[derive(Subcommand)]
+pub enum SbomCommands {
+    /// Merge package SBOMs into one document for the active state
+    Export {
+        /// Document format
+        #[arg(long, value_enum, default_value_t = SbomFormat::default())]
+        format: SbomFormat,
+        /// Write to this file instead of stdout
+        #[arg(long, short, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Report schema subcommands
+#[derive(Subcommand)]
+pub enum SchemaCommands {
+    /// Print the JSON Schema of one report, or of every report keyed by name
+    Dump {
+        /// Report to print (build-report, install-report,
+        /// verification-result)
+        name: Option<String>,
+    },
+}
+
 impl Commands {}
+
+/// How `uninstall` treats installed packages that need the removed ones
+pub fn dependents_policy(cascade: bool, force: bool) -> DependentsPolicy {
+    if cascade {
+        DependentsPolicy::Cascade
+    } else if force {
+        DependentsPolicy::Force
+    } else {
+        DependentsPolicy::Block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn command_line_definition_is_consistent() {
+        Cli::command().debug_assert();
+    }
+}
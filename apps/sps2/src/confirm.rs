@@ -0,0 +1,125 @@
+//! Confirmation before commands that change installed packages
+//!
+//! Installs, updates, upgrades, uninstalls, autoremoves, applied change
+//! sets, syncs and rollbacks first show the resolved change plan and ask
+//! whether to go ahead. `--yes` or `general.assume_yes` skips the
+//! question; so do check mode, JSON output and a non-interactive stdin, so
+//! scripts behave as before.
+//!
+//! `self destruct` instead wants `yes` typed out, and without `--yes` it
+//! refuses to run where it cannot ask.
+
+use crate::cli::Commands;
+use crate::display::OutputRenderer;
+use crate::error::CliError;
+use sps2_ops::{OperationResult, OpsCtx, PlannedOperation, SelfDestructReport};
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Whether `command` changes installed packages and should be confirmed
+pub fn asks_confirmation(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::Install { dry_run: false, .. }
+            | Commands::Update { dry_run: false, .. }
+            | Commands::Upgrade { dry_run: false, .. }
+            | Commands::Uninstall { dry_run: false, .. }
+            | Commands::Autoremove { dry_run: false }
+            | Commands::Apply { dry_run: false, .. }
+            | Commands::Sync { dry_run: false, .. }
+            | Commands::Rollback {
+                os_snapshot: false,
+                ..
+            }
+    )
+}
+
+/// Whether a prompt can be answered: stdin and stdout are a terminal
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Show what `command` would change and ask whether to go ahead
+///
+/// `ctx` is a check-mode context; its preview events are not shown. Returns
+/// whether the command should run, which it does without asking when it
+/// would change nothing.
+///
+/// # Errors
+///
+/// Returns an error if the plan cannot be worked out or the answer cannot
+/// be read.
+pub async fn confirm(
+    command: &Commands,
+    ctx: &OpsCtx,
+    renderer: &OutputRenderer,
+) -> Result<bool, CliError> {
+    let changes = match command {
+        Commands::Apply { file, .. } => Some(sps2_ops::ChangeSet::load(file).await?),
+        _ => None,
+    };
+    let manifest = match command {
+        Commands::Sync { manifest, .. } => Some(sps2_ops::Manifest::load(manifest).await?),
+        _ => None,
+    };
+    let operation = match command {
+        Commands::Install { packages, .. } => PlannedOperation::Install(packages),
+        Commands::Update {
+            packages, force, ..
+        } => PlannedOperation::Update(packages, *force),
+        Commands::Upgrade {
+            packages, force, ..
+        } => PlannedOperation::Upgrade(packages, *force),
+        Commands::Uninstall {
+            packages,
+            cascade,
+            force,
+            ..
+        } => PlannedOperation::Uninstall(packages, crate::cli::dependents_policy(*cascade, *force)),
+        Commands::Autoremove { .. } => PlannedOperation::Autoremove,
+        Commands::Apply { force, .. } => match &changes {
+            Some(changes) => PlannedOperation::Apply(changes, *force),
+            None => return Ok(true),
+        },
+        Commands::Sync { .. } => match &manifest {
+            Some(manifest) => PlannedOperation::Sync(manifest),
+            None => return Ok(true),
+        },
+        Commands::Rollback { target, .. } => PlannedOperation::Rollback(match target {
+            Some(target) => Some(sps2_ops::resolve_state(ctx, target).await?),
+            None => None,
+        }),
+        _ => return Ok(true),
+    };
+    let plan = sps2_ops::change_plan(ctx, operation).await?;
+    if plan.is_empty() {
+        return Ok(true);
+    }
+
+    renderer.render_change_plan(&plan);
+    print!("Proceed? [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+/// Show what `sps2 self destruct` would remove and ask for a typed `yes`
+///
+/// # Errors
+///
+/// Returns an error if the list cannot be shown or the answer cannot be
+/// read.
+pub fn confirm_self_destruct(
+    plan: &SelfDestructReport,
+    renderer: &OutputRenderer,
+) -> Result<bool, CliError> {
+    renderer.render_result(&OperationResult::SelfDestructReport(plan.clone()))?;
+    print!("This cannot be undone. Type 'yes' to remove everything listed: ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("yes"))
+}
@@ -1,12 +1,17 @@
 //! Output rendering and formatting
 
-use comfy_table::{presets::UTF8_FULL, Attribute, Cell, Color, ContentArrangement, Table};
-use console::{Style, Term};
+use crate::theme::Theme;
+use comfy_table::{presets::UTF8_FULL, Cell, ContentArrangement, Table};
+use console::{measure_text_width, truncate_str, Term};
+use sps2_config::ThemeRole;
 use sps2_ops::{
-    BuildReport, HealthCheck, HealthStatus, InstallReport, IssueSeverity, OperationResult,
-    PackageInfo, PackageStatus, SearchResult, StateInfo,
+    BrokenDependency, BuildReport, ChangePlan, CommandResolution, FileChange, FileOwnership,
+    HealthCheck, HealthStatus, InitReport, InitStatus, InstallReport, IssueSeverity, OperationPlan,
+    OperationResult, PackageChange, PackageDiff, PackageFiles, PackageInfo, PackageStatus,
+    ProjectShell, SearchResult, SelfDestructReport, ServiceStatus, StateDetail, StateInfo,
+    StoreStats, VerificationHistory,
 };
-use sps2_types::ColorChoice;
+use sps2_types::EllipsisPolicy;
 use std::io;
 
 /// Output renderer for CLI results
@@ -14,18 +19,21 @@ use std::io;
 pub struct OutputRenderer {
     /// Use JSON output format
     json_output: bool,
-    /// Color configuration
-    color_choice: ColorChoice,
+    /// Color theme
+    theme: Theme,
+    /// How cells too long for the terminal are shortened
+    ellipsis: EllipsisPolicy,
     /// Terminal instance
     term: Term,
 }
 
 impl OutputRenderer {
     /// Create new output renderer
-    pub fn new(json_output: bool, color_choice: ColorChoice) -> Self {
+    pub fn new(json_output: bool, theme: Theme, ellipsis: EllipsisPolicy) -> Self {
         Self {
             json_output,
-            color_choice,
+            theme,
+            ellipsis,
             term: Term::stdout(),
         }
     }
@@ -54,12 +62,37 @@ impl OutputRenderer {
             OperationResult::SearchResults(results) => self.render_search_results(results),
             OperationResult::InstallReport(report) => self.render_install_report(report),
             OperationResult::BuildReport(report) => self.render_build_report(report),
+            OperationResult::BuildReports(reports) => {
+                for (i, report) in reports.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+                    self.render_build_report(report)?;
+                }
+                Ok(())
+            }
             OperationResult::StateInfo(info) => self.render_state_info(info),
             OperationResult::StateHistory(history) => self.render_state_history(history),
             OperationResult::HealthCheck(health) => self.render_health_check(health),
             OperationResult::Success(message) => self.render_success_message(message),
             OperationResult::Report(report) => self.render_op_report(report),
             OperationResult::VerificationResult(result) => self.render_verification_result(result),
+            OperationResult::StoreStats(stats) => self.render_store_stats(stats),
+            OperationResult::VerificationHistory(history) => {
+                self.render_verification_history(history)
+            }
+            OperationResult::StateDetail(detail) => self.render_state_detail(detail),
+            OperationResult::PackageDiff(diff) => self.render_package_diff(diff),
+            OperationResult::FileOwnership(ownership) => self.render_file_ownership(ownership),
+            OperationResult::PackageFiles(files) => self.render_package_files(files),
+            OperationResult::CommandResolution(resolution) => {
+                self.render_command_resolution(resolution)
+            }
+            OperationResult::Plan(plan) => self.render_operation_plan(plan),
+            OperationResult::InitReport(report) => self.render_init_report(report),
+            OperationResult::SelfDestructReport(report) => self.render_self_destruct_report(report),
+            OperationResult::Services(services) => self.render_services(services),
+            OperationResult::ProjectShell(shell) => Self::render_project_shell(shell),
         }
     }
 
@@ -94,33 +127,35 @@ impl OutputRenderer {
             return Ok(());
         }
 
-        let mut table = Table::new();
-        table
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic);
-
-        // Add headers
-        table.set_header(vec![
-            Cell::new("Package").add_attribute(Attribute::Bold),
-            Cell::new("Version").add_attribute(Attribute::Bold),
-            Cell::new("Status").add_attribute(Attribute::Bold),
-            Cell::new("Description").add_attribute(Attribute::Bold),
-        ]);
-
-        // Add package rows
-        for package in packages {
-            let status_cell = self.format_package_status(&package.status);
-            let version_str = package
-                .version
-                .as_ref()
-                .map(|v| v.to_string())
-                .unwrap_or_else(|| "-".to_string());
-
+        let columns = [
+            ("Package", Fit::Shorten),
+            ("Version", Fit::Shorten),
+            ("Status", Fit::Keep),
+            ("Description", Fit::Wrap),
+        ];
+        let rows: Vec<Vec<String>> = packages
+            .iter()
+            .map(|package| {
+                vec![
+                    package.name.clone(),
+                    package
+                        .version
+                        .as_ref()
+                        .map_or_else(|| "-".to_string(), ToString::to_string),
+                    package_status_label(&package.status).to_string(),
+                    package.description.as_deref().unwrap_or("-").to_string(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (package, row) in packages.iter().zip(&rows) {
             table.add_row(vec![
-                Cell::new(&package.name),
-                Cell::new(version_str),
-                status_cell,
-                Cell::new(package.description.as_deref().unwrap_or("-")),
+                Cell::new(fit.cell(0, &row[0])),
+                Cell::new(fit.cell(1, &row[1])),
+                self.format_package_status(&package.status),
+                Cell::new(fit.cell(3, &row[3])),
             ]);
         }
 
@@ -135,7 +170,7 @@ impl OutputRenderer {
 
         // Basic information
         if let Some(description) = &info.description {
-            println!("Description: {description}");
+            println!("Description: {}", self.wrap_field(description, 13));
         }
 
         if let Some(version) = &info.version {
@@ -184,31 +219,38 @@ impl OutputRenderer {
             return Ok(());
         }
 
-        let mut table = Table::new();
-        table
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic);
-
-        table.set_header(vec![
-            Cell::new("Package").add_attribute(Attribute::Bold),
-            Cell::new("Version").add_attribute(Attribute::Bold),
-            Cell::new("Installed").add_attribute(Attribute::Bold),
-            Cell::new("Description").add_attribute(Attribute::Bold),
-        ]);
-
-        for result in results {
-            let installed_text = if result.installed { "Yes" } else { "No" };
+        let columns = [
+            ("Package", Fit::Shorten),
+            ("Version", Fit::Shorten),
+            ("Installed", Fit::Keep),
+            ("Description", Fit::Wrap),
+        ];
+        let rows: Vec<Vec<String>> = results
+            .iter()
+            .map(|result| {
+                vec![
+                    result.name.clone(),
+                    result.version.to_string(),
+                    if result.installed { "Yes" } else { "No" }.to_string(),
+                    result.description.as_deref().unwrap_or("-").to_string(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (result, row) in results.iter().zip(&rows) {
             let installed_cell = if result.installed {
-                Cell::new(installed_text).fg(Color::Green)
+                self.theme.cell(ThemeRole::Installed, &row[2])
             } else {
-                Cell::new(installed_text)
+                Cell::new(&row[2])
             };
 
             table.add_row(vec![
-                Cell::new(&result.name),
-                Cell::new(result.version.to_string()),
+                Cell::new(fit.cell(0, &row[0])),
+                Cell::new(fit.cell(1, &row[1])),
                 installed_cell,
-                Cell::new(result.description.as_deref().unwrap_or("-")),
+                Cell::new(fit.cell(3, &row[3])),
             ]);
         }
 
@@ -273,12 +315,80 @@ impl OutputRenderer {
             println!();
         }
 
+        print_broken_dependencies(&report.broken_dependencies);
+
         println!("Completed in {}ms", report.duration_ms);
         println!("State: {}", report.state_id);
 
         Ok(())
     }
 
+    /// Render the changes an operation is about to make, before confirmation
+    pub fn render_change_plan(&self, plan: &ChangePlan) {
+        println!("The {} will make these changes:", plan.operation);
+        println!();
+        print_package_changes(plan);
+
+        let sized = |changes: &[PackageChange]| -> u64 {
+            changes.iter().filter_map(|change| change.size).sum()
+        };
+        let added = sized(&plan.install) + sized(&plan.upgrade);
+        let removed = sized(&plan.remove);
+        if added > 0 {
+            println!("Size of packages to install: {}", format_size(added));
+        }
+        if removed > 0 {
+            println!("Size of packages to remove: {}", format_size(removed));
+        }
+    }
+
+    /// Render the result of a dry run
+    fn render_operation_plan(&self, plan: &OperationPlan) -> io::Result<()> {
+        if plan.changes.is_empty() {
+            println!(
+                "Dry run: the {} would change nothing.",
+                plan.changes.operation
+            );
+            return Ok(());
+        }
+
+        println!(
+            "Dry run: the {} would make these changes:",
+            plan.changes.operation
+        );
+        println!();
+        print_package_changes(&plan.changes);
+
+        if !plan.downloads.is_empty() {
+            println!("Download ({}):", plan.downloads.len());
+            for download in &plan.downloads {
+                let size = download
+                    .size
+                    .map_or_else(|| "size unknown".to_string(), format_size);
+                println!("  • {} {} ({size})", download.name, download.version);
+            }
+            println!();
+        }
+
+        let unknown = plan.downloads.iter().filter(|d| d.size.is_none()).count();
+        let unknown = if unknown > 0 {
+            format!(" (plus {unknown} of unknown size)")
+        } else {
+            String::new()
+        };
+        println!(
+            "Download size:  {}{unknown}",
+            format_size(plan.download_bytes)
+        );
+        println!("Leaving live:   {}", format_size(plan.removed_bytes));
+        if let Some(available) = plan.available_bytes {
+            println!("Store free:     {}", format_size(available));
+        }
+        println!("Based on state: {}", plan.state_id);
+        println!("Nothing was changed.");
+        Ok(())
+    }
+
     /// Render build report
     fn render_build_report(&self, report: &BuildReport) -> io::Result<()> {
         println!("Build Summary");
@@ -307,6 +417,9 @@ impl OutputRenderer {
         if let Some(parent) = info.parent {
             println!("Parent:   {parent}");
         }
+        if !info.tags.is_empty() {
+            println!("Snapshots: {}", info.tags.join(", "));
+        }
 
         if !info.changes.is_empty() {
             println!();
@@ -362,6 +475,37 @@ impl OutputRenderer {
         Ok(())
     }
 
+    /// Render a state with its audit trail
+    fn render_state_detail(&self, detail: &StateDetail) -> io::Result<()> {
+        self.render_state_info(&detail.state)?;
+
+        println!();
+        if detail.audit.is_empty() {
+            println!("No audit trail recorded for this state.");
+            return Ok(());
+        }
+        println!("Audit trail:");
+        for entry in &detail.audit {
+            println!();
+            println!(
+                "  {}  {}",
+                entry.timestamp().format("%Y-%m-%d %H:%M:%S UTC"),
+                entry.audit.description
+            );
+            if let Some(actor) = &entry.audit.actor {
+                println!("    By:        {actor}");
+            }
+            if let Some(command_line) = &entry.audit.command_line {
+                println!("    Command:   {command_line}");
+            }
+            if !entry.audit.requested.is_empty() {
+                println!("    Requested: {}", entry.audit.requested.join(", "));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Render state history
     fn render_state_history(&self, history: &[StateInfo]) -> io::Result<()> {
         if history.is_empty() {
@@ -369,34 +513,44 @@ impl OutputRenderer {
             return Ok(());
         }
 
-        let mut table = Table::new();
-        table
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic);
-
-        table.set_header(vec![
-            Cell::new("State ID").add_attribute(Attribute::Bold),
-            Cell::new("Current").add_attribute(Attribute::Bold),
-            Cell::new("Operation").add_attribute(Attribute::Bold),
-            Cell::new("Created").add_attribute(Attribute::Bold),
-            Cell::new("Packages").add_attribute(Attribute::Bold),
-        ]);
-
-        for state in history {
+        let columns = [
+            ("State ID", Fit::Keep),
+            ("Current", Fit::Keep),
+            ("Operation", Fit::Shorten),
+            ("Created", Fit::Keep),
+            ("Packages", Fit::Keep),
+            ("Snapshots", Fit::Shorten),
+        ];
+        let rows: Vec<Vec<String>> = history
+            .iter()
+            .map(|state| {
+                vec![
+                    state.id.to_string(),
+                    if state.current { "*" } else { "" }.to_string(),
+                    state.operation.clone(),
+                    state.timestamp.format("%Y-%m-%d %H:%M").to_string(),
+                    state.package_count.to_string(),
+                    state.tags.join(", "),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (state, row) in history.iter().zip(&rows) {
             let current_cell = if state.current {
-                Cell::new("*")
-                    .fg(Color::Green)
-                    .add_attribute(Attribute::Bold)
+                self.theme.cell(ThemeRole::Current, &row[1])
             } else {
-                Cell::new("")
+                Cell::new(&row[1])
             };
 
             table.add_row(vec![
-                Cell::new(state.id.to_string()),
+                Cell::new(&row[0]),
                 current_cell,
-                Cell::new(&state.operation),
-                Cell::new(state.timestamp.format("%Y-%m-%d %H:%M").to_string()),
-                Cell::new(state.package_count.to_string()),
+                Cell::new(fit.cell(2, &row[2])),
+                Cell::new(&row[3]),
+                Cell::new(&row[4]),
+                Cell::new(fit.cell(5, &row[5])),
             ]);
         }
 
@@ -405,36 +559,230 @@ impl OutputRenderer {
     }
 
     /// Render health check results
+    fn render_init_report(&self, report: &InitReport) -> io::Result<()> {
+        let overall_icon = if report.is_ready() { "[OK]" } else { "[ERROR]" };
+        println!("{overall_icon} Installation at {}", report.prefix.display());
+        println!();
+
+        let columns = [
+            ("Step", Fit::Keep),
+            ("Status", Fit::Keep),
+            ("Detail", Fit::Wrap),
+        ];
+        let rows: Vec<Vec<String>> = report
+            .steps
+            .iter()
+            .map(|step| {
+                vec![
+                    step.name.clone(),
+                    match step.status {
+                        InitStatus::Created => "Created",
+                        InitStatus::Ok => "OK",
+                        InitStatus::Warning => "Warning",
+                        InitStatus::Failed => "Failed",
+                    }
+                    .to_string(),
+                    step.detail.clone(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (step, row) in report.steps.iter().zip(&rows) {
+            let status_role = match step.status {
+                InitStatus::Created | InitStatus::Ok => ThemeRole::Success,
+                InitStatus::Warning => ThemeRole::Warning,
+                InitStatus::Failed => ThemeRole::Error,
+            };
+            table.add_row(vec![
+                Cell::new(&row[0]),
+                self.theme.cell(status_role, &row[1]),
+                Cell::new(fit.cell(2, &row[2])),
+            ]);
+        }
+
+        println!("{table}");
+        Ok(())
+    }
+
+    fn render_self_destruct_report(&self, report: &SelfDestructReport) -> io::Result<()> {
+        if report.removals.is_empty() {
+            println!("Nothing to remove");
+            return Ok(());
+        }
+        let total: u64 = report.removals.iter().map(|removal| removal.bytes).sum();
+        if report.dry_run {
+            println!("Would remove ({}):", format_size(total));
+        } else if report.is_complete() {
+            println!("[OK] Removed sps2 ({} freed)", format_size(total));
+        } else {
+            println!("[ERROR] Some paths could not be removed");
+        }
+        println!();
+
+        let columns = [
+            ("What", Fit::Keep),
+            ("Path", Fit::Shorten),
+            ("Size", Fit::Keep),
+            ("Status", Fit::Wrap),
+        ];
+        let rows: Vec<Vec<String>> = report
+            .removals
+            .iter()
+            .map(|removal| {
+                let status = match (&removal.error, report.dry_run) {
+                    (Some(error), _) => error.clone(),
+                    (None, true) => "would remove".to_string(),
+                    (None, false) => "removed".to_string(),
+                };
+                vec![
+                    removal.what.clone(),
+                    removal.path.display().to_string(),
+                    format_size(removal.bytes),
+                    status,
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (removal, row) in report.removals.iter().zip(&rows) {
+            let status_role = if removal.error.is_some() {
+                ThemeRole::Error
+            } else {
+                ThemeRole::Success
+            };
+            table.add_row(vec![
+                Cell::new(&row[0]),
+                Cell::new(fit.cell(1, &row[1])),
+                Cell::new(&row[2]),
+                self.theme.cell(status_role, fit.cell(3, &row[3])),
+            ]);
+        }
+
+        println!("{table}");
+        if let Some(store) = &report.kept_store {
+            println!();
+            println!("Kept the store at {}", store.display());
+        }
+        Ok(())
+    }
+
+    /// Render a project environment on stderr, leaving stdout to the shell
+    /// or command that runs in it
+    fn render_project_shell(shell: &ProjectShell) -> io::Result<()> {
+        let packages: Vec<String> = shell
+            .packages
+            .iter()
+            .map(|(name, version)| format!("{name}-{version}"))
+            .collect();
+        eprintln!(
+            "Environment of {} {}: {}",
+            shell.project.display(),
+            if shell.changed { "updated" } else { "ready" },
+            packages.join(", ")
+        );
+        Ok(())
+    }
+
+    fn render_services(&self, services: &[ServiceStatus]) -> io::Result<()> {
+        if services.is_empty() {
+            println!("No installed package provides a service");
+            return Ok(());
+        }
+
+        let columns = [
+            ("Label", Fit::Shorten),
+            ("Package", Fit::Shorten),
+            ("Domain", Fit::Keep),
+            ("Enabled", Fit::Keep),
+            ("Status", Fit::Keep),
+        ];
+        let rows: Vec<Vec<String>> = services
+            .iter()
+            .map(|service| {
+                let status = match (service.loaded, service.pid) {
+                    (_, Some(pid)) => format!("running (pid {pid})"),
+                    (true, None) => "loaded".to_string(),
+                    (false, None) => "stopped".to_string(),
+                };
+                vec![
+                    service.label.clone(),
+                    service.package.clone(),
+                    service.domain.clone(),
+                    if service.enabled { "yes" } else { "no" }.to_string(),
+                    status,
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (service, row) in services.iter().zip(&rows) {
+            let status = if service.pid.is_some() {
+                self.theme.cell(ThemeRole::Success, &row[4])
+            } else {
+                Cell::new(&row[4])
+            };
+            table.add_row(vec![
+                Cell::new(fit.cell(0, &row[0])),
+                Cell::new(fit.cell(1, &row[1])),
+                Cell::new(&row[2]),
+                Cell::new(&row[3]),
+                status,
+            ]);
+        }
+
+        println!("{table}");
+        Ok(())
+    }
+
     fn render_health_check(&self, health: &HealthCheck) -> io::Result<()> {
         let overall_icon = if health.healthy { "[OK]" } else { "[ERROR]" };
         println!("{overall_icon} System Health Check");
         println!();
 
         // Component status table
-        let mut table = Table::new();
-        table
-            .load_preset(UTF8_FULL)
-            .set_content_arrangement(ContentArrangement::Dynamic);
-
-        table.set_header(vec![
-            Cell::new("Component").add_attribute(Attribute::Bold),
-            Cell::new("Status").add_attribute(Attribute::Bold),
-            Cell::new("Duration").add_attribute(Attribute::Bold),
-            Cell::new("Message").add_attribute(Attribute::Bold),
-        ]);
-
-        for component in health.components.values() {
-            let status_cell = match component.status {
-                HealthStatus::Healthy => Cell::new("Healthy").fg(Color::Green),
-                HealthStatus::Warning => Cell::new("Warning").fg(Color::Yellow),
-                HealthStatus::Error => Cell::new("Error").fg(Color::Red),
+        let columns = [
+            ("Component", Fit::Shorten),
+            ("Status", Fit::Keep),
+            ("Duration", Fit::Keep),
+            ("Message", Fit::Wrap),
+        ];
+        let rows: Vec<Vec<String>> = health
+            .components
+            .values()
+            .map(|component| {
+                vec![
+                    component.name.clone(),
+                    match component.status {
+                        HealthStatus::Healthy => "Healthy",
+                        HealthStatus::Warning => "Warning",
+                        HealthStatus::Error => "Error",
+                    }
+                    .to_string(),
+                    format!("{}ms", component.check_duration_ms),
+                    component.message.clone(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+
+        for (component, row) in health.components.values().zip(&rows) {
+            let status_role = match component.status {
+                HealthStatus::Healthy => ThemeRole::Success,
+                HealthStatus::Warning => ThemeRole::Warning,
+                HealthStatus::Error => ThemeRole::Error,
             };
+            let status_cell = self.theme.cell(status_role, &row[1]);
 
             table.add_row(vec![
-                Cell::new(&component.name),
+                Cell::new(fit.cell(0, &row[0])),
                 status_cell,
-                Cell::new(format!("{}ms", component.check_duration_ms)),
-                Cell::new(&component.message),
+                Cell::new(&row[2]),
+                Cell::new(fit.cell(3, &row[3])),
             ]);
         }
 
@@ -476,6 +824,367 @@ impl OutputRenderer {
         Ok(())
     }
 
+    /// Render store deduplication statistics
+    fn render_store_stats(&self, stats: &StoreStats) -> io::Result<()> {
+        println!(
+            "Logical size:  {} in {} files",
+            format_size(stats.logical_bytes),
+            stats.file_references
+        );
+        println!(
+            "Physical size: {} in {} objects",
+            format_size(stats.physical_bytes),
+            stats.unique_objects
+        );
+        println!("Dedup ratio:   {:.2}x", stats.dedup_ratio);
+        println!(
+            "Orphaned:      {} objects ({}) unreferenced, {} ({}) untracked on disk",
+            stats.orphaned_objects,
+            format_size(stats.orphaned_bytes),
+            stats.untracked_objects,
+            format_size(stats.untracked_bytes)
+        );
+
+        if stats.largest_packages.is_empty() {
+            return Ok(());
+        }
+        println!();
+
+        let columns = [
+            ("Package", Fit::Shorten),
+            ("Version", Fit::Shorten),
+            ("Files", Fit::Keep),
+            ("Size", Fit::Keep),
+            ("Unique", Fit::Keep),
+        ];
+        let rows: Vec<Vec<String>> = stats
+            .largest_packages
+            .iter()
+            .map(|usage| {
+                vec![
+                    usage.name.clone(),
+                    usage.version.clone(),
+                    usage.files.to_string(),
+                    format_size(usage.bytes),
+                    format_size(usage.unique_bytes),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+        for row in &rows {
+            table.add_row(vec![
+                Cell::new(fit.cell(0, &row[0])),
+                Cell::new(fit.cell(1, &row[1])),
+                Cell::new(&row[2]),
+                Cell::new(&row[3]),
+                Cell::new(&row[4]),
+            ]);
+        }
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Render the files a package provides as a table or directory tree
+    fn render_package_files(&self, listing: &PackageFiles) -> io::Result<()> {
+        let files = listing.files.iter().filter(|file| !file.is_directory);
+        println!(
+            "{}-{}: {} files, {}",
+            listing.package,
+            listing.version,
+            files.clone().count(),
+            format_size(listing.total_size)
+        );
+        if listing.tree {
+            for line in file_tree(listing) {
+                println!("{line}");
+            }
+            return Ok(());
+        }
+
+        let columns = [
+            ("Path", Fit::Shorten),
+            ("Size", Fit::Keep),
+            ("Mode", Fit::Keep),
+            ("Hash", Fit::Keep),
+        ];
+        let rows: Vec<Vec<String>> = files
+            .map(|file| {
+                let path = match &file.symlink_target {
+                    Some(target) => format!("{} -> {target}", file.path),
+                    None if file.executable => format!("{}*", file.path),
+                    None => file.path.clone(),
+                };
+                vec![
+                    path,
+                    file.size.map(format_size).unwrap_or_default(),
+                    format!("{:o}", file.mode & 0o7777),
+                    file.hash
+                        .as_deref()
+                        .map(short_hash)
+                        .unwrap_or_default()
+                        .to_string(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+        for row in &rows {
+            table.add_row(
+                row.iter()
+                    .enumerate()
+                    .map(|(column, text)| Cell::new(fit.cell(column, text)))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        println!("{table}");
+        Ok(())
+    }
+
+    /// Render the owners of the files a query matched
+    fn render_file_ownership(&self, ownership: &FileOwnership) -> io::Result<()> {
+        if ownership.owners.is_empty() && ownership.orphans.is_empty() {
+            if ownership.query.is_empty() {
+                println!("No files found.");
+            } else {
+                println!("No files match {}.", ownership.query);
+            }
+            return Ok(());
+        }
+
+        if !ownership.owners.is_empty() {
+            let columns = [
+                ("Path", Fit::Shorten),
+                ("Package", Fit::Shorten),
+                ("Version", Fit::Shorten),
+            ];
+            let rows: Vec<Vec<String>> = ownership
+                .owners
+                .iter()
+                .map(|owner| {
+                    vec![
+                        owner.path.clone(),
+                        owner.package.clone(),
+                        owner.version.clone(),
+                    ]
+                })
+                .collect();
+            let fit = self.fit(&columns, &rows);
+            let mut table = self.table(&columns);
+            for row in &rows {
+                table.add_row(
+                    row.iter()
+                        .enumerate()
+                        .map(|(column, text)| Cell::new(fit.cell(column, text)))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            println!("{table}");
+        }
+
+        if !ownership.orphans.is_empty() {
+            println!("Not owned by any package:");
+            for path in &ownership.orphans {
+                println!("  {path}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render the package providing a command and what shadows it on PATH
+    fn render_command_resolution(&self, resolution: &CommandResolution) -> io::Result<()> {
+        let command = &resolution.command;
+        let Some(live_path) = &resolution.live_path else {
+            println!("{command} is not in the live bin directory");
+            for found in &resolution.on_path {
+                println!("  found on PATH: {}", found.path.display());
+            }
+            return Ok(());
+        };
+
+        println!("{command}: {}", live_path.display());
+        if resolution.providers.is_empty() {
+            println!("  not owned by any package");
+        }
+        for provider in &resolution.providers {
+            println!(
+                "  provided by {}-{} (store {})",
+                provider.package, provider.version, provider.store_hash
+            );
+        }
+        if !resolution.live_bin_on_path {
+            println!("  the live bin directory is not on PATH");
+        }
+        for found in &resolution.on_path {
+            if found.shadows {
+                println!("  shadowed by {}", found.path.display());
+            } else {
+                println!("  also on PATH: {}", found.path.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// Render the files of a package that differ from the store
+    fn render_package_diff(&self, diff: &PackageDiff) -> io::Result<()> {
+        if diff.is_clean() {
+            println!(
+                "{}-{}: all {} files match the store",
+                diff.package, diff.version, diff.files_compared
+            );
+            return Ok(());
+        }
+        println!(
+            "{}-{}: {} of {} files differ from the store",
+            diff.package,
+            diff.version,
+            diff.differences.len(),
+            diff.files_compared
+        );
+
+        let columns = [
+            ("Path", Fit::Shorten),
+            ("Change", Fit::Keep),
+            ("Detail", Fit::Shorten),
+        ];
+        let rows: Vec<Vec<String>> = diff
+            .differences
+            .iter()
+            .map(|file| {
+                let (change, detail) = match &file.change {
+                    FileChange::HashMismatch { expected, actual } => (
+                        "hash mismatch",
+                        format!("{} -> {}", short_hash(expected), short_hash(actual)),
+                    ),
+                    FileChange::ModeChanged { expected, actual } => {
+                        ("mode changed", format!("{expected:o} -> {actual:o}"))
+                    }
+                    FileChange::Missing => ("missing", String::new()),
+                    FileChange::Extra => ("extra", "not tracked by any package".to_string()),
+                };
+                vec![file.path.clone(), change.to_string(), detail]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+        for row in &rows {
+            table.add_row(vec![
+                Cell::new(fit.cell(0, &row[0])),
+                self.theme.cell(ThemeRole::Warning, &row[1]),
+                Cell::new(fit.cell(2, &row[2])),
+            ]);
+        }
+        println!("{table}");
+
+        for unified in diff
+            .differences
+            .iter()
+            .filter_map(|file| file.unified.as_ref())
+        {
+            println!();
+            print!("{unified}");
+        }
+
+        Ok(())
+    }
+
+    /// Render recorded verification runs and recurring discrepancies
+    fn render_verification_history(&self, history: &VerificationHistory) -> io::Result<()> {
+        if history.runs.is_empty() {
+            println!("No verification runs recorded.");
+            return Ok(());
+        }
+
+        let columns = [
+            ("Run at", Fit::Keep),
+            ("State", Fit::Shorten),
+            ("Level", Fit::Keep),
+            ("Scope", Fit::Keep),
+            ("Duration", Fit::Keep),
+            ("Missing", Fit::Keep),
+            ("Corrupted", Fit::Keep),
+            ("Packages", Fit::Keep),
+            ("Unexpected", Fit::Keep),
+            ("Healed", Fit::Keep),
+        ];
+        let rows: Vec<Vec<String>> = history
+            .runs
+            .iter()
+            .map(|run| {
+                vec![
+                    run.timestamp().format("%Y-%m-%d %H:%M").to_string(),
+                    run.state_id.clone(),
+                    run.level.clone(),
+                    run.scope.clone(),
+                    format!("{}ms", run.duration_ms),
+                    run.counts.missing_files.to_string(),
+                    run.counts.corrupted_files.to_string(),
+                    run.counts.missing_packages.to_string(),
+                    run.counts.unexpected_files.to_string(),
+                    run.counts.healed.to_string(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+        for (run, row) in history.runs.iter().zip(&rows) {
+            let mut cells: Vec<Cell> = row
+                .iter()
+                .enumerate()
+                .map(|(column, text)| Cell::new(fit.cell(column, text)))
+                .collect();
+            if run.counts.total() > 0 {
+                cells[0] = self.theme.cell(ThemeRole::Warning, &row[0]);
+            }
+            table.add_row(cells);
+        }
+        println!("{table}");
+
+        if history.recurring.is_empty() {
+            return Ok(());
+        }
+        println!();
+        println!("Found in more than one run:");
+
+        let columns = [
+            ("Kind", Fit::Keep),
+            ("Path", Fit::Shorten),
+            ("Package", Fit::Shorten),
+            ("Runs", Fit::Keep),
+            ("Last seen", Fit::Keep),
+        ];
+        let rows: Vec<Vec<String>> = history
+            .recurring
+            .iter()
+            .map(|recurring| {
+                vec![
+                    recurring.kind.clone(),
+                    recurring.path.clone(),
+                    recurring.package.clone().unwrap_or_default(),
+                    recurring.runs.to_string(),
+                    chrono::DateTime::from_timestamp(recurring.last_seen, 0)
+                        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        let fit = self.fit(&columns, &rows);
+        let mut table = self.table(&columns);
+        for row in &rows {
+            table.add_row(
+                row.iter()
+                    .enumerate()
+                    .map(|(column, text)| Cell::new(fit.cell(column, text)))
+                    .collect::<Vec<_>>(),
+            );
+        }
+        println!("{table}");
+
+        Ok(())
+    }
+
     /// Render operation report
     fn render_op_report(&self, report: &sps2_ops::OpReport) -> io::Result<()> {
         let icon = if report.success { "[OK]" } else { "[ERROR]" };
@@ -538,14 +1247,44 @@ impl OutputRenderer {
         Ok(())
     }
 
+    /// Empty table with a bold header per column
+    ///
+    /// Cells are laid out by [`TableFit`], so the table itself never wraps.
+    fn table(&self, columns: &[(&str, Fit)]) -> Table {
+        let mut table = Table::new();
+        table
+            .load_preset(UTF8_FULL)
+            .set_content_arrangement(ContentArrangement::Disabled)
+            .set_header(
+                columns
+                    .iter()
+                    .map(|(header, _)| self.theme.cell(ThemeRole::Header, header)),
+            );
+        self.theme.apply_to_table(&mut table);
+        table
+    }
+
+    /// Column widths fitting `rows` into the terminal
+    fn fit(&self, columns: &[(&str, Fit)], rows: &[Vec<String>]) -> TableFit {
+        TableFit::new(columns, rows, self.terminal_width(), self.ellipsis)
+    }
+
+    /// Terminal width in columns; `None` when output is not a terminal
+    fn terminal_width(&self) -> Option<usize> {
+        self.term
+            .size_checked()
+            .map(|(_, columns)| usize::from(columns))
+    }
+
     /// Format package status as colored cell
     fn format_package_status(&self, status: &PackageStatus) -> Cell {
-        match status {
-            PackageStatus::Installed => Cell::new("Installed").fg(Color::Green),
-            PackageStatus::Outdated => Cell::new("Outdated").fg(Color::Yellow),
-            PackageStatus::Available => Cell::new("Available").fg(Color::Blue),
-            PackageStatus::Local => Cell::new("Local").fg(Color::Magenta),
-        }
+        let role = match status {
+            PackageStatus::Installed => ThemeRole::Installed,
+            PackageStatus::Outdated => ThemeRole::Outdated,
+            PackageStatus::Available => ThemeRole::Available,
+            PackageStatus::Local => ThemeRole::Local,
+        };
+        self.theme.cell(role, package_status_label(status))
     }
 
     /// Format package status as text
@@ -558,23 +1297,125 @@ impl OutputRenderer {
         }
     }
 
+    /// Wrap a field value printed after a label `indent` columns wide,
+    /// aligning continuation lines with the first
+    fn wrap_field(&self, value: &str, indent: usize) -> String {
+        match (self.terminal_width(), self.ellipsis) {
+            (Some(width), EllipsisPolicy::End | EllipsisPolicy::Middle) => {
+                let width = width.saturating_sub(indent).max(MIN_WRAP_WIDTH);
+                wrap(value, width).join(&format!("\n{:indent$}", ""))
+            }
+            _ => value.to_string(),
+        }
+    }
+
     /// Style package name
     fn style_package_name(&self, name: &str) -> String {
-        if self.supports_color() {
-            Style::new().bold().apply_to(name).to_string()
-        } else {
-            name.to_string()
+        self.theme.paint(ThemeRole::Package, name)
+    }
+}
+
+/// Short package status shown in tables
+fn package_status_label(status: &PackageStatus) -> &'static str {
+    match status {
+        PackageStatus::Installed => "Installed",
+        PackageStatus::Outdated => "Outdated",
+        PackageStatus::Available => "Available",
+        PackageStatus::Local => "Local",
+    }
+}
+
+/// First 12 hex digits of a content hash
+/// Lines of a directory tree of a package's files, executables marked `*`
+fn file_tree(listing: &PackageFiles) -> Vec<String> {
+    #[derive(Default)]
+    struct Node {
+        children: std::collections::BTreeMap<String, Node>,
+        label: Option<String>,
+    }
+
+    fn walk(node: &Node, indent: &str, lines: &mut Vec<String>) {
+        let count = node.children.len();
+        for (index, (name, child)) in node.children.iter().enumerate() {
+            let last = index + 1 == count;
+            let branch = if last { "└── " } else { "├── " };
+            let label = child.label.as_deref().unwrap_or(name);
+            lines.push(format!("{indent}{branch}{label}"));
+            let next = format!("{indent}{}", if last { "    " } else { "│   " });
+            walk(child, &next, lines);
         }
     }
 
-    /// Check if color output is supported
-    fn supports_color(&self) -> bool {
-        match self.color_choice {
-            ColorChoice::Always => true,
-            ColorChoice::Never => false,
-            ColorChoice::Auto => self.term.features().colors_supported(),
+    let mut root = Node::default();
+    for file in &listing.files {
+        let mut node = &mut root;
+        for part in file.path.split('/').filter(|part| !part.is_empty()) {
+            node = node.children.entry(part.to_string()).or_default();
+        }
+        if file.is_directory {
+            continue;
         }
+        let name = file.path.rsplit('/').next().unwrap_or(&file.path);
+        let size = file.size.map(format_size).unwrap_or_default();
+        node.label = Some(match &file.symlink_target {
+            Some(target) => format!("{name} -> {target}"),
+            None if file.executable => format!("{name}* ({size})"),
+            None => format!("{name} ({size})"),
+        });
     }
+
+    let mut lines = Vec::new();
+    walk(&root, "", &mut lines);
+    lines
+}
+
+fn short_hash(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
+
+/// List a plan's installs, upgrades and removals with their sizes
+fn print_package_changes(plan: &ChangePlan) {
+    let sections = [
+        ("Install", &plan.install),
+        ("Upgrade", &plan.upgrade),
+        ("Remove", &plan.remove),
+    ];
+    for (title, changes) in sections {
+        if changes.is_empty() {
+            continue;
+        }
+        println!("{title} ({}):", changes.len());
+        for change in changes {
+            let versions = match (&change.from_version, &change.to_version) {
+                (Some(from), Some(to)) => format!("{from} → {to}"),
+                (Some(version), None) | (None, Some(version)) => version.to_string(),
+                (None, None) => "unknown".to_string(),
+            };
+            let size = change
+                .size
+                .map(|size| format!(" ({})", format_size(size)))
+                .unwrap_or_default();
+            println!("  • {} {versions}{size}", change.name);
+        }
+        println!();
+    }
+    print_broken_dependencies(&plan.broken_dependencies);
+}
+
+/// List installed packages left without a runtime dependency
+fn print_broken_dependencies(broken: &[BrokenDependency]) {
+    if broken.is_empty() {
+        return;
+    }
+    println!("Broken dependencies ({}):", broken.len());
+    for dependency in broken {
+        println!(
+            "  • {} needs {}",
+            dependency.package,
+            dependency.needs.join(", ")
+        );
+    }
+    println!();
 }
 
 /// Format byte size in human readable format
@@ -594,3 +1435,217 @@ fn format_size(bytes: u64) -> String {
         format!("{size:.1} {}", UNITS[unit_index])
     }
 }
+
+/// How a table column behaves when the table is wider than the terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fit {
+    /// Always shown in full (flags, counts, dates, identifiers)
+    Keep,
+    /// Shortened with the ellipsis policy (names, versions)
+    Shorten,
+    /// Word-wrapped into the remaining width (descriptions, messages)
+    Wrap,
+}
+
+/// Narrowest a shortened column gets, in terminal columns
+const MIN_SHORTEN_WIDTH: usize = 8;
+/// Narrowest a wrapped column gets, in terminal columns
+const MIN_WRAP_WIDTH: usize = 20;
+
+/// Column widths that fit a table into the terminal
+struct TableFit {
+    fits: Vec<Fit>,
+    /// Content width per column; `None` leaves every cell as is
+    widths: Option<Vec<usize>>,
+    ellipsis: EllipsisPolicy,
+}
+
+impl TableFit {
+    /// Size `columns` for a terminal `terminal_width` columns wide
+    ///
+    /// Wrapped columns give up width first, then the widest shortened
+    /// columns, each down to its minimum. Without a terminal width or with
+    /// [`EllipsisPolicy::Off`] nothing is changed.
+    fn new(
+        columns: &[(&str, Fit)],
+        rows: &[Vec<String>],
+        terminal_width: Option<usize>,
+        ellipsis: EllipsisPolicy,
+    ) -> Self {
+        let fits: Vec<Fit> = columns.iter().map(|(_, fit)| *fit).collect();
+        let widths = match (terminal_width, ellipsis) {
+            (None, _) | (_, EllipsisPolicy::Off) => None,
+            (Some(terminal_width), _) => Some(fit_widths(columns, rows, terminal_width)),
+        };
+        Self {
+            fits,
+            widths,
+            ellipsis,
+        }
+    }
+
+    /// Cell text for `column`, shortened or wrapped to its width
+    fn cell(&self, column: usize, text: &str) -> String {
+        let Some(width) = self.widths.as_ref().map(|widths| widths[column]) else {
+            return text.to_string();
+        };
+        match self.fits[column] {
+            Fit::Keep => text.to_string(),
+            Fit::Shorten => shorten(text, width, self.ellipsis),
+            Fit::Wrap => wrap(text, width).join("\n"),
+        }
+    }
+}
+
+/// Content widths for `columns` so the table fits `terminal_width`
+fn fit_widths(columns: &[(&str, Fit)], rows: &[Vec<String>], terminal_width: usize) -> Vec<usize> {
+    // Each column has a border and a space of padding on both sides
+    let available = terminal_width.saturating_sub(3 * columns.len() + 1);
+
+    let mut widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, (header, _))| {
+            rows.iter()
+                .flat_map(|row| row[i].lines())
+                .map(measure_text_width)
+                .chain([measure_text_width(header)])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let minimums: Vec<usize> = columns
+        .iter()
+        .zip(&widths)
+        .map(|((header, fit), &width)| match fit {
+            Fit::Keep => width,
+            Fit::Shorten => width.min(MIN_SHORTEN_WIDTH.max(measure_text_width(header))),
+            Fit::Wrap => width.min(MIN_WRAP_WIDTH),
+        })
+        .collect();
+
+    let mut excess = widths.iter().sum::<usize>().saturating_sub(available);
+    for (i, (_, fit)) in columns.iter().enumerate() {
+        if *fit == Fit::Wrap {
+            let give = excess.min(widths[i] - minimums[i]);
+            widths[i] -= give;
+            excess -= give;
+        }
+    }
+    while excess > 0 {
+        let widest = (0..columns.len())
+            .filter(|&i| columns[i].1 == Fit::Shorten && widths[i] > minimums[i])
+            .max_by_key(|&i| widths[i]);
+        let Some(widest) = widest else { break };
+        widths[widest] -= 1;
+        excess -= 1;
+    }
+    widths
+}
+
+/// Shorten `text` to at most `width` terminal columns with an ellipsis
+fn shorten(text: &str, width: usize, policy: EllipsisPolicy) -> String {
+    if measure_text_width(text) <= width || width == 0 {
+        return text.to_string();
+    }
+    match policy {
+        EllipsisPolicy::Off => text.to_string(),
+        EllipsisPolicy::End => truncate_str(text, width, "…").into_owned(),
+        EllipsisPolicy::Middle => {
+            let tail_width = (width - 1) / 2;
+            let head = truncate_str(text, width - 1 - tail_width, "");
+            let mut tail: Vec<char> = Vec::new();
+            let mut used = 0;
+            for c in text.chars().rev() {
+                let char_width = measure_text_width(c.encode_utf8(&mut [0; 4]));
+                if used + char_width > tail_width {
+                    break;
+                }
+                used += char_width;
+                tail.push(c);
+            }
+            let tail: String = tail.into_iter().rev().collect();
+            format!("{head}…{tail}")
+        }
+    }
+}
+
+/// Word-wrap `text` into lines of at most `width` terminal columns
+///
+/// Words longer than a line are split.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            let separator = usize::from(!line.is_empty());
+            if measure_text_width(&line) + separator + measure_text_width(word) <= width {
+                if separator == 1 {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            while measure_text_width(word) > width {
+                let mut split = truncate_str(word, width, "").len();
+                if split == 0 {
+                    // A single character wider than the line
+                    split = word.chars().next().map_or(word.len(), char::len_utf8);
+                }
+                lines.push(word[..split].to_string());
+                word = &word[split..];
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shortening_keeps_the_chosen_end() {
+        let name = "python-cryptography-extras";
+        assert_eq!(shorten(name, 30, EllipsisPolicy::Middle), name);
+        assert_eq!(shorten(name, 12, EllipsisPolicy::End), "python-cryp…");
+        assert_eq!(shorten(name, 12, EllipsisPolicy::Middle), "python…xtras");
+        assert_eq!(shorten(name, 12, EllipsisPolicy::Off), name);
+    }
+
+    #[test]
+    fn wrapping_breaks_at_words_and_splits_long_ones() {
+        assert_eq!(
+            wrap("a fast json processor", 10),
+            ["a fast", "json", "processor"]
+        );
+        assert_eq!(wrap("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn wrapped_columns_shrink_before_shortened_ones() {
+        let columns = [
+            ("Package", Fit::Shorten),
+            ("Status", Fit::Keep),
+            ("Description", Fit::Wrap),
+        ];
+        let rows = vec![vec![
+            "a-rather-long-package-name".to_string(),
+            "Installed".to_string(),
+            "x".repeat(60),
+        ]];
+
+        // 10 columns of borders and padding, 26 + 9 + 20 of content
+        assert_eq!(fit_widths(&columns, &rows, 65), [26, 9, 20]);
+        assert_eq!(fit_widths(&columns, &rows, 55), [16, 9, 20]);
+        assert_eq!(fit_widths(&columns, &rows, 20), [8, 9, 20]);
+        assert_eq!(fit_widths(&columns, &rows, 200), [26, 9, 60]);
+    }
+}
@@ -18,6 +18,10 @@ pub enum CliError {
     InvalidArguments(String),
     /// I/O error
     Io(std::io::Error),
+    /// The user declined to apply the planned changes
+    Cancelled,
+    /// The installed packages differ from a manifest checked with `--check`
+    Drift(usize),
 }
 
 impl fmt::Display for CliError {
@@ -42,6 +46,11 @@ impl fmt::Display for CliError {
 
             CliError::InvalidArguments(msg) => write!(f, "Invalid arguments: {msg}"),
             CliError::Io(e) => write!(f, "I/O error: {e}"),
+            CliError::Cancelled => write!(f, "Cancelled; no changes were made"),
+            CliError::Drift(changes) => write!(
+                f,
+                "Installed packages differ from the manifest: {changes} package changes needed"
+            ),
         }
     }
 }
@@ -1,9 +1,12 @@
 //! Event handling and progress display
 
 use crate::logging::log_event_with_tracing;
-use console::{style, Term};
+use crate::theme::Theme;
+use comfy_table::{presets::UTF8_FULL, Table};
+use console::style;
+use sps2_config::ThemeRole;
 use sps2_events::{
-    events::{LifecycleEvent, LifecycleStage, LifecycleUpdateOperation},
+    events::{LifecycleEvent, LifecycleStage, LifecycleUpdateOperation, QaFinding, QaSeverity},
     AppEvent, EventMessage, EventMeta, ProgressEvent,
 };
 use std::collections::HashMap;
@@ -26,72 +29,52 @@ pub enum EventSeverity {
     Critical,
 }
 
+impl EventSeverity {
+    /// Theme role styling this severity
+    fn role(self) -> ThemeRole {
+        match self {
+            Self::Debug => ThemeRole::Debug,
+            Self::Info => ThemeRole::Info,
+            Self::Success => ThemeRole::Success,
+            Self::Warning => ThemeRole::Warning,
+            Self::Error => ThemeRole::Error,
+            Self::Critical => ThemeRole::Critical,
+        }
+    }
+}
+
 /// UI styling configuration
 #[derive(Clone)]
 pub struct UiStyle {
-    /// Whether colors are supported
-    colors_enabled: bool,
-    /// Terminal instance for feature detection
-    term: Term,
+    /// Color theme; plain text when colors are disabled
+    theme: Theme,
 }
 
 impl UiStyle {
-    pub fn new(colors_enabled: bool) -> Self {
-        Self {
-            colors_enabled,
-            term: Term::stdout(),
-        }
+    pub fn new(theme: Theme) -> Self {
+        Self { theme }
     }
 
     /// Get styled prefix for event severity
     pub fn get_prefix(&self, severity: EventSeverity) -> String {
-        if !self.colors_enabled || !self.term.features().colors_supported() {
-            return match severity {
-                EventSeverity::Debug => "[DEBUG]".to_string(),
-                EventSeverity::Info => "[INFO]".to_string(),
-                EventSeverity::Success => "[OK]".to_string(),
-                EventSeverity::Warning => "[WARN]".to_string(),
-                EventSeverity::Error => "[ERROR]".to_string(),
-                EventSeverity::Critical => "[CRITICAL]".to_string(),
-            };
-        }
-
-        // Use clean text prefixes
-        match severity {
-            EventSeverity::Debug => {
-                format!("{}", style("[DEBUG]").dim().cyan())
-            }
-            EventSeverity::Info => {
-                format!("{}", style("[INFO]").blue())
-            }
-            EventSeverity::Success => {
-                format!("{}", style("[OK]").green().bold())
-            }
-            EventSeverity::Warning => {
-                format!("{}", style("[WARN]").yellow().bold())
-            }
-            EventSeverity::Error => {
-                format!("{}", style("[ERROR]").red().bold())
-            }
-            EventSeverity::Critical => {
-                format!("{}", style("[CRITICAL]").red().bold().underlined())
-            }
-        }
+        let prefix = match severity {
+            EventSeverity::Debug => "[DEBUG]",
+            EventSeverity::Info => "[INFO]",
+            EventSeverity::Success => "[OK]",
+            EventSeverity::Warning => "[WARN]",
+            EventSeverity::Error => "[ERROR]",
+            EventSeverity::Critical => "[CRITICAL]",
+        };
+        self.theme.paint(severity.role(), prefix)
     }
 
     /// Style message text based on severity
+    ///
+    /// Informational messages stay plain; the prefix carries their color.
     pub fn style_message(&self, message: &str, severity: EventSeverity) -> String {
-        if !self.colors_enabled || !self.term.features().colors_supported() {
-            return message.to_string();
-        }
-
         match severity {
-            EventSeverity::Debug => style(message).dim().to_string(),
             EventSeverity::Info => message.to_string(),
-            EventSeverity::Success => style(message).green().bold().to_string(),
-            EventSeverity::Warning => style(message).yellow().to_string(),
-            EventSeverity::Error => style(message).red().bold().to_string(),
-            EventSeverity::Critical => style(message).red().bold().to_string(),
+            _ => self.theme.paint(severity.role(), message),
         }
     }
 
@@ -102,35 +85,16 @@ impl UiStyle {
         operation: &str,
         severity: EventSeverity,
     ) -> String {
-        if !self.colors_enabled || !self.term.features().colors_supported() {
-            return message.to_string();
-        }
-
         // Apply bold styling for important operations
         let should_bold = matches!(
             operation,
             "install" | "uninstall" | "build" | "upgrade" | "rollback" | "health" | "2pc"
         );
 
-        match severity {
-            EventSeverity::Debug => style(message).dim().to_string(),
-            EventSeverity::Info => {
-                if should_bold {
-                    style(message).bold().to_string()
-                } else {
-                    message.to_string()
-                }
-            }
-            EventSeverity::Success => style(message).green().bold().to_string(),
-            EventSeverity::Warning => {
-                if should_bold {
-                    style(message).yellow().bold().to_string()
-                } else {
-                    style(message).yellow().to_string()
-                }
-            }
-            EventSeverity::Error => style(message).red().bold().to_string(),
-            EventSeverity::Critical => style(message).red().bold().to_string(),
+        if severity == EventSeverity::Info && should_bold && self.theme.colors() {
+            style(message).bold().force_styling(true).to_string()
+        } else {
+            self.style_message(message, severity)
         }
     }
 }
@@ -154,17 +118,29 @@ pub struct EventHandler {
     debug_enabled: bool,
     /// Active progress trackers keyed by progress identifier
     progress_states: HashMap<String, ProgressState>,
+    /// Write events as JSON lines instead of displaying them
+    event_stream: bool,
+    /// QA findings of the current build with their check, summarised when it ends
+    qa_findings: Vec<(String, QaFinding)>,
 }
 
 impl EventHandler {
-    pub fn new(colors_enabled: bool, debug_enabled: bool) -> Self {
+    pub fn new(theme: Theme, debug_enabled: bool) -> Self {
         Self {
-            ui_style: UiStyle::new(colors_enabled),
+            ui_style: UiStyle::new(theme),
             debug_enabled,
             progress_states: HashMap::new(),
+            event_stream: false,
+            qa_findings: Vec::new(),
         }
     }
 
+    /// Write every event to stdout as a JSON line, for a remote build client
+    pub fn with_event_stream(mut self, event_stream: bool) -> Self {
+        self.event_stream = event_stream;
+        self
+    }
+
     fn show_operation_message(&mut self, message: &str, operation: &str, severity: EventSeverity) {
         let prefix = self.ui_style.get_prefix(severity);
         let styled = self
@@ -184,6 +160,14 @@ impl EventHandler {
         // Log event with structured logging
         log_event_with_tracing(&message);
 
+        if self.event_stream {
+            match serde_json::to_string(&message) {
+                Ok(line) => println!("{line}"),
+                Err(e) => tracing::warn!("Failed to serialize event: {}", e),
+            }
+            return;
+        }
+
         let EventMessage { meta, event } = message;
 
         match event {
@@ -207,6 +191,7 @@ impl EventHandler {
                         &context.url,
                         context.package.as_deref(),
                         context.bytes_downloaded.unwrap_or(0),
+                        context.cached,
                     );
                 }
                 LifecycleStage::Failed => {
@@ -216,6 +201,7 @@ impl EventHandler {
                             &context.url,
                             context.package.as_deref(),
                             &failure_ctx,
+                            context.integrity.as_ref(),
                         );
                     }
                 }
@@ -535,6 +521,23 @@ impl EventHandler {
                             EventSeverity::Error,
                         );
                     }
+                    StateEvent::DiskDeltaComputed { delta } => {
+                        let available = delta
+                            .available_bytes
+                            .map(|bytes| format!(", {} free", self.format_bytes(bytes)))
+                            .unwrap_or_default();
+                        self.show_operation(
+                            &meta,
+                            format!(
+                                "Disk usage: {} downloaded, {} new in store, {} staging{available}",
+                                self.format_bytes(delta.downloaded_bytes),
+                                self.format_bytes(delta.store_bytes),
+                                self.format_bytes(delta.staging_bytes),
+                            ),
+                            "install",
+                            EventSeverity::Info,
+                        );
+                    }
                 }
             }
 
@@ -585,6 +588,63 @@ impl EventHandler {
                             "build",
                             EventSeverity::Success,
                         );
+                        self.show_qa_summary();
+                    }
+                    BuildEvent::SourceAcquired {
+                        source,
+                        destination,
+                        checksum,
+                        duration_ms,
+                        ..
+                    } => {
+                        let verified = checksum
+                            .as_deref()
+                            .map(|algorithm| format!(", {algorithm} verified"))
+                            .unwrap_or_default();
+                        self.show_operation(
+                            &meta,
+                            format!(
+                                "Fetched {source} into {} in {}{verified}",
+                                destination.display(),
+                                format_duration(std::time::Duration::from_millis(duration_ms))
+                            ),
+                            "build",
+                            EventSeverity::Info,
+                        );
+                    }
+                    BuildEvent::TestsCompleted {
+                        target, results, ..
+                    } => {
+                        use sps2_types::{TestFailurePolicy, TestStatus};
+                        let duration =
+                            format_duration(std::time::Duration::from_millis(results.duration_ms));
+                        let (message, severity) = match results.status {
+                            TestStatus::Passed => (
+                                format!(
+                                    "Tests passed for {} {} in {duration}",
+                                    target.package, target.version
+                                ),
+                                EventSeverity::Success,
+                            ),
+                            TestStatus::Failed => (
+                                format!(
+                                    "Tests failed for {} {}: {}",
+                                    target.package,
+                                    target.version,
+                                    results.failure.as_deref().unwrap_or("unknown error")
+                                ),
+                                if results.policy == TestFailurePolicy::Warn {
+                                    EventSeverity::Warning
+                                } else {
+                                    EventSeverity::Error
+                                },
+                            ),
+                            TestStatus::Skipped => (
+                                format!("Tests skipped for {} {}", target.package, target.version),
+                                EventSeverity::Info,
+                            ),
+                        };
+                        self.show_operation(&meta, message, "build", severity);
                     }
                     BuildEvent::Failed {
                         target,
@@ -621,6 +681,7 @@ impl EventHandler {
                             EventSeverity::Error
                         };
                         self.show_operation(&meta, message, "build", severity);
+                        self.show_qa_summary();
                     }
                     BuildEvent::PhaseStatus { phase, status, .. } => match status {
                         PhaseStatus::Started => {
@@ -766,6 +827,9 @@ impl EventHandler {
                         if let Some(hint) = &failure_ctx.hint {
                             message.push_str(&format!(" (hint: {hint})"));
                         }
+                        for step in &context.conflict_explanation {
+                            message.push_str(&format!("\n  - {step}"));
+                        }
                         let severity = if failure_ctx.retryable {
                             EventSeverity::Warning
                         } else {
@@ -1132,6 +1196,31 @@ impl EventHandler {
                             severity,
                         );
                     }
+                    QaEvent::FindingReported { check, finding, .. } => {
+                        let location = finding
+                            .file
+                            .as_ref()
+                            .map(|file| format!(" in {}", file.display()))
+                            .unwrap_or_default();
+                        let (suffix, severity) = match &finding.suppressed {
+                            Some(justification) => (
+                                format!(" (suppressed: {justification})"),
+                                EventSeverity::Debug,
+                            ),
+                            None => (String::new(), qa_severity(&finding.severity)),
+                        };
+                        self.show_operation(
+                            &meta,
+                            format!(
+                                "{check} [{}]{location}: {}{suffix}",
+                                finding.rule.as_deref().unwrap_or("-"),
+                                finding.message
+                            ),
+                            "qa",
+                            severity,
+                        );
+                        self.qa_findings.push((check, finding));
+                    }
                 }
             }
 
@@ -1301,6 +1390,24 @@ impl EventHandler {
                             severity,
                         );
                     }
+                    GuardEvent::PathHealed {
+                        path,
+                        package,
+                        version,
+                        source,
+                        ..
+                    } => {
+                        let package_info = match (&package, &version) {
+                            (Some(pkg), Some(ver)) => format!(" [{pkg}:{ver}]"),
+                            (Some(pkg), None) => format!(" [{pkg}]"),
+                            _ => String::new(),
+                        };
+                        self.show_meta_message(
+                            &meta,
+                            format!("healed{package_info}: {path} (from {source})"),
+                            EventSeverity::Info,
+                        );
+                    }
                     GuardEvent::DiscrepancyReported { discrepancy, .. } => {
                         let severity = match discrepancy.severity {
                             GuardSeverity::Critical => EventSeverity::Critical,
@@ -1576,6 +1683,52 @@ impl EventHandler {
         self.show_operation_message(&formatted, operation, severity);
     }
 
+    /// Print the QA findings of the build that just ended as a table
+    ///
+    /// Suppressed findings are listed with the recipe's justification in
+    /// place of a suggested fix.
+    fn show_qa_summary(&mut self) {
+        if self.qa_findings.is_empty() {
+            return;
+        }
+        let theme = &self.ui_style.theme;
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL).set_header(
+            ["Severity", "Check", "Rule", "File", "Finding", "Fix"]
+                .iter()
+                .map(|header| theme.cell(ThemeRole::Header, header)),
+        );
+        theme.apply_to_table(&mut table);
+        for (check, finding) in self.qa_findings.drain(..) {
+            let (severity, fix) = match finding.suppressed {
+                Some(justification) => (
+                    theme.cell(ThemeRole::Debug, "suppressed"),
+                    format!("suppressed: {justification}"),
+                ),
+                None => {
+                    let severity = qa_severity(&finding.severity);
+                    (
+                        theme.cell(severity.role(), format!("{:?}", finding.severity)),
+                        finding.suggestion.unwrap_or_default(),
+                    )
+                }
+            };
+            table.add_row(vec![
+                severity,
+                check.into(),
+                finding.rule.unwrap_or_default().into(),
+                finding
+                    .file
+                    .map(|file| file.display().to_string())
+                    .unwrap_or_default()
+                    .into(),
+                finding.message.into(),
+                fix.into(),
+            ]);
+        }
+        println!("QA findings:\n{table}");
+    }
+
     fn show_meta_message(&mut self, meta: &EventMeta, message: String, severity: EventSeverity) {
         let formatted = self.decorate_message(meta, message);
         self.show_message(&formatted, severity);
@@ -1653,18 +1806,19 @@ impl EventHandler {
         url: &str,
         package: Option<&str>,
         bytes_downloaded: u64,
+        cached: bool,
     ) {
         let filename = url.split('/').next_back().unwrap_or(url);
         let name = package.unwrap_or(filename);
-        self.show_operation(
-            meta,
+        let message = if cached {
+            format!("Finished downloading {name} (cached)")
+        } else {
             format!(
                 "Finished downloading {name} ({} fetched)",
                 self.format_bytes(bytes_downloaded)
-            ),
-            "download",
-            EventSeverity::Success,
-        );
+            )
+        };
+        self.show_operation(meta, message, "download", EventSeverity::Success);
     }
 
     /// Handle download failed event
@@ -1674,6 +1828,7 @@ impl EventHandler {
         url: &str,
         package: Option<&str>,
         failure: &sps2_events::FailureContext,
+        integrity: Option<&sps2_events::DownloadIntegrity>,
     ) {
         let filename = url.split('/').next_back().unwrap_or(url);
         let name = package.unwrap_or(filename);
@@ -1689,6 +1844,9 @@ impl EventHandler {
         if let Some(hint) = &failure.hint {
             text.push_str(&format!(" (hint: {hint})"));
         }
+        if let Some(path) = integrity.and_then(|i| i.quarantine_path.as_ref()) {
+            text.push_str(&format!(" (quarantined at {})", path.display()));
+        }
         self.show_operation(meta, text, "download", EventSeverity::Error);
     }
 
@@ -1699,11 +1857,11 @@ impl EventHandler {
         action: &str,
         details: &std::collections::HashMap<String, String>,
     ) {
-        if self.ui_style.colors_enabled {
-            println!("  {} {}", style("PREVIEW:").blue().bold(), action);
-        } else {
-            println!("  PREVIEW: {action}");
-        }
+        println!(
+            "  {} {}",
+            self.ui_style.theme.paint(ThemeRole::Preview, "PREVIEW:"),
+            action
+        );
 
         // Show relevant details
         for (key, value) in details {
@@ -1718,19 +1876,12 @@ impl EventHandler {
         total_changes: &usize,
         categories: &std::collections::HashMap<String, usize>,
     ) {
-        if self.ui_style.colors_enabled {
-            println!(
-                "
+        println!(
+            "
 {} Summary for {}:",
-                style("CHECK MODE").yellow().bold(),
-                operation
-            );
-        } else {
-            println!(
-                "
-CHECK MODE Summary for {operation}:"
-            );
-        }
+            self.ui_style.theme.paint(ThemeRole::Warning, "CHECK MODE"),
+            operation
+        );
         println!("  Total changes: {total_changes}");
 
         for (category, count) in categories {
@@ -1761,6 +1912,16 @@ No changes were made. Use without --check to execute."
     }
 }
 
+/// Event severity a QA finding is shown with
+fn qa_severity(severity: &QaSeverity) -> EventSeverity {
+    match severity {
+        QaSeverity::Info => EventSeverity::Info,
+        QaSeverity::Warning => EventSeverity::Warning,
+        QaSeverity::Error => EventSeverity::Error,
+        QaSeverity::Critical => EventSeverity::Critical,
+    }
+}
+
 fn format_duration(duration: Duration) -> String {
     let secs = duration.as_secs();
     if secs == 0 {
@@ -44,6 +44,7 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                         url = %context.url,
                         package = ?context.package,
                         bytes_downloaded = ?context.bytes_downloaded,
+                        cached = context.cached,
                         "Download completed"
                     );
                 }
@@ -59,6 +60,9 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                             code = ?failure_ctx.code,
                             message = %failure_ctx.message,
                             hint = ?failure_ctx.hint,
+                            expected_hash = ?context.integrity.as_ref().map(|i| &i.expected_hash),
+                            actual_hash = ?context.integrity.as_ref().map(|i| &i.actual_hash),
+                            quarantine_path = ?context.integrity.as_ref().and_then(|i| i.quarantine_path.as_ref()),
                             "Download failed"
                         );
                     }
@@ -98,6 +102,40 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                         "Build completed"
                     );
                 }
+                BuildEvent::SourceAcquired {
+                    source,
+                    destination,
+                    checksum,
+                    duration_ms,
+                    ..
+                } => {
+                    info!(
+                        source = meta.source.as_str(),
+                        event_id = %meta.event_id,
+                        correlation = ?meta.correlation_id,
+                        recipe_source = %source,
+                        destination = %destination.display(),
+                        checksum = ?checksum,
+                        duration_ms,
+                        "Build source acquired"
+                    );
+                }
+                BuildEvent::TestsCompleted {
+                    target, results, ..
+                } => {
+                    info!(
+                        source = meta.source.as_str(),
+                        event_id = %meta.event_id,
+                        correlation = ?meta.correlation_id,
+                        package = %target.package,
+                        version = %target.version,
+                        status = ?results.status,
+                        policy = ?results.policy,
+                        duration_ms = results.duration_ms,
+                        failure = ?results.failure,
+                        "Build tests completed"
+                    );
+                }
                 BuildEvent::Failed {
                     target,
                     failure,
@@ -373,6 +411,24 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                         );
                     }
                 }
+                GuardEvent::PathHealed {
+                    path,
+                    package,
+                    version,
+                    source,
+                    ..
+                } => {
+                    info!(
+                        source = meta.source.as_str(),
+                        event_id = %meta.event_id,
+                        correlation = ?meta.correlation_id,
+                        path = %path,
+                        package = ?package,
+                        version = ?version,
+                        from = %source,
+                        "Guard healed path",
+                    );
+                }
                 GuardEvent::DiscrepancyReported { discrepancy, .. } => {
                     let severity = match discrepancy.severity {
                         GuardSeverity::Critical => "critical",
@@ -440,6 +496,7 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                                 message = %failure_ctx.message,
                                 hint = ?failure_ctx.hint,
                                 conflicts = ?context.conflicting_packages,
+                                explanation = ?context.conflict_explanation,
                                 "Dependency resolution failed"
                             );
                         } else {
@@ -452,6 +509,7 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                                 message = %failure_ctx.message,
                                 hint = ?failure_ctx.hint,
                                 conflicts = ?context.conflicting_packages,
+                                explanation = ?context.conflict_explanation,
                                 "Dependency resolution failed"
                             );
                         }
@@ -619,6 +677,32 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                         "QA pipeline failed"
                     );
                 }
+                QaEvent::FindingReported {
+                    target,
+                    check,
+                    finding,
+                } => {
+                    let level = if finding.suppressed.is_some() {
+                        "suppressed".to_string()
+                    } else {
+                        format!("{:?}", finding.severity)
+                    };
+                    info!(
+                        source = meta.source.as_str(),
+                        event_id = %meta.event_id,
+                        correlation = ?meta.correlation_id,
+                        package = %target.package,
+                        version = %target.version,
+                        check = %check,
+                        rule = ?finding.rule,
+                        severity = %level,
+                        file = ?finding.file,
+                        message = %finding.message,
+                        suggestion = ?finding.suggestion,
+                        justification = ?finding.suppressed,
+                        "QA finding reported"
+                    );
+                }
                 QaEvent::CheckEvaluated { summary, .. } => {
                     let status_str = format!("{:?}", summary.status);
                     let severity = match summary.status {
@@ -816,6 +900,18 @@ pub fn log_event_with_tracing(message: &EventMessage) {
                         "Cleanup failed"
                     );
                 }
+                StateEvent::DiskDeltaComputed { delta } => {
+                    info!(
+                        source = meta.source.as_str(),
+                        event_id = %meta.event_id,
+                        correlation = ?meta.correlation_id,
+                        downloaded_bytes = delta.downloaded_bytes,
+                        store_bytes = delta.store_bytes,
+                        staging_bytes = delta.staging_bytes,
+                        available_bytes = delta.available_bytes,
+                        "Disk delta computed"
+                    );
+                }
             }
         }
 
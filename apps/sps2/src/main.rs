@@ -5,23 +5,35 @@
 //! operations through the ops crate.
 
 mod cli;
+mod confirm;
 mod display;
 mod error;
 mod events;
 mod logging;
 mod setup;
+mod theme;
 
-use crate::cli::{Cli, Commands, KeysCommands};
+use crate::cli::{
+    CleanCommands, Cli, Commands, KeysCommands, SbomCommands, SchemaCommands, SelfCommands,
+    ServicesCommands, SnapshotCommands, StoreCommands,
+};
 use crate::display::OutputRenderer;
 use crate::error::CliError;
 use crate::events::EventHandler;
 use crate::setup::SystemSetup;
+use crate::theme::Theme;
 use clap::Parser;
 use sps2_config::{fixed_paths, Config};
-use sps2_events::{EventReceiver, EventSender};
-use sps2_ops::{OperationResult, OpsContextBuilder};
+use sps2_events::{
+    AppEvent, ChannelConfig, EventMessage, EventReceiver, EventSender, GeneralEvent, HostContext,
+};
+use sps2_index::IndexManager;
+use sps2_ops::{OperationResult, OpsContextBuilder, PlannedOperation, Requirements};
 use sps2_state::StateManager;
 use sps2_types::state::TransactionPhase;
+use sps2_types::EllipsisPolicy;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process;
 use tokio::select;
 use tracing::{error, info, warn};
@@ -29,11 +41,15 @@ use tracing::{error, info, warn};
 #[tokio::main]
 async fn main() {
     // Parse command line arguments first to check for JSON mode
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    // A worker's output is read by the client, not by a person
+    if matches!(cli.command, Commands::Build { worker: true, .. }) {
+        cli.global.json = true;
+    }
     let json_mode = cli.global.json;
 
     // Initialize tracing with JSON awareness
-    init_tracing(json_mode, cli.global.debug);
+    init_tracing(json_mode, cli.global.debug, &log_dir(&cli.global));
 
     // Run the application and handle errors
     if let Err(e) = run(cli).await {
@@ -49,11 +65,26 @@ async fn main() {
 async fn run(cli: Cli) -> Result<(), CliError> {
     info!("Starting sps2 v{}", env!("CARGO_PKG_VERSION"));
 
+    // Schemas are compiled in and need neither config nor an installation
+    if let Commands::Schema(SchemaCommands::Dump { name }) = &cli.command {
+        return dump_schemas(name.as_deref());
+    }
+
+    // Config files that loading is about to write with defaults
+    let config_files = if matches!(cli.command, Commands::Init) {
+        config_files(&cli.global)
+    } else {
+        Vec::new()
+    };
+
     // Load configuration with proper precedence:
-    // 1. Start with file config (or defaults)
-    let mut config =
-        Config::load_or_default_with_builder(&cli.global.config, &cli.global.builder_config)
-            .await?;
+    // 1. Start with file config (or defaults), found under the root
+    let mut config = Config::load_or_default_with_builder(
+        &cli.global.config,
+        &cli.global.builder_config,
+        cli_root(&cli.global).as_deref(),
+    )
+    .await?;
 
     // 2. Merge environment variables
     config.merge_env()?;
@@ -61,6 +92,42 @@ async fn run(cli: Cli) -> Result<(), CliError> {
     // 3. Apply CLI flags (highest precedence)
     apply_cli_config(&mut config, &cli.global, &cli.command)?;
 
+    // Resolve colors once for results and events
+    let colors_enabled = cli
+        .global
+        .color
+        .unwrap_or(config.general.color)
+        .enabled(std::io::stdout().is_terminal());
+    let theme = Theme::new(config.theme.clone(), colors_enabled);
+
+    // Create output renderer
+    let renderer = OutputRenderer::new(cli.global.json, theme.clone(), config.general.ellipsis);
+
+    // Init sets up what every other command expects to be in place
+    if matches!(cli.command, Commands::Init) {
+        let config_files: Vec<_> = config_files
+            .into_iter()
+            .map(|(path, existed)| {
+                let written = !existed && path.exists();
+                (path, written)
+            })
+            .collect();
+        let report = sps2_ops::init(&config, &config_files).await;
+        let ready = report.is_ready();
+        renderer.render_result(&OperationResult::InitReport(report))?;
+        if !ready {
+            return Err(CliError::Setup(
+                "initialization did not complete".to_string(),
+            ));
+        }
+        return Ok(());
+    }
+
+    // Removal must work even on an installation too broken to set up
+    if let Commands::SelfCommand(SelfCommands::Destruct { keep_store }) = cli.command {
+        return self_destruct(&config, keep_store, &cli.global, &renderer).await;
+    }
+
     // Initialize system setup
     let mut setup = SystemSetup::new(config.clone());
 
@@ -80,7 +147,32 @@ async fn run(cli: Cli) -> Result<(), CliError> {
     // --- END RECOVERY LOGIC ---
 
     // Create event channel
-    let (event_sender, event_receiver) = sps2_events::channel();
+    let host = if config.general.event_host_context {
+        Some(HostContext {
+            hostname: sps2_platform::host::hostname(),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            os_build: sps2_platform::host::os_build(),
+            state_id: setup.state().get_active_state().await.ok(),
+        })
+    } else {
+        None
+    };
+    let (event_sender, event_receiver) = sps2_events::channel_with(ChannelConfig {
+        capacity: config.general.event_queue_limit,
+        host,
+        ..ChannelConfig::default()
+    });
+
+    let show_index_notice = !cli.global.json && shows_index_notice(&cli.command);
+
+    // `install --from-dir` resolves against the repository in that directory
+    let local_index = match &cli.command {
+        Commands::Install {
+            from_dir: Some(dir),
+            ..
+        } => Some(sps2_ops::local_repository(&config, dir).await?),
+        _ => None,
+    };
 
     // Build operations context
     let ops_ctx = build_ops_context(
@@ -88,22 +180,49 @@ async fn run(cli: Cli) -> Result<(), CliError> {
         event_sender.clone(),
         config.clone(),
         cli.global.check,
+        local_index.clone(),
     )
     .await?;
 
-    // Create output renderer
-    let renderer = OutputRenderer::new(
-        cli.global.json,
-        cli.global.color.unwrap_or(config.general.color),
-    );
+    // Read before the command runs, which may replace the cached index
+    let index_notice = if show_index_notice {
+        sps2_ops::index_notice(&ops_ctx).await.unwrap_or_else(|e| {
+            warn!("Failed to read index refresh status: {}", e);
+            None
+        })
+    } else {
+        None
+    };
+
+    // Show the plan and ask before changing installed packages
+    if confirm::asks_confirmation(&cli.command)
+        && !config.general.assume_yes
+        && !cli.global.check
+        && !cli.global.json
+        && confirm::is_interactive()
+    {
+        // Preview events would repeat the plan, so they go nowhere
+        let (plan_sender, _) = sps2_events::channel();
+        let plan_ctx =
+            build_ops_context(&setup, plan_sender, config.clone(), true, local_index).await?;
+        if !confirm::confirm(&cli.command, &plan_ctx, &renderer).await? {
+            return Err(CliError::Cancelled);
+        }
+    }
 
     // Create event handler
-    let colors_enabled = match cli.global.color.unwrap_or(config.general.color) {
-        sps2_types::ColorChoice::Always => true,
-        sps2_types::ColorChoice::Never => false,
-        sps2_types::ColorChoice::Auto => console::Term::stdout().features().colors_supported(),
+    let mut event_handler = EventHandler::new(theme, cli.global.debug)
+        .with_event_stream(matches!(cli.command, Commands::Build { worker: true, .. }));
+
+    // A project shell starts once the environment is ready; JSON output
+    // only describes the environment
+    let shell_command = match &cli.command {
+        Commands::Shell { command } if !cli.global.json => Some(command.clone()),
+        _ => None,
     };
-    let mut event_handler = EventHandler::new(colors_enabled, cli.global.debug);
+
+    // A manifest checked for drift fails when anything would change
+    let detects_drift = cli.global.check && matches!(cli.command, Commands::Sync { .. });
 
     // Execute command with event handling
     let result =
@@ -113,15 +232,62 @@ async fn run(cli: Cli) -> Result<(), CliError> {
     // Render final result
     renderer.render_result(&result)?;
 
-    // Show PATH reminder if this was an install operation and PATH not set
+    if let (true, OperationResult::InstallReport(report)) = (detects_drift, &result) {
+        let changes = report.installed.len() + report.updated.len() + report.removed.len();
+        if changes > 0 {
+            return Err(CliError::Drift(changes));
+        }
+    }
+
+    if let Some(notice) = index_notice {
+        eprintln!("Note: {notice}");
+    }
+
+    // Show how to set up the environment after an install if PATH lacks it
     if matches!(result, OperationResult::InstallReport(_)) {
-        show_path_reminder_if_needed();
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let sps2 = std::env::current_exe().unwrap_or_else(|_| config.bin_path().join("sps2"));
+        if let Some(hint) = sps2_ops::env_hint(&config.bin_path(), &path, &sps2) {
+            eprintln!();
+            eprintln!("{hint}");
+            eprintln!();
+        }
+    }
+
+    if let (Some(command), OperationResult::ProjectShell(shell)) = (shell_command, &result) {
+        let code = run_in_environment(shell, &command)?;
+        if code != 0 {
+            process::exit(code);
+        }
     }
 
     info!("Command completed successfully");
     Ok(())
 }
 
+/// Run `command`, or the shell in `$SHELL` when it is empty, with the
+/// variables of a project environment, and return its exit code
+fn run_in_environment(shell: &sps2_ops::ProjectShell, command: &[String]) -> Result<i32, CliError> {
+    let (program, args) = match command.split_first() {
+        Some((program, args)) => (program.clone(), args),
+        None => (
+            std::env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string()),
+            &[][..],
+        ),
+    };
+    let status = process::Command::new(&program)
+        .args(args)
+        .envs(&shell.variables)
+        .status()
+        .map_err(|e| {
+            CliError::Io(std::io::Error::new(
+                e.kind(),
+                format!("failed to run {program}: {e}"),
+            ))
+        })?;
+    Ok(status.code().unwrap_or(1))
+}
+
 /// Execute command with concurrent event handling
 async fn execute_command_with_events(
     command: Commands,
@@ -129,7 +295,14 @@ async fn execute_command_with_events(
     mut event_receiver: EventReceiver,
     event_handler: &mut EventHandler,
 ) -> Result<OperationResult, CliError> {
-    let mut command_future = Box::pin(execute_command(command, ops_ctx));
+    let mut command_future = Box::pin(async {
+        let scheduled = runs_scheduled_verification(&command);
+        let result = execute_command(command, &ops_ctx).await?;
+        if scheduled {
+            sps2_ops::scheduled_verification(&ops_ctx).await;
+        }
+        Ok(result)
+    });
 
     // Handle events concurrently with command execution
     loop {
@@ -140,6 +313,15 @@ async fn execute_command_with_events(
                 while let Ok(event) = event_receiver.try_recv() {
                     event_handler.handle_event(event);
                 }
+                let stats = event_receiver.stats();
+                if !stats.is_empty() {
+                    event_handler.handle_event(EventMessage::from_event(AppEvent::General(
+                        GeneralEvent::debug(format!(
+                            "Events not shown: {} progress updates coalesced, {} dropped from a full queue",
+                            stats.coalesced, stats.dropped
+                        )),
+                    )));
+                }
                 return result;
             }
 
@@ -157,105 +339,385 @@ async fn execute_command_with_events(
 /// Execute the specified command
 async fn execute_command(
     command: Commands,
-    ctx: sps2_ops::OpsCtx,
+    ctx: &sps2_ops::OpsCtx,
 ) -> Result<OperationResult, CliError> {
+    // Initialize only the components this command needs
+    ctx.prepare(command_requirements(&command)).await?;
+
     match command {
+        Commands::Init => unreachable!("init runs before system setup"),
+        Commands::SelfCommand(_) => unreachable!("self commands run before system setup"),
+        Commands::Schema(_) => unreachable!("schema commands run before loading config"),
+
         // Small operations (implemented in ops crate)
-        Commands::Reposync { yes } => {
-            let result = sps2_ops::reposync(&ctx, yes).await?;
+        Commands::Reposync { accept_keys } => {
+            // Trusting signing keys needs the explicit flag; `assume_yes`
+            // in the config does not answer this question
+            let keys = if accept_keys {
+                sps2_ops::KeyChangePolicy::Accept
+            } else if confirm::is_interactive() {
+                sps2_ops::KeyChangePolicy::Ask
+            } else {
+                sps2_ops::KeyChangePolicy::Refuse
+            };
+            let result = sps2_ops::reposync(ctx, keys).await?;
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Mirror {
+            repo,
+            dest,
+            packages,
+        } => {
+            let result = sps2_ops::mirror(ctx, &repo, &dest, &packages).await?;
             Ok(OperationResult::Success(result))
         }
 
         Commands::Repo(repo_cmd) => match repo_cmd {
             cli::RepoCommands::Add { name, url } => {
-                let result = sps2_ops::small_ops::add_repo(&ctx, &name, &url).await?;
+                let result = sps2_ops::small_ops::add_repo(ctx, &name, &url).await?;
                 Ok(OperationResult::Success(result))
             }
             cli::RepoCommands::List => {
-                let result = sps2_ops::small_ops::list_repos(&ctx).await?;
+                let result = sps2_ops::small_ops::list_repos(ctx).await?;
                 Ok(OperationResult::Success(result))
             }
             cli::RepoCommands::Remove { name } => {
-                let result = sps2_ops::small_ops::remove_repo(&ctx, &name).await?;
+                let result = sps2_ops::small_ops::remove_repo(ctx, &name).await?;
                 Ok(OperationResult::Success(result))
             }
         },
 
         Commands::Keys(keys_cmd) => match keys_cmd {
             KeysCommands::List => {
-                let result = sps2_ops::keys::keys_list(&ctx).await?;
+                let result = sps2_ops::keys::keys_list(ctx).await?;
                 Ok(OperationResult::Success(result))
             }
             KeysCommands::Import { file, comment } => {
-                let result = sps2_ops::keys::keys_import_from_file(&ctx, &file, comment).await?;
+                let result = sps2_ops::keys::keys_import_from_file(ctx, &file, comment).await?;
                 Ok(OperationResult::Success(result))
             }
             KeysCommands::Remove { key_id } => {
-                let result = sps2_ops::keys::keys_remove(&ctx, &key_id).await?;
+                let result = sps2_ops::keys::keys_remove(ctx, &key_id).await?;
                 Ok(OperationResult::Success(result))
             }
         },
 
+        Commands::Sbom(SbomCommands::Export { format, output }) => {
+            let result = sps2_ops::sbom_export(ctx, format, output.as_deref()).await?;
+            Ok(OperationResult::Success(result))
+        }
+
         Commands::List => {
-            let packages = sps2_ops::list_packages(&ctx).await?;
+            let packages = sps2_ops::list_packages(ctx).await?;
             Ok(OperationResult::PackageList(packages))
         }
 
         Commands::Info { package } => {
-            let info = sps2_ops::package_info(&ctx, &package).await?;
+            let info = sps2_ops::package_info(ctx, &package).await?;
             Ok(OperationResult::PackageInfo(info))
         }
 
-        Commands::Search { query } => {
-            let results = sps2_ops::search_packages(&ctx, &query).await?;
+        Commands::Files { package, tree } => {
+            let files = sps2_ops::package_files(ctx, &package, tree).await?;
+            Ok(OperationResult::PackageFiles(files))
+        }
+
+        Commands::Owns { path, orphans } => {
+            let ownership = sps2_ops::owns(ctx, path.as_deref(), orphans).await?;
+            Ok(OperationResult::FileOwnership(ownership))
+        }
+
+        Commands::Which { command, all } => {
+            let resolution = sps2_ops::which(ctx, &command, all).await?;
+            Ok(OperationResult::CommandResolution(resolution))
+        }
+
+        Commands::Env { package, shell } => {
+            let statements = sps2_ops::env(ctx, package.as_deref(), shell).await?;
+            Ok(OperationResult::Success(statements))
+        }
+
+        Commands::Shell { .. } => {
+            let dir = std::env::current_dir().map_err(CliError::Io)?;
+            let shell = sps2_ops::project_shell(ctx, &dir).await?;
+            Ok(OperationResult::ProjectShell(shell))
+        }
+
+        Commands::Search { query, remote } => {
+            let results = if remote {
+                sps2_ops::search_packages_remote(ctx, &query).await?
+            } else {
+                sps2_ops::search_packages(ctx, &query).await?
+            };
             Ok(OperationResult::SearchResults(results))
         }
 
-        Commands::Cleanup => {
-            let result = sps2_ops::cleanup(&ctx).await?;
+        Commands::Cleanup {
+            quarantine: true,
+            purge,
+            ..
+        } => {
+            let result = sps2_ops::cleanup_quarantine(ctx, purge).await?;
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Cleanup {
+            downloads: true,
+            purge,
+            ..
+        } => {
+            let result = sps2_ops::cleanup_downloads(ctx, purge).await?;
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Cleanup { .. } => {
+            let result = sps2_ops::cleanup(ctx).await?;
             // Also update the GC timestamp through SystemSetup (best effort)
-            if let Err(e) = crate::setup::SystemSetup::update_gc_timestamp_static().await {
+            if let Err(e) = crate::setup::SystemSetup::update_gc_timestamp_static(
+                &ctx.config.last_gc_timestamp_path(),
+            )
+            .await
+            {
                 tracing::warn!("Failed to update GC timestamp: {}", e);
             }
             Ok(OperationResult::Success(result))
         }
 
-        Commands::Rollback { state_id } => {
-            let state_info = sps2_ops::rollback(&ctx, state_id).await?;
+        Commands::Daemon {
+            interval,
+            once,
+            launchd_plist,
+        } => {
+            let interval = std::time::Duration::from_secs(interval);
+            let result = if launchd_plist {
+                sps2_ops::launchd_plist(&std::env::current_exe()?, interval)
+            } else {
+                sps2_ops::daemon(ctx, interval, once).await?
+            };
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Clean(CleanCommands::Cache { kinds, all }) => {
+            let result = if all {
+                sps2_ops::cache_clear(ctx, &sps2_types::CacheKind::ALL).await?
+            } else if kinds.is_empty() {
+                sps2_ops::cache_list(ctx).await?
+            } else {
+                sps2_ops::cache_clear(ctx, &kinds).await?
+            };
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Rollback {
+            target,
+            os_snapshot,
+        } => {
+            let state_id = match target {
+                Some(target) => Some(sps2_ops::resolve_state(ctx, &target).await?),
+                None => None,
+            };
+            if os_snapshot {
+                let guidance = sps2_ops::rollback_os_snapshot(ctx, state_id).await?;
+                return Ok(OperationResult::Success(guidance));
+            }
+            let state_info = sps2_ops::rollback(ctx, state_id).await?;
             Ok(OperationResult::StateInfo(state_info))
         }
 
-        Commands::History { all, verify, limit } => {
-            let history = sps2_ops::history(&ctx, all, verify, limit).await?;
+        Commands::Snapshot(snapshot_cmd) => {
+            let result = match snapshot_cmd {
+                SnapshotCommands::Create { name, state } => {
+                    sps2_ops::snapshot_create(ctx, &name, state).await?
+                }
+                SnapshotCommands::List => sps2_ops::snapshot_list(ctx).await?,
+                SnapshotCommands::Delete { name } => sps2_ops::snapshot_delete(ctx, &name).await?,
+            };
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Services(services_cmd) => {
+            let result = match services_cmd {
+                ServicesCommands::List => {
+                    let services = sps2_ops::services_list(ctx).await?;
+                    return Ok(OperationResult::Services(services));
+                }
+                ServicesCommands::Start { label } => sps2_ops::services_start(ctx, &label).await?,
+                ServicesCommands::Stop { label } => sps2_ops::services_stop(ctx, &label).await?,
+                ServicesCommands::Restart { label } => {
+                    sps2_ops::services_restart(ctx, &label).await?
+                }
+                ServicesCommands::Enable { label } => {
+                    sps2_ops::services_enable(ctx, &label).await?
+                }
+                ServicesCommands::Disable { label } => {
+                    sps2_ops::services_disable(ctx, &label).await?
+                }
+            };
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Store(StoreCommands::Stats { top }) => {
+            let stats = sps2_ops::store_stats(ctx, top).await?;
+            Ok(OperationResult::StoreStats(stats))
+        }
+
+        Commands::Store(StoreCommands::Relocate { new_path, keep_old }) => {
+            let result = sps2_ops::store_relocate(ctx, &new_path, keep_old).await?;
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::Store(StoreCommands::Compact) => {
+            let result = sps2_ops::store_compact(ctx).await?;
+            Ok(OperationResult::Success(result))
+        }
+
+        Commands::History {
+            detail: Some(target),
+            ..
+        } => {
+            let state_id = sps2_ops::resolve_state(ctx, &target).await?;
+            let detail = sps2_ops::history_detail(ctx, state_id).await?;
+            Ok(OperationResult::StateDetail(detail))
+        }
+
+        Commands::History {
+            all, verify, limit, ..
+        } => {
+            let history = sps2_ops::history(ctx, all, verify, limit).await?;
             Ok(OperationResult::StateHistory(history))
         }
 
         Commands::CheckHealth => {
-            let health = sps2_ops::check_health(&ctx).await?;
+            let health = sps2_ops::check_health(ctx).await?;
             Ok(OperationResult::HealthCheck(health))
         }
 
         // Large operations (delegate to specialized crates)
+        Commands::Install {
+            packages,
+            dry_run: true,
+            ..
+        } => {
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Install(&packages)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
         Commands::Install {
             packages,
             force_download,
+            require_hashes,
+            ..
         } => {
-            let report = sps2_ops::install(&ctx, &packages, force_download).await?;
+            let required_hashes = match require_hashes {
+                Some(path) => Some(sps2_ops::RequiredHashes::load(&path).await?),
+                None => None,
+            };
+            let report =
+                sps2_ops::install(ctx, &packages, force_download, required_hashes.as_ref()).await?;
             Ok(OperationResult::InstallReport(report))
         }
 
-        Commands::Update { packages } => {
-            let report = sps2_ops::update(&ctx, &packages).await?;
+        Commands::Update {
+            packages,
+            dry_run: true,
+            force,
+        } => {
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Update(&packages, force)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Upgrade {
+            packages,
+            dry_run: true,
+            force,
+            ..
+        } => {
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Upgrade(&packages, force)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Uninstall {
+            packages,
+            cascade,
+            force,
+            dry_run: true,
+            ..
+        } => {
+            let policy = cli::dependents_policy(cascade, force);
+            let plan =
+                sps2_ops::dry_run(ctx, PlannedOperation::Uninstall(&packages, policy)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Autoremove { dry_run: true } => {
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Autoremove).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Apply {
+            file,
+            force,
+            dry_run: true,
+        } => {
+            let changes = sps2_ops::ChangeSet::load(&file).await?;
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Apply(&changes, force)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Sync {
+            manifest,
+            dry_run: true,
+        } => {
+            let manifest = sps2_ops::Manifest::load(&manifest).await?;
+            let plan = sps2_ops::dry_run(ctx, PlannedOperation::Sync(&manifest)).await?;
+            Ok(OperationResult::Plan(plan))
+        }
+
+        Commands::Update {
+            packages, force, ..
+        } => {
+            let report = sps2_ops::update(ctx, &packages, force).await?;
+            Ok(OperationResult::InstallReport(report))
+        }
+
+        Commands::Upgrade {
+            packages, force, ..
+        } => {
+            let report = sps2_ops::upgrade(ctx, &packages, force).await?;
+            Ok(OperationResult::InstallReport(report))
+        }
+
+        Commands::Uninstall {
+            packages,
+            cascade,
+            force,
+            ..
+        } => {
+            let policy = cli::dependents_policy(cascade, force);
+            let report = sps2_ops::uninstall(ctx, &packages, policy).await?;
+            Ok(OperationResult::InstallReport(report))
+        }
+
+        Commands::Autoremove { .. } => {
+            let report = sps2_ops::autoremove(ctx).await?;
+            Ok(OperationResult::InstallReport(report))
+        }
+
+        Commands::Apply { file, force, .. } => {
+            let changes = sps2_ops::ChangeSet::load(&file).await?;
+            let report = sps2_ops::apply(ctx, &changes, force).await?;
             Ok(OperationResult::InstallReport(report))
         }
 
-        Commands::Upgrade { packages } => {
-            let report = sps2_ops::upgrade(&ctx, &packages).await?;
+        Commands::Sync { manifest, .. } => {
+            let manifest = sps2_ops::Manifest::load(&manifest).await?;
+            let report = sps2_ops::sync(ctx, &manifest).await?;
             Ok(OperationResult::InstallReport(report))
         }
 
-        Commands::Uninstall { packages } => {
-            let report = sps2_ops::uninstall(&ctx, &packages).await?;
+        Commands::Reinstall { packages } => {
+            let report = sps2_ops::reinstall(ctx, &packages).await?;
             Ok(OperationResult::InstallReport(report))
         }
 
@@ -264,9 +726,30 @@ async fn execute_command(
             output_dir,
             network,
             jobs,
+            recursive,
+            recipe_dir,
+            worker,
+            ..
         } => {
             let output_path = output_dir.as_deref();
-            let report = sps2_ops::build(&ctx, &recipe, output_path, network, jobs).await?;
+            if worker {
+                let report =
+                    sps2_ops::worker_build(ctx, &recipe, output_path, network, jobs).await?;
+                return Ok(OperationResult::BuildReport(report));
+            }
+            if recursive {
+                let reports = sps2_ops::build_recursive(
+                    ctx,
+                    &recipe,
+                    recipe_dir.as_deref(),
+                    output_path,
+                    network,
+                    jobs,
+                )
+                .await?;
+                return Ok(OperationResult::BuildReports(reports));
+            }
+            let report = sps2_ops::build(ctx, &recipe, output_path, network, jobs).await?;
             Ok(OperationResult::BuildReport(report))
         }
 
@@ -286,12 +769,12 @@ async fn execute_command(
                         "--manifest is required with --directory".to_string(),
                     ));
                 };
-                sps2_ops::pack_from_directory(&ctx, &dir, &manifest_path, output_path).await?
+                sps2_ops::pack_from_directory(ctx, &dir, &manifest_path, output_path).await?
             } else if let Some(rec) = recipe {
                 if no_post {
-                    sps2_ops::pack_from_recipe_no_post(&ctx, &rec, output_path).await?
+                    sps2_ops::pack_from_recipe_no_post(ctx, &rec, output_path).await?
                 } else {
-                    sps2_ops::pack_from_recipe(&ctx, &rec, output_path).await?
+                    sps2_ops::pack_from_recipe(ctx, &rec, output_path).await?
                 }
             } else {
                 // This case should be prevented by clap's arg group
@@ -303,46 +786,260 @@ async fn execute_command(
         }
 
         Commands::SelfUpdate { skip_verify, force } => {
-            let result = sps2_ops::self_update(&ctx, skip_verify, force).await?;
+            let result = sps2_ops::self_update(ctx, skip_verify, force).await?;
             Ok(OperationResult::Success(result))
         }
 
+        Commands::Verify {
+            history: true,
+            limit,
+            ..
+        } => {
+            let history = sps2_ops::verify_history(ctx, limit).await?;
+            Ok(OperationResult::VerificationHistory(history))
+        }
+
+        Commands::Verify {
+            diff: true,
+            package: Some(package),
+            unified,
+            ..
+        } => {
+            let diff = sps2_ops::package_diff(ctx, &package, unified).await?;
+            Ok(OperationResult::PackageDiff(diff))
+        }
+
         Commands::Verify {
             heal,
             level,
+            force_hash,
             scope,
             sync_refcounts,
+            ..
         } => {
-            let result = sps2_ops::verify(&ctx, heal, &level, &scope, sync_refcounts).await?;
+            let level = if force_hash {
+                "full".to_string()
+            } else {
+                level
+            };
+            let result = sps2_ops::verify(ctx, heal, &level, &scope, sync_refcounts).await?;
             Ok(OperationResult::VerificationResult(result))
         }
     }
 }
 
-/// Build operations context with all required components
+/// Lazily initialized components required by a command
+fn command_requirements(command: &Commands) -> Requirements {
+    use sps2_ops::requirements;
+
+    match command {
+        Commands::Init | Commands::SelfCommand(_) | Commands::Schema(_) => Requirements::NONE,
+        Commands::Install { .. } => requirements::INSTALL,
+        Commands::Update { .. } | Commands::Upgrade { .. } => requirements::UPDATE,
+        Commands::Uninstall { .. } | Commands::Autoremove { .. } => requirements::UNINSTALL,
+        Commands::Apply { .. } => requirements::APPLY,
+        Commands::Sync { .. } => requirements::SYNC,
+        Commands::Reinstall { .. } => requirements::REINSTALL,
+        Commands::Build { .. } => requirements::BUILD,
+        Commands::Pack { .. } => requirements::PACK,
+        Commands::List => requirements::LIST_PACKAGES,
+        Commands::Info { .. } => requirements::PACKAGE_INFO,
+        Commands::Files { .. } => requirements::PACKAGE_FILES,
+        Commands::Owns { .. } => requirements::OWNS,
+        Commands::Which { .. } => requirements::WHICH,
+        Commands::Env { .. } => requirements::ENV,
+        Commands::Shell { .. } => requirements::PROJECT_SHELL,
+        Commands::Search { remote: false, .. } => requirements::SEARCH_PACKAGES,
+        Commands::Search { remote: true, .. } => requirements::SEARCH_PACKAGES_REMOTE,
+        Commands::Reposync { .. } => requirements::REPOSYNC,
+        Commands::Mirror { .. } => requirements::MIRROR,
+        Commands::Cleanup {
+            quarantine: true, ..
+        } => requirements::CLEANUP_QUARANTINE,
+        Commands::Cleanup {
+            downloads: true, ..
+        } => requirements::CLEANUP_DOWNLOADS,
+        Commands::Cleanup { .. } => requirements::CLEANUP,
+        Commands::Clean(_) => requirements::CACHE,
+        Commands::Daemon {
+            launchd_plist: true,
+            ..
+        } => Requirements::NONE,
+        Commands::Daemon { .. } => requirements::DAEMON,
+        Commands::Rollback {
+            os_snapshot: true, ..
+        } => requirements::ROLLBACK_OS_SNAPSHOT,
+        Commands::Rollback { .. } => requirements::ROLLBACK,
+        Commands::Snapshot(_) => requirements::SNAPSHOT,
+        Commands::Services(_) => requirements::SERVICES,
+        Commands::Store(StoreCommands::Compact) => requirements::STORE_COMPACT,
+        Commands::Store(StoreCommands::Relocate { .. }) => requirements::STORE_RELOCATE,
+        Commands::Store(StoreCommands::Stats { .. }) => requirements::STORE_STATS,
+        Commands::History { .. } => requirements::HISTORY,
+        Commands::CheckHealth => requirements::CHECK_HEALTH,
+        Commands::SelfUpdate { .. } => requirements::SELF_UPDATE,
+        Commands::Verify { history: true, .. } => requirements::VERIFY_HISTORY,
+        Commands::Verify { diff: true, .. } => requirements::PACKAGE_DIFF,
+        Commands::Verify { heal: true, .. } => requirements::VERIFY_HEAL,
+        Commands::Verify { .. } => requirements::VERIFY,
+        Commands::Repo(_) => requirements::REPO_CONFIG,
+        Commands::Keys(_) => requirements::KEYS,
+        Commands::Sbom(_) => requirements::SBOM_EXPORT,
+    }
+}
+
+/// Whether a command is followed by any verification the guard schedule has
+/// due; `verify` itself records a run instead
+fn runs_scheduled_verification(command: &Commands) -> bool {
+    !matches!(command, Commands::Verify { .. })
+}
+
+/// Whether a command reports the background index refresh status
+fn shows_index_notice(command: &Commands) -> bool {
+    matches!(
+        command,
+        Commands::List | Commands::Info { .. } | Commands::Search { remote: false, .. }
+    )
+}
+
+/// Remove the installation after showing what goes and asking
+///
+/// Only `--yes` on this invocation skips the question; `assume_yes` in the
+/// config does not. Without it, JSON output and a non-interactive stdin
+/// refuse instead of going ahead.
+async fn self_destruct(
+    config: &Config,
+    keep_store: bool,
+    global: &cli::GlobalArgs,
+    renderer: &OutputRenderer,
+) -> Result<(), CliError> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let plan = sps2_ops::self_destruct(config, home.as_deref(), keep_store, true).await;
+    if global.check || plan.removals.is_empty() {
+        renderer.render_result(&OperationResult::SelfDestructReport(plan))?;
+        return Ok(());
+    }
+
+    if !global.yes {
+        if global.json || !confirm::is_interactive() {
+            return Err(CliError::InvalidArguments(
+                "`self destruct` removes the installation; pass --yes to run it without asking"
+                    .to_string(),
+            ));
+        }
+        if !confirm::confirm_self_destruct(&plan, renderer)? {
+            return Err(CliError::Cancelled);
+        }
+    }
+
+    let report = sps2_ops::self_destruct(config, home.as_deref(), keep_store, false).await;
+    let complete = report.is_complete();
+    renderer.render_result(&OperationResult::SelfDestructReport(report))?;
+    if !complete {
+        return Err(CliError::Io(std::io::Error::other(
+            "some paths could not be removed",
+        )));
+    }
+    Ok(())
+}
+
+/// Print the JSON Schema of the report `name`, or every schema in an
+/// object keyed by report name
+fn dump_schemas(name: Option<&str>) -> Result<(), CliError> {
+    let Some(name) = name else {
+        let mut schemas = serde_json::Map::new();
+        for schema in &sps2_types::REPORT_SCHEMAS {
+            let value = serde_json::from_str(schema.schema)
+                .map_err(|e| CliError::Io(std::io::Error::other(e)))?;
+            schemas.insert(schema.name.to_string(), value);
+        }
+        let json = serde_json::to_string_pretty(&schemas)
+            .map_err(|e| CliError::Io(std::io::Error::other(e)))?;
+        println!("{json}");
+        return Ok(());
+    };
+
+    let schema = sps2_types::report_schema(name).ok_or_else(|| {
+        let names: Vec<&str> = sps2_types::REPORT_SCHEMAS
+            .iter()
+            .map(|schema| schema.name)
+            .collect();
+        CliError::InvalidArguments(format!(
+            "no schema named {name}; choose one of {}",
+            names.join(", ")
+        ))
+    })?;
+    print!("{}", schema.schema);
+    Ok(())
+}
+
+/// Config files in use, each with whether it exists yet
+fn config_files(global: &cli::GlobalArgs) -> Vec<(PathBuf, bool)> {
+    let root = cli_root(global);
+    let config = global
+        .config
+        .clone()
+        .or_else(|| Config::default_path_in(root.as_deref()).ok());
+    let builder = global
+        .builder_config
+        .clone()
+        .or_else(|| sps2_config::BuilderConfig::default_path_in(root.as_deref()).ok());
+    config
+        .into_iter()
+        .chain(builder)
+        .map(|path| {
+            let existed = path.exists();
+            (path, existed)
+        })
+        .collect()
+}
+
+/// Build operations context; heavy components are created on demand
 async fn build_ops_context(
     setup: &SystemSetup,
     event_sender: EventSender,
     config: Config,
     check_mode: bool,
+    index: Option<IndexManager>,
 ) -> Result<sps2_ops::OpsCtx, CliError> {
-    let ctx = OpsContextBuilder::new()
+    let mut builder = OpsContextBuilder::new()
         .with_store(setup.store().clone())
         .with_state(setup.state().clone())
-        .with_index(setup.index().clone())
-        .with_net(setup.net().clone())
-        .with_resolver(setup.resolver().clone())
-        .with_builder(setup.builder())
         .with_event_sender(event_sender)
         .with_config(config)
         .with_check_mode(check_mode)
-        .build()?;
+        .with_command_line(std::env::args().collect::<Vec<_>>().join(" "));
+    if let Some(index) = index {
+        builder = builder.with_index(index);
+    }
+    let ctx = builder.build()?;
 
     Ok(ctx)
 }
 
+/// Root given on the command line or in `SPS2_ROOT`
+///
+/// Logging and the location of the config files are settled before the
+/// configuration is loaded, so the root is read here directly.
+fn cli_root(global: &cli::GlobalArgs) -> Option<PathBuf> {
+    global.root.clone().or_else(|| {
+        std::env::var_os("SPS2_ROOT")
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+/// Log directory, honoring the root given on the command line or in
+/// `SPS2_ROOT`
+fn log_dir(global: &cli::GlobalArgs) -> PathBuf {
+    match cli_root(global) {
+        Some(root) => root.join(fixed_paths::LOGS_DIR.trim_start_matches('/')),
+        None => PathBuf::from(fixed_paths::LOGS_DIR),
+    }
+}
+
 /// Initialize tracing/logging
-fn init_tracing(json_mode: bool, debug_enabled_flag: bool) {
+fn init_tracing(json_mode: bool, debug_enabled_flag: bool, log_dir: &Path) {
     // Check if debug logging is enabled
     let debug_enabled = std::env::var("RUST_LOG").is_ok() || debug_enabled_flag;
 
@@ -350,7 +1047,6 @@ fn init_tracing(json_mode: bool, debug_enabled_flag: bool) {
         // JSON mode: suppress all console output to avoid contaminating JSON
         if debug_enabled {
             // In debug mode with JSON, still log to file
-            let log_dir = std::path::Path::new(fixed_paths::LOGS_DIR);
             if std::fs::create_dir_all(log_dir).is_ok() {
                 let log_file = log_dir.join(format!(
                     "sps2-{}.log",
@@ -382,7 +1078,6 @@ fn init_tracing(json_mode: bool, debug_enabled_flag: bool) {
             .init();
     } else if debug_enabled {
         // Debug mode: structured JSON logs to file
-        let log_dir = std::path::Path::new(fixed_paths::LOGS_DIR);
         if let Err(e) = std::fs::create_dir_all(log_dir) {
             eprintln!("Warning: Failed to create log directory: {e}");
         }
@@ -430,24 +1125,6 @@ fn init_tracing(json_mode: bool, debug_enabled_flag: bool) {
     }
 }
 
-/// Show PATH reminder if needed
-fn show_path_reminder_if_needed() {
-    let path = std::env::var("PATH").unwrap_or_default();
-    if !path.contains(fixed_paths::BIN_DIR) {
-        eprintln!();
-        eprintln!(
-            "Add {} to your PATH to use installed packages:",
-            fixed_paths::BIN_DIR
-        );
-        eprintln!(
-            "   echo 'export PATH=\"{}:$PATH\"' >> ~/.zshrc",
-            fixed_paths::BIN_DIR
-        );
-        eprintln!("   source ~/.zshrc");
-        eprintln!();
-    }
-}
-
 /// Apply CLI configuration overrides (highest precedence)
 fn apply_cli_config(
     config: &mut Config,
@@ -455,17 +1132,65 @@ fn apply_cli_config(
     command: &cli::Commands,
 ) -> Result<(), CliError> {
     // Global CLI flags override everything
+    if let Some(root) = &global.root {
+        config.paths.root = Some(root.clone());
+    }
     if let Some(color) = &global.color {
         config.general.color = *color;
     }
+    if global.offline {
+        config.network.offline = true;
+    }
+    if global.wide {
+        config.general.ellipsis = EllipsisPolicy::Off;
+    }
+    if global.yes {
+        config.general.assume_yes = true;
+    }
 
     // Command-specific CLI flags
+    match command {
+        cli::Commands::Install {
+            no_recommends,
+            conflict_policy,
+            ..
+        } => {
+            if *no_recommends {
+                config.general.install_recommends = false;
+            }
+            if let Some(policy) = conflict_policy {
+                config.general.conflict_policy = *policy;
+            }
+        }
+        cli::Commands::Uninstall {
+            autoremove: true, ..
+        }
+        | cli::Commands::Upgrade {
+            autoremove: true, ..
+        } => config.general.autoremove = true,
+        _ => {}
+    }
     if let cli::Commands::Build {
-        jobs: Some(job_count),
+        jobs,
+        no_cache,
+        remote,
+        worker,
         ..
     } = command
     {
-        config.builder.build.build_jobs = *job_count;
+        if let Some(job_count) = jobs {
+            config.builder.build.build_jobs = *job_count;
+        }
+        if *no_cache {
+            config.builder.performance.cache.artifacts = false;
+        }
+        if let Some(host) = remote {
+            config.builder.remote.host = Some(host.clone());
+        }
+        // A worker builds itself rather than passing the build on
+        if *worker {
+            config.builder.remote.host = None;
+        }
     }
 
     Ok(())
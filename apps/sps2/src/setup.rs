@@ -1,11 +1,7 @@
 //! System setup and initialization
 
 use crate::error::CliError;
-use sps2_builder::Builder;
-use sps2_config::{fixed_paths, Config};
-use sps2_index::IndexManager;
-use sps2_net::NetClient;
-use sps2_resolver::Resolver;
+use sps2_config::Config;
 use sps2_state::StateManager;
 use sps2_store::PackageStore;
 use std::path::{Path, PathBuf};
@@ -16,10 +12,6 @@ pub struct SystemSetup {
     config: Config,
     store: Option<PackageStore>,
     state: Option<StateManager>,
-    index: Option<IndexManager>,
-    net: Option<NetClient>,
-    resolver: Option<Resolver>,
-    builder: Option<Builder>,
 }
 
 impl SystemSetup {
@@ -29,14 +21,13 @@ impl SystemSetup {
             config,
             store: None,
             state: None,
-            index: None,
-            net: None,
-            resolver: None,
-            builder: None,
         }
     }
 
-    /// Initialize all system components
+    /// Initialize the store, state and startup maintenance
+    ///
+    /// The index, network client, resolver and builder are created on demand
+    /// by the operations context.
     pub async fn initialize(&mut self) -> Result<(), CliError> {
         info!("Initializing sps2 system components");
 
@@ -46,10 +37,6 @@ impl SystemSetup {
         // Initialize core components
         self.init_store().await?;
         self.init_state().await?;
-        self.init_index().await?;
-        self.init_net().await?;
-        self.init_resolver().await?;
-        self.init_builder().await?;
 
         // Initialize platform cache for optimized tool discovery
         self.init_platform_cache().await?;
@@ -71,47 +58,26 @@ impl SystemSetup {
         self.state.as_ref().expect("state not initialized")
     }
 
-    /// Get index manager
-    pub fn index(&self) -> &IndexManager {
-        self.index.as_ref().expect("index not initialized")
-    }
-
-    /// Get network client
-    pub fn net(&self) -> &NetClient {
-        self.net.as_ref().expect("net not initialized")
-    }
-
-    /// Get resolver
-    pub fn resolver(&self) -> &Resolver {
-        self.resolver.as_ref().expect("resolver not initialized")
-    }
-
-    /// Get builder
-    pub fn builder(&self) -> Builder {
-        self.builder
-            .as_ref()
-            .expect("builder not initialized")
-            .clone()
-    }
-
     /// Ensure required system directories exist
     async fn ensure_system_directories(&self) -> Result<(), CliError> {
         let required_dirs = [
-            fixed_paths::PREFIX,
-            fixed_paths::STORE_DIR,
-            fixed_paths::STATES_DIR,
-            fixed_paths::LIVE_DIR,
-            fixed_paths::LOGS_DIR,
-            fixed_paths::KEYS_DIR,
+            self.config.prefix_path(),
+            self.config.store_path(),
+            self.config.state_path(),
+            self.config.live_path(),
+            self.config.logs_path(),
+            self.config.keys_path(),
         ];
 
-        for dir in &required_dirs {
-            let path = Path::new(dir);
+        for path in &required_dirs {
             if !path.exists() {
-                debug!("Creating directory: {}", dir);
-                tokio::fs::create_dir_all(path)
-                    .await
-                    .map_err(|e| CliError::Setup(format!("Failed to create {dir}: {e}")))?;
+                debug!("Creating directory: {}", path.display());
+                tokio::fs::create_dir_all(path).await.map_err(|e| {
+                    CliError::Setup(format!(
+                        "Failed to create {}: {e} (run `sudo sps2 init` to set up the installation)",
+                        path.display()
+                    ))
+                })?;
             }
         }
 
@@ -126,26 +92,20 @@ impl SystemSetup {
 
     /// Seed default repositories and embedded public keys on first run
     async fn seed_default_repositories_and_keys(&self) -> Result<(), CliError> {
-        use sps2_config::fixed_paths;
         use tokio::fs;
 
-        // Initialize repos in config if empty
-        // For now, write defaults into config file only if none are present
-        // Embedded public key placeholder (replace with real key for production)
-        let bootstrap_key = "RWSGOq2NVecA2UPNdBUZykp1MLhfMmkAK/SZSjK3bpq2q7I8LbSVVBDm".to_string();
-
         // Ensure keys dir exists
-        fs::create_dir_all(fixed_paths::KEYS_DIR)
+        let keys_dir = self.config.keys_path();
+        fs::create_dir_all(&keys_dir)
             .await
             .map_err(|e| CliError::Setup(format!("Failed to create keys dir: {e}")))?;
 
         // Initialize trusted_keys.json if missing using KeyManager (ensures correct key_id)
-        let keys_file = std::path::Path::new(fixed_paths::KEYS_DIR).join("trusted_keys.json");
+        let keys_file = keys_dir.join("trusted_keys.json");
         if !keys_file.exists() {
-            let mut key_manager =
-                sps2_ops::keys::KeyManager::new(std::path::PathBuf::from(fixed_paths::KEYS_DIR));
+            let mut key_manager = sps2_ops::keys::KeyManager::new(keys_dir);
             key_manager
-                .initialize_with_bootstrap(&bootstrap_key)
+                .initialize_with_bootstrap(sps2_ops::keys::BOOTSTRAP_KEY)
                 .await
                 .map_err(|e| CliError::Setup(format!("Failed to initialize bootstrap key: {e}")))?;
         }
@@ -156,20 +116,23 @@ impl SystemSetup {
     /// Check permissions on system directories
     async fn check_permissions(&self) -> Result<(), CliError> {
         let paths_to_check = [
-            fixed_paths::PREFIX,
-            fixed_paths::STORE_DIR,
-            fixed_paths::STATES_DIR,
-            fixed_paths::LIVE_DIR,
+            self.config.prefix_path(),
+            self.config.store_path(),
+            self.config.state_path(),
+            self.config.live_path(),
         ];
 
         for path in &paths_to_check {
             let metadata = tokio::fs::metadata(path)
                 .await
-                .map_err(|e| CliError::Setup(format!("Cannot access {path}: {e}")))?;
+                .map_err(|e| CliError::Setup(format!("Cannot access {}: {e}", path.display())))?;
 
             // Check if we can write to the directory
             if metadata.permissions().readonly() {
-                return Err(CliError::Setup(format!("No write permission for {path}")));
+                return Err(CliError::Setup(format!(
+                    "No write permission for {} (run `sudo sps2 init` to take ownership)",
+                    path.display()
+                )));
             }
         }
 
@@ -179,8 +142,9 @@ impl SystemSetup {
     /// Initialize package store
     async fn init_store(&mut self) -> Result<(), CliError> {
         debug!("Initializing package store");
-        let store_path = Path::new(fixed_paths::STORE_DIR);
-        let store = PackageStore::new(store_path.to_path_buf());
+        let store = PackageStore::new(self.config.store_path())
+            .with_link_strategy(self.config.cas.link_strategy)
+            .with_temp_path(self.config.tmp_path());
 
         self.store = Some(store);
         Ok(())
@@ -189,8 +153,7 @@ impl SystemSetup {
     /// Initialize state manager
     async fn init_state(&mut self) -> Result<(), CliError> {
         debug!("Initializing state manager");
-        let state_path = Path::new(fixed_paths::PREFIX);
-        let state = StateManager::new(state_path)
+        let state = StateManager::new(&self.config.prefix_path())
             .await
             .map_err(|e| CliError::Setup(format!("Failed to initialize state: {e}")))?;
 
@@ -198,77 +161,6 @@ impl SystemSetup {
         Ok(())
     }
 
-    /// Initialize index manager
-    async fn init_index(&mut self) -> Result<(), CliError> {
-        debug!("Initializing index manager");
-        let cache_path = Path::new(fixed_paths::PREFIX);
-        let mut index = IndexManager::new(cache_path);
-
-        // Try to load cached index
-        match index.load(None).await {
-            Ok(()) => {
-                debug!("Loaded cached index");
-            }
-            Err(e) => {
-                warn!("Failed to load cached index, will need reposync: {}", e);
-                // Create empty index for now
-                let empty_index = sps2_index::Index::new();
-                let json = empty_index
-                    .to_json()
-                    .map_err(|e| CliError::Setup(format!("Failed to create empty index: {e}")))?;
-                index
-                    .load(Some(&json))
-                    .await
-                    .map_err(|e| CliError::Setup(format!("Failed to load empty index: {e}")))?;
-            }
-        }
-
-        self.index = Some(index);
-        Ok(())
-    }
-
-    /// Initialize network client
-    async fn init_net(&mut self) -> Result<(), CliError> {
-        debug!("Initializing network client");
-
-        // Create NetConfig from our configuration
-        let net_config = sps2_net::NetConfig {
-            timeout: std::time::Duration::from_secs(self.config.network.timeout),
-            connect_timeout: std::time::Duration::from_secs(30),
-            pool_idle_timeout: std::time::Duration::from_secs(90),
-            pool_max_idle_per_host: 10,
-            retry_count: self.config.network.retries,
-            retry_delay: std::time::Duration::from_secs(self.config.network.retry_delay),
-            user_agent: format!("sps2/{}", env!("CARGO_PKG_VERSION")),
-        };
-
-        let net = sps2_net::NetClient::new(net_config)
-            .map_err(|e| CliError::Setup(format!("Failed to create network client: {e}")))?;
-
-        self.net = Some(net);
-        Ok(())
-    }
-
-    /// Initialize resolver
-    async fn init_resolver(&mut self) -> Result<(), CliError> {
-        debug!("Initializing resolver");
-        let index = self.index.as_ref().unwrap().clone();
-        let resolver = Resolver::new(index);
-
-        self.resolver = Some(resolver);
-        Ok(())
-    }
-
-    /// Initialize builder
-    async fn init_builder(&mut self) -> Result<(), CliError> {
-        debug!("Initializing builder");
-        let net = self.net.as_ref().unwrap().clone();
-        let builder = Builder::new().with_net(net);
-
-        self.builder = Some(builder);
-        Ok(())
-    }
-
     /// Initialize platform cache
     async fn init_platform_cache(&mut self) -> Result<(), CliError> {
         debug!("Initializing platform cache");
@@ -299,6 +191,19 @@ impl SystemSetup {
                 .await
                 .map_err(|e| CliError::Setup(format!("Startup GC failed: {e}")))?;
 
+            // Delete states beyond the CAS retention policy
+            let cas = &self.config.cas;
+            let pruned = state
+                .prune_states(cas.keep_states_count, cas.keep_days, cas.dry_run)
+                .await
+                .map_err(|e| CliError::Setup(format!("Startup GC failed: {e}")))?;
+            if pruned.states_deleted > 0 {
+                info!(
+                    "Startup GC: deleted {} states beyond retention, releasing {} bytes",
+                    pruned.states_deleted, pruned.bytes_released
+                );
+            }
+
             // Clean up orphaned packages
             let store = self.store.as_ref().unwrap();
             let cleaned_packages = store
@@ -320,6 +225,23 @@ impl SystemSetup {
         // Clean orphaned staging directories
         self.clean_orphaned_staging().await?;
 
+        // Quarantine package directories an interrupted ingestion left behind
+        let store = self.store.as_ref().unwrap();
+        match store.quarantine_incomplete_packages().await {
+            Ok(scan) => {
+                if scan.abandoned > 0 {
+                    debug!("Removed {} abandoned package ingestions", scan.abandoned);
+                }
+                if !scan.quarantined.is_empty() {
+                    warn!(
+                        "Quarantined {} incomplete package directories; run `sps2 verify --heal` to restore them",
+                        scan.quarantined.len()
+                    );
+                }
+            }
+            Err(e) => warn!("Failed to scan the store for incomplete packages: {e}"),
+        }
+
         Ok(())
     }
 
@@ -346,12 +268,12 @@ impl SystemSetup {
 
     /// Clean up orphaned staging directories (only safe to remove)
     async fn clean_orphaned_staging(&self) -> Result<(), CliError> {
-        let states_dir = Path::new(fixed_paths::STATES_DIR);
+        let states_dir = self.config.state_path();
         if !states_dir.exists() {
             return Ok(());
         }
 
-        let mut entries = tokio::fs::read_dir(states_dir)
+        let mut entries = tokio::fs::read_dir(&states_dir)
             .await
             .map_err(|e| CliError::Setup(format!("Failed to read states directory: {e}")))?;
 
@@ -433,12 +355,11 @@ impl SystemSetup {
 
     /// Get the path to the GC timestamp file
     fn gc_timestamp_path(&self) -> PathBuf {
-        Path::new(fixed_paths::LAST_GC_TIMESTAMP).to_path_buf()
+        self.config.last_gc_timestamp_path()
     }
 
     /// Update GC timestamp - public static method for ops crate
-    pub async fn update_gc_timestamp_static() -> Result<(), CliError> {
-        let timestamp_path = std::path::Path::new(fixed_paths::LAST_GC_TIMESTAMP);
+    pub async fn update_gc_timestamp_static(timestamp_path: &Path) -> Result<(), CliError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -0,0 +1,117 @@
+//! Color theme applied to terminal output
+
+use comfy_table::{Attribute, Cell, Color, Table};
+use console::Style;
+use sps2_config::{StyleSpec, ThemeColor, ThemeConfig, ThemeRole};
+
+/// Theme styles, or plain text when colors are disabled
+#[derive(Clone)]
+pub struct Theme {
+    config: ThemeConfig,
+    colors: bool,
+}
+
+impl Theme {
+    /// Create a theme; `colors` is the resolved color choice
+    pub fn new(config: ThemeConfig, colors: bool) -> Self {
+        Self { config, colors }
+    }
+
+    /// Whether output is colored
+    pub fn colors(&self) -> bool {
+        self.colors
+    }
+
+    /// Style `text` for `role`
+    pub fn paint(&self, role: ThemeRole, text: &str) -> String {
+        if !self.colors {
+            return text.to_string();
+        }
+        console_style(self.config.style(role))
+            .apply_to(text)
+            .to_string()
+    }
+
+    /// Table cell styled for `role`
+    pub fn cell(&self, role: ThemeRole, text: impl ToString) -> Cell {
+        let cell = Cell::new(text.to_string());
+        if !self.colors {
+            return cell;
+        }
+        let spec = self.config.style(role);
+        let mut cell = match spec.color {
+            Some(color) => cell.fg(Color::AnsiValue(palette_index(color))),
+            None => cell,
+        };
+        for (set, attribute) in [
+            (spec.bold, Attribute::Bold),
+            (spec.dim, Attribute::Dim),
+            (spec.italic, Attribute::Italic),
+            (spec.underlined, Attribute::Underlined),
+        ] {
+            if set {
+                cell = cell.add_attribute(attribute);
+            }
+        }
+        cell
+    }
+
+    /// Style `table` according to the color choice rather than whether
+    /// stdout is a terminal
+    pub fn apply_to_table(&self, table: &mut Table) {
+        if self.colors {
+            table.enforce_styling();
+        } else {
+            table.force_no_tty();
+        }
+    }
+}
+
+fn console_style(spec: StyleSpec) -> Style {
+    let mut style = Style::new().force_styling(true);
+    style = match spec.color {
+        Some(ThemeColor::Named { index, bright }) => {
+            let style = style.fg(named_color(index));
+            if bright {
+                style.bright()
+            } else {
+                style
+            }
+        }
+        Some(ThemeColor::Palette(index)) => style.color256(index),
+        None => style,
+    };
+    if spec.bold {
+        style = style.bold();
+    }
+    if spec.dim {
+        style = style.dim();
+    }
+    if spec.italic {
+        style = style.italic();
+    }
+    if spec.underlined {
+        style = style.underlined();
+    }
+    style
+}
+
+fn named_color(index: u8) -> console::Color {
+    match index {
+        0 => console::Color::Black,
+        1 => console::Color::Red,
+        2 => console::Color::Green,
+        3 => console::Color::Yellow,
+        4 => console::Color::Blue,
+        5 => console::Color::Magenta,
+        6 => console::Color::Cyan,
+        _ => console::Color::White,
+    }
+}
+
+fn palette_index(color: ThemeColor) -> u8 {
+    match color {
+        ThemeColor::Named { index, bright } => index + if bright { 8 } else { 0 },
+        ThemeColor::Palette(index) => index,
+    }
+}
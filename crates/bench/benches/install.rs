@@ -0,0 +1,45 @@
+//! Install hot paths, timed with criterion
+//!
+//! `cargo bench -p sps2-bench` runs every scenario against one fixture, sized
+//! through the `SPS2_BENCH_*` variables of
+//! [`sps2_bench::fixture_spec_from_env`]. Each iteration sets up its own
+//! environment and only the operation under test is timed. Results are
+//! written as JSON to `target/criterion/install/<scenario>/new/`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use sps2_bench::{Fixture, Scenario};
+use std::time::Duration;
+
+fn scenarios(c: &mut Criterion) {
+    let runtime = tokio::runtime::Runtime::new().expect("tokio runtime");
+    let spec = sps2_bench::fixture_spec_from_env().expect("fixture spec");
+    let fixture = runtime
+        .block_on(Fixture::generate(&spec))
+        .expect("generate fixture");
+
+    let mut group = c.benchmark_group("install");
+    // Iterations build whole environments; criterion's default of 100 samples
+    // would take minutes per scenario
+    group.sample_size(10);
+    for scenario in Scenario::ALL {
+        group.bench_function(scenario.as_str(), |b| {
+            b.to_async(&runtime).iter_custom(|iters| {
+                let fixture = &fixture;
+                async move {
+                    let mut total = Duration::ZERO;
+                    for _ in 0..iters {
+                        match scenario.run(fixture).await {
+                            Ok(elapsed) => total += elapsed,
+                            Err(e) => panic!("{scenario} failed: {e}"),
+                        }
+                    }
+                    total
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scenarios);
+criterion_main!(benches);
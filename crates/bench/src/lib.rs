@@ -0,0 +1,52 @@
+#![warn(mismatched_lifetime_syntaxes)]
+#![deny(clippy::pedantic, unsafe_code)]
+#![allow(clippy::module_name_repetitions)]
+
+//! Benchmark scenarios for sps2
+//!
+//! Generates synthetic package fixtures (N packages with M files each) and
+//! times the hot paths of an install end to end: store ingestion, dependency
+//! resolution, staging into a new state and state verification. The
+//! criterion benchmarks in `benches/` run them; criterion's
+//! `--save-baseline` and `--baseline` catch performance regressions locally.
+//!
+//! There is no separate binary writing JSON reports any more: criterion
+//! already writes every result as JSON, to
+//! `target/criterion/install/<scenario>/new/estimates.json` with the
+//! samples in `sample.json` beside it, and compares baselines with proper
+//! statistics, which the hand-rolled report did not. Scripts read those
+//! files instead.
+
+pub mod scenarios;
+
+pub use scenarios::Scenario;
+pub use sps2_fixtures::{Fixture, FixturePackage, FixtureSpec, GraphShape};
+
+/// Fixture to benchmark, sized by `SPS2_BENCH_PACKAGES`, `SPS2_BENCH_FILES`,
+/// `SPS2_BENCH_FILE_SIZE`, `SPS2_BENCH_SHAPE` and `SPS2_BENCH_SEED`
+///
+/// Unset variables keep the [`FixtureSpec`] defaults.
+///
+/// # Errors
+///
+/// Returns the name and value of a variable that does not parse.
+pub fn fixture_spec_from_env() -> Result<FixtureSpec, String> {
+    fn var<T: std::str::FromStr>(name: &str, default: T) -> Result<T, String> {
+        match std::env::var(name) {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("invalid {name}: {value}")),
+            Err(_) => Ok(default),
+        }
+    }
+
+    let default = FixtureSpec::default();
+    Ok(FixtureSpec {
+        packages: var("SPS2_BENCH_PACKAGES", default.packages)?,
+        files_per_package: var("SPS2_BENCH_FILES", default.files_per_package)?,
+        file_size: var("SPS2_BENCH_FILE_SIZE", default.file_size)?,
+        shape: var("SPS2_BENCH_SHAPE", default.shape)?,
+        seed: var("SPS2_BENCH_SEED", default.seed)?,
+        ..default
+    })
+}
@@ -0,0 +1,201 @@
+//! Benchmarked hot paths
+//!
+//! Every scenario builds a fresh, isolated environment (state database, store
+//! and live directory in a temporary directory) per iteration and times only
+//! the operation under test.
+
+use sps2_errors::{Error, OpsError, StorageError};
+use sps2_fixtures::Fixture;
+use sps2_guard::{VerificationLevel, Verifier};
+use sps2_index::IndexManager;
+use sps2_install::{AtomicInstaller, InstallContext, PreparedPackage};
+use sps2_resolver::{PackageId, ResolutionContext, ResolvedNode, Resolver};
+use sps2_state::StateManager;
+use sps2_store::PackageStore;
+use sps2_types::PackageSpec;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// A benchmarked operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scenario {
+    /// Add every fixture package to an empty store
+    StoreIngestion,
+    /// Resolve the fixture's root package against its synthetic index
+    Resolution,
+    /// Stage and commit all fixture packages into a new state
+    Staging,
+    /// Fully verify the live state after installing the fixture
+    Verification,
+}
+
+impl Scenario {
+    /// All scenarios in pipeline order
+    pub const ALL: [Self; 4] = [
+        Self::StoreIngestion,
+        Self::Resolution,
+        Self::Staging,
+        Self::Verification,
+    ];
+
+    /// Stable benchmark id in criterion's `install` group, which also names
+    /// the directory its results are written to
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::StoreIngestion => "store-ingestion",
+            Self::Resolution => "resolution",
+            Self::Staging => "staging",
+            Self::Verification => "verification",
+        }
+    }
+
+    /// Run one iteration and return the duration of the measured section
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if environment setup or the operation itself fails.
+    pub async fn run(self, fixture: &Fixture) -> Result<Duration, Error> {
+        match self {
+            Self::StoreIngestion => store_ingestion(fixture).await,
+            Self::Resolution => resolution(fixture).await,
+            Self::Staging => staging(fixture).await,
+            Self::Verification => verification(fixture).await,
+        }
+    }
+}
+
+impl fmt::Display for Scenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Isolated state, store and live directory
+struct Environment {
+    state: StateManager,
+    store: PackageStore,
+    _dir: TempDir,
+}
+
+impl Environment {
+    async fn new() -> Result<Self, Error> {
+        let dir = TempDir::new().map_err(|e| StorageError::IoError {
+            message: format!("failed to create benchmark directory: {e}"),
+        })?;
+        let state = StateManager::new(dir.path()).await?;
+        let store_dir = dir.path().join("store");
+        tokio::fs::create_dir_all(&store_dir).await?;
+        let store = PackageStore::new(store_dir);
+        Ok(Self {
+            state,
+            store,
+            _dir: dir,
+        })
+    }
+
+    /// Add all fixture packages to the store and describe them for installation
+    async fn ingest(
+        &self,
+        fixture: &Fixture,
+    ) -> Result<
+        (
+            HashMap<PackageId, ResolvedNode>,
+            HashMap<PackageId, PreparedPackage>,
+        ),
+        Error,
+    > {
+        let mut resolved = HashMap::new();
+        let mut prepared = HashMap::new();
+
+        for pkg in fixture.latest_packages() {
+            let stored = self.store.add_package(&pkg.sp_path).await?;
+            let hash = stored.hash().ok_or_else(|| OpsError::OperationFailed {
+                message: format!("stored package {} has no hash", pkg.name),
+            })?;
+            let store_path = self.store.package_path(&hash);
+            let size = tokio::fs::metadata(&pkg.sp_path).await?.len();
+
+            let id = PackageId::new(pkg.name.clone(), pkg.version.clone());
+            resolved.insert(
+                id.clone(),
+                ResolvedNode::local(
+                    pkg.name.clone(),
+                    pkg.version.clone(),
+                    store_path.clone(),
+                    Vec::new(),
+                ),
+            );
+            prepared.insert(
+                id,
+                PreparedPackage {
+                    hash,
+                    size,
+                    store_path,
+                    is_local: true,
+                    package_hash: None,
+                    signing_key: None,
+                },
+            );
+        }
+
+        Ok((resolved, prepared))
+    }
+
+    async fn install(&self, fixture: &Fixture) -> Result<Duration, Error> {
+        let (resolved, prepared) = self.ingest(fixture).await?;
+        let mut installer = AtomicInstaller::new(self.state.clone(), self.store.clone());
+        let context = InstallContext::new();
+
+        let start = Instant::now();
+        installer
+            .install(&context, &resolved, Some(&prepared))
+            .await?;
+        Ok(start.elapsed())
+    }
+}
+
+async fn store_ingestion(fixture: &Fixture) -> Result<Duration, Error> {
+    let env = Environment::new().await?;
+
+    let start = Instant::now();
+    for pkg in fixture.packages() {
+        env.store.add_package(&pkg.sp_path).await?;
+    }
+    Ok(start.elapsed())
+}
+
+async fn resolution(fixture: &Fixture) -> Result<Duration, Error> {
+    let Some(root) = fixture.root_package() else {
+        return Ok(Duration::ZERO);
+    };
+
+    let dir = TempDir::new().map_err(|e| StorageError::IoError {
+        message: format!("failed to create index directory: {e}"),
+    })?;
+    let mut index = IndexManager::new(dir.path());
+    index.load(Some(&fixture.index().to_json()?)).await?;
+    let resolver = Resolver::new(index);
+    let context = ResolutionContext::new().add_runtime_dep(PackageSpec::parse(root)?);
+
+    let start = Instant::now();
+    resolver.resolve_with_sat(context).await?;
+    Ok(start.elapsed())
+}
+
+async fn staging(fixture: &Fixture) -> Result<Duration, Error> {
+    Environment::new().await?.install(fixture).await
+}
+
+async fn verification(fixture: &Fixture) -> Result<Duration, Error> {
+    let env = Environment::new().await?;
+    env.install(fixture).await?;
+    let (tx, _rx) = sps2_events::channel();
+    let verifier = Verifier::new(env.state.clone(), env.store.clone(), tx);
+
+    let start = Instant::now();
+    verifier.verify(VerificationLevel::Full).await?;
+    Ok(start.elapsed())
+}
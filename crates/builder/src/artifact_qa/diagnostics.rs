@@ -69,6 +69,43 @@ impl IssueType {
             Self::Custom { message } => message.clone(),
         }
     }
+
+    /// Rule id recipes use to suppress the issue
+    ///
+    /// Custom issues have no rule of their own and use the rule of the check
+    /// that reported them.
+    #[must_use]
+    pub fn rule(&self) -> Option<&'static str> {
+        match self {
+            Self::HardcodedBuildPath { .. } => Some("hardcoded-build-path"),
+            Self::HardcodedPlaceholder { .. } => Some("hardcoded-placeholder"),
+            Self::BadRPath { .. } => Some("bad-rpath"),
+            Self::BadInstallName { .. } => Some("bad-install-name"),
+            Self::SelfReferencingInstallName { .. } => Some("self-referencing-install-name"),
+            Self::BuildPathInArchive { .. } => Some("build-path-in-archive"),
+            Self::Custom { .. } => None,
+        }
+    }
+
+    /// Suggested fix for the issue, if there is a usual one
+    #[must_use]
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Self::HardcodedBuildPath { .. } | Self::BuildPathInArchive { .. } => Some(
+                "pass the install prefix instead of the build directory to configure, or remove the file in post",
+            ),
+            Self::HardcodedPlaceholder { .. } => {
+                Some("make sure the placeholder patcher runs for this file type")
+            }
+            Self::BadRPath { .. } => {
+                Some("set patch_rpaths: default in post, or link with -rpath @loader_path/../lib")
+            }
+            Self::BadInstallName { .. } | Self::SelfReferencingInstallName { .. } => {
+                Some("link with -install_name @rpath/<library>")
+            }
+            Self::Custom { .. } => None,
+        }
+    }
 }
 
 /// Collector for validation findings
@@ -3,22 +3,26 @@
 pub mod diagnostics;
 pub mod macho_utils;
 pub mod patchers;
+pub mod plugins;
 pub mod reports;
 pub mod router;
 pub mod scanners;
+pub mod suppression;
 pub mod traits;
 
 use crate::{utils::events::send_event, BuildContext, BuildEnvironment};
 use diagnostics::DiagnosticCollector;
 use reports::{MergedReport, Report};
+use sps2_config::builder::{QaFailOn, QaSettings};
 use sps2_errors::{BuildError, Error};
 use sps2_events::{
-    events::{QaCheckStatus, QaCheckSummary, QaFinding, QaLevel, QaSeverity, QaTarget},
+    events::{QaCheckStatus, QaCheckSummary, QaFinding, QaLevel, QaTarget},
     AppEvent, FailureContext, GeneralEvent, QaEvent,
 };
 use sps2_types::BuildSystemProfile;
 use std::convert::TryFrom;
 use std::time::{Duration, Instant};
+use suppression::Suppressions;
 use traits::Action;
 
 /// Enum for all validators
@@ -27,6 +31,8 @@ pub enum ValidatorAction {
     MachOScanner(scanners::macho::MachOScanner),
     ArchiveScanner(scanners::archive::ArchiveScanner),
     StagingScanner(scanners::staging::StagingScanner),
+    ReproducibilityScanner(scanners::reproducibility::ReproducibilityScanner),
+    Plugin(plugins::PluginAction),
 }
 
 /// Enum for all patchers
@@ -42,15 +48,32 @@ pub enum PatcherAction {
     PythonBytecodeCleanupPatcher(patchers::python_bytecode_cleanup::PythonBytecodeCleanupPatcher),
     PythonIsolationPatcher(patchers::python_isolation::PythonIsolationPatcher),
     CodeSigner(patchers::codesigner::CodeSigner),
+    Plugin(plugins::PluginAction),
 }
 
 impl ValidatorAction {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         match self {
             Self::HardcodedScanner(_) => scanners::hardcoded::HardcodedScanner::NAME,
             Self::MachOScanner(_) => scanners::macho::MachOScanner::NAME,
             Self::ArchiveScanner(_) => scanners::archive::ArchiveScanner::NAME,
             Self::StagingScanner(_) => scanners::staging::StagingScanner::NAME,
+            Self::ReproducibilityScanner(_) => {
+                scanners::reproducibility::ReproducibilityScanner::NAME
+            }
+            Self::Plugin(plugin) => plugin.name(),
+        }
+    }
+
+    /// Rule id recipes use to suppress the check's own findings
+    fn rule(&self) -> &str {
+        match self {
+            Self::HardcodedScanner(_) => "hardcoded-paths",
+            Self::MachOScanner(_) => "macho",
+            Self::ArchiveScanner(_) => "archive",
+            Self::StagingScanner(_) => "staging",
+            Self::ReproducibilityScanner(_) => "reproducibility",
+            Self::Plugin(plugin) => plugin.name(),
         }
     }
 
@@ -71,12 +94,16 @@ impl ValidatorAction {
             Self::StagingScanner(_) => {
                 scanners::staging::StagingScanner::run(ctx, env, findings).await
             }
+            Self::ReproducibilityScanner(_) => {
+                scanners::reproducibility::ReproducibilityScanner::run(ctx, env, findings).await
+            }
+            Self::Plugin(plugin) => plugin.run(ctx, env).await,
         }
     }
 }
 
 impl PatcherAction {
-    fn name(&self) -> &'static str {
+    fn name(&self) -> &str {
         match self {
             Self::PermissionsFixer(_) => patchers::permissions::PermissionsFixer::NAME,
             Self::PlaceholderPatcher(_) => patchers::placeholder::PlaceholderPatcher::NAME,
@@ -93,6 +120,25 @@ impl PatcherAction {
                 patchers::python_isolation::PythonIsolationPatcher::NAME
             }
             Self::CodeSigner(_) => patchers::codesigner::CodeSigner::NAME,
+            Self::Plugin(plugin) => plugin.name(),
+        }
+    }
+
+    /// Rule id recipes use to suppress the patcher's findings
+    fn rule(&self) -> &str {
+        match self {
+            Self::PermissionsFixer(_) => "permissions",
+            Self::PlaceholderPatcher(_) => "placeholders",
+            Self::RPathPatcher(_) => "rpath",
+            Self::HeaderPatcher(_) => "headers",
+            Self::PkgConfigPatcher(_) => "pkgconfig",
+            Self::BinaryStringPatcher(_) => "binary-strings",
+            Self::LaFileCleaner(_) => "la-files",
+            Self::ObjectFileCleaner(_) => "object-files",
+            Self::PythonBytecodeCleanupPatcher(_) => "python-bytecode",
+            Self::PythonIsolationPatcher(_) => "python-isolation",
+            Self::CodeSigner(_) => "codesign",
+            Self::Plugin(plugin) => plugin.name(),
         }
     }
 
@@ -135,6 +181,7 @@ impl PatcherAction {
                 patchers::python_isolation::PythonIsolationPatcher::run(ctx, env, findings).await
             }
             Self::CodeSigner(_) => patchers::codesigner::CodeSigner::run(ctx, env, findings).await,
+            Self::Plugin(plugin) => plugin.run(ctx, env).await,
         }
     }
 }
@@ -145,6 +192,10 @@ impl PatcherAction {
 /// * P – patch tree in‑place
 /// * V2 – must be clean, else the build fails
 ///
+/// Site plugins from `settings` run with the built-in validators and
+/// patchers. Findings the recipe suppresses are reported but do not fail the
+/// build; with `fail_on = "warning"` unsuppressed warnings fail it too.
+///
 /// # Errors
 ///
 /// Returns an error if:
@@ -152,16 +203,19 @@ impl PatcherAction {
 /// - Failed to apply patches during the patching phase
 /// - I/O errors occur during file analysis
 /// - The final validation phase fails (V2 phase)
+/// - A recipe suppression has an invalid file glob
 ///
 /// # Panics
 ///
 /// This function will panic if `qa_override` results in a profile selection that returns `None`
 /// from `determine_profile_with_override` but is not the `Skip` variant (this should not happen
 /// in normal operation).
+#[allow(clippy::too_many_lines)]
 pub async fn run_quality_pipeline(
     ctx: &BuildContext,
     env: &BuildEnvironment,
     qa_override: Option<sps2_types::QaPipelineOverride>,
+    settings: &QaSettings,
 ) -> Result<(), Error> {
     let pipeline_start = Instant::now();
     let mut stats = QaStats::default();
@@ -181,6 +235,18 @@ pub async fn run_quality_pipeline(
     }
     let profile = profile_opt.unwrap();
     let qa_level = qa_level_for_profile(profile);
+    let plugins = &settings.plugins;
+    let suppressions = match Suppressions::new(
+        env.staging_dir(),
+        env.get_live_prefix(),
+        env.qa_suppressions(),
+    ) {
+        Ok(suppressions) => suppressions,
+        Err(err) => {
+            emit_pipeline_failed(ctx, &target, &err);
+            return Err(err);
+        }
+    };
 
     send_event(
         ctx,
@@ -191,11 +257,17 @@ pub async fn run_quality_pipeline(
     );
 
     // ----------------    PHASE 1  -----------------
+    let validators = || {
+        let mut validators = router::get_validators_for_profile(profile);
+        validators.extend(plugins::validators(plugins));
+        validators
+    };
     let mut pre = match run_validators(
         ctx,
         env,
-        router::get_validators_for_profile(profile),
-        false, // Don't allow early break - run all validators
+        validators(),
+        ValidationPass::Pre,
+        &Suppressions::default(),
         &target,
         &mut stats,
     )
@@ -212,11 +284,14 @@ pub async fn run_quality_pipeline(
     let validator_findings = pre.take_findings();
 
     // ----------------    PHASE 2  -----------------
+    let mut patchers = router::get_patchers_for_profile(profile);
+    plugins::add_patchers(&mut patchers, plugins);
     if let Err(err) = run_patchers(
         ctx,
         env,
         validator_findings,
-        router::get_patchers_for_profile(profile),
+        patchers,
+        &suppressions,
         &target,
         &mut stats,
     )
@@ -230,8 +305,9 @@ pub async fn run_quality_pipeline(
     let post = match run_validators(
         ctx,
         env,
-        router::get_validators_for_profile(profile),
-        true, // Allow early break in final validation
+        validators(),
+        ValidationPass::Final,
+        &suppressions,
         &target,
         &mut stats,
     )
@@ -244,6 +320,8 @@ pub async fn run_quality_pipeline(
         }
     };
 
+    let advisory = run_advisory_validators(ctx, env, &suppressions, &target, &mut stats).await?;
+
     if post.is_fatal() {
         let failure_error: Error = BuildError::Failed {
             message: post.render("Relocatability check failed"),
@@ -251,6 +329,17 @@ pub async fn run_quality_pipeline(
         .into();
         emit_pipeline_failed(ctx, &target, &failure_error);
         return Err(failure_error);
+    } else if settings.fail_on == QaFailOn::Warning
+        && (post.has_warnings() || advisory.has_warnings())
+    {
+        let mut warnings = post;
+        warnings.absorb_merged(advisory);
+        let failure_error: Error = BuildError::Failed {
+            message: warnings.render("QA warnings fail the build (fail_on = \"warning\")"),
+        }
+        .into();
+        emit_pipeline_failed(ctx, &target, &failure_error);
+        return Err(failure_error);
     } else if !pre.is_fatal() && !post.is_fatal() {
         send_event(
             ctx,
@@ -280,12 +369,27 @@ pub async fn run_quality_pipeline(
     Ok(())
 }
 
+/// Validation pass a validator runs in
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ValidationPass {
+    /// Before patching; findings are handed to the patchers
+    Pre,
+    /// After patching; stops at the first failing validator
+    Final,
+    /// Warnings only, after the final pass
+    Advisory,
+}
+
 /// Utility that runs validators and merges their reports.
+///
+/// Findings of the final and advisory passes are reported one by one, after
+/// the recipe's suppressions are applied.
 async fn run_validators(
     ctx: &BuildContext,
     env: &BuildEnvironment,
     actions: Vec<ValidatorAction>,
-    allow_early_break: bool,
+    pass: ValidationPass,
+    suppressions: &Suppressions<'_>,
     target: &QaTarget,
     stats: &mut QaStats,
 ) -> Result<MergedReport, Error> {
@@ -294,30 +398,60 @@ async fn run_validators(
     for action in &actions {
         let action_name = action.name();
         let check_start = Instant::now();
-        let rep = action.run(ctx, env, None).await?;
-        emit_qa_check(
-            ctx,
-            target,
+        let mut rep = action.run(ctx, env, None).await?;
+        let findings = suppressions.apply(action.rule(), &mut rep);
+        if pass != ValidationPass::Pre {
+            emit_findings(ctx, target, action_name, &findings);
+        }
+        let summary = build_check_summary(
             "validator",
             action_name,
             &rep,
+            findings,
             check_start.elapsed(),
-            stats,
         );
+        emit_qa_check(ctx, target, summary, stats);
         merged.absorb(rep);
-        if allow_early_break && merged.is_fatal() {
+        if pass == ValidationPass::Final && merged.is_fatal() {
             break; // short‑circuit early (saves time)
         }
     }
     Ok(merged)
 }
 
+/// Run the validators whose warnings only fail the build under `fail_on = "warning"`
+///
+/// An error running a validator is returned after the pipeline failure has
+/// been reported.
+async fn run_advisory_validators(
+    ctx: &BuildContext,
+    env: &BuildEnvironment,
+    suppressions: &Suppressions<'_>,
+    target: &QaTarget,
+    stats: &mut QaStats,
+) -> Result<MergedReport, Error> {
+    run_validators(
+        ctx,
+        env,
+        vec![ValidatorAction::ReproducibilityScanner(
+            scanners::ReproducibilityScanner,
+        )],
+        ValidationPass::Advisory,
+        suppressions,
+        target,
+        stats,
+    )
+    .await
+    .inspect_err(|err| emit_pipeline_failed(ctx, target, err))
+}
+
 /// Utility that runs patchers and merges their reports.
 async fn run_patchers(
     ctx: &BuildContext,
     env: &BuildEnvironment,
     validator_findings: Option<DiagnosticCollector>,
     actions: Vec<PatcherAction>,
+    suppressions: &Suppressions<'_>,
     target: &QaTarget,
     stats: &mut QaStats,
 ) -> Result<MergedReport, Error> {
@@ -326,16 +460,17 @@ async fn run_patchers(
     for action in &actions {
         let action_name = action.name();
         let check_start = Instant::now();
-        let rep = action.run(ctx, env, validator_findings.as_ref()).await?;
-        emit_qa_check(
-            ctx,
-            target,
+        let mut rep = action.run(ctx, env, validator_findings.as_ref()).await?;
+        let findings = suppressions.apply(action.rule(), &mut rep);
+        emit_findings(ctx, target, action_name, &findings);
+        let summary = build_check_summary(
             "patcher",
             action_name,
             &rep,
+            findings,
             check_start.elapsed(),
-            stats,
         );
+        emit_qa_check(ctx, target, summary, stats);
         merged.absorb(rep);
         if merged.is_fatal() {
             break; // short‑circuit early (saves time)
@@ -349,7 +484,7 @@ struct QaStats {
     failed: usize,
 }
 
-fn qa_target(ctx: &BuildContext) -> QaTarget {
+pub(crate) fn qa_target(ctx: &BuildContext) -> QaTarget {
     QaTarget {
         package: ctx.name.clone(),
         version: ctx.version.clone(),
@@ -364,45 +499,11 @@ fn qa_level_for_profile(profile: BuildSystemProfile) -> QaLevel {
     }
 }
 
-fn qa_findings_from_report(report: &Report) -> Vec<QaFinding> {
-    let mut findings = Vec::new();
-
-    for message in &report.errors {
-        findings.push(QaFinding {
-            severity: QaSeverity::Error,
-            message: message.clone(),
-            file: None,
-            line: None,
-        });
-    }
-
-    for message in &report.warnings {
-        findings.push(QaFinding {
-            severity: QaSeverity::Warning,
-            message: message.clone(),
-            file: None,
-            line: None,
-        });
-    }
-
-    if let Some(diags) = &report.findings {
-        for finding in diags.findings() {
-            findings.push(QaFinding {
-                severity: QaSeverity::Warning,
-                message: finding.issue_type.description(),
-                file: Some(finding.file_path.clone()),
-                line: None,
-            });
-        }
-    }
-
-    findings
-}
-
 fn build_check_summary(
     category: &str,
     name: &str,
     report: &Report,
+    findings: Vec<QaFinding>,
     duration: Duration,
 ) -> QaCheckSummary {
     QaCheckSummary {
@@ -414,20 +515,16 @@ fn build_check_summary(
             QaCheckStatus::Passed
         },
         duration_ms: Some(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)),
-        findings: qa_findings_from_report(report),
+        findings,
     }
 }
 
 fn emit_qa_check(
     ctx: &BuildContext,
     target: &QaTarget,
-    category: &str,
-    action_name: &str,
-    report: &Report,
-    duration: Duration,
+    summary: QaCheckSummary,
     stats: &mut QaStats,
 ) {
-    let summary = build_check_summary(category, action_name, report, duration);
     stats.total += 1;
     if matches!(summary.status, QaCheckStatus::Failed) {
         stats.failed += 1;
@@ -442,6 +539,19 @@ fn emit_qa_check(
     );
 }
 
+fn emit_findings(ctx: &BuildContext, target: &QaTarget, check: &str, findings: &[QaFinding]) {
+    for finding in findings {
+        send_event(
+            ctx,
+            AppEvent::Qa(QaEvent::FindingReported {
+                target: target.clone(),
+                check: check.to_string(),
+                finding: finding.clone(),
+            }),
+        );
+    }
+}
+
 fn emit_pipeline_failed(ctx: &BuildContext, target: &QaTarget, error: &Error) {
     send_event(
         ctx,
@@ -1,9 +1,19 @@
 //! Re-signs binaries after patching to fix code signature issues on macOS
-
-use crate::artifact_qa::{macho_utils, reports::Report, traits::Patcher};
+//!
+//! Each re-signed binary is reported as a QA finding of its own. A binary
+//! whose new signature does not verify, or lost the entitlements or hardened
+//! runtime flag of the old one, fails the check: notarized binaries rely on
+//! both.
+
+use crate::artifact_qa::diagnostics::{DiagnosticCollector, IssueType, ValidationFinding};
+use crate::artifact_qa::{macho_utils, qa_target, reports::Report, traits::Patcher};
+use crate::utils::events::send_event;
 use crate::{BuildContext, BuildEnvironment};
 use sps2_errors::Error;
-use sps2_platform::{PlatformContext, PlatformManager};
+use sps2_events::events::{QaFinding, QaSeverity};
+use sps2_events::{AppEvent, QaEvent};
+use sps2_platform::{PlatformContext, PlatformManager, SignatureInfo};
+use std::collections::HashMap;
 use std::path::Path;
 
 pub struct CodeSigner {
@@ -32,26 +42,62 @@ impl CodeSigner {
         macho_utils::is_macho_file(path)
     }
 
-    /// Re-sign a binary with ad-hoc signature
+    /// Re-sign a binary ad hoc if its signature no longer verifies
+    ///
+    /// Returns the new signature, or `None` if the old one was still valid.
+    /// Entitlements and the hardened runtime flag of the old signature are
+    /// carried over; the new signature must verify and keep both.
     async fn resign_binary(
         &self,
         ctx: &PlatformContext,
         path: &Path,
-    ) -> Result<bool, sps2_errors::Error> {
-        // First check if the signature is valid
-        let is_valid =
-            (self.platform.binary().verify_signature(ctx, path).await).unwrap_or_default();
-
-        // If signature is invalid or modified, re-sign it
-        if is_valid {
-            Ok(false) // No re-signing needed
-        } else {
-            // Re-sign with ad-hoc signature (identity = None)
-            match self.platform.binary().sign_binary(ctx, path, None).await {
-                Ok(()) => Ok(true),
-                Err(e) => Err(e.into()),
-            }
+    ) -> Result<Option<SignatureInfo>, String> {
+        let binary = self.platform.binary();
+        if binary.verify_signature(ctx, path).await.unwrap_or_default() {
+            return Ok(None);
+        }
+
+        let before = binary.signature_info(ctx, path).await.unwrap_or_default();
+        // Re-sign with ad-hoc signature (identity = None)
+        binary
+            .sign_binary(ctx, path, None)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if !binary
+            .verify_signature(ctx, path)
+            .await
+            .map_err(|e| e.to_string())?
+        {
+            return Err("signature does not verify after re-signing".to_string());
+        }
+        let after = binary
+            .signature_info(ctx, path)
+            .await
+            .map_err(|e| e.to_string())?;
+        if before.entitlements.is_some() && after.entitlements != before.entitlements {
+            return Err("re-signing dropped the entitlements".to_string());
+        }
+        if before.hardened_runtime && !after.hardened_runtime {
+            return Err("re-signing dropped the hardened runtime flag".to_string());
         }
+        Ok(Some(after))
+    }
+}
+
+/// Describe a fresh signature for the per-binary QA event
+fn resigned_message(info: &SignatureInfo) -> String {
+    let mut kept = Vec::new();
+    if info.entitlements.is_some() {
+        kept.push("entitlements");
+    }
+    if info.hardened_runtime {
+        kept.push("hardened runtime");
+    }
+    if kept.is_empty() {
+        "Re-signed ad hoc".to_string()
+    } else {
+        format!("Re-signed ad hoc, keeping {}", kept.join(" and "))
     }
 }
 
@@ -61,7 +107,7 @@ impl crate::artifact_qa::traits::Action for CodeSigner {
     async fn run(
         ctx: &BuildContext,
         env: &BuildEnvironment,
-        _findings: Option<&crate::artifact_qa::diagnostics::DiagnosticCollector>,
+        _findings: Option<&DiagnosticCollector>,
     ) -> Result<Report, Error> {
         // Only run on macOS
         if !cfg!(target_os = "macos") {
@@ -73,8 +119,10 @@ impl crate::artifact_qa::traits::Action for CodeSigner {
         // Create platform context from build context
         let platform_ctx = signer.platform.create_context(ctx.event_sender.clone());
 
+        let target = qa_target(ctx);
         let mut resigned_count = 0;
         let mut errors = Vec::new();
+        let mut failures = DiagnosticCollector::new();
 
         // Walk through all files in staging directory
         for entry in ignore::WalkBuilder::new(env.staging_dir())
@@ -89,10 +137,35 @@ impl crate::artifact_qa::traits::Action for CodeSigner {
             }
 
             match signer.resign_binary(&platform_ctx, &path).await {
-                Ok(true) => resigned_count += 1,
-                Ok(false) => {} // No re-signing needed
+                Ok(Some(info)) => {
+                    resigned_count += 1;
+                    send_event(
+                        ctx,
+                        AppEvent::Qa(QaEvent::FindingReported {
+                            target: target.clone(),
+                            check: Self::NAME.to_string(),
+                            finding: QaFinding {
+                                message: resigned_message(&info),
+                                severity: QaSeverity::Info,
+                                file: Some(path.clone()),
+                                line: None,
+                                rule: Some("codesign".to_string()),
+                                suggestion: None,
+                                suppressed: None,
+                            },
+                        }),
+                    );
+                }
+                Ok(None) => {} // No re-signing needed
                 Err(e) => {
                     errors.push(format!("Failed to re-sign {}: {}", path.display(), e));
+                    failures.add_finding(ValidationFinding {
+                        file_path: path,
+                        issue_type: IssueType::Custom {
+                            message: format!("Failed to re-sign: {e}"),
+                        },
+                        context: HashMap::new(),
+                    });
                 }
             }
         }
@@ -106,7 +179,7 @@ impl crate::artifact_qa::traits::Action for CodeSigner {
             changed_files: Vec::new(),
             warnings,
             errors,
-            ..Default::default()
+            findings: failures.has_findings().then_some(failures),
         })
     }
 }
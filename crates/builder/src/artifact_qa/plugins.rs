@@ -0,0 +1,154 @@
+//! Site-specific QA plugins
+//!
+//! External commands configured under `[[qa.plugins]]` in the builder config
+//! run next to the built-in validators and patchers, so a site can enforce
+//! its own policies without changing the builder. Each command runs in the
+//! staging directory with `SPS2_STAGING_DIR`, `SPS2_PACKAGE_NAME` and
+//! `SPS2_PACKAGE_VERSION` set, and reports one finding per stdout line.
+
+use super::{reports::Report, PatcherAction, ValidatorAction};
+use crate::{BuildContext, BuildEnvironment};
+use sps2_config::builder::{QaPlugin, QaPluginKind};
+use sps2_errors::{BuildError, Error};
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// An external command taking part in the QA pipeline
+pub struct PluginAction {
+    plugin: QaPlugin,
+}
+
+impl PluginAction {
+    #[must_use]
+    pub fn new(plugin: QaPlugin) -> Self {
+        Self { plugin }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.plugin.name
+    }
+
+    /// Run the plugin against the staged files
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the command cannot be started.
+    pub async fn run(&self, ctx: &BuildContext, env: &BuildEnvironment) -> Result<Report, Error> {
+        let output = Command::new(&self.plugin.command)
+            .args(&self.plugin.args)
+            .current_dir(env.staging_dir())
+            .env("SPS2_STAGING_DIR", env.staging_dir())
+            .env("SPS2_PACKAGE_NAME", &ctx.name)
+            .env("SPS2_PACKAGE_VERSION", ctx.version.to_string())
+            .output()
+            .await
+            .map_err(|e| BuildError::ValidationFailed {
+                message: format!(
+                    "failed to run QA plugin {} ({}): {e}",
+                    self.plugin.name,
+                    self.plugin.command.display()
+                ),
+            })?;
+
+        let mut report = parse_report(&String::from_utf8_lossy(&output.stdout));
+        if !output.status.success() && report.errors.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            report.errors.push(format!(
+                "QA plugin {} failed with {}: {}",
+                self.plugin.name,
+                output.status,
+                stderr.trim()
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// Validator actions for the configured validator plugins
+pub(crate) fn validators(plugins: &[QaPlugin]) -> impl Iterator<Item = ValidatorAction> + '_ {
+    plugins
+        .iter()
+        .filter(|plugin| plugin.kind == QaPluginKind::Validator)
+        .map(|plugin| ValidatorAction::Plugin(PluginAction::new(plugin.clone())))
+}
+
+/// Add the configured patcher plugins to `patchers`
+///
+/// They run after the built-in patchers but before code signing, so that
+/// binaries they change are signed again.
+pub(crate) fn add_patchers(patchers: &mut Vec<PatcherAction>, plugins: &[QaPlugin]) {
+    let at = patchers
+        .iter()
+        .position(|patcher| matches!(patcher, PatcherAction::CodeSigner(_)))
+        .unwrap_or(patchers.len());
+    let plugins = plugins
+        .iter()
+        .filter(|plugin| plugin.kind == QaPluginKind::Patcher)
+        .map(|plugin| PatcherAction::Plugin(PluginAction::new(plugin.clone())));
+    patchers.splice(at..at, plugins);
+}
+
+/// Parse `error:`, `warning:` and `changed:` lines, ignoring anything else
+fn parse_report(stdout: &str) -> Report {
+    let mut report = Report::default();
+    for line in stdout.lines() {
+        let Some((kind, message)) = line.split_once(':') else {
+            continue;
+        };
+        let message = message.trim().to_string();
+        match kind.trim() {
+            "error" => report.errors.push(message),
+            "warning" => report.warnings.push(message),
+            "changed" => report.changed_files.push(PathBuf::from(message)),
+            _ => {}
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact_qa::patchers::{codesigner::CodeSigner, headers::HeaderPatcher};
+    use crate::artifact_qa::traits::Action;
+
+    fn plugin(name: &str, kind: QaPluginKind) -> QaPlugin {
+        QaPlugin {
+            name: name.to_string(),
+            kind,
+            command: PathBuf::from("/bin/true"),
+            args: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn findings_are_parsed_from_stdout() {
+        let report = parse_report(
+            "checking 3 files\nerror: bin/foo links libtelemetry\nwarning: no license file\nchanged: lib/foo.pc\n",
+        );
+        assert_eq!(report.errors, ["bin/foo links libtelemetry"]);
+        assert_eq!(report.warnings, ["no license file"]);
+        assert_eq!(report.changed_files, [PathBuf::from("lib/foo.pc")]);
+    }
+
+    #[test]
+    fn patcher_plugins_run_before_code_signing() {
+        let mut patchers = vec![
+            PatcherAction::HeaderPatcher(HeaderPatcher),
+            PatcherAction::CodeSigner(CodeSigner::new()),
+        ];
+        let plugins = [
+            plugin("strip-telemetry", QaPluginKind::Patcher),
+            plugin("naming-policy", QaPluginKind::Validator),
+        ];
+        add_patchers(&mut patchers, &plugins);
+
+        let names: Vec<&str> = patchers.iter().map(PatcherAction::name).collect();
+        assert_eq!(
+            names,
+            [HeaderPatcher::NAME, "strip-telemetry", CodeSigner::NAME]
+        );
+        assert_eq!(validators(&plugins).count(), 1);
+    }
+}
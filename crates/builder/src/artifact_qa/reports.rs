@@ -72,6 +72,10 @@ impl MergedReport {
     pub fn absorb(&mut self, r: Report) {
         self.0.absorb(r);
     }
+    /// Add another merged report's data into `self`.
+    pub fn absorb_merged(&mut self, other: MergedReport) {
+        self.0.absorb(other.0);
+    }
     /// Check if the merged report contains fatal errors
     ///
     /// Returns true if any absorbed report contained errors.
@@ -79,6 +83,13 @@ impl MergedReport {
     pub fn is_fatal(&self) -> bool {
         self.0.is_fatal()
     }
+    /// Check if the merged report contains warnings
+    ///
+    /// Returns true if any absorbed report contained warnings.
+    #[must_use]
+    pub fn has_warnings(&self) -> bool {
+        !self.0.warnings.is_empty()
+    }
     /// Render the merged report as a formatted string
     ///
     /// Returns a human-readable summary of all absorbed reports.
@@ -3,10 +3,12 @@
 pub mod archive;
 pub mod hardcoded;
 pub mod macho;
+pub mod reproducibility;
 pub mod staging;
 
 // Re-export the concrete types for convenient access elsewhere.
 pub use archive::ArchiveScanner;
 pub use hardcoded::HardcodedScanner;
 pub use macho::MachOScanner;
+pub use reproducibility::ReproducibilityScanner;
 pub use staging::StagingScanner;
@@ -0,0 +1,231 @@
+//! Advisory validator that looks for build dates and host paths embedded in
+//! the staged files. Such files differ from one build to the next, so each
+//! finding is reported as a warning together with a fix for the recipe.
+
+use crate::artifact_qa::{diagnostics::DiagnosticCollector, reports::Report, traits::Validator};
+use crate::{BuildContext, BuildEnvironment};
+use bstr::ByteSlice;
+use chrono::{DateTime, Datelike, Utc};
+use ignore::WalkBuilder;
+use sps2_errors::Error;
+use std::path::Path;
+
+pub struct ReproducibilityScanner;
+impl crate::artifact_qa::traits::Action for ReproducibilityScanner {
+    const NAME: &'static str = "Reproducibility scanner";
+
+    async fn run(
+        _ctx: &BuildContext,
+        env: &BuildEnvironment,
+        _findings: Option<&DiagnosticCollector>,
+    ) -> Result<Report, Error> {
+        let needles = Needles::for_build(
+            Utc::now(),
+            env.env_vars().get("SOURCE_DATE_EPOCH").map(String::as_str),
+        );
+        let mut report = Report::default();
+
+        for entry in WalkBuilder::new(env.staging_dir())
+            .hidden(false)
+            .parents(false)
+            .build()
+            .filter_map(Result::ok)
+        {
+            let path = entry.into_path();
+            if !path.is_file() {
+                continue;
+            }
+            // Python bytecode is regenerated at install time
+            if let Some(ext) = path.extension() {
+                if ext == "pyc" || ext == "pyo" {
+                    continue;
+                }
+            }
+            let Ok(data) = std::fs::read(&path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(env.staging_dir()).unwrap_or(&path);
+            for issue in needles.inspect(&data) {
+                report.warnings.push(issue.describe(relative));
+            }
+        }
+
+        Ok(report)
+    }
+}
+impl Validator for ReproducibilityScanner {}
+
+/// Something in a file that changes from build to build
+#[derive(Debug, PartialEq, Eq)]
+enum Issue {
+    BuildDate(String),
+    GzipTimestamp,
+    ArchiveTimestamp,
+    HostPath(String),
+}
+
+impl Issue {
+    fn describe(&self, file: &Path) -> String {
+        let file = file.display();
+        match self {
+            Self::BuildDate(date) => format!(
+                "{file} embeds the build date ({date}); make the build honour \
+                 SOURCE_DATE_EPOCH and avoid __DATE__/__TIME__"
+            ),
+            Self::GzipTimestamp => {
+                format!("{file} is gzip-compressed with a timestamp; compress it with `gzip -n`")
+            }
+            Self::ArchiveTimestamp => format!(
+                "{file} records member timestamps; create it with `ar D` or keep \
+                 ZERO_AR_DATE=1 set"
+            ),
+            Self::HostPath(path) => format!(
+                "{file} embeds the host path {path}; add -ffile-prefix-map={path}=. \
+                 to CFLAGS or keep the path out of the build"
+            ),
+        }
+    }
+}
+
+/// What the scanner searches for in each file
+struct Needles {
+    dates: Vec<String>,
+    host_paths: Vec<String>,
+}
+
+impl Needles {
+    /// Needles for a build run at `now` with the given `SOURCE_DATE_EPOCH`
+    ///
+    /// Today's date is only suspicious if it is not the stamped date.
+    fn for_build(now: DateTime<Utc>, source_date_epoch: Option<&str>) -> Self {
+        let stamped = source_date_epoch
+            .and_then(|epoch| epoch.parse::<i64>().ok())
+            .and_then(|epoch| DateTime::<Utc>::from_timestamp(epoch, 0))
+            .map(|stamped| stamped.date_naive());
+        let dates = if stamped == Some(now.date_naive()) {
+            Vec::new()
+        } else {
+            // `__DATE__` pads single-digit days with a space
+            vec![
+                format!("{} {:>2} {}", now.format("%b"), now.day(), now.year()),
+                now.format("%Y-%m-%d").to_string(),
+            ]
+        };
+
+        let temp_dir = std::env::temp_dir();
+        let host_paths = [
+            std::env::var("HOME").ok(),
+            temp_dir.to_str().map(str::to_string),
+        ]
+        .into_iter()
+        .flatten()
+        .map(|path| path.trim_end_matches('/').to_string())
+        // A bare `/` or `/tmp` would match far too much
+        .filter(|path| path.len() > "/tmp".len())
+        .collect();
+
+        Self { dates, host_paths }
+    }
+
+    fn inspect(&self, data: &[u8]) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        if let Some(date) = self
+            .dates
+            .iter()
+            .find(|date| data.find(date.as_bytes()).is_some())
+        {
+            issues.push(Issue::BuildDate(date.clone()));
+        }
+        if data.starts_with(&[0x1f, 0x8b]) && data.get(4..8).is_some_and(|mtime| mtime != [0; 4]) {
+            issues.push(Issue::GzipTimestamp);
+        }
+        if data.starts_with(b"!<arch>\n") && archive_member_mtime(data).is_some_and(|t| t != 0) {
+            issues.push(Issue::ArchiveTimestamp);
+        }
+        if let Some(path) = self
+            .host_paths
+            .iter()
+            .find(|path| data.find(path.as_bytes()).is_some())
+        {
+            issues.push(Issue::HostPath(path.clone()));
+        }
+
+        issues
+    }
+}
+
+/// Modification time of the first member of an `ar` archive
+fn archive_member_mtime(data: &[u8]) -> Option<u64> {
+    // The member header follows the 8-byte magic; its mtime field is the
+    // 12 bytes after the 16-byte name
+    let field = data.get(8 + 16..8 + 28)?;
+    std::str::from_utf8(field).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn needles() -> Needles {
+        Needles {
+            dates: Needles::for_build(Utc.with_ymd_and_hms(2026, 3, 7, 12, 0, 0).unwrap(), None)
+                .dates,
+            host_paths: vec!["/Users/alice".to_string()],
+        }
+    }
+
+    fn ar_archive(mtime: &str) -> Vec<u8> {
+        let mut data = b"!<arch>\n".to_vec();
+        data.extend_from_slice(format!("{:<16}{mtime:<12}", "foo.o/").as_bytes());
+        data.extend_from_slice(b"0     0     100644  0         `\n");
+        data
+    }
+
+    #[test]
+    fn build_dates_are_found_in_both_formats() {
+        let needles = needles();
+        assert_eq!(
+            needles.inspect(b"built on Mar  7 2026 at 12:00:00"),
+            vec![Issue::BuildDate("Mar  7 2026".to_string())]
+        );
+        assert_eq!(
+            needles.inspect(b"version 1.0 (2026-03-07)"),
+            vec![Issue::BuildDate("2026-03-07".to_string())]
+        );
+        assert!(needles.inspect(b"version 1.0 (2025-03-07)").is_empty());
+    }
+
+    #[test]
+    fn the_stamped_date_is_not_reported() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 7, 12, 0, 0).unwrap();
+        let epoch = now.timestamp().to_string();
+        assert!(Needles::for_build(now, Some(&epoch)).dates.is_empty());
+        assert!(!Needles::for_build(now, Some("0")).dates.is_empty());
+    }
+
+    #[test]
+    fn timestamped_gzip_and_ar_files_are_reported() {
+        let needles = needles();
+        assert_eq!(
+            needles.inspect(&[0x1f, 0x8b, 8, 0, 0x10, 0x20, 0x30, 0x40]),
+            vec![Issue::GzipTimestamp]
+        );
+        assert!(needles.inspect(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0]).is_empty());
+
+        assert_eq!(
+            needles.inspect(&ar_archive("1700000000")),
+            vec![Issue::ArchiveTimestamp]
+        );
+        assert!(needles.inspect(&ar_archive("0")).is_empty());
+    }
+
+    #[test]
+    fn host_paths_are_reported() {
+        assert_eq!(
+            needles().inspect(b"/Users/alice/src/foo/main.c"),
+            vec![Issue::HostPath("/Users/alice".to_string())]
+        );
+    }
+}
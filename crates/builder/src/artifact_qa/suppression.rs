@@ -0,0 +1,249 @@
+//! Recipe suppressions for QA findings
+//!
+//! A recipe accepts findings under `post.qa_suppress`, by rule id and
+//! optionally only for some files. Suppressed findings no longer fail the
+//! build, but are still reported together with the recipe's justification.
+
+use super::reports::Report;
+use crate::packaging::filters::{glob, matches_path};
+use crate::recipe::model::QaSuppression;
+use globset::GlobMatcher;
+use sps2_errors::Error;
+use sps2_events::events::{QaFinding, QaSeverity};
+use std::path::{Path, PathBuf};
+
+/// Compiled `post.qa_suppress` entries of a recipe
+#[derive(Default)]
+pub(crate) struct Suppressions<'a> {
+    /// Finding paths are made relative to this directory before matching
+    root: PathBuf,
+    entries: Vec<(&'a QaSuppression, Vec<GlobMatcher>)>,
+}
+
+impl<'a> Suppressions<'a> {
+    /// Compile `suppressions` for files staged in `staging_dir` under the
+    /// `live_prefix` the build installs to
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file glob is invalid.
+    pub(crate) fn new(
+        staging_dir: &Path,
+        live_prefix: &str,
+        suppressions: &'a [QaSuppression],
+    ) -> Result<Self, Error> {
+        let entries = suppressions
+            .iter()
+            .map(|suppression| {
+                let files = suppression
+                    .files
+                    .iter()
+                    .map(|pattern| Ok(glob(pattern)?.compile_matcher()))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                Ok((suppression, files))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(Self {
+            root: staging_dir.join(live_prefix.trim_start_matches('/')),
+            entries,
+        })
+    }
+
+    /// Justification of the suppression covering `rule` for `file`, if any
+    ///
+    /// Findings without a file are only covered by suppressions that list no
+    /// files.
+    fn justification(&self, rule: &str, file: Option<&Path>) -> Option<&'a str> {
+        let relative = file.map(|file| file.strip_prefix(&self.root).unwrap_or(file));
+        self.entries
+            .iter()
+            .find(|(suppression, files)| {
+                suppression.rule == rule
+                    && (files.is_empty()
+                        || relative.is_some_and(|path| {
+                            files
+                                .iter()
+                                .any(|matcher| matches_path(|path| matcher.is_match(path), path))
+                        }))
+            })
+            .map(|(suppression, _)| suppression.justification.as_str())
+    }
+
+    /// Remove the suppressed findings from the report of the check `rule`
+    ///
+    /// Returns every finding of the report, suppressed ones included. When a
+    /// check reports per-file diagnostics its errors only summarise them, so
+    /// they are dropped once all of the diagnostics are suppressed.
+    pub(crate) fn apply(&self, rule: &str, report: &mut Report) -> Vec<QaFinding> {
+        let mut findings = Vec::new();
+
+        if let Some(collector) = report.findings.take() {
+            let severity = if report.is_fatal() {
+                QaSeverity::Error
+            } else {
+                QaSeverity::Warning
+            };
+            let mut kept = super::diagnostics::DiagnosticCollector::new();
+            for diagnostic in collector.into_findings() {
+                let issue_rule = diagnostic.issue_type.rule().unwrap_or(rule);
+                let suppressed = self.justification(issue_rule, Some(&diagnostic.file_path));
+                findings.push(QaFinding {
+                    severity: severity.clone(),
+                    message: diagnostic.issue_type.description(),
+                    file: Some(diagnostic.file_path.clone()),
+                    line: None,
+                    rule: Some(issue_rule.to_string()),
+                    suggestion: diagnostic.issue_type.suggestion().map(str::to_string),
+                    suppressed: suppressed.map(str::to_string),
+                });
+                if suppressed.is_none() {
+                    kept.add_finding(diagnostic);
+                }
+            }
+            if kept.has_findings() {
+                report.findings = Some(kept);
+            } else {
+                report.errors.clear();
+            }
+        }
+
+        let suppressed = self.justification(rule, None);
+        let errors = if report.findings.is_some() {
+            &[][..]
+        } else {
+            &report.errors[..]
+        };
+        let messages = errors
+            .iter()
+            .map(|message| (QaSeverity::Error, message))
+            .chain(
+                report
+                    .warnings
+                    .iter()
+                    .map(|message| (QaSeverity::Warning, message)),
+            );
+        for (severity, message) in messages {
+            findings.push(QaFinding {
+                severity,
+                message: message.clone(),
+                file: None,
+                line: None,
+                rule: Some(rule.to_string()),
+                suggestion: None,
+                suppressed: suppressed.map(str::to_string),
+            });
+        }
+        if suppressed.is_some() {
+            report.errors.clear();
+            report.warnings.clear();
+        }
+
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artifact_qa::diagnostics::DiagnosticCollector;
+
+    fn suppression(rule: &str, files: &[&str]) -> QaSuppression {
+        QaSuppression {
+            rule: rule.to_string(),
+            files: files.iter().map(ToString::to_string).collect(),
+            justification: "accepted".to_string(),
+        }
+    }
+
+    const LIVE: &str = sps2_config::fixed_paths::LIVE_DIR;
+
+    fn hardcoded_report(live_root: &Path, files: &[&str]) -> Report {
+        let mut collector = DiagnosticCollector::new();
+        for file in files {
+            collector.add_hardcoded_path(live_root.join(file), "/opt/pm/build", false);
+        }
+        Report {
+            errors: vec![format!(
+                "Hardcoded path(s) found in {} file(s)",
+                files.len()
+            )],
+            findings: Some(collector),
+            ..Report::default()
+        }
+    }
+
+    #[test]
+    fn suppressed_file_findings_are_kept_but_no_longer_fatal() {
+        let staging = Path::new("/tmp/stage");
+        let entries = [suppression("hardcoded-build-path", &["lib/*/Makefile"])];
+        let suppressions = Suppressions::new(staging, LIVE, &entries).unwrap();
+        let live_root = staging.join(LIVE.trim_start_matches('/'));
+
+        let mut report = hardcoded_report(&live_root, &["lib/python3.12/Makefile"]);
+        let findings = suppressions.apply("hardcoded-paths", &mut report);
+        assert!(!report.is_fatal());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule.as_deref(), Some("hardcoded-build-path"));
+        assert_eq!(findings[0].suppressed.as_deref(), Some("accepted"));
+
+        let mut report = hardcoded_report(
+            &live_root,
+            &["lib/python3.12/Makefile", "bin/python3-config"],
+        );
+        let findings = suppressions.apply("hardcoded-paths", &mut report);
+        assert!(report.is_fatal());
+        assert_eq!(
+            report.findings.as_ref().map(DiagnosticCollector::count),
+            Some(1)
+        );
+        assert_eq!(
+            findings
+                .iter()
+                .filter(|finding| finding.suppressed.is_none())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn check_messages_are_only_suppressed_for_all_files() {
+        let staging = Path::new("/tmp/stage");
+        let report = || Report {
+            warnings: vec!["bin/foo embeds the build date".to_string()],
+            ..Report::default()
+        };
+
+        let entries = [suppression("reproducibility", &["bin/*"])];
+        let mut scoped = report();
+        Suppressions::new(staging, LIVE, &entries)
+            .unwrap()
+            .apply("reproducibility", &mut scoped);
+        assert_eq!(scoped.warnings.len(), 1);
+
+        let entries = [suppression("reproducibility", &[])];
+        let mut everywhere = report();
+        let findings = Suppressions::new(staging, LIVE, &entries)
+            .unwrap()
+            .apply("reproducibility", &mut everywhere);
+        assert!(everywhere.warnings.is_empty());
+        assert_eq!(findings[0].suppressed.as_deref(), Some("accepted"));
+    }
+
+    #[test]
+    fn file_globs_are_relative_to_the_live_prefix() {
+        let staging = Path::new("/tmp/stage");
+        let entries = [suppression("hardcoded-build-path", &["lib/*/Makefile"])];
+        let suppressions = Suppressions::new(staging, "/usr/local/sps2", &entries).unwrap();
+
+        let mut report = hardcoded_report(
+            &staging.join("usr/local/sps2"),
+            &["lib/python3.12/Makefile"],
+        );
+        suppressions.apply("hardcoded-paths", &mut report);
+        assert!(!report.is_fatal());
+
+        let mut elsewhere = hardcoded_report(&staging.join("opt/pm/live"), &["lib/x/Makefile"]);
+        suppressions.apply("hardcoded-paths", &mut elsewhere);
+        assert!(elsewhere.is_fatal());
+    }
+}
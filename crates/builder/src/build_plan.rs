@@ -1,8 +1,10 @@
 //! Build plan representation for staged execution
 
 use crate::environment::IsolationLevel;
-use crate::recipe::model::YamlRecipe;
-use crate::stages::{BuildCommand, PostStep, SourceStep};
+use crate::recipe::model::{
+    BuildSystem, Limits, Package, QaSuppression, RuntimeDepsPolicy, YamlRecipe,
+};
+use crate::stages::{BuildCommand, NamedSourceStep, PatchSource, PatchStep, PostStep, SourceStep};
 use crate::validation;
 use crate::yaml::RecipeMetadata;
 use sps2_errors::Error;
@@ -12,8 +14,9 @@ use std::path::Path;
 
 /// Collection of steps by stage type
 struct StageSteps {
-    source: Vec<SourceStep>,
+    source: Vec<NamedSourceStep>,
     build: Vec<BuildCommand>,
+    test: Vec<BuildCommand>,
     post: Vec<PostStep>,
 }
 
@@ -26,18 +29,33 @@ pub struct BuildPlan {
     /// Environment configuration (extracted from recipe, applied before build)
     pub environment: EnvironmentConfig,
 
-    /// Source operations (fetch, git, local, patches)
-    pub source_steps: Vec<SourceStep>,
+    /// Source operations (fetch, git, local)
+    pub source_steps: Vec<NamedSourceStep>,
+
+    /// Patches applied after the sources are in place
+    pub patches: Vec<PatchStep>,
 
     /// Build operations (configure, make, etc.)
     pub build_steps: Vec<BuildCommand>,
 
+    /// Test stage, run after the build
+    pub test: TestPlan,
+
     /// Post-processing operations
     pub post_steps: Vec<PostStep>,
 
     /// QA pipeline override
     pub qa_pipeline: sps2_types::QaPipelineOverride,
 
+    /// QA findings the recipe accepts
+    pub qa_suppress: Vec<QaSuppression>,
+
+    /// Handling of undeclared runtime dependencies found in the binaries
+    pub runtime_deps: RuntimeDepsPolicy,
+
+    /// Packaging filters and assertions
+    pub package: Package,
+
     /// Whether to automatically install after build
     pub auto_install: bool,
 }
@@ -56,6 +74,30 @@ pub struct EnvironmentConfig {
 
     /// Environment variables to set
     pub variables: HashMap<String, String>,
+
+    /// Resource limits
+    pub limits: Limits,
+}
+
+/// Tests to run after the build
+#[derive(Debug, Clone, Default)]
+pub struct TestPlan {
+    /// Build system whose test suite runs after the test steps
+    pub system: Option<BuildSystem>,
+
+    /// Test commands
+    pub steps: Vec<BuildCommand>,
+
+    /// Recipe's failure policy, overriding the builder config's
+    pub on_failure: Option<sps2_types::TestFailurePolicy>,
+}
+
+impl TestPlan {
+    /// Whether there are any tests to run
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.system.is_none() && self.steps.is_empty()
+    }
 }
 
 impl BuildPlan {
@@ -75,6 +117,7 @@ impl BuildPlan {
             defaults: recipe.environment.defaults,
             network: recipe.environment.network,
             variables: recipe.environment.variables.clone(),
+            limits: recipe.environment.limits,
         };
 
         // Convert metadata
@@ -86,6 +129,8 @@ impl BuildPlan {
             license: Some(recipe.metadata.license.clone()),
             runtime_deps: recipe.metadata.dependencies.runtime.clone(),
             build_deps: recipe.metadata.dependencies.build.clone(),
+            recommends: recipe.metadata.dependencies.recommends.clone(),
+            environment: recipe.install.environment.clone(),
         };
 
         // Extract steps by stage
@@ -95,9 +140,18 @@ impl BuildPlan {
             metadata,
             environment,
             source_steps: stage_steps.source,
+            patches: Self::extract_patch_steps(recipe)?,
             build_steps: stage_steps.build,
+            test: TestPlan {
+                system: recipe.test.system,
+                steps: stage_steps.test,
+                on_failure: recipe.test.on_failure,
+            },
             post_steps: stage_steps.post,
             qa_pipeline: recipe.post.qa_pipeline,
+            qa_suppress: recipe.post.qa_suppress.clone(),
+            runtime_deps: recipe.post.runtime_deps,
+            package: recipe.package.clone(),
             auto_install: recipe.install.auto,
         })
     }
@@ -110,105 +164,168 @@ impl BuildPlan {
     ) -> Result<StageSteps, Error> {
         let source_steps = Self::extract_source_steps(recipe, recipe_path)?;
         let build_steps = Self::extract_build_steps(recipe, sps2_config)?;
+        let test_steps = recipe
+            .test
+            .steps
+            .iter()
+            .map(|step| validation::validate_build_step(step, sps2_config))
+            .collect::<Result<Vec<_>, _>>()?;
         let post_steps = Self::extract_post_steps(recipe, &build_steps, sps2_config)?;
 
         Ok(StageSteps {
             source: source_steps,
             build: build_steps,
+            test: test_steps,
             post: post_steps,
         })
     }
 
     /// Extract source steps from recipe
+    ///
+    /// Each of several sources is placed in its `extract_to` directory, or
+    /// the directory named after it, under the build root.
     fn extract_source_steps(
         recipe: &YamlRecipe,
         recipe_path: &Path,
-    ) -> Result<Vec<SourceStep>, Error> {
+    ) -> Result<Vec<NamedSourceStep>, Error> {
+        use crate::recipe::model::SourceMethod;
+
         let mut source_steps = Vec::new();
 
         // Source acquisition
         if let Some(method) = &recipe.source.method {
-            Self::add_source_method_steps(&mut source_steps, method, None);
+            source_steps.push(NamedSourceStep {
+                name: Self::source_label(method),
+                step: Self::source_method_step(method, None),
+            });
         } else {
             // Handle multi-source case
             for named_source in &recipe.source.sources {
-                Self::add_source_method_steps(
-                    &mut source_steps,
-                    &named_source.method,
-                    named_source.extract_to.clone(),
-                );
+                let method = &named_source.method;
+                let extract_to = named_source
+                    .extract_to
+                    .clone()
+                    .or_else(|| match method {
+                        SourceMethod::Fetch { fetch } => fetch.extract_to.clone(),
+                        _ => None,
+                    })
+                    .or_else(|| named_source.name.clone());
+                source_steps.push(NamedSourceStep {
+                    name: named_source
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| Self::source_label(method)),
+                    step: Self::source_method_step(method, extract_to),
+                });
             }
         }
 
-        // Apply patches
-        for patch in &recipe.source.patches {
-            source_steps.push(SourceStep::ApplyPatch {
-                path: patch.clone(),
-            });
-        }
-
         // Validate all source steps
         let recipe_dir = recipe_path.parent().unwrap_or(Path::new("."));
-        for step in &source_steps {
-            validation::validate_source_step(step, recipe_dir)?;
+        for source in &source_steps {
+            validation::validate_source_step(&source.step, recipe_dir)?;
         }
+        validation::validate_distinct_sources(&source_steps)?;
 
         Ok(source_steps)
     }
 
-    /// Add source method steps to the steps vector
-    fn add_source_method_steps(
-        source_steps: &mut Vec<SourceStep>,
+    /// Name for an unnamed source: its archive, repository or path
+    fn source_label(method: &crate::recipe::model::SourceMethod) -> String {
+        use crate::recipe::model::SourceMethod;
+        let location = match method {
+            SourceMethod::Git { git } => &git.url,
+            SourceMethod::Fetch { fetch } => &fetch.url,
+            SourceMethod::Local { local } => return local.path.clone(),
+        };
+        location
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(location)
+            .to_string()
+    }
+
+    /// Extract the patch steps from the recipe's `source.patches`
+    fn extract_patch_steps(recipe: &YamlRecipe) -> Result<Vec<PatchStep>, Error> {
+        use crate::recipe::model::Patch;
+
+        let mut patch_steps = Vec::new();
+        for (index, patch) in recipe.source.patches.iter().enumerate() {
+            let (source, checksum) = match patch {
+                Patch::Path(file) => (PatchSource::File(file.clone()), None),
+                Patch::File { file, checksum, .. } => {
+                    (PatchSource::File(file.clone()), checksum.as_ref())
+                }
+                Patch::Fetch { url, checksum, .. } => {
+                    (PatchSource::Fetch(url.clone()), checksum.as_ref())
+                }
+                Patch::Inline { inline, name, .. } => (
+                    PatchSource::Inline {
+                        name: name
+                            .clone()
+                            .unwrap_or_else(|| format!("inline-{}.patch", index + 1)),
+                        content: inline.clone(),
+                    },
+                    None,
+                ),
+            };
+            let step = PatchStep {
+                source,
+                strip: patch.strip(),
+                checksum: checksum.map(|checksum| checksum.algorithm.clone()),
+            };
+            validation::validate_patch_step(&step)?;
+            patch_steps.push(step);
+        }
+        Ok(patch_steps)
+    }
+
+    /// Step acquiring a source
+    fn source_method_step(
         method: &crate::recipe::model::SourceMethod,
         extract_to: Option<String>,
-    ) {
+    ) -> SourceStep {
         use crate::recipe::model::{ChecksumAlgorithm, SourceMethod};
         match method {
-            SourceMethod::Git { git } => {
-                source_steps.push(SourceStep::Git {
-                    url: git.url.clone(),
-                    ref_: git.git_ref.clone(),
-                });
-            }
+            SourceMethod::Git { git } => SourceStep::Git {
+                url: git.url.clone(),
+                ref_: git.git_ref.clone(),
+                commit: git.commit.clone(),
+                submodules: git.submodules,
+                depth: git.depth,
+                extract_to,
+            },
             SourceMethod::Fetch { fetch } => {
                 let extract_to = extract_to.or_else(|| fetch.extract_to.clone());
                 match &fetch.checksum {
                     Some(checksum) => match &checksum.algorithm {
-                        ChecksumAlgorithm::Blake3 { blake3 } => {
-                            source_steps.push(SourceStep::FetchBlake3 {
-                                url: fetch.url.clone(),
-                                blake3: blake3.clone(),
-                                extract_to,
-                            });
-                        }
-                        ChecksumAlgorithm::Sha256 { sha256 } => {
-                            source_steps.push(SourceStep::FetchSha256 {
-                                url: fetch.url.clone(),
-                                sha256: sha256.clone(),
-                                extract_to,
-                            });
-                        }
-                        ChecksumAlgorithm::Md5 { md5 } => {
-                            source_steps.push(SourceStep::FetchMd5 {
-                                url: fetch.url.clone(),
-                                md5: md5.clone(),
-                                extract_to,
-                            });
-                        }
-                    },
-                    None => {
-                        source_steps.push(SourceStep::Fetch {
+                        ChecksumAlgorithm::Blake3 { blake3 } => SourceStep::FetchBlake3 {
                             url: fetch.url.clone(),
+                            blake3: blake3.clone(),
                             extract_to,
-                        });
-                    }
+                        },
+                        ChecksumAlgorithm::Sha256 { sha256 } => SourceStep::FetchSha256 {
+                            url: fetch.url.clone(),
+                            sha256: sha256.clone(),
+                            extract_to,
+                        },
+                        ChecksumAlgorithm::Md5 { md5 } => SourceStep::FetchMd5 {
+                            url: fetch.url.clone(),
+                            md5: md5.clone(),
+                            extract_to,
+                        },
+                    },
+                    None => SourceStep::Fetch {
+                        url: fetch.url.clone(),
+                        extract_to,
+                    },
                 }
             }
-            SourceMethod::Local { local } => {
-                source_steps.push(SourceStep::Copy {
-                    src_path: Some(local.path.clone()),
-                });
-            }
+            SourceMethod::Local { local } => SourceStep::Copy {
+                src_path: Some(local.path.clone()),
+                extract_to,
+            },
         }
     }
 
@@ -5,12 +5,16 @@
 //! This module provides build caching, artifact storage, and incremental build tracking
 //! to speed up repeated builds and avoid unnecessary recompilation.
 
+use crate::config::BuildConfig;
+use crate::recipe::model::{Patch, SourceMethod, YamlRecipe};
 use sps2_errors::Error;
 use sps2_events::{AppEvent, BuildDiagnostic, BuildEvent, EventEmitter, EventSender};
+use sps2_hash::Hash;
+use sps2_types::Version;
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 use tokio::fs;
 use tokio::sync::RwLock;
 
@@ -102,6 +106,260 @@ impl BuildCache {
     }
 }
 
+/// Inputs that decide what a build produces, hashed
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    /// Key of a recipe build
+    ///
+    /// Covers the recipe, its sources and patches, the versions its build
+    /// dependencies resolve to, the builder version and the builder's
+    /// signing and network settings. Returns `None` when a source is not
+    /// pinned, such as a fetch without a checksum or a git ref that is not a
+    /// commit, because its content may change between builds.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recipe or a local source or patch cannot be
+    /// read.
+    pub async fn for_recipe(
+        recipe_path: &Path,
+        recipe: &YamlRecipe,
+        build_deps: &[(String, Version)],
+        config: &BuildConfig,
+    ) -> Result<Option<Self>, Error> {
+        let recipe_dir = recipe_path.parent().unwrap_or_else(|| Path::new("."));
+        let signing = &config.packaging_settings().signing;
+        let mut inputs = vec![
+            format!("builder {}", env!("CARGO_PKG_VERSION")),
+            format!(
+                "recipe {}",
+                Hash::blake3_hash_file(recipe_path).await?.to_hex()
+            ),
+            format!(
+                "signing {} {:?} hardened-runtime={} entitlements={:?}",
+                signing.enabled,
+                signing.identity,
+                signing.enable_hardened_runtime,
+                signing.entitlements_file
+            ),
+            format!("network {}", config.default_allow_network()),
+        ];
+
+        let methods = recipe
+            .source
+            .method
+            .iter()
+            .chain(recipe.source.sources.iter().map(|source| &source.method));
+        for method in methods {
+            let input = match method {
+                SourceMethod::Fetch { fetch } => match &fetch.checksum {
+                    Some(checksum) => format!("fetch {} {checksum:?}", fetch.url),
+                    None => return Ok(None),
+                },
+                SourceMethod::Git { git } => {
+                    let is_commit = git.git_ref.len() == 40
+                        && git.git_ref.chars().all(|c| c.is_ascii_hexdigit());
+                    let commit = if is_commit {
+                        &git.git_ref
+                    } else {
+                        match &git.commit {
+                            Some(commit) => commit,
+                            None => return Ok(None),
+                        }
+                    };
+                    format!("git {} {commit} submodules={}", git.url, git.submodules)
+                }
+                SourceMethod::Local { local } => {
+                    let path = recipe_dir.join(&local.path);
+                    let hash = if path.is_dir() {
+                        Hash::hash_directory(&path).await?
+                    } else {
+                        Hash::blake3_hash_file(&path).await?
+                    };
+                    format!("local {} {}", local.path, hash.to_hex())
+                }
+            };
+            inputs.push(input);
+        }
+        for patch in &recipe.source.patches {
+            let input = match patch {
+                Patch::Path(file) | Patch::File { file, .. } => {
+                    let path = recipe_dir.join(file);
+                    if !path.is_file() {
+                        continue;
+                    }
+                    let hash = Hash::blake3_hash_file(&path).await?;
+                    format!("patch {file} {}", hash.to_hex())
+                }
+                Patch::Fetch { url, checksum, .. } => match checksum {
+                    Some(checksum) => format!("patch {url} {checksum:?}"),
+                    None => return Ok(None),
+                },
+                Patch::Inline { inline, .. } => {
+                    let hash = Hash::blake3_from_data(inline.as_bytes());
+                    format!("patch inline {}", hash.to_hex())
+                }
+            };
+            inputs.push(format!("{input} -p{}", patch.strip()));
+        }
+
+        let mut deps: Vec<String> = build_deps
+            .iter()
+            .map(|(name, version)| format!("build-dep {name} {version}"))
+            .collect();
+        deps.sort();
+        inputs.extend(deps);
+
+        let hash = Hash::blake3_from_data(inputs.join("\n").as_bytes());
+        Ok(Some(Self(hash.to_hex())))
+    }
+
+    /// The key as a hex string
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Packages from earlier builds, keyed by [`CacheKey`]
+///
+/// Each entry is a directory named after its key holding the package and a
+/// stamp file whose modification time records when the entry was last used.
+#[derive(Debug, Clone)]
+pub struct ArtifactCache {
+    dir: PathBuf,
+}
+
+const LAST_USED: &str = "last-used";
+
+impl ArtifactCache {
+    /// Cache kept in `dir`
+    #[must_use]
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Package built for `key`, if cached; marks the entry as used
+    pub async fn get(&self, key: &CacheKey) -> Option<PathBuf> {
+        let entry = self.dir.join(key.as_str());
+        let mut files = fs::read_dir(&entry).await.ok()?;
+        while let Ok(Some(file)) = files.next_entry().await {
+            let path = file.path();
+            if path.extension().is_some_and(|ext| ext == "sp") {
+                let _ = fs::write(entry.join(LAST_USED), b"").await;
+                return Some(path);
+            }
+        }
+        None
+    }
+
+    /// Every cached package, without marking entries as used
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read.
+    pub async fn packages(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut packages = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            // Entries still being assembled start with a dot
+            if entry.file_name().to_string_lossy().starts_with('.')
+                || !entry.file_type().await?.is_dir()
+            {
+                continue;
+            }
+            let mut files = fs::read_dir(entry.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let path = file.path();
+                if path.extension().is_some_and(|ext| ext == "sp") {
+                    packages.push(path);
+                }
+            }
+        }
+        packages.sort();
+        Ok(packages)
+    }
+
+    /// Store a copy of `package` for `key`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package cannot be copied into the cache.
+    pub async fn put(&self, key: &CacheKey, package: &Path) -> Result<(), Error> {
+        let Some(file_name) = package.file_name() else {
+            return Ok(());
+        };
+        // Assembled aside and renamed, so readers never see half an entry
+        let staging = self
+            .dir
+            .join(format!(".{key}.{}", uuid::Uuid::new_v4().simple()));
+        fs::create_dir_all(&staging).await?;
+        fs::copy(package, staging.join(file_name)).await?;
+        fs::write(staging.join(LAST_USED), b"").await?;
+
+        let entry = self.dir.join(key.as_str());
+        if fs::rename(&staging, &entry).await.is_err() {
+            // Another build cached the same key first
+            let _ = fs::remove_dir_all(&staging).await;
+        }
+        Ok(())
+    }
+
+    /// Remove entries unused for longer than `max_age`
+    ///
+    /// Returns the number of entries removed and the bytes freed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read or an entry
+    /// cannot be removed.
+    pub async fn prune(&self, max_age: Duration) -> Result<(usize, u64), Error> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(cutoff) = SystemTime::now().checked_sub(max_age) else {
+            return Ok((0, 0));
+        };
+
+        let (mut removed, mut freed) = (0, 0);
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let stamp = match fs::metadata(path.join(LAST_USED)).await {
+                Ok(metadata) => metadata,
+                Err(_) => entry.metadata().await?,
+            };
+            if stamp.modified()? >= cutoff {
+                continue;
+            }
+
+            let mut files = fs::read_dir(&path).await?;
+            while let Some(file) = files.next_entry().await? {
+                freed += file.metadata().await?.len();
+            }
+            fs::remove_dir_all(&path).await?;
+            removed += 1;
+        }
+        Ok((removed, freed))
+    }
+}
+
 /// Simple source cache for downloads and git repositories
 #[derive(Debug)]
 pub struct SourceCache {
@@ -368,3 +626,127 @@ impl CacheStatistics {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recipe::parser::parse_yaml_recipe_from_string;
+    use tempfile::TempDir;
+
+    const RECIPE: &str = r"
+metadata:
+  name: hello
+  version: 1.0.0
+  description: Hello
+  license: MIT
+source:
+  local:
+    path: src
+build:
+  system: make
+";
+
+    async fn write_recipe(dir: &Path, content: &str) -> (PathBuf, YamlRecipe) {
+        let path = dir.join("hello.yml");
+        fs::write(&path, content).await.unwrap();
+        fs::create_dir_all(dir.join("src")).await.unwrap();
+        fs::write(dir.join("src/main.c"), "int main() {}")
+            .await
+            .unwrap();
+        (path, parse_yaml_recipe_from_string(content).unwrap())
+    }
+
+    #[tokio::test]
+    async fn key_follows_sources_and_build_deps() {
+        let temp = TempDir::new().unwrap();
+        let (path, recipe) = write_recipe(temp.path(), RECIPE).await;
+        let deps = [("make".to_string(), Version::new(4, 4, 0))];
+        let config = BuildConfig::default();
+
+        let key = CacheKey::for_recipe(&path, &recipe, &deps, &config)
+            .await
+            .unwrap();
+        let again = CacheKey::for_recipe(&path, &recipe, &deps, &config)
+            .await
+            .unwrap();
+        assert!(key.is_some());
+        assert_eq!(key, again);
+
+        let newer_deps = [("make".to_string(), Version::new(4, 5, 0))];
+        let other_deps = CacheKey::for_recipe(&path, &recipe, &newer_deps, &config)
+            .await
+            .unwrap();
+        assert_ne!(key, other_deps);
+
+        fs::write(temp.path().join("src/main.c"), "int main() { return 1; }")
+            .await
+            .unwrap();
+        let edited = CacheKey::for_recipe(&path, &recipe, &deps, &config)
+            .await
+            .unwrap();
+        assert_ne!(key, edited);
+    }
+
+    #[tokio::test]
+    async fn key_follows_signing_and_network_settings() {
+        let temp = TempDir::new().unwrap();
+        let (path, recipe) = write_recipe(temp.path(), RECIPE).await;
+        let key = |config: BuildConfig| {
+            let (path, recipe) = (path.clone(), recipe.clone());
+            async move {
+                CacheKey::for_recipe(&path, &recipe, &[], &config)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+        let plain = key(BuildConfig::default()).await;
+
+        let mut signed = BuildConfig::default();
+        signed.config.packaging.signing.enabled = true;
+        signed.config.packaging.signing.identity = Some("/keys/release.key".to_string());
+        assert_ne!(key(signed).await, plain);
+
+        let mut networked = BuildConfig::default();
+        networked.config.build.default_allow_network = true;
+        assert_ne!(key(networked).await, plain);
+    }
+
+    #[tokio::test]
+    async fn unpinned_sources_are_not_cached() {
+        let temp = TempDir::new().unwrap();
+        let content = RECIPE.replace(
+            "  local:\n    path: src",
+            "  git:\n    url: https://example.com/hello.git\n    ref: main",
+        );
+        let (path, recipe) = write_recipe(temp.path(), &content).await;
+        let key = CacheKey::for_recipe(&path, &recipe, &[], &BuildConfig::default())
+            .await
+            .unwrap();
+        assert!(key.is_none());
+    }
+
+    #[tokio::test]
+    async fn cached_packages_are_found_until_pruned() {
+        let temp = TempDir::new().unwrap();
+        let cache = ArtifactCache::new(temp.path().join("cache"));
+        let key = CacheKey("abc123".to_string());
+        let package = temp.path().join("hello-1.0.0-1.arm64.sp");
+        fs::write(&package, vec![0u8; 64]).await.unwrap();
+
+        assert!(cache.get(&key).await.is_none());
+        cache.put(&key, &package).await.unwrap();
+        let cached = cache.get(&key).await.unwrap();
+        assert_eq!(cached.file_name(), package.file_name());
+        assert_eq!(cache.packages().await.unwrap(), vec![cached.clone()]);
+
+        assert_eq!(
+            cache.prune(Duration::from_secs(3600)).await.unwrap(),
+            (0, 0)
+        );
+        let (removed, freed) = cache.prune(Duration::ZERO).await.unwrap();
+        assert_eq!(removed, 1);
+        assert!(freed >= 64);
+        assert!(cache.get(&key).await.is_none());
+    }
+}
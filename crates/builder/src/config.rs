@@ -7,10 +7,11 @@
 
 use sps2_config::builder::{
     BuildSettings, BuilderConfig, CacheSettings, EnvironmentSettings, PackagingSettings,
-    PerformanceSettings, SbomSettings, SecuritySettings, ShellExpansionPolicy, SigningSettings,
-    ValidationConfig, ValidationMode,
+    PerformanceSettings, QaSettings, RemoteSettings, SbomSettings, SecuritySettings,
+    ShellExpansionPolicy, SigningSettings, ValidationConfig, ValidationMode,
 };
 use sps2_config::ResourceManager;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 /// Builder context configuration
@@ -85,6 +86,18 @@ impl BuildConfig {
         &self.config.security
     }
 
+    /// Get remote build worker settings
+    #[must_use]
+    pub fn remote_settings(&self) -> &RemoteSettings {
+        &self.config.remote
+    }
+
+    /// Get site-specific QA settings
+    #[must_use]
+    pub fn qa_settings(&self) -> &QaSettings {
+        &self.config.qa
+    }
+
     /// Get SBOM configuration
     #[must_use]
     pub fn sbom_config(&self) -> &SbomSettings {
@@ -115,6 +128,24 @@ impl BuildConfig {
         self.config.build.default_allow_network
     }
 
+    /// Prefix of the installation builds run against, holding the state
+    /// that installed build and runtime dependencies are looked up in
+    #[must_use]
+    pub fn prefix_path(&self) -> PathBuf {
+        self.sps2_config.as_ref().map_or_else(
+            || PathBuf::from(sps2_config::fixed_paths::PREFIX),
+            sps2_config::Config::prefix_path,
+        )
+    }
+
+    /// Whether sps2 runs offline, so sources cannot be fetched
+    #[must_use]
+    pub fn offline(&self) -> bool {
+        self.sps2_config
+            .as_ref()
+            .is_some_and(|config| config.network.offline)
+    }
+
     /// Get default isolation level (can be overridden by recipe)
     #[must_use]
     pub fn default_isolation_level(&self) -> &str {
@@ -19,6 +19,7 @@ use std::sync::Arc;
 
 /// Builder API exposed to Starlark recipes
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)] // independent network, SBOM and install switches
 pub struct BuilderApi {
     /// Working directory for source extraction
     pub(crate) working_dir: PathBuf,
@@ -28,6 +29,8 @@ pub struct BuilderApi {
     net_client: NetClient,
     /// Whether network access is allowed
     allow_network: bool,
+    /// Whether sps2 runs offline, so sources cannot be fetched
+    offline: bool,
     /// SBOM generation enabled
     auto_sbom: bool,
     /// SBOM exclusion patterns
@@ -40,6 +43,9 @@ pub struct BuilderApi {
     explicit_isolation_level: Option<IsolationLevel>,
     /// Resource manager
     resources: Arc<ResourceManager>,
+    /// sps2's temp area, for temporary files; the system's temp directory
+    /// is used when unset
+    temp_dir: Option<PathBuf>,
 }
 
 impl BuilderApi {
@@ -54,6 +60,7 @@ impl BuilderApi {
             downloads: HashMap::new(),
             net_client: NetClient::new(NetConfig::default())?,
             allow_network: false,
+            offline: false,
             auto_sbom: true,
             sbom_excludes: vec![
                 "./*.dSYM".to_string(),
@@ -65,6 +72,7 @@ impl BuilderApi {
             build_metadata: HashMap::new(),
             explicit_isolation_level: None,
             resources,
+            temp_dir: None,
         })
     }
 
@@ -75,11 +83,30 @@ impl BuilderApi {
         self
     }
 
+    /// Refuse to fetch sources because sps2 runs offline
+    #[must_use]
+    pub fn offline(&mut self, offline: bool) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Keep temporary files in sps2's temp area at `dir`
+    #[must_use]
+    pub fn temp_dir(&mut self, dir: PathBuf) -> &mut Self {
+        self.temp_dir = Some(dir);
+        self
+    }
+
     /// Update the working directory (used after git clone to point to the correct source)
     pub fn set_working_dir(&mut self, new_working_dir: PathBuf) {
         self.working_dir = new_working_dir;
     }
 
+    /// Directory that `extract_to` destinations are relative to
+    fn build_root(&self) -> &Path {
+        self.working_dir.parent().unwrap_or(&self.working_dir)
+    }
+
     /// Download a file
     ///
     /// # Errors
@@ -240,13 +267,28 @@ impl BuilderApi {
 
     /// Clone a git repository
     ///
+    /// Checks out `ref_`, a branch, tag, full commit hash or `HEAD`, with
+    /// `depth` commits of history (`0` for all of it). With `commit`, the
+    /// checkout must resolve to that commit. With `extract_to`, the repository
+    /// is cloned to that directory of the build root and the working directory
+    /// is left unchanged.
+    ///
     /// # Errors
     ///
     /// Returns an error if:
-    /// - Network access is disabled
+    /// - The builder is offline
     /// - The URL is invalid
     /// - The git clone fails
-    pub async fn git(&mut self, url: &str, ref_: &str) -> Result<PathBuf, Error> {
+    /// - The checkout does not resolve to `commit`
+    pub async fn git(
+        &mut self,
+        url: &str,
+        ref_: &str,
+        commit: Option<&str>,
+        submodules: bool,
+        depth: u32,
+        extract_to: Option<&str>,
+    ) -> Result<PathBuf, Error> {
         // Git operations always have network access - they're source fetching, not build operations
 
         // Check if already cloned
@@ -254,6 +296,13 @@ impl BuilderApi {
             return Ok(path.clone());
         }
 
+        if self.offline {
+            return Err(BuildError::NetworkDisabled {
+                url: url.to_string(),
+            }
+            .into());
+        }
+
         // Extract repository name from URL
         let repo_name = url
             .split('/')
@@ -263,57 +312,66 @@ impl BuilderApi {
                 url: url.to_string(),
             })?;
 
-        let clone_path = self.working_dir.join(repo_name);
-
-        // Use platform abstraction for process execution
-        let platform = PlatformManager::instance().platform();
-        let context = PlatformContext::new(None);
+        let clone_path = match extract_to {
+            Some(extract_to) => {
+                let clone_path = self.build_root().join(extract_to);
+                if let Some(parent) = clone_path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                clone_path
+            }
+            None => self.working_dir.join(repo_name),
+        };
+        let clone_dir = clone_path.display().to_string();
+        let depth_arg = format!("--depth={depth}");
+        let shallow = (depth > 0).then_some(depth_arg.as_str());
 
         // Clone using git command (better compatibility than git2 crate)
-        let output = if ref_ == "HEAD" {
-            // For HEAD, don't use --branch flag
-            let mut cmd = platform.process().create_command("git");
-            cmd.args([
-                "clone",
-                "--depth",
-                "1",
-                url,
-                &clone_path.display().to_string(),
-            ]);
-            cmd.current_dir(&self.working_dir);
-            platform.process().execute_command(&context, cmd).await?
+        let is_commit = ref_.len() == 40 && ref_.chars().all(|c| c.is_ascii_hexdigit());
+        if is_commit {
+            // Commits cannot be cloned by name, so fetch one into a new repository
+            run_git(url, &self.working_dir, &["init", "--quiet", &clone_dir]).await?;
+            run_git(url, &clone_path, &["remote", "add", "origin", url]).await?;
+            let mut fetch = vec!["fetch", "--quiet"];
+            fetch.extend(shallow);
+            fetch.extend(["origin", ref_]);
+            run_git(url, &clone_path, &fetch).await?;
+            run_git(url, &clone_path, &["checkout", "--quiet", "FETCH_HEAD"]).await?;
         } else {
-            // For specific branches/tags, use --branch
-            let mut cmd = platform.process().create_command("git");
-            cmd.args([
-                "clone",
-                "--depth",
-                "1",
-                "--branch",
-                ref_,
-                url,
-                &clone_path.display().to_string(),
-            ]);
-            cmd.current_dir(&self.working_dir);
-            platform.process().execute_command(&context, cmd).await?
-        };
-
-        if !output.status.success() {
-            return Err(BuildError::GitCloneFailed {
-                message: format!(
-                    "Failed to clone {}: {}",
-                    url,
-                    String::from_utf8_lossy(&output.stderr)
-                ),
+            let mut clone = vec!["clone", "--quiet"];
+            clone.extend(shallow);
+            // For HEAD, don't use --branch flag
+            if ref_ != "HEAD" {
+                clone.extend(["--branch", ref_]);
             }
-            .into());
+            clone.extend([url, &clone_dir]);
+            run_git(url, &self.working_dir, &clone).await?;
+        }
+
+        if submodules {
+            let mut update = vec!["submodule", "update", "--init", "--recursive"];
+            update.extend(shallow);
+            run_git(url, &clone_path, &update).await?;
         }
 
+        let resolved = run_git(url, &clone_path, &["rev-parse", "HEAD"]).await?;
+        if let Some(expected) = commit {
+            if !resolved.starts_with(&expected.to_ascii_lowercase()) {
+                return Err(BuildError::GitCommitMismatch {
+                    url: url.to_string(),
+                    expected: expected.to_string(),
+                    actual: resolved,
+                }
+                .into());
+            }
+        }
         self.downloads.insert(url.to_string(), clone_path.clone());
 
         // Update working directory to the cloned path so subsequent operations
         // (like cargo build) work in the correct directory
-        self.set_working_dir(clone_path.clone());
+        if extract_to.is_none() {
+            self.set_working_dir(clone_path.clone());
+        }
 
         Ok(clone_path)
     }
@@ -326,11 +384,16 @@ impl BuilderApi {
     pub async fn apply_patch(
         &self,
         patch_path: &Path,
+        strip: u32,
         env: &BuildEnvironment,
     ) -> Result<BuildCommandResult, Error> {
         env.execute_command(
             "patch",
-            &["-p1", "-i", &patch_path.display().to_string()],
+            &[
+                &format!("-p{strip}"),
+                "-i",
+                &patch_path.display().to_string(),
+            ],
             Some(&self.working_dir),
         )
         .await
@@ -703,6 +766,47 @@ impl BuilderApi {
             .await
     }
 
+    /// Run the test suite of `system` on the built sources
+    ///
+    /// Plain `make` projects run `make check`, falling back to `make test`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the test suite cannot be run.
+    pub async fn test(
+        &self,
+        system: crate::recipe::model::BuildSystem,
+        env: &BuildEnvironment,
+    ) -> Result<crate::build_systems::TestResults, Error> {
+        use crate::build_systems::{
+            AutotoolsBuildSystem, BuildSystem, BuildSystemContext, CMakeBuildSystem,
+            CargoBuildSystem, GoBuildSystem, MesonBuildSystem, NodeJsBuildSystem,
+            PythonBuildSystem,
+        };
+        use crate::recipe::model::BuildSystem as RecipeBuildSystem;
+
+        let build_system: Box<dyn BuildSystem> = match system {
+            RecipeBuildSystem::Autotools | RecipeBuildSystem::Make => {
+                Box::new(AutotoolsBuildSystem::new())
+            }
+            RecipeBuildSystem::Cmake => Box::new(CMakeBuildSystem::new()),
+            RecipeBuildSystem::Meson => Box::new(MesonBuildSystem::new()),
+            RecipeBuildSystem::Cargo => Box::new(CargoBuildSystem::new()),
+            RecipeBuildSystem::Go => Box::new(GoBuildSystem::new()),
+            RecipeBuildSystem::Python => Box::new(PythonBuildSystem::new()),
+            RecipeBuildSystem::Nodejs => Box::new(NodeJsBuildSystem::new()),
+        };
+
+        let mut ctx = BuildSystemContext::new(env.clone(), self.working_dir.clone());
+        // Same out-of-source directory the cmake and meson builds use
+        if build_system.prefers_out_of_source_build() {
+            ctx.build_dir = self.working_dir.join("build");
+        }
+        ctx.network_allowed = self.allow_network;
+
+        build_system.test(&ctx).await
+    }
+
     /// Mark that installation is requested
     ///
     /// This method does not actually perform installation during recipe execution.
@@ -995,7 +1099,13 @@ impl BuilderApi {
         use tokio::io::{AsyncWriteExt, BufReader};
 
         // Create a temporary file to decompress to
-        let temp_dir = tempfile::tempdir().map_err(|e| BuildError::ExtractionFailed {
+        let temp_dir = match &self.temp_dir {
+            Some(dir) => sps2_store::TempArea::new(dir.clone())
+                .tempdir()
+                .map_err(|e| e.to_string()),
+            None => tempfile::tempdir().map_err(|e| e.to_string()),
+        }
+        .map_err(|e| BuildError::ExtractionFailed {
             message: format!("Failed to create temp directory: {e}"),
         })?;
         let temp_path = temp_dir.path().join("archive.tar");
@@ -1132,6 +1242,9 @@ impl BuilderApi {
 
     /// Copy source files from a directory to the working directory
     ///
+    /// With `extract_to`, the files are copied to that directory of the build
+    /// root instead.
+    ///
     /// # Errors
     ///
     /// Returns an error if:
@@ -1141,6 +1254,7 @@ impl BuilderApi {
     pub async fn copy(
         &mut self,
         src_path: Option<&str>,
+        extract_to: Option<&str>,
         context: &crate::BuildContext,
     ) -> Result<(), Error> {
         use crate::utils::fileops::copy_source_files;
@@ -1168,7 +1282,15 @@ impl BuilderApi {
         }
 
         // Copy source files from the source directory to working directory
-        copy_source_files(&source_dir, &self.working_dir, context).await?;
+        let dest_dir = match extract_to {
+            Some(extract_to) => {
+                let dest_dir = self.build_root().join(extract_to);
+                fs::create_dir_all(&dest_dir).await?;
+                dest_dir
+            }
+            None => self.working_dir.clone(),
+        };
+        copy_source_files(&source_dir, &dest_dir, context).await?;
 
         Ok(())
     }
@@ -1500,6 +1622,29 @@ impl BuilderApi {
     }
 }
 
+/// Run git in `dir` for the repository at `url`, returning trimmed stdout
+async fn run_git(url: &str, dir: &Path, args: &[&str]) -> Result<String, Error> {
+    // Use platform abstraction for process execution
+    let platform = PlatformManager::instance().platform();
+    let context = PlatformContext::new(None);
+    let mut cmd = platform.process().create_command("git");
+    cmd.args(args);
+    cmd.current_dir(dir);
+    let output = platform.process().execute_command(&context, cmd).await?;
+
+    if !output.status.success() {
+        return Err(BuildError::GitCloneFailed {
+            message: format!(
+                "git {} failed for {url}: {}",
+                args.first().copied().unwrap_or_default(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        }
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
 /// Fix permissions on a file if needed
 fn fix_file_permissions(path: &std::path::Path) -> Result<bool, std::io::Error> {
     use std::os::unix::fs::PermissionsExt;
@@ -2,17 +2,24 @@
 
 use super::context::BuildContext;
 use crate::artifact_qa::run_quality_pipeline;
+use crate::cache::{ArtifactCache, CacheKey};
 use crate::config::BuildConfig;
-use crate::packaging::create_and_sign_package;
 use crate::packaging::manifest::create_manifest;
+use crate::packaging::runtime_deps::apply_runtime_deps;
+use crate::packaging::{create_and_sign_package, sign_package};
 use crate::recipe::execute_recipe;
+use crate::recipe::model::YamlRecipe;
+use crate::recipe::parser::parse_yaml_recipe;
 use crate::utils::events::send_event;
 use crate::{BuildEnvironment, BuildResult};
 use sps2_errors::Error;
 use sps2_events::{AppEvent, GeneralEvent};
 use sps2_net::NetClient;
 use sps2_resolver::Resolver;
+use sps2_state::StateManager;
 use sps2_store::PackageStore;
+use sps2_types::package::PackageSpec;
+use sps2_types::Version;
 use std::path::Path;
 
 use sps2_config::ResourceManager;
@@ -98,16 +105,29 @@ impl Builder {
             }),
         );
 
+        // Reuse a package built from identical inputs
+        let cached = self.artifact_cache(&context).await;
+        if let Some((cache, key)) = &cached {
+            if let Some(result) = self.reuse_cached(&context, cache, key).await? {
+                return Ok(result);
+            }
+        }
+
         // Setup build environment
         let mut environment = self.setup_build_environment(&context).await?;
 
         // Execute recipe and setup dependencies
-        let (runtime_deps, recipe_metadata, install_requested, qa_pipeline) = self
-            .execute_recipe_and_setup_deps(&context, &mut environment)
-            .await?;
+        let (mut runtime_deps, recipe_metadata, install_requested, qa_pipeline) =
+            Box::pin(self.execute_recipe_and_setup_deps(&context, &mut environment)).await?;
 
         // Run quality checks
-        run_quality_pipeline(&context, &environment, Some(qa_pipeline)).await?;
+        run_quality_pipeline(
+            &context,
+            &environment,
+            Some(qa_pipeline),
+            self.config.qa_settings(),
+        )
+        .await?;
 
         // If fix_permissions was requested in the recipe, run it now as final step
         if let Some(paths) = &environment.fix_permissions_request {
@@ -142,6 +162,15 @@ impl Builder {
             }
         }
 
+        // Check linked libraries against the declared runtime dependencies
+        apply_runtime_deps(
+            &context,
+            &environment,
+            &self.config.prefix_path(),
+            &mut runtime_deps,
+        )
+        .await?;
+
         // Create manifest (SBOM soft-disabled here)
         let manifest = create_manifest(&context, runtime_deps, &recipe_metadata, &environment);
 
@@ -156,7 +185,102 @@ impl Builder {
         // Cleanup and finalize
         Self::cleanup_and_finalize(&updated_context, &environment, &package_path);
 
-        Ok(BuildResult::new(package_path).with_install_requested(install_requested))
+        if let Some((cache, key)) = &cached {
+            if let Err(error) = cache.put(key, &package_path).await {
+                send_event(
+                    &context,
+                    AppEvent::General(GeneralEvent::warning(format!(
+                        "Failed to cache build of {} {}: {error}",
+                        context.name, context.version
+                    ))),
+                );
+            }
+        }
+
+        Ok(BuildResult::new(package_path)
+            .with_install_requested(install_requested)
+            .with_tests(environment.test_results().cloned()))
+    }
+
+    /// Artifact cache and key for this build, unless caching is disabled or
+    /// the build's inputs cannot be pinned down
+    async fn artifact_cache(&self, context: &BuildContext) -> Option<(ArtifactCache, CacheKey)> {
+        let settings = self.config.cache_config();
+        if !settings.artifacts {
+            return None;
+        }
+        let recipe = parse_yaml_recipe(&context.recipe_path).await.ok()?;
+        let build_deps = self.installed_build_deps(&recipe).await?;
+        match CacheKey::for_recipe(&context.recipe_path, &recipe, &build_deps, &self.config).await {
+            Ok(Some(key)) => Some((ArtifactCache::new(settings.artifact_dir.clone()), key)),
+            Ok(None) => {
+                send_event(
+                    context,
+                    AppEvent::General(GeneralEvent::debug(
+                        "Build not cached: a source is not pinned to a checksum or commit",
+                    )),
+                );
+                None
+            }
+            Err(error) => {
+                send_event(
+                    context,
+                    AppEvent::General(GeneralEvent::debug(format!("Build not cached: {error}"))),
+                );
+                None
+            }
+        }
+    }
+
+    /// Installed versions the recipe's build dependencies resolve to; `None`
+    /// if one is not installed, in which case the build fails anyway
+    async fn installed_build_deps(&self, recipe: &YamlRecipe) -> Option<Vec<(String, Version)>> {
+        let state = StateManager::new(&self.config.prefix_path()).await.ok()?;
+        let installed = state.get_installed_packages().await.ok()?;
+        let mut resolved = Vec::new();
+        for dep in &recipe.metadata.dependencies.build {
+            let spec = PackageSpec::parse(dep).ok()?;
+            let package = installed.iter().find(|package| {
+                package.name == spec.name && spec.version_spec.matches(&package.version())
+            })?;
+            resolved.push((package.name.clone(), package.version()));
+        }
+        Some(resolved)
+    }
+
+    /// Copy the cached package for `key` into the output directory and sign
+    /// it as a fresh build would be
+    async fn reuse_cached(
+        &self,
+        context: &BuildContext,
+        cache: &ArtifactCache,
+        key: &CacheKey,
+    ) -> Result<Option<BuildResult>, Error> {
+        let Some(cached) = cache.get(key).await else {
+            return Ok(None);
+        };
+        let Some(file_name) = cached.file_name() else {
+            return Ok(None);
+        };
+        tokio::fs::create_dir_all(&context.output_dir).await?;
+        let package_path = context.output_dir.join(file_name);
+        tokio::fs::copy(&cached, &package_path).await?;
+        sign_package(&self.config, context, &package_path).await?;
+
+        let recipe = parse_yaml_recipe(&context.recipe_path).await?;
+        send_event(
+            context,
+            AppEvent::General(GeneralEvent::OperationCompleted {
+                operation: format!(
+                    "Reused cached build of {} {} ({key})",
+                    context.name, context.version
+                ),
+                success: true,
+            }),
+        );
+        Ok(Some(
+            BuildResult::new(package_path).with_install_requested(recipe.install.auto),
+        ))
     }
 
     /// Setup build environment with full isolation
@@ -223,6 +347,8 @@ impl Builder {
             license: Some(yaml_recipe.metadata.license.clone()),
             runtime_deps: yaml_recipe.metadata.dependencies.runtime.clone(),
             build_deps: yaml_recipe.metadata.dependencies.build.clone(),
+            recommends: yaml_recipe.metadata.dependencies.recommends.clone(),
+            environment: yaml_recipe.install.environment.clone(),
         };
 
         // Extract build dependencies as PackageSpec
@@ -303,3 +429,71 @@ impl Default for Builder {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packaging::signing::PackageSigner;
+    use tempfile::TempDir;
+
+    const RECIPE: &str = r"
+metadata:
+  name: hello
+  version: 1.0.0
+  description: Hello
+  license: MIT
+source:
+  local:
+    path: src
+build:
+  system: make
+";
+
+    #[tokio::test]
+    async fn cached_packages_are_signed_when_reused() {
+        let temp = TempDir::new().unwrap();
+        let recipe_path = temp.path().join("hello.yml");
+        tokio::fs::write(&recipe_path, RECIPE).await.unwrap();
+        let minisign::KeyPair { pk, sk } =
+            minisign::KeyPair::generate_encrypted_keypair(Some("secret".to_string())).unwrap();
+        let key_path = temp.path().join("release.key");
+        PackageSigner::save_secret_key(&sk, &key_path, None)
+            .await
+            .unwrap();
+
+        let cache = ArtifactCache::new(temp.path().join("cache"));
+        let built = temp.path().join("hello-1.0.0-1.arm64.sp");
+        tokio::fs::write(&built, vec![0u8; 64]).await.unwrap();
+
+        let mut config = BuildConfig::default();
+        config.config.packaging.signing.enabled = true;
+        config.config.packaging.signing.identity = Some(key_path.display().to_string());
+        config.config.packaging.signing.keychain_path = Some("secret".into());
+        tokio::fs::create_dir_all(temp.path().join("src"))
+            .await
+            .unwrap();
+        let recipe = parse_yaml_recipe(&recipe_path).await.unwrap();
+        let key = CacheKey::for_recipe(&recipe_path, &recipe, &[], &config)
+            .await
+            .unwrap()
+            .unwrap();
+        cache.put(&key, &built).await.unwrap();
+        let signing = config.config.packaging.signing.clone();
+        let context = BuildContext::new(
+            "hello".to_string(),
+            Version::new(1, 0, 0),
+            recipe_path,
+            temp.path().join("out"),
+        );
+        let result = Builder::with_config(config)
+            .reuse_cached(&context, &cache, &key)
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(PackageSigner::new(signing)
+            .verify_package(&result.package_path, &pk)
+            .await
+            .unwrap());
+    }
+}
@@ -41,7 +41,7 @@ impl BuildContext {
             name,
             version,
             revision: 1,
-            arch: "arm64".to_string(),
+            arch: sps2_types::Arch::host().to_string(),
             recipe_path,
             output_dir,
             event_sender: None,
@@ -89,10 +89,13 @@ impl BuildContext {
     /// Get package filename
     #[must_use]
     pub fn package_filename(&self) -> String {
-        format!(
-            "{}-{}-{}.{}.sp",
-            self.name, self.version, self.revision, self.arch
-        )
+        sps2_types::PackageFilename {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            revision: self.revision,
+            arch: self.arch.clone(),
+        }
+        .to_string()
     }
 
     /// Get full output path
@@ -41,8 +41,21 @@ pub struct BuildEnvironment {
     pub(crate) used_build_systems: HashSet<String>,
     /// Fix permissions requests (None if not requested, Some(paths) if requested)
     pub(crate) fix_permissions_request: Option<Vec<String>>,
+    /// Packaging filters and assertions from the recipe
+    pub(crate) package_filters: crate::recipe::model::Package,
+    /// Outcome of the recipe's test stage (None if it has no tests)
+    pub(crate) test_results: Option<sps2_types::TestResults>,
+    /// QA findings the recipe accepts
+    pub(crate) qa_suppressions: Vec<crate::recipe::model::QaSuppression>,
+    pub(crate) runtime_deps_policy: crate::recipe::model::RuntimeDepsPolicy,
+    /// Source patches applied, in order, for the package manifest
+    pub(crate) applied_patches: Vec<sps2_types::AppliedPatch>,
     /// Current isolation level
     pub(crate) isolation_level: crate::environment::IsolationLevel,
+    /// Sandbox profile build commands run under (enhanced and hermetic isolation)
+    pub(crate) sandbox_profile: Option<String>,
+    /// Message the profile's deny rules log, to find this build's denials
+    pub(crate) sandbox_tag: String,
 }
 
 impl EventEmitter for BuildEnvironment {
@@ -82,7 +95,14 @@ impl BuildEnvironment {
             with_defaults_called: false,
             used_build_systems: HashSet::new(),
             fix_permissions_request: None,
+            package_filters: crate::recipe::model::Package::default(),
+            test_results: None,
+            qa_suppressions: Vec::new(),
+            runtime_deps_policy: crate::recipe::model::RuntimeDepsPolicy::default(),
+            applied_patches: Vec::new(),
             isolation_level: crate::environment::IsolationLevel::default(),
+            sandbox_profile: None,
+            sandbox_tag: super::sandbox::sandbox_tag(),
         })
     }
 
@@ -231,6 +251,61 @@ impl BuildEnvironment {
         }
     }
 
+    /// Set the recipe's packaging filters, applied when the package is created
+    pub fn set_package_filters(&mut self, filters: crate::recipe::model::Package) {
+        self.package_filters = filters;
+    }
+
+    /// Packaging filters and assertions from the recipe
+    #[must_use]
+    pub fn package_filters(&self) -> &crate::recipe::model::Package {
+        &self.package_filters
+    }
+
+    /// Record the outcome of the recipe's test stage
+    pub fn set_test_results(&mut self, results: sps2_types::TestResults) {
+        self.test_results = Some(results);
+    }
+
+    /// Outcome of the recipe's test stage, if it has one
+    #[must_use]
+    pub fn test_results(&self) -> Option<&sps2_types::TestResults> {
+        self.test_results.as_ref()
+    }
+
+    /// Set the QA findings the recipe accepts
+    pub fn set_qa_suppressions(&mut self, suppressions: Vec<crate::recipe::model::QaSuppression>) {
+        self.qa_suppressions = suppressions;
+    }
+
+    /// QA findings the recipe accepts
+    #[must_use]
+    pub fn qa_suppressions(&self) -> &[crate::recipe::model::QaSuppression] {
+        &self.qa_suppressions
+    }
+
+    /// Set the handling of undeclared runtime dependencies
+    pub fn set_runtime_deps_policy(&mut self, policy: crate::recipe::model::RuntimeDepsPolicy) {
+        self.runtime_deps_policy = policy;
+    }
+
+    /// Handling of undeclared runtime dependencies
+    #[must_use]
+    pub fn runtime_deps_policy(&self) -> crate::recipe::model::RuntimeDepsPolicy {
+        self.runtime_deps_policy
+    }
+
+    /// Record a source patch applied to the sources
+    pub fn record_applied_patch(&mut self, patch: sps2_types::AppliedPatch) {
+        self.applied_patches.push(patch);
+    }
+
+    /// Source patches applied so far, in order
+    #[must_use]
+    pub fn applied_patches(&self) -> &[sps2_types::AppliedPatch] {
+        &self.applied_patches
+    }
+
     /// Set isolation level from recipe
     pub fn set_isolation_level_from_recipe(&mut self, level: crate::environment::IsolationLevel) {
         self.isolation_level = level;
@@ -252,6 +327,17 @@ impl BuildEnvironment {
         build_root.join(name).join(version.to_string())
     }
 
+    /// Run at most `jobs` parallel compile jobs
+    pub(crate) fn limit_jobs(&mut self, jobs: usize) {
+        let jobs = jobs.max(1);
+        self.env_vars.insert("JOBS".to_string(), jobs.to_string());
+        // Hermetic environments leave MAKEFLAGS unset
+        if self.env_vars.contains_key("MAKEFLAGS") {
+            self.env_vars
+                .insert("MAKEFLAGS".to_string(), format!("-j{jobs}"));
+        }
+    }
+
     /// Get CPU count for parallel builds
     #[must_use]
     pub(crate) fn cpu_count() -> usize {
@@ -281,6 +367,16 @@ impl BuildEnvironment {
         // Update current isolation level
         self.isolation_level = level;
 
+        // Confine build commands at the levels that promise isolation
+        self.sandbox_profile = matches!(level, IsolationLevel::Enhanced | IsolationLevel::Hermetic)
+            .then(|| {
+                super::sandbox::sandbox_profile(
+                    &self.build_prefix,
+                    allow_network,
+                    &self.sandbox_tag,
+                )
+            });
+
         match level {
             IsolationLevel::None => {
                 // No isolation - warn the user
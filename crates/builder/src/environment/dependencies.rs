@@ -41,8 +41,11 @@ impl BuildEnvironment {
 
         let local_target_count = installed_packages.len();
 
-        // Include installed packages to check before repository resolution
-        resolution_context = resolution_context.with_installed_packages(installed_packages.clone());
+        // Include installed packages to check before repository resolution;
+        // build environments get only what the recipe asks for
+        resolution_context = resolution_context
+            .with_installed_packages(installed_packages.clone())
+            .with_recommends(false);
 
         let resolve_start = Instant::now();
         self.send_event(AppEvent::Lifecycle(LifecycleEvent::resolver_started(
@@ -57,6 +60,7 @@ impl BuildEnvironment {
                 self.send_event(AppEvent::Lifecycle(LifecycleEvent::resolver_failed(
                     FailureContext::from_error(&error),
                     Vec::new(),
+                    Vec::new(),
                 )));
                 return Err(error);
             }
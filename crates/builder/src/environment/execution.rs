@@ -1,11 +1,13 @@
 //! Command execution in isolated environment
 
-use super::{core::BuildEnvironment, types::BuildCommandResult};
+use super::{core::BuildEnvironment, types::BuildCommandResult, BUILD_UMASK};
 use sps2_errors::{BuildError, Error};
 use sps2_events::{AppEvent, BuildDiagnostic, BuildEvent, EventEmitter, LogStream};
-use sps2_platform::{PlatformContext, PlatformManager};
+use sps2_platform::process::PlatformCommand;
+use sps2_platform::{Platform, PlatformContext, PlatformManager};
 use std::collections::HashMap;
 use std::path::Path;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 impl BuildEnvironment {
@@ -19,6 +21,19 @@ impl BuildEnvironment {
         self.env_vars.clone()
     }
 
+    /// Command that runs `program` under the build umask
+    ///
+    /// The umask cannot be passed through the environment, so a shell sets
+    /// it and then replaces itself with `program`; arguments added to the
+    /// returned command go to `program` unchanged.
+    fn create_build_command(platform: &Platform, program: &str) -> PlatformCommand {
+        let mut cmd = platform.process().create_command("/bin/sh");
+        cmd.arg("-c")
+            .arg(format!("umask {BUILD_UMASK} && exec \"$@\""))
+            .args(["sh", program]);
+        cmd
+    }
+
     /// Execute a command in the build environment using the environment stored on the struct.
     ///
     /// # Errors
@@ -55,7 +70,10 @@ impl BuildEnvironment {
         let platform = PlatformManager::instance().platform();
         let context = PlatformContext::new(self.context.event_sender.clone());
 
-        let mut cmd = platform.process().create_command(program);
+        let mut cmd = Self::create_build_command(platform, program);
+        if let Some(profile) = &self.sandbox_profile {
+            cmd.sandbox_profile(profile);
+        }
 
         // Replace placeholders in command arguments
         let converted_args = Self::convert_args_to_strings(args);
@@ -82,6 +100,7 @@ impl BuildEnvironment {
             )]),
         );
 
+        let started = Instant::now();
         let output = platform
             .process()
             .execute_command(&context, cmd)
@@ -143,6 +162,11 @@ impl BuildEnvironment {
             stderr: stderr_text,
         };
 
+        if !result.success && self.sandbox_profile.is_some() {
+            self.check_sandbox_denials(platform, started.elapsed(), allow_failure)
+                .await?;
+        }
+
         if !result.success && !allow_failure {
             return Err(BuildError::CompileFailed {
                 message: format!(
@@ -161,6 +185,43 @@ impl BuildEnvironment {
         Ok(result)
     }
 
+    /// Report what the sandbox denied a failed command
+    ///
+    /// Only denials logged under this build's sandbox tag are considered.
+    /// Every denial is emitted as a warning. Unless failure is allowed, the
+    /// first one is returned as the error, as it usually caused the failure.
+    async fn check_sandbox_denials(
+        &self,
+        platform: &Platform,
+        window: Duration,
+        allow_failure: bool,
+    ) -> Result<(), Error> {
+        // The log is only a diagnostic aid; without it the plain failure stands
+        let Ok(denials) = platform
+            .process()
+            .sandbox_denials(window, &self.sandbox_tag)
+            .await
+        else {
+            return Ok(());
+        };
+        for denial in &denials {
+            self.emit_warning(format!(
+                "Sandbox denied {} {} {}",
+                denial.process, denial.operation, denial.target
+            ));
+        }
+
+        match denials.into_iter().next() {
+            Some(denial) if !allow_failure => Err(BuildError::SandboxDenied {
+                process: denial.process,
+                operation: denial.operation,
+                target: denial.target,
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
     /// Check if libtool --finish needs to be run based on command output
     fn check_libtool_finish_needed(result: &BuildCommandResult) -> Vec<String> {
         use std::collections::HashSet;
@@ -207,7 +268,7 @@ impl BuildEnvironment {
         let platform = PlatformManager::instance().platform();
         let context = PlatformContext::new(self.context.event_sender.clone());
 
-        let mut cmd = platform.process().create_command(&libtool_path);
+        let mut cmd = Self::create_build_command(platform, &libtool_path);
         cmd.args(["--finish", dir]);
         cmd.envs(&self.env_vars);
         cmd.current_dir(&self.build_prefix);
@@ -178,10 +178,8 @@ impl BuildEnvironment {
         self.env_vars.remove("DYLD_FALLBACK_LIBRARY_PATH"); // macOS specific
         self.env_vars.remove("PKG_CONFIG_PATH"); // Will be set when build deps are installed
 
-        // Set locale to ensure consistent behavior
-        self.env_vars
-            .insert("LANG".to_string(), "C.UTF-8".to_string());
-        self.env_vars.insert("LC_ALL".to_string(), "C".to_string());
+        // Pin locale, time zone and clock for consistent behavior
+        self.stamp_deterministic_environment();
     }
 
     /// Setup temporary home directory
@@ -346,6 +344,9 @@ impl BuildEnvironment {
                 "PKG_CONFIG_PATH",
                 "LANG",
                 "LC_ALL",
+                "SOURCE_DATE_EPOCH",
+                "TZ",
+                "ZERO_AR_DATE",
             ];
 
             if !config.allowed_env_vars.contains(key) && !build_vars.contains(&key.as_str()) {
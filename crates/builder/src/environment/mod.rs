@@ -10,9 +10,13 @@ mod directories;
 mod execution;
 mod hermetic;
 mod isolation;
+mod sandbox;
 mod types;
 mod variables;
 
 // Re-export public API
 pub use core::BuildEnvironment;
 pub use types::{BuildCommandResult, BuildResult, IsolationLevel};
+
+/// File mode creation mask every build command runs under
+pub(crate) const BUILD_UMASK: &str = "022";
@@ -0,0 +1,94 @@
+//! macOS sandbox profiles for build commands
+//!
+//! Enhanced and hermetic builds run every command under `sandbox-exec` with
+//! a profile generated for the build: writes are confined to the build
+//! directory, and network access is denied unless the recipe asks for it.
+//! Every deny rule carries a tag unique to the build as its message, so the
+//! denials the kernel logs for the build can be told apart from those of
+//! other sandboxed processes.
+
+use std::fmt::Write;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Paths outside the build directory that build tools must be able to write
+const WRITABLE_DEVICES: &[&str] = &["/dev/null", "/dev/zero", "/dev/tty", "/dev/dtracehelper"];
+
+/// Tag for the deny rules of a new build's profile
+pub(crate) fn sandbox_tag() -> String {
+    format!("sps2-build-{}", Uuid::new_v4().simple())
+}
+
+/// Generate the sandbox profile for a build rooted at `build_prefix`
+///
+/// The sandbox matches resolved paths, so `build_prefix` is canonicalized
+/// when it exists. Denials are logged with `tag` as their message.
+pub(crate) fn sandbox_profile(build_prefix: &Path, allow_network: bool, tag: &str) -> String {
+    let build_prefix = build_prefix
+        .canonicalize()
+        .unwrap_or_else(|_| build_prefix.to_path_buf());
+    let message = format!("(with message {})", quote(tag));
+
+    let mut profile = String::from("(version 1)\n(allow default)\n\n");
+    let _ = writeln!(profile, "(deny file-write* {message})");
+    profile.push_str("(allow file-write*\n");
+    let _ = writeln!(
+        profile,
+        "    (subpath {})",
+        quote(&build_prefix.to_string_lossy())
+    );
+    // Compiler and xcrun caches live in the per-user temporary folders
+    profile.push_str("    (subpath \"/private/var/folders\")\n");
+    for device in WRITABLE_DEVICES {
+        let _ = writeln!(profile, "    (literal {})", quote(device));
+    }
+    profile.push_str("    (regex #\"^/dev/fd/\"))\n");
+
+    if !allow_network {
+        // Local sockets and loopback stay usable, e.g. for test suites
+        let _ = write!(profile, "\n(deny network* {message})\n");
+        profile.push_str(
+            "(allow network*\n    (remote unix-socket)\n    (local ip \"localhost:*\")\n    (remote ip \"localhost:*\"))\n",
+        );
+    }
+
+    profile
+}
+
+/// Quote `value` as an SBPL string literal
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_are_confined_to_the_build_directory() {
+        let profile = sandbox_profile(Path::new("/opt/pm/build/foo/1.0.0"), false, "t");
+        assert!(profile.contains("(deny file-write* (with message \"t\"))"));
+        assert!(profile.contains("(subpath \"/opt/pm/build/foo/1.0.0\")"));
+        assert!(profile.contains("(literal \"/dev/null\")"));
+    }
+
+    #[test]
+    fn network_is_denied_unless_allowed() {
+        let build_prefix = Path::new("/opt/pm/build/foo/1.0.0");
+        assert!(sandbox_profile(build_prefix, false, "t")
+            .contains("(deny network* (with message \"t\"))"));
+        assert!(!sandbox_profile(build_prefix, true, "t").contains("network"));
+    }
+
+    #[test]
+    fn builds_get_their_own_tag() {
+        let tag = sandbox_tag();
+        assert!(tag.starts_with("sps2-build-"));
+        assert_ne!(tag, sandbox_tag());
+    }
+
+    #[test]
+    fn paths_are_quoted() {
+        assert_eq!(quote(r#"/tmp/a "b"\c"#), r#""/tmp/a \"b\"\\c""#);
+    }
+}
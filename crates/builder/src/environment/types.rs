@@ -29,6 +29,8 @@ pub struct BuildResult {
     pub build_log: String,
     /// Whether the recipe requested the package be installed after building
     pub install_requested: bool,
+    /// Outcome of the recipe's test stage, if it has one
+    pub tests: Option<sps2_types::TestResults>,
 }
 
 impl BuildResult {
@@ -40,6 +42,7 @@ impl BuildResult {
             sbom_files: Vec::new(),
             build_log: String::new(),
             install_requested: false,
+            tests: None,
         }
     }
 
@@ -59,6 +62,13 @@ impl BuildResult {
         self.install_requested = install_requested;
         self
     }
+
+    /// Set the outcome of the recipe's test stage
+    #[must_use]
+    pub fn with_tests(mut self, tests: Option<sps2_types::TestResults>) -> Self {
+        self.tests = tests;
+        self
+    }
 }
 
 /// Build isolation level
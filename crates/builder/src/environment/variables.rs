@@ -1,6 +1,7 @@
 //! Environment variable setup and isolation
 
 use super::core::BuildEnvironment;
+use sps2_types::Arch;
 use std::collections::HashMap;
 
 impl BuildEnvironment {
@@ -94,6 +95,30 @@ impl BuildEnvironment {
         // macOS specific settings - targeting Apple Silicon Macs (macOS 12.0+)
         self.env_vars
             .insert("MACOSX_DEPLOYMENT_TARGET".to_string(), "12.0".to_string());
+
+        self.stamp_deterministic_environment();
+    }
+
+    /// Pin the clock, locale and time zone the build sees
+    ///
+    /// Tools that embed a date read `SOURCE_DATE_EPOCH`, the same timestamp
+    /// the package archive uses, and `ZERO_AR_DATE` makes Apple's `ar` and
+    /// `ld` write zero timestamps. The values never change between builds, so
+    /// compiler cache hits are not lost to them. Commands also run under
+    /// [`BUILD_UMASK`](super::BUILD_UMASK).
+    pub(crate) fn stamp_deterministic_environment(&mut self) {
+        for (key, value) in [
+            (
+                "SOURCE_DATE_EPOCH",
+                crate::get_deterministic_timestamp().to_string(),
+            ),
+            ("LANG", "C.UTF-8".to_string()),
+            ("LC_ALL", "C".to_string()),
+            ("TZ", "UTC".to_string()),
+            ("ZERO_AR_DATE", "1".to_string()),
+        ] {
+            self.env_vars.insert(key.to_string(), value);
+        }
     }
 
     /// Setup a clean environment by removing potentially harmful variables
@@ -165,16 +190,22 @@ impl BuildEnvironment {
 
     /// Apply default compiler flags for optimization and security
     ///
-    /// This method sets recommended compiler flags for macOS ARM64 builds.
-    /// It preserves existing flags while adding optimizations.
-    /// Does NOT modify dependency paths - those are handled separately.
+    /// This method sets recommended compiler flags for the package's target
+    /// architecture on macOS. It preserves existing flags while adding
+    /// optimizations. Does NOT modify dependency paths - those are handled
+    /// separately.
     pub fn apply_default_compiler_flags(&mut self) {
         // Mark that with_defaults() was called
         self.with_defaults_called = true;
-        // Detect target architecture
-        let arch = std::env::consts::ARCH;
-        let is_arm64 = arch == "aarch64";
+        // Target architecture of the package being built
+        let target = self.context.arch.parse().unwrap_or_else(|_| Arch::host());
+        let is_arm64 = target == Arch::Arm64;
         let is_macos = cfg!(target_os = "macos");
+        let arch_flags: Vec<&str> = target
+            .slices()
+            .iter()
+            .flat_map(|slice| ["-arch", *slice])
+            .collect();
 
         // Base C/C++ optimization flags
         let mut base_cflags = vec![
@@ -183,14 +214,19 @@ impl BuildEnvironment {
             "-fstack-protector-strong", // Stack protection for security
         ];
 
+        // Compile every slice the target needs
+        if is_macos {
+            base_cflags.extend(&arch_flags);
+        }
+
         // Architecture-specific optimizations for Apple Silicon
         if is_arm64 && is_macos {
             // Use apple-m1 as a baseline for all Apple Silicon
             // This is compatible with M1, M2, M3, and newer
-            base_cflags.extend(&[
-                "-mcpu=apple-m1", // Target Apple Silicon baseline
-                "-mtune=native",  // Tune for the build machine
-            ]);
+            base_cflags.push("-mcpu=apple-m1"); // Target Apple Silicon baseline
+            if Arch::host() == Arch::Arm64 {
+                base_cflags.push("-mtune=native"); // Tune for the build machine
+            }
         }
 
         // Merge C flags with existing ones
@@ -199,10 +235,11 @@ impl BuildEnvironment {
 
         // Linker flags for macOS
         if is_macos {
-            let linker_flags = vec![
+            let mut linker_flags = vec![
                 "-Wl,-dead_strip",              // Remove unused code
                 "-headerpad_max_install_names", // Reserve space for install name changes
             ];
+            linker_flags.extend(&arch_flags);
             self.merge_compiler_flags("LDFLAGS", &linker_flags);
         }
 
@@ -229,7 +266,7 @@ impl BuildEnvironment {
         }
 
         // Go-specific optimizations
-        if is_arm64 && is_macos {
+        if is_macos {
             // CGO flags inherit from CFLAGS/LDFLAGS automatically
             // but we can set explicit Go flags
             self.env_vars
@@ -237,15 +274,17 @@ impl BuildEnvironment {
         }
 
         // Python-specific architecture flag
-        if is_arm64 && is_macos {
+        if is_macos {
             self.env_vars
-                .insert("ARCHFLAGS".to_string(), "-arch arm64".to_string());
+                .insert("ARCHFLAGS".to_string(), arch_flags.join(" "));
         }
 
         // CMake-specific variables (will be picked up by CMake build system)
-        if is_arm64 && is_macos {
-            self.env_vars
-                .insert("CMAKE_OSX_ARCHITECTURES".to_string(), "arm64".to_string());
+        if is_macos {
+            self.env_vars.insert(
+                "CMAKE_OSX_ARCHITECTURES".to_string(),
+                target.slices().join(";"),
+            );
         }
 
         // Note: CMAKE_INSTALL_NAME_DIR is now handled by the CMake build system
@@ -14,6 +14,7 @@ mod core;
 mod environment;
 mod packaging;
 mod recipe;
+mod remote;
 mod security;
 mod stages;
 mod utils;
@@ -26,8 +27,8 @@ pub use build_systems::{
     NodeJsBuildSystem, PythonBuildSystem, TestFailure, TestResults,
 };
 pub use cache::{
-    BuildCache, CacheStatistics, CompilerCache, CompilerCacheType, IncrementalBuildTracker,
-    SourceCache,
+    ArtifactCache, BuildCache, CacheKey, CacheStatistics, CompilerCache, CompilerCacheType,
+    IncrementalBuildTracker, SourceCache,
 };
 pub use config::BuildConfig;
 pub use core::api::BuilderApi;
@@ -57,6 +58,7 @@ pub use recipe::model::{
 pub use recipe::parser::parse_yaml_recipe;
 
 pub use core::context::BuildContext;
+pub use remote::RemoteBuilder;
 
 // Re-export build plan and security types for pack command
 pub use build_plan::BuildPlan;
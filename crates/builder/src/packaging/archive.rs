@@ -1,6 +1,7 @@
 //! Deterministic TAR archive creation for reproducible builds
 
-use sps2_errors::{BuildError, Error};
+use sps2_errors::{BuildError, Error, PackageError};
+use sps2_platform::filesystem_helpers::{file_flags, file_flags_to_string, SPECIAL_MODE_BITS};
 use std::path::{Path, PathBuf};
 use tokio::fs::File;
 
@@ -94,6 +95,10 @@ fn add_directory_to_tar_with_timestamp(
         } else {
             tar_path.join(&file_name)
         };
+        // Entry paths end up as text in files.json and the state database
+        let tar_entry_name = tar_entry_path
+            .to_str()
+            .ok_or_else(|| PackageError::non_utf8_path(&file_path))?;
 
         let metadata = entry.metadata()?;
 
@@ -112,7 +117,7 @@ fn add_directory_to_tar_with_timestamp(
             header.set_device_minor(0)?; // Clear device numbers
             header.set_cksum();
 
-            let tar_dir_path = format!("{}/", tar_entry_path.display());
+            let tar_dir_path = format!("{tar_entry_name}/");
             tar_builder.append_data(&mut header, &tar_dir_path, std::io::empty())?;
 
             // Recursively add directory contents
@@ -138,14 +143,19 @@ fn add_directory_to_tar_with_timestamp(
             header.set_device_minor(0)?; // Clear device numbers
             header.set_cksum();
 
-            tar_builder.append_data(
-                &mut header,
-                tar_entry_path.display().to_string(),
-                &mut file,
-            )?;
+            // BSD flags go in a pax header ahead of the entry, as bsdtar
+            // writes them
+            let flags = file_flags_to_string(file_flags(&metadata));
+            if !flags.is_empty() {
+                tar_builder.append_pax_extensions([("SCHILY.fflags", flags.as_bytes())])?;
+            }
+            tar_builder.append_data(&mut header, tar_entry_name, &mut file)?;
         } else if metadata.is_symlink() {
             // Handle symlinks deterministically
             let target = std::fs::read_link(&file_path)?;
+            if target.to_str().is_none() {
+                return Err(PackageError::non_utf8_path(&file_path).into());
+            }
             let mut header = tar::Header::new_gnu();
             header.set_entry_type(tar::EntryType::Symlink);
             header.set_size(0);
@@ -160,11 +170,7 @@ fn add_directory_to_tar_with_timestamp(
             header.set_device_minor(0)?; // Clear device numbers
             header.set_cksum();
 
-            tar_builder.append_data(
-                &mut header,
-                tar_entry_path.display().to_string(),
-                std::io::empty(),
-            )?;
+            tar_builder.append_data(&mut header, tar_entry_name, std::io::empty())?;
         }
         // Skip other special files (device nodes, fifos, etc.) for security and consistency
     }
@@ -192,11 +198,12 @@ fn normalize_file_permissions(metadata: &std::fs::Metadata) -> u32 {
     if metadata.is_dir() {
         0o755 // Directories: rwxr-xr-x
     } else if metadata.is_file() {
-        // Files: check if any execute bit is set
+        // Files: check if any execute bit is set; setuid/setgid are kept
+        let special = current_mode & SPECIAL_MODE_BITS;
         if current_mode & 0o111 != 0 {
-            0o755 // Executable files: rwxr-xr-x
+            0o755 | special // Executable files: rwxr-xr-x
         } else {
-            0o644 // Regular files: rw-r--r--
+            0o644 | special // Regular files: rw-r--r--
         }
     } else {
         0o644 // Default for other file types
@@ -0,0 +1,204 @@
+//! Recipe packaging filters
+//!
+//! Removes staged files the recipe excludes, or does not include, and checks
+//! that every required file is still there before the archive is created.
+
+use crate::recipe::model::Package;
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use sps2_errors::{BuildError, Error};
+use std::path::{Path, PathBuf};
+
+/// Apply `filters` to the files staged in `staging_dir`
+///
+/// Returns the removed files, relative to the install prefix.
+///
+/// # Errors
+///
+/// Returns an error if a glob is invalid, a file cannot be removed, or a
+/// required glob matches no packaged file.
+pub fn apply_package_filters(staging_dir: &Path, filters: &Package) -> Result<Vec<PathBuf>, Error> {
+    // Packages are laid out relative to the install prefix
+    let live_prefix = staging_dir.join("opt").join("pm").join("live");
+    let root = if live_prefix.is_dir() {
+        live_prefix
+    } else {
+        staging_dir.to_path_buf()
+    };
+
+    let include = glob_set(&filters.include)?;
+    let exclude = glob_set(&filters.exclude)?;
+    let require = filters
+        .require
+        .iter()
+        .map(|pattern| Ok((pattern, glob(pattern)?.compile_matcher())))
+        .collect::<Result<Vec<(&String, GlobMatcher)>, Error>>()?;
+
+    // Collect first so nothing is removed while the tree is being walked
+    let files: Vec<PathBuf> = WalkBuilder::new(&root)
+        .standard_filters(false)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_some_and(|kind| !kind.is_dir()))
+        .map(ignore::DirEntry::into_path)
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut removed = Vec::new();
+    for file in files {
+        let Ok(relative) = file.strip_prefix(&root) else {
+            continue;
+        };
+        let included =
+            filters.include.is_empty() || matches_path(|path| include.is_match(path), relative);
+        if included && !matches_path(|path| exclude.is_match(path), relative) {
+            kept.push(relative.to_path_buf());
+            continue;
+        }
+
+        std::fs::remove_file(&file)?;
+        remove_empty_parents(&root, &file);
+        removed.push(relative.to_path_buf());
+    }
+
+    let missing: Vec<&str> = require
+        .iter()
+        .filter(|(_, matcher)| {
+            !kept
+                .iter()
+                .any(|path| matches_path(|path| matcher.is_match(path), path))
+        })
+        .map(|(pattern, _)| pattern.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(BuildError::ValidationFailed {
+            message: format!("package is missing required files: {}", missing.join(", ")),
+        }
+        .into());
+    }
+
+    Ok(removed)
+}
+
+/// Whether `path` or one of its parent directories is a match
+pub(crate) fn matches_path(is_match: impl Fn(&Path) -> bool, path: &Path) -> bool {
+    path.ancestors()
+        .any(|path| !path.as_os_str().is_empty() && is_match(path))
+}
+
+/// Remove the directories above `file` that are left empty, up to `root`
+fn remove_empty_parents(root: &Path, file: &Path) {
+    for dir in file.ancestors().skip(1) {
+        if dir == root || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(glob(pattern)?);
+    }
+    builder.build().map_err(|e| invalid_glob(&e))
+}
+
+/// `*` stays within one path component; `**` crosses directories
+pub(crate) fn glob(pattern: &str) -> Result<Glob, Error> {
+    GlobBuilder::new(pattern.trim_matches('/'))
+        .literal_separator(true)
+        .build()
+        .map_err(|e| invalid_glob(&e))
+}
+
+fn invalid_glob(error: &globset::Error) -> Error {
+    BuildError::RecipeError {
+        message: format!("invalid package glob: {error}"),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let live = dir.path().join("opt/pm/live");
+        for file in files {
+            let path = live.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file).unwrap();
+        }
+        dir
+    }
+
+    fn filters(include: &[&str], exclude: &[&str], require: &[&str]) -> Package {
+        let strings = |globs: &[&str]| globs.iter().map(ToString::to_string).collect();
+        Package {
+            include: strings(include),
+            exclude: strings(exclude),
+            require: strings(require),
+        }
+    }
+
+    #[test]
+    fn excluded_files_and_directories_are_removed() {
+        let dir = stage(&[
+            "bin/foo",
+            "lib/libfoo.dylib",
+            "lib/libfoo.la",
+            "lib/libfoo.a",
+            "share/foo/tests/fixture.txt",
+        ]);
+        let mut removed = apply_package_filters(
+            dir.path(),
+            &filters(&[], &["lib/*.la", "**/*.a", "share/foo/tests"], &[]),
+        )
+        .unwrap();
+        removed.sort();
+
+        assert_eq!(
+            removed,
+            [
+                "lib/libfoo.a",
+                "lib/libfoo.la",
+                "share/foo/tests/fixture.txt"
+            ]
+            .map(PathBuf::from)
+        );
+        let live = dir.path().join("opt/pm/live");
+        assert!(live.join("bin/foo").exists());
+        assert!(live.join("lib/libfoo.dylib").exists());
+        assert!(!live.join("share").exists());
+    }
+
+    #[test]
+    fn only_included_files_are_kept() {
+        let dir = stage(&["bin/foo", "bin/foo-test", "lib/libfoo.dylib"]);
+        let removed =
+            apply_package_filters(dir.path(), &filters(&["bin", "lib"], &["bin/*-test"], &[]))
+                .unwrap();
+        assert_eq!(removed, [PathBuf::from("bin/foo-test")]);
+    }
+
+    #[test]
+    fn missing_required_files_fail_packaging() {
+        let dir = stage(&["bin/foo", "lib/libfoo.a"]);
+        let error = apply_package_filters(
+            dir.path(),
+            &filters(&[], &["**/*.a"], &["bin/foo", "lib/libfoo.*"]),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("lib/libfoo.*"));
+        assert!(!error.to_string().contains("bin/foo"));
+    }
+
+    #[test]
+    fn invalid_globs_are_recipe_errors() {
+        let dir = stage(&["bin/foo"]);
+        let error =
+            apply_package_filters(dir.path(), &filters(&[], &["lib/[*.a"], &[])).unwrap_err();
+        assert!(error.to_string().contains("invalid package glob"));
+    }
+}
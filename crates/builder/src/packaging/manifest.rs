@@ -39,7 +39,10 @@ pub fn create_manifest(
         dependencies: Dependencies {
             runtime: runtime_deps,
             build: Vec::new(), // Build deps not included in final manifest
+            recommends: recipe_metadata.recommends.clone(),
         },
+        patches: environment.applied_patches().to_vec(),
+        environment: recipe_metadata.environment.clone(),
         python: python_metadata,
     }
 }
@@ -3,7 +3,9 @@
 
 pub mod archive;
 pub mod compression;
+pub mod filters;
 pub mod manifest;
+pub mod runtime_deps;
 
 pub mod signing;
 
@@ -79,6 +81,9 @@ pub async fn create_package(
         manifest.python = Some(python_metadata);
     }
 
+    // Apply the recipe's packaging filters before anything is archived
+    apply_recipe_filters(context, environment)?;
+
     // Create package using the real manifest data
     let manifest_string = toml::to_string(&manifest).map_err(|e| BuildError::Failed {
         message: format!("failed to serialize manifest: {e}"),
@@ -97,6 +102,34 @@ pub async fn create_package(
     Ok(package_path)
 }
 
+/// Remove the staged files the recipe leaves out and check required ones
+fn apply_recipe_filters(
+    context: &BuildContext,
+    environment: &BuildEnvironment,
+) -> Result<(), Error> {
+    let filters = environment.package_filters();
+    if filters.is_empty() {
+        return Ok(());
+    }
+
+    let removed = filters::apply_package_filters(environment.staging_dir(), filters)?;
+    if !removed.is_empty() {
+        let files: Vec<String> = removed
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        send_event(
+            context,
+            AppEvent::General(GeneralEvent::debug(format!(
+                "Left {} file(s) out of the package: {}",
+                removed.len(),
+                files.join(", ")
+            ))),
+        );
+    }
+    Ok(())
+}
+
 /// Create a .sp package archive with manifest and tar+zstd compression
 ///
 /// # Errors
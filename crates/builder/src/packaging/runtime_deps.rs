@@ -0,0 +1,315 @@
+//! Runtime dependencies detected from the libraries staged binaries link
+//!
+//! Every Mach-O file in the staging directory is inspected for the dylibs it
+//! loads. Libraries that resolve into the live prefix but are not staged by
+//! the package itself are looked up in the active state, and the packages
+//! that install them are compared with the recipe's declared runtime
+//! dependencies. The recipe's `post.runtime_deps` policy decides whether
+//! undeclared ones are added, fail the build, or are ignored.
+
+use crate::artifact_qa::macho_utils;
+use crate::recipe::model::RuntimeDepsPolicy;
+use crate::utils::events::send_event;
+use crate::{BuildContext, BuildEnvironment};
+use sps2_config::fixed_paths;
+use sps2_errors::{BuildError, Error};
+use sps2_events::{AppEvent, GeneralEvent};
+use sps2_platform::PlatformManager;
+use sps2_state::{queries, StateManager};
+use sps2_types::package::PackageSpec;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// A staged file loading a library another package installs
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Link {
+    /// Staged file, relative to the live prefix
+    file: String,
+    /// Linked library, relative to the live prefix
+    library: String,
+}
+
+/// Check the staged binaries' linked libraries against the declared runtime
+/// dependencies and apply the recipe's policy
+///
+/// With [`RuntimeDepsPolicy::Add`] the missing packages are appended to
+/// `runtime_deps`; with [`RuntimeDepsPolicy::Fail`] they are reported as an
+/// error. Nothing is checked when `prefix` has no state to query.
+///
+/// # Errors
+///
+/// Returns [`BuildError::UndeclaredRuntimeDeps`] when the policy is `fail`
+/// and a staged binary links a library from an undeclared package.
+pub async fn apply_runtime_deps(
+    context: &BuildContext,
+    environment: &BuildEnvironment,
+    prefix: &Path,
+    runtime_deps: &mut Vec<String>,
+) -> Result<(), Error> {
+    let policy = environment.runtime_deps_policy();
+    if policy == RuntimeDepsPolicy::Off {
+        return Ok(());
+    }
+
+    let live_root = environment
+        .staging_dir()
+        .join(fixed_paths::LIVE_DIR.trim_start_matches('/'));
+    let external = external_libraries(&live_root).await;
+    if external.is_empty() {
+        return Ok(());
+    }
+
+    let Ok(state) = StateManager::new(prefix).await else {
+        send_event(
+            context,
+            AppEvent::General(GeneralEvent::debug(
+                "No installed state to check linked libraries against".to_string(),
+            )),
+        );
+        return Ok(());
+    };
+    let providers = library_providers(&state, &context.name, external).await?;
+
+    let declared = declared_names(runtime_deps);
+    let missing: BTreeMap<String, Vec<Link>> = providers
+        .into_iter()
+        .filter(|(package, _)| !declared.contains(package))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let report = format_report(&missing);
+    match policy {
+        RuntimeDepsPolicy::Fail => Err(BuildError::UndeclaredRuntimeDeps {
+            package: context.name.clone(),
+            report,
+        }
+        .into()),
+        RuntimeDepsPolicy::Add => {
+            send_event(
+                context,
+                AppEvent::General(GeneralEvent::warning(format!(
+                    "Adding undeclared runtime dependencies of {}:\n{report}",
+                    context.name
+                ))),
+            );
+            runtime_deps.extend(missing.into_keys());
+            Ok(())
+        }
+        RuntimeDepsPolicy::Off => Ok(()),
+    }
+}
+
+/// Libraries the staged Mach-O files load from the live prefix that the
+/// package does not stage itself
+///
+/// Each entry carries the candidate paths the install name may resolve to,
+/// in search order.
+async fn external_libraries(live_root: &Path) -> Vec<(String, Vec<String>)> {
+    if !live_root.is_dir() {
+        return Vec::new();
+    }
+
+    let mut staged = HashSet::new();
+    let mut binaries = Vec::new();
+    for entry in ignore::WalkBuilder::new(live_root)
+        .hidden(false)
+        .parents(false)
+        .git_ignore(false)
+        .build()
+        .filter_map(Result::ok)
+    {
+        let path = entry.into_path();
+        let Some(rel) = path
+            .strip_prefix(live_root)
+            .ok()
+            .and_then(|rel| rel.to_str())
+            .map(str::to_string)
+        else {
+            continue;
+        };
+        if path.is_file() && macho_utils::is_macho_file(&path) {
+            binaries.push((path, rel.clone()));
+        }
+        staged.insert(rel);
+    }
+
+    let platform = PlatformManager::instance().platform();
+    let ctx = platform.create_context(None);
+    let mut external = Vec::new();
+    for (path, rel) in binaries {
+        let Ok(libraries) = platform.binary().get_dependencies(&ctx, &path).await else {
+            continue;
+        };
+        let rpaths = platform
+            .binary()
+            .get_rpath_entries(&ctx, &path)
+            .await
+            .unwrap_or_default();
+        let loader_dir = Path::new(&rel).parent().unwrap_or(Path::new(""));
+
+        for library in libraries {
+            let candidates = resolve_install_name(&library, &rpaths, loader_dir);
+            if candidates.is_empty() || candidates.iter().any(|c| staged.contains(c)) {
+                continue;
+            }
+            external.push((rel.clone(), candidates));
+        }
+    }
+    external
+}
+
+/// Packages in the active state installing the given libraries, excluding
+/// the package being built
+async fn library_providers(
+    state: &StateManager,
+    package: &str,
+    external: Vec<(String, Vec<String>)>,
+) -> Result<BTreeMap<String, Vec<Link>>, Error> {
+    let state_id = state.get_active_state().await?;
+    let mut tx = state.begin_transaction().await?;
+    let mut providers: BTreeMap<String, Vec<Link>> = BTreeMap::new();
+    for (file, candidates) in external {
+        for library in candidates {
+            let owners = queries::get_path_providers(&mut tx, &state_id, &library).await?;
+            if owners.is_empty() {
+                continue;
+            }
+            for owner in owners.into_iter().filter(|owner| owner.package != package) {
+                let link = Link {
+                    file: file.clone(),
+                    library: library.clone(),
+                };
+                let links = providers.entry(owner.package).or_default();
+                if !links.contains(&link) {
+                    links.push(link);
+                }
+            }
+            break;
+        }
+    }
+    tx.commit().await?;
+    Ok(providers)
+}
+
+/// Paths relative to the live prefix an install name may resolve to
+///
+/// `@rpath/` names are tried against each rpath in turn and `@loader_path`
+/// is taken relative to `loader_dir`. System libraries and names that
+/// depend on the executable loading them resolve to nothing.
+fn resolve_install_name(install_name: &str, rpaths: &[String], loader_dir: &Path) -> Vec<String> {
+    if let Some(rest) = install_name.strip_prefix("@rpath/") {
+        rpaths
+            .iter()
+            .filter_map(|rpath| live_relative(rpath, loader_dir))
+            .filter_map(|dir| normalize(&dir.join(rest)))
+            .collect()
+    } else {
+        live_relative(install_name, loader_dir)
+            .and_then(|path| normalize(&path))
+            .into_iter()
+            .collect()
+    }
+}
+
+/// `path` relative to the live prefix, if it points into it
+fn live_relative(path: &str, loader_dir: &Path) -> Option<PathBuf> {
+    if let Some(rest) = path.strip_prefix("@loader_path") {
+        Some(loader_dir.join(rest.trim_start_matches('/')))
+    } else {
+        path.strip_prefix(fixed_paths::LIVE_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .map(PathBuf::from)
+    }
+}
+
+/// Collapse `.` and `..` in a relative path, refusing to leave its root
+fn normalize(path: &Path) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| parts.join("/"))
+}
+
+/// Package names of the declared runtime dependencies
+fn declared_names(runtime_deps: &[String]) -> BTreeSet<String> {
+    runtime_deps
+        .iter()
+        .map(|dep| {
+            PackageSpec::parse(dep).map_or_else(|_| dep.trim().to_string(), |spec| spec.name)
+        })
+        .collect()
+}
+
+/// One line per staged file and the library it loads, grouped by package
+fn format_report(missing: &BTreeMap<String, Vec<Link>>) -> String {
+    missing
+        .iter()
+        .flat_map(|(package, links)| {
+            links
+                .iter()
+                .map(move |link| format!("  {} links {} from {package}", link.file, link.library))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_install_names_into_the_live_prefix() {
+        let rpaths = vec![
+            "@loader_path/../lib".to_string(),
+            "/opt/pm/live/lib/extra".to_string(),
+            "/usr/lib".to_string(),
+        ];
+        let bin = Path::new("bin");
+
+        assert_eq!(
+            resolve_install_name("/opt/pm/live/lib/libz.1.dylib", &rpaths, bin),
+            vec!["lib/libz.1.dylib"]
+        );
+        assert_eq!(
+            resolve_install_name("@rpath/libssl.3.dylib", &rpaths, bin),
+            vec!["lib/libssl.3.dylib", "lib/extra/libssl.3.dylib"]
+        );
+        assert!(resolve_install_name("/usr/lib/libSystem.B.dylib", &rpaths, bin).is_empty());
+        assert!(resolve_install_name("@executable_path/libfoo.dylib", &rpaths, bin).is_empty());
+        assert!(resolve_install_name("@loader_path/../../etc/x.dylib", &rpaths, bin).is_empty());
+    }
+
+    #[test]
+    fn declared_names_ignore_version_constraints() {
+        let declared = declared_names(&["openssl>=3.0.0".to_string(), "zlib".to_string()]);
+        assert!(declared.contains("openssl"));
+        assert!(declared.contains("zlib"));
+        assert!(!declared.contains("openssl>=3.0.0"));
+    }
+
+    #[test]
+    fn report_lists_each_link_with_its_package() {
+        let mut missing = BTreeMap::new();
+        missing.insert(
+            "zlib".to_string(),
+            vec![Link {
+                file: "bin/curl".to_string(),
+                library: "lib/libz.1.dylib".to_string(),
+            }],
+        );
+        assert_eq!(
+            format_report(&missing),
+            "  bin/curl links lib/libz.1.dylib from zlib"
+        );
+    }
+}
@@ -5,7 +5,8 @@
 
 use crate::environment::IsolationLevel;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sps2_types::Arch;
+use std::collections::{BTreeMap, HashMap};
 
 /// Complete YAML recipe structure
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,10 +28,18 @@ pub struct YamlRecipe {
     /// Build stage (required)
     pub build: Build,
 
+    /// Test stage, run after the build (optional)
+    #[serde(default)]
+    pub test: Test,
+
     /// Post-processing stage (optional)
     #[serde(default)]
     pub post: Post,
 
+    /// Packaging filters and assertions (optional)
+    #[serde(default)]
+    pub package: Package,
+
     /// Installation behavior (optional)
     #[serde(default)]
     pub install: Install,
@@ -59,6 +68,10 @@ pub struct Dependencies {
 
     #[serde(default)]
     pub build: Vec<String>,
+
+    /// Packages installed alongside by default, but not needed to run
+    #[serde(default)]
+    pub recommends: Vec<String>,
 }
 
 /// Environment setup stage
@@ -79,6 +92,32 @@ pub struct Environment {
     /// Environment variables
     #[serde(default)]
     pub variables: HashMap<String, String>,
+
+    /// Resource limits, applied on top of the builder config's
+    #[serde(default)]
+    pub limits: Limits,
+
+    /// Architecture to build for; defaults to the build machine's
+    #[serde(default)]
+    pub arch: Option<Arch>,
+}
+
+/// Resource limits for a build
+///
+/// Where the builder config sets the same limit, the lower one applies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Wall-clock time the build stage may take
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+
+    /// Memory the build's processes may use together, in MiB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+
+    /// Maximum number of parallel compile jobs
+    #[serde(default)]
+    pub jobs: Option<usize>,
 }
 
 fn default_isolation() -> IsolationLevel {
@@ -92,6 +131,8 @@ impl Default for Environment {
             defaults: false,
             network: false,
             variables: HashMap::new(),
+            limits: Limits::default(),
+            arch: None,
         }
     }
 }
@@ -109,12 +150,72 @@ pub struct Source {
 
     /// Patches to apply after extraction
     #[serde(default)]
-    pub patches: Vec<String>,
+    pub patches: Vec<Patch>,
+}
+
+/// A patch applied to the sources before the build
+///
+/// File paths are looked up next to the recipe, then in the source tree. A
+/// plain string names a patch file applied with `-p1`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Patch {
+    /// Patch file applied with the default strip level
+    Path(String),
+    /// Patch file
+    File {
+        file: String,
+        /// Leading path components to strip (`patch -p`)
+        #[serde(default = "default_patch_strip")]
+        strip: u32,
+        #[serde(default)]
+        checksum: Option<Checksum>,
+    },
+    /// Patch downloaded from a URL; it must have a checksum
+    Fetch {
+        url: String,
+        #[serde(default)]
+        checksum: Option<Checksum>,
+        /// Leading path components to strip (`patch -p`)
+        #[serde(default = "default_patch_strip")]
+        strip: u32,
+    },
+    /// Patch written out in the recipe
+    Inline {
+        inline: String,
+        /// File name the patch is recorded under
+        #[serde(default)]
+        name: Option<String>,
+        /// Leading path components to strip (`patch -p`)
+        #[serde(default = "default_patch_strip")]
+        strip: u32,
+    },
+}
+
+impl Patch {
+    /// Leading path components to strip (`patch -p`)
+    #[must_use]
+    pub fn strip(&self) -> u32 {
+        match self {
+            Self::Path(_) => default_patch_strip(),
+            Self::File { strip, .. } | Self::Fetch { strip, .. } | Self::Inline { strip, .. } => {
+                *strip
+            }
+        }
+    }
+}
+
+fn default_patch_strip() -> u32 {
+    1
 }
 
 /// Named source with optional extract location
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamedSource {
+    /// Name shown in build output; also the default extract location
+    #[serde(default)]
+    pub name: Option<String>,
+
     /// Source method
     #[serde(flatten)]
     pub method: SourceMethod,
@@ -137,8 +238,22 @@ pub enum SourceMethod {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitSource {
     pub url: String,
+    /// Branch, tag, full commit hash or `HEAD`
     #[serde(rename = "ref")]
     pub git_ref: String,
+    /// Commit the checkout must resolve to (full or abbreviated hash)
+    #[serde(default)]
+    pub commit: Option<String>,
+    /// Initialize submodules recursively
+    #[serde(default)]
+    pub submodules: bool,
+    /// History depth to clone, `0` for the full history
+    #[serde(default = "default_git_depth")]
+    pub depth: u32,
+}
+
+fn default_git_depth() -> u32 {
+    1
 }
 
 /// Fetch source specification
@@ -187,8 +302,32 @@ pub enum Build {
     Steps { steps: Vec<ParsedStep> },
 }
 
+/// Test stage
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Test {
+    /// Run this build system's test suite, e.g. `cargo test` or `make check`
+    #[serde(default)]
+    pub system: Option<BuildSystem>,
+
+    /// Test commands, in the same forms as build steps
+    #[serde(default)]
+    pub steps: Vec<ParsedStep>,
+
+    /// What failing tests do to the build (defaults to the builder config)
+    #[serde(default)]
+    pub on_failure: Option<sps2_types::TestFailurePolicy>,
+}
+
+impl Test {
+    /// Whether the recipe has any tests to run
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.system.is_none() && self.steps.is_empty()
+    }
+}
+
 /// Supported build systems
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BuildSystem {
     Autotools,
@@ -201,6 +340,63 @@ pub enum BuildSystem {
     Nodejs,
 }
 
+impl<'de> serde::Deserialize<'de> for BuildSystem {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct BuildSystemVisitor;
+
+        impl serde::de::Visitor<'_> for BuildSystemVisitor {
+            type Value = BuildSystem;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(
+                    "a build system (autotools, cmake, meson, cargo, make, go, python, or nodejs)",
+                )
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match value.trim().to_ascii_lowercase().as_str() {
+                    "autotools" => Ok(BuildSystem::Autotools),
+                    "cmake" => Ok(BuildSystem::Cmake),
+                    "meson" => Ok(BuildSystem::Meson),
+                    "cargo" => Ok(BuildSystem::Cargo),
+                    "make" => Ok(BuildSystem::Make),
+                    "go" => Ok(BuildSystem::Go),
+                    "python" => Ok(BuildSystem::Python),
+                    "nodejs" => Ok(BuildSystem::Nodejs),
+                    other => Err(serde::de::Error::unknown_variant(
+                        other,
+                        &[
+                            "autotools",
+                            "cmake",
+                            "meson",
+                            "cargo",
+                            "make",
+                            "go",
+                            "python",
+                            "nodejs",
+                        ],
+                    )),
+                }
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_str(&value)
+            }
+        }
+
+        deserializer.deserialize_any(BuildSystemVisitor)
+    }
+}
+
 /// Parsed build step from YAML recipe
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -237,6 +433,46 @@ pub struct Post {
     /// Custom post-processing commands
     #[serde(default)]
     pub commands: Vec<PostCommand>,
+
+    /// QA findings accepted for this package
+    #[serde(default)]
+    pub qa_suppress: Vec<QaSuppression>,
+
+    /// What to do about runtime dependencies the binaries link against but
+    /// the recipe does not declare
+    #[serde(default)]
+    pub runtime_deps: RuntimeDepsPolicy,
+}
+
+/// Handling of runtime dependencies detected from linked libraries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeDepsPolicy {
+    /// Add undeclared dependencies to the manifest
+    #[default]
+    Add,
+    /// Fail the build, listing the undeclared dependencies
+    Fail,
+    /// Trust the declared dependencies
+    Off,
+}
+
+/// A QA rule accepted for some or all of a package's files
+///
+/// `files` globs are matched against paths relative to the install prefix,
+/// like the packaging globs. An empty list suppresses the rule everywhere.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaSuppression {
+    /// Rule id as shown in the QA summary, such as `hardcoded-build-path`
+    pub rule: String,
+
+    /// Files the suppression applies to
+    #[serde(default)]
+    pub files: Vec<String>,
+
+    /// Why the finding is acceptable for this package
+    #[serde(default)]
+    pub justification: String,
 }
 
 /// Post-processing command
@@ -317,12 +553,46 @@ impl<'de> serde::Deserialize<'de> for RpathPatchOption {
     }
 }
 
+/// Packaging stage
+///
+/// Globs are matched against paths relative to the install prefix, such as
+/// `bin/foo` or `lib/*.a`. A glob that matches a directory applies to
+/// everything below it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Package {
+    /// Only package files matching one of these globs (all files if empty)
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// Leave out files matching any of these globs, even if included
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// Globs that must each match at least one packaged file
+    #[serde(default)]
+    pub require: Vec<String>,
+}
+
+impl Package {
+    /// Whether the recipe asks for any filtering or assertions
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.require.is_empty()
+    }
+}
+
 /// Installation behavior
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Install {
     /// Auto-install after building
     #[serde(default)]
     pub auto: bool,
+
+    /// Environment variables users of the package need, exported through
+    /// the live prefix's profile snippets; `${PREFIX}` is left for the
+    /// installer to expand
+    #[serde(default)]
+    pub environment: BTreeMap<String, String>,
 }
 
 #[cfg(test)]
@@ -352,6 +622,197 @@ build:
         assert_eq!(recipe.metadata.version, "1.3.1");
     }
 
+    #[test]
+    fn test_parse_test_stage_with_build_system() {
+        let yaml = r"
+metadata:
+  name: ripgrep
+  version: 14.1.1
+  description: Recursively search directories
+  license: MIT
+
+source:
+  local:
+    path: ./src
+
+build:
+  system: cargo
+
+test:
+  system: cargo
+";
+        let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
+        assert!(matches!(recipe.test.system, Some(BuildSystem::Cargo)));
+        assert!(recipe.test.steps.is_empty());
+        assert_eq!(recipe.test.on_failure, None);
+    }
+
+    #[test]
+    fn test_parse_patches() {
+        let yaml = r"
+metadata:
+  name: zlib
+  version: 1.3.1
+  description: General-purpose lossless data compression library
+  license: Zlib
+
+source:
+  local:
+    path: ./src
+  patches:
+    - fix-configure.patch
+    - file: patches/no-rpath.patch
+      strip: 0
+    - url: https://example.com/fixes/cve.patch
+      checksum:
+        sha256: 0123456789abcdef
+    - name: typo.patch
+      inline: |
+        --- a/README
+        +++ b/README
+        @@ -1 +1 @@
+        -teh
+        +the
+
+build:
+  system: autotools
+";
+        let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
+        let patches = &recipe.source.patches;
+        assert!(matches!(&patches[0], Patch::Path(path) if path == "fix-configure.patch"));
+        assert!(matches!(&patches[1], Patch::File { strip: 0, .. }));
+        assert!(matches!(
+            &patches[2],
+            Patch::Fetch {
+                checksum: Some(_),
+                strip: 1,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&patches[3], Patch::Inline { inline, name: Some(name), .. }
+                if name == "typo.patch" && inline.starts_with("--- a/README"))
+        );
+    }
+
+    #[test]
+    fn test_parse_git_source() {
+        let yaml = r"
+metadata:
+  name: neovim
+  version: 0.11.0
+  description: Vim-fork focused on extensibility and usability
+  license: Apache-2.0
+
+source:
+  sources:
+    - git:
+        url: https://github.com/neovim/neovim.git
+        ref: v0.11.0
+        commit: 8b70a2c
+        submodules: true
+        depth: 0
+    - git:
+        url: https://github.com/neovim/deps.git
+        ref: main
+
+build:
+  system: cmake
+";
+        let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
+        let gits: Vec<&GitSource> = recipe
+            .source
+            .sources
+            .iter()
+            .filter_map(|source| match &source.method {
+                SourceMethod::Git { git } => Some(git),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(gits.len(), 2);
+        assert_eq!(gits[0].commit.as_deref(), Some("8b70a2c"));
+        assert!(gits[0].submodules);
+        assert_eq!(gits[0].depth, 0);
+        assert_eq!(gits[1].commit, None);
+        assert!(!gits[1].submodules);
+        assert_eq!(gits[1].depth, 1);
+    }
+
+    #[test]
+    fn test_parse_named_sources() {
+        let yaml = r"
+metadata:
+  name: gcc
+  version: 15.1.0
+  description: GNU Compiler Collection
+  license: GPL-3.0-or-later
+
+source:
+  sources:
+    - fetch:
+        url: https://ftp.gnu.org/gnu/gcc/gcc-15.1.0/gcc-15.1.0.tar.xz
+      extract_to: src
+    - name: gmp
+      fetch:
+        url: https://ftp.gnu.org/gnu/gmp/gmp-6.3.0.tar.xz
+        checksum:
+          sha256: abc123
+    - name: mpc
+      git:
+        url: https://gitlab.inria.fr/mpc/mpc.git
+        ref: 1.3.1
+
+build:
+  system: autotools
+";
+        let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
+        let sources = &recipe.source.sources;
+        assert_eq!(sources.len(), 3);
+        assert_eq!(sources[0].name, None);
+        assert_eq!(sources[0].extract_to.as_deref(), Some("src"));
+        assert_eq!(sources[1].name.as_deref(), Some("gmp"));
+        assert_eq!(sources[1].extract_to, None);
+        assert!(matches!(
+            &sources[1].method,
+            SourceMethod::Fetch { fetch } if fetch.checksum.is_some()
+        ));
+        assert_eq!(sources[2].name.as_deref(), Some("mpc"));
+        assert!(matches!(&sources[2].method, SourceMethod::Git { .. }));
+    }
+
+    #[test]
+    fn test_parse_qa_suppressions() {
+        let yaml = r"
+metadata:
+  name: python
+  version: 3.12.0
+  description: Python interpreter
+  license: PSF-2.0
+
+source:
+  local:
+    path: ./src
+
+build:
+  system: autotools
+
+post:
+  qa_suppress:
+    - rule: hardcoded-build-path
+      files:
+        - lib/python3.12/config-*/Makefile
+      justification: Kept for sysconfig, which only reads it at build time
+    - rule: reproducibility
+      justification: Upstream embeds the build date
+";
+        let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
+        let suppressions = &recipe.post.qa_suppress;
+        assert_eq!(suppressions.len(), 2);
+        assert_eq!(suppressions[0].rule, "hardcoded-build-path");
+        assert_eq!(suppressions[0].files, ["lib/python3.12/config-*/Makefile"]);
+        assert!(suppressions[1].files.is_empty());
+    }
+
     #[test]
     fn test_parse_complex_recipe() {
         let yaml = r#"
@@ -373,6 +834,9 @@ environment:
   defaults: true
   variables:
     LDFLAGS: "-L${PREFIX}/lib"
+  limits:
+    memory_mb: 8192
+    jobs: 4
 
 source:
   local:
@@ -385,8 +849,20 @@ build:
     - command: mkdir -p build
     - command: cd build && ../configure --build=${build_triple}
 
+test:
+  steps:
+    - shell: cd build && make check
+  on_failure: warn
+
 post:
   fix_permissions: true
+
+package:
+  exclude:
+    - lib/*.la
+    - share/doc
+  require:
+    - bin/gcc
 "#;
         let recipe: YamlRecipe = serde_yaml2::from_str(yaml).unwrap();
         assert_eq!(recipe.metadata.name, "gcc");
@@ -394,5 +870,22 @@ post:
             recipe.facts.get("build_triple").unwrap(),
             "aarch64-apple-darwin24"
         );
+        assert_eq!(
+            recipe.environment.limits,
+            Limits {
+                timeout_seconds: None,
+                memory_mb: Some(8192),
+                jobs: Some(4),
+            }
+        );
+        assert_eq!(recipe.package.exclude, ["lib/*.la", "share/doc"]);
+        assert_eq!(recipe.package.require, ["bin/gcc"]);
+        assert!(recipe.package.include.is_empty());
+        assert!(recipe.test.system.is_none());
+        assert_eq!(recipe.test.steps.len(), 1);
+        assert_eq!(
+            recipe.test.on_failure,
+            Some(sps2_types::TestFailurePolicy::Warn)
+        );
     }
 }
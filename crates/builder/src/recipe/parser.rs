@@ -50,12 +50,7 @@ pub fn parse_yaml_recipe_from_string(content: &str) -> Result<YamlRecipe, Error>
 /// Validate a parsed recipe
 fn validate_recipe(recipe: &YamlRecipe) -> Result<(), Error> {
     // Validate metadata
-    if recipe.metadata.name.is_empty() {
-        return Err(BuildError::RecipeError {
-            message: "metadata.name cannot be empty".to_string(),
-        }
-        .into());
-    }
+    sps2_types::name::validate_package_name(&recipe.metadata.name)?;
 
     if recipe.metadata.version.is_empty() {
         return Err(BuildError::RecipeError {
@@ -80,6 +75,30 @@ fn validate_recipe(recipe: &YamlRecipe) -> Result<(), Error> {
         }
     }
 
+    if let Some(name) = recipe
+        .install
+        .environment
+        .keys()
+        .find(|name| !sps2_types::manifest::is_valid_environment_name(name))
+    {
+        return Err(BuildError::RecipeError {
+            message: format!("install.environment cannot set '{name}'"),
+        }
+        .into());
+    }
+
+    for suppression in &recipe.post.qa_suppress {
+        if suppression.justification.trim().is_empty() {
+            return Err(BuildError::RecipeError {
+                message: format!(
+                    "post.qa_suppress for rule '{}' needs a justification",
+                    suppression.rule
+                ),
+            }
+            .into());
+        }
+    }
+
     Ok(())
 }
 
@@ -102,6 +121,14 @@ fn expand_variables(recipe: &mut YamlRecipe) {
         context.insert(key.clone(), value.clone());
     }
 
+    // Package environment values keep ${PREFIX}, which the installer
+    // expands for the live prefix they end up in
+    let mut package_context = context.clone();
+    package_context.remove("PREFIX");
+    for value in recipe.install.environment.values_mut() {
+        *value = expand_string(value, &package_context);
+    }
+
     // Add environment variables (they can reference facts)
     let mut env_vars = recipe.environment.variables.clone();
     for value in env_vars.values_mut() {
@@ -128,6 +155,11 @@ fn expand_variables(recipe: &mut YamlRecipe) {
         }
     }
 
+    // Expand variables in test steps
+    for step in &mut recipe.test.steps {
+        expand_build_step(step, &context);
+    }
+
     // Expand variables in post commands
     for cmd in &mut recipe.post.commands {
         match cmd {
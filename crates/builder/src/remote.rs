@@ -0,0 +1,354 @@
+//! Remote build execution
+//!
+//! Runs a build on a dedicated macOS ARM64 worker over SSH. Only the
+//! recipe's directory is shipped to the worker, as a tar stream, so local
+//! sources and patch files must lie inside it; recipes referring to files
+//! elsewhere are refused before anything is sent. The worker runs
+//! `sps2 build --worker`, which writes every event as a JSON line; those are
+//! replayed into the local event channel, and the finished package is
+//! copied back.
+
+use crate::recipe::model::{Patch, SourceMethod, YamlRecipe};
+use crate::{parse_yaml_recipe, BuildContext, BuildResult};
+use sps2_config::builder::RemoteSettings;
+use sps2_errors::{BuildError, Error};
+use sps2_events::{AppEvent, BuildEvent, EventEmitter, EventMessage};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// Builds recipes on a remote worker
+#[derive(Clone, Debug)]
+pub struct RemoteBuilder {
+    host: String,
+    sps2_path: String,
+    work_dir: PathBuf,
+    allow_network: bool,
+}
+
+impl RemoteBuilder {
+    /// Remote builder for the configured worker, if one is configured
+    #[must_use]
+    pub fn from_settings(settings: &RemoteSettings) -> Option<Self> {
+        let host = settings.host.clone().filter(|host| !host.is_empty())?;
+        Some(Self {
+            host,
+            sps2_path: settings.sps2_path.clone(),
+            work_dir: settings.work_dir.clone(),
+            allow_network: false,
+        })
+    }
+
+    /// Allow network access during the build
+    #[must_use]
+    pub fn with_network(mut self, allow_network: bool) -> Self {
+        self.allow_network = allow_network;
+        self
+    }
+
+    /// SSH destination of the worker
+    #[must_use]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Build `context`'s recipe on the worker
+    ///
+    /// The package is written to the context's output directory. The worker
+    /// never installs it; the result reports whether the recipe asked for
+    /// that, so the caller can install it locally.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recipe cannot be read or refers to local files
+    /// outside its directory, the worker cannot be reached, the build fails
+    /// on the worker, or the package cannot be copied back.
+    pub async fn build(&self, context: &BuildContext) -> Result<BuildResult, Error> {
+        let recipe = parse_yaml_recipe(&context.recipe_path).await?;
+        if let Some(path) = file_outside_recipe_dir(&recipe) {
+            return Err(BuildError::RecipeError {
+                message: format!(
+                    "{path} is outside the recipe directory and would not reach the worker"
+                ),
+            }
+            .into());
+        }
+        let (Some(recipe_dir), Some(recipe_file)) = (
+            context.recipe_path.parent(),
+            context.recipe_path.file_name(),
+        ) else {
+            return Err(BuildError::RecipeError {
+                message: format!("invalid recipe path {}", context.recipe_path.display()),
+            }
+            .into());
+        };
+
+        let remote_dir = self
+            .work_dir
+            .join(uuid::Uuid::new_v4().simple().to_string());
+        let result = async {
+            self.ship(recipe_dir, &remote_dir).await?;
+            let remote_recipe = remote_dir.join("src").join(recipe_file);
+            let remote_package = self.run(context, &remote_recipe, &remote_dir).await?;
+            self.fetch(&remote_package, &context.output_dir).await
+        }
+        .await;
+
+        // Best effort: a leftover directory only costs space on the worker
+        let cleanup = format!("rm -rf {}", shell_quote(&remote_dir.to_string_lossy()));
+        if let Err(error) = self.ssh(&cleanup).output().await {
+            context.emit_warning(format!(
+                "failed to clean up {} on {}: {error}",
+                remote_dir.display(),
+                self.host
+            ));
+        }
+
+        Ok(BuildResult::new(result?).with_install_requested(recipe.install.auto))
+    }
+
+    /// Copy the recipe directory to `remote_dir/src` on the worker
+    async fn ship(&self, recipe_dir: &Path, remote_dir: &Path) -> Result<(), Error> {
+        let mut archive = Command::new("tar")
+            .arg("-C")
+            .arg(recipe_dir)
+            .args(["-cf", "-", "."])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let Some(stdout) = archive.stdout.take() else {
+            return Err(self.failed("could not read the recipe archive"));
+        };
+
+        let src = shell_quote(&remote_dir.join("src").to_string_lossy());
+        let out = shell_quote(&remote_dir.join("out").to_string_lossy());
+        let unpack = format!("mkdir -p {src} {out} && tar -C {src} -xf -");
+        let archive_stream: Stdio = stdout.try_into()?;
+        let unpacked = self.ssh(&unpack).stdin(archive_stream).status().await?;
+        let archived = archive.wait().await?;
+        if !archived.success() || !unpacked.success() {
+            return Err(self.failed("could not copy the recipe to the worker"));
+        }
+        Ok(())
+    }
+
+    /// Run the build on the worker, replaying its events locally
+    ///
+    /// Returns the package path on the worker.
+    async fn run(
+        &self,
+        context: &BuildContext,
+        remote_recipe: &Path,
+        remote_dir: &Path,
+    ) -> Result<PathBuf, Error> {
+        let mut build = format!(
+            "{} build --worker {} --output-dir {}",
+            shell_quote(&self.sps2_path),
+            shell_quote(&remote_recipe.to_string_lossy()),
+            shell_quote(&remote_dir.join("out").to_string_lossy()),
+        );
+        if self.allow_network {
+            build.push_str(" --network");
+        }
+
+        // The worker's errors go straight to our stderr
+        let mut child = self
+            .ssh(&build)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let Some(stdout) = child.stdout.take() else {
+            return Err(self.failed("could not read the worker's events"));
+        };
+
+        let mut package = None;
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await? {
+            // Anything but an event, such as the final report, is skipped
+            let Ok(message) = serde_json::from_str::<EventMessage>(&line) else {
+                continue;
+            };
+            match &message.event {
+                // The local build session reports its own start and end
+                AppEvent::Build(BuildEvent::Completed { artifacts, .. }) => {
+                    package = artifacts.first().cloned();
+                }
+                AppEvent::Build(BuildEvent::Started { .. } | BuildEvent::Failed { .. }) => {}
+                _ => {
+                    if let Some(sender) = context.event_sender() {
+                        let _ = sender.send(message);
+                    }
+                }
+            }
+        }
+
+        let status = child.wait().await?;
+        if !status.success() {
+            return Err(self.failed(&format!("worker exited with {status}")));
+        }
+        package.ok_or_else(|| self.failed("worker reported no package"))
+    }
+
+    /// Copy the package back into `output_dir`
+    async fn fetch(&self, remote_package: &Path, output_dir: &Path) -> Result<PathBuf, Error> {
+        let Some(file_name) = remote_package.file_name() else {
+            return Err(self.failed(&format!(
+                "invalid package path {}",
+                remote_package.display()
+            )));
+        };
+        tokio::fs::create_dir_all(output_dir).await?;
+        let package_path = output_dir.join(file_name);
+
+        // Streamed to disk, so large packages are never held in memory
+        let mut child = self
+            .ssh(&format!(
+                "cat {}",
+                shell_quote(&remote_package.to_string_lossy())
+            ))
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let mut stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| self.failed("ssh has no output"))?;
+        let mut file = tokio::fs::File::create(&package_path).await?;
+        let copied = tokio::io::copy(&mut stdout, &mut file).await;
+        let flushed = file.flush().await;
+        let status = child.wait().await;
+        let error = match (copied, flushed, status) {
+            (Ok(copied), Ok(()), Ok(status)) if status.success() && copied > 0 => {
+                return Ok(package_path);
+            }
+            (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => e.into(),
+            _ => self.failed("could not copy the package back"),
+        };
+        let _ = tokio::fs::remove_file(&package_path).await;
+        Err(error)
+    }
+
+    fn ssh(&self, remote_command: &str) -> Command {
+        let mut command = Command::new("ssh");
+        command
+            .args(["-o", "BatchMode=yes"])
+            // Ends the options, so a host starting with `-` is not read as one
+            .arg("--")
+            .arg(&self.host)
+            .arg(remote_command);
+        command
+    }
+
+    fn failed(&self, reason: &str) -> Error {
+        BuildError::Failed {
+            message: format!("remote build on {}: {reason}", self.host),
+        }
+        .into()
+    }
+}
+
+/// First local source or patch file of `recipe` not inside its directory
+fn file_outside_recipe_dir(recipe: &YamlRecipe) -> Option<&str> {
+    let local_sources = recipe
+        .source
+        .method
+        .iter()
+        .chain(recipe.source.sources.iter().map(|source| &source.method))
+        .filter_map(|method| match method {
+            SourceMethod::Local { local } => Some(local.path.as_str()),
+            _ => None,
+        });
+    let patch_files = recipe
+        .source
+        .patches
+        .iter()
+        .filter_map(|patch| match patch {
+            Patch::Path(file) | Patch::File { file, .. } => Some(file.as_str()),
+            _ => None,
+        });
+    local_sources.chain(patch_files).find(|path| {
+        let path = Path::new(path);
+        path.is_absolute()
+            || path
+                .components()
+                .any(|c| matches!(c, std::path::Component::ParentDir))
+    })
+}
+
+/// Quote `value` as a single word for the worker's shell
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoting_keeps_words_intact() {
+        assert_eq!(shell_quote("/tmp/a b"), "'/tmp/a b'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn remote_building_needs_a_host() {
+        let mut settings = RemoteSettings::default();
+        assert!(RemoteBuilder::from_settings(&settings).is_none());
+
+        settings.host = Some("builder@mac-mini.local".to_string());
+        let remote = RemoteBuilder::from_settings(&settings).unwrap();
+        assert_eq!(remote.host(), "builder@mac-mini.local");
+    }
+
+    #[test]
+    fn hosts_are_never_taken_for_ssh_options() {
+        let settings = RemoteSettings {
+            host: Some("-oProxyCommand=touch /tmp/pwned".to_string()),
+            ..RemoteSettings::default()
+        };
+        let remote = RemoteBuilder::from_settings(&settings).unwrap();
+        let command = remote.ssh("true");
+        let args: Vec<_> = command.as_std().get_args().collect();
+        assert_eq!(
+            args,
+            [
+                "-o",
+                "BatchMode=yes",
+                "--",
+                "-oProxyCommand=touch /tmp/pwned",
+                "true"
+            ]
+        );
+    }
+
+    fn recipe(source: &str) -> YamlRecipe {
+        let yaml = format!(
+            "
+metadata:
+  name: hello
+  version: 1.0.0
+  description: Greets
+  license: MIT
+
+source:
+{source}
+
+build:
+  system: make
+"
+        );
+        serde_yaml2::from_str(&yaml).unwrap()
+    }
+
+    #[test]
+    fn only_files_inside_the_recipe_directory_are_shipped() {
+        let inside = recipe("  local:\n    path: ./src\n  patches:\n    - patches/fix.patch");
+        assert_eq!(file_outside_recipe_dir(&inside), None);
+
+        let source = recipe("  local:\n    path: ../hello-src");
+        assert_eq!(file_outside_recipe_dir(&source), Some("../hello-src"));
+
+        let patch = recipe("  local:\n    path: ./src\n  patches:\n    - /tmp/fix.patch");
+        assert_eq!(file_outside_recipe_dir(&patch), Some("/tmp/fix.patch"));
+    }
+}
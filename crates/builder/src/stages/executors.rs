@@ -1,11 +1,14 @@
 //! Stage-specific execution functions
 
+use crate::recipe::model::ChecksumAlgorithm;
 use crate::security::SecurityContext;
-use crate::stages::{BuildCommand, EnvironmentStep, PostStep, SourceStep};
+use crate::stages::{BuildCommand, EnvironmentStep, PatchSource, PatchStep, PostStep, SourceStep};
 use crate::utils::events::send_event;
 use crate::{BuildCommandResult, BuildContext, BuildEnvironment, BuilderApi};
-use sps2_errors::Error;
+use sps2_errors::{BuildError, Error};
 use sps2_events::{AppEvent, GeneralEvent};
+use sps2_hash::Hash;
+use sps2_types::AppliedPatch;
 use std::path::Path;
 use tokio::fs;
 
@@ -96,20 +99,115 @@ pub async fn execute_source_step(
         SourceStep::Extract { extract_to } => {
             api.extract_downloads_to(extract_to.as_deref()).await?;
         }
-        SourceStep::Git { url, ref_ } => {
-            api.git(url, ref_).await?;
-        }
-        SourceStep::Copy { src_path } => {
-            api.copy(src_path.as_deref(), &environment.context).await?;
+        SourceStep::Git {
+            url,
+            ref_,
+            commit,
+            submodules,
+            depth,
+            extract_to,
+        } => {
+            api.git(
+                url,
+                ref_,
+                commit.as_deref(),
+                *submodules,
+                *depth,
+                extract_to.as_deref(),
+            )
+            .await?;
         }
-        SourceStep::ApplyPatch { path } => {
-            let patch_path = environment.build_prefix().join("src").join(path);
-            api.apply_patch(&patch_path, environment).await?;
+        SourceStep::Copy {
+            src_path,
+            extract_to,
+        } => {
+            api.copy(
+                src_path.as_deref(),
+                extract_to.as_deref(),
+                &environment.context,
+            )
+            .await?;
         }
     }
     Ok(())
 }
 
+/// Apply a patch to the sources in `api`'s working directory
+///
+/// File patches are looked up in `recipe_dir`, then in the source tree.
+/// Downloaded and inline patches are written to `fetch_api`'s working
+/// directory, outside the source tree. Returns the patch as recorded in the
+/// package manifest.
+pub async fn execute_patch_step(
+    step: &PatchStep,
+    recipe_dir: &Path,
+    fetch_api: &mut BuilderApi,
+    api: &BuilderApi,
+    environment: &BuildEnvironment,
+) -> Result<AppliedPatch, Error> {
+    let patch_path = match &step.source {
+        PatchSource::File(path) => {
+            let beside_recipe = recipe_dir.join(path);
+            if beside_recipe.is_file() {
+                beside_recipe
+            } else {
+                api.working_dir.join(path)
+            }
+        }
+        PatchSource::Fetch(url) => fetch_api.fetch(url).await?,
+        PatchSource::Inline { name, content } => {
+            let path = fetch_api.working_dir.join(name);
+            fs::write(&path, content).await?;
+            path
+        }
+    };
+    if !patch_path.is_file() {
+        return Err(BuildError::RecipeError {
+            message: format!("patch {} not found", step.label()),
+        }
+        .into());
+    }
+
+    let contents = fs::read(&patch_path).await?;
+    if let Some(checksum) = &step.checksum {
+        verify_checksum(step.label(), &contents, checksum)?;
+    }
+    api.apply_patch(&patch_path, step.strip, environment)
+        .await?;
+
+    Ok(AppliedPatch {
+        source: step.label().to_string(),
+        blake3: Hash::blake3_from_data(&contents).to_hex(),
+        strip: step.strip,
+    })
+}
+
+/// Check `contents` of the patch `label` against its recipe checksum
+fn verify_checksum(
+    label: &str,
+    contents: &[u8],
+    checksum: &ChecksumAlgorithm,
+) -> Result<(), Error> {
+    use md5::{Digest, Md5};
+    use sha2::Sha256;
+
+    let (expected, actual) = match checksum {
+        ChecksumAlgorithm::Blake3 { blake3 } => (blake3, Hash::blake3_from_data(contents).to_hex()),
+        ChecksumAlgorithm::Sha256 { sha256 } => (sha256, format!("{:x}", Sha256::digest(contents))),
+        ChecksumAlgorithm::Md5 { md5 } => (md5, format!("{:x}", Md5::digest(contents))),
+    };
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(BuildError::HashMismatch {
+            file: label.to_string(),
+            expected: expected.clone(),
+            actual,
+        }
+        .into())
+    }
+}
+
 /// Execute a build command
 pub async fn execute_build_command(
     command: &BuildCommand,
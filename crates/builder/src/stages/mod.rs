@@ -6,13 +6,15 @@
 pub mod build;
 pub mod environment;
 pub mod executors;
+pub mod patch;
 pub mod post;
 pub mod source;
 
 // Re-export execution types
 pub use build::BuildCommand;
 pub use environment::EnvironmentStep;
+pub use patch::{PatchSource, PatchStep};
 pub use post::PostStep;
-pub use source::SourceStep;
+pub use source::{NamedSourceStep, SourceStep};
 
 // The executors are used internally by utils/executor.rs
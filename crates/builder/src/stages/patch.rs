@@ -0,0 +1,37 @@
+//! Patch stage types
+
+use crate::recipe::model::ChecksumAlgorithm;
+use serde::{Deserialize, Serialize};
+
+/// A patch applied to the sources between the source and build stages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatchStep {
+    /// Where the patch comes from
+    pub source: PatchSource,
+    /// Leading path components to strip (`patch -p`)
+    pub strip: u32,
+    /// Checksum the patch must match
+    pub checksum: Option<ChecksumAlgorithm>,
+}
+
+/// Where a patch comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PatchSource {
+    /// Patch file, looked up next to the recipe, then in the source tree
+    File(String),
+    /// Patch downloaded from a URL
+    Fetch(String),
+    /// Patch text from the recipe, written to a file of this name
+    Inline { name: String, content: String },
+}
+
+impl PatchStep {
+    /// How the patch is identified in the package manifest
+    #[must_use]
+    pub fn label(&self) -> &str {
+        match &self.source {
+            PatchSource::File(path) | PatchSource::Fetch(path) => path,
+            PatchSource::Inline { name, .. } => name,
+        }
+    }
+}
@@ -39,13 +39,61 @@ pub enum SourceStep {
     Extract { extract_to: Option<String> },
 
     /// Clone from git
-    Git { url: String, ref_: String },
+    Git {
+        url: String,
+        ref_: String,
+        /// Commit the checkout must resolve to
+        commit: Option<String>,
+        submodules: bool,
+        /// History depth, `0` for the full history
+        depth: u32,
+        /// Clone here, relative to the build root, instead of the source directory
+        extract_to: Option<String>,
+    },
 
     /// Copy local files
-    Copy { src_path: Option<String> },
+    Copy {
+        src_path: Option<String>,
+        /// Copy here, relative to the build root, instead of the source directory
+        extract_to: Option<String>,
+    },
+}
+
+impl SourceStep {
+    /// Directory relative to the build root the source is placed in, if not
+    /// the source directory
+    #[must_use]
+    pub fn extract_to(&self) -> Option<&str> {
+        match self {
+            Self::Fetch { extract_to, .. }
+            | Self::FetchMd5 { extract_to, .. }
+            | Self::FetchSha256 { extract_to, .. }
+            | Self::FetchBlake3 { extract_to, .. }
+            | Self::Extract { extract_to }
+            | Self::Git { extract_to, .. }
+            | Self::Copy { extract_to, .. } => extract_to.as_deref(),
+            Self::Cleanup => None,
+        }
+    }
 
-    /// Apply a patch
-    ApplyPatch { path: String },
+    /// Algorithm the download is verified with, if any
+    #[must_use]
+    pub fn checksum_algorithm(&self) -> Option<&'static str> {
+        match self {
+            Self::FetchMd5 { .. } => Some("md5"),
+            Self::FetchSha256 { .. } => Some("sha256"),
+            Self::FetchBlake3 { .. } => Some("blake3"),
+            _ => None,
+        }
+    }
+}
+
+/// A recipe source and the step acquiring it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NamedSourceStep {
+    /// Name from the recipe, or the archive, repository or path it comes from
+    pub name: String,
+    pub step: SourceStep,
 }
 
 // Note: ParsedSource is recipe::model::Source
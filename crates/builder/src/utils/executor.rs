@@ -1,18 +1,23 @@
 //! Staged execution implementation for proper build ordering
 
-use crate::build_plan::{BuildPlan, EnvironmentConfig};
+use crate::build_plan::{BuildPlan, EnvironmentConfig, TestPlan};
 use crate::environment::BuildEnvironment;
 use crate::recipe::parser::parse_yaml_recipe;
 use crate::security::SecurityContext;
 use crate::stages::executors::{
-    execute_build_commands_list_with_security, execute_post_step_with_security, execute_source_step,
+    execute_build_commands_list_with_security, execute_patch_step, execute_post_step_with_security,
+    execute_source_step,
 };
 use crate::utils::events::send_event;
+use crate::utils::limits::{run_within_limits, BuildLimits};
 use crate::yaml::RecipeMetadata;
 use crate::{BuildConfig, BuildContext, BuilderApi};
-use sps2_errors::Error;
-use sps2_events::{AppEvent, GeneralEvent};
+use sps2_errors::{BuildError, Error};
+use sps2_events::{AppEvent, BuildEvent, BuildTarget, EventEmitter, GeneralEvent};
+use sps2_types::{TestFailurePolicy, TestResults, TestStatus};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use tokio::fs;
 
 /// Execute a build using staged execution model
@@ -55,21 +60,40 @@ pub async fn execute_staged_build(
 
     // Stage 1: Apply environment configuration
     apply_environment_config(context, environment, &build_plan.environment).await?;
+    let limits = BuildLimits::resolve(config, &build_plan.environment.limits);
+    if let Some(jobs) = limits.jobs {
+        environment.limit_jobs(jobs);
+    }
 
     // Stage 2: Execute source operations
-    execute_source_stage(config, context, environment, &build_plan).await?;
+    let source_dir = execute_source_stage(config, context, environment, &build_plan).await?;
+
+    // Stage 3: Apply the recipe's patches to the sources
+    execute_patch_stage(config, context, environment, &build_plan, source_dir).await?;
 
-    // Stage 3: Execute build operations (with security context)
+    // Stage 4: Execute build operations (with security context)
     execute_build_stage_with_security(
         config,
         context,
         environment,
         &build_plan,
         &mut security_context,
+        limits,
     )
     .await?;
 
-    // Stage 4: Execute post-processing operations (with security context)
+    // Stage 5: Run the recipe's tests (with security context)
+    execute_test_stage(
+        config,
+        context,
+        environment,
+        &build_plan,
+        &mut security_context,
+        limits,
+    )
+    .await?;
+
+    // Stage 6: Execute post-processing operations (with security context)
     execute_post_stage_with_security(
         config,
         context,
@@ -79,6 +103,11 @@ pub async fn execute_staged_build(
     )
     .await?;
 
+    // Packaging filters and QA suppressions apply once the build is done
+    environment.set_package_filters(build_plan.package.clone());
+    environment.set_qa_suppressions(build_plan.qa_suppress.clone());
+    environment.set_runtime_deps_policy(build_plan.runtime_deps);
+
     // Extract dependencies
     let runtime_deps = build_plan.metadata.runtime_deps.clone();
     let build_deps: Vec<sps2_types::package::PackageSpec> = build_plan
@@ -157,14 +186,18 @@ async fn apply_environment_config(
 }
 
 /// Execute source acquisition stage
+///
+/// Returns the directory the sources ended up in, which is below the `src`
+/// working directory for git sources.
 async fn execute_source_stage(
     config: &BuildConfig,
     context: &BuildContext,
     environment: &mut BuildEnvironment,
     build_plan: &BuildPlan,
-) -> Result<(), Error> {
+) -> Result<PathBuf, Error> {
+    let working_dir = environment.build_prefix().join("src");
     if build_plan.source_steps.is_empty() {
-        return Ok(());
+        return Ok(working_dir);
     }
 
     send_event(
@@ -173,13 +206,15 @@ async fn execute_source_stage(
     );
 
     // Create working directory
-    let working_dir = environment.build_prefix().join("src");
     fs::create_dir_all(&working_dir).await?;
 
     // Create builder API
-    let mut api = BuilderApi::new(working_dir.clone(), config.resources.clone())?;
+    let mut api = BuilderApi::new(working_dir, config.resources.clone())?;
     // Source stage always allows network for fetching
-    let _result = api.allow_network(true);
+    let _result = api.allow_network(true).offline(config.offline());
+    if let Some(sps2_config) = &config.sps2_config {
+        let _result = api.temp_dir(sps2_config.tmp_path());
+    }
 
     // Clean staging area first
     send_event(
@@ -191,10 +226,21 @@ async fn execute_source_stage(
     execute_source_step(&crate::stages::SourceStep::Cleanup, &mut api, environment).await?;
 
     // Execute source steps
-    for step in &build_plan.source_steps {
-        execute_source_step(step, &mut api, environment).await?;
-
-        // Command completed - duration tracking removed as per architectural decision
+    let build_root = environment.build_prefix().to_path_buf();
+    for source in &build_plan.source_steps {
+        let start = Instant::now();
+        execute_source_step(&source.step, &mut api, environment).await?;
+        let destination = match source.step.extract_to() {
+            Some(extract_to) => build_root.join(extract_to),
+            None => api.working_dir.clone(),
+        };
+        context.emit(AppEvent::Build(BuildEvent::SourceAcquired {
+            session_id: context.session_id(),
+            source: source.name.clone(),
+            destination,
+            checksum: source.step.checksum_algorithm().map(str::to_string),
+            duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        }));
     }
 
     send_event(
@@ -202,16 +248,64 @@ async fn execute_source_stage(
         AppEvent::General(GeneralEvent::debug("Source acquisition completed")),
     );
 
+    Ok(api.working_dir)
+}
+
+/// Apply the recipe's patches to the sources in `source_dir`
+///
+/// Each patch is recorded in the environment for the package manifest.
+async fn execute_patch_stage(
+    config: &BuildConfig,
+    context: &BuildContext,
+    environment: &mut BuildEnvironment,
+    build_plan: &BuildPlan,
+    source_dir: PathBuf,
+) -> Result<(), Error> {
+    if build_plan.patches.is_empty() {
+        return Ok(());
+    }
+
+    send_event(
+        context,
+        AppEvent::General(GeneralEvent::debug("Applying patches")),
+    );
+
+    // Downloaded and inline patches are kept out of the source tree
+    let patch_dir = environment.build_prefix().join("patches");
+    fs::create_dir_all(&patch_dir).await?;
+    let mut fetch_api = BuilderApi::new(patch_dir, config.resources.clone())?;
+    let _result = fetch_api.allow_network(true);
+    let api = BuilderApi::new(source_dir, config.resources.clone())?;
+    let recipe_dir = context
+        .recipe_path
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+
+    for step in &build_plan.patches {
+        let applied =
+            execute_patch_step(step, &recipe_dir, &mut fetch_api, &api, environment).await?;
+        send_event(
+            context,
+            AppEvent::General(GeneralEvent::debug(format!(
+                "Applied patch {} (-p{})",
+                applied.source, applied.strip
+            ))),
+        );
+        environment.record_applied_patch(applied);
+    }
+
     Ok(())
 }
 
-/// Execute build stage with security context
+/// Execute build stage with security context, within `limits`
 async fn execute_build_stage_with_security(
     config: &BuildConfig,
     context: &BuildContext,
     environment: &mut BuildEnvironment,
     build_plan: &BuildPlan,
     security_context: &mut SecurityContext,
+    limits: BuildLimits,
 ) -> Result<(), Error> {
     if build_plan.build_steps.is_empty() {
         return Ok(());
@@ -233,8 +327,8 @@ async fn execute_build_stage_with_security(
     // Use network setting from YAML recipe's environment config
     let _result = api.allow_network(build_plan.environment.network);
 
-    // Execute build steps with timeout and security context
-    crate::utils::timeout::with_optional_timeout(
+    // Execute build steps within the resource limits and security context
+    run_within_limits(
         execute_build_commands_list_with_security(
             context,
             &build_plan.build_steps,
@@ -243,8 +337,9 @@ async fn execute_build_stage_with_security(
             security_context,
             config.sps2_config.as_ref(),
         ),
-        config.max_build_time(),
-        &context.name,
+        limits,
+        config,
+        context,
     )
     .await?;
 
@@ -261,6 +356,129 @@ async fn execute_build_stage_with_security(
     Ok(())
 }
 
+/// Run the recipe's tests within `limits` and record their outcome
+///
+/// Failing tests fail the build or only warn, as the recipe or builder
+/// config says; under the `skip` policy they are not run at all.
+async fn execute_test_stage(
+    config: &BuildConfig,
+    context: &BuildContext,
+    environment: &mut BuildEnvironment,
+    build_plan: &BuildPlan,
+    security_context: &mut SecurityContext,
+    limits: BuildLimits,
+) -> Result<(), Error> {
+    let plan = &build_plan.test;
+    if plan.is_empty() {
+        return Ok(());
+    }
+
+    let policy = plan
+        .on_failure
+        .unwrap_or(config.build_settings().test_failure_policy);
+    let start = Instant::now();
+    let failure = if policy == TestFailurePolicy::Skip {
+        None
+    } else {
+        send_event(
+            context,
+            AppEvent::General(GeneralEvent::debug("Running tests")),
+        );
+
+        let working_dir = environment.build_prefix().join("src");
+        security_context.set_current_dir(working_dir.clone());
+        let mut api = BuilderApi::new(working_dir, config.resources.clone())?;
+        let _result = api.allow_network(build_plan.environment.network);
+
+        let tests = run_tests(
+            config,
+            context,
+            plan,
+            &mut api,
+            environment,
+            security_context,
+        );
+        // A test command that fails is a test failure like any other
+        run_within_limits(tests, limits, config, context)
+            .await
+            .unwrap_or_else(|error| Some(error.to_string()))
+    };
+
+    let results = TestResults {
+        status: match (policy, &failure) {
+            (TestFailurePolicy::Skip, _) => TestStatus::Skipped,
+            (_, None) => TestStatus::Passed,
+            (_, Some(_)) => TestStatus::Failed,
+        },
+        policy,
+        duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+        failure: failure.clone(),
+    };
+    context.emit(AppEvent::Build(BuildEvent::TestsCompleted {
+        session_id: context.session_id(),
+        target: BuildTarget {
+            package: context.name.clone(),
+            version: context.version.clone(),
+        },
+        results: results.clone(),
+    }));
+    environment.set_test_results(results);
+
+    match failure {
+        Some(message) if policy == TestFailurePolicy::Fail => Err(BuildError::TestsFailed {
+            package: context.name.clone(),
+            message,
+        }
+        .into()),
+        Some(message) => {
+            context.emit_warning(format!("Tests failed for {}: {message}", context.name));
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Run the test steps, then the build system's test suite
+///
+/// Returns why the test suite failed, if it did.
+async fn run_tests(
+    config: &BuildConfig,
+    context: &BuildContext,
+    plan: &TestPlan,
+    api: &mut BuilderApi,
+    environment: &mut BuildEnvironment,
+    security_context: &mut SecurityContext,
+) -> Result<Option<String>, Error> {
+    execute_build_commands_list_with_security(
+        context,
+        &plan.steps,
+        api,
+        environment,
+        security_context,
+        config.sps2_config.as_ref(),
+    )
+    .await?;
+
+    if let Some(system) = plan.system {
+        let results = api.test(system, environment).await?;
+        if !results.all_passed() {
+            let names: Vec<&str> = results.failures.iter().map(|f| f.name.as_str()).collect();
+            let message = if names.is_empty() {
+                format!("{} of {} tests failed", results.failed, results.total)
+            } else {
+                format!(
+                    "{} of {} tests failed ({})",
+                    results.failed,
+                    results.total,
+                    names.join(", ")
+                )
+            };
+            return Ok(Some(message));
+        }
+    }
+    Ok(None)
+}
+
 /// Execute post-processing stage with security context
 async fn execute_post_stage_with_security(
     config: &BuildConfig,
@@ -308,3 +526,286 @@ async fn execute_post_stage_with_security(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_errors::Error;
+    use sps2_types::Version;
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    /// Recipe around `source` and the stages after it
+    fn recipe(source: &str, rest: &str) -> String {
+        format!(
+            "metadata:\n  name: hello\n  version: 1.0.0\n  description: Hello\n  \
+             license: MIT\nsource:\n{source}\nbuild:\n  steps:\n    - shell: echo built\n{rest}"
+        )
+    }
+
+    /// Builder config allowing the default build commands
+    fn config() -> BuildConfig {
+        BuildConfig::default().with_sps2_config(sps2_config::Config::default())
+    }
+
+    /// Run the stages of the recipe `setup` returns, after it had the chance
+    /// to add files to the recipe directory
+    async fn run(
+        config: BuildConfig,
+        setup: impl FnOnce(&Path) -> String,
+    ) -> (Result<(), Error>, BuildEnvironment, Vec<AppEvent>, TempDir) {
+        let dir = TempDir::new().unwrap();
+        let recipe = setup(dir.path());
+        let recipe_path = dir.path().join("hello.yml");
+        fs::write(&recipe_path, recipe).await.unwrap();
+
+        let (sender, mut receiver) = sps2_events::channel();
+        let context = BuildContext::new(
+            "hello".to_string(),
+            Version::new(1, 0, 0),
+            recipe_path,
+            dir.path().join("out"),
+        )
+        .with_event_sender(sender);
+        let mut environment =
+            BuildEnvironment::new(context.clone(), &dir.path().join("build")).unwrap();
+        environment.initialize().await.unwrap();
+
+        let result = Box::pin(execute_staged_build(&config, &context, &mut environment))
+            .await
+            .map(|_| ());
+        let mut events = Vec::new();
+        while let Ok(message) = receiver.try_recv() {
+            events.push(message.event);
+        }
+        (result, environment, events, dir)
+    }
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .args(["-c", "user.name=sps2", "-c", "user.email=sps2@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "git {args:?} failed");
+        String::from_utf8(output.stdout).unwrap().trim().to_string()
+    }
+
+    /// Repository `name` in `dir` with `README` committed and tagged `v1`;
+    /// returns the commit
+    fn git_repository(dir: &Path, name: &str, readme: &str) -> String {
+        let repo = dir.join(name);
+        std::fs::create_dir_all(&repo).unwrap();
+        git(&repo, &["init", "--quiet"]);
+        std::fs::write(repo.join("README"), readme).unwrap();
+        git(&repo, &["add", "README"]);
+        git(&repo, &["commit", "--quiet", "-m", "initial"]);
+        git(&repo, &["tag", "v1"]);
+        git(&repo, &["rev-parse", "HEAD"])
+    }
+
+    /// Recipe building the sources of a repository with `README` holding
+    /// `readme`, followed by `rest`
+    fn from_git(dir: &Path, readme: &str, rest: &str) -> String {
+        git_repository(dir, "upstream", readme);
+        let url = dir.join("upstream");
+        recipe(
+            &format!("  git:\n    url: {}\n    ref: v1", url.display()),
+            rest,
+        )
+    }
+
+    /// Where the sources of `from_git` recipes are checked out
+    fn checkout(environment: &BuildEnvironment) -> PathBuf {
+        environment.build_prefix().join("src/upstream")
+    }
+
+    fn test_results(environment: &BuildEnvironment) -> &TestResults {
+        environment.test_results().expect("no test results")
+    }
+
+    #[tokio::test]
+    async fn failing_tests_fail_or_warn_as_the_policy_says() {
+        let tested = |test: &str| {
+            let test = test.to_string();
+            move |dir: &Path| from_git(dir, "hello\n", &test)
+        };
+        let failing = "test:\n  steps:\n    - shell: \"touch tests-ran && false\"\n";
+
+        let passing = "test:\n  steps:\n    - shell: echo passed\n";
+        let (result, environment, _, _dir) = run(config(), tested(passing)).await;
+        result.unwrap();
+        assert_eq!(test_results(&environment).status, TestStatus::Passed);
+
+        let (result, environment, events, _dir) = run(config(), tested(failing)).await;
+        let Err(Error::Build(BuildError::TestsFailed { package, .. })) = result else {
+            panic!("tests did not fail the build: {result:?}");
+        };
+        assert_eq!(package, "hello");
+        assert_eq!(test_results(&environment).status, TestStatus::Failed);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            AppEvent::Build(BuildEvent::TestsCompleted { results, .. })
+                if results.status == TestStatus::Failed
+        )));
+
+        let warn = format!("{failing}  on_failure: warn\n");
+        let (result, environment, events, _dir) = run(config(), tested(&warn)).await;
+        result.unwrap();
+        let results = test_results(&environment);
+        assert_eq!(results.status, TestStatus::Failed);
+        assert_eq!(results.policy, TestFailurePolicy::Warn);
+        assert!(events.iter().any(|event| matches!(
+            event,
+            AppEvent::General(GeneralEvent::Warning { message, .. })
+                if message.starts_with("Tests failed for hello")
+        )));
+
+        let skip = format!("{failing}  on_failure: skip\n");
+        let (result, environment, _, _dir) = run(config(), tested(&skip)).await;
+        result.unwrap();
+        assert_eq!(test_results(&environment).status, TestStatus::Skipped);
+        let build_prefix = environment.build_prefix();
+        assert!(!build_prefix.join("src/tests-ran").exists());
+        assert!(!checkout(&environment).join("tests-ran").exists());
+    }
+
+    #[tokio::test]
+    async fn patches_are_applied_checked_and_recorded() {
+        const PATCH: &str = "--- a/README\n+++ b/README\n@@ -1 +1 @@\n-teh sources\n+the sources\n";
+        let blake3 = sps2_hash::Hash::blake3_from_data(PATCH.as_bytes()).to_hex();
+        let patched = |patches: &str| {
+            let patches = format!("  patches:\n{patches}\n");
+            move |dir: &Path| {
+                std::fs::write(dir.join("typo.patch"), PATCH).unwrap();
+                // Patches belong to the source section
+                from_git(dir, "teh sources\n", "")
+                    .replace("\nbuild:", &format!("\n{patches}build:"))
+            }
+        };
+        let readme = |environment: &BuildEnvironment| {
+            std::fs::read_to_string(checkout(environment).join("README")).unwrap()
+        };
+
+        let file = format!("    - file: typo.patch\n      checksum:\n        blake3: {blake3}");
+        let (result, environment, _, _dir) = run(config(), patched(&file)).await;
+        result.unwrap();
+        assert_eq!(readme(&environment), "the sources\n");
+        let applied = environment.applied_patches();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].source, "typo.patch");
+        assert_eq!(applied[0].blake3, blake3);
+        assert_eq!(applied[0].strip, 1);
+
+        let unprefixed = PATCH
+            .replace("a/README", "README")
+            .replace("b/README", "README");
+        let inline = format!(
+            "    - name: typo.patch\n      strip: 0\n      inline: |\n        {}",
+            unprefixed.trim_end().replace('\n', "\n        ")
+        );
+        let (result, environment, _, _dir) = run(config(), patched(&inline)).await;
+        result.unwrap();
+        assert_eq!(readme(&environment), "the sources\n");
+        assert_eq!(environment.applied_patches()[0].strip, 0);
+
+        let tampered = file.replace(&blake3, &"f".repeat(64));
+        let (result, environment, _, _dir) = run(config(), patched(&tampered)).await;
+        assert!(
+            matches!(result, Err(Error::Build(BuildError::HashMismatch { .. }))),
+            "{result:?}"
+        );
+        assert_eq!(readme(&environment), "teh sources\n");
+    }
+
+    #[tokio::test]
+    async fn git_sources_are_cloned_at_the_pinned_commit() {
+        let upstream = TempDir::new().unwrap();
+        let commit = git_repository(upstream.path(), "upstream", "upstream\n");
+        let repo = upstream.path().join("upstream");
+        let source = |git_ref: &str, pin: &str| {
+            let source = format!(
+                "  git:\n    url: {}\n    ref: {git_ref}\n    commit: {pin}",
+                repo.display()
+            );
+            move |_: &Path| recipe(&source, "")
+        };
+
+        let (result, environment, _, _dir) = run(config(), source("v1", &commit[..8])).await;
+        result.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(checkout(&environment).join("README")).unwrap(),
+            "upstream\n"
+        );
+        assert_eq!(git(&checkout(&environment), &["rev-parse", "HEAD"]), commit);
+
+        let (result, environment, _, _dir) = run(config(), source(&commit, &commit)).await;
+        result.unwrap();
+        assert_eq!(git(&checkout(&environment), &["rev-parse", "HEAD"]), commit);
+
+        let (result, _, _, _dir) = run(config(), source("v1", "abcdef0")).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::Build(BuildError::GitCommitMismatch { .. }))
+            ),
+            "{result:?}"
+        );
+
+        let mut sps2_config = sps2_config::Config::default();
+        sps2_config.network.offline = true;
+        let offline = BuildConfig::default().with_sps2_config(sps2_config);
+        let (result, _, _, _dir) = run(offline, source("v1", &commit)).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::Build(BuildError::NetworkDisabled { .. }))
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn named_sources_are_acquired_into_their_own_directories() {
+        let (result, environment, events, _dir) = run(config(), |dir| {
+            git_repository(dir, "gcc", "gcc\n");
+            git_repository(dir, "gmp", "gmp\n");
+            recipe(
+                &format!(
+                    "  sources:\n    - name: gcc\n      git:\n        url: {}\n        ref: v1\n      \
+                     extract_to: src/gcc\n    - name: gmp\n      git:\n        url: {}\n        \
+                     ref: v1\n      extract_to: src/gcc/gmp",
+                    dir.join("gcc").display(),
+                    dir.join("gmp").display()
+                ),
+                "",
+            )
+        })
+        .await;
+        result.unwrap();
+
+        let gcc = environment.build_prefix().join("src/gcc");
+        let gmp = gcc.join("gmp");
+        assert_eq!(
+            std::fs::read_to_string(gcc.join("README")).unwrap(),
+            "gcc\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(gmp.join("README")).unwrap(),
+            "gmp\n"
+        );
+        let acquired: Vec<(&str, &Path)> = events
+            .iter()
+            .filter_map(|event| match event {
+                AppEvent::Build(BuildEvent::SourceAcquired {
+                    source,
+                    destination,
+                    ..
+                }) => Some((source.as_str(), destination.as_path())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(acquired, [("gcc", gcc.as_path()), ("gmp", gmp.as_path())]);
+    }
+}
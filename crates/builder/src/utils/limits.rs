@@ -0,0 +1,379 @@
+//! Resource limits for the build stage
+//!
+//! Wall-clock time and the memory of the build's processes are watched while
+//! the build commands run. Going past a limit terminates those processes and
+//! fails the build instead of letting it hang or swap the machine to a halt.
+
+use crate::recipe::model::Limits;
+use crate::{BuildConfig, BuildContext};
+use sps2_config::{MemoryPressure, ResourceLimits, ResourceManager};
+use sps2_errors::{BuildError, Error};
+use sps2_events::EventEmitter;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+/// How often time and memory use are checked
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Limits a build runs under, combined from the recipe and builder config
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildLimits {
+    pub timeout: Option<Duration>,
+    pub memory_bytes: Option<u64>,
+    pub jobs: Option<usize>,
+}
+
+impl BuildLimits {
+    /// Combine the recipe's limits with the builder config's, keeping the lower
+    #[must_use]
+    pub fn resolve(config: &BuildConfig, recipe: &Limits) -> Self {
+        let max_memory_mb = config.build_settings().max_memory_mb;
+        Self {
+            timeout: lower(recipe.timeout_seconds, config.max_build_time())
+                .map(Duration::from_secs),
+            memory_bytes: lower(
+                recipe.memory_mb,
+                (max_memory_mb > 0).then_some(max_memory_mb),
+            )
+            .map(|mb| mb.saturating_mul(1024 * 1024)),
+            jobs: lower(recipe.jobs, config.build_jobs()),
+        }
+    }
+}
+
+fn lower<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Run the build stage `future` within `limits`
+///
+/// Warns once when time or memory use reaches 90% of its limit.
+///
+/// # Errors
+///
+/// Returns the error of `future`, or `BuildError::ResourceLimitExceeded`
+/// once a limit is exceeded.
+pub async fn run_within_limits<T, F>(
+    future: F,
+    limits: BuildLimits,
+    config: &BuildConfig,
+    context: &BuildContext,
+) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    watch(future, limits, config, context, std::process::id()).await
+}
+
+/// [`run_within_limits`] for the processes below `root`
+async fn watch<T, F>(
+    future: F,
+    limits: BuildLimits,
+    config: &BuildConfig,
+    context: &BuildContext,
+    root: u32,
+) -> Result<T, Error>
+where
+    F: Future<Output = Result<T, Error>>,
+{
+    if limits.timeout.is_none() && limits.memory_bytes.is_none() {
+        return future.await;
+    }
+
+    let monitor = ResourceManager::new(ResourceLimits {
+        memory_usage: limits.memory_bytes,
+        ..config.resources.limits.clone()
+    });
+    let started = Instant::now();
+    let mut time_warned = false;
+    let mut memory_warned = false;
+    let mut memory_unmeasured = false;
+    let mut ticks = tokio::time::interval(SAMPLE_INTERVAL);
+    tokio::pin!(future);
+
+    loop {
+        tokio::select! {
+            result = &mut future => return result,
+            _ = ticks.tick() => {}
+        }
+
+        if let Some(timeout) = limits.timeout {
+            let elapsed = started.elapsed();
+            if elapsed > timeout {
+                terminate_build_processes(root, context).await;
+                return Err(exceeded(context, "time", format!("{}s", timeout.as_secs())));
+            }
+            if !time_warned && elapsed >= timeout / 10 * 9 {
+                time_warned = true;
+                context.emit_warning(format!(
+                    "Build of {} has used {}s of its {}s time limit",
+                    context.name,
+                    elapsed.as_secs(),
+                    timeout.as_secs()
+                ));
+            }
+        }
+
+        if let (Some(limit), false) = (limits.memory_bytes, memory_unmeasured) {
+            let usage = match build_processes(root).await {
+                Ok(processes) => processes.iter().map(|p| p.rss_bytes).sum(),
+                Err(e) => {
+                    memory_unmeasured = true;
+                    context.emit_warning(format!(
+                        "The memory limit of the build of {} is not enforced: {e}",
+                        context.name
+                    ));
+                    continue;
+                }
+            };
+            match monitor.record_memory_usage(usage) {
+                MemoryPressure::Exceeded => {
+                    terminate_build_processes(root, context).await;
+                    return Err(exceeded(context, "memory", format_mib(limit)));
+                }
+                MemoryPressure::Approaching if !memory_warned => {
+                    memory_warned = true;
+                    context.emit_warning(format!(
+                        "Build of {} is using {} of its {} memory limit",
+                        context.name,
+                        format_mib(usage),
+                        format_mib(limit)
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn exceeded(context: &BuildContext, resource: &str, limit: String) -> Error {
+    BuildError::ResourceLimitExceeded {
+        package: context.name.clone(),
+        resource: resource.to_string(),
+        limit,
+    }
+    .into()
+}
+
+fn format_mib(bytes: u64) -> String {
+    format!("{} MiB", bytes / (1024 * 1024))
+}
+
+/// A process started, directly or not, by this one
+#[derive(Debug, PartialEq, Eq)]
+struct BuildProcess {
+    pid: u32,
+    rss_bytes: u64,
+}
+
+/// The processes below `root`, sampled with `ps`
+///
+/// # Errors
+///
+/// Returns an error if `ps` cannot be run or fails.
+async fn build_processes(root: u32) -> Result<Vec<BuildProcess>, Error> {
+    let failed = |message: String| BuildError::Failed {
+        message: format!("cannot list the build's processes: {message}"),
+    };
+    let ps = Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,rss="])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    let ps_pid = ps.id();
+    let output = ps
+        .wait_with_output()
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(failed(format!(
+            "ps {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    let mut processes = descendants(&String::from_utf8_lossy(&output.stdout), root);
+    processes.retain(|process| Some(process.pid) != ps_pid);
+    Ok(processes)
+}
+
+/// Processes below `root` in `ps` output of pid, parent pid and RSS in KiB
+fn descendants(ps_output: &str, root: u32) -> Vec<BuildProcess> {
+    let table: Vec<(u32, u32, u64)> = ps_output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().map(str::parse::<u64>);
+            let (Some(Ok(pid)), Some(Ok(parent_pid)), Some(Ok(rss))) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return None;
+            };
+            Some((
+                u32::try_from(pid).ok()?,
+                u32::try_from(parent_pid).ok()?,
+                rss,
+            ))
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    let mut parents = vec![root];
+    while let Some(parent) = parents.pop() {
+        for &(pid, parent_pid, rss) in &table {
+            if parent_pid == parent && pid != root {
+                parents.push(pid);
+                found.push(BuildProcess {
+                    pid,
+                    rss_bytes: rss.saturating_mul(1024),
+                });
+            }
+        }
+    }
+    found
+}
+
+/// Ask every process below `root` to terminate
+async fn terminate_build_processes(root: u32, context: &BuildContext) {
+    let processes = match build_processes(root).await {
+        Ok(processes) => processes,
+        Err(e) => {
+            context.emit_warning(format!(
+                "Processes of the build of {} may still be running: {e}",
+                context.name
+            ));
+            return;
+        }
+    };
+    let pids: Vec<String> = processes
+        .iter()
+        .map(|process| process.pid.to_string())
+        .collect();
+    if !pids.is_empty() {
+        let _ = Command::new("kill").arg("-TERM").args(&pids).status().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_types::Version;
+    use std::path::PathBuf;
+
+    fn context() -> BuildContext {
+        BuildContext::new(
+            "foo".to_string(),
+            Version::new(1, 0, 0),
+            PathBuf::from("foo.yaml"),
+            PathBuf::from("."),
+        )
+    }
+
+    #[test]
+    fn the_lower_of_recipe_and_config_limits_applies() {
+        let config = BuildConfig::default().with_timeout(3600).with_jobs(8);
+        let recipe = Limits {
+            timeout_seconds: Some(7200),
+            memory_mb: Some(2048),
+            jobs: Some(4),
+        };
+        assert_eq!(
+            BuildLimits::resolve(&config, &recipe),
+            BuildLimits {
+                timeout: Some(Duration::from_secs(3600)),
+                memory_bytes: Some(2048 * 1024 * 1024),
+                jobs: Some(4),
+            }
+        );
+        assert_eq!(
+            BuildLimits::resolve(&config, &Limits::default()).memory_bytes,
+            None
+        );
+    }
+
+    #[test]
+    fn descendants_are_found_through_the_process_tree() {
+        let ps =
+            "  1     0  1000\n 10     1   500\n 11    10   100\n 12    11   200\n 13     1   300\n";
+        assert_eq!(
+            descendants(ps, 10),
+            vec![
+                BuildProcess {
+                    pid: 11,
+                    rss_bytes: 100 * 1024
+                },
+                BuildProcess {
+                    pid: 12,
+                    rss_bytes: 200 * 1024
+                },
+            ]
+        );
+    }
+
+    /// A shell waiting for a `sleep` it started, standing in for a build
+    /// command; returns once the `sleep` is running
+    async fn build_command() -> (tokio::process::Child, u32) {
+        let shell = Command::new("sh")
+            .args(["-c", "sleep 30 & wait"])
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let pid = shell.id().unwrap();
+        for _ in 0..100 {
+            if !build_processes(pid).await.unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        (shell, pid)
+    }
+
+    #[tokio::test]
+    async fn spawned_processes_are_listed() {
+        let (mut shell, pid) = build_command().await;
+        let processes = build_processes(std::process::id()).await.unwrap();
+        let below_shell = build_processes(pid).await.unwrap();
+        shell.kill().await.unwrap();
+
+        assert!(
+            processes
+                .iter()
+                .any(|process| process.pid == pid && process.rss_bytes > 0),
+            "{processes:?}"
+        );
+        assert_eq!(below_shell.len(), 1, "{below_shell:?}");
+        assert!(below_shell[0].rss_bytes > 0);
+    }
+
+    #[tokio::test]
+    async fn builds_past_their_time_limit_fail() {
+        let limits = BuildLimits {
+            timeout: Some(Duration::from_millis(100)),
+            ..BuildLimits::default()
+        };
+        let (mut shell, pid) = build_command().await;
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            Ok(())
+        };
+        let error = watch(slow, limits, &BuildConfig::default(), &context(), pid)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("exceeded its time limit"));
+
+        // The shell returns from `wait` once its sleep was terminated
+        tokio::time::timeout(Duration::from_secs(10), shell.wait())
+            .await
+            .expect("the build command's child was not terminated")
+            .unwrap();
+
+        let quick = async { Ok(42) };
+        let result = run_within_limits(quick, limits, &BuildConfig::default(), &context()).await;
+        assert_eq!(result.unwrap(), 42);
+    }
+}
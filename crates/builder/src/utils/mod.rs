@@ -4,4 +4,4 @@ pub mod events;
 pub mod executor;
 pub mod fileops;
 pub mod format;
-pub mod timeout;
+pub mod limits;
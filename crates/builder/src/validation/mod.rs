@@ -8,7 +8,7 @@ pub mod parser;
 pub mod rules;
 
 use crate::recipe::model::{ParsedStep, PostCommand};
-use crate::stages::{BuildCommand, PostStep, SourceStep};
+use crate::stages::{BuildCommand, NamedSourceStep, PatchSource, PatchStep, PostStep, SourceStep};
 use sps2_errors::{BuildError, Error};
 
 /// Validate and convert a source step
@@ -20,20 +20,93 @@ pub fn validate_source_step(step: &SourceStep, _recipe_dir: &std::path::Path) ->
         | SourceStep::FetchBlake3 { url, .. } => {
             validate_url(url)?;
         }
-        SourceStep::Git { url, .. } => {
+        SourceStep::Git { url, commit, .. } => {
             validate_git_url(url)?;
+            if let Some(commit) = commit {
+                validate_git_commit(commit)?;
+            }
         }
         SourceStep::Copy {
             src_path: Some(path),
-        }
-        | SourceStep::ApplyPatch { path } => {
+            ..
+        } => {
             validate_path(path)?;
         }
-        SourceStep::Copy { src_path: None } | SourceStep::Cleanup | SourceStep::Extract { .. } => {}
+        SourceStep::Copy { src_path: None, .. }
+        | SourceStep::Cleanup
+        | SourceStep::Extract { .. } => {}
+    }
+    if let Some(extract_to) = step.extract_to() {
+        validate_extract_to(extract_to)?;
     }
     Ok(())
 }
 
+/// Check that several sources do not share a name or destination
+pub fn validate_distinct_sources(sources: &[NamedSourceStep]) -> Result<(), Error> {
+    for (i, source) in sources.iter().enumerate() {
+        for other in &sources[..i] {
+            if other.name == source.name {
+                return Err(BuildError::RecipeError {
+                    message: format!("source name '{}' is used more than once", source.name),
+                }
+                .into());
+            }
+            if let (Some(a), Some(b)) = (other.step.extract_to(), source.step.extract_to()) {
+                if a.trim_end_matches('/') == b.trim_end_matches('/') {
+                    return Err(BuildError::RecipeError {
+                        message: format!(
+                            "sources '{}' and '{}' are both extracted to '{a}'",
+                            other.name, source.name
+                        ),
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Validate a source destination, which must stay inside the build root
+fn validate_extract_to(extract_to: &str) -> Result<(), Error> {
+    let path = std::path::Path::new(extract_to);
+    let escapes = path.components().any(|component| {
+        !matches!(
+            component,
+            std::path::Component::Normal(_) | std::path::Component::CurDir
+        )
+    });
+    if extract_to.trim().is_empty() || escapes {
+        return Err(BuildError::InvalidPath {
+            path: extract_to.to_string(),
+            reason: "Source destination must be a relative path inside the build root".to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Validate a patch step
+///
+/// Downloaded patches must have a checksum.
+pub fn validate_patch_step(step: &PatchStep) -> Result<(), Error> {
+    match &step.source {
+        PatchSource::File(path) => validate_path(path),
+        PatchSource::Fetch(url) => {
+            validate_url(url)?;
+            if step.checksum.is_none() {
+                return Err(BuildError::RecipeError {
+                    message: format!("patch {url} needs a checksum"),
+                }
+                .into());
+            }
+            Ok(())
+        }
+        PatchSource::Inline { name, .. } => validate_path(name),
+    }
+}
+
 /// Validate and convert a parsed build step to an executable command
 pub fn validate_build_step(
     step: &ParsedStep,
@@ -170,6 +243,17 @@ fn validate_git_url(url: &str) -> Result<(), Error> {
     Ok(())
 }
 
+/// Validate a pinned git commit hash
+fn validate_git_commit(commit: &str) -> Result<(), Error> {
+    if !(7..=40).contains(&commit.len()) || !commit.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(BuildError::RecipeError {
+            message: format!("git commit '{commit}' must be a hash of 7 to 40 hex digits"),
+        }
+        .into());
+    }
+    Ok(())
+}
+
 /// Validate a file path
 pub(crate) fn validate_path(path: &str) -> Result<(), Error> {
     // Check if path is within allowed build environment
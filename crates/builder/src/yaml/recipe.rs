@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use sps2_types::RpathStyle;
+use std::collections::BTreeMap;
 
 /// Recipe metadata collected from `metadata()` function
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -13,6 +14,10 @@ pub struct RecipeMetadata {
     pub license: Option<String>,
     pub runtime_deps: Vec<String>,
     pub build_deps: Vec<String>,
+    /// Packages the manifest recommends installing alongside
+    pub recommends: Vec<String>,
+    /// Environment variables for the manifest
+    pub environment: BTreeMap<String, String>,
 }
 
 /// A build step from the `build()` function
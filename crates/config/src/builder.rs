@@ -2,6 +2,7 @@
 
 use serde::{de::IgnoredAny, Deserialize, Serialize};
 use sps2_errors::{ConfigError, Error};
+use sps2_types::TestFailurePolicy;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
@@ -18,6 +19,10 @@ pub struct BuilderConfig {
     pub performance: PerformanceSettings,
     #[serde(default)]
     pub security: SecuritySettings,
+    #[serde(default)]
+    pub remote: RemoteSettings,
+    #[serde(default)]
+    pub qa: QaSettings,
 }
 
 /// Core build execution settings (global defaults and policies)
@@ -27,6 +32,8 @@ pub struct BuildSettings {
     pub build_jobs: usize, // 0 = auto-detect, can be overridden per recipe
     #[serde(default = "default_timeout_seconds")]
     pub timeout_seconds: u64, // Default timeout, can be overridden per recipe
+    #[serde(default)]
+    pub max_memory_mb: u64, // 0 = unlimited, can be lowered per recipe
     #[serde(default = "default_build_root")]
     pub build_root: PathBuf, // Global build directory
     #[serde(default = "default_cleanup_on_success")]
@@ -38,6 +45,8 @@ pub struct BuildSettings {
     pub default_isolation_level: String, // "none", "default", "enhanced", "hermetic"
     #[serde(default = "default_allow_network")]
     pub default_allow_network: bool, // Default network access policy
+    #[serde(default)]
+    pub test_failure_policy: TestFailurePolicy, // "fail", "warn", "skip"; recipes can override
 }
 
 impl Default for BuildSettings {
@@ -45,11 +54,13 @@ impl Default for BuildSettings {
         Self {
             build_jobs: 0,         // 0 = auto-detect
             timeout_seconds: 3600, // 1 hour
+            max_memory_mb: 0,      // unlimited
             build_root: PathBuf::from("/opt/pm/build"),
             cleanup_on_success: true,
             strict_mode: true,
             default_isolation_level: "default".to_string(),
             default_allow_network: false,
+            test_failure_policy: TestFailurePolicy::Fail,
         }
     }
 }
@@ -166,6 +177,14 @@ pub struct CacheSettings {
     pub max_size_mb: u64,
     #[serde(default = "default_distributed_cache")]
     pub distributed: bool,
+    /// Reuse packages built from identical inputs
+    #[serde(default = "default_artifact_cache")]
+    pub artifacts: bool,
+    #[serde(default = "default_artifact_dir")]
+    pub artifact_dir: PathBuf,
+    /// `cleanup` removes cached packages unused for this many days
+    #[serde(default = "default_artifact_max_age_days")]
+    pub artifact_max_age_days: u32,
 }
 
 impl Default for CacheSettings {
@@ -176,6 +195,9 @@ impl Default for CacheSettings {
             cache_dir: default_cache_dir(), // Auto-detect
             max_size_mb: 5000,              // 5GB
             distributed: false,
+            artifacts: true,
+            artifact_dir: default_artifact_dir(),
+            artifact_max_age_days: 30,
         }
     }
 }
@@ -207,6 +229,80 @@ impl Default for BuildSystemSettings {
     }
 }
 
+/// Remote build worker settings
+///
+/// When `host` is set, builds run on that macOS ARM64 machine over SSH
+/// instead of locally. The worker needs sps2 installed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteSettings {
+    /// SSH destination of the worker, e.g. `builder@mac-mini.local`
+    #[serde(default)]
+    pub host: Option<String>,
+    /// sps2 executable on the worker
+    #[serde(default = "default_remote_sps2_path")]
+    pub sps2_path: String,
+    /// Directory on the worker that recipes are shipped to
+    #[serde(default = "default_remote_work_dir")]
+    pub work_dir: PathBuf,
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self {
+            host: None,
+            sps2_path: default_remote_sps2_path(),
+            work_dir: default_remote_work_dir(),
+        }
+    }
+}
+
+/// Site-specific artifact QA checks
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct QaSettings {
+    /// External validators and patchers run alongside the built-in ones
+    #[serde(default)]
+    pub plugins: Vec<QaPlugin>,
+    /// Lowest severity of unsuppressed finding that fails the build
+    #[serde(default)]
+    pub fail_on: QaFailOn,
+}
+
+/// Severity at which QA findings fail the build
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QaFailOn {
+    /// Errors fail the build; warnings are only reported
+    #[default]
+    Error,
+    /// Warnings fail the build too
+    Warning,
+}
+
+/// An external command taking part in the artifact QA pipeline
+///
+/// The command runs in the staging directory and reports one finding per
+/// stdout line: `error: <message>`, `warning: <message>` or, for patchers,
+/// `changed: <path>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QaPlugin {
+    /// Name shown in QA events
+    pub name: String,
+    pub kind: QaPluginKind,
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+/// Whether a QA plugin checks the staged files or modifies them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QaPluginKind {
+    /// Runs before and after patching; errors fail the build
+    Validator,
+    /// Runs with the built-in patchers, before code signing
+    Patcher,
+}
+
 /// Security and validation settings
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct SecuritySettings {
@@ -314,6 +410,17 @@ impl BuilderConfig {
             .join("builder.config.toml"))
     }
 
+    /// Get the default builder config file path under an alternate root
+    ///
+    /// Without a root this is [`BuilderConfig::default_path`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn default_path_in(root: Option<&Path>) -> Result<PathBuf, Error> {
+        Ok(crate::under_root(root, Self::default_path()?))
+    }
+
     /// Load builder configuration from file
     ///
     /// # Errors
@@ -344,14 +451,25 @@ impl BuilderConfig {
     /// Returns an error if the configuration file exists but cannot be read
     /// or contains invalid TOML syntax.
     pub async fn load() -> Result<Self, Error> {
-        let config_path = Self::default_path()?;
+        Self::load_in(None).await
+    }
+
+    /// Load builder configuration from the default location under an
+    /// alternate root, creating it with defaults when missing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration file exists but cannot be read
+    /// or contains invalid TOML syntax.
+    pub async fn load_in(root: Option<&Path>) -> Result<Self, Error> {
+        let config_path = Self::default_path_in(root)?;
 
         if config_path.exists() {
             Self::load_from_file(&config_path).await
         } else {
             // Create default config and save it
             let config = Self::default();
-            if let Err(e) = config.save().await {
+            if let Err(e) = config.save_to(&config_path).await {
                 tracing::warn!("Failed to save default builder config: {}", e);
             }
             Ok(config)
@@ -608,6 +726,18 @@ fn default_distributed_cache() -> bool {
     false
 }
 
+fn default_artifact_cache() -> bool {
+    true
+}
+
+fn default_artifact_dir() -> PathBuf {
+    PathBuf::from("/opt/pm/build-cache")
+}
+
+fn default_artifact_max_age_days() -> u32 {
+    30
+}
+
 fn default_parallel_builds() -> bool {
     true
 }
@@ -616,6 +746,14 @@ fn default_out_of_source() -> bool {
     true
 }
 
+fn default_remote_sps2_path() -> String {
+    "sps2".to_string()
+}
+
+fn default_remote_work_dir() -> PathBuf {
+    PathBuf::from("/tmp/sps2-remote-builds")
+}
+
 fn default_allowed_commands() -> Vec<String> {
     let mut commands = Vec::new();
 
@@ -2,6 +2,10 @@
 //!
 //! These paths are deliberately not exposed via TOML configuration to keep the
 //! installation prefix stable. Packages are built against this fixed prefix.
+//!
+//! Code managing an installation should go through the path accessors on
+//! [`Config`](crate::Config) instead, which place these paths under an
+//! alternate root when one is configured (`--root`, `SPS2_ROOT`).
 
 pub const PREFIX: &str = "/opt/pm";
 
@@ -12,6 +16,8 @@ pub const BIN_DIR: &str = "/opt/pm/live/bin";
 
 pub const LOGS_DIR: &str = "/opt/pm/logs";
 pub const KEYS_DIR: &str = "/opt/pm/keys";
+pub const QUARANTINE_DIR: &str = "/opt/pm/quarantine";
+pub const ENVS_DIR: &str = "/opt/pm/envs";
 
 pub const DB_PATH: &str = "/opt/pm/state.sqlite";
 
@@ -2,18 +2,46 @@
 
 use super::repository::Repositories;
 use serde::{Deserialize, Serialize};
-use sps2_types::{ColorChoice, OutputFormat};
+use sps2_types::{ColorChoice, ConflictPolicy, EllipsisPolicy, LinkStrategy, OutputFormat};
 use std::path::PathBuf;
 
 /// General application configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // independent behavior switches
 pub struct GeneralConfig {
     #[serde(default = "default_output_format")]
     pub default_output: OutputFormat,
     #[serde(default = "default_color_choice")]
     pub color: ColorChoice,
+    /// How long table cells are shortened to fit the terminal
+    #[serde(default)]
+    pub ellipsis: EllipsisPolicy,
     #[serde(default = "default_parallel_downloads")]
     pub parallel_downloads: usize,
+    /// Apply installs, upgrades, removals and rollbacks without asking for
+    /// confirmation
+    #[serde(default)]
+    pub assume_yes: bool,
+    /// Drop progress updates and debug messages while this many events wait
+    /// to be shown; unlimited when unset
+    #[serde(default)]
+    pub event_queue_limit: Option<usize>,
+    /// Stamp events with the hostname, sps2 version, OS build and active
+    /// state, for collecting event streams from many machines
+    #[serde(default)]
+    pub event_host_context: bool,
+    /// Install the packages that installed packages recommend
+    #[serde(default = "default_install_recommends")]
+    pub install_recommends: bool,
+    /// When uninstalling or upgrading, also remove packages installed only
+    /// as dependencies or recommendations that nothing installed needs or
+    /// recommends any more
+    #[serde(default)]
+    pub autoremove: bool,
+    /// What to do when installed packages would place different files at
+    /// the same path
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
 }
 
 impl Default for GeneralConfig {
@@ -21,13 +49,21 @@ impl Default for GeneralConfig {
         Self {
             default_output: OutputFormat::Tty,
             color: ColorChoice::Auto,
+            ellipsis: EllipsisPolicy::default(),
             parallel_downloads: 4,
+            assume_yes: false,
+            event_queue_limit: None,
+            event_host_context: false,
+            install_recommends: true,
+            autoremove: false,
+            conflict_policy: ConflictPolicy::default(),
         }
     }
 }
 
 /// Security configuration shared across crates
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[allow(clippy::struct_excessive_bools)] // independent verification and trust switches
 pub struct SecurityConfig {
     #[serde(default = "default_verify_signatures")]
     pub verify_signatures: bool,
@@ -35,6 +71,17 @@ pub struct SecurityConfig {
     pub allow_unsigned: bool,
     #[serde(default = "default_index_max_age_days")]
     pub index_max_age_days: u32,
+    /// Accept plain `http://` URLs in repository indexes
+    #[serde(default)]
+    pub allow_insecure_index_urls: bool,
+    /// Remove `com.apple.quarantine` from executables of packages signed by
+    /// a trusted key, so Gatekeeper does not prompt on first run
+    #[serde(default = "default_strip_quarantine")]
+    pub strip_quarantine: bool,
+    /// Keep the setuid and setgid bits packages give their files; without
+    /// it they are installed without them
+    #[serde(default)]
+    pub allow_setuid: bool,
 }
 
 impl Default for SecurityConfig {
@@ -43,6 +90,9 @@ impl Default for SecurityConfig {
             verify_signatures: true,
             allow_unsigned: false,
             index_max_age_days: 7,
+            allow_insecure_index_urls: false,
+            strip_quarantine: true,
+            allow_setuid: false,
         }
     }
 }
@@ -56,6 +106,10 @@ pub struct StateConfig {
     pub retention_days: u32,
     #[serde(default = "default_history_verify_limit")]
     pub history_verify_limit: usize,
+    /// Take a local APFS snapshot before committing a transition that
+    /// changes at least this many packages; unset never takes one
+    #[serde(default)]
+    pub os_snapshot_min_changes: Option<usize>,
 }
 
 impl Default for StateConfig {
@@ -64,6 +118,7 @@ impl Default for StateConfig {
             retention_count: 10, // Keep last 10 states
             retention_days: 30,  // Or 30 days, whichever is less
             history_verify_limit: default_history_verify_limit(),
+            os_snapshot_min_changes: None,
         }
     }
 }
@@ -71,9 +126,15 @@ impl Default for StateConfig {
 /// Path configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct PathConfig {
+    /// Alternate root the whole installation lives under, e.g. a mounted
+    /// volume or a CI test root; the fixed prefix is placed inside it
+    pub root: Option<PathBuf>,
     pub store_path: Option<PathBuf>,
     pub state_path: Option<PathBuf>,
     pub build_path: Option<PathBuf>,
+    /// Temporary files of store ingestion, downloads and builds; keep it on
+    /// the store's volume so results can be linked or renamed into place
+    pub tmp_path: Option<PathBuf>,
 }
 
 /// CAS cleanup/retention configuration
@@ -89,6 +150,20 @@ pub struct CasConfig {
     pub object_grace_days: u32,
     #[serde(default)]
     pub dry_run: bool,
+    /// How store files are placed into state slots
+    #[serde(default)]
+    pub link_strategy: LinkStrategy,
+    /// Age after which cleanup removes entries left in the temp area, e.g.
+    /// by an interrupted operation
+    #[serde(default = "default_tmp_max_age_hours")]
+    pub tmp_max_age_hours: u32,
+    /// Days a verified package download is kept in the download cache after
+    /// its last use; 0 disables the cache
+    #[serde(default = "default_downloads_keep_days")]
+    pub downloads_keep_days: u32,
+    /// Size the download cache is pruned down to, least recently used first
+    #[serde(default = "default_downloads_max_mb")]
+    pub downloads_max_mb: u64,
 }
 
 impl Default for CasConfig {
@@ -99,6 +174,10 @@ impl Default for CasConfig {
             package_grace_days: default_package_grace_days(),
             object_grace_days: default_object_grace_days(),
             dry_run: false,
+            link_strategy: LinkStrategy::default(),
+            tmp_max_age_hours: default_tmp_max_age_hours(),
+            downloads_keep_days: default_downloads_keep_days(),
+            downloads_max_mb: default_downloads_max_mb(),
         }
     }
 }
@@ -119,6 +198,42 @@ pub struct NetworkConfig {
     pub retries: u32,
     #[serde(default = "default_retry_delay")]
     pub retry_delay: u64, // seconds
+    /// Longest wait a server may request with `Retry-After` before a
+    /// rate-limited request fails instead
+    #[serde(default = "default_max_retry_after")]
+    pub max_retry_after: u64, // seconds
+    /// Resolve and install only from the local index cache and store
+    #[serde(default)]
+    pub offline: bool,
+    /// Explicit HTTP(S) proxy settings
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+}
+
+/// HTTP(S) proxy configuration
+///
+/// When no proxy is configured the standard `HTTP_PROXY`, `HTTPS_PROXY` and
+/// `NO_PROXY` environment variables are honoured. Configuring either proxy
+/// here replaces the environment settings entirely.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    /// Proxy URL for plain HTTP requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http: Option<String>,
+    /// Proxy URL for HTTPS requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub https: Option<String>,
+    /// Hosts, domains or CIDR ranges that bypass the proxy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Whether any proxy is explicitly configured
+    #[must_use]
+    pub fn is_configured(&self) -> bool {
+        self.http.is_some() || self.https.is_some()
+    }
 }
 
 impl Default for NetworkConfig {
@@ -127,6 +242,9 @@ impl Default for NetworkConfig {
             timeout: 300, // 5 minutes
             retries: 3,
             retry_delay: 1, // 1 second
+            max_retry_after: 60,
+            offline: false,
+            proxy: ProxyConfig::default(),
         }
     }
 }
@@ -144,6 +262,10 @@ fn default_parallel_downloads() -> usize {
     4
 }
 
+fn default_install_recommends() -> bool {
+    true
+}
+
 fn default_verify_signatures() -> bool {
     true
 }
@@ -152,6 +274,10 @@ fn default_allow_unsigned() -> bool {
     false
 }
 
+fn default_strip_quarantine() -> bool {
+    true
+}
+
 fn default_index_max_age_days() -> u32 {
     7
 }
@@ -176,6 +302,10 @@ fn default_retry_delay() -> u64 {
     1 // 1 second
 }
 
+fn default_max_retry_after() -> u64 {
+    60
+}
+
 fn default_package_grace_days() -> u32 {
     7
 }
@@ -184,6 +314,18 @@ fn default_object_grace_days() -> u32 {
     7
 }
 
+fn default_tmp_max_age_hours() -> u32 {
+    24
+}
+
+fn default_downloads_keep_days() -> u32 {
+    30
+}
+
+fn default_downloads_max_mb() -> u64 {
+    2048
+}
+
 fn default_history_verify_limit() -> usize {
     20
 }
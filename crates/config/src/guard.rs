@@ -1,6 +1,8 @@
 //! Guard configuration for verification and integrity checking
 
 use serde::{Deserialize, Serialize};
+use sps2_types::ManagedDirectory;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
 /// Symlink handling policy for guard operations
@@ -177,6 +179,46 @@ impl Default for GuardPerformanceConfig {
     }
 }
 
+/// Policy for verifications sps2 runs on its own
+///
+/// A due verification runs after the command that noticed it and reports
+/// discrepancies as warnings; it never heals. Any recorded verification run,
+/// including a manual `sps2 verify`, counts as the last verification.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardScheduleConfig {
+    /// Verify once after each boot
+    #[serde(default)]
+    pub verify_on_boot: bool,
+    /// Verify after every install, update or upgrade that changed packages
+    #[serde(default)]
+    pub verify_after_install: bool,
+    /// Verify when the last verification is this many days old (0 disables)
+    #[serde(default)]
+    pub interval_days: u32,
+    /// Level of scheduled verifications
+    #[serde(default = "default_schedule_level")]
+    pub level: String, // "quick", "standard", or "full"
+}
+
+impl Default for GuardScheduleConfig {
+    fn default() -> Self {
+        Self {
+            verify_on_boot: false,
+            verify_after_install: false,
+            interval_days: 0,
+            level: default_schedule_level(),
+        }
+    }
+}
+
+impl GuardScheduleConfig {
+    /// Whether any trigger is enabled
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.verify_on_boot || self.verify_after_install || self.interval_days > 0
+    }
+}
+
 /// Directory configuration for lenient symlink handling (array of tables approach)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GuardDirectoryConfig {
@@ -247,6 +289,21 @@ pub struct GuardConfiguration {
     pub store_verification: StoreVerificationConfig,
     #[serde(default = "default_guard_lenient_symlink_directories")]
     pub lenient_symlink_directories: Vec<GuardDirectoryConfig>,
+    /// Globs, relative to the live prefix, that verification and orphan
+    /// handling skip for every package
+    #[serde(default = "default_guard_ignore")]
+    pub ignore: Vec<String>,
+    /// Further globs by package name, skipped for that package's files and
+    /// for untracked files while the package is installed
+    #[serde(default)]
+    pub package_ignore: BTreeMap<String, Vec<String>>,
+    /// When verification runs without being asked for
+    #[serde(default)]
+    pub schedule: GuardScheduleConfig,
+    /// Directories outside the live prefix that hold copies of part of it,
+    /// populated after each state change and covered by verification
+    #[serde(default)]
+    pub managed_directories: Vec<ManagedDirectory>,
 
     // Legacy compatibility fields - deprecated
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -270,6 +327,10 @@ impl Default for GuardConfiguration {
             performance: GuardPerformanceConfig::default(),
             store_verification: StoreVerificationConfig::default(),
             lenient_symlink_directories: default_guard_lenient_symlink_directories(),
+            ignore: default_guard_ignore(),
+            package_ignore: BTreeMap::new(),
+            schedule: GuardScheduleConfig::default(),
+            managed_directories: Vec::new(),
             auto_heal: None,
             fail_on_discrepancy: None,
             preserve_user_files: None,
@@ -345,6 +406,15 @@ fn default_guard_lenient_symlink_directories() -> Vec<GuardDirectoryConfig> {
     ]
 }
 
+fn default_guard_ignore() -> Vec<String> {
+    // Python writes bytecode caches next to the sources it imports
+    vec!["**/__pycache__/**".to_string(), "**/*.pyc".to_string()]
+}
+
+fn default_schedule_level() -> String {
+    "quick".to_string()
+}
+
 fn default_lenient_symlink_directories() -> Vec<PathBuf> {
     vec![
         PathBuf::from("/opt/pm/live/bin"),
@@ -19,26 +19,32 @@ pub mod repository;
 pub mod resources_limits;
 pub mod resources_manager;
 pub mod resources_semaphore;
+pub mod theme;
 
 // Re-export main types for convenience
 pub use builder::BuilderConfig;
 pub use constants as fixed_paths;
-pub use core::{GeneralConfig, NetworkConfig, PathConfig, SecurityConfig, StateConfig};
+pub use core::{
+    GeneralConfig, NetworkConfig, PathConfig, ProxyConfig, SecurityConfig, StateConfig,
+};
 pub use guard::{
     DiscrepancyHandling, GuardConfiguration, GuardDirectoryConfig, GuardPerformanceConfig,
-    GuardSymlinkPolicy, PerformanceConfigToml, SymlinkPolicyConfig, UserFilePolicy,
-    VerificationConfig,
+    GuardScheduleConfig, GuardSymlinkPolicy, PerformanceConfigToml, SymlinkPolicyConfig,
+    UserFilePolicy, VerificationConfig,
 };
 pub use repository::{Repositories, RepositoryConfig};
-pub use resources_limits::{IntoResourceLimits, ResourceAvailability, ResourceLimits};
+pub use resources_limits::{
+    IntoResourceLimits, MemoryPressure, ResourceAvailability, ResourceLimits,
+};
 pub use resources_manager::ResourceManager;
 pub use resources_semaphore::{
     acquire_semaphore_permit, create_semaphore, try_acquire_semaphore_permit,
 };
+pub use theme::{StyleSpec, ThemeColor, ThemeConfig, ThemePreset, ThemeRole};
 
 use serde::{Deserialize, Serialize};
 use sps2_errors::{ConfigError, Error};
-use sps2_types::{ColorChoice, OutputFormat};
+use sps2_types::{ColorChoice, EllipsisPolicy, LinkStrategy, ManagedDirectory, OutputFormat};
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
@@ -63,6 +69,10 @@ pub struct Config {
     #[serde(default)]
     pub verification: VerificationConfig,
 
+    /// Terminal color theme
+    #[serde(default)]
+    pub theme: ThemeConfig,
+
     #[serde(default)]
     pub guard: Option<GuardConfiguration>,
 
@@ -74,9 +84,13 @@ pub struct Config {
     #[serde(default)]
     pub repos: repository::Repositories,
 
-    /// Content-addressable store cleanup policy
+    /// Content-addressable store cleanup and linking policy
     #[serde(default)]
     pub cas: core::CasConfig,
+
+    /// File the configuration was loaded from
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
 }
 
 impl Config {
@@ -92,6 +106,17 @@ impl Config {
         Ok(home_dir.join(".config").join("sps2").join("config.toml"))
     }
 
+    /// Get the default config file path under an alternate root
+    ///
+    /// Without a root this is [`Config::default_path`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the home directory cannot be determined.
+    pub fn default_path_in(root: Option<&Path>) -> Result<PathBuf, Error> {
+        Ok(under_root(root, Self::default_path()?))
+    }
+
     /// Load configuration from file
     ///
     /// # Errors
@@ -99,6 +124,13 @@ impl Config {
     /// Returns an error if the file cannot be read or if the file contents
     /// contain invalid TOML syntax that cannot be parsed.
     pub async fn load_from_file(path: &Path) -> Result<Self, Error> {
+        let mut config = Self::read(path).await?;
+        config.builder = BuilderConfig::load().await?;
+        Ok(config)
+    }
+
+    /// Parse the file at `path`, without the builder config
+    async fn read(path: &Path) -> Result<Self, Error> {
         let contents = fs::read_to_string(path)
             .await
             .map_err(|_| ConfigError::NotFound {
@@ -108,10 +140,23 @@ impl Config {
         let mut config: Self = toml::from_str(&contents).map_err(|e| ConfigError::ParseError {
             message: e.to_string(),
         })?;
+        config.file = Some(path.to_path_buf());
+        Ok(config)
+    }
 
-        // Load builder config
-        config.builder = BuilderConfig::load().await?;
-
+    /// Parse the file at `path`, or write it with defaults when it does not
+    /// exist yet; the builder config is not loaded
+    async fn read_or_create(path: &Path) -> Result<Self, Error> {
+        if path.exists() {
+            return Self::read(path).await;
+        }
+        let config = Self {
+            file: Some(path.to_path_buf()),
+            ..Self::default()
+        };
+        if let Err(e) = config.save_to(path).await {
+            tracing::warn!("Failed to save default config: {}", e);
+        }
         Ok(config)
     }
 
@@ -125,19 +170,8 @@ impl Config {
         path: &Path,
         builder_path: &Option<PathBuf>,
     ) -> Result<Self, Error> {
-        let contents = fs::read_to_string(path)
-            .await
-            .map_err(|_| ConfigError::NotFound {
-                path: path.display().to_string(),
-            })?;
-
-        let mut config: Self = toml::from_str(&contents).map_err(|e| ConfigError::ParseError {
-            message: e.to_string(),
-        })?;
-
-        // Load builder config
+        let mut config = Self::read(path).await?;
         config.builder = BuilderConfig::load_or_default(builder_path).await?;
-
         Ok(config)
     }
 
@@ -150,21 +184,51 @@ impl Config {
     /// Returns an error if the configuration file exists but cannot be read
     /// or contains invalid TOML syntax.
     pub async fn load() -> Result<Self, Error> {
-        let config_path = Self::default_path()?;
+        Self::load_in(None).await
+    }
 
-        if config_path.exists() {
-            Self::load_from_file(&config_path).await
+    /// Load configuration from the default locations under an alternate
+    /// root, creating missing files with defaults
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a configuration file exists but cannot be read
+    /// or contains invalid TOML syntax.
+    pub async fn load_in(root: Option<&Path>) -> Result<Self, Error> {
+        let mut config = Self::read_or_create(&Self::default_path_in(root)?).await?;
+        config.builder = BuilderConfig::load_in(root).await?;
+        Ok(config)
+    }
+
+    /// Load the configuration file at `path` for editing, or start from the
+    /// defaults when it does not exist yet
+    ///
+    /// Nothing is written; save the result with [`Config::save_to`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file exists but cannot be read or parsed.
+    pub async fn load_or_create(path: &Path) -> Result<Self, Error> {
+        if fs::try_exists(path).await.unwrap_or(false) {
+            Self::load_from_file(path).await
         } else {
-            // Create default config and save it
-            let builder = BuilderConfig::load().await?;
-            let config = Self {
-                builder,
+            Ok(Self {
+                file: Some(path.to_path_buf()),
                 ..Self::default()
-            };
-            if let Err(e) = config.save().await {
-                tracing::warn!("Failed to save default config: {}", e);
-            }
-            Ok(config)
+            })
+        }
+    }
+
+    /// File the configuration was loaded from, or the default location
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the configuration was not loaded from a file and
+    /// the home directory cannot be determined.
+    pub fn file_path(&self) -> Result<PathBuf, Error> {
+        match &self.file {
+            Some(path) => Ok(path.clone()),
+            None => Self::default_path(),
         }
     }
 
@@ -187,7 +251,8 @@ impl Config {
     ///
     /// If `config_path` is provided, loads from that file.
     /// If `builder_path` is provided, loads builder config from that file.
-    /// If paths are None, uses the default loading behavior.
+    /// Otherwise the default locations under `root` are used, and created
+    /// with defaults when missing.
     ///
     /// # Errors
     ///
@@ -195,17 +260,17 @@ impl Config {
     pub async fn load_or_default_with_builder(
         config_path: &Option<std::path::PathBuf>,
         builder_path: &Option<std::path::PathBuf>,
+        root: Option<&Path>,
     ) -> Result<Self, Error> {
-        if let Some(path) = config_path {
-            Self::load_from_file_with_builder(path, builder_path).await
-        } else {
-            let mut config = Self::load().await?;
-            // Override builder config if custom path provided
-            if builder_path.is_some() {
-                config.builder = BuilderConfig::load_or_default(builder_path).await?;
-            }
-            Ok(config)
-        }
+        let mut config = match config_path {
+            Some(path) => Self::read(path).await?,
+            None => Self::read_or_create(&Self::default_path_in(root)?).await?,
+        };
+        config.builder = match builder_path {
+            Some(path) => BuilderConfig::load_from_file(path).await?,
+            None => BuilderConfig::load_in(root).await?,
+        };
+        Ok(config)
     }
 
     /// Merge with environment variables
@@ -247,6 +312,27 @@ impl Config {
             };
         }
 
+        // SPS2_ROOT
+        if let Ok(root) = std::env::var("SPS2_ROOT") {
+            self.paths.root = Some(PathBuf::from(root)).filter(|p| !p.as_os_str().is_empty());
+        }
+
+        // SPS2_ELLIPSIS
+        if let Ok(ellipsis) = std::env::var("SPS2_ELLIPSIS") {
+            self.general.ellipsis = match ellipsis.as_str() {
+                "end" => EllipsisPolicy::End,
+                "middle" => EllipsisPolicy::Middle,
+                "off" => EllipsisPolicy::Off,
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        field: "SPS2_ELLIPSIS".to_string(),
+                        value: ellipsis,
+                    }
+                    .into())
+                }
+            };
+        }
+
         // SPS2_BUILD_JOBS
         if let Ok(jobs) = std::env::var("SPS2_BUILD_JOBS") {
             self.builder.build.build_jobs =
@@ -258,6 +344,37 @@ impl Config {
 
         // SPS2_NETWORK_ACCESS removed - network access comes from recipe, not config
 
+        // SPS2_OFFLINE
+        if let Ok(v) = std::env::var("SPS2_OFFLINE") {
+            self.network.offline = match v.to_ascii_lowercase().as_str() {
+                "1" | "true" | "yes" => true,
+                "0" | "false" | "no" => false,
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        field: "SPS2_OFFLINE".to_string(),
+                        value: v,
+                    }
+                    .into())
+                }
+            };
+        }
+
+        // SPS2_HTTP_PROXY / SPS2_HTTPS_PROXY / SPS2_NO_PROXY
+        if let Ok(proxy) = std::env::var("SPS2_HTTP_PROXY") {
+            self.network.proxy.http = Some(proxy).filter(|p| !p.is_empty());
+        }
+        if let Ok(proxy) = std::env::var("SPS2_HTTPS_PROXY") {
+            self.network.proxy.https = Some(proxy).filter(|p| !p.is_empty());
+        }
+        if let Ok(hosts) = std::env::var("SPS2_NO_PROXY") {
+            self.network.proxy.no_proxy = hosts
+                .split(',')
+                .map(str::trim)
+                .filter(|h| !h.is_empty())
+                .map(str::to_string)
+                .collect();
+        }
+
         // SPS2_PARALLEL_DOWNLOADS
         if let Ok(downloads) = std::env::var("SPS2_PARALLEL_DOWNLOADS") {
             self.general.parallel_downloads =
@@ -267,6 +384,28 @@ impl Config {
                 })?;
         }
 
+        self.merge_cas_env()
+    }
+
+    /// Apply `[cas]` overrides from the environment
+    fn merge_cas_env(&mut self) -> Result<(), Error> {
+        // SPS2_LINK_STRATEGY
+        if let Ok(strategy) = std::env::var("SPS2_LINK_STRATEGY") {
+            self.cas.link_strategy = match strategy.as_str() {
+                "auto" => LinkStrategy::Auto,
+                "clonefile" => LinkStrategy::Clonefile,
+                "hardlink" => LinkStrategy::Hardlink,
+                "copy" => LinkStrategy::Copy,
+                _ => {
+                    return Err(ConfigError::InvalidValue {
+                        field: "SPS2_LINK_STRATEGY".to_string(),
+                        value: strategy,
+                    }
+                    .into())
+                }
+            };
+        }
+
         // Optional CAS env overrides (best-effort; ignore if invalid)
         if let Ok(v) = std::env::var("SPS2_CAS_KEEP_STATES") {
             if let Ok(n) = v.parse() {
@@ -288,6 +427,16 @@ impl Config {
                 self.cas.object_grace_days = n;
             }
         }
+        if let Ok(v) = std::env::var("SPS2_CAS_DOWNLOADS_KEEP_DAYS") {
+            if let Ok(n) = v.parse() {
+                self.cas.downloads_keep_days = n;
+            }
+        }
+        if let Ok(v) = std::env::var("SPS2_CAS_DOWNLOADS_MAX_MB") {
+            if let Ok(n) = v.parse() {
+                self.cas.downloads_max_mb = n;
+            }
+        }
         if let Ok(v) = std::env::var("SPS2_CAS_DRY_RUN") {
             self.cas.dry_run = matches!(v.as_str(), "1" | "true" | "yes");
         }
@@ -295,13 +444,27 @@ impl Config {
         Ok(())
     }
 
+    /// Place one of the fixed paths under the configured root
+    ///
+    /// Without a root the fixed path is used as is.
+    #[must_use]
+    pub fn rooted(&self, fixed: &str) -> PathBuf {
+        under_root(self.paths.root.as_deref(), PathBuf::from(fixed))
+    }
+
+    /// Get the installation prefix holding the state database, states and live root
+    #[must_use]
+    pub fn prefix_path(&self) -> PathBuf {
+        self.rooted(crate::constants::PREFIX)
+    }
+
     /// Get the store path (with default)
     #[must_use]
     pub fn store_path(&self) -> PathBuf {
         self.paths
             .store_path
             .clone()
-            .unwrap_or_else(|| PathBuf::from(crate::constants::STORE_DIR))
+            .unwrap_or_else(|| self.rooted(crate::constants::STORE_DIR))
     }
 
     /// Get the state path (with default)
@@ -310,7 +473,27 @@ impl Config {
         self.paths
             .state_path
             .clone()
-            .unwrap_or_else(|| PathBuf::from(crate::constants::STATES_DIR))
+            .unwrap_or_else(|| self.rooted(crate::constants::STATES_DIR))
+    }
+
+    /// Get the temp area path (with default)
+    ///
+    /// Defaults to `tmp` inside the store, so temporary files share the
+    /// store's volume.
+    #[must_use]
+    pub fn tmp_path(&self) -> PathBuf {
+        self.paths
+            .tmp_path
+            .clone()
+            .unwrap_or_else(|| self.store_path().join("tmp"))
+    }
+
+    /// Get the directory verified package downloads are cached in
+    ///
+    /// It lives inside the store, so cached archives share its volume.
+    #[must_use]
+    pub fn downloads_path(&self) -> PathBuf {
+        self.store_path().join("downloads")
     }
 
     /// Get the build path (with default)
@@ -325,13 +508,49 @@ impl Config {
     /// Get the live root path
     #[must_use]
     pub fn live_path(&self) -> PathBuf {
-        PathBuf::from(crate::constants::LIVE_DIR)
+        self.rooted(crate::constants::LIVE_DIR)
+    }
+
+    /// Get the directory of executables in the live root
+    #[must_use]
+    pub fn bin_path(&self) -> PathBuf {
+        self.rooted(crate::constants::BIN_DIR)
     }
 
     /// Get the database path
     #[must_use]
     pub fn db_path(&self) -> PathBuf {
-        PathBuf::from(crate::constants::DB_PATH)
+        self.rooted(crate::constants::DB_PATH)
+    }
+
+    /// Get the log directory
+    #[must_use]
+    pub fn logs_path(&self) -> PathBuf {
+        self.rooted(crate::constants::LOGS_DIR)
+    }
+
+    /// Get the directory holding the trusted keys
+    #[must_use]
+    pub fn keys_path(&self) -> PathBuf {
+        self.rooted(crate::constants::KEYS_DIR)
+    }
+
+    /// Get the directory corrupted downloads are moved to
+    #[must_use]
+    pub fn quarantine_path(&self) -> PathBuf {
+        self.rooted(crate::constants::QUARANTINE_DIR)
+    }
+
+    /// Get the directory holding the environments of projects
+    #[must_use]
+    pub fn envs_path(&self) -> PathBuf {
+        self.rooted(crate::constants::ENVS_DIR)
+    }
+
+    /// Get the file recording when garbage collection last ran
+    #[must_use]
+    pub fn last_gc_timestamp_path(&self) -> PathBuf {
+        self.rooted(crate::constants::LAST_GC_TIMESTAMP)
     }
 
     /// Save configuration to the default location
@@ -378,13 +597,18 @@ impl Config {
              {toml_string}"
         );
 
-        // Write to file
-        fs::write(path, content)
-            .await
-            .map_err(|e| ConfigError::WriteError {
-                path: path.display().to_string(),
-                error: e.to_string(),
-            })?;
+        // Write a sibling temp file and rename it over the target so readers
+        // never observe a partially written config
+        let tmp_path = path.with_extension("toml.tmp");
+        let write_error = |e: std::io::Error| ConfigError::WriteError {
+            path: path.display().to_string(),
+            error: e.to_string(),
+        };
+        fs::write(&tmp_path, content).await.map_err(write_error)?;
+        if let Err(e) = fs::rename(&tmp_path, path).await {
+            let _ = fs::remove_file(&tmp_path).await;
+            return Err(write_error(e).into());
+        }
 
         Ok(())
     }
@@ -416,7 +640,7 @@ impl Config {
         self.validate_verification_config()?;
 
         if let Some(guard_config) = &self.guard {
-            Self::validate_top_level_guard_config(guard_config)?;
+            Self::validate_top_level_guard_config(guard_config, &self.prefix_path())?;
         }
 
         Ok(())
@@ -439,7 +663,10 @@ impl Config {
         Ok(())
     }
 
-    fn validate_top_level_guard_config(guard_config: &GuardConfiguration) -> Result<(), Error> {
+    fn validate_top_level_guard_config(
+        guard_config: &GuardConfiguration,
+        prefix: &Path,
+    ) -> Result<(), Error> {
         Self::validate_verification_level(
             &guard_config.verification_level,
             "guard.verification_level",
@@ -453,6 +680,16 @@ impl Config {
             &guard_config.lenient_symlink_directories,
             "guard.lenient_symlink_directories",
         )?;
+        Self::validate_guard_ignore(&guard_config.ignore, "guard.ignore")?;
+        for (package, patterns) in &guard_config.package_ignore {
+            Self::validate_guard_ignore(patterns, &format!("guard.package_ignore.{package}"))?;
+        }
+        Self::validate_verification_level(&guard_config.schedule.level, "guard.schedule.level")?;
+        Self::validate_managed_directories(
+            &guard_config.managed_directories,
+            prefix,
+            "guard.managed_directories",
+        )?;
         Ok(())
     }
 
@@ -537,6 +774,67 @@ impl Config {
         Ok(())
     }
 
+    /// Ignore globs match paths relative to the live prefix
+    fn validate_guard_ignore(patterns: &[String], field_name: &str) -> Result<(), Error> {
+        for pattern in patterns {
+            if pattern.is_empty() || pattern.starts_with('/') {
+                return Err(ConfigError::InvalidValue {
+                    field: field_name.to_string(),
+                    value: pattern.clone(),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Managed directories need an absolute path of their own, apart from
+    /// `prefix`, and a source directory inside the live prefix
+    ///
+    /// Verification may remove what it finds in a managed directory, so `/`
+    /// and directories holding or inside the prefix are refused, as is a
+    /// source naming the whole live prefix.
+    fn validate_managed_directories(
+        dirs: &[ManagedDirectory],
+        prefix: &Path,
+        field_name: &str,
+    ) -> Result<(), Error> {
+        use std::path::Component;
+
+        for (index, dir) in dirs.iter().enumerate() {
+            let source = Path::new(&dir.source);
+            let invalid = if !dir.path.is_absolute()
+                || dir.path.parent().is_none()
+                || dir
+                    .path
+                    .components()
+                    .any(|c| matches!(c, Component::ParentDir | Component::CurDir))
+                || dir.path.starts_with(prefix)
+                || prefix.starts_with(&dir.path)
+                || dirs[..index].iter().any(|other| other.path == dir.path)
+            {
+                Some(dir.path.display().to_string())
+            } else if source.is_absolute()
+                || source.components().any(|c| c == Component::ParentDir)
+                || !source
+                    .components()
+                    .any(|c| matches!(c, Component::Normal(_)))
+            {
+                Some(dir.source.clone())
+            } else {
+                None
+            };
+            if let Some(value) = invalid {
+                return Err(ConfigError::InvalidValue {
+                    field: field_name.to_string(),
+                    value,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
     fn validate_guard_symlink_directories(
         dirs: &[guard::GuardDirectoryConfig],
         field_name: &str,
@@ -554,6 +852,14 @@ impl Config {
     }
 }
 
+/// `path` placed under `root`, or as is without a root
+pub(crate) fn under_root(root: Option<&Path>, path: PathBuf) -> PathBuf {
+    match root {
+        Some(root) => root.join(path.strip_prefix("/").unwrap_or(&path)),
+        None => path,
+    }
+}
+
 /// Calculate build jobs based on CPU count
 #[must_use]
 pub fn calculate_build_jobs(config_value: usize) -> usize {
@@ -568,3 +874,130 @@ pub fn calculate_build_jobs(config_value: usize) -> usize {
         (cpus * 3 / 4).max(1)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rooted_at(root: &str) -> Config {
+        let mut config = Config::default();
+        config.paths.root = Some(PathBuf::from(root));
+        config
+    }
+
+    #[test]
+    fn fixed_paths_are_placed_under_the_root() {
+        let config = rooted_at("/Volumes/test");
+        assert_eq!(
+            config.rooted("/opt/pm/live"),
+            Path::new("/Volumes/test/opt/pm/live")
+        );
+        assert_eq!(
+            Config::default().rooted("/opt/pm/live"),
+            Path::new("/opt/pm/live")
+        );
+    }
+
+    #[test]
+    fn installation_paths_follow_the_root() {
+        let config = rooted_at("/r");
+        assert_eq!(config.prefix_path(), Path::new("/r/opt/pm"));
+        assert_eq!(config.live_path(), Path::new("/r/opt/pm/live"));
+        assert_eq!(config.bin_path(), Path::new("/r/opt/pm/live/bin"));
+        assert_eq!(config.store_path(), Path::new("/r/opt/pm/store"));
+        assert_eq!(config.state_path(), Path::new("/r/opt/pm/states"));
+        assert_eq!(config.db_path(), Path::new("/r/opt/pm/state.sqlite"));
+        assert_eq!(config.logs_path(), Path::new("/r/opt/pm/logs"));
+        assert_eq!(config.keys_path(), Path::new("/r/opt/pm/keys"));
+        assert_eq!(config.envs_path(), Path::new("/r/opt/pm/envs"));
+        assert_eq!(config.quarantine_path(), Path::new("/r/opt/pm/quarantine"));
+        assert_eq!(
+            config.last_gc_timestamp_path(),
+            Path::new("/r/opt/pm/.last_gc_timestamp")
+        );
+    }
+
+    #[test]
+    fn configured_paths_are_not_rerooted() {
+        let mut config = rooted_at("/r");
+        config.paths.store_path = Some(PathBuf::from("/Volumes/fast/store"));
+        assert_eq!(config.store_path(), Path::new("/Volumes/fast/store"));
+        assert_eq!(config.tmp_path(), Path::new("/Volumes/fast/store/tmp"));
+    }
+
+    #[test]
+    fn config_files_follow_the_root() {
+        let home = Config::default_path().unwrap();
+        let rooted = Config::default_path_in(Some(Path::new("/r"))).unwrap();
+        assert_eq!(
+            rooted,
+            Path::new("/r").join(home.strip_prefix("/").unwrap())
+        );
+        assert_eq!(Config::default_path_in(None).unwrap(), home);
+
+        let builder = BuilderConfig::default_path_in(Some(Path::new("/r"))).unwrap();
+        assert!(builder.starts_with("/r"));
+        assert!(builder.ends_with(".config/sps2/builder.config.toml"));
+    }
+
+    #[tokio::test]
+    async fn loading_under_a_root_writes_the_defaults_there() {
+        let root = tempfile::tempdir().unwrap();
+        let config = Config::load_or_default_with_builder(&None, &None, Some(root.path()))
+            .await
+            .unwrap();
+        let file = config.file.unwrap();
+        assert!(file.starts_with(root.path()));
+        assert!(file.exists());
+        assert!(BuilderConfig::default_path_in(Some(root.path()))
+            .unwrap()
+            .exists());
+    }
+
+    fn with_managed_directory(config: &mut Config, path: &str, source: &str) {
+        config.guard = Some(GuardConfiguration {
+            managed_directories: vec![ManagedDirectory {
+                path: PathBuf::from(path),
+                source: source.to_string(),
+                populate: true,
+                drift: sps2_types::ManagedDriftPolicy::default(),
+                untracked: sps2_types::ManagedUntrackedPolicy::default(),
+            }],
+            ..GuardConfiguration::default()
+        });
+    }
+
+    #[test]
+    fn managed_directories_stay_apart_from_the_prefix() {
+        let valid = |config: &mut Config, path: &str, source: &str| {
+            with_managed_directory(config, path, source);
+            config.validate_guard_config().is_ok()
+        };
+        let mut config = Config::default();
+        assert!(valid(&mut config, "/Library/Fonts/sps2", "share/fonts"));
+        assert!(valid(&mut config, "/Library/Fonts/sps2", "./share/fonts"));
+
+        for path in [
+            "/",
+            "/opt",
+            "/opt/pm",
+            "/opt/pm/live/share",
+            "/Library/../opt/pm",
+            "Library/Fonts",
+        ] {
+            assert!(!valid(&mut config, path, "share/fonts"), "{path}");
+        }
+        for source in ["", ".", "/opt/pm/live/share", "share/../.."] {
+            assert!(
+                !valid(&mut config, "/Library/Fonts/sps2", source),
+                "{source}"
+            );
+        }
+
+        // The prefix moves with the root
+        let mut rooted = rooted_at("/Volumes/test");
+        assert!(valid(&mut rooted, "/opt/pm/live/share", "share/fonts"));
+        assert!(!valid(&mut rooted, "/Volumes/test/opt/pm", "share/fonts"));
+        assert!(!valid(&mut rooted, "/Volumes", "share/fonts"));
+    }
+}
@@ -9,6 +9,13 @@ pub struct RepositoryConfig {
     pub algorithm: String, // "minisign" | "openpgp" (future)
     #[serde(default)]
     pub key_ids: Vec<String>,
+    /// Mirror base URLs serving the same content as `url`, tried on failure
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors: Vec<String>,
+    /// Remote search endpoint used by `sps2 search --remote`, overriding the
+    /// one the repository's signed index declares
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_url: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -39,6 +46,17 @@ impl Repositories {
         all.extend(self.extras.values());
         all
     }
+
+    /// Repository configured under `name`: `fast`, `slow`, `stable` or an extra
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&RepositoryConfig> {
+        match name {
+            "fast" => self.fast.as_ref(),
+            "slow" => self.slow.as_ref(),
+            "stable" => self.stable.as_ref(),
+            _ => self.extras.get(name),
+        }
+    }
 }
 
 fn default_priority() -> u32 {
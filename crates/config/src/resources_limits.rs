@@ -95,3 +95,14 @@ impl ResourceAvailability {
             && self.installation >= limits.concurrent_installations
     }
 }
+
+/// Memory usage measured against the configured limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryPressure {
+    /// Comfortably below the limit, or no limit set
+    Normal,
+    /// At least 90% of the limit
+    Approaching,
+    /// Over the limit
+    Exceeded,
+}
@@ -3,12 +3,12 @@
 //! This module provides the main `ResourceManager` struct that coordinates
 //! semaphores and resource limits for concurrent operations.
 
-use crate::resources_limits::{ResourceAvailability, ResourceLimits};
+use crate::resources_limits::{MemoryPressure, ResourceAvailability, ResourceLimits};
 use crate::resources_semaphore::{
     acquire_semaphore_permit, create_semaphore, try_acquire_semaphore_permit,
 };
 use sps2_errors::Error;
-use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
@@ -112,6 +112,17 @@ impl ResourceManager {
         }
     }
 
+    /// Record the current memory usage and measure it against the limit
+    #[must_use]
+    pub fn record_memory_usage(&self, current_usage: u64) -> MemoryPressure {
+        self.memory_usage.store(current_usage, Ordering::Relaxed);
+        match self.limits.memory_usage {
+            Some(limit) if current_usage > limit => MemoryPressure::Exceeded,
+            Some(limit) if current_usage >= limit / 10 * 9 => MemoryPressure::Approaching,
+            _ => MemoryPressure::Normal,
+        }
+    }
+
     /// Get current resource availability
     #[must_use]
     pub fn get_resource_availability(&self) -> ResourceAvailability {
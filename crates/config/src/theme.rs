@@ -0,0 +1,259 @@
+//! Terminal color theme configuration
+//!
+//! A theme maps event severities and table roles to styles. A preset
+//! supplies every role; entries under `[theme.styles]` override single roles:
+//!
+//! ```toml
+//! [theme]
+//! preset = "high-contrast"
+//!
+//! [theme.styles]
+//! warning = "bright-yellow bold"
+//! header = "cyan underlined"
+//! ```
+//!
+//! A style is a space-separated list of at most one color and any number of
+//! attributes. Colors are `black`, `red`, `green`, `yellow`, `blue`,
+//! `magenta`, `cyan`, `white`, their `bright-` variants, or a 256-color
+//! palette index; attributes are `bold`, `dim`, `italic` and `underlined`.
+//! `plain` is a style without color or attributes.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Theme configuration
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    /// Preset providing the style of every role
+    #[serde(default)]
+    pub preset: ThemePreset,
+    /// Per-role overrides of the preset
+    #[serde(default)]
+    pub styles: BTreeMap<ThemeRole, StyleSpec>,
+}
+
+impl ThemeConfig {
+    /// Style for `role`: the override if configured, else the preset's
+    #[must_use]
+    pub fn style(&self, role: ThemeRole) -> StyleSpec {
+        self.styles
+            .get(&role)
+            .copied()
+            .unwrap_or_else(|| self.preset.style(role))
+    }
+}
+
+/// Built-in themes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemePreset {
+    /// The standard colors
+    #[default]
+    Default,
+    /// Bright, bold colors and no dimmed text, for low vision and
+    /// low-contrast displays
+    HighContrast,
+}
+
+impl ThemePreset {
+    /// Style this preset gives `role`
+    ///
+    /// # Panics
+    ///
+    /// Never; preset styles are valid literals.
+    #[must_use]
+    pub fn style(self, role: ThemeRole) -> StyleSpec {
+        let spec = match self {
+            Self::Default => match role {
+                ThemeRole::Debug => "cyan dim",
+                ThemeRole::Info => "blue",
+                ThemeRole::Success => "green bold",
+                ThemeRole::Warning => "yellow bold",
+                ThemeRole::Error => "red bold",
+                ThemeRole::Critical => "red bold underlined",
+                ThemeRole::Header | ThemeRole::Package => "bold",
+                ThemeRole::Installed => "bright-green",
+                ThemeRole::Outdated => "bright-yellow",
+                ThemeRole::Available => "bright-blue",
+                ThemeRole::Local => "bright-magenta",
+                ThemeRole::Current => "bright-green bold",
+                ThemeRole::Preview => "blue bold",
+            },
+            Self::HighContrast => match role {
+                ThemeRole::Debug => "white",
+                ThemeRole::Info => "bright-white",
+                ThemeRole::Success | ThemeRole::Installed | ThemeRole::Current => {
+                    "bright-green bold"
+                }
+                ThemeRole::Warning | ThemeRole::Outdated => "bright-yellow bold",
+                ThemeRole::Error => "bright-red bold",
+                ThemeRole::Critical => "bright-red bold underlined",
+                ThemeRole::Header => "bright-white bold underlined",
+                ThemeRole::Package => "bright-white bold",
+                ThemeRole::Available | ThemeRole::Preview => "bright-cyan bold",
+                ThemeRole::Local => "bright-magenta bold",
+            },
+        };
+        spec.parse().expect("valid preset style")
+    }
+}
+
+/// What a style is applied to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeRole {
+    /// Debug events
+    Debug,
+    /// Informational events
+    Info,
+    /// Successful operations, healthy components
+    Success,
+    /// Warnings
+    Warning,
+    /// Errors
+    Error,
+    /// Critical errors
+    Critical,
+    /// Table headers
+    Header,
+    /// Package name headings
+    Package,
+    /// Installed packages
+    Installed,
+    /// Installed packages with an update available
+    Outdated,
+    /// Packages available from the repository
+    Available,
+    /// Packages installed from local files
+    Local,
+    /// The active state in history
+    Current,
+    /// Check mode previews
+    Preview,
+}
+
+/// A terminal style: an optional color plus attributes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)] // one flag per independent terminal attribute
+pub struct StyleSpec {
+    pub color: Option<ThemeColor>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underlined: bool,
+}
+
+/// A foreground color
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThemeColor {
+    /// One of the eight standard colors, `0` (black) to `7` (white),
+    /// optionally in its bright variant
+    Named { index: u8, bright: bool },
+    /// An entry of the 256-color palette
+    Palette(u8),
+}
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+impl FromStr for StyleSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = Self::default();
+        for word in s.split_whitespace() {
+            match word {
+                "plain" => {}
+                "bold" => spec.bold = true,
+                "dim" => spec.dim = true,
+                "italic" => spec.italic = true,
+                "underlined" => spec.underlined = true,
+                _ => {
+                    if spec.color.is_some() {
+                        return Err(format!("more than one color in style `{s}`"));
+                    }
+                    spec.color = Some(word.parse()?);
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl fmt::Display for StyleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = Vec::new();
+        if let Some(color) = self.color {
+            words.push(color.to_string());
+        }
+        for (set, name) in [
+            (self.bold, "bold"),
+            (self.dim, "dim"),
+            (self.italic, "italic"),
+            (self.underlined, "underlined"),
+        ] {
+            if set {
+                words.push(name.to_string());
+            }
+        }
+        if words.is_empty() {
+            f.write_str("plain")
+        } else {
+            f.write_str(&words.join(" "))
+        }
+    }
+}
+
+impl FromStr for ThemeColor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(index) = s.parse::<u8>() {
+            return Ok(Self::Palette(index));
+        }
+        let (name, bright) = match s.strip_prefix("bright-") {
+            Some(name) => (name, true),
+            None => (s, false),
+        };
+        COLOR_NAMES
+            .iter()
+            .position(|&candidate| candidate == name)
+            .map(|index| Self::Named {
+                index: u8::try_from(index).expect("eight colors"),
+                bright,
+            })
+            .ok_or_else(|| format!("unknown color or attribute `{s}`"))
+    }
+}
+
+impl fmt::Display for ThemeColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Named { index, bright } => {
+                let name = COLOR_NAMES[usize::from(*index)];
+                if *bright {
+                    write!(f, "bright-{name}")
+                } else {
+                    f.write_str(name)
+                }
+            }
+            Self::Palette(index) => write!(f, "{index}"),
+        }
+    }
+}
+
+impl Serialize for StyleSpec {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for StyleSpec {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
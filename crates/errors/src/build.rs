@@ -36,6 +36,13 @@ pub enum BuildError {
     #[error("sandbox violation: {message}")]
     SandboxViolation { message: String },
 
+    #[error("sandbox denied {process} {operation} {target}")]
+    SandboxDenied {
+        process: String,
+        operation: String,
+        target: String,
+    },
+
     #[error("network access denied")]
     NetworkAccessDenied,
 
@@ -58,6 +65,13 @@ pub enum BuildError {
         timeout_seconds: u64,
     },
 
+    #[error("build of {package} exceeded its {resource} limit of {limit}")]
+    ResourceLimitExceeded {
+        package: String,
+        resource: String,
+        limit: String,
+    },
+
     #[error("extraction failed: {message}")]
     ExtractionFailed { message: String },
 
@@ -79,8 +93,11 @@ pub enum BuildError {
     #[error("compilation failed: {message}")]
     CompilationFailed { message: String },
 
-    #[error("tests failed: {passed}/{total} tests passed")]
-    TestsFailed { passed: usize, total: usize },
+    #[error("tests failed for {package}: {message}")]
+    TestsFailed { package: String, message: String },
+
+    #[error("{package} links libraries from undeclared runtime dependencies:\n{report}")]
+    UndeclaredRuntimeDeps { package: String, report: String },
 
     #[error("quality assurance failed: {message}")]
     QualityAssuranceFailed { message: String },
@@ -112,6 +129,13 @@ pub enum BuildError {
     #[error("git clone failed: {message}")]
     GitCloneFailed { message: String },
 
+    #[error("git checkout of {url} resolved to {actual}, expected commit {expected}")]
+    GitCommitMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+
     #[error("validation failed: {message}")]
     ValidationFailed { message: String },
 
@@ -169,12 +193,27 @@ impl UserFacingError for BuildError {
             Self::NetworkAccessDenied => {
                 Some("Allow network access for the build or supply pre-fetched sources.")
             }
+            Self::SandboxDenied { .. } => Some(
+                "Set `network: true` in the recipe if the build needs the network, or keep writes inside the build directory.",
+            ),
+            Self::GitCommitMismatch { .. } => Some(
+                "The ref now points to another commit. Review the change, then update the recipe's `commit`.",
+            ),
             Self::PatchFailed { .. } => {
                 Some("Update the patch so it applies cleanly to the current sources.")
             }
             Self::Timeout { .. } | Self::BuildTimeout { .. } => {
                 Some("Increase the build timeout or reduce parallelism, then retry.")
             }
+            Self::ResourceLimitExceeded { .. } => Some(
+                "Raise the limit in the recipe's `environment.limits` or the builder config, or lower `jobs`.",
+            ),
+            Self::TestsFailed { .. } => Some(
+                "Fix the failing tests, or set `test.on_failure: warn` in the recipe to keep building.",
+            ),
+            Self::UndeclaredRuntimeDeps { .. } => Some(
+                "Declare them under `metadata.dependencies.runtime`, or set `post.runtime_deps: add`.",
+            ),
             Self::SigningError { .. } => {
                 Some("Verify signing configuration and ensure the required keys are available.")
             }
@@ -205,11 +244,13 @@ impl UserFacingError for BuildError {
             Self::CompileFailed { .. } => "build.compile_failed",
             Self::InstallFailed { .. } => "build.install_failed",
             Self::SandboxViolation { .. } => "build.sandbox_violation",
+            Self::SandboxDenied { .. } => "build.sandbox_denied",
             Self::NetworkAccessDenied => "build.network_access_denied",
             Self::Timeout { .. } => "build.timeout",
             Self::HashMismatch { .. } => "build.hash_mismatch",
             Self::SbomError { .. } => "build.sbom_error",
             Self::BuildTimeout { .. } => "build.build_timeout",
+            Self::ResourceLimitExceeded { .. } => "build.resource_limit_exceeded",
             Self::ExtractionFailed { .. } => "build.extraction_failed",
             Self::NetworkDisabled { .. } => "build.network_disabled",
             Self::InvalidUrl { .. } => "build.invalid_url",
@@ -218,6 +259,7 @@ impl UserFacingError for BuildError {
             Self::DependencyConflict { .. } => "build.dependency_conflict",
             Self::CompilationFailed { .. } => "build.compilation_failed",
             Self::TestsFailed { .. } => "build.tests_failed",
+            Self::UndeclaredRuntimeDeps { .. } => "build.undeclared_runtime_deps",
             Self::QualityAssuranceFailed { .. } => "build.quality_assurance_failed",
             Self::LinterError { .. } => "build.linter_error",
             Self::SecurityVulnerability { .. } => "build.security_vulnerability",
@@ -228,6 +270,7 @@ impl UserFacingError for BuildError {
             Self::DraftSourceFailed { .. } => "build.draft_source_failed",
             Self::UnsupportedArchiveFormat { .. } => "build.unsupported_archive_format",
             Self::GitCloneFailed { .. } => "build.git_clone_failed",
+            Self::GitCommitMismatch { .. } => "build.git_commit_mismatch",
             Self::ValidationFailed { .. } => "build.validation_failed",
             Self::DangerousCommand { .. } => "build.dangerous_command",
             Self::InvalidPath { .. } => "build.invalid_path",
@@ -33,8 +33,8 @@ pub enum InstallError {
     #[error("state not found: {state_id}")]
     StateNotFound { state_id: String },
 
-    #[error("package has dependents: {package}")]
-    PackageHasDependents { package: String },
+    #[error("cannot remove {package}: needed by {dependents}")]
+    PackageHasDependents { package: String, dependents: String },
 
     #[error("no packages specified")]
     NoPackagesSpecified,
@@ -75,6 +75,24 @@ pub enum InstallError {
 
     #[error("no progress detected: {message}")]
     NoProgress { message: String },
+
+    #[error("not available offline: {packages} not found in the local store")]
+    NotAvailableOffline { packages: String },
+
+    #[error("hash not pinned: {package} artifact {hash} is not listed in the required hashes")]
+    HashNotPinned { package: String, hash: String },
+
+    #[error("invalid required hashes file {path}: {message}")]
+    InvalidHashPins { path: String, message: String },
+
+    #[error("file conflict: {packages} install different files at {paths}")]
+    FileConflict { paths: String, packages: String },
+
+    #[error("updating would break installed packages: {constraints}")]
+    UpdateBreaksDependents { constraints: String },
+
+    #[error("cannot remove {package} while the same change installs {needed_by}")]
+    RemovedPackageNeeded { package: String, needed_by: String },
 }
 
 impl UserFacingError for InstallError {
@@ -90,6 +108,24 @@ impl UserFacingError for InstallError {
             Self::MissingDownloadUrl { .. } | Self::MissingLocalPath { .. } => {
                 Some("Ensure the package manifest includes a valid source.")
             }
+            Self::NotAvailableOffline { .. } => Some(
+                "Install these packages once while online so they are cached in the store, or run without --offline.",
+            ),
+            Self::PackageHasDependents { .. } => Some(
+                "Use --cascade to remove the dependent packages too, or --force to remove it anyway.",
+            ),
+            Self::HashNotPinned { .. } => Some(
+                "Add the artifact's blake3 hash to the required hashes file after reviewing it, or install a pinned version.",
+            ),
+            Self::FileConflict { .. } => {
+                Some("Install only one of these packages, or pass --conflict-policy prefer-first or prefer-explicit to keep one package's files.")
+            }
+            Self::UpdateBreaksDependents { .. } => Some(
+                "Update the dependent packages too once compatible versions are available, or pass --force to update anyway.",
+            ),
+            Self::RemovedPackageNeeded { .. } => {
+                Some("Keep the package, or leave out the packages that need it.")
+            }
             _ => None,
         }
     }
@@ -124,6 +160,12 @@ impl UserFacingError for InstallError {
             Self::TempFileError { .. } => "install.temp_file_error",
             Self::OperationTimeout { .. } => "install.operation_timeout",
             Self::NoProgress { .. } => "install.no_progress",
+            Self::NotAvailableOffline { .. } => "install.not_available_offline",
+            Self::HashNotPinned { .. } => "install.hash_not_pinned",
+            Self::InvalidHashPins { .. } => "install.invalid_hash_pins",
+            Self::FileConflict { .. } => "install.file_conflict",
+            Self::UpdateBreaksDependents { .. } => "install.update_breaks_dependents",
+            Self::RemovedPackageNeeded { .. } => "install.removed_package_needed",
         };
         Some(code)
     }
@@ -7,6 +7,8 @@ use thiserror::Error;
 
 const HINT_CHECK_CONNECTION: &str = "Check your network connection and retry.";
 const HINT_RETRY_LATER: &str = "Retry the operation; the service may recover shortly.";
+const HINT_OFFLINE: &str =
+    "Run without --offline (or unset network.offline) to allow network access, or run `sps2 reposync` while online to refresh the local cache.";
 
 #[derive(Debug, Clone, Error)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -24,8 +26,14 @@ pub enum NetworkError {
     #[error("invalid URL: {0}")]
     InvalidUrl(String),
 
-    #[error("HTTP error {status}: {message}")]
-    HttpError { status: u16, message: String },
+    /// `request_id` is the server's identifier for the failed request, for
+    /// matching a client failure against the repository's logs
+    #[error("HTTP error {status}: {message}{}", request_id_suffix(.request_id.as_deref()))]
+    HttpError {
+        status: u16,
+        message: String,
+        request_id: Option<String>,
+    },
 
     #[error("checksum mismatch: expected {expected}, got {actual}")]
     ChecksumMismatch { expected: String, actual: String },
@@ -36,8 +44,11 @@ pub enum NetworkError {
     #[error("network unavailable")]
     NetworkUnavailable,
 
-    #[error("rate limited: retry after {seconds} seconds")]
-    RateLimited { seconds: u64 },
+    #[error("rate limited: retry after {seconds} seconds{}", request_id_suffix(.request_id.as_deref()))]
+    RateLimited {
+        seconds: u64,
+        request_id: Option<String>,
+    },
 
     #[error("partial content not supported for resumable download")]
     PartialContentNotSupported,
@@ -56,6 +67,13 @@ pub enum NetworkError {
 
     #[error("unsupported protocol: {protocol}")]
     UnsupportedProtocol { protocol: String },
+
+    #[error("offline mode: network access to {url} is disabled")]
+    Offline { url: String },
+}
+
+fn request_id_suffix(request_id: Option<&str>) -> String {
+    request_id.map_or_else(String::new, |id| format!(" (request ID {id})"))
 }
 
 impl UserFacingError for NetworkError {
@@ -71,6 +89,7 @@ impl UserFacingError for NetworkError {
                 Some("Retry without resume or select a different mirror.")
             }
             Self::StreamInterrupted { .. } => Some(HINT_RETRY_LATER),
+            Self::Offline { .. } => Some(HINT_OFFLINE),
             Self::ChecksumMismatch { .. } => {
                 Some("Retry with `--no-cache` or verify the artifact.")
             }
@@ -109,6 +128,7 @@ impl UserFacingError for NetworkError {
             Self::FileSizeExceeded { .. } => "network.file_size_exceeded",
             Self::StreamInterrupted { .. } => "network.stream_interrupted",
             Self::UnsupportedProtocol { .. } => "network.unsupported_protocol",
+            Self::Offline { .. } => "network.offline",
         };
         Some(code)
     }
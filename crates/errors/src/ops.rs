@@ -59,6 +59,34 @@ pub enum OpsError {
 
     #[error("invalid staging directory {path}: {reason}")]
     InvalidStagingDirectory { path: String, reason: String },
+
+    #[error("service not found: {label}")]
+    ServiceNotFound { label: String },
+
+    #[error("launchctl {action} {label} failed: {message}")]
+    ServiceCommandFailed {
+        action: String,
+        label: String,
+        message: String,
+    },
+
+    #[error("no sps2.toml or .sps2 in {dir} or any directory above it")]
+    ProjectNotFound { dir: String },
+
+    #[error("invalid project file {path}: {reason}")]
+    InvalidProject { path: String, reason: String },
+
+    #[error("invalid change set {path}: {reason}")]
+    InvalidChangeSet { path: String, reason: String },
+
+    #[error("invalid manifest {path}: {reason}")]
+    InvalidManifest { path: String, reason: String },
+
+    #[error("invalid local repository {path}: {reason}")]
+    InvalidLocalRepository { path: String, reason: String },
+
+    #[error("the signing keys of {url} changed:\n{changes}")]
+    RepositoryKeysChanged { url: String, changes: String },
 }
 
 impl UserFacingError for OpsError {
@@ -70,6 +98,18 @@ impl UserFacingError for OpsError {
         match self {
             Self::NoPackagesSpecified => Some(HINT_PROVIDE_PACKAGE),
             Self::NoPreviousState => Some("Create a state snapshot before attempting rollback."),
+            Self::ServiceNotFound { .. } => {
+                Some("Run `sps2 services list` to see the services installed packages provide.")
+            }
+            Self::ProjectNotFound { .. } => Some(
+                "Create sps2.toml in the project directory listing its packages, e.g. `packages = [\"ripgrep\"]`.",
+            ),
+            Self::InvalidLocalRepository { .. } => Some(
+                "Copy index.json, index.json.minisig and the packages' .sp and .minisig files into one directory, and trust the repository key with `sps2 keys import`.",
+            ),
+            Self::RepositoryKeysChanged { .. } => Some(
+                "Check the changes with the repository's maintainers, then accept them with `sps2 reposync --accept-keys`.",
+            ),
             _ => None,
         }
     }
@@ -95,6 +135,14 @@ impl UserFacingError for OpsError {
             Self::VerificationFailed { .. } => "ops.verification_failed",
             Self::StagingDirectoryNotFound { .. } => "ops.staging_directory_not_found",
             Self::InvalidStagingDirectory { .. } => "ops.invalid_staging_directory",
+            Self::ServiceNotFound { .. } => "ops.service_not_found",
+            Self::ServiceCommandFailed { .. } => "ops.service_command_failed",
+            Self::ProjectNotFound { .. } => "ops.project_not_found",
+            Self::InvalidProject { .. } => "ops.invalid_project",
+            Self::InvalidChangeSet { .. } => "ops.invalid_change_set",
+            Self::InvalidManifest { .. } => "ops.invalid_manifest",
+            Self::InvalidLocalRepository { .. } => "ops.invalid_local_repository",
+            Self::RepositoryKeysChanged { .. } => "ops.repository_keys_changed",
         };
         Some(code)
     }
@@ -53,6 +53,32 @@ pub enum PackageError {
 
     #[error("source not available: {package}")]
     SourceNotAvailable { package: String },
+
+    #[error("path is not valid UTF-8: {path}")]
+    NonUtf8Path { path: String },
+
+    #[error("invalid package name {name:?}: {reason}")]
+    InvalidName { name: String, reason: String },
+
+    #[error("{package} is built for {arch} and cannot run on {host}")]
+    UnsupportedArch {
+        package: String,
+        arch: String,
+        host: String,
+    },
+}
+
+impl PackageError {
+    /// Error for a package path or symlink target that is not valid UTF-8
+    ///
+    /// Package paths are stored as text in `files.json`, the state database
+    /// and hashes, so they are rejected instead of converted lossily.
+    #[must_use]
+    pub fn non_utf8_path(path: &std::path::Path) -> Self {
+        Self::NonUtf8Path {
+            path: path.display().to_string(),
+        }
+    }
 }
 
 impl UserFacingError for PackageError {
@@ -81,6 +107,15 @@ impl UserFacingError for PackageError {
             Self::SourceNotAvailable { .. } => {
                 Some("Ensure the source repository is reachable or configured.")
             }
+            Self::NonUtf8Path { .. } => {
+                Some("Rename the file or symlink target; package paths must be valid UTF-8.")
+            }
+            Self::InvalidName { .. } => Some(
+                "Package names use lowercase letters, digits, `.`, `_`, `+` and `-`, and start with a letter or digit.",
+            ),
+            Self::UnsupportedArch { .. } => {
+                Some("Install a build for this machine's architecture or a universal2 build.")
+            }
             _ => None,
         }
     }
@@ -109,6 +144,9 @@ impl UserFacingError for PackageError {
             Self::IncompatibleFormat { .. } => "package.incompatible_format",
             Self::ResolutionTimeout { .. } => "package.resolution_timeout",
             Self::SourceNotAvailable { .. } => "package.source_not_available",
+            Self::NonUtf8Path { .. } => "package.non_utf8_path",
+            Self::InvalidName { .. } => "package.invalid_name",
+            Self::UnsupportedArch { .. } => "package.unsupported_arch",
         };
         Some(code)
     }
@@ -35,6 +35,15 @@ pub enum StateError {
 
     #[error("migration failed: {message}")]
     MigrationFailed { message: String },
+
+    #[error("snapshot {name} already names state {state_id}")]
+    TagExists { name: String, state_id: String },
+
+    #[error("snapshot not found: {name}")]
+    TagNotFound { name: String },
+
+    #[error("invalid snapshot name {name:?}: {reason}")]
+    InvalidTagName { name: String, reason: String },
 }
 
 impl UserFacingError for StateError {
@@ -52,6 +61,13 @@ impl UserFacingError for StateError {
             Self::MigrationFailed { .. } => {
                 Some("Review the migration logs and rerun `sps2 check-health`.")
             }
+            Self::TagExists { .. } => Some(
+                "Delete the existing snapshot with `sps2 snapshot delete` or pick another name.",
+            ),
+            Self::TagNotFound { .. } => Some("List snapshots with `sps2 snapshot list`."),
+            Self::InvalidTagName { .. } => {
+                Some("Use letters, digits, `.`, `_` and `-`, starting with a letter or digit.")
+            }
             _ => None,
         }
     }
@@ -71,6 +87,9 @@ impl UserFacingError for StateError {
             Self::RollbackFailed { .. } => "state.rollback_failed",
             Self::ActiveStateMissing => "state.active_state_missing",
             Self::MigrationFailed { .. } => "state.migration_failed",
+            Self::TagExists { .. } => "state.tag_exists",
+            Self::TagNotFound { .. } => "state.tag_not_found",
+            Self::InvalidTagName { .. } => "state.invalid_tag_name",
         };
         Some(code)
     }
@@ -12,6 +12,17 @@ pub enum StorageError {
     #[error("disk full: {path}")]
     DiskFull { path: String },
 
+    #[error(
+        "not enough space on {path}: {} MiB needed, {} MiB available",
+        .required.div_ceil(1 << 20),
+        .available >> 20
+    )]
+    InsufficientSpace {
+        path: String,
+        required: u64,
+        available: u64,
+    },
+
     #[error("permission denied: {path}")]
     PermissionDenied { path: String },
 
@@ -84,6 +95,9 @@ impl UserFacingError for StorageError {
     fn user_hint(&self) -> Option<&'static str> {
         match self {
             Self::DiskFull { .. } => Some("Free up disk space under /opt/pm and retry."),
+            Self::InsufficientSpace { .. } => {
+                Some("Free up space (for example with `sps2 cleanup`) or install fewer packages at once.")
+            }
             Self::PermissionDenied { .. } => {
                 Some("Adjust filesystem permissions or retry with elevated privileges.")
             }
@@ -101,6 +115,7 @@ impl UserFacingError for StorageError {
     fn user_code(&self) -> Option<&'static str> {
         let code = match self {
             Self::DiskFull { .. } => "storage.disk_full",
+            Self::InsufficientSpace { .. } => "storage.insufficient_space",
             Self::PermissionDenied { .. } => "storage.permission_denied",
             Self::PathNotFound { .. } => "storage.path_not_found",
             Self::DirectoryNotFound { .. } => "storage.directory_not_found",
@@ -0,0 +1,459 @@
+//! The event channel, with progress coalescing and an optional queue limit
+//!
+//! Large operations can report progress thousands of times per second,
+//! far more than anyone can read. The sender therefore passes at most one
+//! [`ProgressEvent::Updated`] per progress id and interval. Updates in
+//! between are held back, latest value wins, and the held-back update is
+//! delivered once the interval has passed or before any other event, so the
+//! receiver never sees an older value after a newer one. A receiver waiting
+//! in [`EventReceiver::recv`] gets it when its interval is up even if
+//! nothing else is sent.
+//!
+//! With a queue limit, progress updates and debug messages are dropped
+//! while that many events wait for the receiver. Everything else is always
+//! queued: emitting never blocks, and nothing the user must see is lost.
+//!
+//! A channel can also stamp every event with a [`HostContext`], so events
+//! from any crate carry it without each emitter knowing about it.
+
+use crate::{AppEvent, EventLevel, EventMessage, HostContext, ProgressConfig, ProgressEvent};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::error::{SendError, TryRecvError};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::Notify;
+
+/// Settings for an event channel
+#[derive(Debug, Clone)]
+pub struct ChannelConfig {
+    /// Shortest time between two progress updates for the same id
+    pub coalesce_interval: Duration,
+    /// Number of queued events above which progress updates and debug
+    /// messages are dropped; unlimited when `None`
+    pub capacity: Option<usize>,
+    /// Attached to events that carry no host context of their own
+    pub host: Option<HostContext>,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            coalesce_interval: ProgressConfig::default().update_interval,
+            capacity: None,
+            host: None,
+        }
+    }
+}
+
+/// Events a channel did not deliver
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    /// Progress updates replaced by a later update for the same id
+    pub coalesced: u64,
+    /// Events dropped because the queue was full
+    pub dropped: u64,
+}
+
+impl ChannelStats {
+    /// Whether every event sent was delivered
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.coalesced == 0 && self.dropped == 0
+    }
+}
+
+/// Progress updates of one id
+#[derive(Debug)]
+struct ProgressSlot {
+    last_sent: Instant,
+    pending: Option<EventMessage>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    config: ChannelConfig,
+    queued: AtomicUsize,
+    coalesced: AtomicU64,
+    dropped: AtomicU64,
+    progress: Mutex<HashMap<String, ProgressSlot>>,
+    /// Wakes a waiting receiver when an update is held back
+    held_back: Notify,
+}
+
+impl Shared {
+    fn progress(&self) -> std::sync::MutexGuard<'_, HashMap<String, ProgressSlot>> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// When the earliest held-back update is due
+    fn next_due(&self) -> Option<Instant> {
+        self.progress()
+            .values()
+            .filter(|slot| slot.pending.is_some())
+            .map(|slot| slot.last_sent + self.config.coalesce_interval)
+            .min()
+    }
+
+    /// Take a held-back update that is due at `now`, or any when `now` is
+    /// `None`
+    fn take_pending(&self, now: Option<Instant>) -> Option<EventMessage> {
+        let interval = self.config.coalesce_interval;
+        let mut progress = self.progress();
+        let slot = progress.values_mut().find(|slot| {
+            slot.pending.is_some()
+                && now.is_none_or(|now| now.duration_since(slot.last_sent) >= interval)
+        })?;
+        if let Some(now) = now {
+            slot.last_sent = now;
+        }
+        slot.pending.take()
+    }
+}
+
+/// Sending half of an event channel
+#[derive(Debug, Clone)]
+pub struct EventSender {
+    tx: UnboundedSender<EventMessage>,
+    shared: Arc<Shared>,
+}
+
+/// Receiving half of an event channel
+#[derive(Debug)]
+pub struct EventReceiver {
+    rx: UnboundedReceiver<EventMessage>,
+    shared: Arc<Shared>,
+}
+
+/// Create an event channel with the default settings
+#[must_use]
+pub fn channel() -> (EventSender, EventReceiver) {
+    channel_with(ChannelConfig::default())
+}
+
+/// Create an event channel with the given settings
+#[must_use]
+pub fn channel_with(config: ChannelConfig) -> (EventSender, EventReceiver) {
+    let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+    let shared = Arc::new(Shared {
+        config,
+        queued: AtomicUsize::new(0),
+        coalesced: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+        progress: Mutex::new(HashMap::new()),
+        held_back: Notify::new(),
+    });
+    (
+        EventSender {
+            tx,
+            shared: Arc::clone(&shared),
+        },
+        EventReceiver { rx, shared },
+    )
+}
+
+fn is_update(event: &AppEvent) -> bool {
+    matches!(event, AppEvent::Progress(ProgressEvent::Updated { .. }))
+}
+
+impl EventSender {
+    /// Send an event, unless it is coalesced or dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the receiver has been dropped.
+    pub fn send(&self, mut message: EventMessage) -> Result<(), SendError<()>> {
+        if message.meta.host.is_none() {
+            message.meta.host.clone_from(&self.shared.config.host);
+        }
+        let now = Instant::now();
+        let interval = self.shared.config.coalesce_interval;
+        let mut progress = self.shared.progress();
+
+        let update_id = match &message.event {
+            AppEvent::Progress(ProgressEvent::Updated { id, .. }) => Some(id.clone()),
+            _ => None,
+        };
+        if let Some(id) = update_id {
+            if let Some(slot) = progress.get_mut(&id) {
+                if now.duration_since(slot.last_sent) < interval {
+                    if slot.pending.replace(message).is_some() {
+                        self.shared.coalesced.fetch_add(1, Ordering::Relaxed);
+                    } else {
+                        self.shared.held_back.notify_one();
+                    }
+                    return self.flush_due(&mut progress, now);
+                }
+                if slot.pending.take().is_some() {
+                    self.shared.coalesced.fetch_add(1, Ordering::Relaxed);
+                }
+                slot.last_sent = now;
+            } else {
+                progress.insert(
+                    id,
+                    ProgressSlot {
+                        last_sent: now,
+                        pending: None,
+                    },
+                );
+            }
+            self.flush_due(&mut progress, now)?;
+            return self.enqueue(message);
+        }
+
+        // Anything else comes after every update sent before it
+        for slot in progress.values_mut() {
+            if let Some(pending) = slot.pending.take() {
+                slot.last_sent = now;
+                self.enqueue(pending)?;
+            }
+        }
+        if let AppEvent::Progress(
+            ProgressEvent::Completed { id, .. } | ProgressEvent::Failed { id, .. },
+        ) = &message.event
+        {
+            progress.remove(id);
+        }
+        self.enqueue(message)
+    }
+
+    /// Deliver held-back updates whose interval has passed
+    fn flush_due(
+        &self,
+        progress: &mut HashMap<String, ProgressSlot>,
+        now: Instant,
+    ) -> Result<(), SendError<()>> {
+        let interval = self.shared.config.coalesce_interval;
+        for slot in progress.values_mut() {
+            if slot.pending.is_some() && now.duration_since(slot.last_sent) >= interval {
+                slot.last_sent = now;
+                if let Some(pending) = slot.pending.take() {
+                    self.enqueue(pending)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn enqueue(&self, message: EventMessage) -> Result<(), SendError<()>> {
+        if let Some(capacity) = self.shared.config.capacity {
+            let droppable = is_update(&message.event) || message.meta.level <= EventLevel::Debug;
+            if droppable && self.shared.queued.load(Ordering::Relaxed) >= capacity {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+        self.shared.queued.fetch_add(1, Ordering::Relaxed);
+        self.tx.send(message).map_err(|_| {
+            self.shared.queued.fetch_sub(1, Ordering::Relaxed);
+            SendError(())
+        })
+    }
+}
+
+impl EventReceiver {
+    /// Receive the next event, waiting for one
+    ///
+    /// A held-back progress update is returned once its interval has passed
+    /// when no other event arrives first. Returns `None` once every sender
+    /// is gone and neither queued events nor held-back updates are left.
+    pub async fn recv(&mut self) -> Option<EventMessage> {
+        loop {
+            let message = if let Some(due) = self.shared.next_due() {
+                match tokio::time::timeout_at(due.into(), self.rx.recv()).await {
+                    Ok(message) => message,
+                    Err(_) => match self.shared.take_pending(Some(Instant::now())) {
+                        Some(pending) => return Some(pending),
+                        None => continue,
+                    },
+                }
+            } else {
+                tokio::select! {
+                    message = self.rx.recv() => message,
+                    () = self.shared.held_back.notified() => continue,
+                }
+            };
+            return match message {
+                Some(message) => {
+                    self.shared.queued.fetch_sub(1, Ordering::Relaxed);
+                    Some(message)
+                }
+                None => self.shared.take_pending(None),
+            };
+        }
+    }
+
+    /// Receive the next event without waiting
+    ///
+    /// Once the queue is empty, progress updates still held back are
+    /// returned, so draining the receiver yields the latest value of every
+    /// progress id.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no event is available.
+    pub fn try_recv(&mut self) -> Result<EventMessage, TryRecvError> {
+        match self.rx.try_recv() {
+            Ok(message) => {
+                self.shared.queued.fetch_sub(1, Ordering::Relaxed);
+                Ok(message)
+            }
+            Err(e) => self.shared.take_pending(None).ok_or(e),
+        }
+    }
+
+    /// Events not delivered so far
+    #[must_use]
+    pub fn stats(&self) -> ChannelStats {
+        ChannelStats {
+            coalesced: self.shared.coalesced.load(Ordering::Relaxed),
+            dropped: self.shared.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GeneralEvent;
+
+    fn update(id: &str, current: u64) -> EventMessage {
+        EventMessage::from_event(AppEvent::Progress(ProgressEvent::updated(
+            id,
+            current,
+            Some(100),
+        )))
+    }
+
+    fn current(message: &EventMessage) -> Option<u64> {
+        match &message.event {
+            AppEvent::Progress(ProgressEvent::Updated { current, .. }) => Some(*current),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn updates_within_the_interval_keep_the_latest_value() {
+        let (tx, mut rx) = channel_with(ChannelConfig {
+            coalesce_interval: Duration::from_secs(3600),
+            ..ChannelConfig::default()
+        });
+        for i in 1..=50 {
+            tx.send(update("download", i)).unwrap();
+        }
+        tx.send(update("other", 7)).unwrap();
+
+        // The first update of each id goes through at once
+        assert_eq!(current(&rx.try_recv().unwrap()), Some(1));
+        assert_eq!(current(&rx.try_recv().unwrap()), Some(7));
+
+        // Another event flushes the held-back update ahead of it
+        tx.send(EventMessage::from_event(AppEvent::General(
+            GeneralEvent::warning("slow mirror"),
+        )))
+        .unwrap();
+        assert_eq!(current(&rx.try_recv().unwrap()), Some(50));
+        assert!(current(&rx.try_recv().unwrap()).is_none());
+        assert!(rx.try_recv().is_err());
+
+        // Draining returns updates still held back
+        tx.send(update("other", 8)).unwrap();
+        assert_eq!(current(&rx.try_recv().unwrap()), Some(8));
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(
+            rx.stats(),
+            ChannelStats {
+                coalesced: 48,
+                dropped: 0
+            }
+        );
+    }
+
+    #[test]
+    fn a_full_queue_drops_only_updates_and_debug_messages() {
+        let (tx, mut rx) = channel_with(ChannelConfig {
+            coalesce_interval: Duration::ZERO,
+            capacity: Some(2),
+            ..ChannelConfig::default()
+        });
+        for i in 1..=5 {
+            tx.send(update("download", i)).unwrap();
+        }
+        tx.send(EventMessage::from_event(AppEvent::General(
+            GeneralEvent::debug("noise"),
+        )))
+        .unwrap();
+        tx.send(EventMessage::from_event(AppEvent::General(
+            GeneralEvent::error("disk full"),
+        )))
+        .unwrap();
+
+        let received: Vec<_> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(received.len(), 3);
+        assert!(matches!(
+            received[2].event,
+            AppEvent::General(GeneralEvent::Error { .. })
+        ));
+        assert_eq!(rx.stats().dropped, 4);
+
+        // Receiving makes room again
+        tx.send(update("download", 6)).unwrap();
+        assert_eq!(current(&rx.try_recv().unwrap()), Some(6));
+    }
+
+    #[test]
+    fn events_without_host_context_get_the_channels() {
+        let host = HostContext {
+            hostname: Some("build-07".to_string()),
+            ..HostContext::default()
+        };
+        let (tx, mut rx) = channel_with(ChannelConfig {
+            host: Some(host.clone()),
+            ..ChannelConfig::default()
+        });
+        tx.send(EventMessage::from_event(AppEvent::General(
+            GeneralEvent::warning("slow mirror"),
+        )))
+        .unwrap();
+        let mut forwarded = EventMessage::from_event(AppEvent::General(GeneralEvent::warning(
+            "from a build worker",
+        )));
+        forwarded.meta.host = Some(HostContext::default());
+        tx.send(forwarded).unwrap();
+
+        assert_eq!(rx.try_recv().unwrap().meta.host, Some(host));
+        assert_eq!(
+            rx.try_recv().unwrap().meta.host,
+            Some(HostContext::default())
+        );
+    }
+
+    #[tokio::test]
+    async fn a_waiting_receiver_gets_held_back_updates_when_due() {
+        let (tx, mut rx) = channel_with(ChannelConfig {
+            coalesce_interval: Duration::from_millis(100),
+            ..ChannelConfig::default()
+        });
+        tx.send(update("download", 1)).unwrap();
+        assert_eq!(current(&rx.recv().await.unwrap()), Some(1));
+
+        // Held back while the receiver already waits, and nothing follows
+        let waiting = tokio::spawn(async move {
+            let message = rx.recv().await;
+            (rx, message)
+        });
+        tokio::task::yield_now().await;
+        tx.send(update("download", 2)).unwrap();
+        tx.send(update("download", 3)).unwrap();
+        let (mut rx, message) = waiting.await.unwrap();
+        assert_eq!(current(&message.unwrap()), Some(3));
+
+        // The last update is not lost when the sender goes away
+        tx.send(update("download", 4)).unwrap();
+        drop(tx);
+        assert_eq!(current(&rx.recv().await.unwrap()), Some(4));
+        assert!(rx.recv().await.is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use sps2_types::Version;
+use sps2_types::{TestResults, Version};
 use std::path::PathBuf;
 
 /// Build system types supported by sps2
@@ -20,6 +20,7 @@ pub enum BuildSystem {
 pub enum BuildPhase {
     Source,
     Build,
+    Test,
     PostProcess,
     Package,
 }
@@ -106,6 +107,24 @@ pub enum BuildEvent {
         status: PhaseStatus,
     },
 
+    /// A recipe source was fetched and placed in the build root.
+    SourceAcquired {
+        session_id: String,
+        source: String,
+        destination: PathBuf,
+        /// Checksum algorithm the download was verified with
+        #[serde(skip_serializing_if = "Option::is_none")]
+        checksum: Option<String>,
+        duration_ms: u64,
+    },
+
+    /// Recipe test stage finished, or was skipped.
+    TestsCompleted {
+        session_id: String,
+        target: BuildTarget,
+        results: TestResults,
+    },
+
     /// Build completed successfully.
     Completed {
         session_id: String,
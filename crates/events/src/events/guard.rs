@@ -130,6 +130,19 @@ pub enum GuardEvent {
         healed: usize,
     },
 
+    /// A single path restored during healing.
+    PathHealed {
+        operation_id: String,
+        path: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        package: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        /// Where the content came from: `store`, `repository`, or `live` for
+        /// copies in managed directories
+        source: String,
+    },
+
     /// Discrepancy discovered during verification or healing.
     DiscrepancyReported {
         operation_id: String,
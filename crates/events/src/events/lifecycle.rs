@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use sps2_types::Version;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use super::FailureContext;
@@ -58,6 +59,24 @@ pub struct DownloadContext {
     pub total_bytes: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bytes_downloaded: Option<u64>,
+    /// Hash details when the downloaded content failed verification
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<DownloadIntegrity>,
+    /// Whether the content came from the download cache instead of the URL
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub cached: bool,
+}
+
+/// Hash verification failure of a downloaded file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadIntegrity {
+    /// BLAKE3 hash the download was expected to have
+    pub expected_hash: String,
+    /// BLAKE3 hash of the received content
+    pub actual_hash: String,
+    /// Where the corrupted file was moved, if quarantining succeeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quarantine_path: Option<PathBuf>,
 }
 
 /// Context for install events
@@ -91,6 +110,9 @@ pub struct ResolverContext {
     // Failed fields
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub conflicting_packages: Vec<String>,
+    /// Human-readable chain of requirements that could not be satisfied together
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflict_explanation: Vec<String>,
 }
 
 /// Context for repo sync events
@@ -300,6 +322,8 @@ impl LifecycleEvent {
                 package,
                 total_bytes,
                 bytes_downloaded: None,
+                integrity: None,
+                cached: false,
             },
             failure: None,
         }
@@ -315,6 +339,26 @@ impl LifecycleEvent {
                 package,
                 total_bytes: None,
                 bytes_downloaded: Some(bytes_downloaded),
+                integrity: None,
+                cached: false,
+            },
+            failure: None,
+        }
+    }
+
+    /// Create a download completed event for content served from the
+    /// download cache
+    #[must_use]
+    pub fn download_cached(url: String, package: Option<String>, bytes: u64) -> Self {
+        Self::Download {
+            stage: LifecycleStage::Completed,
+            context: DownloadContext {
+                url,
+                package,
+                total_bytes: None,
+                bytes_downloaded: Some(bytes),
+                integrity: None,
+                cached: true,
             },
             failure: None,
         }
@@ -330,6 +374,30 @@ impl LifecycleEvent {
                 package,
                 total_bytes: None,
                 bytes_downloaded: None,
+                integrity: None,
+                cached: false,
+            },
+            failure: Some(failure),
+        }
+    }
+
+    /// Create a download failed event for content that did not match its expected hash
+    #[must_use]
+    pub fn download_verification_failed(
+        url: String,
+        package: Option<String>,
+        integrity: DownloadIntegrity,
+        failure: FailureContext,
+    ) -> Self {
+        Self::Download {
+            stage: LifecycleStage::Failed,
+            context: DownloadContext {
+                url,
+                package,
+                total_bytes: None,
+                bytes_downloaded: None,
+                integrity: Some(integrity),
+                cached: false,
             },
             failure: Some(failure),
         }
@@ -398,6 +466,7 @@ impl LifecycleEvent {
                 reused_packages: None,
                 duration_ms: None,
                 conflicting_packages: vec![],
+                conflict_explanation: vec![],
             },
             failure: None,
         }
@@ -422,6 +491,7 @@ impl LifecycleEvent {
                 reused_packages: Some(reused_packages),
                 duration_ms: Some(duration_ms),
                 conflicting_packages: vec![],
+                conflict_explanation: vec![],
             },
             failure: None,
         }
@@ -429,7 +499,11 @@ impl LifecycleEvent {
 
     /// Create a resolver failed event
     #[must_use]
-    pub fn resolver_failed(failure: FailureContext, conflicting_packages: Vec<String>) -> Self {
+    pub fn resolver_failed(
+        failure: FailureContext,
+        conflicting_packages: Vec<String>,
+        conflict_explanation: Vec<String>,
+    ) -> Self {
         Self::Resolver {
             stage: LifecycleStage::Failed,
             context: ResolverContext {
@@ -441,6 +515,7 @@ impl LifecycleEvent {
                 reused_packages: None,
                 duration_ms: None,
                 conflicting_packages,
+                conflict_explanation,
             },
             failure: Some(failure),
         }
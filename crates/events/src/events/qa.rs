@@ -49,6 +49,15 @@ pub struct QaFinding {
     pub file: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub line: Option<u32>,
+    /// Rule id recipes use to suppress the finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    /// How to fix the finding in the recipe
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+    /// Recipe's justification, if the finding is suppressed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppressed: Option<String>,
 }
 
 /// Summary emitted after a QA check completes.
@@ -86,4 +95,10 @@ pub enum QaEvent {
         target: QaTarget,
         summary: QaCheckSummary,
     },
+    /// A finding of the final validation, a patcher or an advisory check.
+    FindingReported {
+        target: QaTarget,
+        check: String,
+        finding: QaFinding,
+    },
 }
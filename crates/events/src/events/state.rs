@@ -45,6 +45,28 @@ pub struct CleanupSummary {
     pub duration_ms: Option<u64>,
 }
 
+/// Disk space an install needs, computed before anything is committed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiskDelta {
+    /// Package archives downloaded for the install
+    pub downloaded_bytes: u64,
+    /// New store objects, after deduplication against existing ones
+    pub store_bytes: u64,
+    /// Space the staged state takes beyond what it shares with the store
+    pub staging_bytes: u64,
+    /// Free space on the store's volume, if it could be determined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available_bytes: Option<u64>,
+}
+
+impl DiskDelta {
+    /// Space the install will take on disk
+    #[must_use]
+    pub fn required_bytes(&self) -> u64 {
+        self.store_bytes.saturating_add(self.staging_bytes)
+    }
+}
+
 /// State events emitted by state manager and install flows.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -83,4 +105,7 @@ pub enum StateEvent {
         summary: CleanupSummary,
         failure: FailureContext,
     },
+    DiskDeltaComputed {
+        delta: DiskDelta,
+    },
 }
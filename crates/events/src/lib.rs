@@ -19,7 +19,10 @@
 use serde::{Deserialize, Serialize};
 
 pub mod meta;
-pub use meta::{EventLevel, EventMeta, EventSource};
+pub use meta::{EventLevel, EventMeta, EventSource, HostContext};
+
+pub mod channel;
+pub use channel::{channel, channel_with, ChannelConfig, ChannelStats, EventReceiver, EventSender};
 
 // Re-export the progress tracking system
 pub mod progress;
@@ -38,7 +41,9 @@ pub use events::{
     BuildTarget,
     CleanupSummary,
     CommandDescriptor,
+    DiskDelta,
     DownloadContext,
+    DownloadIntegrity,
     FailureContext,
     GeneralEvent,
     GuardDiscrepancy,
@@ -74,8 +79,6 @@ pub use events::{
     UpdateContext,
 };
 
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-
 /// Envelope carrying metadata alongside an application event.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct EventMessage {
@@ -96,18 +99,6 @@ impl EventMessage {
     }
 }
 
-/// Type alias for event sender using the new `AppEvent` system
-pub type EventSender = UnboundedSender<EventMessage>;
-
-/// Type alias for event receiver using the new `AppEvent` system
-pub type EventReceiver = UnboundedReceiver<EventMessage>;
-
-/// Create a new event channel with the `AppEvent` system
-#[must_use]
-pub fn channel() -> (EventSender, EventReceiver) {
-    tokio::sync::mpsc::unbounded_channel()
-}
-
 /// The unified trait for emitting events throughout the sps2 system
 ///
 /// This trait provides a single, consistent API for emitting events regardless of
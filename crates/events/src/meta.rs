@@ -29,6 +29,21 @@ pub struct EventMeta {
     /// Optional free-form labels for downstream enrichment (kept small on purpose).
     #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
     pub labels: BTreeMap<String, String>,
+    /// Machine the event was emitted on, when enrichment is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<HostContext>,
+}
+
+/// Where an event comes from, for aggregating streams from many machines.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HostContext {
+    pub hostname: Option<String>,
+    /// Version of sps2 that emitted the event.
+    pub version: Option<String>,
+    pub os_build: Option<String>,
+    /// Active state when the command started.
+    pub state_id: Option<Uuid>,
 }
 
 impl EventMeta {
@@ -43,6 +58,7 @@ impl EventMeta {
             level: level.into(),
             source: source.into(),
             labels: BTreeMap::new(),
+            host: None,
         }
     }
 
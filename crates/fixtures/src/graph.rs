@@ -0,0 +1,198 @@
+//! Synthetic dependency graphs
+//!
+//! Packages are numbered `0..n` and may only depend on packages with a
+//! higher number, so every generated graph is acyclic and package `0` is a
+//! natural root for resolution.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Shape of the dependency graph between fixture packages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "kind", content = "degree")]
+pub enum GraphShape {
+    /// No dependencies at all
+    Flat,
+    /// Each package depends on the next one
+    Chain,
+    /// Each package depends on the next `n` packages, forming overlapping diamonds
+    Fanout(usize),
+    /// Balanced tree where each package depends on its `n` children
+    Tree(usize),
+    /// Each package depends on up to `n` randomly chosen higher-numbered packages
+    Random(usize),
+}
+
+impl Default for GraphShape {
+    fn default() -> Self {
+        Self::Fanout(2)
+    }
+}
+
+impl fmt::Display for GraphShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flat => f.write_str("flat"),
+            Self::Chain => f.write_str("chain"),
+            Self::Fanout(n) => write!(f, "fanout:{n}"),
+            Self::Tree(n) => write!(f, "tree:{n}"),
+            Self::Random(n) => write!(f, "random:{n}"),
+        }
+    }
+}
+
+impl FromStr for GraphShape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, degree) = match s.split_once(':') {
+            Some((kind, degree)) => (
+                kind,
+                Some(
+                    degree
+                        .parse::<usize>()
+                        .map_err(|e| format!("invalid degree in '{s}': {e}"))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        match (kind, degree) {
+            ("flat", None) => Ok(Self::Flat),
+            ("chain", None) => Ok(Self::Chain),
+            ("fanout", Some(n)) => Ok(Self::Fanout(n)),
+            ("tree", Some(n)) => Ok(Self::Tree(n)),
+            ("random", Some(n)) => Ok(Self::Random(n)),
+            _ => Err(format!(
+                "unknown graph shape '{s}' (expected flat, chain, fanout:N, tree:N or random:N)"
+            )),
+        }
+    }
+}
+
+/// Adjacency list of a generated graph; `edges[i]` lists the dependencies of package `i`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    pub edges: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    /// Generate a graph of `packages` nodes
+    ///
+    /// The same shape, size and seed always produce the same graph.
+    #[must_use]
+    pub fn generate(shape: GraphShape, packages: usize, seed: u64) -> Self {
+        let mut rng = SplitMix64(seed);
+        let edges = (0..packages)
+            .map(|i| match shape {
+                GraphShape::Flat => Vec::new(),
+                GraphShape::Chain => (i + 1..packages.min(i + 2)).collect(),
+                GraphShape::Fanout(n) => (i + 1..packages.min(i + 1 + n)).collect(),
+                GraphShape::Tree(n) => (1..=n)
+                    .map(|child| i * n + child)
+                    .filter(|&child| child < packages)
+                    .collect(),
+                GraphShape::Random(n) => {
+                    let mut deps = Vec::new();
+                    let candidates = packages - i - 1;
+                    for _ in 0..n.min(candidates) {
+                        let dep = i + 1 + rng.below(candidates);
+                        if !deps.contains(&dep) {
+                            deps.push(dep);
+                        }
+                    }
+                    deps.sort_unstable();
+                    deps
+                }
+            })
+            .collect();
+        Self { edges }
+    }
+
+    /// Number of packages reachable from `root`, including itself
+    #[must_use]
+    pub fn closure_size(&self, root: usize) -> usize {
+        let mut seen = vec![false; self.edges.len()];
+        let mut stack = vec![root];
+        let mut count = 0;
+        while let Some(node) = stack.pop() {
+            if node >= seen.len() || seen[node] {
+                continue;
+            }
+            seen[node] = true;
+            count += 1;
+            stack.extend(&self.edges[node]);
+        }
+        count
+    }
+}
+
+/// Small, stable PRNG so graphs do not change with external crate versions
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    #[allow(clippy::cast_possible_truncation)] // result is below `bound`
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_are_acyclic_and_deterministic() {
+        for shape in [
+            GraphShape::Flat,
+            GraphShape::Chain,
+            GraphShape::Fanout(3),
+            GraphShape::Tree(2),
+            GraphShape::Random(4),
+        ] {
+            let graph = DependencyGraph::generate(shape, 20, 7);
+            assert_eq!(graph, DependencyGraph::generate(shape, 20, 7));
+            for (i, deps) in graph.edges.iter().enumerate() {
+                assert!(deps.iter().all(|&dep| dep > i && dep < 20), "{shape}");
+            }
+        }
+    }
+
+    #[test]
+    fn connected_shapes_span_all_packages() {
+        for shape in [
+            GraphShape::Chain,
+            GraphShape::Fanout(2),
+            GraphShape::Tree(3),
+        ] {
+            assert_eq!(DependencyGraph::generate(shape, 15, 0).closure_size(0), 15);
+        }
+        assert_eq!(
+            DependencyGraph::generate(GraphShape::Flat, 15, 0).closure_size(0),
+            1
+        );
+    }
+
+    #[test]
+    fn shape_round_trips_through_strings() {
+        for shape in [
+            GraphShape::Flat,
+            GraphShape::Chain,
+            GraphShape::Fanout(2),
+            GraphShape::Tree(4),
+            GraphShape::Random(3),
+        ] {
+            assert_eq!(shape.to_string().parse::<GraphShape>(), Ok(shape));
+        }
+        assert!("fanout".parse::<GraphShape>().is_err());
+        assert!("mesh:2".parse::<GraphShape>().is_err());
+    }
+}
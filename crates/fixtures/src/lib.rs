@@ -0,0 +1,17 @@
+#![warn(mismatched_lifetime_syntaxes)]
+#![deny(clippy::pedantic, unsafe_code)]
+#![allow(clippy::module_name_repetitions)]
+
+//! Deterministic test fixtures for sps2
+//!
+//! Generates synthetic `.sp` packages, dependency graphs of controllable
+//! size and shape, and signed repositories, so resolver, installer and guard
+//! can be exercised without real upstream packages.
+
+pub mod graph;
+pub mod packages;
+pub mod repository;
+
+pub use graph::{DependencyGraph, GraphShape};
+pub use packages::{index_for, write_packages, Fixture, FixturePackage, FixtureSpec};
+pub use repository::RepositoryFixture;
@@ -0,0 +1,73 @@
+#![warn(mismatched_lifetime_syntaxes)]
+#![deny(clippy::pedantic, unsafe_code)]
+
+use clap::Parser;
+use sps2_errors::Error;
+use sps2_fixtures::{FixtureSpec, GraphShape, RepositoryFixture};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(name = "sps2-fixtures")]
+#[command(version = env!("CARGO_PKG_VERSION"))]
+#[command(about = "Generate signed repositories of synthetic packages for testing", long_about = None)]
+struct Cli {
+    /// Repository directory path (local filesystem)
+    #[arg(long, value_name = "DIR")]
+    repo_dir: PathBuf,
+
+    /// Directory for the generated (unencrypted) signing key pair
+    #[arg(long, value_name = "DIR")]
+    key_dir: PathBuf,
+
+    /// Base URL for download links in index (e.g., <http://localhost:8680>)
+    #[arg(long, value_name = "URL")]
+    base_url: String,
+
+    /// Number of distinct packages
+    #[arg(long, default_value_t = FixtureSpec::default().packages)]
+    packages: usize,
+
+    /// Number of versions per package
+    #[arg(long, default_value_t = FixtureSpec::default().versions)]
+    versions: usize,
+
+    /// Number of files per package
+    #[arg(long, default_value_t = FixtureSpec::default().files_per_package)]
+    files: usize,
+
+    /// Size of each file in bytes
+    #[arg(long, default_value_t = FixtureSpec::default().file_size)]
+    file_size: usize,
+
+    /// Dependency graph shape (flat, chain, fanout:N, tree:N, random:N)
+    #[arg(long, default_value_t = FixtureSpec::default().shape)]
+    shape: GraphShape,
+
+    /// Seed for randomized graph shapes
+    #[arg(long, default_value_t = FixtureSpec::default().seed)]
+    seed: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+    let spec = FixtureSpec {
+        packages: cli.packages,
+        versions: cli.versions,
+        files_per_package: cli.files,
+        file_size: cli.file_size,
+        shape: cli.shape,
+        seed: cli.seed,
+    };
+
+    let repo =
+        RepositoryFixture::publish(&spec, &cli.repo_dir, &cli.key_dir, &cli.base_url).await?;
+    println!(
+        "Generated fixture repository with {} packages ({} graph) in {}",
+        repo.packages.len(),
+        spec.shape,
+        repo.dir.display()
+    );
+    println!("Signing key: {}", repo.secret_key_path().display());
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! Synthetic `.sp` packages
+//!
+//! Package names, versions, dependency edges and file contents are derived
+//! from the [`FixtureSpec`] alone, so two runs with the same spec exercise
+//! identical data.
+
+use crate::graph::{DependencyGraph, GraphShape};
+use serde::{Deserialize, Serialize};
+use sps2_errors::{Error, StorageError};
+use sps2_hash::Hash;
+use sps2_index::{DependencyInfo, Index, VersionEntry};
+use sps2_store::create_package;
+use sps2_types::{Arch, Manifest, Version};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use tokio::fs;
+
+/// Base URL used for indexes of fixtures that are not published anywhere
+const UNPUBLISHED_BASE_URL: &str = "https://fixtures.invalid";
+
+/// Shape of a generated fixture
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FixtureSpec {
+    /// Number of distinct packages
+    pub packages: usize,
+    /// Number of versions generated for each package
+    pub versions: usize,
+    /// Number of files in each package
+    pub files_per_package: usize,
+    /// Size of each file in bytes
+    pub file_size: usize,
+    /// Dependency graph between packages
+    pub shape: GraphShape,
+    /// Seed for randomized graph shapes
+    pub seed: u64,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        Self {
+            packages: 50,
+            versions: 1,
+            files_per_package: 20,
+            file_size: 4096,
+            shape: GraphShape::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// A generated `.sp` package
+#[derive(Debug, Clone)]
+pub struct FixturePackage {
+    pub name: String,
+    pub version: Version,
+    pub sp_path: PathBuf,
+    /// BLAKE3 hash of the `.sp` file
+    pub hash: Hash,
+    /// Names of the packages this one depends on at runtime
+    pub dependencies: Vec<String>,
+}
+
+impl FixturePackage {
+    /// File name of the package, in the form repositories expect
+    #[must_use]
+    pub fn file_name(&self) -> String {
+        self.sp_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// A set of generated packages in a temporary directory
+///
+/// The backing directory is removed when the fixture is dropped.
+#[derive(Debug)]
+pub struct Fixture {
+    spec: FixtureSpec,
+    packages: Vec<FixturePackage>,
+    _dir: TempDir,
+}
+
+impl Fixture {
+    /// Generate packages according to `spec`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the fixture directory or any package cannot be written.
+    pub async fn generate(spec: &FixtureSpec) -> Result<Self, Error> {
+        let dir = TempDir::new().map_err(|e| StorageError::IoError {
+            message: format!("failed to create fixture directory: {e}"),
+        })?;
+        let packages = write_packages(spec, dir.path()).await?;
+        Ok(Self {
+            spec: spec.clone(),
+            packages,
+            _dir: dir,
+        })
+    }
+
+    /// The spec this fixture was generated from
+    #[must_use]
+    pub fn spec(&self) -> &FixtureSpec {
+        &self.spec
+    }
+
+    /// Generated packages, grouped by package in index order
+    #[must_use]
+    pub fn packages(&self) -> &[FixturePackage] {
+        &self.packages
+    }
+
+    /// Latest version of every package
+    #[must_use]
+    pub fn latest_packages(&self) -> Vec<&FixturePackage> {
+        latest(&self.packages)
+    }
+
+    /// Name of the package whose dependency closure is the largest
+    #[must_use]
+    pub fn root_package(&self) -> Option<&str> {
+        self.packages.first().map(|pkg| pkg.name.as_str())
+    }
+
+    /// Repository index listing every fixture package and its dependencies
+    #[must_use]
+    pub fn index(&self) -> Index {
+        index_for(&self.packages, UNPUBLISHED_BASE_URL)
+    }
+}
+
+/// Write the packages described by `spec` into `dir`
+///
+/// # Errors
+///
+/// Returns an error if any package cannot be written or hashed.
+pub async fn write_packages(spec: &FixtureSpec, dir: &Path) -> Result<Vec<FixturePackage>, Error> {
+    fs::create_dir_all(dir).await?;
+    let graph = DependencyGraph::generate(spec.shape, spec.packages, spec.seed);
+
+    let mut packages = Vec::with_capacity(spec.packages * spec.versions);
+    for (i, edges) in graph.edges.iter().enumerate() {
+        let name = package_name(i);
+        let dependencies: Vec<String> = edges.iter().map(|&dep| package_name(dep)).collect();
+
+        for minor in 0..spec.versions {
+            let version = Version::new(1, u64::try_from(minor).unwrap_or(u64::MAX), 0);
+            let sp_path = dir.join(format!("{name}-{version}-1.arm64.sp"));
+            write_package(spec, &name, &version, &dependencies, &sp_path).await?;
+
+            packages.push(FixturePackage {
+                hash: Hash::blake3_hash_file(&sp_path).await?,
+                name: name.clone(),
+                version,
+                sp_path,
+                dependencies: dependencies.clone(),
+            });
+        }
+    }
+    Ok(packages)
+}
+
+/// Build a repository index for `packages` with download URLs below `base_url`
+#[must_use]
+pub fn index_for(packages: &[FixturePackage], base_url: &str) -> Index {
+    let base_url = base_url.trim_end_matches('/');
+    let mut index = Index::new();
+    for pkg in packages {
+        let file_name = pkg.file_name();
+        index.add_version(
+            pkg.name.clone(),
+            pkg.version.to_string(),
+            VersionEntry {
+                revision: 1,
+                arch: "arm64".to_string(),
+                blake3: pkg.hash.to_hex(),
+                download_url: format!("{base_url}/{file_name}"),
+                minisig_url: format!("{base_url}/{file_name}.minisig"),
+                dependencies: DependencyInfo {
+                    runtime: runtime_requirements(&pkg.dependencies),
+                    build: Vec::new(),
+                    recommends: Vec::new(),
+                },
+                sbom: None,
+                description: Some(format!("Synthetic fixture package {}", pkg.name)),
+                homepage: None,
+                license: None,
+            },
+        );
+    }
+    index
+}
+
+async fn write_package(
+    spec: &FixtureSpec,
+    name: &str,
+    version: &Version,
+    dependencies: &[String],
+    sp_path: &Path,
+) -> Result<(), Error> {
+    let src = sp_path.with_extension("src");
+    fs::create_dir_all(&src).await?;
+
+    let mut manifest = Manifest::new(name.to_string(), version, 1, &Arch::Arm64);
+    manifest.dependencies.runtime = runtime_requirements(dependencies);
+    sps2_store::manifest_io::write_manifest(&src.join("manifest.toml"), &manifest).await?;
+
+    let content_dir = src.join("opt/pm/live/share").join(name);
+    fs::create_dir_all(&content_dir).await?;
+    for file in 0..spec.files_per_package {
+        fs::write(
+            content_dir.join(format!("file-{file:04}.dat")),
+            file_contents(&format!("{name}-{version}"), file, spec.file_size),
+        )
+        .await?;
+    }
+
+    create_package(&src, sp_path).await?;
+    fs::remove_dir_all(&src).await?;
+    Ok(())
+}
+
+fn latest(packages: &[FixturePackage]) -> Vec<&FixturePackage> {
+    let mut latest: Vec<&FixturePackage> = Vec::new();
+    for pkg in packages {
+        match latest.last_mut() {
+            Some(last) if last.name == pkg.name => {
+                if pkg.version > last.version {
+                    *last = pkg;
+                }
+            }
+            _ => latest.push(pkg),
+        }
+    }
+    latest
+}
+
+fn package_name(i: usize) -> String {
+    format!("fixture-pkg-{i:05}")
+}
+
+fn runtime_requirements(dependencies: &[String]) -> Vec<String> {
+    dependencies
+        .iter()
+        .map(|dep| format!("{dep}>=1.0.0"))
+        .collect()
+}
+
+/// Deterministic file contents, unique per package version and file so the
+/// content-addressed store cannot deduplicate them
+fn file_contents(package: &str, file: usize, size: usize) -> Vec<u8> {
+    let seed = format!("{package}/{file}\n");
+    seed.bytes().cycle().take(size.max(seed.len())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_contents_are_unique_and_sized() {
+        let a = file_contents("a-1.0.0", 0, 64);
+        let b = file_contents("a-1.0.0", 1, 64);
+        let c = file_contents("a-1.1.0", 0, 64);
+        assert_eq!(a.len(), 64);
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn spec_deserializes_with_defaults() {
+        let spec: FixtureSpec = serde_json::from_str(r#"{"packages": 3}"#).unwrap();
+        assert_eq!(spec.packages, 3);
+        assert_eq!(spec.shape, GraphShape::default());
+    }
+}
@@ -0,0 +1,114 @@
+//! Signed repositories built from synthetic packages
+//!
+//! A published fixture repository has the same layout `sbs` produces: `.sp`
+//! files with detached minisign signatures, `keys.json`, and a signed
+//! `index.json`. A fresh throwaway key pair is generated for every
+//! repository, so only signatures differ between otherwise identical runs.
+
+use crate::packages::{index_for, write_packages, FixturePackage, FixtureSpec};
+use minisign::KeyPair;
+use sps2_errors::Error;
+use sps2_repository::keys as repo_keys;
+use sps2_repository::{LocalStore, Publisher};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// File name of the unencrypted secret key written next to the repository
+pub const SECRET_KEY_FILE: &str = "fixture.key";
+
+/// File name of the public key written next to the repository
+pub const PUBLIC_KEY_FILE: &str = "fixture.pub";
+
+/// A generated repository on disk
+#[derive(Debug, Clone)]
+pub struct RepositoryFixture {
+    /// Directory holding packages, signatures, `keys.json` and the index
+    pub dir: PathBuf,
+    /// Base URL the index points download links at
+    pub base_url: String,
+    pub packages: Vec<FixturePackage>,
+    /// Minisign public key (base64) the repository is signed with
+    pub public_key: String,
+    /// Directory holding [`SECRET_KEY_FILE`] and [`PUBLIC_KEY_FILE`]
+    pub key_dir: PathBuf,
+}
+
+impl RepositoryFixture {
+    /// Generate packages for `spec` and publish them as a signed repository
+    ///
+    /// Key files are written to `key_dir`, which should not be served
+    /// together with `repo_dir`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if packages cannot be generated, key generation or
+    /// signing fails, or any file cannot be written.
+    pub async fn publish(
+        spec: &FixtureSpec,
+        repo_dir: &Path,
+        key_dir: &Path,
+        base_url: &str,
+    ) -> Result<Self, Error> {
+        let packages = write_packages(spec, repo_dir).await?;
+        let (secret_key, public_key) = generate_keys(key_dir).await?;
+
+        let keys = repo_keys::make_single_key(
+            public_key.clone(),
+            Some("sps2 fixture repository key".to_string()),
+        )?;
+        repo_keys::write_keys_json(repo_dir, &keys).await?;
+
+        for pkg in &packages {
+            let file_name = pkg.file_name();
+            let data = fs::read(&pkg.sp_path).await?;
+            let sig = sps2_net::signing::minisign_sign_bytes(
+                &data,
+                &secret_key,
+                None,
+                Some("sps2 package signature"),
+                Some(&file_name),
+            )?;
+            fs::write(repo_dir.join(format!("{file_name}.minisig")), sig).await?;
+        }
+
+        let index = index_for(&packages, base_url);
+        Publisher::new(LocalStore::new(repo_dir), base_url.to_string())
+            .publish_index(&index, &secret_key, None)
+            .await?;
+
+        Ok(Self {
+            dir: repo_dir.to_path_buf(),
+            base_url: base_url.to_string(),
+            packages,
+            public_key,
+            key_dir: key_dir.to_path_buf(),
+        })
+    }
+
+    /// Path of the secret key the repository was signed with
+    #[must_use]
+    pub fn secret_key_path(&self) -> PathBuf {
+        self.key_dir.join(SECRET_KEY_FILE)
+    }
+}
+
+/// Write an unencrypted key pair to `key_dir`; returns the secret key path
+/// and the base64 public key
+async fn generate_keys(key_dir: &Path) -> Result<(PathBuf, String), Error> {
+    fs::create_dir_all(key_dir).await?;
+    let KeyPair { pk, sk } = KeyPair::generate_unencrypted_keypair()
+        .map_err(|e| Error::internal(format!("keypair generation failed: {e}")))?;
+
+    let sk_box = sk
+        .to_box(None)
+        .map_err(|e| Error::internal(format!("secret key serialize failed: {e}")))?;
+    let pk_box = pk
+        .to_box()
+        .map_err(|e| Error::internal(format!("public key serialize failed: {e}")))?;
+
+    let secret_key = key_dir.join(SECRET_KEY_FILE);
+    fs::write(&secret_key, sk_box.to_string()).await?;
+    fs::write(key_dir.join(PUBLIC_KEY_FILE), pk_box.to_string()).await?;
+
+    Ok((secret_key, repo_keys::extract_base64(&pk_box.to_string())))
+}
@@ -0,0 +1,230 @@
+//! File-by-file comparison of one package's live files with its stored content
+
+use crate::ignore::IgnoreRules;
+use crate::verifier::is_generated;
+use serde::Serialize;
+use sps2_errors::{Error, InstallError};
+use sps2_hash::Hash;
+use sps2_state::{queries, StateManager};
+use sps2_store::{FileVerificationResult, PackageStore};
+use std::collections::{BTreeSet, HashSet};
+use std::path::Path;
+use tokio::fs;
+
+/// Text files larger than this are compared by hash only
+const MAX_TEXT_DIFF_BYTES: u64 = 1024 * 1024;
+
+/// The store drops write bits from every object it links, so only read,
+/// execute, setuid and setgid bits are compared
+const COMPARED_MODE_BITS: u32 = 0o6555;
+
+/// How a live file differs from what its package stored
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum FileChange {
+    /// Contents no longer hash to the stored object
+    HashMismatch { expected: String, actual: String },
+    /// Read, execute, setuid or setgid permission bits changed
+    ModeChanged { expected: u32, actual: u32 },
+    /// Tracked by the package but gone from the live prefix
+    Missing,
+    /// Untracked file next to the package's files
+    Extra,
+}
+
+/// One difference between a package's live files and the store
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    #[serde(flatten)]
+    pub change: FileChange,
+    /// Unified diff from the stored to the live content, for text files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unified: Option<String>,
+}
+
+/// Differences between a package's live files and its stored content
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageDiff {
+    pub package: String,
+    pub version: String,
+    /// Tracked files compared
+    pub files_compared: usize,
+    pub differences: Vec<FileDiff>,
+}
+
+impl PackageDiff {
+    /// Whether the live files match the store
+    #[must_use]
+    pub fn is_clean(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+pub(crate) async fn diff_package(
+    state: &StateManager,
+    store: &PackageStore,
+    ignore: &IgnoreRules,
+    name: &str,
+    unified: bool,
+) -> Result<PackageDiff, Error> {
+    let mut tx = state.begin_transaction().await?;
+    let state_id = queries::get_active_state(&mut tx).await?;
+    let packages = queries::get_state_packages(&mut tx, &state_id).await?;
+    let package = packages
+        .iter()
+        .find(|package| package.name == name)
+        .ok_or_else(|| InstallError::PackageNotInstalled {
+            package: name.to_string(),
+        })?;
+    let entries = queries::get_package_file_entries_by_name(
+        &mut tx,
+        &state_id,
+        &package.name,
+        &package.version,
+    )
+    .await?;
+    let tracked: HashSet<String> = queries::get_state_file_paths(&mut tx, &state_id)
+        .await?
+        .into_iter()
+        .collect();
+    tx.commit().await?;
+
+    let live_root = state.live_path();
+    let mut differences = Vec::new();
+    let mut files_compared = 0;
+    let mut directories = BTreeSet::new();
+    for entry in &entries {
+        if let Some(parent) = Path::new(&entry.relative_path).parent() {
+            directories.insert(parent.to_path_buf());
+        }
+        if ignore.ignores(name, &entry.relative_path) {
+            continue;
+        }
+
+        let live_path = live_root.join(&entry.relative_path);
+        let Ok(metadata) = fs::symlink_metadata(&live_path).await else {
+            differences.push(FileDiff {
+                path: entry.relative_path.clone(),
+                change: FileChange::Missing,
+                unified: None,
+            });
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        files_compared += 1;
+
+        let expected = Hash::from_hex(&entry.file_hash)?;
+        if let FileVerificationResult::HashMismatch { actual, .. } = store
+            .file_store()
+            .compare_file(&expected, &live_path)
+            .await?
+        {
+            let unified = if unified {
+                text_diff(
+                    &store.file_path(&expected),
+                    &live_path,
+                    &entry.relative_path,
+                )
+                .await
+            } else {
+                None
+            };
+            differences.push(FileDiff {
+                path: entry.relative_path.clone(),
+                change: FileChange::HashMismatch {
+                    expected: expected.to_hex(),
+                    actual: actual.to_hex(),
+                },
+                unified,
+            });
+        }
+
+        let expected_mode = u32::try_from(entry.permissions).unwrap_or(0) & COMPARED_MODE_BITS;
+        let actual_mode = file_mode(&metadata) & COMPARED_MODE_BITS;
+        if expected_mode != actual_mode {
+            differences.push(FileDiff {
+                path: entry.relative_path.clone(),
+                change: FileChange::ModeChanged {
+                    expected: expected_mode,
+                    actual: actual_mode,
+                },
+                unified: None,
+            });
+        }
+    }
+
+    // Untracked files in the directories the package installs into
+    let installed: HashSet<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+    for directory in directories {
+        let Ok(mut children) = fs::read_dir(live_root.join(&directory)).await else {
+            continue;
+        };
+        while let Some(child) = children.next_entry().await? {
+            if child.file_type().await?.is_dir() {
+                continue;
+            }
+            let path = directory
+                .join(child.file_name())
+                .to_string_lossy()
+                .replace('\\', "/");
+            if is_generated(&path)
+                || tracked.contains(&path)
+                || ignore.ignores_untracked(&path, &installed)
+            {
+                continue;
+            }
+            differences.push(FileDiff {
+                path,
+                change: FileChange::Extra,
+                unified: None,
+            });
+        }
+    }
+
+    differences.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(PackageDiff {
+        package: package.name.clone(),
+        version: package.version.clone(),
+        files_compared,
+        differences,
+    })
+}
+
+/// Unified diff of two text files, or `None` if either is binary, too large
+/// or unreadable
+async fn text_diff(stored: &Path, live: &Path, path: &str) -> Option<String> {
+    let old = read_text(stored).await?;
+    let new = read_text(live).await?;
+    let diff = similar::TextDiff::from_lines(&old, &new);
+    Some(
+        diff.unified_diff()
+            .header(&format!("store/{path}"), &format!("live/{path}"))
+            .to_string(),
+    )
+}
+
+async fn read_text(path: &Path) -> Option<String> {
+    let metadata = fs::metadata(path).await.ok()?;
+    if metadata.len() > MAX_TEXT_DIFF_BYTES {
+        return None;
+    }
+    let bytes = fs::read(path).await.ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(unix)]
+pub(crate) fn file_mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn file_mode(_metadata: &std::fs::Metadata) -> u32 {
+    COMPARED_MODE_BITS
+}
@@ -0,0 +1,124 @@
+//! Paths verification leaves alone
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use sps2_errors::{ConfigError, Error};
+use std::collections::{BTreeMap, HashSet};
+
+/// Globs for files packages legitimately generate under the live prefix
+///
+/// Patterns match paths relative to the live prefix; `*` stops at `/` and
+/// `**` crosses directories. Global patterns apply to every file, package
+/// patterns to that package's files and to untracked files while the package
+/// is installed.
+#[derive(Debug, Clone)]
+pub struct IgnoreRules {
+    global: GlobSet,
+    packages: BTreeMap<String, GlobSet>,
+}
+
+impl Default for IgnoreRules {
+    fn default() -> Self {
+        Self {
+            global: GlobSet::empty(),
+            packages: BTreeMap::new(),
+        }
+    }
+}
+
+impl IgnoreRules {
+    /// Compile global and per-package patterns
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the first pattern that is not a valid glob.
+    pub fn new(global: &[String], packages: &BTreeMap<String, Vec<String>>) -> Result<Self, Error> {
+        Ok(Self {
+            global: compile(global, "guard.ignore")?,
+            packages: packages
+                .iter()
+                .map(|(name, patterns)| {
+                    compile(patterns, &format!("guard.package_ignore.{name}"))
+                        .map(|set| (name.clone(), set))
+                })
+                .collect::<Result<_, Error>>()?,
+        })
+    }
+
+    /// Whether `path`, a file of `package`, is ignored
+    #[must_use]
+    pub fn ignores(&self, package: &str, path: &str) -> bool {
+        self.global.is_match(path)
+            || self
+                .packages
+                .get(package)
+                .is_some_and(|set| set.is_match(path))
+    }
+
+    /// Whether `path`, tracked by no package, is ignored given the installed
+    /// package names
+    #[must_use]
+    pub fn ignores_untracked(&self, path: &str, installed: &HashSet<&str>) -> bool {
+        self.global.is_match(path)
+            || self
+                .packages
+                .iter()
+                .any(|(name, set)| installed.contains(name.as_str()) && set.is_match(path))
+    }
+}
+
+fn compile(patterns: &[String], field: &str) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|_| ConfigError::InvalidValue {
+                field: field.to_string(),
+                value: pattern.clone(),
+            })?;
+        builder.add(glob);
+    }
+    builder.build().map_err(|e| {
+        ConfigError::InvalidValue {
+            field: field.to_string(),
+            value: e.to_string(),
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn package_patterns_only_apply_to_their_package() {
+        let rules = IgnoreRules::new(
+            &["**/__pycache__/**".to_string()],
+            &BTreeMap::from([("node".to_string(), vec!["lib/node/.cache/**".to_string()])]),
+        )
+        .unwrap();
+
+        assert!(rules.ignores("python", "lib/python3.12/__pycache__/os.cpython-312.pyc"));
+        assert!(rules.ignores("node", "lib/node/.cache/x/y"));
+        assert!(!rules.ignores("python", "lib/node/.cache/x/y"));
+        assert!(!rules.ignores("node", "lib/node/index.js"));
+
+        let mut installed = HashSet::from(["python"]);
+        assert!(!rules.ignores_untracked("lib/node/.cache/x", &installed));
+        installed.insert("node");
+        assert!(rules.ignores_untracked("lib/node/.cache/x", &installed));
+    }
+
+    #[test]
+    fn single_star_stays_in_one_directory() {
+        let rules = IgnoreRules::new(&["share/*.log".to_string()], &BTreeMap::new()).unwrap();
+        assert!(rules.ignores("any", "share/build.log"));
+        assert!(!rules.ignores("any", "share/nested/build.log"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(IgnoreRules::new(&["lib/[".to_string()], &BTreeMap::new()).is_err());
+    }
+}
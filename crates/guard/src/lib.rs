@@ -1,10 +1,17 @@
 #![warn(mismatched_lifetime_syntaxes)]
 //! Lightweight state guard utilities for verifying and healing package installations.
 
+mod diff;
+mod ignore;
+mod managed;
+mod mtime;
 mod refcount;
 mod store;
 mod verifier;
 
+pub use diff::{FileChange, FileDiff, PackageDiff};
+pub use ignore::IgnoreRules;
+pub use managed::{sync_managed_directories, ManagedSync};
 pub use refcount::sync_refcounts_to_active_state;
 pub use store::{StoreVerificationConfig, StoreVerificationStats, StoreVerifier};
 pub use verifier::{Discrepancy, VerificationLevel, VerificationResult, Verifier};
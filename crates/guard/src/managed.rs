@@ -0,0 +1,375 @@
+//! Directories outside the live prefix that mirror part of it
+//!
+//! A [`ManagedDirectory`] holds copies of the package files below its
+//! `source` in the live prefix. The live prefix is the reference: copies are
+//! populated and healed from it, so they match what is installed once the
+//! live prefix does. The paths populated last are listed in `.sps2-managed`
+//! inside the directory, which lets a later sync remove copies no package
+//! provides any more without touching files other software keeps there.
+
+use crate::verifier::{load_packages, Discrepancy};
+use sps2_errors::Error;
+use sps2_hash::Hash;
+use sps2_state::{Package, PackageFileEntry, StateManager};
+use sps2_types::{ManagedDirectory, ManagedDriftPolicy, ManagedUntrackedPolicy};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use walkdir::WalkDir;
+
+/// Paths populated by the last sync, relative to the directory
+const MANIFEST: &str = ".sps2-managed";
+
+/// Changes a sync made to one managed directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedSync {
+    pub path: PathBuf,
+    /// Copies written because they were missing or differed from the live
+    /// prefix
+    pub copied: usize,
+    /// Copies removed because no package provides them any more
+    pub removed: usize,
+}
+
+/// A package file a managed directory holds a copy of
+pub(crate) struct ManagedFile<'a> {
+    pub(crate) package: &'a Package,
+    pub(crate) entry: &'a PackageFileEntry,
+    /// Path of the copy, relative to the managed directory
+    pub(crate) rel_path: &'a str,
+}
+
+/// What verifying a managed directory found and repaired
+#[derive(Default)]
+pub(crate) struct ManagedCheck {
+    pub(crate) discrepancies: Vec<Discrepancy>,
+    /// Absolute paths restored or removed
+    pub(crate) healed: Vec<String>,
+}
+
+/// Populate the managed directories that have `populate` set from the live
+/// prefix of the active state
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read or a directory
+/// cannot be written; directories before it have been synced.
+pub async fn sync_managed_directories(
+    state: &StateManager,
+    directories: &[ManagedDirectory],
+) -> Result<Vec<ManagedSync>, Error> {
+    if !directories.iter().any(|directory| directory.populate) {
+        return Ok(Vec::new());
+    }
+    let state_id = state.get_active_state().await?;
+    let packages = load_packages(state, &state_id).await?;
+    let mut synced = Vec::new();
+    for directory in directories.iter().filter(|directory| directory.populate) {
+        let files = managed_files(directory, &packages);
+        synced.push(sync_directory(directory, state.live_path(), &files).await?);
+    }
+    Ok(synced)
+}
+
+/// The files of `packages` that `directory` holds copies of
+pub(crate) fn managed_files<'a>(
+    directory: &ManagedDirectory,
+    packages: &'a [(Package, Vec<PackageFileEntry>)],
+) -> Vec<ManagedFile<'a>> {
+    let source = directory.source.trim_end_matches('/');
+    packages
+        .iter()
+        .flat_map(|(package, entries)| {
+            entries.iter().filter_map(move |entry| {
+                let rel_path = entry
+                    .relative_path
+                    .strip_prefix(source)?
+                    .strip_prefix('/')?;
+                (!rel_path.is_empty()).then_some(ManagedFile {
+                    package,
+                    entry,
+                    rel_path,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Bring `directory` in line with the live prefix at `live_root`
+///
+/// Copies that are missing or differ are written again, and copies listed
+/// in the manifest that no file provides any more are removed.
+pub(crate) async fn sync_directory(
+    directory: &ManagedDirectory,
+    live_root: &Path,
+    files: &[ManagedFile<'_>],
+) -> Result<ManagedSync, Error> {
+    let mut sync = ManagedSync {
+        path: directory.path.clone(),
+        copied: 0,
+        removed: 0,
+    };
+    fs::create_dir_all(&directory.path).await?;
+
+    let mut current = BTreeSet::new();
+    for file in files {
+        let source = live_root.join(&file.entry.relative_path);
+        if fs::symlink_metadata(&source).await.is_err() {
+            // Healing the live prefix brings it back, and the next sync
+            // copies it
+            continue;
+        }
+        current.insert(file.rel_path.to_string());
+        let target = directory.path.join(file.rel_path);
+        if !copy_matches(&source, &target, file.entry, true).await? {
+            copy_from_live(&source, &target).await?;
+            sync.copied += 1;
+        }
+    }
+
+    for rel_path in read_manifest(&directory.path).await? {
+        if current.contains(&rel_path) {
+            continue;
+        }
+        match fs::remove_file(directory.path.join(&rel_path)).await {
+            Ok(()) => sync.removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    let manifest: Vec<&str> = current.iter().map(String::as_str).collect();
+    fs::write(directory.path.join(MANIFEST), manifest.join("\n")).await?;
+    Ok(sync)
+}
+
+/// Check the copies in `directory` against the live prefix
+///
+/// Without `hash`, only their presence is checked. Discrepancies carry
+/// absolute paths, which tells them apart from those in the live prefix.
+pub(crate) async fn verify_directory(
+    directory: &ManagedDirectory,
+    live_root: &Path,
+    files: &[ManagedFile<'_>],
+    hash: bool,
+    heal: bool,
+) -> Result<ManagedCheck, Error> {
+    let mut check = ManagedCheck::default();
+    let restore = heal && directory.drift == ManagedDriftPolicy::Heal;
+
+    for file in files {
+        let source = live_root.join(&file.entry.relative_path);
+        let target = directory.path.join(file.rel_path);
+        let path = target.display().to_string();
+        let present = fs::symlink_metadata(&target).await.is_ok();
+        if present && copy_matches(&source, &target, file.entry, hash).await? {
+            continue;
+        }
+        if restore
+            && fs::symlink_metadata(&source).await.is_ok()
+            && copy_from_live(&source, &target).await.is_ok()
+        {
+            check.healed.push(path);
+            continue;
+        }
+        let package = file.package.name.clone();
+        let version = file.package.version.clone();
+        check.discrepancies.push(if present {
+            Discrepancy::CorruptedFile {
+                package,
+                version,
+                path,
+            }
+        } else {
+            Discrepancy::MissingFile {
+                package,
+                version,
+                path,
+            }
+        });
+    }
+
+    if directory.untracked == ManagedUntrackedPolicy::Ignore || !directory.path.exists() {
+        return Ok(check);
+    }
+    let expected: HashSet<&str> = files.iter().map(|file| file.rel_path).collect();
+    for entry in WalkDir::new(&directory.path).follow_links(false) {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let Ok(rel_path) = entry.path().strip_prefix(&directory.path) else {
+            continue;
+        };
+        let rel_path = rel_path.to_string_lossy();
+        if rel_path == MANIFEST || expected.contains(rel_path.as_ref()) {
+            continue;
+        }
+        let path = entry.path().display().to_string();
+        if heal
+            && directory.untracked == ManagedUntrackedPolicy::Remove
+            && fs::remove_file(entry.path()).await.is_ok()
+        {
+            check.healed.push(path);
+            continue;
+        }
+        check
+            .discrepancies
+            .push(Discrepancy::UnexpectedFile { path });
+    }
+    Ok(check)
+}
+
+/// Whether the copy at `target` matches the live file at `source`
+///
+/// Symlinks match when they point at the same place. Without `hash`, any
+/// regular file matches a regular file.
+async fn copy_matches(
+    source: &Path,
+    target: &Path,
+    entry: &PackageFileEntry,
+    hash: bool,
+) -> Result<bool, Error> {
+    let Ok(target_meta) = fs::symlink_metadata(target).await else {
+        return Ok(false);
+    };
+    if fs::symlink_metadata(source)
+        .await
+        .is_ok_and(|meta| meta.file_type().is_symlink())
+    {
+        return Ok(target_meta.file_type().is_symlink()
+            && fs::read_link(source).await.ok() == fs::read_link(target).await.ok());
+    }
+    if !target_meta.is_file() {
+        return Ok(false);
+    }
+    if !hash {
+        return Ok(true);
+    }
+    let Ok(expected) = Hash::from_hex(&entry.file_hash) else {
+        return Ok(false);
+    };
+    Ok(Hash::hash_file(target).await? == expected)
+}
+
+/// Replace whatever is at `target` with a copy of the live file `source`
+async fn copy_from_live(source: &Path, target: &Path) -> Result<(), Error> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    match fs::symlink_metadata(target).await {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(target).await?,
+        Ok(_) => fs::remove_file(target).await?,
+        Err(_) => {}
+    }
+    let meta = fs::symlink_metadata(source).await?;
+    if meta.file_type().is_symlink() {
+        fs::symlink(fs::read_link(source).await?, target).await?;
+    } else {
+        fs::copy(source, target).await?;
+    }
+    Ok(())
+}
+
+async fn read_manifest(dir: &Path) -> Result<Vec<String>, Error> {
+    match fs::read_to_string(dir.join(MANIFEST)).await {
+        Ok(content) => Ok(content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entry(relative_path: &str, content: &[u8]) -> PackageFileEntry {
+        PackageFileEntry {
+            id: 0,
+            package_id: 1,
+            file_hash: Hash::from_data(content).to_hex(),
+            relative_path: relative_path.to_string(),
+            permissions: 0o644,
+            uid: 0,
+            gid: 0,
+            mtime: None,
+            flags: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn copies_follow_the_live_prefix() {
+        let temp = TempDir::new().unwrap();
+        let live = temp.path().join("live");
+        std::fs::create_dir_all(live.join("share/acme/fonts")).unwrap();
+        std::fs::write(live.join("share/acme/fonts/a.ttf"), b"a").unwrap();
+        std::fs::write(live.join("share/acme/b.conf"), b"b").unwrap();
+        let directory = ManagedDirectory {
+            path: temp.path().join("shared"),
+            source: "share/acme".to_string(),
+            populate: true,
+            drift: ManagedDriftPolicy::Heal,
+            untracked: ManagedUntrackedPolicy::Report,
+        };
+        let package = Package {
+            id: 1,
+            state_id: String::new(),
+            name: "acme".to_string(),
+            version: "1.0.0".to_string(),
+            hash: String::new(),
+            size: 0,
+            installed_at: 0,
+            venv_path: None,
+            recommended: false,
+            dependency: false,
+        };
+        let packages = vec![(
+            package.clone(),
+            vec![
+                entry("share/acme/fonts/a.ttf", b"a"),
+                entry("share/acme/b.conf", b"b"),
+                entry("share/acme-extra/c", b"c"),
+                entry("bin/acme", b"bin"),
+            ],
+        )];
+
+        let files = managed_files(&directory, &packages);
+        let paths: Vec<&str> = files.iter().map(|file| file.rel_path).collect();
+        assert_eq!(paths, ["fonts/a.ttf", "b.conf"]);
+
+        let sync = sync_directory(&directory, &live, &files).await.unwrap();
+        assert_eq!((sync.copied, sync.removed), (2, 0));
+        assert_eq!(
+            std::fs::read(directory.path.join("fonts/a.ttf")).unwrap(),
+            b"a"
+        );
+
+        std::fs::write(directory.path.join("b.conf"), b"edited").unwrap();
+        std::fs::write(directory.path.join("local"), b"mine").unwrap();
+        let check = verify_directory(&directory, &live, &files, true, false)
+            .await
+            .unwrap();
+        let kinds: Vec<&str> = check.discrepancies.iter().map(Discrepancy::kind).collect();
+        assert_eq!(kinds, ["corrupted_file", "unexpected_file"]);
+        let check = verify_directory(&directory, &live, &files, true, true)
+            .await
+            .unwrap();
+        assert_eq!(check.healed.len(), 1);
+        assert_eq!(std::fs::read(directory.path.join("b.conf")).unwrap(), b"b");
+
+        // Only copies the last sync wrote are removed once the file is gone
+        let packages = vec![(package, vec![entry("share/acme/b.conf", b"b")])];
+        let files = managed_files(&directory, &packages);
+        let sync = sync_directory(&directory, &live, &files).await.unwrap();
+        assert_eq!((sync.copied, sync.removed), (0, 1));
+        assert!(!directory.path.join("fonts/a.ttf").exists());
+        assert!(directory.path.join("local").exists());
+    }
+}
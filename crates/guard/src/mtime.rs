@@ -0,0 +1,109 @@
+//! Size and mtime fast path for quick verification
+
+use sps2_errors::Error;
+use sps2_state::{queries, FileMTimeTracker, StateManager};
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+
+/// Trackers loaded at the start of a run and the changes to write back
+pub(crate) struct MTimeCache {
+    known: HashMap<String, FileMTimeTracker>,
+    verified: Vec<FileMTimeTracker>,
+    stale: Vec<String>,
+    skipped: usize,
+    hashed: usize,
+}
+
+impl MTimeCache {
+    pub(crate) async fn load(state: &StateManager) -> Result<Self, Error> {
+        let mut tx = state.begin_transaction().await?;
+        let known = queries::get_all_file_mtimes(&mut tx).await?;
+        tx.commit().await?;
+        Ok(Self {
+            known,
+            verified: Vec::new(),
+            stale: Vec::new(),
+            skipped: 0,
+            hashed: 0,
+        })
+    }
+
+    /// Whether `path` still has the size and mtime it had when it last hashed
+    /// to `file_hash`; counts the file as skipped if so
+    pub(crate) fn is_unchanged(
+        &mut self,
+        path: &str,
+        metadata: &Metadata,
+        file_hash: &str,
+    ) -> bool {
+        let unchanged = match (self.known.get(path), fingerprint(metadata)) {
+            (Some(tracker), Some((mtime, size))) => tracker.is_current(mtime, size, file_hash),
+            _ => false,
+        };
+        if unchanged {
+            self.skipped += 1;
+        }
+        unchanged
+    }
+
+    /// Remember that `path` hashed to `file_hash` with this metadata
+    pub(crate) fn record(&mut self, path: &str, metadata: &Metadata, file_hash: &str) {
+        self.hashed += 1;
+        if let Some((mtime, size)) = fingerprint(metadata) {
+            let tracker = FileMTimeTracker {
+                file_path: path.to_string(),
+                last_verified_mtime: mtime,
+                last_verified_size: Some(size),
+                file_hash: Some(file_hash.to_string()),
+            };
+            if self.known.get(path) != Some(&tracker) {
+                self.verified.push(tracker);
+            }
+        }
+    }
+
+    /// Forget `path`, which failed verification
+    pub(crate) fn forget(&mut self, path: &str) {
+        self.hashed += 1;
+        if self.known.contains_key(path) {
+            self.stale.push(path.to_string());
+        }
+    }
+
+    /// Share of hashed-or-skipped files that were skipped
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn hit_rate(&self) -> f32 {
+        let checked = self.skipped + self.hashed;
+        if checked == 0 {
+            0.0
+        } else {
+            self.skipped as f32 / checked as f32
+        }
+    }
+
+    /// Write the changes back in one transaction
+    pub(crate) async fn save(self, state: &StateManager) -> Result<(), Error> {
+        if self.verified.is_empty() && self.stale.is_empty() {
+            return Ok(());
+        }
+        let mut tx = state.begin_transaction().await?;
+        for tracker in &self.verified {
+            queries::update_file_mtime(&mut tx, tracker).await?;
+        }
+        for path in &self.stale {
+            queries::delete_file_mtime(&mut tx, path).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Modification time in nanoseconds and size of a file
+fn fingerprint(metadata: &Metadata) -> Option<(i64, i64)> {
+    let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((
+        i64::try_from(mtime.as_nanos()).ok()?,
+        i64::try_from(metadata.len()).ok()?,
+    ))
+}
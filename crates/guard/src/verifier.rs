@@ -1,3 +1,7 @@
+use crate::diff::{diff_package, file_mode, PackageDiff};
+use crate::ignore::IgnoreRules;
+use crate::managed;
+use crate::mtime::MTimeCache;
 use crate::refcount::sync_refcounts_to_active_state;
 use sps2_errors::{Error, OpsError};
 use sps2_events::{
@@ -5,9 +9,13 @@ use sps2_events::{
     GuardSeverity, GuardTargetSummary, GuardVerificationMetrics,
 };
 use sps2_hash::Hash;
+use sps2_platform::filesystem_helpers::{apply_special_permissions, file_flags, SPECIAL_MODE_BITS};
 use sps2_platform::PlatformManager;
-use sps2_state::{queries, Package, PackageFileEntry, StateManager};
+use sps2_state::{
+    queries, Package, PackageFileEntry, StateManager, VerificationCounts, VerificationPath,
+};
 use sps2_store::{PackageStore, StoredPackage};
+use sps2_types::{ManagedDirectory, ManagedDriftPolicy, Shell};
 use std::collections::HashSet;
 use std::path::Path;
 use std::time::Instant;
@@ -18,15 +26,23 @@ use walkdir::WalkDir;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum EntryStatus {
     Ok,
+    Healed,
     Missing,
     Corrupted,
+    PermissionsChanged,
 }
 
 /// Verification level controls the depth of checks performed.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VerificationLevel {
+    /// Check presence and re-hash only files whose size or modification time
+    /// changed since they last hashed correctly; copies in managed
+    /// directories are only checked for presence
     Quick,
+    /// Check what `Quick` does, and re-hash the copies in managed
+    /// directories
     Standard,
+    /// Check presence and re-hash every file
     Full,
 }
 
@@ -41,6 +57,16 @@ impl From<&str> for VerificationLevel {
 }
 
 impl VerificationLevel {
+    /// Name of the level as accepted by `From<&str>`
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VerificationLevel::Quick => "quick",
+            VerificationLevel::Standard => "standard",
+            VerificationLevel::Full => "full",
+        }
+    }
+
     fn as_guard_level(self) -> GuardLevel {
         match self {
             VerificationLevel::Quick => GuardLevel::Quick,
@@ -63,6 +89,12 @@ pub enum Discrepancy {
         version: String,
         path: String,
     },
+    /// Setuid/setgid bits or BSD flags differ from those installed
+    PermissionsChanged {
+        package: String,
+        version: String,
+        path: String,
+    },
     MissingPackageContent {
         package: String,
         version: String,
@@ -73,6 +105,52 @@ pub enum Discrepancy {
 }
 
 impl Discrepancy {
+    /// Stable name of the discrepancy class
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Discrepancy::MissingFile { .. } => "missing_file",
+            Discrepancy::CorruptedFile { .. } => "corrupted_file",
+            Discrepancy::PermissionsChanged { .. } => "permissions_changed",
+            Discrepancy::MissingPackageContent { .. } => "missing_package_content",
+            Discrepancy::UnexpectedFile { .. } => "unexpected_file",
+        }
+    }
+
+    /// Where the discrepancy was found, as recorded in verification history
+    ///
+    /// Package-level discrepancies are keyed by `name-version`.
+    #[must_use]
+    pub fn to_path(&self) -> VerificationPath {
+        let (path, package) = match self {
+            Discrepancy::MissingFile {
+                package,
+                version,
+                path,
+            }
+            | Discrepancy::CorruptedFile {
+                package,
+                version,
+                path,
+            }
+            | Discrepancy::PermissionsChanged {
+                package,
+                version,
+                path,
+            } => (path.clone(), Some(format!("{package}-{version}"))),
+            Discrepancy::MissingPackageContent { package, version } => {
+                let id = format!("{package}-{version}");
+                (id.clone(), Some(id))
+            }
+            Discrepancy::UnexpectedFile { path } => (path.clone(), None),
+        };
+        VerificationPath {
+            kind: self.kind().to_string(),
+            path,
+            package,
+        }
+    }
+
     fn to_event(&self) -> GuardDiscrepancy {
         match self {
             Discrepancy::MissingFile {
@@ -103,6 +181,22 @@ impl Discrepancy {
                 auto_heal_available: true,
                 requires_confirmation: false,
             },
+            Discrepancy::PermissionsChanged {
+                package,
+                version,
+                path,
+            } => GuardDiscrepancy {
+                kind: "permissions_changed".to_string(),
+                severity: GuardSeverity::High,
+                location: Some(path.clone()),
+                package: Some(package.clone()),
+                version: Some(version.clone()),
+                message: format!(
+                    "{package}-{version} has different setuid/setgid bits or flags on {path}"
+                ),
+                auto_heal_available: true,
+                requires_confirmation: false,
+            },
             Discrepancy::MissingPackageContent { package, version } => GuardDiscrepancy {
                 kind: "missing_package_content".to_string(),
                 severity: GuardSeverity::Critical,
@@ -130,21 +224,58 @@ impl Discrepancy {
 /// Result of a verification run.
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct VerificationResult {
+    /// Version of the JSON schema the result follows
+    pub schema_version: u32,
     pub state_id: Uuid,
     pub discrepancies: Vec<Discrepancy>,
     pub is_valid: bool,
     pub duration_ms: u64,
+    /// Discrepancies repaired during the run; they are not in `discrepancies`
+    pub healed: usize,
 }
 
 impl VerificationResult {
+    /// Current version of the verification result schema
+    pub const SCHEMA_VERSION: u32 = 1;
+
     pub fn new(state_id: Uuid, discrepancies: Vec<Discrepancy>, duration_ms: u64) -> Self {
         let is_valid = discrepancies.is_empty();
         Self {
+            schema_version: Self::SCHEMA_VERSION,
             state_id,
             discrepancies,
             is_valid,
             duration_ms,
+            healed: 0,
+        }
+    }
+
+    /// Set the number of discrepancies healed during the run
+    #[must_use]
+    pub fn with_healed(mut self, healed: usize) -> Self {
+        self.healed = healed;
+        self
+    }
+
+    /// Remaining discrepancies by class, with the healed count
+    #[must_use]
+    pub fn counts(&self) -> VerificationCounts {
+        let mut counts = VerificationCounts {
+            healed: i64::try_from(self.healed).unwrap_or(i64::MAX),
+            ..VerificationCounts::default()
+        };
+        for discrepancy in &self.discrepancies {
+            match discrepancy {
+                Discrepancy::MissingFile { .. } => counts.missing_files += 1,
+                // Counted with corrupted files: the file is not as installed
+                Discrepancy::CorruptedFile { .. } | Discrepancy::PermissionsChanged { .. } => {
+                    counts.corrupted_files += 1;
+                }
+                Discrepancy::MissingPackageContent { .. } => counts.missing_packages += 1,
+                Discrepancy::UnexpectedFile { .. } => counts.unexpected_files += 1,
+            }
         }
+        counts
     }
 }
 
@@ -153,6 +284,9 @@ pub struct Verifier {
     state: StateManager,
     store: PackageStore,
     tx: EventSender,
+    ignore: IgnoreRules,
+    managed: Vec<ManagedDirectory>,
+    heal_source: &'static str,
 }
 
 impl EventEmitter for Verifier {
@@ -163,7 +297,35 @@ impl EventEmitter for Verifier {
 
 impl Verifier {
     pub fn new(state: StateManager, store: PackageStore, tx: EventSender) -> Self {
-        Self { state, store, tx }
+        Self {
+            state,
+            store,
+            tx,
+            ignore: IgnoreRules::default(),
+            managed: Vec::new(),
+            heal_source: "store",
+        }
+    }
+
+    /// Skip files matching `ignore` when verifying and looking for orphans
+    #[must_use]
+    pub fn with_ignore_rules(mut self, ignore: IgnoreRules) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// Also verify the copies in `directories`, following their policies
+    #[must_use]
+    pub fn with_managed_directories(mut self, directories: Vec<ManagedDirectory>) -> Self {
+        self.managed = directories;
+        self
+    }
+
+    /// Label reported as the source of restored paths, `store` by default
+    #[must_use]
+    pub fn with_heal_source(mut self, source: &'static str) -> Self {
+        self.heal_source = source;
+        self
     }
 
     pub async fn verify(&self, level: VerificationLevel) -> Result<VerificationResult, Error> {
@@ -181,11 +343,47 @@ impl Verifier {
         sync_refcounts_to_active_state(&self.state).await
     }
 
+    /// Compare one installed package's live files with the store, file by file
+    ///
+    /// With `unified`, hash mismatches of text files carry a unified diff
+    /// from the stored content to the live content. Nothing is healed.
+    pub async fn diff_package(&self, name: &str, unified: bool) -> Result<PackageDiff, Error> {
+        diff_package(&self.state, &self.store, &self.ignore, name, unified).await
+    }
+
+    /// Files under the live prefix that no installed package tracks
+    ///
+    /// Ignored paths are left out, as in verification. Nothing is removed.
+    pub async fn untracked_files(&self) -> Result<Vec<String>, Error> {
+        let state_id = self.state.get_active_state().await?;
+        let mut tx = self.state.begin_transaction().await?;
+        let packages = queries::get_state_packages(&mut tx, &state_id).await?;
+        let tracked: HashSet<String> = queries::get_state_file_paths(&mut tx, &state_id)
+            .await?
+            .into_iter()
+            .collect();
+        tx.commit().await?;
+
+        let installed: HashSet<&str> = packages.iter().map(|p| p.name.as_str()).collect();
+        let (unexpected, _) = self
+            .detect_orphans(self.state.live_path(), &tracked, &installed, false)
+            .await?;
+        let mut paths: Vec<String> = unexpected
+            .into_iter()
+            .filter_map(|discrepancy| match discrepancy {
+                Discrepancy::UnexpectedFile { path } => Some(path),
+                _ => None,
+            })
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
     async fn run(&self, level: VerificationLevel, heal: bool) -> Result<VerificationResult, Error> {
         let start = Instant::now();
         let state_id = self.state.get_active_state().await?;
         let live_root = self.state.live_path().to_path_buf();
-        let packages = self.load_packages(&state_id).await?;
+        let packages = load_packages(&self.state, &state_id).await?;
 
         let total_files: usize = packages.iter().map(|(_, entries)| entries.len()).sum();
 
@@ -204,7 +402,9 @@ impl Verifier {
             },
         }));
 
+        let mut mtimes = MTimeCache::load(&self.state).await?;
         let mut discrepancies = Vec::new();
+        let mut healed = 0;
         let mut tracked_files: HashSet<String> = HashSet::new();
 
         for (package, entries) in packages.iter() {
@@ -214,7 +414,7 @@ impl Verifier {
                 })
             })?;
             let store_path = self.store.package_path(&package_hash);
-            if !store_path.exists() {
+            if !self.store.has_complete_package(&package_hash).await {
                 let discrepancy = Discrepancy::MissingPackageContent {
                     package: package.name.clone(),
                     version: package.version.clone(),
@@ -228,20 +428,40 @@ impl Verifier {
 
             for entry in entries {
                 tracked_files.insert(entry.relative_path.clone());
+                if self.ignore.ignores(&package.name, &entry.relative_path) {
+                    continue;
+                }
                 match self
-                    .verify_entry(&stored_package, package, entry, &live_root, level, heal)
+                    .verify_entry(
+                        &stored_package,
+                        package,
+                        entry,
+                        &live_root,
+                        level,
+                        heal,
+                        &mut mtimes,
+                    )
                     .await?
                 {
                     EntryStatus::Ok => {}
+                    EntryStatus::Healed => {
+                        healed += 1;
+                        self.emit(AppEvent::Guard(GuardEvent::PathHealed {
+                            operation_id: operation_id.clone(),
+                            path: entry.relative_path.clone(),
+                            package: Some(package.name.clone()),
+                            version: Some(package.version.clone()),
+                            source: self.heal_source.to_string(),
+                        }));
+                    }
                     EntryStatus::Missing => {
                         let discrepancy =
                             self.make_discrepancy(package, entry, EntryStatus::Missing);
                         self.emit_discrepancy(&operation_id, &discrepancy);
                         discrepancies.push(discrepancy);
                     }
-                    EntryStatus::Corrupted => {
-                        let discrepancy =
-                            self.make_discrepancy(package, entry, EntryStatus::Corrupted);
+                    status @ (EntryStatus::Corrupted | EntryStatus::PermissionsChanged) => {
+                        let discrepancy = self.make_discrepancy(package, entry, status);
                         self.emit_discrepancy(&operation_id, &discrepancy);
                         discrepancies.push(discrepancy);
                     }
@@ -250,14 +470,52 @@ impl Verifier {
         }
 
         // Detect unexpected files in live directory
-        let unexpected = self
-            .detect_orphans(&live_root, &tracked_files, heal)
+        let installed: HashSet<&str> = packages
+            .iter()
+            .map(|(package, _)| package.name.as_str())
+            .collect();
+        let (unexpected, removed) = self
+            .detect_orphans(&live_root, &tracked_files, &installed, heal)
             .await?;
+        healed += removed;
         for discrepancy in unexpected {
             self.emit_discrepancy(&operation_id, &discrepancy);
             discrepancies.push(discrepancy);
         }
 
+        // Managed directories are checked last, against a healed live prefix
+        for directory in &self.managed {
+            if directory.drift == ManagedDriftPolicy::Skip {
+                continue;
+            }
+            let files = managed::managed_files(directory, &packages);
+            let check = managed::verify_directory(
+                directory,
+                &live_root,
+                &files,
+                level != VerificationLevel::Quick,
+                heal,
+            )
+            .await?;
+            healed += check.healed.len();
+            for path in check.healed {
+                self.emit(AppEvent::Guard(GuardEvent::PathHealed {
+                    operation_id: operation_id.clone(),
+                    path,
+                    package: None,
+                    version: None,
+                    source: "live".to_string(),
+                }));
+            }
+            for discrepancy in check.discrepancies {
+                self.emit_discrepancy(&operation_id, &discrepancy);
+                discrepancies.push(discrepancy);
+            }
+        }
+
+        let cache_hit_rate = mtimes.hit_rate();
+        mtimes.save(&self.state).await?;
+
         let duration = start.elapsed();
         self.emit(AppEvent::Guard(GuardEvent::VerificationCompleted {
             operation_id,
@@ -265,18 +523,18 @@ impl Verifier {
             discrepancies: discrepancies.len(),
             metrics: GuardVerificationMetrics {
                 duration_ms: duration.as_millis() as u64,
-                cache_hit_rate: 0.0,
+                cache_hit_rate,
                 coverage_percent: 100.0,
             },
         }));
 
-        Ok(VerificationResult::new(
-            state_id,
-            discrepancies,
-            duration.as_millis() as u64,
-        ))
+        Ok(
+            VerificationResult::new(state_id, discrepancies, duration.as_millis() as u64)
+                .with_healed(healed),
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn verify_entry(
         &self,
         stored_package: &StoredPackage,
@@ -285,6 +543,7 @@ impl Verifier {
         live_root: &Path,
         level: VerificationLevel,
         heal: bool,
+        mtimes: &mut MTimeCache,
     ) -> Result<EntryStatus, Error> {
         let full_path = live_root.join(&entry.relative_path);
         if !full_path.exists() {
@@ -295,7 +554,7 @@ impl Verifier {
                     .is_ok()
                 && full_path.exists()
             {
-                return Ok(EntryStatus::Ok);
+                return Ok(EntryStatus::Healed);
             }
             return Ok(EntryStatus::Missing);
         }
@@ -306,16 +565,13 @@ impl Verifier {
             return Ok(EntryStatus::Ok);
         }
 
-        if level == VerificationLevel::Quick {
-            return Ok(EntryStatus::Ok);
-        }
-
-        // Standard level: verify basic file permissions
-        if level == VerificationLevel::Standard {
-            return Ok(EntryStatus::Ok);
+        // Below full, only files changed since they last verified are re-hashed
+        if level != VerificationLevel::Full
+            && mtimes.is_unchanged(&entry.relative_path, &metadata, &entry.file_hash)
+        {
+            return Ok(check_special_permissions(entry, &full_path, &metadata, heal).await);
         }
 
-        // Full level: hash comparison
         let expected_hash = Hash::from_hex(&entry.file_hash).map_err(|e| {
             Error::from(OpsError::OperationFailed {
                 message: format!(
@@ -325,14 +581,10 @@ impl Verifier {
             })
         })?;
 
-        // Skip Python bytecode caches for stability
-        if entry.relative_path.ends_with(".pyc") || entry.relative_path.contains("__pycache__") {
-            return Ok(EntryStatus::Ok);
-        }
-
         let actual_hash = Hash::hash_file(&full_path).await?;
         if actual_hash == expected_hash {
-            return Ok(EntryStatus::Ok);
+            mtimes.record(&entry.relative_path, &metadata, &entry.file_hash);
+            return Ok(check_special_permissions(entry, &full_path, &metadata, heal).await);
         }
 
         if heal
@@ -343,9 +595,12 @@ impl Verifier {
         {
             let rehash = Hash::hash_file(&full_path).await?;
             if rehash == expected_hash {
-                return Ok(EntryStatus::Ok);
+                let metadata = fs::symlink_metadata(&full_path).await?;
+                mtimes.record(&entry.relative_path, &metadata, &entry.file_hash);
+                return Ok(EntryStatus::Healed);
             }
         }
+        mtimes.forget(&entry.relative_path);
         Ok(EntryStatus::Corrupted)
     }
 
@@ -415,6 +670,9 @@ impl Verifier {
                 .filesystem()
                 .clone_file(&ctx, &source_path, target_path)
                 .await?;
+            if let Some((mode, flags)) = recorded_special_permissions(entry) {
+                apply_special_permissions(target_path, mode, flags).await?;
+            }
         }
 
         Ok(())
@@ -437,7 +695,12 @@ impl Verifier {
                 version: package.version.clone(),
                 path: entry.relative_path.clone(),
             },
-            EntryStatus::Ok => unreachable!(),
+            EntryStatus::PermissionsChanged => Discrepancy::PermissionsChanged {
+                package: package.name.clone(),
+                version: package.version.clone(),
+                path: entry.relative_path.clone(),
+            },
+            EntryStatus::Ok | EntryStatus::Healed => unreachable!(),
         }
     }
 
@@ -445,13 +708,15 @@ impl Verifier {
         &self,
         live_root: &Path,
         tracked: &HashSet<String>,
+        installed: &HashSet<&str>,
         heal: bool,
-    ) -> Result<Vec<Discrepancy>, Error> {
+    ) -> Result<(Vec<Discrepancy>, usize), Error> {
         if !live_root.exists() {
-            return Ok(Vec::new());
+            return Ok((Vec::new(), 0));
         }
 
         let mut unexpected = Vec::new();
+        let mut removed = 0;
         for entry in WalkDir::new(live_root).follow_links(false) {
             let entry = match entry {
                 Ok(e) => e,
@@ -462,25 +727,32 @@ impl Verifier {
                 continue;
             }
 
+            // Tracked paths are valid UTF-8 (packaging rejects anything else),
+            // so a lossily converted name never matches one and is reported
             let rel_path = match entry.path().strip_prefix(live_root) {
                 Ok(p) => p.to_string_lossy().replace('\\', "/"),
                 Err(_) => continue,
             };
 
-            if rel_path.ends_with(".pyc") || rel_path.contains("__pycache__") || rel_path == "STATE"
-            {
+            if is_generated(&rel_path) || self.ignore.ignores_untracked(&rel_path, installed) {
+                continue;
+            }
+            // The installer links man pages and completions into the trees
+            // tools search; such a link belongs to the file it points at
+            if entry.path_is_symlink() && links_to_tracked(entry.path(), &rel_path, tracked) {
                 continue;
             }
 
             if !tracked.contains(&rel_path) {
                 if heal && fs::remove_file(entry.path()).await.is_ok() {
+                    removed += 1;
                     continue;
                 }
                 unexpected.push(Discrepancy::UnexpectedFile { path: rel_path });
             }
         }
 
-        Ok(unexpected)
+        Ok((unexpected, removed))
     }
 
     fn emit_discrepancy(&self, operation_id: &str, discrepancy: &Discrepancy) {
@@ -489,23 +761,84 @@ impl Verifier {
             discrepancy: discrepancy.to_event(),
         }));
     }
+}
 
-    async fn load_packages(
-        &self,
-        state_id: &Uuid,
-    ) -> Result<Vec<(Package, Vec<PackageFileEntry>)>, Error> {
-        let mut tx = self.state.begin_transaction().await?;
-        let packages = queries::get_state_packages(&mut tx, state_id).await?;
+/// The packages of state `state_id` with their file entries
+pub(crate) async fn load_packages(
+    state: &StateManager,
+    state_id: &Uuid,
+) -> Result<Vec<(Package, Vec<PackageFileEntry>)>, Error> {
+    let mut tx = state.begin_transaction().await?;
+    let packages = queries::get_state_packages(&mut tx, state_id).await?;
+    tx.commit().await?;
+
+    let mut result = Vec::new();
+    for package in packages {
+        let mut tx = state.begin_transaction().await?;
+        let entries = queries::get_package_file_entries(&mut tx, package.id).await?;
         tx.commit().await?;
+        result.push((package, entries));
+    }
 
-        let mut result = Vec::new();
-        for package in packages {
-            let mut tx = self.state.begin_transaction().await?;
-            let entries = queries::get_package_file_entries(&mut tx, package.id).await?;
-            tx.commit().await?;
-            result.push((package, entries));
-        }
+    Ok(result)
+}
+
+/// Setuid/setgid bits and BSD flags recorded for `entry`, if it has any
+fn recorded_special_permissions(entry: &PackageFileEntry) -> Option<(u32, u32)> {
+    let mode = u32::try_from(entry.permissions).unwrap_or(0) & SPECIAL_MODE_BITS;
+    let flags = u32::try_from(entry.flags).unwrap_or(0);
+    (mode != 0 || flags != 0).then_some((mode, flags))
+}
 
-        Ok(result)
+/// Status of a file with the right contents, going by its setuid/setgid
+/// bits and BSD flags, which are set again when healing
+async fn check_special_permissions(
+    entry: &PackageFileEntry,
+    path: &Path,
+    metadata: &std::fs::Metadata,
+    heal: bool,
+) -> EntryStatus {
+    let (mode, flags) = recorded_special_permissions(entry).unwrap_or_default();
+    if file_mode(metadata) & SPECIAL_MODE_BITS == mode && file_flags(metadata) == flags {
+        return EntryStatus::Ok;
+    }
+    if heal && apply_special_permissions(path, mode, flags).await.is_ok() {
+        return EntryStatus::Healed;
+    }
+    EntryStatus::PermissionsChanged
+}
+
+/// Whether the file at `rel_path` in the live prefix is written by sps2
+/// itself rather than shipped by a package
+pub(crate) fn is_generated(rel_path: &str) -> bool {
+    rel_path == "STATE"
+        || Shell::ALL
+            .iter()
+            .any(|shell| shell.profile_snippet() == rel_path)
+}
+
+/// Whether `link`, at `rel_path` in the live prefix, is a relative symlink
+/// to a tracked file
+fn links_to_tracked(link: &Path, rel_path: &str, tracked: &HashSet<String>) -> bool {
+    let Ok(dest) = std::fs::read_link(link) else {
+        return false;
+    };
+    let mut resolved: Vec<&str> = rel_path.split('/').collect();
+    resolved.pop();
+    for component in dest.components() {
+        match component {
+            std::path::Component::Normal(name) => match name.to_str() {
+                Some(name) => resolved.push(name),
+                None => return false,
+            },
+            std::path::Component::ParentDir => {
+                if resolved.pop().is_none() {
+                    return false;
+                }
+            }
+            std::path::Component::CurDir => {}
+            std::path::Component::RootDir | std::path::Component::Prefix(_) => return false,
+        }
     }
+    tracked.contains(&resolved.join("/"))
 }
@@ -5,7 +5,7 @@
 //! processing and metadata collection.
 
 use crate::Hash;
-use sps2_errors::{Error, StorageError};
+use sps2_errors::{Error, PackageError, StorageError};
 use std::path::{Path, PathBuf};
 use tokio::sync::mpsc;
 use tokio::task::JoinSet;
@@ -26,6 +26,9 @@ pub struct FileHashResult {
     /// Unix permissions (if available)
     #[cfg(unix)]
     pub mode: Option<u32>,
+    /// BSD file flags (`chflags`), if any are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flags: Option<u32>,
 }
 
 /// Configuration for file hashing operations
@@ -79,7 +82,8 @@ impl FileHasher {
             .map_err(|_| StorageError::IoError {
                 message: format!("failed to compute relative path for {}", path.display()),
             })?
-            .to_string_lossy()
+            .to_str()
+            .ok_or_else(|| PackageError::non_utf8_path(path))?
             .to_string();
 
         // Handle different file types
@@ -95,12 +99,15 @@ impl FileHasher {
                     use std::os::unix::fs::PermissionsExt;
                     Some(metadata.permissions().mode())
                 },
+                flags: file_flags(&metadata),
             })
         } else if metadata.is_symlink() {
             // For symlinks, hash the target path
             let target = tokio::fs::read_link(path).await?;
-            let target_string = target.to_string_lossy().to_string();
-            let target_bytes = target_string.as_bytes();
+            let target_bytes = target
+                .to_str()
+                .ok_or_else(|| PackageError::non_utf8_path(&target))?
+                .as_bytes();
 
             Ok(FileHashResult {
                 relative_path,
@@ -113,6 +120,7 @@ impl FileHasher {
                     use std::os::unix::fs::PermissionsExt;
                     Some(metadata.permissions().mode())
                 },
+                flags: file_flags(&metadata),
             })
         } else {
             // Regular file
@@ -129,6 +137,7 @@ impl FileHasher {
                     use std::os::unix::fs::PermissionsExt;
                     Some(metadata.permissions().mode())
                 },
+                flags: file_flags(&metadata),
             })
         }
     }
@@ -311,6 +320,18 @@ pub fn calculate_file_storage_path(hash: &Hash) -> (String, String) {
     (format!("{prefix1}/{prefix2}"), hex)
 }
 
+/// BSD flags set on a file; only macOS has them
+#[cfg(target_os = "macos")]
+fn file_flags(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::macos::fs::MetadataExt;
+    Some(metadata.st_flags()).filter(|flags| *flags != 0)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn file_flags(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -365,6 +386,29 @@ mod tests {
             .any(|r| r.relative_path == "subdir/file2.txt"));
     }
 
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_symlink_target_is_rejected() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let link = temp_dir.path().join("link");
+        fs::symlink(OsStr::from_bytes(b"target-\xff"), &link)
+            .await
+            .unwrap();
+
+        let hasher = FileHasher::new(FileHasherConfig::default());
+        let err = hasher
+            .hash_file_with_metadata(&link, temp_dir.path())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Package(PackageError::NonUtf8Path { .. })
+        ));
+    }
+
     #[test]
     fn test_storage_path_calculation() {
         let hash = Hash::from_data(b"test data");
@@ -13,7 +13,7 @@ pub use file_hasher::{calculate_file_storage_path, FileHashResult, FileHasher, F
 
 use blake3::Hasher as Blake3Hasher;
 use serde::{Deserialize, Serialize};
-use sps2_errors::{Error, StorageError};
+use sps2_errors::{Error, PackageError, StorageError};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::path::Path;
@@ -353,7 +353,10 @@ impl Hash {
                     } else if metadata.is_symlink() {
                         // Hash symlink target
                         let target = tokio::fs::read_link(&full_path).await?;
-                        dir_hasher.update(target.to_string_lossy().as_bytes());
+                        let target = target
+                            .to_str()
+                            .ok_or_else(|| PackageError::non_utf8_path(&target))?;
+                        dir_hasher.update(target.as_bytes());
                     }
 
                     // Add another separator
@@ -388,7 +391,10 @@ impl Hash {
                     } else if metadata.is_symlink() {
                         // Hash symlink target
                         let target = tokio::fs::read_link(&full_path).await?;
-                        dir_hasher.update(target.to_string_lossy().as_bytes());
+                        let target = target
+                            .to_str()
+                            .ok_or_else(|| PackageError::non_utf8_path(&target))?;
+                        dir_hasher.update(target.as_bytes());
                     }
 
                     // Add another separator
@@ -460,7 +466,8 @@ async fn collect_files(
             .map_err(|_| StorageError::IoError {
                 message: "failed to compute relative path".to_string(),
             })?
-            .to_string_lossy()
+            .to_str()
+            .ok_or_else(|| PackageError::non_utf8_path(&path))?
             .to_string();
 
         files.insert(rel_path.clone(), (path.clone(), metadata.clone()));
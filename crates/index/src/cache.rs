@@ -29,6 +29,17 @@ impl IndexCache {
         self.cache_dir.join("index.meta")
     }
 
+    /// Get the file naming the repository the cached index came from
+    fn source_path(&self) -> PathBuf {
+        self.cache_dir.join("index.source")
+    }
+
+    /// Files the cache is stored in, whether or not they exist yet
+    #[must_use]
+    pub fn files(&self) -> [PathBuf; 3] {
+        [self.index_path(), self.metadata_path(), self.source_path()]
+    }
+
     /// Load index from cache
     ///
     /// # Errors
@@ -46,12 +57,39 @@ impl IndexCache {
         Index::from_json(&content)
     }
 
+    /// Load the cached index text as stored
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache file doesn't exist or cannot be read.
+    pub async fn load_raw(&self) -> Result<String, Error> {
+        let path = self.index_path();
+        fs::read_to_string(&path).await.map_err(|_e| {
+            StorageError::PathNotFound {
+                path: path.display().to_string(),
+            }
+            .into()
+        })
+    }
+
     /// Save index to cache
     ///
     /// # Errors
     ///
     /// Returns an error if the cache directory cannot be created or the file cannot be written.
     pub async fn save(&self, index: &Index) -> Result<(), Error> {
+        self.save_raw(&index.to_json()?).await
+    }
+
+    /// Save index text to cache exactly as downloaded
+    ///
+    /// Keeping the repository's bytes lets the next sync fetch only the
+    /// blocks that changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be created or the file cannot be written.
+    pub async fn save_raw(&self, json: &str) -> Result<(), Error> {
         // Ensure cache directory exists
         fs::create_dir_all(&self.cache_dir)
             .await
@@ -60,11 +98,10 @@ impl IndexCache {
             })?;
 
         let path = self.index_path();
-        let json = index.to_json()?;
 
         // Write to temporary file first
         let temp_path = path.with_extension("tmp");
-        fs::write(&temp_path, &json)
+        fs::write(&temp_path, json)
             .await
             .map_err(|e| StorageError::IoError {
                 message: format!("failed to write cache: {e}"),
@@ -118,6 +155,7 @@ impl IndexCache {
     pub async fn clear(&self) -> Result<(), Error> {
         let _ = fs::remove_file(self.index_path()).await;
         let _ = fs::remove_file(self.metadata_path()).await;
+        let _ = fs::remove_file(self.source_path()).await;
         Ok(())
     }
 
@@ -154,4 +192,31 @@ impl IndexCache {
 
         Ok(())
     }
+
+    /// Load the base URL of the repository the cached index came from
+    ///
+    /// # Errors
+    ///
+    /// Does not return errors - a missing file returns `None`.
+    pub async fn load_source(&self) -> Result<Option<String>, Error> {
+        match fs::read_to_string(self.source_path()).await {
+            Ok(content) => Ok(content.lines().next().map(String::from)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Save the base URL of the repository the cached index came from
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source file cannot be written.
+    pub async fn save_source(&self, base_url: &str) -> Result<(), Error> {
+        fs::write(self.source_path(), base_url)
+            .await
+            .map_err(|e| StorageError::IoError {
+                message: format!("failed to save index source: {e}"),
+            })?;
+
+        Ok(())
+    }
 }
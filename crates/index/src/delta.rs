@@ -0,0 +1,403 @@
+//! Block digests for fetching only the changed parts of an index
+//!
+//! Publishers write an [`IndexDigest`] next to `index.json`: the index split
+//! into fixed-size blocks, each with an rsync-style rolling checksum and a
+//! truncated BLAKE3 hash. A client holding an older index finds the blocks it
+//! already has, at any offset, and requests only the rest with HTTP range
+//! requests. The assembled index is checked against the digest's full BLAKE3
+//! hash before it is used; its signature is checked as usual afterwards.
+
+use serde::{Deserialize, Serialize};
+use sps2_errors::{Error, PackageError};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Name of the digest file published next to `index.json`
+pub const INDEX_DIGEST_FILE: &str = "index.json.digest";
+
+/// Smallest block size; larger indexes use larger blocks so the digest stays
+/// within [`MAX_DIGEST_BLOCKS`]
+const MIN_BLOCK_SIZE: usize = 4 * 1024;
+
+/// Upper bound on the number of blocks a digest describes
+const MAX_DIGEST_BLOCKS: usize = 2048;
+
+/// Hex digits of BLAKE3 kept per block; the full-index hash catches collisions
+const STRONG_HEX_LEN: usize = 16;
+
+/// Checksums of one block: rolling checksum and truncated BLAKE3 hex
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockDigest(pub u32, pub String);
+
+/// Block-level description of a published index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexDigest {
+    /// Size of the index in bytes
+    pub size: u64,
+    pub block_size: u32,
+    /// BLAKE3 hex of the whole index
+    pub blake3: String,
+    pub blocks: Vec<BlockDigest>,
+}
+
+impl IndexDigest {
+    /// Describe `data` in blocks sized for its length
+    #[must_use]
+    pub fn new(data: &[u8]) -> Self {
+        let block_size = data
+            .len()
+            .div_ceil(MAX_DIGEST_BLOCKS)
+            .next_power_of_two()
+            .max(MIN_BLOCK_SIZE);
+        Self {
+            size: data.len() as u64,
+            block_size: u32::try_from(block_size).unwrap_or(u32::MAX),
+            blake3: blake3::hash(data).to_hex().to_string(),
+            blocks: data
+                .chunks(block_size)
+                .map(|block| BlockDigest(rolling_checksum(block), strong_hash(block)))
+                .collect(),
+        }
+    }
+
+    /// Parse a digest from JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed or the blocks do not cover
+    /// `size` bytes.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let digest: Self = serde_json::from_str(json).map_err(|e| PackageError::InvalidFormat {
+            message: format!("invalid index digest: {e}"),
+        })?;
+        let block_size = u64::from(digest.block_size);
+        if block_size == 0 || digest.size.div_ceil(block_size) != digest.blocks.len() as u64 {
+            return Err(PackageError::InvalidFormat {
+                message: "index digest blocks do not cover the index".to_string(),
+            }
+            .into());
+        }
+        Ok(digest)
+    }
+
+    /// Serialize the digest to JSON
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the digest cannot be serialized.
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|e| {
+            PackageError::InvalidFormat {
+                message: format!("failed to serialize index digest: {e}"),
+            }
+            .into()
+        })
+    }
+
+    /// Whether `data` is exactly the described index
+    #[must_use]
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() as u64 == self.size && blake3::hash(data).to_hex().as_str() == self.blake3
+    }
+
+    /// Find the blocks `local`, an older copy of the index, already contains
+    #[must_use]
+    pub fn plan(&self, local: &[u8]) -> DeltaPlan {
+        let block_size = self.block_size as usize;
+        let mut sources = vec![None; self.blocks.len()];
+
+        // Only full blocks can be found by rolling over `local`
+        let mut by_checksum: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (index, block) in self.blocks.iter().enumerate() {
+            if self.block_len(index) == block_size {
+                by_checksum.entry(block.0).or_default().push(index);
+            }
+        }
+
+        let mut offset = 0;
+        let mut window = Rolling::new(local, offset, block_size);
+        while let Some(checksum) = window.as_ref().map(Rolling::checksum) {
+            let mut found = false;
+            if let Some(candidates) = by_checksum.get(&checksum) {
+                let strong = strong_hash(&local[offset..offset + block_size]);
+                for &index in candidates {
+                    if sources[index].is_none() && self.blocks[index].1 == strong {
+                        sources[index] = Some(offset);
+                        found = true;
+                    }
+                }
+            }
+
+            if found {
+                offset += block_size;
+                window = Rolling::new(local, offset, block_size);
+            } else if let Some(rolling) = window.as_mut() {
+                if !rolling.roll(local, offset) {
+                    break;
+                }
+                offset += 1;
+            }
+        }
+
+        // A short last block cannot be rolled over; it is found if the index
+        // still ends the same way
+        if let Some(last) = self.blocks.len().checked_sub(1) {
+            let len = self.block_len(last);
+            if sources[last].is_none() && len < block_size && local.len() >= len {
+                let offset = local.len() - len;
+                if strong_hash(&local[offset..]) == self.blocks[last].1 {
+                    sources[last] = Some(offset);
+                }
+            }
+        }
+
+        DeltaPlan {
+            block_size,
+            sources,
+        }
+    }
+
+    /// Length of block `index`; only the last block may be short
+    fn block_len(&self, index: usize) -> usize {
+        let range = self.block_range(index);
+        usize::try_from(range.end - range.start).unwrap_or(usize::MAX)
+    }
+
+    /// Bytes of the index covered by block `index`
+    fn block_range(&self, index: usize) -> Range<u64> {
+        let block_size = u64::from(self.block_size);
+        let start = index as u64 * block_size;
+        start..(start + block_size).min(self.size)
+    }
+
+    /// Put the index together from `local` and the fetched ranges
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a fetched range does not have the requested
+    /// length, a block is left uncovered, or the result does not hash to
+    /// [`blake3`](Self::blake3).
+    pub fn assemble(
+        &self,
+        plan: &DeltaPlan,
+        local: &[u8],
+        fetched: &[(Range<u64>, Vec<u8>)],
+    ) -> Result<Vec<u8>, Error> {
+        let invalid = |message: &str| -> Error {
+            PackageError::InvalidFormat {
+                message: format!("index delta: {message}"),
+            }
+            .into()
+        };
+
+        let size = usize::try_from(self.size).map_err(|_| invalid("index too large"))?;
+        let mut data = vec![0; size];
+        let mut covered = vec![false; self.blocks.len()];
+        for (index, source) in plan.sources.iter().enumerate() {
+            if let Some(offset) = *source {
+                let len = self.block_len(index);
+                data[index * plan.block_size..][..len]
+                    .copy_from_slice(&local[offset..offset + len]);
+                covered[index] = true;
+            }
+        }
+        for (range, bytes) in fetched {
+            let start = usize::try_from(range.start).map_err(|_| invalid("range too large"))?;
+            if bytes.len() as u64 != range.end - range.start || start + bytes.len() > size {
+                return Err(invalid("fetched range has the wrong length"));
+            }
+            data[start..start + bytes.len()].copy_from_slice(bytes);
+            let first = start / plan.block_size;
+            let last = (start + bytes.len()).div_ceil(plan.block_size);
+            covered[first..last].fill(true);
+        }
+
+        if covered.contains(&false) {
+            return Err(invalid("blocks missing after fetch"));
+        }
+        if !self.matches(&data) {
+            return Err(invalid("assembled index does not match its digest"));
+        }
+        Ok(data)
+    }
+}
+
+/// Which blocks of a new index can be copied from an older one
+#[derive(Debug, Clone)]
+pub struct DeltaPlan {
+    block_size: usize,
+    /// Offset in the local copy each block is found at
+    sources: Vec<Option<usize>>,
+}
+
+impl DeltaPlan {
+    /// Byte ranges of the new index to fetch, adjacent blocks merged
+    #[must_use]
+    pub fn missing_ranges(&self, digest: &IndexDigest) -> Vec<Range<u64>> {
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for (index, source) in self.sources.iter().enumerate() {
+            if source.is_some() {
+                continue;
+            }
+            let range = digest.block_range(index);
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        ranges
+    }
+
+    /// Bytes the missing ranges add up to
+    #[must_use]
+    pub fn missing_bytes(&self, digest: &IndexDigest) -> u64 {
+        self.missing_ranges(digest)
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+}
+
+/// rsync's weak checksum over a window of `len` bytes
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: usize,
+    /// `len` as the weight of the byte leaving the window
+    weight: u32,
+}
+
+impl Rolling {
+    /// Window starting at `offset`, or `None` if fewer than `len` bytes remain
+    fn new(data: &[u8], offset: usize, len: usize) -> Option<Self> {
+        let window = data.get(offset..offset.checked_add(len)?)?;
+        let (a, b) = sums(window);
+        Some(Self {
+            a,
+            b,
+            len,
+            weight: u32::try_from(len).ok()?,
+        })
+    }
+
+    fn checksum(&self) -> u32 {
+        (self.a & 0xffff) | ((self.b & 0xffff) << 16)
+    }
+
+    /// Slide the window starting at `offset` one byte forward; `false` at the
+    /// end of `data`
+    fn roll(&mut self, data: &[u8], offset: usize) -> bool {
+        let Some(&incoming) = data.get(offset + self.len) else {
+            return false;
+        };
+        let outgoing = u32::from(data[offset]);
+        self.a = self
+            .a
+            .wrapping_sub(outgoing)
+            .wrapping_add(u32::from(incoming));
+        self.b = self
+            .b
+            .wrapping_sub(self.weight.wrapping_mul(outgoing))
+            .wrapping_add(self.a);
+        true
+    }
+}
+
+/// Byte sum and the sum of its running totals, which weighs each byte by
+/// its distance from the end of the block
+fn sums(block: &[u8]) -> (u32, u32) {
+    block.iter().fold((0u32, 0u32), |(a, b), &byte| {
+        let a = a.wrapping_add(u32::from(byte));
+        (a, b.wrapping_add(a))
+    })
+}
+
+fn rolling_checksum(block: &[u8]) -> u32 {
+    let (a, b) = sums(block);
+    (a & 0xffff) | ((b & 0xffff) << 16)
+}
+
+fn strong_hash(block: &[u8]) -> String {
+    let mut hex = blake3::hash(block).to_hex().to_string();
+    hex.truncate(STRONG_HEX_LEN);
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    fn index_text(packages: std::ops::Range<usize>) -> Vec<u8> {
+        let mut text = String::new();
+        for n in packages {
+            writeln!(
+                text,
+                "{{\"name\": \"package-{n:05}\", \"version\": \"1.{n}.0\"}}"
+            )
+            .unwrap();
+        }
+        text.into_bytes()
+    }
+
+    fn fetch(new: &[u8], ranges: &[Range<u64>]) -> Vec<(Range<u64>, Vec<u8>)> {
+        ranges
+            .iter()
+            .map(|range| {
+                let start = usize::try_from(range.start).unwrap();
+                let end = usize::try_from(range.end).unwrap();
+                let bytes = new[start..end].to_vec();
+                (range.clone(), bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_index_needs_no_ranges() {
+        let data = index_text(0..2000);
+        let digest = IndexDigest::new(&data);
+        assert!(digest.matches(&data));
+
+        let plan = digest.plan(&data);
+        assert!(plan.missing_ranges(&digest).is_empty());
+        assert_eq!(digest.assemble(&plan, &data, &[]).unwrap(), data);
+    }
+
+    #[test]
+    fn shifted_content_is_found_and_reassembled() {
+        let old = index_text(0..2000);
+        // One package inserted near the start shifts everything after it
+        let mut new = index_text(0..10);
+        new.extend_from_slice(b"{\"name\": \"inserted\", \"version\": \"0.1.0\"}\n");
+        new.extend_from_slice(&index_text(10..2000));
+
+        let digest = IndexDigest::from_json(&IndexDigest::new(&new).to_json().unwrap()).unwrap();
+        let plan = digest.plan(&old);
+        let missing = plan.missing_bytes(&digest);
+        assert!(missing <= 3 * u64::from(digest.block_size), "{missing}");
+
+        let fetched = fetch(&new, &plan.missing_ranges(&digest));
+        let assembled = digest.assemble(&plan, &old, &fetched).unwrap();
+        assert_eq!(assembled, new);
+    }
+
+    #[test]
+    fn wrong_bytes_are_rejected() {
+        let old = index_text(0..500);
+        let new = index_text(0..600);
+        let digest = IndexDigest::new(&new);
+        let plan = digest.plan(&old);
+
+        let mut fetched = fetch(&new, &plan.missing_ranges(&digest));
+        fetched[0].1[0] ^= 1;
+        assert!(digest.assemble(&plan, &old, &fetched).is_err());
+        assert!(digest.assemble(&plan, &old, &[]).is_err());
+    }
+
+    #[test]
+    fn digest_must_cover_the_index() {
+        let mut digest = IndexDigest::new(&index_text(0..100));
+        digest.blocks.pop();
+        assert!(IndexDigest::from_json(&digest.to_json().unwrap()).is_err());
+    }
+}
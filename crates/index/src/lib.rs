@@ -9,16 +9,24 @@
 //! offline use and validated for freshness.
 
 mod cache;
+mod delta;
 mod models;
+mod validation;
 
 pub use cache::IndexCache;
+pub use delta::{BlockDigest, DeltaPlan, IndexDigest, INDEX_DIGEST_FILE};
 pub use models::{
-    DependencyInfo, Index, IndexMetadata, PackageEntry, SbomEntry, SbomInfo, VersionEntry,
+    DependencyInfo, Index, IndexMetadata, PackageEntry, SbomEntry, SbomInfo, VersionBuilds,
+    VersionEntry,
+};
+pub use validation::{
+    ValidationPolicy, DEFAULT_MAX_INDEX_BYTES, DEFAULT_MAX_PACKAGES, DEFAULT_MAX_TOTAL_VERSIONS,
+    DEFAULT_MAX_VERSIONS_PER_PACKAGE,
 };
 
 use chrono::Utc;
 use sps2_errors::Error;
-use sps2_types::{package::PackageSpec, Version};
+use sps2_types::{collate, package::PackageSpec, Version};
 // HashMap removed - not used
 use std::path::Path;
 
@@ -30,6 +38,7 @@ pub const SUPPORTED_INDEX_VERSION: u32 = 1;
 pub struct IndexManager {
     index: Option<Index>,
     pub cache: IndexCache,
+    policy: ValidationPolicy,
 }
 
 impl IndexManager {
@@ -38,9 +47,23 @@ impl IndexManager {
         Self {
             index: None,
             cache: IndexCache::new(cache_dir),
+            policy: ValidationPolicy::default(),
         }
     }
 
+    /// Set the validation policy applied when loading indexes
+    #[must_use]
+    pub fn with_validation_policy(mut self, policy: ValidationPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Get the validation policy applied when loading indexes
+    #[must_use]
+    pub fn validation_policy(&self) -> &ValidationPolicy {
+        &self.policy
+    }
+
     /// Load index from cache or JSON content
     ///
     /// # Errors
@@ -50,14 +73,14 @@ impl IndexManager {
     pub async fn load(&mut self, content: Option<&str>) -> Result<(), Error> {
         let index = if let Some(json) = content {
             // Parse provided content
-            Index::from_json(json)?
+            Index::from_json_limited(json, self.policy.max_index_bytes)?
         } else {
             // Try to load from cache
             self.cache.load().await?
         };
 
         // Validate index
-        index.validate()?;
+        index.validate_with_policy(&self.policy)?;
 
         self.index = Some(index);
         Ok(())
@@ -95,7 +118,7 @@ impl IndexManager {
             .map(String::as_str)
             .collect();
 
-        results.sort_unstable();
+        results.sort_by(|a, b| collate::natural_cmp(a, b));
         results
     }
 
@@ -105,13 +128,7 @@ impl IndexManager {
         let index = self.index.as_ref()?;
         let package = index.packages.get(name)?;
 
-        let mut versions: Vec<(&String, &VersionEntry)> = package.versions.iter().collect();
-        versions.sort_by(|a, b| {
-            // Sort by version descending (newest first)
-            Version::parse(b.0)
-                .unwrap_or_else(|_| Version::new(0, 0, 0))
-                .cmp(&Version::parse(a.0).unwrap_or_else(|_| Version::new(0, 0, 0)))
-        });
+        let versions = newest_first(package);
 
         Some(versions.into_iter().map(|(_, entry)| entry).collect())
     }
@@ -126,13 +143,7 @@ impl IndexManager {
         let index = self.index.as_ref()?;
         let package = index.packages.get(name)?;
 
-        let mut versions: Vec<(&String, &VersionEntry)> = package.versions.iter().collect();
-        versions.sort_by(|a, b| {
-            // Sort by version descending (newest first)
-            Version::parse(b.0)
-                .unwrap_or_else(|_| Version::new(0, 0, 0))
-                .cmp(&Version::parse(a.0).unwrap_or_else(|_| Version::new(0, 0, 0)))
-        });
+        let versions = newest_first(package);
 
         Some(
             versions
@@ -158,14 +169,8 @@ impl IndexManager {
         let index = self.index.as_ref()?;
         let package = index.packages.get(&spec.name)?;
 
-        // Collect versions with their version strings, sort by version descending
-        let mut versions: Vec<(&String, &VersionEntry)> = package.versions.iter().collect();
-        versions.sort_by(|a, b| {
-            // Sort by version descending (newest first)
-            Version::parse(b.0)
-                .unwrap_or_else(|_| Version::new(0, 0, 0))
-                .cmp(&Version::parse(a.0).unwrap_or_else(|_| Version::new(0, 0, 0)))
-        });
+        // Versions with their version strings, newest first
+        let versions = newest_first(package);
 
         // Find highest version that satisfies the spec
         versions.into_iter().find_map(|(version_str, entry)| {
@@ -182,6 +187,9 @@ impl IndexManager {
     }
 
     /// Get a specific version entry
+    ///
+    /// Of a version built for several architectures, this is the build for
+    /// this machine; see [`VersionBuilds::preferred`].
     #[must_use]
     pub fn get_version(&self, name: &str, version: &str) -> Option<&VersionEntry> {
         self.index
@@ -189,7 +197,8 @@ impl IndexManager {
             .packages
             .get(name)?
             .versions
-            .get(version)
+            .get(version)?
+            .preferred()
     }
 
     /// Check if index is stale (older than `max_age_days`)
@@ -219,3 +228,14 @@ impl IndexManager {
         self.index = Some(index);
     }
 }
+
+/// Versions of a package, newest first, each with its preferred build
+fn newest_first(package: &PackageEntry) -> Vec<(&String, &VersionEntry)> {
+    let mut versions: Vec<(&String, &VersionEntry)> = package
+        .versions
+        .iter()
+        .filter_map(|(version, builds)| Some((version, builds.preferred()?)))
+        .collect();
+    versions.sort_by(|a, b| collate::version_cmp(b.0, a.0));
+    versions
+}
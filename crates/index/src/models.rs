@@ -1,16 +1,22 @@
 //! Index data models
 
+use crate::validation::ValidationPolicy;
+use crate::validation::DEFAULT_MAX_INDEX_BYTES;
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sps2_errors::{Error, PackageError};
 use sps2_types::Arch;
 use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
 
 /// Repository index
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Index {
     #[serde(flatten)]
     pub metadata: IndexMetadata,
+    #[serde(deserialize_with = "unique_keys")]
     pub packages: HashMap<String, PackageEntry>,
 }
 
@@ -20,14 +26,26 @@ pub struct IndexMetadata {
     pub version: u32,
     pub minimum_client: String,
     pub timestamp: DateTime<Utc>,
+    /// Search endpoint the repository serves for `sps2 search --remote`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_url: Option<String>,
 }
 
 /// Package entry in index
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PackageEntry {
-    pub versions: HashMap<String, VersionEntry>,
+    #[serde(deserialize_with = "unique_keys")]
+    pub versions: HashMap<String, VersionBuilds>,
 }
 
+/// Builds of one version, one per architecture
+///
+/// A version with a single build is written as that build's entry, so
+/// indexes listing one build per version keep their format; one with
+/// several builds is written as a list of entries.
+#[derive(Debug, Clone, Default)]
+pub struct VersionBuilds(Vec<VersionEntry>);
+
 /// Version entry in index
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VersionEntry {
@@ -54,6 +72,9 @@ pub struct DependencyInfo {
     pub runtime: Vec<String>,
     #[serde(default)]
     pub build: Vec<String>,
+    /// Packages installed alongside unless recommendations are turned off
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recommends: Vec<String>,
 }
 
 /// SBOM information
@@ -86,6 +107,7 @@ impl Index {
                 version: crate::SUPPORTED_INDEX_VERSION,
                 minimum_client: "0.1.0".to_string(),
                 timestamp: Utc::now(),
+                search_url: None,
             },
             packages: HashMap::new(),
         }
@@ -95,8 +117,28 @@ impl Index {
     ///
     /// # Errors
     ///
-    /// Returns an error if the JSON is malformed or cannot be parsed.
+    /// Returns an error if the JSON is malformed, cannot be parsed, lists a
+    /// package or version twice, or exceeds [`DEFAULT_MAX_INDEX_BYTES`].
     pub fn from_json(json: &str) -> Result<Self, Error> {
+        Self::from_json_limited(json, DEFAULT_MAX_INDEX_BYTES)
+    }
+
+    /// Parse index from JSON of at most `max_bytes`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is longer than `max_bytes`, malformed,
+    /// cannot be parsed, or lists a package or version twice.
+    pub fn from_json_limited(json: &str, max_bytes: usize) -> Result<Self, Error> {
+        if json.len() > max_bytes {
+            return Err(PackageError::InvalidFormat {
+                message: format!(
+                    "index is {} bytes, exceeding the limit of {max_bytes}",
+                    json.len()
+                ),
+            }
+            .into());
+        }
         serde_json::from_str(json).map_err(|e| {
             PackageError::InvalidFormat {
                 message: format!("invalid index JSON: {e}"),
@@ -107,25 +149,43 @@ impl Index {
 
     /// Serialize index to JSON
     ///
+    /// Keys come out sorted, so publishing the same packages twice yields the
+    /// same bytes and block digests of successive indexes line up.
+    ///
     /// # Errors
     ///
     /// Returns an error if the index cannot be serialized to JSON.
     pub fn to_json(&self) -> Result<String, Error> {
-        serde_json::to_string_pretty(self).map_err(|e| {
-            PackageError::InvalidFormat {
-                message: format!("failed to serialize index: {e}"),
-            }
-            .into()
-        })
+        let sorted = serde_json::to_value(self);
+        sorted
+            .and_then(|value| serde_json::to_string_pretty(&value))
+            .map_err(|e| {
+                PackageError::InvalidFormat {
+                    message: format!("failed to serialize index: {e}"),
+                }
+                .into()
+            })
     }
 
-    /// Validate index format and version
+    /// Validate index format and version using the default policy
     ///
     /// # Errors
     ///
-    /// Returns an error if the index version is unsupported, package names are empty,
-    /// versions are missing, architectures are unsupported, or required fields are missing.
+    /// Returns an error if the index version is unsupported or any entry
+    /// violates the default [`ValidationPolicy`].
     pub fn validate(&self) -> Result<(), Error> {
+        self.validate_with_policy(&ValidationPolicy::default())
+    }
+
+    /// Validate index format and version against a policy
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index version is unsupported, package names are empty
+    /// or duplicated, versions are missing or duplicated, architectures are unsupported,
+    /// URLs use a disallowed scheme, hashes are malformed, or the index exceeds the
+    /// policy's size limits. Error messages name the offending index location.
+    pub fn validate_with_policy(&self, policy: &ValidationPolicy) -> Result<(), Error> {
         // Check version compatibility
         if self.metadata.version > crate::SUPPORTED_INDEX_VERSION {
             return Err(PackageError::InvalidFormat {
@@ -138,62 +198,25 @@ impl Index {
             .into());
         }
 
-        // Validate entries
-        for (name, package) in &self.packages {
-            if name.is_empty() {
-                return Err(PackageError::InvalidFormat {
-                    message: "empty package name in index".to_string(),
-                }
-                .into());
-            }
-
-            for (version, entry) in &package.versions {
-                if version.is_empty() {
-                    return Err(PackageError::InvalidFormat {
-                        message: format!("empty version for package {name}"),
-                    }
-                    .into());
-                }
-
-                // Validate architecture
-                if entry.arch != "arm64" {
-                    return Err(PackageError::InvalidFormat {
-                        message: format!("unsupported architecture: {}", entry.arch),
-                    }
-                    .into());
-                }
-
-                // Validate URLs
-                if entry.download_url.is_empty() {
-                    return Err(PackageError::InvalidFormat {
-                        message: format!("missing download URL for {name}-{version}"),
-                    }
-                    .into());
-                }
-
-                if entry.blake3.is_empty() {
-                    return Err(PackageError::InvalidFormat {
-                        message: format!("missing BLAKE3 hash for {name}-{version}"),
-                    }
-                    .into());
-                }
-            }
-        }
-
-        Ok(())
+        crate::validation::validate_entries(self, policy)
     }
 
     /// Add or update a package version
+    ///
+    /// The build replaces one of the same version and architecture, and is
+    /// listed next to builds of the version for other architectures.
     pub fn add_version(&mut self, name: String, version: String, entry: VersionEntry) {
         self.packages
             .entry(name)
             .or_default()
             .versions
-            .insert(version, entry);
+            .entry(version)
+            .or_default()
+            .insert(entry);
     }
 
-    /// Remove a package version
-    pub fn remove_version(&mut self, name: &str, version: &str) -> Option<VersionEntry> {
+    /// Remove a package version with all its builds
+    pub fn remove_version(&mut self, name: &str, version: &str) -> Option<VersionBuilds> {
         self.packages.get_mut(name)?.versions.remove(version)
     }
 
@@ -226,13 +249,13 @@ impl VersionEntry {
     ///
     /// Returns an error if the architecture string is not supported.
     pub fn arch(&self) -> Result<Arch, Error> {
-        match self.arch.as_str() {
-            "arm64" => Ok(Arch::Arm64),
-            _ => Err(PackageError::InvalidFormat {
-                message: format!("unsupported architecture: {}", self.arch),
-            }
-            .into()),
-        }
+        self.arch.parse()
+    }
+
+    /// Check whether this build runs on the current machine
+    #[must_use]
+    pub fn runs_on_host(&self) -> bool {
+        self.arch().is_ok_and(|arch| arch.runs_on(Arch::host()))
     }
 
     /// Check if this version has SBOM data
@@ -241,3 +264,136 @@ impl VersionEntry {
         self.sbom.is_some()
     }
 }
+
+impl VersionBuilds {
+    /// Number of builds
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no build is listed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the builds in index order
+    pub fn iter(&self) -> std::slice::Iter<'_, VersionEntry> {
+        self.0.iter()
+    }
+
+    /// Add a build, replacing the one for the same architecture
+    pub fn insert(&mut self, entry: VersionEntry) {
+        match self.0.iter_mut().find(|build| build.arch == entry.arch) {
+            Some(build) => *build = entry,
+            None => self.0.push(entry),
+        }
+    }
+
+    /// Keep only the builds for which `keep` returns true, letting it
+    /// update them
+    pub fn retain_mut(&mut self, keep: impl FnMut(&mut VersionEntry) -> bool) {
+        self.0.retain_mut(keep);
+    }
+
+    /// Build to install on this machine
+    ///
+    /// A build for the host's own architecture is preferred over a
+    /// `universal2` one. Builds that do not run here are never returned.
+    #[must_use]
+    pub fn for_host(&self) -> Option<&VersionEntry> {
+        self.0
+            .iter()
+            .filter(|build| build.runs_on_host())
+            .min_by_key(|build| build.arch().ok() != Some(Arch::host()))
+    }
+
+    /// Build describing the version: the one for this machine, or the
+    /// first listed when none runs here
+    #[must_use]
+    pub fn preferred(&self) -> Option<&VersionEntry> {
+        self.for_host().or_else(|| self.0.first())
+    }
+}
+
+impl From<VersionEntry> for VersionBuilds {
+    fn from(entry: VersionEntry) -> Self {
+        Self(vec![entry])
+    }
+}
+
+impl<'a> IntoIterator for &'a VersionBuilds {
+    type Item = &'a VersionEntry;
+    type IntoIter = std::slice::Iter<'a, VersionEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Serialize for VersionBuilds {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0.as_slice() {
+            [build] => build.serialize(serializer),
+            builds => builds.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionBuilds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Builds;
+
+        impl<'de> Visitor<'de> for Builds {
+            type Value = VersionBuilds;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a version entry or a list of entries, one per architecture")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                VersionEntry::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(VersionBuilds::from)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Vec::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(VersionBuilds)
+            }
+        }
+
+        deserializer.deserialize_any(Builds)
+    }
+}
+
+/// Deserialize a map, rejecting a key that appears twice
+///
+/// Left to serde, the last of the repeated entries would silently win.
+fn unique_keys<'de, D, V>(deserializer: D) -> Result<HashMap<String, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    struct UniqueKeys<V>(PhantomData<V>);
+
+    impl<'de, V: Deserialize<'de>> Visitor<'de> for UniqueKeys<V> {
+        type Value = HashMap<String, V>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map with unique keys")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut entries = HashMap::new();
+            while let Some((key, value)) = map.next_entry::<String, V>()? {
+                if entries.contains_key(&key) {
+                    return Err(de::Error::custom(format!("duplicate key {key:?}")));
+                }
+                entries.insert(key, value);
+            }
+            Ok(entries)
+        }
+    }
+
+    deserializer.deserialize_map(UniqueKeys(PhantomData))
+}
@@ -0,0 +1,445 @@
+//! Index validation policy and structural checks
+//!
+//! Indexes come from remote repositories and are treated as untrusted input.
+//! Every error produced here names the offending location inside the index
+//! (for example `packages["curl"].versions["8.5.0"].download_url`) so that
+//! repository maintainers can find and fix the problem quickly.
+
+use crate::models::{Index, VersionEntry};
+use sps2_errors::{Error, PackageError};
+use sps2_types::Version;
+use std::collections::HashMap;
+
+/// Default upper bound on the number of packages in a single index
+pub const DEFAULT_MAX_PACKAGES: usize = 100_000;
+
+/// Default upper bound on the number of versions listed for one package
+pub const DEFAULT_MAX_VERSIONS_PER_PACKAGE: usize = 2_000;
+
+/// Default upper bound on the total number of version entries in an index
+pub const DEFAULT_MAX_TOTAL_VERSIONS: usize = 1_000_000;
+
+/// Default upper bound on the size of index JSON, checked before parsing
+pub const DEFAULT_MAX_INDEX_BYTES: usize = 256 * 1024 * 1024;
+
+/// Length of a hex-encoded BLAKE3 digest
+const BLAKE3_HEX_LEN: usize = 64;
+
+/// Policy controlling how strictly an index is validated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationPolicy {
+    /// Accept `http://` URLs for downloads, signatures and SBOMs
+    pub allow_insecure_urls: bool,
+    /// Accept `file://` URLs, as in an index read from a local directory
+    pub allow_file_urls: bool,
+    /// Maximum number of packages accepted in one index
+    pub max_packages: usize,
+    /// Maximum number of versions accepted for a single package
+    pub max_versions_per_package: usize,
+    /// Maximum number of version entries accepted across the whole index
+    pub max_total_versions: usize,
+    /// Maximum size in bytes of index JSON accepted for parsing
+    pub max_index_bytes: usize,
+}
+
+impl Default for ValidationPolicy {
+    fn default() -> Self {
+        Self {
+            allow_insecure_urls: false,
+            allow_file_urls: false,
+            max_packages: DEFAULT_MAX_PACKAGES,
+            max_versions_per_package: DEFAULT_MAX_VERSIONS_PER_PACKAGE,
+            max_total_versions: DEFAULT_MAX_TOTAL_VERSIONS,
+            max_index_bytes: DEFAULT_MAX_INDEX_BYTES,
+        }
+    }
+}
+
+impl ValidationPolicy {
+    /// Allow or reject plain `http://` URLs
+    #[must_use]
+    pub fn with_allow_insecure_urls(mut self, allow: bool) -> Self {
+        self.allow_insecure_urls = allow;
+        self
+    }
+
+    /// Allow or reject `file://` URLs
+    #[must_use]
+    pub fn with_allow_file_urls(mut self, allow: bool) -> Self {
+        self.allow_file_urls = allow;
+        self
+    }
+}
+
+fn invalid(location: &str, message: impl std::fmt::Display) -> Error {
+    PackageError::InvalidFormat {
+        message: format!("{location}: {message}"),
+    }
+    .into()
+}
+
+/// Validate the package and version entries of an index against a policy
+pub(crate) fn validate_entries(index: &Index, policy: &ValidationPolicy) -> Result<(), Error> {
+    if index.packages.len() > policy.max_packages {
+        return Err(invalid(
+            "packages",
+            format!(
+                "index lists {} packages, exceeding the limit of {}",
+                index.packages.len(),
+                policy.max_packages
+            ),
+        ));
+    }
+
+    let total_versions = index.version_count();
+    if total_versions > policy.max_total_versions {
+        return Err(invalid(
+            "packages",
+            format!(
+                "index lists {total_versions} versions, exceeding the limit of {}",
+                policy.max_total_versions
+            ),
+        ));
+    }
+
+    if let Some(url) = &index.metadata.search_url {
+        validate_url("search_url", url, policy)?;
+    }
+
+    // Package names that only differ by case collide on case-insensitive filesystems
+    let mut folded_names: HashMap<String, &str> = HashMap::with_capacity(index.packages.len());
+
+    for (name, package) in &index.packages {
+        let location = format!("packages[{name:?}]");
+
+        if name.is_empty() {
+            return Err(invalid(&location, "empty package name"));
+        }
+
+        if let Some(other) = folded_names.insert(name.to_lowercase(), name) {
+            return Err(invalid(
+                &location,
+                format!("duplicate package name (conflicts with {other:?})"),
+            ));
+        }
+
+        if package.versions.len() > policy.max_versions_per_package {
+            return Err(invalid(
+                &location,
+                format!(
+                    "package lists {} versions, exceeding the limit of {}",
+                    package.versions.len(),
+                    policy.max_versions_per_package
+                ),
+            ));
+        }
+
+        // Version strings that differ textually but have identical precedence
+        // (e.g. "1.0.0" and "1.0.0+build") would make resolution ambiguous
+        let mut seen: HashMap<(u64, u64, u64, String, &str), &str> = HashMap::new();
+
+        for (version, builds) in &package.versions {
+            let version_location = format!("{location}.versions[{version:?}]");
+
+            if version.is_empty() {
+                return Err(invalid(&version_location, "empty version"));
+            }
+            if builds.is_empty() {
+                return Err(invalid(&version_location, "no builds listed"));
+            }
+
+            for (position, entry) in builds.iter().enumerate() {
+                // A version with one build is written as that build alone
+                let location = if builds.len() == 1 {
+                    version_location.clone()
+                } else {
+                    format!("{version_location}[{position}]")
+                };
+
+                validate_version_entry(&location, entry, policy)?;
+
+                if let Ok(parsed) = Version::parse(version) {
+                    let key = (
+                        parsed.major,
+                        parsed.minor,
+                        parsed.patch,
+                        parsed.pre.to_string(),
+                        entry.arch.as_str(),
+                    );
+                    if let Some(other) = seen.insert(key, version) {
+                        return Err(invalid(
+                            &location,
+                            format!(
+                                "duplicate entry for {name} {version} ({}) (conflicts with {other:?})",
+                                entry.arch
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_version_entry(
+    location: &str,
+    entry: &VersionEntry,
+    policy: &ValidationPolicy,
+) -> Result<(), Error> {
+    if entry.arch().is_err() {
+        return Err(invalid(
+            &format!("{location}.arch"),
+            format!("unsupported architecture: {}", entry.arch),
+        ));
+    }
+
+    if entry.download_url.is_empty() {
+        return Err(invalid(
+            &format!("{location}.download_url"),
+            "missing download URL",
+        ));
+    }
+    validate_url(
+        &format!("{location}.download_url"),
+        &entry.download_url,
+        policy,
+    )?;
+
+    if !entry.minisig_url.is_empty() {
+        validate_url(
+            &format!("{location}.minisig_url"),
+            &entry.minisig_url,
+            policy,
+        )?;
+    }
+
+    if entry.blake3.is_empty() {
+        return Err(invalid(
+            &format!("{location}.blake3"),
+            "missing BLAKE3 hash",
+        ));
+    }
+    validate_blake3(&format!("{location}.blake3"), &entry.blake3)?;
+
+    if let Some(sbom) = &entry.sbom {
+        let spdx = format!("{location}.sbom.spdx");
+        validate_url(&format!("{spdx}.url"), &sbom.spdx.url, policy)?;
+        validate_blake3(&format!("{spdx}.blake3"), &sbom.spdx.blake3)?;
+
+        if let Some(cyclonedx) = &sbom.cyclonedx {
+            let cdx = format!("{location}.sbom.cyclonedx");
+            validate_url(&format!("{cdx}.url"), &cyclonedx.url, policy)?;
+            validate_blake3(&format!("{cdx}.blake3"), &cyclonedx.blake3)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that a URL uses an accepted scheme
+fn validate_url(location: &str, url: &str, policy: &ValidationPolicy) -> Result<(), Error> {
+    let scheme = url
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+
+    match scheme.as_deref() {
+        Some("https") => Ok(()),
+        Some("http") if policy.allow_insecure_urls => Ok(()),
+        Some("file") if policy.allow_file_urls => Ok(()),
+        Some("http") => Err(invalid(
+            location,
+            format!("insecure URL {url:?} (set security.allow_insecure_index_urls to permit http)"),
+        )),
+        Some(other) => Err(invalid(
+            location,
+            format!("unsupported URL scheme {other:?} in {url:?}"),
+        )),
+        None => Err(invalid(location, format!("malformed URL {url:?}"))),
+    }
+}
+
+/// Check that a hash is a 64 character lowercase hex BLAKE3 digest
+fn validate_blake3(location: &str, hash: &str) -> Result<(), Error> {
+    if hash.len() != BLAKE3_HEX_LEN
+        || !hash
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        return Err(invalid(
+            location,
+            format!("malformed BLAKE3 hash {hash:?} (expected {BLAKE3_HEX_LEN} lowercase hex characters)"),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DependencyInfo;
+
+    fn entry(url: &str) -> VersionEntry {
+        VersionEntry {
+            revision: 1,
+            arch: "arm64".to_string(),
+            blake3: "a".repeat(BLAKE3_HEX_LEN),
+            download_url: url.to_string(),
+            minisig_url: format!("{url}.minisig"),
+            dependencies: DependencyInfo::default(),
+            sbom: None,
+            description: None,
+            homepage: None,
+            license: None,
+        }
+    }
+
+    fn index_with(entries: &[(&str, &str, VersionEntry)]) -> Index {
+        let mut index = Index::new();
+        for (name, version, entry) in entries {
+            index.add_version((*name).to_string(), (*version).to_string(), entry.clone());
+        }
+        index
+    }
+
+    #[test]
+    fn accepts_well_formed_index() {
+        let index = index_with(&[("curl", "8.5.0", entry("https://repo.example/curl.sp"))]);
+        assert!(index.validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_http_unless_allowed() {
+        let index = index_with(&[("curl", "8.5.0", entry("http://repo.example/curl.sp"))]);
+        let err = index.validate().unwrap_err().to_string();
+        assert!(err.contains(r#"packages["curl"].versions["8.5.0"].download_url"#));
+
+        let policy = ValidationPolicy::default().with_allow_insecure_urls(true);
+        assert!(index.validate_with_policy(&policy).is_ok());
+    }
+
+    #[test]
+    fn accepts_intel_and_universal_builds_only() {
+        let url = "https://repo.example/curl.sp";
+        for (arch, ok) in [("x86_64", true), ("universal2", true), ("ppc", false)] {
+            let mut build = entry(url);
+            build.arch = arch.to_string();
+            let index = index_with(&[("curl", "8.5.0", build)]);
+            assert_eq!(index.validate().is_ok(), ok, "{arch}");
+        }
+    }
+
+    #[test]
+    fn accepts_one_build_per_architecture_of_a_version() {
+        let url = "https://repo.example/curl.sp";
+        let mut intel = entry(url);
+        intel.arch = "x86_64".to_string();
+        let index = index_with(&[("curl", "8.5.0", entry(url)), ("curl", "8.5.0", intel)]);
+        assert_eq!(index.packages["curl"].versions["8.5.0"].len(), 2);
+        assert!(index.validate().is_ok());
+
+        let json = index.to_json().unwrap();
+        let mut twice = Index::from_json(&json).unwrap();
+        assert_eq!(twice.packages["curl"].versions["8.5.0"].len(), 2);
+
+        let mut bad = entry("http://repo.example/curl-intel.sp");
+        bad.arch = "x86_64".to_string();
+        twice.add_version("curl".to_string(), "8.5.0".to_string(), bad);
+        let err = twice.validate().unwrap_err().to_string();
+        assert!(
+            err.contains(r#"packages["curl"].versions["8.5.0"][1].download_url"#),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn rejects_two_builds_for_one_architecture_when_parsing_a_list() {
+        let build = serde_json::to_string(&entry("https://repo.example/curl.sp")).unwrap();
+        let json = format!(
+            r#"{{"version":1,"minimum_client":"0.1.0","timestamp":"2024-01-01T00:00:00Z","packages":{{"curl":{{"versions":{{"8.5.0":[{build},{build}]}}}}}}}}"#
+        );
+        let err = Index::from_json(&json).unwrap().validate().unwrap_err();
+        assert!(err.to_string().contains("duplicate entry"), "{err}");
+    }
+
+    #[test]
+    fn rejects_unknown_schemes() {
+        let index = index_with(&[("curl", "8.5.0", entry("ftp://repo.example/curl.sp"))]);
+        let policy = ValidationPolicy::default().with_allow_insecure_urls(true);
+        assert!(index.validate_with_policy(&policy).is_err());
+    }
+
+    #[test]
+    fn accepts_file_urls_only_when_allowed() {
+        let index = index_with(&[("curl", "8.5.0", entry("file:///Volumes/USB/curl.sp"))]);
+        assert!(index.validate().is_err());
+
+        let policy = ValidationPolicy::default().with_allow_file_urls(true);
+        assert!(index.validate_with_policy(&policy).is_ok());
+    }
+
+    #[test]
+    fn rejects_malformed_hashes() {
+        let mut bad = entry("https://repo.example/curl.sp");
+        bad.blake3 = "xyz".to_string();
+        let index = index_with(&[("curl", "8.5.0", bad)]);
+        let err = index.validate().unwrap_err().to_string();
+        assert!(err.contains(".blake3"));
+    }
+
+    #[test]
+    fn rejects_duplicate_versions_and_names() {
+        let url = "https://repo.example/curl.sp";
+        let index = index_with(&[
+            ("curl", "8.5.0", entry(url)),
+            ("curl", "8.5.0+rebuild", entry(url)),
+        ]);
+        assert!(index.validate().is_err());
+
+        let index = index_with(&[("curl", "8.5.0", entry(url)), ("Curl", "8.5.0", entry(url))]);
+        assert!(index.validate().is_err());
+    }
+
+    #[test]
+    fn enforces_size_caps() {
+        let url = "https://repo.example/curl.sp";
+        let index = index_with(&[("curl", "8.5.0", entry(url)), ("wget", "1.0.0", entry(url))]);
+        let policy = ValidationPolicy {
+            max_packages: 1,
+            ..ValidationPolicy::default()
+        };
+        assert!(index.validate_with_policy(&policy).is_err());
+    }
+
+    #[test]
+    fn rejects_repeated_keys_when_parsing() {
+        let version = serde_json::to_string(&entry("https://repo.example/curl.sp")).unwrap();
+        let index = |packages: &str| {
+            format!(
+                r#"{{"version":1,"minimum_client":"0.1.0","timestamp":"2024-01-01T00:00:00Z","packages":{{{packages}}}}}"#
+            )
+        };
+        let curl = format!(r#""curl":{{"versions":{{"8.5.0":{version}}}}}"#);
+        assert!(Index::from_json(&index(&curl)).is_ok());
+
+        let err = Index::from_json(&index(&format!("{curl},{curl}")))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(r#"duplicate key "curl""#), "{err}");
+
+        let repeated = format!(r#""curl":{{"versions":{{"8.5.0":{version},"8.5.0":{version}}}}}"#);
+        let err = Index::from_json(&index(&repeated)).unwrap_err().to_string();
+        assert!(err.contains(r#"duplicate key "8.5.0""#), "{err}");
+    }
+
+    #[test]
+    fn rejects_oversized_json_before_parsing() {
+        let json = Index::new().to_json().unwrap();
+        assert!(Index::from_json_limited(&json, json.len()).is_ok());
+        let err = Index::from_json_limited(&json, json.len() - 1)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("exceeding the limit"), "{err}");
+    }
+}
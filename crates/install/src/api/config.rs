@@ -1,3 +1,10 @@
+use sps2_errors::{Error, InstallError};
+use sps2_hash::{Hash, HashAlgorithm};
+use sps2_net::PackageDownloadConfig;
+use sps2_types::ConflictPolicy;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
 /// Installer configuration
 #[derive(Clone, Debug)]
 pub struct InstallConfig {
@@ -9,6 +16,20 @@ pub struct InstallConfig {
     pub enable_apfs: bool,
     /// State retention policy (number of states to keep)
     pub state_retention: usize,
+    /// Only use packages already present in the store
+    pub offline: bool,
+    /// Also install the packages that resolved packages recommend
+    pub recommends: bool,
+    /// How packages placing different files at the same path are settled
+    pub conflict_policy: ConflictPolicy,
+    /// Signature enforcement for downloaded packages
+    pub security: SecurityPolicy,
+    /// Download settings, including where trusted keys and quarantined
+    /// downloads live
+    pub download: PackageDownloadConfig,
+    /// Take a local APFS snapshot before committing changes to at least
+    /// this many packages; `None` never takes one
+    pub os_snapshot_min_changes: Option<usize>,
 }
 
 impl Default for InstallConfig {
@@ -18,6 +39,12 @@ impl Default for InstallConfig {
             download_timeout: 300, // 5 minutes
             enable_apfs: cfg!(target_os = "macos"),
             state_retention: 10,
+            offline: false,
+            recommends: true,
+            conflict_policy: ConflictPolicy::default(),
+            security: SecurityPolicy::default(),
+            download: PackageDownloadConfig::default(),
+            os_snapshot_min_changes: None,
         }
     }
 }
@@ -50,11 +77,166 @@ impl InstallConfig {
         self.state_retention = count;
         self
     }
+
+    /// Restrict installation to packages already present in the store
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Include or leave out recommended packages
+    #[must_use]
+    pub fn with_recommends(mut self, recommends: bool) -> Self {
+        self.recommends = recommends;
+        self
+    }
+
+    /// Set how packages placing different files at the same path are
+    /// settled
+    #[must_use]
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Set the signature policy for downloaded packages
+    #[must_use]
+    pub fn with_security_policy(mut self, security: SecurityPolicy) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download: PackageDownloadConfig) -> Self {
+        self.download = download;
+        self
+    }
+
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.os_snapshot_min_changes = min_changes;
+        self
+    }
+}
+
+/// Artifact hashes an install is restricted to
+///
+/// Parsed from a pip-style requirements file: one package per line followed
+/// by one or more `blake3:<hex>` hashes, optionally written as
+/// `--hash=blake3:<hex>`. A package may be listed on several lines; `#`
+/// starts a comment. Every artifact installed must hash to one of the values
+/// listed for its package, whatever the index or its signature says.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequiredHashes {
+    pins: HashMap<String, HashSet<String>>,
+}
+
+impl RequiredHashes {
+    /// Read and parse a required hashes file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or does not parse.
+    pub async fn load(path: &Path) -> Result<Self, Error> {
+        let display = path.display().to_string();
+        let text =
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|e| InstallError::InvalidHashPins {
+                    path: display.clone(),
+                    message: e.to_string(),
+                })?;
+        Self::parse(&text, &display)
+    }
+
+    /// Parse a required hashes file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line if a package has no hashes or a hash
+    /// is not a blake3 hex digest.
+    pub fn parse(text: &str, path: &str) -> Result<Self, Error> {
+        let invalid = |line: usize, message: String| InstallError::InvalidHashPins {
+            path: path.to_string(),
+            message: format!("line {line}: {message}"),
+        };
+
+        let mut pins: HashMap<String, HashSet<String>> = HashMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            let mut words = line.split_whitespace().filter(|word| *word != "\\");
+            let Some(package) = words.next() else {
+                continue;
+            };
+            let hashes = pins.entry(package.to_string()).or_default();
+            let before = hashes.len();
+            for word in words {
+                let hex = word
+                    .trim_start_matches("--hash=")
+                    .strip_prefix("blake3:")
+                    .ok_or_else(|| {
+                        invalid(index + 1, format!("expected blake3:<hex>, got {word}"))
+                    })?;
+                if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid(index + 1, format!("invalid blake3 hash {hex}")).into());
+                }
+                hashes.insert(hex.to_ascii_lowercase());
+            }
+            if hashes.len() == before && before == 0 {
+                return Err(invalid(index + 1, format!("no hashes listed for {package}")).into());
+            }
+        }
+        Ok(Self { pins })
+    }
+
+    /// Refuse an artifact whose blake3 hash is not listed for its package
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstallError::HashNotPinned`] if the package is not listed
+    /// or the hash is not among its pinned hashes.
+    pub fn check(&self, package: &str, hash: &Hash) -> Result<(), Error> {
+        let hex = hash.to_hex();
+        let pinned = hash.algorithm() == HashAlgorithm::Blake3
+            && self
+                .pins
+                .get(package)
+                .is_some_and(|hashes| hashes.contains(&hex));
+        if pinned {
+            Ok(())
+        } else {
+            Err(InstallError::HashNotPinned {
+                package: package.to_string(),
+                hash: hex,
+            }
+            .into())
+        }
+    }
 }
 
 /// Security policy for signature enforcement
 #[derive(Clone, Copy, Debug)]
+#[allow(clippy::struct_excessive_bools)] // independent settings
 pub struct SecurityPolicy {
     pub verify_signatures: bool,
     pub allow_unsigned: bool,
+    /// Strip the quarantine attribute from executables of signed packages
+    pub strip_quarantine: bool,
+    /// Install setuid and setgid bits instead of dropping them
+    pub allow_setuid: bool,
+}
+
+impl Default for SecurityPolicy {
+    fn default() -> Self {
+        Self {
+            verify_signatures: true,
+            allow_unsigned: false,
+            strip_quarantine: true,
+            allow_setuid: false,
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::RequiredHashes;
 use sps2_events::EventSender;
 use sps2_types::PackageSpec;
 use std::path::PathBuf;
@@ -15,6 +16,9 @@ pub struct InstallContext {
     /// Force re-download even if cached in the store
     pub force_download: bool,
 
+    /// Artifact hashes packages are restricted to, if pinned
+    pub required_hashes: Option<RequiredHashes>,
+
     /// Event sender for progress reporting
     pub event_sender: Option<EventSender>,
 }
@@ -25,6 +29,7 @@ context_builder! {
         local_files: Vec<PathBuf>,
         force: bool,
         force_download: bool,
+        required_hashes: Option<RequiredHashes>,
 
     }
 }
@@ -39,6 +44,11 @@ pub struct UninstallContext {
     pub autoremove: bool,
     /// Force removal even with dependents
     pub force: bool,
+    /// Also remove the installed packages that depend on the removed ones
+    pub cascade: bool,
+    /// Installed packages to remove with `autoremove` like dependencies,
+    /// once nothing left installed needs them
+    pub prune: Vec<String>,
 
     /// Event sender for progress reporting
     pub event_sender: Option<EventSender>,
@@ -49,6 +59,8 @@ context_builder! {
         packages: Vec<String>,
         autoremove: bool,
         force: bool,
+        cascade: bool,
+        prune: Vec<String>,
 
     }
 }
@@ -61,6 +73,8 @@ pub struct UpdateContext {
     pub packages: Vec<String>,
     /// Upgrade mode (ignore upper bounds)
     pub upgrade: bool,
+    /// Update even if installed packages' version constraints break
+    pub force: bool,
 
     /// Event sender for progress reporting
     pub event_sender: Option<EventSender>,
@@ -70,7 +84,65 @@ context_builder! {
     UpdateContext {
         packages: Vec<String>,
         upgrade: bool,
+        force: bool,
 
     }
 }
 context_add_package_method!(UpdateContext, String);
+
+/// Apply context: installs, removals and upgrades made as one change
+#[derive(Clone, Debug)]
+pub struct ApplyContext {
+    /// Package specifications to install
+    pub install: Vec<PackageSpec>,
+    /// Package names to remove
+    pub remove: Vec<String>,
+    /// Package names to upgrade
+    pub upgrade: Vec<String>,
+    /// Upgrade every installed package that is not removed
+    pub upgrade_all: bool,
+    /// Apply even if installed packages' dependencies or version
+    /// constraints break
+    pub force: bool,
+    /// Also remove packages installed only as dependencies or
+    /// recommendations that nothing left installed needs, unless the
+    /// change installs something needing them
+    pub autoremove: bool,
+    /// Package names to remove with `autoremove`, kept while a package
+    /// left installed needs them
+    pub prune: Vec<String>,
+
+    /// Event sender for progress reporting
+    pub event_sender: Option<EventSender>,
+}
+
+context_builder! {
+    ApplyContext {
+        install: Vec<PackageSpec>,
+        remove: Vec<String>,
+        upgrade: Vec<String>,
+        upgrade_all: bool,
+        force: bool,
+        autoremove: bool,
+        prune: Vec<String>,
+
+    }
+}
+
+/// Reinstall context
+#[derive(Clone, Debug)]
+pub struct ReinstallContext {
+    /// Package names to reinstall
+    pub packages: Vec<String>,
+
+    /// Event sender for progress reporting
+    pub event_sender: Option<EventSender>,
+}
+
+context_builder! {
+    ReinstallContext {
+        packages: Vec<String>,
+
+    }
+}
+context_add_package_method!(ReinstallContext, String);
@@ -1,5 +1,7 @@
 use chrono::{DateTime, Utc};
+use sps2_errors::{Error, InstallError};
 use sps2_resolver::PackageId;
+use sps2_types::BrokenDependency;
 use uuid::Uuid;
 
 /// Installation result
@@ -13,6 +15,8 @@ pub struct InstallResult {
     pub updated_packages: Vec<PackageId>,
     /// Packages that were removed
     pub removed_packages: Vec<PackageId>,
+    /// Installed packages left without a runtime dependency by a forced removal
+    pub broken_dependencies: Vec<BrokenDependency>,
 }
 
 impl InstallResult {
@@ -24,6 +28,7 @@ impl InstallResult {
             installed_packages: Vec::new(),
             updated_packages: Vec::new(),
             removed_packages: Vec::new(),
+            broken_dependencies: Vec::new(),
         }
     }
 
@@ -49,6 +54,74 @@ impl InstallResult {
     }
 }
 
+/// The packages an uninstall removes, and the dependencies it breaks
+#[derive(Debug, Default)]
+pub struct RemovalSet {
+    /// Installed packages named in the request
+    pub requested: Vec<PackageId>,
+    /// Installed packages removed because they depend on a removed one
+    pub cascaded: Vec<PackageId>,
+    /// Recommended packages removed because nothing left installed needs
+    /// or recommends them
+    pub autoremoved: Vec<PackageId>,
+    /// Installed packages left without a runtime dependency
+    pub broken: Vec<BrokenDependency>,
+    /// Requested names that are not installed
+    pub not_installed: Vec<String>,
+}
+
+impl RemovalSet {
+    /// Every package the uninstall removes, requested ones first
+    pub fn packages(&self) -> impl Iterator<Item = &PackageId> {
+        self.requested
+            .iter()
+            .chain(&self.cascaded)
+            .chain(&self.autoremoved)
+    }
+
+    /// Fail if a removed package is still needed by an installed one
+    ///
+    /// # Errors
+    ///
+    /// Returns [`InstallError::PackageHasDependents`] for the first removed
+    /// package that others need.
+    pub fn ensure_nothing_broken(&self) -> Result<(), Error> {
+        for package in self.packages() {
+            let dependents: Vec<&str> = self
+                .broken
+                .iter()
+                .filter(|broken| broken.needs.contains(&package.name))
+                .map(|broken| broken.package.as_str())
+                .collect();
+            if !dependents.is_empty() {
+                return Err(InstallError::PackageHasDependents {
+                    package: package.name.clone(),
+                    dependents: dependents.join(", "),
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An artifact a planned install would download
+#[derive(Debug, Clone)]
+pub struct PlannedDownload {
+    pub package: PackageId,
+    pub url: String,
+}
+
+/// What an install, update or uninstall would do, worked out without
+/// touching the store or the states
+#[derive(Debug)]
+pub struct InstallPlan {
+    /// Package changes; `state_id` is the active state they apply to
+    pub result: InstallResult,
+    /// Artifacts to download; other resolved packages are reused from the store
+    pub downloads: Vec<PlannedDownload>,
+}
+
 /// State information for listing
 #[derive(Debug, Clone)]
 pub struct StateInfo {
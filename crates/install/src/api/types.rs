@@ -18,4 +18,6 @@ pub struct PreparedPackage {
     pub is_local: bool,
     /// Optional package archive hash (BLAKE3) provided by the repository
     pub package_hash: Option<Hash>,
+    /// Trusted key that verified the package's signature, if it was signed
+    pub signing_key: Option<String>,
 }
@@ -0,0 +1,144 @@
+//! Project environments: packages linked into a prefix of their own
+//!
+//! An environment directory holds a `live` prefix and two slots managed by
+//! [`LiveSlots`], just like the installation's live prefix. Packages are
+//! linked into the inactive slot along with the man page links and profile
+//! snippets, and the slot is then swapped in, so a shell already using the
+//! environment never sees it half built. Environments are not recorded in
+//! the state database; their state id is derived from the store hashes of
+//! their packages, so syncing packages that are already in place changes
+//! nothing.
+
+use crate::atomic::{integration, profile::ProfileEnvironment};
+use crate::PreparedPackage;
+use sps2_errors::Error;
+use sps2_platform::filesystem_helpers as fs;
+use sps2_resolver::PackageId;
+use sps2_state::live_slots::LiveSlots;
+use sps2_store::StoredPackage;
+use sps2_types::LinkStrategy;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Directory of an environment's prefix within the environment directory
+pub const ENVIRONMENT_PREFIX: &str = "live";
+
+/// An environment after [`sync_environment`]
+#[derive(Debug, Clone)]
+pub struct EnvironmentSync {
+    /// Prefix the packages are linked into
+    pub prefix: PathBuf,
+    /// Whether the prefix was rebuilt, rather than already holding the
+    /// packages
+    pub changed: bool,
+    /// Variables the packages declare, for the prefix
+    pub profile: ProfileEnvironment,
+}
+
+/// Make the environment at `env_dir` hold exactly `packages`
+///
+/// # Errors
+///
+/// Returns an error if a package cannot be loaded from the store or linked,
+/// or the slots cannot be prepared or swapped.
+pub async fn sync_environment<S: BuildHasher>(
+    env_dir: &Path,
+    packages: &HashMap<PackageId, PreparedPackage, S>,
+    link_strategy: LinkStrategy,
+) -> Result<EnvironmentSync, Error> {
+    let prefix = env_dir.join(ENVIRONMENT_PREFIX);
+    let mut slots = LiveSlots::initialize(env_dir.to_path_buf(), prefix.clone()).await?;
+
+    let mut manifests = Vec::new();
+    for prepared in packages.values() {
+        let path = prepared.store_path.join("manifest.toml");
+        manifests.push(sps2_store::manifest_io::read_manifest(&path).await?);
+    }
+    let profile = ProfileEnvironment::collect(
+        &prefix,
+        manifests
+            .iter()
+            .map(|manifest| (manifest.package.name.as_str(), &manifest.environment)),
+    );
+
+    let state = environment_state(packages);
+    let active = slots.active_slot();
+    if slots.slot_state(active) == Some(state) {
+        return Ok(EnvironmentSync {
+            prefix,
+            changed: false,
+            profile,
+        });
+    }
+
+    // The inactive slot holds whatever the environment had before; build
+    // from scratch rather than work out the difference
+    let staging = slots.inactive_slot();
+    let slot = slots.slot_path(staging);
+    if fs::exists(&slot).await {
+        fs::remove_dir_all(&slot).await?;
+    }
+    slots.ensure_slot_dir(staging).await?;
+    for prepared in packages.values() {
+        StoredPackage::load(&prepared.store_path)
+            .await?
+            .link_to(&slot, link_strategy)
+            .await?;
+    }
+    tokio::task::spawn_blocking({
+        let slot = slot.clone();
+        move || integration::link_trees(&slot)
+    })
+    .await
+    .map_err(|e| Error::internal(format!("linking man pages and completions failed: {e}")))??;
+    profile.write(&slot)?;
+
+    slots.record_slot_state(staging, Some(state)).await?;
+    let parent = slots.slot_state(active).unwrap_or_else(Uuid::nil);
+    slots.swap_to_live(staging, state, parent).await?;
+
+    Ok(EnvironmentSync {
+        prefix,
+        changed: true,
+        profile,
+    })
+}
+
+/// State id of an environment holding `packages`
+fn environment_state<S: BuildHasher>(packages: &HashMap<PackageId, PreparedPackage, S>) -> Uuid {
+    let mut hashes: Vec<String> = packages
+        .values()
+        .map(|prepared| prepared.hash.to_hex())
+        .collect();
+    hashes.sort();
+    let digest = blake3::hash(hashes.join("\n").as_bytes());
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest.as_bytes()[..16]);
+    Uuid::from_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn unchanged_packages_keep_the_prefix() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let env_dir = temp.path().join("env");
+        let packages = HashMap::new();
+
+        let first = sync_environment(&env_dir, &packages, LinkStrategy::default())
+            .await
+            .unwrap();
+        assert!(first.changed);
+        assert_eq!(first.prefix, env_dir.join("live"));
+        assert!(first.prefix.join("STATE").is_file());
+
+        let second = sync_environment(&env_dir, &packages, LinkStrategy::default())
+            .await
+            .unwrap();
+        assert!(!second.changed);
+    }
+}
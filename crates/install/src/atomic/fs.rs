@@ -30,7 +30,9 @@ pub(super) async fn link_package_to_staging(
         }));
     }
 
-    stored_package.link_to(staging_prefix).await?;
+    stored_package
+        .link_to(staging_prefix, transition.link_strategy)
+        .await?;
 
     let mut had_file_hashes = false;
     let mut linked_entry_count = 0usize;
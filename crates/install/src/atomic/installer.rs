@@ -1,16 +1,20 @@
 //! Atomic installer implementation using slot-based staging.
 
-use crate::atomic::{package, transition::StateTransition};
+use crate::atomic::{integration, package, profile, special, staging, transition::StateTransition};
 // Removed Python venv handling - Python packages are now handled like regular packages
 use crate::{InstallContext, InstallResult, PreparedPackage};
+use sps2_config::ResourceManager;
 use sps2_errors::{Error, InstallError};
 use sps2_events::events::{LifecycleEvent, StateTransitionContext, TransitionSummary};
 use sps2_events::{AppEvent, EventEmitter, EventSender, FailureContext, StateEvent};
 use sps2_resolver::{PackageId, ResolvedNode};
 use sps2_state::StateManager;
 use sps2_store::PackageStore;
+use sps2_types::ConflictPolicy;
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
 use std::time::Instant;
 use uuid::Uuid;
 
@@ -28,6 +32,13 @@ impl EventEmitter for crate::UninstallContext {
     }
 }
 
+/// Implement `EventEmitter` for `ReinstallContext`
+impl EventEmitter for crate::ReinstallContext {
+    fn event_sender(&self) -> Option<&EventSender> {
+        self.event_sender.as_ref()
+    }
+}
+
 /// Implement `EventEmitter` for `UpdateContext`
 impl EventEmitter for crate::UpdateContext {
     fn event_sender(&self) -> Option<&EventSender> {
@@ -35,19 +46,50 @@ impl EventEmitter for crate::UpdateContext {
     }
 }
 
+/// Implement `EventEmitter` for `ApplyContext`
+impl EventEmitter for crate::ApplyContext {
+    fn event_sender(&self) -> Option<&EventSender> {
+        self.event_sender.as_ref()
+    }
+}
+
 /// Atomic installer using APFS optimizations
 pub struct AtomicInstaller {
     /// State manager for atomic transitions
     state_manager: StateManager,
     /// Content-addressable package store
     store: PackageStore,
+    /// Strip the quarantine attribute from executables of signed packages
+    strip_quarantine: bool,
+    /// Names of the packages to reinstall that a trusted key signed
+    signed: HashSet<String>,
+    /// Install the setuid and setgid bits packages give their files
+    allow_setuid: bool,
+    /// Limits how many packages are linked into staging at once
+    resources: Arc<ResourceManager>,
+    /// Names of the packages the user asked for
+    requested: HashSet<String>,
+    /// How packages placing different files at the same path are settled
+    conflict_policy: ConflictPolicy,
+    /// Installed packages to remove in the same transition as installing
+    removals: Vec<PackageId>,
+    /// Operation the new state is recorded as
+    operation: &'static str,
+    /// Take a local APFS snapshot before committing transitions that change
+    /// at least this many packages
+    os_snapshot_min_changes: Option<usize>,
 }
 
 impl AtomicInstaller {
     /// Execute two-phase commit flow for a transition
+    ///
+    /// `changes` is the number of packages the transition installs, updates
+    /// or removes, which decides whether a local APFS snapshot is taken
+    /// first.
     async fn execute_two_phase_commit<T: EventEmitter>(
         &self,
         transition: &StateTransition,
+        changes: usize,
         context: &T,
     ) -> Result<(), Error> {
         let source = transition.parent_id;
@@ -65,6 +107,29 @@ impl AtomicInstaller {
         };
         let transition_start = Instant::now();
 
+        self.link_integration_trees(&transition.slot_path, context)
+            .await;
+        let profile = self
+            .state_profile(transition.package_refs.iter().map(|r| r.hash.as_str()))
+            .await;
+        for conflict in &profile.conflicts {
+            context.emit_warning(format!(
+                "{} sets {} differently from {}, whose value is used",
+                conflict.package, conflict.variable, conflict.winner
+            ));
+        }
+        for refused in &profile.refused {
+            context.emit_warning(format!(
+                "{} may not set the environment variable {:?}; it is left out",
+                refused.package, refused.variable
+            ));
+        }
+        if let Err(e) = profile.write(&transition.slot_path) {
+            context.emit_warning(format!("Could not write the profile snippets: {e}"));
+        }
+
+        let os_snapshot = self.take_os_snapshot(changes, context).await;
+
         context.emit(AppEvent::State(StateEvent::TransitionStarted {
             context: transition_context.clone(),
         }));
@@ -110,6 +175,18 @@ impl AtomicInstaller {
             return Err(e);
         }
 
+        if let Some(snapshot) = os_snapshot {
+            if let Err(e) = self
+                .state_manager
+                .record_os_snapshot(&target, &snapshot)
+                .await
+            {
+                context.emit_warning(format!(
+                    "Could not record local snapshot {snapshot} on state {target}: {e}"
+                ));
+            }
+        }
+
         let summary = TransitionSummary {
             duration_ms: Some(
                 u64::try_from(transition_start.elapsed().as_millis()).unwrap_or(u64::MAX),
@@ -124,6 +201,111 @@ impl AtomicInstaller {
         Ok(())
     }
 
+    /// Take a local APFS snapshot if the transition changes enough packages
+    ///
+    /// The snapshot is a safety net beyond sps2's own states, so failing to
+    /// take one is reported, not fatal.
+    async fn take_os_snapshot<T: EventEmitter>(
+        &self,
+        changes: usize,
+        context: &T,
+    ) -> Option<String> {
+        let min_changes = self.os_snapshot_min_changes?;
+        if changes < min_changes {
+            return None;
+        }
+        let platform = sps2_platform::PlatformManager::instance().platform();
+        match platform.process().local_snapshot().await {
+            Ok(Some(snapshot)) => {
+                context.emit_debug(format!(
+                    "Took local snapshot {snapshot} before changing {changes} package(s)"
+                ));
+                Some(snapshot)
+            }
+            Ok(None) => None,
+            Err(e) => {
+                context.emit_warning(format!(
+                    "Could not take a local snapshot before changing {changes} package(s): {e}"
+                ));
+                None
+            }
+        }
+    }
+
+    /// Link man pages and completions into their well-known trees in the
+    /// staging slot
+    ///
+    /// Without the links only docs and completions are missing, so failing
+    /// to create them is reported, not fatal.
+    async fn link_integration_trees<T: EventEmitter>(&self, slot: &Path, context: &T) {
+        let slot = slot.to_path_buf();
+        let result = tokio::task::spawn_blocking({
+            let slot = slot.clone();
+            move || integration::link_trees(&slot)
+        })
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|result| result);
+        let trees = match result {
+            Ok(trees) => trees,
+            Err(e) => {
+                context.emit_warning(format!("Could not link man pages and completions: {e}"));
+                Vec::new()
+            }
+        };
+        for tree in trees {
+            if !tree.linked.is_empty() {
+                context.emit_debug(format!(
+                    "Linked {} file(s) from {} into {}",
+                    tree.linked.len(),
+                    tree.source,
+                    tree.target
+                ));
+            }
+            if tree.removed > 0 {
+                context.emit_debug(format!(
+                    "Removed {} link(s) from {} to files no longer in {}",
+                    tree.removed, tree.target, tree.source
+                ));
+            }
+        }
+
+        if slot.join("share/man").is_dir() {
+            let manpath = std::env::var("MANPATH").ok();
+            let man_dir = self.state_manager.live_path().join("share/man");
+            if let Some(hint) = integration::manpath_hint(manpath.as_deref(), &man_dir) {
+                context.emit_warning(hint);
+            }
+        }
+    }
+
+    /// Environment variables declared by the packages with store hashes
+    /// `hashes`
+    ///
+    /// A package whose manifest cannot be read contributes nothing; without
+    /// the snippets only its variables are missing.
+    async fn state_profile<'a>(
+        &self,
+        hashes: impl Iterator<Item = &'a str>,
+    ) -> profile::ProfileEnvironment {
+        let mut manifests = Vec::new();
+        for hash in hashes {
+            let Ok(hash) = sps2_hash::Hash::from_hex(hash) else {
+                continue;
+            };
+            let path = self.store.package_path(&hash).join("manifest.toml");
+            if let Ok(manifest) = sps2_store::manifest_io::read_manifest(&path).await {
+                manifests.push(manifest);
+            }
+        }
+        profile::ProfileEnvironment::collect(
+            self.state_manager.live_path(),
+            manifests
+                .iter()
+                .map(|manifest| (manifest.package.name.as_str(), &manifest.environment)),
+        )
+    }
+
     /// Setup state transition and staging directory
     async fn setup_state_transition<T: EventEmitter>(
         &self,
@@ -136,6 +318,7 @@ impl AtomicInstaller {
 
         // Set event sender on transition
         transition.event_sender = context.event_sender().cloned();
+        transition.link_strategy = self.store.file_store().link_strategy();
 
         context.emit_debug(format!(
             "Prepared staging slot {} at {}",
@@ -156,9 +339,94 @@ impl AtomicInstaller {
         Self {
             state_manager,
             store,
+            strip_quarantine: false,
+            signed: HashSet::new(),
+            allow_setuid: false,
+            resources: Arc::new(ResourceManager::default()),
+            requested: HashSet::new(),
+            conflict_policy: ConflictPolicy::default(),
+            removals: Vec::new(),
+            operation: "install",
+            os_snapshot_min_changes: None,
         }
     }
 
+    /// Strip `com.apple.quarantine` from the executables of installed
+    /// packages signed by a trusted key
+    #[must_use]
+    pub fn with_strip_quarantine(mut self, strip: bool) -> Self {
+        self.strip_quarantine = strip;
+        self
+    }
+
+    /// Name the packages to reinstall whose store copy a trusted key
+    /// signed; installs take this from the prepared packages instead
+    #[must_use]
+    pub fn with_signed(mut self, signed: impl IntoIterator<Item = String>) -> Self {
+        self.signed = signed.into_iter().collect();
+        self
+    }
+
+    /// Keep the setuid and setgid bits of installed packages' files
+    /// instead of dropping them
+    #[must_use]
+    pub fn with_allow_setuid(mut self, allow: bool) -> Self {
+        self.allow_setuid = allow;
+        self
+    }
+
+    /// Share the installation permits of `resources` for linking packages
+    /// into staging
+    #[must_use]
+    pub fn with_resources(mut self, resources: Arc<ResourceManager>) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    /// Name the packages the user asked for
+    ///
+    /// Installed packages keep being counted as recommended or as
+    /// dependencies across updates until the user asks for them by name.
+    #[must_use]
+    pub fn with_requested(mut self, requested: impl IntoIterator<Item = String>) -> Self {
+        self.requested = requested.into_iter().collect();
+        self
+    }
+
+    /// Settle packages placing different files at the same path by
+    /// `policy` instead of failing
+    #[must_use]
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Remove these installed packages in the state [`install`] creates,
+    /// so installing and removing commit together
+    ///
+    /// [`install`]: Self::install
+    #[must_use]
+    pub fn with_removals(mut self, removals: Vec<PackageId>) -> Self {
+        self.removals = removals;
+        self
+    }
+
+    /// Record the state [`install`](Self::install) creates as `operation`
+    /// instead of `install`
+    #[must_use]
+    pub fn with_operation(mut self, operation: &'static str) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    /// Take a local APFS snapshot before committing a transition that
+    /// changes at least `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.os_snapshot_min_changes = min_changes;
+        self
+    }
+
     /// Perform atomic installation
     ///
     /// # Errors
@@ -172,7 +440,7 @@ impl AtomicInstaller {
         prepared_packages: Option<&HashMap<PackageId, PreparedPackage>>,
     ) -> Result<InstallResult, Error> {
         // Setup state transition and staging directory
-        let mut transition = self.setup_state_transition("install", context).await?;
+        let mut transition = self.setup_state_transition(self.operation, context).await?;
 
         // Collect the current state's packages so we can carry forward untouched entries and
         // detect in-place upgrades cleanly.
@@ -201,30 +469,112 @@ impl AtomicInstaller {
         // register package references for unchanged packages.
         let exclude_names: HashSet<String> = resolved_packages
             .keys()
+            .chain(&self.removals)
             .map(|pkg| pkg.name.clone())
             .collect();
         package::carry_forward_packages(&mut transition, &parent_packages, &exclude_names);
 
         // Apply package changes to staging
         let mut result = InstallResult::new(transition.staging_id);
+        for pkg in &parent_packages {
+            if self.removals.iter().any(|removal| removal.name == pkg.name) {
+                result.add_removed(PackageId::new(pkg.name.clone(), pkg.version()));
+                package::remove_package_from_staging(&self.state_manager, &mut transition, pkg)
+                    .await?;
+            }
+        }
 
-        for (package_id, node) in resolved_packages {
+        // In name order, so the new state does not depend on map iteration
+        let mut resolved: Vec<_> = resolved_packages.iter().collect();
+        resolved.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        let mut jobs = Vec::with_capacity(resolved.len());
+        for (package_id, node) in resolved {
             let prepared_package = prepared_packages.and_then(|packages| packages.get(package_id));
-            package::install_package_to_staging(
-                &self.state_manager,
-                &mut transition,
-                package_id,
-                node,
-                prepared_package,
-                parent_lookup.get(&package_id.name),
-                &mut result,
-            )
-            .await?;
+            jobs.push(
+                package::prepare_package_staging(
+                    &self.state_manager,
+                    &mut transition,
+                    package_id,
+                    node,
+                    prepared_package,
+                    parent_lookup.get(&package_id.name),
+                    self.requested.contains(&package_id.name),
+                )
+                .await?,
+            );
+        }
+        let installed = package::carried_forward_files(
+            &self.state_manager,
+            &parent_packages,
+            &exclude_names,
+            &self.requested,
+        )
+        .await?;
+        let file_hashes = staging::link_packages(
+            &transition,
+            &jobs,
+            installed,
+            self.conflict_policy,
+            &self.resources,
+        )
+        .await?;
+        for (job, hashes) in jobs.into_iter().zip(file_hashes) {
+            package::finish_package_staging(&mut transition, job, hashes, &mut result);
+        }
+        special::apply_to_staged(&mut transition, self.allow_setuid, context).await?;
+
+        if self.strip_quarantine {
+            if let Some(prepared) = prepared_packages {
+                let signed = |package_id: &PackageId| {
+                    prepared
+                        .get(package_id)
+                        .is_some_and(|package| package.signing_key.is_some())
+                };
+                strip_quarantine(&transition, signed, context).await;
+            }
         }
 
         // Execute two-phase commit
-        self.execute_two_phase_commit(&transition, context).await?;
+        self.execute_two_phase_commit(&transition, result.total_changes(), context)
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Work out what [`install`](Self::install) would change, without
+    /// staging or committing anything
+    ///
+    /// The result is keyed to the active state, which the changes apply to.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state database cannot be read.
+    pub async fn plan_install(
+        &self,
+        resolved_packages: &HashMap<PackageId, ResolvedNode>,
+    ) -> Result<InstallResult, Error> {
+        let parent_lookup: HashMap<String, sps2_state::models::Package> = self
+            .state_manager
+            .get_installed_packages()
+            .await?
+            .into_iter()
+            .map(|pkg| (pkg.name.clone(), pkg))
+            .collect();
 
+        let mut result = InstallResult::new(self.state_manager.get_current_state_id().await?);
+        for package_id in resolved_packages.keys() {
+            match parent_lookup.get(&package_id.name) {
+                Some(existing) if existing.version() != package_id.version => {
+                    result.add_updated(package_id.clone());
+                }
+                _ => result.add_installed(package_id.clone()),
+            }
+        }
+        for removal in &self.removals {
+            if let Some(existing) = parent_lookup.get(&removal.name) {
+                result.add_removed(PackageId::new(existing.name.clone(), existing.version()));
+            }
+        }
         Ok(result)
     }
 
@@ -293,7 +643,8 @@ impl AtomicInstaller {
         package::carry_forward_packages(&mut transition, &parent_packages, &exclude_names);
 
         // Execute two-phase commit
-        self.execute_two_phase_commit(&transition, context).await?;
+        self.execute_two_phase_commit(&transition, result.total_changes(), context)
+            .await?;
 
         for pkg in &result.removed_packages {
             context.emit(AppEvent::Lifecycle(LifecycleEvent::uninstall_completed(
@@ -306,6 +657,108 @@ impl AtomicInstaller {
         Ok(result)
     }
 
+    /// Work out what [`uninstall`](Self::uninstall) would change, without
+    /// staging or committing anything
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state database cannot be read.
+    pub async fn plan_uninstall(
+        &self,
+        packages_to_remove: &[PackageId],
+    ) -> Result<InstallResult, Error> {
+        let mut result = InstallResult::new(self.state_manager.get_current_state_id().await?);
+        for pkg in self.state_manager.get_installed_packages().await? {
+            if packages_to_remove
+                .iter()
+                .any(|remove_pkg| remove_pkg.name == pkg.name)
+            {
+                result.add_removed(PackageId::new(pkg.name.clone(), pkg.version()));
+            }
+        }
+        Ok(result)
+    }
+
+    /// Perform atomic reinstallation
+    ///
+    /// Links the installed versions of `packages_to_reinstall` from the store
+    /// into a new state again. Config files changed in the live prefix keep
+    /// their changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if state transition fails, a package is missing from
+    /// the store, or filesystem operations fail.
+    pub async fn reinstall(
+        &mut self,
+        packages_to_reinstall: &[PackageId],
+        context: &crate::ReinstallContext,
+    ) -> Result<InstallResult, Error> {
+        let mut transition = self.setup_state_transition("reinstall", context).await?;
+
+        let parent_packages = if let Some(parent_id) = transition.parent_id {
+            self.state_manager
+                .get_installed_packages_in_state(&parent_id)
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        package::sync_slot_with_parent(
+            &self.state_manager,
+            &self.store,
+            &mut transition,
+            &parent_packages,
+        )
+        .await?;
+
+        let mut result = InstallResult::new(transition.staging_id);
+        for pkg in &parent_packages {
+            if !packages_to_reinstall
+                .iter()
+                .any(|reinstall_pkg| reinstall_pkg.name == pkg.name)
+            {
+                continue;
+            }
+
+            let config_files = package::modified_config_files(&self.state_manager, pkg).await?;
+            package::remove_package_from_staging(&self.state_manager, &mut transition, pkg).await?;
+            package::relink_package_to_staging(&self.store, &mut transition, pkg).await?;
+            special::restore_recorded(&self.state_manager, &transition, pkg).await?;
+            package::restore_config_files(
+                &transition,
+                self.state_manager.live_path(),
+                &config_files,
+            )
+            .await?;
+
+            context.emit_debug(format!(
+                "Reinstalled {} {}, keeping {} changed config file(s)",
+                pkg.name,
+                pkg.version,
+                config_files.len()
+            ));
+            result.add_installed(PackageId::new(pkg.name.clone(), pkg.version()));
+        }
+
+        let exclude_names: HashSet<String> = packages_to_reinstall
+            .iter()
+            .map(|pkg| pkg.name.clone())
+            .collect();
+        package::carry_forward_packages(&mut transition, &parent_packages, &exclude_names);
+
+        if self.strip_quarantine {
+            let signed = |package_id: &PackageId| self.signed.contains(&package_id.name);
+            strip_quarantine(&transition, signed, context).await;
+        }
+
+        // Execute two-phase commit
+        self.execute_two_phase_commit(&transition, result.total_changes(), context)
+            .await?;
+
+        Ok(result)
+    }
+
     // Removed remove_package_venv - Python packages are now handled like regular packages
 
     /// Rollback by moving active to an existing target state without creating a new state row
@@ -318,6 +771,7 @@ impl AtomicInstaller {
 
         let mut transition =
             StateTransition::new(&self.state_manager, "rollback".to_string()).await?;
+        transition.link_strategy = self.store.file_store().link_strategy();
         let target_packages = self
             .state_manager
             .get_installed_packages_in_state(&target_state_id)
@@ -335,6 +789,16 @@ impl AtomicInstaller {
         )
         .await?;
 
+        // The slot's links and profile snippets may have been made for other
+        // packages. Rollback has nowhere to report a failure to, and a
+        // missing link or snippet only costs docs, completions or variables.
+        let slot = transition.slot_path.clone();
+        let _ = tokio::task::spawn_blocking(move || integration::link_trees(&slot)).await;
+        let _ = self
+            .state_profile(target_packages.iter().map(|p| p.hash.as_str()))
+            .await
+            .write(&transition.slot_path);
+
         let journal = sps2_types::state::TransactionJournal {
             new_state_id: target_state_id,
             parent_state_id: current_state_id,
@@ -361,6 +825,42 @@ impl AtomicInstaller {
     }
 }
 
+/// Let staged executables of signed packages run without a Gatekeeper prompt
+///
+/// Unsigned and local packages keep the attribute. Failing to remove it
+/// only means a prompt on first run, so it is reported, not fatal.
+async fn strip_quarantine(
+    transition: &StateTransition,
+    signed: impl Fn(&PackageId) -> bool,
+    context: &impl EventEmitter,
+) {
+    for (package_id, files) in &transition.pending_file_hashes {
+        if !signed(package_id) {
+            continue;
+        }
+        let executables: Vec<_> = files
+            .iter()
+            .filter(|file| {
+                !file.is_directory
+                    && !file.is_symlink
+                    && file.mode.is_some_and(|mode| mode & 0o111 != 0)
+            })
+            .map(|file| transition.slot_path.join(&file.relative_path))
+            .collect();
+        match sps2_platform::filesystem_helpers::strip_quarantine(&executables).await {
+            Ok(0) => {}
+            Ok(count) => context.emit_debug(format!(
+                "Removed quarantine from {count} executables of {}",
+                package_id.name
+            )),
+            Err(e) => context.emit_warning(format!(
+                "Could not remove quarantine from {}: {e}",
+                package_id.name
+            )),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -407,6 +907,13 @@ mod tests {
                 afs::create_dir_all(parent).await.unwrap();
             }
             afs::write(&p, content.as_bytes()).await.unwrap();
+            #[cfg(unix)]
+            if rel.starts_with("bin/") {
+                use std::os::unix::fs::PermissionsExt;
+                afs::set_permissions(&p, std::fs::Permissions::from_mode(0o755))
+                    .await
+                    .unwrap();
+            }
         }
         // create .sp
         let sp = td.path().join("pkg.sp");
@@ -511,6 +1018,7 @@ mod tests {
                 store_path: path_a.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let ctx = crate::InstallContext {
@@ -518,6 +1026,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let _ = installer
@@ -549,6 +1058,7 @@ mod tests {
                 store_path: path_b.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let ctx_b = crate::InstallContext {
@@ -556,6 +1066,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let _ = installer
@@ -626,6 +1137,7 @@ mod tests {
                 store_path: path_v1.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let ctx = crate::InstallContext {
@@ -633,6 +1145,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let _ = ai.install(&ctx, &resolved, Some(&prepared)).await.unwrap();
@@ -675,6 +1188,7 @@ mod tests {
                 store_path: path_v2.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let update_ctx = crate::InstallContext {
@@ -682,6 +1196,7 @@ mod tests {
             local_files: vec![],
             force: true,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let update_result = ai
@@ -749,6 +1264,7 @@ mod tests {
                 store_path: store_path.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let ctx = crate::InstallContext {
@@ -756,6 +1272,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let _res = ai.install(&ctx, &resolved, Some(&prepared)).await.unwrap();
@@ -771,6 +1288,8 @@ mod tests {
             packages: vec!["A".to_string()],
             autoremove: false,
             force: true,
+            cascade: false,
+            prune: Vec::new(),
             event_sender: None,
         };
         let _u = ai
@@ -790,6 +1309,268 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn reinstall_restores_files_but_keeps_changed_config() {
+        let (_td, state, store) = mk_env().await;
+        let (hash, store_path, size, _file_hashes) = make_sp_and_add_to_store(
+            &store,
+            "A",
+            "1.0.0",
+            &[("bin/x", "binary"), ("etc/a.conf", "port = 1")],
+        )
+        .await;
+
+        let mut ai = AtomicInstaller::new(state.clone(), store.clone());
+        let pid = PackageId::new("A".to_string(), Version::parse("1.0.0").unwrap());
+        let resolved = HashMap::from([(
+            pid.clone(),
+            ResolvedNode::local(
+                "A".to_string(),
+                pid.version.clone(),
+                store_path.clone(),
+                vec![],
+            ),
+        )]);
+        let prepared = HashMap::from([(
+            pid.clone(),
+            crate::PreparedPackage {
+                hash,
+                size,
+                store_path,
+                is_local: true,
+                package_hash: None,
+                signing_key: None,
+            },
+        )]);
+        ai.install(&crate::InstallContext::new(), &resolved, Some(&prepared))
+            .await
+            .unwrap();
+
+        let live = state.live_path().join("opt/pm/live");
+        afs::remove_file(live.join("bin/x")).await.unwrap();
+        // Edit the config file the way an editor would, replacing it
+        afs::remove_file(live.join("etc/a.conf")).await.unwrap();
+        afs::write(live.join("etc/a.conf"), "port = 2")
+            .await
+            .unwrap();
+
+        let before = state.get_current_state_id().await.unwrap();
+        let result = ai
+            .reinstall(std::slice::from_ref(&pid), &crate::ReinstallContext::new())
+            .await
+            .unwrap();
+        assert_ne!(result.state_id, before);
+        assert_eq!(result.installed_packages, [pid]);
+
+        assert_eq!(
+            afs::read_to_string(live.join("bin/x")).await.unwrap(),
+            "binary"
+        );
+        assert_eq!(
+            afs::read_to_string(live.join("etc/a.conf")).await.unwrap(),
+            "port = 2"
+        );
+        let stored = sps2_hash::Hash::from_data(b"port = 1");
+        assert_eq!(
+            afs::read_to_string(store.file_store().file_path(&stored))
+                .await
+                .unwrap(),
+            "port = 1"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn setuid_bits_are_installed_only_when_allowed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        for allow in [false, true] {
+            let (_td, state, store) = mk_env().await;
+            let td = TempDir::new().unwrap();
+            let src = td.path().join("src");
+            let helper = src.join("opt/pm/live/libexec/helper");
+            afs::create_dir_all(helper.parent().unwrap()).await.unwrap();
+            afs::write(&helper, "helper").await.unwrap();
+            afs::set_permissions(&helper, std::fs::Permissions::from_mode(0o4755))
+                .await
+                .unwrap();
+            let m = Manifest::new(
+                "A".to_string(),
+                &Version::parse("1.0.0").unwrap(),
+                1,
+                &Arch::Arm64,
+            );
+            sps2_store::manifest_io::write_manifest(&src.join("manifest.toml"), &m)
+                .await
+                .unwrap();
+            let sp = td.path().join("pkg.sp");
+            create_package(&src, &sp).await.unwrap();
+            let stored = store.add_package(&sp).await.unwrap();
+            let hash = stored.hash().unwrap();
+
+            let pid = PackageId::new("A".to_string(), Version::parse("1.0.0").unwrap());
+            let store_path = store.package_path(&hash);
+            let resolved = HashMap::from([(
+                pid.clone(),
+                ResolvedNode::local(
+                    "A".to_string(),
+                    pid.version.clone(),
+                    store_path.clone(),
+                    vec![],
+                ),
+            )]);
+            let prepared = HashMap::from([(
+                pid.clone(),
+                crate::PreparedPackage {
+                    hash,
+                    size: 1,
+                    store_path,
+                    is_local: true,
+                    package_hash: None,
+                    signing_key: None,
+                },
+            )]);
+            let mut ai =
+                AtomicInstaller::new(state.clone(), store.clone()).with_allow_setuid(allow);
+            ai.install(&crate::InstallContext::new(), &resolved, Some(&prepared))
+                .await
+                .unwrap();
+
+            let live = state.live_path().join("opt/pm/live/libexec/helper");
+            let mode = || async { afs::metadata(&live).await.unwrap().permissions().mode() };
+            assert_eq!(mode().await & 0o4000 != 0, allow);
+            let object = store
+                .file_store()
+                .file_path(&sps2_hash::Hash::from_data(b"helper"));
+            assert_eq!(
+                afs::metadata(object).await.unwrap().permissions().mode() & 0o4000,
+                0
+            );
+
+            // Linking the package again keeps what the state recorded
+            ai.reinstall(std::slice::from_ref(&pid), &crate::ReinstallContext::new())
+                .await
+                .unwrap();
+            assert_eq!(mode().await & 0o4000 != 0, allow);
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn quarantine_xattr(path: &std::path::Path, set: bool) -> bool {
+        use std::ffi::CString;
+        let c_path = CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+        let name = CString::new(sps2_platform::filesystem_helpers::QUARANTINE_XATTR).unwrap();
+        let value = b"0081;00000000;Safari;";
+        // SAFETY: both strings are NUL-terminated and the value outlives the call
+        unsafe {
+            if set {
+                libc::setxattr(
+                    c_path.as_ptr(),
+                    name.as_ptr(),
+                    value.as_ptr().cast(),
+                    value.len(),
+                    0,
+                    libc::XATTR_NOFOLLOW,
+                ) == 0
+            } else {
+                libc::getxattr(
+                    c_path.as_ptr(),
+                    name.as_ptr(),
+                    std::ptr::null_mut(),
+                    0,
+                    0,
+                    libc::XATTR_NOFOLLOW,
+                ) >= 0
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn reinstall_strips_quarantine_from_signed_packages() {
+        let (td, state, store) = mk_env().await;
+        let keys_dir = td.path().join("keys");
+        let trust = sps2_net::trust_fingerprint(&keys_dir).unwrap();
+
+        let mut pids = Vec::new();
+        let mut resolved = HashMap::new();
+        let mut prepared = HashMap::new();
+        for (name, key_id) in [("A", Some("key")), ("B", None)] {
+            let (hash, store_path, size, _file_hashes) =
+                make_sp_and_add_to_store(&store, name, "1.0.0", &[(&format!("bin/{name}"), name)])
+                    .await;
+            state
+                .record_validation_stamp(&sps2_state::ValidationStamp {
+                    store_hash: hash.to_hex(),
+                    package_hash: None,
+                    key_id: key_id.map(str::to_string),
+                    trust_fingerprint: trust.clone(),
+                    validated_at: 0,
+                })
+                .await
+                .unwrap();
+            let pid = PackageId::new(name.to_string(), Version::parse("1.0.0").unwrap());
+            resolved.insert(
+                pid.clone(),
+                ResolvedNode::local(
+                    name.to_string(),
+                    pid.version.clone(),
+                    store_path.clone(),
+                    vec![],
+                ),
+            );
+            prepared.insert(
+                pid.clone(),
+                crate::PreparedPackage {
+                    hash,
+                    size,
+                    store_path,
+                    is_local: false,
+                    package_hash: None,
+                    signing_key: key_id.map(str::to_string),
+                },
+            );
+            pids.push(pid);
+        }
+        AtomicInstaller::new(state.clone(), store.clone())
+            .with_strip_quarantine(true)
+            .install(&crate::InstallContext::new(), &resolved, Some(&prepared))
+            .await
+            .unwrap();
+
+        let mut operation = crate::ReinstallOperation::new(state.clone(), store.clone())
+            .with_download_config(sps2_net::PackageDownloadConfig {
+                keys_dir,
+                ..sps2_net::PackageDownloadConfig::default()
+            });
+        assert_eq!(
+            operation.signed_packages(&pids).await.unwrap(),
+            HashSet::from(["A".to_string()])
+        );
+
+        // Mark the store objects, which reinstalling links or clones again
+        #[cfg(target_os = "macos")]
+        for name in ["A", "B"] {
+            let object = store
+                .file_store()
+                .file_path(&sps2_hash::Hash::from_data(name.as_bytes()));
+            assert!(quarantine_xattr(&object, true));
+        }
+
+        let context = crate::ReinstallContext::new()
+            .add_package("A".to_string())
+            .add_package("B".to_string());
+        let result = operation.execute(context).await.unwrap();
+        assert_eq!(result.installed_packages.len(), 2);
+
+        let live = state.live_path().join("opt/pm/live/bin");
+        assert_eq!(afs::read_to_string(live.join("A")).await.unwrap(), "A");
+        #[cfg(target_os = "macos")]
+        {
+            assert!(!quarantine_xattr(&live.join("A"), false));
+            assert!(quarantine_xattr(&live.join("B"), false));
+        }
+    }
+
     #[tokio::test]
     async fn shared_file_uninstall_decrements_but_not_zero() {
         let (_td, state, store) = mk_env().await;
@@ -854,6 +1635,7 @@ mod tests {
                 store_path: path_a.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         prepared.insert(
@@ -864,6 +1646,7 @@ mod tests {
                 store_path: path_b.clone(),
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let ctx = crate::InstallContext {
@@ -871,6 +1654,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         let _res = ai.install(&ctx, &resolved, Some(&prepared)).await.unwrap();
@@ -880,6 +1664,8 @@ mod tests {
             packages: vec!["A".to_string()],
             autoremove: false,
             force: true,
+            cascade: false,
+            prune: Vec::new(),
             event_sender: None,
         };
         let _u = ai
@@ -0,0 +1,217 @@
+//! Links that put man pages and shell completions where tools look for them
+//!
+//! Packages do not agree on where these files go: some install man pages
+//! to `man/` instead of `share/man/`, or bash completions to the eagerly
+//! sourced `etc/bash_completion.d/` instead of the directory
+//! bash-completion loads on demand. Before each commit the staging slot
+//! gets relative symlinks from the well-known trees to such files, so `man`
+//! finds pages through `PATH` and each shell finds completions in one
+//! directory. Links whose file went away with its package are removed
+//! again, and a file a package installs at the well-known path wins over a
+//! link.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Trees packages install into, and the well-known tree each is linked into
+pub const LINKED_TREES: [(&str, &str); 3] = [
+    ("man", "share/man"),
+    ("etc/bash_completion.d", "share/bash-completion/completions"),
+    ("share/zsh/vendor-completions", "share/zsh/site-functions"),
+];
+
+/// Changes to the links of one well-known tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeLinks {
+    pub source: &'static str,
+    pub target: &'static str,
+    /// Files newly linked, relative to the tree
+    pub linked: Vec<PathBuf>,
+    /// Links removed because their file is gone
+    pub removed: usize,
+}
+
+/// Bring the links of every tree in `slot` up to date
+///
+/// # Errors
+///
+/// Returns an error if a tree cannot be read or a link cannot be created
+/// or removed.
+pub fn link_trees(slot: &Path) -> io::Result<Vec<TreeLinks>> {
+    LINKED_TREES
+        .iter()
+        .map(|(source, target)| link_tree(slot, source, target))
+        .collect()
+}
+
+/// Why `man` would not find the pages in `man_dir` with `MANPATH` set to
+/// `manpath`, if it would not
+///
+/// Without `MANPATH`, or with an empty entry in it, `man` searches next to
+/// every `bin` directory on `PATH` and needs no hint.
+#[must_use]
+pub fn manpath_hint(manpath: Option<&str>, man_dir: &Path) -> Option<String> {
+    let manpath = manpath.filter(|manpath| !manpath.is_empty())?;
+    let searched = manpath
+        .split(':')
+        .any(|entry| entry.is_empty() || Path::new(entry) == man_dir);
+    (!searched).then(|| {
+        format!(
+            "MANPATH does not include {}, so man pages of installed packages are not found; \
+             add it, or end MANPATH with ':' to keep the default search path",
+            man_dir.display()
+        )
+    })
+}
+
+fn link_tree(slot: &Path, source: &'static str, target: &'static str) -> io::Result<TreeLinks> {
+    let mut links = TreeLinks {
+        source,
+        target,
+        linked: Vec::new(),
+        removed: 0,
+    };
+    let source_dir = slot.join(source);
+    let target_dir = slot.join(target);
+    // A package that links either tree to the other already did the job,
+    // and writing through such a link would put files in the wrong tree
+    if is_symlink(&source_dir) || is_symlink(&target_dir) {
+        return Ok(links);
+    }
+
+    for rel in files(&target_dir)? {
+        let link = target_dir.join(&rel);
+        let Ok(dest) = std::fs::read_link(&link) else {
+            continue;
+        };
+        let Some(parent) = Path::new(target).join(&rel).parent().map(Path::to_path_buf) else {
+            continue;
+        };
+        let ours = resolve(&parent, &dest).is_some_and(|path| path.starts_with(source));
+        if ours && !link.exists() {
+            std::fs::remove_file(&link)?;
+            links.removed += 1;
+        }
+    }
+
+    for rel in files(&source_dir)? {
+        let link = target_dir.join(&rel);
+        if std::fs::symlink_metadata(&link).is_ok() {
+            continue;
+        }
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let depth = Path::new(target).join(&rel).components().count() - 1;
+        let mut dest: PathBuf = std::iter::repeat_n("..", depth).collect();
+        dest.push(source);
+        dest.push(&rel);
+        std::os::unix::fs::symlink(&dest, &link)?;
+        links.linked.push(rel);
+    }
+    Ok(links)
+}
+
+/// Paths of everything but directories below `dir`, relative to it; links
+/// are not followed
+fn files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(rel) = pending.pop() {
+        let entries = match std::fs::read_dir(dir.join(&rel)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = rel.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else {
+                found.push(path);
+            }
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+/// `dest` of a link in `parent`, relative to the slot; `None` if it is
+/// absolute or leaves the slot
+fn resolve(parent: &Path, dest: &Path) -> Option<PathBuf> {
+    let mut path = parent.to_path_buf();
+    for component in dest.components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::ParentDir => {
+                if !path.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+fn is_symlink(path: &Path) -> bool {
+    std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_symlink())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn files_are_linked_into_well_known_trees_until_they_go() {
+        let slot = TempDir::new().unwrap();
+        let root = slot.path();
+        fs::create_dir_all(root.join("man/man1")).unwrap();
+        fs::write(root.join("man/man1/jq.1"), "jq").unwrap();
+        fs::write(root.join("man/man1/yq.1"), "yq").unwrap();
+        fs::create_dir_all(root.join("share/man/man1")).unwrap();
+        fs::write(root.join("share/man/man1/yq.1"), "packaged").unwrap();
+
+        let links = link_trees(root).unwrap();
+        assert_eq!(links[0].linked, vec![PathBuf::from("man1/jq.1")]);
+        assert_eq!(
+            fs::read_to_string(root.join("share/man/man1/jq.1")).unwrap(),
+            "jq"
+        );
+        assert_eq!(
+            fs::read_link(root.join("share/man/man1/jq.1")).unwrap(),
+            Path::new("../../../man/man1/jq.1")
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("share/man/man1/yq.1")).unwrap(),
+            "packaged"
+        );
+
+        // Nothing changes on a second run
+        assert!(link_trees(root)
+            .unwrap()
+            .iter()
+            .all(|tree| tree.linked.is_empty() && tree.removed == 0));
+
+        fs::remove_file(root.join("man/man1/jq.1")).unwrap();
+        assert_eq!(link_trees(root).unwrap()[0].removed, 1);
+        assert!(fs::symlink_metadata(root.join("share/man/man1/jq.1")).is_err());
+    }
+
+    #[test]
+    fn manpath_needs_the_live_man_directory_or_an_empty_entry() {
+        let man = Path::new("/opt/pm/live/share/man");
+        assert_eq!(manpath_hint(None, man), None);
+        assert_eq!(manpath_hint(Some(""), man), None);
+        assert_eq!(manpath_hint(Some("/usr/share/man:"), man), None);
+        assert_eq!(
+            manpath_hint(Some("/usr/share/man:/opt/pm/live/share/man"), man),
+            None
+        );
+        assert!(manpath_hint(Some("/usr/share/man"), man).is_some());
+    }
+}
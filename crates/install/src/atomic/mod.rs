@@ -2,13 +2,18 @@
 //!
 //! This module provides atomic installation capabilities with:
 //! - APFS-optimized file operations for instant, space-efficient copies
-//! - Hard link creation for efficient package linking
+//! - Hard link creation for efficient package linking, several packages at once
 //! - State transitions with rollback support
 //! - Platform-specific filesystem optimizations
 
+pub mod environment;
 pub mod fs;
 pub mod installer;
+pub mod integration;
 pub mod package;
+pub mod profile;
+mod special;
+pub mod staging;
 pub mod transition;
 
 // Re-export main public API
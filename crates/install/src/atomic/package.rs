@@ -3,10 +3,13 @@
 //! This module handles:
 //! - Carrying forward packages from parent states
 //! - Syncing staging slots with parent state
-//! - Installing packages to staging
+//! - Preparing and registering packages staged in parallel
 //! - Removing packages from staging
+//! - Keeping changed config files across reinstalls
 
 use crate::atomic::fs;
+use crate::atomic::special;
+use crate::atomic::staging::PackageFiles;
 use crate::atomic::transition::StateTransition;
 use crate::{InstallResult, PreparedPackage};
 use sps2_errors::{Error, InstallError};
@@ -41,11 +44,43 @@ pub(super) fn carry_forward_packages(
             package_id: PackageId::new(pkg.name.clone(), pkg.version()),
             hash: pkg.hash.clone(),
             size: pkg.size,
+            recommended: pkg.recommended,
+            dependency: pkg.dependency,
+            dependencies: None,
         };
         transition.package_refs.push(package_ref);
     }
 }
 
+/// Read the files of the packages staying installed unchanged, excluding
+/// specified packages, for conflict detection while staging
+///
+/// # Errors
+///
+/// Returns an error if the file entries cannot be read from the database.
+pub(super) async fn carried_forward_files(
+    state_manager: &StateManager,
+    parent_packages: &[sps2_state::models::Package],
+    exclude_names: &HashSet<String>,
+    requested: &HashSet<String>,
+) -> Result<Vec<PackageFiles>, Error> {
+    let mut tx = state_manager.begin_transaction().await?;
+    let mut packages = Vec::new();
+    for pkg in parent_packages {
+        if exclude_names.contains(&pkg.name) {
+            continue;
+        }
+        let entries = file_queries_runtime::get_package_file_entries(&mut tx, pkg.id).await?;
+        packages.push(PackageFiles::installed(
+            pkg.name.clone(),
+            requested.contains(&pkg.name),
+            entries,
+        ));
+    }
+    tx.commit().await?;
+    Ok(packages)
+}
+
 /// Sync staging slot to a specific target state
 ///
 /// This ensures the staging slot mirrors an arbitrary target state by:
@@ -115,6 +150,7 @@ pub(super) async fn sync_slot_to_state(
         let store_path = store.package_path(&hash);
         let package_id = PackageId::new(pkg.name.clone(), pkg.version());
         link_package_to_staging(transition, &store_path, &package_id, false).await?;
+        special::restore_recorded(state_manager, transition, pkg).await?;
     }
 
     state_manager
@@ -156,28 +192,53 @@ pub(super) async fn sync_slot_with_parent(
     .await
 }
 
-/// Install a single package to staging directory
+/// A resolved package ready to be linked into staging
+#[allow(clippy::struct_excessive_bools)] // independent facts about the package
+pub(super) struct StagingJob {
+    pub package_id: PackageId,
+    pub package: sps2_store::StoredPackage,
+    store_hash_hex: String,
+    size: i64,
+    /// Whether the package replaces another version of itself
+    updated: bool,
+    /// Whether the package is installed only because another recommends it
+    recommended: bool,
+    /// Whether the package is installed only because another depends on it
+    dependency: bool,
+    /// Whether the user named the package
+    pub requested: bool,
+    /// Whether some version of the package was installed before
+    pub installed: bool,
+}
+
+/// Prepare a single package for staging
 ///
 /// This function:
 /// - Validates prepared package data
 /// - Handles package upgrades by removing old versions
 /// - Ensures store references exist
-/// - Links package files to staging
-/// - Registers package references
+///
+/// A package installed before stays recommended or a dependency as it was,
+/// unless `requested` by name, which makes it neither.
+///
+/// The returned job is linked by [`staging::link_packages`] and recorded
+/// with [`finish_package_staging`].
+///
+/// [`staging::link_packages`]: crate::atomic::staging::link_packages
 ///
 /// # Errors
 ///
 /// Returns an error if package data is missing, filesystem operations fail,
 /// or state manager operations fail.
-pub(super) async fn install_package_to_staging(
+pub(super) async fn prepare_package_staging(
     state_manager: &StateManager,
     transition: &mut StateTransition,
     package_id: &PackageId,
     node: &ResolvedNode,
     prepared_package: Option<&PreparedPackage>,
     prior_package: Option<&sps2_state::models::Package>,
-    result: &mut InstallResult,
-) -> Result<(), Error> {
+    requested: bool,
+) -> Result<StagingJob, Error> {
     // Install the package files (both Download and Local actions are handled identically)
     let action_name = match &node.action {
         sps2_resolver::NodeAction::Download => "downloaded",
@@ -194,7 +255,6 @@ pub(super) async fn install_package_to_staging(
     })?;
 
     let hash = &prepared.hash;
-    let store_path = &prepared.store_path;
     let size = prepared.size;
     let store_hash_hex = hash.to_hex();
     let package_hash_hex = prepared.package_hash.as_ref().map(sps2_hash::Hash::to_hex);
@@ -211,7 +271,7 @@ pub(super) async fn install_package_to_staging(
     }
 
     // Load package from the prepared store path
-    let _stored_package = sps2_store::StoredPackage::load(store_path).await?;
+    let package = sps2_store::StoredPackage::load(&prepared.store_path).await?;
 
     // Ensure store_refs entry exists before adding to package_map
     let size_i64 = i64::try_from(size).map_err(|_| {
@@ -236,30 +296,172 @@ pub(super) async fn install_package_to_staging(
         )
         .await?;
 
-    // Link package files to staging
-    let (_, file_hashes) =
-        link_package_to_staging(transition, store_path, package_id, true).await?;
+    Ok(StagingJob {
+        package_id: package_id.clone(),
+        package,
+        store_hash_hex,
+        size: size_i64,
+        updated: was_present && version_changed,
+        recommended: !requested
+            && prior_package.map_or(node.recommended, |existing| existing.recommended),
+        dependency: !requested
+            && prior_package.map_or(!node.recommended, |existing| existing.dependency),
+        requested,
+        installed: was_present,
+    })
+}
+
+/// Register a package linked into staging, with the file hashes linking
+/// returned
+pub(super) fn finish_package_staging(
+    transition: &mut StateTransition,
+    job: StagingJob,
+    file_hashes: Vec<sps2_hash::FileHashResult>,
+    result: &mut InstallResult,
+) {
+    transition
+        .pending_file_hashes
+        .push((job.package_id.clone(), file_hashes));
 
-    // Store file hashes if we got them
+    transition.package_refs.push(PackageRef {
+        state_id: transition.staging_id,
+        package_id: job.package_id.clone(),
+        hash: job.store_hash_hex,
+        size: job.size,
+        recommended: job.recommended,
+        dependency: job.dependency,
+        dependencies: Some(job.package.manifest().dependencies.runtime.clone()),
+    });
+
+    if job.updated {
+        result.add_updated(job.package_id);
+    } else {
+        result.add_installed(job.package_id);
+    }
+}
+
+/// Link an installed package from the store to staging again, unchanged
+///
+/// # Errors
+///
+/// Returns an error if the package hash is invalid or the package cannot be
+/// loaded from the store or linked.
+pub(super) async fn relink_package_to_staging(
+    store: &PackageStore,
+    transition: &mut StateTransition,
+    package: &sps2_state::models::Package,
+) -> Result<(), Error> {
+    let hash = Hash::from_hex(&package.hash).map_err(|e| {
+        Error::from(InstallError::AtomicOperationFailed {
+            message: format!(
+                "invalid package hash for {}-{} during reinstall: {e}",
+                package.name, package.version
+            ),
+        })
+    })?;
+    let package_id = PackageId::new(package.name.clone(), package.version());
+    let (_, file_hashes) =
+        link_package_to_staging(transition, &store.package_path(&hash), &package_id, true).await?;
     if let Some(hashes) = file_hashes {
         transition
             .pending_file_hashes
             .push((package_id.clone(), hashes));
     }
 
-    // Add the package reference
-    let package_ref = PackageRef {
+    transition.package_refs.push(PackageRef {
         state_id: transition.staging_id,
-        package_id: package_id.clone(),
-        hash: store_hash_hex.clone(),
-        size: size_i64,
-    };
-    transition.package_refs.push(package_ref);
+        package_id,
+        hash: package.hash.clone(),
+        size: package.size,
+        recommended: package.recommended,
+        dependency: package.dependency,
+        dependencies: None,
+    });
+    Ok(())
+}
 
-    if was_present && version_changed {
-        result.add_updated(package_id.clone());
-    } else {
-        result.add_installed(package_id.clone());
+/// Whether `path`, a package file entry, is a config file
+///
+/// Config files are the files a package installs under `etc/` of the live
+/// prefix. Users are expected to edit them, so reinstalling a package keeps
+/// their changes.
+fn is_config_file(path: &str) -> bool {
+    path.strip_prefix("opt/pm/live/")
+        .unwrap_or(path)
+        .starts_with("etc/")
+}
+
+/// Config files of `package` whose live contents differ from the package's
+///
+/// # Errors
+///
+/// Returns an error if the package's files cannot be queried or a live
+/// config file cannot be read.
+pub(super) async fn modified_config_files(
+    state_manager: &StateManager,
+    package: &sps2_state::models::Package,
+) -> Result<Vec<String>, Error> {
+    let state_id =
+        Uuid::parse_str(&package.state_id).map_err(|e| InstallError::AtomicOperationFailed {
+            message: format!(
+                "failed to parse associated state ID for package {}: {e}",
+                package.name
+            ),
+        })?;
+
+    let mut tx = state_manager.begin_transaction().await?;
+    let entries = file_queries_runtime::get_package_file_entries_by_name(
+        &mut tx,
+        &state_id,
+        &package.name,
+        &package.version,
+    )
+    .await?;
+    tx.commit().await?;
+
+    let mut modified = Vec::new();
+    for entry in entries {
+        if !is_config_file(&entry.relative_path) {
+            continue;
+        }
+        let live_file = state_manager.live_path().join(&entry.relative_path);
+        let is_file = tokio::fs::symlink_metadata(&live_file)
+            .await
+            .is_ok_and(|metadata| metadata.is_file());
+        if is_file && Hash::hash_file(&live_file).await?.to_hex() != entry.file_hash {
+            modified.push(entry.relative_path);
+        }
+    }
+    Ok(modified)
+}
+
+/// Copy the live versions of `config_files` over the staged ones
+///
+/// # Errors
+///
+/// Returns an error if a config file cannot be copied.
+pub(super) async fn restore_config_files(
+    transition: &StateTransition,
+    live_path: &Path,
+    config_files: &[String],
+) -> Result<(), Error> {
+    for config_file in config_files {
+        let staged = transition.slot_path.join(config_file);
+        // The staged file may share its inode with the store object, so it is
+        // replaced rather than written through
+        if tokio::fs::symlink_metadata(&staged).await.is_ok() {
+            tokio::fs::remove_file(&staged).await?;
+        }
+        if let Some(parent) = staged.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::copy(live_path.join(config_file), &staged)
+            .await
+            .map_err(|e| InstallError::FilesystemError {
+                operation: "restore_config_file".to_string(),
+                path: staged.display().to_string(),
+                message: e.to_string(),
+            })?;
     }
     Ok(())
 }
@@ -366,6 +568,8 @@ mod tests {
                 size: 1000,
                 installed_at: chrono::Utc::now().timestamp(),
                 venv_path: None,
+                recommended: false,
+                dependency: false,
             },
             sps2_state::models::Package {
                 id: 0,
@@ -376,6 +580,8 @@ mod tests {
                 size: 2000,
                 installed_at: chrono::Utc::now().timestamp(),
                 venv_path: None,
+                recommended: false,
+                dependency: false,
             },
         ];
 
@@ -406,6 +612,8 @@ mod tests {
                 size: 1000,
                 installed_at: chrono::Utc::now().timestamp(),
                 venv_path: None,
+                recommended: false,
+                dependency: false,
             },
             sps2_state::models::Package {
                 id: 0,
@@ -416,6 +624,8 @@ mod tests {
                 size: 2000,
                 installed_at: chrono::Utc::now().timestamp(),
                 venv_path: None,
+                recommended: false,
+                dependency: false,
             },
         ];
 
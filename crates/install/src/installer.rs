@@ -1,10 +1,12 @@
 //! Main installer implementation
 
 use crate::{
-    InstallConfig, InstallContext, InstallOperation, InstallResult, StateInfo, UninstallContext,
+    ApplyContext, ApplyOperation, InstallConfig, InstallContext, InstallOperation, InstallPlan,
+    InstallResult, ReinstallContext, ReinstallOperation, StateInfo, UninstallContext,
     UninstallOperation, UpdateContext, UpdateOperation,
 };
 use sps2_errors::{Error, InstallError};
+use sps2_net::NetClient;
 // EventSender not used directly in this module but imported for potential future use
 use sps2_resolver::Resolver;
 use sps2_state::StateManager;
@@ -21,6 +23,8 @@ pub struct Installer {
     state_manager: StateManager,
     /// Package store
     store: PackageStore,
+    /// Shared network client for package downloads
+    net_client: Option<NetClient>,
 }
 
 impl std::fmt::Debug for Installer {
@@ -45,9 +49,17 @@ impl Installer {
             resolver,
             state_manager,
             store,
+            net_client: None,
         }
     }
 
+    /// Download packages through a shared network client
+    #[must_use]
+    pub fn with_net_client(mut self, client: NetClient) -> Self {
+        self.net_client = Some(client);
+        self
+    }
+
     /// Install packages
     ///
     /// # Errors
@@ -62,7 +74,15 @@ impl Installer {
             self.resolver.clone(),
             self.state_manager.clone(),
             self.store.clone(),
-        )?;
+        )?
+        .with_offline(self.config.offline)
+        .with_recommends(self.config.recommends)
+        .with_conflict_policy(self.config.conflict_policy)
+        .with_requested(context.packages.iter().map(|spec| spec.name.clone()))
+        .with_security_policy(self.config.security)
+        .with_net_client(self.net_client.clone())
+        .with_download_config(self.config.download.clone())
+        .with_os_snapshots(self.config.os_snapshot_min_changes);
 
         // Execute installation
         let result = operation.execute(context).await?;
@@ -83,7 +103,8 @@ impl Installer {
         Self::validate_uninstall_context(&context)?;
 
         // Create uninstall operation
-        let mut operation = UninstallOperation::new(self.state_manager.clone(), self.store.clone());
+        let mut operation = UninstallOperation::new(self.state_manager.clone(), self.store.clone())
+            .with_os_snapshots(self.config.os_snapshot_min_changes);
 
         // Execute uninstallation
         let result = operation.execute(context).await?;
@@ -94,6 +115,29 @@ impl Installer {
         Ok(result)
     }
 
+    /// Reinstall packages from the store at their installed versions
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no packages are given, a package is not installed,
+    /// or reinstallation fails.
+    pub async fn reinstall(&mut self, context: ReinstallContext) -> Result<InstallResult, Error> {
+        if context.packages.is_empty() {
+            return Err(InstallError::NoPackagesSpecified.into());
+        }
+
+        let mut operation = ReinstallOperation::new(self.state_manager.clone(), self.store.clone())
+            .with_os_snapshots(self.config.os_snapshot_min_changes)
+            .with_security_policy(self.config.security)
+            .with_download_config(self.config.download.clone());
+        let result = operation.execute(context).await?;
+
+        // Trigger garbage collection
+        self.cleanup_old_states().await?;
+
+        Ok(result)
+    }
+
     /// Update packages
     ///
     /// # Errors
@@ -108,7 +152,14 @@ impl Installer {
             self.resolver.clone(),
             self.state_manager.clone(),
             self.store.clone(),
-        )?;
+        )?
+        .with_offline(self.config.offline)
+        .with_recommends(self.config.recommends)
+        .with_conflict_policy(self.config.conflict_policy)
+        .with_security_policy(self.config.security)
+        .with_net_client(self.net_client.clone())
+        .with_download_config(self.config.download.clone())
+        .with_os_snapshots(self.config.os_snapshot_min_changes);
 
         // Execute update
         let result = operation.execute(context).await?;
@@ -119,6 +170,106 @@ impl Installer {
         Ok(result)
     }
 
+    /// Install, remove and upgrade packages as one change, committed as a
+    /// single new state
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context changes nothing, a package to remove
+    /// or upgrade is not installed, removals would break dependencies and
+    /// are not forced, or resolution, download or installation fails.
+    pub async fn apply(&mut self, context: ApplyContext) -> Result<InstallResult, Error> {
+        Self::validate_apply_context(&context)?;
+
+        let result = self.apply_operation()?.execute(context).await?;
+
+        // Trigger garbage collection
+        self.cleanup_old_states().await?;
+
+        Ok(result)
+    }
+
+    /// Work out what [`apply`](Self::apply) would do, without touching the
+    /// store or the states
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors [`apply`](Self::apply) would fail with before
+    /// changing anything.
+    pub async fn plan_apply(&self, context: &ApplyContext) -> Result<InstallPlan, Error> {
+        Self::validate_apply_context(context)?;
+        self.apply_operation()?.plan(context).await
+    }
+
+    /// Apply operation set up from the configuration
+    fn apply_operation(&self) -> Result<ApplyOperation, Error> {
+        Ok(ApplyOperation::new(
+            self.resolver.clone(),
+            self.state_manager.clone(),
+            self.store.clone(),
+        )?
+        .with_offline(self.config.offline)
+        .with_recommends(self.config.recommends)
+        .with_conflict_policy(self.config.conflict_policy)
+        .with_security_policy(self.config.security)
+        .with_net_client(self.net_client.clone())
+        .with_download_config(self.config.download.clone())
+        .with_os_snapshots(self.config.os_snapshot_min_changes))
+    }
+
+    /// Work out what [`install`](Self::install) would do, without touching
+    /// the store or the states
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context is invalid or package resolution fails.
+    pub async fn plan_install(&self, context: &InstallContext) -> Result<InstallPlan, Error> {
+        Self::validate_install_context(context)?;
+        InstallOperation::new(
+            self.resolver.clone(),
+            self.state_manager.clone(),
+            self.store.clone(),
+        )?
+        .with_offline(self.config.offline)
+        .with_recommends(self.config.recommends)
+        .with_conflict_policy(self.config.conflict_policy)
+        .plan(context)
+        .await
+    }
+
+    /// Work out what [`uninstall`](Self::uninstall) would do, without
+    /// touching the store or the states
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the context is invalid or a package cannot be removed.
+    pub async fn plan_uninstall(&self, context: &UninstallContext) -> Result<InstallPlan, Error> {
+        Self::validate_uninstall_context(context)?;
+        UninstallOperation::new(self.state_manager.clone(), self.store.clone())
+            .plan(context)
+            .await
+    }
+
+    /// Work out what [`update`](Self::update) would do, without touching
+    /// the store or the states
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if package resolution fails.
+    pub async fn plan_update(&self, context: &UpdateContext) -> Result<InstallPlan, Error> {
+        Self::validate_update_context(context);
+        UpdateOperation::new(
+            self.resolver.clone(),
+            self.state_manager.clone(),
+            self.store.clone(),
+        )?
+        .with_offline(self.config.offline)
+        .with_recommends(self.config.recommends)
+        .with_conflict_policy(self.config.conflict_policy)
+        .plan(context)
+        .await
+    }
+
     /// List available states for rollback
     ///
     /// # Errors
@@ -212,9 +363,23 @@ impl Installer {
         Ok(())
     }
 
-    /// Validate uninstall context
+    /// Validate uninstall context; without packages, it can only autoremove
     fn validate_uninstall_context(context: &UninstallContext) -> Result<(), Error> {
-        if context.packages.is_empty() {
+        if context.packages.is_empty() && !context.autoremove {
+            return Err(InstallError::NoPackagesSpecified.into());
+        }
+
+        Ok(())
+    }
+
+    /// Validate apply context
+    fn validate_apply_context(context: &ApplyContext) -> Result<(), Error> {
+        if context.install.is_empty()
+            && context.remove.is_empty()
+            && context.upgrade.is_empty()
+            && !context.upgrade_all
+            && !context.autoremove
+        {
             return Err(InstallError::NoPackagesSpecified.into());
         }
 
@@ -308,6 +473,7 @@ mod tests {
                 store_path,
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
 
@@ -316,6 +482,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
 
@@ -16,15 +16,21 @@ mod prepare;
 //mod pipeline;
 //pub mod validation;
 
+pub use atomic::environment::{sync_environment, EnvironmentSync};
+pub use atomic::profile::{ProfileConflict, ProfileEnvironment, RefusedVariable};
 pub use atomic::{AtomicInstaller, StateTransition};
 pub use installer::Installer;
-pub use operations::{InstallOperation, UninstallOperation, UpdateOperation};
+pub use operations::{
+    ApplyOperation, InstallOperation, ReinstallOperation, UninstallOperation, UpdateOperation,
+};
 pub use prepare::{ExecutionContext, ParallelExecutor};
 
 // Re-export the public API surface from api module
-pub use api::config::{InstallConfig, SecurityPolicy};
-pub use api::context::{InstallContext, UninstallContext, UpdateContext};
-pub use api::result::{InstallResult, StateInfo};
+pub use api::config::{InstallConfig, RequiredHashes, SecurityPolicy};
+pub use api::context::{
+    ApplyContext, InstallContext, ReinstallContext, UninstallContext, UpdateContext,
+};
+pub use api::result::{InstallPlan, InstallResult, PlannedDownload, RemovalSet, StateInfo};
 pub use api::types::PreparedPackage;
 
 // Re-export EventSender for use by macros and contexts
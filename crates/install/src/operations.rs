@@ -2,17 +2,22 @@
 
 use crate::SecurityPolicy;
 use crate::{
-    AtomicInstaller, ExecutionContext, InstallContext, InstallResult, ParallelExecutor,
-    UninstallContext, UpdateContext,
+    ApplyContext, AtomicInstaller, ExecutionContext, InstallContext, InstallPlan, InstallResult,
+    ParallelExecutor, PlannedDownload, ReinstallContext, RemovalSet, UninstallContext,
+    UpdateContext,
 };
 use sps2_errors::{Error, InstallError};
 use sps2_events::events::GeneralEvent;
 use sps2_events::{AppEvent, EventEmitter};
+use sps2_net::{NetClient, PackageDownloadConfig};
 
-use sps2_resolver::{NodeAction, ResolutionContext, ResolutionResult, Resolver};
+use sps2_resolver::{
+    NodeAction, PackageId, ResolutionContext, ResolutionResult, ResolvedNode, Resolver,
+};
 use sps2_state::StateManager;
 use sps2_store::PackageStore;
-use sps2_types::PackageSpec;
+use sps2_types::{BrokenDependency, ConflictPolicy, PackageSpec};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
 /// Install operation
@@ -25,6 +30,32 @@ pub struct InstallOperation {
     store: PackageStore,
     /// Parallel executor
     executor: ParallelExecutor,
+    /// Concurrency limits shared by the executor and staging
+    resources: Arc<sps2_config::ResourceManager>,
+    /// Only use packages already present in the store
+    offline: bool,
+    /// Also install the packages that resolved packages recommend
+    recommends: bool,
+    /// Names of the packages the user asked for
+    requested: HashSet<String>,
+    /// How packages placing different files at the same path are settled
+    conflict_policy: ConflictPolicy,
+    /// Signature enforcement for downloaded packages
+    security_policy: SecurityPolicy,
+    /// Shared network client for package downloads
+    net_client: Option<NetClient>,
+    /// Download settings
+    download_config: PackageDownloadConfig,
+    /// Installed packages to remove in the same state
+    removals: Vec<PackageId>,
+    /// Installed packages to remove in the same state unless the
+    /// resolution needs them
+    autoremovals: Vec<PackageId>,
+    /// Operation the new state is recorded as
+    operation: &'static str,
+    /// Take a local APFS snapshot before committing changes to at least
+    /// this many packages
+    os_snapshot_min_changes: Option<usize>,
 }
 
 impl InstallOperation {
@@ -40,16 +71,111 @@ impl InstallOperation {
     ) -> Result<Self, Error> {
         // Create a default ResourceManager for the ParallelExecutor
         let resources = Arc::new(sps2_config::ResourceManager::default());
-        let executor = ParallelExecutor::new(store.clone(), state_manager.clone(), resources)?;
+        let executor =
+            ParallelExecutor::new(store.clone(), state_manager.clone(), resources.clone())?;
 
         Ok(Self {
             resolver,
             state_manager,
             store,
             executor,
+            resources,
+            offline: false,
+            recommends: true,
+            requested: HashSet::new(),
+            conflict_policy: ConflictPolicy::default(),
+            security_policy: SecurityPolicy::default(),
+            net_client: None,
+            download_config: PackageDownloadConfig::default(),
+            removals: Vec::new(),
+            autoremovals: Vec::new(),
+            operation: "install",
+            os_snapshot_min_changes: None,
         })
     }
 
+    /// Restrict installation to packages already present in the store
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Include or leave out recommended packages
+    #[must_use]
+    pub fn with_recommends(mut self, recommends: bool) -> Self {
+        self.recommends = recommends;
+        self
+    }
+
+    /// Name the packages the user asked for; local package files count as
+    /// asked for too
+    #[must_use]
+    pub fn with_requested(mut self, requested: impl IntoIterator<Item = String>) -> Self {
+        self.requested = requested.into_iter().collect();
+        self
+    }
+
+    /// Settle packages placing different files at the same path by
+    /// `policy` instead of failing
+    #[must_use]
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Set the signature policy for downloaded packages
+    #[must_use]
+    pub fn with_security_policy(mut self, policy: SecurityPolicy) -> Self {
+        self.security_policy = policy;
+        self
+    }
+
+    /// Download packages through a shared network client
+    #[must_use]
+    pub fn with_net_client(mut self, client: Option<NetClient>) -> Self {
+        self.net_client = client;
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download_config: PackageDownloadConfig) -> Self {
+        self.download_config = download_config;
+        self
+    }
+
+    /// Remove these installed packages in the state the installation
+    /// creates; resolving a package that is removed fails
+    #[must_use]
+    pub fn with_removals(mut self, removals: Vec<PackageId>) -> Self {
+        self.removals = removals;
+        self
+    }
+
+    /// Also remove these installed packages in the new state, keeping those
+    /// the resolution needs
+    #[must_use]
+    pub fn with_autoremovals(mut self, autoremovals: Vec<PackageId>) -> Self {
+        self.autoremovals = autoremovals;
+        self
+    }
+
+    /// Record the new state as `operation` instead of `install`
+    #[must_use]
+    pub fn with_operation(mut self, operation: &'static str) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.os_snapshot_min_changes = min_changes;
+        self
+    }
+
     /// Execute installation
     ///
     /// # Errors
@@ -75,11 +201,15 @@ impl InstallOperation {
                     .clone()
                     .unwrap_or_else(|| sps2_events::channel().0),
             )
-            .with_security_policy(SecurityPolicy {
-                verify_signatures: true, // default to verify in this path
-                allow_unsigned: false,
-            })
-            .with_force_redownload(context.force_download);
+            .with_security_policy(self.security_policy)
+            .with_force_redownload(context.force_download)
+            .with_offline(self.offline)
+            .with_required_hashes(context.required_hashes.clone())
+            .with_download_config(self.download_config.clone());
+        let exec_context = match &self.net_client {
+            Some(client) => exec_context.with_net_client(client.clone()),
+            None => exec_context,
+        };
 
         // Debug: Check what packages we're trying to process
         context.emit_debug(format!(
@@ -101,8 +231,21 @@ impl InstallOperation {
         // ParallelExecutor now returns prepared package data instead of doing database operations
 
         // Perform atomic installation
+        let local = resolution
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.action == NodeAction::Local)
+            .map(|(package_id, _)| package_id.name.clone());
         let mut atomic_installer =
-            AtomicInstaller::new(self.state_manager.clone(), self.store.clone());
+            AtomicInstaller::new(self.state_manager.clone(), self.store.clone())
+                .with_strip_quarantine(self.security_policy.strip_quarantine)
+                .with_allow_setuid(self.security_policy.allow_setuid)
+                .with_resources(self.resources.clone())
+                .with_requested(self.requested.iter().cloned().chain(local))
+                .with_conflict_policy(self.conflict_policy)
+                .with_removals(self.removals_for(&resolution))
+                .with_operation(self.operation)
+                .with_os_snapshots(self.os_snapshot_min_changes);
 
         let result = atomic_installer
             .install(&context, &resolution.nodes, Some(&prepared_packages))
@@ -111,26 +254,86 @@ impl InstallOperation {
         Ok(result)
     }
 
+    /// Work out what [`execute`](Self::execute) would do without downloading,
+    /// storing or installing anything
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a local file is missing, dependency resolution
+    /// fails, or offline mode is on and a package would have to be downloaded.
+    pub async fn plan(&self, context: &InstallContext) -> Result<InstallPlan, Error> {
+        Self::check_local_packages_exist(context)?;
+        let resolution = self.resolve_dependencies(context).await?;
+
+        let mut downloads = Vec::new();
+        for (package_id, node) in &resolution.nodes {
+            if node.action != NodeAction::Download
+                || (!context.force_download && self.is_in_store(node).await?)
+            {
+                continue;
+            }
+            downloads.push(PlannedDownload {
+                package: package_id.clone(),
+                url: node.url.clone().unwrap_or_default(),
+            });
+        }
+        downloads.sort_by(|a, b| a.package.name.cmp(&b.package.name));
+
+        let remote: Vec<&PlannedDownload> = downloads
+            .iter()
+            .filter(|download| sps2_net::file_url_path(&download.url).is_none())
+            .collect();
+        if self.offline && !remote.is_empty() {
+            let packages: Vec<String> = remote
+                .iter()
+                .map(|download| format!("{}-{}", download.package.name, download.package.version))
+                .collect();
+            return Err(InstallError::NotAvailableOffline {
+                packages: packages.join(", "),
+            }
+            .into());
+        }
+
+        let result = AtomicInstaller::new(self.state_manager.clone(), self.store.clone())
+            .with_removals(self.removals_for(&resolution))
+            .plan_install(&resolution.nodes)
+            .await?;
+        Ok(InstallPlan { result, downloads })
+    }
+
+    /// Whether a resolved package would be taken from the store instead of
+    /// downloaded
+    async fn is_in_store(&self, node: &ResolvedNode) -> Result<bool, Error> {
+        let Some(expected_hash) = &node.expected_hash else {
+            return Ok(false);
+        };
+        let Some(store_hash) = self
+            .state_manager
+            .get_store_hash_for_package_hash(&expected_hash.to_hex())
+            .await?
+        else {
+            return Ok(false);
+        };
+        let store_hash = sps2_hash::Hash::from_hex(&store_hash)?;
+        Ok(self
+            .store
+            .load_package_if_exists(&store_hash)
+            .await?
+            .is_some())
+    }
+
     /// Resolve dependencies for installation
     async fn resolve_dependencies(
         &self,
         context: &InstallContext,
     ) -> Result<sps2_resolver::ResolutionResult, Error> {
-        let mut resolution_context = ResolutionContext::new();
-
-        // Add requested packages as runtime dependencies
-        for spec in &context.packages {
-            resolution_context = resolution_context.add_runtime_dep(spec.clone());
-        }
-
-        // Add local files
-        for path in &context.local_files {
-            resolution_context = resolution_context.add_local_file(path.clone());
-        }
-
         context.emit_operation_started("Resolving dependencies");
 
-        let resolution = match self.resolver.resolve_with_sat(resolution_context).await {
+        let resolution = match self
+            .resolver
+            .resolve_with_sat(self.resolution_context(context))
+            .await
+        {
             Ok(result) => result,
             Err(e) => {
                 // Emit helpful error event for resolution failures
@@ -153,9 +356,63 @@ impl InstallOperation {
 
         context.emit_operation_completed("Dependency resolution", true);
 
+        self.check_removals_unneeded(&resolution)?;
         Ok(resolution)
     }
 
+    /// Fail if the resolution installs a package that is being removed,
+    /// naming the resolved packages that need it
+    fn check_removals_unneeded(&self, resolution: &ResolutionResult) -> Result<(), Error> {
+        for removal in &self.removals {
+            if !resolution.nodes.keys().any(|id| id.name == removal.name) {
+                continue;
+            }
+            let mut needed_by: Vec<&str> = resolution
+                .nodes
+                .iter()
+                .filter(|(_, node)| node.deps.iter().any(|dep| dep.name == removal.name))
+                .map(|(id, _)| id.name.as_str())
+                .collect();
+            needed_by.sort_unstable();
+            return Err(InstallError::RemovedPackageNeeded {
+                package: removal.name.clone(),
+                needed_by: if needed_by.is_empty() {
+                    removal.name.clone()
+                } else {
+                    needed_by.join(", ")
+                },
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    /// The packages to remove along with installing `resolution`: the
+    /// removals, and the autoremovals it does not need
+    fn removals_for(&self, resolution: &ResolutionResult) -> Vec<PackageId> {
+        let autoremovals = self
+            .autoremovals
+            .iter()
+            .filter(|removal| !resolution.nodes.keys().any(|id| id.name == removal.name));
+        self.removals.iter().chain(autoremovals).cloned().collect()
+    }
+
+    /// Resolution context for the packages and local files of `context`
+    fn resolution_context(&self, context: &InstallContext) -> ResolutionContext {
+        let mut resolution_context = ResolutionContext::new().with_recommends(self.recommends);
+
+        // Add requested packages as runtime dependencies
+        for spec in &context.packages {
+            resolution_context = resolution_context.add_runtime_dep(spec.clone());
+        }
+
+        // Add local files
+        for path in &context.local_files {
+            resolution_context = resolution_context.add_local_file(path.clone());
+        }
+        resolution_context
+    }
+
     /// Check local .sp package files exist (validation moved to `AtomicInstaller`)
     fn check_local_packages_exist(context: &InstallContext) -> Result<(), Error> {
         for local_file in &context.local_files {
@@ -201,6 +458,9 @@ pub struct UninstallOperation {
     state_manager: StateManager,
     /// Package store
     store: PackageStore,
+    /// Take a local APFS snapshot before committing changes to at least
+    /// this many packages
+    os_snapshot_min_changes: Option<usize>,
 }
 
 impl UninstallOperation {
@@ -210,63 +470,394 @@ impl UninstallOperation {
         Self {
             state_manager,
             store,
+            os_snapshot_min_changes: None,
         }
     }
 
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.os_snapshot_min_changes = min_changes;
+        self
+    }
+
     /// Execute uninstallation
     ///
     /// # Errors
     ///
     /// Returns an error if package removal fails or dependency checks fail.
     pub async fn execute(&mut self, context: UninstallContext) -> Result<InstallResult, Error> {
-        // Get currently installed packages
-        let current_packages = self.state_manager.get_installed_packages().await?;
+        let removal = self.checked_removal_set(&context).await?;
+        let package_ids: Vec<PackageId> = removal.packages().cloned().collect();
 
-        // Find packages to remove
-        let mut packages_to_remove = Vec::new();
-        for package_name in &context.packages {
-            if let Some(package_id) = current_packages
-                .iter()
-                .find(|pkg| &pkg.name == package_name)
-            {
-                packages_to_remove.push(package_id.clone());
-            } else if !context.force {
+        // Perform atomic uninstallation using AtomicInstaller
+        let mut atomic_installer =
+            AtomicInstaller::new(self.state_manager.clone(), self.store.clone())
+                .with_os_snapshots(self.os_snapshot_min_changes);
+        let mut result = atomic_installer.uninstall(&package_ids, &context).await?;
+        result.broken_dependencies = removal.broken;
+
+        Ok(result)
+    }
+
+    /// Work out what [`execute`](Self::execute) would remove without
+    /// changing anything
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a package is not installed or has dependents and
+    /// the context does not force removal.
+    pub async fn plan(&self, context: &UninstallContext) -> Result<InstallPlan, Error> {
+        let removal = self.checked_removal_set(context).await?;
+        let package_ids: Vec<PackageId> = removal.packages().cloned().collect();
+        let mut result = AtomicInstaller::new(self.state_manager.clone(), self.store.clone())
+            .plan_uninstall(&package_ids)
+            .await?;
+        result.broken_dependencies = removal.broken;
+        Ok(InstallPlan {
+            result,
+            downloads: Vec::new(),
+        })
+    }
+
+    /// Work out which installed packages the context removes and which
+    /// runtime dependencies that breaks, without checking either
+    ///
+    /// With `cascade`, installed packages depending on a removed one are
+    /// removed too, so no dependency is broken. With `autoremove`, packages
+    /// installed only as dependencies or recommendations that nothing left
+    /// installed needs or recommends are removed as well, and so are the
+    /// `prune` packages on the same terms.
+    ///
+    /// With `force`, a package whose manifest cannot be read is passed over
+    /// with a warning. What it needs is then unknown, so nothing is
+    /// autoremoved.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the installed packages, or without `force` their
+    /// manifests, cannot be read.
+    pub async fn removal_set(&self, context: &UninstallContext) -> Result<RemovalSet, Error> {
+        let installed = self.state_manager.get_installed_packages().await?;
+        let mut needs = self.installed_needs(&installed, context).await?;
+        let autoremove = context.autoremove && needs.unreadable.is_empty();
+        if context.autoremove && !autoremove {
+            context.emit_warning(format!(
+                "Not removing packages that are no longer needed, as what {} needs is unknown",
+                needs.unreadable.join(", ")
+            ));
+        }
+        if autoremove {
+            needs.automatic.extend(context.prune.iter().cloned());
+        }
+        let installed: Vec<PackageId> = installed
+            .iter()
+            .map(|package| PackageId::new(package.name.clone(), package.version()))
+            .collect();
+        Ok(plan_removal(
+            &installed,
+            &context.packages,
+            &needs,
+            context.cascade,
+            autoremove,
+        ))
+    }
+
+    /// The removal set, checked for missing packages and broken
+    /// dependencies unless forced
+    async fn checked_removal_set(&self, context: &UninstallContext) -> Result<RemovalSet, Error> {
+        let removal = self.removal_set(context).await?;
+        if !context.force {
+            if let Some(package) = removal.not_installed.first() {
                 return Err(InstallError::PackageNotInstalled {
-                    package: package_name.clone(),
+                    package: package.clone(),
                 }
                 .into());
             }
+            removal.ensure_nothing_broken()?;
         }
+        Ok(removal)
+    }
 
-        // Check for dependents if not forcing
-        if !context.force {
-            for package in &packages_to_remove {
-                let package_id =
-                    sps2_resolver::PackageId::new(package.name.clone(), package.version());
-                let dependents = self
-                    .state_manager
-                    .get_package_dependents(&package_id)
-                    .await?;
-                if !dependents.is_empty() {
-                    return Err(InstallError::PackageHasDependents {
-                        package: package_id.name.clone(),
-                    }
-                    .into());
+    /// How the `installed` packages need each other, from their manifests
+    ///
+    /// Unreadable manifests fail unless `context` forces removal.
+    async fn installed_needs(
+        &self,
+        installed: &[sps2_state::models::Package],
+        context: &UninstallContext,
+    ) -> Result<InstalledNeeds, Error> {
+        let mut needs = InstalledNeeds::default();
+        for package in installed {
+            if package.recommended || package.dependency {
+                needs.automatic.insert(package.name.clone());
+            }
+            let (dependencies, recommendations) = match self.package_needs(package).await {
+                Ok(package_needs) => package_needs,
+                Err(e) if context.force => {
+                    context.emit_warning(format!(
+                        "Ignoring what {} {} needs, as its manifest cannot be read: {e}",
+                        package.name, package.version
+                    ));
+                    needs.unreadable.push(package.name.clone());
+                    continue;
                 }
+                Err(e) => return Err(e),
+            };
+            for dependency in dependencies {
+                needs
+                    .dependents
+                    .entry(dependency.name)
+                    .or_default()
+                    .push(package.name.clone());
+            }
+            for recommendation in recommendations {
+                needs
+                    .recommenders
+                    .entry(recommendation.name)
+                    .or_default()
+                    .push(package.name.clone());
             }
         }
+        Ok(needs)
+    }
 
-        // Perform atomic uninstallation using AtomicInstaller
-        let package_ids: Vec<sps2_resolver::PackageId> = packages_to_remove
-            .iter()
-            .map(|pkg| sps2_resolver::PackageId::new(pkg.name.clone(), pkg.version()))
-            .collect();
+    /// Runtime dependencies and recommendations of an installed package
+    async fn package_needs(
+        &self,
+        package: &sps2_state::models::Package,
+    ) -> Result<(Vec<PackageSpec>, Vec<PackageSpec>), Error> {
+        let hash = sps2_hash::Hash::from_hex(&package.hash)?;
+        let stored = sps2_store::StoredPackage::load(&self.store.package_path(&hash)).await?;
+        let manifest = stored.manifest();
+        Ok((manifest.runtime_deps()?, manifest.recommended_deps()?))
+    }
+}
 
-        let mut atomic_installer =
-            AtomicInstaller::new(self.state_manager.clone(), self.store.clone());
-        let result = atomic_installer.uninstall(&package_ids, &context).await?;
+/// How installed packages need each other
+#[derive(Debug, Default)]
+struct InstalledNeeds {
+    /// Package names to the installed packages needing them at runtime
+    dependents: HashMap<String, Vec<String>>,
+    /// Package names to the installed packages recommending them
+    recommenders: HashMap<String, Vec<String>>,
+    /// Installed packages that are there only because another needed or
+    /// recommended them
+    automatic: HashSet<String>,
+    /// Installed packages whose manifest could not be read
+    unreadable: Vec<String>,
+}
 
-        Ok(result)
+/// Removal set for the `requested` names among the `installed` packages
+fn plan_removal(
+    installed: &[PackageId],
+    requested: &[String],
+    needs: &InstalledNeeds,
+    cascade: bool,
+    autoremove: bool,
+) -> RemovalSet {
+    let dependents = &needs.dependents;
+    let mut removal = RemovalSet::default();
+    for name in requested {
+        match installed.iter().find(|package| &package.name == name) {
+            Some(package) if !removal.requested.contains(package) => {
+                removal.requested.push(package.clone());
+            }
+            Some(_) => {}
+            None => removal.not_installed.push(name.clone()),
+        }
+    }
+
+    let mut removed: HashSet<String> = removal
+        .requested
+        .iter()
+        .map(|package| package.name.clone())
+        .collect();
+    if cascade {
+        let mut queue: VecDeque<String> = removed.iter().cloned().collect();
+        while let Some(name) = queue.pop_front() {
+            for dependent in dependents.get(&name).into_iter().flatten() {
+                let Some(package) = installed.iter().find(|p| &p.name == dependent) else {
+                    continue;
+                };
+                if removed.insert(dependent.clone()) {
+                    removal.cascaded.push(package.clone());
+                    queue.push_back(dependent.clone());
+                }
+            }
+        }
+    }
+
+    if autoremove {
+        // Removing one orphan may orphan the packages it recommends
+        while let Some(package) = installed.iter().find(|package| {
+            needs.automatic.contains(&package.name)
+                && !removed.contains(&package.name)
+                && needs
+                    .dependents
+                    .get(&package.name)
+                    .into_iter()
+                    .chain(needs.recommenders.get(&package.name))
+                    .flatten()
+                    .all(|other| removed.contains(other))
+        }) {
+            removed.insert(package.name.clone());
+            removal.autoremoved.push(package.clone());
+        }
+    }
+
+    let mut broken: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for package in removal.packages() {
+        for dependent in dependents.get(&package.name).into_iter().flatten() {
+            if removed.contains(dependent) {
+                continue;
+            }
+            let needs = broken.entry(dependent).or_default();
+            if !needs.contains(&package.name) {
+                needs.push(package.name.clone());
+            }
+        }
+    }
+    removal.broken = broken
+        .into_iter()
+        .map(|(package, needs)| BrokenDependency {
+            package: package.to_string(),
+            needs,
+        })
+        .collect();
+    removal
+}
+
+/// Reinstall operation
+pub struct ReinstallOperation {
+    /// State manager
+    state_manager: StateManager,
+    /// Package store
+    store: PackageStore,
+    /// Take a local APFS snapshot before committing changes to at least
+    /// this many packages
+    os_snapshot_min_changes: Option<usize>,
+    /// Whether to strip the quarantine attribute from signed packages
+    security_policy: SecurityPolicy,
+    /// Download settings, for the trusted keys
+    download_config: PackageDownloadConfig,
+}
+
+impl ReinstallOperation {
+    /// Create new reinstall operation
+    #[must_use]
+    pub fn new(state_manager: StateManager, store: PackageStore) -> Self {
+        Self {
+            state_manager,
+            store,
+            os_snapshot_min_changes: None,
+            security_policy: SecurityPolicy::default(),
+            download_config: PackageDownloadConfig::default(),
+        }
+    }
+
+    /// Set the security policy
+    #[must_use]
+    pub fn with_security_policy(mut self, policy: SecurityPolicy) -> Self {
+        self.security_policy = policy;
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download_config: PackageDownloadConfig) -> Self {
+        self.download_config = download_config;
+        self
+    }
+
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.os_snapshot_min_changes = min_changes;
+        self
+    }
+
+    /// Execute reinstallation
+    ///
+    /// The packages must already be intact in the store.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a package is not installed or staging fails.
+    pub async fn execute(&mut self, context: ReinstallContext) -> Result<InstallResult, Error> {
+        let package_ids = self.installed_packages(&context).await?;
+        let signed = if self.security_policy.strip_quarantine {
+            self.signed_packages(&package_ids).await?
+        } else {
+            HashSet::new()
+        };
+        AtomicInstaller::new(self.state_manager.clone(), self.store.clone())
+            .with_os_snapshots(self.os_snapshot_min_changes)
+            .with_strip_quarantine(self.security_policy.strip_quarantine)
+            .with_signed(signed)
+            .reinstall(&package_ids, &context)
+            .await
+    }
+
+    /// Names of `packages` whose store copy was signed by a trusted key
+    ///
+    /// The key is only known while the validation stamp from the original
+    /// download holds under the current trusted keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn signed_packages(&self, packages: &[PackageId]) -> Result<HashSet<String>, Error> {
+        let Ok(trust) = sps2_net::trust_fingerprint(&self.download_config.keys_dir) else {
+            return Ok(HashSet::new());
+        };
+        let mut signed = HashSet::new();
+        for installed in self.state_manager.get_installed_packages().await? {
+            if !packages
+                .iter()
+                .any(|package| package.name == installed.name)
+            {
+                continue;
+            }
+            let stamp = self
+                .state_manager
+                .validation_stamp(&installed.hash, &trust)
+                .await?;
+            if stamp.is_some_and(|stamp| stamp.key_id.is_some()) {
+                signed.insert(installed.name);
+            }
+        }
+        Ok(signed)
+    }
+
+    /// Installed versions of the packages in the context
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a package is not installed.
+    pub async fn installed_packages(
+        &self,
+        context: &ReinstallContext,
+    ) -> Result<Vec<PackageId>, Error> {
+        let installed = self.state_manager.get_installed_packages().await?;
+        context
+            .packages
+            .iter()
+            .map(|name| {
+                installed
+                    .iter()
+                    .find(|package| &package.name == name)
+                    .map(|package| PackageId::new(package.name.clone(), package.version()))
+                    .ok_or_else(|| {
+                        InstallError::PackageNotInstalled {
+                            package: name.clone(),
+                        }
+                        .into()
+                    })
+            })
+            .collect()
     }
 }
 
@@ -297,50 +888,258 @@ impl UpdateOperation {
         })
     }
 
+    /// Restrict updates to packages already present in the store
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.install_operation = self.install_operation.with_offline(offline);
+        self
+    }
+
+    /// Include or leave out recommended packages
+    #[must_use]
+    pub fn with_recommends(mut self, recommends: bool) -> Self {
+        self.install_operation = self.install_operation.with_recommends(recommends);
+        self
+    }
+
+    /// Settle packages placing different files at the same path by
+    /// `policy` instead of failing
+    #[must_use]
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.install_operation = self.install_operation.with_conflict_policy(policy);
+        self
+    }
+
+    /// Set the signature policy for downloaded packages
+    #[must_use]
+    pub fn with_security_policy(mut self, policy: SecurityPolicy) -> Self {
+        self.install_operation = self.install_operation.with_security_policy(policy);
+        self
+    }
+
+    /// Download packages through a shared network client
+    #[must_use]
+    pub fn with_net_client(mut self, client: Option<NetClient>) -> Self {
+        self.install_operation = self.install_operation.with_net_client(client);
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download_config: PackageDownloadConfig) -> Self {
+        self.install_operation = self.install_operation.with_download_config(download_config);
+        self
+    }
+
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.install_operation = self.install_operation.with_os_snapshots(min_changes);
+        self
+    }
+
     /// Execute update
     ///
     /// # Errors
     ///
     /// Returns an error if package resolution fails, update conflicts occur, or installation fails.
     pub async fn execute(&mut self, context: UpdateContext) -> Result<InstallResult, Error> {
+        let Some(install_context) = self.install_context(&context).await? else {
+            // No packages need updating - return early with empty result
+            return Ok(InstallResult::new(uuid::Uuid::nil()));
+        };
+
+        // Execute installation (which handles updates)
+        self.install_operation.execute(install_context).await
+    }
+
+    /// Work out what [`execute`](Self::execute) would do without
+    /// downloading, storing or installing anything
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if package resolution fails.
+    pub async fn plan(&self, context: &UpdateContext) -> Result<InstallPlan, Error> {
+        match self.install_context(context).await? {
+            Some(install_context) => self.install_operation.plan(&install_context).await,
+            None => Ok(InstallPlan {
+                result: InstallResult::new(self.state_manager.get_current_state_id().await?),
+                downloads: Vec::new(),
+            }),
+        }
+    }
+
+    /// Install context for the packages that have an update, or `None` if
+    /// none do
+    async fn install_context(
+        &self,
+        context: &UpdateContext,
+    ) -> Result<Option<InstallContext>, Error> {
         // Get currently installed packages
         let current_packages = self.state_manager.get_installed_packages().await?;
 
         // Determine packages to update
-        let packages_to_update = if context.packages.is_empty() {
+        let packages_to_update: Vec<_> = if context.packages.is_empty() {
             // Update all packages
-            current_packages
+            current_packages.clone()
         } else {
             // Update specified packages
             current_packages
-                .into_iter()
+                .iter()
                 .filter(|pkg| context.packages.contains(&pkg.name))
+                .cloned()
                 .collect()
         };
 
         // Check if any updates are actually needed
         if packages_to_update.is_empty() {
-            // No packages to update - return early with empty result
-            let result = InstallResult::new(uuid::Uuid::nil());
-
-            return Ok(result);
+            return Ok(None);
         }
 
         // For each package, check if an update is available before proceeding
         let (packages_needing_update, _packages_up_to_date) = self
-            .check_packages_for_updates(&packages_to_update, &context)
+            .check_packages_for_updates(&packages_to_update, context)
             .await?;
 
         // If no packages need updating, return early
         if packages_needing_update.is_empty() {
-            let result = InstallResult::new(uuid::Uuid::nil());
-
-            return Ok(result);
+            return Ok(None);
         }
 
         // Convert to package specs for installation
-        self.build_and_execute_install(&packages_needing_update, &context)
+        let install_context = Self::build_install_context(&packages_needing_update, context)?;
+        self.include_dependents(install_context, &current_packages, context)
             .await
+            .map(Some)
+    }
+
+    /// Add the updates installed packages need to keep their version
+    /// constraints on the updated packages satisfied
+    ///
+    /// Dependents are added until resolving stops changing packages they
+    /// constrain, or until a dependent has no version that fits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if resolution fails, or constraints of installed
+    /// packages stay broken and the update is not forced.
+    async fn include_dependents(
+        &self,
+        mut install_context: InstallContext,
+        installed: &[sps2_state::models::Package],
+        context: &UpdateContext,
+    ) -> Result<InstallContext, Error> {
+        self.record_missing_dependencies().await?;
+        let installed_versions: HashMap<&str, sps2_types::Version> = installed
+            .iter()
+            .map(|package| (package.name.as_str(), package.version()))
+            .collect();
+        let mut included: HashSet<String> = install_context
+            .packages
+            .iter()
+            .map(|spec| spec.name.clone())
+            .collect();
+        let resolver = &self.install_operation.resolver;
+        let mut resolution = resolver
+            .resolve_with_sat(self.install_operation.resolution_context(&install_context))
+            .await?;
+
+        loop {
+            let changes: HashMap<String, sps2_types::Version> = resolution
+                .nodes
+                .keys()
+                .filter(|id| {
+                    installed_versions
+                        .get(id.name.as_str())
+                        .is_some_and(|version| *version != id.version)
+                })
+                .map(|id| (id.name.clone(), id.version.clone()))
+                .collect();
+            let mut constraints = Vec::new();
+            for name in changes.keys() {
+                constraints.extend(self.state_manager.get_reverse_constraints(name).await?);
+            }
+            let broken = broken_constraints(&changes, &constraints);
+            if broken.is_empty() {
+                return Ok(install_context);
+            }
+
+            let dependents: BTreeSet<&str> = broken
+                .iter()
+                .map(|constraint| constraint.dependent.as_str())
+                .filter(|dependent| !included.contains(*dependent))
+                .collect();
+            let mut widened = install_context.clone();
+            for dependent in &dependents {
+                if let Some(package) = installed.iter().find(|p| p.name == *dependent) {
+                    widened = widened.add_package(update_spec(package, context.upgrade)?);
+                }
+            }
+            let widened_resolution = if dependents.is_empty() {
+                None
+            } else {
+                resolver
+                    .resolve_with_sat(self.install_operation.resolution_context(&widened))
+                    .await
+                    .ok()
+            };
+            let Some(widened_resolution) = widened_resolution else {
+                let listed = broken
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                if !context.force {
+                    return Err(InstallError::UpdateBreaksDependents {
+                        constraints: listed,
+                    }
+                    .into());
+                }
+                context.emit_warning(format!("Updating anyway, which breaks {listed}"));
+                return Ok(install_context);
+            };
+
+            for constraint in &broken {
+                if dependents.contains(constraint.dependent.as_str()) {
+                    context.emit_operation_started(format!(
+                        "Also updating {}, which requires {}",
+                        constraint.dependent, constraint.spec
+                    ));
+                }
+            }
+            included.extend(dependents.into_iter().map(String::from));
+            install_context = widened;
+            resolution = widened_resolution;
+        }
+    }
+
+    /// Record the runtime dependencies of installed packages that predate
+    /// dependency recording, from their manifests in the store
+    async fn record_missing_dependencies(&self) -> Result<(), Error> {
+        for package in self
+            .state_manager
+            .get_packages_without_recorded_dependencies()
+            .await?
+        {
+            let hash = sps2_hash::Hash::from_hex(&package.hash)?;
+            let Some(stored) = self
+                .install_operation
+                .store
+                .load_package_if_exists(&hash)
+                .await?
+            else {
+                continue;
+            };
+            self.state_manager
+                .record_package_dependencies(
+                    &package.name,
+                    &package.version,
+                    &stored.manifest().dependencies.runtime,
+                )
+                .await?;
+        }
+        Ok(())
     }
 
     /// Check which packages have available updates
@@ -367,8 +1166,9 @@ impl UpdateOperation {
                 PackageSpec::parse(&format!("{}~={}", package_id.name, package_id.version))?
             };
 
-            // Create resolution context to check for available updates
-            let mut resolution_context = ResolutionContext::new();
+            // Create resolution context to check for available updates; only
+            // the package's own version matters here
+            let mut resolution_context = ResolutionContext::new().with_recommends(false);
             resolution_context = resolution_context.add_runtime_dep(spec);
 
             // Resolve to see what version would be installed
@@ -531,24 +1331,15 @@ impl UpdateOperation {
         Ok((packages_needing_update, packages_up_to_date))
     }
 
-    /// Build install context and execute installation for packages needing update
-    async fn build_and_execute_install(
-        &mut self,
+    /// Build the install context for packages needing update
+    fn build_install_context(
         packages_needing_update: &[sps2_state::models::Package],
         context: &UpdateContext,
-    ) -> Result<InstallResult, Error> {
+    ) -> Result<InstallContext, Error> {
         let mut install_context = InstallContext::new();
 
-        for package_id in packages_needing_update {
-            let spec = if context.upgrade {
-                // Upgrade mode: ignore upper bounds
-                PackageSpec::parse(&format!("{}>=0.0.0", package_id.name))?
-            } else {
-                // Update mode: respect constraints (compatible release)
-                PackageSpec::parse(&format!("{}~={}", package_id.name, package_id.version))?
-            };
-
-            install_context = install_context.add_package(spec);
+        for package in packages_needing_update {
+            install_context = install_context.add_package(update_spec(package, context.upgrade)?);
         }
 
         install_context = install_context.with_force(true); // Force reinstallation for updates
@@ -557,7 +1348,447 @@ impl UpdateOperation {
             install_context = install_context.with_event_sender(sender.clone());
         }
 
-        // Execute installation (which handles updates)
-        self.install_operation.execute(install_context).await
+        Ok(install_context)
+    }
+}
+
+/// Apply operation
+///
+/// Installs, removes and upgrades packages as one change, committed as a
+/// single new state, so the change is made whole or not at all.
+pub struct ApplyOperation {
+    /// Works out the upgrades; its install operation commits the change
+    update_operation: UpdateOperation,
+}
+
+impl ApplyOperation {
+    /// Create new apply operation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if install operation initialization fails.
+    pub fn new(
+        resolver: Resolver,
+        state_manager: StateManager,
+        store: PackageStore,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            update_operation: UpdateOperation::new(resolver, state_manager, store)?,
+        })
+    }
+
+    /// Restrict installs and upgrades to packages already present in the
+    /// store
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.update_operation = self.update_operation.with_offline(offline);
+        self
+    }
+
+    /// Include or leave out recommended packages
+    #[must_use]
+    pub fn with_recommends(mut self, recommends: bool) -> Self {
+        self.update_operation = self.update_operation.with_recommends(recommends);
+        self
+    }
+
+    /// Settle packages placing different files at the same path by
+    /// `policy` instead of failing
+    #[must_use]
+    pub fn with_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.update_operation = self.update_operation.with_conflict_policy(policy);
+        self
+    }
+
+    /// Set the signature policy for downloaded packages
+    #[must_use]
+    pub fn with_security_policy(mut self, policy: SecurityPolicy) -> Self {
+        self.update_operation = self.update_operation.with_security_policy(policy);
+        self
+    }
+
+    /// Download packages through a shared network client
+    #[must_use]
+    pub fn with_net_client(mut self, client: Option<NetClient>) -> Self {
+        self.update_operation = self.update_operation.with_net_client(client);
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download_config: PackageDownloadConfig) -> Self {
+        self.update_operation = self.update_operation.with_download_config(download_config);
+        self
+    }
+
+    /// Take a local APFS snapshot before committing changes to at least
+    /// `min_changes` packages; `None` never takes one
+    #[must_use]
+    pub fn with_os_snapshots(mut self, min_changes: Option<usize>) -> Self {
+        self.update_operation = self.update_operation.with_os_snapshots(min_changes);
+        self
+    }
+
+    /// Execute the change
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a package to remove or upgrade is not installed,
+    /// removing packages would break dependencies and the context does not
+    /// force it, resolution fails or needs a package being removed, or
+    /// installation fails.
+    pub async fn execute(&mut self, context: ApplyContext) -> Result<InstallResult, Error> {
+        let Some((install_context, broken)) = self.prepare(&context).await? else {
+            return Ok(InstallResult::new(uuid::Uuid::nil()));
+        };
+        let mut result = self
+            .update_operation
+            .install_operation
+            .execute(install_context)
+            .await?;
+        result.broken_dependencies = broken;
+        Ok(result)
+    }
+
+    /// Work out what [`execute`](Self::execute) would do without
+    /// downloading, storing or installing anything
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors [`execute`](Self::execute) would fail with before
+    /// changing anything.
+    pub async fn plan(&mut self, context: &ApplyContext) -> Result<InstallPlan, Error> {
+        let Some((install_context, broken)) = self.prepare(context).await? else {
+            return Ok(InstallPlan {
+                result: InstallResult::new(
+                    self.update_operation
+                        .state_manager
+                        .get_current_state_id()
+                        .await?,
+                ),
+                downloads: Vec::new(),
+            });
+        };
+        let mut plan = self
+            .update_operation
+            .install_operation
+            .plan(&install_context)
+            .await?;
+        plan.result.broken_dependencies = broken;
+        Ok(plan)
+    }
+
+    /// Set up the install operation for the change and return its install
+    /// context with the dependencies the removals break, or `None` if the
+    /// change changes nothing
+    async fn prepare(
+        &mut self,
+        context: &ApplyContext,
+    ) -> Result<Option<(InstallContext, Vec<BrokenDependency>)>, Error> {
+        let state_manager = self.update_operation.state_manager.clone();
+        let store = self.update_operation.install_operation.store.clone();
+        let mut uninstall_context = UninstallContext::new()
+            .with_packages(context.remove.clone())
+            .with_force(context.force)
+            .with_autoremove(context.autoremove)
+            .with_prune(context.prune.clone());
+        if let Some(sender) = &context.event_sender {
+            uninstall_context = uninstall_context.with_event_sender(sender.clone());
+        }
+        let removal = UninstallOperation::new(state_manager.clone(), store)
+            .checked_removal_set(&uninstall_context)
+            .await?;
+        let removals: Vec<PackageId> = removal
+            .requested
+            .iter()
+            .chain(&removal.cascaded)
+            .cloned()
+            .collect();
+
+        let installed = state_manager.get_installed_packages().await?;
+        let upgrade: Vec<String> = if context.upgrade_all {
+            installed
+                .iter()
+                .map(|package| package.name.clone())
+                .filter(|name| !removal.packages().any(|removal| &removal.name == name))
+                .collect()
+        } else {
+            if let Some(name) = context
+                .upgrade
+                .iter()
+                .find(|name| !installed.iter().any(|package| &package.name == *name))
+            {
+                return Err(InstallError::PackageNotInstalled {
+                    package: name.clone(),
+                }
+                .into());
+            }
+            context.upgrade.clone()
+        };
+
+        let mut install_context = None;
+        if !upgrade.is_empty() {
+            let mut update_context = UpdateContext::new()
+                .with_packages(upgrade)
+                .with_upgrade(true)
+                .with_force(context.force);
+            if let Some(sender) = &context.event_sender {
+                update_context = update_context.with_event_sender(sender.clone());
+            }
+            install_context = self
+                .update_operation
+                .install_context(&update_context)
+                .await?;
+        }
+        let mut install_context = install_context.unwrap_or_default();
+        for spec in &context.install {
+            install_context = install_context.add_package(spec.clone());
+        }
+        if let Some(sender) = &context.event_sender {
+            install_context = install_context.with_event_sender(sender.clone());
+        }
+        if install_context.packages.is_empty() && removal.packages().next().is_none() {
+            return Ok(None);
+        }
+
+        let install_operation = &mut self.update_operation.install_operation;
+        install_operation.requested = context
+            .install
+            .iter()
+            .map(|spec| spec.name.clone())
+            .collect();
+        install_operation.removals = removals;
+        install_operation.autoremovals = removal.autoremoved;
+        install_operation.operation = "apply";
+        Ok(Some((install_context, removal.broken)))
+    }
+}
+
+/// Spec selecting the versions `package` may be updated to
+fn update_spec(package: &sps2_state::models::Package, upgrade: bool) -> Result<PackageSpec, Error> {
+    let spec = if upgrade {
+        // Upgrade mode: ignore upper bounds
+        PackageSpec::parse(&format!("{}>=0.0.0", package.name))?
+    } else {
+        // Update mode: respect constraints (compatible release)
+        PackageSpec::parse(&format!("{}~={}", package.name, package.version))?
+    };
+    Ok(spec)
+}
+
+/// An installed package's version constraint an update would break
+#[derive(Debug, PartialEq, Eq)]
+struct BrokenConstraint {
+    dependent: String,
+    dependent_version: String,
+    spec: String,
+    /// The version the constrained package would be updated to
+    version: sps2_types::Version,
+}
+
+impl std::fmt::Display for BrokenConstraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} requires {}, not {}",
+            self.dependent, self.dependent_version, self.spec, self.version
+        )
+    }
+}
+
+/// The `constraints` the new versions of installed packages in `changes`
+/// break
+///
+/// Constraints of packages that change themselves are left out, as their
+/// new versions are resolved together with the rest.
+fn broken_constraints(
+    changes: &HashMap<String, sps2_types::Version>,
+    constraints: &[sps2_state::ReverseConstraint],
+) -> Vec<BrokenConstraint> {
+    let mut broken: Vec<BrokenConstraint> = constraints
+        .iter()
+        .filter(|constraint| !changes.contains_key(&constraint.package))
+        .filter_map(|constraint| {
+            let spec = PackageSpec::parse(&constraint.spec).ok()?;
+            let version = changes.get(&spec.name)?;
+            (!spec.version_spec.matches(version)).then(|| BrokenConstraint {
+                dependent: constraint.package.clone(),
+                dependent_version: constraint.version.clone(),
+                spec: constraint.spec.clone(),
+                version: version.clone(),
+            })
+        })
+        .collect();
+    broken.sort_by(|a, b| (&a.dependent, &a.spec).cmp(&(&b.dependent, &b.spec)));
+    broken.dedup();
+    broken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_types::Version;
+
+    fn installed(names: &[&str]) -> Vec<PackageId> {
+        names
+            .iter()
+            .map(|name| PackageId::new((*name).to_string(), Version::new(1, 0, 0)))
+            .collect()
+    }
+
+    /// curl and git need openssl; git also needs curl; jq needs nothing
+    fn needs() -> InstalledNeeds {
+        InstalledNeeds {
+            dependents: HashMap::from([
+                (
+                    "openssl".to_string(),
+                    vec!["curl".to_string(), "git".to_string()],
+                ),
+                ("curl".to_string(), vec!["git".to_string()]),
+            ]),
+            ..InstalledNeeds::default()
+        }
+    }
+
+    fn names(packages: &[PackageId]) -> Vec<&str> {
+        packages
+            .iter()
+            .map(|package| package.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn removal_reports_the_dependents_it_would_break() {
+        let installed = installed(&["openssl", "curl", "git", "jq"]);
+        let removal = plan_removal(
+            &installed,
+            &["openssl".to_string(), "nano".to_string()],
+            &needs(),
+            false,
+            false,
+        );
+        assert_eq!(names(&removal.requested), ["openssl"]);
+        assert!(removal.cascaded.is_empty());
+        assert_eq!(removal.not_installed, ["nano"]);
+        assert_eq!(
+            removal.broken,
+            [
+                BrokenDependency {
+                    package: "curl".to_string(),
+                    needs: vec!["openssl".to_string()],
+                },
+                BrokenDependency {
+                    package: "git".to_string(),
+                    needs: vec!["openssl".to_string()],
+                },
+            ]
+        );
+        assert!(removal.ensure_nothing_broken().is_err());
+
+        // Removing a package together with its dependents breaks nothing
+        let removal = plan_removal(
+            &installed,
+            &["curl".to_string(), "git".to_string()],
+            &needs(),
+            false,
+            false,
+        );
+        assert!(removal.broken.is_empty());
+        assert!(removal.ensure_nothing_broken().is_ok());
+    }
+
+    #[test]
+    fn cascade_removes_dependents_transitively() {
+        let installed = installed(&["openssl", "curl", "git", "jq"]);
+        let removal = plan_removal(&installed, &["openssl".to_string()], &needs(), true, false);
+        assert_eq!(names(&removal.requested), ["openssl"]);
+        assert_eq!(names(&removal.cascaded), ["curl", "git"]);
+        assert!(removal.broken.is_empty());
+    }
+
+    #[test]
+    fn autoremove_takes_recommended_packages_nothing_else_wants() {
+        // git recommends less and jq, which jless also recommends; less
+        // recommends lesspipe
+        let installed = installed(&["git", "less", "lesspipe", "jq", "jless"]);
+        let needs = InstalledNeeds {
+            recommenders: HashMap::from([
+                ("less".to_string(), vec!["git".to_string()]),
+                ("lesspipe".to_string(), vec!["less".to_string()]),
+                (
+                    "jq".to_string(),
+                    vec!["git".to_string(), "jless".to_string()],
+                ),
+            ]),
+            automatic: ["less", "lesspipe", "jq"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            ..InstalledNeeds::default()
+        };
+
+        let removal = plan_removal(&installed, &["git".to_string()], &needs, false, false);
+        assert!(removal.autoremoved.is_empty());
+
+        let removal = plan_removal(&installed, &["git".to_string()], &needs, false, true);
+        assert_eq!(names(&removal.requested), ["git"]);
+        assert_eq!(names(&removal.autoremoved), ["less", "lesspipe"]);
+        assert!(removal.broken.is_empty());
+    }
+
+    #[test]
+    fn autoremove_takes_dependencies_left_without_dependents() {
+        let installed = installed(&["openssl", "curl", "git", "jq"]);
+        let needs = InstalledNeeds {
+            automatic: ["openssl", "curl"].into_iter().map(String::from).collect(),
+            ..needs()
+        };
+
+        // Nothing is orphaned while git is installed
+        let removal = plan_removal(&installed, &[], &needs, false, true);
+        assert_eq!(removal.packages().count(), 0);
+
+        let removal = plan_removal(&installed, &["git".to_string()], &needs, false, true);
+        assert_eq!(names(&removal.autoremoved), ["curl", "openssl"]);
+        assert!(removal.broken.is_empty());
+    }
+
+    #[test]
+    fn updates_break_constraints_of_dependents_left_behind() {
+        let constraint = |package: &str, spec: &str| sps2_state::ReverseConstraint {
+            package: package.to_string(),
+            version: "1.0.0".to_string(),
+            spec: spec.to_string(),
+        };
+        let constraints = [
+            constraint("curl", "openssl>=3.0.0,<4.0.0"),
+            constraint("git", "openssl>=3.0.0"),
+            constraint("git", "curl~=1.0.0"),
+            constraint("python", "openssl<4.0.0"),
+        ];
+
+        let changes = HashMap::from([("openssl".to_string(), Version::new(3, 2, 0))]);
+        assert!(broken_constraints(&changes, &constraints).is_empty());
+
+        let changes = HashMap::from([("openssl".to_string(), Version::new(4, 0, 0))]);
+        let broken = broken_constraints(&changes, &constraints);
+        assert_eq!(
+            broken.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            [
+                "curl 1.0.0 requires openssl>=3.0.0,<4.0.0, not 4.0.0",
+                "python 1.0.0 requires openssl<4.0.0, not 4.0.0",
+            ]
+        );
+
+        // Dependents updated along with openssl bring their own constraints
+        let changes = HashMap::from([
+            ("openssl".to_string(), Version::new(4, 0, 0)),
+            ("curl".to_string(), Version::new(2, 0, 0)),
+            ("python".to_string(), Version::new(3, 13, 0)),
+        ]);
+        let broken = broken_constraints(&changes, &constraints);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].dependent, "git");
+        assert_eq!(broken[0].spec, "curl~=1.0.0");
     }
 }
@@ -1,7 +1,8 @@
 //! Execution context for parallel operations
 
-use crate::SecurityPolicy;
+use crate::{RequiredHashes, SecurityPolicy};
 use sps2_events::{EventEmitter, EventSender};
+use sps2_net::{NetClient, PackageDownloadConfig};
 
 /// Execution context for parallel operations
 #[derive(Clone)]
@@ -12,6 +13,15 @@ pub struct ExecutionContext {
     security_policy: Option<SecurityPolicy>,
     /// Whether downloads should bypass cache reuse
     force_redownload: bool,
+    /// Whether packages must come from the store without downloading
+    offline: bool,
+    /// Shared network client carrying proxy and mirror settings
+    net_client: Option<NetClient>,
+    /// Artifact hashes packages are restricted to, if pinned
+    required_hashes: Option<RequiredHashes>,
+    /// Download settings, including where trusted keys and quarantined
+    /// downloads live
+    download_config: PackageDownloadConfig,
 }
 
 impl ExecutionContext {
@@ -22,6 +32,10 @@ impl ExecutionContext {
             event_sender: None,
             security_policy: None,
             force_redownload: false,
+            offline: false,
+            net_client: None,
+            required_hashes: None,
+            download_config: PackageDownloadConfig::default(),
         }
     }
 
@@ -46,12 +60,61 @@ impl ExecutionContext {
         self
     }
 
+    /// Set whether downloads are forbidden
+    #[must_use]
+    pub fn with_offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Set the network client used for package downloads
+    #[must_use]
+    pub fn with_net_client(mut self, client: NetClient) -> Self {
+        self.net_client = Some(client);
+        self
+    }
+
+    /// Restrict packages to the given artifact hashes
+    #[must_use]
+    pub fn with_required_hashes(mut self, required_hashes: Option<RequiredHashes>) -> Self {
+        self.required_hashes = required_hashes;
+        self
+    }
+
+    /// Set the download settings
+    #[must_use]
+    pub fn with_download_config(mut self, download_config: PackageDownloadConfig) -> Self {
+        self.download_config = download_config;
+        self
+    }
+
     /// Should downstream logic bypass store reuse
     #[must_use]
     pub fn force_redownload(&self) -> bool {
         self.force_redownload
     }
 
+    /// Are downloads forbidden
+    #[must_use]
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Get the network client if set
+    pub(crate) fn net_client(&self) -> Option<&NetClient> {
+        self.net_client.as_ref()
+    }
+
+    /// Get the pinned artifact hashes if set
+    pub(crate) fn required_hashes(&self) -> Option<&RequiredHashes> {
+        self.required_hashes.as_ref()
+    }
+
+    /// Get the download settings
+    pub(crate) fn download_config(&self) -> &PackageDownloadConfig {
+        &self.download_config
+    }
+
     /// Get the security policy if set
     pub(crate) fn security_policy(&self) -> Option<SecurityPolicy> {
         self.security_policy
@@ -16,6 +16,7 @@ use tokio::task::JoinHandle;
 use tokio::time::{Duration, Instant};
 
 use super::context::ExecutionContext;
+use super::preflight::{check_disk_space, store_pending, PendingPackage};
 use super::worker::{process_package, ProcessPackageArgs};
 
 /// Parallel executor for package operations
@@ -74,6 +75,7 @@ impl ParallelExecutor {
         let ready_queue = Arc::new(SegQueue::new());
         let inflight = Arc::new(DashMap::new());
         let prepared_packages = Arc::new(DashMap::new());
+        let pending_packages = Arc::new(DashMap::new());
         let graph = Self::build_execution_graph(self, execution_plan, resolved_packages);
 
         // Initialize ready queue with packages that have no dependencies
@@ -225,6 +227,7 @@ impl ParallelExecutor {
                     context.clone(),
                     permit,
                     prepared_packages.clone(),
+                    pending_packages.clone(),
                 );
 
                 inflight.insert(package_id, handle);
@@ -253,6 +256,30 @@ impl ParallelExecutor {
             context: std::collections::HashMap::new(),
         }));
 
+        // Nothing reaches the store until the whole install is known to fit
+        let pending_packages =
+            Arc::try_unwrap(pending_packages).map_err(|_| InstallError::ConcurrencyError {
+                message: "failed to unwrap pending packages Arc".to_string(),
+            })?;
+        if !pending_packages.is_empty() || !prepared_packages.is_empty() {
+            check_disk_space(
+                &self.store,
+                &self.state_manager,
+                &pending_packages,
+                &prepared_packages,
+                context,
+            )
+            .await?;
+        }
+        store_pending(
+            &self.store,
+            &self.state_manager,
+            pending_packages,
+            &prepared_packages,
+            context,
+        )
+        .await?;
+
         // Convert DashMap to HashMap and return prepared packages
         let prepared_packages =
             Arc::try_unwrap(prepared_packages).map_err(|_| InstallError::ConcurrencyError {
@@ -296,6 +323,7 @@ impl ParallelExecutor {
         context: ExecutionContext,
         permit: tokio::sync::OwnedSemaphorePermit,
         prepared_packages: Arc<DashMap<PackageId, PreparedPackage>>,
+        pending_packages: Arc<DashMap<PackageId, PendingPackage>>,
     ) -> JoinHandle<Result<PackageId, Error>> {
         let store = self.store.clone();
         let state_manager = self.state_manager.clone();
@@ -310,6 +338,7 @@ impl ParallelExecutor {
                 state_manager,
                 timeout_duration,
                 prepared_packages,
+                pending_packages,
                 permit,
             })
             .await
@@ -529,6 +558,58 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn disk_delta_counts_only_new_store_objects() {
+        let (_td, state, store) = mk_env().await;
+        let (_pkg_dir, pkg_sp) = create_sp("pkg-space", "1.0.0").await;
+
+        let node = ResolvedNode::local(
+            "pkg-space".to_string(),
+            Version::parse("1.0.0").unwrap(),
+            pkg_sp,
+            vec![],
+        );
+        let pkg_id = node.package_id();
+        let resolved_packages = HashMap::from([(pkg_id.clone(), node.clone())]);
+        let mut graph = DependencyGraph::new();
+        graph.add_node(node);
+
+        let executor = ParallelExecutor::new(
+            store.clone(),
+            state,
+            Arc::new(sps2_config::ResourceManager::default()),
+        )
+        .expect("parallel executor");
+
+        let mut deltas = Vec::new();
+        for _ in 0..2 {
+            let execution_plan =
+                ExecutionPlan::from_sorted_packages(std::slice::from_ref(&pkg_id), &graph);
+            let (tx, mut rx) = sps2_events::channel();
+            let context = ExecutionContext::new().with_event_sender(tx);
+            let prepared = executor
+                .execute_parallel(&execution_plan, &resolved_packages, &context)
+                .await
+                .expect("execute parallel");
+            assert!(store.has_package(&prepared[&pkg_id].hash).await);
+
+            while let Ok(message) = rx.try_recv() {
+                if let AppEvent::State(sps2_events::StateEvent::DiskDeltaComputed { delta }) =
+                    message.event
+                {
+                    deltas.push(delta);
+                }
+            }
+        }
+
+        assert_eq!(deltas.len(), 2);
+        assert_eq!(deltas[0].downloaded_bytes, 0);
+        assert_eq!(deltas[0].store_bytes, "pkg-space".len() as u64);
+        assert!(deltas[0].available_bytes.is_some());
+        // Objects already in the store take no new space
+        assert_eq!(deltas[1].store_bytes, 0);
+    }
+
     #[tokio::test]
     async fn try_prepare_from_store_returns_package_when_available() {
         let (_td, state, store) = mk_env().await;
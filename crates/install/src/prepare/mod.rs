@@ -1,5 +1,6 @@
 pub mod context;
 pub mod executor;
+mod preflight;
 pub mod worker;
 
 pub use context::ExecutionContext;
@@ -0,0 +1,199 @@
+//! Disk space pre-flight for prepared packages
+//!
+//! Workers unpack downloaded and local packages without touching the store.
+//! Once every package is unpacked, the space the install will take is worked
+//! out and checked against the free space on the target volume, and only then
+//! are the packages added to the store. An install that cannot fit fails
+//! before it has written anything under the store or the live prefix.
+
+use crate::PreparedPackage;
+use dashmap::DashMap;
+use sps2_errors::{Error, InstallError, StorageError};
+use sps2_events::{AppEvent, DiskDelta, EventEmitter, GeneralEvent, StateEvent};
+use sps2_hash::Hash;
+use sps2_net::trust_fingerprint;
+use sps2_platform::filesystem_helpers::available_space;
+use sps2_resolver::PackageId;
+use sps2_state::{StateManager, ValidationStamp};
+use sps2_store::{volume_id, PackageStore, StoredPackage, UnpackedPackage};
+use sps2_types::LinkStrategy;
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::context::ExecutionContext;
+
+/// A package unpacked by a worker and waiting to be added to the store
+pub(crate) struct PendingPackage {
+    pub unpacked: UnpackedPackage,
+    /// Size of the downloaded archive; zero for local packages
+    pub downloaded_bytes: u64,
+    pub is_local: bool,
+    /// Archive hash (BLAKE3) provided by the repository
+    pub package_hash: Option<Hash>,
+    /// Trusted key that verified the downloaded archive's signature
+    pub signing_key: Option<String>,
+    /// Store entry replaced by a forced re-download
+    pub replaces: Option<Hash>,
+}
+
+/// Work out the disk delta of an install, report it, and refuse it if the
+/// target volume lacks the space
+///
+/// Store bytes count each file object once and skip objects the store
+/// already holds. Staging only takes space of its own when files are copied
+/// out of the store rather than cloned or hard linked.
+pub(crate) async fn check_disk_space(
+    store: &PackageStore,
+    state_manager: &StateManager,
+    pending: &DashMap<PackageId, PendingPackage>,
+    prepared: &DashMap<PackageId, PreparedPackage>,
+    context: &ExecutionContext,
+) -> Result<DiskDelta, Error> {
+    let mut seen = HashSet::new();
+    let mut downloaded_bytes = 0u64;
+    let mut store_bytes = 0u64;
+    let mut content_bytes = 0u64;
+    for entry in pending {
+        let package = entry.value();
+        downloaded_bytes += package.downloaded_bytes;
+        content_bytes += package.unpacked.content_bytes();
+        for file in package.unpacked.files() {
+            if file.is_directory || file.is_symlink || !seen.insert(file.hash.clone()) {
+                continue;
+            }
+            if !store.file_store().has_file(&file.hash).await {
+                store_bytes += file.size;
+            }
+        }
+    }
+
+    let live_path = state_manager.live_path();
+    let copies = store.file_store().resolve_link_strategy(live_path).await == LinkStrategy::Copy;
+    let staging_bytes = if copies {
+        for entry in prepared {
+            content_bytes += StoredPackage::load(&entry.value().store_path)
+                .await?
+                .content_bytes();
+        }
+        content_bytes
+    } else {
+        0
+    };
+
+    let store_path = store.base_path();
+    let available_bytes = available_space(store_path).await.ok();
+    let delta = DiskDelta {
+        downloaded_bytes,
+        store_bytes,
+        staging_bytes,
+        available_bytes,
+    };
+    context.emit(AppEvent::State(StateEvent::DiskDeltaComputed {
+        delta: delta.clone(),
+    }));
+
+    let same_volume = volume_id(store_path).await == volume_id(live_path).await;
+    if same_volume {
+        ensure_space(store_path, available_bytes, delta.required_bytes())?;
+    } else {
+        ensure_space(store_path, available_bytes, store_bytes)?;
+        if staging_bytes > 0 {
+            let live_available = available_space(live_path).await.ok();
+            ensure_space(live_path, live_available, staging_bytes)?;
+        }
+    }
+
+    Ok(delta)
+}
+
+/// Fail unless `required` bytes fit; unknown free space is not checked
+fn ensure_space(path: &Path, available: Option<u64>, required: u64) -> Result<(), Error> {
+    match available {
+        Some(available) if available < required => Err(StorageError::InsufficientSpace {
+            path: path.display().to_string(),
+            required,
+            available,
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Add the unpacked packages to the store, recording them as prepared
+///
+/// Downloaded packages get a validation stamp, so later operations reusing
+/// them from the store need not verify their content again.
+pub(crate) async fn store_pending(
+    store: &PackageStore,
+    state_manager: &StateManager,
+    pending: DashMap<PackageId, PendingPackage>,
+    prepared: &DashMap<PackageId, PreparedPackage>,
+    context: &ExecutionContext,
+) -> Result<(), Error> {
+    for (package_id, package) in pending {
+        if let Some(previous) = &package.replaces {
+            store.remove_package(previous).await?;
+        }
+        let stored_package = store.add_unpacked(&package.unpacked).await?;
+        let hash = stored_package
+            .hash()
+            .ok_or_else(|| InstallError::AtomicOperationFailed {
+                message: format!(
+                    "failed to get hash from stored package {}-{}",
+                    package_id.name, package_id.version
+                ),
+            })?;
+
+        context.emit(AppEvent::General(GeneralEvent::debug(format!(
+            "Package {}-{} stored with hash {} (prepared for installation)",
+            package_id.name,
+            package_id.version,
+            hash.to_hex()
+        ))));
+
+        if !package.is_local {
+            stamp_validated(state_manager, &hash, &package, context).await;
+        }
+
+        prepared.insert(
+            package_id,
+            PreparedPackage {
+                hash,
+                size: stored_package.size().await?,
+                store_path: stored_package.path().to_path_buf(),
+                is_local: package.is_local,
+                package_hash: package.package_hash,
+                signing_key: package.signing_key.clone(),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Record that a downloaded package passed hash and signature checks
+///
+/// A missing stamp only costs a later re-check, so failures are reported
+/// and otherwise ignored.
+async fn stamp_validated(
+    state_manager: &StateManager,
+    hash: &Hash,
+    package: &PendingPackage,
+    context: &ExecutionContext,
+) {
+    let stamped = async {
+        let stamp = ValidationStamp {
+            store_hash: hash.to_hex(),
+            package_hash: package.package_hash.as_ref().map(Hash::to_hex),
+            key_id: package.signing_key.clone(),
+            trust_fingerprint: trust_fingerprint(&context.download_config().keys_dir)?,
+            validated_at: chrono::Utc::now().timestamp(),
+        };
+        state_manager.record_validation_stamp(&stamp).await
+    };
+    if let Err(e) = stamped.await {
+        context.emit(AppEvent::General(GeneralEvent::debug(format!(
+            "Could not record validation stamp for {}: {e}",
+            hash.to_hex()
+        ))));
+    }
+}
@@ -5,7 +5,7 @@ use dashmap::DashMap;
 use sps2_errors::{Error, InstallError};
 use sps2_events::events::{LifecycleAcquisitionSource, LifecycleEvent};
 use sps2_events::{AppEvent, EventEmitter, FailureContext, GeneralEvent};
-use sps2_net::{PackageDownloadConfig, PackageDownloader};
+use sps2_net::{trust_fingerprint, PackageDownloader};
 use sps2_resolver::{NodeAction, PackageId, ResolvedNode};
 use sps2_state::StateManager;
 use sps2_store::PackageStore;
@@ -14,6 +14,7 @@ use tokio::sync::OwnedSemaphorePermit;
 use tokio::time::Duration;
 
 use super::context::ExecutionContext;
+use super::preflight::PendingPackage;
 
 pub(crate) struct ProcessPackageArgs {
     pub package_id: PackageId,
@@ -23,6 +24,7 @@ pub(crate) struct ProcessPackageArgs {
     pub state_manager: StateManager,
     pub timeout_duration: Duration,
     pub prepared_packages: Arc<DashMap<PackageId, PreparedPackage>>,
+    pub pending_packages: Arc<DashMap<PackageId, PendingPackage>>,
     pub permit: OwnedSemaphorePermit,
 }
 
@@ -40,6 +42,7 @@ pub(crate) async fn process_package(args: ProcessPackageArgs) -> Result<PackageI
         state_manager,
         timeout_duration,
         prepared_packages,
+        pending_packages,
         permit: _permit,
     } = args;
     context.emit(AppEvent::General(GeneralEvent::DebugLog {
@@ -69,6 +72,7 @@ pub(crate) async fn process_package(args: ProcessPackageArgs) -> Result<PackageI
                         &state_manager,
                         &context,
                         &prepared_packages,
+                        &pending_packages,
                     ),
                 )
                 .await;
@@ -155,55 +159,46 @@ pub(crate) async fn process_package(args: ProcessPackageArgs) -> Result<PackageI
                     return Ok(package_id);
                 }
                 context.emit(AppEvent::General(GeneralEvent::DebugLog {
-                    message: format!("DEBUG: Adding local package to store: {}", path.display()),
+                    message: format!("DEBUG: Unpacking local package: {}", path.display()),
                     context: std::collections::HashMap::new(),
                 }));
 
-                // For local packages, add to store and prepare data
-                let stored_package = store.add_package(path).await?;
+                // A local file is held to the same pins as a download
+                if let Some(required) = context.required_hashes() {
+                    let hash = sps2_hash::Hash::blake3_hash_file(path).await?;
+                    required.check(&package_id.name, &hash)?;
+                }
 
-                if let Some(hash) = stored_package.hash() {
-                    let size = stored_package.size().await?;
-                    let store_path = stored_package.path().to_path_buf();
+                // Unpack now; the package is stored once the install is known to fit
+                let unpacked = store.unpack_package(path).await?;
 
-                    context.emit(AppEvent::General(GeneralEvent::DebugLog {
-                        message: format!(
-                            "DEBUG: Local package stored with hash {} at {}",
-                            hash.to_hex(),
-                            store_path.display()
-                        ),
-                        context: std::collections::HashMap::new(),
-                    }));
+                context.emit(AppEvent::General(GeneralEvent::DebugLog {
+                    message: format!(
+                        "DEBUG: Local package {}-{} unpacked with hash {}",
+                        package_id.name,
+                        package_id.version,
+                        unpacked.hash().to_hex()
+                    ),
+                    context: std::collections::HashMap::new(),
+                }));
 
-                    let prepared_package = PreparedPackage {
-                        hash: hash.clone(),
-                        size,
-                        store_path,
+                pending_packages.insert(
+                    package_id.clone(),
+                    PendingPackage {
+                        unpacked,
+                        downloaded_bytes: 0,
                         is_local: true,
                         package_hash: None,
-                    };
-
-                    prepared_packages.insert(package_id.clone(), prepared_package);
-
-                    context.emit(AppEvent::General(GeneralEvent::DebugLog {
-                        message: format!(
-                            "DEBUG: Added prepared package for {}-{}",
-                            package_id.name, package_id.version
-                        ),
-                        context: std::collections::HashMap::new(),
-                    }));
-
-                    context.emit(AppEvent::Lifecycle(LifecycleEvent::install_completed(
-                        package_id.name.clone(),
-                        package_id.version.clone(),
-                        0, // TODO: Count actual files
-                    )));
-                } else {
-                    return Err(InstallError::AtomicOperationFailed {
-                        message: "failed to get hash from local package".to_string(),
-                    }
-                    .into());
-                }
+                        signing_key: None,
+                        replaces: None,
+                    },
+                );
+
+                context.emit(AppEvent::Lifecycle(LifecycleEvent::install_completed(
+                    package_id.name.clone(),
+                    package_id.version.clone(),
+                    0, // TODO: Count actual files
+                )));
             } else {
                 return Err(InstallError::MissingLocalPath {
                     package: package_id.name.clone(),
@@ -216,11 +211,12 @@ pub(crate) async fn process_package(args: ProcessPackageArgs) -> Result<PackageI
     Ok(package_id)
 }
 
-/// Download a package and add to store (no validation - `AtomicInstaller` handles that)
+/// Download and unpack a package (no validation - `AtomicInstaller` handles that)
 ///
 /// Complex download workflow including store caching, signature verification, and deduplication.
 /// Function length reflects the comprehensive error handling and security checks required.
-#[allow(clippy::too_many_lines)]
+/// The unpacked package is added to the store after the disk space pre-flight.
+#[allow(clippy::too_many_lines, clippy::too_many_arguments)]
 pub(crate) async fn download_package_only(
     url: &str,
     package_id: &PackageId,
@@ -229,6 +225,7 @@ pub(crate) async fn download_package_only(
     state_manager: &StateManager,
     context: &ExecutionContext,
     prepared_packages: &Arc<DashMap<PackageId, PreparedPackage>>,
+    pending_packages: &Arc<DashMap<PackageId, PendingPackage>>,
 ) -> Result<u64, Error> {
     if let Some(size) = try_prepare_from_store(
         package_id,
@@ -243,6 +240,13 @@ pub(crate) async fn download_package_only(
         return Ok(size);
     }
 
+    if context.offline() && sps2_net::file_url_path(url).is_none() {
+        return Err(InstallError::NotAvailableOffline {
+            packages: format!("{}-{}", package_id.name, package_id.version),
+        }
+        .into());
+    }
+
     context.emit(AppEvent::Lifecycle(LifecycleEvent::acquisition_started(
         package_id.name.clone(),
         package_id.version.clone(),
@@ -252,16 +256,28 @@ pub(crate) async fn download_package_only(
         },
     )));
 
-    // Create a temporary directory for the download
-    let temp_dir = tempfile::tempdir().map_err(|e| InstallError::TempFileError {
-        message: e.to_string(),
-    })?;
+    // Download into the store's temp area, so unpacking stays on its volume
+    let temp_dir = store
+        .temp_area()
+        .tempdir()
+        .map_err(|e| InstallError::TempFileError {
+            message: e.to_string(),
+        })?;
 
     // Use high-level PackageDownloader to benefit from hash/signature handling
-    let downloader = PackageDownloader::new(
-        PackageDownloadConfig::default(),
-        sps2_events::ProgressManager::new(),
-    )?;
+    let mut download_config = context.download_config().clone();
+    if context.force_redownload() {
+        // A forced download always fetches the archive
+        download_config.cache = None;
+    }
+    let downloader = match context.net_client() {
+        Some(client) => PackageDownloader::with_client(
+            download_config,
+            client.clone(),
+            sps2_events::ProgressManager::new(),
+        ),
+        None => PackageDownloader::new(download_config, sps2_events::ProgressManager::new())?,
+    };
 
     let tx = context
         .event_sender()
@@ -304,7 +320,12 @@ pub(crate) async fn download_package_only(
         }
     }
 
-    let previous_store_hash = if context.force_redownload() {
+    // Pinned hashes override whatever the index and its signature vouch for
+    if let Some(required) = context.required_hashes() {
+        required.check(&package_id.name, &download_result.hash)?;
+    }
+
+    let replaces = if context.force_redownload() {
         if let Some(expected_hash) = node.expected_hash.as_ref() {
             state_manager
                 .get_store_hash_for_package_hash(&expected_hash.to_hex())
@@ -318,62 +339,30 @@ pub(crate) async fn download_package_only(
         None
     };
 
-    // Add to store and prepare package data
-    let mut stored_package = store
-        .add_package_from_file(
-            &download_result.package_path,
-            &package_id.name,
-            &package_id.version,
-        )
-        .await?;
-
-    if let Some(prev_hash) = previous_store_hash {
-        if let Some(current_hash) = stored_package.hash() {
-            if current_hash == prev_hash {
-                store.remove_package(&prev_hash).await?;
-                stored_package = store
-                    .add_package_from_file(
-                        &download_result.package_path,
-                        &package_id.name,
-                        &package_id.version,
-                    )
-                    .await?;
-            } else {
-                store.remove_package(&prev_hash).await?;
-            }
-        }
-    }
+    let unpacked = store.unpack_package(&download_result.package_path).await?;
 
-    if let Some(hash) = stored_package.hash() {
-        let size = stored_package.size().await?;
-        let store_path = stored_package.path().to_path_buf();
+    context.emit(AppEvent::General(GeneralEvent::DebugLog {
+        message: format!(
+            "Package {}-{} downloaded and unpacked with hash {}",
+            package_id.name,
+            package_id.version,
+            unpacked.hash().to_hex()
+        ),
+        context: std::collections::HashMap::new(),
+    }));
 
-        let prepared_package = PreparedPackage {
-            hash: hash.clone(),
-            size,
-            store_path,
+    pending_packages.insert(
+        package_id.clone(),
+        PendingPackage {
+            unpacked,
+            downloaded_bytes: download_result.size,
             is_local: false,
             package_hash: node.expected_hash.clone(),
-        };
-
-        prepared_packages.insert(package_id.clone(), prepared_package);
-
-        context.emit(AppEvent::General(GeneralEvent::DebugLog {
-            message: format!(
-                "Package {}-{} downloaded and stored with hash {} (prepared for installation)",
-                package_id.name,
-                package_id.version,
-                hash.to_hex()
-            ),
-            context: std::collections::HashMap::new(),
-        }));
-        Ok(size)
-    } else {
-        Err(InstallError::AtomicOperationFailed {
-            message: "failed to get hash from downloaded package".to_string(),
-        }
-        .into())
-    }
+            signing_key: download_result.signing_key.clone(),
+            replaces,
+        },
+    );
+    Ok(download_result.size)
 }
 
 pub(crate) async fn try_prepare_from_store(
@@ -392,6 +381,10 @@ pub(crate) async fn try_prepare_from_store(
         return Ok(None);
     };
 
+    if let Some(required) = context.required_hashes() {
+        required.check(&package_id.name, expected_hash)?;
+    }
+
     let Some(store_hash_hex) = state_manager
         .get_store_hash_for_package_hash(&expected_hash.to_hex())
         .await?
@@ -415,6 +408,14 @@ pub(crate) async fn try_prepare_from_store(
 
     let size = stored_package.size().await?;
     let store_path = stored_package.path().to_path_buf();
+    // The key is only known while the stamp from the original download holds
+    let signing_key = match trust_fingerprint(&context.download_config().keys_dir) {
+        Ok(trust) => state_manager
+            .validation_stamp(&store_hash_hex, &trust)
+            .await?
+            .and_then(|stamp| stamp.key_id),
+        Err(_) => None,
+    };
 
     let prepared_package = PreparedPackage {
         hash: store_hash,
@@ -422,6 +423,7 @@ pub(crate) async fn try_prepare_from_store(
         store_path,
         is_local: false,
         package_hash: Some(expected_hash.clone()),
+        signing_key,
     };
 
     prepared_packages.insert(package_id.clone(), prepared_package);
@@ -1,9 +1,12 @@
 //! HTTP client with connection pooling and retry logic
 
+use crate::mirrors::{MirrorGroup, MirrorHealth};
 use futures::StreamExt;
-use reqwest::{Client, Response, StatusCode};
+use reqwest::{Client, ClientBuilder, Method, NoProxy, Proxy, Response, StatusCode};
+use sps2_config::ProxyConfig;
 use sps2_errors::{Error, NetworkError};
-use std::time::Duration;
+use sps2_events::{AppEvent, EventEmitter, EventSender, GeneralEvent};
+use std::time::{Duration, SystemTime};
 
 /// Download progress information
 #[derive(Debug, Clone)]
@@ -21,7 +24,16 @@ pub struct NetConfig {
     pub pool_max_idle_per_host: usize,
     pub retry_count: u32,
     pub retry_delay: Duration,
+    /// Longest `Retry-After` wait honored on 429 and 503 responses; servers
+    /// asking for more fail the request instead
+    pub max_retry_after: Duration,
     pub user_agent: String,
+    /// Refuse all requests instead of touching the network
+    pub offline: bool,
+    /// Explicit proxy settings, replacing the proxy environment when set
+    pub proxy: ProxyConfig,
+    /// Mirror groups used for automatic failover
+    pub mirrors: Vec<MirrorGroup>,
 }
 
 impl Default for NetConfig {
@@ -33,7 +45,11 @@ impl Default for NetConfig {
             pool_max_idle_per_host: 10,
             retry_count: 3,
             retry_delay: Duration::from_secs(1),
+            max_retry_after: Duration::from_secs(60),
             user_agent: format!("sps2/{}", env!("CARGO_PKG_VERSION")),
+            offline: false,
+            proxy: ProxyConfig::default(),
+            mirrors: Vec::new(),
         }
     }
 }
@@ -43,6 +59,11 @@ impl Default for NetConfig {
 pub struct NetClient {
     client: Client,
     config: NetConfig,
+    health: MirrorHealth,
+    /// Where waits requested by servers are reported
+    events: Option<EventSender>,
+    #[cfg(any(test, feature = "fault-injection"))]
+    faults: crate::fault::NetFaults,
 }
 
 impl std::fmt::Debug for NetClient {
@@ -60,17 +81,12 @@ impl NetClient {
     ///
     /// Returns an error if the HTTP client cannot be created.
     pub fn new_without_proxies(config: NetConfig) -> Result<Self, Error> {
-        let client = Client::builder()
-            .timeout(config.timeout)
-            .connect_timeout(config.connect_timeout)
-            .pool_idle_timeout(config.pool_idle_timeout)
-            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        let client = Self::base_builder(&config)
             .no_proxy()
-            .user_agent(&config.user_agent)
             .build()
             .map_err(|e| NetworkError::ConnectionRefused(e.to_string()))?;
 
-        Ok(Self { client, config })
+        Ok(Self::from_parts(client, config))
     }
 
     /// Create a new network client
@@ -80,16 +96,57 @@ impl NetClient {
     /// Returns an error if the HTTP client cannot be created due to invalid configuration
     /// or if the underlying reqwest client fails to initialize.
     pub fn new(config: NetConfig) -> Result<Self, Error> {
-        let client = Client::builder()
+        let mut builder = Self::base_builder(&config);
+        for proxy in Self::proxies(&config.proxy)? {
+            builder = builder.proxy(proxy);
+        }
+        let client = builder
+            .build()
+            .map_err(|e| NetworkError::ConnectionRefused(e.to_string()))?;
+
+        Ok(Self::from_parts(client, config))
+    }
+
+    fn base_builder(config: &NetConfig) -> ClientBuilder {
+        Client::builder()
             .timeout(config.timeout)
             .connect_timeout(config.connect_timeout)
             .pool_idle_timeout(config.pool_idle_timeout)
             .pool_max_idle_per_host(config.pool_max_idle_per_host)
             .user_agent(&config.user_agent)
-            .build()
-            .map_err(|e| NetworkError::ConnectionRefused(e.to_string()))?;
+    }
+
+    fn from_parts(client: Client, config: NetConfig) -> Self {
+        Self {
+            client,
+            config,
+            health: MirrorHealth::default(),
+            events: None,
+            #[cfg(any(test, feature = "fault-injection"))]
+            faults: crate::fault::NetFaults::default(),
+        }
+    }
 
-        Ok(Self { client, config })
+    /// Report waits requested by servers through `Retry-After` as events
+    #[must_use]
+    pub fn with_event_sender(mut self, tx: EventSender) -> Self {
+        self.events = Some(tx);
+        self
+    }
+
+    /// Build explicit proxies; an empty list leaves the system proxy settings in effect
+    fn proxies(config: &ProxyConfig) -> Result<Vec<Proxy>, Error> {
+        let no_proxy = || NoProxy::from_string(&config.no_proxy.join(","));
+        let invalid = |e: reqwest::Error| NetworkError::InvalidUrl(format!("proxy: {e}"));
+
+        let mut proxies = Vec::new();
+        if let Some(url) = &config.http {
+            proxies.push(Proxy::http(url).map_err(invalid)?.no_proxy(no_proxy()));
+        }
+        if let Some(url) = &config.https {
+            proxies.push(Proxy::https(url).map_err(invalid)?.no_proxy(no_proxy()));
+        }
+        Ok(proxies)
     }
 
     /// Create with default configuration
@@ -108,7 +165,7 @@ impl NetClient {
     /// Returns an error if the request fails after all retry attempts, including
     /// network timeouts, connection failures, or server errors.
     pub async fn get(&self, url: &str) -> Result<Response, Error> {
-        self.retry_request(|| self.client.get(url).send()).await
+        self.send_with_failover(Method::GET, url, &[]).await
     }
 
     /// Execute a GET request with custom headers and retries
@@ -122,18 +179,15 @@ impl NetClient {
         url: &str,
         headers: &[(&str, &str)],
     ) -> Result<Response, Error> {
-        self.retry_request(|| {
-            let mut request = self.client.get(url);
-            for (key, value) in headers {
-                request = request.header(*key, *value);
-            }
-            request.send()
-        })
-        .await
+        self.send_with_failover(Method::GET, url, headers).await
     }
 
     /// Execute a GET request with Range header for partial content
     ///
+    /// Fails over to mirrors like [`Self::get`]; a mirror that cannot
+    /// satisfy the range, as when it holds an older copy of the file, counts
+    /// as failed.
+    ///
     /// # Errors
     ///
     /// Returns an error if the request fails after all retry attempts, including
@@ -149,7 +203,8 @@ impl NetClient {
             None => format!("bytes={start_byte}-"),
         };
 
-        self.get_with_headers(url, &[("Range", &range_value)]).await
+        self.send_with_failover(Method::GET, url, &[("Range", &range_value)])
+            .await
     }
 
     /// Check if server supports range requests
@@ -174,7 +229,101 @@ impl NetClient {
     /// Returns an error if the request fails after all retry attempts, including
     /// network timeouts, connection failures, or server errors.
     pub async fn head(&self, url: &str) -> Result<Response, Error> {
-        self.retry_request(|| self.client.head(url).send()).await
+        self.send_with_failover(Method::HEAD, url, &[]).await
+    }
+
+    /// Whether this client refuses network access
+    #[must_use]
+    pub fn is_offline(&self) -> bool {
+        self.config.offline
+    }
+
+    /// Faults injected into requests made by this client and its clones
+    #[cfg(any(test, feature = "fault-injection"))]
+    #[must_use]
+    pub fn faults(&self) -> &crate::fault::NetFaults {
+        &self.faults
+    }
+
+    /// Penalize the mirror that served `url` after its content failed verification
+    ///
+    /// The next request for the same path prefers another member of the group.
+    pub(crate) fn report_corrupt(&self, url: &str) {
+        self.health.record_url(&self.config.mirrors, url, false);
+    }
+
+    /// Send a request, failing over to mirrors of the URL's repository
+    ///
+    /// Each candidate is retried according to the retry policy before moving
+    /// on. Connection errors, server errors, missing files and unsatisfiable
+    /// ranges count as mirror failures; the outcome of the last candidate is
+    /// returned as-is.
+    async fn send_with_failover(
+        &self,
+        method: Method,
+        url: &str,
+        headers: &[(&str, &str)],
+    ) -> Result<Response, Error> {
+        self.ensure_online(url)?;
+
+        let mut last_result = None;
+        for candidate in self.health.candidates(&self.config.mirrors, url) {
+            let result = match self.injected_fault(&candidate.url) {
+                Some(err) => Err(err),
+                None => {
+                    self.retry_request(|| {
+                        let mut request = self.client.request(method.clone(), &candidate.url);
+                        for (key, value) in headers {
+                            request = request.header(*key, *value);
+                        }
+                        request.send()
+                    })
+                    .await
+                }
+            };
+
+            let failed = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == StatusCode::NOT_FOUND
+                        || response.status() == StatusCode::RANGE_NOT_SATISFIABLE
+                }
+                Err(_) => true,
+            };
+            if let Some(base) = &candidate.base {
+                self.health.record(base, !failed);
+            }
+            if !failed {
+                return result;
+            }
+            last_result = Some(result);
+        }
+
+        last_result.unwrap_or_else(|| {
+            Err(NetworkError::DownloadFailed(format!("no candidate URL for {url}")).into())
+        })
+    }
+
+    #[cfg(any(test, feature = "fault-injection"))]
+    fn injected_fault(&self, url: &str) -> Option<Error> {
+        self.faults.check(url)
+    }
+
+    #[cfg(not(any(test, feature = "fault-injection")))]
+    #[allow(clippy::unused_self)]
+    fn injected_fault(&self, _url: &str) -> Option<Error> {
+        None
+    }
+
+    /// Fail fast instead of waiting on timeouts when offline mode is enabled
+    fn ensure_online(&self, url: &str) -> Result<(), Error> {
+        if self.config.offline {
+            return Err(NetworkError::Offline {
+                url: url.to_string(),
+            }
+            .into());
+        }
+        Ok(())
     }
 
     /// Download file with progress callback
@@ -215,35 +364,50 @@ impl NetClient {
     }
 
     /// Execute a request with retries
+    ///
+    /// A 429 or 503 response carrying `Retry-After` is retried after the
+    /// requested wait, reported as a warning event, while attempts remain and
+    /// the wait is within `max_retry_after`. Otherwise a 429 fails with
+    /// [`NetworkError::RateLimited`] and a 503 is returned as is.
     async fn retry_request<F, Fut>(&self, mut f: F) -> Result<Response, Error>
     where
         F: FnMut() -> Fut,
         Fut: std::future::Future<Output = Result<Response, reqwest::Error>>,
     {
         let mut last_error = None;
+        let mut waited = false;
 
         for attempt in 0..=self.config.retry_count {
-            if attempt > 0 {
+            if attempt > 0 && !waited {
                 tokio::time::sleep(self.config.retry_delay * attempt).await;
             }
+            waited = false;
 
             match f().await {
                 Ok(response) => {
-                    // Check for rate limiting
-                    if response.status() == StatusCode::TOO_MANY_REQUESTS {
-                        if let Some(retry_after) = response
-                            .headers()
-                            .get("retry-after")
-                            .and_then(|v| v.to_str().ok())
-                            .and_then(|s| s.parse::<u64>().ok())
-                        {
-                            return Err(NetworkError::RateLimited {
-                                seconds: retry_after,
-                            }
-                            .into());
-                        }
+                    let status = response.status();
+                    if status != StatusCode::TOO_MANY_REQUESTS
+                        && status != StatusCode::SERVICE_UNAVAILABLE
+                    {
+                        return Ok(response);
                     }
+                    let Some(wait) = retry_after(response.headers()) else {
+                        return Ok(response);
+                    };
 
+                    if attempt < self.config.retry_count && wait <= self.config.max_retry_after {
+                        self.report_wait(&response, wait);
+                        tokio::time::sleep(wait).await;
+                        waited = true;
+                        continue;
+                    }
+                    if status == StatusCode::TOO_MANY_REQUESTS {
+                        return Err(NetworkError::RateLimited {
+                            seconds: wait.as_secs(),
+                            request_id: crate::problem::request_id(response.headers()),
+                        }
+                        .into());
+                    }
                     return Ok(response);
                 }
                 Err(e) => {
@@ -272,6 +436,23 @@ impl NetClient {
         }
     }
 
+    /// Tell the user why a request is paused
+    fn report_wait(&self, response: &Response, wait: Duration) {
+        let Some(tx) = &self.events else {
+            return;
+        };
+        let host = response.url().host_str().unwrap_or("server");
+        let status = response.status().as_u16();
+        let context = match crate::problem::request_id(response.headers()) {
+            Some(id) => format!("HTTP {status}, request ID {id}"),
+            None => format!("HTTP {status}"),
+        };
+        tx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            format!("{host} asked to retry in {}s, waiting", wait.as_secs()),
+            context,
+        )));
+    }
+
     /// Determine if an error should be retried
     fn should_retry(error: &reqwest::Error) -> bool {
         // Retry on timeout, connection errors, and server errors
@@ -286,3 +467,112 @@ impl NetClient {
         &self.client
     }
 }
+
+/// Wait requested by a `Retry-After` header, in seconds or as an HTTP date
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(
+        at.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::MockServer;
+
+    #[tokio::test]
+    async fn offline_client_refuses_requests_without_sending_them() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/index.json");
+                then.status(200).body("{}");
+            })
+            .await;
+
+        let client = NetClient::new_without_proxies(NetConfig {
+            offline: true,
+            ..NetConfig::default()
+        })
+        .unwrap();
+        assert!(client.is_offline());
+
+        let url = server.url("/index.json");
+        for result in [
+            client.get(&url).await,
+            client.head(&url).await,
+            client.get_range(&url, 0, Some(1)).await,
+        ] {
+            assert!(matches!(
+                result.unwrap_err(),
+                Error::Network(NetworkError::Offline { url: ref offline }) if *offline == url
+            ));
+        }
+        let (tx, _rx) = sps2_events::channel();
+        assert!(matches!(
+            crate::fetch_text(&client, &url, &tx).await.unwrap_err(),
+            Error::Network(NetworkError::Offline { .. })
+        ));
+        assert_eq!(mock.calls_async().await, 0);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_waited_out_and_reported() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/index.json");
+                then.status(429)
+                    .header("retry-after", "0")
+                    .header("x-request-id", "req-42");
+            })
+            .await;
+
+        let (tx, mut rx) = sps2_events::channel();
+        let client = NetClient::new_without_proxies(NetConfig {
+            retry_count: 1,
+            retry_delay: Duration::ZERO,
+            ..NetConfig::default()
+        })
+        .unwrap()
+        .with_event_sender(tx);
+
+        let err = client.get(&server.url("/index.json")).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Network(NetworkError::RateLimited { seconds: 0, ref request_id })
+                if request_id.as_deref() == Some("req-42")
+        ));
+        assert_eq!(mock.calls_async().await, 2);
+
+        let message = rx.try_recv().unwrap();
+        assert!(matches!(
+            message.event,
+            AppEvent::General(GeneralEvent::Warning { ref context, .. })
+                if context.as_deref() == Some("HTTP 429, request ID req-42")
+        ));
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_dates() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(7)));
+
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+}
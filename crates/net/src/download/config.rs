@@ -1,5 +1,6 @@
 //! Configuration structures for package downloads
 
+use crate::download_cache::DownloadCache;
 use sps2_hash::Hash;
 use sps2_types::Version;
 use std::path::PathBuf;
@@ -25,6 +26,13 @@ pub struct PackageDownloadConfig {
     pub min_chunk_size: u64,
     /// Resource manager
     pub resources: Arc<ResourceManager>,
+    /// Directory corrupted downloads are moved to
+    pub quarantine_dir: PathBuf,
+    /// Directory holding the trusted keys signatures are checked against
+    pub keys_dir: PathBuf,
+    /// Cache verified packages are kept in and looked up from; `None`
+    /// always downloads
+    pub cache: Option<DownloadCache>,
 }
 
 impl Default for PackageDownloadConfig {
@@ -37,6 +45,9 @@ impl Default for PackageDownloadConfig {
             chunk_timeout: Duration::from_secs(30),
             min_chunk_size: 1024 * 1024, // 1MB
             resources: Arc::new(ResourceManager::default()),
+            quarantine_dir: PathBuf::from(sps2_config::fixed_paths::QUARANTINE_DIR),
+            keys_dir: PathBuf::from(sps2_config::fixed_paths::KEYS_DIR),
+            cache: None,
         }
     }
 }
@@ -87,12 +98,13 @@ pub struct PackageDownloadResult {
     pub size: u64,
     pub download_time: Duration,
     pub signature_verified: bool,
+    /// Id of the trusted key that verified the signature
+    pub signing_key: Option<String>,
 }
 
 /// Parameters for streaming download with unified progress tracking
 pub(super) struct StreamParams<'a> {
     pub total_size: u64,
-    pub expected_hash: Option<&'a Hash>,
     pub event_sender: &'a sps2_events::EventSender,
     /// URL being downloaded - used for timeout error reporting
     pub url: &'a str,
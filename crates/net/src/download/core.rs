@@ -4,14 +4,17 @@ use super::config::{
     DownloadResult, PackageDownloadConfig, PackageDownloadRequest, PackageDownloadResult,
     StreamParams,
 };
+use super::local::{copy_local_file, file_url_path, url_file_name};
 use super::resume::get_resume_offset;
 use super::retry::calculate_backoff_delay;
 use super::stream::{download_file_simple, stream_download};
 use super::validation::{validate_response, validate_url};
 use crate::client::{NetClient, NetConfig};
+use crate::quarantine::{now_secs, quarantine_file, QuarantineRecord};
 use sps2_errors::{Error, NetworkError, SigningError};
 use sps2_events::{
-    AppEvent, EventEmitter, EventSender, FailureContext, GeneralEvent, LifecycleEvent,
+    AppEvent, DownloadIntegrity, EventEmitter, EventSender, FailureContext, GeneralEvent,
+    LifecycleEvent,
 };
 use sps2_hash::Hash;
 use sps2_types::Version;
@@ -54,6 +57,22 @@ impl PackageDownloader {
         })
     }
 
+    /// Create a package downloader that shares an existing network client
+    ///
+    /// The client's proxy, mirror and offline settings apply to all downloads.
+    #[must_use]
+    pub fn with_client(
+        config: PackageDownloadConfig,
+        client: NetClient,
+        progress_manager: sps2_events::ProgressManager,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            progress_manager,
+        }
+    }
+
     /// Create with default configuration
     ///
     /// # Errors
@@ -86,11 +105,8 @@ impl PackageDownloader {
 
         // Create destination paths
         // Extract filename from URL instead of constructing it
-        let package_filename = package_url
-            .split('/')
-            .next_back()
-            .unwrap_or(&format!("{package_name}-{version}.sp"))
-            .to_string();
+        let package_filename = url_file_name(package_url)
+            .map_or_else(|| format!("{package_name}-{version}.sp"), str::to_string);
         let package_path = dest_dir.join(&package_filename);
         let signature_path =
             signature_url.map(|_| dest_dir.join(format!("{package_filename}.minisig")));
@@ -98,6 +114,20 @@ impl PackageDownloader {
         // Ensure destination directory exists
         tokio_fs::create_dir_all(dest_dir).await?;
 
+        if let Some(cached) = self
+            .copy_from_cache(
+                package_name,
+                package_url,
+                &package_path,
+                signature_path.as_deref(),
+                expected_hash,
+                tx,
+            )
+            .await?
+        {
+            return Ok(cached);
+        }
+
         // Download package and signature concurrently
         // Create progress tracker if not provided
         let tracker_id = if progress_tracker_id.is_empty() {
@@ -137,29 +167,121 @@ impl PackageDownloader {
         let download_time = start_time.elapsed();
 
         // Verify signature if available
-        let signature_verified = if let Some(sig_path) = &signature_path {
-            if sig_path.exists() {
+        let signing_key = match &signature_path {
+            Some(sig_path) if sig_path.exists() => {
                 self.verify_package_signature(&package_path, sig_path)
                     .await?
-            } else {
-                false
             }
-        } else {
-            false
+            _ => None,
         };
 
+        // Only downloads verified against the index are cached, and local
+        // repositories need no cache
+        let remote = file_url_path(package_url).is_none();
+        if let (true, Some(cache), Some(expected)) = (remote, &self.config.cache, expected_hash) {
+            if let Err(e) = cache
+                .insert(expected, &package_path, signature_path.as_deref())
+                .await
+            {
+                tx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!("Failed to cache the download of {package_name}"),
+                    e.to_string(),
+                )));
+            }
+        }
+
         Ok(PackageDownloadResult {
             package_path,
             signature_path,
             hash: package_result.hash,
             size: package_result.size,
             download_time,
-            signature_verified,
+            signature_verified: signing_key.is_some(),
+            signing_key,
         })
     }
 
+    /// Copy the package with `expected_hash` out of the download cache
+    ///
+    /// The copy is hashed again and, when a signature is expected, checked
+    /// against the cached signature; entries failing either are dropped
+    /// from the cache. Returns `None` when the package has to be downloaded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a cached file cannot be copied or hashed.
+    async fn copy_from_cache(
+        &self,
+        package_name: &str,
+        package_url: &str,
+        package_path: &Path,
+        signature_path: Option<&Path>,
+        expected_hash: Option<&Hash>,
+        tx: &EventSender,
+    ) -> Result<Option<PackageDownloadResult>, Error> {
+        let (Some(cache), Some(expected)) = (&self.config.cache, expected_hash) else {
+            return Ok(None);
+        };
+        let Some(cached) = cache.lookup(expected).await else {
+            return Ok(None);
+        };
+        if signature_path.is_some() && cached.signature_path.is_none() {
+            return Ok(None);
+        }
+
+        let start_time = Instant::now();
+        tokio_fs::copy(&cached.path, package_path).await?;
+        let hash = Hash::blake3_hash_file(package_path).await?;
+        let signing_key = if hash == *expected {
+            match (signature_path, &cached.signature_path) {
+                (Some(dest), Some(source)) => {
+                    tokio_fs::copy(source, dest).await?;
+                    self.verify_package_signature(package_path, dest).await
+                }
+                _ => Ok(None),
+            }
+        } else {
+            Err(NetworkError::ChecksumMismatch {
+                expected: expected.to_hex(),
+                actual: hash.to_hex(),
+            }
+            .into())
+        };
+        let signing_key = match signing_key {
+            Ok(signing_key) => signing_key,
+            Err(e) => {
+                cache.remove(expected).await;
+                let _ = tokio_fs::remove_file(package_path).await;
+                tx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!("Dropped the cached download of {package_name}"),
+                    e.to_string(),
+                )));
+                return Ok(None);
+            }
+        };
+
+        let size = tokio_fs::metadata(package_path).await?.len();
+        tx.emit(AppEvent::Lifecycle(LifecycleEvent::download_cached(
+            package_url.to_string(),
+            Some(package_name.to_string()),
+            size,
+        )));
+        Ok(Some(PackageDownloadResult {
+            package_path: package_path.to_path_buf(),
+            signature_path: signature_path.map(Path::to_path_buf),
+            hash,
+            size,
+            download_time: start_time.elapsed(),
+            signature_verified: signing_key.is_some(),
+            signing_key,
+        }))
+    }
+
     /// Verify the signature of a downloaded package
     ///
+    /// Returns the id of the trusted key that made the signature, or `None`
+    /// when no trusted key matches.
+    ///
     /// # Errors
     ///
     /// Returns an error if signature file cannot be read, trusted keys cannot be loaded,
@@ -168,7 +290,7 @@ impl PackageDownloader {
         &self,
         package_path: &Path,
         signature_path: &Path,
-    ) -> Result<bool, Error> {
+    ) -> Result<Option<String>, Error> {
         // Read signature file
         let sig_str = tokio::fs::read_to_string(signature_path)
             .await
@@ -179,9 +301,8 @@ impl PackageDownloader {
                 ))
             })?;
 
-        // Load trusted keys from the standard location
-        let keys_dir = std::path::Path::new(sps2_config::fixed_paths::KEYS_DIR);
-        let keys_file = keys_dir.join("trusted_keys.json");
+        // Load trusted keys from the configured location
+        let keys_file = self.config.keys_dir.join("trusted_keys.json");
 
         let mut allowed = Vec::new();
 
@@ -211,8 +332,8 @@ impl PackageDownloader {
             },
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
                 // No trusted keys file - this is a warning condition, not an error
-                // Return false to indicate signature could not be verified
-                return Ok(false);
+                // Return None to indicate signature could not be verified
+                return Ok(None);
             }
             Err(e) => {
                 return Err(NetworkError::DownloadFailed(format!(
@@ -225,16 +346,16 @@ impl PackageDownloader {
 
         // If no keys were found in the file, we cannot verify
         if allowed.is_empty() {
-            return Ok(false);
+            return Ok(None);
         }
 
         // Perform signature verification with the loaded keys
         match crate::signing::verify_minisign_file_with_keys(package_path, &sig_str, &allowed) {
-            Ok(_key_id) => Ok(true),
+            Ok(key_id) => Ok(Some(key_id)),
             Err(Error::Signing(SigningError::NoTrustedKeyFound { .. })) => {
                 // Key ID from signature doesn't match any of our trusted keys
-                // This is not necessarily an error - return false to indicate unverified
-                Ok(false)
+                // This is not necessarily an error - return None to indicate unverified
+                Ok(None)
             }
             Err(e) => {
                 // Actual verification failure (signature mismatch, invalid format, etc.)
@@ -365,6 +486,12 @@ impl PackageDownloader {
         tx: EventSender,
     ) -> Result<DownloadResult, Error> {
         let url = validate_url(url)?;
+        if let Some(source) = file_url_path(&url) {
+            return self
+                .copy_local_package(&source, &url, dest_path, expected_hash, package, &tx)
+                .await;
+        }
+
         let mut retry_count = 0;
         #[allow(unused_assignments)] // Used after retry loop for error reporting
         let mut last_error: Option<Error> = None;
@@ -483,7 +610,7 @@ impl PackageDownloader {
         };
 
         // Validate response
-        validate_response(&response, resume_offset > 0)?;
+        let response = validate_response(response, resume_offset > 0).await?;
 
         // Get total size information
         let content_length = response.content_length().unwrap_or(0);
@@ -510,10 +637,12 @@ impl PackageDownloader {
             Some(total_size),
         )));
 
+        // Mirror failover may have served the request from another base URL
+        let served_url = response.url().to_string();
+
         // Download with streaming and progress
         let params = StreamParams {
             total_size,
-            expected_hash,
             event_sender: tx,
             url,
             progress_tracker_id,
@@ -523,6 +652,21 @@ impl PackageDownloader {
         let result =
             stream_download(&self.config, response, dest_path, resume_offset, &params).await?;
 
+        if let Some(expected) = expected_hash {
+            if result.hash != *expected {
+                return Err(self
+                    .reject_corrupt_download(
+                        dest_path,
+                        &served_url,
+                        package,
+                        expected,
+                        &result.hash,
+                        tx,
+                    )
+                    .await);
+            }
+        }
+
         tx.emit(AppEvent::Lifecycle(LifecycleEvent::download_completed(
             url.to_string(),
             package.map(str::to_string),
@@ -533,6 +677,117 @@ impl PackageDownloader {
     }
 }
 
+impl PackageDownloader {
+    /// Copy a package out of a local repository directory
+    ///
+    /// Local files are not retried; a hash mismatch is quarantined like a
+    /// corrupted download.
+    async fn copy_local_package(
+        &self,
+        source: &Path,
+        url: &str,
+        dest_path: &Path,
+        expected_hash: Option<&Hash>,
+        package: Option<String>,
+        tx: &EventSender,
+    ) -> Result<DownloadResult, Error> {
+        tx.emit(AppEvent::Lifecycle(LifecycleEvent::download_started(
+            url.to_string(),
+            package.clone(),
+            tokio_fs::metadata(source).await.ok().map(|m| m.len()),
+        )));
+
+        let result = match copy_local_file(source, dest_path).await {
+            Ok(result) => result,
+            Err(e) => {
+                tx.emit(AppEvent::Lifecycle(LifecycleEvent::download_failed(
+                    url.to_string(),
+                    package,
+                    FailureContext::from_error(&e),
+                )));
+                return Err(e);
+            }
+        };
+        if let Some(expected) = expected_hash {
+            if result.hash != *expected {
+                return Err(self
+                    .reject_corrupt_download(
+                        dest_path,
+                        url,
+                        package.as_deref(),
+                        expected,
+                        &result.hash,
+                        tx,
+                    )
+                    .await);
+            }
+        }
+
+        tx.emit(AppEvent::Lifecycle(LifecycleEvent::download_completed(
+            url.to_string(),
+            package,
+            result.size,
+        )));
+        Ok(result)
+    }
+
+    /// Quarantine a download whose hash does not match and report it
+    ///
+    /// The serving mirror is penalized so that the retry prefers another
+    /// mirror of the repository. Returns the checksum error to propagate.
+    async fn reject_corrupt_download(
+        &self,
+        dest_path: &Path,
+        url: &str,
+        package: Option<&str>,
+        expected: &Hash,
+        actual: &Hash,
+        tx: &EventSender,
+    ) -> Error {
+        self.client.report_corrupt(url);
+
+        let record = QuarantineRecord {
+            url: url.to_string(),
+            package: package.map(str::to_string),
+            expected_hash: expected.to_hex(),
+            actual_hash: actual.to_hex(),
+            quarantined_at: now_secs(),
+        };
+        let quarantine_path =
+            match quarantine_file(&self.config.quarantine_dir, dest_path, &record).await {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    // Never leave corrupted content where a resume could pick it up
+                    let _ = tokio_fs::remove_file(dest_path).await;
+                    tx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                        "Failed to quarantine corrupted download",
+                        e.to_string(),
+                    )));
+                    None
+                }
+            };
+
+        let error: Error = NetworkError::ChecksumMismatch {
+            expected: record.expected_hash.clone(),
+            actual: record.actual_hash.clone(),
+        }
+        .into();
+        tx.emit(AppEvent::Lifecycle(
+            LifecycleEvent::download_verification_failed(
+                record.url,
+                record.package,
+                DownloadIntegrity {
+                    expected_hash: record.expected_hash,
+                    actual_hash: record.actual_hash,
+                    quarantine_path,
+                },
+                FailureContext::from_error(&error),
+            ),
+        ));
+        error
+    }
+}
+
 impl Clone for PackageDownloader {
     fn clone(&self) -> Self {
         Self {
@@ -0,0 +1,80 @@
+//! Package files served from a local repository directory
+
+use super::config::DownloadResult;
+use sps2_errors::{Error, NetworkError};
+use sps2_hash::Hash;
+use std::path::{Path, PathBuf};
+use tokio::fs as tokio_fs;
+
+/// Path a `file://` URL points at, or `None` for any other URL
+#[must_use]
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+    let url = url::Url::parse(url).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    url.to_file_path().ok()
+}
+
+/// Name of the file `url` points at, without its query or fragment
+///
+/// Downloads are saved under this name, and local repositories look
+/// packages up by it.
+#[must_use]
+pub fn url_file_name(url: &str) -> Option<&str> {
+    let name = url.split(['?', '#']).next()?.rsplit('/').next()?;
+    (!name.is_empty()).then_some(name)
+}
+
+/// `file://` URL of an absolute path
+#[must_use]
+pub fn file_url(path: &Path) -> Option<String> {
+    url::Url::from_file_path(path).ok().map(String::from)
+}
+
+/// Copy a local file to `dest_path`, hashing the copy
+pub(super) async fn copy_local_file(
+    source: &Path,
+    dest_path: &Path,
+) -> Result<DownloadResult, Error> {
+    let size = tokio_fs::copy(source, dest_path).await.map_err(|e| {
+        NetworkError::DownloadFailed(format!("Failed to copy {}: {e}", source.display()))
+    })?;
+    let hash = Hash::blake3_hash_file(dest_path).await?;
+    Ok(DownloadResult { hash, size })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_file_urls_have_paths() {
+        assert_eq!(
+            file_url_path("file:///media/usb/repo/jq-1.7.0-1.arm64.sp"),
+            Some(PathBuf::from("/media/usb/repo/jq-1.7.0-1.arm64.sp"))
+        );
+        assert_eq!(file_url_path("https://cdn.example.com/jq.sp"), None);
+        assert_eq!(file_url_path("not a url"), None);
+    }
+
+    #[test]
+    fn file_names_leave_out_query_and_fragment() {
+        for url in [
+            "https://cdn.example.com/jq-1.7.0-1.arm64.sp",
+            "https://cdn.example.com/jq-1.7.0-1.arm64.sp?token=abc",
+            "https://cdn.example.com/jq-1.7.0-1.arm64.sp#sha",
+        ] {
+            assert_eq!(url_file_name(url), Some("jq-1.7.0-1.arm64.sp"), "{url}");
+        }
+        assert_eq!(url_file_name("https://cdn.example.com/"), None);
+    }
+
+    #[test]
+    fn file_urls_round_trip_paths_with_spaces() {
+        let path = Path::new("/Volumes/USB DRIVE/repo/jq-1.7.0-1.arm64.sp");
+        let url = file_url(path).unwrap();
+        assert_eq!(url, "file:///Volumes/USB%20DRIVE/repo/jq-1.7.0-1.arm64.sp");
+        assert_eq!(file_url_path(&url).as_deref(), Some(path));
+    }
+}
@@ -5,6 +5,7 @@
 
 mod config;
 mod core;
+mod local;
 mod resume;
 mod retry;
 mod stream;
@@ -16,3 +17,4 @@ pub use config::{
     RetryConfig,
 };
 pub use core::PackageDownloader;
+pub use local::{file_url, file_url_path, url_file_name};
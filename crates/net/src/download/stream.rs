@@ -101,25 +101,6 @@ fn report_progress(params: &StreamParams<'_>, current_downloaded: u64) {
     }
 }
 
-/// Verify download hash matches expected
-fn verify_hash(
-    final_hash: &Hash,
-    expected_hash: Option<&Hash>,
-    dest_path: &Path,
-) -> Result<(), Error> {
-    if let Some(expected) = expected_hash {
-        if final_hash != expected {
-            let _ = std::fs::remove_file(dest_path);
-            return Err(NetworkError::ChecksumMismatch {
-                expected: expected.to_hex(),
-                actual: final_hash.to_hex(),
-            }
-            .into());
-        }
-    }
-    Ok(())
-}
-
 /// Stream download with progress reporting and hash calculation
 pub(super) async fn stream_download(
     config: &super::config::PackageDownloadConfig,
@@ -181,12 +162,10 @@ pub(super) async fn stream_download(
     let final_downloaded = downloaded.load(Ordering::Relaxed);
     report_progress(params, final_downloaded);
 
-    let final_hash = Hash::from_blake3_bytes(*hasher.finalize().as_bytes());
-    verify_hash(&final_hash, params.expected_hash, dest_path)?;
-
-    // Lock guard automatically cleaned up on drop
+    // Hash verification is left to the caller, which decides what happens to
+    // corrupted content. Lock guard automatically cleaned up on drop
     Ok(DownloadResult {
-        hash: final_hash,
+        hash: Hash::from_blake3_bytes(*hasher.finalize().as_bytes()),
         size: final_downloaded,
     })
 }
@@ -198,14 +177,15 @@ pub(super) async fn download_file_simple(
     dest_path: &Path,
     _tx: &sps2_events::EventSender,
 ) -> Result<(), Error> {
+    if let Some(source) = super::local::file_url_path(url) {
+        super::local::copy_local_file(&source, dest_path).await?;
+        return Ok(());
+    }
+
     let response = client.get(url).await?;
 
     if !response.status().is_success() {
-        return Err(NetworkError::HttpError {
-            status: response.status().as_u16(),
-            message: response.status().to_string(),
-        }
-        .into());
+        return Err(crate::problem::http_error(response).await);
     }
 
     let content = response
@@ -16,11 +16,11 @@ pub(super) fn validate_url(url: &str) -> Result<String, Error> {
     }
 }
 
-/// Validate HTTP response for download
-pub(super) fn validate_response(
-    response: &reqwest::Response,
+/// Validate HTTP response for download, handing it back if usable
+pub(super) async fn validate_response(
+    response: reqwest::Response,
     is_resume: bool,
-) -> Result<(), Error> {
+) -> Result<reqwest::Response, Error> {
     let status = response.status();
 
     if is_resume {
@@ -28,12 +28,8 @@ pub(super) fn validate_response(
             return Err(NetworkError::PartialContentNotSupported.into());
         }
     } else if !status.is_success() {
-        return Err(NetworkError::HttpError {
-            status: status.as_u16(),
-            message: status.to_string(),
-        }
-        .into());
+        return Err(crate::problem::http_error(response).await);
     }
 
-    Ok(())
+    Ok(response)
 }
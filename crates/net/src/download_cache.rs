@@ -0,0 +1,357 @@
+//! Cache of verified package downloads
+//!
+//! Archives whose hash matched the index are kept by their BLAKE3 hash, as
+//! `<hash>.sp` with the signature next to it as `<hash>.sp.minisig`, so
+//! installing the same package again, e.g. rolling forward after a rollback
+//! once cleanup evicted it from the store, does not fetch it again. A file's
+//! modification time records when it was last used; pruning removes entries
+//! unused for longer than the retention period and then the least recently
+//! used ones until the cache fits its size limit.
+
+use sps2_errors::{Error, StorageError};
+use sps2_hash::Hash;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+/// Extension of cached archives
+const PACKAGE_EXTENSION: &str = "sp";
+
+/// Extension of the signature cached next to an archive
+const SIGNATURE_EXTENSION: &str = "sp.minisig";
+
+/// Directory of cached downloads and its retention policy
+#[derive(Debug, Clone)]
+pub struct DownloadCache {
+    dir: PathBuf,
+    max_age: Duration,
+    max_bytes: u64,
+}
+
+/// A cached archive
+#[derive(Debug, Clone)]
+pub struct CachedDownload {
+    pub hash: Hash,
+    pub path: PathBuf,
+    /// `None` if no signature was downloaded with the archive
+    pub signature_path: Option<PathBuf>,
+    /// Size of the archive and its signature
+    pub size: u64,
+    pub last_used: SystemTime,
+}
+
+impl DownloadCache {
+    /// Cache in `dir` keeping downloads for `max_age` after their last use
+    /// and at most `max_bytes` in total
+    #[must_use]
+    pub fn new(dir: PathBuf, max_age: Duration, max_bytes: u64) -> Self {
+        Self {
+            dir,
+            max_age,
+            max_bytes,
+        }
+    }
+
+    /// Directory the downloads are cached in
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The cached archive with `hash`, marked as used
+    ///
+    /// Only BLAKE3 hashes are cached. The archive is not verified here;
+    /// callers hash what they copy out of the cache.
+    pub async fn lookup(&self, hash: &Hash) -> Option<CachedDownload> {
+        if !hash.is_blake3() {
+            return None;
+        }
+        let path = self.package_path(hash);
+        let metadata = fs::metadata(&path).await.ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+        let signature_path = signature_path(&path);
+        let signature_size = fs::metadata(&signature_path).await.ok().map(|m| m.len());
+
+        let now = SystemTime::now();
+        touch(&path, now).await;
+        Some(CachedDownload {
+            hash: hash.clone(),
+            signature_path: signature_size.map(|_| signature_path),
+            path,
+            size: metadata.len() + signature_size.unwrap_or(0),
+            last_used: now,
+        })
+    }
+
+    /// Copy a verified archive with `hash`, and its signature if there is
+    /// one, into the cache and prune it
+    ///
+    /// On APFS the copies are clones and take no extra space until the
+    /// downloaded file is removed. Archives whose hash is not BLAKE3 are
+    /// not cached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be created or a file
+    /// cannot be copied into it.
+    pub async fn insert(
+        &self,
+        hash: &Hash,
+        package_path: &Path,
+        signature: Option<&Path>,
+    ) -> Result<(), Error> {
+        if !hash.is_blake3() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| StorageError::IoError {
+                message: format!(
+                    "failed to create download cache {}: {e}",
+                    self.dir.display()
+                ),
+            })?;
+
+        let target = self.package_path(hash);
+        // The signature goes first so an archive is never cached without
+        // the signature it was verified with
+        match signature {
+            Some(signature) => copy_into(signature, &signature_path(&target)).await?,
+            None => {
+                let _ = fs::remove_file(signature_path(&target)).await;
+            }
+        }
+        copy_into(package_path, &target).await?;
+        self.prune().await?;
+        Ok(())
+    }
+
+    /// Drop the cached archive with `hash`, e.g. after it failed
+    /// verification
+    pub async fn remove(&self, hash: &Hash) {
+        let path = self.package_path(hash);
+        let _ = fs::remove_file(&path).await;
+        let _ = fs::remove_file(signature_path(&path)).await;
+    }
+
+    /// List the cached archives, least recently used first
+    ///
+    /// A missing cache directory is treated as empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read.
+    pub async fn list(&self) -> Result<Vec<CachedDownload>, Error> {
+        let mut dir = match fs::read_dir(&self.dir).await {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            let Some(hash) = cached_hash(&path) else {
+                continue;
+            };
+            let metadata = entry.metadata().await?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let signature_path = signature_path(&path);
+            let signature_size = fs::metadata(&signature_path).await.ok().map(|m| m.len());
+            entries.push(CachedDownload {
+                hash,
+                signature_path: signature_size.map(|_| signature_path),
+                path,
+                size: metadata.len() + signature_size.unwrap_or(0),
+                last_used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            });
+        }
+
+        entries.sort_by(|a, b| a.last_used.cmp(&b.last_used).then(a.path.cmp(&b.path)));
+        Ok(entries)
+    }
+
+    /// Cached archives unused for longer than the retention period, then
+    /// the least recently used ones until the cache fits its size limit
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read.
+    pub async fn prunable(&self) -> Result<Vec<CachedDownload>, Error> {
+        let entries = self.list().await?;
+        let now = SystemTime::now();
+        let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+        let mut prunable = Vec::new();
+        for entry in entries {
+            let expired = now
+                .duration_since(entry.last_used)
+                .is_ok_and(|age| age >= self.max_age);
+            if expired || total > self.max_bytes {
+                total -= entry.size;
+                prunable.push(entry);
+            }
+        }
+        Ok(prunable)
+    }
+
+    /// Remove the [`prunable`](Self::prunable) archives
+    ///
+    /// Returns the number of archives removed and the bytes freed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read or a file
+    /// cannot be removed.
+    pub async fn prune(&self) -> Result<(usize, u64), Error> {
+        remove_entries(&self.prunable().await?).await
+    }
+
+    /// Remove every cached archive
+    ///
+    /// Returns the number of archives removed and the bytes freed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cache directory cannot be read or a file
+    /// cannot be removed.
+    pub async fn clear(&self) -> Result<(usize, u64), Error> {
+        remove_entries(&self.list().await?).await
+    }
+
+    fn package_path(&self, hash: &Hash) -> PathBuf {
+        self.dir
+            .join(format!("{}.{PACKAGE_EXTENSION}", hash.to_hex()))
+    }
+}
+
+/// Copy `source` to `target` through a temporary file, so an interrupted
+/// copy, or two downloads of the same package caching it at once, never
+/// leave a truncated entry behind
+async fn copy_into(source: &Path, target: &Path) -> Result<(), Error> {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(format!(".{:016x}.partial", rand::random::<u64>()));
+    let partial = PathBuf::from(partial);
+    let copied = async {
+        fs::copy(source, &partial).await?;
+        fs::rename(&partial, target).await
+    }
+    .await;
+    if let Err(e) = copied {
+        let _ = fs::remove_file(&partial).await;
+        return Err(StorageError::IoError {
+            message: format!("failed to cache {}: {e}", source.display()),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+async fn remove_entries(entries: &[CachedDownload]) -> Result<(usize, u64), Error> {
+    for entry in entries {
+        fs::remove_file(&entry.path).await?;
+        if let Some(signature) = &entry.signature_path {
+            let _ = fs::remove_file(signature).await;
+        }
+    }
+    Ok((entries.len(), entries.iter().map(|entry| entry.size).sum()))
+}
+
+/// Record `now` as the last use of a cached file; best effort
+async fn touch(path: &Path, now: SystemTime) {
+    if let Ok(file) = fs::OpenOptions::new().write(true).open(path).await {
+        let _ = file.into_std().await.set_modified(now);
+    }
+}
+
+fn signature_path(package_path: &Path) -> PathBuf {
+    package_path.with_extension(SIGNATURE_EXTENSION)
+}
+
+/// Hash of a cached archive named `<hash>.sp`
+fn cached_hash(path: &Path) -> Option<Hash> {
+    let name = path.file_name()?.to_str()?;
+    let hex = name.strip_suffix(&format!(".{PACKAGE_EXTENSION}"))?;
+    Hash::from_hex(hex).ok().filter(Hash::is_blake3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn archive(dir: &Path, content: &[u8]) -> (Hash, PathBuf) {
+        let path = dir.join(format!("pkg-{}.sp", content.len()));
+        fs::write(&path, content).await.unwrap();
+        (Hash::blake3_from_data(content), path)
+    }
+
+    #[tokio::test]
+    async fn insert_lookup_and_clear() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(
+            temp.path().join("cache"),
+            Duration::from_secs(86_400),
+            u64::MAX,
+        );
+        let (hash, path) = archive(temp.path(), b"archive").await;
+        assert!(cache.lookup(&hash).await.is_none());
+
+        let signature = temp.path().join("pkg.sp.minisig");
+        fs::write(&signature, b"sig").await.unwrap();
+        cache.insert(&hash, &path, Some(&signature)).await.unwrap();
+
+        let cached = cache.lookup(&hash).await.unwrap();
+        assert_eq!(cached.size, 10);
+        assert_eq!(fs::read(&cached.path).await.unwrap(), b"archive");
+        assert_eq!(
+            fs::read(cached.signature_path.unwrap()).await.unwrap(),
+            b"sig"
+        );
+        assert_eq!(cache.list().await.unwrap().len(), 1);
+
+        assert_eq!(cache.clear().await.unwrap(), (1, 10));
+        assert!(cache.lookup(&hash).await.is_none());
+        assert!(fs::read_dir(cache.dir())
+            .await
+            .unwrap()
+            .next_entry()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn prune_evicts_least_recently_used_beyond_the_limit() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(temp.path().join("cache"), Duration::from_secs(86_400), 8);
+        let (old, old_path) = archive(temp.path(), b"12345").await;
+        let (new, new_path) = archive(temp.path(), b"1234").await;
+        cache.insert(&old, &old_path, None).await.unwrap();
+        touch(
+            &cache.package_path(&old),
+            SystemTime::now() - Duration::from_secs(60),
+        )
+        .await;
+
+        // Inserting prunes the least recently used archive to fit 8 bytes
+        cache.insert(&new, &new_path, None).await.unwrap();
+        assert!(cache.lookup(&old).await.is_none());
+        assert!(cache.lookup(&new).await.is_some());
+
+        let expiring = DownloadCache::new(cache.dir().to_path_buf(), Duration::ZERO, u64::MAX);
+        assert_eq!(expiring.prunable().await.unwrap().len(), 1);
+        assert_eq!(expiring.prune().await.unwrap(), (1, 4));
+        assert!(cache.list().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn missing_cache_is_empty() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache = DownloadCache::new(temp.path().join("none"), Duration::ZERO, 0);
+        assert!(cache.list().await.unwrap().is_empty());
+        assert_eq!(cache.prune().await.unwrap(), (0, 0));
+    }
+}
@@ -0,0 +1,232 @@
+//! Fault injection for network failure testing
+//!
+//! Available with the `fault-injection` feature. Faults are armed per client
+//! through [`NetClient::faults`](crate::NetClient::faults) and are checked once
+//! for every candidate URL a request is sent to, before any retries, so an
+//! injected failure counts against the mirror exactly like a real one.
+
+use sps2_errors::{Error, NetworkError};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Kind of failure produced when an armed fault fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetFault {
+    /// Fail as if the request timed out
+    Timeout,
+    /// Fail as if the connection was refused or reset
+    Disconnect,
+}
+
+impl NetFault {
+    fn into_error(self, url: &str) -> Error {
+        match self {
+            Self::Timeout => NetworkError::Timeout {
+                url: url.to_string(),
+            }
+            .into(),
+            Self::Disconnect => {
+                NetworkError::ConnectionRefused(format!("injected fault: {url}")).into()
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Rule {
+    /// Only requests whose URL contains this string count towards the rule
+    url_contains: Option<String>,
+    /// Matching requests left before the fault fires
+    remaining: u64,
+    fault: NetFault,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    rules: Vec<Rule>,
+    requests: u64,
+}
+
+/// Faults armed for a client; clones of the client share them
+#[derive(Debug, Clone, Default)]
+pub struct NetFaults {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl NetFaults {
+    /// Fail the `n`th request from now on (1-based)
+    pub fn fail_nth(&self, n: u64, fault: NetFault) {
+        self.push(None, n, fault);
+    }
+
+    /// Fail the `n`th request from now on whose URL contains `pattern`
+    pub fn fail_nth_matching(&self, pattern: &str, n: u64, fault: NetFault) {
+        self.push(Some(pattern.to_string()), n, fault);
+    }
+
+    /// Disarm all faults and forget the request count
+    pub fn reset(&self) {
+        *self.lock() = Inner::default();
+    }
+
+    /// Number of requests checked since the last reset
+    #[must_use]
+    pub fn requests(&self) -> u64 {
+        self.lock().requests
+    }
+
+    /// Record a request to `url` and return the injected error if a fault is due
+    ///
+    /// Faults due on the same request collapse into the first one armed.
+    pub(crate) fn check(&self, url: &str) -> Option<Error> {
+        let mut inner = self.lock();
+        inner.requests += 1;
+
+        let mut due = None;
+        for rule in &mut inner.rules {
+            if rule
+                .url_contains
+                .as_deref()
+                .is_none_or(|pattern| url.contains(pattern))
+            {
+                rule.remaining -= 1;
+                if rule.remaining == 0 && due.is_none() {
+                    due = Some(rule.fault);
+                }
+            }
+        }
+        inner.rules.retain(|rule| rule.remaining > 0);
+
+        due.map(|fault| fault.into_error(url))
+    }
+
+    fn push(&self, url_contains: Option<String>, n: u64, fault: NetFault) {
+        self.lock().rules.push(Rule {
+            url_contains,
+            remaining: n.max(1),
+            fault,
+        });
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MirrorGroup, NetClient, NetConfig};
+    use httpmock::MockServer;
+    use std::time::Duration;
+
+    fn client(mirrors: Vec<MirrorGroup>) -> NetClient {
+        NetClient::new_without_proxies(NetConfig {
+            retry_count: 0,
+            retry_delay: Duration::ZERO,
+            mirrors,
+            ..NetConfig::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn faults_fire_once_on_the_nth_matching_request() {
+        let faults = NetFaults::default();
+        faults.fail_nth_matching("b.example", 2, NetFault::Timeout);
+
+        assert!(faults.check("https://a.example/x").is_none());
+        assert!(faults.check("https://b.example/x").is_none());
+        let err = faults.check("https://b.example/y").unwrap();
+        assert!(matches!(
+            err,
+            Error::Network(NetworkError::Timeout { ref url }) if url == "https://b.example/y"
+        ));
+        assert!(faults.check("https://b.example/z").is_none());
+        assert_eq!(faults.requests(), 4);
+    }
+
+    #[tokio::test]
+    async fn injected_failure_is_returned_then_cleared() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/index.json");
+                then.status(200).body("{}");
+            })
+            .await;
+
+        let client = client(Vec::new());
+        client.faults().fail_nth(1, NetFault::Disconnect);
+
+        let url = server.url("/index.json");
+        let err = client.get(&url).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Network(NetworkError::ConnectionRefused(_))
+        ));
+        assert_eq!(mock.calls_async().await, 0);
+
+        assert!(client.get(&url).await.unwrap().status().is_success());
+        assert_eq!(mock.calls_async().await, 1);
+    }
+
+    #[tokio::test]
+    async fn injected_failure_fails_over_to_mirror() {
+        let primary = MockServer::start_async().await;
+        let mirror = MockServer::start_async().await;
+        let mirror_mock = mirror
+            .mock_async(|when, then| {
+                when.path("/pkg.sp");
+                then.status(200).body("data");
+            })
+            .await;
+
+        let client = client(vec![MirrorGroup::new(
+            primary.base_url(),
+            vec![mirror.base_url()],
+        )]);
+        client
+            .faults()
+            .fail_nth_matching(&primary.base_url(), 1, NetFault::Timeout);
+
+        let response = client.get(&primary.url("/pkg.sp")).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "data");
+        assert_eq!(mirror_mock.calls_async().await, 1);
+    }
+
+    #[tokio::test]
+    async fn range_requests_fail_over_to_mirror() {
+        let primary = MockServer::start_async().await;
+        let mirror = MockServer::start_async().await;
+        primary
+            .mock_async(|when, then| {
+                when.path("/index.json");
+                then.status(416);
+            })
+            .await;
+        let mirror_mock = mirror
+            .mock_async(|when, then| {
+                when.path("/index.json").header("Range", "bytes=2-5");
+                then.status(206).body("dex.");
+            })
+            .await;
+
+        let client = client(vec![MirrorGroup::new(
+            primary.base_url(),
+            vec![mirror.base_url()],
+        )]);
+        let url = primary.url("/index.json");
+        let response = client.get_range(&url, 2, Some(5)).await.unwrap();
+        assert_eq!(response.status().as_u16(), 206);
+        assert_eq!(response.text().await.unwrap(), "dex.");
+        assert_eq!(mirror_mock.calls_async().await, 1);
+
+        client
+            .faults()
+            .fail_nth_matching(&mirror.base_url(), 1, NetFault::Timeout);
+        assert_eq!(
+            client.get_range(&url, 2, Some(5)).await.unwrap().status(),
+            416
+        );
+    }
+}
@@ -9,15 +9,25 @@
 
 mod client;
 mod download;
+pub mod download_cache;
+#[cfg(any(test, feature = "fault-injection"))]
+pub mod fault;
+mod mirrors;
+pub mod problem;
+pub mod quarantine;
 pub mod signing;
 
 pub use client::{NetClient, NetConfig};
 pub use download::{
-    DownloadResult, PackageDownloadConfig, PackageDownloadRequest, PackageDownloadResult,
-    PackageDownloader, RetryConfig,
+    file_url, file_url_path, url_file_name, DownloadResult, PackageDownloadConfig,
+    PackageDownloadRequest, PackageDownloadResult, PackageDownloader, RetryConfig,
 };
+pub use download_cache::{CachedDownload, DownloadCache};
+pub use mirrors::MirrorGroup;
+pub use quarantine::{QuarantineEntry, QuarantineRecord};
 pub use signing::{
-    verify_minisign_bytes_with_keys, verify_minisign_file_with_keys, Algorithm, PublicKeyRef,
+    trust_fingerprint, verify_minisign_bytes_with_keys, verify_minisign_file_with_keys, Algorithm,
+    PublicKeyRef,
 };
 
 use sps2_errors::{Error, NetworkError};
@@ -68,11 +78,7 @@ pub async fn fetch_text(client: &NetClient, url: &str, tx: &EventSender) -> Resu
     let response = client.get(url).await?;
 
     if !response.status().is_success() {
-        return Err(NetworkError::HttpError {
-            status: response.status().as_u16(),
-            message: response.status().to_string(),
-        }
-        .into());
+        return Err(problem::http_error(response).await);
     }
 
     response
@@ -118,11 +124,7 @@ pub async fn fetch_text_conditional(
     }
 
     if !response.status().is_success() {
-        return Err(NetworkError::HttpError {
-            status: response.status().as_u16(),
-            message: response.status().to_string(),
-        }
-        .into());
+        return Err(problem::http_error(response).await);
     }
 
     // Extract new ETag from response headers
@@ -158,11 +160,7 @@ pub async fn fetch_bytes(
     let response = client.get(url).await?;
 
     if !response.status().is_success() {
-        return Err(NetworkError::HttpError {
-            status: response.status().as_u16(),
-            message: response.status().to_string(),
-        }
-        .into());
+        return Err(problem::http_error(response).await);
     }
 
     response
@@ -0,0 +1,210 @@
+//! Repository mirror groups and health scoring for failover
+//!
+//! A mirror group maps a primary repository base URL to alternative base
+//! URLs serving the same content. Requests below the primary base are tried
+//! against every member of the group, best scoring first. Successful requests
+//! raise a mirror's score, failures lower it sharply, so an unreachable
+//! mirror quickly drops to the back of the queue and recovers slowly.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Score added to a mirror after a successful request
+const SUCCESS_REWARD: i32 = 1;
+
+/// Score removed from a mirror after a failed request
+const FAILURE_PENALTY: i32 = 5;
+
+/// Highest score a mirror can accumulate
+const MAX_SCORE: i32 = 10;
+
+/// Lowest score a mirror can fall to
+const MIN_SCORE: i32 = -50;
+
+/// A primary repository base URL and its mirrors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MirrorGroup {
+    /// Base URL the index and packages are published under
+    pub primary: String,
+    /// Alternative base URLs, in order of preference
+    pub mirrors: Vec<String>,
+}
+
+impl MirrorGroup {
+    /// Create a mirror group for a primary base URL
+    #[must_use]
+    pub fn new(primary: impl Into<String>, mirrors: Vec<String>) -> Self {
+        Self {
+            primary: primary.into(),
+            mirrors,
+        }
+    }
+
+    /// Path of `url` relative to the primary base, if it lies below it
+    fn relative_path<'a>(&self, url: &'a str) -> Option<&'a str> {
+        strip_base(&self.primary, url)
+    }
+
+    /// All base URLs of this group, primary first
+    fn bases(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.primary.as_str())
+            .chain(self.mirrors.iter().map(String::as_str))
+            .map(|base| base.trim_end_matches('/'))
+    }
+}
+
+/// Path of `url` relative to `base`, if it lies below it
+fn strip_base<'a>(base: &str, url: &'a str) -> Option<&'a str> {
+    let rest = url.strip_prefix(base.trim_end_matches('/'))?;
+    (rest.is_empty() || rest.starts_with('/') || rest.starts_with('?')).then_some(rest)
+}
+
+/// Health scores for mirror base URLs, shared between client clones
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MirrorHealth {
+    scores: Arc<Mutex<HashMap<String, i32>>>,
+}
+
+/// A candidate URL for a request and the mirror base it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Candidate {
+    pub(crate) url: String,
+    pub(crate) base: Option<String>,
+}
+
+impl MirrorHealth {
+    /// Current score of a base URL
+    pub(crate) fn score(&self, base: &str) -> i32 {
+        self.scores
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(base)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Record the outcome of a request against a base URL
+    pub(crate) fn record(&self, base: &str, success: bool) {
+        let mut scores = self.scores.lock().unwrap_or_else(PoisonError::into_inner);
+        let score = scores.entry(base.to_string()).or_insert(0);
+        *score = if success {
+            (*score + SUCCESS_REWARD).min(MAX_SCORE)
+        } else {
+            (*score - FAILURE_PENALTY).max(MIN_SCORE)
+        };
+    }
+
+    /// Record the outcome of a completed request by the URL that served it
+    ///
+    /// Used when a failure only becomes visible after the response was
+    /// accepted, such as content that fails hash verification.
+    pub(crate) fn record_url(&self, groups: &[MirrorGroup], url: &str, success: bool) {
+        let base = groups
+            .iter()
+            .find_map(|group| group.bases().find(|base| strip_base(base, url).is_some()));
+        if let Some(base) = base {
+            self.record(base, success);
+        }
+    }
+
+    /// Expand a URL into the candidates to try, healthiest mirror first
+    ///
+    /// URLs outside every mirror group yield a single candidate.
+    pub(crate) fn candidates(&self, groups: &[MirrorGroup], url: &str) -> Vec<Candidate> {
+        let Some((group, rest)) = groups
+            .iter()
+            .find_map(|group| group.relative_path(url).map(|rest| (group, rest)))
+        else {
+            return vec![Candidate {
+                url: url.to_string(),
+                base: None,
+            }];
+        };
+
+        let mut bases: Vec<(&str, i32)> =
+            group.bases().map(|base| (base, self.score(base))).collect();
+        // Stable sort keeps the declared order between equally healthy mirrors
+        bases.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+
+        bases
+            .into_iter()
+            .map(|(base, _)| Candidate {
+                url: format!("{base}{rest}"),
+                base: Some(base.to_string()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups() -> Vec<MirrorGroup> {
+        vec![MirrorGroup::new(
+            "https://repo.example/",
+            vec!["https://eu.mirror.example".to_string()],
+        )]
+    }
+
+    #[test]
+    fn urls_outside_groups_are_untouched() {
+        let health = MirrorHealth::default();
+        let candidates = health.candidates(&groups(), "https://other.example/index.json");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].base, None);
+
+        // A shared prefix without a path boundary is not part of the group
+        let candidates = health.candidates(&groups(), "https://repo.example.org/index.json");
+        assert_eq!(candidates.len(), 1);
+    }
+
+    #[test]
+    fn failing_primary_moves_behind_mirror() {
+        let health = MirrorHealth::default();
+        let urls = |health: &MirrorHealth| {
+            health
+                .candidates(&groups(), "https://repo.example/packages/curl.sp")
+                .into_iter()
+                .map(|c| c.url)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            urls(&health),
+            [
+                "https://repo.example/packages/curl.sp",
+                "https://eu.mirror.example/packages/curl.sp"
+            ]
+        );
+
+        health.record("https://repo.example", false);
+        assert_eq!(
+            urls(&health)[0],
+            "https://eu.mirror.example/packages/curl.sp"
+        );
+    }
+
+    #[test]
+    fn outcomes_are_recorded_by_serving_url() {
+        let health = MirrorHealth::default();
+        health.record_url(
+            &groups(),
+            "https://eu.mirror.example/packages/curl.sp",
+            false,
+        );
+        assert_eq!(health.score("https://eu.mirror.example"), -FAILURE_PENALTY);
+        assert_eq!(health.score("https://repo.example"), 0);
+    }
+
+    #[test]
+    fn scores_are_clamped() {
+        let health = MirrorHealth::default();
+        for _ in 0..100 {
+            health.record("a", true);
+            health.record("b", false);
+        }
+        assert_eq!(health.score("a"), MAX_SCORE);
+        assert_eq!(health.score("b"), MIN_SCORE);
+    }
+}
@@ -0,0 +1,159 @@
+//! Errors reported by repository servers
+//!
+//! Servers may describe a failure with an RFC 9457 problem-details body
+//! (`application/problem+json`) and tag each request with an identifier.
+//! Both are carried into [`NetworkError::HttpError`] so a failure seen by a
+//! client can be found in the server's logs.
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::Response;
+use serde::Deserialize;
+use sps2_errors::{Error, NetworkError};
+
+/// Headers servers and CDNs commonly put request identifiers in
+const REQUEST_ID_HEADERS: &[&str] = &[
+    "x-request-id",
+    "request-id",
+    "x-amz-request-id",
+    "x-correlation-id",
+    "cf-ray",
+];
+
+/// Error bodies larger than this are not parsed
+const MAX_PROBLEM_BYTES: usize = 64 * 1024;
+
+/// RFC 9457 problem details, with the request-id extension some servers add
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: Option<String>,
+    pub title: Option<String>,
+    pub status: Option<u16>,
+    pub detail: Option<String>,
+    pub instance: Option<String>,
+    #[serde(alias = "requestId", alias = "request-id")]
+    pub request_id: Option<String>,
+}
+
+impl ProblemDetails {
+    /// Human-readable summary: title and detail, whichever are present
+    #[must_use]
+    pub fn message(&self) -> Option<String> {
+        match (&self.title, &self.detail) {
+            (Some(title), Some(detail)) => Some(format!("{title}: {detail}")),
+            (Some(text), None) | (None, Some(text)) => Some(text.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Request identifier the server put in the response headers
+#[must_use]
+pub fn request_id(headers: &HeaderMap) -> Option<String> {
+    REQUEST_ID_HEADERS.iter().find_map(|name| {
+        headers
+            .get(*name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    })
+}
+
+/// Turn an unsuccessful response into an [`NetworkError::HttpError`]
+///
+/// A JSON problem-details body supplies the message; otherwise the status
+/// text is used. The request ID comes from the response headers, or from the
+/// body if the headers carry none.
+pub async fn http_error(response: Response) -> Error {
+    let status = response.status();
+    let header_id = request_id(response.headers());
+    let is_json = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.contains("json"));
+    let fits = response
+        .content_length()
+        .is_none_or(|len| len <= MAX_PROBLEM_BYTES as u64);
+
+    let problem = if is_json && fits {
+        response
+            .bytes()
+            .await
+            .ok()
+            .filter(|body| body.len() <= MAX_PROBLEM_BYTES)
+            .and_then(|body| serde_json::from_slice::<ProblemDetails>(&body).ok())
+    } else {
+        None
+    }
+    .unwrap_or_default();
+
+    NetworkError::HttpError {
+        status: status.as_u16(),
+        message: problem.message().unwrap_or_else(|| status.to_string()),
+        request_id: header_id.or(problem.request_id),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NetClient, NetConfig};
+    use httpmock::MockServer;
+    use std::time::Duration;
+
+    async fn error_for(status: u16, content_type: &str, body: &str, id: Option<&str>) -> Error {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/index.json");
+                let then = then
+                    .status(status)
+                    .header("content-type", content_type)
+                    .body(body);
+                if let Some(id) = id {
+                    then.header("x-request-id", id);
+                }
+            })
+            .await;
+        let client = NetClient::new_without_proxies(NetConfig {
+            retry_count: 0,
+            retry_delay: Duration::ZERO,
+            ..NetConfig::default()
+        })
+        .unwrap();
+        let response = client.get(&server.url("/index.json")).await.unwrap();
+        http_error(response).await
+    }
+
+    #[tokio::test]
+    async fn problem_details_supply_message_and_request_id() {
+        let body = r#"{"type":"about:blank","title":"Forbidden","status":403,
+            "detail":"token expired","requestId":"body-id"}"#;
+        let err = error_for(403, "application/problem+json", body, None).await;
+        let Error::Network(NetworkError::HttpError {
+            status,
+            message,
+            request_id,
+        }) = &err
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(*status, 403);
+        assert_eq!(message, "Forbidden: token expired");
+        assert_eq!(request_id.as_deref(), Some("body-id"));
+        assert!(err.to_string().ends_with("(request ID body-id)"));
+    }
+
+    #[tokio::test]
+    async fn plain_bodies_fall_back_to_status_text() {
+        let err = error_for(403, "text/html", "<h1>nope</h1>", Some("hdr-id")).await;
+        assert!(matches!(
+            err,
+            Error::Network(NetworkError::HttpError { ref message, ref request_id, .. })
+                if message == "403 Forbidden" && request_id.as_deref() == Some("hdr-id")
+        ));
+    }
+}
@@ -0,0 +1,215 @@
+//! Quarantine for downloads that failed integrity verification
+//!
+//! Corrupted files are moved out of the download directory instead of being
+//! deleted so they can be inspected later. Every quarantined file gets a JSON
+//! record next to it describing where it came from and which hashes were
+//! compared.
+
+use serde::{Deserialize, Serialize};
+use sps2_errors::{Error, StorageError};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+/// Extension of the record written next to each quarantined file
+const RECORD_EXTENSION: &str = "quarantine.json";
+
+/// Metadata stored alongside a quarantined file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantineRecord {
+    /// URL the file was downloaded from
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    pub expected_hash: String,
+    pub actual_hash: String,
+    /// Seconds since the Unix epoch
+    pub quarantined_at: u64,
+}
+
+/// A quarantined file and its record
+#[derive(Debug, Clone)]
+pub struct QuarantineEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// `None` if the record is missing or unreadable
+    pub record: Option<QuarantineRecord>,
+}
+
+/// Move a corrupted download into `quarantine_dir`
+///
+/// The file name is prefixed with the quarantine time so repeated failures
+/// of the same download do not overwrite each other. Returns the new path.
+///
+/// # Errors
+///
+/// Returns an error if the quarantine directory cannot be created or the
+/// file cannot be moved.
+pub async fn quarantine_file(
+    quarantine_dir: &Path,
+    path: &Path,
+    record: &QuarantineRecord,
+) -> Result<PathBuf, Error> {
+    fs::create_dir_all(quarantine_dir)
+        .await
+        .map_err(|e| StorageError::IoError {
+            message: format!(
+                "failed to create quarantine directory {}: {e}",
+                quarantine_dir.display()
+            ),
+        })?;
+
+    let file_name = path
+        .file_name()
+        .map_or_else(|| "download".into(), |name| name.to_string_lossy());
+    let target = quarantine_dir.join(format!("{}-{file_name}", record.quarantined_at));
+
+    // Downloads and the quarantine usually share a volume; fall back to
+    // copying when they do not
+    if fs::rename(path, &target).await.is_err() {
+        fs::copy(path, &target)
+            .await
+            .map_err(|e| StorageError::IoError {
+                message: format!("failed to quarantine {}: {e}", path.display()),
+            })?;
+        fs::remove_file(path).await?;
+    }
+
+    let json = serde_json::to_vec_pretty(record).map_err(|e| StorageError::IoError {
+        message: format!("failed to serialize quarantine record: {e}"),
+    })?;
+    fs::write(record_path(&target), json).await?;
+
+    Ok(target)
+}
+
+/// List quarantined files, oldest first
+///
+/// A missing quarantine directory is treated as empty.
+///
+/// # Errors
+///
+/// Returns an error if the quarantine directory cannot be read.
+pub async fn list_quarantine(quarantine_dir: &Path) -> Result<Vec<QuarantineEntry>, Error> {
+    let mut dir = match fs::read_dir(quarantine_dir).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut entries = Vec::new();
+    while let Some(entry) = dir.next_entry().await? {
+        let path = entry.path();
+        if is_record(&path) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let record = fs::read(record_path(&path))
+            .await
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+        entries.push(QuarantineEntry {
+            path,
+            size: metadata.len(),
+            record,
+        });
+    }
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Remove all quarantined files and their records
+///
+/// Returns the number of files removed and the bytes freed.
+///
+/// # Errors
+///
+/// Returns an error if the quarantine directory cannot be read or a file
+/// cannot be removed.
+pub async fn purge_quarantine(quarantine_dir: &Path) -> Result<(usize, u64), Error> {
+    let entries = list_quarantine(quarantine_dir).await?;
+    let mut freed = 0;
+    for entry in &entries {
+        fs::remove_file(&entry.path).await?;
+        let _ = fs::remove_file(record_path(&entry.path)).await;
+        freed += entry.size;
+    }
+    Ok((entries.len(), freed))
+}
+
+/// Current time in seconds since the Unix epoch, for [`QuarantineRecord`]
+#[must_use]
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn record_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(RECORD_EXTENSION);
+    PathBuf::from(name)
+}
+
+fn is_record(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|p| p.ends_with(&format!(".{RECORD_EXTENSION}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn quarantine_list_and_purge() {
+        let temp = tempfile::tempdir().unwrap();
+        let downloads = temp.path().join("downloads");
+        let quarantine = temp.path().join("quarantine");
+        fs::create_dir_all(&downloads).await.unwrap();
+        let corrupt = downloads.join("pkg-1.0.0.sp");
+        fs::write(&corrupt, b"corrupt").await.unwrap();
+
+        let record = QuarantineRecord {
+            url: "https://repo.example/pkg-1.0.0.sp".to_string(),
+            package: Some("pkg".to_string()),
+            expected_hash: "aa".to_string(),
+            actual_hash: "bb".to_string(),
+            quarantined_at: 1_700_000_000,
+        };
+        let moved = quarantine_file(&quarantine, &corrupt, &record)
+            .await
+            .unwrap();
+        assert!(!corrupt.exists());
+        assert_eq!(
+            moved.file_name().unwrap().to_str(),
+            Some("1700000000-pkg-1.0.0.sp")
+        );
+
+        let entries = list_quarantine(&quarantine).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].size, 7);
+        assert_eq!(entries[0].record.as_ref(), Some(&record));
+
+        assert_eq!(purge_quarantine(&quarantine).await.unwrap(), (1, 7));
+        assert!(list_quarantine(&quarantine).await.unwrap().is_empty());
+        assert!(fs::read_dir(&quarantine)
+            .await
+            .unwrap()
+            .next_entry()
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_quarantine_is_empty() {
+        let temp = tempfile::tempdir().unwrap();
+        let entries = list_quarantine(&temp.path().join("none")).await.unwrap();
+        assert!(entries.is_empty());
+    }
+}
@@ -6,6 +6,8 @@ use minisign::{sign, SecretKeyBox};
 use minisign_verify::{PublicKey, Signature};
 use serde::{Deserialize, Serialize};
 use sps2_errors::{Error, SigningError};
+use sps2_hash::Hash;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
@@ -109,6 +111,54 @@ pub fn verify_minisign_bytes_with_keys(
     })
 }
 
+/// Fingerprint of the trusted keys kept in `keys_dir`
+///
+/// Covers each key id and its public key, independent of the order they are
+/// stored in, so it changes exactly when a key is added, removed or replaced.
+/// Anything validated under one fingerprint is not vouched for under another.
+///
+/// # Errors
+///
+/// Returns an error if the trusted keys file exists but cannot be read or
+/// parsed.
+pub fn trust_fingerprint(keys_dir: &Path) -> Result<String, Error> {
+    let keys_file = keys_dir.join("trusted_keys.json");
+    let content = match fs::read_to_string(&keys_file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => {
+            return Err(Error::internal(format!(
+                "failed to read trusted keys file {}: {e}",
+                keys_file.display()
+            )))
+        }
+    };
+
+    let mut keys = BTreeMap::new();
+    if !content.trim().is_empty() {
+        let json: serde_json::Value = serde_json::from_str(&content).map_err(|e| {
+            Error::internal(format!(
+                "failed to parse trusted keys file {}: {e}",
+                keys_file.display()
+            ))
+        })?;
+        for (key_id, entry) in json.as_object().into_iter().flatten() {
+            let public_key = entry
+                .get("public_key")
+                .and_then(serde_json::Value::as_str)
+                .unwrap_or_default();
+            keys.insert(key_id.clone(), public_key.to_string());
+        }
+    }
+
+    let canonical = keys
+        .iter()
+        .map(|(key_id, public_key)| format!("{key_id}:{public_key}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(Hash::from_data(canonical.as_bytes()).to_hex())
+}
+
 /// Sign raw bytes with a Minisign secret key file and return the signature string.
 ///
 /// The secret key file is expected to be in Minisign "secret key box" format.
@@ -133,9 +183,17 @@ pub fn minisign_sign_bytes(
     let sk_box = SecretKeyBox::from_string(&sk_box_str)
         .map_err(|e| Error::internal(format!("Failed to parse secret key: {e}")))?;
 
-    let secret_key = sk_box
-        .into_secret_key(passphrase_or_keychain.map(std::string::ToString::to_string))
-        .map_err(|e| Error::internal(format!("Failed to decrypt secret key: {e}")))?;
+    // Without a passphrase, accept unencrypted keys (such as throwaway test keys)
+    let unencrypted = passphrase_or_keychain
+        .is_none()
+        .then(|| sk_box.clone().into_unencrypted_secret_key().ok())
+        .flatten();
+    let secret_key = match unencrypted {
+        Some(secret_key) => secret_key,
+        None => sk_box
+            .into_secret_key(passphrase_or_keychain.map(std::string::ToString::to_string))
+            .map_err(|e| Error::internal(format!("Failed to decrypt secret key: {e}")))?,
+    };
 
     let signature = sign(
         None,
@@ -148,3 +206,34 @@ pub fn minisign_sign_bytes(
 
     Ok(signature.into_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trust_fingerprint_follows_the_set_of_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let keys_file = dir.path().join("trusted_keys.json");
+        let empty = trust_fingerprint(dir.path()).unwrap();
+
+        std::fs::write(
+            &keys_file,
+            r#"{"a1": {"public_key": "RWQa"}, "b2": {"public_key": "RWQb"}}"#,
+        )
+        .unwrap();
+        let two_keys = trust_fingerprint(dir.path()).unwrap();
+        assert_ne!(empty, two_keys);
+
+        // Same keys written in another order
+        std::fs::write(
+            &keys_file,
+            r#"{"b2": {"public_key": "RWQb"}, "a1": {"public_key": "RWQa"}}"#,
+        )
+        .unwrap();
+        assert_eq!(trust_fingerprint(dir.path()).unwrap(), two_keys);
+
+        std::fs::write(&keys_file, r#"{"a1": {"public_key": "RWQa"}}"#).unwrap();
+        assert_ne!(trust_fingerprint(dir.path()).unwrap(), two_keys);
+    }
+}
@@ -0,0 +1,299 @@
+//! Apply command implementation
+//!
+//! A change set declares packages to install, remove and upgrade:
+//!
+//! ```toml
+//! install = ["python>=3.12.0", "jq"]
+//! remove = ["wget"]
+//! upgrade = ["openssl"]
+//! # or upgrade every installed package the change set does not remove
+//! upgrade_all = true
+//! ```
+//!
+//! [`apply`] resolves the whole change together and commits it as a single
+//! new state, so configuration management tools get one atomic transition
+//! instead of issuing an install, an uninstall and an upgrade in turn.
+
+use crate::transition::after_transition;
+use crate::{InstallReport, OpsCtx, PackageChange};
+use serde::Deserialize;
+use sps2_errors::{Error, OpsError};
+use sps2_install::{ApplyContext, InstallResult, Installer};
+use sps2_state::models::Package;
+use sps2_types::PackageSpec;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Packages to install, remove and upgrade in one change
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChangeSet {
+    /// Package specs to install, as given to `sps2 install`
+    pub install: Vec<String>,
+    /// Names of installed packages to remove
+    pub remove: Vec<String>,
+    /// Names of installed packages to upgrade
+    pub upgrade: Vec<String>,
+    /// Upgrade every installed package that is not removed
+    pub upgrade_all: bool,
+}
+
+impl ChangeSet {
+    /// Read and check a change set file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, a package
+    /// spec is invalid, the change set changes nothing, or it both removes
+    /// a package and installs or upgrades it.
+    pub async fn load(path: &Path) -> Result<Self, Error> {
+        let invalid = |reason: String| OpsError::InvalidChangeSet {
+            path: path.display().to_string(),
+            reason,
+        };
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| invalid(e.to_string()))?;
+        let changes: Self = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+        changes.check().map_err(invalid)?;
+        Ok(changes)
+    }
+
+    /// Whether the change set is consistent, or why not
+    fn check(&self) -> Result<(), String> {
+        if self.is_empty() {
+            return Err("it installs, removes and upgrades nothing".to_string());
+        }
+        let mut names = HashSet::new();
+        for spec in self.install_specs()? {
+            names.insert(spec.name);
+        }
+        names.extend(self.upgrade.iter().cloned());
+        match self.remove.iter().find(|name| names.contains(*name)) {
+            Some(name) => Err(format!("{name} is both removed and installed or upgraded")),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the change set changes nothing
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty()
+            && self.remove.is_empty()
+            && self.upgrade.is_empty()
+            && !self.upgrade_all
+    }
+
+    /// The package specs to install
+    fn install_specs(&self) -> Result<Vec<PackageSpec>, String> {
+        self.install
+            .iter()
+            .map(|spec| {
+                PackageSpec::parse(spec).map_err(|e| format!("invalid package spec '{spec}': {e}"))
+            })
+            .collect()
+    }
+}
+
+/// Apply a change set as one state transition
+///
+/// Removals are checked like [`uninstall`](crate::uninstall) without
+/// cascading, upgrades like [`upgrade`](crate::upgrade), and `force` lets
+/// both break installed packages' dependencies. In check mode the change is
+/// only planned.
+///
+/// # Errors
+///
+/// Returns an error if the change set is invalid, a package to remove or
+/// upgrade is not installed, the change would break installed packages and
+/// is not forced, resolution needs a package being removed, or downloading
+/// or installing fails. Nothing is changed then.
+pub async fn apply(ctx: &OpsCtx, changes: &ChangeSet, force: bool) -> Result<InstallReport, Error> {
+    let names = change_names(changes);
+    let _correlation = ctx.push_correlation_for_packages("apply", &names);
+    let context = apply_context(ctx, changes, force)?;
+    commit(ctx, context, "apply", &names).await
+}
+
+/// Apply `context` as one state transition, recording it in the audit log
+/// as `operation` on the `requested` packages, or only plan it in check
+/// mode
+pub(crate) async fn commit(
+    ctx: &OpsCtx,
+    context: ApplyContext,
+    operation: &str,
+    requested: &[String],
+) -> Result<InstallReport, Error> {
+    let start = Instant::now();
+    if ctx.check_mode {
+        return preview(ctx, &context).await;
+    }
+
+    let installed = installed_packages(ctx).await?;
+    let result = new_installer(ctx).await?.apply(context).await?;
+    let report = apply_report(
+        &result,
+        &installed,
+        result.state_id,
+        u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+    );
+    if result.state_id.is_nil() {
+        return Ok(report);
+    }
+
+    after_transition(ctx, &report, operation, requested).await;
+    Ok(report)
+}
+
+/// Report what applying `context` would do without changing anything
+pub(crate) async fn preview(ctx: &OpsCtx, context: &ApplyContext) -> Result<InstallReport, Error> {
+    let installed = installed_packages(ctx).await?;
+    let plan = new_installer(ctx).await?.plan_apply(context).await?;
+    Ok(apply_report(&plan.result, &installed, Uuid::nil(), 0))
+}
+
+async fn new_installer(ctx: &OpsCtx) -> Result<Installer, Error> {
+    Ok(Installer::new(
+        ctx.install_config(),
+        ctx.resolver().await?.clone(),
+        ctx.state.clone(),
+        ctx.store.clone(),
+    )
+    .with_net_client(ctx.net()?.clone()))
+}
+
+/// Installer context for applying `changes`
+pub(crate) fn apply_context(
+    ctx: &OpsCtx,
+    changes: &ChangeSet,
+    force: bool,
+) -> Result<ApplyContext, Error> {
+    let specs = changes
+        .install_specs()
+        .map_err(|reason| OpsError::InvalidChangeSet {
+            path: "change set".to_string(),
+            reason,
+        })?;
+    Ok(ApplyContext::new()
+        .with_install(specs)
+        .with_remove(changes.remove.clone())
+        .with_upgrade(changes.upgrade.clone())
+        .with_upgrade_all(changes.upgrade_all)
+        .with_force(force)
+        .with_event_sender(ctx.tx.clone()))
+}
+
+/// Installed packages by name
+pub(crate) async fn installed_packages(ctx: &OpsCtx) -> Result<HashMap<String, Package>, Error> {
+    Ok(ctx
+        .state
+        .get_installed_packages()
+        .await?
+        .into_iter()
+        .map(|package| (package.name.clone(), package))
+        .collect())
+}
+
+/// Names of the packages a change set names, for the audit log
+fn change_names(changes: &ChangeSet) -> Vec<String> {
+    let installs = changes
+        .install
+        .iter()
+        .map(|spec| PackageSpec::parse(spec).map_or_else(|_| spec.clone(), |spec| spec.name));
+    installs
+        .chain(changes.remove.iter().cloned())
+        .chain(changes.upgrade.iter().cloned())
+        .collect()
+}
+
+/// Report of the installer's `result`
+fn apply_report(
+    result: &InstallResult,
+    installed: &HashMap<String, Package>,
+    state_id: Uuid,
+    duration_ms: u64,
+) -> InstallReport {
+    let installed_version = |name: &str| installed.get(name).map(Package::version);
+    let installed_size = |name: &str| {
+        installed
+            .get(name)
+            .and_then(|package| u64::try_from(package.size).ok())
+    };
+    InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
+        installed: result
+            .installed_packages
+            .iter()
+            .map(|package| PackageChange {
+                name: package.name.clone(),
+                from_version: None,
+                to_version: Some(package.version.clone()),
+                size: None,
+            })
+            .collect(),
+        updated: result
+            .updated_packages
+            .iter()
+            .map(|package| PackageChange {
+                name: package.name.clone(),
+                from_version: installed_version(&package.name),
+                to_version: Some(package.version.clone()),
+                size: None,
+            })
+            .collect(),
+        removed: result
+            .removed_packages
+            .iter()
+            .map(|package| PackageChange {
+                name: package.name.clone(),
+                from_version: Some(package.version.clone()),
+                to_version: None,
+                size: installed_size(&package.name),
+            })
+            .collect(),
+        broken_dependencies: result.broken_dependencies.clone(),
+        state_id,
+        duration_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn change_sets_must_change_something_consistently() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("changes.toml");
+        let load = |content: &'static str| {
+            let path = path.clone();
+            async move {
+                std::fs::write(&path, content).unwrap();
+                ChangeSet::load(&path).await
+            }
+        };
+
+        let changes = load("install = [\"jq>=1.7.0\"]\nremove = [\"wget\"]\nupgrade_all = true\n")
+            .await
+            .unwrap();
+        assert_eq!(changes.install, ["jq>=1.7.0"]);
+        assert_eq!(changes.remove, ["wget"]);
+        assert!(changes.upgrade_all);
+
+        for content in [
+            "",
+            "install = [\"jq\"]\nremove = [\"jq\"]\n",
+            "upgrade = [\"jq\"]\nremove = [\"jq\"]\n",
+            "install = [\"jq>>1\"]\n",
+            "purge = [\"jq\"]\n",
+        ] {
+            let err = load(content).await.unwrap_err();
+            assert!(
+                matches!(err, Error::Ops(OpsError::InvalidChangeSet { .. })),
+                "{content:?}: {err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Audit trail of state transitions
+//!
+//! Every operation that moves the system to another state records what it
+//! was asked for, what it resolved to and who ran it, for
+//! `sps2 history --detail`. Accepted changes to the trusted signing keys are
+//! recorded against the state that was active at the time.
+
+use crate::OpsCtx;
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_state::StateAudit;
+use sps2_types::{ChangeType, InstallReport, OpChange, StateId, Version};
+
+/// Package changes of an install, update or uninstall report
+pub(crate) fn report_changes(report: &InstallReport) -> Vec<OpChange> {
+    let installed = report.installed.iter().map(|change| OpChange {
+        change_type: ChangeType::Install,
+        package: change.name.clone(),
+        old_version: None,
+        new_version: change.to_version.clone(),
+    });
+    let updated = report.updated.iter().map(|change| {
+        let downgrade = matches!(
+            (&change.from_version, &change.to_version),
+            (Some(from), Some(to)) if to < from
+        );
+        OpChange {
+            change_type: if downgrade {
+                ChangeType::Downgrade
+            } else {
+                ChangeType::Update
+            },
+            package: change.name.clone(),
+            old_version: change.from_version.clone(),
+            new_version: change.to_version.clone(),
+        }
+    });
+    let removed = report.removed.iter().map(|change| OpChange {
+        change_type: ChangeType::Remove,
+        package: change.name.clone(),
+        old_version: change.from_version.clone(),
+        new_version: None,
+    });
+    installed.chain(updated).chain(removed).collect()
+}
+
+/// Record an audit entry for the transition to `state_id`
+///
+/// The transition has already happened by the time this runs, so a failure
+/// to record it is reported as a warning rather than failing the operation.
+/// Nothing is recorded for operations that changed nothing.
+pub(crate) async fn record_transition(
+    ctx: &OpsCtx,
+    state_id: &StateId,
+    operation: &str,
+    requested: &[String],
+    changes: Vec<OpChange>,
+) {
+    if changes.is_empty() {
+        return;
+    }
+
+    let audit = StateAudit {
+        state_id: state_id.to_string(),
+        operation: operation.to_string(),
+        description: describe(operation, requested, &changes),
+        actor: actor(),
+        command_line: ctx.command_line.clone(),
+        requested: requested.to_vec(),
+        changes,
+    };
+    if let Err(e) = ctx.state.record_state_audit(&audit).await {
+        ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            format!("Failed to record audit entry for state {state_id}"),
+            e.to_string(),
+        )));
+    }
+}
+
+/// Record an accepted change to the trusted signing keys
+///
+/// Trusting a key does not move the system to another state, so the entry
+/// goes to the active one. As with transitions, a failure to record it is
+/// only a warning.
+pub(crate) async fn record_key_changes(ctx: &OpsCtx, description: &str) {
+    let recorded = async {
+        let state_id = ctx.state.get_active_state().await?;
+        let audit = StateAudit {
+            state_id: state_id.to_string(),
+            operation: "reposync".to_string(),
+            description: format!("reposync: {description}"),
+            actor: actor(),
+            command_line: ctx.command_line.clone(),
+            requested: Vec::new(),
+            changes: Vec::new(),
+        };
+        ctx.state.record_state_audit(&audit).await
+    };
+    if let Err(e) = recorded.await {
+        ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            "Failed to record audit entry for the key change",
+            e.to_string(),
+        )));
+    }
+}
+
+/// One-line summary such as `install jq: installed jq 1.7.1, oniguruma 6.9.9`
+fn describe(operation: &str, requested: &[String], changes: &[OpChange]) -> String {
+    let mut groups: Vec<(&str, Vec<String>)> = Vec::new();
+    for change in changes {
+        let (verb, text) = match change.change_type {
+            ChangeType::Install => (
+                "installed",
+                with_version(&change.package, change.new_version.as_ref()),
+            ),
+            ChangeType::Remove => (
+                "removed",
+                with_version(&change.package, change.old_version.as_ref()),
+            ),
+            ChangeType::Update => ("updated", transition(change)),
+            ChangeType::Downgrade => ("downgraded", transition(change)),
+        };
+        match groups.iter_mut().find(|(group, _)| *group == verb) {
+            Some((_, texts)) => texts.push(text),
+            None => groups.push((verb, vec![text])),
+        }
+    }
+
+    let summary = groups
+        .into_iter()
+        .map(|(verb, texts)| format!("{verb} {}", texts.join(", ")))
+        .collect::<Vec<_>>()
+        .join("; ");
+    if requested.is_empty() {
+        format!("{operation}: {summary}")
+    } else {
+        format!("{operation} {}: {summary}", requested.join(" "))
+    }
+}
+
+fn with_version(package: &str, version: Option<&Version>) -> String {
+    match version {
+        Some(version) => format!("{package} {version}"),
+        None => package.to_string(),
+    }
+}
+
+fn transition(change: &OpChange) -> String {
+    match (&change.old_version, &change.new_version) {
+        (Some(old), Some(new)) => format!("{} {old} -> {new}", change.package),
+        _ => with_version(&change.package, change.new_version.as_ref()),
+    }
+}
+
+/// The user behind the command, looking through `sudo`
+fn actor() -> Option<String> {
+    std::env::var("SUDO_USER")
+        .or_else(|_| std::env::var("USER"))
+        .ok()
+        .filter(|user| !user.is_empty())
+}
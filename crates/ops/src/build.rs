@@ -4,10 +4,12 @@
 //! Delegates to `sps2_builder` crate for the actual build logic.
 
 use crate::{BuildReport, OpsCtx};
-use sps2_builder::{parse_yaml_recipe, BuildContext};
-use sps2_errors::{Error, OpsError};
+use sps2_builder::{parse_yaml_recipe, BuildContext, RemoteBuilder};
+use sps2_errors::{Error, OpsError, PackageError};
 use sps2_events::{AppEvent, BuildEvent, BuildSession, BuildTarget, EventEmitter, FailureContext};
-use sps2_types::Version;
+use sps2_types::package::PackageSpec;
+use sps2_types::{Arch, Version};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
 
@@ -25,6 +27,51 @@ pub async fn build(
     output_dir: Option<&Path>,
     network: bool,
     jobs: Option<usize>,
+) -> Result<BuildReport, Error> {
+    Box::pin(build_recipe(
+        ctx,
+        recipe_path,
+        output_dir,
+        network,
+        jobs,
+        true,
+    ))
+    .await
+}
+
+/// Build a recipe on behalf of a remote client
+///
+/// Always builds locally and never installs the package, even if the
+/// recipe asks for that: the client installs it once copied back.
+///
+/// # Errors
+///
+/// Returns the same errors as [`build`].
+pub async fn worker_build(
+    ctx: &OpsCtx,
+    recipe_path: &Path,
+    output_dir: Option<&Path>,
+    network: bool,
+    jobs: Option<usize>,
+) -> Result<BuildReport, Error> {
+    Box::pin(build_recipe(
+        ctx,
+        recipe_path,
+        output_dir,
+        network,
+        jobs,
+        false,
+    ))
+    .await
+}
+
+async fn build_recipe(
+    ctx: &OpsCtx,
+    recipe_path: &Path,
+    output_dir: Option<&Path>,
+    network: bool,
+    jobs: Option<usize>,
+    is_client: bool,
 ) -> Result<BuildReport, Error> {
     let start = Instant::now();
 
@@ -32,7 +79,7 @@ pub async fn build(
     let _correlation = ctx.push_correlation(correlation_label);
 
     ensure_recipe_path(recipe_path)?;
-    let (package_name, package_version) = load_recipe_metadata(recipe_path).await?;
+    let (package_name, package_version, arch) = load_recipe_metadata(recipe_path).await?;
     let (session, target, session_id) =
         build_session(package_name.clone(), package_version.clone());
 
@@ -49,13 +96,20 @@ pub async fn build(
         canonical_recipe_path,
         output_directory,
     )
+    .with_arch(arch.to_string())
     .with_event_sender(ctx.tx.clone())
     .with_session_id(session_id.clone());
 
-    let builder = configure_builder(ctx, network, jobs);
+    let remote = RemoteBuilder::from_settings(&ctx.config.builder.remote).filter(|_| is_client);
+    let result = if let Some(remote) = remote {
+        ctx.emit_debug(format!("Building on {}", remote.host()));
+        remote.with_network(network).build(&build_context).await
+    } else {
+        let builder = configure_builder(ctx, ctx.resolver().await?.clone(), network, jobs);
+        Box::pin(builder.build(build_context)).await
+    };
 
-    // Use the builder with custom configuration
-    let result = match builder.build(build_context).await {
+    let result = match result {
         Ok(result) => result,
         Err(error) => {
             ctx.emit(AppEvent::Build(BuildEvent::Failed {
@@ -70,12 +124,12 @@ pub async fn build(
     };
 
     // Check if install was requested during recipe execution
-    if result.install_requested {
+    if result.install_requested && is_client {
         ctx.emit_operation_started("Building package");
 
         // Install the built package
         let package_path_str = result.package_path.to_string_lossy().to_string();
-        let _install_report = crate::install(ctx, &[package_path_str], false).await?;
+        let _install_report = crate::install(ctx, &[package_path_str], false, None).await?;
 
         ctx.emit_operation_completed(
             format!("Installed {package_name} {package_version} successfully"),
@@ -84,10 +138,12 @@ pub async fn build(
     }
 
     let report = BuildReport {
+        schema_version: BuildReport::SCHEMA_VERSION,
         package: package_name,
         version: package_version,
         output_path: result.package_path,
         duration_ms: elapsed_millis(start),
+        tests: result.tests,
     };
 
     ctx.emit(AppEvent::Build(BuildEvent::Completed {
@@ -100,6 +156,218 @@ pub async fn build(
     Ok(report)
 }
 
+/// Build a recipe after its unbuilt dependencies
+///
+/// Build dependencies that no installed package satisfies are looked up in
+/// `recipe_dir` (the recipe's own directory by default). Those with a recipe
+/// there are built first, in dependency order, and each is installed before
+/// the next build so dependency resolution finds it. Dependencies without a
+/// local recipe are left to the builder, which reports them as missing.
+/// YAML files in `recipe_dir` that are not recipes are skipped with a
+/// warning.
+///
+/// Packages installed for the builds are removed again once the requested
+/// recipe is built, or has failed to build. One that a package installed
+/// since needs is kept, with a warning.
+///
+/// Returns the reports of every build, the requested recipe last.
+///
+/// # Errors
+///
+/// Returns an error if the recipe directory cannot be read, the local
+/// recipes depend on each other in a cycle, or any build or install fails.
+pub async fn build_recursive(
+    ctx: &OpsCtx,
+    recipe_path: &Path,
+    recipe_dir: Option<&Path>,
+    output_dir: Option<&Path>,
+    network: bool,
+    jobs: Option<usize>,
+) -> Result<Vec<BuildReport>, Error> {
+    ensure_recipe_path(recipe_path)?;
+    let recipe_dir = recipe_dir
+        .map(Path::to_path_buf)
+        .or_else(|| recipe_path.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let root = parse_yaml_recipe(recipe_path).await?;
+    let root_deps = parse_specs(recipe_path, &root.metadata.dependencies.build)?;
+    let (recipes, skipped) = local_recipes(&recipe_dir).await?;
+    for (path, error) in skipped {
+        ctx.emit_warning(format!(
+            "{} is not a recipe and is skipped: {error}",
+            path.display()
+        ));
+    }
+    let mut installed = Vec::new();
+    for package in ctx.state.get_installed_packages().await? {
+        installed.push((package.name.clone(), package.version()));
+    }
+    let order = build_order(&root_deps, &recipes, &installed)?;
+
+    let mut reports = Vec::new();
+    let result = async {
+        for name in order {
+            let recipe = &recipes[&name];
+            let report = Box::pin(build(ctx, &recipe.path, output_dir, network, jobs)).await?;
+            // A recipe may have asked for its package to be installed already
+            let is_installed = ctx
+                .state
+                .get_installed_packages()
+                .await?
+                .iter()
+                .any(|package| {
+                    package.name == report.package && package.version() == report.version
+                });
+            if !is_installed {
+                let package_path = report.output_path.to_string_lossy().to_string();
+                crate::install(ctx, &[package_path], false, None).await?;
+            }
+            reports.push(report);
+        }
+        Box::pin(build(ctx, recipe_path, output_dir, network, jobs)).await
+    }
+    .await;
+
+    remove_build_installs(ctx, &installed, &root.metadata.name).await;
+    reports.push(result?);
+    Ok(reports)
+}
+
+/// Remove the packages installed since `before`, except `keep`
+async fn remove_build_installs(ctx: &OpsCtx, before: &[(String, Version)], keep: &str) {
+    let added: Vec<String> = match ctx.state.get_installed_packages().await {
+        Ok(packages) => packages
+            .into_iter()
+            .map(|package| package.name)
+            .filter(|name| name != keep && !before.iter().any(|(old, _)| old == name))
+            .collect(),
+        Err(error) => {
+            ctx.emit_warning(format!(
+                "Cannot read the installed packages to remove build dependencies: {error}"
+            ));
+            return;
+        }
+    };
+    if added.is_empty() {
+        return;
+    }
+    if let Err(error) = crate::uninstall(ctx, &added, crate::DependentsPolicy::Block).await {
+        ctx.emit_warning(format!(
+            "Kept the packages installed for the build ({}): {error}",
+            added.join(", ")
+        ));
+    }
+}
+
+/// A recipe found in the recipe directory
+struct LocalRecipe {
+    path: PathBuf,
+    version: Version,
+    /// Build and runtime dependencies: the package is installed once built
+    dependencies: Vec<PackageSpec>,
+}
+
+/// Recipes in `dir` by package name, and the YAML files that are not
+/// recipes with the reason
+async fn local_recipes(
+    dir: &Path,
+) -> Result<(HashMap<String, LocalRecipe>, Vec<(PathBuf, Error)>), Error> {
+    let mut recipes = HashMap::new();
+    let mut skipped = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        if !matches!(extension, Some("yaml" | "yml")) {
+            continue;
+        }
+        match local_recipe(&path).await {
+            Ok((name, recipe)) => {
+                recipes.insert(name, recipe);
+            }
+            Err(error) => skipped.push((path, error)),
+        }
+    }
+    Ok((recipes, skipped))
+}
+
+async fn local_recipe(path: &Path) -> Result<(String, LocalRecipe), Error> {
+    let recipe = parse_yaml_recipe(path).await?;
+    let dependencies = &recipe.metadata.dependencies;
+    let dependencies = parse_specs(path, dependencies.build.iter().chain(&dependencies.runtime))?;
+    Ok((
+        recipe.metadata.name.clone(),
+        LocalRecipe {
+            version: Version::parse(&recipe.metadata.version)?,
+            path: path.to_path_buf(),
+            dependencies,
+        },
+    ))
+}
+
+fn parse_specs<'a>(
+    recipe_path: &Path,
+    specs: impl IntoIterator<Item = &'a String>,
+) -> Result<Vec<PackageSpec>, Error> {
+    specs
+        .into_iter()
+        .map(|spec| {
+            PackageSpec::parse(spec).map_err(|e| {
+                OpsError::InvalidRecipe {
+                    path: recipe_path.display().to_string(),
+                    reason: format!("invalid dependency '{spec}': {e}"),
+                }
+                .into()
+            })
+        })
+        .collect()
+}
+
+/// Local recipes to build for `deps`, each after the recipes it depends on
+fn build_order(
+    deps: &[PackageSpec],
+    recipes: &HashMap<String, LocalRecipe>,
+    installed: &[(String, Version)],
+) -> Result<Vec<String>, Error> {
+    fn visit(
+        deps: &[PackageSpec],
+        recipes: &HashMap<String, LocalRecipe>,
+        installed: &[(String, Version)],
+        visiting: &mut HashSet<String>,
+        order: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        for dep in deps {
+            let is_installed = installed
+                .iter()
+                .any(|(name, version)| *name == dep.name && dep.version_spec.matches(version));
+            let Some(recipe) = recipes.get(&dep.name) else {
+                continue;
+            };
+            if is_installed
+                || order.contains(&dep.name)
+                || !dep.version_spec.matches(&recipe.version)
+            {
+                continue;
+            }
+            if !visiting.insert(dep.name.clone()) {
+                return Err(PackageError::DependencyCycle {
+                    package: dep.name.clone(),
+                }
+                .into());
+            }
+            visit(&recipe.dependencies, recipes, installed, visiting, order)?;
+            visiting.remove(&dep.name);
+            order.push(dep.name.clone());
+        }
+        Ok(())
+    }
+
+    let mut order = Vec::new();
+    visit(deps, recipes, installed, &mut HashSet::new(), &mut order)?;
+    Ok(order)
+}
+
 fn elapsed_millis(start: Instant) -> u64 {
     u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX)
 }
@@ -124,10 +392,11 @@ fn ensure_recipe_path(recipe_path: &Path) -> Result<(), Error> {
     .into())
 }
 
-async fn load_recipe_metadata(recipe_path: &Path) -> Result<(String, Version), Error> {
+async fn load_recipe_metadata(recipe_path: &Path) -> Result<(String, Version, Arch), Error> {
     let yaml_recipe = parse_yaml_recipe(recipe_path).await?;
     let version = Version::parse(&yaml_recipe.metadata.version)?;
-    Ok((yaml_recipe.metadata.name.clone(), version))
+    let arch = yaml_recipe.environment.arch.unwrap_or_else(Arch::host);
+    Ok((yaml_recipe.metadata.name.clone(), version, arch))
 }
 
 fn build_session(
@@ -164,15 +433,143 @@ fn canonicalize_recipe_path(recipe_path: &Path) -> Result<PathBuf, Error> {
     })
 }
 
-fn configure_builder(ctx: &OpsCtx, network: bool, jobs: Option<usize>) -> sps2_builder::Builder {
+fn configure_builder(
+    ctx: &OpsCtx,
+    resolver: sps2_resolver::Resolver,
+    network: bool,
+    jobs: Option<usize>,
+) -> sps2_builder::Builder {
+    sps2_builder::Builder::with_config(builder_config(&ctx.config, network, jobs))
+        .with_resolver(resolver)
+        .with_store(ctx.store.clone())
+}
+
+/// Builder settings taken from the configuration in use
+fn builder_config(
+    config: &sps2_config::Config,
+    network: bool,
+    jobs: Option<usize>,
+) -> sps2_builder::BuildConfig {
     let mut builder_config = sps2_builder::BuildConfig::default();
     if network {
         builder_config.config.build.default_allow_network = true;
     }
-    let _ = jobs;
-    builder_config.sps2_config = Some(ctx.config.clone());
+    let settings = &config.builder.build;
+    builder_config.config.build.build_jobs = jobs.unwrap_or(settings.build_jobs);
+    builder_config.config.build.timeout_seconds = settings.timeout_seconds;
+    builder_config.config.build.max_memory_mb = settings.max_memory_mb;
+    builder_config.config.performance.cache = config.builder.performance.cache.clone();
+    builder_config.config.qa = config.builder.qa.clone();
+    builder_config.sps2_config = Some(config.clone());
+    builder_config
+}
 
-    sps2_builder::Builder::with_config(builder_config)
-        .with_resolver(ctx.resolver.clone())
-        .with_store(ctx.store.clone())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recipe(name: &str, version: &str, dependencies: &[&str]) -> (String, LocalRecipe) {
+        (
+            name.to_string(),
+            LocalRecipe {
+                path: PathBuf::from(format!("{name}.yaml")),
+                version: Version::parse(version).unwrap(),
+                dependencies: dependencies
+                    .iter()
+                    .map(|spec| PackageSpec::parse(spec).unwrap())
+                    .collect(),
+            },
+        )
+    }
+
+    fn specs(specs: &[&str]) -> Vec<PackageSpec> {
+        specs
+            .iter()
+            .map(|spec| PackageSpec::parse(spec).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn dependencies_build_before_their_dependents() {
+        let recipes = HashMap::from([
+            recipe("app-lib", "2.0.0", &["zlib>=1.3.0", "cmake>=3.0.0"]),
+            recipe("zlib", "1.3.1", &[]),
+            recipe("cmake", "3.30.0", &["zlib>=1.0.0"]),
+        ]);
+        let order = build_order(&specs(&["app-lib>=2.0.0"]), &recipes, &[]).unwrap();
+        assert_eq!(order, ["zlib", "cmake", "app-lib"]);
+    }
+
+    #[test]
+    fn installed_and_unmatched_dependencies_are_not_built() {
+        let recipes = HashMap::from([recipe("zlib", "1.3.1", &[]), recipe("cmake", "3.30.0", &[])]);
+        let installed = [("zlib".to_string(), Version::parse("1.3.0").unwrap())];
+        let deps = specs(&["zlib>=1.2.0", "cmake>=4.0.0", "pkgconf>=2.0.0"]);
+        assert!(build_order(&deps, &recipes, &installed).unwrap().is_empty());
+    }
+
+    #[test]
+    fn recipe_cycles_are_reported() {
+        let recipes = HashMap::from([
+            recipe("a", "1.0.0", &["b>=1.0.0"]),
+            recipe("b", "1.0.0", &["a>=1.0.0"]),
+        ]);
+        let error = build_order(&specs(&["a>=1.0.0"]), &recipes, &[]).unwrap_err();
+        assert!(error.to_string().contains("cycle"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn configured_qa_plugins_run_in_builds() {
+        use sps2_builder::artifact_qa::plugins::PluginAction;
+        use sps2_config::builder::{QaPlugin, QaPluginKind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("site-policy-ran");
+        let mut config = sps2_config::Config::default();
+        config.builder.qa.plugins.push(QaPlugin {
+            name: "site-policy".to_string(),
+            kind: QaPluginKind::Validator,
+            command: PathBuf::from("/bin/sh"),
+            args: vec!["-c".to_string(), format!("touch '{}'", marker.display())],
+        });
+
+        let build_config = builder_config(&config, false, None);
+        let context = sps2_builder::BuildContext::new(
+            "tool".to_string(),
+            Version::parse("1.0.0").unwrap(),
+            dir.path().join("tool.yaml"),
+            dir.path().to_path_buf(),
+        );
+        let environment = sps2_builder::BuildEnvironment::new(context.clone(), dir.path()).unwrap();
+        std::fs::create_dir_all(environment.staging_dir()).unwrap();
+
+        let plugins = &build_config.qa_settings().plugins;
+        assert_eq!(plugins.len(), 1);
+        for plugin in plugins {
+            let report = PluginAction::new(plugin.clone())
+                .run(&context, &environment)
+                .await
+                .unwrap();
+            assert!(report.errors.is_empty(), "{:?}", report.errors);
+        }
+        assert!(marker.exists());
+    }
+
+    #[tokio::test]
+    async fn yaml_files_that_are_not_recipes_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("zlib.yaml"),
+            "metadata:\n  name: zlib\n  version: 1.3.1\n  description: Compression\n  \
+             license: Zlib\nsource:\n  local:\n    path: src\nbuild:\n  system: make\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("ci.yml"), "on: push\njobs: {}\n").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not yaml").unwrap();
+
+        let (recipes, skipped) = local_recipes(dir.path()).await.unwrap();
+        assert_eq!(recipes.keys().collect::<Vec<_>>(), ["zlib"]);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, dir.path().join("ci.yml"));
+    }
 }
@@ -0,0 +1,213 @@
+//! Per-cache listing and clearing
+//!
+//! Each [`CacheKind`] maps to the files or directory it keeps on disk, so
+//! caches can be inspected and cleared one at a time instead of through the
+//! state and store garbage collection done by [`cleanup`](crate::cleanup).
+//! Clearing a directory cache removes its contents and keeps the directory.
+
+use crate::OpsCtx;
+use sps2_config::Config;
+use sps2_errors::Error;
+use sps2_index::IndexCache;
+use sps2_platform::core::PlatformCache;
+use sps2_types::CacheKind;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Where a cache keeps its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Location {
+    /// Everything inside the directory
+    Contents(PathBuf),
+    /// Individual files
+    Files(Vec<PathBuf>),
+    /// Not kept on disk with the current configuration
+    Unconfigured(&'static str),
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Contents(dir) => write!(f, "{}", dir.display()),
+            Self::Files(files) => {
+                let files: Vec<_> = files
+                    .iter()
+                    .map(|file| file.display().to_string())
+                    .collect();
+                f.write_str(&files.join(", "))
+            }
+            Self::Unconfigured(reason) => f.write_str(reason),
+        }
+    }
+}
+
+/// List every cache with its location and size
+///
+/// # Errors
+///
+/// Returns an error if a cache location cannot be determined or measured.
+pub async fn cache_list(ctx: &OpsCtx) -> Result<String, Error> {
+    let mut lines = Vec::with_capacity(CacheKind::ALL.len());
+    let mut total = 0;
+    for kind in CacheKind::ALL {
+        let location = location(&ctx.config, kind)?;
+        let size = size(&location).await?;
+        total += size;
+        lines.push(format!("{kind:<15} {size:>12} bytes  {location}"));
+    }
+    lines.push(format!("{:<15} {total:>12} bytes", "total"));
+    Ok(lines.join("\n"))
+}
+
+/// Clear the given caches, or report what would be freed in check mode
+///
+/// # Errors
+///
+/// Returns an error if a cache location cannot be determined or a file
+/// cannot be removed.
+pub async fn cache_clear(ctx: &OpsCtx, kinds: &[CacheKind]) -> Result<String, Error> {
+    let mut lines = Vec::with_capacity(kinds.len() + 1);
+    let mut total = 0;
+    for &kind in kinds {
+        let location = location(&ctx.config, kind)?;
+        if let Location::Unconfigured(reason) = location {
+            lines.push(format!("{kind:<15} skipped: {reason}"));
+            continue;
+        }
+
+        let freed = if ctx.check_mode {
+            size(&location).await?
+        } else {
+            clear(&location).await?
+        };
+        total += freed;
+        lines.push(format!("{kind:<15} {freed:>12} bytes"));
+    }
+
+    let verb = if ctx.check_mode {
+        "Would free"
+    } else {
+        "Freed"
+    };
+    lines.push(format!("{verb} {total} bytes"));
+    Ok(lines.join("\n"))
+}
+
+pub(crate) fn location(config: &Config, kind: CacheKind) -> Result<Location, Error> {
+    let builder = &config.builder;
+    Ok(match kind {
+        CacheKind::Index => Location::Files(IndexCache::new(config.prefix_path()).files().into()),
+        CacheKind::Downloads => Location::Contents(config.downloads_path()),
+        CacheKind::Quarantine => Location::Contents(config.quarantine_path()),
+        CacheKind::BuildSources => Location::Contents(builder.build.build_root.clone()),
+        CacheKind::BuildArtifacts => {
+            Location::Contents(builder.performance.cache.artifact_dir.clone())
+        }
+        CacheKind::Compiler => match &builder.performance.cache.cache_dir {
+            Some(dir) => Location::Contents(dir.clone()),
+            None => Location::Unconfigured("no cache_dir configured"),
+        },
+        CacheKind::PlatformTools => Location::Files(vec![PlatformCache::default_path()?]),
+        CacheKind::Temp => Location::Contents(config.tmp_path()),
+    })
+}
+
+/// Bytes the cache currently occupies; missing files count as empty
+pub(crate) async fn size(location: &Location) -> Result<u64, Error> {
+    match location {
+        Location::Contents(dir) => path_size(dir).await,
+        Location::Files(files) => {
+            let mut total = 0;
+            for file in files {
+                total += path_size(file).await?;
+            }
+            Ok(total)
+        }
+        Location::Unconfigured(_) => Ok(0),
+    }
+}
+
+/// Remove the cached data and return the bytes freed
+async fn clear(location: &Location) -> Result<u64, Error> {
+    let mut freed = 0;
+    match location {
+        Location::Contents(dir) => {
+            let mut entries = match fs::read_dir(dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                freed += remove(&entry.path()).await?;
+            }
+        }
+        Location::Files(files) => {
+            for file in files {
+                freed += remove(file).await?;
+            }
+        }
+        Location::Unconfigured(_) => {}
+    }
+    Ok(freed)
+}
+
+async fn path_size(path: &Path) -> Result<u64, Error> {
+    if fs::symlink_metadata(path).await.is_err() {
+        return Ok(0);
+    }
+    sps2_platform::fs::size(path).await
+}
+
+/// Remove a file or directory tree, returning its size
+pub(crate) async fn remove(path: &Path) -> Result<u64, Error> {
+    let Ok(metadata) = fs::symlink_metadata(path).await else {
+        return Ok(0);
+    };
+    let size = path_size(path).await?;
+    if metadata.is_dir() {
+        sps2_platform::fs::remove_dir_all(path).await?;
+    } else {
+        sps2_platform::fs::remove_file(path).await?;
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn clearing_contents_keeps_directory_and_reports_size() {
+        let temp = TempDir::new().unwrap();
+        let dir = temp.path().join("sources");
+        fs::create_dir_all(dir.join("pkg/src")).await.unwrap();
+        fs::write(dir.join("pkg/src/a.tar.gz"), vec![0u8; 100])
+            .await
+            .unwrap();
+        fs::write(dir.join("stray.log"), vec![0u8; 20])
+            .await
+            .unwrap();
+
+        let location = Location::Contents(dir.clone());
+        assert_eq!(size(&location).await.unwrap(), 120);
+        assert_eq!(clear(&location).await.unwrap(), 120);
+        assert!(dir.is_dir());
+        assert_eq!(size(&location).await.unwrap(), 0);
+
+        let missing = Location::Contents(temp.path().join("missing"));
+        assert_eq!(clear(&missing).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn clearing_files_skips_missing_ones() {
+        let temp = TempDir::new().unwrap();
+        let present = temp.path().join("index.json");
+        fs::write(&present, b"{}").await.unwrap();
+
+        let location = Location::Files(vec![present.clone(), temp.path().join("index.meta")]);
+        assert_eq!(size(&location).await.unwrap(), 2);
+        assert_eq!(clear(&location).await.unwrap(), 2);
+        assert!(!present.exists());
+    }
+}
@@ -1,27 +1,40 @@
 //! Operations context for dependency injection
-
+//!
+//! The store, state, event sender and configuration are required up front.
+//! The index, network client, resolver and builder are comparatively costly
+//! to set up (index parsing, TLS initialization), so they are created from the
+//! configuration on first use unless supplied to the builder. Operations
+//! declare the components they rely on through [`Requirements`](crate::Requirements), which lets
+//! callers initialize exactly those before running them.
+
+use crate::Requirements;
 use sps2_builder::Builder;
 use sps2_config::Config;
-use sps2_events::{EventEmitter, EventSender};
+use sps2_errors::{Error, OpsError};
+use sps2_events::{AppEvent, EventEmitter, EventSender, GeneralEvent};
 use sps2_index::IndexManager;
-use sps2_net::NetClient;
+use sps2_install::{InstallConfig, SecurityPolicy};
+use sps2_net::{DownloadCache, MirrorGroup, NetClient, NetConfig, PackageDownloadConfig};
 use sps2_resolver::Resolver;
 use sps2_state::StateManager;
 use sps2_store::PackageStore;
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::fmt::Write;
+use std::time::Duration;
 
 /// Operations context providing access to all system components.
 pub struct OpsCtx {
     pub store: PackageStore,
     pub state: StateManager,
-    pub index: IndexManager,
-    pub net: NetClient,
-    pub resolver: Resolver,
-    pub builder: Builder,
     pub tx: EventSender,
     pub config: Config,
     pub check_mode: bool,
+    /// Command line the operation was started from, for the audit trail
+    pub command_line: Option<String>,
+    index: tokio::sync::OnceCell<IndexManager>,
+    net: OnceCell<NetClient>,
+    resolver: tokio::sync::OnceCell<Resolver>,
+    builder: OnceCell<Builder>,
     correlation_id: RefCell<Option<String>>,
 }
 
@@ -43,6 +56,138 @@ impl EventEmitter for OpsCtx {
 }
 
 impl OpsCtx {
+    /// Package index, loaded from the local cache on first use
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if neither the cached index nor an empty index can be loaded.
+    pub async fn index(&self) -> Result<&IndexManager, Error> {
+        self.index.get_or_try_init(|| self.load_index()).await
+    }
+
+    /// Network client, created from the network configuration on first use
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn net(&self) -> Result<&NetClient, Error> {
+        if let Some(net) = self.net.get() {
+            return Ok(net);
+        }
+        let net = NetClient::new(net_config(&self.config))?.with_event_sender(self.tx.clone());
+        Ok(self.net.get_or_init(|| net))
+    }
+
+    /// Dependency resolver over the package index
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index cannot be loaded.
+    pub async fn resolver(&self) -> Result<&Resolver, Error> {
+        self.resolver
+            .get_or_try_init(|| async {
+                Ok::<_, Error>(Resolver::new(self.index().await?.clone()))
+            })
+            .await
+    }
+
+    /// Package builder using the shared network client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the network client cannot be created.
+    pub fn builder(&self) -> Result<&Builder, Error> {
+        if let Some(builder) = self.builder.get() {
+            return Ok(builder);
+        }
+        let builder = Builder::new().with_net(self.net()?.clone());
+        Ok(self.builder.get_or_init(|| builder))
+    }
+
+    /// Initialize the components an operation requires ahead of running it
+    ///
+    /// Components are otherwise created on first use; preparing them up front
+    /// surfaces setup failures before the operation starts emitting events.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any required component fails to initialize.
+    pub async fn prepare(&self, requirements: Requirements) -> Result<(), Error> {
+        if requirements.index() {
+            self.index().await?;
+        }
+        if requirements.net() {
+            self.net()?;
+        }
+        if requirements.resolver() {
+            self.resolver().await?;
+        }
+        if requirements.builder() {
+            self.builder()?;
+        }
+        Ok(())
+    }
+
+    /// Signature policy for package downloads from the security configuration
+    pub(crate) fn security_policy(&self) -> SecurityPolicy {
+        SecurityPolicy {
+            verify_signatures: self.config.security.verify_signatures,
+            allow_unsigned: self.config.security.allow_unsigned,
+            strip_quarantine: self.config.security.strip_quarantine,
+            allow_setuid: self.config.security.allow_setuid,
+        }
+    }
+
+    /// Download settings pointing at the configured keys, quarantine and
+    /// download cache
+    pub(crate) fn download_config(&self) -> PackageDownloadConfig {
+        let cas = &self.config.cas;
+        PackageDownloadConfig {
+            quarantine_dir: self.config.quarantine_path(),
+            keys_dir: self.config.keys_path(),
+            cache: (cas.downloads_keep_days > 0 && cas.downloads_max_mb > 0)
+                .then(|| self.download_cache()),
+            ..PackageDownloadConfig::default()
+        }
+    }
+
+    /// Download cache with the `[cas]` retention policy
+    pub(crate) fn download_cache(&self) -> DownloadCache {
+        let cas = &self.config.cas;
+        DownloadCache::new(
+            self.config.downloads_path(),
+            Duration::from_secs(u64::from(cas.downloads_keep_days) * 86_400),
+            cas.downloads_max_mb.saturating_mul(1024 * 1024),
+        )
+    }
+
+    /// Installer settings derived from the user configuration
+    pub(crate) fn install_config(&self) -> InstallConfig {
+        InstallConfig::default()
+            .with_offline(self.config.network.offline)
+            .with_recommends(self.config.general.install_recommends)
+            .with_conflict_policy(self.config.general.conflict_policy)
+            .with_security_policy(self.security_policy())
+            .with_download_config(self.download_config())
+            .with_os_snapshots(self.config.state.os_snapshot_min_changes)
+    }
+
+    async fn load_index(&self) -> Result<IndexManager, Error> {
+        let policy = sps2_index::ValidationPolicy::default()
+            .with_allow_insecure_urls(self.config.security.allow_insecure_index_urls);
+        let mut index = IndexManager::new(self.config.prefix_path()).with_validation_policy(policy);
+
+        if let Err(e) = index.load(None).await {
+            self.emit(AppEvent::General(GeneralEvent::debug(format!(
+                "No usable cached index, run reposync to fetch one: {e}"
+            ))));
+            let empty = sps2_index::Index::new().to_json()?;
+            index.load(Some(&empty)).await?;
+        }
+
+        Ok(index)
+    }
+
     #[must_use]
     pub fn push_correlation(&self, correlation: impl Into<String>) -> CorrelationGuard<'_> {
         let mut slot = self.correlation_id.borrow_mut();
@@ -104,6 +249,7 @@ pub struct OpsContextBuilder {
     tx: Option<EventSender>,
     config: Option<Config>,
     check_mode: Option<bool>,
+    command_line: Option<String>,
 }
 
 impl OpsContextBuilder {
@@ -119,6 +265,7 @@ impl OpsContextBuilder {
             tx: None,
             config: None,
             check_mode: None,
+            command_line: None,
         }
     }
 
@@ -176,73 +323,78 @@ impl OpsContextBuilder {
         self
     }
 
+    #[must_use]
+    pub fn with_command_line(mut self, command_line: impl Into<String>) -> Self {
+        self.command_line = Some(command_line.into());
+        self
+    }
+
+    /// Build the context
+    ///
+    /// The index, network client, resolver and builder may be omitted; they
+    /// are created from the configuration when first used.
+    ///
     /// # Errors
     ///
-    /// Returns an error if any required dependency is missing from the builder.
-    pub fn build(self) -> Result<OpsCtx, sps2_errors::Error> {
-        let store = self
-            .store
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "store".to_string(),
-            })?;
-
-        let state = self
-            .state
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "state".to_string(),
-            })?;
-
-        let index = self
-            .index
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "index".to_string(),
-            })?;
+    /// Returns an error if the store, state, event sender or configuration is
+    /// missing, or if a resolver is supplied without the index it was built on.
+    pub fn build(self) -> Result<OpsCtx, Error> {
+        let missing = |component: &str| OpsError::MissingComponent {
+            component: component.to_string(),
+        };
+
+        let store = self.store.ok_or_else(|| missing("store"))?;
+        let state = self.state.ok_or_else(|| missing("state"))?;
+        let tx = self.tx.ok_or_else(|| missing("event_sender"))?;
+        let config = self.config.ok_or_else(|| missing("config"))?;
+
+        // A resolver carries its own copy of the index; lazily loading a
+        // second one would let queries and resolution disagree
+        if self.resolver.is_some() && self.index.is_none() {
+            return Err(missing("index (required alongside an explicit resolver)").into());
+        }
 
         let net = self
             .net
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "net".to_string(),
-            })?;
-
-        let resolver = self
-            .resolver
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "resolver".to_string(),
-            })?;
-
-        let builder = self
-            .builder
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "builder".to_string(),
-            })?;
-
-        let tx = self
-            .tx
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "event_sender".to_string(),
-            })?;
-
-        let config = self
-            .config
-            .ok_or_else(|| sps2_errors::OpsError::MissingComponent {
-                component: "config".to_string(),
-            })?;
+            .map(|net| OnceCell::from(net.with_event_sender(tx.clone())))
+            .unwrap_or_default();
 
         Ok(OpsCtx {
             store,
             state,
-            index,
-            net,
-            resolver,
-            builder,
             tx,
             config,
             check_mode: self.check_mode.unwrap_or(false),
+            command_line: self.command_line,
+            index: tokio::sync::OnceCell::new_with(self.index),
+            net,
+            resolver: tokio::sync::OnceCell::new_with(self.resolver),
+            builder: self.builder.map(OnceCell::from).unwrap_or_default(),
             correlation_id: RefCell::new(None),
         })
     }
 }
 
+/// Network client settings derived from the user configuration
+fn net_config(config: &Config) -> NetConfig {
+    NetConfig {
+        timeout: Duration::from_secs(config.network.timeout),
+        retry_count: config.network.retries,
+        retry_delay: Duration::from_secs(config.network.retry_delay),
+        max_retry_after: Duration::from_secs(config.network.max_retry_after),
+        offline: config.network.offline,
+        proxy: config.network.proxy.clone(),
+        mirrors: config
+            .repos
+            .get_all()
+            .into_iter()
+            .filter(|repo| !repo.mirrors.is_empty())
+            .map(|repo| MirrorGroup::new(repo.url.clone(), repo.mirrors.clone()))
+            .collect(),
+        ..NetConfig::default()
+    }
+}
+
 impl Default for OpsContextBuilder {
     fn default() -> Self {
         Self::new()
@@ -292,4 +444,39 @@ mod tests {
         }
         assert!(ctx.current_correlation().is_none());
     }
+
+    #[tokio::test]
+    async fn build_defers_heavy_components() {
+        let temp_dir = TempDir::new().unwrap();
+        let state_dir = temp_dir.path().join("state");
+        let store_dir = temp_dir.path().join("store");
+
+        tokio::fs::create_dir_all(&state_dir).await.unwrap();
+        tokio::fs::create_dir_all(&store_dir).await.unwrap();
+
+        let state = StateManager::new(&state_dir).await.unwrap();
+        let store = PackageStore::new(store_dir.clone());
+        let (tx, _rx) = sps2_events::channel();
+
+        let ctx = OpsContextBuilder::new()
+            .with_state(state.clone())
+            .with_store(store.clone())
+            .with_event_sender(tx.clone())
+            .with_config(Config::default())
+            .build()
+            .unwrap();
+        assert!(ctx.net.get().is_none());
+        assert!(ctx.net().is_ok());
+
+        // An explicit resolver must come with the index it was built on
+        let index = IndexManager::new(&store_dir);
+        let result = OpsContextBuilder::new()
+            .with_state(state)
+            .with_store(store)
+            .with_event_sender(tx)
+            .with_config(Config::default())
+            .with_resolver(Resolver::new(index))
+            .build();
+        assert!(result.is_err());
+    }
 }
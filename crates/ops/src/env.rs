@@ -0,0 +1,322 @@
+//! Shell environment for the live prefix
+//!
+//! Prints the statements that put the live prefix on the search paths of the
+//! shell, of `man`, and of compilers and pkg-config, for `eval` in a shell
+//! startup file. Each variable is prepended to, so what it held before
+//! still works, and an unset `MANPATH` ends in `:` so `man` keeps its
+//! default search path. The variables installed packages declare follow,
+//! from the profile snippet the installer generates for each state.
+
+use crate::OpsCtx;
+use sps2_errors::{Error, InstallError};
+use sps2_hash::Hash;
+use sps2_install::ProfileEnvironment;
+use sps2_state::queries;
+use sps2_types::Shell;
+use std::ffi::OsStr;
+use std::path::Path;
+use tokio::fs;
+
+/// A search path variable and the live directories it gets
+struct SearchPath {
+    var: &'static str,
+    dirs: &'static [&'static str],
+    /// Whether files in subdirectories count when deciding if a package
+    /// needs the variable
+    nested: bool,
+}
+
+const SEARCH_PATHS: [SearchPath; 5] = [
+    SearchPath {
+        var: "PATH",
+        dirs: &["bin"],
+        nested: false,
+    },
+    SearchPath {
+        var: "MANPATH",
+        dirs: &["share/man"],
+        nested: true,
+    },
+    SearchPath {
+        var: "PKG_CONFIG_PATH",
+        dirs: &["lib/pkgconfig", "share/pkgconfig"],
+        nested: false,
+    },
+    SearchPath {
+        var: "CPATH",
+        dirs: &["include"],
+        nested: true,
+    },
+    SearchPath {
+        var: "LIBRARY_PATH",
+        dirs: &["lib"],
+        nested: false,
+    },
+];
+
+/// Statements setting the search paths for the live prefix and the
+/// variables installed packages declare
+///
+/// With `package`, only the search paths that package installs files for
+/// and the variables it declares are set. Without it, nothing is set inside
+/// `sps2 shell`, whose environment already has the search paths. Without
+/// `shell`, the shell is taken from `$SHELL`, and zsh is assumed when that
+/// names none of the supported shells.
+///
+/// # Errors
+///
+/// Returns an error if `package` is not installed or the state database
+/// cannot be read.
+pub async fn env(
+    ctx: &OpsCtx,
+    package: Option<&str>,
+    shell: Option<Shell>,
+) -> Result<String, Error> {
+    let shell = shell.unwrap_or_else(login_shell);
+    let live = ctx.state.live_path();
+
+    let Some(package) = package else {
+        // A startup file evaluating this in `sps2 shell` would put the live
+        // prefix back in front of the project's environment
+        if let Some(project) = std::env::var_os("SPS2_PROJECT") {
+            return Ok(format!(
+                "# Search paths are set for the environment of {}",
+                Path::new(&project).display()
+            ));
+        }
+        let search_paths = statements(shell, live, SEARCH_PATHS.iter());
+        return Ok(
+            match fs::read_to_string(live.join(shell.profile_snippet())).await {
+                Ok(snippet) => format!("{search_paths}\n{}", snippet.trim_end()),
+                Err(_) => search_paths,
+            },
+        );
+    };
+    let mut tx = ctx.state.begin_transaction().await?;
+    let state_id = queries::get_active_state(&mut tx).await?;
+    let installed = queries::get_state_packages(&mut tx, &state_id)
+        .await?
+        .into_iter()
+        .find(|installed| installed.name == package)
+        .ok_or_else(|| InstallError::PackageNotInstalled {
+            package: package.to_string(),
+        })?;
+    let files =
+        queries::get_package_file_listing(&mut tx, &state_id, &installed.name, &installed.version)
+            .await?;
+    tx.commit().await?;
+
+    let paths: Vec<&str> = files
+        .iter()
+        .filter(|file| !file.is_directory)
+        .map(|file| file.path.as_str())
+        .collect();
+    let needed: Vec<&SearchPath> = SEARCH_PATHS
+        .iter()
+        .filter(|search| paths.iter().any(|path| search.covers(path)))
+        .collect();
+    let manifest_path = ctx
+        .store
+        .package_path(&Hash::from_hex(&installed.hash)?)
+        .join("manifest.toml");
+    let manifest = sps2_store::manifest_io::read_manifest(&manifest_path).await?;
+    let profile =
+        ProfileEnvironment::collect(live, [(installed.name.as_str(), &manifest.environment)]);
+
+    let lines: Vec<String> = [
+        statements(shell, live, needed.into_iter()),
+        profile.render(shell),
+    ]
+    .into_iter()
+    .filter(|lines| !lines.is_empty())
+    .collect();
+    if lines.is_empty() {
+        return Ok(format!(
+            "# {package} needs no search path or environment variable"
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// How to set up the environment when the live `bin/` directory is not on
+/// `path_var`
+///
+/// `sps2` is the path of the sps2 executable, for the startup file line.
+#[must_use]
+pub fn env_hint(bin_dir: &Path, path_var: &OsStr, sps2: &Path) -> Option<String> {
+    if std::env::split_paths(path_var).any(|dir| dir == bin_dir) {
+        return None;
+    }
+    let shell = login_shell();
+    let (line, rc_file) = match shell {
+        Shell::Zsh => (format!("eval \"$({} env)\"", sps2.display()), "~/.zshrc"),
+        Shell::Bash => (
+            format!("eval \"$({} env)\"", sps2.display()),
+            "~/.bash_profile",
+        ),
+        Shell::Fish => (
+            format!("{} env --shell fish | source", sps2.display()),
+            "~/.config/fish/config.fish",
+        ),
+    };
+    Some(format!(
+        "Add {} to your PATH to use installed packages:\n   \
+         echo '{line}' >> {rc_file}\n   \
+         source {rc_file}",
+        bin_dir.display()
+    ))
+}
+
+/// Values of the search path variables with the directories of `prefix`
+/// in front of what `current` gives for each
+///
+/// As in the statements, `MANPATH` ends in `:` when it was unset.
+pub(crate) fn search_path_values(
+    prefix: &Path,
+    current: impl Fn(&str) -> Option<String>,
+) -> Vec<(&'static str, String)> {
+    SEARCH_PATHS
+        .iter()
+        .map(|search| {
+            let dirs: Vec<String> = search
+                .dirs
+                .iter()
+                .map(|dir| prefix.join(dir).display().to_string())
+                .collect();
+            let mut value = dirs.join(":");
+            match current(search.var) {
+                Some(existing) if !existing.is_empty() => {
+                    value.push(':');
+                    value.push_str(&existing);
+                }
+                _ if search.var == "MANPATH" => value.push(':'),
+                _ => {}
+            }
+            (search.var, value)
+        })
+        .collect()
+}
+
+impl SearchPath {
+    /// Whether the package file at `path` is found through this variable
+    fn covers(&self, path: &str) -> bool {
+        self.dirs.iter().any(|dir| {
+            path.strip_prefix(dir)
+                .and_then(|rest| rest.strip_prefix('/'))
+                .is_some_and(|rest| self.nested || !rest.contains('/'))
+        })
+    }
+}
+
+/// The shell named by `$SHELL`, or zsh, the macOS default
+fn login_shell() -> Shell {
+    std::env::var_os("SHELL")
+        .and_then(|shell| Shell::from_path(Path::new(&shell)))
+        .unwrap_or(Shell::Zsh)
+}
+
+fn statements<'a>(
+    shell: Shell,
+    live: &Path,
+    search_paths: impl Iterator<Item = &'a SearchPath>,
+) -> String {
+    let mut lines = Vec::new();
+    for search in search_paths {
+        let dirs: Vec<String> = search
+            .dirs
+            .iter()
+            .map(|dir| live.join(dir).display().to_string())
+            .collect();
+        let var = search.var;
+        match shell {
+            Shell::Zsh | Shell::Bash => {
+                let dirs = sh_quote(&dirs.join(":"));
+                lines.push(if var == "MANPATH" {
+                    format!("export {var}=\"{dirs}:${{{var}-}}\"")
+                } else {
+                    format!("export {var}=\"{dirs}${{{var}:+:${var}}}\"")
+                });
+            }
+            Shell::Fish => {
+                let dirs: Vec<String> = dirs.iter().map(|dir| fish_quote(dir)).collect();
+                if var == "MANPATH" {
+                    lines.push(format!("set -q {var}; or set -gx {var} ''"));
+                }
+                lines.push(format!("set -gx {var} {} ${var}", dirs.join(" ")));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// `value` escaped for use between double quotes in sh
+fn sh_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted
+}
+
+/// `value` as a single-quoted fish string
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_prepend_to_existing_values() {
+        let live = Path::new("/opt/pm/live");
+        let zsh = statements(Shell::Zsh, live, SEARCH_PATHS.iter());
+        assert!(zsh.contains("export PATH=\"/opt/pm/live/bin${PATH:+:$PATH}\"\n"));
+        assert!(zsh.contains("export MANPATH=\"/opt/pm/live/share/man:${MANPATH-}\"\n"));
+        assert!(zsh.contains(
+            "export PKG_CONFIG_PATH=\"/opt/pm/live/lib/pkgconfig:/opt/pm/live/share/pkgconfig${PKG_CONFIG_PATH:+:$PKG_CONFIG_PATH}\""
+        ));
+
+        let fish = statements(Shell::Fish, Path::new("/tmp/my root"), SEARCH_PATHS.iter());
+        assert!(fish.starts_with("set -gx PATH '/tmp/my root/bin' $PATH\n"));
+        assert!(fish.contains("set -q MANPATH; or set -gx MANPATH ''\n"));
+    }
+
+    #[test]
+    fn values_prepend_to_existing_values() {
+        let values = search_path_values(Path::new("/envs/app/live"), |var| {
+            (var == "PATH").then(|| "/usr/bin".to_string())
+        });
+        assert!(values.contains(&("PATH", "/envs/app/live/bin:/usr/bin".to_string())));
+        assert!(values.contains(&("MANPATH", "/envs/app/live/share/man:".to_string())));
+        assert!(values.contains(&("CPATH", "/envs/app/live/include".to_string())));
+    }
+
+    #[test]
+    fn packages_need_the_variables_of_their_files() {
+        let covers = |var: &str, path: &str| {
+            SEARCH_PATHS
+                .iter()
+                .find(|search| search.var == var)
+                .is_some_and(|search| search.covers(path))
+        };
+        assert!(covers("PATH", "bin/jq"));
+        assert!(!covers("PATH", "binaries/jq"));
+        assert!(covers("MANPATH", "share/man/man1/jq.1"));
+        assert!(covers("CPATH", "include/openssl/ssl.h"));
+        assert!(covers("LIBRARY_PATH", "lib/libz.dylib"));
+        assert!(!covers("LIBRARY_PATH", "lib/python3.12/site.py"));
+    }
+
+    #[test]
+    fn hint_only_when_bin_is_not_on_path() {
+        let bin = Path::new("/opt/pm/live/bin");
+        let sps2 = bin.join("sps2");
+        assert!(env_hint(bin, OsStr::new("/usr/bin:/opt/pm/live/bin"), &sps2).is_none());
+        let hint = env_hint(bin, OsStr::new("/usr/bin:/opt/pm/live/bin/extra"), &sps2).unwrap();
+        assert!(hint.contains("/opt/pm/live/bin/sps2 env"));
+    }
+}
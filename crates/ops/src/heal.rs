@@ -0,0 +1,310 @@
+//! Repository fallback for guard healing
+//!
+//! When a live file is missing or corrupted and its store object is gone as
+//! well, the verifier has nothing local to restore it from. The owning
+//! package is taken from the build cache or downloaded again, checked against
+//! its recorded BLAKE3, and only the store objects the live state needs are
+//! put back.
+
+use crate::OpsCtx;
+use sps2_errors::{Error, OpsError};
+use sps2_events::{
+    events::{GuardEvent, GuardHealingPlan},
+    AppEvent, EventEmitter, GeneralEvent,
+};
+use sps2_guard::Discrepancy;
+use sps2_hash::Hash;
+use sps2_net::PackageDownloader;
+use sps2_types::Version;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Store content lost for one installed package
+#[derive(Default)]
+pub(crate) struct LostContent {
+    /// Live paths whose store object has to come back
+    pub(crate) paths: Vec<String>,
+    /// The whole package directory is missing from the store
+    pub(crate) package: bool,
+}
+
+/// Packages whose remaining discrepancies need store content back
+fn lost_content(discrepancies: &[Discrepancy]) -> BTreeMap<(String, String), LostContent> {
+    let mut lost: BTreeMap<(String, String), LostContent> = BTreeMap::new();
+    for discrepancy in discrepancies {
+        match discrepancy {
+            Discrepancy::MissingFile {
+                package,
+                version,
+                path,
+            }
+            | Discrepancy::CorruptedFile {
+                package,
+                version,
+                path,
+            } => {
+                // Copies in managed directories heal from the live prefix
+                if !path.starts_with('/') {
+                    lost.entry((package.clone(), version.clone()))
+                        .or_default()
+                        .paths
+                        .push(path.clone());
+                }
+            }
+            Discrepancy::MissingPackageContent { package, version } => {
+                lost.entry((package.clone(), version.clone()))
+                    .or_default()
+                    .package = true;
+            }
+            // Set again from the state, not the store
+            Discrepancy::PermissionsChanged { .. } | Discrepancy::UnexpectedFile { .. } => {}
+        }
+    }
+    lost
+}
+
+/// Refill the store from the build cache or the repository for discrepancies
+/// a store heal left
+///
+/// Returns how many packages were restored to the store; the caller re-runs
+/// the verifier to put their files back in place. Packages that cannot be
+/// fetched are reported as warnings and counted as failed.
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read.
+pub(crate) async fn refill_store(
+    ctx: &OpsCtx,
+    operation_id: &str,
+    discrepancies: &[Discrepancy],
+) -> Result<usize, Error> {
+    let lost = lost_content(discrepancies);
+    if lost.is_empty() {
+        return Ok(0);
+    }
+
+    let start = Instant::now();
+    ctx.emit(AppEvent::Guard(GuardEvent::HealingStarted {
+        operation_id: operation_id.to_string(),
+        plan: GuardHealingPlan {
+            total: lost.len(),
+            auto_heal: lost.len(),
+            confirmation_required: 0,
+            manual_only: 0,
+        },
+    }));
+
+    let state_id = ctx.state.get_active_state().await?;
+    let mut refilled = 0;
+    let mut failed = 0;
+    for ((name, version), content) in &lost {
+        match refill_package(ctx, &state_id, name, version, content).await {
+            Ok(_) => refilled += 1,
+            Err(e) => {
+                failed += 1;
+                ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!("Could not restore {name}-{version}"),
+                    e.to_string(),
+                )));
+            }
+        }
+    }
+
+    ctx.emit(AppEvent::Guard(GuardEvent::HealingCompleted {
+        operation_id: operation_id.to_string(),
+        healed: refilled,
+        failed,
+        duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+    }));
+
+    Ok(refilled)
+}
+
+/// Put back the lost store content of `name`-`version`
+///
+/// A build of the package in the artifact cache is used when it matches the
+/// recorded archive hash; otherwise the package is downloaded again. Returns
+/// the trusted key that verified the downloaded archive, if any.
+///
+/// # Errors
+///
+/// Returns an error if no cached build matches and the package is not in
+/// the index, the download or its signature check fails, or the store
+/// cannot be written.
+pub(crate) async fn refill_package(
+    ctx: &OpsCtx,
+    state_id: &uuid::Uuid,
+    name: &str,
+    version: &str,
+    content: &LostContent,
+) -> Result<Option<String>, Error> {
+    // The hash recorded at install time wins over whatever the index says now
+    let recorded = ctx.state.get_package_archive_hash(name, version).await?;
+    if let Some(recorded) = &recorded {
+        if let Some(archive) = cached_archive(ctx, name, version, recorded).await? {
+            ctx.emit(AppEvent::General(GeneralEvent::debug(format!(
+                "Restoring {name}-{version} from the build cache"
+            ))));
+            restore_from_archive(ctx, state_id, name, version, content, &archive).await?;
+            return Ok(None);
+        }
+    }
+    if ctx.config.network.offline {
+        return Err(OpsError::OperationFailed {
+            message: format!("no cached build of {name}-{version}, and the network is offline"),
+        }
+        .into());
+    }
+
+    let entry = ctx
+        .index()
+        .await?
+        .get_version(name, version)
+        .cloned()
+        .ok_or_else(|| OpsError::OperationFailed {
+            message: format!("{name}-{version} is no longer in the repository index"),
+        })?;
+    let expected = Hash::from_hex(recorded.as_ref().unwrap_or(&entry.blake3))?;
+
+    let signature_url = Some(entry.minisig_url.as_str()).filter(|url| !url.is_empty());
+    let parsed_version = Version::parse(version)?;
+    let download_dir = ctx.store.temp_area().tempdir()?;
+    let downloader = PackageDownloader::with_client(
+        ctx.download_config(),
+        ctx.net()?.clone(),
+        sps2_events::ProgressManager::new(),
+    );
+    let download = downloader
+        .download_package(
+            name,
+            &parsed_version,
+            &entry.download_url,
+            signature_url,
+            download_dir.path(),
+            Some(&expected),
+            String::new(),
+            None,
+            &ctx.tx,
+        )
+        .await?;
+
+    let policy = ctx.security_policy();
+    if policy.verify_signatures && !policy.allow_unsigned && !download.signature_verified {
+        return Err(sps2_errors::SigningError::VerificationFailed {
+            reason: format!("signature of {name}-{version} could not be verified"),
+        }
+        .into());
+    }
+
+    restore_from_archive(
+        ctx,
+        state_id,
+        name,
+        version,
+        content,
+        &download.package_path,
+    )
+    .await?;
+    Ok(download.signing_key)
+}
+
+/// A package in the artifact cache whose archive hash is `recorded`
+///
+/// Only files named after `name`-`version` are hashed.
+async fn cached_archive(
+    ctx: &OpsCtx,
+    name: &str,
+    version: &str,
+    recorded: &str,
+) -> Result<Option<PathBuf>, Error> {
+    let cache_dir = ctx.config.builder.performance.cache.artifact_dir.clone();
+    let prefix = format!("{name}-{version}-");
+    for package in sps2_builder::ArtifactCache::new(cache_dir)
+        .packages()
+        .await?
+    {
+        let named = package
+            .file_name()
+            .and_then(|file| file.to_str())
+            .is_some_and(|file| file.starts_with(&prefix));
+        if named && Hash::blake3_hash_file(&package).await?.to_hex() == recorded {
+            return Ok(Some(package));
+        }
+    }
+    Ok(None)
+}
+
+/// Add the package in `archive` back to the store, or just the objects of
+/// the lost live paths, as `content` asks
+async fn restore_from_archive(
+    ctx: &OpsCtx,
+    state_id: &uuid::Uuid,
+    name: &str,
+    version: &str,
+    content: &LostContent,
+    archive: &Path,
+) -> Result<(), Error> {
+    if content.package {
+        let stored = ctx.store.add_package(archive).await?;
+        if let Some(hash) = stored.hash() {
+            ctx.store.discard_incomplete_package(&hash).await?;
+        }
+    }
+    if content.paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = ctx.state.begin_transaction().await?;
+    let entries =
+        sps2_state::queries::get_package_file_entries_by_name(&mut tx, state_id, name, version)
+            .await?;
+    tx.commit().await?;
+    let wanted: HashSet<&str> = content.paths.iter().map(String::as_str).collect();
+    let needed: HashSet<String> = entries
+        .iter()
+        .filter(|entry| wanted.contains(entry.relative_path.as_str()))
+        .map(|entry| entry.file_hash.clone())
+        .collect();
+
+    let extract_dir = ctx.store.temp_area().tempdir()?;
+    sps2_store::extract_package(archive, extract_dir.path()).await?;
+    restore_objects(ctx, extract_dir.path(), &needed).await
+}
+
+/// Put back the store objects in `needed` from an extracted package
+async fn restore_objects(ctx: &OpsCtx, root: &Path, needed: &HashSet<String>) -> Result<(), Error> {
+    let file_store = ctx.store.file_store();
+    let mut restored = HashSet::new();
+    for entry in walkdir::WalkDir::new(root).follow_links(false) {
+        let entry = entry.map_err(|e| OpsError::OperationFailed {
+            message: format!("failed to read extracted package: {e}"),
+        })?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let hash = Hash::hash_file(entry.path()).await?;
+        let hex = hash.to_hex();
+        if !needed.contains(&hex) || restored.contains(&hex) {
+            continue;
+        }
+        // A corrupted object has to go before the good copy can take its place
+        if file_store.has_file(&hash).await && !file_store.verify_file(&hash).await? {
+            file_store.remove_file(&hash).await?;
+        }
+        file_store.store_file(entry.path(), &hash).await?;
+        restored.insert(hex);
+    }
+
+    if restored.len() < needed.len() {
+        return Err(OpsError::OperationFailed {
+            message: format!(
+                "package provides {} of {} lost files",
+                restored.len(),
+                needed.len()
+            ),
+        }
+        .into());
+    }
+    Ok(())
+}
@@ -64,7 +64,7 @@ pub async fn check_health(ctx: &OpsCtx) -> Result<HealthCheck, Error> {
 
     // Check index health
     let index_start = Instant::now();
-    let index_health = check_index_health(ctx, &mut issues);
+    let index_health = check_index_health(ctx, &mut issues).await;
     components.insert(
         "index".to_string(),
         ComponentHealth {
@@ -114,6 +114,23 @@ async fn check_store_health(ctx: &OpsCtx, issues: &mut Vec<HealthIssue>) -> Heal
         return HealthStatus::Error;
     }
 
+    // Check for package directories quarantined as incomplete
+    let incomplete = ctx.store.incomplete_packages().await.unwrap_or_default();
+    if !incomplete.is_empty() {
+        issues.push(HealthIssue {
+            component: "store".to_string(),
+            severity: IssueSeverity::Medium,
+            description: format!(
+                "{} package directories were incomplete and have been quarantined",
+                incomplete.len()
+            ),
+            suggestion: Some(
+                "Run 'sps2 verify --heal' to restore installed packages, then 'sps2 cleanup' to remove the quarantined directories"
+                    .to_string(),
+            ),
+        });
+    }
+
     // Check store verification status
 
     let config = StoreVerificationConfig::default();
@@ -124,7 +141,11 @@ async fn check_store_health(ctx: &OpsCtx, issues: &mut Vec<HealthIssue>) -> Heal
     );
 
     if let Ok(verification_stats) = verifier.get_stats().await {
-        let mut health_status = HealthStatus::Healthy;
+        let mut health_status = if incomplete.is_empty() {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Warning
+        };
 
         // Check for failed verifications
         if verification_stats.failed_count > 0 {
@@ -214,9 +235,22 @@ async fn check_state_health(ctx: &OpsCtx, issues: &mut Vec<HealthIssue>) -> Heal
 }
 
 /// Check index health
-fn check_index_health(ctx: &OpsCtx, issues: &mut Vec<HealthIssue>) -> HealthStatus {
+async fn check_index_health(ctx: &OpsCtx, issues: &mut Vec<HealthIssue>) -> HealthStatus {
+    let index = match ctx.index().await {
+        Ok(index) => index,
+        Err(e) => {
+            issues.push(HealthIssue {
+                component: "index".to_string(),
+                severity: IssueSeverity::High,
+                description: format!("Package index could not be loaded: {e}"),
+                suggestion: Some("Run 'sps2 reposync' to fetch a fresh index".to_string()),
+            });
+            return HealthStatus::Error;
+        }
+    };
+
     // Check if index is stale
-    if ctx.index.is_stale(7) {
+    if index.is_stale(7) {
         issues.push(HealthIssue {
             component: "index".to_string(),
             severity: IssueSeverity::Medium,
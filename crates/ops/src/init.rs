@@ -0,0 +1,292 @@
+//! Setting up an installation from scratch
+//!
+//! `sps2 init` creates the prefix hierarchy, the state database and the
+//! trusted keys, then checks what the platform offers. Every step can be
+//! re-run: what is already in place is reported and left alone.
+
+use crate::keys::{KeyManager, BOOTSTRAP_KEY};
+use crate::types::{InitReport, InitStatus, InitStep};
+use sps2_config::Config;
+use sps2_platform::core::ToolCategory;
+use sps2_platform::PlatformManager;
+use sps2_state::StateManager;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Set up the installation `config` points at
+///
+/// `config_files` are the configuration files in use, each with whether
+/// loading the configuration just wrote it with defaults. When run through
+/// `sudo`, everything created is handed to the user who ran it, so later
+/// commands need no elevated rights.
+///
+/// Failures are recorded in the report rather than returned. Once a
+/// directory cannot be set up, the remaining steps are skipped.
+pub async fn init(config: &Config, config_files: &[(PathBuf, bool)]) -> InitReport {
+    let owner = sudo_owner();
+    let mut report = InitReport {
+        prefix: config.prefix_path(),
+        steps: Vec::new(),
+    };
+
+    let directories = [
+        ("prefix", config.prefix_path()),
+        ("store", config.store_path()),
+        ("temp area", config.tmp_path()),
+        ("states", config.state_path()),
+        ("live", config.live_path()),
+        ("logs", config.logs_path()),
+        ("keys", config.keys_path()),
+        ("quarantine", config.quarantine_path()),
+        ("environments", config.envs_path()),
+    ];
+    for (name, dir) in &directories {
+        report.steps.push(ensure_directory(name, dir, owner).await);
+    }
+    if !report.is_ready() {
+        return report;
+    }
+
+    report.steps.push(init_database(config, owner).await);
+    for (path, written) in config_files {
+        report.steps.push(config_file(path, *written));
+    }
+    report
+        .steps
+        .push(seed_keys(&config.keys_path(), owner).await);
+    report.steps.push(check_tools().await);
+    report
+        .steps
+        .push(check_clonefile(&config.store_path()).await);
+
+    report
+}
+
+fn step(name: &str, status: InitStatus, detail: String) -> InitStep {
+    InitStep {
+        name: name.to_string(),
+        status,
+        detail,
+    }
+}
+
+/// The user `sudo` was run by, who should own the installation
+fn sudo_owner() -> Option<(u32, u32)> {
+    let uid = std::env::var("SUDO_UID").ok()?.parse().ok()?;
+    let gid = std::env::var("SUDO_GID").ok()?.parse().ok()?;
+    Some((uid, gid))
+}
+
+fn hand_over(path: &Path, owner: Option<(u32, u32)>) -> std::io::Result<()> {
+    match owner {
+        Some((uid, gid)) => std::os::unix::fs::chown(path, Some(uid), Some(gid)),
+        None => Ok(()),
+    }
+}
+
+async fn ensure_directory(name: &str, dir: &Path, owner: Option<(u32, u32)>) -> InitStep {
+    let existed = dir.is_dir();
+    if !existed {
+        if let Err(e) = tokio::fs::create_dir_all(dir).await {
+            return step(
+                name,
+                InitStatus::Failed,
+                format!("cannot create {}: {e}; run `sudo sps2 init`", dir.display()),
+            );
+        }
+        let mode = std::fs::Permissions::from_mode(0o755);
+        if let Err(e) = tokio::fs::set_permissions(dir, mode).await {
+            return step(
+                name,
+                InitStatus::Failed,
+                format!("cannot set permissions on {}: {e}", dir.display()),
+            );
+        }
+    }
+    if let Err(e) = hand_over(dir, owner) {
+        return step(
+            name,
+            InitStatus::Failed,
+            format!("cannot change the owner of {}: {e}", dir.display()),
+        );
+    }
+    // Probe with a file: permission bits alone miss ACLs and read-only mounts
+    if owner.is_none() && tempfile::NamedTempFile::new_in(dir).is_err() {
+        return step(
+            name,
+            InitStatus::Failed,
+            format!(
+                "{} is not writable; run `sudo sps2 init` to take ownership",
+                dir.display()
+            ),
+        );
+    }
+
+    let status = if existed {
+        InitStatus::Ok
+    } else {
+        InitStatus::Created
+    };
+    step(name, status, dir.display().to_string())
+}
+
+async fn init_database(config: &Config, owner: Option<(u32, u32)>) -> InitStep {
+    let db_path = config.db_path();
+    let existed = db_path.exists();
+    let state = match StateManager::new(&config.prefix_path()).await {
+        Ok(state) => state,
+        Err(e) => {
+            return step(
+                "state database",
+                InitStatus::Failed,
+                format!("cannot open {}: {e}", db_path.display()),
+            )
+        }
+    };
+
+    // SQLite keeps its journal next to the database
+    let mut sidecars = vec![db_path.clone()];
+    for suffix in ["-wal", "-shm"] {
+        let mut name = db_path.clone().into_os_string();
+        name.push(suffix);
+        sidecars.push(PathBuf::from(name));
+    }
+    for path in sidecars.iter().filter(|path| path.exists()) {
+        if let Err(e) = hand_over(path, owner) {
+            return step(
+                "state database",
+                InitStatus::Failed,
+                format!("cannot change the owner of {}: {e}", path.display()),
+            );
+        }
+    }
+
+    let detail = match state.get_active_state().await {
+        Ok(active) => format!("{} (active state {active})", db_path.display()),
+        Err(_) => db_path.display().to_string(),
+    };
+    let status = if existed {
+        InitStatus::Ok
+    } else {
+        InitStatus::Created
+    };
+    step("state database", status, detail)
+}
+
+fn config_file(path: &Path, written: bool) -> InitStep {
+    let (status, detail) = if !path.exists() {
+        (
+            InitStatus::Warning,
+            format!("{} could not be written; defaults are used", path.display()),
+        )
+    } else if written {
+        (InitStatus::Created, path.display().to_string())
+    } else {
+        (InitStatus::Ok, path.display().to_string())
+    };
+    step("config file", status, detail)
+}
+
+async fn seed_keys(keys_dir: &Path, owner: Option<(u32, u32)>) -> InitStep {
+    let keys_file = keys_dir.join("trusted_keys.json");
+    let existed = keys_file.exists();
+    let mut manager = KeyManager::new(keys_dir);
+    let loaded = if existed {
+        manager.load_trusted_keys().await
+    } else {
+        manager.initialize_with_bootstrap(BOOTSTRAP_KEY).await
+    };
+    if let Err(e) = loaded.and_then(|()| hand_over(&keys_file, owner).map_err(Into::into)) {
+        return step(
+            "trusted keys",
+            InitStatus::Failed,
+            format!("{}: {e}", keys_file.display()),
+        );
+    }
+
+    let count = manager.get_trusted_keys().len();
+    if count == 0 {
+        return step(
+            "trusted keys",
+            InitStatus::Warning,
+            format!(
+                "{} trusts no keys; add one with `sps2 keys import`",
+                keys_file.display()
+            ),
+        );
+    }
+    let status = if existed {
+        InitStatus::Ok
+    } else {
+        InitStatus::Created
+    };
+    step(
+        "trusted keys",
+        status,
+        format!("{count} in {}", keys_file.display()),
+    )
+}
+
+/// Tools installing and relocating packages depends on
+async fn check_tools() -> InitStep {
+    let platform = PlatformManager::instance();
+    let registry = platform.tool_registry();
+    let mut missing = Vec::new();
+    for tool in registry
+        .get_tools_by_category(ToolCategory::PlatformCritical)
+        .await
+    {
+        if platform.get_tool(&tool).await.is_err() {
+            missing.push(tool);
+        }
+    }
+
+    if missing.is_empty() {
+        step(
+            "tools",
+            InitStatus::Ok,
+            "all required tools found".to_string(),
+        )
+    } else {
+        step(
+            "tools",
+            InitStatus::Warning,
+            format!(
+                "missing {}; install the Xcode Command Line Tools",
+                missing.join(", ")
+            ),
+        )
+    }
+}
+
+/// Whether the store's filesystem can clone files, which installs rely on
+async fn check_clonefile(store_dir: &Path) -> InitStep {
+    let probe = store_dir.join(".init-clonefile-probe");
+    let clone = store_dir.join(".init-clonefile-probe-clone");
+    if let Err(e) = tokio::fs::write(&probe, b"sps2").await {
+        return step(
+            "clonefile",
+            InitStatus::Warning,
+            format!("cannot write to {}: {e}", store_dir.display()),
+        );
+    }
+
+    let platform = PlatformManager::instance().platform();
+    let ctx = platform.create_context(None);
+    let cloned = platform.clone_file(&ctx, &probe, &clone).await;
+    let _ = tokio::fs::remove_file(&probe).await;
+    let _ = tokio::fs::remove_file(&clone).await;
+
+    match cloned {
+        Ok(()) => step(
+            "clonefile",
+            InitStatus::Ok,
+            "supported by the store's filesystem".to_string(),
+        ),
+        Err(e) => step(
+            "clonefile",
+            InitStatus::Warning,
+            format!("unavailable ({e}); keep the store on an APFS volume"),
+        ),
+    }
+}
@@ -3,13 +3,14 @@
 //! Handles package installation with support for both local .sp files and remote packages.
 //! Delegates to `sps2_install` crate for the actual installation logic.
 
+use crate::transition::after_transition;
 use crate::{InstallReport, InstallRequest, OpsCtx};
-use sps2_errors::{Error, OpsError};
+use sps2_errors::{Error, InstallError, OpsError};
 use sps2_events::{
     AppEvent, EventEmitter, FailureContext, GeneralEvent, LifecycleEvent, ProgressEvent,
 };
-use sps2_install::{InstallConfig, InstallContext, Installer};
-use sps2_types::{PackageSpec, Version};
+use sps2_install::{InstallContext, Installer, RequiredHashes};
+use sps2_types::{PackageFilename, PackageSpec, Version};
 use std::convert::TryFrom;
 use std::path::{Path, PathBuf};
 use std::time::Instant;
@@ -19,6 +20,8 @@ use uuid::Uuid;
 ///
 /// This function provides a unified installation workflow that seamlessly handles
 /// both local .sp files and remote packages with optimal performance.
+/// With `required_hashes`, every artifact must match a hash pinned for its
+/// package, including dependencies and packages reused from the store.
 ///
 /// # Errors
 ///
@@ -26,11 +29,13 @@ use uuid::Uuid;
 /// - No packages are specified
 /// - Package specifications cannot be parsed
 /// - Installation fails
+/// - An artifact's hash is not pinned in `required_hashes`
 #[allow(clippy::too_many_lines)] // Complex orchestration function coordinating multiple subsystems
 pub async fn install(
     ctx: &OpsCtx,
     package_specs: &[String],
     force_download: bool,
+    required_hashes: Option<&RequiredHashes>,
 ) -> Result<InstallReport, Error> {
     let start = Instant::now();
 
@@ -38,6 +43,13 @@ pub async fn install(
         return Err(OpsError::NoPackagesSpecified.into());
     }
 
+    if force_download && ctx.config.network.offline {
+        return Err(OpsError::InvalidOperation {
+            operation: "--force-download cannot be combined with offline mode".to_string(),
+        }
+        .into());
+    }
+
     let _correlation = ctx.push_correlation_for_packages("install", package_specs);
 
     // Check mode: preview what would be installed
@@ -73,7 +85,9 @@ pub async fn install(
     // Use different strategies based on the mix of packages with enhanced error handling
     let result = if !remote_specs.is_empty() && local_files.is_empty() {
         // All remote packages - use high-performance parallel pipeline
-        match install_remote_packages_parallel(ctx, &remote_specs, force_download).await {
+        match install_remote_packages_parallel(ctx, &remote_specs, force_download, required_hashes)
+            .await
+        {
             Ok(result) => result,
             Err(e) => {
                 // Provide specific guidance for remote package failures
@@ -83,7 +97,7 @@ pub async fn install(
         }
     } else if remote_specs.is_empty() && !local_files.is_empty() {
         // All local files - use local installer
-        match install_local_packages(ctx, &local_files, force_download).await {
+        match install_local_packages(ctx, &local_files, force_download, required_hashes).await {
             Ok(result) => result,
             Err(e) => {
                 // Provide specific guidance for local file failures
@@ -102,7 +116,15 @@ pub async fn install(
         }
     } else {
         // Mixed local and remote - use hybrid approach
-        match install_mixed_packages(ctx, &remote_specs, &local_files, force_download).await {
+        match install_mixed_packages(
+            ctx,
+            &remote_specs,
+            &local_files,
+            force_download,
+            required_hashes,
+        )
+        .await
+        {
             Ok(result) => result,
             Err(e) => {
                 // Provide guidance for mixed installation failures
@@ -127,6 +149,7 @@ pub async fn install(
 
     // Convert to report format with proper change tracking
     let report = InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: result
             .installed_packages
             .iter()
@@ -159,18 +182,22 @@ pub async fn install(
                 size: None,
             })
             .collect(),
+        broken_dependencies: Vec::new(),
         state_id: result.state_id,
         duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
     };
 
-    // Event emission moved to operations layer where actual work happens
+    after_transition(ctx, &report, "install", package_specs).await;
 
     Ok(report)
 }
 
 /// Preview what would be installed without executing
 #[allow(clippy::too_many_lines)]
-async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<InstallReport, Error> {
+pub(crate) async fn preview_install(
+    ctx: &OpsCtx,
+    package_specs: &[String],
+) -> Result<InstallReport, Error> {
     use std::collections::HashMap;
 
     // Parse install requests
@@ -204,13 +231,18 @@ async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<Insta
     // Handle remote packages
     if !remote_specs.is_empty() {
         // Create resolution context
-        let mut resolution_context = sps2_resolver::ResolutionContext::new();
+        let mut resolution_context = sps2_resolver::ResolutionContext::new()
+            .with_recommends(ctx.config.general.install_recommends);
         for spec in &remote_specs {
             resolution_context = resolution_context.add_runtime_dep(spec.clone());
         }
 
         // Resolve dependencies
-        let resolution_result = ctx.resolver.resolve_with_sat(resolution_context).await?;
+        let resolution_result = ctx
+            .resolver()
+            .await?
+            .resolve_with_sat(resolution_context)
+            .await?;
 
         // Process resolved packages
         for (package_id, node) in &resolution_result.nodes {
@@ -248,7 +280,9 @@ async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<Insta
                 }
             } else {
                 // Package would be newly installed
-                let action_text = if is_dependency {
+                let action_text = if node.recommended {
+                    format!("Would install {package_id} (recommended)")
+                } else if is_dependency {
                     format!("Would install {package_id} (dependency)")
                 } else {
                     format!("Would install {package_id}")
@@ -268,7 +302,9 @@ async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<Insta
                         ),
                         (
                             "type".to_string(),
-                            if is_dependency {
+                            if node.recommended {
+                                "recommended".to_string()
+                            } else if is_dependency {
                                 "dependency".to_string()
                             } else {
                                 "requested".to_string()
@@ -306,15 +342,26 @@ async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<Insta
             ]),
         }));
 
-        // Add to preview (we don't know the exact package name/version without reading)
+        // Name and version come from the filename; the manifest is not read
+        let parsed = local_file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| PackageFilename::parse(name).ok());
         preview_installed.push(crate::PackageChange {
-            name: format!(
-                "local-{}",
-                local_file.file_stem().unwrap_or_default().to_string_lossy()
+            name: parsed.as_ref().map_or_else(
+                || {
+                    format!(
+                        "local-{}",
+                        local_file.file_stem().unwrap_or_default().to_string_lossy()
+                    )
+                },
+                |parsed| parsed.name.clone(),
             ),
             from_version: None,
-            to_version: Some(Version::new(0, 0, 0)), // Placeholder
-            size: None,
+            to_version: Some(parsed.map_or_else(|| Version::new(0, 0, 0), |parsed| parsed.version)),
+            size: std::fs::metadata(local_file)
+                .ok()
+                .map(|metadata| metadata.len()),
         });
         new_packages_count += 1;
     }
@@ -336,9 +383,11 @@ async fn preview_install(ctx: &OpsCtx, package_specs: &[String]) -> Result<Insta
 
     // Return preview report (no actual state changes)
     Ok(InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: preview_installed,
         updated: preview_updated,
         removed: Vec::new(),
+        broken_dependencies: Vec::new(),
         state_id: Uuid::nil(), // No state change in preview
         duration_ms: 0,
     })
@@ -350,6 +399,7 @@ async fn install_remote_packages_parallel(
     ctx: &OpsCtx,
     specs: &[PackageSpec],
     force_download: bool,
+    required_hashes: Option<&RequiredHashes>,
 ) -> Result<sps2_install::InstallResult, Error> {
     use sps2_events::{patterns::InstallProgressConfig, ProgressManager};
     // use sps2_state::PackageRef;
@@ -379,12 +429,18 @@ async fn install_remote_packages_parallel(
         0,
     )));
 
-    let mut resolution_context = sps2_resolver::ResolutionContext::new();
+    let mut resolution_context = sps2_resolver::ResolutionContext::new()
+        .with_recommends(ctx.config.general.install_recommends);
     for spec in specs {
         resolution_context = resolution_context.add_runtime_dep(spec.clone());
     }
 
-    let resolution_result = match ctx.resolver.resolve_with_sat(resolution_context).await {
+    let resolution_result = match ctx
+        .resolver()
+        .await?
+        .resolve_with_sat(resolution_context)
+        .await
+    {
         Ok(result) => result,
         Err(e) => {
             let failure = FailureContext::from_error(&e);
@@ -394,6 +450,7 @@ async fn install_remote_packages_parallel(
             ctx.emit(AppEvent::Lifecycle(LifecycleEvent::resolver_failed(
                 failure.clone(),
                 Vec::new(),
+                Vec::new(),
             )));
 
             ctx.emit(AppEvent::Progress(ProgressEvent::Failed {
@@ -427,20 +484,36 @@ async fn install_remote_packages_parallel(
 
     progress_manager.update_phase_to_done(&progress_id, "Resolve", ctx);
 
+    if let Err(e) = ensure_available_offline(ctx, &resolved_packages).await {
+        let failure = FailureContext::from_error(&e);
+        ctx.emit_operation_failed("install", failure.clone());
+        ctx.emit(AppEvent::Progress(ProgressEvent::Failed {
+            id: progress_id.clone(),
+            failure,
+            completed_items: 0,
+            partial_duration: std::time::Duration::default(),
+        }));
+        return Err(e);
+    }
+
     // Phase 2-4: Parallel execution (download, store, prepare)
     // Use the same approach as the regular installer with ParallelExecutor
     let exec_context = sps2_install::ExecutionContext::new()
         .with_event_sender(ctx.tx.clone())
-        .with_security_policy(sps2_install::SecurityPolicy {
-            verify_signatures: ctx.config.security.verify_signatures,
-            allow_unsigned: ctx.config.security.allow_unsigned,
-        })
-        .with_force_redownload(force_download);
+        .with_security_policy(ctx.security_policy())
+        .with_download_config(ctx.download_config())
+        .with_force_redownload(force_download)
+        .with_offline(ctx.config.network.offline)
+        .with_required_hashes(required_hashes.cloned())
+        .with_net_client(ctx.net()?.clone());
 
     // Create parallel executor
     let resources = std::sync::Arc::new(sps2_config::ResourceManager::default());
-    let executor =
-        sps2_install::ParallelExecutor::new(ctx.store.clone(), ctx.state.clone(), resources)?;
+    let executor = sps2_install::ParallelExecutor::new(
+        ctx.store.clone(),
+        ctx.state.clone(),
+        resources.clone(),
+    )?;
 
     // Execute parallel downloads and store packages
     let prepared_packages = match executor
@@ -471,7 +544,12 @@ async fn install_remote_packages_parallel(
 
     // Perform atomic installation using the prepared packages
     let mut atomic_installer =
-        sps2_install::AtomicInstaller::new(ctx.state.clone(), ctx.store.clone());
+        sps2_install::AtomicInstaller::new(ctx.state.clone(), ctx.store.clone())
+            .with_allow_setuid(ctx.config.security.allow_setuid)
+            .with_resources(resources)
+            .with_requested(specs.iter().map(|spec| spec.name.clone()))
+            .with_conflict_policy(ctx.config.general.conflict_policy)
+            .with_os_snapshots(ctx.config.state.os_snapshot_min_changes);
 
     let install_context = sps2_install::InstallContext::new()
         .with_event_sender(ctx.tx.clone())
@@ -502,26 +580,86 @@ async fn install_remote_packages_parallel(
     Ok(install_result)
 }
 
+/// In offline mode, fail before any work starts if a resolved package would
+/// have to be downloaded because it is not already in the store
+pub(crate) async fn ensure_available_offline(
+    ctx: &OpsCtx,
+    nodes: &std::collections::HashMap<sps2_resolver::PackageId, sps2_resolver::ResolvedNode>,
+) -> Result<(), Error> {
+    if !ctx.config.network.offline {
+        return Ok(());
+    }
+
+    let mut missing = Vec::new();
+    for (package_id, node) in nodes {
+        if node.action != sps2_resolver::NodeAction::Download {
+            continue;
+        }
+        // Packages from a local repository directory need no network
+        if node
+            .url
+            .as_deref()
+            .and_then(sps2_net::file_url_path)
+            .is_some()
+        {
+            continue;
+        }
+
+        let mut cached = false;
+        if let Some(expected_hash) = &node.expected_hash {
+            if let Some(store_hash) = ctx
+                .state
+                .get_store_hash_for_package_hash(&expected_hash.to_hex())
+                .await?
+            {
+                let store_hash = sps2_hash::Hash::from_hex(&store_hash)?;
+                cached = ctx
+                    .store
+                    .load_package_if_exists(&store_hash)
+                    .await?
+                    .is_some();
+            }
+        }
+
+        if !cached {
+            missing.push(format!("{}-{}", package_id.name, package_id.version));
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    missing.sort_by(|a, b| sps2_types::collate::natural_cmp(a, b));
+    Err(InstallError::NotAvailableOffline {
+        packages: missing.join(", "),
+    }
+    .into())
+}
+
 /// Install local packages using the regular installer
 async fn install_local_packages(
     ctx: &OpsCtx,
     files: &[PathBuf],
     force_download: bool,
+    required_hashes: Option<&RequiredHashes>,
 ) -> Result<sps2_install::InstallResult, Error> {
     // Create installer for local files
-    let config = InstallConfig::default();
+    let config = ctx.install_config();
     let mut installer = Installer::new(
         config,
-        ctx.resolver.clone(),
+        ctx.resolver().await?.clone(),
         ctx.state.clone(),
         ctx.store.clone(),
-    );
+    )
+    .with_net_client(ctx.net()?.clone());
 
     // Build install context for local files
     let install_context = InstallContext::new()
         .with_event_sender(ctx.tx.clone())
         .with_local_files(files.to_vec())
-        .with_force_download(force_download);
+        .with_force_download(force_download)
+        .with_required_hashes(required_hashes.cloned());
 
     // Execute installation
     installer.install(install_context).await
@@ -533,22 +671,25 @@ async fn install_mixed_packages(
     remote_specs: &[PackageSpec],
     local_files: &[PathBuf],
     force_download: bool,
+    required_hashes: Option<&RequiredHashes>,
 ) -> Result<sps2_install::InstallResult, Error> {
     // For mixed installs, use the regular installer for now
     // TODO: Optimize this by using pipeline for remote and merging results
-    let config = InstallConfig::default();
+    let config = ctx.install_config();
     let mut installer = Installer::new(
         config,
-        ctx.resolver.clone(),
+        ctx.resolver().await?.clone(),
         ctx.state.clone(),
         ctx.store.clone(),
-    );
+    )
+    .with_net_client(ctx.net()?.clone());
 
     // Build install context with both remote and local
     let mut install_context = InstallContext::new()
         .with_event_sender(ctx.tx.clone())
         .with_local_files(local_files.to_vec())
-        .with_force_download(force_download);
+        .with_force_download(force_download)
+        .with_required_hashes(required_hashes.cloned());
 
     for spec in remote_specs {
         install_context = install_context.add_package(spec.clone());
@@ -559,7 +700,7 @@ async fn install_mixed_packages(
 }
 
 /// Parse install requests from string specifications
-fn parse_install_requests(specs: &[String]) -> Result<Vec<InstallRequest>, Error> {
+pub(crate) fn parse_install_requests(specs: &[String]) -> Result<Vec<InstallRequest>, Error> {
     let mut requests = Vec::new();
 
     for spec in specs {
@@ -573,6 +714,7 @@ fn parse_install_requests(specs: &[String]) -> Result<Vec<InstallRequest>, Error
         } else {
             // Remote package with version constraints
             let package_spec = PackageSpec::parse(spec)?;
+            sps2_types::name::validate_package_name(&package_spec.name)?;
             requests.push(InstallRequest::Remote(package_spec));
         }
     }
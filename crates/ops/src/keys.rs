@@ -38,6 +38,9 @@ pub struct TrustedKey {
     pub expires_at: Option<i64>,
 }
 
+/// Public key of the default repository, trusted on first run
+pub const BOOTSTRAP_KEY: &str = "RWSGOq2NVecA2UPNdBUZykp1MLhfMmkAK/SZSjK3bpq2q7I8LbSVVBDm";
+
 /// Repository keys.json format
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RepositoryKeys {
@@ -49,14 +52,92 @@ pub struct RepositoryKeys {
     pub max_signature_age: Option<u64>,
 }
 
+/// Key IDs each repository published when its keys were last accepted
+const PUBLISHED_KEYS_FILE: &str = "published_keys.json";
+
+/// How a repository's `keys.json` changed since its keys were last accepted
+#[derive(Debug, Clone, Default)]
+pub struct KeyChanges {
+    /// New keys, each with the trusted key whose signed rotation vouches for it
+    pub rotated: Vec<(TrustedKey, String)>,
+    /// New keys no trusted key vouches for; accepting does not trust them
+    pub unvouched: Vec<TrustedKey>,
+    /// Trusted keys the repository no longer publishes; accepting stops
+    /// trusting them
+    pub removed: Vec<String>,
+}
+
+impl KeyChanges {
+    /// Whether nothing changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rotated.is_empty() && self.unvouched.is_empty() && self.removed.is_empty()
+    }
+
+    /// One line per change, `+` for keys to trust and `-` for keys to drop
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let rotated = self.rotated.iter().map(|(key, previous)| {
+            format!("+ {}{} (rotated from {previous})", key.key_id, comment(key))
+        });
+        let unvouched = self.unvouched.iter().map(|key| {
+            format!(
+                "  {}{} (no rotation from a trusted key; stays untrusted)",
+                key.key_id,
+                comment(key)
+            )
+        });
+        let removed = self
+            .removed
+            .iter()
+            .map(|key_id| format!("- {key_id} (no longer published)"));
+        rotated
+            .chain(unvouched)
+            .chain(removed)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// One-line account of the change for the audit trail
+    #[must_use]
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.rotated.is_empty() {
+            let keys: Vec<String> = self
+                .rotated
+                .iter()
+                .map(|(key, previous)| format!("{} (rotated from {previous})", key.key_id))
+                .collect();
+            parts.push(format!("trusted {}", keys.join(", ")));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("stopped trusting {}", self.removed.join(", ")));
+        }
+        if !self.unvouched.is_empty() {
+            let keys: Vec<&str> = self
+                .unvouched
+                .iter()
+                .map(|key| key.key_id.as_str())
+                .collect();
+            parts.push(format!("left {} untrusted", keys.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
+fn comment(key: &TrustedKey) -> String {
+    key.comment
+        .as_deref()
+        .map(|comment| format!(" \"{comment}\""))
+        .unwrap_or_default()
+}
+
 /// Key manager for handling trusted keys and verification
 pub struct KeyManager {
     /// Path to keys directory (/opt/pm/keys/)
     keys_dir: PathBuf,
     /// Currently loaded trusted keys
     trusted_keys: HashMap<String, TrustedKey>,
-    /// Bootstrap key for initial trust
-    bootstrap_key: Option<TrustedKey>,
 }
 
 impl KeyManager {
@@ -65,7 +146,6 @@ impl KeyManager {
         Self {
             keys_dir: keys_dir.as_ref().to_path_buf(),
             trusted_keys: HashMap::new(),
-            bootstrap_key: None,
         }
     }
 
@@ -106,7 +186,6 @@ impl KeyManager {
             expires_at: None,
         };
 
-        self.bootstrap_key = Some(bootstrap.clone());
         self.trusted_keys.insert(key_id, bootstrap);
 
         self.save_trusted_keys().await?;
@@ -151,45 +230,116 @@ impl KeyManager {
         Ok(())
     }
 
-    /// Fetch and verify keys from repository
+    /// Fetch a repository's `keys.json` and check its key rotations
     ///
     /// # Errors
     ///
     /// Returns an error if:
     /// - The keys cannot be fetched from the repository
     /// - The keys content cannot be parsed as JSON
-    /// - Signature verification fails
-    pub async fn fetch_and_verify_keys(
-        &mut self,
+    /// - A rotation is not signed by the trusted key it rotates from
+    pub async fn fetch_repository_keys(
+        &self,
         net_client: &sps2_net::NetClient,
         keys_url: &str,
         tx: &sps2_events::EventSender,
-    ) -> Result<Vec<sps2_net::PublicKeyRef>, Error> {
+    ) -> Result<RepositoryKeys, Error> {
         let keys_content = sps2_net::fetch_text(net_client, keys_url, tx).await?;
-
         let repo_keys: RepositoryKeys = serde_json::from_str(&keys_content)?;
-
         self.verify_key_rotations(&repo_keys)?;
+        Ok(repo_keys)
+    }
+
+    /// How `repo_keys`, served at `keys_url`, differs from the keys last
+    /// accepted from there
+    ///
+    /// Keys already trusted are not new. Until keys from `keys_url` have been
+    /// accepted once, no key counts as removed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record of published keys cannot be read.
+    pub async fn key_changes(
+        &self,
+        keys_url: &str,
+        repo_keys: &RepositoryKeys,
+    ) -> Result<KeyChanges, Error> {
+        let published = self.load_published_keys().await?;
+        let before = published.get(keys_url);
+        let mut changes = KeyChanges::default();
 
         for key in &repo_keys.keys {
-            if !self.trusted_keys.contains_key(&key.key_id)
-                && self.is_key_rotation_valid(&repo_keys, &key.key_id)
+            if self.trusted_keys.contains_key(&key.key_id)
+                || before.is_some_and(|ids| ids.contains(&key.key_id))
             {
-                self.trusted_keys.insert(key.key_id.clone(), key.clone());
+                continue;
+            }
+            // Trust the key the rotation signed, never the unsigned list entry
+            match self.trusted_rotation(repo_keys, &key.key_id) {
+                Some(rotation) if rotation.new_key.public_key == key.public_key => {
+                    changes
+                        .rotated
+                        .push((rotation.new_key.clone(), rotation.previous_key_id.clone()));
+                }
+                _ => changes.unvouched.push(key.clone()),
             }
         }
 
+        for key_id in before.into_iter().flatten() {
+            if self.trusted_keys.contains_key(key_id)
+                && !repo_keys.keys.iter().any(|key| &key.key_id == key_id)
+            {
+                changes.removed.push(key_id.clone());
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Trust the rotated keys of `changes`, stop trusting its removed keys and
+    /// remember which keys `keys_url` published
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the trusted keys or the record of published keys
+    /// cannot be saved.
+    pub async fn accept_key_changes(
+        &mut self,
+        keys_url: &str,
+        repo_keys: &RepositoryKeys,
+        changes: &KeyChanges,
+    ) -> Result<(), Error> {
+        for (key, _) in &changes.rotated {
+            self.trusted_keys.insert(key.key_id.clone(), key.clone());
+        }
+        for key_id in &changes.removed {
+            self.trusted_keys.remove(key_id);
+        }
         self.save_trusted_keys().await?;
 
-        Ok(self
-            .trusted_keys
-            .values()
-            .map(|k| sps2_net::PublicKeyRef {
-                id: k.key_id.clone(),
-                algo: sps2_net::Algorithm::Minisign,
-                data: k.public_key.clone(),
-            })
-            .collect())
+        let mut published = self.load_published_keys().await?;
+        published.insert(
+            keys_url.to_string(),
+            repo_keys
+                .keys
+                .iter()
+                .map(|key| key.key_id.clone())
+                .collect(),
+        );
+        let content = serde_json::to_string_pretty(&published)
+            .map_err(|e| Error::internal(format!("Failed to serialize published keys: {e}")))?;
+        fs::write(self.keys_dir.join(PUBLISHED_KEYS_FILE), content).await?;
+        Ok(())
+    }
+
+    /// Key IDs each repository published when its keys were last accepted
+    async fn load_published_keys(&self) -> Result<HashMap<String, Vec<String>>, Error> {
+        let path = self.keys_dir.join(PUBLISHED_KEYS_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let content = fs::read_to_string(&path).await?;
+        serde_json::from_str(&content)
+            .map_err(|e| Error::internal(format!("Failed to parse published keys: {e}")))
     }
 
     /// Verify signature against content using trusted keys
@@ -242,17 +392,19 @@ impl KeyManager {
         ))
     }
 
-    /// Verify key rotations are valid
+    /// Verify the key rotations that would bring in a new key
+    ///
+    /// Rotations to keys already trusted, and rotations from keys no longer
+    /// trusted, vouch for nothing and are not checked, so a repository may
+    /// keep listing its rotation history after an old key was dropped.
     fn verify_key_rotations(&self, repo_keys: &RepositoryKeys) -> Result<(), Error> {
         for rotation in &repo_keys.rotations {
-            let previous_key = self
-                .trusted_keys
-                .get(&rotation.previous_key_id)
-                .ok_or_else(|| {
-                    Error::Signing(sps2_errors::SigningError::NoTrustedKeyFound {
-                        key_id: rotation.previous_key_id.clone(),
-                    })
-                })?;
+            if self.trusted_keys.contains_key(&rotation.new_key.key_id) {
+                continue;
+            }
+            let Some(previous_key) = self.trusted_keys.get(&rotation.previous_key_id) else {
+                continue;
+            };
 
             let rotation_content = format!(
                 "{}{}{}",
@@ -269,23 +421,21 @@ impl KeyManager {
         Ok(())
     }
 
-    /// Check if a key rotation is valid for a given key ID
-    fn is_key_rotation_valid(&self, repo_keys: &RepositoryKeys, key_id: &str) -> bool {
-        if let Some(bootstrap) = &self.bootstrap_key {
-            if bootstrap.key_id == key_id {
-                return true;
-            }
-        }
-
-        for rotation in &repo_keys.rotations {
-            if rotation.new_key.key_id == key_id
+    /// Rotation in `repo_keys` from a trusted key to `key_id`
+    ///
+    /// Only meaningful for `repo_keys` returned by
+    /// [`fetch_repository_keys`](Self::fetch_repository_keys), which checked
+    /// its signature.
+    #[must_use]
+    pub fn trusted_rotation<'a>(
+        &self,
+        repo_keys: &'a RepositoryKeys,
+        key_id: &str,
+    ) -> Option<&'a KeyRotation> {
+        repo_keys.rotations.iter().find(|rotation| {
+            rotation.new_key.key_id == key_id
                 && self.trusted_keys.contains_key(&rotation.previous_key_id)
-            {
-                return true;
-            }
-        }
-
-        false
+        })
     }
 
     /// Get all trusted keys
@@ -350,8 +500,8 @@ fn extract_base64(pk_input: &str) -> String {
 /// # Errors
 ///
 /// Returns an error if the trusted keys file cannot be read/parsed.
-pub async fn keys_list(_ctx: &OpsCtx) -> Result<String, Error> {
-    let mut km = KeyManager::new(PathBuf::from(sps2_config::fixed_paths::KEYS_DIR));
+pub async fn keys_list(ctx: &OpsCtx) -> Result<String, Error> {
+    let mut km = KeyManager::new(ctx.config.keys_path());
     km.load_trusted_keys().await?;
     let keys = km.get_trusted_keys();
     if keys.is_empty() {
@@ -376,7 +526,7 @@ pub async fn keys_list(_ctx: &OpsCtx) -> Result<String, Error> {
 ///
 /// Returns an error if the key cannot be read, decoded, or saved.
 pub async fn keys_import_from_file(
-    _ctx: &OpsCtx,
+    ctx: &OpsCtx,
     pubkey_path: &Path,
     comment: Option<String>,
 ) -> Result<String, Error> {
@@ -392,7 +542,7 @@ pub async fn keys_import_from_file(
     }
     let key_id = hex::encode(&decoded[2..10]);
 
-    let mut km = KeyManager::new(PathBuf::from(sps2_config::fixed_paths::KEYS_DIR));
+    let mut km = KeyManager::new(ctx.config.keys_path());
     km.load_trusted_keys().await?;
     let trusted = TrustedKey {
         key_id: key_id.clone(),
@@ -410,8 +560,8 @@ pub async fn keys_import_from_file(
 /// # Errors
 ///
 /// Returns an error if saving the updated trusted keys fails or if the key is not present.
-pub async fn keys_remove(_ctx: &OpsCtx, key_id: &str) -> Result<String, Error> {
-    let mut km = KeyManager::new(PathBuf::from(sps2_config::fixed_paths::KEYS_DIR));
+pub async fn keys_remove(ctx: &OpsCtx, key_id: &str) -> Result<String, Error> {
+    let mut km = KeyManager::new(ctx.config.keys_path());
     km.load_trusted_keys().await?;
     // Proceed even if key doesn't exist; report accordingly
     let existed = km.trusted_keys.contains_key(key_id);
@@ -422,3 +572,116 @@ pub async fn keys_remove(_ctx: &OpsCtx, key_id: &str) -> Result<String, Error> {
         Ok(format!("Key {key_id} not found (no changes)"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS_URL: &str = "https://repo.example/keys.json";
+
+    fn key(key_id: &str) -> TrustedKey {
+        TrustedKey {
+            key_id: key_id.to_string(),
+            public_key: format!("public-{key_id}"),
+            comment: None,
+            trusted_since: 0,
+            expires_at: None,
+        }
+    }
+
+    fn repository(keys: &[&str], rotations: &[(&str, &str)]) -> RepositoryKeys {
+        RepositoryKeys {
+            keys: keys.iter().map(|key_id| key(key_id)).collect(),
+            rotations: rotations
+                .iter()
+                .map(|(previous, new)| KeyRotation {
+                    previous_key_id: (*previous).to_string(),
+                    new_key: key(new),
+                    rotation_signature: String::new(),
+                    timestamp: 0,
+                })
+                .collect(),
+            max_signature_age: None,
+        }
+    }
+
+    fn ids(keys: &[TrustedKey]) -> Vec<&str> {
+        keys.iter().map(|key| key.key_id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn accepted_rotations_and_removals_change_the_trusted_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = KeyManager::new(dir.path());
+        manager.import_key(&key("a")).await.unwrap();
+
+        let published = repository(&["a", "b", "c"], &[("a", "b")]);
+        let changes = manager.key_changes(KEYS_URL, &published).await.unwrap();
+        assert_eq!(changes.rotated.len(), 1);
+        assert_eq!(changes.rotated[0].0.key_id, "b");
+        assert_eq!(changes.rotated[0].1, "a");
+        assert_eq!(ids(&changes.unvouched), ["c"]);
+        assert!(changes.removed.is_empty(), "nothing was accepted before");
+        manager
+            .accept_key_changes(KEYS_URL, &published, &changes)
+            .await
+            .unwrap();
+
+        let unchanged = manager.key_changes(KEYS_URL, &published).await.unwrap();
+        assert!(unchanged.is_empty(), "{}", unchanged.summary());
+
+        let rotated_out = repository(&["b"], &[]);
+        let changes = manager.key_changes(KEYS_URL, &rotated_out).await.unwrap();
+        assert_eq!(changes.removed, ["a"]);
+        assert!(changes.rotated.is_empty() && changes.unvouched.is_empty());
+        assert_eq!(changes.summary(), "- a (no longer published)");
+        manager
+            .accept_key_changes(KEYS_URL, &rotated_out, &changes)
+            .await
+            .unwrap();
+
+        let mut reloaded = KeyManager::new(dir.path());
+        reloaded.load_trusted_keys().await.unwrap();
+        let trusted: Vec<String> = reloaded
+            .get_trusted_keys()
+            .into_iter()
+            .map(|key| key.id)
+            .collect();
+        assert_eq!(trusted, ["b"]);
+    }
+
+    #[tokio::test]
+    async fn list_entries_that_differ_from_the_signed_rotation_stay_untrusted() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = KeyManager::new(dir.path());
+        manager.import_key(&key("a")).await.unwrap();
+
+        let mut tampered = repository(&["b"], &[("a", "b")]);
+        tampered.keys[0].public_key = "public-forged".to_string();
+        let changes = manager.key_changes(KEYS_URL, &tampered).await.unwrap();
+        assert!(changes.rotated.is_empty());
+        assert_eq!(ids(&changes.unvouched), ["b"]);
+
+        let published = repository(&["b"], &[("a", "b")]);
+        let changes = manager.key_changes(KEYS_URL, &published).await.unwrap();
+        assert_eq!(changes.rotated[0].0.public_key, "public-b");
+    }
+
+    #[tokio::test]
+    async fn rotations_from_removed_keys_are_not_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut manager = KeyManager::new(dir.path());
+        manager.import_key(&key("b")).await.unwrap();
+
+        // `a` was dropped after rotating to `b`; rotations from it that are
+        // still listed must not fail the sync
+        let history = repository(&["b", "c"], &[("a", "b"), ("a", "c")]);
+        manager.verify_key_rotations(&history).unwrap();
+        let changes = manager.key_changes(KEYS_URL, &history).await.unwrap();
+        assert!(changes.rotated.is_empty());
+        assert_eq!(ids(&changes.unvouched), ["c"]);
+
+        let forged = repository(&["b", "c"], &[("b", "c")]);
+        assert!(manager.verify_key_rotations(&forged).is_err());
+    }
+}
@@ -11,56 +11,113 @@
 mod context;
 
 pub mod keys;
+pub mod requirements;
 pub mod small_ops;
 
 // Import modularized operations
+mod apply;
+mod audit;
+mod cache;
+mod env;
+mod heal;
 mod health;
+mod init;
+mod local_repo;
 mod maintenance;
+mod managed;
+mod mirror;
+mod os_snapshot;
+mod owns;
+mod plan;
+mod project;
 mod query;
+mod refresh;
 mod repository;
+mod sbom;
+mod schedule;
+mod self_destruct;
 mod self_update;
+mod services;
+mod snapshot;
+mod store;
+mod sync;
+mod transition;
 mod types;
+mod which;
 
 // Import command modules
 mod build;
 mod install;
 mod pack;
+mod reinstall;
 mod uninstall;
 mod update;
 
 pub use context::{OpsContextBuilder, OpsCtx};
+pub use requirements::Requirements;
 pub use sps2_guard::{
-    Discrepancy, StoreVerificationConfig, StoreVerificationStats, StoreVerifier, VerificationLevel,
-    VerificationResult, Verifier,
+    Discrepancy, FileChange, FileDiff, IgnoreRules, PackageDiff, StoreVerificationConfig,
+    StoreVerificationStats, StoreVerifier, VerificationLevel, VerificationResult, Verifier,
 };
 // Re-export consolidated types from sps2_types
 pub use sps2_types::{
-    BuildReport, ChangeType, InstallReport, OpChange, PackageChange, PackageInfo, PackageStatus,
-    SearchResult, StateInfo,
+    BrokenDependency, BuildReport, ChangeType, InstallReport, OpChange, PackageChange, PackageInfo,
+    PackageStatus, SearchResult, StateInfo,
 };
 // Re-export health status from events
 pub use sps2_events::HealthStatus;
+pub use sps2_install::RequiredHashes;
 // Re-export ops-specific types from local types module
 pub use types::{
-    ComponentHealth, HealthCheck, HealthIssue, InstallRequest, IssueSeverity, OpReport,
+    ChangePlan, CommandResolution, ComponentHealth, FileOwnership, HealthCheck, HealthIssue,
+    InitReport, InitStatus, InitStep, InstallRequest, IssueSeverity, OpReport, OperationPlan,
+    PackageFiles, PackageUsage, PathCommand, PlannedDownload, ProjectShell, Removal,
+    SelfDestructReport, ServiceStatus, StateDetail, StoreStats, VerificationHistory,
 };
 
 // Re-export operation functions
-pub use build::build;
+pub use apply::{apply, ChangeSet};
+pub use build::{build, build_recursive, worker_build};
+pub use cache::{cache_clear, cache_list};
+pub use env::{env, env_hint};
+pub use init::init;
 pub use install::install;
+pub use local_repo::{local_repository, LOCAL_INDEX_FILE};
+pub use mirror::mirror;
+pub use os_snapshot::rollback_os_snapshot;
+pub use owns::owns;
 pub use pack::{pack_from_directory, pack_from_recipe, pack_from_recipe_no_post};
+pub use plan::{change_plan, dry_run, PlannedOperation};
+pub use project::{project_shell, PROJECT_FILES};
+pub use refresh::{daemon, index_notice, launchd_plist, refresh_index};
+pub use reinstall::reinstall;
+pub use sbom::sbom_export;
+pub use schedule::scheduled_verification;
+pub use self_destruct::self_destruct;
+pub use services::{
+    services_disable, services_enable, services_list, services_restart, services_start,
+    services_stop,
+};
 pub use small_ops::{
-    check_health, cleanup, history, list_packages, package_info, reposync, rollback,
-    search_packages, self_update,
+    check_health, cleanup, cleanup_downloads, cleanup_quarantine, history, history_detail,
+    list_packages, package_files, package_info, reposync, rollback, search_packages,
+    search_packages_remote, self_update, KeyChangePolicy,
 };
-pub use uninstall::uninstall;
+pub use snapshot::{resolve_state, snapshot_create, snapshot_delete, snapshot_list};
+pub use store::{store_compact, store_relocate, store_stats};
+pub use sync::{sync, Manifest};
+pub use uninstall::{autoremove, uninstall, DependentsPolicy};
 pub use update::{update, upgrade};
+pub use which::which;
 
 use sps2_errors::Error;
 use std::sync::Arc;
 
 /// Verify the integrity of the current state
 ///
+/// Each run's summary is recorded in the state database for
+/// [`verify_history`], except in check mode.
+///
 /// # Errors
 ///
 /// Returns an error if verification fails.
@@ -82,7 +139,7 @@ pub async fn verify(
         verification_level = VerificationLevel::Full;
     }
 
-    match scope {
+    let (result, store_failures) = match scope {
         "store" => {
             let config = StoreVerificationConfig::default();
             let verifier = StoreVerifier::new(
@@ -94,16 +151,14 @@ pub async fn verify(
             let stats = verifier.verify_with_progress(&ctx.tx).await?;
             let state_id = ctx.state.get_active_state().await?;
 
-            Ok(VerificationResult::new(
-                state_id,
-                Vec::new(),
-                stats.duration.as_millis() as u64,
-            ))
+            let result =
+                VerificationResult::new(state_id, Vec::new(), stats.duration.as_millis() as u64);
+            (result, stats.failed_this_run)
         }
         "all" => {
-            let verifier = Verifier::new(ctx.state.clone(), ctx.store.clone(), ctx.tx.clone());
+            let verifier = live_verifier(ctx)?;
             let result = if heal {
-                verifier.verify_and_heal(VerificationLevel::Full).await?
+                heal_live(ctx, &verifier).await?
             } else {
                 verifier.verify(verification_level).await?
             };
@@ -114,18 +169,18 @@ pub async fn verify(
                 Arc::new(ctx.store.file_store().clone()),
                 config,
             );
-            let _ = store_verifier.verify_with_progress(&ctx.tx).await?;
+            let stats = store_verifier.verify_with_progress(&ctx.tx).await?;
 
             if sync_refcounts {
                 verifier.sync_refcounts().await?;
             }
 
-            Ok(result)
+            (result, stats.failed_this_run)
         }
         _ => {
-            let verifier = Verifier::new(ctx.state.clone(), ctx.store.clone(), ctx.tx.clone());
+            let verifier = live_verifier(ctx)?;
             let result = if heal {
-                verifier.verify_and_heal(VerificationLevel::Full).await?
+                heal_live(ctx, &verifier).await?
             } else {
                 verifier.verify(verification_level).await?
             };
@@ -134,10 +189,112 @@ pub async fn verify(
                 verifier.sync_refcounts().await?;
             }
 
-            Ok(result)
+            (result, 0)
         }
+    };
+
+    if !ctx.check_mode {
+        // Store objects that failed their hash count as corrupted files; they
+        // have no live path to record
+        let mut counts = result.counts();
+        counts.corrupted_files += i64::try_from(store_failures).unwrap_or(i64::MAX);
+        let paths: Vec<_> = result
+            .discrepancies
+            .iter()
+            .map(Discrepancy::to_path)
+            .collect();
+        let level = if heal {
+            "full"
+        } else {
+            verification_level.as_str()
+        };
+        let scope = match scope {
+            "store" | "all" => scope,
+            _ => "live",
+        };
+        ctx.state
+            .record_verification_run(
+                &result.state_id,
+                level,
+                scope,
+                result.duration_ms,
+                &counts,
+                &paths,
+            )
+            .await?;
+    }
+
+    Ok(result)
+}
+
+/// Heal the live directory from the store, falling back to the build cache
+/// and the repository for content the store has lost
+///
+/// Nothing is refilled in check mode, and only the build cache is consulted
+/// when offline.
+async fn heal_live(ctx: &OpsCtx, verifier: &Verifier) -> Result<VerificationResult, Error> {
+    let mut result = verifier.verify_and_heal(VerificationLevel::Full).await?;
+    if result.is_valid || ctx.check_mode {
+        return Ok(result);
+    }
+
+    let operation_id = uuid::Uuid::new_v4().to_string();
+    if heal::refill_store(ctx, &operation_id, &result.discrepancies).await? > 0 {
+        let healed = result.healed;
+        result = live_verifier(ctx)?
+            .with_heal_source("repository")
+            .verify_and_heal(VerificationLevel::Full)
+            .await?;
+        let healed = healed + result.healed;
+        result = result.with_healed(healed);
     }
+
+    if !result.is_valid {
+        os_snapshot::hint_after_heal(ctx, result.discrepancies.len()).await;
+    }
+    Ok(result)
+}
+
+/// Verifier for the live prefix that skips the configured ignore globs and
+/// covers the configured managed directories
+fn live_verifier(ctx: &OpsCtx) -> Result<Verifier, Error> {
+    let guard = ctx.config.guard.clone().unwrap_or_default();
+    let ignore = IgnoreRules::new(&guard.ignore, &guard.package_ignore)?;
+    Ok(
+        Verifier::new(ctx.state.clone(), ctx.store.clone(), ctx.tx.clone())
+            .with_ignore_rules(ignore)
+            .with_managed_directories(guard.managed_directories),
+    )
+}
+
+/// Compare an installed package's live files with the store, file by file
+///
+/// Reports hash mismatches, permission changes, missing files and untracked
+/// files next to the package's own, honouring the guard ignore globs. With
+/// `unified`, changed text files carry a unified diff. Nothing is healed.
+///
+/// # Errors
+///
+/// Returns an error if the package is not installed or the comparison fails.
+pub async fn package_diff(
+    ctx: &OpsCtx,
+    package: &str,
+    unified: bool,
+) -> Result<PackageDiff, Error> {
+    live_verifier(ctx)?.diff_package(package, unified).await
 }
+
+/// The `limit` most recent verification runs and the paths that drifted in
+/// more than one of them
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read.
+pub async fn verify_history(ctx: &OpsCtx, limit: usize) -> Result<VerificationHistory, Error> {
+    let (runs, recurring) = ctx.state.verification_history(limit).await?;
+    Ok(VerificationHistory { runs, recurring })
+}
+
 /// Operation result that can be serialized for CLI output
 #[derive(Clone, Debug, serde::Serialize)]
 #[serde(tag = "type", content = "data")]
@@ -152,6 +309,8 @@ pub enum OperationResult {
     InstallReport(InstallReport),
     /// Build report
     BuildReport(BuildReport),
+    /// Reports of a recursive build, the requested recipe last
+    BuildReports(Vec<BuildReport>),
     /// State information
     StateInfo(StateInfo),
     /// State history
@@ -164,6 +323,30 @@ pub enum OperationResult {
     Report(OpReport),
     /// Verification result
     VerificationResult(VerificationResult),
+    /// Store deduplication statistics
+    StoreStats(StoreStats),
+    /// Recorded verification runs
+    VerificationHistory(VerificationHistory),
+    /// A state with its audit trail
+    StateDetail(StateDetail),
+    /// Differences between a package's live files and the store
+    PackageDiff(PackageDiff),
+    /// Packages owning files under the live prefix
+    FileOwnership(FileOwnership),
+    /// Files an installed package provides
+    PackageFiles(PackageFiles),
+    /// The package and `PATH` entries a command name resolves to
+    CommandResolution(CommandResolution),
+    /// What an operation would do, from a dry run
+    Plan(OperationPlan),
+    /// Steps taken to set up an installation
+    InitReport(InitReport),
+    /// What was removed to uninstall sps2, or would be
+    SelfDestructReport(SelfDestructReport),
+    /// Launchd services of installed packages
+    Services(Vec<ServiceStatus>),
+    /// A project's environment, for `sps2 shell`
+    ProjectShell(ProjectShell),
 }
 
 impl OperationResult {
@@ -191,11 +374,24 @@ impl OperationResult {
             | OperationResult::SearchResults(_)
             | OperationResult::InstallReport(_)
             | OperationResult::BuildReport(_)
+            | OperationResult::BuildReports(_)
             | OperationResult::StateInfo(_)
             | OperationResult::StateHistory(_)
-            | OperationResult::Report(_) => true,
+            | OperationResult::Report(_)
+            | OperationResult::StoreStats(_)
+            | OperationResult::VerificationHistory(_)
+            | OperationResult::StateDetail(_)
+            | OperationResult::FileOwnership(_)
+            | OperationResult::PackageFiles(_)
+            | OperationResult::Plan(_)
+            | OperationResult::Services(_)
+            | OperationResult::ProjectShell(_) => true,
             OperationResult::HealthCheck(health) => health.is_healthy(),
             OperationResult::VerificationResult(result) => result.is_valid,
+            OperationResult::PackageDiff(diff) => diff.is_clean(),
+            OperationResult::CommandResolution(resolution) => resolution.live_path.is_some(),
+            OperationResult::InitReport(report) => report.is_ready(),
+            OperationResult::SelfDestructReport(report) => report.is_complete(),
         }
     }
 }
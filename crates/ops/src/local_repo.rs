@@ -0,0 +1,177 @@
+//! Repositories copied to a local directory
+//!
+//! `sps2 install --from-dir` installs from a repository on local media, such
+//! as a USB drive carried to an air-gapped machine. The directory holds
+//! `index.json`, its `index.json.minisig` and the `.sp` and `.minisig` files
+//! of the packages. The index must be signed by a trusted key; its package
+//! URLs are pointed at the files in the directory, whose hashes and
+//! signatures are then checked like any download.
+
+use crate::keys::KeyManager;
+use sps2_config::Config;
+use sps2_errors::{Error, OpsError, SigningError};
+use sps2_index::{Index, IndexManager, ValidationPolicy};
+use std::path::Path;
+
+/// Index file of a repository directory
+pub const LOCAL_INDEX_FILE: &str = "index.json";
+
+/// Load the signed index of a repository directory
+///
+/// Versions whose package file or its signature is missing from the
+/// directory are left out, so a partial copy of a repository only offers
+/// what was copied.
+///
+/// # Errors
+///
+/// Returns an error if the directory has no readable index or signature, the
+/// index is not signed by a trusted key, or it fails validation.
+pub async fn local_repository(config: &Config, dir: &Path) -> Result<IndexManager, Error> {
+    let invalid = |reason: String| -> Error {
+        OpsError::InvalidLocalRepository {
+            path: dir.display().to_string(),
+            reason,
+        }
+        .into()
+    };
+    let dir = tokio::fs::canonicalize(dir)
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+    let read = |name: String| {
+        let path = dir.join(&name);
+        async move {
+            tokio::fs::read_to_string(&path)
+                .await
+                .map_err(|e| invalid(format!("cannot read {name}: {e}")))
+        }
+    };
+    let index_json = read(LOCAL_INDEX_FILE.to_string()).await?;
+    let signature = read(format!("{LOCAL_INDEX_FILE}.minisig")).await?;
+
+    let mut key_manager = KeyManager::new(config.keys_path());
+    key_manager.load_trusted_keys().await?;
+    match sps2_net::verify_minisign_bytes_with_keys(
+        index_json.as_bytes(),
+        &signature,
+        &key_manager.get_trusted_keys(),
+    ) {
+        Ok(_) => {}
+        Err(SigningError::NoTrustedKeyFound { key_id }) => {
+            return Err(invalid(format!(
+                "{LOCAL_INDEX_FILE} is signed by key {key_id}, which is not trusted"
+            )));
+        }
+        Err(e) => return Err(invalid(format!("{LOCAL_INDEX_FILE}: {e}"))),
+    }
+
+    let mut index = Index::from_json(&index_json)?;
+    localize(&mut index, &dir);
+    let policy = ValidationPolicy::default()
+        .with_allow_insecure_urls(config.security.allow_insecure_index_urls)
+        .with_allow_file_urls(true);
+    let mut manager = IndexManager::new(config.prefix_path()).with_validation_policy(policy);
+    manager.load(Some(&index.to_json()?)).await?;
+    Ok(manager)
+}
+
+/// Point the URLs of an index at same-named files in `dir`, dropping the
+/// builds whose package or signature was not copied
+fn localize(index: &mut Index, dir: &Path) {
+    let local = |url: &str| {
+        let path = dir.join(sps2_net::url_file_name(url)?);
+        if path.is_file() {
+            sps2_net::file_url(&path)
+        } else {
+            None
+        }
+    };
+
+    for package in index.packages.values_mut() {
+        for builds in package.versions.values_mut() {
+            builds.retain_mut(|entry| {
+                let (Some(download_url), Some(minisig_url)) =
+                    (local(&entry.download_url), local(&entry.minisig_url))
+                else {
+                    return false;
+                };
+                entry.download_url = download_url;
+                entry.minisig_url = minisig_url;
+                if let Some(sbom) = &mut entry.sbom {
+                    for sbom_entry in std::iter::once(&mut sbom.spdx).chain(&mut sbom.cyclonedx) {
+                        if let Some(url) = local(&sbom_entry.url) {
+                            sbom_entry.url = url;
+                        }
+                    }
+                }
+                true
+            });
+        }
+        package.versions.retain(|_, builds| !builds.is_empty());
+    }
+    index
+        .packages
+        .retain(|_, package| !package.versions.is_empty());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_index::{DependencyInfo, VersionEntry};
+
+    fn entry(file: &str) -> VersionEntry {
+        VersionEntry {
+            revision: 1,
+            arch: "arm64".to_string(),
+            blake3: "a".repeat(64),
+            download_url: format!("https://repo.example/packages/{file}"),
+            minisig_url: format!("https://repo.example/packages/{file}.minisig"),
+            dependencies: DependencyInfo::default(),
+            sbom: None,
+            description: None,
+            homepage: None,
+            license: None,
+        }
+    }
+
+    #[test]
+    fn localize_points_at_copied_files_and_drops_missing_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("jq-1.7.0-1.arm64.sp"), b"sp").unwrap();
+        std::fs::write(dir.path().join("jq-1.7.0-1.arm64.sp.minisig"), b"sig").unwrap();
+        std::fs::write(dir.path().join("jq-1.6.0-1.arm64.sp"), b"unsigned").unwrap();
+
+        let mut index = Index::new();
+        index.add_version(
+            "jq".to_string(),
+            "1.7.0".to_string(),
+            entry("jq-1.7.0-1.arm64.sp"),
+        );
+        index.add_version(
+            "jq".to_string(),
+            "1.6.0".to_string(),
+            entry("jq-1.6.0-1.arm64.sp"),
+        );
+        index.add_version(
+            "jq".to_string(),
+            "1.8.0".to_string(),
+            entry("jq-1.8.0-1.arm64.sp"),
+        );
+        index.add_version(
+            "curl".to_string(),
+            "8.5.0".to_string(),
+            entry("curl-8.5.0-1.arm64.sp"),
+        );
+        localize(&mut index, dir.path());
+
+        assert!(!index.packages.contains_key("curl"));
+        let versions = &index.packages["jq"].versions;
+        assert_eq!(versions.len(), 1);
+        let jq = versions["1.7.0"].preferred().unwrap();
+        assert_eq!(
+            sps2_net::file_url_path(&jq.download_url),
+            Some(dir.path().join("jq-1.7.0-1.arm64.sp"))
+        );
+        assert!(jq.minisig_url.starts_with("file://"));
+        assert!(jq.minisig_url.ends_with(".sp.minisig"));
+    }
+}
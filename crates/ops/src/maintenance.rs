@@ -1,6 +1,7 @@
 //! System Cleanup and State Management Operations
 
-use crate::{ChangeType, OpChange, OpsCtx, StateInfo};
+use crate::transition::after_state_change;
+use crate::{ChangeType, OpChange, OpsCtx, StateDetail, StateInfo};
 use sps2_errors::{Error, OpsError};
 use sps2_events::{
     events::{PackageOperation, PackageOutcome},
@@ -8,7 +9,7 @@ use sps2_events::{
     StateEvent,
 };
 use std::convert::TryFrom;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 async fn compute_kept_states(
     ctx: &OpsCtx,
@@ -32,6 +33,8 @@ async fn compute_kept_states(
         }
     }
     kept.insert(ctx.state.get_current_state_id().await?);
+    // Named states stay restorable regardless of age
+    kept.extend(state_tags(ctx).await?.into_keys());
     Ok(kept)
 }
 
@@ -178,6 +181,7 @@ use uuid::Uuid;
 /// # Errors
 ///
 /// Returns an error if cleanup operation fails.
+#[allow(clippy::too_many_lines)]
 pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
     let start = Instant::now();
     ctx.emit(AppEvent::Package(PackageEvent::OperationStarted {
@@ -201,6 +205,13 @@ pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
     let obj_grace_secs = i64::from(cas_cfg.object_grace_days) * 86_400;
     let now = chrono::Utc::now().timestamp();
 
+    // Delete states beyond the CAS policy; their content is evicted below
+    // once no kept state needs it and its grace period has passed
+    let prune_result = ctx
+        .state
+        .prune_states(keep_count, cas_cfg.keep_days, cas_cfg.dry_run)
+        .await?;
+
     let kept = compute_kept_states(ctx, keep_count, keep_days).await?;
     let (required_pkg_hashes, required_file_hashes) = collect_required_hashes(ctx, &kept).await?;
     let last = collect_last_ref_and_inventory(ctx).await?;
@@ -224,11 +235,31 @@ pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
     )
     .await?;
 
+    let (artifacts_removed, artifact_space_freed, incomplete) = if cas_cfg.dry_run {
+        (0, 0, 0)
+    } else {
+        let (removed, freed) = prune_artifact_cache(ctx).await?;
+        (removed, freed, discard_incomplete_packages(ctx).await?)
+    };
+    let (temp_removed, temp_space_freed) = if cas_cfg.dry_run {
+        (0, 0)
+    } else {
+        let max_age = Duration::from_secs(u64::from(cas_cfg.tmp_max_age_hours) * 3600);
+        ctx.store.temp_area().prune(max_age).await?
+    };
+    let (downloads_removed, downloads_space_freed) = if cas_cfg.dry_run {
+        (0, 0)
+    } else {
+        ctx.download_cache().prune().await?
+    };
+
     let duration = u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX);
     let message = if cas_cfg.dry_run {
         format!(
-            "Dry-run: would prune {} states, remove {} dirs, {} packages ({} bytes), {} objects ({} bytes)",
+            "Dry-run: would prune {} states, delete {} states (releasing {} bytes), remove {} dirs, {} packages ({} bytes), {} objects ({} bytes)",
             cleanup_result.states_pruned,
+            prune_result.states_deleted,
+            prune_result.bytes_released,
             cleanup_result.states_removed,
             packages_evicted,
             pkg_space_freed,
@@ -237,8 +268,10 @@ pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
         )
     } else {
         format!(
-            "Pruned {} states, cleaned {} dirs, removed {} packages ({} bytes), {} objects ({} bytes)",
+            "Pruned {} states, deleted {} states (releasing {} bytes), cleaned {} dirs, removed {} packages ({} bytes), {} objects ({} bytes)",
             cleanup_result.states_pruned,
+            prune_result.states_deleted,
+            prune_result.bytes_released,
             cleanup_result.states_removed,
             packages_evicted,
             pkg_space_freed,
@@ -247,16 +280,37 @@ pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
         )
     };
 
+    let message = if artifacts_removed > 0 {
+        format!("{message}, {artifacts_removed} cached builds ({artifact_space_freed} bytes)")
+    } else {
+        message
+    };
+    let message = if incomplete > 0 {
+        format!("{message}, {incomplete} incomplete package directories")
+    } else {
+        message
+    };
+    let message = if temp_removed > 0 {
+        format!("{message}, {temp_removed} stale temp entries ({temp_space_freed} bytes)")
+    } else {
+        message
+    };
+    let message = if downloads_removed > 0 {
+        format!("{message}, {downloads_removed} cached downloads ({downloads_space_freed} bytes)")
+    } else {
+        message
+    };
+
     ctx.emit(AppEvent::Package(PackageEvent::OperationCompleted {
         operation: PackageOperation::Cleanup,
         outcome: PackageOutcome::Cleanup {
-            states_removed: cleanup_result.states_removed,
+            states_removed: cleanup_result.states_removed + prune_result.states_deleted,
             packages_removed: packages_evicted,
             duration_ms: duration,
         },
     }));
 
-    if let Err(e) = update_gc_timestamp().await {
+    if let Err(e) = update_gc_timestamp(ctx).await {
         use sps2_events::events::GeneralEvent;
         ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
             "Failed to update GC timestamp",
@@ -267,6 +321,118 @@ pub async fn cleanup(ctx: &OpsCtx) -> Result<String, Error> {
     Ok(message)
 }
 
+/// Remove cached builds nobody has reused for a while
+async fn prune_artifact_cache(ctx: &OpsCtx) -> Result<(usize, u64), Error> {
+    let cache_cfg = &ctx.config.builder.performance.cache;
+    let max_age = Duration::from_secs(u64::from(cache_cfg.artifact_max_age_days) * 86_400);
+    sps2_builder::ArtifactCache::new(cache_cfg.artifact_dir.clone())
+        .prune(max_age)
+        .await
+}
+
+/// Remove package directories quarantined as incomplete
+///
+/// They are only kept for inspection; healing adds their packages again
+/// from an archive.
+async fn discard_incomplete_packages(ctx: &OpsCtx) -> Result<usize, Error> {
+    let incomplete = ctx.store.incomplete_packages().await?;
+    for hash in &incomplete {
+        ctx.store.discard_incomplete_package(hash).await?;
+    }
+    Ok(incomplete.len())
+}
+
+/// List or purge downloads quarantined after failing hash verification
+///
+/// Without `purge` the quarantined files are listed together with the hashes
+/// that were compared. With `purge` they are deleted, unless in check mode.
+///
+/// # Errors
+///
+/// Returns an error if the quarantine directory cannot be read or a file
+/// cannot be removed.
+pub async fn cleanup_quarantine(ctx: &OpsCtx, purge: bool) -> Result<String, Error> {
+    let dir = ctx.config.quarantine_path();
+    let entries = sps2_net::quarantine::list_quarantine(&dir).await?;
+    if entries.is_empty() {
+        return Ok("No quarantined downloads".to_string());
+    }
+
+    if purge && !ctx.check_mode {
+        let (removed, freed) = sps2_net::quarantine::purge_quarantine(&dir).await?;
+        return Ok(format!(
+            "Removed {removed} quarantined downloads ({freed} bytes)"
+        ));
+    }
+
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut lines = vec![if purge {
+        format!(
+            "Would remove {} quarantined downloads ({total} bytes):",
+            entries.len()
+        )
+    } else {
+        format!("{} quarantined downloads ({total} bytes):", entries.len())
+    }];
+    for entry in &entries {
+        lines.push(format!("  {} ({} bytes)", entry.path.display(), entry.size));
+        if let Some(record) = &entry.record {
+            lines.push(format!("    from     {}", record.url));
+            lines.push(format!("    expected {}", record.expected_hash));
+            lines.push(format!("    actual   {}", record.actual_hash));
+        }
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Prune or clear the cache of verified package downloads
+///
+/// Without `purge` the cached downloads beyond the `[cas]` retention policy
+/// are removed; with `purge` all of them are. In check mode they are only
+/// listed.
+///
+/// # Errors
+///
+/// Returns an error if the cache directory cannot be read or a file cannot
+/// be removed.
+pub async fn cleanup_downloads(ctx: &OpsCtx, purge: bool) -> Result<String, Error> {
+    let cache = ctx.download_cache();
+    if !ctx.check_mode {
+        let (removed, freed) = if purge {
+            cache.clear().await?
+        } else {
+            cache.prune().await?
+        };
+        return Ok(format!(
+            "Removed {removed} cached downloads ({freed} bytes)"
+        ));
+    }
+
+    let entries = if purge {
+        cache.list().await?
+    } else {
+        cache.prunable().await?
+    };
+    if entries.is_empty() {
+        return Ok("No cached downloads to remove".to_string());
+    }
+    let total: u64 = entries.iter().map(|entry| entry.size).sum();
+    let mut lines = vec![format!(
+        "Would remove {} cached downloads ({total} bytes):",
+        entries.len()
+    )];
+    for entry in &entries {
+        let last_used = chrono::DateTime::<chrono::Utc>::from(entry.last_used);
+        lines.push(format!(
+            "  {} ({} bytes, last used {})",
+            entry.path.display(),
+            entry.size,
+            last_used.format("%Y-%m-%d %H:%M")
+        ));
+    }
+    Ok(lines.join("\n"))
+}
+
 /// Rollback to a previous state
 ///
 /// # Errors
@@ -337,12 +503,25 @@ pub async fn rollback(ctx: &OpsCtx, target_state: Option<Uuid>) -> Result<StateI
         summary: Some(summary),
     }));
 
+    let requested: Vec<String> = target_state.iter().map(ToString::to_string).collect();
+    after_state_change(
+        ctx,
+        &target_id,
+        "rollback",
+        &requested,
+        state_info.changes.clone(),
+    )
+    .await;
+
     Ok(state_info)
 }
 
 /// Preview what would be rolled back without executing
 #[allow(clippy::too_many_lines)]
-async fn preview_rollback(ctx: &OpsCtx, target_state: Option<Uuid>) -> Result<StateInfo, Error> {
+pub(crate) async fn preview_rollback(
+    ctx: &OpsCtx,
+    target_state: Option<Uuid>,
+) -> Result<StateInfo, Error> {
     use std::collections::HashMap;
 
     // Resolve target state (same logic as main rollback)
@@ -504,6 +683,7 @@ pub async fn history(
 ) -> Result<Vec<StateInfo>, Error> {
     let all_states = ctx.state.list_states_detailed().await?;
     let current_id = ctx.state.get_current_state_id().await?;
+    let mut tags = state_tags(ctx).await?;
 
     if verify {
         // Deep verify across full DB history; cap by override or config (newest first)
@@ -531,6 +711,7 @@ pub async fn history(
                     package_count,
                     total_size: 0,
                     changes,
+                    tags: tags.remove(&id).unwrap_or_default(),
                 });
                 if out.len() >= limit {
                     break;
@@ -566,11 +747,30 @@ pub async fn history(
             package_count,
             total_size: 0,
             changes,
+            tags: tags.remove(&id).unwrap_or_default(),
         });
     }
     Ok(state_infos)
 }
 
+/// A single state with its package changes and audit trail
+///
+/// # Errors
+///
+/// Returns an error if the state does not exist or the state database
+/// cannot be read.
+pub async fn history_detail(ctx: &OpsCtx, state_id: Uuid) -> Result<StateDetail, Error> {
+    let parent_id = ctx.state.get_parent_state_id(&state_id).await?;
+    let changes = if let Some(parent_id) = parent_id {
+        calculate_state_changes(ctx, &parent_id, &state_id).await?
+    } else {
+        get_initial_state_changes(ctx, &state_id).await?
+    };
+    let state = get_rollback_state_info_with_changes(ctx, state_id, changes).await?;
+    let audit = ctx.state.state_audit(&state_id).await?;
+    Ok(StateDetail { state, audit })
+}
+
 async fn is_state_available(ctx: &OpsCtx, state_id: &Uuid) -> Result<bool, Error> {
     // Check every package in the state
     let packages = ctx.state.get_installed_packages_in_state(state_id).await?;
@@ -634,6 +834,10 @@ async fn get_rollback_state_info_with_changes(
 
     // Get actual package count for target state
     let package_count = get_state_package_count(ctx, &target_id).await?;
+    let tags = state_tags(ctx)
+        .await?
+        .remove(&target_id)
+        .unwrap_or_default();
 
     Ok(StateInfo {
         id: target_id,
@@ -644,9 +848,20 @@ async fn get_rollback_state_info_with_changes(
         package_count,
         total_size: 0, // TODO: Calculate actual size
         changes,       // Use pre-calculated changes
+        tags,
     })
 }
 
+/// Snapshot names by the state they name
+async fn state_tags(ctx: &OpsCtx) -> Result<std::collections::HashMap<Uuid, Vec<String>>, Error> {
+    let mut by_state: std::collections::HashMap<Uuid, Vec<String>> =
+        std::collections::HashMap::new();
+    for tag in ctx.state.list_state_tags().await? {
+        by_state.entry(tag.state_id()).or_default().push(tag.name);
+    }
+    Ok(by_state)
+}
+
 /// Get package count for a specific state
 async fn get_state_package_count(ctx: &OpsCtx, state_id: &Uuid) -> Result<usize, Error> {
     let packages = ctx.state.get_state_packages(state_id).await?;
@@ -730,8 +945,8 @@ async fn get_initial_state_changes(ctx: &OpsCtx, state_id: &Uuid) -> Result<Vec<
 }
 
 /// Update the GC timestamp after successful cleanup
-async fn update_gc_timestamp() -> Result<(), Error> {
-    let timestamp_path = std::path::Path::new(sps2_config::fixed_paths::LAST_GC_TIMESTAMP);
+async fn update_gc_timestamp(ctx: &OpsCtx) -> Result<(), Error> {
+    let timestamp_path = ctx.config.last_gc_timestamp_path();
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap_or_default()
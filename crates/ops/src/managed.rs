@@ -0,0 +1,34 @@
+//! Managed directories outside the live prefix
+//!
+//! Directories configured under `guard.managed_directories` with `populate`
+//! set are brought in line with the live prefix after every transition;
+//! verification covers them through `live_verifier`.
+
+use crate::OpsCtx;
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+
+/// Copy the live prefix into the managed directories
+///
+/// Runs after a transition has committed, so failures are reported as
+/// warnings rather than failing the operation.
+pub(crate) async fn sync_managed_directories(ctx: &OpsCtx) {
+    let Some(guard) = &ctx.config.guard else {
+        return;
+    };
+    match sps2_guard::sync_managed_directories(&ctx.state, &guard.managed_directories).await {
+        Ok(synced) => {
+            for sync in synced.iter().filter(|s| s.copied + s.removed > 0) {
+                ctx.emit(AppEvent::General(GeneralEvent::debug(format!(
+                    "Managed directory {}: {} copied, {} removed",
+                    sync.path.display(),
+                    sync.copied,
+                    sync.removed
+                ))));
+            }
+        }
+        Err(e) => ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            "Failed to update managed directories",
+            e.to_string(),
+        ))),
+    }
+}
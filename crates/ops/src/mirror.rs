@@ -0,0 +1,347 @@
+//! Copy a repository into a local directory
+//!
+//! `sps2 mirror` fetches a configured repository's signed index and the
+//! `.sp` and `.minisig` files of its packages into a directory that
+//! `sps2 install --from-dir` reads, such as a USB drive for an air-gapped
+//! machine. With package specs only those packages, their dependencies and
+//! what they recommend are copied. Packages already in the directory with
+//! the indexed hash are kept, so an interrupted mirror picks up where it
+//! stopped; the index is written last.
+
+use crate::keys::KeyManager;
+use crate::local_repo::LOCAL_INDEX_FILE;
+use crate::OpsCtx;
+use sps2_errors::{ConfigError, Error, OpsError, SigningError};
+use sps2_events::EventEmitter;
+use sps2_hash::Hash;
+use sps2_index::{IndexManager, ValidationPolicy, VersionBuilds, VersionEntry};
+use sps2_net::{PackageDownloadConfig, PackageDownloadRequest, PackageDownloader};
+use sps2_types::package::PackageSpec;
+use sps2_types::Version;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Mirror repository `repo` into `dest`, limited to the closure of
+/// `packages` unless it is empty
+///
+/// # Errors
+///
+/// Returns an error if the repository is not configured, its index is not
+/// signed by a trusted key, a requested package is not in it, or a package
+/// fails to download or verify.
+pub async fn mirror(
+    ctx: &OpsCtx,
+    repo: &str,
+    dest: &Path,
+    packages: &[String],
+) -> Result<String, Error> {
+    let _correlation = ctx.push_correlation(format!("mirror:{repo}"));
+    let base_url = ctx
+        .config
+        .repos
+        .get(repo)
+        .map(|config| config.url.trim_end_matches('/').to_string())
+        .ok_or_else(|| ConfigError::Invalid {
+            message: format!("Repository '{repo}' not found."),
+        })?;
+
+    let net = ctx.net()?;
+    let index_url = format!("{base_url}/{LOCAL_INDEX_FILE}");
+    let index_json = sps2_net::fetch_text(net, &index_url, &ctx.tx).await?;
+    let signature = sps2_net::fetch_text(net, &format!("{index_url}.minisig"), &ctx.tx).await?;
+    let mut key_manager = KeyManager::new(ctx.config.keys_path());
+    key_manager.load_trusted_keys().await?;
+    sps2_net::verify_minisign_bytes_with_keys(
+        index_json.as_bytes(),
+        &signature,
+        &key_manager.get_trusted_keys(),
+    )?;
+
+    let policy = ValidationPolicy::default()
+        .with_allow_insecure_urls(ctx.config.security.allow_insecure_index_urls);
+    let mut index = IndexManager::new(ctx.config.prefix_path()).with_validation_policy(policy);
+    index.load(Some(&index_json)).await?;
+    let (selected, skipped) = select(&index, packages)?;
+    for message in skipped {
+        ctx.emit_warning(message);
+    }
+
+    tokio::fs::create_dir_all(dest).await?;
+    let mut present = 0;
+    let mut requests = Vec::new();
+    for ((name, version), builds) in &selected {
+        for entry in builds {
+            let expected = Hash::from_hex(&entry.blake3)?;
+            if is_mirrored(dest, entry, &expected).await {
+                present += 1;
+                continue;
+            }
+            requests.push(PackageDownloadRequest {
+                name: name.clone(),
+                version: Version::parse(version)?,
+                package_url: entry.download_url.clone(),
+                signature_url: Some(entry.minisig_url.clone()).filter(|url| !url.is_empty()),
+                expected_hash: Some(expected),
+            });
+        }
+    }
+
+    // Mirrored packages stay out of the download cache
+    let downloader = PackageDownloader::with_client(
+        PackageDownloadConfig {
+            cache: None,
+            ..ctx.download_config()
+        },
+        net.clone(),
+        sps2_events::ProgressManager::new(),
+    );
+    let downloads = downloader
+        .download_packages_batch(requests, dest, None, &ctx.tx)
+        .await?;
+
+    let policy = ctx.security_policy();
+    let mut bytes = 0;
+    for download in &downloads {
+        bytes += download.size;
+        if policy.verify_signatures && !policy.allow_unsigned && !download.signature_verified {
+            // Removed so that a later run does not take it as mirrored
+            let _ = tokio::fs::remove_file(&download.package_path).await;
+            return Err(SigningError::VerificationFailed {
+                reason: format!(
+                    "signature of {} could not be verified",
+                    download.package_path.display()
+                ),
+            }
+            .into());
+        }
+    }
+
+    tokio::fs::write(dest.join(format!("{LOCAL_INDEX_FILE}.minisig")), &signature).await?;
+    tokio::fs::write(dest.join(LOCAL_INDEX_FILE), &index_json).await?;
+
+    #[allow(clippy::cast_precision_loss)]
+    let megabytes = bytes as f64 / (1024.0 * 1024.0);
+    Ok(format!(
+        "Mirrored {} packages from {repo} into {} ({} downloaded, {megabytes:.1} MB; {present} already present)",
+        selected.len(),
+        dest.display(),
+        downloads.len()
+    ))
+}
+
+/// Index entries to mirror, keyed by name and version
+type Selection = BTreeMap<(String, String), VersionBuilds>;
+
+/// Select the index entries to mirror
+///
+/// With no specs every version is selected. Otherwise each spec selects its
+/// best version, and so do the runtime dependencies and recommendations of
+/// every selected version in turn. A selected version comes with its builds
+/// for every architecture. Recommendations are optional: one that
+/// is malformed or not in the index is skipped, and a message saying so is
+/// returned with the selection.
+fn select(index: &IndexManager, packages: &[String]) -> Result<(Selection, Vec<String>), Error> {
+    let mut selected = BTreeMap::new();
+    let mut skipped = Vec::new();
+    if packages.is_empty() {
+        for (name, package) in index.index().into_iter().flat_map(|index| &index.packages) {
+            for (version, builds) in &package.versions {
+                selected.insert((name.clone(), version.clone()), builds.clone());
+            }
+        }
+        return Ok((selected, skipped));
+    }
+
+    // Each spec comes with the package recommending it, if it is optional
+    let mut pending: Vec<(String, Option<String>)> =
+        packages.iter().map(|spec| (spec.clone(), None)).collect();
+    let mut seen = HashSet::new();
+    while let Some((spec, recommended_by)) = pending.pop() {
+        if !seen.insert(spec.clone()) {
+            continue;
+        }
+        let found = PackageSpec::parse(&spec)
+            .map_err(Error::from)
+            .and_then(|parsed| {
+                index
+                    .find_best_version_with_string(&parsed)
+                    .and_then(|(version, _)| {
+                        let builds = index.index()?.packages.get(&parsed.name)?;
+                        Some((parsed.name.clone(), version, builds.versions.get(version)?))
+                    })
+                    .ok_or_else(|| {
+                        OpsError::PackageNotFound {
+                            package: spec.clone(),
+                        }
+                        .into()
+                    })
+            });
+        let (name, version, builds) = match (found, recommended_by) {
+            (Ok(found), _) => found,
+            (Err(e), Some(package)) => {
+                skipped.push(format!("Skipping {spec}, which {package} recommends: {e}"));
+                continue;
+            }
+            (Err(e), None) => return Err(e),
+        };
+        let key = (name, version.to_string());
+        if selected.contains_key(&key) {
+            continue;
+        }
+        let package = format!("{}-{}", key.0, key.1);
+        for entry in builds {
+            pending.extend(
+                entry
+                    .dependencies
+                    .runtime
+                    .iter()
+                    .map(|spec| (spec.clone(), None)),
+            );
+            pending.extend(
+                entry
+                    .dependencies
+                    .recommends
+                    .iter()
+                    .map(|spec| (spec.clone(), Some(package.clone()))),
+            );
+        }
+        selected.insert(key, builds.clone());
+    }
+    Ok((selected, skipped))
+}
+
+/// Whether `dest` already holds the package of `entry` with hash `expected`
+/// and, when the index lists one, its signature
+async fn is_mirrored(dest: &Path, entry: &VersionEntry, expected: &Hash) -> bool {
+    let Some(file) = sps2_net::url_file_name(&entry.download_url) else {
+        return false;
+    };
+    let path = dest.join(file);
+    if !entry.minisig_url.is_empty()
+        && !tokio::fs::try_exists(dest.join(format!("{file}.minisig")))
+            .await
+            .unwrap_or(false)
+    {
+        return false;
+    }
+    matches!(Hash::blake3_hash_file(&path).await, Ok(hash) if hash == *expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_index::{DependencyInfo, Index};
+
+    fn entry(deps: &[&str], recommends: &[&str]) -> VersionEntry {
+        VersionEntry {
+            revision: 1,
+            arch: "arm64".to_string(),
+            blake3: "a".repeat(64),
+            download_url: "https://repo.example/pkg.sp".to_string(),
+            minisig_url: "https://repo.example/pkg.sp.minisig".to_string(),
+            dependencies: DependencyInfo {
+                runtime: deps.iter().map(ToString::to_string).collect(),
+                build: Vec::new(),
+                recommends: recommends.iter().map(ToString::to_string).collect(),
+            },
+            sbom: None,
+            description: None,
+            homepage: None,
+            license: None,
+        }
+    }
+
+    fn index() -> IndexManager {
+        let mut index = Index::new();
+        for (name, version, entry) in [
+            ("jq", "1.6.0", entry(&[], &[])),
+            ("jq", "1.7.0", entry(&["oniguruma>=6.0.0"], &["jq-docs"])),
+            ("oniguruma", "6.9.0", entry(&[], &[])),
+            ("jq-docs", "1.7.0", entry(&[], &[])),
+            ("curl", "8.5.0", entry(&[], &[])),
+        ] {
+            index.add_version(name.to_string(), version.to_string(), entry);
+        }
+        let mut manager = IndexManager::new(".");
+        manager.set_index(index);
+        manager
+    }
+
+    fn keys(selected: &Selection) -> Vec<String> {
+        selected
+            .keys()
+            .map(|(name, version)| format!("{name}-{version}"))
+            .collect()
+    }
+
+    #[test]
+    fn selects_the_closure_of_requested_packages() {
+        let (selected, skipped) = select(&index(), &["jq".to_string()]).unwrap();
+        assert_eq!(
+            keys(&selected),
+            ["jq-1.7.0", "jq-docs-1.7.0", "oniguruma-6.9.0"]
+        );
+        assert!(skipped.is_empty());
+    }
+
+    #[test]
+    fn skips_recommendations_missing_from_the_index() {
+        let mut index = Index::new();
+        index.add_version(
+            "jq".to_string(),
+            "1.7.0".to_string(),
+            entry(&[], &["jq-docs", "jq-completions>=oops"]),
+        );
+        let mut manager = IndexManager::new(".");
+        manager.set_index(index);
+
+        let (selected, skipped) = select(&manager, &["jq".to_string()]).unwrap();
+        assert_eq!(keys(&selected), ["jq-1.7.0"]);
+        assert_eq!(skipped.len(), 2, "{skipped:?}");
+        assert!(skipped.iter().all(|message| message.contains("jq-1.7.0")));
+    }
+
+    #[test]
+    fn selects_the_builds_of_a_version_for_every_architecture() {
+        let mut index = Index::new();
+        for arch in ["arm64", "x86_64"] {
+            let mut build = entry(&[], &[]);
+            build.arch = arch.to_string();
+            index.add_version("jq".to_string(), "1.7.0".to_string(), build);
+        }
+        let mut manager = IndexManager::new(".");
+        manager.set_index(index);
+
+        let (selected, _) = select(&manager, &["jq".to_string()]).unwrap();
+        let arches: Vec<&str> = selected[&("jq".to_string(), "1.7.0".to_string())]
+            .iter()
+            .map(|build| build.arch.as_str())
+            .collect();
+        assert_eq!(arches, ["arm64", "x86_64"]);
+    }
+
+    #[test]
+    fn selects_everything_without_specs() {
+        assert_eq!(select(&index(), &[]).unwrap().0.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn mirrored_packages_are_found_by_their_name_without_query() {
+        let dest = tempfile::tempdir().unwrap();
+        std::fs::write(dest.path().join("pkg.sp"), b"package").unwrap();
+        std::fs::write(dest.path().join("pkg.sp.minisig"), b"signature").unwrap();
+        let expected = Hash::blake3_hash_file(&dest.path().join("pkg.sp"))
+            .await
+            .unwrap();
+
+        let mut signed = entry(&[], &[]);
+        signed.download_url = "https://repo.example/pkg.sp?token=abc#x".to_string();
+        assert!(is_mirrored(dest.path(), &signed, &expected).await);
+    }
+
+    #[test]
+    fn rejects_packages_missing_from_the_index() {
+        let err = select(&index(), &["wget".to_string()]).unwrap_err();
+        assert!(matches!(err, Error::Ops(OpsError::PackageNotFound { .. })));
+    }
+}
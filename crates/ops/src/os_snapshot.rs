@@ -0,0 +1,82 @@
+//! Local APFS snapshots
+//!
+//! With `state.os_snapshot_min_changes` set, the installer asks Time Machine
+//! for a local snapshot before committing a transition that changes at least
+//! that many packages and records its date on the new state. sps2 cannot
+//! restore such a snapshot itself: it covers the whole volume, not just the
+//! prefix, and is restored from macOS Recovery. [`rollback_os_snapshot`]
+//! explains how, for when a system is broken beyond what rolling back
+//! sps2's own states repairs.
+
+use crate::OpsCtx;
+use sps2_errors::Error;
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_state::models::OsSnapshot;
+use sps2_types::StateId;
+
+/// How to restore the local snapshot taken before `state` was committed, or
+/// the latest one when `None`
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read.
+pub async fn rollback_os_snapshot(ctx: &OpsCtx, state: Option<StateId>) -> Result<String, Error> {
+    let snapshot = match state {
+        Some(state_id) => ctx.state.get_os_snapshot(&state_id).await?,
+        None => ctx.state.list_os_snapshots().await?.into_iter().next(),
+    };
+    let Some(snapshot) = snapshot else {
+        return Ok(match state {
+            Some(state_id) => format!("No local snapshot was taken before state {state_id}"),
+            None => "No local snapshots were taken; set state.os_snapshot_min_changes to take \
+                     one before large changes"
+                .to_string(),
+        });
+    };
+    Ok(restore_guidance(&snapshot))
+}
+
+/// Point at the latest local snapshot when healing left discrepancies it
+/// could not repair
+pub(crate) async fn hint_after_heal(ctx: &OpsCtx, remaining: usize) {
+    let Ok(snapshots) = ctx.state.list_os_snapshots().await else {
+        return;
+    };
+    if let Some(snapshot) = snapshots.first() {
+        ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            format!("{remaining} discrepancies remain after healing"),
+            format!(
+                "local snapshot {} from before state {} can restore the whole volume; \
+                 run `sps2 rollback --os-snapshot` for how",
+                snapshot.snapshot, snapshot.state_id
+            ),
+        )));
+    }
+}
+
+fn restore_guidance(snapshot: &OsSnapshot) -> String {
+    let created = chrono::DateTime::from_timestamp(snapshot.created_at, 0)
+        .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+    [
+        format!(
+            "Local snapshot {} was taken before state {} ({}, {created}).",
+            snapshot.snapshot, snapshot.state_id, snapshot.operation
+        ),
+        "It covers the whole volume, not just sps2's prefix, so restoring it also undoes \
+         every other change made since. To restore it:"
+            .to_string(),
+        format!(
+            "  1. Check that it still exists: tmutil listlocalsnapshotdates / | grep {}",
+            snapshot.snapshot
+        ),
+        "  2. Restart into macOS Recovery and choose Restore from Time Machine".to_string(),
+        format!(
+            "  3. Select the volume and the snapshot dated {}",
+            snapshot.snapshot
+        ),
+        "macOS deletes local snapshots after about 24 hours or when the disk runs low on space."
+            .to_string(),
+    ]
+    .join("\n")
+}
@@ -0,0 +1,139 @@
+//! File ownership lookups
+//!
+//! Maps paths under the live prefix back to the installed packages that
+//! provide them, and lists the files no package provides.
+
+use crate::{live_verifier, FileOwnership, OpsCtx};
+use globset::GlobBuilder;
+use sps2_errors::{Error, OpsError};
+use sps2_state::queries;
+use std::path::Path;
+
+/// Characters that make a query a glob
+const GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+/// Find the packages owning a path under the live prefix
+///
+/// `query` is an absolute path under the live prefix or a path relative to
+/// it, and may be a glob (`*` stays within a directory, `**` crosses
+/// directories). A directory matches every file below it. With `orphans`,
+/// the files matching the query that no package owns are listed instead,
+/// skipping the guard ignore globs; without a query the whole prefix is
+/// searched. A single path nobody owns is reported as an orphan if it exists.
+///
+/// # Errors
+///
+/// Returns an error if the query is outside the live prefix or not a valid
+/// glob, or if the state database cannot be read.
+pub async fn owns(
+    ctx: &OpsCtx,
+    query: Option<&str>,
+    orphans: bool,
+) -> Result<FileOwnership, Error> {
+    let live_root = ctx.state.live_path();
+    let query = match query {
+        Some(query) => relative_query(live_root, query)?,
+        None => String::new(),
+    };
+    let matcher = Matcher::new(&query)?;
+
+    if orphans {
+        let orphans = live_verifier(ctx)?
+            .untracked_files()
+            .await?
+            .into_iter()
+            .filter(|path| matcher.is_match(path))
+            .collect();
+        return Ok(FileOwnership {
+            query,
+            owners: Vec::new(),
+            orphans,
+        });
+    }
+
+    let state_id = ctx.state.get_active_state().await?;
+    let mut tx = ctx.state.begin_transaction().await?;
+    let owners: Vec<_> = queries::get_file_owners(&mut tx, &state_id, matcher.prefix())
+        .await?
+        .into_iter()
+        .filter(|owner| matcher.is_match(&owner.path))
+        .collect();
+    tx.commit().await?;
+
+    let orphans = if owners.is_empty()
+        && matches!(matcher, Matcher::Path(_))
+        && live_root.join(&query).is_file()
+    {
+        vec![query.clone()]
+    } else {
+        Vec::new()
+    };
+
+    Ok(FileOwnership {
+        query,
+        owners,
+        orphans,
+    })
+}
+
+/// `query` relative to the live prefix, without surrounding slashes
+fn relative_query(live_root: &Path, query: &str) -> Result<String, Error> {
+    let path = Path::new(query);
+    let relative = if path.is_absolute() {
+        path.strip_prefix(live_root)
+            .map_err(|_| OpsError::OperationFailed {
+                message: format!("{query} is not under {}", live_root.display()),
+            })?
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        query.trim_start_matches("./").to_string()
+    };
+    Ok(relative.trim_matches('/').to_string())
+}
+
+/// Paths a query selects
+enum Matcher {
+    /// A file, or every file below a directory; empty for the whole prefix
+    Path(String),
+    /// A glob and the literal text before its first wildcard
+    Glob(globset::GlobMatcher, String),
+}
+
+impl Matcher {
+    fn new(query: &str) -> Result<Self, Error> {
+        let Some(wildcard) = query.find(GLOB_CHARS) else {
+            return Ok(Self::Path(query.to_string()));
+        };
+        let glob = GlobBuilder::new(query)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| OpsError::OperationFailed {
+                message: format!("invalid glob {query}: {e}"),
+            })?;
+        Ok(Self::Glob(
+            glob.compile_matcher(),
+            query[..wildcard].to_string(),
+        ))
+    }
+
+    /// Literal prefix every matching path starts with
+    fn prefix(&self) -> &str {
+        match self {
+            Self::Path(path) | Self::Glob(_, path) => path,
+        }
+    }
+
+    fn is_match(&self, path: &str) -> bool {
+        match self {
+            Self::Path(query) => {
+                query.is_empty()
+                    || path == query
+                    || path
+                        .strip_prefix(query.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+            Self::Glob(glob, _) => glob.is_match(path),
+        }
+    }
+}
@@ -11,7 +11,7 @@ use sps2_builder::{
 };
 use sps2_errors::{Error, OpsError};
 use sps2_events::{events::BuildPhase, AppEvent, BuildEvent, EventEmitter, PhaseStatus};
-use sps2_types::{BuildReport, Version};
+use sps2_types::{Arch, BuildReport, Version};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::time::Instant;
@@ -86,21 +86,22 @@ pub async fn pack_from_directory(
 
     // Load the manifest to get package metadata
     let manifest = sps2_store::manifest_io::read_manifest(manifest_path).await?;
+    sps2_types::name::validate_package_name(&manifest.package.name)?;
     let package_name = manifest.package.name.clone();
     let package_version = manifest.version()?;
 
     let _correlation = ctx.push_correlation(format!("pack:{package_name}"));
 
     // Create a minimal build context
-    let output_path = determine_output_path(output_dir, &package_name, &package_version);
     let session_id = Uuid::new_v4().to_string();
     let build_context = BuildContext::new(
         package_name.clone(),
         package_version.clone(),
         manifest_path.to_path_buf(), // Use manifest path as a stand-in for recipe
-        output_path.parent().unwrap_or(directory).to_path_buf(),
+        determine_output_dir(output_dir),
     )
     .with_revision(manifest.package.revision)
+    .with_arch(manifest.package.arch.clone())
     .with_event_sender(ctx.tx.clone())
     .with_session_id(session_id);
 
@@ -125,10 +126,12 @@ pub async fn pack_from_directory(
     );
 
     Ok(BuildReport {
+        schema_version: BuildReport::SCHEMA_VERSION,
         package: package_name,
         version: package_version,
         output_path: package_path,
         duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        tests: None,
     })
 }
 
@@ -171,20 +174,28 @@ async fn pack_from_recipe_impl(
     validate_staging_directory(&staging_dir, &package_name, &package_version)?;
 
     // Create build context for packaging (same as build command)
-    let output_path = determine_output_path(output_dir, &package_name, &package_version);
     let session_id = Uuid::new_v4().to_string();
     let build_context = BuildContext::new(
         package_name.clone(),
         package_version.clone(),
         recipe_path.to_path_buf(),
-        output_path.parent().unwrap_or(&build_root).to_path_buf(),
+        determine_output_dir(output_dir),
     )
     .with_revision(1)
+    .with_arch(
+        yaml_recipe
+            .environment
+            .arch
+            .unwrap_or_else(Arch::host)
+            .to_string(),
+    )
     .with_event_sender(ctx.tx.clone())
     .with_session_id(session_id);
 
     // Create build environment pointing to existing staging directory
     let mut environment = BuildEnvironment::new(build_context.clone(), &build_root)?;
+    environment.set_package_filters(yaml_recipe.package.clone());
+    environment.set_qa_suppressions(yaml_recipe.post.qa_suppress.clone());
 
     // If post steps are requested, execute them (same as build command)
     if execute_post {
@@ -201,7 +212,13 @@ async fn pack_from_recipe_impl(
 
         // Run QA pipeline (same as build command)
         let qa_pipeline_override = Some(yaml_recipe.post.qa_pipeline);
-        run_quality_pipeline(&build_context, &environment, qa_pipeline_override).await?;
+        run_quality_pipeline(
+            &build_context,
+            &environment,
+            qa_pipeline_override,
+            &ctx.config.builder.qa,
+        )
+        .await?;
     }
 
     // Create build config (same as build command)
@@ -216,6 +233,8 @@ async fn pack_from_recipe_impl(
         license: Some(yaml_recipe.metadata.license.clone()),
         runtime_deps: yaml_recipe.metadata.dependencies.runtime.clone(),
         build_deps: yaml_recipe.metadata.dependencies.build.clone(),
+        recommends: yaml_recipe.metadata.dependencies.recommends.clone(),
+        environment: yaml_recipe.install.environment.clone(),
     };
 
     // Create manifest (SBOM removed)
@@ -239,10 +258,12 @@ async fn pack_from_recipe_impl(
 
     // Create BuildReport
     Ok(BuildReport {
+        schema_version: BuildReport::SCHEMA_VERSION,
         package: package_name,
         version: package_version,
         output_path: package_path,
         duration_ms: u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+        tests: None,
     })
 }
 
@@ -383,15 +404,12 @@ fn validate_staging_directory(
     Ok(())
 }
 
-/// Determine output path for package
-fn determine_output_path(
-    output_dir: Option<&Path>,
-    package_name: &str,
-    package_version: &Version,
-) -> PathBuf {
-    let filename = format!("{package_name}-{package_version}-1.arm64.sp");
-
-    output_dir.unwrap_or_else(|| Path::new(".")).join(filename)
+/// Directory the package is written to
+///
+/// The file itself is named by the build context, after the package's
+/// revision and architecture.
+fn determine_output_dir(output_dir: Option<&Path>) -> PathBuf {
+    output_dir.unwrap_or_else(|| Path::new(".")).to_path_buf()
 }
 
 /// Detect build systems used in build steps for QA pipeline routing
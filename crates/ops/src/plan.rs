@@ -0,0 +1,410 @@
+//! Change plans shown before an operation changes the system
+//!
+//! A change plan is the preview the operation produces in check mode, with
+//! sizes filled in where they are known: the installed size for packages
+//! the state database has a record of, and the artifact size the repository
+//! reports for packages that would be downloaded.
+//!
+//! A dry run goes further and runs the installer's own resolution and
+//! plan-only path, so it reports the exact changes, downloads and disk usage
+//! the operation would have.
+
+use crate::{
+    apply, maintenance, sync, uninstall, update, ChangePlan, ChangeType, DependentsPolicy,
+    InstallRequest, OperationPlan, OpsCtx, PackageChange, PlannedDownload,
+};
+use sps2_errors::Error;
+use sps2_install::{InstallContext, InstallPlan, Installer, UpdateContext};
+use sps2_net::NetClient;
+use sps2_platform::filesystem_helpers::available_space;
+use sps2_state::models::Package;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// An operation to plan
+#[derive(Debug, Clone, Copy)]
+pub enum PlannedOperation<'a> {
+    Install(&'a [String]),
+    /// Packages to update, and whether to update even if installed
+    /// packages' version constraints break
+    Update(&'a [String], bool),
+    /// Packages to upgrade, and whether to upgrade even if installed
+    /// packages' version constraints break
+    Upgrade(&'a [String], bool),
+    Uninstall(&'a [String], DependentsPolicy),
+    /// Remove packages nothing installed needs any more
+    Autoremove,
+    /// Change set to apply, and whether to apply it even if installed
+    /// packages' version constraints break
+    Apply(&'a apply::ChangeSet, bool),
+    /// Bring the installed packages in line with a manifest
+    Sync(&'a sync::Manifest),
+    /// Roll back to a state, or to the previous one
+    Rollback(Option<Uuid>),
+}
+
+/// Work out what an operation would change without changing anything
+///
+/// Emits the same preview events as running the operation in check mode.
+///
+/// # Errors
+///
+/// Returns an error if the operation's preview fails, for example because
+/// resolution fails or the rollback target does not exist.
+pub async fn change_plan(
+    ctx: &OpsCtx,
+    operation: PlannedOperation<'_>,
+) -> Result<ChangePlan, Error> {
+    let mut plan = match operation {
+        PlannedOperation::Install(specs) => ChangePlan::from_report(
+            "install",
+            crate::install::preview_install(ctx, specs).await?,
+        ),
+        PlannedOperation::Update(names, _) => ChangePlan::from_report(
+            "update",
+            update::preview_update_or_upgrade(ctx, names, update::UpdateMode::Update).await?,
+        ),
+        PlannedOperation::Upgrade(names, _) => ChangePlan::from_report(
+            "upgrade",
+            update::preview_update_or_upgrade(ctx, names, update::UpdateMode::Upgrade).await?,
+        ),
+        PlannedOperation::Uninstall(names, policy) => ChangePlan::from_report(
+            "uninstall",
+            uninstall::preview_uninstall(ctx, names, policy).await?,
+        ),
+        PlannedOperation::Autoremove => ChangePlan::from_report(
+            "autoremove",
+            uninstall::preview_uninstall(ctx, &[], DependentsPolicy::Block).await?,
+        ),
+        PlannedOperation::Apply(changes, force) => ChangePlan::from_report(
+            "apply",
+            apply::preview(ctx, &apply::apply_context(ctx, changes, force)?).await?,
+        ),
+        PlannedOperation::Sync(manifest) => ChangePlan::from_report(
+            "sync",
+            apply::preview(ctx, &sync::sync_context(ctx, manifest).await?).await?,
+        ),
+        PlannedOperation::Rollback(target) => {
+            let state = maintenance::preview_rollback(ctx, target).await?;
+            rollback_plan(ctx, state.id, &state.changes).await?
+        }
+    };
+    fill_download_sizes(ctx, &mut plan).await;
+    Ok(plan)
+}
+
+/// Work out exactly what an operation would do without doing it
+///
+/// Nothing is downloaded, stored or staged, and no state is created.
+///
+/// # Errors
+///
+/// Returns the error the operation would fail with before changing
+/// anything: resolution failures, packages that are not installed or have
+/// dependents, or packages that are unavailable offline.
+pub async fn dry_run(
+    ctx: &OpsCtx,
+    operation: PlannedOperation<'_>,
+) -> Result<OperationPlan, Error> {
+    let installed: HashMap<String, Package> = ctx
+        .state
+        .get_installed_packages()
+        .await?
+        .into_iter()
+        .map(|package| (package.name.clone(), package))
+        .collect();
+    let new_installer = || async {
+        Ok::<_, Error>(
+            Installer::new(
+                ctx.install_config(),
+                ctx.resolver().await?.clone(),
+                ctx.state.clone(),
+                ctx.store.clone(),
+            )
+            .with_net_client(ctx.net()?.clone()),
+        )
+    };
+
+    let (name, plan) = match operation {
+        PlannedOperation::Install(specs) => {
+            let mut context = InstallContext::new().with_event_sender(ctx.tx.clone());
+            let mut local_files = Vec::new();
+            for request in crate::install::parse_install_requests(specs)? {
+                match request {
+                    InstallRequest::Remote(spec) => context = context.add_package(spec),
+                    InstallRequest::LocalFile(path) => local_files.push(path),
+                }
+            }
+            let context = context.with_local_files(local_files);
+            (
+                "install",
+                new_installer().await?.plan_install(&context).await?,
+            )
+        }
+        PlannedOperation::Update(names, force) | PlannedOperation::Upgrade(names, force) => {
+            let upgrade = matches!(operation, PlannedOperation::Upgrade(..));
+            let mut context = UpdateContext::new()
+                .with_upgrade(upgrade)
+                .with_force(force)
+                .with_event_sender(ctx.tx.clone());
+            for name in names {
+                context = context.add_package(name.clone());
+            }
+            let name = if upgrade { "upgrade" } else { "update" };
+            (name, new_installer().await?.plan_update(&context).await?)
+        }
+        PlannedOperation::Uninstall(names, policy) => {
+            let context = uninstall::uninstall_context(ctx, names, policy);
+            (
+                "uninstall",
+                new_installer().await?.plan_uninstall(&context).await?,
+            )
+        }
+        PlannedOperation::Autoremove => {
+            let context = uninstall::uninstall_context(ctx, &[], DependentsPolicy::Block);
+            (
+                "autoremove",
+                new_installer().await?.plan_uninstall(&context).await?,
+            )
+        }
+        PlannedOperation::Apply(changes, force) => {
+            let context = apply::apply_context(ctx, changes, force)?;
+            ("apply", new_installer().await?.plan_apply(&context).await?)
+        }
+        PlannedOperation::Sync(manifest) => {
+            let context = sync::sync_context(ctx, manifest).await?;
+            ("sync", new_installer().await?.plan_apply(&context).await?)
+        }
+        PlannedOperation::Rollback(target) => {
+            let state = maintenance::preview_rollback(ctx, target).await?;
+            let changes = rollback_plan(ctx, state.id, &state.changes).await?;
+            return Ok(sized_plan(
+                ctx,
+                ctx.state.get_current_state_id().await?,
+                changes,
+                Vec::new(),
+                &installed,
+            )
+            .await);
+        }
+    };
+
+    let InstallPlan { result, downloads } = plan;
+    let changes = result_changes(name, &result, &installed);
+    let downloads = size_downloads(ctx, downloads).await;
+    Ok(sized_plan(ctx, result.state_id, changes, downloads, &installed).await)
+}
+
+/// Change plan of the installer's result, sorted by package name
+fn result_changes(
+    operation: &str,
+    result: &sps2_install::InstallResult,
+    installed: &HashMap<String, Package>,
+) -> ChangePlan {
+    let mut plan = ChangePlan::new(operation);
+    for package in &result.installed_packages {
+        plan.install.push(PackageChange {
+            name: package.name.clone(),
+            from_version: None,
+            to_version: Some(package.version.clone()),
+            size: None,
+        });
+    }
+    for package in &result.updated_packages {
+        plan.upgrade.push(PackageChange {
+            name: package.name.clone(),
+            from_version: installed.get(&package.name).map(Package::version),
+            to_version: Some(package.version.clone()),
+            size: None,
+        });
+    }
+    for package in &result.removed_packages {
+        plan.remove.push(PackageChange {
+            name: package.name.clone(),
+            from_version: Some(package.version.clone()),
+            to_version: None,
+            size: installed
+                .get(&package.name)
+                .and_then(|p| u64::try_from(p.size).ok()),
+        });
+    }
+    for list in [&mut plan.install, &mut plan.upgrade, &mut plan.remove] {
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    plan.broken_dependencies
+        .clone_from(&result.broken_dependencies);
+    plan
+}
+
+/// Downloads of a plan with the artifact sizes the repository reports
+async fn size_downloads(
+    ctx: &OpsCtx,
+    downloads: Vec<sps2_install::PlannedDownload>,
+) -> Vec<PlannedDownload> {
+    let net = if ctx.config.network.offline {
+        None
+    } else {
+        ctx.net().ok()
+    };
+    let mut sized = Vec::with_capacity(downloads.len());
+    for download in downloads {
+        let size = match (sps2_net::file_url_path(&download.url), net) {
+            (Some(path), _) => tokio::fs::metadata(path).await.ok().map(|m| m.len()),
+            (None, Some(net)) => artifact_size(net, &download.url).await,
+            (None, None) => None,
+        };
+        sized.push(PlannedDownload {
+            name: download.package.name,
+            version: download.package.version,
+            url: download.url,
+            size,
+        });
+    }
+    sized
+}
+
+/// Complete a dry-run plan with download sizes and disk usage
+async fn sized_plan(
+    ctx: &OpsCtx,
+    state_id: Uuid,
+    mut changes: ChangePlan,
+    downloads: Vec<PlannedDownload>,
+    installed: &HashMap<String, Package>,
+) -> OperationPlan {
+    for change in changes.install.iter_mut().chain(changes.upgrade.iter_mut()) {
+        if change.size.is_none() {
+            change.size = downloads
+                .iter()
+                .find(|d| d.name == change.name && Some(&d.version) == change.to_version.as_ref())
+                .and_then(|d| d.size);
+        }
+    }
+    let installed_size = |name: &str| {
+        installed
+            .get(name)
+            .and_then(|package| u64::try_from(package.size).ok())
+            .unwrap_or(0)
+    };
+    let removed_bytes = changes
+        .upgrade
+        .iter()
+        .chain(&changes.remove)
+        .map(|change| installed_size(&change.name))
+        .sum();
+    OperationPlan {
+        state_id,
+        download_bytes: downloads.iter().filter_map(|d| d.size).sum(),
+        removed_bytes,
+        available_bytes: available_space(ctx.store.base_path()).await.ok(),
+        changes,
+        downloads,
+    }
+}
+
+/// Plan of a rollback, sized from the package records of both states
+async fn rollback_plan(
+    ctx: &OpsCtx,
+    target: Uuid,
+    changes: &[sps2_types::OpChange],
+) -> Result<ChangePlan, Error> {
+    let sizes = |packages: Vec<sps2_state::models::Package>| -> HashMap<String, u64> {
+        packages
+            .into_iter()
+            .filter_map(|p| Some((p.name, u64::try_from(p.size).ok()?)))
+            .collect()
+    };
+    let target_sizes = sizes(ctx.state.get_installed_packages_in_state(&target).await?);
+    let current_sizes = sizes(ctx.state.get_installed_packages().await?);
+
+    let mut plan = ChangePlan::new("rollback");
+    for change in changes {
+        let package = PackageChange {
+            name: change.package.clone(),
+            from_version: change.old_version.clone(),
+            to_version: change.new_version.clone(),
+            size: None,
+        };
+        match change.change_type {
+            ChangeType::Install => plan.install.push(PackageChange {
+                size: target_sizes.get(&change.package).copied(),
+                ..package
+            }),
+            ChangeType::Update | ChangeType::Downgrade => plan.upgrade.push(PackageChange {
+                size: target_sizes.get(&change.package).copied(),
+                ..package
+            }),
+            ChangeType::Remove => plan.remove.push(PackageChange {
+                size: current_sizes.get(&change.package).copied(),
+                ..package
+            }),
+        }
+    }
+    Ok(plan)
+}
+
+/// Ask the repository for the artifact size of packages that would be
+/// downloaded; sizes stay unknown offline or when the server does not say
+async fn fill_download_sizes(ctx: &OpsCtx, plan: &mut ChangePlan) {
+    if ctx.config.network.offline {
+        return;
+    }
+    let Ok(index) = ctx.index().await else {
+        return;
+    };
+    let Ok(net) = ctx.net() else {
+        return;
+    };
+    for package in plan.install.iter_mut().chain(plan.upgrade.iter_mut()) {
+        if package.size.is_some() {
+            continue;
+        }
+        let Some(entry) = package
+            .to_version
+            .as_ref()
+            .and_then(|version| index.get_version(&package.name, &version.to_string()))
+        else {
+            continue;
+        };
+        package.size = artifact_size(net, &entry.download_url).await;
+    }
+}
+
+/// Size of an artifact as the server reports it
+///
+/// Read from the header: a HEAD response has no body for its length to be
+/// taken from.
+async fn artifact_size(net: &NetClient, url: &str) -> Option<u64> {
+    let response = net.head(url).await.ok()?;
+    if !response.status().is_success() {
+        return None;
+    }
+    response
+        .headers()
+        .get("content-length")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+impl ChangePlan {
+    fn new(operation: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            install: Vec::new(),
+            upgrade: Vec::new(),
+            remove: Vec::new(),
+            broken_dependencies: Vec::new(),
+        }
+    }
+
+    fn from_report(operation: &str, report: crate::InstallReport) -> Self {
+        Self {
+            operation: operation.to_string(),
+            install: report.installed,
+            upgrade: report.updated,
+            remove: report.removed,
+            broken_dependencies: report.broken_dependencies,
+        }
+    }
+}
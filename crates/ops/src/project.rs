@@ -0,0 +1,197 @@
+//! Project environments
+//!
+//! A project lists the packages it needs in `sps2.toml`, or `.sps2`, in its
+//! root directory:
+//!
+//! ```toml
+//! packages = ["python>=3.12.0", "jq"]
+//! ```
+//!
+//! [`project_shell`] resolves them with their dependencies and links them
+//! into an environment of the project's own under the environments
+//! directory, apart from the live prefix and its states. The environment
+//! is swapped in whole when its packages change, the same way as the live
+//! prefix, and is left alone when they have not.
+
+use crate::{OpsCtx, ProjectShell};
+use serde::Deserialize;
+use sps2_errors::{Error, OpsError};
+use sps2_events::EventEmitter;
+use sps2_hash::Hash;
+use sps2_install::{ExecutionContext, ParallelExecutor};
+use sps2_resolver::ResolutionContext;
+use sps2_types::PackageSpec;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Names of the project file, in the order they are looked for in each
+/// directory
+pub const PROJECT_FILES: [&str; 2] = ["sps2.toml", ".sps2"];
+
+/// Contents of a project file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    /// Package specs, as given to `sps2 install`
+    packages: Vec<String>,
+}
+
+/// Set up the environment of the project containing `dir`, for a shell
+///
+/// The project is the nearest directory at or above `dir` with a project
+/// file. The variables put the environment's directories in front of the
+/// current search paths, set those its packages declare, and set
+/// `SPS2_PROJECT` to the project directory.
+///
+/// # Errors
+///
+/// Returns an error if no project file is found or it cannot be read,
+/// resolution fails, a package cannot be downloaded, or the environment
+/// cannot be synced.
+pub async fn project_shell(ctx: &OpsCtx, dir: &Path) -> Result<ProjectShell, Error> {
+    let dir = std::fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+    let file = find_project_file(&dir).ok_or_else(|| OpsError::ProjectNotFound {
+        dir: dir.display().to_string(),
+    })?;
+    let project = file.parent().unwrap_or(&dir).to_path_buf();
+    let specs = read_project_file(&file).await?;
+    if specs.is_empty() {
+        return Err(OpsError::InvalidProject {
+            path: file.display().to_string(),
+            reason: "packages lists no packages".to_string(),
+        }
+        .into());
+    }
+
+    let spec_names: Vec<String> = specs.iter().map(ToString::to_string).collect();
+    let _correlation = ctx.push_correlation_for_packages("shell", &spec_names);
+
+    let mut resolution_context =
+        ResolutionContext::new().with_recommends(ctx.config.general.install_recommends);
+    for spec in specs {
+        resolution_context = resolution_context.add_runtime_dep(spec);
+    }
+    let resolution = ctx
+        .resolver()
+        .await?
+        .resolve_with_sat(resolution_context)
+        .await?;
+    crate::install::ensure_available_offline(ctx, &resolution.nodes).await?;
+
+    let exec_context = ExecutionContext::new()
+        .with_event_sender(ctx.tx.clone())
+        .with_security_policy(ctx.security_policy())
+        .with_offline(ctx.config.network.offline)
+        .with_download_config(ctx.download_config())
+        .with_net_client(ctx.net()?.clone());
+    let resources = std::sync::Arc::new(sps2_config::ResourceManager::default());
+    let executor = ParallelExecutor::new(ctx.store.clone(), ctx.state.clone(), resources)?;
+    let prepared = executor
+        .execute_parallel(&resolution.execution_plan, &resolution.nodes, &exec_context)
+        .await?;
+
+    let env_dir = ctx.config.envs_path().join(environment_name(&project));
+    let sync =
+        sps2_install::sync_environment(&env_dir, &prepared, ctx.store.file_store().link_strategy())
+            .await?;
+    for conflict in &sync.profile.conflicts {
+        ctx.emit_warning(format!(
+            "{} sets {} differently from {}, whose value is used",
+            conflict.package, conflict.variable, conflict.winner
+        ));
+    }
+
+    let mut variables: BTreeMap<String, String> =
+        crate::env::search_path_values(&sync.prefix, |var| std::env::var(var).ok())
+            .into_iter()
+            .map(|(var, value)| (var.to_string(), value))
+            .collect();
+    variables.extend(sync.profile.variables);
+    variables.insert("SPS2_PROJECT".to_string(), project.display().to_string());
+
+    Ok(ProjectShell {
+        project,
+        prefix: sync.prefix,
+        packages: prepared
+            .keys()
+            .map(|package| (package.name.clone(), package.version.clone()))
+            .collect(),
+        changed: sync.changed,
+        variables,
+    })
+}
+
+/// The project file in `dir` or the nearest directory above it that has one
+fn find_project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().find_map(|dir| {
+        PROJECT_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file())
+    })
+}
+
+/// The package specs a project file lists
+async fn read_project_file(path: &Path) -> Result<Vec<PackageSpec>, Error> {
+    let invalid = |reason: String| OpsError::InvalidProject {
+        path: path.display().to_string(),
+        reason,
+    };
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+    let project: ProjectFile = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+    project
+        .packages
+        .iter()
+        .map(|spec| {
+            PackageSpec::parse(spec)
+                .map_err(|e| invalid(format!("invalid package spec '{spec}': {e}")).into())
+        })
+        .collect()
+}
+
+/// Directory name of the environment for the project at `project`
+fn environment_name(project: &Path) -> String {
+    let name = project
+        .file_name()
+        .map_or_else(|| "root".into(), |name| name.to_string_lossy());
+    let hash = Hash::blake3_from_data(project.as_os_str().as_encoded_bytes()).to_hex();
+    format!("{name}-{}", &hash[..12])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn nearest_project_file_lists_the_packages() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let project = temp.path().join("app");
+        let nested = project.join("src/bin");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(temp.path().join(".sps2"), "packages = [\"jq\"]\n").unwrap();
+        std::fs::write(
+            project.join("sps2.toml"),
+            "packages = [\"python>=3.12.0\", \"jq\"]\n",
+        )
+        .unwrap();
+
+        let file = find_project_file(&nested).unwrap();
+        assert_eq!(file, project.join("sps2.toml"));
+        let specs = read_project_file(&file).await.unwrap();
+        assert_eq!(specs.len(), 2);
+        assert_eq!(specs[0].name, "python");
+
+        std::fs::write(project.join("sps2.toml"), "package = [\"jq\"]\n").unwrap();
+        let err = read_project_file(&file).await.unwrap_err();
+        assert!(err.to_string().contains("invalid project file"));
+    }
+
+    #[test]
+    fn environments_are_named_after_the_project() {
+        let name = environment_name(Path::new("/Users/me/src/app"));
+        assert!(name.starts_with("app-"));
+        assert_ne!(name, environment_name(Path::new("/Users/me/old/app")));
+    }
+}
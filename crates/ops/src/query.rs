@@ -1,13 +1,16 @@
 //! Package Information and Search Operations
 
-use crate::{OpsCtx, PackageInfo, PackageStatus, SearchResult};
-use sps2_errors::{Error, OpsError};
+use crate::{OpsCtx, PackageFiles, PackageInfo, PackageStatus, SearchResult};
+use serde::Deserialize;
+use sps2_errors::{Error, InstallError, OpsError};
 use sps2_events::{
-    events::{PackageOperation, PackageOutcome},
+    events::{GeneralEvent, PackageOperation, PackageOutcome},
     AppEvent, EventEmitter, PackageEvent,
 };
 use sps2_hash::Hash;
+use sps2_state::queries;
 use sps2_store::StoredPackage;
+use sps2_types::collate;
 
 /// List installed packages
 ///
@@ -30,7 +33,8 @@ pub async fn list_packages(ctx: &OpsCtx) -> Result<Vec<PackageInfo>, Error> {
         // Get package details from index
         let package_version = package.version();
         let index_entry = ctx
-            .index
+            .index()
+            .await?
             .get_version(&package.name, &package_version.to_string());
 
         let (mut description, mut homepage, mut license, mut dependencies) =
@@ -72,7 +76,8 @@ pub async fn list_packages(ctx: &OpsCtx) -> Result<Vec<PackageInfo>, Error> {
 
         // Check if there's an available update
         let available_version = ctx
-            .index
+            .index()
+            .await?
             .get_package_versions_with_strings(&package.name)
             .and_then(|versions| {
                 versions
@@ -105,8 +110,7 @@ pub async fn list_packages(ctx: &OpsCtx) -> Result<Vec<PackageInfo>, Error> {
         package_infos.push(package_info);
     }
 
-    // Sort by name
-    package_infos.sort_by(|a, b| a.name.cmp(&b.name));
+    package_infos.sort_by(|a, b| collate::natural_cmp(&a.name, &b.name));
 
     ctx.emit(AppEvent::Package(PackageEvent::OperationCompleted {
         operation: PackageOperation::List,
@@ -133,7 +137,8 @@ pub async fn package_info(ctx: &OpsCtx, package_name: &str) -> Result<PackageInf
 
     // Get available versions from index (with version strings)
     let versions = ctx
-        .index
+        .index()
+        .await?
         .get_package_versions_with_strings(package_name)
         .ok_or_else(|| OpsError::PackageNotFound {
             package: package_name.to_string(),
@@ -185,6 +190,43 @@ pub async fn package_info(ctx: &OpsCtx, package_name: &str) -> Result<PackageInf
     Ok(package_info)
 }
 
+/// List the files an installed package put in the live prefix
+///
+/// Sizes, hashes and the executable bit come from the state database and
+/// the store's object metadata; the live files are not read.
+///
+/// # Errors
+///
+/// Returns an error if the package is not installed or the state database
+/// cannot be read.
+pub async fn package_files(
+    ctx: &OpsCtx,
+    package_name: &str,
+    tree: bool,
+) -> Result<PackageFiles, Error> {
+    let mut tx = ctx.state.begin_transaction().await?;
+    let state_id = queries::get_active_state(&mut tx).await?;
+    let package = queries::get_state_packages(&mut tx, &state_id)
+        .await?
+        .into_iter()
+        .find(|package| package.name == package_name)
+        .ok_or_else(|| InstallError::PackageNotInstalled {
+            package: package_name.to_string(),
+        })?;
+    let files =
+        queries::get_package_file_listing(&mut tx, &state_id, &package.name, &package.version)
+            .await?;
+    tx.commit().await?;
+
+    Ok(PackageFiles {
+        total_size: files.iter().filter_map(|file| file.size).sum(),
+        package: package.name,
+        version: package.version,
+        files,
+        tree,
+    })
+}
+
 /// Search for packages
 ///
 /// # Errors
@@ -197,14 +239,33 @@ pub async fn search_packages(ctx: &OpsCtx, query: &str) -> Result<Vec<SearchResu
         operation: PackageOperation::Search,
     }));
 
+    let results = search_local_index(ctx, query).await?;
+
+    ctx.emit(AppEvent::Package(PackageEvent::OperationCompleted {
+        operation: PackageOperation::Search,
+        outcome: PackageOutcome::Search {
+            query: query.to_string(),
+            total: results.len(),
+        },
+    }));
+
+    Ok(results)
+}
+
+/// Search the locally synced index without emitting operation events
+async fn search_local_index(ctx: &OpsCtx, query: &str) -> Result<Vec<SearchResult>, Error> {
     // Search package names in index
-    let package_names = ctx.index.search(query);
+    let package_names = ctx.index().await?.search(query);
 
     let mut results = Vec::new();
     let installed_packages = ctx.state.get_installed_packages().await?;
 
     for package_name in package_names {
-        if let Some(versions) = ctx.index.get_package_versions_with_strings(package_name) {
+        if let Some(versions) = ctx
+            .index()
+            .await?
+            .get_package_versions_with_strings(package_name)
+        {
             if let Some((version_str, latest)) = versions.first() {
                 if let Ok(version) = sps2_types::Version::parse(version_str) {
                     let installed = installed_packages
@@ -223,6 +284,95 @@ pub async fn search_packages(ctx: &OpsCtx, query: &str) -> Result<Vec<SearchResu
         }
     }
 
+    Ok(results)
+}
+
+/// Response body returned by a repository search endpoint
+#[derive(Debug, Deserialize)]
+struct RemoteSearchResponse {
+    #[serde(default)]
+    results: Vec<RemoteSearchEntry>,
+}
+
+/// A single package match returned by a repository search endpoint
+#[derive(Debug, Deserialize)]
+struct RemoteSearchEntry {
+    name: String,
+    version: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    homepage: Option<String>,
+}
+
+/// Search for packages, querying repository search endpoints as well
+///
+/// The synced repository's endpoint is read from its signed index; a
+/// `search_url` in a repository's configuration overrides it, and is the
+/// only way to query repositories whose index is not synced. Matches from
+/// the local index take precedence. Without any endpoint, or in offline
+/// mode, this falls back to [`search_packages`].
+///
+/// # Errors
+///
+/// Returns an error if the local package search fails. Failing endpoints are
+/// reported as warnings and skipped.
+pub async fn search_packages_remote(ctx: &OpsCtx, query: &str) -> Result<Vec<SearchResult>, Error> {
+    let endpoints = search_endpoints(ctx).await;
+
+    if endpoints.is_empty() || ctx.config.network.offline {
+        let reason = if endpoints.is_empty() {
+            "No repository declares a search endpoint"
+        } else {
+            "Offline mode is enabled"
+        };
+        ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            "Remote search unavailable, searching the local index",
+            reason,
+        )));
+        return search_packages(ctx, query).await;
+    }
+
+    let _correlation = ctx.push_correlation(format!("query:search-remote:{query}"));
+
+    ctx.emit(AppEvent::Package(PackageEvent::OperationStarted {
+        operation: PackageOperation::Search,
+    }));
+
+    let mut results = search_local_index(ctx, query).await?;
+    let installed_packages = ctx.state.get_installed_packages().await?;
+
+    for (repo_url, search_url) in endpoints {
+        let entries = match query_search_endpoint(ctx, &search_url, query).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!("Remote search failed for repository {repo_url}"),
+                    e.to_string(),
+                )));
+                continue;
+            }
+        };
+
+        for entry in entries {
+            if results.iter().any(|result| result.name == entry.name) {
+                continue;
+            }
+            let Ok(version) = sps2_types::Version::parse(&entry.version) else {
+                continue;
+            };
+            let installed = installed_packages.iter().any(|pkg| pkg.name == entry.name);
+            results.push(SearchResult {
+                name: entry.name,
+                version,
+                description: entry.description,
+                homepage: entry.homepage,
+                installed,
+            });
+        }
+    }
+    results.sort_by(|a, b| collate::natural_cmp(&a.name, &b.name));
+
     ctx.emit(AppEvent::Package(PackageEvent::OperationCompleted {
         operation: PackageOperation::Search,
         outcome: PackageOutcome::Search {
@@ -234,6 +384,52 @@ pub async fn search_packages(ctx: &OpsCtx, query: &str) -> Result<Vec<SearchResu
     Ok(results)
 }
 
+/// Search endpoints by repository URL, highest priority first
+///
+/// Only the repository reposync fetched the synced index from can fall back
+/// to the endpoint the index declares.
+async fn search_endpoints(ctx: &OpsCtx) -> Vec<(String, String)> {
+    let (signed, source) = match ctx.index().await {
+        Ok(index) => (
+            index
+                .index()
+                .and_then(|index| index.metadata.search_url.clone()),
+            index.cache.load_source().await.unwrap_or(None),
+        ),
+        Err(_) => (None, None),
+    };
+    let supplied_index = |url: &str| {
+        source
+            .as_deref()
+            .is_some_and(|source| source.trim_end_matches('/') == url.trim_end_matches('/'))
+    };
+
+    let mut repos = ctx.config.repos.get_all();
+    repos.sort_by_key(|repo| repo.priority);
+    repos
+        .into_iter()
+        .filter_map(|repo| {
+            let search_url = repo
+                .search_url
+                .clone()
+                .or_else(|| signed.clone().filter(|_| supplied_index(&repo.url)))?;
+            Some((repo.url.clone(), search_url))
+        })
+        .collect()
+}
+
+async fn query_search_endpoint(
+    ctx: &OpsCtx,
+    search_url: &str,
+    query: &str,
+) -> Result<Vec<RemoteSearchEntry>, Error> {
+    let mut url = sps2_net::parse_url(search_url)?;
+    url.query_pairs_mut().append_pair("q", query);
+    let response: RemoteSearchResponse =
+        sps2_net::fetch_json(ctx.net()?, url.as_str(), &ctx.tx).await?;
+    Ok(response.results)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -322,6 +518,7 @@ mod tests {
                 store_path,
                 is_local: true,
                 package_hash: None,
+                signing_key: None,
             },
         );
         let install_ctx = InstallContext {
@@ -329,6 +526,7 @@ mod tests {
             local_files: vec![],
             force: false,
             force_download: false,
+            required_hashes: None,
             event_sender: None,
         };
         atomic
@@ -360,4 +558,193 @@ mod tests {
         assert_eq!(packages[0].name, "demo");
         assert_eq!(packages[0].description.as_deref(), Some(description));
     }
+
+    /// Context whose index, synced from `main`, lists `ripgrep` and declares
+    /// `search_url`, with `main` configured to maybe override it and a
+    /// lower-priority `fallback` repository
+    async fn search_ctx(
+        temp_dir: &TempDir,
+        search_url: Option<String>,
+        override_url: Option<String>,
+        offline: bool,
+    ) -> OpsCtx {
+        let state_dir = temp_dir.path().join("state");
+        let store_dir = temp_dir.path().join("store");
+        afs::create_dir_all(&state_dir).await.expect("state dir");
+        afs::create_dir_all(&store_dir).await.expect("store dir");
+
+        let mut index = sps2_index::Index::new();
+        index.metadata.search_url = search_url;
+        index.add_version(
+            "ripgrep".to_string(),
+            "14.1.0".to_string(),
+            sps2_index::VersionEntry {
+                revision: 1,
+                arch: "arm64".to_string(),
+                blake3: "a".repeat(64),
+                download_url: "https://repo.example/ripgrep.sp".to_string(),
+                minisig_url: "https://repo.example/ripgrep.sp.minisig".to_string(),
+                dependencies: sps2_index::DependencyInfo::default(),
+                sbom: None,
+                description: Some("Local ripgrep".to_string()),
+                homepage: None,
+                license: None,
+            },
+        );
+        let mut index_manager = sps2_index::IndexManager::new(temp_dir.path().join("index"))
+            .with_validation_policy(
+                sps2_index::ValidationPolicy::default().with_allow_insecure_urls(true),
+            );
+        let json = index.to_json().expect("index json");
+        index_manager.load(Some(&json)).await.expect("load index");
+        index_manager
+            .cache
+            .save_raw(&json)
+            .await
+            .expect("index cache");
+        index_manager
+            .cache
+            .save_source("https://repo.example")
+            .await
+            .expect("index source");
+
+        let mut config = Config::default();
+        config.network.offline = offline;
+        config.repos.extras.insert(
+            "main".to_string(),
+            sps2_config::RepositoryConfig {
+                url: "https://repo.example".to_string(),
+                priority: 1,
+                algorithm: "minisign".to_string(),
+                key_ids: Vec::new(),
+                mirrors: Vec::new(),
+                search_url: override_url,
+            },
+        );
+        config.repos.extras.insert(
+            "fallback".to_string(),
+            sps2_config::RepositoryConfig {
+                url: "https://fallback.example".to_string(),
+                priority: 2,
+                algorithm: "minisign".to_string(),
+                key_ids: Vec::new(),
+                mirrors: Vec::new(),
+                search_url: None,
+            },
+        );
+
+        let (tx, _rx) = sps2_events::channel();
+        OpsContextBuilder::new()
+            .with_state(StateManager::new(&state_dir).await.expect("state manager"))
+            .with_store(PackageStore::new(store_dir))
+            .with_index(index_manager.clone())
+            .with_net(
+                NetClient::new_without_proxies(NetConfig {
+                    offline,
+                    ..NetConfig::default()
+                })
+                .expect("net client"),
+            )
+            .with_resolver(Resolver::with_events(index_manager, tx.clone()))
+            .with_builder(Builder::new())
+            .with_event_sender(tx)
+            .with_config(config)
+            .build()
+            .expect("ops ctx")
+    }
+
+    async fn search_endpoint<'a>(
+        server: &'a httpmock::MockServer,
+        path: &str,
+    ) -> httpmock::Mock<'a> {
+        server
+            .mock_async(|when, then| {
+                when.path(path).query_param("q", "rip");
+                then.status(200).body(
+                    r#"{"results":[
+                        {"name":"ripgrep","version":"15.0.0"},
+                        {"name":"ripgrep-all","version":"0.10.6","description":"Remote only"}
+                    ]}"#,
+                );
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn remote_search_uses_the_endpoint_the_signed_index_declares() {
+        let server = httpmock::MockServer::start_async().await;
+        let endpoint = search_endpoint(&server, "/search").await;
+        let temp_dir = TempDir::new().expect("ops tempdir");
+        let ctx = search_ctx(&temp_dir, Some(server.url("/search")), None, false).await;
+
+        let results = search_packages_remote(&ctx, "rip").await.expect("search");
+        let found: Vec<(&str, String)> = results
+            .iter()
+            .map(|result| (result.name.as_str(), result.version.to_string()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("ripgrep", "14.1.0".to_string()),
+                ("ripgrep-all", "0.10.6".to_string())
+            ]
+        );
+        assert_eq!(results[1].description.as_deref(), Some("Remote only"));
+        assert_eq!(endpoint.calls_async().await, 1);
+    }
+
+    #[tokio::test]
+    async fn configured_search_url_overrides_the_index() {
+        let server = httpmock::MockServer::start_async().await;
+        let declared = search_endpoint(&server, "/search").await;
+        let configured = search_endpoint(&server, "/mirror-search").await;
+        let temp_dir = TempDir::new().expect("ops tempdir");
+        let ctx = search_ctx(
+            &temp_dir,
+            Some(server.url("/search")),
+            Some(server.url("/mirror-search")),
+            false,
+        )
+        .await;
+
+        let results = search_packages_remote(&ctx, "rip").await.expect("search");
+        assert_eq!(results.len(), 2);
+        assert_eq!(configured.calls_async().await, 1);
+        assert_eq!(declared.calls_async().await, 0);
+    }
+
+    #[tokio::test]
+    async fn the_index_search_url_belongs_to_the_repository_that_supplied_it() {
+        let temp_dir = TempDir::new().expect("ops tempdir");
+        let declared = "https://fallback.example/search".to_string();
+        let ctx = search_ctx(&temp_dir, Some(declared.clone()), None, false).await;
+        ctx.index()
+            .await
+            .expect("index")
+            .cache
+            .save_source("https://fallback.example/")
+            .await
+            .expect("index source");
+
+        assert_eq!(
+            search_endpoints(&ctx).await,
+            [("https://fallback.example".to_string(), declared)]
+        );
+    }
+
+    #[tokio::test]
+    async fn remote_search_falls_back_to_the_local_index() {
+        let server = httpmock::MockServer::start_async().await;
+        let endpoint = search_endpoint(&server, "/search").await;
+
+        for (search_url, offline) in [(None, false), (Some(server.url("/search")), true)] {
+            let temp_dir = TempDir::new().expect("ops tempdir");
+            let ctx = search_ctx(&temp_dir, search_url, None, offline).await;
+            let results = search_packages_remote(&ctx, "rip").await.expect("search");
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].name, "ripgrep");
+            assert_eq!(results[0].description.as_deref(), Some("Local ripgrep"));
+        }
+        assert_eq!(endpoint.calls_async().await, 0);
+    }
 }
@@ -0,0 +1,241 @@
+//! Background index refresh
+//!
+//! [`daemon`] syncs the repository index on an interval and records each
+//! run, with the number of upgrades the new index offers, in the state
+//! database. Interactive commands read that record through [`index_notice`]
+//! instead of contacting the repository themselves. Verifications due under
+//! the guard schedule run alongside the refreshes.
+
+use crate::OpsCtx;
+use sps2_errors::Error;
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_index::{IndexCache, IndexManager};
+use sps2_types::Version;
+use std::path::Path;
+use std::time::Duration;
+
+/// Launchd label of the refresh agent written by [`launchd_plist`]
+pub const LAUNCHD_LABEL: &str = "org.sps2.index-refresh";
+
+/// Sync the index once and record the outcome in the state database
+///
+/// A failed sync is recorded as well, so the failure shows up in
+/// [`index_notice`].
+///
+/// # Errors
+///
+/// Returns the sync error, or an error if the outcome cannot be recorded.
+pub async fn refresh_index(ctx: &OpsCtx) -> Result<String, Error> {
+    let upgrades = sync_and_record(ctx).await??;
+    Ok(format!("Index refreshed, {}", upgrades_available(upgrades)))
+}
+
+/// Refresh the index every `interval` until interrupted
+///
+/// Failed refreshes are reported and recorded; the loop keeps running. After
+/// each refresh a verification runs if the guard schedule has one due. With
+/// `once` a single refresh runs and its result is returned.
+///
+/// # Errors
+///
+/// Returns an error if the single refresh of `once` fails, or if a refresh
+/// outcome cannot be recorded.
+pub async fn daemon(ctx: &OpsCtx, interval: Duration, once: bool) -> Result<String, Error> {
+    if once {
+        return refresh_index(ctx).await;
+    }
+
+    loop {
+        match sync_and_record(ctx).await? {
+            Ok(upgrades) => ctx.emit(AppEvent::General(GeneralEvent::debug(format!(
+                "Index refreshed, {}",
+                upgrades_available(upgrades)
+            )))),
+            Err(e) => ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                "Index refresh failed",
+                e.to_string(),
+            ))),
+        }
+        crate::scheduled_verification(ctx).await;
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Summary of the cached index and the last background refresh
+///
+/// Returns `None` when no background refresh has run yet. Only local files
+/// and the state database are read.
+///
+/// # Errors
+///
+/// Returns an error if the state database or the index cache cannot be read.
+pub async fn index_notice(ctx: &OpsCtx) -> Result<Option<String>, Error> {
+    let Some(run) = ctx.state.last_index_refresh().await? else {
+        return Ok(None);
+    };
+    let Some(age) = IndexCache::new(ctx.config.prefix_path()).age().await? else {
+        return Ok(None);
+    };
+
+    let age = format_age(age);
+    Ok(Some(match run.error {
+        None => format!(
+            "index is {age} old, {}",
+            upgrades_available(usize::try_from(run.upgrades_available).unwrap_or(0))
+        ),
+        Some(error) => format!("index is {age} old, last background refresh failed: {error}"),
+    }))
+}
+
+/// Launchd agent that runs `sps2 daemon --once` from `program` every
+/// `interval`, leaving the scheduling to launchd
+#[must_use]
+pub fn launchd_plist(program: &Path, interval: Duration) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>daemon</string>
+        <string>--once</string>
+    </array>
+    <key>StartInterval</key>
+    <integer>{}</integer>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>ProcessType</key>
+    <string>Background</string>
+</dict>
+</plist>
+"#,
+        program.display(),
+        interval.as_secs()
+    )
+}
+
+/// Sync the index and record the outcome; the outer error is a failure to
+/// record, the inner one a failed sync
+async fn sync_and_record(ctx: &OpsCtx) -> Result<Result<usize, Error>, Error> {
+    let outcome = sync_and_count_upgrades(ctx).await;
+    match &outcome {
+        Ok(upgrades) => ctx.state.record_index_refresh(*upgrades, None).await?,
+        Err(e) => {
+            ctx.state
+                .record_index_refresh(0, Some(&e.to_string()))
+                .await?;
+        }
+    }
+    Ok(outcome)
+}
+
+async fn sync_and_count_upgrades(ctx: &OpsCtx) -> Result<usize, Error> {
+    // Nobody can be asked here; key changes wait for an interactive reposync
+    crate::reposync(ctx, crate::KeyChangePolicy::Refuse).await?;
+
+    // The context keeps the index it loaded first; read the synced one back
+    let mut index = ctx.index().await?.clone();
+    index.load(None).await?;
+
+    let installed: Vec<(String, Version)> = ctx
+        .state
+        .get_installed_packages()
+        .await?
+        .into_iter()
+        .map(|pkg| {
+            let version = pkg.version();
+            (pkg.name, version)
+        })
+        .collect();
+    Ok(count_upgrades(&index, &installed))
+}
+
+/// Installed packages for which the index has a newer version
+fn count_upgrades(index: &IndexManager, installed: &[(String, Version)]) -> usize {
+    installed
+        .iter()
+        .filter(|(name, version)| {
+            index
+                .get_package_versions_with_strings(name)
+                .and_then(|versions| versions.first().map(|(latest, _)| Version::parse(latest)))
+                .is_some_and(|latest| latest.is_ok_and(|latest| &latest > version))
+        })
+        .count()
+}
+
+fn upgrades_available(count: usize) -> String {
+    match count {
+        1 => "1 upgrade available".to_string(),
+        n => format!("{n} upgrades available"),
+    }
+}
+
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        0..3600 => format!("{} minutes", seconds / 60),
+        3600..7200 => "1 hour".to_string(),
+        7200..172_800 => format!("{} hours", seconds / 3600),
+        _ => format!("{} days", seconds / 86_400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_index::{DependencyInfo, Index, VersionEntry};
+
+    fn entry() -> VersionEntry {
+        VersionEntry {
+            revision: 1,
+            arch: "arm64".to_string(),
+            blake3: String::new(),
+            download_url: String::new(),
+            minisig_url: String::new(),
+            dependencies: DependencyInfo::default(),
+            sbom: None,
+            description: None,
+            homepage: None,
+            license: None,
+        }
+    }
+
+    fn index_with(packages: &[(&str, &[&str])]) -> IndexManager {
+        let mut index = Index::new();
+        for (name, versions) in packages {
+            for version in *versions {
+                index.add_version((*name).to_string(), (*version).to_string(), entry());
+            }
+        }
+        let mut manager = IndexManager::new("/nonexistent");
+        manager.set_index(index);
+        manager
+    }
+
+    #[test]
+    fn counts_installed_packages_with_newer_versions() {
+        let index = index_with(&[
+            ("curl", &["8.0.0", "8.1.0"]),
+            ("jq", &["1.7.0"]),
+            ("git", &["2.40.0"]),
+        ]);
+        let installed = [
+            ("curl".to_string(), Version::new(8, 0, 0)),
+            ("jq".to_string(), Version::new(1, 7, 0)),
+            ("git".to_string(), Version::new(2, 41, 0)),
+            ("local-only".to_string(), Version::new(1, 0, 0)),
+        ];
+        assert_eq!(count_upgrades(&index, &installed), 1);
+    }
+
+    #[test]
+    fn ages_are_rounded_down_to_a_readable_unit() {
+        assert_eq!(format_age(59), "0 minutes");
+        assert_eq!(format_age(3_700), "1 hour");
+        assert_eq!(format_age(5 * 3600 + 10), "5 hours");
+        assert_eq!(format_age(3 * 86_400), "3 days");
+    }
+}
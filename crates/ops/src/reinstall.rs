@@ -0,0 +1,275 @@
+//! Reinstall command implementation
+//!
+//! Stages installed packages again at the same versions, into a new state.
+//! Packages whose store copy is missing or corrupt are downloaded again
+//! first. Store copies with a validation stamp under the current trusted
+//! keys are only checked for presence, not hashed again, unless one of
+//! their objects has failed a hash check since. Config files under `etc/`
+//! that were changed in the live prefix keep their changes.
+
+use crate::heal::{refill_package, LostContent};
+use crate::transition::after_transition;
+use crate::{InstallReport, OpsCtx};
+use sps2_errors::{Error, OpsError};
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_hash::Hash;
+use sps2_install::{Installer, ReinstallContext, ReinstallOperation};
+use sps2_net::trust_fingerprint;
+use sps2_resolver::PackageId;
+use sps2_state::{PackageFileEntry, ValidationStamp};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+/// Reinstall packages from the store
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - No packages are specified
+/// - A package is not installed
+/// - A damaged store copy cannot be downloaded again
+/// - Reinstallation fails
+#[allow(clippy::too_many_lines)]
+pub async fn reinstall(ctx: &OpsCtx, package_names: &[String]) -> Result<InstallReport, Error> {
+    let start = Instant::now();
+
+    if package_names.is_empty() {
+        return Err(OpsError::NoPackagesSpecified.into());
+    }
+
+    let _correlation = ctx.push_correlation_for_packages("reinstall", package_names);
+
+    let mut context = ReinstallContext::new().with_event_sender(ctx.tx.clone());
+    for package_name in package_names {
+        context = context.add_package(package_name.clone());
+    }
+    let packages = ReinstallOperation::new(ctx.state.clone(), ctx.store.clone())
+        .installed_packages(&context)
+        .await?;
+
+    let state_id = ctx.state.get_active_state().await?;
+    let hashes: HashMap<String, String> = ctx
+        .state
+        .get_installed_packages()
+        .await?
+        .into_iter()
+        .map(|installed| (installed.name, installed.hash))
+        .collect();
+    let trust = trust_fingerprint(&ctx.config.keys_path())?;
+    let failed = failed_objects(ctx).await?;
+    let mut damaged = HashMap::new();
+    let mut verified = Vec::new();
+    for package in &packages {
+        let hash = &hashes[&package.name];
+        let stamp = ctx.state.validation_stamp(hash, &trust).await?;
+        let (lost, rehashed) =
+            lost_store_content(ctx, &state_id, package, hash, stamp.is_none(), &failed).await?;
+        let message = match &stamp {
+            Some(_) if rehashed => Some(format!(
+                "Store copy of {package} failed a hash check since it was validated, \
+                 hashed it again"
+            )),
+            Some(stamp) => Some(format!(
+                "Store copy of {package} was validated at {}, not hashing it again",
+                stamp.validated_at
+            )),
+            None => None,
+        };
+        if let Some(message) = message {
+            ctx.emit(AppEvent::General(GeneralEvent::debug(message)));
+        }
+        if lost.package || !lost.paths.is_empty() {
+            damaged.insert(package.name.clone(), lost);
+        } else if rehashed {
+            verified.push(package);
+        }
+    }
+
+    if ctx.check_mode {
+        return Ok(preview_reinstall(ctx, &packages, &damaged));
+    }
+
+    for package in verified {
+        stamp_store_copy(ctx, package, &hashes[&package.name], None, &trust).await?;
+    }
+    for package in &packages {
+        let Some(lost) = damaged.get(&package.name) else {
+            continue;
+        };
+        ctx.emit(AppEvent::General(GeneralEvent::debug(format!(
+            "Store copy of {package} is damaged, downloading it again"
+        ))));
+        let signing_key = refill_package(
+            ctx,
+            &state_id,
+            &package.name,
+            &package.version.to_string(),
+            lost,
+        )
+        .await?;
+        stamp_store_copy(ctx, package, &hashes[&package.name], signing_key, &trust).await?;
+    }
+
+    let mut installer = Installer::new(
+        ctx.install_config(),
+        ctx.resolver().await?.clone(),
+        ctx.state.clone(),
+        ctx.store.clone(),
+    );
+    let result = installer.reinstall(context).await?;
+
+    let report = InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
+        installed: result
+            .installed_packages
+            .iter()
+            .map(|pkg| crate::PackageChange {
+                name: pkg.name.clone(),
+                from_version: Some(pkg.version.clone()),
+                to_version: Some(pkg.version.clone()),
+                size: None,
+            })
+            .collect(),
+        updated: Vec::new(),
+        removed: Vec::new(),
+        broken_dependencies: Vec::new(),
+        state_id: result.state_id,
+        duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+    };
+
+    after_transition(ctx, &report, "reinstall", package_names).await;
+
+    Ok(report)
+}
+
+/// File objects that failed their last hash check
+async fn failed_objects(ctx: &OpsCtx) -> Result<HashSet<String>, Error> {
+    let mut tx = ctx.state.begin_transaction().await?;
+    let failed = sps2_state::queries::get_failed_verification_objects(&mut tx, i64::MAX).await?;
+    tx.commit().await?;
+    Ok(failed.into_iter().map(|(hash, _, _)| hash).collect())
+}
+
+/// Store content of `package`, stored under `hash`, that is missing or corrupt
+///
+/// File objects are only hashed again when `rehash` is set, or once any of
+/// them is among the `failed` objects; otherwise they just have to be
+/// present. Also returns whether they were hashed.
+async fn lost_store_content(
+    ctx: &OpsCtx,
+    state_id: &uuid::Uuid,
+    package: &PackageId,
+    hash: &str,
+    rehash: bool,
+    failed: &HashSet<String>,
+) -> Result<(LostContent, bool), Error> {
+    let mut lost = LostContent {
+        package: !ctx.store.package_path(&Hash::from_hex(hash)?).exists(),
+        ..LostContent::default()
+    };
+
+    let mut tx = ctx.state.begin_transaction().await?;
+    let entries = sps2_state::queries::get_package_file_entries_by_name(
+        &mut tx,
+        state_id,
+        &package.name,
+        &package.version.to_string(),
+    )
+    .await?;
+    let rehash = rehash
+        || entries
+            .iter()
+            .any(|entry| failed.contains(&entry.file_hash));
+
+    let file_store = ctx.store.file_store();
+    for entry in entries
+        .into_iter()
+        .filter(PackageFileEntry::has_store_object)
+    {
+        let file_hash = Hash::from_hex(&entry.file_hash)?;
+        let intact = file_store.has_file(&file_hash).await
+            && (!rehash
+                || sps2_state::queries::verify_file_with_tracking(&mut tx, file_store, &file_hash)
+                    .await?);
+        if !intact {
+            lost.paths.push(entry.relative_path);
+        }
+    }
+    tx.commit().await?;
+    Ok((lost, rehash))
+}
+
+/// Record that the store copy of `package` passed verification
+async fn stamp_store_copy(
+    ctx: &OpsCtx,
+    package: &PackageId,
+    hash: &str,
+    key_id: Option<String>,
+    trust: &str,
+) -> Result<(), Error> {
+    let package_hash = ctx
+        .state
+        .get_package_archive_hash(&package.name, &package.version.to_string())
+        .await?;
+    ctx.state
+        .record_validation_stamp(&ValidationStamp {
+            store_hash: hash.to_string(),
+            package_hash,
+            key_id,
+            trust_fingerprint: trust.to_string(),
+            validated_at: chrono::Utc::now().timestamp(),
+        })
+        .await
+}
+
+/// Report what [`reinstall`] would do without changing anything
+fn preview_reinstall(
+    ctx: &OpsCtx,
+    packages: &[PackageId],
+    damaged: &HashMap<String, LostContent>,
+) -> InstallReport {
+    for package in packages {
+        let (action, store) = if damaged.contains_key(&package.name) {
+            (format!("Would download and reinstall {package}"), "damaged")
+        } else {
+            (format!("Would reinstall {package}"), "intact")
+        };
+        ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
+            operation: "reinstall".to_string(),
+            action,
+            details: HashMap::from([
+                ("version".to_string(), package.version.to_string()),
+                ("store".to_string(), store.to_string()),
+            ]),
+        }));
+    }
+
+    let mut categories = HashMap::new();
+    categories.insert("packages_reinstalled".to_string(), packages.len());
+    if !damaged.is_empty() {
+        categories.insert("packages_downloaded".to_string(), damaged.len());
+    }
+    ctx.emit(AppEvent::General(GeneralEvent::CheckModeSummary {
+        operation: "reinstall".to_string(),
+        total_changes: packages.len(),
+        categories,
+    }));
+
+    InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
+        installed: packages
+            .iter()
+            .map(|package| crate::PackageChange {
+                name: package.name.clone(),
+                from_version: Some(package.version.clone()),
+                to_version: Some(package.version.clone()),
+                size: None,
+            })
+            .collect(),
+        updated: Vec::new(),
+        removed: Vec::new(),
+        broken_dependencies: Vec::new(),
+        state_id: uuid::Uuid::nil(), // No state change in preview
+        duration_ms: 0,
+    }
+}
@@ -1,23 +1,44 @@
 //! Repository and Index Management Operations
 
-use crate::keys;
-use crate::{keys::KeyManager, OpsCtx};
+use crate::audit;
+use crate::keys::{self, KeyChanges, KeyManager};
+use crate::OpsCtx;
 use dialoguer::{theme::ColorfulTheme, Confirm};
 use sps2_config::{Config, RepositoryConfig};
 use sps2_errors::{ConfigError, Error, OpsError, SigningError};
 use sps2_events::{AppEvent, EventEmitter, FailureContext, GeneralEvent, LifecycleEvent};
-use std::path::PathBuf;
 use std::time::Instant;
+
+/// Most byte ranges requested for one index delta before fetching it whole
+const MAX_INDEX_DELTA_RANGES: usize = 64;
+
+/// What [`reposync`] does when a repository's signing keys changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyChangePolicy {
+    /// Show the changes and ask whether to accept them
+    Ask,
+    /// Accept the changes without asking
+    Accept,
+    /// Fail, listing the changes
+    Refuse,
+}
+
 /// Sync repository index
 ///
+/// Changes to the keys the repository publishes in `keys.json`, and an
+/// index signed by a key that is not trusted, are handled according to
+/// `keys`. Accepted changes are recorded in the audit trail of the active
+/// state.
+///
 /// # Errors
 ///
-/// Returns an error if index synchronization fails.
+/// Returns an error if index synchronization fails, or if key changes are
+/// refused or declined.
 ///
 /// # Panics
 ///
 /// Panics if `base_url` is None after validation (should never happen).
-pub async fn reposync(ctx: &OpsCtx, yes: bool) -> Result<String, Error> {
+pub async fn reposync(ctx: &OpsCtx, keys: KeyChangePolicy) -> Result<String, Error> {
     let start = Instant::now();
     let _correlation = ctx.push_correlation("reposync");
 
@@ -36,7 +57,7 @@ pub async fn reposync(ctx: &OpsCtx, yes: bool) -> Result<String, Error> {
         Some(base_url.to_string()),
     )));
 
-    let index_result = sync_and_verify_index(ctx, &base_url, start, yes).await;
+    let index_result = sync_and_verify_index(ctx, &base_url, start, keys).await;
     let index_json = match index_result {
         Ok(json) => json,
         Err(e) => {
@@ -71,7 +92,7 @@ pub async fn reposync(ctx: &OpsCtx, yes: bool) -> Result<String, Error> {
         }
     }
 
-    finalize_index_update(ctx, &index_json, start).await
+    finalize_index_update(ctx, &base_url, &index_json, start).await
 }
 
 fn get_base_url(ctx: &OpsCtx) -> Option<String> {
@@ -84,17 +105,20 @@ async fn sync_and_verify_index(
     ctx: &OpsCtx,
     base_url: &str,
     start: Instant,
-    yes: bool,
+    keys: KeyChangePolicy,
 ) -> Result<String, Error> {
     let index_url = format!("{base_url}/index.json");
     let index_sig_url = format!("{base_url}/index.json.minisig");
     let keys_url = format!("{base_url}/keys.json");
 
-    let cached_etag = ctx.index.cache.load_etag().await.unwrap_or(None);
-    let index_json =
-        download_index_conditional(ctx, &index_url, cached_etag.as_deref(), start).await?;
-    let index_signature = sps2_net::fetch_text(&ctx.net, &index_sig_url, &ctx.tx).await?;
-    let mut trusted_keys = fetch_and_verify_keys(ctx, &ctx.net, &keys_url, &ctx.tx).await?;
+    let index_json = if let Some(json) = fetch_index_delta(ctx, base_url, &index_url).await {
+        json
+    } else {
+        let cached_etag = ctx.index().await?.cache.load_etag().await.unwrap_or(None);
+        download_index_conditional(ctx, &index_url, cached_etag.as_deref(), start).await?
+    };
+    let index_signature = sps2_net::fetch_text(ctx.net()?, &index_sig_url, &ctx.tx).await?;
+    let mut trusted_keys = fetch_and_verify_keys(ctx, &keys_url, keys).await?;
 
     if let Err(e) = sps2_net::verify_minisign_bytes_with_keys(
         index_json.as_bytes(),
@@ -107,7 +131,7 @@ async fn sync_and_verify_index(
             &keys_url,
             &index_json,
             &index_signature,
-            yes,
+            keys,
             &mut trusted_keys,
         )
         .await?;
@@ -122,31 +146,27 @@ async fn handle_signature_verification_error(
     keys_url: &str,
     index_json: &str,
     index_signature: &str,
-    yes: bool,
+    keys: KeyChangePolicy,
     trusted_keys: &mut Vec<sps2_net::PublicKeyRef>,
 ) -> Result<(), Error> {
     match e {
         SigningError::NoTrustedKeyFound { key_id } => {
-            let repo_keys: keys::RepositoryKeys =
-                sps2_net::fetch_json(&ctx.net, keys_url, &ctx.tx).await?;
-            let key_to_trust = repo_keys.keys.iter().find(|k| k.key_id == key_id);
+            let mut key_manager = KeyManager::new(ctx.config.keys_path());
+            key_manager.load_trusted_keys().await?;
+            let repo_keys = key_manager
+                .fetch_repository_keys(ctx.net()?, keys_url, &ctx.tx)
+                .await?;
+            // Only a key a trusted key signed a rotation to may be trusted
+            let key_to_trust = key_manager
+                .trusted_rotation(&repo_keys, &key_id)
+                .map(|rotation| rotation.new_key.clone());
 
             if let Some(key) = key_to_trust {
                 let prompt = format!(
                     "The repository index is signed with a new key: {key_id}. Do you want to trust it?"
                 );
-                if yes
-                    || Confirm::with_theme(&ColorfulTheme::default())
-                        .with_prompt(prompt)
-                        .interact()
-                        .map_err(|e| {
-                            Error::internal(format!("Failed to get user confirmation: {e}"))
-                        })?
-                {
-                    let mut key_manager =
-                        KeyManager::new(PathBuf::from(sps2_config::fixed_paths::KEYS_DIR));
-                    key_manager.load_trusted_keys().await?;
-                    key_manager.import_key(key).await?;
+                if confirm(keys, &prompt)? {
+                    key_manager.import_key(&key).await?;
                     *trusted_keys = key_manager.get_trusted_keys();
                     // Re-verify
                     sps2_net::verify_minisign_bytes_with_keys(
@@ -154,6 +174,11 @@ async fn handle_signature_verification_error(
                         index_signature,
                         trusted_keys,
                     )?;
+                    audit::record_key_changes(
+                        ctx,
+                        &format!("trusted {key_id}, which signs the index of {keys_url}"),
+                    )
+                    .await;
                 } else {
                     return Err(Error::Signing(SigningError::NoTrustedKeyFound { key_id }));
                 }
@@ -180,9 +205,9 @@ async fn handle_signature_verification_error(
 /// - The configuration file cannot be loaded or created
 /// - The repository URL is invalid
 /// - The configuration cannot be saved
-pub async fn add_repo(_ctx: &OpsCtx, name: &str, url: &str) -> Result<String, Error> {
-    let config_path = Config::default_path()?;
-    let mut config = Config::load_or_default(&Some(config_path)).await?;
+pub async fn add_repo(ctx: &OpsCtx, name: &str, url: &str) -> Result<String, Error> {
+    let config_path = ctx.config.file_path()?;
+    let mut config = Config::load_or_default(&Some(config_path.clone())).await?;
 
     if config.repos.extras.contains_key(name) {
         return Err(Error::Config(ConfigError::Invalid {
@@ -195,10 +220,12 @@ pub async fn add_repo(_ctx: &OpsCtx, name: &str, url: &str) -> Result<String, Er
         priority: 10,
         algorithm: "minisign".to_string(),
         key_ids: vec![],
+        mirrors: vec![],
+        search_url: None,
     };
     config.repos.extras.insert(name.to_string(), new_repo);
 
-    config.save().await?;
+    config.save_to(&config_path).await?;
 
     Ok(format!("Repository '{name}' added successfully."))
 }
@@ -208,8 +235,8 @@ pub async fn add_repo(_ctx: &OpsCtx, name: &str, url: &str) -> Result<String, Er
 /// # Errors
 ///
 /// Returns an error if the configuration file cannot be read.
-pub async fn list_repos(_ctx: &OpsCtx) -> Result<String, Error> {
-    let config_path = Config::default_path()?;
+pub async fn list_repos(ctx: &OpsCtx) -> Result<String, Error> {
+    let config_path = ctx.config.file_path()?;
     let config = Config::load_or_default(&Some(config_path)).await?;
 
     let mut lines = Vec::new();
@@ -250,9 +277,9 @@ pub async fn list_repos(_ctx: &OpsCtx) -> Result<String, Error> {
 ///
 /// Returns an error if the configuration cannot be loaded or saved, or if the
 /// named repository does not exist.
-pub async fn remove_repo(_ctx: &OpsCtx, name: &str) -> Result<String, Error> {
-    let config_path = Config::default_path()?;
-    let mut config = Config::load_or_default(&Some(config_path)).await?;
+pub async fn remove_repo(ctx: &OpsCtx, name: &str) -> Result<String, Error> {
+    let config_path = ctx.config.file_path()?;
+    let mut config = Config::load_or_default(&Some(config_path.clone())).await?;
 
     let mut removed = false;
     match name {
@@ -284,10 +311,80 @@ pub async fn remove_repo(_ctx: &OpsCtx, name: &str) -> Result<String, Error> {
         }));
     }
 
-    config.save().await?;
+    config.save_to(&config_path).await?;
     Ok(format!("Repository '{name}' removed successfully."))
 }
 
+/// Rebuild the new index from the cached one and the blocks that changed
+///
+/// Uses the block digest the repository publishes next to `index.json` to
+/// request only the changed byte ranges. Returns `None` whenever the full
+/// index should be downloaded instead: no cached index or digest, a server
+/// without range support, or so many changes that ranges would not pay off.
+/// The result is checked against the digest here and against the index
+/// signature by the caller.
+async fn fetch_index_delta(ctx: &OpsCtx, base_url: &str, index_url: &str) -> Option<String> {
+    let local = ctx.index().await.ok()?.cache.load_raw().await.ok()?;
+    let net = ctx.net().ok()?;
+    let digest_url = format!("{base_url}/{}", sps2_index::INDEX_DIGEST_FILE);
+    let digest = match sps2_net::fetch_text(net, &digest_url, &ctx.tx).await {
+        Ok(text) => sps2_index::IndexDigest::from_json(&text).ok()?,
+        Err(e) => {
+            ctx.emit_debug(format!("No index digest, fetching the full index: {e}"));
+            return None;
+        }
+    };
+
+    if digest.matches(local.as_bytes()) {
+        ctx.emit_debug("Cached index matches the repository digest");
+        return Some(local);
+    }
+
+    let plan = digest.plan(local.as_bytes());
+    let ranges = plan.missing_ranges(&digest);
+    let missing = plan.missing_bytes(&digest);
+    if ranges.len() > MAX_INDEX_DELTA_RANGES || missing > digest.size / 2 {
+        ctx.emit_debug(format!(
+            "Index changed too much for a delta ({missing} of {} bytes), fetching it whole",
+            digest.size
+        ));
+        return None;
+    }
+
+    let mut fetched = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let response = net
+            .get_range(index_url, range.start, Some(range.end - 1))
+            .await
+            .ok()?;
+        if response.status().as_u16() != 206 {
+            ctx.emit_debug("Repository ignores range requests, fetching the full index");
+            return None;
+        }
+        let bytes = response.bytes().await.ok()?;
+        fetched.push((range, bytes.to_vec()));
+    }
+
+    match digest
+        .assemble(&plan, local.as_bytes(), &fetched)
+        .and_then(|data| {
+            String::from_utf8(data).map_err(|e| Error::internal(format!("index is not UTF-8: {e}")))
+        }) {
+        Ok(json) => {
+            ctx.emit_debug(format!(
+                "Fetched {missing} of {} index bytes in {} range requests",
+                digest.size,
+                fetched.len()
+            ));
+            Some(json)
+        }
+        Err(e) => {
+            ctx.emit_debug(format!("Index delta failed, fetching the full index: {e}"));
+            None
+        }
+    }
+}
+
 /// Download index conditionally with `ETag` support
 async fn download_index_conditional(
     ctx: &OpsCtx,
@@ -296,11 +393,11 @@ async fn download_index_conditional(
     start: Instant,
 ) -> Result<String, Error> {
     let response =
-        sps2_net::fetch_text_conditional(&ctx.net, index_url, cached_etag, &ctx.tx).await?;
+        sps2_net::fetch_text_conditional(ctx.net()?, index_url, cached_etag, &ctx.tx).await?;
 
     if let Some((content, new_etag)) = response {
         if let Some(etag) = new_etag {
-            if let Err(e) = ctx.index.cache.save_etag(&etag).await {
+            if let Err(e) = ctx.index().await?.cache.save_etag(&etag).await {
                 ctx.emit_warning(format!("Failed to save ETag: {e}"));
             }
         }
@@ -316,15 +413,20 @@ async fn download_index_conditional(
     }
 }
 
-/// Process and save the new index
+/// Process and save the new index, recording `base_url` as its source
 async fn finalize_index_update(
     ctx: &OpsCtx,
+    base_url: &str,
     index_json: &str,
     start: Instant,
 ) -> Result<String, Error> {
-    let old_package_count = ctx.index.index().map_or(0, |idx| idx.packages.len());
+    let old_package_count = ctx
+        .index()
+        .await?
+        .index()
+        .map_or(0, |idx| idx.packages.len());
 
-    let mut new_index_manager = ctx.index.clone();
+    let mut new_index_manager = ctx.index().await?.clone();
     new_index_manager.load(Some(index_json)).await?;
 
     let new_package_count = new_index_manager
@@ -332,7 +434,8 @@ async fn finalize_index_update(
         .map_or(0, |idx| idx.packages.len());
     let packages_updated = new_package_count.saturating_sub(old_package_count);
 
-    new_index_manager.save_to_cache().await?;
+    new_index_manager.cache.save_raw(index_json).await?;
+    new_index_manager.cache.save_source(base_url).await?;
 
     let message = if packages_updated > 0 {
         format!("Updated {packages_updated} packages from repository")
@@ -350,33 +453,79 @@ async fn finalize_index_update(
 }
 
 /// Fetch and verify signing keys with rotation support
+///
+/// Keys added or removed since the repository's keys were last accepted are
+/// listed and accepted according to `policy` before the trusted set changes.
 async fn fetch_and_verify_keys(
-    _ctx: &OpsCtx,
-    net_client: &sps2_net::NetClient,
+    ctx: &OpsCtx,
     keys_url: &str,
-    tx: &sps2_events::EventSender,
+    policy: KeyChangePolicy,
 ) -> Result<Vec<sps2_net::PublicKeyRef>, Error> {
-    let mut key_manager = KeyManager::new(PathBuf::from(sps2_config::fixed_paths::KEYS_DIR));
+    let mut key_manager = KeyManager::new(ctx.config.keys_path());
 
     key_manager.load_trusted_keys().await?;
 
     if key_manager.get_trusted_keys().is_empty() {
-        let bootstrap_key = "RWSGOq2NVecA2UPNdBUZykp1MLhfMmkAK/SZSjK3bpq2q7I8LbSVVBDm";
-        tx.emit(AppEvent::General(GeneralEvent::Warning {
+        ctx.emit(AppEvent::General(GeneralEvent::Warning {
             message: "Initializing with bootstrap key".to_string(),
             context: Some("First run - no trusted keys found".to_string()),
         }));
-        key_manager.initialize_with_bootstrap(bootstrap_key).await?;
+        key_manager
+            .initialize_with_bootstrap(keys::BOOTSTRAP_KEY)
+            .await?;
     }
 
-    let trusted_keys = key_manager
-        .fetch_and_verify_keys(net_client, keys_url, tx)
+    let repo_keys = key_manager
+        .fetch_repository_keys(ctx.net()?, keys_url, &ctx.tx)
+        .await?;
+    let changes = key_manager.key_changes(keys_url, &repo_keys).await?;
+    if !changes.is_empty() {
+        accept_key_changes(keys_url, &changes, policy)?;
+    }
+    key_manager
+        .accept_key_changes(keys_url, &repo_keys, &changes)
         .await?;
+    if !changes.is_empty() {
+        audit::record_key_changes(ctx, &format!("{keys_url}: {}", changes.describe())).await;
+    }
 
-    tx.emit(AppEvent::General(GeneralEvent::OperationCompleted {
+    ctx.emit(AppEvent::General(GeneralEvent::OperationCompleted {
         operation: "Key verification".to_string(),
         success: true,
     }));
 
-    Ok(trusted_keys)
+    Ok(key_manager.get_trusted_keys())
+}
+
+/// Accept `changes` to the keys served at `keys_url` according to `policy`
+fn accept_key_changes(
+    keys_url: &str,
+    changes: &KeyChanges,
+    policy: KeyChangePolicy,
+) -> Result<(), Error> {
+    let summary = changes.summary();
+    let prompt =
+        format!("The signing keys of {keys_url} changed:\n{summary}\nAccept these changes?");
+    if confirm(policy, &prompt)? {
+        Ok(())
+    } else {
+        Err(OpsError::RepositoryKeysChanged {
+            url: keys_url.to_string(),
+            changes: summary,
+        }
+        .into())
+    }
+}
+
+/// Whether `policy` lets a key change go ahead, asking with `prompt` if so
+fn confirm(policy: KeyChangePolicy, prompt: &str) -> Result<bool, Error> {
+    match policy {
+        KeyChangePolicy::Accept => Ok(true),
+        KeyChangePolicy::Refuse => Ok(false),
+        KeyChangePolicy::Ask => Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .default(false)
+            .interact()
+            .map_err(|e| Error::internal(format!("Failed to get user confirmation: {e}"))),
+    }
 }
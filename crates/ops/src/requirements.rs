@@ -0,0 +1,188 @@
+//! Component requirements declared per operation
+//!
+//! Each public operation lists the lazily initialized [`OpsCtx`](crate::OpsCtx)
+//! components it uses, so front ends can prepare exactly those before running
+//! it and skip the rest for read-only commands.
+
+/// Lazily initialized components an operation depends on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Requirements(u8);
+
+impl Requirements {
+    const INDEX_BIT: u8 = 1 << 0;
+    const NET_BIT: u8 = 1 << 1;
+    const RESOLVER_BIT: u8 = 1 << 2;
+    const BUILDER_BIT: u8 = 1 << 3;
+
+    /// Only the store, state and configuration
+    pub const NONE: Self = Self(0);
+
+    /// The package index
+    pub const INDEX: Self = Self(Self::INDEX_BIT);
+
+    /// The network client
+    pub const NET: Self = Self(Self::NET_BIT);
+
+    /// The dependency resolver (and the index it reads)
+    pub const RESOLVER: Self = Self(Self::RESOLVER_BIT | Self::INDEX_BIT);
+
+    /// The package builder (and the network client it uses)
+    pub const BUILDER: Self = Self(Self::BUILDER_BIT | Self::NET_BIT);
+
+    /// Combine two sets of requirements
+    #[must_use]
+    pub const fn and(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Whether the package index is needed
+    #[must_use]
+    pub const fn index(&self) -> bool {
+        self.0 & Self::INDEX_BIT != 0
+    }
+
+    /// Whether the network client is needed
+    #[must_use]
+    pub const fn net(&self) -> bool {
+        self.0 & Self::NET_BIT != 0
+    }
+
+    /// Whether the dependency resolver is needed
+    #[must_use]
+    pub const fn resolver(&self) -> bool {
+        self.0 & Self::RESOLVER_BIT != 0
+    }
+
+    /// Whether the package builder is needed
+    #[must_use]
+    pub const fn builder(&self) -> bool {
+        self.0 & Self::BUILDER_BIT != 0
+    }
+}
+
+/// Requirements of [`apply`](crate::apply)
+pub const APPLY: Requirements = Requirements::RESOLVER.and(Requirements::NET);
+
+/// Requirements of [`build`](crate::build)
+pub const BUILD: Requirements = Requirements::RESOLVER;
+
+/// Requirements of [`cache_list`](crate::cache_list) and [`cache_clear`](crate::cache_clear)
+pub const CACHE: Requirements = Requirements::NONE;
+
+/// Requirements of [`check_health`](crate::check_health)
+pub const CHECK_HEALTH: Requirements = Requirements::INDEX;
+
+/// Requirements of [`cleanup`](crate::cleanup)
+pub const CLEANUP: Requirements = Requirements::NONE;
+
+/// Requirements of [`cleanup_downloads`](crate::cleanup_downloads)
+pub const CLEANUP_DOWNLOADS: Requirements = Requirements::NONE;
+
+/// Requirements of [`cleanup_quarantine`](crate::cleanup_quarantine)
+pub const CLEANUP_QUARANTINE: Requirements = Requirements::NONE;
+
+/// Requirements of [`daemon`](crate::daemon) and [`refresh_index`](crate::refresh_index)
+pub const DAEMON: Requirements = REPOSYNC;
+
+/// Requirements of [`env`](crate::env)
+pub const ENV: Requirements = Requirements::NONE;
+
+/// Requirements of [`history`](crate::history) and
+/// [`history_detail`](crate::history_detail)
+pub const HISTORY: Requirements = Requirements::NONE;
+
+/// Requirements of [`install`](crate::install)
+pub const INSTALL: Requirements = Requirements::RESOLVER.and(Requirements::NET);
+
+/// Requirements of the key management operations in [`keys`](crate::keys)
+pub const KEYS: Requirements = Requirements::NONE;
+
+/// Requirements of [`list_packages`](crate::list_packages)
+pub const LIST_PACKAGES: Requirements = Requirements::INDEX;
+
+/// Requirements of the `pack_*` operations
+pub const PACK: Requirements = Requirements::NONE;
+
+/// Requirements of [`package_files`](crate::package_files)
+pub const PACKAGE_FILES: Requirements = Requirements::NONE;
+
+/// Requirements of [`package_info`](crate::package_info)
+pub const PACKAGE_INFO: Requirements = Requirements::INDEX;
+
+/// Requirements of [`project_shell`](crate::project_shell)
+pub const PROJECT_SHELL: Requirements = INSTALL;
+
+/// Requirements of the repository configuration operations
+pub const REPO_CONFIG: Requirements = Requirements::NONE;
+
+/// Requirements of [`reposync`](crate::reposync)
+pub const REPOSYNC: Requirements = Requirements::INDEX.and(Requirements::NET);
+
+/// Requirements of [`reinstall`](crate::reinstall), which can download
+/// damaged packages again
+pub const REINSTALL: Requirements = Requirements::RESOLVER.and(Requirements::NET);
+
+/// Requirements of [`rollback`](crate::rollback)
+pub const ROLLBACK: Requirements = Requirements::NONE;
+
+/// Requirements of [`rollback_os_snapshot`](crate::rollback_os_snapshot)
+pub const ROLLBACK_OS_SNAPSHOT: Requirements = Requirements::NONE;
+
+/// Requirements of [`search_packages`](crate::search_packages)
+pub const SEARCH_PACKAGES: Requirements = Requirements::INDEX;
+
+/// Requirements of [`search_packages_remote`](crate::search_packages_remote)
+pub const SEARCH_PACKAGES_REMOTE: Requirements = Requirements::INDEX.and(Requirements::NET);
+
+/// Requirements of [`sbom_export`](crate::sbom_export)
+pub const SBOM_EXPORT: Requirements = Requirements::NONE;
+
+/// Requirements of [`self_update`](crate::self_update)
+pub const SELF_UPDATE: Requirements = Requirements::NET;
+
+/// Requirements of the `services_*` operations, such as [`services_list`](crate::services_list)
+pub const SERVICES: Requirements = Requirements::NONE;
+
+/// Requirements of the `snapshot_*` operations and [`resolve_state`](crate::resolve_state)
+pub const SNAPSHOT: Requirements = Requirements::NONE;
+
+/// Requirements of [`store_compact`](crate::store_compact)
+pub const STORE_COMPACT: Requirements = Requirements::NONE;
+
+/// Requirements of [`store_relocate`](crate::store_relocate)
+pub const STORE_RELOCATE: Requirements = Requirements::NONE;
+
+/// Requirements of [`store_stats`](crate::store_stats)
+pub const STORE_STATS: Requirements = Requirements::NONE;
+
+/// Requirements of [`sync`](crate::sync)
+pub const SYNC: Requirements = Requirements::RESOLVER.and(Requirements::NET);
+
+/// Requirements of [`uninstall`](crate::uninstall)
+pub const UNINSTALL: Requirements = Requirements::RESOLVER;
+
+/// Requirements of [`update`](crate::update) and [`upgrade`](crate::upgrade)
+pub const UPDATE: Requirements = Requirements::RESOLVER.and(Requirements::NET);
+
+/// Requirements of [`verify`](crate::verify)
+pub const VERIFY: Requirements = Requirements::NONE;
+
+/// Requirements of [`verify`](crate::verify) with healing, which can fetch
+/// lost store content from the repository
+pub const VERIFY_HEAL: Requirements = Requirements::INDEX.and(Requirements::NET);
+
+/// Requirements of [`mirror`](crate::mirror), which reads the repository's
+/// own index instead of the cached one
+pub const MIRROR: Requirements = Requirements::NET;
+
+/// Requirements of [`owns`](crate::owns)
+pub const OWNS: Requirements = Requirements::NONE;
+
+/// Requirements of [`which`](crate::which)
+pub const WHICH: Requirements = Requirements::NONE;
+
+/// Requirements of [`package_diff`](crate::package_diff)
+pub const PACKAGE_DIFF: Requirements = Requirements::NONE;
+
+/// Requirements of [`verify_history`](crate::verify_history)
+pub const VERIFY_HISTORY: Requirements = Requirements::NONE;
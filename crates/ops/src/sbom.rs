@@ -0,0 +1,522 @@
+//! System-level SBOM export
+//!
+//! Merges the per-package SBOMs kept in the store into one document for the
+//! active state. Package SBOMs may be SPDX or `CycloneDX`; their components
+//! are normalized, deduplicated by package URL (or name and version when no
+//! URL is present), and rendered in the requested format.
+//!
+//! An SPDX document for the active state is also kept next to the state
+//! database as `sbom.spdx.json`. It is rewritten after every transition
+//! commits, by atomic rename, so readers only ever see a complete document
+//! for a committed state. Its name carries the state ID; a document left
+//! behind by an interrupted operation is replaced by the next transition.
+//! Exporting only reads the state and never touches it.
+
+use crate::OpsCtx;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sps2_errors::{Error, OpsError};
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_hash::Hash;
+use sps2_types::SbomFormat;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// File name of the system SBOM, next to the state database
+const SYSTEM_SBOM_FILE: &str = "sbom.spdx.json";
+
+/// A software component found in the active state
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Component {
+    name: String,
+    version: String,
+    purl: Option<String>,
+    licenses: BTreeSet<String>,
+    /// Installed sps2 packages that ship this component
+    provided_by: BTreeSet<String>,
+}
+
+impl Component {
+    fn key(&self) -> String {
+        self.purl
+            .clone()
+            .unwrap_or_else(|| format!("{}@{}", self.name, self.version))
+    }
+}
+
+/// Components merged across all package SBOMs, keyed for deduplication
+#[derive(Debug, Default)]
+struct Inventory {
+    components: BTreeMap<String, Component>,
+}
+
+impl Inventory {
+    fn add(&mut self, component: Component) {
+        match self.components.get_mut(&component.key()) {
+            Some(existing) => {
+                existing.licenses.extend(component.licenses);
+                existing.provided_by.extend(component.provided_by);
+            }
+            None => {
+                self.components.insert(component.key(), component);
+            }
+        }
+    }
+}
+
+/// Export a merged SBOM for the active state
+///
+/// Writes the document to `output` when given and returns a summary;
+/// otherwise returns the document itself.
+///
+/// # Errors
+///
+/// Returns an error if the installed packages cannot be listed, the
+/// document cannot be serialized, or the output file cannot be written.
+pub async fn sbom_export(
+    ctx: &OpsCtx,
+    format: SbomFormat,
+    output: Option<&Path>,
+) -> Result<String, Error> {
+    let state_id = ctx.state.get_active_state().await?.to_string();
+    let (inventory, without_sbom) = inventory(ctx, &state_id).await?;
+
+    let document = match format {
+        SbomFormat::Spdx => render_spdx(&inventory, &state_id),
+        SbomFormat::CycloneDx => render_cyclonedx(&inventory, &state_id),
+    };
+    let json =
+        serde_json::to_string_pretty(&document).map_err(|e| OpsError::SerializationError {
+            message: e.to_string(),
+        })?;
+
+    let Some(path) = output else {
+        return Ok(json);
+    };
+    tokio::fs::write(path, json).await?;
+
+    let missing = if without_sbom > 0 {
+        format!(" ({without_sbom} packages had no SBOM)")
+    } else {
+        String::new()
+    };
+    Ok(format!(
+        "Wrote {format} SBOM with {} components for state {state_id} to {}{missing}",
+        inventory.components.len(),
+        path.display()
+    ))
+}
+
+/// Rewrite the system SBOM for the active state
+///
+/// Runs after a transition has committed, so failures are reported as
+/// warnings rather than failing the operation.
+pub(crate) async fn update_system_sbom(ctx: &OpsCtx) {
+    let result = async {
+        let state_id = ctx.state.get_active_state().await?.to_string();
+        if system_sbom_is_current(ctx, &state_id).await {
+            return Ok(());
+        }
+        let (inventory, _) = inventory(ctx, &state_id).await?;
+        let json =
+            serde_json::to_string_pretty(&render_spdx(&inventory, &state_id)).map_err(|e| {
+                OpsError::SerializationError {
+                    message: e.to_string(),
+                }
+            })?;
+        write_system_sbom(ctx, &json).await
+    }
+    .await;
+    if let Err(e) = result {
+        warn_not_updated(ctx, &e);
+    }
+}
+
+/// Path of the system SBOM
+pub(crate) fn system_sbom_path(ctx: &OpsCtx) -> PathBuf {
+    ctx.state
+        .state_path()
+        .parent()
+        .map_or_else(|| ctx.config.prefix_path(), Path::to_path_buf)
+        .join(SYSTEM_SBOM_FILE)
+}
+
+/// Whether the system SBOM describes `state_id`
+async fn system_sbom_is_current(ctx: &OpsCtx, state_id: &str) -> bool {
+    let Ok(bytes) = tokio::fs::read(system_sbom_path(ctx)).await else {
+        return false;
+    };
+    serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|document| document.get("name")?.as_str().map(str::to_string))
+        .is_some_and(|name| name == document_name(state_id))
+}
+
+/// Replace the system SBOM with `json` by atomic rename
+async fn write_system_sbom(ctx: &OpsCtx, json: &str) -> Result<(), Error> {
+    let path = system_sbom_path(ctx);
+    let temp = path.with_extension(format!("json.{}.tmp", uuid::Uuid::new_v4()));
+    tokio::fs::write(&temp, json).await?;
+    if let Err(e) = tokio::fs::rename(&temp, &path).await {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn warn_not_updated(ctx: &OpsCtx, error: &Error) {
+    ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+        format!(
+            "Failed to update the system SBOM at {}",
+            system_sbom_path(ctx).display()
+        ),
+        error.to_string(),
+    )));
+}
+
+/// Components of every package installed in `state_id`, and how many
+/// packages had no readable SBOM
+async fn inventory(ctx: &OpsCtx, state_id: &str) -> Result<(Inventory, usize), Error> {
+    let state_id = uuid::Uuid::parse_str(state_id).map_err(|e| OpsError::OperationFailed {
+        message: format!("invalid state ID {state_id}: {e}"),
+    })?;
+    let packages = ctx.state.get_installed_packages_in_state(&state_id).await?;
+
+    let mut inventory = Inventory::default();
+    let mut without_sbom = 0;
+    for package in &packages {
+        let owner = format!("{}@{}", package.name, package.version);
+        inventory.add(Component {
+            name: package.name.clone(),
+            version: package.version.clone(),
+            purl: Some(format!("pkg:generic/{}@{}", package.name, package.version)),
+            licenses: BTreeSet::new(),
+            provided_by: BTreeSet::from([owner.clone()]),
+        });
+
+        let hash = Hash::from_hex(&package.hash).map_err(|e| OpsError::OperationFailed {
+            message: format!("invalid hash for {owner}: {e}"),
+        })?;
+        let Ok(bytes) = ctx.store.get_package_sbom(&hash).await else {
+            without_sbom += 1;
+            continue;
+        };
+        match parse_components(&bytes, &owner) {
+            Ok(components) => components.into_iter().for_each(|c| inventory.add(c)),
+            Err(e) => {
+                without_sbom += 1;
+                ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!("Ignoring unreadable SBOM of {owner}"),
+                    e,
+                )));
+            }
+        }
+    }
+    Ok((inventory, without_sbom))
+}
+
+// ----------------------------------------------------------------------------
+// Parsing
+// ----------------------------------------------------------------------------
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument {
+    #[serde(default)]
+    packages: Vec<SpdxPackage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    #[serde(default)]
+    version_info: Option<String>,
+    #[serde(default)]
+    license_concluded: Option<String>,
+    #[serde(default)]
+    license_declared: Option<String>,
+    #[serde(default)]
+    external_refs: Vec<SpdxExternalRef>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_type: String,
+    reference_locator: String,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxDocument {
+    #[serde(default)]
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxComponent {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    purl: Option<String>,
+    #[serde(default)]
+    licenses: Vec<CycloneDxLicenseChoice>,
+    /// Nested components are flattened into the inventory
+    #[serde(default)]
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxLicenseChoice {
+    #[serde(default)]
+    license: Option<CycloneDxLicense>,
+    #[serde(default)]
+    expression: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxLicense {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Extract components from an SPDX or `CycloneDX` JSON document
+fn parse_components(bytes: &[u8], owner: &str) -> Result<Vec<Component>, String> {
+    let value: Value = serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+    let provided_by = BTreeSet::from([owner.to_string()]);
+
+    if value.get("spdxVersion").is_some() {
+        let doc: SpdxDocument = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        return Ok(doc
+            .packages
+            .into_iter()
+            .map(|pkg| Component {
+                purl: pkg
+                    .external_refs
+                    .into_iter()
+                    .find(|r| r.reference_type == "purl")
+                    .map(|r| r.reference_locator),
+                licenses: [pkg.license_concluded, pkg.license_declared]
+                    .into_iter()
+                    .flatten()
+                    .filter(|l| is_known_license(l))
+                    .collect(),
+                name: pkg.name,
+                version: pkg.version_info.unwrap_or_default(),
+                provided_by: provided_by.clone(),
+            })
+            .collect());
+    }
+
+    if value.get("bomFormat").and_then(Value::as_str) == Some("CycloneDX") {
+        let doc: CycloneDxDocument = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let mut components = Vec::new();
+        let mut pending = doc.components;
+        while let Some(component) = pending.pop() {
+            components.push(Component {
+                name: component.name,
+                version: component.version.unwrap_or_default(),
+                purl: component.purl,
+                licenses: component
+                    .licenses
+                    .into_iter()
+                    .filter_map(|choice| {
+                        choice.expression.or_else(|| {
+                            choice
+                                .license
+                                .and_then(|license| license.id.or(license.name))
+                        })
+                    })
+                    .collect(),
+                provided_by: provided_by.clone(),
+            });
+            pending.extend(component.components);
+        }
+        return Ok(components);
+    }
+
+    Err("not an SPDX or CycloneDX JSON document".to_string())
+}
+
+fn is_known_license(license: &str) -> bool {
+    !license.is_empty() && license != "NOASSERTION" && license != "NONE"
+}
+
+// ----------------------------------------------------------------------------
+// Rendering
+// ----------------------------------------------------------------------------
+
+fn tool_name() -> String {
+    format!("sps2-{}", env!("CARGO_PKG_VERSION"))
+}
+
+fn document_name(state_id: &str) -> String {
+    format!("sps2-state-{state_id}")
+}
+
+fn render_spdx(inventory: &Inventory, state_id: &str) -> Value {
+    let mut packages = Vec::new();
+    let mut relationships = Vec::new();
+    for (i, component) in inventory.components.values().enumerate() {
+        let spdx_id = format!("SPDXRef-Package-{}", i + 1);
+        let license = if component.licenses.is_empty() {
+            "NOASSERTION".to_string()
+        } else {
+            component
+                .licenses
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" AND ")
+        };
+        let mut package = json!({
+            "SPDXID": spdx_id,
+            "name": component.name,
+            "versionInfo": component.version,
+            "downloadLocation": "NOASSERTION",
+            "licenseConcluded": license,
+            "filesAnalyzed": false,
+        });
+        if let Some(purl) = &component.purl {
+            package["externalRefs"] = json!([{
+                "referenceCategory": "PACKAGE-MANAGER",
+                "referenceType": "purl",
+                "referenceLocator": purl,
+            }]);
+        }
+        packages.push(package);
+        relationships.push(json!({
+            "spdxElementId": "SPDXRef-DOCUMENT",
+            "relationshipType": "DESCRIBES",
+            "relatedSpdxElement": spdx_id,
+        }));
+    }
+
+    json!({
+        "spdxVersion": "SPDX-2.3",
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": document_name(state_id),
+        "documentNamespace": format!(
+            "https://spdx.org/spdxdocs/sps2-state-{state_id}-{}",
+            uuid::Uuid::new_v4()
+        ),
+        "creationInfo": {
+            "created": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "creators": [format!("Tool: {}", tool_name())],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+fn render_cyclonedx(inventory: &Inventory, state_id: &str) -> Value {
+    let components: Vec<Value> = inventory
+        .components
+        .iter()
+        .map(|(key, component)| {
+            let mut value = json!({
+                "type": "library",
+                "bom-ref": key,
+                "name": component.name,
+                "version": component.version,
+            });
+            if let Some(purl) = &component.purl {
+                value["purl"] = json!(purl);
+            }
+            if !component.licenses.is_empty() {
+                value["licenses"] = component
+                    .licenses
+                    .iter()
+                    .map(|license| json!({ "expression": license }))
+                    .collect();
+            }
+            value
+        })
+        .collect();
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "serialNumber": format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+        "version": 1,
+        "metadata": {
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "tools": [{ "vendor": "sps2", "name": tool_name() }],
+            "component": {
+                "type": "operating-system",
+                "name": format!("sps2-state-{state_id}"),
+            },
+        },
+        "components": components,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPDX: &str = r#"{
+        "spdxVersion": "SPDX-2.3",
+        "packages": [
+            {
+                "name": "zlib",
+                "versionInfo": "1.3.1",
+                "licenseConcluded": "Zlib",
+                "externalRefs": [
+                    {"referenceCategory": "PACKAGE-MANAGER", "referenceType": "purl",
+                     "referenceLocator": "pkg:generic/zlib@1.3.1"}
+                ]
+            }
+        ]
+    }"#;
+
+    const CYCLONEDX: &str = r#"{
+        "bomFormat": "CycloneDX",
+        "specVersion": "1.5",
+        "components": [
+            {
+                "type": "library",
+                "name": "zlib",
+                "version": "1.3.1",
+                "purl": "pkg:generic/zlib@1.3.1",
+                "licenses": [{"license": {"id": "Zlib"}}],
+                "components": [{"type": "library", "name": "adler32", "version": "1.0"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn components_are_deduplicated_across_formats() {
+        let mut inventory = Inventory::default();
+        for component in parse_components(SPDX.as_bytes(), "curl@8.0.0").unwrap() {
+            inventory.add(component);
+        }
+        for component in parse_components(CYCLONEDX.as_bytes(), "openssl@3.0.0").unwrap() {
+            inventory.add(component);
+        }
+
+        assert_eq!(inventory.components.len(), 2);
+        let zlib = &inventory.components["pkg:generic/zlib@1.3.1"];
+        assert_eq!(zlib.licenses, BTreeSet::from(["Zlib".to_string()]));
+        assert_eq!(zlib.provided_by.len(), 2);
+        assert!(inventory.components.contains_key("adler32@1.0"));
+    }
+
+    #[test]
+    fn rendered_documents_list_every_component() {
+        let mut inventory = Inventory::default();
+        for component in parse_components(CYCLONEDX.as_bytes(), "openssl@3.0.0").unwrap() {
+            inventory.add(component);
+        }
+
+        let spdx = render_spdx(&inventory, "state");
+        assert_eq!(spdx["packages"].as_array().unwrap().len(), 2);
+        let cdx = render_cyclonedx(&inventory, "state");
+        assert_eq!(cdx["components"].as_array().unwrap().len(), 2);
+        assert!(parse_components(b"{}", "x").is_err());
+    }
+}
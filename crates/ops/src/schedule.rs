@@ -0,0 +1,152 @@
+//! Verifications run by policy rather than on request
+//!
+//! `[guard.schedule]` asks for the live prefix to be verified after each
+//! boot, after installs, or every few days. Whether a run is due is decided
+//! from the last recorded verification run, so a manual `sps2 verify`
+//! resets the clock. Scheduled runs never heal; discrepancies are reported
+//! as warnings pointing at `sps2 verify --heal`.
+
+use crate::{OpsCtx, VerificationResult};
+use sps2_config::GuardScheduleConfig;
+use sps2_errors::Error;
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Why a scheduled verification ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VerificationTrigger {
+    /// First verification since the system booted
+    Boot,
+    /// Packages were installed or updated
+    AfterInstall,
+    /// The last verification is older than the configured interval
+    Interval,
+}
+
+impl VerificationTrigger {
+    fn describe(self) -> &'static str {
+        match self {
+            Self::Boot => "after boot",
+            Self::AfterInstall => "after install",
+            Self::Interval => "on schedule",
+        }
+    }
+}
+
+/// Run the verification the guard schedule calls for, if one is due
+///
+/// Checks the boot and interval triggers against the last recorded
+/// verification. Failures are reported as warnings; the command that
+/// triggered the check has already succeeded.
+pub async fn scheduled_verification(ctx: &OpsCtx) -> Option<VerificationResult> {
+    let schedule = schedule(ctx)?;
+    let trigger = match due_trigger(ctx, &schedule).await {
+        Ok(trigger) => trigger?,
+        Err(e) => {
+            ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                "Failed to check the verification schedule",
+                e.to_string(),
+            )));
+            return None;
+        }
+    };
+    run(ctx, &schedule, trigger).await
+}
+
+/// Verify after an install or update changed packages, if configured
+pub(crate) async fn verify_after_install(ctx: &OpsCtx) {
+    let Some(schedule) = schedule(ctx) else {
+        return;
+    };
+    if schedule.verify_after_install {
+        run(ctx, &schedule, VerificationTrigger::AfterInstall).await;
+    }
+}
+
+/// The schedule in effect; `None` in check mode or when nothing is scheduled
+fn schedule(ctx: &OpsCtx) -> Option<GuardScheduleConfig> {
+    let guard = ctx.config.guard.as_ref()?;
+    (guard.enabled && guard.schedule.is_enabled() && !ctx.check_mode)
+        .then(|| guard.schedule.clone())
+}
+
+async fn due_trigger(
+    ctx: &OpsCtx,
+    schedule: &GuardScheduleConfig,
+) -> Result<Option<VerificationTrigger>, Error> {
+    let last = ctx.state.last_verification_at().await?;
+    if schedule.verify_on_boot {
+        if let Some(boot) = boot_time() {
+            if last.is_none_or(|last| last < boot) {
+                return Ok(Some(VerificationTrigger::Boot));
+            }
+        }
+    }
+    if schedule.interval_days > 0 {
+        let interval = i64::from(schedule.interval_days) * SECONDS_PER_DAY;
+        let now = chrono::Utc::now().timestamp();
+        if last.is_none_or(|last| now - last >= interval) {
+            return Ok(Some(VerificationTrigger::Interval));
+        }
+    }
+    Ok(None)
+}
+
+async fn run(
+    ctx: &OpsCtx,
+    schedule: &GuardScheduleConfig,
+    trigger: VerificationTrigger,
+) -> Option<VerificationResult> {
+    ctx.emit(AppEvent::General(GeneralEvent::debug(format!(
+        "Running scheduled {} verification {}",
+        schedule.level,
+        trigger.describe()
+    ))));
+    match crate::verify(ctx, false, &schedule.level, "live", false).await {
+        Ok(result) => {
+            if !result.is_valid {
+                ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                    format!(
+                        "Verification {} found {} discrepancies",
+                        trigger.describe(),
+                        result.discrepancies.len()
+                    ),
+                    "run `sps2 verify --heal` to repair them",
+                )));
+            }
+            Some(result)
+        }
+        Err(e) => {
+            ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+                format!("Verification {} failed", trigger.describe()),
+                e.to_string(),
+            )));
+            None
+        }
+    }
+}
+
+/// When the system last booted, as a Unix timestamp
+#[cfg(target_os = "macos")]
+fn boot_time() -> Option<i64> {
+    // `{ sec = 1718000000, usec = 123456 } Mon Jun 10 ...`
+    let output = std::process::Command::new("/usr/sbin/sysctl")
+        .args(["-n", "kern.boottime"])
+        .output()
+        .ok()?;
+    let text = String::from_utf8(output.stdout).ok()?;
+    let (_, rest) = text.split_once("sec = ")?;
+    rest.split(',').next()?.trim().parse().ok()
+}
+
+/// When the system last booted, as a Unix timestamp
+#[cfg(not(target_os = "macos"))]
+fn boot_time() -> Option<i64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()
+}
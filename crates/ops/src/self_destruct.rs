@@ -0,0 +1,251 @@
+//! Removing an installation (`sps2 self destruct`)
+//!
+//! Everything sps2 keeps on disk goes: the prefix with the live tree,
+//! states, database, logs and keys, the store, the build caches, the
+//! refresh agent for launchd, the launchd services rendered for packages
+//! and the lines sps2 asks users to add to their shell startup files to
+//! put it on `PATH`. Configuration in `~/.config/sps2` is left alone, as
+//! is a compiler cache, which may be shared with other tools.
+
+use crate::cache::{self, Location};
+use crate::refresh::LAUNCHD_LABEL;
+use crate::types::{Removal, SelfDestructReport};
+use sps2_config::Config;
+use sps2_types::CacheKind;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Shell startup files searched for the `PATH` lines
+const SHELL_STARTUP_FILES: [&str; 6] = [
+    ".zshrc",
+    ".zprofile",
+    ".bash_profile",
+    ".bashrc",
+    ".profile",
+    ".config/fish/config.fish",
+];
+
+/// Remove the installation `config` points at, or list what would go
+///
+/// `home` is where the launchd agent and shell startup files are looked
+/// for. With `keep_store` the store survives, so a later installation can
+/// reuse its packages. Under an alternate root only what lies inside it is
+/// touched.
+///
+/// Failures are recorded per removal rather than returned, so one stuck
+/// path does not leave everything else behind.
+pub async fn self_destruct(
+    config: &Config,
+    home: Option<&Path>,
+    keep_store: bool,
+    dry_run: bool,
+) -> SelfDestructReport {
+    let mut targets = Vec::new();
+    if let Some(home) = home {
+        if config.paths.root.is_none() {
+            let agent = home
+                .join("Library/LaunchAgents")
+                .join(format!("{LAUNCHD_LABEL}.plist"));
+            if fs::symlink_metadata(&agent).await.is_ok() {
+                if !dry_run {
+                    unload_agent(&agent).await;
+                }
+                targets.push(("launchd agent".to_string(), agent));
+            }
+        }
+    }
+    for (label, plist) in service_plists(config).await {
+        if !dry_run {
+            unload_agent(&plist).await;
+        }
+        targets.push((format!("service {label}"), plist));
+    }
+    targets.extend(cache_targets(config).await);
+    targets.extend(installation_targets(config, keep_store).await);
+
+    let mut removals = Vec::with_capacity(targets.len());
+    for (what, path) in targets {
+        let location = Location::Files(vec![path.clone()]);
+        let result = if dry_run {
+            cache::size(&location).await
+        } else {
+            cache::remove(&path).await
+        };
+        removals.push(removal(what, path, result));
+    }
+    if let Some(home) = home {
+        removals.extend(remove_path_lines(&config.bin_path(), home, dry_run).await);
+    }
+
+    SelfDestructReport {
+        dry_run,
+        kept_store: keep_store.then(|| config.store_path()),
+        removals,
+    }
+}
+
+fn removal(what: String, path: PathBuf, result: Result<u64, sps2_errors::Error>) -> Removal {
+    match result {
+        Ok(bytes) => Removal {
+            what,
+            path,
+            bytes,
+            error: None,
+        },
+        Err(e) => Removal {
+            what,
+            path,
+            bytes: 0,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Build caches outside the prefix; the others go with it
+async fn cache_targets(config: &Config) -> Vec<(String, PathBuf)> {
+    let prefix = config.prefix_path();
+    let mut targets = Vec::new();
+    for kind in [CacheKind::BuildSources, CacheKind::BuildArtifacts] {
+        let Ok(Location::Contents(dir)) = cache::location(config, kind) else {
+            continue;
+        };
+        let outside_root = config
+            .paths
+            .root
+            .as_ref()
+            .is_some_and(|root| !dir.starts_with(root));
+        if dir.starts_with(&prefix) || outside_root {
+            continue;
+        }
+        if fs::symlink_metadata(&dir).await.is_ok() {
+            targets.push((format!("{kind} cache"), dir));
+        }
+    }
+    targets
+}
+
+/// The prefix, or its entries other than the store with `keep_store`, plus
+/// a store or states directory configured outside it
+async fn installation_targets(config: &Config, keep_store: bool) -> Vec<(String, PathBuf)> {
+    let prefix = config.prefix_path();
+    let store = config.store_path();
+    let mut targets = Vec::new();
+
+    if keep_store {
+        if let Ok(mut entries) = fs::read_dir(&prefix).await {
+            let mut paths = Vec::new();
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                paths.push(entry.path());
+            }
+            paths.sort();
+            for path in paths {
+                if !store.starts_with(&path) {
+                    let name = path
+                        .file_name()
+                        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                    targets.push((name, path));
+                }
+            }
+        }
+    } else if fs::symlink_metadata(&prefix).await.is_ok() {
+        targets.push(("prefix".to_string(), prefix.clone()));
+    }
+
+    let states = config.state_path();
+    if !states.starts_with(&prefix) && fs::symlink_metadata(&states).await.is_ok() {
+        targets.push(("states".to_string(), states));
+    }
+    if !keep_store && !store.starts_with(&prefix) && fs::symlink_metadata(&store).await.is_ok() {
+        targets.push(("store".to_string(), store));
+    }
+    targets
+}
+
+/// Plists of the services recorded in the state database that are still
+/// on disk, by label
+///
+/// An unreadable database yields none, since the plists cannot be found
+/// without it.
+async fn service_plists(config: &Config) -> Vec<(String, PathBuf)> {
+    let db_path = config.db_path();
+    if fs::symlink_metadata(&db_path).await.is_err() {
+        return Vec::new();
+    }
+    let Ok(pool) = sps2_state::create_pool(&db_path).await else {
+        return Vec::new();
+    };
+    let records = match pool.begin().await {
+        Ok(mut tx) => sps2_state::queries::list_services(&mut tx)
+            .await
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    };
+    pool.close().await;
+
+    let mut plists = Vec::new();
+    for record in records {
+        let plist = PathBuf::from(record.plist_path);
+        let outside_root = config
+            .paths
+            .root
+            .as_ref()
+            .is_some_and(|root| !plist.starts_with(root));
+        if !outside_root && fs::symlink_metadata(&plist).await.is_ok() {
+            plists.push((record.label, plist));
+        }
+    }
+    plists
+}
+
+/// Stop an agent or service before its plist disappears; failing to is
+/// harmless, as launchd drops services whose plist is gone at the next
+/// login or boot
+async fn unload_agent(plist: &Path) {
+    if cfg!(target_os = "macos") {
+        let _ = tokio::process::Command::new("launchctl")
+            .arg("unload")
+            .arg(plist)
+            .output()
+            .await;
+    }
+}
+
+/// Drop the lines sps2 suggests for putting `bin` on `PATH` from the shell
+/// startup files in `home`, leaving every other line as it was
+///
+/// Those are the `export PATH` line and the `eval "$(sps2 env)"` line, or
+/// its fish equivalent, naming `sps2` either bare or by its path in `bin`.
+async fn remove_path_lines(bin: &Path, home: &Path, dry_run: bool) -> Vec<Removal> {
+    let mut lines = vec![format!("export PATH=\"{}:$PATH\"", bin.display())];
+    for sps2 in ["sps2".to_string(), bin.join("sps2").display().to_string()] {
+        lines.push(format!("eval \"$({sps2} env)\""));
+        lines.push(format!("{sps2} env --shell fish | source"));
+    }
+    let mut removals = Vec::new();
+    for name in SHELL_STARTUP_FILES {
+        let path = home.join(name);
+        let Ok(contents) = fs::read_to_string(&path).await else {
+            continue;
+        };
+        let kept: Vec<&str> = contents
+            .split_inclusive('\n')
+            .filter(|l| !lines.iter().any(|line| l.trim() == line))
+            .collect();
+        let removed_bytes = contents.len() - kept.iter().map(|l| l.len()).sum::<usize>();
+        if removed_bytes == 0 {
+            continue;
+        }
+
+        let result = if dry_run {
+            Ok(())
+        } else {
+            fs::write(&path, kept.concat()).await
+        };
+        removals.push(removal(
+            "PATH line".to_string(),
+            path,
+            result.map(|()| removed_bytes as u64).map_err(Into::into),
+        ));
+    }
+    removals
+}
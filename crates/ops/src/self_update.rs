@@ -27,7 +27,7 @@ pub async fn self_update(ctx: &OpsCtx, skip_verify: bool, force: bool) -> Result
 
     let result: Result<(String, String, String), Error> = async {
         // Check latest version from GitHub API
-        let latest_version = get_latest_version(&ctx.net, &ctx.tx).await?;
+        let latest_version = get_latest_version(ctx.net()?, &ctx.tx).await?;
 
         // Compare versions
         let current = sps2_types::Version::parse(&current_version)?;
@@ -56,7 +56,7 @@ pub async fn self_update(ctx: &OpsCtx, skip_verify: bool, force: bool) -> Result
         let temp_signature = temp_dir.path().join("sps2-new.minisig");
 
         // Download new binary
-        sps2_net::download_file(&ctx.net, &binary_url, &temp_binary, None, &ctx.tx)
+        sps2_net::download_file(ctx.net()?, &binary_url, &temp_binary, None, &ctx.tx)
             .await
             .map_err(|e| OpsError::SelfUpdateFailed {
                 message: format!("Failed to download binary: {e}"),
@@ -64,7 +64,7 @@ pub async fn self_update(ctx: &OpsCtx, skip_verify: bool, force: bool) -> Result
 
         if !skip_verify {
             // Download signature
-            sps2_net::download_file(&ctx.net, &signature_url, &temp_signature, None, &ctx.tx)
+            sps2_net::download_file(ctx.net()?, &signature_url, &temp_signature, None, &ctx.tx)
                 .await
                 .map_err(|e| OpsError::SelfUpdateFailed {
                     message: format!("Failed to download signature: {e}"),
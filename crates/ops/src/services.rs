@@ -0,0 +1,503 @@
+//! Launchd services shipped by packages
+//!
+//! A package provides a service by shipping a launchd plist template at
+//! `share/sps2/services/<label>.plist`, in which `${PREFIX}` stands for the
+//! live prefix. After every transition the templates of the active state
+//! are rendered into `~/Library/LaunchAgents`, or `/Library/LaunchDaemons`
+//! when running as root, as `<Label>.plist` after the plist's `Label` key,
+//! and recorded in the state database with the package they belong to. A
+//! plist sps2 did not write is never overwritten. Services whose package is
+//! gone are stopped and their plists removed.
+//!
+//! A rendered service stays disabled until `sps2 services enable` loads it
+//! and lets launchd start it at login or boot; `start` runs it for the
+//! current session only.
+
+use crate::types::ServiceStatus;
+use crate::OpsCtx;
+use sps2_errors::{Error, OpsError};
+use sps2_events::{AppEvent, EventEmitter, GeneralEvent};
+use sps2_state::{queries, ServiceRecord};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Directory, relative to the live prefix, packages ship templates in
+const TEMPLATE_DIR: &str = "share/sps2/services/";
+
+/// Placeholder for the live prefix in templates
+const PREFIX_PLACEHOLDER: &str = "${PREFIX}";
+
+/// The launchd domain this process manages services in
+struct Domain {
+    /// Domain target for launchctl, `gui/<uid>` or `system`
+    target: String,
+    /// Directory rendered plists are written to
+    dir: PathBuf,
+}
+
+impl Domain {
+    /// The system domain as root, the user's GUI domain otherwise
+    ///
+    /// Returns `None` under an alternate root, whose services launchd
+    /// cannot run, and for a user without a home directory.
+    fn current(ctx: &OpsCtx) -> Option<Self> {
+        if ctx.config.paths.root.is_some() {
+            return None;
+        }
+        let uid = sps2_platform::host::user_id();
+        if uid == 0 {
+            return Some(Self {
+                target: "system".to_string(),
+                dir: PathBuf::from("/Library/LaunchDaemons"),
+            });
+        }
+        let home = std::env::var_os("HOME")?;
+        Some(Self {
+            target: format!("gui/{uid}"),
+            dir: Path::new(&home).join("Library/LaunchAgents"),
+        })
+    }
+
+    fn service(&self, label: &str) -> String {
+        format!("{}/{label}", self.target)
+    }
+}
+
+/// A service template an installed package ships, rendered for the live
+/// prefix
+struct Template {
+    label: String,
+    package: String,
+    rendered: String,
+}
+
+/// List the services installed packages provide, with whether they run
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read.
+pub async fn services_list(ctx: &OpsCtx) -> Result<Vec<ServiceStatus>, Error> {
+    let mut services = Vec::new();
+    for record in ctx.state.list_services().await? {
+        let (loaded, pid) = status(&format!("{}/{}", record.domain, record.label)).await;
+        services.push(ServiceStatus {
+            label: record.label,
+            package: record.package_name,
+            domain: record.domain,
+            plist_path: PathBuf::from(record.plist_path),
+            enabled: record.enabled,
+            loaded,
+            pid,
+        });
+    }
+    Ok(services)
+}
+
+/// Start a service for the current session, without enabling it
+///
+/// # Errors
+///
+/// Returns an error if no installed package provides the service or
+/// launchctl fails.
+pub async fn services_start(ctx: &OpsCtx, label: &str) -> Result<String, Error> {
+    let (domain, record) = find(ctx, label).await?;
+    if ctx.check_mode {
+        return Ok(format!("Would start {label}"));
+    }
+    let service = domain.service(label);
+    if !status(&service).await.0 {
+        bootstrap_once(&domain, &record).await?;
+    }
+    launchctl("kickstart", label, &[&service]).await?;
+    Ok(format!("Started {label}"))
+}
+
+/// Stop a service; an enabled one starts again at the next login or boot
+///
+/// # Errors
+///
+/// Returns an error if no installed package provides the service or
+/// launchctl fails.
+pub async fn services_stop(ctx: &OpsCtx, label: &str) -> Result<String, Error> {
+    let (domain, _) = find(ctx, label).await?;
+    let service = domain.service(label);
+    if !status(&service).await.0 {
+        return Ok(format!("{label} is not running"));
+    }
+    if ctx.check_mode {
+        return Ok(format!("Would stop {label}"));
+    }
+    launchctl("bootout", label, &[&service]).await?;
+    Ok(format!("Stopped {label}"))
+}
+
+/// Restart a service, or start it if it is not loaded
+///
+/// # Errors
+///
+/// Returns an error if no installed package provides the service or
+/// launchctl fails.
+pub async fn services_restart(ctx: &OpsCtx, label: &str) -> Result<String, Error> {
+    let (domain, _) = find(ctx, label).await?;
+    let service = domain.service(label);
+    if !status(&service).await.0 {
+        return services_start(ctx, label).await;
+    }
+    if ctx.check_mode {
+        return Ok(format!("Would restart {label}"));
+    }
+    launchctl("kickstart", label, &["-k", &service]).await?;
+    Ok(format!("Restarted {label}"))
+}
+
+/// Load a service now and at every login or boot
+///
+/// # Errors
+///
+/// Returns an error if no installed package provides the service, launchctl
+/// fails, or the state database cannot be updated.
+pub async fn services_enable(ctx: &OpsCtx, label: &str) -> Result<String, Error> {
+    let (domain, record) = find(ctx, label).await?;
+    if ctx.check_mode {
+        return Ok(format!("Would enable {label}"));
+    }
+    let service = domain.service(label);
+    launchctl("enable", label, &[&service]).await?;
+    if !status(&service).await.0 {
+        launchctl("bootstrap", label, &[&domain.target, &record.plist_path]).await?;
+    }
+    ctx.state
+        .set_service_enabled(label, &domain.target, true)
+        .await?;
+    let when = if domain.target == "system" {
+        "boot"
+    } else {
+        "login"
+    };
+    Ok(format!("Enabled {label}; it starts at every {when}"))
+}
+
+/// Stop a service and keep it from loading at login or boot
+///
+/// # Errors
+///
+/// Returns an error if no installed package provides the service, launchctl
+/// fails, or the state database cannot be updated.
+pub async fn services_disable(ctx: &OpsCtx, label: &str) -> Result<String, Error> {
+    let (domain, _) = find(ctx, label).await?;
+    if ctx.check_mode {
+        return Ok(format!("Would disable {label}"));
+    }
+    let service = domain.service(label);
+    if status(&service).await.0 {
+        launchctl("bootout", label, &[&service]).await?;
+    }
+    launchctl("disable", label, &[&service]).await?;
+    ctx.state
+        .set_service_enabled(label, &domain.target, false)
+        .await?;
+    Ok(format!("Disabled {label}"))
+}
+
+/// Render the service templates of the active state and remove the
+/// services of packages that are gone
+///
+/// Runs after a transition has committed, so failures are reported as
+/// warnings rather than failing the operation.
+pub(crate) async fn sync_services(ctx: &OpsCtx) {
+    let Some(domain) = Domain::current(ctx) else {
+        return;
+    };
+    if let Err(e) = sync(ctx, &domain).await {
+        ctx.emit(AppEvent::General(GeneralEvent::warning_with_context(
+            format!(
+                "Failed to update launchd services in {}",
+                domain.dir.display()
+            ),
+            e.to_string(),
+        )));
+    }
+}
+
+async fn sync(ctx: &OpsCtx, domain: &Domain) -> Result<(), Error> {
+    let templates = templates(ctx).await?;
+    let records: Vec<ServiceRecord> = ctx
+        .state
+        .list_services()
+        .await?
+        .into_iter()
+        .filter(|record| record.domain == domain.target)
+        .collect();
+
+    for record in &records {
+        if templates.iter().any(|t| t.label == record.label) {
+            continue;
+        }
+        // Failing to stop is harmless: launchd drops services whose plist
+        // is gone at the next login or boot
+        launchctl("bootout", &record.label, &[&domain.service(&record.label)])
+            .await
+            .ok();
+        match fs::remove_file(&record.plist_path).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        ctx.state
+            .remove_service(&record.label, &domain.target)
+            .await?;
+    }
+
+    for template in templates {
+        let plist = domain.dir.join(format!("{}.plist", template.label));
+        let record = records.iter().find(|r| r.label == template.label);
+        if record.is_none() && fs::symlink_metadata(&plist).await.is_ok() {
+            ctx.emit_warning(format!(
+                "{} already exists and was not written by sps2; the {} service of {} is not installed",
+                plist.display(),
+                template.label,
+                template.package
+            ));
+            continue;
+        }
+        let unchanged = record.is_some_and(|r| r.package_name == template.package)
+            && fs::read_to_string(&plist)
+                .await
+                .is_ok_and(|current| current == template.rendered);
+        if unchanged {
+            continue;
+        }
+
+        fs::create_dir_all(&domain.dir).await?;
+        fs::write(&plist, template.rendered).await?;
+        let plist = plist.display().to_string();
+        let service = domain.service(&template.label);
+        if record.is_none() {
+            // launchd loads every plist in the directory at the next login
+            // or boot unless the service is disabled
+            launchctl("disable", &template.label, &[&service])
+                .await
+                .ok();
+        } else if status(&service).await.0 {
+            // Reload so launchd runs the upgraded plist
+            launchctl("bootout", &template.label, &[&service])
+                .await
+                .ok();
+            launchctl("bootstrap", &template.label, &[&domain.target, &plist]).await?;
+        }
+        ctx.state
+            .record_service(&ServiceRecord {
+                label: template.label,
+                domain: domain.target.clone(),
+                package_name: template.package,
+                plist_path: plist,
+                enabled: record.is_some_and(|r| r.enabled),
+                rendered_at: chrono::Utc::now().timestamp(),
+            })
+            .await?;
+    }
+    Ok(())
+}
+
+/// Service templates shipped by the packages of the active state
+///
+/// A template without a usable `Label`, or with the label of an earlier
+/// one, is skipped with a warning.
+async fn templates(ctx: &OpsCtx) -> Result<Vec<Template>, Error> {
+    let state_id = ctx.state.get_active_state().await?;
+    let mut tx = ctx.state.begin_transaction().await?;
+    let owners = queries::get_file_owners(&mut tx, &state_id, TEMPLATE_DIR).await?;
+    tx.commit().await?;
+
+    let live = ctx.state.live_path();
+    let mut templates: Vec<Template> = Vec::new();
+    for owner in owners {
+        if !is_template(&owner.path) {
+            continue;
+        }
+        let rendered = render(&fs::read_to_string(live.join(&owner.path)).await?, live);
+        let Some(label) = plist_label(&rendered) else {
+            ctx.emit_warning(format!(
+                "{} of {} has no valid Label; it is not installed as a service",
+                owner.path, owner.package
+            ));
+            continue;
+        };
+        if let Some(first) = templates.iter().find(|t| t.label == label) {
+            ctx.emit_warning(format!(
+                "{} of {} uses the label {label} of a service from {}; it is not installed",
+                owner.path, owner.package, first.package
+            ));
+            continue;
+        }
+        templates.push(Template {
+            label,
+            package: owner.package,
+            rendered,
+        });
+    }
+    Ok(templates)
+}
+
+/// Whether `rel_path` is a `.plist` file directly in the template directory
+fn is_template(rel_path: &str) -> bool {
+    rel_path
+        .strip_prefix(TEMPLATE_DIR)
+        .and_then(|name| name.strip_suffix(".plist"))
+        .is_some_and(|stem| !stem.is_empty() && !stem.contains('/'))
+}
+
+/// The `Label` of an XML plist, if it can name the rendered plist file
+fn plist_label(plist: &str) -> Option<String> {
+    let value = plist::Value::from_reader_xml(plist.as_bytes()).ok()?;
+    let label = value.as_dictionary()?.get("Label")?.as_string()?;
+    let usable = !label.is_empty() && !label.starts_with('.') && !label.contains('/');
+    usable.then(|| label.to_string())
+}
+
+/// `template` with the placeholder replaced by `prefix`
+fn render(template: &str, prefix: &Path) -> String {
+    template.replace(PREFIX_PLACEHOLDER, &prefix.display().to_string())
+}
+
+/// The rendered service with `label` in the current domain
+async fn find(ctx: &OpsCtx, label: &str) -> Result<(Domain, ServiceRecord), Error> {
+    let not_found = || OpsError::ServiceNotFound {
+        label: label.to_string(),
+    };
+    let domain = Domain::current(ctx).ok_or_else(not_found)?;
+    let record = ctx
+        .state
+        .list_services()
+        .await?
+        .into_iter()
+        .find(|record| record.label == label && record.domain == domain.target)
+        .ok_or_else(not_found)?;
+    Ok((domain, record))
+}
+
+/// Load a service that is not enabled, leaving it disabled for the next
+/// login or boot; a disabled service cannot be loaded
+async fn bootstrap_once(domain: &Domain, record: &ServiceRecord) -> Result<(), Error> {
+    let service = domain.service(&record.label);
+    if !record.enabled {
+        launchctl("enable", &record.label, &[&service]).await?;
+    }
+    let result = launchctl(
+        "bootstrap",
+        &record.label,
+        &[&domain.target, &record.plist_path],
+    )
+    .await;
+    // Disabling a loaded service leaves it running
+    if !record.enabled {
+        launchctl("disable", &record.label, &[&service]).await.ok();
+    }
+    result.map(|_| ())
+}
+
+/// Whether launchd has `service` loaded, and its process ID while it runs
+async fn status(service: &str) -> (bool, Option<u32>) {
+    match launchctl("print", service, &[service]).await {
+        Ok(output) => (true, running_pid(&output)),
+        Err(_) => (false, None),
+    }
+}
+
+/// Process ID in the output of `launchctl print`
+fn running_pid(print: &str) -> Option<u32> {
+    print
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("pid = ")?.parse().ok())
+}
+
+/// Run `launchctl <action> <args>`, returning its output
+async fn launchctl(action: &str, label: &str, args: &[&str]) -> Result<String, Error> {
+    let failed = |message: String| OpsError::ServiceCommandFailed {
+        action: action.to_string(),
+        label: label.to_string(),
+        message,
+    };
+    if !cfg!(target_os = "macos") {
+        return Err(failed("launchd is only available on macOS".to_string()).into());
+    }
+    let output = tokio::process::Command::new("launchctl")
+        .arg(action)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| failed(e.to_string()))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = if stderr.trim().is_empty() {
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    } else {
+        stderr.trim().to_string()
+    };
+    Err(failed(message).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn templates_are_plists_directly_in_the_template_directory() {
+        assert!(is_template(
+            "share/sps2/services/org.postgresql.server.plist"
+        ));
+        assert!(!is_template("share/sps2/services/extra/a.plist"));
+        assert!(!is_template("share/sps2/services/README"));
+        assert!(!is_template("share/sps2/services/.plist"));
+        assert!(!is_template("etc/org.nginx.plist"));
+    }
+
+    #[test]
+    fn the_label_comes_from_the_plist() {
+        let plist = |label: &str| {
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <plist version=\"1.0\"><dict>\
+                 <key>Label</key><string>{label}</string>\
+                 <key>RunAtLoad</key><true/>\
+                 </dict></plist>"
+            )
+        };
+        assert_eq!(
+            plist_label(&plist("org.redis.server")).as_deref(),
+            Some("org.redis.server")
+        );
+        assert_eq!(plist_label(&plist("../evil")), None);
+        assert_eq!(plist_label(&plist("")), None);
+        assert_eq!(
+            plist_label("<plist version=\"1.0\"><dict></dict></plist>"),
+            None
+        );
+        assert_eq!(plist_label("not a plist"), None);
+    }
+
+    #[test]
+    fn rendering_points_templates_at_the_live_prefix() {
+        let template = "<string>${PREFIX}/bin/redis-server</string>\n\
+                        <string>${PREFIX}/etc/redis.conf</string>";
+        assert_eq!(
+            render(template, Path::new("/opt/pm/live")),
+            "<string>/opt/pm/live/bin/redis-server</string>\n\
+             <string>/opt/pm/live/etc/redis.conf</string>"
+        );
+    }
+
+    #[test]
+    fn the_pid_is_read_from_launchctl_print() {
+        let print = "gui/501/org.redis = {\n\tactive count = 1\n\tpath = /x.plist\n\
+                     \tstate = running\n\n\tprogram = /opt/pm/live/bin/redis-server\n\
+                     \tpid = 4711\n}\n";
+        assert_eq!(running_pid(print), Some(4711));
+        assert_eq!(
+            running_pid("gui/501/org.redis = {\n\tstate = not running\n}"),
+            None
+        );
+    }
+}
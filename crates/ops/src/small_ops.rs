@@ -13,7 +13,11 @@ use crate::self_update as self_update_module;
 
 // Re-export all public functions to maintain API compatibility
 pub use health::check_health;
-pub use maintenance::{cleanup, history, rollback};
-pub use query::{list_packages, package_info, search_packages};
-pub use repository::{add_repo, list_repos, remove_repo, reposync};
+pub use maintenance::{
+    cleanup, cleanup_downloads, cleanup_quarantine, history, history_detail, rollback,
+};
+pub use query::{
+    list_packages, package_files, package_info, search_packages, search_packages_remote,
+};
+pub use repository::{add_repo, list_repos, remove_repo, reposync, KeyChangePolicy};
 pub use self_update_module::self_update;
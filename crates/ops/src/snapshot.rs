@@ -0,0 +1,165 @@
+//! Named states
+//!
+//! A snapshot is a name given to an existing state, such as
+//! `before-llvm-upgrade`. Named states are kept by cleanup and can be passed
+//! to [`rollback`](crate::rollback) through [`resolve_state`] in place of
+//! their ID. Deleting a snapshot removes the name, not the state.
+
+use crate::OpsCtx;
+use sps2_errors::{Error, OpsError, StateError};
+use sps2_types::StateId;
+use uuid::Uuid;
+
+/// Longest accepted snapshot name
+const MAX_NAME_LEN: usize = 64;
+
+/// Name `state`, or the current state when `None`
+///
+/// # Errors
+///
+/// Returns an error if the name is invalid or taken, the state does not
+/// exist, or the state database cannot be updated.
+pub async fn snapshot_create(
+    ctx: &OpsCtx,
+    name: &str,
+    state: Option<StateId>,
+) -> Result<String, Error> {
+    validate_name(name)?;
+    let state_id = match state {
+        Some(id) => id,
+        None => ctx.state.get_current_state_id().await?,
+    };
+    if !ctx.state.state_exists(&state_id).await? {
+        return Err(OpsError::StateNotFound { state_id }.into());
+    }
+    if let Some(existing) = ctx.state.get_tagged_state(name).await? {
+        return Err(StateError::TagExists {
+            name: name.to_string(),
+            state_id: existing.to_string(),
+        }
+        .into());
+    }
+
+    if ctx.check_mode {
+        return Ok(format!("Would create snapshot {name} of state {state_id}"));
+    }
+    ctx.state.tag_state(&state_id, name).await?;
+    Ok(format!("Created snapshot {name} of state {state_id}"))
+}
+
+/// List snapshots, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the state database cannot be read.
+pub async fn snapshot_list(ctx: &OpsCtx) -> Result<String, Error> {
+    let tags = ctx.state.list_state_tags().await?;
+    if tags.is_empty() {
+        return Ok("No snapshots".to_string());
+    }
+
+    let current = ctx.state.get_current_state_id().await?;
+    let width = tags.iter().map(|tag| tag.name.len()).max().unwrap_or(0);
+    let lines: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            let created = chrono::DateTime::from_timestamp(tag.created_at, 0)
+                .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+            let marker = if tag.state_id() == current {
+                "  (current)"
+            } else {
+                ""
+            };
+            format!("{:<width$}  {}  {created}{marker}", tag.name, tag.state_id)
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+/// Remove a snapshot name; the state itself is kept until cleanup prunes it
+///
+/// # Errors
+///
+/// Returns an error if no snapshot has this name or the state database
+/// cannot be updated.
+pub async fn snapshot_delete(ctx: &OpsCtx, name: &str) -> Result<String, Error> {
+    let Some(state_id) = ctx.state.get_tagged_state(name).await? else {
+        return Err(StateError::TagNotFound {
+            name: name.to_string(),
+        }
+        .into());
+    };
+
+    if ctx.check_mode {
+        return Ok(format!("Would delete snapshot {name} of state {state_id}"));
+    }
+    ctx.state.untag_state(name).await?;
+    Ok(format!("Deleted snapshot {name} of state {state_id}"))
+}
+
+/// State a rollback target refers to: a state ID or a snapshot name
+///
+/// # Errors
+///
+/// Returns an error if `target` is neither a state ID nor a snapshot name.
+pub async fn resolve_state(ctx: &OpsCtx, target: &str) -> Result<StateId, Error> {
+    if let Ok(id) = Uuid::parse_str(target) {
+        return Ok(id);
+    }
+    ctx.state.get_tagged_state(target).await?.ok_or_else(|| {
+        StateError::TagNotFound {
+            name: target.to_string(),
+        }
+        .into()
+    })
+}
+
+/// Snapshot names share the rollback argument with state IDs, so they must
+/// not parse as one
+fn validate_name(name: &str) -> Result<(), StateError> {
+    let reason = if name.is_empty() {
+        Some("name is empty".to_string())
+    } else if name.len() > MAX_NAME_LEN {
+        Some(format!("longer than {MAX_NAME_LEN} characters"))
+    } else if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        Some("must start with a letter or digit".to_string())
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+    {
+        Some(format!("contains {c:?}"))
+    } else if Uuid::parse_str(name).is_ok() {
+        Some("looks like a state ID".to_string())
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => Err(StateError::InvalidTagName {
+            name: name.to_string(),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_restricted_to_a_safe_charset() {
+        assert!(validate_name("before-llvm-upgrade").is_ok());
+        assert!(validate_name("v1.2_rc").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("-leading-dash").is_err());
+        assert!(validate_name("has space").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+
+    #[test]
+    fn names_cannot_shadow_state_ids() {
+        let id = Uuid::new_v4().to_string();
+        assert!(validate_name(&id).is_err());
+    }
+}
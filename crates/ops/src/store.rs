@@ -0,0 +1,263 @@
+//! File store statistics, relocation and compaction
+//!
+//! Totals come from the state database, which records every file of every
+//! package version; the objects directory is scanned only to find objects the
+//! database has lost track of.
+
+use crate::{OpsCtx, PackageUsage, StoreStats};
+use sps2_config::Config;
+use sps2_errors::{Error, StorageError};
+use sps2_hash::Hash;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Deduplication statistics and the `top` largest package versions
+///
+/// # Errors
+///
+/// Returns an error if the state database or the objects directory cannot be
+/// read.
+pub async fn store_stats(ctx: &OpsCtx, top: usize) -> Result<StoreStats, Error> {
+    let stats = ctx.state.file_storage_stats().await?;
+    let largest = ctx.state.largest_packages(top).await?;
+    let tracked = ctx.state.file_object_hashes().await?;
+    let on_disk = ctx.store.file_store().stats(&tracked).await?;
+
+    Ok(StoreStats {
+        file_references: count(stats.total_files),
+        logical_bytes: count(stats.total_size),
+        unique_objects: count(stats.unique_files),
+        physical_bytes: count(stats.deduplicated_size),
+        dedup_ratio: stats.deduplication_ratio,
+        orphaned_objects: count(stats.orphaned_files),
+        orphaned_bytes: count(stats.orphaned_size),
+        untracked_objects: on_disk.untracked_objects,
+        untracked_bytes: on_disk.untracked_bytes,
+        largest_packages: largest
+            .into_iter()
+            .map(|usage| PackageUsage {
+                name: usage.name,
+                version: usage.version,
+                files: count(usage.files),
+                bytes: count(usage.total_size),
+                unique_bytes: count(usage.unique_size),
+            })
+            .collect(),
+    })
+}
+
+/// Move the store to `new_path` and point the configuration at it
+///
+/// The installation stays locked throughout, so no other operation adds to
+/// the store while it is copied. The store is cloned (same volume) or copied
+/// (another volume) into a staging directory beside `new_path`, verified,
+/// and renamed into place. Only then is `paths.store_path` rewritten in the
+/// configuration file in use; if that fails the copy is removed again, so a
+/// failed or interrupted relocation leaves the old store in use. The old
+/// store is removed, once the configuration points away from it, unless
+/// `keep_old`.
+///
+/// # Errors
+///
+/// Returns an error if `new_path` is relative, overlaps the current store or
+/// is a non-empty directory, if the configuration file cannot be read, if
+/// the copy fails verification, or if the configuration cannot be saved.
+pub async fn store_relocate(
+    ctx: &OpsCtx,
+    new_path: &Path,
+    keep_old: bool,
+) -> Result<String, Error> {
+    let old_path = ctx.store.base_path();
+    check_target(old_path, new_path).await?;
+
+    if ctx.check_mode {
+        return Ok(format!(
+            "Would move the store from {} to {}",
+            old_path.display(),
+            new_path.display()
+        ));
+    }
+
+    let _lock = ctx.state.lock_install().await?;
+    // Read the configuration before moving anything, so a broken file
+    // stops the relocation while the old store is still the only one
+    let config_path = ctx.config.file_path()?;
+    let mut config = Config::load_or_create(&config_path).await?;
+
+    let staging = staging_path(new_path);
+    if fs::symlink_metadata(&staging).await.is_ok() {
+        // Left behind by an interrupted relocation; the config never pointed here
+        fs::remove_dir_all(&staging).await?;
+    }
+    let copy = match ctx.store.copy_to(&staging).await {
+        Ok(copy) => copy,
+        Err(e) => {
+            let _ = fs::remove_dir_all(&staging).await;
+            return Err(e);
+        }
+    };
+    let mut renamed = false;
+    let moved = async {
+        if fs::symlink_metadata(new_path).await.is_ok() {
+            fs::remove_dir(new_path).await?;
+        }
+        fs::rename(&staging, new_path).await?;
+        renamed = true;
+        config.paths.store_path = Some(new_path.to_path_buf());
+        config.save_to(&config_path).await
+    }
+    .await;
+    if let Err(e) = moved {
+        let copy = if renamed { new_path } else { &staging };
+        let _ = fs::remove_dir_all(copy).await;
+        return Err(e);
+    }
+
+    let method = if copy.cloned { "cloned" } else { "copied" };
+    let mut message = format!(
+        "Moved the store from {} to {} ({} files, {} bytes {method})",
+        old_path.display(),
+        new_path.display(),
+        copy.files,
+        copy.bytes
+    );
+    if keep_old {
+        let _ = write!(message, "\nKept the old store at {}", old_path.display());
+    } else if let Err(e) = fs::remove_dir_all(old_path).await {
+        // The move itself succeeded; a leftover old store only costs space
+        let _ = write!(
+            message,
+            "\nCould not remove the old store at {}: {e}",
+            old_path.display()
+        );
+    }
+    Ok(message)
+}
+
+/// Remove objects stored twice under hashes of different algorithms
+///
+/// Each duplicate's references are merged into the object kept in the state
+/// database first, then the package file lists are rewritten and the
+/// duplicates removed. Each step can be repeated, so an interrupted
+/// compaction finishes on the next run.
+///
+/// # Errors
+///
+/// Returns an error if the objects cannot be read or hashed, or the state
+/// database, a package file list or an object cannot be updated.
+pub async fn store_compact(ctx: &OpsCtx) -> Result<String, Error> {
+    // Keep installs and garbage collection from referencing objects while
+    // they are merged and removed
+    let _lock = ctx.state.lock_install().await?;
+    let groups = ctx.store.find_duplicate_objects().await?;
+    let duplicates: Vec<Hash> = groups
+        .iter()
+        .flat_map(|group| group.duplicates.iter().cloned())
+        .collect();
+    if duplicates.is_empty() {
+        return Ok("No duplicate objects in the store".to_string());
+    }
+    let bytes: u64 = groups
+        .iter()
+        .map(|group| group.size * group.duplicates.len() as u64)
+        .sum();
+
+    if ctx.check_mode {
+        return Ok(format!(
+            "Would remove {} duplicate objects ({})",
+            duplicates.len(),
+            megabytes(bytes)
+        ));
+    }
+
+    let merges: Vec<(String, String)> = groups
+        .iter()
+        .flat_map(|group| {
+            group
+                .duplicates
+                .iter()
+                .map(|duplicate| (duplicate.to_hex(), group.keep.to_hex()))
+        })
+        .collect();
+    let repointed = ctx.state.merge_file_objects(&merges).await?;
+    let packages = ctx.store.rewrite_file_hashes(&groups).await?;
+    let removed = ctx.store.remove_objects(&duplicates).await?;
+
+    let mut message = format!(
+        "Removed {} duplicate objects and recovered {} ({repointed} file records and {packages} package file lists updated)",
+        removed.objects,
+        megabytes(removed.freed_bytes)
+    );
+    if removed.linked_objects > 0 {
+        let _ = write!(
+            message,
+            "\n{} removed objects are still hard-linked into a prefix; their space is freed when those files are replaced",
+            removed.linked_objects
+        );
+    }
+    Ok(message)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+/// Reject targets the store cannot be moved to
+async fn check_target(old_path: &Path, new_path: &Path) -> Result<(), Error> {
+    let invalid = || StorageError::InvalidPath {
+        path: new_path.display().to_string(),
+    };
+    if !new_path.is_absolute() {
+        return Err(invalid().into());
+    }
+
+    let old_path = fs::canonicalize(old_path).await?;
+    let target = canonical_target(new_path).await;
+    if target.starts_with(&old_path) || old_path.starts_with(&target) {
+        return Err(invalid().into());
+    }
+
+    match fs::symlink_metadata(new_path).await {
+        Err(_) => Ok(()),
+        Ok(metadata) if metadata.is_dir() => {
+            if fs::read_dir(new_path).await?.next_entry().await?.is_none() {
+                Ok(())
+            } else {
+                Err(StorageError::AlreadyExists {
+                    path: new_path.display().to_string(),
+                }
+                .into())
+            }
+        }
+        Ok(_) => Err(StorageError::AlreadyExists {
+            path: new_path.display().to_string(),
+        }
+        .into()),
+    }
+}
+
+/// `path` with its closest existing ancestor resolved through symlinks
+async fn canonical_target(path: &Path) -> PathBuf {
+    for ancestor in path.ancestors() {
+        if let Ok(resolved) = fs::canonicalize(ancestor).await {
+            let rest = path.strip_prefix(ancestor).unwrap_or(Path::new(""));
+            return resolved.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Hidden sibling of `new_path` the store is copied into before the rename
+fn staging_path(new_path: &Path) -> PathBuf {
+    let name = new_path
+        .file_name()
+        .map_or_else(|| "store".into(), |name| name.to_string_lossy());
+    new_path.with_file_name(format!(".{name}.relocating"))
+}
+
+/// Database counts and sizes are never negative
+fn count(value: i64) -> u64 {
+    u64::try_from(value).unwrap_or(0)
+}
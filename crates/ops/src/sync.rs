@@ -0,0 +1,204 @@
+//! Sync command implementation
+//!
+//! A manifest lists the packages a system should have, the same way a
+//! project file does:
+//!
+//! ```toml
+//! packages = ["python>=3.12.0", "jq==1.7.1"]
+//! ```
+//!
+//! [`sync`] installs the listed packages that are missing or whose
+//! installed version the spec does not accept, and removes every other
+//! installed package that the listed ones do not need, all in one state
+//! transition. Listed packages whose installed version fits are left as
+//! they are, so syncing an unchanged manifest again changes nothing.
+
+use crate::{apply, InstallReport, OpsCtx};
+use serde::Deserialize;
+use sps2_errors::{Error, OpsError};
+use sps2_install::ApplyContext;
+use sps2_state::models::Package;
+use sps2_types::PackageSpec;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Packages a system should have installed
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// Package specs, as given to `sps2 install`
+    packages: Vec<String>,
+    /// The parsed `packages`
+    #[serde(skip)]
+    specs: Vec<PackageSpec>,
+}
+
+impl Manifest {
+    /// Read and check a manifest file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be read or parsed, lists no
+    /// packages, a package spec is invalid, or it lists a package twice.
+    pub async fn load(path: &Path) -> Result<Self, Error> {
+        let invalid = |reason: String| OpsError::InvalidManifest {
+            path: path.display().to_string(),
+            reason,
+        };
+        let content = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| invalid(e.to_string()))?;
+        let mut manifest: Self = toml::from_str(&content).map_err(|e| invalid(e.to_string()))?;
+        if manifest.packages.is_empty() {
+            return Err(invalid("packages lists no packages".to_string()).into());
+        }
+        let mut names = HashSet::new();
+        for spec in &manifest.packages {
+            let parsed = PackageSpec::parse(spec)
+                .map_err(|e| invalid(format!("invalid package spec '{spec}': {e}")))?;
+            if !names.insert(parsed.name.clone()) {
+                return Err(invalid(format!("{} is listed more than once", parsed.name)).into());
+            }
+            manifest.specs.push(parsed);
+        }
+        Ok(manifest)
+    }
+
+    /// Names of the listed packages
+    #[must_use]
+    pub fn names(&self) -> Vec<String> {
+        self.specs.iter().map(|spec| spec.name.clone()).collect()
+    }
+}
+
+/// Bring the installed packages in line with a manifest in one state
+/// transition
+///
+/// Installed packages the manifest does not list are removed unless a
+/// package left installed needs them. In check mode the change is only
+/// planned, and a report that changes nothing means the system matches the
+/// manifest.
+///
+/// # Errors
+///
+/// Returns an error if resolution fails, a package cannot be downloaded,
+/// or installing fails. Nothing is changed then.
+pub async fn sync(ctx: &OpsCtx, manifest: &Manifest) -> Result<InstallReport, Error> {
+    let names = manifest.names();
+    let _correlation = ctx.push_correlation_for_packages("sync", &names);
+    let context = sync_context(ctx, manifest).await?;
+    apply::commit(ctx, context, "sync", &names).await
+}
+
+/// Installer context bringing the installed packages in line with
+/// `manifest`
+pub(crate) async fn sync_context(ctx: &OpsCtx, manifest: &Manifest) -> Result<ApplyContext, Error> {
+    let installed = apply::installed_packages(ctx).await?;
+    let (install, prune) = manifest_changes(manifest, &installed)?;
+    Ok(ApplyContext::new()
+        .with_install(install)
+        .with_autoremove(true)
+        .with_prune(prune)
+        .with_event_sender(ctx.tx.clone()))
+}
+
+/// The specs to install and the installed names to prune for `manifest`
+///
+/// Listed packages installed only as dependencies or recommendations are
+/// installed again at the same version, so they count as asked for and are
+/// not removed with what needed them.
+fn manifest_changes(
+    manifest: &Manifest,
+    installed: &HashMap<String, Package>,
+) -> Result<(Vec<PackageSpec>, Vec<String>), Error> {
+    let mut install = Vec::new();
+    for spec in &manifest.specs {
+        match installed.get(&spec.name) {
+            Some(package) if spec.version_spec.matches(&package.version()) => {
+                if package.dependency || package.recommended {
+                    install.push(PackageSpec::parse(&format!(
+                        "{}=={}",
+                        package.name, package.version
+                    ))?);
+                }
+            }
+            _ => install.push(spec.clone()),
+        }
+    }
+    let listed: HashSet<&str> = manifest
+        .specs
+        .iter()
+        .map(|spec| spec.name.as_str())
+        .collect();
+    let mut prune: Vec<String> = installed
+        .keys()
+        .filter(|name| !listed.contains(name.as_str()))
+        .cloned()
+        .collect();
+    prune.sort();
+    Ok((install, prune))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(packages: &[(&str, &str, bool)]) -> HashMap<String, Package> {
+        packages
+            .iter()
+            .map(|(name, version, dependency)| {
+                let package = Package {
+                    id: 0,
+                    state_id: String::new(),
+                    name: (*name).to_string(),
+                    version: (*version).to_string(),
+                    hash: String::new(),
+                    size: 0,
+                    installed_at: 0,
+                    venv_path: None,
+                    recommended: false,
+                    dependency: *dependency,
+                };
+                ((*name).to_string(), package)
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn manifest_changes_install_missing_and_prune_unlisted() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("manifest.toml");
+        std::fs::write(&path, "packages = [\"jq>=1.7.0\", \"curl\", \"ripgrep\"]\n").unwrap();
+        let manifest = Manifest::load(&path).await.unwrap();
+        assert_eq!(manifest.names(), ["jq", "curl", "ripgrep"]);
+
+        let (install, prune) = manifest_changes(
+            &manifest,
+            &installed(&[
+                ("jq", "1.6.0", false),
+                ("curl", "8.0.0", true),
+                ("ripgrep", "14.0.0", false),
+                ("openssl", "3.0.0", true),
+                ("wget", "1.21.0", false),
+            ]),
+        )
+        .unwrap();
+        let install: Vec<String> = install.iter().map(ToString::to_string).collect();
+        assert_eq!(install, ["jq>=1.7.0", "curl==8.0.0"]);
+        assert_eq!(prune, ["openssl", "wget"]);
+
+        for content in [
+            "packages = []\n",
+            "packages = [\"jq\", \"jq>=1.7.0\"]\n",
+            "packages = [\"jq>>1\"]\n",
+            "pacakges = [\"jq\"]\n",
+        ] {
+            std::fs::write(&path, content).unwrap();
+            let err = Manifest::load(&path).await.unwrap_err();
+            assert!(
+                matches!(err, Error::Ops(OpsError::InvalidManifest { .. })),
+                "{content:?}: {err}"
+            );
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Follow-up work after a state transition
+//!
+//! Every operation that moves the system to another state finishes through
+//! here, so the audit trail, the system SBOM, services, managed directories
+//! and scheduled verification stay in step with the active state whichever
+//! operation changed it.
+
+use crate::{audit, managed, sbom, schedule, services, OpsCtx};
+use sps2_types::{InstallReport, OpChange, StateId};
+
+/// Finish the transition `report` describes, recording it in the audit log
+/// as `operation` on the `requested` packages
+pub(crate) async fn after_transition(
+    ctx: &OpsCtx,
+    report: &InstallReport,
+    operation: &str,
+    requested: &[String],
+) {
+    after_state_change(
+        ctx,
+        &report.state_id,
+        operation,
+        requested,
+        audit::report_changes(report),
+    )
+    .await;
+}
+
+/// Finish the transition to `state_id`, which made `changes`
+///
+/// Verification scheduled after installs only runs when a package was
+/// installed or changed version.
+pub(crate) async fn after_state_change(
+    ctx: &OpsCtx,
+    state_id: &StateId,
+    operation: &str,
+    requested: &[String],
+    changes: Vec<OpChange>,
+) {
+    let added = changes.iter().any(|change| change.new_version.is_some());
+    audit::record_transition(ctx, state_id, operation, requested, changes).await;
+    sbom::update_system_sbom(ctx).await;
+    services::sync_services(ctx).await;
+    managed::sync_managed_directories(ctx).await;
+    if added {
+        schedule::verify_after_install(ctx).await;
+    }
+}
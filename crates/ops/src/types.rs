@@ -2,8 +2,12 @@
 
 use serde::{Deserialize, Serialize};
 use sps2_events::HealthStatus;
-use sps2_types::{OpChange, PackageSpec};
-use std::collections::HashMap;
+use sps2_state::{
+    FileOwner, PackageFileInfo, PathProvider, RecurringDiscrepancy, StateAuditEntry,
+    VerificationRun,
+};
+use sps2_types::{BrokenDependency, OpChange, PackageChange, PackageSpec, StateInfo, Version};
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 // No longer needed - uuid::Uuid imported from sps2_types
 
@@ -124,6 +128,260 @@ pub enum IssueSeverity {
     Critical,
 }
 
+/// Deduplication statistics for the file store
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StoreStats {
+    /// File references across all package versions
+    pub file_references: u64,
+    /// Bytes those files would take without deduplication
+    pub logical_bytes: u64,
+    /// Distinct objects referenced by package versions
+    pub unique_objects: u64,
+    /// Bytes those objects take in the store
+    pub physical_bytes: u64,
+    /// `logical_bytes` divided by `physical_bytes`
+    pub dedup_ratio: f64,
+    /// Objects in the database that no package version references
+    pub orphaned_objects: u64,
+    /// Bytes taken by orphaned objects
+    pub orphaned_bytes: u64,
+    /// Objects on disk the database does not know about
+    pub untracked_objects: u64,
+    /// Bytes taken by untracked objects
+    pub untracked_bytes: u64,
+    /// Package versions using the most storage, largest first
+    pub largest_packages: Vec<PackageUsage>,
+}
+
+/// Storage used by one package version
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackageUsage {
+    /// Package name
+    pub name: String,
+    /// Package version
+    pub version: String,
+    /// Number of files
+    pub files: u64,
+    /// Bytes of all its files
+    pub bytes: u64,
+    /// Bytes of the files no other package version shares
+    pub unique_bytes: u64,
+}
+
+/// Recorded verification runs and the paths that keep drifting
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VerificationHistory {
+    /// Runs, newest first
+    pub runs: Vec<VerificationRun>,
+    /// Paths reported by more than one of `runs`, most frequent first
+    pub recurring: Vec<RecurringDiscrepancy>,
+}
+
+/// A state together with the audit trail of transitions to it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateDetail {
+    pub state: StateInfo,
+    /// Who moved the system to this state and why, oldest first
+    pub audit: Vec<StateAuditEntry>,
+}
+
+/// Packages owning the files a path or glob matches
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FileOwnership {
+    /// The query, relative to the live prefix
+    pub query: String,
+    /// Matching tracked files with their packages
+    pub owners: Vec<FileOwner>,
+    /// Matching files under the live prefix that no package owns
+    pub orphans: Vec<String>,
+}
+
+/// What a command name resolves to
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CommandResolution {
+    pub command: String,
+    /// The command in the live `bin/` directory, if it is there
+    pub live_path: Option<PathBuf>,
+    /// Installed packages providing `bin/<command>`
+    pub providers: Vec<PathProvider>,
+    /// Whether the live `bin/` directory is on `PATH` at all
+    pub live_bin_on_path: bool,
+    /// Other executables of the same name on `PATH`, in search order
+    pub on_path: Vec<PathCommand>,
+}
+
+/// An executable found on `PATH`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PathCommand {
+    pub path: PathBuf,
+    /// Whether it comes before the live command on `PATH`
+    pub shadows: bool,
+}
+
+/// Outcome of `sps2 init`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitReport {
+    /// Prefix the installation lives in
+    pub prefix: PathBuf,
+    pub steps: Vec<InitStep>,
+}
+
+impl InitReport {
+    /// Whether the installation is usable: no step failed
+    #[must_use]
+    pub fn is_ready(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| step.status != InitStatus::Failed)
+    }
+}
+
+/// One thing `sps2 init` set up or checked
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InitStep {
+    pub name: String,
+    pub status: InitStatus,
+    pub detail: String,
+}
+
+/// What happened to an [`InitStep`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InitStatus {
+    /// Set up by this run
+    Created,
+    /// Already in place, or the check passed
+    Ok,
+    /// Usable, but something is missing or degraded
+    Warning,
+    /// Could not be set up
+    Failed,
+}
+
+/// Outcome of `sps2 self destruct`, or what it would remove
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SelfDestructReport {
+    /// Whether nothing was removed yet and this lists what would be
+    pub dry_run: bool,
+    /// Store left in place with `--keep-store`
+    pub kept_store: Option<PathBuf>,
+    pub removals: Vec<Removal>,
+}
+
+impl SelfDestructReport {
+    /// Whether every removal succeeded
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.removals.iter().all(|removal| removal.error.is_none())
+    }
+}
+
+/// A path `sps2 self destruct` removes, or a file it removes lines from
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Removal {
+    pub what: String,
+    pub path: PathBuf,
+    /// Bytes freed, or that would be
+    pub bytes: u64,
+    /// Why the path could not be removed
+    pub error: Option<String>,
+}
+
+/// A launchd service an installed package provides
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub label: String,
+    /// Package that ships the service
+    pub package: String,
+    /// Launchd domain target, `gui/<uid>` or `system`
+    pub domain: String,
+    pub plist_path: PathBuf,
+    /// Whether the service loads at login or boot
+    pub enabled: bool,
+    /// Whether launchd has the service loaded
+    pub loaded: bool,
+    /// Process ID while the service runs
+    pub pid: Option<u32>,
+}
+
+/// A project's environment, ready for a shell
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProjectShell {
+    /// Directory of the project file
+    pub project: PathBuf,
+    /// Prefix the project's packages are linked into
+    pub prefix: PathBuf,
+    /// Versions of the packages in the environment, dependencies included
+    pub packages: BTreeMap<String, Version>,
+    /// Whether the environment changed, rather than already holding the
+    /// packages
+    pub changed: bool,
+    /// Variables to set for the shell
+    pub variables: BTreeMap<String, String>,
+}
+
+/// What an operation would change, shown before asking for confirmation
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChangePlan {
+    pub operation: String,
+    /// Packages that would be newly installed
+    pub install: Vec<PackageChange>,
+    /// Packages that would change version, including rollback downgrades
+    pub upgrade: Vec<PackageChange>,
+    /// Packages that would be removed
+    pub remove: Vec<PackageChange>,
+    /// Installed packages a forced removal would leave without a dependency
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub broken_dependencies: Vec<BrokenDependency>,
+}
+
+impl ChangePlan {
+    /// Whether the operation would change nothing
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.install.is_empty() && self.upgrade.is_empty() && self.remove.is_empty()
+    }
+}
+
+/// An artifact a dry run would download
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlannedDownload {
+    pub name: String,
+    pub version: Version,
+    pub url: String,
+    /// Size the repository reports, if it does
+    pub size: Option<u64>,
+}
+
+/// What an operation would do, worked out by a dry run
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OperationPlan {
+    /// Active state the changes apply to
+    pub state_id: uuid::Uuid,
+    pub changes: ChangePlan,
+    /// Artifacts to download; other packages are reused from the store
+    pub downloads: Vec<PlannedDownload>,
+    /// Bytes to download, over the downloads whose size is known
+    pub download_bytes: u64,
+    /// Installed size of the package versions leaving the live prefix
+    pub removed_bytes: u64,
+    /// Free space on the store volume
+    pub available_bytes: Option<u64>,
+}
+
+/// Files an installed package provides
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PackageFiles {
+    pub package: String,
+    pub version: String,
+    /// Combined size of the package's files
+    pub total_size: u64,
+    pub files: Vec<PackageFileInfo>,
+    /// Render as a directory tree instead of a table
+    #[serde(skip)]
+    pub tree: bool,
+}
+
 /// Install request type
 #[derive(Clone, Debug)]
 pub enum InstallRequest {
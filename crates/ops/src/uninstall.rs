@@ -1,28 +1,45 @@
 //! Uninstall command implementation
 //!
-//! Handles package removal with dependency checking.
+//! Handles package removal with dependency checking, and autoremove of
+//! packages that were installed only as dependencies or recommendations and
+//! that nothing installed needs anymore.
 //! Delegates to `sps2_install` crate for the actual uninstall logic.
 
+use crate::transition::after_transition;
 use crate::{InstallReport, OpsCtx};
 use sps2_errors::{Error, OpsError};
 use sps2_events::{
     patterns::UninstallProgressConfig, AppEvent, EventEmitter, GeneralEvent, ProgressManager,
 };
-use sps2_install::{InstallConfig, Installer, UninstallContext};
+use sps2_install::{InstallConfig, Installer, UninstallContext, UninstallOperation};
 use std::time::Instant;
 use uuid::Uuid;
 
+/// What to do about installed packages that need a package being removed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DependentsPolicy {
+    /// Refuse to remove packages that others need
+    #[default]
+    Block,
+    /// Remove the packages that need them too
+    Cascade,
+    /// Remove them anyway, leaving the packages that need them broken
+    Force,
+}
+
 /// Uninstall packages (delegates to install crate)
 ///
 /// # Errors
 ///
 /// Returns an error if:
 /// - No packages are specified
-/// - Package removal would break dependencies
+/// - Package removal would break dependencies and `policy` blocks it
 /// - Uninstallation fails
-pub async fn uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<InstallReport, Error> {
-    let start = Instant::now();
-
+pub async fn uninstall(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    policy: DependentsPolicy,
+) -> Result<InstallReport, Error> {
     if package_names.is_empty() {
         return Err(OpsError::NoPackagesSpecified.into());
     }
@@ -31,13 +48,65 @@ pub async fn uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<Install
 
     // Check mode: preview what would be uninstalled
     if ctx.check_mode {
-        return preview_uninstall(ctx, package_names).await;
+        return preview_uninstall(ctx, package_names, policy).await;
+    }
+
+    remove_packages(ctx, package_names, policy, "uninstall", package_names.len()).await
+}
+
+/// Remove packages installed only as dependencies or recommendations that
+/// nothing installed needs or recommends anymore
+///
+/// Removing one such package can leave others without dependents; they are
+/// removed too. When there is nothing to remove, no state is created and
+/// the report carries the current state.
+///
+/// # Errors
+///
+/// Returns an error if the installed packages cannot be read or the
+/// removal fails.
+pub async fn autoremove(ctx: &OpsCtx) -> Result<InstallReport, Error> {
+    let _correlation = ctx.push_correlation("autoremove");
+
+    if ctx.check_mode {
+        return preview_uninstall(ctx, &[], DependentsPolicy::Block).await;
     }
 
+    let removal = UninstallOperation::new(ctx.state.clone(), ctx.store.clone())
+        .removal_set(&uninstall_context(ctx, &[], DependentsPolicy::Block))
+        .await?;
+    if removal.autoremoved.is_empty() {
+        ctx.emit_debug("No orphaned packages to remove");
+        return Ok(InstallReport {
+            schema_version: InstallReport::SCHEMA_VERSION,
+            installed: Vec::new(),
+            updated: Vec::new(),
+            removed: Vec::new(),
+            broken_dependencies: Vec::new(),
+            state_id: ctx.state.get_current_state_id().await?,
+            duration_ms: 0,
+        });
+    }
+
+    let count = removal.autoremoved.len();
+    remove_packages(ctx, &[], DependentsPolicy::Block, "autoremove", count).await
+}
+
+/// Remove `package_names`, and whatever `policy` and autoremove add, as
+/// `operation` expected to remove `package_count` packages
+async fn remove_packages(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    policy: DependentsPolicy,
+    operation: &str,
+    package_count: usize,
+) -> Result<InstallReport, Error> {
+    let start = Instant::now();
+
     let progress_manager = ProgressManager::new();
     let uninstall_config = UninstallProgressConfig {
         operation_name: "Uninstalling packages".to_string(),
-        package_count: package_names.len() as u64,
+        package_count: package_count as u64,
     };
     let progress_id = progress_manager.create_uninstall_tracker(uninstall_config);
     let correlation = ctx.current_correlation();
@@ -47,23 +116,19 @@ pub async fn uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<Install
     let config = InstallConfig::default();
     let mut installer = Installer::new(
         config,
-        ctx.resolver.clone(),
+        ctx.resolver().await?.clone(),
         ctx.state.clone(),
         ctx.store.clone(),
     );
 
-    // Build uninstall context
-    let mut uninstall_context = UninstallContext::new().with_event_sender(ctx.tx.clone());
-
-    for package_name in package_names {
-        uninstall_context = uninstall_context.add_package(package_name.clone());
-    }
-
     // Execute uninstallation
-    let result = installer.uninstall(uninstall_context).await?;
+    let result = installer
+        .uninstall(uninstall_context(ctx, package_names, policy))
+        .await?;
 
     // Convert to report format
     let report = InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: Vec::new(), // No packages installed during uninstall
         updated: Vec::new(),   // No packages updated during uninstall
         removed: result
@@ -76,40 +141,65 @@ pub async fn uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<Install
                 size: None,
             })
             .collect(),
+        broken_dependencies: result.broken_dependencies,
         state_id: result.state_id,
         duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
     };
 
     progress_manager.complete_operation(&progress_id, ctx);
 
+    after_transition(ctx, &report, operation, package_names).await;
+
     Ok(report)
 }
 
+/// Uninstall context removing `package_names` under `policy`
+///
+/// Without package names, the context only autoremoves.
+pub(crate) fn uninstall_context(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    policy: DependentsPolicy,
+) -> UninstallContext {
+    let mut context = UninstallContext::new()
+        .with_cascade(policy == DependentsPolicy::Cascade)
+        .with_force(policy == DependentsPolicy::Force)
+        .with_autoremove(ctx.config.general.autoremove || package_names.is_empty())
+        .with_event_sender(ctx.tx.clone());
+    for package_name in package_names {
+        context = context.add_package(package_name.clone());
+    }
+    context
+}
+
 /// Preview what would be uninstalled without executing
+///
+/// # Errors
+///
+/// Returns an error if the installed packages cannot be read, or if a
+/// requested package is needed by others and `policy` blocks its removal.
 #[allow(clippy::too_many_lines)]
-async fn preview_uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<InstallReport, Error> {
+pub(crate) async fn preview_uninstall(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    policy: DependentsPolicy,
+) -> Result<InstallReport, Error> {
     use std::collections::HashMap;
 
-    // Get currently installed packages
-    let current_packages = ctx.state.get_installed_packages().await?;
-
-    // Find packages to remove
-    let mut packages_to_remove = Vec::new();
-    let mut not_found_packages = Vec::new();
-
-    for package_name in package_names {
-        if let Some(package_id) = current_packages
-            .iter()
-            .find(|pkg| &pkg.name == package_name)
-        {
-            packages_to_remove.push(package_id.clone());
-        } else {
-            not_found_packages.push(package_name.clone());
-        }
-    }
+    let context = uninstall_context(ctx, package_names, policy);
+    let removal = UninstallOperation::new(ctx.state.clone(), ctx.store.clone())
+        .removal_set(&context)
+        .await?;
+    let sizes: HashMap<String, i64> = ctx
+        .state
+        .get_installed_packages()
+        .await?
+        .into_iter()
+        .map(|package| (package.name, package.size))
+        .collect();
 
     // Report packages that would not be found
-    for package_name in &not_found_packages {
+    for package_name in &removal.not_installed {
         ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
             operation: "uninstall".to_string(),
             action: format!("Package {package_name} is not installed"),
@@ -120,84 +210,108 @@ async fn preview_uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<Ins
         }));
     }
 
-    let mut preview_removed = Vec::new();
-    let mut broken_dependencies = Vec::new();
-
     // Check each package for dependents
-    for package in &packages_to_remove {
-        let package_id = sps2_resolver::PackageId::new(package.name.clone(), package.version());
-
-        // Check for dependents
-        let dependents = ctx.state.get_package_dependents(&package_id).await?;
+    for package_id in &removal.requested {
+        let dependent_names: Vec<&str> = removal
+            .broken
+            .iter()
+            .filter(|broken| broken.needs.contains(&package_id.name))
+            .map(|broken| broken.package.as_str())
+            .collect();
 
-        if dependents.is_empty() {
+        if dependent_names.is_empty() {
             // Safe to remove
             ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
                 operation: "uninstall".to_string(),
                 action: format!("Would remove {package_id}"),
                 details: HashMap::from([
-                    ("version".to_string(), package.version().to_string()),
+                    ("version".to_string(), package_id.version.to_string()),
                     ("dependents".to_string(), "0".to_string()),
                     ("status".to_string(), "safe_to_remove".to_string()),
                 ]),
             }));
         } else {
             // Has dependents - would break dependencies
-            let dependent_names: Vec<String> = dependents
-                .iter()
-                .map(std::string::ToString::to_string)
-                .collect();
-
             ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
                 operation: "uninstall".to_string(),
                 action: format!("Would remove {package_id} (breaks dependencies)"),
                 details: HashMap::from([
-                    ("version".to_string(), package.version().to_string()),
-                    ("dependents".to_string(), dependents.len().to_string()),
+                    ("version".to_string(), package_id.version.to_string()),
+                    ("dependents".to_string(), dependent_names.len().to_string()),
                     ("dependent_packages".to_string(), dependent_names.join(", ")),
                     ("status".to_string(), "breaks_dependencies".to_string()),
                 ]),
             }));
-
-            broken_dependencies.extend(dependent_names);
         }
+    }
 
-        preview_removed.push(crate::PackageChange {
-            name: package.name.clone(),
-            from_version: Some(package.version()),
-            to_version: None,
-            size: None,
-        });
+    for package_id in &removal.cascaded {
+        ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
+            operation: "uninstall".to_string(),
+            action: format!("Would remove {package_id} (needs a removed package)"),
+            details: HashMap::from([
+                ("version".to_string(), package_id.version.to_string()),
+                ("status".to_string(), "cascade".to_string()),
+            ]),
+        }));
+    }
+
+    for package_id in &removal.autoremoved {
+        ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
+            operation: "uninstall".to_string(),
+            action: format!("Would remove {package_id} (no longer needed)"),
+            details: HashMap::from([
+                ("version".to_string(), package_id.version.to_string()),
+                ("status".to_string(), "autoremove".to_string()),
+            ]),
+        }));
     }
 
     // Show warning for broken dependencies
-    if !broken_dependencies.is_empty() {
+    if !removal.broken.is_empty() {
+        let affected: Vec<&str> = removal
+            .broken
+            .iter()
+            .map(|broken| broken.package.as_str())
+            .collect();
+        let severity = if policy == DependentsPolicy::Force {
+            "warning"
+        } else {
+            "error"
+        };
         ctx.emit(AppEvent::General(GeneralEvent::CheckModePreview {
             operation: "uninstall".to_string(),
             action: "WARNING: This would break dependencies for:".to_string(),
             details: HashMap::from([
-                (
-                    "affected_packages".to_string(),
-                    broken_dependencies.join(", "),
-                ),
-                ("severity".to_string(), "error".to_string()),
+                ("affected_packages".to_string(), affected.join(", ")),
+                ("severity".to_string(), severity.to_string()),
                 (
                     "suggestion".to_string(),
-                    "Use --force to override dependency checks".to_string(),
+                    "Use --cascade to remove them too, or --force to override dependency checks"
+                        .to_string(),
                 ),
             ]),
         }));
     }
 
     // Emit summary
-    let total_changes = packages_to_remove.len();
+    let total_changes = removal.packages().count();
     let mut categories = HashMap::new();
-    categories.insert("packages_removed".to_string(), packages_to_remove.len());
-    if !broken_dependencies.is_empty() {
-        categories.insert("broken_dependencies".to_string(), broken_dependencies.len());
+    categories.insert("packages_removed".to_string(), total_changes);
+    if !removal.cascaded.is_empty() {
+        categories.insert("dependents_removed".to_string(), removal.cascaded.len());
+    }
+    if !removal.autoremoved.is_empty() {
+        categories.insert("orphans_removed".to_string(), removal.autoremoved.len());
     }
-    if !not_found_packages.is_empty() {
-        categories.insert("packages_not_found".to_string(), not_found_packages.len());
+    if !removal.broken.is_empty() {
+        categories.insert("broken_dependencies".to_string(), removal.broken.len());
+    }
+    if !removal.not_installed.is_empty() {
+        categories.insert(
+            "packages_not_found".to_string(),
+            removal.not_installed.len(),
+        );
     }
 
     ctx.emit(AppEvent::General(GeneralEvent::CheckModeSummary {
@@ -206,11 +320,29 @@ async fn preview_uninstall(ctx: &OpsCtx, package_names: &[String]) -> Result<Ins
         categories,
     }));
 
+    if policy == DependentsPolicy::Block {
+        removal.ensure_nothing_broken()?;
+    }
+
+    let preview_removed = removal
+        .packages()
+        .map(|package_id| crate::PackageChange {
+            name: package_id.name.clone(),
+            from_version: Some(package_id.version.clone()),
+            to_version: None,
+            size: sizes
+                .get(&package_id.name)
+                .and_then(|size| u64::try_from(*size).ok()),
+        })
+        .collect();
+
     // Return preview report (no actual state changes)
     Ok(InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: Vec::new(),
         updated: Vec::new(),
         removed: preview_removed,
+        broken_dependencies: removal.broken,
         state_id: Uuid::nil(), // No state change in preview
         duration_ms: 0,
     })
@@ -260,7 +392,9 @@ mod tests {
             .build()
             .unwrap();
 
-        let preview = preview_uninstall(&ctx, &[]).await.unwrap();
+        let preview = preview_uninstall(&ctx, &[], DependentsPolicy::Block)
+            .await
+            .unwrap();
         assert!(preview.removed.is_empty());
     }
 }
@@ -5,6 +5,7 @@
 //!
 //! Both delegate to `sps2_install` crate for the actual update logic.
 
+use crate::transition::after_transition;
 use crate::{InstallReport, OpsCtx};
 use sps2_errors::Error;
 use sps2_events::{
@@ -13,7 +14,7 @@ use sps2_events::{
     AppEvent, EventEmitter, FailureContext, GeneralEvent, LifecycleEvent, ProgressEvent,
     ProgressManager,
 };
-use sps2_install::{InstallConfig, Installer, UpdateContext};
+use sps2_install::{Installer, UpdateContext};
 use sps2_types::{PackageSpec, Version};
 use std::time::Instant;
 use uuid::Uuid;
@@ -74,20 +75,37 @@ impl UpdateMode {
 /// - No packages are installed or specified
 /// - Update resolution fails
 /// - Installation of updates fails
-pub async fn update(ctx: &OpsCtx, package_names: &[String]) -> Result<InstallReport, Error> {
-    update_or_upgrade(ctx, package_names, UpdateMode::Update).await
+/// - Installed packages' version constraints would break and `force` is
+///   not set
+pub async fn update(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    force: bool,
+) -> Result<InstallReport, Error> {
+    update_or_upgrade(ctx, package_names, UpdateMode::Update, force).await
 }
 
 /// Upgrade packages (delegates to install crate)
 ///
+/// With `general.autoremove`, packages the upgrade leaves without anything
+/// needing them are removed afterwards, as by [`autoremove`].
+///
+/// [`autoremove`]: crate::autoremove
+///
 /// # Errors
 ///
 /// Returns an error if:
 /// - No packages are installed or specified
 /// - Upgrade resolution fails
 /// - Installation of upgrades fails
-pub async fn upgrade(ctx: &OpsCtx, package_names: &[String]) -> Result<InstallReport, Error> {
-    update_or_upgrade(ctx, package_names, UpdateMode::Upgrade).await
+/// - Installed packages' version constraints would break and `force` is
+///   not set
+pub async fn upgrade(
+    ctx: &OpsCtx,
+    package_names: &[String],
+    force: bool,
+) -> Result<InstallReport, Error> {
+    update_or_upgrade(ctx, package_names, UpdateMode::Upgrade, force).await
 }
 
 /// Internal implementation for both update and upgrade
@@ -95,6 +113,7 @@ async fn update_or_upgrade(
     ctx: &OpsCtx,
     package_names: &[String],
     mode: UpdateMode,
+    force: bool,
 ) -> Result<InstallReport, Error> {
     let start = Instant::now();
 
@@ -106,17 +125,19 @@ async fn update_or_upgrade(
     }
 
     // Create installer
-    let config = InstallConfig::default();
+    let config = ctx.install_config();
     let mut installer = Installer::new(
         config,
-        ctx.resolver.clone(),
+        ctx.resolver().await?.clone(),
         ctx.state.clone(),
         ctx.store.clone(),
-    );
+    )
+    .with_net_client(ctx.net()?.clone());
 
     // Build update context with appropriate mode
     let mut update_context = UpdateContext::new()
         .with_upgrade(mode.is_upgrade())
+        .with_force(force)
         .with_event_sender(ctx.tx.clone());
 
     for package_name in package_names {
@@ -181,7 +202,7 @@ async fn update_or_upgrade(
         )));
     })?;
 
-    let report = create_update_report(
+    let mut report = create_update_report(
         &result,
         &installed_map,
         start,
@@ -194,9 +215,29 @@ async fn update_or_upgrade(
             mode,
         },
     );
+    after_transition(ctx, &report, mode.operation_name(), package_names).await;
+    autoremove_after_upgrade(ctx, mode, &mut report).await?;
     Ok(report)
 }
 
+/// With `general.autoremove`, remove the dependencies an upgrade left
+/// without anything needing them, adding them to the upgrade's `report`
+async fn autoremove_after_upgrade(
+    ctx: &OpsCtx,
+    mode: UpdateMode,
+    report: &mut InstallReport,
+) -> Result<(), Error> {
+    if !mode.is_upgrade() || !ctx.config.general.autoremove {
+        return Ok(());
+    }
+    let orphans = crate::uninstall::autoremove(ctx).await?;
+    if !orphans.removed.is_empty() {
+        report.removed.extend(orphans.removed);
+        report.state_id = orphans.state_id;
+    }
+    Ok(())
+}
+
 struct UpdateReportContext<'a> {
     progress_id: &'a str,
     progress_manager: &'a ProgressManager,
@@ -214,6 +255,7 @@ fn create_update_report(
 ) -> InstallReport {
     // Convert to report format
     let report = InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: result
             .installed_packages
             .iter()
@@ -244,6 +286,7 @@ fn create_update_report(
                 size: None,
             })
             .collect(),
+        broken_dependencies: Vec::new(),
         state_id: result.state_id,
         duration_ms: u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
     };
@@ -286,7 +329,7 @@ fn create_update_report(
 
 /// Preview what would be updated/upgraded without executing
 #[allow(clippy::too_many_lines)]
-async fn preview_update_or_upgrade(
+pub(crate) async fn preview_update_or_upgrade(
     ctx: &OpsCtx,
     package_names: &[String],
     mode: UpdateMode,
@@ -356,12 +399,18 @@ async fn preview_update_or_upgrade(
             }
         };
 
-        // Create resolution context for this package
-        let mut resolution_context = sps2_resolver::ResolutionContext::new();
+        // Create resolution context for this package; only its own version
+        // matters here
+        let mut resolution_context = sps2_resolver::ResolutionContext::new().with_recommends(false);
         resolution_context = resolution_context.add_runtime_dep(spec);
 
         // Resolve to see what version would be installed
-        match ctx.resolver.resolve_with_sat(resolution_context).await {
+        match ctx
+            .resolver()
+            .await?
+            .resolve_with_sat(resolution_context)
+            .await
+        {
             Ok(resolution_result) => {
                 // Check if any resolved package is newer than current
                 let mut found_update = false;
@@ -500,9 +549,11 @@ async fn preview_update_or_upgrade(
 
     // Return preview report (no actual state changes)
     Ok(InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
         installed: Vec::new(),
         updated: preview_updated,
         removed: Vec::new(),
+        broken_dependencies: Vec::new(),
         state_id: Uuid::nil(), // No state change in preview
         duration_ms: 0,
     })
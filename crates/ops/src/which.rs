@@ -0,0 +1,140 @@
+//! Command lookups
+//!
+//! Resolves a command name to its entry in the live `bin/` directory, the
+//! installed package providing it, and the other executables of the same
+//! name on `PATH`.
+
+use crate::{types::PathCommand, CommandResolution, OpsCtx};
+use sps2_errors::{Error, OpsError};
+use sps2_state::queries;
+use std::ffi::OsStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Find the package providing a command
+///
+/// Looks `command` up in the live `bin/` directory and the file ownership
+/// map, then searches `PATH` for executables that run instead of it. With
+/// `all`, every executable of that name on `PATH` is listed, not just the
+/// ones shadowing the live command.
+///
+/// # Errors
+///
+/// Returns an error if `command` is not a plain command name or the state
+/// database cannot be read.
+pub async fn which(ctx: &OpsCtx, command: &str, all: bool) -> Result<CommandResolution, Error> {
+    if command.is_empty() || command.contains('/') {
+        return Err(OpsError::OperationFailed {
+            message: format!("'{command}' is not a command name"),
+        }
+        .into());
+    }
+
+    let live_bin = ctx.state.live_path().join("bin");
+    let live_path = live_bin.join(command);
+    let live_path = is_executable(&live_path).then_some(live_path);
+
+    let state_id = ctx.state.get_active_state().await?;
+    let mut tx = ctx.state.begin_transaction().await?;
+    let providers =
+        queries::get_path_providers(&mut tx, &state_id, &format!("bin/{command}")).await?;
+    tx.commit().await?;
+
+    let path_var = std::env::var_os("PATH").unwrap_or_default();
+    let (mut on_path, live_bin_on_path) = search_path(&path_var, command, &live_bin);
+    if !all {
+        on_path.retain(|found| found.shadows);
+    }
+
+    Ok(CommandResolution {
+        command: command.to_string(),
+        live_path,
+        providers,
+        live_bin_on_path,
+        on_path,
+    })
+}
+
+/// Executables named `command` on `path_var`, other than the one in `live_bin`
+///
+/// Also returns whether `live_bin` is on the path. Executables in directories
+/// searched before it shadow the live command; if it is not on the path, all
+/// of them do.
+fn search_path(path_var: &OsStr, command: &str, live_bin: &Path) -> (Vec<PathCommand>, bool) {
+    let live_bin = canonical(live_bin);
+    let mut found = Vec::new();
+    let mut live_bin_seen = false;
+    for dir in std::env::split_paths(path_var) {
+        if dir.as_os_str().is_empty() {
+            continue;
+        }
+        if canonical(&dir) == live_bin {
+            live_bin_seen = true;
+            continue;
+        }
+        let path = dir.join(command);
+        if is_executable(&path) && !found.iter().any(|seen: &PathCommand| seen.path == path) {
+            found.push(PathCommand {
+                path,
+                shadows: !live_bin_seen,
+            });
+        }
+    }
+    (found, live_bin_seen)
+}
+
+/// `path` with symlinks resolved, or as given if it does not exist
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn install(dir: &Path, command: &str, mode: u32) {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(command);
+        std::fs::write(&path, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn executables_before_the_live_bin_directory_shadow_it() {
+        let root = TempDir::new().unwrap();
+        let local = root.path().join("usr/local/bin");
+        let live = root.path().join("opt/pm/live/bin");
+        let system = root.path().join("usr/bin");
+        let docs = root.path().join("usr/share");
+        install(&local, "jq", 0o755);
+        install(&live, "jq", 0o755);
+        install(&system, "jq", 0o755);
+        install(&docs, "jq", 0o644);
+
+        let path_var = std::env::join_paths([&docs, &local, &live, &system]).unwrap();
+        let (found, live_on_path) = search_path(&path_var, "jq", &live);
+        assert!(live_on_path);
+        let found: Vec<(&Path, bool)> = found
+            .iter()
+            .map(|found| (found.path.as_path(), found.shadows))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (local.join("jq").as_path(), true),
+                (system.join("jq").as_path(), false)
+            ]
+        );
+
+        let path_var = std::env::join_paths([&system]).unwrap();
+        let (found, live_on_path) = search_path(&path_var, "jq", &live);
+        assert!(!live_on_path);
+        assert!(found[0].shadows);
+    }
+}
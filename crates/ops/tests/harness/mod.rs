@@ -0,0 +1,283 @@
+//! tests/harness/mod.rs
+//!
+//! Ephemeral root prefix for end-to-end operation tests: a temporary store,
+//! state database and live directory, plus a signed fixture repository
+//! served over local HTTP or read from a directory. Operations run through a regular [`OpsCtx`] and
+//! every event they emit is captured for assertions.
+
+use httpmock::{Method::GET, MockServer};
+use sps2_config::{Config, RepositoryConfig};
+use sps2_events::{AppEvent, EventReceiver};
+use sps2_fixtures::{FixtureSpec, RepositoryFixture};
+use sps2_index::{IndexManager, ValidationPolicy};
+use sps2_net::signing::{Algorithm, PublicKeyRef};
+use sps2_net::{NetClient, NetConfig};
+use sps2_ops::keys::{KeyManager, TrustedKey};
+use sps2_ops::{OpsContextBuilder, OpsCtx};
+use sps2_state::StateManager;
+use sps2_store::PackageStore;
+use sps2_types::Version;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// Name the fixture repository is configured under
+pub const REPOSITORY: &str = "fixture";
+
+/// A throwaway installation with its own repository
+pub struct TestPrefix {
+    pub ctx: OpsCtx,
+    events: EventReceiver,
+    repository_key: String,
+    _server: Option<MockServer>,
+    _root: TempDir,
+}
+
+impl TestPrefix {
+    /// Publish `spec` as a repository and set up an empty prefix using it
+    pub async fn new(spec: &FixtureSpec) -> Self {
+        // The resolver upgrades download URLs to HTTPS unless told otherwise
+        std::env::set_var("SPS2_ALLOW_HTTP", "1");
+
+        let root = TempDir::new().unwrap();
+        let server = MockServer::start_async().await;
+        let repo = RepositoryFixture::publish(
+            spec,
+            &root.path().join("repo"),
+            &root.path().join("keys"),
+            &server.base_url(),
+        )
+        .await
+        .unwrap();
+        serve_dir(&server, &repo.dir).await;
+
+        let net = NetClient::new_without_proxies(NetConfig {
+            retry_delay: Duration::ZERO,
+            ..NetConfig::default()
+        })
+        .unwrap();
+        let index = fetch_index(&net, &repo, &root.path().join("index")).await;
+
+        let mut config = config(root.path());
+        config.repos.extras.insert(
+            REPOSITORY.to_string(),
+            RepositoryConfig {
+                url: server.base_url(),
+                priority: 1,
+                algorithm: "minisign".to_string(),
+                key_ids: Vec::new(),
+                mirrors: Vec::new(),
+                search_url: None,
+            },
+        );
+        Self::build(
+            root,
+            Some(server),
+            config,
+            index,
+            Some(net),
+            repo.public_key,
+        )
+        .await
+    }
+
+    /// Publish `spec` to a directory, as if copied to removable media, and
+    /// set up an offline prefix that trusts its key and installs from it
+    pub async fn from_dir(spec: &FixtureSpec) -> Self {
+        let root = TempDir::new().unwrap();
+        let repo_dir = root.path().join("repo");
+        let repo = RepositoryFixture::publish(
+            spec,
+            &repo_dir,
+            &root.path().join("keys"),
+            "https://repo.invalid",
+        )
+        .await
+        .unwrap();
+
+        let mut config = config(root.path());
+        config.network.offline = true;
+        trust_key(&config.keys_path(), &repo.public_key).await;
+        let index = sps2_ops::local_repository(&config, &repo_dir)
+            .await
+            .unwrap();
+
+        Self::build(root, None, config, index, None, repo.public_key).await
+    }
+
+    async fn build(
+        root: TempDir,
+        server: Option<MockServer>,
+        config: Config,
+        index: IndexManager,
+        net: Option<NetClient>,
+        repository_key: String,
+    ) -> Self {
+        let store_dir = config.store_path();
+        let state_dir = root.path().join("state");
+        tokio::fs::create_dir_all(&store_dir).await.unwrap();
+        tokio::fs::create_dir_all(&state_dir).await.unwrap();
+
+        let (tx, events) = sps2_events::channel();
+        let mut builder = OpsContextBuilder::new()
+            .with_store(PackageStore::new(store_dir))
+            .with_state(StateManager::new(&state_dir).await.unwrap())
+            .with_event_sender(tx)
+            .with_config(config)
+            .with_index(index);
+        if let Some(net) = net {
+            builder = builder.with_net(net);
+        }
+        let ctx = builder.build().unwrap();
+
+        Self {
+            ctx,
+            events,
+            repository_key,
+            _server: server,
+            _root: root,
+        }
+    }
+
+    /// Trust the key the fixture repository is signed with
+    pub async fn trust_repository_key(&self) {
+        trust_key(&self.ctx.config.keys_path(), &self.repository_key).await;
+    }
+
+    /// Directory the fixture repository was published to
+    pub fn repository_dir(&self) -> PathBuf {
+        self._root.path().join("repo")
+    }
+
+    /// Live directory of the prefix
+    pub fn live_path(&self) -> &Path {
+        self.ctx.state.live_path()
+    }
+
+    /// Path of a package's `file`th content file below the live directory
+    ///
+    /// Fixture packages keep their files below `opt/pm/live`, which the
+    /// installer mirrors into the live directory as is.
+    pub fn content_file(&self, package: &str, file: usize) -> PathBuf {
+        self.live_path()
+            .join("opt/pm/live/share")
+            .join(package)
+            .join(format!("file-{file:04}.dat"))
+    }
+
+    /// Events emitted since the last call
+    pub fn drain_events(&mut self) -> Vec<AppEvent> {
+        let mut events = Vec::new();
+        while let Ok(message) = self.events.try_recv() {
+            events.push(message.event);
+        }
+        events
+    }
+
+    /// Installed packages of the active state, sorted by name
+    pub async fn installed(&self) -> Vec<(String, Version)> {
+        let mut installed: Vec<_> = self
+            .ctx
+            .state
+            .get_installed_packages()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|pkg| {
+                let version = pkg.version();
+                (pkg.name, version)
+            })
+            .collect();
+        installed.sort();
+        installed
+    }
+}
+
+/// Version a fixture content file was written for
+///
+/// Fixture files repeat a `<name>-<version>/<file>` line; the version is read
+/// back from the first one.
+pub async fn content_version(path: &Path) -> Version {
+    let contents = tokio::fs::read_to_string(path).await.unwrap();
+    let line = contents.lines().next().unwrap();
+    let (name_version, _file) = line.rsplit_once('/').unwrap();
+    let (_name, version) = name_version.rsplit_once('-').unwrap();
+    version.parse().unwrap()
+}
+
+/// Configuration for the prefix below `root`
+///
+/// Trusted keys are read from the prefix's keys directory, which the fixture
+/// key is only added to on request, so packages are accepted unsigned. The
+/// repository is served over plain HTTP. Paths derived from the store, like
+/// the download cache, stay inside the prefix's store.
+fn config(root: &Path) -> Config {
+    let mut config = Config {
+        // Operations that edit the configuration must not touch the user's
+        file: Some(root.join("config.toml")),
+        ..Config::default()
+    };
+    config.paths.root = Some(root.join("root"));
+    config.paths.store_path = Some(root.join("store"));
+    config.security.allow_unsigned = true;
+    config.security.allow_insecure_index_urls = true;
+    config.network.retries = 0;
+    config.network.retry_delay = 0;
+    config
+}
+
+/// Add a minisign public key to the trusted keys in `keys_dir`
+async fn trust_key(keys_dir: &Path, public_key: &str) {
+    tokio::fs::create_dir_all(keys_dir).await.unwrap();
+    let mut key_manager = KeyManager::new(keys_dir);
+    key_manager.load_trusted_keys().await.unwrap();
+    key_manager
+        .import_key(&TrustedKey {
+            key_id: sps2_repository::keys::key_id_from_public_base64(public_key).unwrap(),
+            public_key: public_key.to_string(),
+            comment: None,
+            trusted_since: 0,
+            expires_at: None,
+        })
+        .await
+        .unwrap();
+}
+
+/// Serve every file in `dir` at `/<file name>`
+async fn serve_dir(server: &MockServer, dir: &Path) {
+    let mut entries = tokio::fs::read_dir(dir).await.unwrap();
+    while let Some(entry) = entries.next_entry().await.unwrap() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let body = tokio::fs::read(entry.path()).await.unwrap();
+        server
+            .mock_async(|when, then| {
+                when.method(GET).path(format!("/{name}"));
+                then.status(200).body(body);
+            })
+            .await;
+    }
+}
+
+/// Download the repository index, check its signature against the fixture
+/// key and load it
+async fn fetch_index(net: &NetClient, repo: &RepositoryFixture, cache_dir: &Path) -> IndexManager {
+    let get = |file: &str| {
+        let url = format!("{}/{file}", repo.base_url);
+        async move { net.get(&url).await.unwrap().text().await.unwrap() }
+    };
+    let json = get("index.json").await;
+    let signature = get("index.json.minisig").await;
+
+    let key = PublicKeyRef {
+        id: sps2_repository::keys::key_id_from_public_base64(&repo.public_key).unwrap(),
+        algo: Algorithm::Minisign,
+        data: repo.public_key.clone(),
+    };
+    sps2_net::signing::verify_minisign_bytes_with_keys(json.as_bytes(), &signature, &[key])
+        .unwrap();
+
+    let mut index = IndexManager::new(cache_dir)
+        .with_validation_policy(ValidationPolicy::default().with_allow_insecure_urls(true));
+    index.load(Some(&json)).await.unwrap();
+    index
+}
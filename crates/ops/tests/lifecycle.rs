@@ -0,0 +1,1580 @@
+//! tests/lifecycle.rs
+//!
+//! Full install → upgrade → rollback → verify cycle against an ephemeral
+//! prefix and a local HTTP repository, asserting on the emitted events and
+//! on what ends up in the live directory.
+
+mod harness;
+
+use harness::{content_version, TestPrefix, REPOSITORY};
+use sps2_config::{GuardConfiguration, GuardScheduleConfig};
+use sps2_events::{AppEvent, GeneralEvent, GuardEvent, LifecycleEvent, LifecycleStage, StateEvent};
+use sps2_fixtures::{FixtureSpec, GraphShape};
+use sps2_ops::{DependentsPolicy, FileChange, KeyChangePolicy};
+use sps2_types::Version;
+use std::os::unix::fs::PermissionsExt;
+
+/// Requested package; depends on [`DEPENDENCY`]
+const ROOT: &str = "fixture-pkg-00000";
+const DEPENDENCY: &str = "fixture-pkg-00001";
+
+fn spec() -> FixtureSpec {
+    FixtureSpec {
+        packages: 2,
+        versions: 2,
+        files_per_package: 3,
+        file_size: 256,
+        shape: GraphShape::Chain,
+        seed: 0,
+    }
+}
+
+fn v(minor: u64) -> Version {
+    Version::new(1, minor, 0)
+}
+
+fn transitions(events: &[AppEvent]) -> Vec<&str> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            AppEvent::State(StateEvent::TransitionCompleted { context, .. }) => {
+                Some(context.operation.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+async fn verification_runs(ctx: &sps2_ops::OpsCtx) -> Vec<sps2_state::VerificationRun> {
+    sps2_ops::verify_history(ctx, 10).await.unwrap().runs
+}
+
+fn assert_no_failures(events: &[AppEvent]) {
+    for event in events {
+        assert!(
+            !matches!(
+                event,
+                AppEvent::State(
+                    StateEvent::TransitionFailed { .. } | StateEvent::RollbackFailed { .. }
+                ) | AppEvent::Lifecycle(LifecycleEvent::Install {
+                    stage: LifecycleStage::Failed,
+                    ..
+                })
+            ),
+            "unexpected failure event: {event:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn install_upgrade_rollback_verify() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let root_file = prefix.content_file(ROOT, 0);
+
+    // Install the older release; the dependency resolves to its latest
+    let installed = sps2_ops::install(&prefix.ctx, &[format!("{ROOT}==1.0.0")], false, None)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(transitions(&events), ["install"]);
+    assert_eq!(installed.installed.len(), 2);
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(0)), (DEPENDENCY.to_string(), v(1))]
+    );
+    assert_eq!(content_version(&root_file).await, v(0));
+    assert_eq!(
+        content_version(&prefix.content_file(DEPENDENCY, 2)).await,
+        v(1)
+    );
+    let install_state = prefix.ctx.state.get_current_state_id().await.unwrap();
+    assert_eq!(installed.state_id, install_state);
+
+    // Upgrade replaces the files of the requested package in place
+    let upgraded = sps2_ops::upgrade(&prefix.ctx, &[ROOT.to_string()], false)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(transitions(&events).len(), 1);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::Lifecycle(LifecycleEvent::Update {
+            stage: LifecycleStage::Completed,
+            ..
+        })
+    )));
+    assert_eq!(upgraded.updated.len(), 1);
+    assert_eq!(upgraded.updated[0].from_version, Some(v(0)));
+    assert_eq!(upgraded.updated[0].to_version, Some(v(1)));
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(1)), (DEPENDENCY.to_string(), v(1))]
+    );
+    assert_eq!(content_version(&root_file).await, v(1));
+    let upgrade_state = prefix.ctx.state.get_current_state_id().await.unwrap();
+    assert_ne!(upgrade_state, install_state);
+
+    // Rolling back moves the live directory to the install state again
+    let rolled_back = sps2_ops::rollback(&prefix.ctx, None).await.unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::State(StateEvent::RollbackCompleted { context, .. })
+            if context.from == upgrade_state && context.to == install_state
+    )));
+    assert_eq!(rolled_back.id, install_state);
+    assert_eq!(
+        prefix.ctx.state.get_current_state_id().await.unwrap(),
+        install_state
+    );
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(0)), (DEPENDENCY.to_string(), v(1))]
+    );
+    assert_eq!(content_version(&root_file).await, v(0));
+
+    // The install state was entered twice: by the install and the rollback
+    let detail = sps2_ops::history_detail(&prefix.ctx, install_state)
+        .await
+        .unwrap();
+    let operations: Vec<_> = detail
+        .audit
+        .iter()
+        .map(|entry| entry.audit.operation.as_str())
+        .collect();
+    assert_eq!(operations, ["install", "rollback"]);
+    assert_eq!(detail.audit[0].audit.requested, [format!("{ROOT}==1.0.0")]);
+    assert_eq!(detail.audit[0].audit.changes.len(), 2);
+    assert!(detail.audit[0]
+        .audit
+        .description
+        .starts_with(&format!("install {ROOT}==1.0.0: installed ")));
+
+    // The live directory matches the store for the active state
+    let verification = sps2_ops::verify(&prefix.ctx, false, "full", "all", false)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert!(
+        verification.is_valid,
+        "discrepancies: {:?}",
+        verification.discrepancies
+    );
+    assert_eq!(verification.state_id, install_state);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::Guard(GuardEvent::VerificationCompleted {
+            discrepancies: 0,
+            ..
+        })
+    )));
+}
+
+#[tokio::test]
+async fn quick_verify_rehashes_only_changed_files() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    // A full run hashes everything and records what it saw
+    let full = sps2_ops::verify(&prefix.ctx, false, "full", "live", false)
+        .await
+        .unwrap();
+    assert!(full.is_valid, "discrepancies: {:?}", full.discrepancies);
+    prefix.drain_events();
+
+    let hit_rate = |events: &[AppEvent]| {
+        events.iter().find_map(|event| match event {
+            AppEvent::Guard(GuardEvent::VerificationCompleted { metrics, .. }) => {
+                Some(metrics.cache_hit_rate)
+            }
+            _ => None,
+        })
+    };
+
+    // Nothing changed, so a quick run hashes nothing
+    let quick = sps2_ops::verify(&prefix.ctx, false, "quick", "live", false)
+        .await
+        .unwrap();
+    assert!(quick.is_valid);
+    assert_eq!(hit_rate(&prefix.drain_events()), Some(1.0));
+
+    // A rewritten file is re-hashed and caught
+    let root_file = prefix.content_file(ROOT, 0);
+    tokio::fs::write(&root_file, b"rewritten by another tool")
+        .await
+        .unwrap();
+    let quick = sps2_ops::verify(&prefix.ctx, false, "quick", "live", false)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let relative = root_file
+        .strip_prefix(prefix.live_path())
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert!(matches!(
+        quick.discrepancies.as_slice(),
+        [sps2_ops::Discrepancy::CorruptedFile { path, .. }] if path == relative
+    ));
+
+    // Standard checks at least what quick does
+    let standard = sps2_ops::verify(&prefix.ctx, false, "standard", "live", false)
+        .await
+        .unwrap();
+    assert!(matches!(
+        standard.discrepancies.as_slice(),
+        [sps2_ops::Discrepancy::CorruptedFile { path, .. }] if path == relative
+    ));
+}
+
+#[tokio::test]
+async fn heal_refetches_lost_store_objects_from_repository() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    // Lose a file from both the live directory and the store
+    let root_file = prefix.content_file(ROOT, 0);
+    let hash = sps2_hash::Hash::hash_file(&root_file).await.unwrap();
+    tokio::fs::remove_file(&root_file).await.unwrap();
+    prefix
+        .ctx
+        .store
+        .file_store()
+        .remove_file(&hash)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    let healed = sps2_ops::verify(&prefix.ctx, true, "standard", "live", false)
+        .await
+        .unwrap();
+    assert!(healed.is_valid, "discrepancies: {:?}", healed.discrepancies);
+    assert_eq!(healed.healed, 1);
+    assert_eq!(content_version(&root_file).await, v(1));
+    assert!(prefix.ctx.store.file_store().has_file(&hash).await);
+
+    let relative = root_file
+        .strip_prefix(prefix.live_path())
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let events = prefix.drain_events();
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::Guard(GuardEvent::PathHealed { path, source, .. })
+            if path == relative && source == "repository"
+    )));
+}
+
+#[tokio::test]
+async fn store_compact_merges_objects_named_by_an_older_algorithm() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    // Name one object by BLAKE3 as a package stored before the switch to
+    // xxHash128 would, while a later package keeps the xxHash128 copy
+    let store = &prefix.ctx.store;
+    let root_file = prefix.content_file(ROOT, 0);
+    let current = sps2_hash::Hash::hash_file(&root_file).await.unwrap();
+    let older = sps2_hash::Hash::blake3_hash_file(&root_file).await.unwrap();
+    store
+        .file_store()
+        .store_file(&store.file_path(&current), &older)
+        .await
+        .unwrap();
+    prefix
+        .ctx
+        .state
+        .merge_file_objects(&[(current.to_hex(), older.to_hex())])
+        .await
+        .unwrap();
+    store
+        .rewrite_file_hashes(&[sps2_store::DuplicateObjects {
+            keep: older.clone(),
+            duplicates: vec![current.clone()],
+            size: 256,
+        }])
+        .await
+        .unwrap();
+
+    prefix.ctx.check_mode = true;
+    let preview = sps2_ops::store_compact(&prefix.ctx).await.unwrap();
+    assert_eq!(preview, "Would remove 1 duplicate objects (0.0 MB)");
+    assert!(prefix.ctx.store.file_store().has_file(&older).await);
+
+    prefix.ctx.check_mode = false;
+    let report = sps2_ops::store_compact(&prefix.ctx).await.unwrap();
+    assert!(
+        report.starts_with("Removed 1 duplicate objects"),
+        "{report}"
+    );
+    assert!(!prefix.ctx.store.file_store().has_file(&older).await);
+    assert!(prefix.ctx.store.file_store().has_file(&current).await);
+    assert_eq!(
+        sps2_ops::store_compact(&prefix.ctx).await.unwrap(),
+        "No duplicate objects in the store"
+    );
+
+    let result = sps2_ops::verify(&prefix.ctx, false, "full", "live", false)
+        .await
+        .unwrap();
+    assert!(result.is_valid, "discrepancies: {:?}", result.discrepancies);
+    assert!(prefix
+        .ctx
+        .state
+        .file_object_hashes()
+        .await
+        .unwrap()
+        .contains(&current.to_hex()));
+}
+
+#[tokio::test]
+async fn reinstall_rehashes_validated_packages_whose_objects_failed_a_check() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    let installed = prefix.ctx.state.get_installed_packages().await.unwrap();
+    let root = installed.iter().find(|p| p.name == ROOT).unwrap();
+    let trust = sps2_net::trust_fingerprint(&prefix.ctx.config.keys_path()).unwrap();
+    assert!(prefix
+        .ctx
+        .state
+        .validation_stamp(&root.hash, &trust)
+        .await
+        .unwrap()
+        .is_some());
+
+    // A store object of the validated package rots, and a store
+    // verification notices
+    let root_file = prefix.content_file(ROOT, 0);
+    let object = sps2_hash::Hash::hash_file(&root_file).await.unwrap();
+    let object_path = prefix.ctx.store.file_path(&object);
+    std::fs::remove_file(&object_path).unwrap();
+    std::fs::write(&object_path, b"bit rot").unwrap();
+    sps2_ops::verify(&prefix.ctx, false, "standard", "store", false)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    sps2_ops::reinstall(&prefix.ctx, &[ROOT.to_string()])
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::General(GeneralEvent::DebugLog { message, .. })
+            if message.contains("failed a hash check since it was validated")
+    )));
+    assert!(prefix
+        .ctx
+        .store
+        .file_store()
+        .verify_file(&object)
+        .await
+        .unwrap());
+    assert_eq!(
+        sps2_hash::Hash::hash_file(&root_file).await.unwrap(),
+        object
+    );
+}
+
+#[tokio::test]
+async fn reinstalling_evicted_packages_uses_the_download_cache() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let downloads = |events: &[AppEvent]| -> Vec<(String, bool)> {
+        let mut downloads: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                AppEvent::Lifecycle(LifecycleEvent::Download {
+                    stage: LifecycleStage::Completed,
+                    context,
+                    ..
+                }) => Some((context.package.clone().unwrap(), context.cached)),
+                _ => None,
+            })
+            .collect();
+        // A package can be acquired by more than one worker
+        downloads.sort();
+        downloads.dedup();
+        downloads
+    };
+
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    // A second acquisition of a package may already find it cached
+    let fetched = downloads(&prefix.drain_events());
+    assert!(
+        fetched.contains(&(ROOT.to_string(), false))
+            && fetched.contains(&(DEPENDENCY.to_string(), false)),
+        "{fetched:?}"
+    );
+    let cache = sps2_net::DownloadCache::new(
+        prefix.ctx.config.downloads_path(),
+        std::time::Duration::from_secs(86_400),
+        u64::MAX,
+    );
+    assert_eq!(cache.list().await.unwrap().len(), 2);
+
+    // Remove the packages and evict them from the store, as cleanup would
+    let installed = prefix.ctx.state.get_installed_packages().await.unwrap();
+    sps2_ops::uninstall(&prefix.ctx, &[ROOT.to_string()], DependentsPolicy::Cascade)
+        .await
+        .unwrap();
+    for package in &installed {
+        let hash = sps2_hash::Hash::from_hex(&package.hash).unwrap();
+        prefix.ctx.store.remove_package(&hash).await.unwrap();
+        assert!(!prefix.ctx.store.has_package(&hash).await);
+    }
+    prefix.drain_events();
+
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(
+        downloads(&events),
+        [(ROOT.to_string(), true), (DEPENDENCY.to_string(), true)]
+    );
+    assert!(!events.iter().any(|event| matches!(
+        event,
+        AppEvent::Lifecycle(LifecycleEvent::Download {
+            stage: LifecycleStage::Started,
+            ..
+        })
+    )));
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(1)), (DEPENDENCY.to_string(), v(1))]
+    );
+
+    let cleared = sps2_ops::cleanup_downloads(&prefix.ctx, true)
+        .await
+        .unwrap();
+    assert!(
+        cleared.starts_with("Removed 2 cached downloads"),
+        "{cleared}"
+    );
+    assert!(cache.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reposync_asks_before_accepting_changed_repository_keys() {
+    let prefix = TestPrefix::new(&spec()).await;
+
+    // Only the bootstrap key is trusted, so the fixture key is a change
+    let err = sps2_ops::reposync(&prefix.ctx, KeyChangePolicy::Refuse)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            sps2_errors::Error::Ops(sps2_errors::OpsError::RepositoryKeysChanged { .. })
+        ),
+        "{err}"
+    );
+
+    // Accepting does not trust a key no trusted key rotated to
+    let err = sps2_ops::reposync(&prefix.ctx, KeyChangePolicy::Accept)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            sps2_errors::Error::Signing(sps2_errors::SigningError::NoTrustedKeyFound { .. })
+        ),
+        "{err}"
+    );
+
+    prefix.trust_repository_key().await;
+    sps2_ops::reposync(&prefix.ctx, KeyChangePolicy::Refuse)
+        .await
+        .unwrap();
+
+    let state = prefix.ctx.state.get_active_state().await.unwrap();
+    let audit: Vec<String> = prefix
+        .ctx
+        .state
+        .state_audit(&state)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.audit.description)
+        .collect();
+    assert_eq!(audit.len(), 1, "{audit:?}");
+    assert!(audit[0].contains("untrusted"), "{audit:?}");
+}
+
+#[tokio::test]
+async fn install_from_dir_works_offline() {
+    let mut prefix = TestPrefix::from_dir(&spec()).await;
+
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(transitions(&events), ["install"]);
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(1)), (DEPENDENCY.to_string(), v(1))]
+    );
+    assert_eq!(content_version(&prefix.content_file(ROOT, 0)).await, v(1));
+
+    // Every package was copied out of the directory and none was cached
+    let urls: Vec<&str> = events
+        .iter()
+        .filter_map(|event| match event {
+            AppEvent::Lifecycle(LifecycleEvent::Download {
+                stage: LifecycleStage::Completed,
+                context,
+                ..
+            }) => Some(context.url.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert!(!urls.is_empty());
+    assert!(
+        urls.iter().all(|url| url.starts_with("file://")),
+        "{urls:?}"
+    );
+    let cache = sps2_net::DownloadCache::new(
+        prefix.ctx.config.downloads_path(),
+        std::time::Duration::from_secs(86_400),
+        u64::MAX,
+    );
+    assert!(cache.list().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn mirror_copies_a_package_closure_for_install_from_dir() {
+    let prefix = TestPrefix::new(&spec()).await;
+    let dest = tempfile::TempDir::new().unwrap();
+
+    // The index has to be signed by a trusted key
+    let err = sps2_ops::mirror(&prefix.ctx, REPOSITORY, dest.path(), &[])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, sps2_errors::Error::Signing(_)), "{err}");
+    prefix.trust_repository_key().await;
+
+    let summary = sps2_ops::mirror(
+        &prefix.ctx,
+        REPOSITORY,
+        dest.path(),
+        &[format!("{ROOT}==1.0.0")],
+    )
+    .await
+    .unwrap();
+    assert!(
+        summary.starts_with(&format!("Mirrored 2 packages from {REPOSITORY}")),
+        "{summary}"
+    );
+    let mut files: Vec<String> = std::fs::read_dir(dest.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 6, "{files:?}");
+    assert!(files.contains(&"index.json".to_string()));
+    assert!(files.contains(&"index.json.minisig".to_string()));
+    assert!(files
+        .iter()
+        .any(|file| file.starts_with(&format!("{ROOT}-1.0.0"))));
+
+    // A second run only fetches what went missing
+    let package = files
+        .iter()
+        .find(|file| file.starts_with(DEPENDENCY) && file.ends_with(".sp"))
+        .unwrap();
+    std::fs::remove_file(dest.path().join(package)).unwrap();
+    let summary = sps2_ops::mirror(
+        &prefix.ctx,
+        REPOSITORY,
+        dest.path(),
+        &[format!("{ROOT}==1.0.0")],
+    )
+    .await
+    .unwrap();
+    assert!(
+        summary.contains("(1 downloaded") && summary.ends_with("1 already present)"),
+        "{summary}"
+    );
+
+    // The mirror only offers what was copied
+    let index = sps2_ops::local_repository(&prefix.ctx.config, dest.path())
+        .await
+        .unwrap();
+    let versions: Vec<_> = index
+        .get_package_versions_with_strings(ROOT)
+        .unwrap()
+        .into_iter()
+        .map(|(version, _)| version.to_string())
+        .collect();
+    assert_eq!(versions, ["1.0.0"]);
+}
+
+#[tokio::test]
+async fn rollback_os_snapshot_explains_restoring_the_recorded_snapshot() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let guidance = sps2_ops::rollback_os_snapshot(&prefix.ctx, None)
+        .await
+        .unwrap();
+    assert!(
+        guidance.contains("state.os_snapshot_min_changes"),
+        "{guidance}"
+    );
+
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let installed = prefix.ctx.state.get_current_state_id().await.unwrap();
+    let guidance = sps2_ops::rollback_os_snapshot(&prefix.ctx, Some(installed))
+        .await
+        .unwrap();
+    assert_eq!(
+        guidance,
+        format!("No local snapshot was taken before state {installed}")
+    );
+
+    prefix
+        .ctx
+        .state
+        .record_os_snapshot(&installed, "2026-10-18-120000")
+        .await
+        .unwrap();
+    for state in [None, Some(installed)] {
+        let guidance = sps2_ops::rollback_os_snapshot(&prefix.ctx, state)
+            .await
+            .unwrap();
+        assert!(
+            guidance.starts_with(&format!(
+                "Local snapshot 2026-10-18-120000 was taken before state {installed} (install,"
+            )),
+            "{guidance}"
+        );
+        assert!(guidance.contains("macOS Recovery"), "{guidance}");
+    }
+}
+
+#[tokio::test]
+async fn package_diff_reports_changed_missing_and_extra_files() {
+    let prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    let clean = sps2_ops::package_diff(&prefix.ctx, ROOT, true)
+        .await
+        .unwrap();
+    assert!(clean.is_clean(), "differences: {:?}", clean.differences);
+    assert_eq!(clean.files_compared, 3);
+
+    let relative = |path: &std::path::Path| {
+        path.strip_prefix(prefix.live_path())
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+    let edited = prefix.content_file(ROOT, 0);
+    let original = tokio::fs::read_to_string(&edited).await.unwrap();
+    tokio::fs::remove_file(&edited).await.unwrap();
+    tokio::fs::write(&edited, format!("{original}local edit\n"))
+        .await
+        .unwrap();
+    let chmodded = prefix.content_file(ROOT, 1);
+    std::fs::set_permissions(&chmodded, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let removed = prefix.content_file(ROOT, 2);
+    tokio::fs::remove_file(&removed).await.unwrap();
+    let extra = edited.with_file_name("notes.txt");
+    tokio::fs::write(&extra, "scratch\n").await.unwrap();
+
+    let diff = sps2_ops::package_diff(&prefix.ctx, ROOT, true)
+        .await
+        .unwrap();
+    assert!(!diff.is_clean());
+    let changes: Vec<(String, &str)> = diff
+        .differences
+        .iter()
+        .map(|file| {
+            let change = match file.change {
+                FileChange::HashMismatch { .. } => "hash_mismatch",
+                FileChange::ModeChanged { .. } => "mode_changed",
+                FileChange::Missing => "missing",
+                FileChange::Extra => "extra",
+            };
+            (file.path.clone(), change)
+        })
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (relative(&edited), "hash_mismatch"),
+            (relative(&chmodded), "mode_changed"),
+            (relative(&removed), "missing"),
+            (relative(&extra), "extra"),
+        ]
+    );
+    let unified = diff.differences[0].unified.as_deref().unwrap();
+    assert!(unified.contains("local edit"), "{unified}");
+
+    assert!(sps2_ops::package_diff(&prefix.ctx, "not-installed", false)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn scheduled_verification_follows_guard_policy() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let mut guard = GuardConfiguration {
+        schedule: GuardScheduleConfig {
+            verify_on_boot: true,
+            ..GuardScheduleConfig::default()
+        },
+        ..GuardConfiguration::default()
+    };
+    prefix.ctx.config.guard = Some(guard.clone());
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    assert!(verification_runs(&prefix.ctx).await.is_empty());
+
+    // Nothing has verified since boot: the first check runs and warns
+    tokio::fs::remove_file(prefix.content_file(ROOT, 0))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let result = sps2_ops::scheduled_verification(&prefix.ctx).await.unwrap();
+    assert!(!result.is_valid);
+    assert!(prefix.drain_events().iter().any(|event| matches!(
+        event,
+        AppEvent::General(GeneralEvent::Warning { message, .. }) if message.contains("after boot")
+    )));
+    assert_eq!(verification_runs(&prefix.ctx).await[0].level, "quick");
+    assert!(sps2_ops::scheduled_verification(&prefix.ctx)
+        .await
+        .is_none());
+
+    guard.schedule.verify_after_install = true;
+    prefix.ctx.config.guard = Some(guard);
+    sps2_ops::uninstall(&prefix.ctx, &[ROOT.to_string()], DependentsPolicy::Block)
+        .await
+        .unwrap();
+    assert_eq!(verification_runs(&prefix.ctx).await.len(), 1);
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    let runs = verification_runs(&prefix.ctx).await;
+    assert_eq!(runs.len(), 2);
+    assert_eq!(runs[0].counts.total(), 0);
+}
+
+#[tokio::test]
+async fn owns_maps_paths_to_packages_and_finds_orphans() {
+    let prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    let root_file = prefix.content_file(ROOT, 1);
+    let relative = |path: &std::path::Path| {
+        path.strip_prefix(prefix.live_path())
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    };
+
+    // Absolute paths resolve to the package and version installed
+    let ownership = sps2_ops::owns(&prefix.ctx, root_file.to_str(), false)
+        .await
+        .unwrap();
+    assert_eq!(ownership.query, relative(&root_file));
+    assert_eq!(ownership.owners.len(), 1);
+    assert_eq!(ownership.owners[0].package, ROOT);
+    assert_eq!(ownership.owners[0].version, v(1).to_string());
+
+    // A glob spans packages; a directory covers the files below it
+    let ownership = sps2_ops::owns(
+        &prefix.ctx,
+        Some("opt/pm/live/share/*/file-0000.dat"),
+        false,
+    )
+    .await
+    .unwrap();
+    let packages: Vec<&str> = ownership
+        .owners
+        .iter()
+        .map(|owner| owner.package.as_str())
+        .collect();
+    assert_eq!(packages, vec![ROOT, DEPENDENCY]);
+    let directory = root_file.parent().unwrap();
+    let ownership = sps2_ops::owns(&prefix.ctx, directory.to_str(), false)
+        .await
+        .unwrap();
+    assert!(ownership.owners.iter().all(|owner| owner.package == ROOT));
+    let files = ownership
+        .owners
+        .iter()
+        .filter(|owner| owner.path.ends_with(".dat"))
+        .count();
+    assert_eq!(files, 3);
+
+    // Files no package installed are orphans, found directly or by listing
+    let stray = root_file.with_file_name("stray.log");
+    tokio::fs::write(&stray, "stray\n").await.unwrap();
+    let ownership = sps2_ops::owns(&prefix.ctx, stray.to_str(), false)
+        .await
+        .unwrap();
+    assert!(ownership.owners.is_empty());
+    assert_eq!(ownership.orphans, vec![relative(&stray)]);
+    let ownership = sps2_ops::owns(&prefix.ctx, None, true).await.unwrap();
+    assert_eq!(ownership.orphans, vec![relative(&stray)]);
+    let ownership = sps2_ops::owns(&prefix.ctx, Some("**/*.dat"), true)
+        .await
+        .unwrap();
+    assert!(ownership.orphans.is_empty());
+
+    assert!(sps2_ops::owns(&prefix.ctx, Some("/elsewhere/file"), false)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn package_files_lists_installed_files_with_store_metadata() {
+    let prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+
+    let listing = sps2_ops::package_files(&prefix.ctx, ROOT, false)
+        .await
+        .unwrap();
+    assert_eq!(listing.package, ROOT);
+    assert_eq!(listing.version, v(1).to_string());
+
+    let files: Vec<_> = listing
+        .files
+        .iter()
+        .filter(|file| !file.is_directory)
+        .collect();
+    assert_eq!(files.len(), 3);
+    let root_file = prefix.content_file(ROOT, 1);
+    let contents = tokio::fs::read(&root_file).await.unwrap();
+    let file = files
+        .iter()
+        .find(|file| prefix.live_path().join(&file.path) == root_file)
+        .unwrap();
+    assert_eq!(file.size, Some(contents.len() as u64));
+    let hash = sps2_hash::Hash::hash_file(&root_file).await.unwrap();
+    assert_eq!(file.hash.as_deref(), Some(hash.to_hex().as_str()));
+    assert!(!file.executable);
+    assert_eq!(
+        listing.total_size,
+        files.iter().filter_map(|file| file.size).sum::<u64>()
+    );
+
+    // Directories are listed without content
+    assert!(listing
+        .files
+        .iter()
+        .any(|file| file.is_directory && file.hash.is_none() && file.size.is_none()));
+
+    assert!(sps2_ops::package_files(&prefix.ctx, "not-installed", false)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn require_hashes_refuses_unpinned_artifacts() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let index = prefix.ctx.index().await.unwrap();
+    let pin = |name: &str| {
+        let entry = index.get_version(name, &v(1).to_string()).unwrap();
+        format!("{name} blake3:{}\n", entry.blake3)
+    };
+    let root_pin = pin(ROOT);
+    let dependency_pin = pin(DEPENDENCY);
+
+    // Dependencies must be pinned too, even though the index vouches for them
+    let required = sps2_ops::RequiredHashes::parse(&root_pin, "pins").unwrap();
+    let err = sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, Some(&required))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            sps2_errors::Error::Install(sps2_errors::InstallError::HashNotPinned { package, .. })
+                if package == DEPENDENCY
+        ),
+        "unexpected error {err:?}"
+    );
+    assert!(prefix.installed().await.is_empty());
+
+    let wrong = format!("{ROOT} --hash=blake3:{}\n{dependency_pin}", "0".repeat(64));
+    let required = sps2_ops::RequiredHashes::parse(&wrong, "pins").unwrap();
+    assert!(
+        sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, Some(&required))
+            .await
+            .is_err()
+    );
+    assert!(prefix.installed().await.is_empty());
+
+    let pins = format!("# reviewed\n{root_pin}{dependency_pin}");
+    let required = sps2_ops::RequiredHashes::parse(&pins, "pins").unwrap();
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, Some(&required))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(1)), (DEPENDENCY.to_string(), v(1))]
+    );
+
+    assert!(sps2_ops::RequiredHashes::parse(&format!("{ROOT}\n"), "pins").is_err());
+    assert!(sps2_ops::RequiredHashes::parse(&format!("{ROOT} sha256:ab\n"), "pins").is_err());
+}
+
+#[tokio::test]
+async fn store_relocate_updates_the_config_in_use_or_leaves_the_store() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let old_store = prefix.ctx.store.base_path().to_path_buf();
+    let config_path = prefix.ctx.config.file_path().unwrap();
+    let target = tempfile::TempDir::new().unwrap();
+
+    // A configuration that cannot be saved leaves the old store in use
+    let blocker = config_path.with_extension("toml.tmp");
+    tokio::fs::create_dir_all(&blocker).await.unwrap();
+    let new_store = target.path().join("store");
+    assert!(sps2_ops::store_relocate(&prefix.ctx, &new_store, false)
+        .await
+        .is_err());
+    assert!(!new_store.exists());
+    assert!(old_store.join("objects").exists());
+    tokio::fs::remove_dir(&blocker).await.unwrap();
+
+    // Without a configuration file one is created
+    sps2_ops::store_relocate(&prefix.ctx, &new_store, false)
+        .await
+        .unwrap();
+    let config = sps2_config::Config::load_from_file(&config_path)
+        .await
+        .unwrap();
+    assert_eq!(
+        config.paths.store_path.as_deref(),
+        Some(new_store.as_path())
+    );
+    assert!(new_store.join("objects").exists());
+    assert!(!old_store.exists());
+}
+
+#[tokio::test]
+async fn pack_names_the_package_after_its_manifest_arch() {
+    let prefix = TestPrefix::new(&spec()).await;
+    let temp = tempfile::TempDir::new().unwrap();
+    let dir = temp.path().join("stage");
+    tokio::fs::create_dir_all(dir.join("bin")).await.unwrap();
+    tokio::fs::write(dir.join("bin/tool"), b"#!/bin/sh\n")
+        .await
+        .unwrap();
+    let manifest_path = temp.path().join("manifest.toml");
+    let manifest = sps2_types::Manifest::new(
+        "tool".to_string(),
+        &Version::new(1, 0, 0),
+        1,
+        &sps2_types::Arch::X86_64,
+    );
+    sps2_store::manifest_io::write_manifest(&manifest_path, &manifest)
+        .await
+        .unwrap();
+
+    let out = temp.path().join("out");
+    tokio::fs::create_dir_all(&out).await.unwrap();
+    let report = sps2_ops::pack_from_directory(&prefix.ctx, &dir, &manifest_path, Some(&out))
+        .await
+        .unwrap();
+    assert_eq!(report.output_path, out.join("tool-1.0.0-1.x86_64.sp"));
+    assert!(report.output_path.exists());
+}
+
+#[tokio::test]
+async fn require_hashes_applies_to_local_files() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let name = {
+        let index = prefix.ctx.index().await.unwrap();
+        let entry = index.get_version(DEPENDENCY, &v(1).to_string()).unwrap();
+        entry.download_url.rsplit('/').next().unwrap().to_string()
+    };
+    // Built packages are zstd-compressed; fixture packages are plain tar
+    let dir = tempfile::TempDir::new().unwrap();
+    let file = dir.path().join(&name);
+    let status = std::process::Command::new("zstd")
+        .arg("-q")
+        .arg("-o")
+        .arg(&file)
+        .arg(prefix.repository_dir().join(&name))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let hash = sps2_hash::Hash::blake3_hash_file(&file).await.unwrap();
+    let file = file.display().to_string();
+
+    let wrong = format!("{DEPENDENCY} blake3:{}\n", "0".repeat(64));
+    let required = sps2_ops::RequiredHashes::parse(&wrong, "pins").unwrap();
+    let err = sps2_ops::install(
+        &prefix.ctx,
+        std::slice::from_ref(&file),
+        false,
+        Some(&required),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            sps2_errors::Error::Install(sps2_errors::InstallError::HashNotPinned { package, .. })
+                if package == DEPENDENCY
+        ),
+        "unexpected error {err:?}"
+    );
+    assert!(prefix.installed().await.is_empty());
+    prefix.drain_events();
+
+    let pins = format!("{DEPENDENCY} blake3:{}\n", hash.to_hex());
+    let required = sps2_ops::RequiredHashes::parse(&pins, "pins").unwrap();
+    sps2_ops::install(&prefix.ctx, &[file], false, Some(&required))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(prefix.installed().await, [(DEPENDENCY.to_string(), v(1))]);
+}
+
+#[tokio::test]
+async fn system_sbom_follows_every_state_change() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let path = prefix
+        .ctx
+        .state
+        .state_path()
+        .parent()
+        .unwrap()
+        .join("sbom.spdx.json");
+    let read = || {
+        let document: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        let mut names: Vec<String> = document["packages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        (document["name"].as_str().unwrap().to_string(), names)
+    };
+
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let state = prefix.ctx.state.get_current_state_id().await.unwrap();
+    let (name, packages) = read();
+    assert_eq!(name, format!("sps2-state-{state}"));
+    assert!(packages.contains(&ROOT.to_string()));
+    assert!(packages.contains(&DEPENDENCY.to_string()));
+
+    sps2_ops::uninstall(&prefix.ctx, &[ROOT.to_string()], DependentsPolicy::Block)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let state = prefix.ctx.state.get_current_state_id().await.unwrap();
+    let (name, packages) = read();
+    assert_eq!(name, format!("sps2-state-{state}"));
+    assert!(!packages.contains(&ROOT.to_string()));
+
+    // Exporting leaves the system SBOM alone
+    std::fs::remove_file(&path).unwrap();
+    let exported = sps2_ops::sbom_export(&prefix.ctx, sps2_types::SbomFormat::Spdx, None)
+        .await
+        .unwrap();
+    assert!(exported.contains(&format!("sps2-state-{state}")));
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn uninstall_blocks_cascades_or_forces_removing_a_dependency() {
+    use sps2_ops::PlannedOperation;
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    let dependency = [DEPENDENCY.to_string()];
+    let err = sps2_ops::uninstall(&prefix.ctx, &dependency, DependentsPolicy::Block)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            &err,
+            sps2_errors::Error::Install(sps2_errors::InstallError::PackageHasDependents {
+                package,
+                dependents,
+            }) if package == DEPENDENCY && dependents == ROOT
+        ),
+        "unexpected error: {err}"
+    );
+    assert_eq!(prefix.installed().await.len(), 2);
+
+    // The confirmation prompt shows the dependents a cascade removes
+    let plan = sps2_ops::change_plan(
+        &prefix.ctx,
+        PlannedOperation::Uninstall(&dependency, DependentsPolicy::Cascade),
+    )
+    .await
+    .unwrap();
+    prefix.drain_events();
+    let mut removed: Vec<&str> = plan.remove.iter().map(|c| c.name.as_str()).collect();
+    removed.sort_unstable();
+    assert_eq!(removed, [ROOT, DEPENDENCY]);
+    assert!(plan.broken_dependencies.is_empty());
+
+    let report = sps2_ops::uninstall(&prefix.ctx, &dependency, DependentsPolicy::Force)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(report.removed.len(), 1);
+    assert_eq!(report.removed[0].name, DEPENDENCY);
+    assert_eq!(report.broken_dependencies.len(), 1);
+    assert_eq!(report.broken_dependencies[0].package, ROOT);
+    assert_eq!(report.broken_dependencies[0].needs, [DEPENDENCY]);
+    assert_eq!(prefix.installed().await.len(), 1);
+}
+
+#[tokio::test]
+async fn forced_uninstall_passes_over_unreadable_packages() {
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let installed = prefix.ctx.state.get_installed_packages().await.unwrap();
+    let root = installed.iter().find(|p| p.name == ROOT).unwrap();
+    let hash = sps2_hash::Hash::from_hex(&root.hash).unwrap();
+    // A dependency without a name cannot be parsed
+    let manifest = prefix.ctx.store.package_path(&hash).join("manifest.toml");
+    let content = std::fs::read_to_string(&manifest).unwrap();
+    assert!(content.contains(DEPENDENCY));
+    std::fs::write(&manifest, content.replace(DEPENDENCY, "")).unwrap();
+
+    let dependency = [DEPENDENCY.to_string()];
+    assert!(
+        sps2_ops::uninstall(&prefix.ctx, &dependency, DependentsPolicy::Block)
+            .await
+            .is_err()
+    );
+    assert_eq!(prefix.installed().await.len(), 2);
+
+    let report = sps2_ops::uninstall(&prefix.ctx, &dependency, DependentsPolicy::Force)
+        .await
+        .unwrap();
+    let events = prefix.drain_events();
+    assert_eq!(report.removed.len(), 1);
+    assert_eq!(report.removed[0].name, DEPENDENCY);
+    assert!(events.iter().any(|event| matches!(
+        event,
+        AppEvent::General(GeneralEvent::Warning { message, .. })
+            if message.contains(ROOT) && message.contains("manifest cannot be read")
+    )));
+    assert_eq!(prefix.installed().await.len(), 1);
+}
+
+#[tokio::test]
+async fn autoremove_takes_dependencies_nothing_needs_any_more() {
+    use sps2_ops::PlannedOperation;
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let installed = prefix.ctx.state.get_installed_packages().await.unwrap();
+    let dependency = |name: &str| {
+        installed
+            .iter()
+            .find(|package| package.name == name)
+            .is_some_and(|package| package.dependency)
+    };
+    assert!(!dependency(ROOT));
+    assert!(dependency(DEPENDENCY));
+
+    // The dependency is still needed
+    let report = sps2_ops::autoremove(&prefix.ctx).await.unwrap();
+    prefix.drain_events();
+    assert!(report.removed.is_empty());
+    assert_eq!(prefix.installed().await.len(), 2);
+
+    sps2_ops::uninstall(&prefix.ctx, &[ROOT.to_string()], DependentsPolicy::Block)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let plan = sps2_ops::change_plan(&prefix.ctx, PlannedOperation::Autoremove)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(plan.remove.len(), 1);
+    assert_eq!(plan.remove[0].name, DEPENDENCY);
+    assert_eq!(prefix.installed().await.len(), 1);
+
+    let report = sps2_ops::autoremove(&prefix.ctx).await.unwrap();
+    prefix.drain_events();
+    assert_eq!(report.removed.len(), 1);
+    assert_eq!(report.removed[0].name, DEPENDENCY);
+    assert!(prefix.installed().await.is_empty());
+}
+
+#[tokio::test]
+async fn apply_commits_a_change_set_as_one_transition() {
+    use sps2_ops::{ChangeSet, PlannedOperation};
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    sps2_ops::install(&prefix.ctx, &[format!("{DEPENDENCY}==1.0.0")], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let before = prefix.ctx.state.get_current_state_id().await.unwrap();
+
+    // Removing a package the same change installs something needing
+    let conflicting = ChangeSet {
+        install: vec![ROOT.to_string()],
+        remove: vec![DEPENDENCY.to_string()],
+        ..ChangeSet::default()
+    };
+    let err = sps2_ops::apply(&prefix.ctx, &conflicting, false)
+        .await
+        .unwrap_err();
+    prefix.drain_events();
+    assert!(
+        matches!(
+            err,
+            sps2_errors::Error::Install(sps2_errors::InstallError::RemovedPackageNeeded { .. })
+        ),
+        "{err}"
+    );
+    assert_eq!(
+        prefix.ctx.state.get_current_state_id().await.unwrap(),
+        before
+    );
+
+    let changes = ChangeSet {
+        install: vec![ROOT.to_string()],
+        upgrade: vec![DEPENDENCY.to_string()],
+        ..ChangeSet::default()
+    };
+    let plan = sps2_ops::change_plan(&prefix.ctx, PlannedOperation::Apply(&changes, false))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(plan.install.len(), 1);
+    assert_eq!(plan.install[0].name, ROOT);
+    assert_eq!(plan.upgrade.len(), 1);
+    assert_eq!(plan.upgrade[0].name, DEPENDENCY);
+    assert_eq!(plan.upgrade[0].from_version, Some(v(0)));
+
+    let report = sps2_ops::apply(&prefix.ctx, &changes, false).await.unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(transitions(&events), ["apply"]);
+    assert_eq!(report.installed.len(), 1);
+    assert_eq!(report.updated.len(), 1);
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(1)), (DEPENDENCY.to_string(), v(1))]
+    );
+
+    let removal = ChangeSet {
+        remove: vec![ROOT.to_string(), DEPENDENCY.to_string()],
+        ..ChangeSet::default()
+    };
+    let report = sps2_ops::apply(&prefix.ctx, &removal, false).await.unwrap();
+    let events = prefix.drain_events();
+    assert_eq!(transitions(&events), ["apply"]);
+    assert_eq!(report.removed.len(), 2);
+    assert!(prefix.installed().await.is_empty());
+}
+
+#[tokio::test]
+async fn sync_brings_installed_packages_in_line_with_a_manifest() {
+    use sps2_ops::{Manifest, PlannedOperation};
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let dir = tempfile::TempDir::new().unwrap();
+    let manifest_path = dir.path().join("manifest.toml");
+    let manifest = |packages: &str| {
+        std::fs::write(&manifest_path, format!("packages = [{packages}]\n")).unwrap();
+        Manifest::load(&manifest_path)
+    };
+    sps2_ops::install(&prefix.ctx, &[ROOT.to_string()], false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    // The dependency is listed, so it stays and counts as asked for
+    let only_dependency = manifest(&format!("\"{DEPENDENCY}\"")).await.unwrap();
+    let report = sps2_ops::sync(&prefix.ctx, &only_dependency).await.unwrap();
+    let events = prefix.drain_events();
+    assert_no_failures(&events);
+    assert_eq!(transitions(&events), ["apply"]);
+    assert_eq!(report.removed.len(), 1);
+    assert_eq!(report.removed[0].name, ROOT);
+    assert_eq!(prefix.installed().await, [(DEPENDENCY.to_string(), v(1))]);
+    let installed = prefix.ctx.state.get_installed_packages().await.unwrap();
+    assert!(!installed[0].dependency);
+
+    // In line with the manifest, nothing changes
+    let before = prefix.ctx.state.get_current_state_id().await.unwrap();
+    let plan = sps2_ops::change_plan(&prefix.ctx, PlannedOperation::Sync(&only_dependency))
+        .await
+        .unwrap();
+    assert!(plan.is_empty());
+    let report = sps2_ops::sync(&prefix.ctx, &only_dependency).await.unwrap();
+    prefix.drain_events();
+    assert!(report.installed.is_empty() && report.removed.is_empty());
+    assert_eq!(
+        prefix.ctx.state.get_current_state_id().await.unwrap(),
+        before
+    );
+
+    // The unlisted dependency is kept for the package that needs it
+    let only_root = manifest(&format!("\"{ROOT}==1.0.0\"")).await.unwrap();
+    let report = sps2_ops::sync(&prefix.ctx, &only_root).await.unwrap();
+    prefix.drain_events();
+    assert!(report.removed.is_empty());
+    assert_eq!(
+        prefix.installed().await,
+        [(ROOT.to_string(), v(0)), (DEPENDENCY.to_string(), v(1))]
+    );
+}
+
+#[tokio::test]
+async fn change_plan_lists_changes_without_applying_them() {
+    use sps2_ops::PlannedOperation;
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let names = |changes: &[sps2_ops::PackageChange]| {
+        let mut names: Vec<String> = changes.iter().map(|c| c.name.clone()).collect();
+        names.sort();
+        names
+    };
+
+    let root = [ROOT.to_string()];
+    let plan = sps2_ops::change_plan(&prefix.ctx, PlannedOperation::Install(&root))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let mut expected = vec![ROOT.to_string(), DEPENDENCY.to_string()];
+    expected.sort();
+    assert_eq!(names(&plan.install), expected);
+    assert!(plan.upgrade.is_empty() && plan.remove.is_empty());
+    assert!(prefix.installed().await.is_empty());
+
+    sps2_ops::install(&prefix.ctx, &root, false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    let plan = sps2_ops::change_plan(
+        &prefix.ctx,
+        PlannedOperation::Uninstall(&root, DependentsPolicy::Block),
+    )
+    .await
+    .unwrap();
+    prefix.drain_events();
+    assert_eq!(names(&plan.remove), vec![ROOT.to_string()]);
+    assert!(plan.remove[0].size.is_some());
+
+    let plan = sps2_ops::change_plan(&prefix.ctx, PlannedOperation::Rollback(None))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert_eq!(names(&plan.remove), expected);
+    assert_eq!(prefix.installed().await.len(), 2);
+}
+
+#[tokio::test]
+async fn dry_run_reports_the_installer_plan_without_changing_anything() {
+    use sps2_ops::PlannedOperation;
+
+    let mut prefix = TestPrefix::new(&spec()).await;
+    let root = [ROOT.to_string()];
+    let before = prefix.ctx.state.get_current_state_id().await.unwrap();
+
+    let plan = sps2_ops::dry_run(&prefix.ctx, PlannedOperation::Install(&root))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    let mut expected = vec![ROOT.to_string(), DEPENDENCY.to_string()];
+    expected.sort();
+    let installs: Vec<String> = plan
+        .changes
+        .install
+        .iter()
+        .map(|c| c.name.clone())
+        .collect();
+    assert_eq!(installs, expected);
+    let downloads: Vec<String> = plan.downloads.iter().map(|d| d.name.clone()).collect();
+    assert_eq!(downloads, expected);
+    assert_eq!(plan.state_id, before);
+    assert!(prefix.installed().await.is_empty());
+    assert_eq!(
+        prefix.ctx.state.get_current_state_id().await.unwrap(),
+        before
+    );
+
+    sps2_ops::install(&prefix.ctx, &root, false, None)
+        .await
+        .unwrap();
+    prefix.drain_events();
+
+    // Installed packages are reused from the store rather than downloaded
+    let plan = sps2_ops::dry_run(&prefix.ctx, PlannedOperation::Install(&root))
+        .await
+        .unwrap();
+    prefix.drain_events();
+    assert!(plan.downloads.is_empty());
+
+    let plan = sps2_ops::dry_run(
+        &prefix.ctx,
+        PlannedOperation::Uninstall(&root, DependentsPolicy::Block),
+    )
+    .await
+    .unwrap();
+    prefix.drain_events();
+    assert_eq!(plan.changes.remove.len(), 1);
+    assert_eq!(plan.changes.remove[0].name, ROOT);
+    assert!(plan.removed_bytes > 0);
+    assert_eq!(prefix.installed().await.len(), 2);
+}
+
+#[tokio::test]
+async fn init_sets_up_an_alternate_root_and_can_be_rerun() {
+    use sps2_ops::InitStatus;
+
+    let root = tempfile::TempDir::new().unwrap();
+    let mut config = sps2_config::Config::default();
+    config.paths.root = Some(root.path().to_path_buf());
+    let config_file = root.path().join("config.toml");
+    std::fs::write(&config_file, "").unwrap();
+    let config_files = [(config_file, true)];
+
+    let first = sps2_ops::init(&config, &config_files).await;
+    assert!(first.is_ready(), "{:?}", first.steps);
+    assert_eq!(first.prefix, root.path().join("opt/pm"));
+    let status = |report: &sps2_ops::InitReport, name: &str| {
+        report
+            .steps
+            .iter()
+            .find(|step| step.name == name)
+            .map(|step| step.status)
+    };
+    assert_eq!(status(&first, "prefix"), Some(InitStatus::Created));
+    assert_eq!(status(&first, "state database"), Some(InitStatus::Created));
+    assert_eq!(status(&first, "config file"), Some(InitStatus::Created));
+    assert_eq!(status(&first, "trusted keys"), Some(InitStatus::Created));
+    assert!(config.db_path().exists());
+    assert!(config.live_path().is_dir());
+    assert!(config.keys_path().join("trusted_keys.json").exists());
+
+    let config_files = [(root.path().join("config.toml"), false)];
+    let second = sps2_ops::init(&config, &config_files).await;
+    assert!(second.is_ready(), "{:?}", second.steps);
+    assert!(second
+        .steps
+        .iter()
+        .filter(|step| !matches!(step.name.as_str(), "tools" | "clonefile"))
+        .all(|step| step.status == InitStatus::Ok));
+}
+
+#[tokio::test]
+async fn self_destruct_keeps_the_store_and_unrelated_shell_lines() {
+    let root = tempfile::TempDir::new().unwrap();
+    let home = tempfile::TempDir::new().unwrap();
+    let mut config = sps2_config::Config::default();
+    config.paths.root = Some(root.path().to_path_buf());
+    assert!(sps2_ops::init(&config, &[]).await.is_ready());
+    std::fs::write(config.store_path().join("blob"), b"kept").unwrap();
+    let plist = root.path().join("LaunchAgents/org.example.daemon.plist");
+    std::fs::create_dir_all(plist.parent().unwrap()).unwrap();
+    std::fs::write(&plist, b"<plist/>").unwrap();
+    let state = sps2_state::StateManager::new(&config.prefix_path())
+        .await
+        .unwrap();
+    state
+        .record_service(&sps2_state::ServiceRecord {
+            label: "org.example.daemon".to_string(),
+            domain: "gui/501".to_string(),
+            package_name: "daemon".to_string(),
+            plist_path: plist.display().to_string(),
+            enabled: true,
+            rendered_at: 0,
+        })
+        .await
+        .unwrap();
+    drop(state);
+
+    let zshrc = home.path().join(".zshrc");
+    let path_line = format!("export PATH=\"{}:$PATH\"", config.bin_path().display());
+    std::fs::write(
+        &zshrc,
+        format!("alias ll='ls -l'\n{path_line}\nexport EDITOR=vi\neval \"$(sps2 env)\"\n"),
+    )
+    .unwrap();
+
+    let plan = sps2_ops::self_destruct(&config, Some(home.path()), true, true).await;
+    assert!(plan.dry_run && plan.is_complete());
+    assert!(plan.removals.iter().any(|r| r.path == config.live_path()));
+    assert!(plan.removals.iter().all(|r| r.path != config.store_path()));
+    assert!(plan
+        .removals
+        .iter()
+        .any(|r| r.path == plist && r.what == "service org.example.daemon"));
+    assert!(config.live_path().is_dir());
+    assert!(plist.exists());
+    assert!(std::fs::read_to_string(&zshrc)
+        .unwrap()
+        .contains(&path_line));
+
+    let report = sps2_ops::self_destruct(&config, Some(home.path()), true, false).await;
+    assert!(report.is_complete(), "{:?}", report.removals);
+    assert_eq!(report.kept_store, Some(config.store_path()));
+    assert!(!config.live_path().exists());
+    assert!(!config.db_path().exists());
+    assert!(!plist.exists());
+    assert!(config.store_path().join("blob").exists());
+    assert_eq!(
+        std::fs::read_to_string(&zshrc).unwrap(),
+        "alias ll='ls -l'\nexport EDITOR=vi\n"
+    );
+
+    let report = sps2_ops::self_destruct(&config, Some(home.path()), false, false).await;
+    assert!(report.is_complete(), "{:?}", report.removals);
+    assert!(!config.prefix_path().exists());
+}
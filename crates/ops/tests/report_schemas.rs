@@ -0,0 +1,251 @@
+//! tests/report_schemas.rs
+//!
+//! The `--json` output of every versioned report checked against its
+//! published JSON Schema, so the schemas and the serialized reports cannot
+//! drift apart. Checking is strict: a field the report prints but the schema
+//! does not declare fails, so new fields get documented.
+
+use serde_json::{json, Value};
+use sps2_ops::{
+    BrokenDependency, BuildReport, Discrepancy, InstallReport, OperationResult, PackageChange,
+    VerificationResult,
+};
+use sps2_types::{
+    report_schema, TestFailurePolicy, TestResults, TestStatus, Uuid, Version, REPORT_SCHEMAS,
+};
+use std::path::PathBuf;
+
+/// Describe every way `value` breaks `schema`, a subschema of `root`
+fn check(root: &Value, schema: &Value, value: &Value, at: &str, errors: &mut Vec<String>) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/$defs/");
+        return check(root, &root["$defs"][name], value, at, errors);
+    }
+    if let Some(expected) = schema.get("const") {
+        if value != expected {
+            errors.push(format!("{at}: {value} is not {expected}"));
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            errors.push(format!("{at}: {value} is not one of {allowed:?}"));
+        }
+    }
+    if let Some(options) = schema["oneOf"].as_array() {
+        let matching = options
+            .iter()
+            .filter(|option| {
+                let mut option_errors = Vec::new();
+                check(root, option, value, at, &mut option_errors);
+                option_errors.is_empty()
+            })
+            .count();
+        if matching != 1 {
+            errors.push(format!("{at}: {value} matches {matching} options of oneOf"));
+        }
+    }
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        let actual = match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(number) if number.is_u64() || number.is_i64() => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        };
+        if !types.contains(&actual) {
+            errors.push(format!("{at}: {actual} is not {types:?}"));
+        }
+    }
+    if let (Some(minimum), Some(number)) = (schema["minimum"].as_i64(), value.as_i64()) {
+        if number < minimum {
+            errors.push(format!("{at}: {number} is below {minimum}"));
+        }
+    }
+    if let (Some(items), Value::Array(elements)) = (schema.get("items"), value) {
+        for (index, element) in elements.iter().enumerate() {
+            check(root, items, element, &format!("{at}[{index}]"), errors);
+        }
+    }
+    if let Value::Object(fields) = value {
+        for required in schema["required"].as_array().into_iter().flatten() {
+            let name = required.as_str().unwrap_or_default();
+            if !fields.contains_key(name) {
+                errors.push(format!("{at}: missing {name}"));
+            }
+        }
+        if let Some(count) = schema["minProperties"].as_u64() {
+            if (fields.len() as u64) < count {
+                errors.push(format!("{at}: fewer than {count} fields"));
+            }
+        }
+        if let Some(count) = schema["maxProperties"].as_u64() {
+            if fields.len() as u64 > count {
+                errors.push(format!("{at}: more than {count} fields"));
+            }
+        }
+        if let Some(properties) = schema["properties"].as_object() {
+            for (name, field) in fields {
+                match properties.get(name) {
+                    Some(property) => check(root, property, field, &format!("{at}.{name}"), errors),
+                    None => errors.push(format!("{at}: {name} is not declared")),
+                }
+            }
+        }
+    }
+}
+
+/// Assert that `result` prints as a report following the schema `name`
+fn assert_follows_schema(name: &str, result: OperationResult) {
+    let schema = report_schema(name).expect("schema exists");
+    let root: Value = serde_json::from_str(schema.schema).expect("schema is JSON");
+    let output: Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+
+    assert_eq!(output["type"], schema.kind);
+    assert_eq!(output["data"]["schema_version"], schema.version);
+    let mut errors = Vec::new();
+    check(&root, &root, &output["data"], "data", &mut errors);
+    assert!(errors.is_empty(), "{name}: {errors:#?}");
+}
+
+fn change(name: &str, from: Option<u64>, to: Option<u64>) -> PackageChange {
+    PackageChange {
+        name: name.to_string(),
+        from_version: from.map(|minor| Version::new(1, minor, 0)),
+        to_version: to.map(|minor| Version::new(1, minor, 0)),
+        size: to.map(|_| 4096),
+    }
+}
+
+fn install_report(broken_dependencies: Vec<BrokenDependency>) -> InstallReport {
+    InstallReport {
+        schema_version: InstallReport::SCHEMA_VERSION,
+        installed: vec![change("jq", None, Some(7))],
+        updated: vec![change("curl", Some(1), Some(2))],
+        removed: vec![change("wget", Some(3), None)],
+        broken_dependencies,
+        state_id: Uuid::nil(),
+        duration_ms: 1200,
+    }
+}
+
+fn build_report(tests: Option<TestResults>) -> BuildReport {
+    BuildReport {
+        schema_version: BuildReport::SCHEMA_VERSION,
+        package: "jq".to_string(),
+        version: Version::new(1, 7, 1),
+        output_path: PathBuf::from("/tmp/jq-1.7.1-1.arm64.sp"),
+        duration_ms: 42_000,
+        tests,
+    }
+}
+
+#[test]
+fn install_reports_follow_their_schema() {
+    assert_follows_schema(
+        "install-report",
+        OperationResult::InstallReport(install_report(Vec::new())),
+    );
+    assert_follows_schema(
+        "install-report",
+        OperationResult::InstallReport(install_report(vec![BrokenDependency {
+            package: "git".to_string(),
+            needs: vec!["openssl".to_string()],
+        }])),
+    );
+}
+
+#[test]
+fn build_reports_follow_their_schema() {
+    assert_follows_schema(
+        "build-report",
+        OperationResult::BuildReport(build_report(None)),
+    );
+    assert_follows_schema(
+        "build-report",
+        OperationResult::BuildReport(build_report(Some(TestResults {
+            status: TestStatus::Failed,
+            policy: TestFailurePolicy::Warn,
+            duration_ms: 300,
+            failure: Some("make check exited with 2".to_string()),
+        }))),
+    );
+}
+
+#[test]
+fn verification_results_follow_their_schema() {
+    let (package, version) = ("jq".to_string(), "1.7.1".to_string());
+    let discrepancies = vec![
+        Discrepancy::MissingFile {
+            package: package.clone(),
+            version: version.clone(),
+            path: "bin/jq".to_string(),
+        },
+        Discrepancy::CorruptedFile {
+            package: package.clone(),
+            version: version.clone(),
+            path: "share/man/man1/jq.1".to_string(),
+        },
+        Discrepancy::PermissionsChanged {
+            package: package.clone(),
+            version: version.clone(),
+            path: "libexec/jq-helper".to_string(),
+        },
+        Discrepancy::MissingPackageContent { package, version },
+        Discrepancy::UnexpectedFile {
+            path: "bin/stray".to_string(),
+        },
+    ];
+    assert_follows_schema(
+        "verification-result",
+        OperationResult::VerificationResult(
+            VerificationResult::new(Uuid::nil(), discrepancies, 90).with_healed(2),
+        ),
+    );
+    assert_eq!(
+        report_schema("verification-result").unwrap().version,
+        VerificationResult::SCHEMA_VERSION
+    );
+}
+
+#[test]
+fn reports_from_before_versioning_still_parse() {
+    let report: InstallReport = serde_json::from_value(json!({
+        "installed": [{ "name": "jq", "from_version": null, "to_version": "1.7.0", "size": 4096 }],
+        "updated": [],
+        "removed": [],
+        "state_id": "00000000-0000-0000-0000-000000000000",
+        "duration_ms": 5
+    }))
+    .unwrap();
+    assert_eq!(report.schema_version, 1);
+
+    let report: BuildReport = serde_json::from_value(json!({
+        "package": "jq",
+        "version": "1.7.1",
+        "output_path": "/tmp/jq-1.7.1-1.arm64.sp",
+        "duration_ms": 5
+    }))
+    .unwrap();
+    assert_eq!(report.schema_version, 1);
+}
+
+#[test]
+fn schemas_are_named_uniquely_and_pin_their_version() {
+    for (index, schema) in REPORT_SCHEMAS.iter().enumerate() {
+        assert!(REPORT_SCHEMAS[..index]
+            .iter()
+            .all(|other| other.name != schema.name));
+        let root: Value = serde_json::from_str(schema.schema).unwrap();
+        assert_eq!(root["title"], schema.kind);
+        assert_eq!(
+            root["properties"]["schema_version"]["const"],
+            schema.version
+        );
+    }
+}
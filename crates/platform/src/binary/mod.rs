@@ -6,6 +6,19 @@ use std::path::Path;
 
 use crate::core::PlatformContext;
 
+/// What a binary's code signature carries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SignatureInfo {
+    /// Whether the binary has a code signature at all, valid or not
+    pub signed: bool,
+    /// Whether the signature is ad hoc rather than made with an identity
+    pub adhoc: bool,
+    /// Whether the hardened runtime flag is set
+    pub hardened_runtime: bool,
+    /// Entitlements plist embedded in the signature
+    pub entitlements: Option<String>,
+}
+
 /// Trait for binary manipulation operations specific to macOS
 #[async_trait]
 pub trait BinaryOperations: Send + Sync {
@@ -70,7 +83,15 @@ pub trait BinaryOperations: Send + Sync {
         binary: &Path,
     ) -> Result<bool, PlatformError>;
 
-    /// Sign binary using codesign
+    /// Read what the code signature carries using codesign -d
+    async fn signature_info(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<SignatureInfo, PlatformError>;
+
+    /// Sign binary using codesign, keeping the entitlements and hardened
+    /// runtime flags of an existing signature
     async fn sign_binary(
         &self,
         ctx: &PlatformContext,
@@ -293,6 +293,12 @@ impl ToolRegistry {
             fallback_paths.insert(tool.to_string(), build_paths.clone());
         }
 
+        // ELF editing on Linux
+        fallback_paths.insert(
+            "patchelf".to_string(),
+            vec![PathBuf::from("/usr/bin"), PathBuf::from("/usr/local/bin")],
+        );
+
         // System tools
         fallback_paths.insert("which".to_string(), vec![PathBuf::from("/usr/bin")]);
 
@@ -750,6 +756,13 @@ impl ToolRegistry {
                 install_suggestion: "Install via sps2: sps2 install meson".to_string(),
             },
 
+            "patchelf" => ToolMetadata {
+                category: ToolCategory::System,
+                is_critical: false,
+                install_suggestion: "Install patchelf with your distribution's package manager"
+                    .to_string(),
+            },
+
             // System tools
             "which" => ToolMetadata {
                 category: ToolCategory::System,
@@ -901,16 +914,36 @@ impl Platform {
     }
 
     /// Internal method to create platform instance for singleton
+    ///
+    /// Targets other than macOS and Linux get the portable mock implementation.
     fn new_internal() -> Self {
+        #[cfg(target_os = "linux")]
+        use crate::implementations::linux::{
+            binary::LinuxBinaryOperations as BinaryOps,
+            filesystem::LinuxFilesystemOperations as FilesystemOps,
+            process::LinuxProcessOperations as ProcessOps,
+        };
+        #[cfg(target_os = "macos")]
         use crate::implementations::macos::{
-            binary::MacOSBinaryOperations, filesystem::MacOSFilesystemOperations,
-            process::MacOSProcessOperations,
+            binary::MacOSBinaryOperations as BinaryOps,
+            filesystem::MacOSFilesystemOperations as FilesystemOps,
+            process::MacOSProcessOperations as ProcessOps,
         };
+        #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+        use crate::implementations::mock::{
+            MockBinaryOperations as BinaryOps, MockFilesystemOperations as FilesystemOps,
+            MockProcessOperations as ProcessOps,
+        };
+
+        #[cfg(feature = "fault-injection")]
+        let filesystem = crate::fault::FaultingFilesystem::new(FilesystemOps::new());
+        #[cfg(not(feature = "fault-injection"))]
+        let filesystem = FilesystemOps::new();
 
         Self::new(
-            Box::new(MacOSBinaryOperations::new()),
-            Box::new(MacOSFilesystemOperations::new()),
-            Box::new(MacOSProcessOperations::new()),
+            Box::new(BinaryOps::new()),
+            Box::new(filesystem),
+            Box::new(ProcessOps::new()),
         )
     }
 
@@ -0,0 +1,301 @@
+//! Fault injection for crash-consistency testing.
+//!
+//! Production code marks interesting places with [`checkpoint`]; every
+//! filesystem operation of the platform singleton is a checkpoint as well,
+//! named `fs.<operation>` (for example `fs.atomic_rename`). Without the
+//! `fault-injection` feature checkpoints compile to nothing.
+//!
+//! With the feature enabled, tests arm faults on the global [`FaultInjector`]
+//! to fail the Nth time a checkpoint is reached, either with an I/O error, a
+//! timeout, or a simulated crash that aborts the operation in progress
+//! without running any of its cleanup.
+
+use sps2_errors::PlatformError;
+
+#[cfg(feature = "fault-injection")]
+pub use injector::{FaultInjector, FaultScope, FaultingFilesystem};
+
+/// Kind of failure produced when an armed checkpoint fires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Fail with the given OS error number
+    Io(i32),
+    /// Fail as if the operation timed out
+    Timeout,
+    /// Abort as if the process died at this point
+    Crash,
+}
+
+impl Fault {
+    /// Generic I/O error (`EIO`)
+    pub const EIO: Self = Self::Io(libc::EIO);
+
+    /// Error returned to the caller of the failing checkpoint
+    #[must_use]
+    pub fn into_error(self, point: &str) -> PlatformError {
+        let message = match self {
+            Self::Io(errno) => std::io::Error::from_raw_os_error(errno).to_string(),
+            Self::Timeout => std::io::Error::from_raw_os_error(libc::ETIMEDOUT).to_string(),
+            Self::Crash => "simulated crash".to_string(),
+        };
+        PlatformError::FilesystemOperationFailed {
+            operation: point.to_string(),
+            message: format!("injected fault: {message}"),
+        }
+    }
+}
+
+/// Mark a point where tests may inject a fault
+///
+/// # Errors
+///
+/// Returns the injected error when a fault armed for `point` fires. Always
+/// succeeds without the `fault-injection` feature.
+#[inline]
+pub fn checkpoint(point: &str) -> Result<(), PlatformError> {
+    #[cfg(feature = "fault-injection")]
+    {
+        FaultInjector::global().check(point)
+    }
+    #[cfg(not(feature = "fault-injection"))]
+    {
+        let _ = point;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+mod injector {
+    use super::{checkpoint, Fault};
+    use crate::core::PlatformContext;
+    use crate::filesystem::FilesystemOperations;
+    use async_trait::async_trait;
+    use sps2_errors::PlatformError;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::{Mutex, OnceLock};
+
+    #[derive(Debug)]
+    struct Rule {
+        point: String,
+        /// Hits of `point` left before the fault fires
+        remaining: u64,
+        fault: Fault,
+    }
+
+    #[derive(Debug, Default)]
+    struct Inner {
+        rules: Vec<Rule>,
+        hits: HashMap<String, u64>,
+        fired: Vec<(String, Fault)>,
+    }
+
+    /// Process-wide registry of armed faults
+    #[derive(Debug, Default)]
+    pub struct FaultInjector {
+        inner: Mutex<Inner>,
+    }
+
+    /// Exclusive use of the global injector for the duration of a test
+    ///
+    /// Armed faults and hit counters are cleared when the scope is created and
+    /// again when it is dropped.
+    pub struct FaultScope {
+        _guard: tokio::sync::MutexGuard<'static, ()>,
+    }
+
+    impl Drop for FaultScope {
+        fn drop(&mut self) {
+            FaultInjector::global().reset();
+        }
+    }
+
+    impl FaultInjector {
+        /// The injector consulted by [`checkpoint`]
+        pub fn global() -> &'static FaultInjector {
+            static INSTANCE: OnceLock<FaultInjector> = OnceLock::new();
+            INSTANCE.get_or_init(FaultInjector::default)
+        }
+
+        /// Take exclusive use of the global injector
+        ///
+        /// Tests sharing the global injector must hold a scope, otherwise
+        /// faults armed by one test fire in another running concurrently.
+        pub async fn scope() -> FaultScope {
+            static LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+            let guard = LOCK.lock().await;
+            Self::global().reset();
+            FaultScope { _guard: guard }
+        }
+
+        /// Fail the `n`th time `point` is reached from now on (1-based)
+        ///
+        /// Each armed fault fires once.
+        pub fn fail_nth(&self, point: &str, n: u64, fault: Fault) {
+            self.lock().rules.push(Rule {
+                point: point.to_string(),
+                remaining: n.max(1),
+                fault,
+            });
+        }
+
+        /// Fail the next time `point` is reached
+        pub fn fail_next(&self, point: &str, fault: Fault) {
+            self.fail_nth(point, 1, fault);
+        }
+
+        /// Disarm all faults and forget hit counts
+        pub fn reset(&self) {
+            *self.lock() = Inner::default();
+        }
+
+        /// Number of times `point` was reached since the last reset
+        pub fn hits(&self, point: &str) -> u64 {
+            self.lock().hits.get(point).copied().unwrap_or(0)
+        }
+
+        /// Faults that fired since the last reset, in order
+        pub fn fired(&self) -> Vec<(String, Fault)> {
+            self.lock().fired.clone()
+        }
+
+        /// Record a hit of `point` and fire the first fault that is due
+        ///
+        /// # Errors
+        ///
+        /// Returns the injected error if an armed fault fires.
+        pub fn check(&self, point: &str) -> Result<(), PlatformError> {
+            let mut inner = self.lock();
+            *inner.hits.entry(point.to_string()).or_insert(0) += 1;
+
+            let mut due = None;
+            for rule in inner.rules.iter_mut().filter(|rule| rule.point == point) {
+                rule.remaining -= 1;
+                if rule.remaining == 0 && due.is_none() {
+                    due = Some(rule.fault);
+                }
+            }
+            // Faults due on the same hit collapse into the first one armed
+            inner.rules.retain(|rule| rule.remaining > 0);
+
+            match due {
+                Some(fault) => {
+                    inner.fired.push((point.to_string(), fault));
+                    Err(fault.into_error(point))
+                }
+                None => Ok(()),
+            }
+        }
+
+        fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+            self.inner
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+        }
+    }
+
+    /// Filesystem operations that pass through [`checkpoint`] before delegating
+    pub struct FaultingFilesystem<F> {
+        inner: F,
+    }
+
+    impl<F> FaultingFilesystem<F> {
+        /// Wrap an existing implementation
+        pub fn new(inner: F) -> Self {
+            Self { inner }
+        }
+    }
+
+    #[async_trait]
+    impl<F: FilesystemOperations> FilesystemOperations for FaultingFilesystem<F> {
+        async fn clone_file(
+            &self,
+            ctx: &PlatformContext,
+            src: &Path,
+            dst: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.clone_file")?;
+            self.inner.clone_file(ctx, src, dst).await
+        }
+
+        async fn clone_directory(
+            &self,
+            ctx: &PlatformContext,
+            src: &Path,
+            dst: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.clone_directory")?;
+            self.inner.clone_directory(ctx, src, dst).await
+        }
+
+        async fn atomic_rename(
+            &self,
+            ctx: &PlatformContext,
+            src: &Path,
+            dst: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.atomic_rename")?;
+            self.inner.atomic_rename(ctx, src, dst).await
+        }
+
+        async fn atomic_swap(
+            &self,
+            ctx: &PlatformContext,
+            path_a: &Path,
+            path_b: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.atomic_swap")?;
+            self.inner.atomic_swap(ctx, path_a, path_b).await
+        }
+
+        async fn hard_link(
+            &self,
+            ctx: &PlatformContext,
+            src: &Path,
+            dst: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.hard_link")?;
+            self.inner.hard_link(ctx, src, dst).await
+        }
+
+        async fn create_dir_all(
+            &self,
+            ctx: &PlatformContext,
+            path: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.create_dir_all")?;
+            self.inner.create_dir_all(ctx, path).await
+        }
+
+        async fn remove_dir_all(
+            &self,
+            ctx: &PlatformContext,
+            path: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.remove_dir_all")?;
+            self.inner.remove_dir_all(ctx, path).await
+        }
+
+        async fn exists(&self, ctx: &PlatformContext, path: &Path) -> bool {
+            self.inner.exists(ctx, path).await
+        }
+
+        async fn remove_file(
+            &self,
+            ctx: &PlatformContext,
+            path: &Path,
+        ) -> Result<(), PlatformError> {
+            checkpoint("fs.remove_file")?;
+            self.inner.remove_file(ctx, path).await
+        }
+
+        async fn size(&self, ctx: &PlatformContext, path: &Path) -> Result<u64, PlatformError> {
+            checkpoint("fs.size")?;
+            self.inner.size(ctx, path).await
+        }
+
+        async fn is_dir(&self, ctx: &PlatformContext, path: &Path) -> bool {
+            self.inner.is_dir(ctx, path).await
+        }
+    }
+}
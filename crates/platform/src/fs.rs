@@ -345,6 +345,65 @@ pub async fn size(path: &Path) -> Result<u64> {
         })
 }
 
+/// Free space available to unprivileged writers on the volume holding `path`
+///
+/// `path` need not exist yet; its nearest existing ancestor is used.
+///
+/// # Errors
+///
+/// Returns an error if no ancestor of `path` exists or the volume cannot be
+/// queried.
+#[cfg(unix)]
+pub async fn available_space(path: &Path) -> Result<u64> {
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || {
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .ok_or_else(|| StorageError::PathNotFound {
+                path: path.display().to_string(),
+            })?;
+        let c_path =
+            std::ffi::CString::new(existing.as_os_str().as_encoded_bytes()).map_err(|e| {
+                StorageError::InvalidPath {
+                    path: format!("{}: {e}", existing.display()),
+                }
+            })?;
+
+        let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        // SAFETY: `c_path` is a valid NUL-terminated string and `stats` is
+        // only read after statvfs reports success.
+        let result = unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) };
+        if result != 0 {
+            return Err(StorageError::IoError {
+                message: format!(
+                    "failed to query free space on {}: {}",
+                    existing.display(),
+                    std::io::Error::last_os_error()
+                ),
+            }
+            .into());
+        }
+        let stats = unsafe { stats.assume_init() };
+        #[allow(clippy::unnecessary_cast)] // field widths differ between platforms
+        Ok((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+    })
+    .await
+    .map_err(|e| StorageError::IoError {
+        message: format!("free space query panicked: {e}"),
+    })?
+}
+
+/// Free space is not queried on non-Unix platforms
+///
+/// # Errors
+///
+/// Always succeeds, reporting unlimited space.
+#[cfg(not(unix))]
+pub async fn available_space(_path: &Path) -> Result<u64> {
+    Ok(u64::MAX)
+}
+
 /// Ensure a directory exists and is empty
 ///
 /// # Errors
@@ -372,6 +431,210 @@ pub fn set_compression(_path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Extended attribute Gatekeeper checks before running a downloaded file
+pub const QUARANTINE_XATTR: &str = "com.apple.quarantine";
+
+/// Remove the quarantine attribute from `paths`, returning how many had it
+///
+/// Symlinks are not followed. Paths without the attribute are skipped.
+///
+/// # Errors
+///
+/// Returns an error if a path cannot be converted or the attribute cannot
+/// be removed for a reason other than being absent.
+#[cfg(target_os = "macos")]
+pub async fn strip_quarantine(paths: &[std::path::PathBuf]) -> Result<usize> {
+    let paths = paths.to_vec();
+    task::spawn_blocking(move || {
+        let name = std::ffi::CString::new(QUARANTINE_XATTR).unwrap_or_default();
+        let mut stripped = 0;
+        for path in &paths {
+            let c_path =
+                std::ffi::CString::new(path.as_os_str().as_encoded_bytes()).map_err(|e| {
+                    StorageError::InvalidPath {
+                        path: format!("{}: {e}", path.display()),
+                    }
+                })?;
+            // SAFETY: both arguments are valid NUL-terminated strings
+            let result =
+                unsafe { libc::removexattr(c_path.as_ptr(), name.as_ptr(), libc::XATTR_NOFOLLOW) };
+            if result == 0 {
+                stripped += 1;
+                continue;
+            }
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::ENOATTR) {
+                return Err(StorageError::IoError {
+                    message: format!(
+                        "failed to remove {QUARANTINE_XATTR} from {}: {err}",
+                        path.display()
+                    ),
+                }
+                .into());
+            }
+        }
+        Ok(stripped)
+    })
+    .await
+    .map_err(|e| StorageError::IoError {
+        message: format!("quarantine removal panicked: {e}"),
+    })?
+}
+
+/// Gatekeeper only exists on macOS; nothing carries the attribute elsewhere
+///
+/// # Errors
+///
+/// Always succeeds, reporting no paths stripped.
+#[cfg(not(target_os = "macos"))]
+pub async fn strip_quarantine(_paths: &[std::path::PathBuf]) -> Result<usize> {
+    Ok(0)
+}
+
+/// Setuid and setgid bits, which the store drops from the objects it keeps
+pub const SPECIAL_MODE_BITS: u32 = 0o6000;
+
+/// BSD flags that stop a file from being changed, renamed or removed
+/// (`uchg`, `uappnd`, `schg`, `sappnd`)
+pub const RESTRICTING_FILE_FLAGS: u32 = 0x0000_0002 | 0x0000_0004 | 0x0002_0000 | 0x0004_0000;
+
+/// BSD file flags by the names `chflags` and bsdtar use for them
+const FILE_FLAG_NAMES: [(&str, u32); 8] = [
+    ("nodump", 0x0000_0001),
+    ("uchg", 0x0000_0002),
+    ("uappnd", 0x0000_0004),
+    ("opaque", 0x0000_0008),
+    ("hidden", 0x0000_8000),
+    ("arch", 0x0001_0000),
+    ("schg", 0x0002_0000),
+    ("sappnd", 0x0004_0000),
+];
+
+/// All flags in [`FILE_FLAG_NAMES`]
+#[cfg(target_os = "macos")]
+fn named_file_flags() -> u32 {
+    FILE_FLAG_NAMES.iter().fold(0, |all, (_, bit)| all | bit)
+}
+
+/// BSD flags set on a file, or 0 where the platform has none
+///
+/// Only flags with a name are returned; ones the system manages itself,
+/// like `compressed`, are left out.
+#[must_use]
+pub fn file_flags(metadata: &std::fs::Metadata) -> u32 {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        metadata.st_flags() & named_file_flags()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        0
+    }
+}
+
+/// Comma-separated names of `flags`, as bsdtar writes them in the
+/// `SCHILY.fflags` pax header; flags without a name are left out
+#[must_use]
+pub fn file_flags_to_string(flags: u32) -> String {
+    FILE_FLAG_NAMES
+        .iter()
+        .filter(|(_, bit)| flags & bit != 0)
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Flags named in a `SCHILY.fflags` value; unknown names are ignored
+#[must_use]
+pub fn parse_file_flags(names: &str) -> u32 {
+    names
+        .split([',', ' '])
+        .filter_map(|name| {
+            FILE_FLAG_NAMES
+                .iter()
+                .find(|(known, _)| *known == name.trim())
+                .map(|(_, bit)| bit)
+        })
+        .fold(0, |flags, bit| flags | bit)
+}
+
+/// Give the file at `path` the setuid/setgid bits of `mode` and the BSD
+/// `flags`
+///
+/// A file sharing its inode with a store object (a hard link) is first
+/// replaced by a private copy, so the bits never reach the object or the
+/// other places it is linked to. Write bits are not added, matching the
+/// store's read-only objects.
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be copied or its mode or flags
+/// cannot be set.
+#[cfg(unix)]
+pub async fn apply_special_permissions(path: &Path, mode: u32, flags: u32) -> Result<()> {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let path = path.to_path_buf();
+    task::spawn_blocking(move || -> std::io::Result<()> {
+        let metadata = std::fs::symlink_metadata(&path)?;
+        if metadata.nlink() > 1 {
+            let mut private = path.clone().into_os_string();
+            private.push(".sps2-private");
+            let private = std::path::PathBuf::from(private);
+            std::fs::copy(&path, &private)?;
+            std::fs::rename(&private, &path)?;
+        }
+        let permissions = (metadata.permissions().mode() & 0o777) | (mode & SPECIAL_MODE_BITS);
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(permissions))?;
+        if file_flags(&metadata) != flags {
+            set_file_flags(&path, &metadata, flags)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| StorageError::IoError {
+        message: format!("setting special permissions panicked: {e}"),
+    })?
+    .map_err(|e| {
+        StorageError::IoError {
+            message: format!("failed to set special permissions: {e}"),
+        }
+        .into()
+    })
+}
+
+/// Special permissions only exist on Unix
+///
+/// # Errors
+///
+/// Always succeeds without changing anything.
+#[cfg(not(unix))]
+pub async fn apply_special_permissions(_path: &Path, _mode: u32, _flags: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Replace the named flags of a file by `flags`, keeping the others
+#[cfg(target_os = "macos")]
+fn set_file_flags(path: &Path, metadata: &std::fs::Metadata, flags: u32) -> std::io::Result<()> {
+    use std::os::macos::fs::MetadataExt;
+
+    let flags = (metadata.st_flags() & !named_file_flags()) | flags;
+    let c_path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: the path is a valid NUL-terminated string
+    if unsafe { libc::lchflags(c_path.as_ptr(), flags) } == 0 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_file_flags(_path: &Path, _metadata: &std::fs::Metadata, _flags: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
 /// Remove a single file
 ///
 /// # Errors
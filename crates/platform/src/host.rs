@@ -0,0 +1,79 @@
+//! Facts about the machine sps2 runs on
+
+use std::ffi::CStr;
+
+/// Name of this host, as `hostname` prints it
+#[must_use]
+pub fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is writable for its full length; a name that does
+    // not fit is cut off and may lack the terminating NUL, which the length
+    // passed leaves room for
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len() - 1) };
+    if result != 0 {
+        return None;
+    }
+    let name = CStr::from_bytes_until_nul(&buf).ok()?.to_string_lossy();
+    (!name.is_empty()).then(|| name.into_owned())
+}
+
+/// Effective user ID of this process; 0 when running as root
+#[must_use]
+pub fn user_id() -> u32 {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() }
+}
+
+/// Build of the operating system, such as `24B83` on macOS
+#[cfg(target_os = "macos")]
+#[must_use]
+pub fn os_build() -> Option<String> {
+    let name = c"kern.osversion";
+    let mut buf = [0u8; 64];
+    let mut len = buf.len();
+    // SAFETY: `name` is NUL-terminated and `len` holds the buffer's size,
+    // which sysctl updates to the length written
+    let result = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr(),
+            buf.as_mut_ptr().cast(),
+            &raw mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    if result != 0 {
+        return None;
+    }
+    let build = CStr::from_bytes_until_nul(&buf[..len])
+        .ok()?
+        .to_string_lossy();
+    Some(build.into_owned())
+}
+
+/// Kernel release, such as `6.8.0-45-generic`, which identifies the build
+/// elsewhere
+#[cfg(not(target_os = "macos"))]
+#[must_use]
+pub fn os_build() -> Option<String> {
+    let mut uts = std::mem::MaybeUninit::<libc::utsname>::uninit();
+    // SAFETY: `uts` is only read after uname reports success
+    if unsafe { libc::uname(uts.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let uts = unsafe { uts.assume_init() };
+    // SAFETY: uname fills `release` with a NUL-terminated string
+    let release = unsafe { CStr::from_ptr(uts.release.as_ptr()) };
+    Some(release.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_this_host() {
+        assert!(hostname().is_some_and(|name| !name.contains('\0')));
+        assert!(os_build().is_some_and(|build| !build.is_empty()));
+    }
+}
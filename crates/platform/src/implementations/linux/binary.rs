@@ -0,0 +1,258 @@
+//! Linux binary operations backed by `patchelf`
+//!
+//! The Mach-O vocabulary of [`BinaryOperations`] maps onto ELF as follows:
+//! the install name is `DT_SONAME`, dependencies are `DT_NEEDED` entries and
+//! rpaths are the colon-separated entries of `DT_RUNPATH` (or `DT_RPATH`).
+
+use async_trait::async_trait;
+use sps2_errors::PlatformError;
+use sps2_events::events::{PlatformOperationContext, PlatformOperationKind};
+use std::path::Path;
+use tokio::process::Command;
+
+use super::observe;
+use crate::binary::{BinaryOperations, SignatureInfo};
+use crate::core::PlatformContext;
+
+/// Linux implementation of binary operations
+#[derive(Debug, Clone, Default)]
+pub struct LinuxBinaryOperations;
+
+impl LinuxBinaryOperations {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn context(operation: &str, target: &Path) -> PlatformOperationContext {
+    PlatformOperationContext {
+        kind: PlatformOperationKind::Binary,
+        operation: operation.to_string(),
+        target: Some(target.to_path_buf()),
+        source: None,
+        command: None,
+    }
+}
+
+fn failed(operation: &str, binary: &Path, message: impl Into<String>) -> PlatformError {
+    PlatformError::BinaryOperationFailed {
+        operation: operation.to_string(),
+        binary_path: binary.display().to_string(),
+        message: message.into(),
+    }
+}
+
+fn ensure_file(operation: &str, binary: &Path) -> Result<(), PlatformError> {
+    if binary.is_file() {
+        Ok(())
+    } else {
+        Err(failed(operation, binary, "no such file"))
+    }
+}
+
+/// Run `patchelf` with `args` followed by `binary`, returning its stdout
+async fn patchelf(
+    ctx: &PlatformContext,
+    operation: &str,
+    binary: &Path,
+    args: &[&str],
+) -> Result<String, PlatformError> {
+    let patchelf_path = ctx.platform_manager().get_tool("patchelf").await?;
+    let output = Command::new(&patchelf_path)
+        .args(args)
+        .arg(binary)
+        .output()
+        .await
+        .map_err(|e| PlatformError::ProcessExecutionFailed {
+            command: format!("patchelf {}", args.join(" ")),
+            message: e.to_string(),
+        })?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(failed(operation, binary, stderr.trim()))
+    }
+}
+
+async fn rpath_entries(ctx: &PlatformContext, binary: &Path) -> Result<Vec<String>, PlatformError> {
+    let rpath = patchelf(ctx, "get_rpath_entries", binary, &["--print-rpath"]).await?;
+    Ok(rpath
+        .trim()
+        .split(':')
+        .filter(|entry| !entry.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+async fn set_rpath(
+    ctx: &PlatformContext,
+    operation: &str,
+    binary: &Path,
+    entries: &[String],
+) -> Result<(), PlatformError> {
+    let rpath = entries.join(":");
+    patchelf(ctx, operation, binary, &["--set-rpath", &rpath])
+        .await
+        .map(|_| ())
+}
+
+#[async_trait]
+impl BinaryOperations for LinuxBinaryOperations {
+    async fn get_install_name(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Option<String>, PlatformError> {
+        observe(ctx, context("get_install_name", binary), async {
+            ensure_file("get_install_name", binary)?;
+            // Executables have no DT_SONAME and patchelf fails on them, like
+            // `otool -D` printing nothing for a non-dylib
+            Ok(
+                patchelf(ctx, "get_install_name", binary, &["--print-soname"])
+                    .await
+                    .ok()
+                    .map(|soname| soname.trim().to_string())
+                    .filter(|soname| !soname.is_empty()),
+            )
+        })
+        .await
+    }
+
+    async fn set_install_name(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+        name: &str,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("set_install_name", binary), async {
+            patchelf(ctx, "set_install_name", binary, &["--set-soname", name])
+                .await
+                .map(|_| ())
+        })
+        .await
+    }
+
+    async fn get_dependencies(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Vec<String>, PlatformError> {
+        observe(ctx, context("get_dependencies", binary), async {
+            let needed = patchelf(ctx, "get_dependencies", binary, &["--print-needed"]).await?;
+            Ok(needed
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .map(String::from)
+                .collect())
+        })
+        .await
+    }
+
+    async fn change_dependency(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+        old: &str,
+        new: &str,
+    ) -> Result<(), PlatformError> {
+        // Like install_name_tool, patchelf ignores references that are not present
+        observe(ctx, context("change_dependency", binary), async {
+            patchelf(
+                ctx,
+                "change_dependency",
+                binary,
+                &["--replace-needed", old, new],
+            )
+            .await
+            .map(|_| ())
+        })
+        .await
+    }
+
+    async fn add_rpath(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+        rpath: &str,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("add_rpath", binary), async {
+            let mut entries = rpath_entries(ctx, binary).await?;
+            if entries.iter().any(|entry| entry == rpath) {
+                return Err(failed(
+                    "add_rpath",
+                    binary,
+                    format!("would duplicate path, file already has rpath: {rpath}"),
+                ));
+            }
+            entries.push(rpath.to_string());
+            set_rpath(ctx, "add_rpath", binary, &entries).await
+        })
+        .await
+    }
+
+    async fn delete_rpath(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+        rpath: &str,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("delete_rpath", binary), async {
+            let mut entries = rpath_entries(ctx, binary).await?;
+            let before = entries.len();
+            entries.retain(|entry| entry != rpath);
+            if entries.len() == before {
+                return Err(failed(
+                    "delete_rpath",
+                    binary,
+                    format!("no rpath entry with path: {rpath}"),
+                ));
+            }
+            set_rpath(ctx, "delete_rpath", binary, &entries).await
+        })
+        .await
+    }
+
+    async fn get_rpath_entries(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Vec<String>, PlatformError> {
+        observe(
+            ctx,
+            context("get_rpath_entries", binary),
+            rpath_entries(ctx, binary),
+        )
+        .await
+    }
+
+    async fn verify_signature(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<bool, PlatformError> {
+        // ELF has no code signatures, so there is nothing to invalidate
+        ensure_file("verify_signature", binary)?;
+        Ok(true)
+    }
+
+    async fn signature_info(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<SignatureInfo, PlatformError> {
+        ensure_file("signature_info", binary)?;
+        Ok(SignatureInfo::default())
+    }
+
+    async fn sign_binary(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        _identity: Option<&str>,
+    ) -> Result<(), PlatformError> {
+        ensure_file("sign_binary", binary)
+    }
+}
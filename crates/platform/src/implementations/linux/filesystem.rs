@@ -0,0 +1,363 @@
+//! Linux filesystem operations (reflink clones, `renameat2` swaps)
+
+use async_trait::async_trait;
+use sps2_errors::PlatformError;
+use sps2_events::events::{PlatformOperationContext, PlatformOperationKind};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use super::observe;
+use crate::core::PlatformContext;
+use crate::filesystem::FilesystemOperations;
+
+/// Linux implementation of filesystem operations
+#[derive(Debug, Clone, Default)]
+pub struct LinuxFilesystemOperations;
+
+impl LinuxFilesystemOperations {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn context(operation: &str, source: Option<&Path>, target: &Path) -> PlatformOperationContext {
+    PlatformOperationContext {
+        kind: PlatformOperationKind::Filesystem,
+        operation: operation.to_string(),
+        target: Some(target.to_path_buf()),
+        source: source.map(Path::to_path_buf),
+        command: None,
+    }
+}
+
+fn failed(operation: &str, message: impl std::fmt::Display) -> PlatformError {
+    PlatformError::FilesystemOperationFailed {
+        operation: operation.to_string(),
+        message: message.to_string(),
+    }
+}
+
+fn c_path(operation: &str, path: &Path) -> Result<CString, PlatformError> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| failed(operation, format!("Invalid path: {}", path.display())))
+}
+
+/// Refuse to overwrite, matching `clonefile` failing with `EEXIST`
+async fn ensure_absent(operation: &str, dst: &Path) -> Result<(), PlatformError> {
+    if fs::symlink_metadata(dst).await.is_ok() {
+        return Err(failed(
+            operation,
+            format!("destination exists: {}", dst.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Share `src`'s extents with a new file at `dst`, or copy them
+///
+/// `FICLONE` fails on filesystems without reflinks and across mounts; the
+/// copy then goes through `copy_file_range`, which `std::io::copy` uses for
+/// file-to-file copies.
+fn clone_regular_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let mut source = File::open(src)?;
+    let permissions = source.metadata()?.permissions();
+    let mut dest = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(dst)?;
+
+    #[allow(unsafe_code)]
+    // SAFETY: both descriptors are open for the duration of the call
+    let cloned = unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } == 0;
+    if !cloned {
+        std::io::copy(&mut source, &mut dest)?;
+    }
+    dest.set_permissions(permissions)
+}
+
+/// Recursively clone `src` to `dst`, preserving symlinks
+fn clone_tree(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(src)?;
+    if metadata.file_type().is_symlink() {
+        std::os::unix::fs::symlink(std::fs::read_link(src)?, dst)
+    } else if metadata.is_dir() {
+        std::fs::create_dir(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            clone_tree(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+        // Set last so a read-only directory can still be filled
+        std::fs::set_permissions(dst, metadata.permissions())
+    } else {
+        clone_regular_file(src, dst)
+    }
+}
+
+/// Exchange `a` and `b` in a single `renameat2(RENAME_EXCHANGE)` call
+async fn exchange(operation: &'static str, a: &Path, b: &Path) -> Result<(), PlatformError> {
+    let a = c_path(operation, a)?;
+    let b = c_path(operation, b)?;
+    tokio::task::spawn_blocking(move || {
+        #[allow(unsafe_code)]
+        // SAFETY: both paths are valid NUL-terminated strings; the raw
+        // syscall is used because glibc only wraps renameat2 since 2.28 and
+        // musl not at all
+        let rc = unsafe {
+            libc::syscall(
+                libc::SYS_renameat2,
+                libc::AT_FDCWD,
+                a.as_ptr(),
+                libc::AT_FDCWD,
+                b.as_ptr(),
+                libc::RENAME_EXCHANGE,
+            )
+        };
+        if rc == 0 {
+            Ok(())
+        } else {
+            let err = std::io::Error::last_os_error();
+            Err(failed(operation, format!("atomic swap failed: {err}")))
+        }
+    })
+    .await
+    .map_err(|e| failed(operation, format!("task join error: {e}")))?
+}
+
+async fn blocking(
+    operation: &'static str,
+    src: &Path,
+    dst: &Path,
+    f: fn(&Path, &Path) -> std::io::Result<()>,
+) -> Result<(), PlatformError> {
+    let (src, dst): (PathBuf, PathBuf) = (src.to_path_buf(), dst.to_path_buf());
+    tokio::task::spawn_blocking(move || f(&src, &dst))
+        .await
+        .map_err(|e| failed(operation, format!("task join error: {e}")))?
+        .map_err(|e| failed(operation, format!("clone failed: {e}")))
+}
+
+async fn calculate_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::symlink_metadata(path).await?;
+    if metadata.is_file() {
+        Ok(metadata.len())
+    } else if metadata.is_dir() {
+        let mut total = 0u64;
+        let mut entries = fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            total += Box::pin(calculate_size(&entry.path())).await?;
+        }
+        Ok(total)
+    } else {
+        Ok(0)
+    }
+}
+
+#[async_trait]
+impl FilesystemOperations for LinuxFilesystemOperations {
+    async fn clone_file(
+        &self,
+        ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("clone_file", Some(src), dst), async {
+            ensure_absent("clone_file", dst).await?;
+            blocking("clone_file", src, dst, clone_regular_file).await
+        })
+        .await
+    }
+
+    async fn clone_directory(
+        &self,
+        ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("clone_directory", Some(src), dst), async {
+            ensure_absent("clone_directory", dst).await?;
+            blocking("clone_directory", src, dst, clone_tree).await
+        })
+        .await
+    }
+
+    async fn atomic_rename(
+        &self,
+        ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("atomic_rename", Some(src), dst), async {
+            // Directories are replaced wholesale, like on macOS, but without a
+            // window where `dst` is missing: exchange, then drop the old tree
+            if fs::metadata(dst).await.is_ok_and(|m| m.is_dir()) {
+                exchange("atomic_rename", src, dst).await?;
+                let _ = fs::remove_dir_all(src).await;
+                return Ok(());
+            }
+
+            fs::rename(src, dst)
+                .await
+                .map_err(|e| failed("atomic_rename", format!("rename failed: {e}")))
+        })
+        .await
+    }
+
+    async fn atomic_swap(
+        &self,
+        ctx: &PlatformContext,
+        path_a: &Path,
+        path_b: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("atomic_swap", Some(path_a), path_b), async {
+            for path in [path_a, path_b] {
+                if fs::symlink_metadata(path).await.is_err() {
+                    return Err(failed(
+                        "atomic_swap",
+                        format!("Path does not exist: {}", path.display()),
+                    ));
+                }
+            }
+            exchange("atomic_swap", path_a, path_b).await
+        })
+        .await
+    }
+
+    async fn hard_link(
+        &self,
+        ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("hard_link", Some(src), dst), async {
+            fs::hard_link(src, dst)
+                .await
+                .map_err(|e| failed("hard_link", format!("hard link failed: {e}")))
+        })
+        .await
+    }
+
+    async fn create_dir_all(
+        &self,
+        ctx: &PlatformContext,
+        path: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("create_dir_all", None, path), async {
+            fs::create_dir_all(path)
+                .await
+                .map_err(|e| failed("create_dir_all", format!("create directory failed: {e}")))
+        })
+        .await
+    }
+
+    async fn remove_dir_all(
+        &self,
+        ctx: &PlatformContext,
+        path: &Path,
+    ) -> Result<(), PlatformError> {
+        observe(ctx, context("remove_dir_all", None, path), async {
+            fs::remove_dir_all(path)
+                .await
+                .map_err(|e| failed("remove_dir_all", format!("remove directory failed: {e}")))
+        })
+        .await
+    }
+
+    async fn exists(&self, _ctx: &PlatformContext, path: &Path) -> bool {
+        fs::metadata(path).await.is_ok()
+    }
+
+    async fn remove_file(&self, ctx: &PlatformContext, path: &Path) -> Result<(), PlatformError> {
+        observe(ctx, context("remove_file", None, path), async {
+            fs::remove_file(path)
+                .await
+                .map_err(|e| failed("remove_file", e))
+        })
+        .await
+    }
+
+    async fn size(&self, _ctx: &PlatformContext, path: &Path) -> Result<u64, PlatformError> {
+        calculate_size(path).await.map_err(|e| failed("size", e))
+    }
+
+    async fn is_dir(&self, _ctx: &PlatformContext, path: &Path) -> bool {
+        fs::metadata(path).await.is_ok_and(|m| m.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn clones_keep_content_modes_and_symlinks() {
+        let td = TempDir::new().unwrap();
+        let fs_ops = LinuxFilesystemOperations::new();
+        let ctx = PlatformContext::new(None);
+
+        let src = td.path().join("src");
+        fs::create_dir_all(src.join("bin")).await.unwrap();
+        fs::write(src.join("bin/tool"), b"#!/bin/sh\n")
+            .await
+            .unwrap();
+        fs::set_permissions(src.join("bin/tool"), PermissionsExt::from_mode(0o755))
+            .await
+            .unwrap();
+        fs::symlink("bin/tool", src.join("link")).await.unwrap();
+
+        let dst = td.path().join("dst");
+        fs_ops.clone_directory(&ctx, &src, &dst).await.unwrap();
+        assert_eq!(
+            fs::read(dst.join("bin/tool")).await.unwrap(),
+            b"#!/bin/sh\n"
+        );
+        let mode = fs::metadata(dst.join("bin/tool"))
+            .await
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(
+            fs::read_link(dst.join("link")).await.unwrap(),
+            Path::new("bin/tool")
+        );
+
+        assert!(fs_ops.clone_directory(&ctx, &src, &dst).await.is_err());
+        assert!(fs_ops
+            .clone_file(&ctx, &src.join("bin/tool"), &dst.join("bin/tool"))
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn rename_replaces_directories_and_swap_exchanges() {
+        let td = TempDir::new().unwrap();
+        let fs_ops = LinuxFilesystemOperations::new();
+        let ctx = PlatformContext::new(None);
+
+        let a = td.path().join("a");
+        let b = td.path().join("b");
+        for (dir, content) in [(&a, "a"), (&b, "b")] {
+            fs::create_dir(dir).await.unwrap();
+            fs::write(dir.join("id"), content).await.unwrap();
+        }
+
+        fs_ops.atomic_swap(&ctx, &a, &b).await.unwrap();
+        assert_eq!(fs::read_to_string(a.join("id")).await.unwrap(), "b");
+        assert_eq!(fs::read_to_string(b.join("id")).await.unwrap(), "a");
+
+        fs_ops.atomic_rename(&ctx, &a, &b).await.unwrap();
+        assert!(!fs_ops.exists(&ctx, &a).await);
+        assert_eq!(fs::read_to_string(b.join("id")).await.unwrap(), "b");
+
+        let missing = td.path().join("missing");
+        assert!(fs_ops.atomic_swap(&ctx, &b, &missing).await.is_err());
+    }
+}
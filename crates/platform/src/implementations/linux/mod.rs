@@ -0,0 +1,82 @@
+//! Linux platform implementation
+//!
+//! Lets the store and install machinery run for real on Linux, mainly so CI
+//! can exercise repositories and builds:
+//! - files are cloned with the `FICLONE` reflink ioctl on filesystems that
+//!   support it (Btrfs, XFS, bcachefs) and copied with `copy_file_range`
+//!   elsewhere
+//! - swaps use `renameat2(RENAME_EXCHANGE)`, so they are as atomic as the
+//!   `renamex_np` swaps on macOS
+//! - binary operations edit ELF dynamic sections with `patchelf`; ELF files
+//!   carry no code signature, so signing is a no-op
+//! - sandbox profiles are SBPL and have no Linux equivalent, so sandboxed
+//!   commands run unconfined
+
+pub mod binary;
+pub mod filesystem;
+pub mod process;
+
+use sps2_errors::PlatformError;
+use sps2_events::{
+    events::{FailureContext, PlatformEvent, PlatformOperationContext, PlatformOperationMetrics},
+    AppEvent,
+};
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::core::PlatformContext;
+
+/// Linux platform implementation
+pub struct LinuxPlatform;
+
+impl LinuxPlatform {
+    /// Create a new Linux platform instance
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> crate::core::Platform {
+        use binary::LinuxBinaryOperations;
+        use filesystem::LinuxFilesystemOperations;
+        use process::LinuxProcessOperations;
+
+        crate::core::Platform::new(
+            Box::new(LinuxBinaryOperations::new()),
+            Box::new(LinuxFilesystemOperations::new()),
+            Box::new(LinuxProcessOperations::new()),
+        )
+    }
+}
+
+fn metrics(duration: Duration) -> PlatformOperationMetrics {
+    PlatformOperationMetrics {
+        duration_ms: Some(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)),
+        exit_code: None,
+        stdout_bytes: None,
+        stderr_bytes: None,
+        changes: None,
+    }
+}
+
+/// Run `operation`, emitting started/completed/failed platform events
+async fn observe<T>(
+    ctx: &PlatformContext,
+    context: PlatformOperationContext,
+    operation: impl Future<Output = Result<T, PlatformError>>,
+) -> Result<T, PlatformError> {
+    let start = Instant::now();
+    ctx.emit_event(AppEvent::Platform(PlatformEvent::OperationStarted {
+        context: context.clone(),
+    }))
+    .await;
+
+    let result = operation.await;
+    let metrics = Some(metrics(start.elapsed()));
+    let event = match &result {
+        Ok(_) => PlatformEvent::OperationCompleted { context, metrics },
+        Err(e) => PlatformEvent::OperationFailed {
+            context,
+            failure: FailureContext::from_error(e),
+            metrics,
+        },
+    };
+    ctx.emit_event(AppEvent::Platform(event)).await;
+    result
+}
@@ -0,0 +1,171 @@
+//! Linux process operations
+
+use async_trait::async_trait;
+use sps2_errors::{Error, PlatformError};
+use sps2_events::{
+    events::{
+        FailureContext, PlatformEvent, PlatformOperationContext, PlatformOperationKind,
+        PlatformOperationMetrics, ProcessCommandDescriptor,
+    },
+    AppEvent,
+};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+
+use crate::core::PlatformContext;
+use crate::process::{CommandOutput, PlatformCommand, ProcessOperations, SandboxDenial};
+
+/// Linux implementation of process operations
+///
+/// Output is captured rather than passed through, so CI logs stay readable
+/// and callers can inspect what a command printed. Sandbox profiles are
+/// ignored: commands run unconfined and no denials are ever reported.
+#[derive(Debug, Clone, Default)]
+pub struct LinuxProcessOperations;
+
+impl LinuxProcessOperations {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn process_context(descriptor: ProcessCommandDescriptor) -> PlatformOperationContext {
+    PlatformOperationContext {
+        kind: PlatformOperationKind::Process,
+        operation: "execute_command".to_string(),
+        target: None,
+        source: None,
+        command: Some(descriptor),
+    }
+}
+
+fn process_metrics(duration: Duration, output: Option<&CommandOutput>) -> PlatformOperationMetrics {
+    PlatformOperationMetrics {
+        duration_ms: Some(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)),
+        exit_code: output.and_then(|o| o.status.code()),
+        stdout_bytes: output.and_then(|o| u64::try_from(o.stdout.len()).ok()),
+        stderr_bytes: output.and_then(|o| u64::try_from(o.stderr.len()).ok()),
+        changes: None,
+    }
+}
+
+#[async_trait]
+impl ProcessOperations for LinuxProcessOperations {
+    async fn execute_command(
+        &self,
+        ctx: &PlatformContext,
+        cmd: PlatformCommand,
+    ) -> Result<CommandOutput, Error> {
+        let start = Instant::now();
+        let descriptor = ProcessCommandDescriptor {
+            program: cmd.program().to_string(),
+            args: cmd.get_args().to_vec(),
+            cwd: cmd.get_current_dir().cloned(),
+        };
+        ctx.emit_event(AppEvent::Platform(PlatformEvent::OperationStarted {
+            context: process_context(descriptor.clone()),
+        }))
+        .await;
+
+        let mut command = Command::new(cmd.program());
+        command.args(cmd.get_args());
+        if let Some(dir) = cmd.get_current_dir() {
+            command.current_dir(dir);
+        }
+        command.envs(cmd.get_env_vars());
+
+        let result = command
+            .output()
+            .await
+            .map(|output| CommandOutput {
+                status: output.status,
+                stdout: output.stdout,
+                stderr: output.stderr,
+            })
+            .map_err(|e| PlatformError::ProcessExecutionFailed {
+                command: cmd.program().to_string(),
+                message: e.to_string(),
+            });
+
+        let duration = start.elapsed();
+        let event = match &result {
+            Ok(output) => PlatformEvent::OperationCompleted {
+                context: process_context(descriptor),
+                metrics: Some(process_metrics(duration, Some(output))),
+            },
+            Err(e) => PlatformEvent::OperationFailed {
+                context: process_context(descriptor),
+                failure: FailureContext::from_error(e),
+                metrics: Some(process_metrics(duration, None)),
+            },
+        };
+        ctx.emit_event(AppEvent::Platform(event)).await;
+
+        result.map_err(Error::from)
+    }
+
+    fn create_command(&self, program: &str) -> PlatformCommand {
+        PlatformCommand::new(program)
+    }
+
+    async fn which(&self, program: &str) -> Result<PathBuf, Error> {
+        std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths)
+            .map(|dir| dir.join(program))
+            .find(|candidate| {
+                candidate
+                    .metadata()
+                    .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            })
+            .ok_or_else(|| {
+                PlatformError::CommandNotFound {
+                    command: program.to_string(),
+                }
+                .into()
+            })
+    }
+
+    async fn sandbox_denials(
+        &self,
+        _window: Duration,
+        _tag: &str,
+    ) -> Result<Vec<SandboxDenial>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn local_snapshot(&self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn captures_output_and_runs_sandboxed_commands_unconfined() {
+        let ops = LinuxProcessOperations::new();
+        let ctx = PlatformContext::new(None);
+
+        let mut cmd = ops.create_command("sh");
+        cmd.args(["-c", "printf \"$GREETING\"; echo oops >&2; exit 3"])
+            .env("GREETING", "hi")
+            .sandbox_profile("(version 1)(deny default)");
+        let output = ops.execute_command(&ctx, cmd).await.unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(output.stdout, b"hi");
+        assert_eq!(output.stderr, b"oops\n");
+
+        assert!(ops.which("sh").await.unwrap().is_absolute());
+        assert!(ops.which("definitely-not-a-program").await.is_err());
+        assert!(ops
+            .sandbox_denials(Duration::from_secs(5), "sps2-build")
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(ops.local_snapshot().await.unwrap(), None);
+    }
+}
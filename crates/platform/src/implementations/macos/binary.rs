@@ -16,7 +16,7 @@ use std::path::Path;
 use std::time::{Duration, Instant};
 use tokio::process::Command;
 
-use crate::binary::BinaryOperations;
+use crate::binary::{BinaryOperations, SignatureInfo};
 use crate::core::PlatformContext;
 
 /// macOS implementation of binary operations
@@ -94,6 +94,38 @@ async fn emit_binary_failed(
     .await;
 }
 
+/// Read the signature kind and flags from `codesign -d --verbose=2` output
+///
+/// The relevant lines look like `Signature=adhoc` and
+/// `CodeDirectory v=20500 size=1234 flags=0x10002(adhoc,runtime) ...`.
+fn parse_signature_details(details: &str) -> SignatureInfo {
+    let mut info = SignatureInfo {
+        signed: true,
+        ..SignatureInfo::default()
+    };
+    for line in details.lines() {
+        if line.trim() == "Signature=adhoc" {
+            info.adhoc = true;
+        }
+        if let Some(flags) = line
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("flags="))
+        {
+            let names = flags
+                .split_once('(')
+                .map_or("", |(_, rest)| rest.trim_end_matches(')'));
+            for name in names.split(',') {
+                match name {
+                    "adhoc" => info.adhoc = true,
+                    "runtime" => info.hardened_runtime = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+    info
+}
+
 #[async_trait]
 impl BinaryOperations for MacOSBinaryOperations {
     async fn get_install_name(
@@ -560,6 +592,77 @@ impl BinaryOperations for MacOSBinaryOperations {
         result
     }
 
+    async fn signature_info(
+        &self,
+        ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<SignatureInfo, PlatformError> {
+        let start = Instant::now();
+        emit_binary_started(ctx, "signature_info", binary).await;
+
+        let result: Result<SignatureInfo, PlatformError> = async {
+            let codesign_path = ctx.platform_manager().get_tool("codesign").await?;
+            // codesign -d writes the signature details to stderr
+            let details = Command::new(&codesign_path)
+                .args(["-d", "--verbose=2", &binary.to_string_lossy()])
+                .output()
+                .await
+                .map_err(|e| PlatformError::ProcessExecutionFailed {
+                    command: "codesign -d".to_string(),
+                    message: e.to_string(),
+                })?;
+            if !details.status.success() {
+                // "code object is not signed at all"
+                return Ok(SignatureInfo::default());
+            }
+            let mut info = parse_signature_details(&String::from_utf8_lossy(&details.stderr));
+
+            let entitlements = Command::new(&codesign_path)
+                .args([
+                    "-d",
+                    "--entitlements",
+                    "-",
+                    "--xml",
+                    &binary.to_string_lossy(),
+                ])
+                .output()
+                .await
+                .map_err(|e| PlatformError::ProcessExecutionFailed {
+                    command: "codesign -d --entitlements".to_string(),
+                    message: e.to_string(),
+                })?;
+            let plist = String::from_utf8_lossy(&entitlements.stdout);
+            if entitlements.status.success() && !plist.trim().is_empty() {
+                info.entitlements = Some(plist.trim().to_string());
+            }
+            Ok(info)
+        }
+        .await;
+
+        let duration = start.elapsed();
+        match &result {
+            Ok(info) => {
+                emit_binary_completed(
+                    ctx,
+                    "signature_info",
+                    binary,
+                    Some(vec![
+                        format!("adhoc={}", info.adhoc),
+                        format!("hardened_runtime={}", info.hardened_runtime),
+                        format!("entitlements={}", info.entitlements.is_some()),
+                    ]),
+                    duration,
+                )
+                .await;
+            }
+            Err(e) => {
+                emit_binary_failed(ctx, "signature_info", binary, e, duration).await;
+            }
+        }
+
+        result
+    }
+
     async fn sign_binary(
         &self,
         ctx: &PlatformContext,
@@ -574,8 +677,16 @@ impl BinaryOperations for MacOSBinaryOperations {
         // Use the proven codesign implementation from CodeSigner
         let result = async {
             let codesign_path = ctx.platform_manager().get_tool("codesign").await?;
+            // Keep what notarized binaries rely on; the designated requirement
+            // is not kept, as it names the original signer
             let output = Command::new(&codesign_path)
-                .args(["-f", "-s", identity_str, &binary.to_string_lossy()])
+                .args([
+                    "-f",
+                    "-s",
+                    identity_str,
+                    "--preserve-metadata=entitlements,flags,runtime",
+                    &binary.to_string_lossy(),
+                ])
                 .output()
                 .await
                 .map_err(|e| PlatformError::ProcessExecutionFailed {
@@ -617,3 +728,140 @@ impl BinaryOperations for MacOSBinaryOperations {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Output of `codesign -dvvv`; the lines read are the same at `--verbose=2`
+    const ADHOC_LINKER_SIGNED: &str = "\
+Executable=/opt/pm/live/bin/jq
+Identifier=jq
+Format=Mach-O thin (arm64)
+CodeDirectory v=20400 size=2346 flags=0x20002(adhoc,linker-signed) hashes=68+0 location=embedded
+Hash type=sha256 size=32
+CandidateCDHash sha256=0c7bd8ad6c8e31e4e2a0f4f9d2b1f0e5c6c1a8f3
+CandidateCDHashFull sha256=0c7bd8ad6c8e31e4e2a0f4f9d2b1f0e5c6c1a8f34e1d7e3b9a6f2c5d8e0b4a71
+Hash choices=sha256
+CMSDigest=0c7bd8ad6c8e31e4e2a0f4f9d2b1f0e5c6c1a8f34e1d7e3b9a6f2c5d8e0b4a71
+CMSDigestType=2
+Executable Segment base=0
+Executable Segment limit=180224
+Executable Segment flags=0x1
+Page size=4096
+CDHash=0c7bd8ad6c8e31e4e2a0f4f9d2b1f0e5c6c1a8f3
+Signature=adhoc
+Info.plist=not bound
+TeamIdentifier=not set
+Sealed Resources=none
+Internal requirements=none
+";
+
+    const ADHOC_RUNTIME: &str = "\
+Executable=/opt/pm/live/bin/node
+Identifier=node
+Format=Mach-O thin (arm64)
+CodeDirectory v=20500 size=702810 flags=0x10002(adhoc,runtime) hashes=21955+7 location=embedded
+Hash type=sha256 size=32
+CandidateCDHash sha256=5e2f0d6b8c3a1e7f4d9b2c6a0e8f1d3b7c5a9e2f
+CandidateCDHashFull sha256=5e2f0d6b8c3a1e7f4d9b2c6a0e8f1d3b7c5a9e2f4b6d8c0a1e3f5b7d9c2e4a68
+Hash choices=sha256
+CMSDigest=5e2f0d6b8c3a1e7f4d9b2c6a0e8f1d3b7c5a9e2f4b6d8c0a1e3f5b7d9c2e4a68
+CMSDigestType=2
+Executable Segment base=0
+Executable Segment limit=89915392
+Executable Segment flags=0x1
+Page size=16384
+Runtime Version=14.0.0
+CDHash=5e2f0d6b8c3a1e7f4d9b2c6a0e8f1d3b7c5a9e2f
+Signature=adhoc
+Info.plist=not bound
+TeamIdentifier=not set
+Sealed Resources=none
+Internal requirements count=0 size=12
+";
+
+    const DEVELOPER_ID_RUNTIME: &str = "\
+Executable=/Applications/Firefox.app/Contents/MacOS/firefox
+Identifier=org.mozilla.firefox
+Format=app bundle with Mach-O universal (x86_64 arm64)
+CodeDirectory v=20500 size=719 flags=0x10000(runtime) hashes=11+7 location=embedded
+Hash type=sha256 size=32
+CandidateCDHash sha256=9a1c4e7b2d5f8a0c3e6b9d2f5a8c1e4b7d0a3f6c
+CandidateCDHashFull sha256=9a1c4e7b2d5f8a0c3e6b9d2f5a8c1e4b7d0a3f6c9e2b5d8a1c4f7e0b3d6a9c2f
+Hash choices=sha256
+CMSDigest=9a1c4e7b2d5f8a0c3e6b9d2f5a8c1e4b7d0a3f6c9e2b5d8a1c4f7e0b3d6a9c2f
+CMSDigestType=2
+Executable Segment base=0
+Executable Segment limit=32768
+Executable Segment flags=0x1
+Page size=4096
+CDHash=9a1c4e7b2d5f8a0c3e6b9d2f5a8c1e4b7d0a3f6c
+Signature size=9046
+Authority=Developer ID Application: Mozilla Corporation (43AQ936H96)
+Authority=Developer ID Certification Authority
+Authority=Apple Root CA
+Timestamp=12 Sep 2024 at 14:02:11
+Notarization Ticket=stapled
+Info.plist entries=27
+TeamIdentifier=43AQ936H96
+Runtime Version=13.3.0
+Sealed Resources version=2 rules=13 files=186
+Internal requirements count=1 size=212
+";
+
+    const PLATFORM_BINARY: &str = "\
+Executable=/bin/ls
+Identifier=com.apple.ls
+Format=Mach-O universal (x86_64 arm64e)
+CodeDirectory v=20400 size=757 flags=0x0(none) hashes=13+2 location=embedded
+Platform identifier=15
+Hash type=sha256 size=32
+CandidateCDHash sha256=3f7a9c1e5b2d8f0a4c6e9b1d3f5a7c0e2b4d6f8a
+CandidateCDHashFull sha256=3f7a9c1e5b2d8f0a4c6e9b1d3f5a7c0e2b4d6f8a1c3e5b7d9f0a2c4e6b8d1f3a
+Hash choices=sha256
+CMSDigest=3f7a9c1e5b2d8f0a4c6e9b1d3f5a7c0e2b4d6f8a1c3e5b7d9f0a2c4e6b8d1f3a
+CMSDigestType=2
+Executable Segment base=0
+Executable Segment limit=32768
+Executable Segment flags=0x1
+Page size=4096
+CDHash=3f7a9c1e5b2d8f0a4c6e9b1d3f5a7c0e2b4d6f8a
+Signature size=4442
+Authority=Software Signing
+Authority=Apple Code Signing Certification Authority
+Authority=Apple Root CA
+Signed Time=4 Aug 2024 at 07:36:25
+Info.plist=not bound
+TeamIdentifier=not set
+Sealed Resources=none
+Internal requirements count=1 size=64
+";
+
+    #[test]
+    fn parses_codesign_details() {
+        let cases = [
+            ("ad hoc, linker signed", ADHOC_LINKER_SIGNED, true, false),
+            ("ad hoc, hardened runtime", ADHOC_RUNTIME, true, true),
+            (
+                "Developer ID, hardened runtime",
+                DEVELOPER_ID_RUNTIME,
+                false,
+                true,
+            ),
+            ("Apple platform binary", PLATFORM_BINARY, false, false),
+        ];
+        for (name, details, adhoc, hardened_runtime) in cases {
+            assert_eq!(
+                parse_signature_details(details),
+                SignatureInfo {
+                    signed: true,
+                    adhoc,
+                    hardened_runtime,
+                    entitlements: None,
+                },
+                "{name}"
+            );
+        }
+    }
+}
@@ -18,7 +18,10 @@ use std::time::{Duration, Instant};
 use tokio::process::Command;
 
 use crate::core::PlatformContext;
-use crate::process::{CommandOutput, PlatformCommand, ProcessOperations};
+use crate::process::{
+    parse_sandbox_denials, snapshot, CommandOutput, PlatformCommand, ProcessOperations,
+    SandboxDenial,
+};
 
 /// macOS implementation of process operations
 pub struct MacOSProcessOperations;
@@ -114,7 +117,13 @@ impl ProcessOperations for MacOSProcessOperations {
 
         // Use inherit to pass through stdout/stderr directly to terminal
         let result: Result<CommandOutput, PlatformError> = async {
-            let mut command = Command::new(cmd.program());
+            let mut command = if let Some(profile) = cmd.get_sandbox_profile() {
+                let mut command = Command::new("/usr/bin/sandbox-exec");
+                command.arg("-p").arg(profile).arg(cmd.program());
+                command
+            } else {
+                Command::new(cmd.program())
+            };
             command.args(cmd.get_args());
             command.stdout(std::process::Stdio::inherit());
             command.stderr(std::process::Stdio::inherit());
@@ -199,4 +208,47 @@ impl ProcessOperations for MacOSProcessOperations {
             }))
         }
     }
+
+    async fn sandbox_denials(
+        &self,
+        window: Duration,
+        tag: &str,
+    ) -> Result<Vec<SandboxDenial>, Error> {
+        // The kernel logs denials with the sandbox as sender, followed by
+        // the message of the rule that denied
+        let output = Command::new("/usr/bin/log")
+            .args(["show", "--style", "syslog", "--last"])
+            .arg(format!("{}s", window.as_secs() + 1))
+            .arg("--predicate")
+            .arg(format!(
+                "sender == \"Sandbox\" AND eventMessage CONTAINS \"{}\"",
+                tag.replace('\\', "\\\\").replace('"', "\\\"")
+            ))
+            .output()
+            .await
+            .map_err(|e| PlatformError::ProcessExecutionFailed {
+                command: "log show".to_string(),
+                message: e.to_string(),
+            })?;
+        Ok(parse_sandbox_denials(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    async fn local_snapshot(&self) -> Result<Option<String>, Error> {
+        let output = Command::new(snapshot::TMUTIL)
+            .args(snapshot::LOCAL_SNAPSHOT_ARGS)
+            .output()
+            .await
+            .map_err(|e| PlatformError::ProcessExecutionFailed {
+                command: "tmutil localsnapshot".to_string(),
+                message: e.to_string(),
+            })?;
+        let output = CommandOutput {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        };
+        snapshot::local_snapshot_from_output(&output).map(Some)
+    }
 }
@@ -0,0 +1,329 @@
+//! Mock binary operations backed by an in-memory Mach-O model
+
+use async_trait::async_trait;
+use sps2_errors::PlatformError;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::binary::{BinaryOperations, SignatureInfo};
+use crate::core::PlatformContext;
+
+/// Load commands and signature state of a modelled binary
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockBinary {
+    /// `LC_ID_DYLIB` install name, for dylibs
+    pub install_name: Option<String>,
+    /// `LC_LOAD_DYLIB` references
+    pub dependencies: Vec<String>,
+    /// `LC_RPATH` entries
+    pub rpaths: Vec<String>,
+    /// Whether the binary carries a valid code signature
+    pub signed: bool,
+    /// Whether the signature sets the hardened runtime flag
+    pub hardened_runtime: bool,
+    /// Entitlements plist embedded in the signature
+    pub entitlements: Option<String>,
+}
+
+/// Binary operations working on [`MockBinary`] models instead of Mach-O files
+///
+/// Any existing file can be inspected; files without a model behave like a
+/// binary without load commands or signature. Modifying a binary updates its
+/// model and leaves the file untouched. Signing, like `codesign` with
+/// preserved metadata, keeps the entitlements and hardened runtime flag.
+/// Clones share the same models.
+#[derive(Debug, Clone, Default)]
+pub struct MockBinaryOperations {
+    binaries: Arc<Mutex<HashMap<PathBuf, MockBinary>>>,
+}
+
+impl MockBinaryOperations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the model for the binary at `path`
+    pub fn insert(&self, path: impl Into<PathBuf>, binary: MockBinary) {
+        self.lock().insert(path.into(), binary);
+    }
+
+    /// Current model of the binary at `path`, if one was set or modified
+    pub fn get(&self, path: &Path) -> Option<MockBinary> {
+        self.lock().get(path).cloned()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, MockBinary>> {
+        self.binaries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read<T>(
+        &self,
+        operation: &str,
+        binary: &Path,
+        f: impl FnOnce(&MockBinary) -> T,
+    ) -> Result<T, PlatformError> {
+        ensure_file(operation, binary)?;
+        Ok(f(self.lock().get(binary).unwrap_or(&MockBinary::default())))
+    }
+
+    fn modify(
+        &self,
+        operation: &str,
+        binary: &Path,
+        f: impl FnOnce(&mut MockBinary) -> Result<(), String>,
+    ) -> Result<(), PlatformError> {
+        ensure_file(operation, binary)?;
+        let mut binaries = self.lock();
+        let model = binaries.entry(binary.to_path_buf()).or_default();
+        f(model).map_err(|message| failed(operation, binary, message))?;
+        // Editing load commands invalidates the signature, as on macOS
+        if operation != "sign_binary" {
+            model.signed = false;
+        }
+        Ok(())
+    }
+}
+
+fn failed(operation: &str, binary: &Path, message: impl Into<String>) -> PlatformError {
+    PlatformError::BinaryOperationFailed {
+        operation: operation.to_string(),
+        binary_path: binary.display().to_string(),
+        message: message.into(),
+    }
+}
+
+fn ensure_file(operation: &str, binary: &Path) -> Result<(), PlatformError> {
+    if binary.is_file() {
+        Ok(())
+    } else {
+        Err(failed(operation, binary, "no such file"))
+    }
+}
+
+#[async_trait]
+impl BinaryOperations for MockBinaryOperations {
+    async fn get_install_name(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Option<String>, PlatformError> {
+        self.read("get_install_name", binary, |b| b.install_name.clone())
+    }
+
+    async fn set_install_name(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        name: &str,
+    ) -> Result<(), PlatformError> {
+        self.modify("set_install_name", binary, |b| {
+            b.install_name = Some(name.to_string());
+            Ok(())
+        })
+    }
+
+    async fn get_dependencies(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Vec<String>, PlatformError> {
+        self.read("get_dependencies", binary, |b| b.dependencies.clone())
+    }
+
+    async fn change_dependency(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        old: &str,
+        new: &str,
+    ) -> Result<(), PlatformError> {
+        // install_name_tool silently ignores references that are not present
+        self.modify("change_dependency", binary, |b| {
+            for dep in &mut b.dependencies {
+                if dep == old {
+                    *dep = new.to_string();
+                }
+            }
+            Ok(())
+        })
+    }
+
+    async fn add_rpath(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        rpath: &str,
+    ) -> Result<(), PlatformError> {
+        self.modify("add_rpath", binary, |b| {
+            if b.rpaths.iter().any(|r| r == rpath) {
+                return Err(format!(
+                    "would duplicate path, file already has LC_RPATH for: {rpath}"
+                ));
+            }
+            b.rpaths.push(rpath.to_string());
+            Ok(())
+        })
+    }
+
+    async fn delete_rpath(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        rpath: &str,
+    ) -> Result<(), PlatformError> {
+        self.modify("delete_rpath", binary, |b| {
+            let before = b.rpaths.len();
+            b.rpaths.retain(|r| r != rpath);
+            if b.rpaths.len() == before {
+                return Err(format!("no LC_RPATH load command with path: {rpath}"));
+            }
+            Ok(())
+        })
+    }
+
+    async fn get_rpath_entries(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<Vec<String>, PlatformError> {
+        self.read("get_rpath_entries", binary, |b| b.rpaths.clone())
+    }
+
+    async fn verify_signature(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<bool, PlatformError> {
+        self.read("verify_signature", binary, |b| b.signed)
+    }
+
+    async fn signature_info(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+    ) -> Result<SignatureInfo, PlatformError> {
+        // An invalidated signature still carries its metadata; models are
+        // signed ad hoc, as the build pipeline does
+        self.read("signature_info", binary, |b| {
+            let signed = b.signed || b.hardened_runtime || b.entitlements.is_some();
+            SignatureInfo {
+                signed,
+                adhoc: signed,
+                hardened_runtime: b.hardened_runtime,
+                entitlements: b.entitlements.clone(),
+            }
+        })
+    }
+
+    async fn sign_binary(
+        &self,
+        _ctx: &PlatformContext,
+        binary: &Path,
+        _identity: Option<&str>,
+    ) -> Result<(), PlatformError> {
+        self.modify("sign_binary", binary, |b| {
+            b.signed = true;
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn edits_update_model_and_invalidate_signature() {
+        let td = TempDir::new().unwrap();
+        let lib = td.path().join("libfoo.dylib");
+        std::fs::write(&lib, b"").unwrap();
+
+        let ops = MockBinaryOperations::new();
+        let ctx = PlatformContext::new(None);
+        ops.insert(
+            &lib,
+            MockBinary {
+                dependencies: vec!["/old/libz.dylib".to_string()],
+                signed: true,
+                ..MockBinary::default()
+            },
+        );
+
+        ops.change_dependency(&ctx, &lib, "/old/libz.dylib", "@rpath/libz.dylib")
+            .await
+            .unwrap();
+        ops.add_rpath(&ctx, &lib, "/opt/pm/live/lib").await.unwrap();
+        assert!(ops.add_rpath(&ctx, &lib, "/opt/pm/live/lib").await.is_err());
+        assert!(!ops.verify_signature(&ctx, &lib).await.unwrap());
+
+        ops.sign_binary(&ctx, &lib, None).await.unwrap();
+        assert_eq!(
+            ops.get(&lib).unwrap(),
+            MockBinary {
+                install_name: None,
+                dependencies: vec!["@rpath/libz.dylib".to_string()],
+                rpaths: vec!["/opt/pm/live/lib".to_string()],
+                signed: true,
+                ..MockBinary::default()
+            }
+        );
+
+        let missing = td.path().join("missing");
+        assert!(ops.get_dependencies(&ctx, &missing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn signature_metadata_survives_edits_and_signing() {
+        let td = TempDir::new().unwrap();
+        let bin = td.path().join("tool");
+        std::fs::write(&bin, b"").unwrap();
+        let entitlements =
+            "<plist><dict><key>com.apple.security.cs.allow-jit</key><true/></dict></plist>";
+
+        let ops = MockBinaryOperations::new();
+        let ctx = PlatformContext::new(None);
+        ops.insert(
+            &bin,
+            MockBinary {
+                signed: true,
+                hardened_runtime: true,
+                entitlements: Some(entitlements.to_string()),
+                ..MockBinary::default()
+            },
+        );
+        let expected = SignatureInfo {
+            signed: true,
+            adhoc: true,
+            hardened_runtime: true,
+            entitlements: Some(entitlements.to_string()),
+        };
+        assert_eq!(ops.signature_info(&ctx, &bin).await.unwrap(), expected);
+
+        // An edit breaks the signature but not what it carries
+        ops.add_rpath(&ctx, &bin, "@loader_path/../lib")
+            .await
+            .unwrap();
+        assert!(!ops.verify_signature(&ctx, &bin).await.unwrap());
+        assert_eq!(ops.signature_info(&ctx, &bin).await.unwrap(), expected);
+
+        ops.sign_binary(&ctx, &bin, None).await.unwrap();
+        assert!(ops.verify_signature(&ctx, &bin).await.unwrap());
+        assert_eq!(ops.signature_info(&ctx, &bin).await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn unsigned_binaries_have_no_signature_info() {
+        let td = TempDir::new().unwrap();
+        let bin = td.path().join("tool");
+        std::fs::write(&bin, b"").unwrap();
+
+        let ops = MockBinaryOperations::new();
+        let ctx = PlatformContext::new(None);
+        assert_eq!(
+            ops.signature_info(&ctx, &bin).await.unwrap(),
+            SignatureInfo::default()
+        );
+    }
+}
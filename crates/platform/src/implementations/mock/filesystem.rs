@@ -0,0 +1,262 @@
+//! Mock filesystem operations backed by portable `tokio::fs` calls
+
+use async_trait::async_trait;
+use sps2_errors::PlatformError;
+use std::path::Path;
+use tokio::fs;
+
+use crate::core::PlatformContext;
+use crate::filesystem::FilesystemOperations;
+
+/// Portable implementation of filesystem operations
+///
+/// Clones are full copies and swaps are three renames, so neither is atomic;
+/// everything else behaves like the macOS implementation.
+#[derive(Debug, Clone, Default)]
+pub struct MockFilesystemOperations;
+
+impl MockFilesystemOperations {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+fn failed(operation: &str, message: impl std::fmt::Display) -> PlatformError {
+    PlatformError::FilesystemOperationFailed {
+        operation: operation.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// Refuse to overwrite, matching `clonefile` failing with `EEXIST`
+async fn ensure_absent(operation: &str, dst: &Path) -> Result<(), PlatformError> {
+    if fs::symlink_metadata(dst).await.is_ok() {
+        return Err(failed(
+            operation,
+            format!("destination exists: {}", dst.display()),
+        ));
+    }
+    Ok(())
+}
+
+/// Recursively copy `src` to `dst`, preserving symlinks
+async fn copy_tree(src: &Path, dst: &Path) -> std::io::Result<()> {
+    let metadata = fs::symlink_metadata(src).await?;
+    if metadata.file_type().is_symlink() {
+        fs::symlink(fs::read_link(src).await?, dst).await?;
+    } else if metadata.is_dir() {
+        fs::create_dir(dst).await?;
+        fs::set_permissions(dst, metadata.permissions()).await?;
+        let mut entries = fs::read_dir(src).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            Box::pin(copy_tree(&entry.path(), &dst.join(entry.file_name()))).await?;
+        }
+    } else {
+        fs::copy(src, dst).await?;
+    }
+    Ok(())
+}
+
+async fn calculate_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::symlink_metadata(path).await?;
+    if metadata.is_file() {
+        Ok(metadata.len())
+    } else if metadata.is_dir() {
+        let mut total = 0u64;
+        let mut entries = fs::read_dir(path).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            total += Box::pin(calculate_size(&entry.path())).await?;
+        }
+        Ok(total)
+    } else {
+        Ok(0)
+    }
+}
+
+#[async_trait]
+impl FilesystemOperations for MockFilesystemOperations {
+    async fn clone_file(
+        &self,
+        _ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        ensure_absent("clone_file", dst).await?;
+        fs::copy(src, dst)
+            .await
+            .map(|_| ())
+            .map_err(|e| failed("clone_file", format!("copy failed: {e}")))
+    }
+
+    async fn clone_directory(
+        &self,
+        _ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        ensure_absent("clone_directory", dst).await?;
+        copy_tree(src, dst)
+            .await
+            .map_err(|e| failed("clone_directory", format!("copy failed: {e}")))
+    }
+
+    async fn atomic_rename(
+        &self,
+        _ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        // Directories are replaced wholesale, like on macOS
+        if fs::metadata(dst).await.is_ok_and(|m| m.is_dir()) {
+            let backup = dst.with_extension("old");
+            fs::rename(dst, &backup).await.map_err(|e| {
+                failed(
+                    "atomic_rename",
+                    format!("failed to backup destination: {e}"),
+                )
+            })?;
+            return match fs::rename(src, dst).await {
+                Ok(()) => {
+                    let _ = fs::remove_dir_all(&backup).await;
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = fs::rename(&backup, dst).await;
+                    Err(failed("atomic_rename", format!("rename failed: {e}")))
+                }
+            };
+        }
+
+        fs::rename(src, dst)
+            .await
+            .map_err(|e| failed("atomic_rename", format!("rename failed: {e}")))
+    }
+
+    async fn atomic_swap(
+        &self,
+        _ctx: &PlatformContext,
+        path_a: &Path,
+        path_b: &Path,
+    ) -> Result<(), PlatformError> {
+        for path in [path_a, path_b] {
+            if fs::symlink_metadata(path).await.is_err() {
+                return Err(failed(
+                    "atomic_swap",
+                    format!("Path does not exist: {}", path.display()),
+                ));
+            }
+        }
+
+        let temp_path = path_a.with_extension("tmp_swap");
+        fs::rename(path_a, &temp_path)
+            .await
+            .map_err(|e| failed("atomic_swap", format!("temp rename failed: {e}")))?;
+        fs::rename(path_b, path_a)
+            .await
+            .map_err(|e| failed("atomic_swap", format!("second rename failed: {e}")))?;
+        fs::rename(&temp_path, path_b)
+            .await
+            .map_err(|e| failed("atomic_swap", format!("final rename failed: {e}")))
+    }
+
+    async fn hard_link(
+        &self,
+        _ctx: &PlatformContext,
+        src: &Path,
+        dst: &Path,
+    ) -> Result<(), PlatformError> {
+        fs::hard_link(src, dst)
+            .await
+            .map_err(|e| failed("hard_link", format!("hard link failed: {e}")))
+    }
+
+    async fn create_dir_all(
+        &self,
+        _ctx: &PlatformContext,
+        path: &Path,
+    ) -> Result<(), PlatformError> {
+        fs::create_dir_all(path)
+            .await
+            .map_err(|e| failed("create_dir_all", format!("create directory failed: {e}")))
+    }
+
+    async fn remove_dir_all(
+        &self,
+        _ctx: &PlatformContext,
+        path: &Path,
+    ) -> Result<(), PlatformError> {
+        fs::remove_dir_all(path)
+            .await
+            .map_err(|e| failed("remove_dir_all", format!("remove directory failed: {e}")))
+    }
+
+    async fn exists(&self, _ctx: &PlatformContext, path: &Path) -> bool {
+        fs::metadata(path).await.is_ok()
+    }
+
+    async fn remove_file(&self, _ctx: &PlatformContext, path: &Path) -> Result<(), PlatformError> {
+        fs::remove_file(path)
+            .await
+            .map_err(|e| failed("remove_file", e))
+    }
+
+    async fn size(&self, _ctx: &PlatformContext, path: &Path) -> Result<u64, PlatformError> {
+        calculate_size(path).await.map_err(|e| failed("size", e))
+    }
+
+    async fn is_dir(&self, _ctx: &PlatformContext, path: &Path) -> bool {
+        fs::metadata(path).await.is_ok_and(|m| m.is_dir())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn clone_directory_copies_tree_and_refuses_overwrite() {
+        let td = TempDir::new().unwrap();
+        let fs_ops = MockFilesystemOperations::new();
+        let ctx = PlatformContext::new(None);
+
+        let src = td.path().join("src");
+        fs::create_dir_all(src.join("nested")).await.unwrap();
+        fs::write(src.join("nested/file"), b"data").await.unwrap();
+        fs::symlink("nested/file", src.join("link")).await.unwrap();
+
+        let dst = td.path().join("dst");
+        fs_ops.clone_directory(&ctx, &src, &dst).await.unwrap();
+        assert_eq!(fs::read(dst.join("nested/file")).await.unwrap(), b"data");
+        assert_eq!(
+            fs::read_link(dst.join("link")).await.unwrap(),
+            Path::new("nested/file")
+        );
+        assert_eq!(fs_ops.size(&ctx, &dst).await.unwrap(), 4);
+
+        assert!(fs_ops.clone_directory(&ctx, &src, &dst).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rename_replaces_directories_and_swap_exchanges() {
+        let td = TempDir::new().unwrap();
+        let fs_ops = MockFilesystemOperations::new();
+        let ctx = PlatformContext::new(None);
+
+        let a = td.path().join("a");
+        let b = td.path().join("b");
+        for (dir, content) in [(&a, "a"), (&b, "b")] {
+            fs::create_dir(dir).await.unwrap();
+            fs::write(dir.join("id"), content).await.unwrap();
+        }
+
+        fs_ops.atomic_swap(&ctx, &a, &b).await.unwrap();
+        assert_eq!(fs::read_to_string(a.join("id")).await.unwrap(), "b");
+        assert_eq!(fs::read_to_string(b.join("id")).await.unwrap(), "a");
+
+        fs_ops.atomic_rename(&ctx, &a, &b).await.unwrap();
+        assert!(!fs_ops.exists(&ctx, &a).await);
+        assert_eq!(fs::read_to_string(b.join("id")).await.unwrap(), "b");
+        assert!(!fs_ops.exists(&ctx, &b.with_extension("old")).await);
+    }
+}
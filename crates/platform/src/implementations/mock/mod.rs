@@ -0,0 +1,48 @@
+//! Portable platform implementation for tests and other targets
+//!
+//! The mock platform lets tests script binaries and processes, and lets the
+//! higher crates build on targets without a native implementation:
+//! - filesystem operations use plain `tokio::fs` calls with the same
+//!   semantics as the macOS implementation, minus APFS cloning and true
+//!   atomic swaps; point them at a temporary directory in tests
+//! - binary operations keep Mach-O metadata (install names, dependencies,
+//!   rpaths, signatures) in an in-memory model instead of running
+//!   `otool`/`install_name_tool`/`codesign`
+//! - processes are spawned normally unless a canned response is registered
+//!
+//! It is what [`PlatformManager`](crate::PlatformManager) uses on every
+//! target other than macOS and Linux.
+
+pub mod binary;
+pub mod filesystem;
+pub mod process;
+
+pub use binary::{MockBinary, MockBinaryOperations};
+pub use filesystem::MockFilesystemOperations;
+pub use process::{MockProcessOperations, MockResponse};
+
+/// Mock platform implementation
+pub struct MockPlatform;
+
+impl MockPlatform {
+    /// Create a new mock platform instance with empty models
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> crate::core::Platform {
+        Self::with_models(MockBinaryOperations::new(), MockProcessOperations::new())
+    }
+
+    /// Create a mock platform sharing the given models
+    ///
+    /// Keep clones of the models to seed binaries, register process
+    /// responses, or inspect recorded calls while the platform is in use.
+    pub fn with_models(
+        binary: MockBinaryOperations,
+        process: MockProcessOperations,
+    ) -> crate::core::Platform {
+        crate::core::Platform::new(
+            Box::new(binary),
+            Box::new(MockFilesystemOperations::new()),
+            Box::new(process),
+        )
+    }
+}
@@ -0,0 +1,264 @@
+//! Mock process operations with canned responses
+
+use async_trait::async_trait;
+use sps2_errors::{Error, PlatformError};
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::core::PlatformContext;
+use crate::process::{snapshot, CommandOutput, PlatformCommand, ProcessOperations, SandboxDenial};
+
+/// Canned result returned instead of running a program
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockResponse {
+    pub exit_code: i32,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Successful run printing `stdout`
+    pub fn success(stdout: impl Into<Vec<u8>>) -> Self {
+        Self {
+            exit_code: 0,
+            stdout: stdout.into(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// Failed run with the given exit code and `stderr`
+    pub fn failure(exit_code: i32, stderr: impl Into<Vec<u8>>) -> Self {
+        Self {
+            exit_code,
+            stdout: Vec::new(),
+            stderr: stderr.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    responses: HashMap<String, MockResponse>,
+    calls: Vec<Vec<String>>,
+    denials: Vec<(String, SandboxDenial)>,
+}
+
+/// Process operations that answer registered programs from canned responses
+///
+/// Programs without a response are spawned normally with captured output.
+/// Every executed command line is recorded. Clones share responses and calls.
+/// Local snapshots are only taken once a response for
+/// [`TMUTIL`](crate::process::TMUTIL) is registered.
+#[derive(Debug, Clone, Default)]
+pub struct MockProcessOperations {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl MockProcessOperations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer every invocation of `program` with `response`
+    pub fn respond(&self, program: &str, response: MockResponse) {
+        self.lock().responses.insert(program.to_string(), response);
+    }
+
+    /// Report `denial` from every later sandbox denial query for `tag`
+    pub fn deny(&self, tag: &str, denial: SandboxDenial) {
+        self.lock().denials.push((tag.to_string(), denial));
+    }
+
+    /// Command lines executed so far, program first
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.lock().calls.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn exit_status(code: i32) -> ExitStatus {
+    // Wait status layout: exit code in the second byte
+    ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+#[async_trait]
+impl ProcessOperations for MockProcessOperations {
+    async fn execute_command(
+        &self,
+        _ctx: &PlatformContext,
+        cmd: PlatformCommand,
+    ) -> Result<CommandOutput, Error> {
+        let response = {
+            let mut inner = self.lock();
+            let mut call = vec![cmd.program().to_string()];
+            call.extend(cmd.get_args().iter().cloned());
+            inner.calls.push(call);
+            inner.responses.get(cmd.program()).cloned()
+        };
+
+        if let Some(response) = response {
+            return Ok(CommandOutput {
+                status: exit_status(response.exit_code),
+                stdout: response.stdout,
+                stderr: response.stderr,
+            });
+        }
+
+        let mut command = Command::new(cmd.program());
+        command.args(cmd.get_args());
+        if let Some(dir) = cmd.get_current_dir() {
+            command.current_dir(dir);
+        }
+        command.envs(cmd.get_env_vars());
+
+        let output = command
+            .output()
+            .await
+            .map_err(|e| PlatformError::ProcessExecutionFailed {
+                command: cmd.program().to_string(),
+                message: e.to_string(),
+            })?;
+
+        Ok(CommandOutput {
+            status: output.status,
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
+
+    fn create_command(&self, program: &str) -> PlatformCommand {
+        PlatformCommand::new(program)
+    }
+
+    async fn which(&self, program: &str) -> Result<PathBuf, Error> {
+        if self.lock().responses.contains_key(program) {
+            return Ok(PathBuf::from(program));
+        }
+
+        std::env::var_os("PATH")
+            .iter()
+            .flat_map(std::env::split_paths)
+            .map(|dir| dir.join(program))
+            .find(|candidate| {
+                candidate
+                    .metadata()
+                    .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            })
+            .ok_or_else(|| {
+                PlatformError::CommandNotFound {
+                    command: program.to_string(),
+                }
+                .into()
+            })
+    }
+
+    async fn sandbox_denials(
+        &self,
+        _window: Duration,
+        tag: &str,
+    ) -> Result<Vec<SandboxDenial>, Error> {
+        Ok(self
+            .lock()
+            .denials
+            .iter()
+            .filter(|(denied, _)| denied == tag)
+            .map(|(_, denial)| denial.clone())
+            .collect())
+    }
+
+    async fn local_snapshot(&self) -> Result<Option<String>, Error> {
+        if !self.lock().responses.contains_key(snapshot::TMUTIL) {
+            return Ok(None);
+        }
+        let mut cmd = self.create_command(snapshot::TMUTIL);
+        cmd.args(snapshot::LOCAL_SNAPSHOT_ARGS);
+        let output = self
+            .execute_command(&PlatformContext::new(None), cmd)
+            .await?;
+        snapshot::local_snapshot_from_output(&output).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn canned_responses_and_real_processes() {
+        let ops = MockProcessOperations::new();
+        let ctx = PlatformContext::new(None);
+        ops.respond("codesign", MockResponse::failure(1, "not signed"));
+
+        let mut cmd = ops.create_command("codesign");
+        cmd.args(["-v", "/bin/foo"]);
+        let output = ops.execute_command(&ctx, cmd).await.unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(output.stderr, b"not signed");
+        assert_eq!(
+            ops.which("codesign").await.unwrap(),
+            PathBuf::from("codesign")
+        );
+
+        let mut cmd = ops.create_command("sh");
+        cmd.args(["-c", "printf hi"]);
+        let output = ops.execute_command(&ctx, cmd).await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hi");
+
+        assert_eq!(ops.calls()[0], ["codesign", "-v", "/bin/foo"]);
+        assert!(ops.which("definitely-not-a-program").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn local_snapshots_answer_from_tmutil_responses() {
+        let ops = MockProcessOperations::new();
+        assert_eq!(ops.local_snapshot().await.unwrap(), None);
+
+        ops.respond(
+            crate::process::TMUTIL,
+            MockResponse::success("Created local snapshot with date: 2026-10-18-120000\n"),
+        );
+        assert_eq!(
+            ops.local_snapshot().await.unwrap().as_deref(),
+            Some("2026-10-18-120000")
+        );
+        assert_eq!(ops.calls(), [[crate::process::TMUTIL, "localsnapshot"]]);
+
+        ops.respond(
+            crate::process::TMUTIL,
+            MockResponse::failure(1, "Failed to create local snapshot"),
+        );
+        assert!(ops.local_snapshot().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn sandbox_denials_are_reported_for_their_tag() {
+        let ops = MockProcessOperations::new();
+        let denial = SandboxDenial {
+            process: "cc".to_string(),
+            operation: "file-write-create".to_string(),
+            target: "/usr/local/include/foo.h".to_string(),
+        };
+        ops.deny("sps2-build-a", denial.clone());
+
+        let window = Duration::from_secs(5);
+        assert_eq!(
+            ops.sandbox_denials(window, "sps2-build-a").await.unwrap(),
+            [denial]
+        );
+        assert!(ops
+            .sandbox_denials(window, "sps2-build-b")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,3 +1,7 @@
 //! Platform-specific implementations
 
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "macos")]
 pub mod macos;
+pub mod mock;
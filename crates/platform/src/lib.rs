@@ -1,5 +1,6 @@
 #![warn(mismatched_lifetime_syntaxes)]
-//! Platform abstraction layer for macOS ARM64 package manager operations.
+//! Platform abstraction layer for macOS ARM64 package manager operations,
+//! with a Linux implementation for running the machinery on CI.
 //!
 //! This crate provides a unified interface for platform-specific operations including:
 //! - Binary operations (install_name_tool, otool, codesign)
@@ -11,18 +12,24 @@
 
 pub mod binary;
 pub mod core;
+pub mod fault;
 pub mod filesystem;
 pub mod fs;
+pub mod host;
 pub mod implementations;
 pub mod process;
 
 pub use core::{
     Platform, PlatformCapabilities, PlatformContext, PlatformManager, ToolInfo, ToolRegistry,
 };
+#[cfg(target_os = "linux")]
+pub use implementations::linux::LinuxPlatform;
+#[cfg(target_os = "macos")]
 pub use implementations::macos::MacOSPlatform;
+pub use implementations::mock::MockPlatform;
 
 /// Re-export commonly used types
-pub use binary::BinaryOperations;
+pub use binary::{BinaryOperations, SignatureInfo};
 pub use filesystem::FilesystemOperations;
 pub use fs as filesystem_helpers;
 pub use process::ProcessOperations;
@@ -5,15 +5,23 @@ use sps2_errors::Error;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process::ExitStatus;
+use std::time::Duration;
 
 use crate::core::PlatformContext;
 
+mod sandbox;
+pub(crate) mod snapshot;
+
+pub use sandbox::{parse_sandbox_denials, SandboxDenial};
+pub use snapshot::{parse_local_snapshot, TMUTIL};
+
 /// Platform-specific command builder and execution
 pub struct PlatformCommand {
     program: String,
     args: Vec<String>,
     current_dir: Option<PathBuf>,
     env_vars: HashMap<String, String>,
+    sandbox_profile: Option<String>,
 }
 
 impl PlatformCommand {
@@ -24,6 +32,7 @@ impl PlatformCommand {
             args: Vec::new(),
             current_dir: None,
             env_vars: HashMap::new(),
+            sandbox_profile: None,
         }
     }
 
@@ -76,6 +85,15 @@ impl PlatformCommand {
         self
     }
 
+    /// Confine the command with a sandbox profile (SBPL source)
+    ///
+    /// On macOS the command runs under `sandbox-exec`; other platforms run
+    /// it unconfined.
+    pub fn sandbox_profile<S: Into<String>>(&mut self, profile: S) -> &mut Self {
+        self.sandbox_profile = Some(profile.into());
+        self
+    }
+
     /// Get the program name
     pub fn program(&self) -> &str {
         &self.program
@@ -95,6 +113,11 @@ impl PlatformCommand {
     pub fn get_env_vars(&self) -> &HashMap<String, String> {
         &self.env_vars
     }
+
+    /// Get the sandbox profile
+    pub fn get_sandbox_profile(&self) -> Option<&str> {
+        self.sandbox_profile.as_deref()
+    }
 }
 
 /// Output from command execution
@@ -119,4 +142,20 @@ pub trait ProcessOperations: Send + Sync {
 
     /// Find the path to an executable
     async fn which(&self, program: &str) -> Result<PathBuf, Error>;
+
+    /// Sandbox denials logged during the last `window` by profile rules
+    /// carrying `tag` as their message, such as
+    /// `(deny file-write* (with message "<tag>"))`
+    ///
+    /// Other sandboxed processes on the system log denials too; the tag
+    /// tells the ones of a given profile apart.
+    async fn sandbox_denials(
+        &self,
+        window: Duration,
+        tag: &str,
+    ) -> Result<Vec<SandboxDenial>, Error>;
+
+    /// Take a local APFS snapshot of the system volume and return its date,
+    /// or `None` where the platform has no local snapshots
+    async fn local_snapshot(&self) -> Result<Option<String>, Error>;
 }
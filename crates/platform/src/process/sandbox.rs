@@ -0,0 +1,64 @@
+//! macOS sandbox denials
+//!
+//! Commands confined with `sandbox-exec` fail with `EPERM` when they break
+//! the profile; the kernel logs each denial as a line such as
+//! `Sandbox: cc(4242) deny(1) file-write-create /usr/local/include/foo.h`.
+
+/// One operation the sandbox refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SandboxDenial {
+    /// Name of the process that was denied
+    pub process: String,
+    /// Sandbox operation, such as `file-write-create` or `network-outbound`
+    pub operation: String,
+    /// Path or address the operation was aimed at
+    pub target: String,
+}
+
+/// Parse the sandbox denials in `log`, one per line, ignoring anything else
+#[must_use]
+pub fn parse_sandbox_denials(log: &str) -> Vec<SandboxDenial> {
+    log.lines().filter_map(parse_denial).collect()
+}
+
+fn parse_denial(line: &str) -> Option<SandboxDenial> {
+    let (_, rest) = line.split_once("Sandbox: ")?;
+    let (process, rest) = rest.split_once('(')?;
+    let (_, rest) = rest.split_once(") deny(")?;
+    let (_, rest) = rest.split_once(") ")?;
+    let (operation, target) = rest.split_once(' ').unwrap_or((rest, ""));
+    Some(SandboxDenial {
+        process: process.trim().to_string(),
+        operation: operation.to_string(),
+        target: target.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn denials_are_parsed_from_kernel_log_lines() {
+        let log = "\
+2026-10-17 12:00:01.123 E  kernel[0:1f2]: (Sandbox) Sandbox: cc(4242) deny(1) file-write-create /usr/local/include/foo.h
+2026-10-17 12:00:01.456 Df kernel[0:1f3]: something unrelated
+2026-10-17 12:00:02.000 E  kernel[0:1f4]: (Sandbox) Sandbox: curl(4343) deny(1) network-outbound 93.184.216.34:443
+";
+        assert_eq!(
+            parse_sandbox_denials(log),
+            vec![
+                SandboxDenial {
+                    process: "cc".to_string(),
+                    operation: "file-write-create".to_string(),
+                    target: "/usr/local/include/foo.h".to_string(),
+                },
+                SandboxDenial {
+                    process: "curl".to_string(),
+                    operation: "network-outbound".to_string(),
+                    target: "93.184.216.34:443".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Local APFS snapshots
+//!
+//! `tmutil localsnapshot` snapshots every local APFS volume Time Machine
+//! knows about and reports the snapshot as a line such as
+//! `Created local snapshot with date: 2026-10-18-120000`. The date names
+//! the snapshot (`com.apple.TimeMachine.<date>.local`) both in
+//! `tmutil listlocalsnapshots /` and in Recovery.
+
+use sps2_errors::{Error, PlatformError};
+
+use super::CommandOutput;
+
+/// Program that takes local snapshots
+pub const TMUTIL: &str = "/usr/bin/tmutil";
+
+/// Arguments asking [`TMUTIL`] for a local snapshot
+pub(crate) const LOCAL_SNAPSHOT_ARGS: [&str; 1] = ["localsnapshot"];
+
+/// Date of the snapshot `tmutil localsnapshot` reported creating in
+/// `output`, if any
+#[must_use]
+pub fn parse_local_snapshot(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let (_, date) = line.split_once("local snapshot with date:")?;
+        let date = date.trim();
+        (!date.is_empty()).then(|| date.to_string())
+    })
+}
+
+/// Date of the snapshot a `tmutil localsnapshot` run created
+pub(crate) fn local_snapshot_from_output(output: &CommandOutput) -> Result<String, Error> {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let failed = |message: String| PlatformError::ProcessExecutionFailed {
+        command: "tmutil localsnapshot".to_string(),
+        message,
+    };
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(failed(format!("{}: {}", output.status, stderr.trim())).into());
+    }
+    parse_local_snapshot(&stdout)
+        .ok_or_else(|| failed(format!("no snapshot reported: {}", stdout.trim())).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_date_is_parsed_from_tmutil_output() {
+        let output = "\
+NOTE: local snapshots are considered purgeable and may be removed at any time by deleted(8).
+Created local snapshot with date: 2026-10-18-120000
+";
+        assert_eq!(
+            parse_local_snapshot(output).as_deref(),
+            Some("2026-10-18-120000")
+        );
+        assert_eq!(
+            parse_local_snapshot("Created local snapshot with date:\n"),
+            None
+        );
+        assert_eq!(parse_local_snapshot(""), None);
+    }
+}
@@ -4,10 +4,10 @@
 
 use base64::Engine as _;
 use chrono::Utc;
-use regex::Regex;
 use sps2_errors::{Error, StorageError};
 use sps2_hash::Hash;
-use sps2_index::{DependencyInfo, Index, VersionEntry};
+use sps2_index::{DependencyInfo, Index, IndexDigest, VersionEntry, INDEX_DIGEST_FILE};
+use sps2_types::filename::{PackageFilename, EXTENSION as PACKAGE_EXTENSION};
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
@@ -87,31 +87,43 @@ impl ObjectStore for LocalStore {
 pub struct Publisher<S: ObjectStore> {
     pub store: S,
     pub base_url: String,
+    /// Search endpoint declared in the index, if the repository serves one
+    pub search_url: Option<String>,
 }
 
 impl<S: ObjectStore> Publisher<S> {
     #[must_use]
     pub fn new(store: S, base_url: String) -> Self {
-        Self { store, base_url }
+        Self {
+            store,
+            base_url,
+            search_url: None,
+        }
+    }
+
+    /// Declare the search endpoint clients query for `sps2 search --remote`
+    #[must_use]
+    pub fn with_search_url(mut self, search_url: Option<String>) -> Self {
+        self.search_url = search_url;
+        self
     }
 
     /// Scan a directory for `.sp` files and return artifacts.
     ///
     /// # Errors
     ///
-    /// Returns an error if directory entries cannot be read, or if hashing any
-    /// matched package file fails.
+    /// Returns an error if directory entries cannot be read, if an `.sp`
+    /// filename does not parse as `<name>-<version>-<revision>.<arch>.sp`, or
+    /// if hashing any package file fails.
     pub async fn scan_packages_local_dir(&self, dir: &Path) -> Result<Vec<PackageArtifact>, Error> {
         let mut artifacts = Vec::new();
         let mut rd = fs::read_dir(dir).await?;
-        let re = Regex::new(r"^(.+?)-([^-]+)-(\d+)\.([^.]+)\.sp$")
-            .map_err(|e| Error::internal(e.to_string()))?;
         while let Some(entry) = rd.next_entry().await? {
             let path = entry.path();
             if !path.is_file() {
                 continue;
             }
-            if path.extension().and_then(|s| s.to_str()) != Some("sp") {
+            if path.extension().and_then(|s| s.to_str()) != Some(PACKAGE_EXTENSION) {
                 continue;
             }
             let filename = path
@@ -119,30 +131,19 @@ impl<S: ObjectStore> Publisher<S> {
                 .and_then(|s| s.to_str())
                 .ok_or_else(|| Error::internal("invalid filename"))?
                 .to_string();
-            if let Some(caps) = re.captures(&filename) {
-                // Be defensive and skip if any capture group is missing
-                let Some(g1) = caps.get(1) else { continue };
-                let Some(g2) = caps.get(2) else { continue };
-                let Some(g3) = caps.get(3) else { continue };
-                let Some(g4) = caps.get(4) else { continue };
-
-                let name = g1.as_str().to_string();
-                let version = g2.as_str().to_string();
-                let revision: u32 = g3.as_str().parse().unwrap_or(1);
-                let arch = g4.as_str().to_string();
+            let parsed = PackageFilename::parse(&filename)?;
 
-                // Compute BLAKE3 hash
-                let hash = Hash::blake3_hash_file(&path).await?.to_hex();
+            // Compute BLAKE3 hash
+            let hash = Hash::blake3_hash_file(&path).await?.to_hex();
 
-                artifacts.push(PackageArtifact {
-                    name,
-                    version,
-                    revision,
-                    arch,
-                    blake3: hash,
-                    filename,
-                });
-            }
+            artifacts.push(PackageArtifact {
+                name: parsed.name,
+                version: parsed.version.to_string(),
+                revision: parsed.revision,
+                arch: parsed.arch,
+                blake3: hash,
+                filename,
+            });
         }
         Ok(artifacts)
     }
@@ -151,6 +152,7 @@ impl<S: ObjectStore> Publisher<S> {
     #[must_use]
     pub fn build_index(&self, artifacts: &[PackageArtifact]) -> Index {
         let mut index = Index::new();
+        index.metadata.search_url.clone_from(&self.search_url);
         for a in artifacts {
             let entry = VersionEntry {
                 revision: a.revision,
@@ -173,7 +175,8 @@ impl<S: ObjectStore> Publisher<S> {
         index
     }
 
-    /// Serialize and sign index, then publish `index.json` and `index.json.minisig` to store.
+    /// Serialize and sign index, then publish `index.json`, `index.json.minisig`
+    /// and the block digest `index.json.digest` to store.
     ///
     /// # Errors
     ///
@@ -194,10 +197,15 @@ impl<S: ObjectStore> Publisher<S> {
             Some("index.json"),
         )?;
 
+        let digest = IndexDigest::new(json.as_bytes()).to_json()?;
+
         self.store.put_object("index.json", json.as_bytes()).await?;
         self.store
             .put_object("index.json.minisig", sig.as_bytes())
             .await?;
+        self.store
+            .put_object(INDEX_DIGEST_FILE, digest.as_bytes())
+            .await?;
         Ok(())
     }
 }
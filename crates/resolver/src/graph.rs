@@ -87,6 +87,8 @@ pub struct ResolvedNode {
     pub signature_url: Option<String>,
     /// Expected BLAKE3 hash for integrity verification (if remote)
     pub expected_hash: Option<sps2_hash::Hash>,
+    /// Whether the package is only installed because another recommends it
+    pub recommended: bool,
 }
 
 impl ResolvedNode {
@@ -102,6 +104,7 @@ impl ResolvedNode {
             path: None,
             signature_url: None,
             expected_hash: None,
+            recommended: false,
         }
     }
 
@@ -117,6 +120,7 @@ impl ResolvedNode {
             path: Some(path),
             signature_url: None,
             expected_hash: None,
+            recommended: false,
         }
     }
 
@@ -51,6 +51,8 @@ pub struct ResolutionContext {
     pub local_files: Vec<PathBuf>,
     /// Already installed packages that can satisfy dependencies
     pub installed_packages: Vec<InstalledPackage>,
+    /// Also resolve the packages that resolved packages recommend
+    pub recommends: bool,
 }
 
 impl ResolutionContext {
@@ -62,6 +64,7 @@ impl ResolutionContext {
             build_deps: Vec::new(),
             local_files: Vec::new(),
             installed_packages: Vec::new(),
+            recommends: true,
         }
     }
 
@@ -92,6 +95,13 @@ impl ResolutionContext {
         self.installed_packages = packages;
         self
     }
+
+    /// Include or leave out recommended packages
+    #[must_use]
+    pub fn with_recommends(mut self, recommends: bool) -> Self {
+        self.recommends = recommends;
+        self
+    }
 }
 
 impl Default for ResolutionContext {
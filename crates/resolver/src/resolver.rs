@@ -12,8 +12,8 @@ use sps2_index::{IndexManager, VersionEntry};
 use sps2_platform::{PlatformContext, PlatformManager};
 use sps2_types::package::PackageSpec;
 use sps2_types::version::VersionConstraint;
-use sps2_types::Manifest;
-use std::collections::{HashMap, HashSet};
+use sps2_types::{Arch, Manifest};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::Path;
 
 /// Type alias for version entries map to reduce complexity
@@ -143,25 +143,14 @@ impl Resolver {
 
             // If we have remaining dependencies to resolve, use SAT solver
             if !remaining_package_deps.is_empty() {
-                // Create SAT problem for remaining dependencies
-                let (mut problem, package_deps) =
-                    Self::create_sat_problem_from_deps(&remaining_package_deps);
-
-                // Add available versions and constraints
-                let mut version_entries =
-                    self.add_package_versions_to_problem(&mut problem, &package_deps);
-
-                // Process transitive dependencies
-                self.process_transitive_dependencies(&mut problem, &mut version_entries);
-
-                // Solve and convert to dependency graph
-                let solution = crate::sat::solve_dependencies(problem, self.event_sender()).await?;
-                let sat_graph =
-                    Self::create_dependency_graph_from_solution(&solution, &version_entries)?;
+                let (sat_graph, recommended) = self
+                    .solve_with_recommends(&context, remaining_package_deps)
+                    .await?;
 
                 // Merge SAT results into main graph
-                for (id, node) in sat_graph.nodes {
-                    graph.nodes.insert(id.clone(), node);
+                for (id, mut node) in sat_graph.nodes {
+                    node.recommended = recommended.contains(&id.name);
+                    graph.nodes.insert(id, node);
                 }
                 for (from, tos) in sat_graph.edges {
                     graph.edges.insert(from, tos);
@@ -188,6 +177,129 @@ impl Resolver {
         })?
     }
 
+    /// Solve `package_deps`, adding the packages the solution recommends
+    /// unless `context` leaves them out
+    ///
+    /// Recommendations are tried one at a time in name order and kept when
+    /// they resolve together with everything already selected. One that is
+    /// installed already, missing from the index or conflicting is left
+    /// out. Returns the graph and the names of the packages added as
+    /// recommendations.
+    async fn solve_with_recommends(
+        &self,
+        context: &ResolutionContext,
+        mut package_deps: HashMap<String, Vec<(PackageSpec, DepKind)>>,
+    ) -> Result<(DependencyGraph, HashSet<String>), Error> {
+        let (mut graph, mut recommends, mut malformed) =
+            self.solve_deps(&package_deps, self.event_sender()).await?;
+        let mut added = HashSet::new();
+        let mut skipped = HashSet::new();
+        if !context.recommends {
+            return Ok((graph, added));
+        }
+
+        let mut warned = HashSet::new();
+        loop {
+            for message in std::mem::take(&mut malformed) {
+                if warned.insert(message.clone()) {
+                    self.emit_warning(message);
+                }
+            }
+
+            let mut candidates: BTreeMap<String, Vec<(PackageSpec, DepKind)>> = BTreeMap::new();
+            for spec in std::mem::take(&mut recommends) {
+                let installed = context
+                    .installed_packages
+                    .iter()
+                    .any(|pkg| pkg.name == spec.name);
+                if !installed && !skipped.contains(&spec.name) {
+                    candidates
+                        .entry(spec.name.clone())
+                        .or_default()
+                        .push((spec, DepKind::Runtime));
+                }
+            }
+            candidates.retain(|name, _| !graph.nodes.keys().any(|id| &id.name == name));
+            if candidates.is_empty() {
+                break;
+            }
+
+            for (name, specs) in candidates {
+                if graph.nodes.keys().any(|id| id.name == name) {
+                    continue;
+                }
+                let in_index = self
+                    .index
+                    .index()
+                    .is_some_and(|index| index.packages.contains_key(&name));
+                if !in_index {
+                    self.emit_debug(format!("Recommended package {name} is not in the index"));
+                    skipped.insert(name);
+                    continue;
+                }
+
+                let mut trial = package_deps.clone();
+                trial.insert(name.clone(), specs);
+                match self.solve_deps(&trial, None).await {
+                    Ok((trial_graph, trial_recommends, trial_malformed)) => {
+                        package_deps = trial;
+                        graph = trial_graph;
+                        recommends = trial_recommends;
+                        malformed = trial_malformed;
+                        added.insert(name);
+                    }
+                    Err(e) => {
+                        self.emit_warning(format!("Leaving out recommended package {name}: {e}"));
+                        skipped.insert(name);
+                    }
+                }
+            }
+        }
+
+        Ok((graph, added))
+    }
+
+    /// Solve `package_deps` against the index
+    ///
+    /// Returns the graph of the selected packages, the packages they
+    /// recommend and a message for each recommendation that is not a valid
+    /// package spec. A conflict is reported to `event_sender`, if given.
+    async fn solve_deps(
+        &self,
+        package_deps: &HashMap<String, Vec<(PackageSpec, DepKind)>>,
+        event_sender: Option<&EventSender>,
+    ) -> Result<(DependencyGraph, Vec<PackageSpec>, Vec<String>), Error> {
+        // Create SAT problem for remaining dependencies
+        let (mut problem, package_deps) = Self::create_sat_problem_from_deps(package_deps);
+
+        // Add available versions and constraints
+        let mut version_entries = self.add_package_versions_to_problem(&mut problem, &package_deps);
+
+        // Process transitive dependencies
+        self.process_transitive_dependencies(&mut problem, &mut version_entries);
+
+        // Solve and convert to dependency graph
+        let solution = crate::sat::solve_dependencies(problem, event_sender).await?;
+        let graph = Self::create_dependency_graph_from_solution(&solution, &version_entries)?;
+
+        let mut recommends = Vec::new();
+        let mut malformed = Vec::new();
+        for (name, version) in &solution.selected {
+            let Some((entry, _)) = version_entries.get(&(name.clone(), version.clone())) else {
+                continue;
+            };
+            for spec in &entry.dependencies.recommends {
+                match PackageSpec::parse(spec) {
+                    Ok(spec) => recommends.push(spec),
+                    Err(e) => malformed.push(format!(
+                        "Ignoring recommendation '{spec}' of {name} {version}: {e}"
+                    )),
+                }
+            }
+        }
+        Ok((graph, recommends, malformed))
+    }
+
     /// Create SAT problem from already-collected dependencies
     fn create_sat_problem_from_deps(
         package_deps: &HashMap<String, Vec<(PackageSpec, DepKind)>>,
@@ -220,7 +332,11 @@ impl Resolver {
         for (package_name, specs) in package_deps {
             if let Some(index) = self.index.index() {
                 if let Some(package_info) = index.packages.get(package_name) {
-                    for (version_str, version_entry) in &package_info.versions {
+                    for (version_str, builds) in &package_info.versions {
+                        // Builds for another architecture cannot be installed here
+                        let Some(version_entry) = builds.for_host() else {
+                            continue;
+                        };
                         if let Ok(version) = Version::parse(version_str) {
                             // Check if this version satisfies any of the specs
                             let mut satisfies_any = false;
@@ -252,12 +368,20 @@ impl Resolver {
             problem.add_at_most_one_constraint(package_name);
 
             // At least one version must be selected (for required packages)
+            let first_clause = problem.clauses.len();
             problem.add_at_least_one_constraint(package_name);
 
             // Add version constraints as clauses
             for (spec, _kind) in specs {
                 Self::add_version_constraints(problem, spec);
             }
+
+            let constraint = specs
+                .iter()
+                .map(|(spec, _)| spec.version_spec.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            problem.record_requirement(None, package_name.clone(), constraint, first_clause);
         }
 
         version_entries
@@ -439,7 +563,10 @@ impl Resolver {
             if let Some(package_info) = index.packages.get(&params.dep_spec.name) {
                 let mut valid_versions = Vec::new();
 
-                for (version_str, version_entry) in &package_info.versions {
+                for (version_str, builds) in &package_info.versions {
+                    let Some(version_entry) = builds.for_host() else {
+                        continue;
+                    };
                     if let Ok(version) = Version::parse(version_str) {
                         if params.dep_spec.version_spec.matches(&version) {
                             let dep_pv =
@@ -467,9 +594,16 @@ impl Resolver {
                     // Add implication: parent => (dep1 OR dep2 OR ...)
                     // Which is equivalent to: !parent OR dep1 OR dep2 OR ...
                     if let Some(parent_var) = problem.variables.get_variable(&parent_pv) {
+                        let first_clause = problem.clauses.len();
                         let mut clause_lits = vec![Literal::negative(parent_var)];
                         clause_lits.extend(valid_versions.into_iter().map(Literal::positive));
                         problem.add_clause(Clause::new(clause_lits));
+                        problem.record_requirement(
+                            Some(parent_pv.clone()),
+                            params.dep_spec.name.clone(),
+                            params.dep_spec.version_spec.to_string(),
+                            first_clause,
+                        );
                     }
 
                     // Ensure at most one version of the dependency
@@ -483,6 +617,15 @@ impl Resolver {
     async fn resolve_local_file(path: &Path, graph: &mut DependencyGraph) -> Result<(), Error> {
         // Load manifest from local .sp file
         let manifest = Self::load_local_manifest(path).await?;
+        let arch = manifest.arch()?;
+        if !arch.runs_on(Arch::host()) {
+            return Err(PackageError::UnsupportedArch {
+                package: manifest.package.name,
+                arch: arch.to_string(),
+                host: Arch::host().to_string(),
+            }
+            .into());
+        }
 
         let version = Version::parse(&manifest.package.version)?;
         let _package_id = PackageId::new(manifest.package.name.clone(), version.clone());
@@ -560,6 +703,7 @@ impl Resolver {
 
         // Step 3: Parse the manifest
         let manifest = Manifest::from_toml(&manifest_content)?;
+        sps2_types::name::validate_package_name(&manifest.package.name)?;
 
         Ok(manifest)
     }
@@ -672,3 +816,125 @@ impl EventEmitter for Resolver {
         self.event_sender.as_ref()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sps2_events::{AppEvent, GeneralEvent};
+    use sps2_index::{DependencyInfo, Index};
+
+    fn entry(runtime: &[&str], recommends: &[&str]) -> VersionEntry {
+        VersionEntry {
+            revision: 1,
+            arch: "universal2".to_string(),
+            blake3: String::new(),
+            download_url: "https://packages.example.com/package.sp".to_string(),
+            minisig_url: "https://packages.example.com/package.sp.minisig".to_string(),
+            dependencies: DependencyInfo {
+                runtime: runtime.iter().map(ToString::to_string).collect(),
+                build: Vec::new(),
+                recommends: recommends.iter().map(ToString::to_string).collect(),
+            },
+            sbom: None,
+            description: None,
+            homepage: None,
+            license: None,
+        }
+    }
+
+    fn resolver(dir: &Path) -> (Resolver, sps2_events::EventReceiver) {
+        let mut index = Index::new();
+        index.add_version(
+            "app".to_string(),
+            "1.0.0".to_string(),
+            entry(&[], &["docs>=1.0.0", ">=not-a-version"]),
+        );
+        index.add_version(
+            "docs".to_string(),
+            "1.2.0".to_string(),
+            entry(&["fonts"], &[]),
+        );
+        index.add_version("fonts".to_string(), "2.0.0".to_string(), entry(&[], &[]));
+        let mut manager = IndexManager::new(dir);
+        manager.set_index(index);
+        let (tx, rx) = sps2_events::channel();
+        (Resolver::with_events(manager, tx), rx)
+    }
+
+    fn resolved_names(result: &ResolutionResult) -> Vec<String> {
+        let mut names: Vec<String> = result.nodes.keys().map(|id| id.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    fn warnings(rx: &mut sps2_events::EventReceiver) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|message| match message.event {
+                AppEvent::General(GeneralEvent::Warning { message, .. }) => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn install_app() -> ResolutionContext {
+        ResolutionContext::new().add_runtime_dep(PackageSpec::parse("app").unwrap())
+    }
+
+    #[tokio::test]
+    async fn recommended_packages_are_resolved_with_their_dependencies() {
+        let dir = tempfile::tempdir().unwrap();
+        let (resolver, mut rx) = resolver(dir.path());
+
+        let result = resolver.resolve_with_sat(install_app()).await.unwrap();
+
+        assert_eq!(resolved_names(&result), ["app", "docs", "fonts"]);
+        let warnings = warnings(&mut rx);
+        assert_eq!(
+            warnings
+                .iter()
+                .filter(|w| w.contains("'>=not-a-version'"))
+                .count(),
+            1,
+            "{warnings:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn recommendations_can_be_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let (resolver, _rx) = resolver(dir.path());
+
+        let context = install_app().with_recommends(false);
+        let result = resolver.resolve_with_sat(context).await.unwrap();
+
+        assert_eq!(resolved_names(&result), ["app"]);
+    }
+
+    #[tokio::test]
+    async fn each_host_installs_its_own_build_of_a_mixed_architecture_release() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut index = Index::new();
+        for arch in ["arm64", "x86_64"] {
+            let mut build = entry(&[], &[]);
+            build.arch = arch.to_string();
+            build.download_url = format!("https://packages.example.com/tool-1.0.0-1.{arch}.sp");
+            index.add_version("tool".to_string(), "1.0.0".to_string(), build);
+        }
+        let mut manager = IndexManager::new(dir.path());
+        manager.set_index(index);
+        let resolver = Resolver::new(manager);
+
+        let context = ResolutionContext::new().add_runtime_dep(PackageSpec::parse("tool").unwrap());
+        let result = resolver.resolve_with_sat(context).await.unwrap();
+
+        let node = result.nodes.values().next().unwrap();
+        let host = match sps2_types::Arch::host() {
+            sps2_types::Arch::X86_64 => "x86_64",
+            _ => "arm64",
+        };
+        assert_eq!(
+            node.url.as_deref(),
+            Some(format!("https://packages.example.com/tool-1.0.0-1.{host}.sp").as_str())
+        );
+    }
+}
@@ -0,0 +1,337 @@
+//! Human-readable explanations for unsatisfiable dependency problems
+//!
+//! When the solver reports UNSAT, the requirements that produced the clauses
+//! are shrunk to a minimal unsatisfiable subset by deletion: each requirement
+//! is dropped in turn and kept out if the remainder is still unsatisfiable.
+//! The surviving requirements form the conflict chain shown to the user, e.g.
+//! "foo 2.1.0 requires bar >=3.0.0, but baz 1.0.0 requires bar <3.0.0".
+
+use super::{Clause, ConflictExplanation, DependencyProblem, Literal, PackageVersion, SatSolver};
+use std::collections::{BTreeMap, HashSet};
+use std::ops::Range;
+
+/// Upper bound on requirements considered for core minimization.
+///
+/// Minimization performs one solver run per requirement; beyond this size the
+/// caller falls back to the solver's learned-clause analysis.
+const MAX_REQUIREMENTS_FOR_CORE: usize = 256;
+
+/// A dependency requirement and the clauses it contributed to the problem
+#[derive(Debug, Clone)]
+pub struct Requirement {
+    /// Package version that declared the requirement, `None` for user requests
+    pub requester: Option<PackageVersion>,
+    /// Name of the required package
+    pub package: String,
+    /// Version constraint as written by the requester
+    pub constraint: String,
+    /// Indices into [`DependencyProblem::clauses`] owned by this requirement
+    pub(crate) clauses: Range<usize>,
+}
+
+impl Requirement {
+    /// Create a requirement owning the given clause range
+    #[must_use]
+    pub fn new(
+        requester: Option<PackageVersion>,
+        package: String,
+        constraint: String,
+        clauses: Range<usize>,
+    ) -> Self {
+        Self {
+            requester,
+            package,
+            constraint,
+            clauses,
+        }
+    }
+
+    fn requester_label(&self) -> String {
+        match &self.requester {
+            Some(pv) => format!("{} {}", pv.name, pv.version),
+            None => "the request".to_string(),
+        }
+    }
+
+    fn target_label(&self) -> String {
+        if self.constraint.is_empty() || self.constraint == "*" {
+            self.package.clone()
+        } else {
+            format!("{} {}", self.package, self.constraint)
+        }
+    }
+
+    /// Describe this requirement as a single sentence fragment
+    #[must_use]
+    pub fn describe(&self) -> String {
+        match &self.requester {
+            Some(_) => format!(
+                "{} requires {}",
+                self.requester_label(),
+                self.target_label()
+            ),
+            None => format!("{} was requested", self.target_label()),
+        }
+    }
+}
+
+/// Explain why a dependency problem has no solution
+///
+/// Returns `None` if the problem has no recorded requirements, is too large
+/// to minimize, or turns out to be satisfiable when rebuilt.
+#[must_use]
+pub fn explain_conflict(problem: &DependencyProblem) -> Option<ConflictExplanation> {
+    let requirements = &problem.requirements;
+    if requirements.is_empty() || requirements.len() > MAX_REQUIREMENTS_FOR_CORE {
+        return None;
+    }
+
+    let owned: HashSet<usize> = requirements
+        .iter()
+        .flat_map(|req| req.clauses.clone())
+        .collect();
+    let structural: Vec<Clause> = problem
+        .clauses
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| !owned.contains(idx))
+        .map(|(_, clause)| clause.clone())
+        .chain(at_most_one_clauses(problem))
+        .chain(required_package_clauses(problem))
+        .collect();
+
+    let mut active: Vec<bool> = vec![true; requirements.len()];
+    if is_satisfiable(problem, &structural, &active) {
+        return None;
+    }
+
+    // Deletion-based minimization of the unsatisfiable core
+    for idx in 0..requirements.len() {
+        active[idx] = false;
+        if is_satisfiable(problem, &structural, &active) {
+            active[idx] = true;
+        }
+    }
+
+    let core: Vec<&Requirement> = requirements
+        .iter()
+        .zip(&active)
+        .filter_map(|(req, keep)| keep.then_some(req))
+        .collect();
+    if core.is_empty() {
+        return None;
+    }
+
+    Some(build_explanation(&core))
+}
+
+fn is_satisfiable(problem: &DependencyProblem, structural: &[Clause], active: &[bool]) -> bool {
+    let mut solver = SatSolver::with_variable_map(&problem.variables);
+    for clause in structural {
+        solver.add_clause(clause.clone());
+    }
+    for (req, _) in problem
+        .requirements
+        .iter()
+        .zip(active)
+        .filter(|(_, keep)| **keep)
+    {
+        for clause in &problem.clauses[req.clauses.clone()] {
+            solver.add_clause(clause.clone());
+        }
+    }
+    solver.solve().is_ok()
+}
+
+fn at_most_one_clauses(problem: &DependencyProblem) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for package in problem.variables.all_packages() {
+        let vars = problem.variables.get_package_variables(package);
+        for i in 0..vars.len() {
+            for j in (i + 1)..vars.len() {
+                clauses.push(Clause::new(vec![
+                    Literal::negative(vars[i]),
+                    Literal::negative(vars[j]),
+                ]));
+            }
+        }
+    }
+    clauses
+}
+
+fn required_package_clauses(problem: &DependencyProblem) -> Vec<Clause> {
+    problem
+        .required_packages
+        .iter()
+        .map(|package| problem.variables.get_package_variables(package))
+        .filter(|vars| !vars.is_empty())
+        .map(|vars| Clause::new(vars.into_iter().map(Literal::positive).collect()))
+        .collect()
+}
+
+fn build_explanation(core: &[&Requirement]) -> ConflictExplanation {
+    // Group requirements by the package they constrain, in a stable order
+    let mut by_package: BTreeMap<&str, Vec<&Requirement>> = BTreeMap::new();
+    for req in core {
+        by_package
+            .entry(req.package.as_str())
+            .or_default()
+            .push(req);
+    }
+
+    let mut conflicts = Vec::new();
+    let mut conflicting_packages = Vec::new();
+    for (package, reqs) in &by_package {
+        if reqs.len() < 2 {
+            continue;
+        }
+        let first = reqs[0];
+        for other in &reqs[1..] {
+            conflicts.push(format!("{}, but {}", first.describe(), other.describe()));
+            for req in [first, *other] {
+                let version = req
+                    .requester
+                    .as_ref()
+                    .map_or_else(|| req.constraint.clone(), |pv| pv.version.to_string());
+                let name = req
+                    .requester
+                    .as_ref()
+                    .map_or_else(|| (*package).to_string(), |pv| pv.name.clone());
+                if !conflicting_packages.contains(&(name.clone(), version.clone())) {
+                    conflicting_packages.push((name, version));
+                }
+            }
+        }
+    }
+
+    // User requests first, then dependency requirements by requester
+    let mut ordered: Vec<&Requirement> = core.to_vec();
+    ordered.sort_by(|a, b| {
+        (a.requester.is_some(), a.requester_label(), &a.package).cmp(&(
+            b.requester.is_some(),
+            b.requester_label(),
+            &b.package,
+        ))
+    });
+    let chain: Vec<String> = ordered.iter().map(|req| req.describe()).collect();
+
+    let message = if conflicts.is_empty() {
+        format!("no compatible versions exist: {}", chain.join("; "))
+    } else {
+        conflicts.join("; ")
+    };
+
+    ConflictExplanation::new(conflicting_packages, message).with_chain(chain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use semver::Version;
+
+    fn pv(name: &str, version: &str) -> PackageVersion {
+        PackageVersion::new(name.to_string(), Version::parse(version).unwrap())
+    }
+
+    /// Request `package` and require that it be installed
+    fn request(problem: &mut DependencyProblem, package: &str) {
+        let first_clause = problem.clauses.len();
+        problem.add_at_least_one_constraint(package);
+        problem.record_requirement(None, package.to_string(), "*".to_string(), first_clause);
+    }
+
+    /// Make `parent` require one of `versions` of `package`
+    fn depend(
+        problem: &mut DependencyProblem,
+        parent: &PackageVersion,
+        package: &str,
+        constraint: &str,
+        versions: &[&PackageVersion],
+    ) {
+        let first_clause = problem.clauses.len();
+        let mut literals = vec![Literal::negative(
+            problem.variables.get_variable(parent).unwrap(),
+        )];
+        literals.extend(
+            versions
+                .iter()
+                .map(|pv| Literal::positive(problem.variables.get_variable(pv).unwrap())),
+        );
+        problem.add_clause(Clause::new(literals));
+        problem.record_requirement(
+            Some(parent.clone()),
+            package.to_string(),
+            constraint.to_string(),
+            first_clause,
+        );
+    }
+
+    /// `foo` and `baz` are required; `foo` needs `bar` 3 and, unless
+    /// `compatible`, `baz` needs `bar` 2; `qux` is requested on the side
+    fn problem(compatible: bool) -> DependencyProblem {
+        let mut problem = DependencyProblem::new();
+        let (foo, baz, qux) = (pv("foo", "1.0.0"), pv("baz", "1.0.0"), pv("qux", "1.0.0"));
+        let (bar2, bar3) = (pv("bar", "2.0.0"), pv("bar", "3.0.0"));
+        for package in [&foo, &baz, &qux, &bar2, &bar3] {
+            problem.add_package_version(package.clone());
+        }
+        problem.require_package("foo".to_string());
+        problem.require_package("baz".to_string());
+
+        request(&mut problem, "qux");
+        depend(&mut problem, &foo, "bar", ">=3.0.0", &[&bar3]);
+        if compatible {
+            depend(&mut problem, &baz, "bar", ">=2.0.0", &[&bar2, &bar3]);
+        } else {
+            depend(&mut problem, &baz, "bar", "<3.0.0", &[&bar2]);
+        }
+        problem
+    }
+
+    #[test]
+    fn version_conflict_reduces_to_the_two_requirements() {
+        let explanation = explain_conflict(&problem(false)).unwrap();
+        assert_eq!(
+            explanation.message,
+            "foo 1.0.0 requires bar >=3.0.0, but baz 1.0.0 requires bar <3.0.0"
+        );
+        assert_eq!(
+            explanation.chain,
+            [
+                "baz 1.0.0 requires bar <3.0.0",
+                "foo 1.0.0 requires bar >=3.0.0"
+            ]
+        );
+        assert_eq!(
+            explanation.conflicting_packages,
+            [
+                ("foo".to_string(), "1.0.0".to_string()),
+                ("baz".to_string(), "1.0.0".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn unrelated_requirement_is_dropped_from_the_core() {
+        let explanation = explain_conflict(&problem(false)).unwrap();
+        assert!(explanation.chain.iter().all(|link| !link.contains("qux")));
+        assert!(!explanation.message.contains("qux"));
+    }
+
+    #[test]
+    fn satisfiable_problem_has_no_explanation() {
+        assert!(explain_conflict(&problem(true)).is_none());
+        assert!(explain_conflict(&DependencyProblem::new()).is_none());
+    }
+
+    #[test]
+    fn core_without_competing_requirements_is_explained_generically() {
+        let requirement = Requirement::new(None, "missing".to_string(), "^1.0".to_string(), 0..0);
+        let explanation = build_explanation(&[&requirement]);
+        assert_eq!(
+            explanation.message,
+            "no compatible versions exist: missing ^1.0 was requested"
+        );
+        assert!(explanation.conflicting_packages.is_empty());
+    }
+}
@@ -15,12 +15,14 @@ use std::fmt;
 
 mod clause;
 mod conflict_analysis;
+mod explanation;
 mod solver;
 mod types;
 mod variable_map;
 
 pub use clause::{Clause, ClauseRef};
 pub use conflict_analysis::ConflictAnalysis;
+pub use explanation::{explain_conflict, Requirement};
 pub use solver::SatSolver;
 pub use types::{Assignment, Literal, Variable};
 pub use variable_map::VariableMap;
@@ -55,6 +57,8 @@ pub struct DependencyProblem {
     pub clauses: Vec<Clause>,
     /// Required packages (at least one version must be selected)
     pub required_packages: HashSet<String>,
+    /// Requirements that produced clauses, used to explain conflicts
+    pub requirements: Vec<Requirement>,
 }
 
 impl DependencyProblem {
@@ -65,6 +69,7 @@ impl DependencyProblem {
             variables: VariableMap::new(),
             clauses: Vec::new(),
             required_packages: HashSet::new(),
+            requirements: Vec::new(),
         }
     }
 
@@ -78,6 +83,25 @@ impl DependencyProblem {
         self.clauses.push(clause);
     }
 
+    /// Record a requirement owning the clauses added since `first_clause`
+    ///
+    /// Call this after adding the clauses that encode the requirement so that
+    /// conflicts can later be explained in terms of who required what.
+    pub fn record_requirement(
+        &mut self,
+        requester: Option<PackageVersion>,
+        package: String,
+        constraint: String,
+        first_clause: usize,
+    ) {
+        self.requirements.push(Requirement::new(
+            requester,
+            package,
+            constraint,
+            first_clause..self.clauses.len(),
+        ));
+    }
+
     /// Mark a package as required
     pub fn require_package(&mut self, name: String) {
         self.required_packages.insert(name);
@@ -170,6 +194,8 @@ impl DependencySolution {
 pub struct ConflictExplanation {
     pub conflicting_packages: Vec<(String, String)>, // (package, version)
     pub message: String,
+    /// Minimal chain of requirements that cannot be satisfied together
+    pub chain: Vec<String>,
 }
 
 impl ConflictExplanation {
@@ -177,8 +203,14 @@ impl ConflictExplanation {
         Self {
             conflicting_packages,
             message,
+            chain: Vec::new(),
         }
     }
+
+    fn with_chain(mut self, chain: Vec<String>) -> Self {
+        self.chain = chain;
+        self
+    }
 }
 
 /// Convert a dependency problem to CNF and solve using SAT
@@ -261,8 +293,10 @@ pub async fn solve_dependencies(
 
         Ok(DependencySolution::new(selected, assignment))
     } else {
-        // Extract conflict information
-        let conflict = solver.analyze_conflict(&problem);
+        // Explain the conflict in terms of requirements, falling back to
+        // the solver's learned-clause analysis for very large problems
+        let conflict =
+            explain_conflict(&problem).unwrap_or_else(|| solver.analyze_conflict(&problem));
 
         // Emit conflict event if sender is available
         let error = PackageError::DependencyConflict {
@@ -277,6 +311,7 @@ pub async fn solve_dependencies(
                     .iter()
                     .map(|(name, version)| format!("{name}@{version}"))
                     .collect(),
+                conflict.chain.clone(),
             )));
         }
 
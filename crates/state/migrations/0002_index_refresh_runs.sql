@@ -0,0 +1,9 @@
+-- Background index refreshes run by `sps2 daemon`
+CREATE TABLE index_refresh_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_at INTEGER NOT NULL,
+    succeeded INTEGER NOT NULL,
+    upgrades_available INTEGER NOT NULL,
+    error TEXT
+);
+CREATE INDEX idx_index_refresh_runs_time ON index_refresh_runs(run_at DESC);
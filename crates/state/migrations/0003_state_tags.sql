@@ -0,0 +1,7 @@
+-- Names given to states with `sps2 snapshot create`
+CREATE TABLE state_tags (
+    name TEXT PRIMARY KEY,
+    state_id TEXT NOT NULL REFERENCES states(id) ON DELETE CASCADE,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX idx_state_tags_state ON state_tags(state_id);
@@ -0,0 +1,24 @@
+-- Summaries of `sps2 verify` runs, kept for `sps2 verify --history`
+CREATE TABLE verification_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    run_at INTEGER NOT NULL,
+    state_id TEXT NOT NULL,
+    level TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    missing_files INTEGER NOT NULL,
+    corrupted_files INTEGER NOT NULL,
+    missing_packages INTEGER NOT NULL,
+    unexpected_files INTEGER NOT NULL,
+    healed INTEGER NOT NULL
+);
+CREATE INDEX idx_verification_runs_time ON verification_runs(run_at DESC);
+
+-- Paths each run found discrepancies at, to spot files that drift repeatedly
+CREATE TABLE verification_run_paths (
+    run_id INTEGER NOT NULL REFERENCES verification_runs(id) ON DELETE CASCADE,
+    kind TEXT NOT NULL,
+    path TEXT NOT NULL,
+    package TEXT
+);
+CREATE INDEX idx_verification_run_paths_run ON verification_run_paths(run_id);
@@ -0,0 +1,5 @@
+-- Let `sps2 verify --level quick` skip re-hashing files whose size and
+-- modification time are unchanged since they last hashed to `file_hash`.
+-- Rows without a size or hash are treated as unverified.
+ALTER TABLE file_mtime_tracker ADD COLUMN last_verified_size INTEGER;
+ALTER TABLE file_mtime_tracker ADD COLUMN file_hash TEXT;
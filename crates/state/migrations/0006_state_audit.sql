@@ -0,0 +1,14 @@
+-- Who moved the system to a state, why, and what it changed, for
+-- `sps2 history --detail`
+CREATE TABLE state_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    state_id TEXT NOT NULL REFERENCES states(id) ON DELETE CASCADE,
+    recorded_at INTEGER NOT NULL,
+    operation TEXT NOT NULL,
+    description TEXT NOT NULL,
+    actor TEXT,
+    command_line TEXT,
+    requested TEXT NOT NULL,  -- JSON array of the specs asked for
+    changes TEXT NOT NULL     -- JSON array of the resolved package changes
+);
+CREATE INDEX idx_state_audit_state ON state_audit(state_id, recorded_at);
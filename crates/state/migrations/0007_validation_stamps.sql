@@ -0,0 +1,10 @@
+-- Store packages that already passed hash and signature checks, so reusing
+-- them does not verify multi-GB content again. A stamp only counts while the
+-- trusted keys still match the fingerprint it was recorded under.
+CREATE TABLE validation_stamps (
+    store_hash TEXT PRIMARY KEY,
+    package_hash TEXT,              -- archive hash the index vouched for
+    key_id TEXT,                    -- trusted key that signed the archive
+    trust_fingerprint TEXT NOT NULL,
+    validated_at INTEGER NOT NULL
+);
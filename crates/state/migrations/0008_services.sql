@@ -0,0 +1,12 @@
+-- Launchd services rendered from the plist templates of installed packages.
+-- A label may be rendered into the user's and the system domain separately.
+CREATE TABLE services (
+    label TEXT NOT NULL,
+    domain TEXT NOT NULL,           -- launchctl domain target, gui/<uid> or system
+    package_name TEXT NOT NULL,
+    plist_path TEXT NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 0,
+    rendered_at INTEGER NOT NULL,
+    PRIMARY KEY (label, domain)
+);
+CREATE INDEX idx_services_package ON services(package_name);
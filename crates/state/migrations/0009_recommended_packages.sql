@@ -0,0 +1,5 @@
+-- Mark packages installed only because an installed package recommends
+-- them, so autoremove can take them away once nothing recommends or needs
+-- them. Packages installed before recommendations existed count as asked
+-- for.
+ALTER TABLE state_packages ADD COLUMN recommended INTEGER NOT NULL DEFAULT 0;
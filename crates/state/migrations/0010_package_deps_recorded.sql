@@ -0,0 +1,5 @@
+-- Track which package versions have their runtime dependencies recorded in
+-- package_deps, so the constraints installed packages put on each other can
+-- be checked before a partial update. Versions installed before recording
+-- began are filled in from their manifests when first needed.
+ALTER TABLE package_versions ADD COLUMN deps_recorded INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- Record the BSD file flags (chflags) a package gives its files, so they
+-- can be set again whenever the files are linked into a state and checked
+-- by verification. Files recorded before this carry no flags.
+ALTER TABLE package_files ADD COLUMN flags INTEGER NOT NULL DEFAULT 0;
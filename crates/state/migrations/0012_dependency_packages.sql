@@ -0,0 +1,4 @@
+-- Mark packages installed only to satisfy another package's runtime
+-- dependencies, so autoremove can take them away once nothing needs them.
+-- Packages installed before this existed count as asked for.
+ALTER TABLE state_packages ADD COLUMN dependency INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Date of the local APFS snapshot taken right before a state was committed,
+-- so a system broken beyond what sps2 can roll back can be restored from it.
+ALTER TABLE states ADD COLUMN os_snapshot TEXT;
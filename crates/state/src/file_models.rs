@@ -56,6 +56,10 @@ pub struct PackageFileEntry {
     pub uid: i64,
     pub gid: i64,
     pub mtime: Option<i64>,
+    /// BSD file flags (`chflags`) given to the file
+    #[sqlx(default)]
+    #[serde(default)]
+    pub flags: i64,
 }
 
 impl PackageFileEntry {
@@ -80,6 +84,13 @@ impl PackageFileEntry {
     pub fn permissions_octal(&self) -> u32 {
         self.permissions as u32
     }
+
+    /// Whether the entry has a store object; directories and symlinks have
+    /// none
+    #[must_use]
+    pub fn has_store_object(&self) -> bool {
+        !matches!(self.permissions_octal() & 0o170_000, 0o040_000 | 0o120_000)
+    }
 }
 
 /// An installed file tracking its location
@@ -115,14 +126,34 @@ impl InstalledFile {
     }
 }
 
-/// Simple modification time tracker for file verification optimization
-#[derive(Debug, Clone)]
+/// Size and modification time of a live file when it last hashed correctly
+///
+/// Lets quick verification skip re-hashing files that have not changed since.
+/// `last_verified_mtime` is in nanoseconds since the Unix epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct FileMTimeTracker {
     pub file_path: String,
     pub last_verified_mtime: i64,
+    pub last_verified_size: Option<i64>,
+    pub file_hash: Option<String>,
+}
+
+impl FileMTimeTracker {
+    /// Whether a file with this size, mtime and expected hash is unchanged
+    /// since it was last verified
+    #[must_use]
+    pub fn is_current(&self, mtime: i64, size: i64, file_hash: &str) -> bool {
+        self.last_verified_mtime == mtime
+            && self.last_verified_size == Some(size)
+            && self.file_hash.as_deref() == Some(file_hash)
+    }
 }
 
 /// Summary statistics for file-level storage
+///
+/// `total_*` counts every file of every package version, `unique_*` and
+/// `deduplicated_size` count each referenced object once. Orphaned objects
+/// are file objects no package version references.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileStorageStats {
     pub total_files: i64,
@@ -130,6 +161,54 @@ pub struct FileStorageStats {
     pub total_size: i64,
     pub deduplicated_size: i64,
     pub deduplication_ratio: f64,
+    pub orphaned_files: i64,
+    pub orphaned_size: i64,
+}
+
+/// File storage used by one package version
+///
+/// `unique_size` counts the files no other package version shares, which is
+/// roughly what removing the package would free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageStorageUsage {
+    pub name: String,
+    pub version: String,
+    pub files: i64,
+    pub total_size: i64,
+    pub unique_size: i64,
+}
+
+/// A file in the live prefix and the installed package that provides it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOwner {
+    /// Path relative to the live prefix
+    pub path: String,
+    pub package: String,
+    pub version: String,
+}
+
+/// An installed package providing a path, with its store hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathProvider {
+    pub package: String,
+    pub version: String,
+    pub store_hash: String,
+}
+
+/// A path a package installed, with the metadata of its store object
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageFileInfo {
+    /// Path relative to the live prefix
+    pub path: String,
+    /// Content hash; `None` for directories
+    pub hash: Option<String>,
+    /// Size of the stored object; `None` for directories
+    pub size: Option<u64>,
+    pub mode: u32,
+    pub executable: bool,
+    pub is_directory: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symlink_target: Option<String>,
 }
 
 /// File metadata for storage operations
@@ -143,6 +222,8 @@ pub struct FileMetadata {
     pub is_executable: bool,
     pub is_symlink: bool,
     pub symlink_target: Option<String>,
+    /// BSD file flags (`chflags`) given to the file
+    pub flags: u32,
 }
 
 impl FileMetadata {
@@ -158,6 +239,7 @@ impl FileMetadata {
             is_executable: permissions & 0o111 != 0,
             is_symlink: false,
             symlink_target: None,
+            flags: 0,
         }
     }
 
@@ -173,6 +255,7 @@ impl FileMetadata {
             is_executable: false,
             is_symlink: true,
             symlink_target: Some(target),
+            flags: 0,
         }
     }
 }
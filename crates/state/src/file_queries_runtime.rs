@@ -1,14 +1,18 @@
 //! File-level queries for CAS (schema v2)
 
 use crate::file_models::{
-    DeduplicationResult, FileMTimeTracker, FileMetadata, FileObject, FileReference,
-    PackageFileEntry,
+    DeduplicationResult, FileMTimeTracker, FileMetadata, FileObject, FileOwner, FileReference,
+    FileStorageStats, PackageFileEntry, PackageFileInfo, PackageStorageUsage, PathProvider,
 };
 use sps2_errors::{Error, StateError};
 use sps2_hash::Hash;
-use sqlx::{query, Row, Sqlite, Transaction};
+use sqlx::{query, sqlite::SqliteRow, Row, Sqlite, Transaction};
 use std::collections::HashMap;
 
+/// File type bits of a recorded mode
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+
 /// Insert or increment a file object entry.
 ///
 /// # Errors
@@ -129,8 +133,8 @@ pub async fn add_package_file_entry(
     query(
         r#"
         INSERT OR IGNORE INTO package_files
-          (package_version_id, file_hash, rel_path, mode, uid, gid, mtime)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+          (package_version_id, file_hash, rel_path, mode, uid, gid, mtime, flags)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
         "#,
     )
     .bind(pv_id)
@@ -140,6 +144,7 @@ pub async fn add_package_file_entry(
     .bind(file_ref.metadata.uid as i64)
     .bind(file_ref.metadata.gid as i64)
     .bind(file_ref.metadata.mtime)
+    .bind(i64::from(file_ref.metadata.flags))
     .execute(&mut **tx)
     .await
     .map_err(|e| StateError::DatabaseError {
@@ -271,6 +276,89 @@ pub async fn set_file_object_ref_count(
     Ok(res.rows_affected())
 }
 
+/// Merge the file object `duplicate` into `keep`, which holds the same content.
+///
+/// `keep` takes over the references and refcount of `duplicate`, and is
+/// created from its metadata if the database does not know it yet; the
+/// rows of `duplicate` are then deleted. Merging an object already merged
+/// changes nothing. Returns the number of package files repointed.
+///
+/// # Errors
+///
+/// Returns an error if the database operations fail.
+pub async fn merge_file_object(
+    tx: &mut Transaction<'_, Sqlite>,
+    duplicate: &str,
+    keep: &str,
+) -> Result<u64, Error> {
+    let map_err = |what: &str, e: sqlx::Error| -> Error {
+        StateError::DatabaseError {
+            message: format!("failed to {what} while merging file objects: {e}"),
+        }
+        .into()
+    };
+
+    query(
+        r#"
+        INSERT OR IGNORE INTO cas_objects (
+            hash, kind, size_bytes, created_at, ref_count, is_executable, is_symlink,
+            symlink_target, last_seen_at, last_state_id, last_removed_at
+        )
+        SELECT ?2, kind, size_bytes, created_at, 0, is_executable, is_symlink,
+               symlink_target, last_seen_at, last_state_id, last_removed_at
+        FROM cas_objects WHERE hash = ?1 AND kind = 'file'
+        "#,
+    )
+    .bind(duplicate)
+    .bind(keep)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| map_err("copy file object", e))?;
+
+    query(
+        r#"
+        UPDATE cas_objects
+        SET ref_count = ref_count + COALESCE(
+            (SELECT ref_count FROM cas_objects WHERE hash = ?1 AND kind = 'file'), 0)
+        WHERE hash = ?2 AND kind = 'file'
+        "#,
+    )
+    .bind(duplicate)
+    .bind(keep)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| map_err("move refcount", e))?;
+
+    let repointed = query("UPDATE package_files SET file_hash = ?2 WHERE file_hash = ?1")
+        .bind(duplicate)
+        .bind(keep)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| map_err("repoint package files", e))?
+        .rows_affected();
+
+    query("UPDATE file_mtime_tracker SET file_hash = ?2 WHERE file_hash = ?1")
+        .bind(duplicate)
+        .bind(keep)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| map_err("repoint mtime trackers", e))?;
+
+    query("DELETE FROM file_verification WHERE file_hash = ?1")
+        .bind(duplicate)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| map_err("delete verification status", e))?;
+
+    query("DELETE FROM cas_objects WHERE hash = ?1 AND kind = 'file'")
+        .bind(duplicate)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| map_err("delete file object", e))?;
+
+    Ok(repointed)
+}
+
 /// Fetch a file object.
 ///
 /// # Errors
@@ -353,11 +441,16 @@ pub async fn get_file_last_ref_map(
 ) -> Result<HashMap<String, i64>, Error> {
     let rows = query(
         r#"
-        SELECT pf.file_hash AS hash, COALESCE(MAX(s.created_at), 0) AS last_ref
-        FROM package_files pf
-        JOIN state_packages sp ON sp.package_version_id = pf.package_version_id
-        JOIN states s ON s.id = sp.state_id
-        GROUP BY pf.file_hash
+        SELECT hash, COALESCE(MAX(last_ref), 0) AS last_ref FROM (
+            SELECT pf.file_hash AS hash, s.created_at AS last_ref
+            FROM package_files pf
+            JOIN state_packages sp ON sp.package_version_id = pf.package_version_id
+            JOIN states s ON s.id = sp.state_id
+            UNION ALL
+            SELECT hash, last_seen_at FROM cas_objects
+            WHERE kind = 'file' AND last_seen_at IS NOT NULL
+        )
+        GROUP BY hash
         "#,
     )
     .fetch_all(&mut **tx)
@@ -392,7 +485,8 @@ pub async fn get_package_file_entries(
           pf.mode      AS permissions,
           pf.uid,
           pf.gid,
-          pf.mtime
+          pf.mtime,
+          pf.flags
         FROM state_packages sp
         JOIN package_files pf ON pf.package_version_id = sp.package_version_id
         WHERE sp.id = ?1
@@ -417,6 +511,7 @@ pub async fn get_package_file_entries(
             uid: r.get("uid"),
             gid: r.get("gid"),
             mtime: r.get("mtime"),
+            flags: r.get("flags"),
         })
         .collect())
 }
@@ -468,20 +563,24 @@ pub async fn get_file_entries_by_hash(
 /// Returns an error if the database operation fails.
 pub async fn update_file_mtime(
     tx: &mut Transaction<'_, Sqlite>,
-    file_path: &str,
-    verified_mtime: i64,
+    tracker: &FileMTimeTracker,
 ) -> Result<(), Error> {
     query(
         r#"
-        INSERT INTO file_mtime_tracker (file_path, last_verified_mtime, created_at, updated_at)
-        VALUES (?1, ?2, strftime('%s','now'), strftime('%s','now'))
+        INSERT INTO file_mtime_tracker
+          (file_path, last_verified_mtime, last_verified_size, file_hash, created_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, strftime('%s','now'), strftime('%s','now'))
         ON CONFLICT(file_path) DO UPDATE SET
             last_verified_mtime = excluded.last_verified_mtime,
+            last_verified_size = excluded.last_verified_size,
+            file_hash = excluded.file_hash,
             updated_at = strftime('%s','now')
         "#,
     )
-    .bind(file_path)
-    .bind(verified_mtime)
+    .bind(&tracker.file_path)
+    .bind(tracker.last_verified_mtime)
+    .bind(tracker.last_verified_size)
+    .bind(tracker.file_hash.as_deref())
     .execute(&mut **tx)
     .await
     .map_err(|e| StateError::DatabaseError {
@@ -490,6 +589,54 @@ pub async fn update_file_mtime(
     Ok(())
 }
 
+/// Remove the mtime tracker entry for a path.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn delete_file_mtime(
+    tx: &mut Transaction<'_, Sqlite>,
+    file_path: &str,
+) -> Result<(), Error> {
+    query("DELETE FROM file_mtime_tracker WHERE file_path = ?1")
+        .bind(file_path)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| StateError::DatabaseError {
+            message: format!("failed to delete mtime tracker: {e}"),
+        })?;
+    Ok(())
+}
+
+/// Fetch every mtime tracker row, keyed by file path.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_all_file_mtimes(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<HashMap<String, FileMTimeTracker>, Error> {
+    let rows = query(
+        r#"
+        SELECT file_path, last_verified_mtime, last_verified_size, file_hash
+        FROM file_mtime_tracker
+        "#,
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to fetch file mtime trackers: {e}"),
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let tracker = tracker_from_row(&r);
+            (tracker.file_path.clone(), tracker)
+        })
+        .collect())
+}
+
 /// Fetch package file entries for a state + name + version.
 ///
 /// # Errors
@@ -511,7 +658,8 @@ pub async fn get_package_file_entries_by_name(
           pf.mode      AS permissions,
           pf.uid,
           pf.gid,
-          pf.mtime
+          pf.mtime,
+          pf.flags
         FROM state_packages sp
         JOIN package_versions pv ON pv.id = sp.package_version_id
         JOIN package_files pf ON pf.package_version_id = pv.id
@@ -539,6 +687,177 @@ pub async fn get_package_file_entries_by_name(
             uid: r.get("uid"),
             gid: r.get("gid"),
             mtime: r.get("mtime"),
+            flags: r.get("flags"),
+        })
+        .collect())
+}
+
+/// Relative paths of every file tracked by a state's packages
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_state_file_paths(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &uuid::Uuid,
+) -> Result<Vec<String>, Error> {
+    let rows = query(
+        r#"
+        SELECT DISTINCT pf.rel_path
+        FROM state_packages sp
+        JOIN package_files pf ON pf.package_version_id = sp.package_version_id
+        WHERE sp.state_id = ?1
+        ORDER BY pf.rel_path
+        "#,
+    )
+    .bind(state_id.to_string())
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to fetch state file paths: {e}"),
+    })?;
+
+    Ok(rows.into_iter().map(|r| r.get("rel_path")).collect())
+}
+
+/// Owners of the files a state tracks below `prefix`
+///
+/// `prefix` is a path relative to the live prefix, compared literally; an
+/// empty prefix returns every file. Callers narrow the result to an exact
+/// path or glob.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_file_owners(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &uuid::Uuid,
+    prefix: &str,
+) -> Result<Vec<FileOwner>, Error> {
+    let rows = query(
+        r#"
+        SELECT pf.rel_path, pv.name, pv.version
+        FROM state_packages sp
+        JOIN package_versions pv ON pv.id = sp.package_version_id
+        JOIN package_files pf ON pf.package_version_id = pv.id
+        WHERE sp.state_id = ?1 AND substr(pf.rel_path, 1, length(?2)) = ?2
+        ORDER BY pf.rel_path, pv.name
+        "#,
+    )
+    .bind(state_id.to_string())
+    .bind(prefix)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to fetch file owners: {e}"),
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| FileOwner {
+            path: r.get("rel_path"),
+            package: r.get("name"),
+            version: r.get("version"),
+        })
+        .collect())
+}
+
+/// Packages of a state that install exactly `rel_path`
+///
+/// `rel_path` is relative to the live prefix. More than one package is
+/// returned only if their files conflict.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_path_providers(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &uuid::Uuid,
+    rel_path: &str,
+) -> Result<Vec<PathProvider>, Error> {
+    let rows = query(
+        r#"
+        SELECT pv.name, pv.version, pv.store_hash
+        FROM state_packages sp
+        JOIN package_versions pv ON pv.id = sp.package_version_id
+        JOIN package_files pf ON pf.package_version_id = pv.id
+        WHERE sp.state_id = ?1 AND pf.rel_path = ?2
+        ORDER BY pv.name
+        "#,
+    )
+    .bind(state_id.to_string())
+    .bind(rel_path)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to fetch path providers: {e}"),
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| PathProvider {
+            package: r.get("name"),
+            version: r.get("version"),
+            store_hash: r.get("store_hash"),
+        })
+        .collect())
+}
+
+/// Paths a package installed in a state, with their store object metadata
+///
+/// Directories, recognised by their mode or a missing content hash, come
+/// back with `hash` and `size` unset.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_package_file_listing(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &uuid::Uuid,
+    package_name: &str,
+    package_version: &str,
+) -> Result<Vec<PackageFileInfo>, Error> {
+    let rows = query(
+        r#"
+        SELECT pf.rel_path, pf.file_hash, pf.mode,
+               co.size_bytes, co.is_executable, co.symlink_target
+        FROM state_packages sp
+        JOIN package_versions pv ON pv.id = sp.package_version_id
+        JOIN package_files pf ON pf.package_version_id = pv.id
+        LEFT JOIN cas_objects co ON co.hash = pf.file_hash
+        WHERE sp.state_id = ?1 AND pv.name = ?2 AND pv.version = ?3
+        ORDER BY pf.rel_path
+        "#,
+    )
+    .bind(state_id.to_string())
+    .bind(package_name)
+    .bind(package_version)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to fetch package file listing: {e}"),
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| {
+            let hash: String = r.get("file_hash");
+            let mode = u32::try_from(r.get::<i64, _>("mode")).unwrap_or(0);
+            let size: Option<i64> = r.get("size_bytes");
+            let is_directory = hash.is_empty() || mode & S_IFMT == S_IFDIR;
+            PackageFileInfo {
+                path: r.get("rel_path"),
+                hash: (!is_directory).then_some(hash),
+                size: size
+                    .filter(|_| !is_directory)
+                    .map(|size| u64::try_from(size).unwrap_or(0)),
+                mode,
+                executable: !is_directory
+                    && (mode & 0o111 != 0
+                        || r.get::<Option<bool>, _>("is_executable").unwrap_or(false)),
+                is_directory,
+                symlink_target: r.get("symlink_target"),
+            }
         })
         .collect())
 }
@@ -569,7 +888,8 @@ pub async fn get_package_file_entries_all_states(
           pf.mode      AS permissions,
           pf.uid,
           pf.gid,
-          pf.mtime
+          pf.mtime,
+          pf.flags
         FROM package_versions pv
         JOIN package_files pf ON pf.package_version_id = pv.id
         WHERE pv.name = ?1 AND pv.version = ?2
@@ -595,6 +915,7 @@ pub async fn get_package_file_entries_all_states(
             uid: r.get("uid"),
             gid: r.get("gid"),
             mtime: r.get("mtime"),
+            flags: r.get("flags"),
         })
         .collect())
 }
@@ -610,7 +931,7 @@ pub async fn get_file_mtime(
 ) -> Result<Option<FileMTimeTracker>, Error> {
     let row = query(
         r#"
-        SELECT file_path, last_verified_mtime
+        SELECT file_path, last_verified_mtime, last_verified_size, file_hash
         FROM file_mtime_tracker
         WHERE file_path = ?1
         "#,
@@ -622,10 +943,16 @@ pub async fn get_file_mtime(
         message: format!("failed to fetch file mtime tracker: {e}"),
     })?;
 
-    Ok(row.map(|r| FileMTimeTracker {
+    Ok(row.as_ref().map(tracker_from_row))
+}
+
+fn tracker_from_row(r: &SqliteRow) -> FileMTimeTracker {
+    FileMTimeTracker {
         file_path: r.get("file_path"),
         last_verified_mtime: r.get("last_verified_mtime"),
-    }))
+        last_verified_size: r.get("last_verified_size"),
+        file_hash: r.get("file_hash"),
+    }
 }
 
 /// Legacy: mark package file hashed (no-op under schema v2)
@@ -653,7 +980,8 @@ pub async fn get_package_file_mtimes(
 ) -> Result<Vec<FileMTimeTracker>, Error> {
     let rows = query(
         r#"
-        SELECT DISTINCT fmt.file_path, fmt.last_verified_mtime
+        SELECT DISTINCT fmt.file_path, fmt.last_verified_mtime, fmt.last_verified_size,
+               fmt.file_hash
         FROM file_mtime_tracker fmt
         JOIN package_files pf ON pf.rel_path = fmt.file_path
         JOIN package_versions pv ON pv.id = pf.package_version_id
@@ -669,13 +997,7 @@ pub async fn get_package_file_mtimes(
         message: format!("failed to fetch package file mtime trackers: {e}"),
     })?;
 
-    Ok(rows
-        .into_iter()
-        .map(|r| FileMTimeTracker {
-            file_path: r.get("file_path"),
-            last_verified_mtime: r.get("last_verified_mtime"),
-        })
-        .collect())
+    Ok(rows.iter().map(tracker_from_row).collect())
 }
 
 /// Clear mtime trackers for a package name/version.
@@ -865,6 +1187,104 @@ pub async fn get_verification_stats(
     ))
 }
 
+/// Deduplication totals over the files of all package versions.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_file_storage_stats(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<FileStorageStats, Error> {
+    let row = query(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM package_files) AS total_files,
+            (SELECT COALESCE(SUM(co.size_bytes), 0)
+               FROM package_files pf JOIN cas_objects co ON co.hash = pf.file_hash) AS total_size,
+            COUNT(CASE WHEN referenced THEN 1 END) AS unique_files,
+            COALESCE(SUM(CASE WHEN referenced THEN size_bytes END), 0) AS deduplicated_size,
+            COUNT(CASE WHEN NOT referenced THEN 1 END) AS orphaned_files,
+            COALESCE(SUM(CASE WHEN NOT referenced THEN size_bytes END), 0) AS orphaned_size
+        FROM (
+            SELECT co.size_bytes,
+                   EXISTS (SELECT 1 FROM package_files pf WHERE pf.file_hash = co.hash) AS referenced
+            FROM cas_objects co
+            WHERE co.kind = 'file'
+        )
+        "#,
+    )
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to compute file storage stats: {e}"),
+    })?;
+
+    let total_size: i64 = row.get("total_size");
+    let deduplicated_size: i64 = row.get("deduplicated_size");
+    #[allow(clippy::cast_precision_loss)]
+    let deduplication_ratio = if deduplicated_size > 0 {
+        total_size as f64 / deduplicated_size as f64
+    } else {
+        1.0
+    };
+
+    Ok(FileStorageStats {
+        total_files: row.get("total_files"),
+        unique_files: row.get("unique_files"),
+        total_size,
+        deduplicated_size,
+        deduplication_ratio,
+        orphaned_files: row.get("orphaned_files"),
+        orphaned_size: row.get("orphaned_size"),
+    })
+}
+
+/// Package versions using the most file storage, largest first.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_package_storage_usage(
+    tx: &mut Transaction<'_, Sqlite>,
+    limit: i64,
+) -> Result<Vec<PackageStorageUsage>, Error> {
+    let rows = query(
+        r#"
+        SELECT pv.name, pv.version,
+               COUNT(*) AS files,
+               SUM(co.size_bytes) AS total_size,
+               SUM(CASE WHEN EXISTS (
+                       SELECT 1 FROM package_files other
+                       WHERE other.file_hash = pf.file_hash
+                         AND other.package_version_id <> pf.package_version_id
+                   ) THEN 0 ELSE co.size_bytes END) AS unique_size
+        FROM package_files pf
+        JOIN cas_objects co ON co.hash = pf.file_hash
+        JOIN package_versions pv ON pv.id = pf.package_version_id
+        GROUP BY pv.id
+        ORDER BY total_size DESC, pv.name ASC, pv.version ASC
+        LIMIT ?1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| StateError::DatabaseError {
+        message: format!("failed to compute package storage usage: {e}"),
+    })?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| PackageStorageUsage {
+            name: r.get("name"),
+            version: r.get("version"),
+            files: r.get("files"),
+            total_size: r.get("total_size"),
+            unique_size: r.get("unique_size"),
+        })
+        .collect())
+}
+
 /// Fetch failed verification objects up to limit.
 ///
 /// # Errors
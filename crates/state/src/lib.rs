@@ -21,6 +21,7 @@ pub mod db;
 pub mod file_models;
 pub mod file_queries_runtime;
 pub mod live_slots;
+pub mod lock;
 pub mod manager;
 pub mod models;
 
@@ -35,10 +36,16 @@ pub mod queries {
 mod queries_runtime;
 
 pub use file_models::{
-    DeduplicationResult, FileMTimeTracker, FileMetadata, FileObject, FileReference,
-    FileStorageStats, InstalledFile, PackageFileEntry,
+    DeduplicationResult, FileMTimeTracker, FileMetadata, FileObject, FileOwner, FileReference,
+    FileStorageStats, InstalledFile, PackageFileEntry, PackageFileInfo, PackageStorageUsage,
+    PathProvider,
+};
+pub use lock::InstallLock;
+pub use models::{
+    IndexRefreshRun, Package, PackageRef, RecurringDiscrepancy, ReverseConstraint, ServiceRecord,
+    State, StateAudit, StateAuditEntry, StateTag, StoreRef, ValidationStamp, VerificationCounts,
+    VerificationPath, VerificationRun,
 };
-pub use models::{Package, PackageRef, State, StoreRef};
 
 use sps2_errors::Error;
 use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
@@ -157,17 +157,7 @@ impl LiveSlots {
     /// Returns an error if reading markers or persisting metadata fails.
     pub async fn refresh_slot_states(&mut self) -> Result<(), Error> {
         for slot in SlotId::ALL {
-            let marker_path = self.slot_path(slot).join(SLOT_STATE_FILENAME);
-            let state = match tokio_fs::read_to_string(&marker_path).await {
-                Ok(content) => parse_state_marker(&content)?,
-                Err(err) if err.kind() == ErrorKind::NotFound => None,
-                Err(err) => {
-                    return Err(Error::internal(format!(
-                        "failed to read slot marker {}: {err}",
-                        marker_path.display()
-                    )))
-                }
-            };
+            let state = read_state_marker(&self.slot_path(slot)).await?;
             self.metadata.set_state(slot, state);
         }
         self.persist_metadata().await
@@ -176,6 +166,11 @@ impl LiveSlots {
     /// Swap the prepared slot into `/opt/pm/live`, preserving the previous live
     /// directory under the previously active slot path.
     ///
+    /// The swap can be resumed after it was interrupted at any step: a live
+    /// directory already carrying the new state's marker is not moved again,
+    /// and a live directory that is missing or empty is not moved over the
+    /// preserved backup.
+    ///
     /// # Errors
     ///
     /// Returns an error if filesystem operations fail.
@@ -185,25 +180,32 @@ impl LiveSlots {
         new_state: Uuid,
         parent_state: Uuid,
     ) -> Result<(), Error> {
-        let current_active = self.metadata.active;
+        // Derived from the staging slot rather than the active one, which is
+        // already the staging slot when resuming a completed swap
+        let backup_slot = staging_slot.other();
         let live_path = self.live_path.clone();
         let staging_path = self.slot_path(staging_slot);
-        let backup_path = self.slot_path(current_active);
+        let backup_path = self.slot_path(backup_slot);
 
-        if fs::exists(&backup_path).await {
-            fs::remove_dir_all(&backup_path).await?;
-        }
+        if read_state_marker(&live_path).await? != Some(new_state) {
+            if has_entries(&live_path).await? {
+                if fs::exists(&backup_path).await {
+                    fs::remove_dir_all(&backup_path).await?;
+                }
+                fs::atomic_rename(&live_path, &backup_path).await?;
+            } else if fs::exists(&live_path).await {
+                // Either a fresh prefix or the placeholder recreated after the
+                // previous live directory was already moved to the backup
+                fs::remove_dir_all(&live_path).await?;
+            }
 
-        if fs::exists(&live_path).await {
-            fs::atomic_rename(&live_path, &backup_path).await?;
-        }
+            if let Some(parent) = live_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
 
-        if let Some(parent) = live_path.parent() {
-            fs::create_dir_all(parent).await?;
+            fs::atomic_rename(&staging_path, &live_path).await?;
         }
 
-        fs::atomic_rename(&staging_path, &live_path).await?;
-
         // Recreate an empty directory for the slot we just promoted so future
         // operations can stage into it once it becomes inactive again.
         fs::create_dir_all(&staging_path).await?;
@@ -212,7 +214,7 @@ impl LiveSlots {
 
         self.metadata.active = staging_slot;
         self.metadata.set_state(staging_slot, Some(new_state));
-        self.metadata.set_state(current_active, Some(parent_state));
+        self.metadata.set_state(backup_slot, Some(parent_state));
         self.persist_metadata().await
     }
 
@@ -268,6 +270,35 @@ impl LiveSlots {
     }
 }
 
+/// Read the state marker stored in `dir`, if any
+async fn read_state_marker(dir: &Path) -> Result<Option<Uuid>, Error> {
+    let marker_path = dir.join(SLOT_STATE_FILENAME);
+    match tokio_fs::read_to_string(&marker_path).await {
+        Ok(content) => parse_state_marker(&content),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(Error::internal(format!(
+            "failed to read slot marker {}: {err}",
+            marker_path.display()
+        ))),
+    }
+}
+
+/// Whether `dir` exists and contains at least one entry
+async fn has_entries(dir: &Path) -> Result<bool, Error> {
+    match tokio_fs::read_dir(dir).await {
+        Ok(mut entries) => Ok(entries
+            .next_entry()
+            .await
+            .map_err(|e| Error::internal(format!("failed to read {}: {e}", dir.display())))?
+            .is_some()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(Error::internal(format!(
+            "failed to read {}: {err}",
+            dir.display()
+        ))),
+    }
+}
+
 fn parse_state_marker(value: &str) -> Result<Option<Uuid>, Error> {
     let trimmed = value.trim();
     if trimmed.is_empty() {
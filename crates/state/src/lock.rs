@@ -0,0 +1,64 @@
+//! Exclusive lock on an installation
+//!
+//! Operations that change the active state or move the store hold an
+//! advisory lock on `install.lock` beside the state database, so a second
+//! sps2 process waits instead of working on a prefix that is being changed
+//! underneath it. The lock is released when the guard is dropped or the
+//! process exits.
+
+use sps2_errors::{Error, StorageError};
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+/// Name of the lock file beside the state database
+pub const LOCK_FILE: &str = "install.lock";
+
+/// Held lock on an installation
+#[derive(Debug)]
+pub struct InstallLock {
+    _file: File,
+}
+
+impl InstallLock {
+    /// Wait for and take the lock in `dir`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be opened or locked.
+    pub async fn acquire(dir: &Path) -> Result<Self, Error> {
+        let path = dir.join(LOCK_FILE);
+        tokio::task::spawn_blocking(move || Self::acquire_blocking(&path))
+            .await
+            .map_err(|e| Error::internal(format!("lock task failed: {e}")))?
+    }
+
+    fn acquire_blocking(path: &Path) -> Result<Self, Error> {
+        let failed = || StorageError::LockFailed {
+            path: path.display().to_string(),
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .map_err(|_| failed())?;
+        file.lock().map_err(|_| failed())?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn second_holder_waits_for_the_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = InstallLock::acquire(dir.path()).await.unwrap();
+
+        let file = File::open(dir.path().join(LOCK_FILE)).unwrap();
+        assert!(file.try_lock().is_err());
+        drop(first);
+        assert!(file.try_lock().is_ok());
+    }
+}
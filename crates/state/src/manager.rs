@@ -1,8 +1,13 @@
 //! State manager implementation
 
 use crate::{
+    file_models::{FileStorageStats, PackageStorageUsage},
     live_slots::LiveSlots,
-    models::{Package, PackageRef, State, StoreRef},
+    models::{
+        IndexRefreshRun, OsSnapshot, Package, PackageRef, RecurringDiscrepancy, ReverseConstraint,
+        ServiceRecord, State, StateAudit, StateAuditEntry, StateTag, StoreRef, ValidationStamp,
+        VerificationCounts, VerificationPath, VerificationRun,
+    },
     queries,
 };
 use sps2_errors::Error;
@@ -65,8 +70,19 @@ impl StateManager {
                 &package_ref.package_id.version.to_string(),
                 &package_ref.hash,
                 package_ref.size,
+                package_ref.recommended,
+                package_ref.dependency,
             )
             .await?;
+            if let Some(dependencies) = &package_ref.dependencies {
+                queries::record_package_deps(
+                    tx,
+                    &package_ref.package_id.name,
+                    &package_ref.package_id.version.to_string(),
+                    dependencies,
+                )
+                .await?;
+            }
 
             // Ensure the CAS row exists, but do not adjust refcounts here.
             queries::get_or_create_store_ref(tx, &package_ref.hash, package_ref.size).await?;
@@ -102,6 +118,7 @@ impl StateManager {
                             is_executable: file_hash.mode.map(|m| m & 0o111 != 0).unwrap_or(false),
                             is_symlink: file_hash.is_symlink,
                             symlink_target: None,
+                            flags: file_hash.flags.unwrap_or(0),
                         },
                     };
 
@@ -317,6 +334,56 @@ impl StateManager {
         Ok(packages)
     }
 
+    /// Get the version constraints installed packages put on `name`
+    ///
+    /// Only packages whose dependencies are recorded are considered; see
+    /// [`Self::get_packages_without_recorded_dependencies`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn get_reverse_constraints(
+        &self,
+        name: &str,
+    ) -> Result<Vec<ReverseConstraint>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let state_id = queries::get_active_state(&mut tx).await?;
+        let constraints = queries::get_reverse_constraints(&mut tx, &state_id, name).await?;
+        tx.commit().await?;
+        Ok(constraints)
+    }
+
+    /// Get the installed packages whose runtime dependencies were never
+    /// recorded, such as those installed by older versions of sps2
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn get_packages_without_recorded_dependencies(&self) -> Result<Vec<Package>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let state_id = queries::get_active_state(&mut tx).await?;
+        let packages = queries::get_packages_without_recorded_deps(&mut tx, &state_id).await?;
+        tx.commit().await?;
+        Ok(packages)
+    }
+
+    /// Record the runtime dependency specs of an installed package version
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub async fn record_package_dependencies(
+        &self,
+        name: &str,
+        version: &str,
+        specs: &[String],
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::record_package_deps(&mut tx, name, version, specs).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     /// Get all installed packages in a specific state
     ///
     /// # Errors
@@ -332,6 +399,322 @@ impl StateManager {
         Ok(packages)
     }
 
+    /// Name a state so it can be found by that name later
+    ///
+    /// Named states are never pruned by cleanup.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the state does not exist, the name is taken, or
+    /// the database operation fails.
+    pub async fn tag_state(&self, state_id: &StateId, name: &str) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        if !queries::state_exists(&mut tx, state_id).await? {
+            return Err(sps2_errors::StateError::StateNotFound {
+                id: state_id.to_string(),
+            }
+            .into());
+        }
+        queries::insert_state_tag(&mut tx, name, state_id).await?;
+        // A named state is visible in history again even if it was pruned
+        queries::unprune_state(&mut tx, &state_id.to_string()).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Remove a state name; the state itself is kept
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no state has this name or the database operation
+    /// fails.
+    pub async fn untag_state(&self, name: &str) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        if !queries::delete_state_tag(&mut tx, name).await? {
+            return Err(sps2_errors::StateError::TagNotFound {
+                name: name.to_string(),
+            }
+            .into());
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// State with the given name, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn get_tagged_state(&self, name: &str) -> Result<Option<StateId>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let tag = queries::get_state_tag(&mut tx, name).await?;
+        tx.commit().await?;
+        Ok(tag.map(|tag| tag.state_id()))
+    }
+
+    /// All state names, oldest first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn list_state_tags(&self) -> Result<Vec<StateTag>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let tags = queries::list_state_tags(&mut tx).await?;
+        tx.commit().await?;
+        Ok(tags)
+    }
+
+    /// Record the local APFS snapshot taken before `state_id` was committed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_os_snapshot(
+        &self,
+        state_id: &StateId,
+        snapshot: &str,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::set_state_os_snapshot(&mut tx, state_id, snapshot).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// The local APFS snapshot taken before `state_id` was committed, if any
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn get_os_snapshot(&self, state_id: &StateId) -> Result<Option<OsSnapshot>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let snapshot = queries::get_state_os_snapshot(&mut tx, state_id).await?;
+        tx.commit().await?;
+        Ok(snapshot)
+    }
+
+    /// All recorded local APFS snapshots, newest first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn list_os_snapshots(&self) -> Result<Vec<OsSnapshot>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let snapshots = queries::list_os_snapshots(&mut tx).await?;
+        tx.commit().await?;
+        Ok(snapshots)
+    }
+
+    /// Record the outcome of a background index refresh
+    ///
+    /// `error` is the failure message of a refresh that did not complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_index_refresh(
+        &self,
+        upgrades_available: usize,
+        error: Option<&str>,
+    ) -> Result<(), Error> {
+        let upgrades_available = i64::try_from(upgrades_available)
+            .map_err(|e| Error::internal(format!("upgrade count overflow: {e}")))?;
+        let mut tx = self.pool.begin().await?;
+        queries::insert_index_refresh(&mut tx, upgrades_available, error).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the most recent background index refresh
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn last_index_refresh(&self) -> Result<Option<IndexRefreshRun>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let run = queries::get_last_index_refresh(&mut tx).await?;
+        tx.commit().await?;
+        Ok(run)
+    }
+
+    /// Record the summary of a verification run
+    ///
+    /// `paths` are the locations discrepancies remained at after healing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_verification_run(
+        &self,
+        state_id: &StateId,
+        level: &str,
+        scope: &str,
+        duration_ms: u64,
+        counts: &VerificationCounts,
+        paths: &[VerificationPath],
+    ) -> Result<(), Error> {
+        let duration_ms = i64::try_from(duration_ms)
+            .map_err(|e| Error::internal(format!("verification duration overflow: {e}")))?;
+        let mut tx = self.pool.begin().await?;
+        queries::insert_verification_run(
+            &mut tx,
+            state_id,
+            level,
+            scope,
+            duration_ms,
+            counts,
+            paths,
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// When the live prefix was last verified, as a Unix timestamp
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn last_verification_at(&self) -> Result<Option<i64>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let run_at = queries::get_last_verification_at(&mut tx).await?;
+        tx.commit().await?;
+        Ok(run_at)
+    }
+
+    /// The `limit` most recent verification runs, newest first, with the
+    /// paths more than one of them reported
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn verification_history(
+        &self,
+        limit: usize,
+    ) -> Result<(Vec<VerificationRun>, Vec<RecurringDiscrepancy>), Error> {
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut tx = self.pool.begin().await?;
+        let runs = queries::list_verification_runs(&mut tx, limit).await?;
+        let recurring = queries::get_recurring_discrepancies(&mut tx, limit).await?;
+        tx.commit().await?;
+        Ok((runs, recurring))
+    }
+
+    /// Record why and by whom the system was moved to a state
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_state_audit(&self, audit: &StateAudit) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::insert_state_audit(&mut tx, audit).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Audit entries recorded for a state, oldest first
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn state_audit(&self, state_id: &StateId) -> Result<Vec<StateAuditEntry>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let entries = queries::get_state_audit(&mut tx, state_id).await?;
+        tx.commit().await?;
+        Ok(entries)
+    }
+
+    /// Record that a store package passed verification
+    ///
+    /// Stamps recorded under other trusted keys are dropped at the same time,
+    /// since they no longer vouch for anything.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_validation_stamp(&self, stamp: &ValidationStamp) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::delete_stale_validation_stamps(&mut tx, &stamp.trust_fingerprint).await?;
+        queries::upsert_validation_stamp(&mut tx, stamp).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Validation stamp of a store package that is still good under the
+    /// trusted keys with `trust_fingerprint`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn validation_stamp(
+        &self,
+        store_hash: &str,
+        trust_fingerprint: &str,
+    ) -> Result<Option<ValidationStamp>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let stamp = queries::get_validation_stamp(&mut tx, store_hash, trust_fingerprint).await?;
+        tx.commit().await?;
+        Ok(stamp)
+    }
+
+    /// Record a rendered service
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn record_service(&self, service: &ServiceRecord) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::upsert_service(&mut tx, service).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Forget a service whose plist was removed
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operation fails.
+    pub async fn remove_service(&self, label: &str, domain: &str) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        queries::delete_service(&mut tx, label, domain).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Record whether a service loads at login or boot
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service is not recorded or the database
+    /// operation fails.
+    pub async fn set_service_enabled(
+        &self,
+        label: &str,
+        domain: &str,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        if !queries::set_service_enabled(&mut tx, label, domain, enabled).await? {
+            return Err(sps2_errors::StateError::DatabaseError {
+                message: format!("no service {label} in domain {domain}"),
+            }
+            .into());
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// All rendered services, by label
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn list_services(&self) -> Result<Vec<ServiceRecord>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let services = queries::list_services(&mut tx).await?;
+        tx.commit().await?;
+        Ok(services)
+    }
+
     /// Begin a state transition
     ///
     /// # Errors
@@ -481,6 +864,111 @@ impl StateManager {
         })
     }
 
+    /// Delete states beyond the CAS retention policy
+    ///
+    /// A state is kept if it is among the `keep_count` newest, younger than
+    /// `keep_days` days (when non-zero), active, or named. Package versions
+    /// left without a state are deleted with it, and refcounts are then
+    /// reconciled with the active state so references only the deleted
+    /// states held are dropped. Store content is left to the CAS eviction,
+    /// whose grace periods keep counting from the deleted states.
+    ///
+    /// With `dry_run` the result is computed and nothing is changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database operations fail.
+    pub async fn prune_states(
+        &self,
+        keep_count: usize,
+        keep_days: u32,
+        dry_run: bool,
+    ) -> Result<PruneResult, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let cutoff =
+            (keep_days > 0).then(|| chrono::Utc::now().timestamp() - i64::from(keep_days) * 86_400);
+        let doomed = queries::get_states_beyond_retention(&mut tx, keep_count, cutoff).await?;
+        for id in &doomed {
+            queries::record_state_last_reference(&mut tx, id).await?;
+            queries::delete_state(&mut tx, id).await?;
+        }
+
+        let bytes_released = queries::get_unreferenced_package_bytes(&mut tx).await?;
+        let packages_deleted = queries::delete_unreferenced_package_versions(&mut tx).await?;
+        let active = queries::get_active_state(&mut tx).await?;
+        let (store_updates, file_updates) = Self::sync_refcounts_with_tx(&mut tx, &active).await?;
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+        }
+
+        Ok(PruneResult {
+            states_deleted: doomed.len(),
+            packages_deleted,
+            refcounts_updated: store_updates + file_updates,
+            bytes_released: u64::try_from(bytes_released).unwrap_or(0),
+        })
+    }
+
+    /// Deduplication statistics for the file store
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn file_storage_stats(&self) -> Result<FileStorageStats, Error> {
+        let mut tx = self.pool.begin().await?;
+        let stats = queries::get_file_storage_stats(&mut tx).await?;
+        tx.commit().await?;
+        Ok(stats)
+    }
+
+    /// The `limit` package versions using the most file storage
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn largest_packages(&self, limit: usize) -> Result<Vec<PackageStorageUsage>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let usage =
+            queries::get_package_storage_usage(&mut tx, i64::try_from(limit).unwrap_or(i64::MAX))
+                .await?;
+        tx.commit().await?;
+        Ok(usage)
+    }
+
+    /// Hashes of all file objects the database knows about
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn file_object_hashes(&self) -> Result<std::collections::HashSet<String>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let objects = queries::get_all_file_objects(&mut tx).await?;
+        tx.commit().await?;
+        Ok(objects.into_iter().map(|object| object.hash).collect())
+    }
+
+    /// Merge duplicate file objects into the objects kept in their place
+    ///
+    /// Takes `(duplicate, keep)` hex hash pairs and merges them all in one
+    /// transaction. Returns the number of package files repointed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database update fails.
+    pub async fn merge_file_objects(&self, merges: &[(String, String)]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut repointed = 0;
+        for (duplicate, keep) in merges {
+            repointed += queries::merge_file_object(&mut tx, duplicate, keep).await?;
+        }
+        tx.commit().await?;
+        Ok(repointed)
+    }
+
     /// Get package dependents
     ///
     /// # Errors
@@ -599,6 +1087,8 @@ impl StateManager {
             &package_ref.package_id.version.to_string(),
             &package_ref.hash,
             package_ref.size,
+            package_ref.recommended,
+            package_ref.dependency,
         )
         .await?;
 
@@ -660,6 +1150,8 @@ impl StateManager {
             &package_ref.package_id.version.to_string(),
             &package_ref.hash,
             package_ref.size,
+            package_ref.recommended,
+            package_ref.dependency,
         )
         .await?;
 
@@ -1027,6 +1519,23 @@ impl StateManager {
         Ok(hash)
     }
 
+    /// Get the archive hash recorded when a package name and version was
+    /// downloaded
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database query fails.
+    pub async fn get_package_archive_hash(
+        &self,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<String>, Error> {
+        let mut tx = self.pool.begin().await?;
+        let hash = queries::get_package_archive_hash(&mut tx, name, version).await?;
+        tx.commit().await?;
+        Ok(hash)
+    }
+
     /// Get the store hash associated with a package archive hash
     ///
     /// # Errors
@@ -1069,12 +1578,20 @@ impl StateManager {
         &self,
         state_id: &sps2_types::StateId,
     ) -> Result<(usize, usize), Error> {
-        use std::collections::HashMap;
-
         let mut tx = self.pool.begin().await?;
+        let updates = Self::sync_refcounts_with_tx(&mut tx, state_id).await?;
+        tx.commit().await?;
+        Ok(updates)
+    }
+
+    async fn sync_refcounts_with_tx(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        state_id: &sps2_types::StateId,
+    ) -> Result<(usize, usize), Error> {
+        use std::collections::HashMap;
 
         // Build desired package-level counts by hash for the target state
-        let packages = queries::get_state_packages(&mut tx, state_id).await?;
+        let packages = queries::get_state_packages(tx, state_id).await?;
         let mut store_counts: HashMap<String, (i64 /*count*/, i64 /*size*/)> = HashMap::new();
         for p in &packages {
             store_counts
@@ -1085,17 +1602,17 @@ impl StateManager {
 
         // Ensure store_ref rows exist for desired hashes
         for (hash, (_cnt, size)) in &store_counts {
-            queries::get_or_create_store_ref(&mut tx, hash, *size).await?;
+            queries::get_or_create_store_ref(tx, hash, *size).await?;
         }
 
         // Set store refcounts to exact values (others -> 0)
-        let rows = queries::get_all_store_refs(&mut tx).await?;
+        let rows = queries::get_all_store_refs(tx).await?;
         let mut store_updates = 0usize;
         for r in rows {
             let desired = store_counts.get(&r.hash).map(|(c, _)| *c).unwrap_or(0);
             if r.ref_count != desired {
                 let updated =
-                    crate::queries_runtime::set_store_ref_count(&mut tx, &r.hash, desired).await?;
+                    crate::queries_runtime::set_store_ref_count(tx, &r.hash, desired).await?;
                 if updated > 0 {
                     store_updates += 1;
                 }
@@ -1105,8 +1622,7 @@ impl StateManager {
         // Build desired file-level counts by file_hash for the target state
         let mut file_counts: HashMap<String, i64> = HashMap::new();
         for p in &packages {
-            let entries =
-                crate::file_queries_runtime::get_package_file_entries(&mut tx, p.id).await?;
+            let entries = crate::file_queries_runtime::get_package_file_entries(tx, p.id).await?;
             for e in entries {
                 file_counts
                     .entry(e.file_hash)
@@ -1116,22 +1632,20 @@ impl StateManager {
         }
 
         // Set file object refcounts to exact values (others -> 0)
-        let all_files = crate::file_queries_runtime::get_all_file_objects(&mut tx).await?;
+        let all_files = crate::file_queries_runtime::get_all_file_objects(tx).await?;
         let mut file_updates = 0usize;
         for fo in all_files {
             let desired = file_counts.get(&fo.hash).copied().unwrap_or(0);
             if fo.ref_count != desired {
-                let updated = crate::file_queries_runtime::set_file_object_ref_count(
-                    &mut tx, &fo.hash, desired,
-                )
-                .await?;
+                let updated =
+                    crate::file_queries_runtime::set_file_object_ref_count(tx, &fo.hash, desired)
+                        .await?;
                 if updated > 0 {
                     file_updates += 1;
                 }
             }
         }
 
-        tx.commit().await?;
         Ok((store_updates, file_updates))
     }
 
@@ -1149,6 +1663,15 @@ pub struct TransactionData<'a> {
 }
 
 impl StateManager {
+    /// Wait for exclusive use of the installation
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the lock file cannot be opened or locked.
+    pub async fn lock_install(&self) -> Result<crate::InstallLock, Error> {
+        crate::InstallLock::acquire(self.state_path.parent().expect("Base path must exist")).await
+    }
+
     /// Returns the canonical path to the journal file
     fn journal_path(&self) -> PathBuf {
         self.state_path
@@ -1301,6 +1824,8 @@ impl StateManager {
             archive_delta.0, archive_delta.1, file_delta.0, file_delta.1, file_inc_count,
         ));
 
+        sps2_platform::fault::checkpoint("state.before_journal")?;
+
         // Create the journal file on disk. This is the "commit point" for Phase 1
         let journal = sps2_types::state::TransactionJournal {
             new_state_id: *staging_id,
@@ -1326,6 +1851,7 @@ impl StateManager {
         &self,
         mut journal: sps2_types::state::TransactionJournal,
     ) -> Result<(), Error> {
+        sps2_platform::fault::checkpoint("state.before_swap")?;
         {
             let mut slots = self.live_slots.lock().await;
             slots
@@ -1336,14 +1862,17 @@ impl StateManager {
                 )
                 .await?;
         }
+        sps2_platform::fault::checkpoint("state.after_swap")?;
 
         // Update the journal to the 'Swapped' phase. If a crash happens now,
         // recovery will know the swap is done
         journal.phase = sps2_types::state::TransactionPhase::Swapped;
         self.write_journal(&journal).await?;
+        sps2_platform::fault::checkpoint("state.after_journal_swapped")?;
 
         // Finalize the database by setting the new state as active
         self.finalize_db_state(journal.new_state_id).await?;
+        sps2_platform::fault::checkpoint("state.after_finalize")?;
 
         // The transaction is fully complete. Delete the journal
         self.clear_journal().await?;
@@ -1379,6 +1908,19 @@ pub struct CleanupResult {
     pub space_freed: u64,
 }
 
+/// Result of deleting states beyond the retention policy
+#[derive(Debug)]
+pub struct PruneResult {
+    /// States deleted from the database
+    pub states_deleted: usize,
+    /// Package versions deleted because no state used them anymore
+    pub packages_deleted: usize,
+    /// CAS objects whose refcount was corrected
+    pub refcounts_updated: usize,
+    /// Bytes of store content no remaining state references
+    pub bytes_released: u64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1418,9 +1960,10 @@ mod tests {
     ) {
         let mut tx = state.begin_transaction().await.expect("tx");
         let sid = queries::get_active_state(&mut tx).await.expect("sid");
-        let pkg_id = queries::add_package(&mut tx, &sid, name, version, pkg_hash_hex, 1)
-            .await
-            .expect("add pkg");
+        let pkg_id =
+            queries::add_package(&mut tx, &sid, name, version, pkg_hash_hex, 1, false, false)
+                .await
+                .expect("add pkg");
 
         for (rel, size) in files {
             let fh = sps2_hash::Hash::from_data(rel.as_bytes());
@@ -1469,6 +2012,9 @@ mod tests {
             package_id: pid,
             hash: pkg_hash.clone(),
             size: 1,
+            recommended: false,
+            dependency: false,
+            dependencies: None,
         };
         let td = TransactionData {
             package_refs: &[pref],
@@ -1507,12 +2053,16 @@ mod tests {
             package_id: pid,
             hash: pkg_hash.clone(),
             size: 1,
+            recommended: false,
+            dependency: false,
+            dependencies: None,
         };
         let file_hashes = vec![
             sps2_hash::FileHashResult {
                 relative_path: "bin/x".to_string(),
                 size: 1,
                 mode: Some(0o755),
+                flags: None,
                 is_symlink: false,
                 is_directory: false,
                 hash: sps2_hash::Hash::from_data(b"bin/x"),
@@ -1521,6 +2071,7 @@ mod tests {
                 relative_path: "share/d".to_string(),
                 size: 2,
                 mode: Some(0o644),
+                flags: None,
                 is_symlink: false,
                 is_directory: false,
                 hash: sps2_hash::Hash::from_data(b"share/d"),
@@ -1598,11 +2149,15 @@ mod tests {
             package_id: pid.clone(),
             hash: pkg_hash_v2.clone(),
             size: 1,
+            recommended: false,
+            dependency: false,
+            dependencies: None,
         };
         let fh = sps2_hash::FileHashResult {
             relative_path: "bin/v2".to_string(),
             size: 1,
             mode: Some(0o755),
+            flags: None,
             is_symlink: false,
             is_directory: false,
             hash: sps2_hash::Hash::from_data(b"bin/v2"),
@@ -1629,4 +2184,91 @@ mod tests {
             1
         );
     }
+
+    #[tokio::test]
+    async fn prune_states_keeps_active_and_named_states() {
+        let (_td, state) = mk_state().await;
+        let initial = state.get_active_state().await.expect("initial");
+        let old = uuid::Uuid::new_v4();
+        let named = uuid::Uuid::new_v4();
+        let now = chrono::Utc::now().timestamp();
+
+        let mut tx = state.begin_transaction().await.expect("tx");
+        for (id, age_days) in [(&old, 30), (&named, 20)] {
+            queries::create_state(&mut tx, id, None, "install")
+                .await
+                .expect("create state");
+            sqlx::query("UPDATE states SET created_at = ?1 WHERE id = ?2")
+                .bind(now - age_days * 86_400)
+                .bind(id.to_string())
+                .execute(&mut *tx)
+                .await
+                .expect("backdate state");
+        }
+        queries::add_package(&mut tx, &old, "old", "1.0.0", "old-hash", 7, false, false)
+            .await
+            .expect("add package");
+        queries::get_or_create_store_ref(&mut tx, "old-hash", 7)
+            .await
+            .expect("store ref");
+        tx.commit().await.expect("commit");
+        state.tag_state(&named, "keep-me").await.expect("tag");
+
+        let preview = state.prune_states(0, 10, true).await.expect("dry run");
+        assert_eq!(preview.states_deleted, 1);
+        assert!(state.state_exists(&old).await.expect("exists"));
+
+        let result = state.prune_states(0, 10, false).await.expect("prune");
+        assert_eq!(result.states_deleted, 1);
+        assert_eq!(result.packages_deleted, 1);
+        assert_eq!(result.bytes_released, 7);
+        assert!(!state.state_exists(&old).await.expect("exists"));
+        assert!(state.state_exists(&named).await.expect("exists"));
+        assert!(state.state_exists(&initial).await.expect("exists"));
+    }
+
+    #[tokio::test]
+    async fn os_snapshots_are_recorded_on_states() {
+        let (_td, state) = mk_state().await;
+        let initial = state.get_active_state().await.expect("initial");
+        let later = uuid::Uuid::new_v4();
+        let mut tx = state.begin_transaction().await.expect("tx");
+        queries::create_state(&mut tx, &later, Some(&initial), "upgrade")
+            .await
+            .expect("create state");
+        sqlx::query("UPDATE states SET created_at = created_at + 60 WHERE id = ?1")
+            .bind(later.to_string())
+            .execute(&mut *tx)
+            .await
+            .expect("order states");
+        tx.commit().await.expect("commit");
+
+        assert!(state.list_os_snapshots().await.expect("list").is_empty());
+        state
+            .record_os_snapshot(&initial, "2026-10-18-110000")
+            .await
+            .expect("record");
+        state
+            .record_os_snapshot(&later, "2026-10-18-120000")
+            .await
+            .expect("record");
+
+        let snapshot = state.get_os_snapshot(&later).await.expect("get").unwrap();
+        assert_eq!(snapshot.snapshot, "2026-10-18-120000");
+        assert_eq!(snapshot.operation, "upgrade");
+        assert_eq!(snapshot.state_id(), later);
+        let listed: Vec<String> = state
+            .list_os_snapshots()
+            .await
+            .expect("list")
+            .into_iter()
+            .map(|snapshot| snapshot.snapshot)
+            .collect();
+        assert_eq!(listed, ["2026-10-18-120000", "2026-10-18-110000"]);
+        assert!(state
+            .get_os_snapshot(&uuid::Uuid::new_v4())
+            .await
+            .expect("get")
+            .is_none());
+    }
 }
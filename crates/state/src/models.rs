@@ -3,7 +3,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sps2_hash::Hash;
-use sps2_types::{StateId, Version};
+use sps2_types::{OpChange, StateId, Version};
 use sqlx::FromRow;
 
 /// A system state record
@@ -51,6 +51,14 @@ pub struct Package {
     pub size: i64,
     pub installed_at: i64,
     pub venv_path: Option<String>,
+    /// Installed only because another package recommends it
+    #[sqlx(default)]
+    #[serde(default)]
+    pub recommended: bool,
+    /// Installed only because another package depends on it
+    #[sqlx(default)]
+    #[serde(default)]
+    pub dependency: bool,
 }
 
 impl Package {
@@ -106,6 +114,208 @@ impl StoreRef {
     }
 }
 
+/// A name given to a state
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct StateTag {
+    pub name: String,
+    pub state_id: String,
+    pub created_at: i64,
+}
+
+impl StateTag {
+    /// Convert to `StateId`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored ID is not a valid UUID.
+    #[must_use]
+    pub fn state_id(&self) -> StateId {
+        uuid::Uuid::parse_str(&self.state_id).expect("valid UUID in database")
+    }
+}
+
+/// A local APFS snapshot taken right before a state was committed
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct OsSnapshot {
+    /// Date `tmutil` named the snapshot with, e.g. `2026-10-18-120000`
+    pub snapshot: String,
+    pub state_id: String,
+    pub operation: String,
+    pub created_at: i64,
+}
+
+impl OsSnapshot {
+    /// Convert to `StateId`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored ID is not a valid UUID.
+    #[must_use]
+    pub fn state_id(&self) -> StateId {
+        uuid::Uuid::parse_str(&self.state_id).expect("valid UUID in database")
+    }
+}
+
+/// A background index refresh record
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct IndexRefreshRun {
+    pub id: i64,
+    pub run_at: i64,
+    pub succeeded: bool,
+    pub upgrades_available: i64,
+    pub error: Option<String>,
+}
+
+impl IndexRefreshRun {
+    /// Get the time the refresh ran
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored timestamp is not valid.
+    #[must_use]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.run_at, 0).expect("valid timestamp in database")
+    }
+}
+
+/// Discrepancies a verification run found, by class, and how many it healed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationCounts {
+    pub missing_files: i64,
+    pub corrupted_files: i64,
+    pub missing_packages: i64,
+    pub unexpected_files: i64,
+    pub healed: i64,
+}
+
+impl VerificationCounts {
+    /// Discrepancies left after healing
+    #[must_use]
+    pub fn total(&self) -> i64 {
+        self.missing_files + self.corrupted_files + self.missing_packages + self.unexpected_files
+    }
+}
+
+/// A path a verification run found a discrepancy at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationPath {
+    pub kind: String,
+    pub path: String,
+    pub package: Option<String>,
+}
+
+/// A recorded `sps2 verify` run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerificationRun {
+    pub id: i64,
+    pub run_at: i64,
+    pub state_id: String,
+    pub level: String,
+    pub scope: String,
+    pub duration_ms: i64,
+    #[serde(flatten)]
+    pub counts: VerificationCounts,
+}
+
+impl VerificationRun {
+    /// Convert to `StateId`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored ID is not a valid UUID.
+    #[must_use]
+    pub fn state_id(&self) -> StateId {
+        uuid::Uuid::parse_str(&self.state_id).expect("valid UUID in database")
+    }
+
+    /// Get the time the verification ran
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored timestamp is not valid.
+    #[must_use]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.run_at, 0).expect("valid timestamp in database")
+    }
+}
+
+/// A path that showed up in more than one verification run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecurringDiscrepancy {
+    pub kind: String,
+    pub path: String,
+    pub package: Option<String>,
+    /// Runs the path was reported in
+    pub runs: i64,
+    /// Most recent run it was reported in
+    pub last_seen: i64,
+}
+
+/// A store package whose content passed hash and signature verification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValidationStamp {
+    pub store_hash: String,
+    /// Hash of the archive the package was unpacked from, as the index lists it
+    pub package_hash: Option<String>,
+    /// Trusted key that verified the archive's signature
+    pub key_id: Option<String>,
+    /// Fingerprint of the trusted keys at validation time
+    pub trust_fingerprint: String,
+    pub validated_at: i64,
+}
+
+/// A launchd service rendered from a package's plist template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceRecord {
+    pub label: String,
+    /// Launchd domain target the service belongs to, `gui/<uid>` or `system`
+    pub domain: String,
+    /// Package that ships the template
+    pub package_name: String,
+    /// Where the rendered plist was written
+    pub plist_path: String,
+    /// Whether the service loads at login or boot
+    pub enabled: bool,
+    pub rendered_at: i64,
+}
+
+/// Why and by whom the system was moved to a state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateAudit {
+    pub state_id: String,
+    pub operation: String,
+    /// Human-readable summary, e.g. `install jq: installed jq 1.7.1`
+    pub description: String,
+    /// User that ran the command
+    pub actor: Option<String>,
+    pub command_line: Option<String>,
+    /// Package specs the operation was asked for
+    pub requested: Vec<String>,
+    /// Package changes the operation resolved to
+    pub changes: Vec<OpChange>,
+}
+
+/// A recorded [`StateAudit`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateAuditEntry {
+    pub id: i64,
+    pub recorded_at: i64,
+    #[serde(flatten)]
+    pub audit: StateAudit,
+}
+
+impl StateAuditEntry {
+    /// Get the time the entry was recorded
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stored timestamp is not valid.
+    #[must_use]
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp(self.recorded_at, 0).expect("valid timestamp in database")
+    }
+}
+
 /// Package reference for state transitions
 #[derive(Debug, Clone)]
 pub struct PackageRef {
@@ -113,4 +323,21 @@ pub struct PackageRef {
     pub package_id: sps2_resolver::PackageId,
     pub hash: String,
     pub size: i64,
+    /// Installed only because another package recommends it
+    pub recommended: bool,
+    /// Installed only because another package depends on it
+    pub dependency: bool,
+    /// Runtime dependency specs to record for the package version, `None`
+    /// to keep what is recorded
+    pub dependencies: Option<Vec<String>>,
+}
+
+/// A version constraint an installed package puts on another package
+#[derive(Debug, Clone, PartialEq, Eq, FromRow, Serialize, Deserialize)]
+pub struct ReverseConstraint {
+    /// The installed package depending on the other
+    pub package: String,
+    pub version: String,
+    /// The dependency spec, as in the package's manifest
+    pub spec: String,
 }
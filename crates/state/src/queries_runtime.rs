@@ -1,6 +1,10 @@
 //! Runtime SQL queries for state operations (schema v2)
 
-use crate::models::{Package, State, StoreRef};
+use crate::models::{
+    IndexRefreshRun, OsSnapshot, Package, RecurringDiscrepancy, ReverseConstraint, ServiceRecord,
+    State, StateAudit, StateAuditEntry, StateTag, StoreRef, ValidationStamp, VerificationCounts,
+    VerificationPath, VerificationRun,
+};
 use sps2_errors::{Error, StateError};
 use sps2_types::StateId;
 use sqlx::{query, Row, Sqlite, Transaction};
@@ -96,7 +100,9 @@ pub async fn get_state_packages(
             pv.version         AS version,
             pv.store_hash      AS hash,
             pv.size_bytes      AS size,
-            sp.added_at        AS installed_at
+            sp.added_at        AS installed_at,
+            sp.recommended     AS recommended,
+            sp.dependency      AS dependency
         FROM state_packages sp
         JOIN package_versions pv ON pv.id = sp.package_version_id
         WHERE sp.state_id = ?1
@@ -118,6 +124,8 @@ pub async fn get_state_packages(
             size: row.get("size"),
             installed_at: row.get("installed_at"),
             venv_path: None,
+            recommended: row.get("recommended"),
+            dependency: row.get("dependency"),
         })
         .collect())
 }
@@ -139,6 +147,7 @@ pub async fn get_all_active_packages(
 /// # Errors
 ///
 /// Returns an error if the database operation fails.
+#[allow(clippy::too_many_arguments)]
 pub async fn add_package(
     tx: &mut Transaction<'_, Sqlite>,
     state_id: &StateId,
@@ -146,6 +155,8 @@ pub async fn add_package(
     version: &str,
     store_hash: &str,
     size: i64,
+    recommended: bool,
+    dependency: bool,
 ) -> Result<i64, Error> {
     let id_str = state_id.to_string();
     let now = chrono::Utc::now().timestamp();
@@ -169,11 +180,14 @@ pub async fn add_package(
 
     query(
         r#"
-        INSERT INTO state_packages (state_id, package_version_id, install_size_bytes, added_at)
+        INSERT INTO state_packages
+            (state_id, package_version_id, install_size_bytes, added_at, recommended, dependency)
         VALUES (?1,
             (SELECT id FROM package_versions WHERE name = ?2 AND version = ?3),
             ?4,
-            ?5)
+            ?5,
+            ?6,
+            ?7)
         "#,
     )
     .bind(&id_str)
@@ -181,6 +195,8 @@ pub async fn add_package(
     .bind(version)
     .bind(size)
     .bind(now)
+    .bind(recommended)
+    .bind(dependency)
     .execute(&mut **tx)
     .await?;
 
@@ -344,7 +360,7 @@ pub async fn state_exists(
 ///
 /// Returns an error if the database operation fails.
 pub async fn list_states(tx: &mut Transaction<'_, Sqlite>) -> Result<Vec<StateId>, Error> {
-    let rows = query("SELECT id FROM states ORDER BY created_at DESC")
+    let rows = query("SELECT id FROM states ORDER BY created_at DESC, rowid DESC")
         .fetch_all(&mut **tx)
         .await?;
     let mut result = Vec::with_capacity(rows.len());
@@ -393,7 +409,7 @@ pub async fn get_all_states(tx: &mut Transaction<'_, Sqlite>) -> Result<Vec<Stat
         r#"
         SELECT id, parent_id, created_at, operation, success, rollback_of, pruned_at
         FROM states
-        ORDER BY created_at DESC
+        ORDER BY created_at DESC, rowid DESC
         "#,
     )
     .fetch_all(&mut **tx)
@@ -427,12 +443,13 @@ pub async fn get_states_for_cleanup(
         r#"
         SELECT id FROM states
         WHERE id NOT IN (
-            SELECT id FROM states ORDER BY created_at DESC LIMIT ?1
+            SELECT id FROM states ORDER BY created_at DESC, rowid DESC LIMIT ?1
         )
         AND created_at < ?2
         AND id NOT IN (SELECT state_id FROM active_state WHERE id = 1)
+        AND id NOT IN (SELECT state_id FROM state_tags)
         AND success = 1
-        ORDER BY created_at ASC
+        ORDER BY created_at ASC, rowid ASC
         "#,
     )
     .bind(i64::try_from(keep_count).map_err(|e| Error::internal(e.to_string()))?)
@@ -481,11 +498,12 @@ pub async fn get_states_for_cleanup_strict(
         r#"
         SELECT id FROM states
         WHERE id NOT IN (
-            SELECT id FROM states ORDER BY created_at DESC LIMIT ?1
+            SELECT id FROM states ORDER BY created_at DESC, rowid DESC LIMIT ?1
         )
         AND id NOT IN (SELECT state_id FROM active_state WHERE id = 1)
+        AND id NOT IN (SELECT state_id FROM state_tags)
         AND success = 1
-        ORDER BY created_at ASC
+        ORDER BY created_at ASC, rowid ASC
         "#,
     )
     .bind(i64::try_from(keep_count).map_err(|e| Error::internal(e.to_string()))?)
@@ -556,11 +574,16 @@ pub async fn get_package_last_ref_map(
 ) -> Result<HashMap<String, i64>, Error> {
     let rows = query(
         r#"
-        SELECT pv.store_hash AS hash, COALESCE(MAX(s.created_at), 0) AS last_ref
-        FROM state_packages sp
-        JOIN package_versions pv ON pv.id = sp.package_version_id
-        JOIN states s ON s.id = sp.state_id
-        GROUP BY pv.store_hash
+        SELECT hash, COALESCE(MAX(last_ref), 0) AS last_ref FROM (
+            SELECT pv.store_hash AS hash, s.created_at AS last_ref
+            FROM state_packages sp
+            JOIN package_versions pv ON pv.id = sp.package_version_id
+            JOIN states s ON s.id = sp.state_id
+            UNION ALL
+            SELECT hash, last_seen_at FROM cas_objects
+            WHERE kind = 'archive' AND last_seen_at IS NOT NULL
+        )
+        GROUP BY hash
         "#,
     )
     .fetch_all(&mut **tx)
@@ -651,6 +674,118 @@ pub async fn get_package_dependents(
     Ok(rows.into_iter().map(|r| r.get("name")).collect())
 }
 
+/// Record the runtime dependency specs of a package version, replacing
+/// what was recorded before
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn record_package_deps(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+    version: &str,
+    specs: &[String],
+) -> Result<(), Error> {
+    let row = query("SELECT id FROM package_versions WHERE name = ?1 AND version = ?2")
+        .bind(name)
+        .bind(version)
+        .fetch_optional(&mut **tx)
+        .await?;
+    let Some(row) = row else {
+        return Ok(());
+    };
+    let package_version_id: i64 = row.get("id");
+
+    query("DELETE FROM package_deps WHERE package_version_id = ?1 AND kind = 'runtime'")
+        .bind(package_version_id)
+        .execute(&mut **tx)
+        .await?;
+    for spec in specs {
+        let dep_name = sps2_types::PackageSpec::parse(spec)
+            .map_or_else(|_| spec.trim().to_string(), |parsed| parsed.name);
+        query(
+            r#"
+            INSERT INTO package_deps (package_version_id, dep_name, dep_spec, kind)
+            VALUES (?1, ?2, ?3, 'runtime')
+            "#,
+        )
+        .bind(package_version_id)
+        .bind(dep_name)
+        .bind(spec)
+        .execute(&mut **tx)
+        .await?;
+    }
+    query("UPDATE package_versions SET deps_recorded = 1 WHERE id = ?1")
+        .bind(package_version_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Packages of a state whose runtime dependencies were never recorded
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_packages_without_recorded_deps(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+) -> Result<Vec<Package>, Error> {
+    let unrecorded: Vec<String> = query(
+        r#"
+        SELECT pv.name AS name
+        FROM state_packages sp
+        JOIN package_versions pv ON pv.id = sp.package_version_id
+        WHERE sp.state_id = ?1 AND pv.deps_recorded = 0
+        "#,
+    )
+    .bind(state_id.to_string())
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .map(|row| row.get("name"))
+    .collect();
+    Ok(get_state_packages(tx, state_id)
+        .await?
+        .into_iter()
+        .filter(|package| unrecorded.contains(&package.name))
+        .collect())
+}
+
+/// The runtime dependency specs packages of a state put on `dep_name`
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_reverse_constraints(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+    dep_name: &str,
+) -> Result<Vec<ReverseConstraint>, Error> {
+    let rows = query(
+        r#"
+        SELECT pv.name AS package, pv.version AS version, d.dep_spec AS spec
+        FROM state_packages sp
+        JOIN package_versions pv ON pv.id = sp.package_version_id
+        JOIN package_deps d ON d.package_version_id = pv.id
+        WHERE sp.state_id = ?1 AND d.dep_name = ?2 AND d.kind = 'runtime'
+        ORDER BY pv.name, d.dep_spec
+        "#,
+    )
+    .bind(state_id.to_string())
+    .bind(dep_name)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| ReverseConstraint {
+            package: row.get("package"),
+            version: row.get("version"),
+            spec: row.get("spec"),
+        })
+        .collect())
+}
+
 /// Detailed state list (alias for `get_all_states`)
 ///
 /// # Errors
@@ -669,14 +804,15 @@ pub async fn get_states_older_than(
     tx: &mut Transaction<'_, Sqlite>,
     cutoff: i64,
 ) -> Result<Vec<String>, Error> {
-    let rows = query("SELECT id FROM states WHERE created_at < ? ORDER BY created_at ASC")
-        .bind(cutoff)
-        .fetch_all(&mut **tx)
-        .await?;
+    let rows =
+        query("SELECT id FROM states WHERE created_at < ? ORDER BY created_at ASC, rowid ASC")
+            .bind(cutoff)
+            .fetch_all(&mut **tx)
+            .await?;
     Ok(rows.into_iter().map(|r| r.get("id")).collect())
 }
 
-/// Mark states as pruned (except the active one)
+/// Mark states as pruned (except the active one and named ones)
 ///
 /// # Errors
 ///
@@ -692,11 +828,17 @@ pub async fn mark_pruned_states(
         if id == active_id {
             continue;
         }
-        let res = query("UPDATE states SET pruned_at = ?1 WHERE id = ?2 AND pruned_at IS NULL")
-            .bind(ts)
-            .bind(id)
-            .execute(&mut **tx)
-            .await?;
+        let res = query(
+            r#"
+            UPDATE states SET pruned_at = ?1
+            WHERE id = ?2 AND pruned_at IS NULL
+            AND id NOT IN (SELECT state_id FROM state_tags)
+            "#,
+        )
+        .bind(ts)
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
         if res.rows_affected() > 0 {
             updated += 1;
         }
@@ -717,6 +859,130 @@ pub async fn unprune_state(tx: &mut Transaction<'_, Sqlite>, id: &str) -> Result
     Ok(())
 }
 
+/// States beyond the retention policy: neither among the `keep_count`
+/// newest nor created at or after `cutoff`, and neither active nor named
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_states_beyond_retention(
+    tx: &mut Transaction<'_, Sqlite>,
+    keep_count: usize,
+    cutoff: Option<i64>,
+) -> Result<Vec<String>, Error> {
+    let rows = query(
+        r#"
+        SELECT id FROM states
+        WHERE id NOT IN (
+            SELECT id FROM states ORDER BY created_at DESC, rowid DESC LIMIT ?1
+        )
+        AND (?2 IS NULL OR created_at < ?2)
+        AND id NOT IN (SELECT state_id FROM active_state WHERE id = 1)
+        AND id NOT IN (SELECT state_id FROM state_tags)
+        ORDER BY created_at ASC, rowid ASC
+        "#,
+    )
+    .bind(i64::try_from(keep_count).map_err(|e| Error::internal(e.to_string()))?)
+    .bind(cutoff)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows.into_iter().map(|r| r.get("id")).collect())
+}
+
+/// Carry a state's creation time over to the CAS objects it references, so
+/// eviction grace periods still count from it once the state is deleted
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn record_state_last_reference(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &str,
+) -> Result<(), Error> {
+    query(
+        r#"
+        WITH
+          state_time AS (SELECT created_at FROM states WHERE id = ?1),
+          refs AS (
+            SELECT pv.store_hash AS hash
+            FROM state_packages sp
+            JOIN package_versions pv ON pv.id = sp.package_version_id
+            WHERE sp.state_id = ?1
+            UNION
+            SELECT pf.file_hash
+            FROM state_packages sp
+            JOIN package_files pf ON pf.package_version_id = sp.package_version_id
+            WHERE sp.state_id = ?1
+          )
+        UPDATE cas_objects
+           SET last_seen_at = MAX(COALESCE(last_seen_at, 0), (SELECT created_at FROM state_time))
+        WHERE hash IN (SELECT hash FROM refs)
+        "#,
+    )
+    .bind(state_id)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Bytes of CAS objects referenced only by package versions that no state
+/// or build environment uses
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_unreferenced_package_bytes(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<i64, Error> {
+    let row = query(
+        r#"
+        WITH
+          orphans AS (
+            SELECT id FROM package_versions
+            WHERE id NOT IN (SELECT package_version_id FROM state_packages)
+            AND id NOT IN (SELECT dep_package_version_id FROM build_env_deps)
+          ),
+          released_archives AS (
+            SELECT store_hash AS hash FROM package_versions WHERE id IN orphans
+            EXCEPT
+            SELECT store_hash FROM package_versions WHERE id NOT IN orphans
+          ),
+          released_files AS (
+            SELECT file_hash AS hash FROM package_files WHERE package_version_id IN orphans
+            EXCEPT
+            SELECT file_hash FROM package_files WHERE package_version_id NOT IN orphans
+          )
+        SELECT COALESCE(SUM(size_bytes), 0) AS bytes FROM cas_objects
+        WHERE (kind = 'archive' AND hash IN released_archives)
+           OR (kind = 'file' AND hash IN released_files)
+        "#,
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(row.get("bytes"))
+}
+
+/// Delete package versions that no state or build environment uses, with
+/// their file lists and dependencies
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn delete_unreferenced_package_versions(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<usize, Error> {
+    let res = query(
+        r#"
+        DELETE FROM package_versions
+        WHERE id NOT IN (SELECT package_version_id FROM state_packages)
+        AND id NOT IN (SELECT dep_package_version_id FROM build_env_deps)
+        "#,
+    )
+    .execute(&mut **tx)
+    .await?;
+    usize::try_from(res.rows_affected()).map_err(|e| Error::internal(e.to_string()))
+}
+
 /// Fetch parent state ID if any
 ///
 /// # Errors
@@ -903,6 +1169,615 @@ pub async fn insert_gc_log(
     Ok(())
 }
 
+/// Record the outcome of a background index refresh
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn insert_index_refresh(
+    tx: &mut Transaction<'_, Sqlite>,
+    upgrades_available: i64,
+    error: Option<&str>,
+) -> Result<(), Error> {
+    let now = chrono::Utc::now().timestamp();
+    query(
+        r#"
+        INSERT INTO index_refresh_runs (run_at, succeeded, upgrades_available, error)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+    )
+    .bind(now)
+    .bind(error.is_none())
+    .bind(upgrades_available)
+    .bind(error)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Most recent background index refresh, if any ran
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_last_index_refresh(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<Option<IndexRefreshRun>, Error> {
+    let row = query(
+        r#"
+        SELECT id, run_at, succeeded, upgrades_available, error
+        FROM index_refresh_runs
+        ORDER BY run_at DESC, id DESC
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(row.map(|row| IndexRefreshRun {
+        id: row.get("id"),
+        run_at: row.get("run_at"),
+        succeeded: row.get("succeeded"),
+        upgrades_available: row.get("upgrades_available"),
+        error: row.get("error"),
+    }))
+}
+
+/// Record a verification run and the paths it found discrepancies at
+///
+/// Returns the ID of the new run.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn insert_verification_run(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+    level: &str,
+    scope: &str,
+    duration_ms: i64,
+    counts: &VerificationCounts,
+    paths: &[VerificationPath],
+) -> Result<i64, Error> {
+    let now = chrono::Utc::now().timestamp();
+    let res = query(
+        r#"
+        INSERT INTO verification_runs (
+            run_at, state_id, level, scope, duration_ms,
+            missing_files, corrupted_files, missing_packages, unexpected_files, healed
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        "#,
+    )
+    .bind(now)
+    .bind(state_id.to_string())
+    .bind(level)
+    .bind(scope)
+    .bind(duration_ms)
+    .bind(counts.missing_files)
+    .bind(counts.corrupted_files)
+    .bind(counts.missing_packages)
+    .bind(counts.unexpected_files)
+    .bind(counts.healed)
+    .execute(&mut **tx)
+    .await?;
+    let run_id = res.last_insert_rowid();
+
+    for path in paths {
+        query("INSERT INTO verification_run_paths (run_id, kind, path, package) VALUES (?1, ?2, ?3, ?4)")
+            .bind(run_id)
+            .bind(&path.kind)
+            .bind(&path.path)
+            .bind(path.package.as_deref())
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(run_id)
+}
+
+/// When the live prefix was last verified, as a Unix timestamp
+///
+/// Store-only runs do not count.
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_last_verification_at(
+    tx: &mut Transaction<'_, Sqlite>,
+) -> Result<Option<i64>, Error> {
+    let row =
+        query("SELECT MAX(run_at) AS run_at FROM verification_runs WHERE scope IN ('live', 'all')")
+            .fetch_one(&mut **tx)
+            .await?;
+    Ok(row.get("run_at"))
+}
+
+/// The `limit` most recent verification runs, newest first
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn list_verification_runs(
+    tx: &mut Transaction<'_, Sqlite>,
+    limit: i64,
+) -> Result<Vec<VerificationRun>, Error> {
+    let rows = query(
+        r#"
+        SELECT id, run_at, state_id, level, scope, duration_ms,
+               missing_files, corrupted_files, missing_packages, unexpected_files, healed
+        FROM verification_runs
+        ORDER BY run_at DESC, id DESC
+        LIMIT ?1
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| VerificationRun {
+            id: row.get("id"),
+            run_at: row.get("run_at"),
+            state_id: row.get("state_id"),
+            level: row.get("level"),
+            scope: row.get("scope"),
+            duration_ms: row.get("duration_ms"),
+            counts: VerificationCounts {
+                missing_files: row.get("missing_files"),
+                corrupted_files: row.get("corrupted_files"),
+                missing_packages: row.get("missing_packages"),
+                unexpected_files: row.get("unexpected_files"),
+                healed: row.get("healed"),
+            },
+        })
+        .collect())
+}
+
+/// Paths reported by more than one of the `limit` most recent verification
+/// runs, most frequent first
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_recurring_discrepancies(
+    tx: &mut Transaction<'_, Sqlite>,
+    limit: i64,
+) -> Result<Vec<RecurringDiscrepancy>, Error> {
+    let rows = query(
+        r#"
+        SELECT p.kind, p.path, MAX(p.package) AS package,
+               COUNT(DISTINCT p.run_id) AS runs, MAX(r.run_at) AS last_seen
+        FROM verification_run_paths p
+        JOIN (
+            SELECT id, run_at FROM verification_runs
+            ORDER BY run_at DESC, id DESC
+            LIMIT ?1
+        ) r ON r.id = p.run_id
+        GROUP BY p.kind, p.path
+        HAVING COUNT(DISTINCT p.run_id) > 1
+        ORDER BY runs DESC, last_seen DESC, p.path
+        "#,
+    )
+    .bind(limit)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| RecurringDiscrepancy {
+            kind: row.get("kind"),
+            path: row.get("path"),
+            package: row.get("package"),
+            runs: row.get("runs"),
+            last_seen: row.get("last_seen"),
+        })
+        .collect())
+}
+
+/// Record why and by whom the system was moved to a state
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn insert_state_audit(
+    tx: &mut Transaction<'_, Sqlite>,
+    audit: &StateAudit,
+) -> Result<i64, Error> {
+    let requested = serde_json::to_string(&audit.requested)
+        .map_err(|e| Error::internal(format!("failed to serialize requested specs: {e}")))?;
+    let changes = serde_json::to_string(&audit.changes)
+        .map_err(|e| Error::internal(format!("failed to serialize package changes: {e}")))?;
+    let res = query(
+        r#"
+        INSERT INTO state_audit (
+            state_id, recorded_at, operation, description, actor, command_line, requested, changes
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        "#,
+    )
+    .bind(&audit.state_id)
+    .bind(chrono::Utc::now().timestamp())
+    .bind(&audit.operation)
+    .bind(&audit.description)
+    .bind(audit.actor.as_deref())
+    .bind(audit.command_line.as_deref())
+    .bind(requested)
+    .bind(changes)
+    .execute(&mut **tx)
+    .await?;
+    Ok(res.last_insert_rowid())
+}
+
+fn audit_json<T: serde::de::DeserializeOwned>(column: &str, text: &str) -> Result<T, Error> {
+    serde_json::from_str(text)
+        .map_err(|e| Error::internal(format!("invalid state audit {column}: {e}")))
+}
+
+/// Audit entries recorded for a state, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails or a stored entry is
+/// not valid JSON.
+pub async fn get_state_audit(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+) -> Result<Vec<StateAuditEntry>, Error> {
+    let rows = query(
+        r#"
+        SELECT id, state_id, recorded_at, operation, description, actor, command_line,
+               requested, changes
+        FROM state_audit
+        WHERE state_id = ?1
+        ORDER BY recorded_at ASC, id ASC
+        "#,
+    )
+    .bind(state_id.to_string())
+    .fetch_all(&mut **tx)
+    .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(StateAuditEntry {
+                id: row.get("id"),
+                recorded_at: row.get("recorded_at"),
+                audit: StateAudit {
+                    state_id: row.get("state_id"),
+                    operation: row.get("operation"),
+                    description: row.get("description"),
+                    actor: row.get("actor"),
+                    command_line: row.get("command_line"),
+                    requested: audit_json("requested", row.get::<&str, _>("requested"))?,
+                    changes: audit_json("changes", row.get::<&str, _>("changes"))?,
+                },
+            })
+        })
+        .collect()
+}
+
+/// Record that a store package passed verification, replacing any earlier
+/// stamp for it
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn upsert_validation_stamp(
+    tx: &mut Transaction<'_, Sqlite>,
+    stamp: &ValidationStamp,
+) -> Result<(), Error> {
+    query(
+        r#"
+        INSERT INTO validation_stamps (
+            store_hash, package_hash, key_id, trust_fingerprint, validated_at
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(store_hash) DO UPDATE SET
+            package_hash = excluded.package_hash,
+            key_id = excluded.key_id,
+            trust_fingerprint = excluded.trust_fingerprint,
+            validated_at = excluded.validated_at
+        "#,
+    )
+    .bind(&stamp.store_hash)
+    .bind(stamp.package_hash.as_deref())
+    .bind(stamp.key_id.as_deref())
+    .bind(&stamp.trust_fingerprint)
+    .bind(stamp.validated_at)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Validation stamp of a store package, if one was recorded under
+/// `trust_fingerprint`
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_validation_stamp(
+    tx: &mut Transaction<'_, Sqlite>,
+    store_hash: &str,
+    trust_fingerprint: &str,
+) -> Result<Option<ValidationStamp>, Error> {
+    let row = query(
+        r#"
+        SELECT store_hash, package_hash, key_id, trust_fingerprint, validated_at
+        FROM validation_stamps
+        WHERE store_hash = ?1 AND trust_fingerprint = ?2
+        "#,
+    )
+    .bind(store_hash)
+    .bind(trust_fingerprint)
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    Ok(row.map(|row| ValidationStamp {
+        store_hash: row.get("store_hash"),
+        package_hash: row.get("package_hash"),
+        key_id: row.get("key_id"),
+        trust_fingerprint: row.get("trust_fingerprint"),
+        validated_at: row.get("validated_at"),
+    }))
+}
+
+/// Drop the stamps recorded under any other trust fingerprint
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn delete_stale_validation_stamps(
+    tx: &mut Transaction<'_, Sqlite>,
+    trust_fingerprint: &str,
+) -> Result<u64, Error> {
+    let res = query("DELETE FROM validation_stamps WHERE trust_fingerprint != ?1")
+        .bind(trust_fingerprint)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Name a state
+///
+/// # Errors
+///
+/// Returns an error if the name is already taken or the database operation
+/// fails.
+pub async fn insert_state_tag(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+    state_id: &StateId,
+) -> Result<(), Error> {
+    if let Some(existing) = get_state_tag(tx, name).await? {
+        return Err(StateError::TagExists {
+            name: name.to_string(),
+            state_id: existing.state_id,
+        }
+        .into());
+    }
+    query("INSERT INTO state_tags (name, state_id, created_at) VALUES (?1, ?2, ?3)")
+        .bind(name)
+        .bind(state_id.to_string())
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Remove a state name, returning whether it existed
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn delete_state_tag(tx: &mut Transaction<'_, Sqlite>, name: &str) -> Result<bool, Error> {
+    let res = query("DELETE FROM state_tags WHERE name = ?1")
+        .bind(name)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Look up a state name
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_state_tag(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+) -> Result<Option<StateTag>, Error> {
+    let row = query("SELECT name, state_id, created_at FROM state_tags WHERE name = ?1")
+        .bind(name)
+        .fetch_optional(&mut **tx)
+        .await?;
+    Ok(row.map(|row| StateTag {
+        name: row.get("name"),
+        state_id: row.get("state_id"),
+        created_at: row.get("created_at"),
+    }))
+}
+
+/// All state names, oldest first
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn list_state_tags(tx: &mut Transaction<'_, Sqlite>) -> Result<Vec<StateTag>, Error> {
+    let rows = query("SELECT name, state_id, created_at FROM state_tags ORDER BY created_at, name")
+        .fetch_all(&mut **tx)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| StateTag {
+            name: row.get("name"),
+            state_id: row.get("state_id"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
+
+/// Record the local APFS snapshot taken before a state was committed
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn set_state_os_snapshot(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+    snapshot: &str,
+) -> Result<(), Error> {
+    query("UPDATE states SET os_snapshot = ?1 WHERE id = ?2")
+        .bind(snapshot)
+        .bind(state_id.to_string())
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// The local APFS snapshot taken before a state was committed, if any
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_state_os_snapshot(
+    tx: &mut Transaction<'_, Sqlite>,
+    state_id: &StateId,
+) -> Result<Option<OsSnapshot>, Error> {
+    let row = query(
+        "SELECT os_snapshot, id, operation, created_at FROM states \
+         WHERE id = ?1 AND os_snapshot IS NOT NULL",
+    )
+    .bind(state_id.to_string())
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(row.map(|row| os_snapshot_from_row(&row)))
+}
+
+/// All recorded local APFS snapshots, newest first
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn list_os_snapshots(tx: &mut Transaction<'_, Sqlite>) -> Result<Vec<OsSnapshot>, Error> {
+    let rows = query(
+        "SELECT os_snapshot, id, operation, created_at FROM states \
+         WHERE os_snapshot IS NOT NULL ORDER BY created_at DESC, os_snapshot DESC",
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows.iter().map(os_snapshot_from_row).collect())
+}
+
+fn os_snapshot_from_row(row: &sqlx::sqlite::SqliteRow) -> OsSnapshot {
+    OsSnapshot {
+        snapshot: row.get("os_snapshot"),
+        state_id: row.get("id"),
+        operation: row.get("operation"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Record a rendered service, replacing an earlier record for its label
+/// and domain
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn upsert_service(
+    tx: &mut Transaction<'_, Sqlite>,
+    service: &ServiceRecord,
+) -> Result<(), Error> {
+    query(
+        r#"
+        INSERT INTO services (
+            label, domain, package_name, plist_path, enabled, rendered_at
+        )
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(label, domain) DO UPDATE SET
+            package_name = excluded.package_name,
+            plist_path = excluded.plist_path,
+            enabled = excluded.enabled,
+            rendered_at = excluded.rendered_at
+        "#,
+    )
+    .bind(&service.label)
+    .bind(&service.domain)
+    .bind(&service.package_name)
+    .bind(&service.plist_path)
+    .bind(service.enabled)
+    .bind(service.rendered_at)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Remove a service record, returning whether it existed
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn delete_service(
+    tx: &mut Transaction<'_, Sqlite>,
+    label: &str,
+    domain: &str,
+) -> Result<bool, Error> {
+    let res = query("DELETE FROM services WHERE label = ?1 AND domain = ?2")
+        .bind(label)
+        .bind(domain)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// Mark a service as loading at login or boot, or not, returning whether it
+/// exists
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn set_service_enabled(
+    tx: &mut Transaction<'_, Sqlite>,
+    label: &str,
+    domain: &str,
+    enabled: bool,
+) -> Result<bool, Error> {
+    let res = query("UPDATE services SET enabled = ?3 WHERE label = ?1 AND domain = ?2")
+        .bind(label)
+        .bind(domain)
+        .bind(enabled)
+        .execute(&mut **tx)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+/// All rendered services, by label
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn list_services(tx: &mut Transaction<'_, Sqlite>) -> Result<Vec<ServiceRecord>, Error> {
+    let rows = query(
+        r#"
+        SELECT label, domain, package_name, plist_path, enabled, rendered_at
+        FROM services
+        ORDER BY label, domain
+        "#,
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| ServiceRecord {
+            label: row.get("label"),
+            domain: row.get("domain"),
+            package_name: row.get("package_name"),
+            plist_path: row.get("plist_path"),
+            enabled: row.get("enabled"),
+            rendered_at: row.get("rendered_at"),
+        })
+        .collect())
+}
+
 /// Add package with venv path (venv ignored in v2 schema)
 ///
 /// # Errors
@@ -917,7 +1792,7 @@ pub async fn add_package_with_venv(
     size: i64,
     _venv_path: Option<&str>,
 ) -> Result<i64, Error> {
-    add_package(tx, state_id, name, version, store_hash, size).await
+    add_package(tx, state_id, name, version, store_hash, size, false, false).await
 }
 
 /// Venv path lookup (always None now)
@@ -1011,6 +1886,24 @@ pub async fn get_package_hash(
     Ok(row.map(|r| r.get("store_hash")))
 }
 
+/// Lookup the BLAKE3 of the package archive recorded for name+version
+///
+/// # Errors
+///
+/// Returns an error if the database operation fails.
+pub async fn get_package_archive_hash(
+    tx: &mut Transaction<'_, Sqlite>,
+    name: &str,
+    version: &str,
+) -> Result<Option<String>, Error> {
+    let row = query("SELECT package_hash FROM package_versions WHERE name = ?1 AND version = ?2")
+        .bind(name)
+        .bind(version)
+        .fetch_optional(&mut **tx)
+        .await?;
+    Ok(row.and_then(|r| r.get("package_hash")))
+}
+
 /// Lookup store hash by package archive hash
 ///
 /// # Errors
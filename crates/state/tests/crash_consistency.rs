@@ -0,0 +1,204 @@
+//! tests/crash_consistency.rs
+//!
+//! Interrupt a state transition at every injectable point, restart (which runs
+//! journal recovery) and check that the system always converges to a valid
+//! state: the new state if the journal was written, the parent state otherwise.
+
+use sps2_platform::fault::{Fault, FaultInjector};
+use sps2_state::{StateManager, TransactionData};
+use sps2_types::state::SlotId;
+use std::path::Path;
+use tempfile::TempDir;
+use uuid::Uuid;
+
+const CONTENT_FILE: &str = "content.txt";
+
+/// Checkpoints hit after the journal is written, with the hit that fails
+const POST_JOURNAL_FAULTS: &[(&str, u64)] = &[
+    ("state.before_swap", 1),
+    // Removing the stale backup slot
+    ("fs.remove_dir_all", 1),
+    // Moving live aside, then promoting the staging slot
+    ("fs.atomic_rename", 1),
+    ("fs.atomic_rename", 2),
+    // Live parent, recreated staging slot, slot metadata
+    ("fs.create_dir_all", 1),
+    ("fs.create_dir_all", 2),
+    ("fs.create_dir_all", 3),
+    ("state.after_swap", 1),
+    ("state.after_journal_swapped", 1),
+    ("state.after_finalize", 1),
+];
+
+const NO_DATA: TransactionData<'static> = TransactionData {
+    package_refs: &[],
+    file_references: &[],
+    pending_file_hashes: &[],
+};
+
+/// Write `label` into the inactive slot and prepare a transaction for it
+async fn stage(
+    state: &StateManager,
+    label: &str,
+) -> (Uuid, Uuid, sps2_types::state::TransactionJournal) {
+    let parent_id = state.get_current_state_id().await.unwrap();
+    let staging_id = Uuid::new_v4();
+    let slot = state.inactive_slot().await;
+    let slot_path = state.ensure_slot_dir(slot).await.unwrap();
+    tokio::fs::write(slot_path.join(CONTENT_FILE), label)
+        .await
+        .unwrap();
+
+    let journal = state
+        .prepare_transaction(&staging_id, &parent_id, slot, "test", &NO_DATA)
+        .await
+        .unwrap();
+    (staging_id, parent_id, journal)
+}
+
+/// A state manager whose live directory holds a committed `v1`
+async fn with_baseline() -> (TempDir, StateManager, Uuid) {
+    let td = TempDir::new().unwrap();
+    let state = StateManager::new(td.path()).await.unwrap();
+    let (v1, _, journal) = stage(&state, "v1").await;
+    state
+        .execute_filesystem_swap_and_finalize(journal)
+        .await
+        .unwrap();
+    (td, state, v1)
+}
+
+async fn live_content(state: &StateManager) -> Option<String> {
+    tokio::fs::read_to_string(state.live_path().join(CONTENT_FILE))
+        .await
+        .ok()
+}
+
+async fn assert_no_journal(base: &Path) {
+    assert!(
+        tokio::fs::metadata(base.join("transaction.json"))
+            .await
+            .is_err(),
+        "journal should be cleared after recovery"
+    );
+}
+
+/// Recovery rolled the transaction forward to `new_id` with content `v2`
+async fn assert_converged_forward(base: &Path, new_id: Uuid, staging_slot: SlotId, context: &str) {
+    let state = StateManager::new(base).await.unwrap();
+    assert_no_journal(base).await;
+    assert_eq!(
+        state.get_current_state_id().await.unwrap(),
+        new_id,
+        "{context}: active state"
+    );
+    assert_eq!(
+        live_content(&state).await.as_deref(),
+        Some("v2"),
+        "{context}: live content"
+    );
+    assert_eq!(state.active_slot().await, staging_slot, "{context}: slot");
+    assert_eq!(
+        state.slot_state(staging_slot).await,
+        Some(new_id),
+        "{context}: slot state"
+    );
+
+    // The previous live directory is kept for rollback
+    let backup = state.slot_path(staging_slot.other()).await;
+    assert_eq!(
+        tokio::fs::read_to_string(backup.join(CONTENT_FILE))
+            .await
+            .ok()
+            .as_deref(),
+        Some("v1"),
+        "{context}: backup content"
+    );
+}
+
+#[tokio::test]
+async fn interrupted_commit_converges_to_new_state() {
+    let _scope = FaultInjector::scope().await;
+    let injector = FaultInjector::global();
+
+    for &(point, nth) in POST_JOURNAL_FAULTS {
+        for fault in [Fault::Crash, Fault::EIO, Fault::Timeout] {
+            let context = format!("{fault:?} at {point}#{nth}");
+            let (td, state, _) = with_baseline().await;
+            let (v2, _, journal) = stage(&state, "v2").await;
+
+            injector.fail_nth(point, nth, fault);
+            let result = state
+                .execute_filesystem_swap_and_finalize(journal.clone())
+                .await;
+            assert!(result.is_err(), "{context}: commit should fail");
+            assert_eq!(injector.fired().len(), 1, "{context}: fault should fire");
+            injector.reset();
+            drop(state);
+
+            assert_converged_forward(td.path(), v2, journal.staging_slot, &context).await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn interrupted_recovery_converges_on_next_restart() {
+    let _scope = FaultInjector::scope().await;
+    let injector = FaultInjector::global();
+
+    for &(first, second) in &[
+        ("fs.atomic_rename", "fs.atomic_rename"),
+        ("fs.remove_dir_all", "state.after_finalize"),
+        ("state.after_swap", "state.after_journal_swapped"),
+        ("fs.atomic_rename", "state.after_journal_swapped"),
+    ] {
+        let context = format!("{first} then {second}");
+        let (td, state, _) = with_baseline().await;
+        let (v2, _, journal) = stage(&state, "v2").await;
+
+        injector.fail_next(first, Fault::Crash);
+        assert!(state
+            .execute_filesystem_swap_and_finalize(journal.clone())
+            .await
+            .is_err());
+        drop(state);
+
+        // Recovery itself is interrupted; the journal must survive it
+        injector.fail_next(second, Fault::Crash);
+        drop(StateManager::new(td.path()).await.unwrap());
+        injector.reset();
+
+        assert_converged_forward(td.path(), v2, journal.staging_slot, &context).await;
+    }
+}
+
+#[tokio::test]
+async fn failure_before_journal_keeps_parent_state() {
+    let _scope = FaultInjector::scope().await;
+    let injector = FaultInjector::global();
+
+    for (point, fault) in [
+        ("state.before_journal", Fault::Crash),
+        ("fs.create_dir_all", Fault::EIO),
+    ] {
+        let (td, state, v1) = with_baseline().await;
+        let slot = state.inactive_slot().await;
+        let slot_path = state.ensure_slot_dir(slot).await.unwrap();
+        tokio::fs::write(slot_path.join(CONTENT_FILE), "v2")
+            .await
+            .unwrap();
+
+        injector.fail_next(point, fault);
+        let result = state
+            .prepare_transaction(&Uuid::new_v4(), &v1, slot, "test", &NO_DATA)
+            .await;
+        assert!(result.is_err(), "{point}: prepare should fail");
+        injector.reset();
+        drop(state);
+
+        let state = StateManager::new(td.path()).await.unwrap();
+        assert_no_journal(td.path()).await;
+        assert_eq!(state.get_current_state_id().await.unwrap(), v1, "{point}");
+        assert_eq!(live_content(&state).await.as_deref(), Some("v1"), "{point}");
+    }
+}
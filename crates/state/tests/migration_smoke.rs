@@ -21,6 +21,12 @@ async fn migrations_apply_and_expose_core_tables() {
         "cas_objects",
         "package_files",
         "file_verification",
+        "index_refresh_runs",
+        "state_tags",
+        "verification_runs",
+        "verification_run_paths",
+        "state_audit",
+        "validation_stamps",
     ] {
         let exists: Option<i64> =
             sqlx::query_scalar("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")
@@ -22,10 +22,18 @@ async fn add_and_fetch_package_round_trip() {
     sps2_state::queries::set_active_state(&mut tx, &state_id)
         .await
         .expect("set active state");
-    let pkg_row =
-        sps2_state::queries::add_package(&mut tx, &state_id, "hello", "1.0.0", "store-hash", 42)
-            .await
-            .expect("add package");
+    let pkg_row = sps2_state::queries::add_package(
+        &mut tx,
+        &state_id,
+        "hello",
+        "1.0.0",
+        "store-hash",
+        42,
+        false,
+        false,
+    )
+    .await
+    .expect("add package");
     tx.commit().await.expect("commit");
 
     let mut tx = pool.begin().await.expect("begin tx2");
@@ -65,9 +73,18 @@ async fn add_file_object_and_entry_round_trip() {
     queries::set_active_state(&mut tx, &state_id)
         .await
         .expect("set active state");
-    let pkg_row = queries::add_package(&mut tx, &state_id, "pkg", "1.0.0", "store-hash", 10)
-        .await
-        .expect("add package");
+    let pkg_row = queries::add_package(
+        &mut tx,
+        &state_id,
+        "pkg",
+        "1.0.0",
+        "store-hash",
+        10,
+        false,
+        false,
+    )
+    .await
+    .expect("add package");
 
     let file_hash = Hash::from_data(b"hello-file");
     let metadata = sps2_state::file_models::FileMetadata {
@@ -79,6 +96,7 @@ async fn add_file_object_and_entry_round_trip() {
         is_executable: true,
         is_symlink: false,
         symlink_target: None,
+        flags: 0,
     };
     files::add_file_object(&mut tx, &file_hash, &metadata)
         .await
@@ -104,3 +122,473 @@ async fn add_file_object_and_entry_round_trip() {
     assert_eq!(entry.relative_path, "bin/hello");
     assert_eq!(entry.file_hash, file_hash.to_hex());
 }
+
+#[tokio::test]
+async fn last_index_refresh_is_the_latest_run() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let db_path = temp_dir.path().join("state.sqlite");
+
+    let pool = sps2_state::create_pool(&db_path)
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let mut tx = pool.begin().await.expect("begin tx");
+    assert!(sps2_state::queries::get_last_index_refresh(&mut tx)
+        .await
+        .expect("query empty table")
+        .is_none());
+    sps2_state::queries::insert_index_refresh(&mut tx, 3, None)
+        .await
+        .expect("record success");
+    sps2_state::queries::insert_index_refresh(&mut tx, 0, Some("network unreachable"))
+        .await
+        .expect("record failure");
+    tx.commit().await.expect("commit");
+
+    let mut tx = pool.begin().await.expect("begin tx2");
+    let run = sps2_state::queries::get_last_index_refresh(&mut tx)
+        .await
+        .expect("get last refresh")
+        .expect("a recorded refresh");
+    assert!(!run.succeeded);
+    assert_eq!(run.upgrades_available, 0);
+    assert_eq!(run.error.as_deref(), Some("network unreachable"));
+}
+
+#[tokio::test]
+async fn tagged_states_are_resolvable_and_kept_by_cleanup() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let db_path = temp_dir.path().join("state.sqlite");
+
+    let pool = sps2_state::create_pool(&db_path)
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let tagged = uuid::Uuid::new_v4();
+    let untagged = uuid::Uuid::new_v4();
+    let active = uuid::Uuid::new_v4();
+    let mut tx = pool.begin().await.expect("begin tx");
+    for id in [&tagged, &untagged, &active] {
+        sps2_state::queries::create_state(&mut tx, id, None, "install")
+            .await
+            .expect("create state");
+    }
+    sps2_state::queries::set_active_state(&mut tx, &active)
+        .await
+        .expect("set active");
+    sps2_state::queries::insert_state_tag(&mut tx, "before-upgrade", &tagged)
+        .await
+        .expect("tag state");
+    assert!(
+        sps2_state::queries::insert_state_tag(&mut tx, "before-upgrade", &untagged)
+            .await
+            .is_err(),
+        "tag names are unique"
+    );
+    tx.commit().await.expect("commit");
+
+    let mut tx = pool.begin().await.expect("begin tx2");
+    let tag = sps2_state::queries::get_state_tag(&mut tx, "before-upgrade")
+        .await
+        .expect("get tag")
+        .expect("tag exists");
+    assert_eq!(tag.state_id(), tagged);
+
+    let cleanup = sps2_state::queries::get_states_for_cleanup_strict(&mut tx, 0)
+        .await
+        .expect("cleanup candidates");
+    assert_eq!(cleanup, vec![untagged.to_string()]);
+
+    assert!(
+        sps2_state::queries::delete_state_tag(&mut tx, "before-upgrade")
+            .await
+            .expect("delete tag")
+    );
+    assert!(sps2_state::queries::list_state_tags(&mut tx)
+        .await
+        .expect("list tags")
+        .is_empty());
+}
+
+#[tokio::test]
+async fn file_storage_stats_count_shared_files_once() {
+    use sps2_state::file_models::{FileMetadata, FileReference};
+    use sps2_state::file_queries_runtime as files;
+    use sps2_state::queries;
+
+    let temp_dir = TempDir::new().expect("tempdir");
+    let pool = sps2_state::create_pool(&temp_dir.path().join("state.sqlite"))
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let state_id = uuid::Uuid::new_v4();
+    let mut tx = pool.begin().await.expect("begin tx");
+    queries::create_state(&mut tx, &state_id, None, "install")
+        .await
+        .expect("create state");
+
+    let shared = Hash::from_data(b"shared");
+    let own = Hash::from_data(b"own");
+    let orphan = Hash::from_data(b"orphan");
+    for (hash, size) in [(&shared, 100), (&own, 30), (&orphan, 7)] {
+        files::add_file_object(&mut tx, hash, &FileMetadata::regular_file(size, 0o644))
+            .await
+            .expect("add file object");
+    }
+
+    for (name, files_in_pkg) in [("big", vec![&shared, &own]), ("small", vec![&shared])] {
+        let pkg_row =
+            queries::add_package(&mut tx, &state_id, name, "1.0.0", name, 0, false, false)
+                .await
+                .expect("add package");
+        for (i, hash) in files_in_pkg.into_iter().enumerate() {
+            let file_ref = FileReference {
+                package_id: pkg_row,
+                relative_path: format!("lib/{i}"),
+                hash: hash.clone(),
+                metadata: FileMetadata::regular_file(0, 0o644),
+            };
+            files::add_package_file_entry(&mut tx, pkg_row, &file_ref)
+                .await
+                .expect("add file entry");
+        }
+    }
+
+    let stats = files::get_file_storage_stats(&mut tx).await.expect("stats");
+    assert_eq!(stats.total_files, 3);
+    assert_eq!(stats.total_size, 230);
+    assert_eq!(stats.unique_files, 2);
+    assert_eq!(stats.deduplicated_size, 130);
+    assert!((stats.deduplication_ratio - 230.0 / 130.0).abs() < 1e-9);
+    assert_eq!((stats.orphaned_files, stats.orphaned_size), (1, 7));
+
+    let usage = files::get_package_storage_usage(&mut tx, 10)
+        .await
+        .expect("usage");
+    let summary: Vec<_> = usage
+        .iter()
+        .map(|u| (u.name.as_str(), u.files, u.total_size, u.unique_size))
+        .collect();
+    assert_eq!(summary, [("big", 2, 130, 30), ("small", 1, 100, 0)]);
+}
+
+#[tokio::test]
+async fn verification_history_reports_recurring_paths() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let db_path = temp_dir.path().join("state.sqlite");
+
+    let pool = sps2_state::create_pool(&db_path)
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let state_id = uuid::Uuid::new_v4();
+    let drifting = sps2_state::VerificationPath {
+        kind: "corrupted_file".to_string(),
+        path: "etc/tool.conf".to_string(),
+        package: Some("tool-1.0.0".to_string()),
+    };
+    let once = sps2_state::VerificationPath {
+        kind: "unexpected_file".to_string(),
+        path: "bin/stray".to_string(),
+        package: None,
+    };
+    let mut tx = pool.begin().await.expect("begin tx");
+    for paths in [vec![drifting.clone(), once], vec![], vec![drifting]] {
+        let counts = sps2_state::VerificationCounts {
+            corrupted_files: i64::try_from(paths.len()).unwrap(),
+            healed: 1,
+            ..Default::default()
+        };
+        sps2_state::queries::insert_verification_run(
+            &mut tx, &state_id, "full", "live", 12, &counts, &paths,
+        )
+        .await
+        .expect("record run");
+    }
+    tx.commit().await.expect("commit");
+
+    let mut tx = pool.begin().await.expect("begin tx2");
+    let runs = sps2_state::queries::list_verification_runs(&mut tx, 2)
+        .await
+        .expect("list runs");
+    assert_eq!(runs.len(), 2);
+    assert!(runs[0].id > runs[1].id, "newest first");
+    assert_eq!(runs[0].counts.corrupted_files, 1);
+    assert_eq!(runs[0].counts.healed, 1);
+    assert_eq!(runs[0].state_id(), state_id);
+
+    // Only the last two runs are considered, and the drifting path is in one of them
+    let recurring = sps2_state::queries::get_recurring_discrepancies(&mut tx, 2)
+        .await
+        .expect("recurring in two runs");
+    assert!(recurring.is_empty());
+
+    let recurring = sps2_state::queries::get_recurring_discrepancies(&mut tx, 3)
+        .await
+        .expect("recurring in three runs");
+    assert_eq!(recurring.len(), 1);
+    assert_eq!(recurring[0].path, "etc/tool.conf");
+    assert_eq!(recurring[0].package.as_deref(), Some("tool-1.0.0"));
+    assert_eq!(recurring[0].runs, 2);
+}
+
+#[tokio::test]
+async fn last_verification_ignores_store_only_runs() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let db_path = temp_dir.path().join("state.sqlite");
+
+    let pool = sps2_state::create_pool(&db_path)
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let state_id = uuid::Uuid::new_v4();
+    let counts = sps2_state::VerificationCounts::default();
+    let mut tx = pool.begin().await.expect("begin tx");
+    assert_eq!(
+        sps2_state::queries::get_last_verification_at(&mut tx)
+            .await
+            .expect("no runs"),
+        None
+    );
+
+    sps2_state::queries::insert_verification_run(
+        &mut tx,
+        &state_id,
+        "standard",
+        "store",
+        5,
+        &counts,
+        &[],
+    )
+    .await
+    .expect("record store run");
+    assert_eq!(
+        sps2_state::queries::get_last_verification_at(&mut tx)
+            .await
+            .expect("store run only"),
+        None
+    );
+
+    sps2_state::queries::insert_verification_run(
+        &mut tx,
+        &state_id,
+        "quick",
+        "live",
+        5,
+        &counts,
+        &[],
+    )
+    .await
+    .expect("record live run");
+    let last = sps2_state::queries::get_last_verification_at(&mut tx)
+        .await
+        .expect("live run")
+        .expect("live run counts");
+    assert!(last <= chrono::Utc::now().timestamp());
+    tx.commit().await.expect("commit");
+}
+
+#[tokio::test]
+async fn state_audit_round_trips_requests_and_changes() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let db_path = temp_dir.path().join("state.sqlite");
+
+    let pool = sps2_state::create_pool(&db_path)
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let state_id = uuid::Uuid::new_v4();
+    let mut tx = pool.begin().await.expect("begin tx");
+    sps2_state::queries::create_state(&mut tx, &state_id, None, "install")
+        .await
+        .expect("create state");
+    let audit = sps2_state::StateAudit {
+        state_id: state_id.to_string(),
+        operation: "install".to_string(),
+        description: "install jq: installed jq 1.7.1".to_string(),
+        actor: Some("alice".to_string()),
+        command_line: Some("sps2 install jq".to_string()),
+        requested: vec!["jq".to_string()],
+        changes: vec![sps2_types::OpChange {
+            change_type: sps2_types::ChangeType::Install,
+            package: "jq".to_string(),
+            old_version: None,
+            new_version: Some(sps2_types::Version::new(1, 7, 1)),
+        }],
+    };
+    sps2_state::queries::insert_state_audit(&mut tx, &audit)
+        .await
+        .expect("record audit");
+    tx.commit().await.expect("commit");
+
+    let mut tx = pool.begin().await.expect("begin tx2");
+    let entries = sps2_state::queries::get_state_audit(&mut tx, &state_id)
+        .await
+        .expect("read audit");
+    assert_eq!(entries.len(), 1);
+    let entry = &entries[0].audit;
+    assert_eq!(entry.description, audit.description);
+    assert_eq!(entry.actor.as_deref(), Some("alice"));
+    assert_eq!(entry.requested, ["jq"]);
+    assert_eq!(entry.changes.len(), 1);
+    assert_eq!(
+        entry.changes[0].new_version,
+        Some(sps2_types::Version::new(1, 7, 1))
+    );
+
+    let other = uuid::Uuid::new_v4();
+    let none = sps2_state::queries::get_state_audit(&mut tx, &other)
+        .await
+        .expect("read audit of another state");
+    assert!(none.is_empty());
+}
+
+#[tokio::test]
+async fn validation_stamps_only_count_under_the_same_trusted_keys() {
+    let temp_dir = TempDir::new().expect("tempdir");
+    let state = sps2_state::StateManager::new(temp_dir.path())
+        .await
+        .expect("state manager");
+
+    let stamp = sps2_state::ValidationStamp {
+        store_hash: "store-hash".to_string(),
+        package_hash: Some("archive-hash".to_string()),
+        key_id: Some("a1b2c3d4e5f60718".to_string()),
+        trust_fingerprint: "keys-v1".to_string(),
+        validated_at: 1_700_000_000,
+    };
+    state
+        .record_validation_stamp(&stamp)
+        .await
+        .expect("record stamp");
+
+    let found = state
+        .validation_stamp("store-hash", "keys-v1")
+        .await
+        .expect("read stamp");
+    assert_eq!(found, Some(stamp.clone()));
+    assert!(state
+        .validation_stamp("store-hash", "keys-v2")
+        .await
+        .expect("read stamp under other keys")
+        .is_none());
+
+    // Stamping under new keys drops everything vouched for by the old ones
+    let other = sps2_state::ValidationStamp {
+        store_hash: "other-hash".to_string(),
+        trust_fingerprint: "keys-v2".to_string(),
+        ..stamp
+    };
+    state
+        .record_validation_stamp(&other)
+        .await
+        .expect("record stamp under new keys");
+    assert!(state
+        .validation_stamp("store-hash", "keys-v1")
+        .await
+        .expect("read stale stamp")
+        .is_none());
+}
+
+#[tokio::test]
+async fn merging_a_duplicate_file_object_moves_its_references() {
+    use sps2_hash::HashAlgorithm;
+    use sps2_state::file_queries_runtime as files;
+    use sps2_state::queries;
+
+    let temp_dir = TempDir::new().expect("tempdir");
+    let pool = sps2_state::create_pool(&temp_dir.path().join("state.sqlite"))
+        .await
+        .expect("create pool");
+    sps2_state::run_migrations(&pool)
+        .await
+        .expect("run migrations");
+
+    let state_id = uuid::Uuid::new_v4();
+    let mut tx = pool.begin().await.expect("begin tx");
+    queries::create_state(&mut tx, &state_id, None, "install")
+        .await
+        .expect("create state");
+    let pkg_row = queries::add_package(
+        &mut tx,
+        &state_id,
+        "pkg",
+        "1.0.0",
+        "store-hash",
+        10,
+        false,
+        false,
+    )
+    .await
+    .expect("add package");
+
+    let duplicate = Hash::from_data_with_algorithm(b"content", HashAlgorithm::Blake3);
+    let keep = Hash::from_data_with_algorithm(b"content", HashAlgorithm::XxHash128);
+    let metadata = sps2_state::file_models::FileMetadata {
+        size: 7,
+        permissions: 0o755,
+        uid: 0,
+        gid: 0,
+        mtime: None,
+        is_executable: true,
+        is_symlink: false,
+        symlink_target: None,
+        flags: 0,
+    };
+    files::add_file_object(&mut tx, &duplicate, &metadata)
+        .await
+        .expect("add file object");
+    let file_ref = sps2_state::file_models::FileReference {
+        package_id: pkg_row,
+        relative_path: "bin/tool".to_string(),
+        hash: duplicate.clone(),
+        metadata,
+    };
+    files::add_package_file_entry(&mut tx, pkg_row, &file_ref)
+        .await
+        .expect("add file entry");
+    files::increment_file_object_ref(&mut tx, &duplicate.to_hex())
+        .await
+        .expect("count reference");
+
+    for expected in [1, 0] {
+        let repointed = files::merge_file_object(&mut tx, &duplicate.to_hex(), &keep.to_hex())
+            .await
+            .expect("merge file object");
+        assert_eq!(repointed, expected);
+    }
+
+    assert!(files::get_file_object(&mut tx, &duplicate)
+        .await
+        .expect("get duplicate")
+        .is_none());
+    let kept = files::get_file_object(&mut tx, &keep)
+        .await
+        .expect("get kept object")
+        .expect("kept object exists");
+    assert_eq!(kept.ref_count, 1);
+    assert_eq!(kept.size, 7);
+    assert!(kept.is_executable);
+    let entries = files::get_package_file_entries(&mut tx, pkg_row)
+        .await
+        .expect("get file entries");
+    assert_eq!(entries[0].file_hash, keep.to_hex());
+}
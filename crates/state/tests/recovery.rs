@@ -27,6 +27,9 @@ async fn test_recovers_from_prepared_state() {
         package_id: pid.clone(),
         hash: pkg_hash.clone(),
         size: 1,
+        recommended: false,
+        dependency: false,
+        dependencies: None,
     };
     let td = TransactionData {
         package_refs: &[pref],
@@ -6,11 +6,21 @@ use async_compression::tokio::bufread::ZstdDecoder as AsyncZstdReader;
 use sps2_errors::{Error, PackageError, StorageError};
 use sps2_events::{AppEvent, EventEmitter, EventSender, GeneralEvent};
 use sps2_platform::core::PlatformContext;
+use sps2_platform::filesystem_helpers::{
+    file_flags, file_flags_to_string, parse_file_flags, SPECIAL_MODE_BITS,
+};
 use sps2_platform::PlatformManager;
+use std::collections::HashMap;
 use std::path::Path;
 use tar::Archive;
 use tokio::io::{AsyncWriteExt, BufReader};
 
+/// Pax header bsdtar records BSD file flags in
+const FFLAGS_PAX_KEY: &str = "SCHILY.fflags";
+
+/// BSD flags of extracted entries, by path within the package
+pub(crate) type FileFlags = HashMap<String, u32>;
+
 /// Create a platform context for filesystem operations
 fn create_platform_context() -> (&'static sps2_platform::Platform, PlatformContext) {
     let platform = PlatformManager::instance().platform();
@@ -43,14 +53,42 @@ pub async fn extract_package_with_events(
     dest: &Path,
     event_sender: Option<&EventSender>,
 ) -> Result<(), Error> {
+    extract_package_inner(sp_file, dest, event_sender)
+        .await
+        .map(drop)
+}
+
+/// Extract a .sp package file to a directory, returning the BSD flags its
+/// entries record by path
+///
+/// The flags are not set on the extracted files.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - Tar extraction fails
+/// - The extracted package is missing manifest.toml
+/// - I/O operations fail
+pub(crate) async fn extract_package_with_flags(
+    sp_file: &Path,
+    dest: &Path,
+) -> Result<FileFlags, Error> {
+    extract_package_inner(sp_file, dest, None).await
+}
+
+async fn extract_package_inner(
+    sp_file: &Path,
+    dest: &Path,
+    event_sender: Option<&EventSender>,
+) -> Result<FileFlags, Error> {
     // Try zstd extraction first, fall back to plain tar if it fails
-    match extract_zstd_tar_file(sp_file, dest, event_sender).await {
-        Ok(()) => {}
+    let flags = match extract_zstd_tar_file(sp_file, dest, event_sender).await {
+        Ok(flags) => flags,
         Err(_) => {
             // Fall back to plain tar
-            extract_plain_tar_file(sp_file, dest, event_sender).await?;
+            extract_plain_tar_file(sp_file, dest, event_sender).await?
         }
-    }
+    };
 
     // Verify manifest exists
     let manifest_path = dest.join("manifest.toml");
@@ -62,7 +100,7 @@ pub async fn extract_package_with_events(
         .into());
     }
 
-    Ok(())
+    Ok(flags)
 }
 
 /// List the contents of a .sp package without extracting
@@ -162,15 +200,20 @@ async fn extract_zstd_tar_file(
     file_path: &Path,
     dest: &Path,
     event_sender: Option<&EventSender>,
-) -> Result<(), Error> {
+) -> Result<FileFlags, Error> {
     // Create destination directory
     let (platform, ctx) = create_platform_context();
     platform.filesystem().create_dir_all(&ctx, dest).await?;
 
-    // Create a temporary file to decompress to, then extract with tar
-    let temp_file = tempfile::NamedTempFile::new().map_err(|e| StorageError::IoError {
-        message: format!("failed to create temp file: {e}"),
-    })?;
+    // Create a temporary file to decompress to, then extract with tar; it
+    // sits beside the destination to stay on its volume, which is the temp
+    // area when the store unpacks a package
+    let temp_file =
+        tempfile::NamedTempFile::new_in(dest.parent().unwrap_or(dest)).map_err(|e| {
+            StorageError::IoError {
+                message: format!("failed to create temp file: {e}"),
+            }
+        })?;
 
     let temp_path = temp_file.path().to_path_buf();
 
@@ -211,7 +254,7 @@ async fn extract_zstd_tar_file(
     let dest = dest.to_path_buf();
 
     // Keep the temp_file alive until after the blocking operation completes
-    tokio::task::spawn_blocking(move || {
+    let flags = tokio::task::spawn_blocking(move || {
         use std::fs::File;
 
         let file = File::open(&temp_path_for_task).map_err(|e| StorageError::IoError {
@@ -226,9 +269,7 @@ async fn extract_zstd_tar_file(
         archive.set_unpack_xattrs(false); // Don't unpack extended attributes
 
         // Extract all entries with security checks
-        extract_archive_entries(&mut archive, &dest)?;
-
-        Ok::<(), Error>(())
+        extract_archive_entries(&mut archive, &dest)
     })
     .await
     .map_err(|e| Error::internal(format!("zstd extract task failed: {e}")))??;
@@ -252,7 +293,7 @@ async fn extract_zstd_tar_file(
         }));
     }
 
-    Ok(())
+    Ok(flags)
 }
 
 /// Extract a plain (uncompressed) tar archive
@@ -260,7 +301,7 @@ async fn extract_plain_tar_file(
     file_path: &Path,
     dest: &Path,
     event_sender: Option<&EventSender>,
-) -> Result<(), Error> {
+) -> Result<FileFlags, Error> {
     // Create destination directory
     let (platform, ctx) = create_platform_context();
     platform.filesystem().create_dir_all(&ctx, dest).await?;
@@ -268,7 +309,7 @@ async fn extract_plain_tar_file(
     let file_path = file_path.to_path_buf();
     let dest = dest.to_path_buf();
 
-    tokio::task::spawn_blocking(move || {
+    let flags = tokio::task::spawn_blocking(move || {
         use std::fs::File;
 
         let file = File::open(&file_path)?;
@@ -280,9 +321,7 @@ async fn extract_plain_tar_file(
         archive.set_unpack_xattrs(false); // Don't unpack extended attributes
 
         // Extract all entries with security checks
-        extract_archive_entries(&mut archive, &dest)?;
-
-        Ok::<(), Error>(())
+        extract_archive_entries(&mut archive, &dest)
     })
     .await
     .map_err(|e| Error::internal(format!("plain tar extract task failed: {e}")))??;
@@ -295,7 +334,7 @@ async fn extract_plain_tar_file(
         }));
     }
 
-    Ok(())
+    Ok(flags)
 }
 
 /// List contents of a zstd-compressed tar archive
@@ -353,7 +392,10 @@ async fn list_zstd_tar_contents(file_path: &Path) -> Result<Vec<String>, Error>
         for entry in archive.entries()? {
             let entry = entry?;
             let path = entry.path()?;
-            files.push(path.to_string_lossy().to_string());
+            let path = path
+                .to_str()
+                .ok_or_else(|| PackageError::non_utf8_path(&path))?;
+            files.push(path.to_string());
         }
 
         files.sort();
@@ -382,7 +424,10 @@ async fn list_plain_tar_contents(file_path: &Path) -> Result<Vec<String>, Error>
         for entry in archive.entries()? {
             let entry = entry?;
             let path = entry.path()?;
-            files.push(path.to_string_lossy().to_string());
+            let path = path
+                .to_str()
+                .ok_or_else(|| PackageError::non_utf8_path(&path))?;
+            files.push(path.to_string());
         }
 
         files.sort();
@@ -393,14 +438,31 @@ async fn list_plain_tar_contents(file_path: &Path) -> Result<Vec<String>, Error>
 }
 
 /// Extract entries from a tar archive with security checks
+///
+/// BSD flags recorded for the entries are returned by path rather than set,
+/// as flags like `uchg` would keep the extracted files from being stored or
+/// cleaned up.
 fn extract_archive_entries<R: std::io::Read>(
     archive: &mut Archive<R>,
     dest: &Path,
-) -> Result<(), Error> {
+) -> Result<FileFlags, Error> {
+    let mut flags = FileFlags::new();
+
     // Extract all entries
     for entry in archive.entries()? {
         let mut entry = entry?;
 
+        let file_flags = entry.pax_extensions()?.and_then(|mut extensions| {
+            extensions
+                .find_map(|ext| ext.ok().filter(|ext| ext.key() == Ok(FFLAGS_PAX_KEY)))
+                .map(|ext| parse_file_flags(ext.value().unwrap_or_default()))
+        });
+        if let Some(file_flags) = file_flags.filter(|flags| *flags != 0) {
+            let path = entry.path()?;
+            let path = path.to_string_lossy();
+            flags.insert(path.trim_end_matches('/').to_string(), file_flags);
+        }
+
         // Get the path
         let path = entry.path()?;
 
@@ -419,7 +481,38 @@ fn extract_archive_entries<R: std::io::Read>(
         entry.unpack_in(dest)?;
     }
 
-    Ok(())
+    Ok(flags)
+}
+
+/// Precede the entry of a file with BSD flags by a `SCHILY.fflags` pax
+/// header naming them, as bsdtar does
+fn append_file_flags<W: std::io::Write>(
+    builder: &mut tar::Builder<W>,
+    metadata: &std::fs::Metadata,
+) -> Result<(), Error> {
+    let flags = file_flags_to_string(file_flags(metadata));
+    if flags.is_empty() {
+        return Ok(());
+    }
+    builder
+        .append_pax_extensions([(FFLAGS_PAX_KEY, flags.as_bytes())])
+        .map_err(|e| {
+            StorageError::IoError {
+                message: e.to_string(),
+            }
+            .into()
+        })
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &std::fs::Metadata) -> u32 {
+    0
 }
 
 /// Recursively add directory contents to tar
@@ -439,6 +532,9 @@ fn add_dir_to_tar<W: std::io::Write>(
 
         let path = entry.path();
         let name = entry.file_name();
+        if name.to_str().is_none() {
+            return Err(PackageError::non_utf8_path(&path).into());
+        }
         let tar_path = prefix.join(&name);
 
         let metadata = entry.metadata().map_err(|e| StorageError::IoError {
@@ -456,13 +552,18 @@ fn add_dir_to_tar<W: std::io::Write>(
             // Recursively add contents
             add_dir_to_tar(builder, &path, &tar_path)?;
         } else if metadata.is_file() {
-            // Add file
+            // Add file; the deterministic header drops setuid/setgid bits,
+            // so they are put back
             let mut file = std::fs::File::open(&path).map_err(|e| StorageError::IoError {
                 message: e.to_string(),
             })?;
+            let mut header = tar::Header::new_gnu();
+            header.set_metadata_in_mode(&metadata, tar::HeaderMode::Deterministic);
+            header.set_mode(header.mode()? | (file_mode(&metadata) & SPECIAL_MODE_BITS));
+            append_file_flags(builder, &metadata)?;
 
             builder
-                .append_file(&tar_path, &mut file)
+                .append_data(&mut header, &tar_path, &mut file)
                 .map_err(|e| StorageError::IoError {
                     message: e.to_string(),
                 })?;
@@ -471,6 +572,9 @@ fn add_dir_to_tar<W: std::io::Write>(
             let target = std::fs::read_link(&path).map_err(|e| StorageError::IoError {
                 message: e.to_string(),
             })?;
+            if target.to_str().is_none() {
+                return Err(PackageError::non_utf8_path(&path).into());
+            }
 
             let mut header = tar::Header::new_gnu();
             header.set_metadata(&metadata);
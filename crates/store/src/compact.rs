@@ -0,0 +1,294 @@
+//! Finding objects stored twice under hashes of different algorithms
+//!
+//! Objects are named by the hash of their content, and that hash has been
+//! BLAKE3 as well as xxHash128, so a file ingested before and after a change
+//! of algorithm is kept twice. [`PackageStore::find_duplicate_objects`]
+//! finds such copies, [`PackageStore::rewrite_file_hashes`] points package
+//! file lists at the copy that is kept, and
+//! [`PackageStore::remove_objects`] deletes the others.
+
+use crate::PackageStore;
+use sps2_errors::{Error, StorageError};
+use sps2_hash::{FileHashResult, Hash, HashAlgorithm};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio::fs;
+
+/// Objects holding the same content under different hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateObjects {
+    /// Object that is kept
+    pub keep: Hash,
+    /// Objects with the same content as `keep`
+    pub duplicates: Vec<Hash>,
+    /// Size of each object in bytes
+    pub size: u64,
+}
+
+/// Outcome of [`PackageStore::remove_objects`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RemovedObjects {
+    /// Objects removed from the store
+    pub objects: u64,
+    /// Bytes freed by objects that had no other link
+    pub freed_bytes: u64,
+    /// Objects that are still hard-linked into a prefix, whose space is
+    /// freed once those files are replaced
+    pub linked_objects: u64,
+}
+
+impl PackageStore {
+    /// Find objects whose content is also stored under another hash
+    ///
+    /// Only objects of equal size and permissions are compared, since a
+    /// file linked into a prefix takes its mode from the object. An object
+    /// whose content no longer matches its own name is left alone. The
+    /// object kept is the one named by the algorithm new objects use.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the objects directory cannot be read or an object
+    /// cannot be hashed
+    pub async fn find_duplicate_objects(&self) -> Result<Vec<DuplicateObjects>, Error> {
+        let mut candidates: HashMap<(u64, u32), Vec<Hash>> = HashMap::new();
+        for (path, size) in self.file_store.objects().await? {
+            let Some(hash) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| Hash::from_hex(name).ok())
+            else {
+                continue;
+            };
+            candidates
+                .entry((size, mode(&path).await?))
+                .or_default()
+                .push(hash);
+        }
+
+        let mut groups = Vec::new();
+        for ((size, _), hashes) in candidates {
+            // Content named twice by one algorithm would have a single name
+            if hashes
+                .iter()
+                .all(|hash| hash.algorithm() == hashes[0].algorithm())
+            {
+                continue;
+            }
+
+            let mut by_content: HashMap<Hash, Vec<Hash>> = HashMap::new();
+            for hash in hashes {
+                if !self.file_store.verify_file(&hash).await? {
+                    continue;
+                }
+                let content = if hash.is_blake3() {
+                    hash.clone()
+                } else {
+                    Hash::blake3_hash_file(&self.file_store.file_path(&hash)).await?
+                };
+                by_content.entry(content).or_default().push(hash);
+            }
+
+            for mut same in by_content.into_values() {
+                if same.len() < 2 {
+                    continue;
+                }
+                same.sort_by_key(|hash| {
+                    (hash.algorithm() != HashAlgorithm::default(), hash.to_hex())
+                });
+                let keep = same.remove(0);
+                groups.push(DuplicateObjects {
+                    keep,
+                    duplicates: same,
+                    size,
+                });
+            }
+        }
+
+        groups.sort_by_key(|group| group.keep.to_hex());
+        Ok(groups)
+    }
+
+    /// Point the `files.json` of every package at the objects kept in `groups`
+    ///
+    /// Each file listed under a duplicate gets the hash of the object kept in
+    /// its place; directories and symlinks are left as they are. A file list is
+    /// rewritten through a temporary file, so it is never left half written.
+    /// Returns the number of packages changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a package's file list cannot be read, parsed or
+    /// written
+    pub async fn rewrite_file_hashes(&self, groups: &[DuplicateObjects]) -> Result<usize, Error> {
+        let replacements: HashMap<&Hash, &Hash> = groups
+            .iter()
+            .flat_map(|group| group.duplicates.iter().map(|hash| (hash, &group.keep)))
+            .collect();
+        let mut rewritten = 0;
+        for package in self.list_packages().await? {
+            let path = self.package_path(&package).join("files.json");
+            if !fs::try_exists(&path).await? {
+                continue;
+            }
+            let content = fs::read_to_string(&path).await?;
+            let mut files: Vec<FileHashResult> =
+                serde_json::from_str(&content).map_err(|e| StorageError::CorruptedData {
+                    message: format!("{}: {e}", path.display()),
+                })?;
+
+            let mut changed = false;
+            for file in files
+                .iter_mut()
+                .filter(|file| !file.is_directory && !file.is_symlink)
+            {
+                if let Some(replacement) = replacements.get(&file.hash) {
+                    file.hash = (*replacement).clone();
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+
+            let json = serde_json::to_string_pretty(&files).map_err(|e| StorageError::IoError {
+                message: format!("failed to serialize file results: {e}"),
+            })?;
+            let temp = path.with_extension("json.tmp");
+            fs::write(&temp, json).await?;
+            fs::rename(&temp, &path).await?;
+            rewritten += 1;
+        }
+        Ok(rewritten)
+    }
+
+    /// Remove objects from the store
+    ///
+    /// Missing objects are skipped. Bytes count as freed only for objects
+    /// the store held the sole link to; clones made from an object share
+    /// its blocks without a link, so they are not told apart.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an object cannot be removed
+    pub async fn remove_objects(&self, hashes: &[Hash]) -> Result<RemovedObjects, Error> {
+        let mut removed = RemovedObjects::default();
+        for hash in hashes {
+            let path = self.file_store.file_path(hash);
+            let Ok(metadata) = fs::symlink_metadata(&path).await else {
+                continue;
+            };
+            if links(&metadata) > 1 {
+                removed.linked_objects += 1;
+            } else {
+                removed.freed_bytes += metadata.len();
+            }
+            self.file_store.remove_file(hash).await?;
+            removed.objects += 1;
+        }
+        Ok(removed)
+    }
+}
+
+/// Permission bits of an object
+async fn mode(path: &Path) -> Result<u32, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        Ok(fs::metadata(path).await?.permissions().mode())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(0)
+    }
+}
+
+/// Hard links to a file, including its own name
+fn links(metadata: &std::fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.nlink()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    async fn store_as(store: &PackageStore, content: &[u8], algorithm: HashAlgorithm) -> Hash {
+        let source = store.base_path().join("source");
+        fs::write(&source, content).await.unwrap();
+        let hash = Hash::hash_file_with_algorithm(&source, algorithm)
+            .await
+            .unwrap();
+        store.file_store.store_file(&source, &hash).await.unwrap();
+        fs::remove_file(&source).await.unwrap();
+        hash
+    }
+
+    fn file(path: &str, hash: &Hash) -> FileHashResult {
+        FileHashResult {
+            relative_path: path.to_string(),
+            hash: hash.clone(),
+            size: 0,
+            is_directory: false,
+            is_symlink: false,
+            #[cfg(unix)]
+            mode: Some(0o644),
+            flags: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn duplicates_across_algorithms_are_merged_into_the_default() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = PackageStore::new(temp_dir.path().to_path_buf());
+        let blake3 = store_as(&store, b"same content", HashAlgorithm::Blake3).await;
+        let xxhash = store_as(&store, b"same content", HashAlgorithm::XxHash128).await;
+        let unique = store_as(&store, b"other", HashAlgorithm::Blake3).await;
+
+        let groups = store.find_duplicate_objects().await.unwrap();
+        assert_eq!(
+            groups,
+            [DuplicateObjects {
+                keep: xxhash.clone(),
+                duplicates: vec![blake3.clone()],
+                size: 12,
+            }]
+        );
+
+        let package = Hash::from_data(b"package");
+        let dir = store.package_path(&package);
+        fs::create_dir_all(&dir).await.unwrap();
+        let files = vec![file("bin/tool", &blake3), file("share/other", &unique)];
+        fs::write(
+            dir.join("files.json"),
+            serde_json::to_string(&files).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(store.rewrite_file_hashes(&groups).await.unwrap(), 1);
+        let files: Vec<FileHashResult> =
+            serde_json::from_str(&fs::read_to_string(dir.join("files.json")).await.unwrap())
+                .unwrap();
+        assert_eq!(files[0].hash, xxhash);
+        assert_eq!(files[1].hash, unique);
+
+        let removed = store
+            .remove_objects(std::slice::from_ref(&blake3))
+            .await
+            .unwrap();
+        assert_eq!(removed.objects, 1);
+        assert_eq!(removed.freed_bytes, 12);
+        assert!(!store.file_store.has_file(&blake3).await);
+        assert!(store.file_store.has_file(&xxhash).await);
+        assert!(store.find_duplicate_objects().await.unwrap().is_empty());
+    }
+}
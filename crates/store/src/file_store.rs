@@ -7,10 +7,15 @@ use sps2_errors::{Error, StorageError};
 use sps2_hash::{calculate_file_storage_path, FileHashResult, FileHasher, FileHasherConfig, Hash};
 use sps2_platform::core::PlatformContext;
 use sps2_platform::PlatformManager;
+use sps2_types::LinkStrategy;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 use uuid::Uuid;
 
+/// Package metadata kept in the store and never linked into a prefix
+pub const METADATA_FILES: [&str; 3] = ["manifest.toml", "sbom.spdx.json", "sbom.cdx.json"];
+
 /// Result of file verification operation
 #[derive(Debug, Clone, PartialEq)]
 pub enum FileVerificationResult {
@@ -24,6 +29,19 @@ pub enum FileVerificationResult {
     Error { message: String },
 }
 
+/// Objects found on disk by [`FileStore::stats`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FileStoreStats {
+    /// Object files in the store
+    pub objects: u64,
+    /// Bytes taken by those objects
+    pub bytes: u64,
+    /// Objects whose hash is not in the tracked set
+    pub untracked_objects: u64,
+    /// Bytes taken by untracked objects
+    pub untracked_bytes: u64,
+}
+
 /// File store for content-addressed file storage
 #[derive(Clone, Debug)]
 pub struct FileStore {
@@ -31,6 +49,8 @@ pub struct FileStore {
     objects_path: PathBuf,
     /// File hasher for computing file hashes
     file_hasher: FileHasher,
+    /// How files are materialized outside the store
+    link_strategy: LinkStrategy,
 }
 
 impl FileStore {
@@ -43,6 +63,40 @@ impl FileStore {
         Self {
             objects_path,
             file_hasher,
+            link_strategy: LinkStrategy::default(),
+        }
+    }
+
+    /// Use `strategy` when linking files out of the store
+    #[must_use]
+    pub fn with_link_strategy(mut self, strategy: LinkStrategy) -> Self {
+        self.link_strategy = strategy;
+        self
+    }
+
+    /// The configured link strategy
+    #[must_use]
+    pub fn link_strategy(&self) -> LinkStrategy {
+        self.link_strategy
+    }
+
+    /// The strategy used for files linked under `dest`
+    ///
+    /// `Auto` becomes `Clonefile` when `dest` is on the store's volume and
+    /// `Copy` when it is not. Neither hard links nor clones can cross a
+    /// filesystem boundary, so an explicit `Hardlink` or `Clonefile` also
+    /// degrades to `Copy` for a `dest` on another volume. If either volume
+    /// cannot be determined the configured strategy is used as-is.
+    pub async fn resolve_link_strategy(&self, dest: &Path) -> LinkStrategy {
+        let cross_volume = matches!(
+            (volume_id(&self.objects_path).await, volume_id(dest).await),
+            (Some(store), Some(dest)) if store != dest
+        );
+        match self.link_strategy {
+            LinkStrategy::Copy => LinkStrategy::Copy,
+            _ if cross_volume => LinkStrategy::Copy,
+            LinkStrategy::Auto => LinkStrategy::Clonefile,
+            strategy => strategy,
         }
     }
 
@@ -184,11 +238,22 @@ impl FileStore {
         Ok((hash, newly_stored))
     }
 
-    /// Link a stored file to a destination
+    /// Link a stored file to a destination with the configured
+    /// [`LinkStrategy`]
     ///
     /// # Errors
     /// Returns an error if the file doesn't exist or linking fails
     pub async fn link_file(&self, hash: &Hash, dest_path: &Path) -> Result<(), Error> {
+        let strategy = self.resolve_link_strategy(dest_path).await;
+        self.link_file_with(hash, dest_path, strategy).await
+    }
+
+    async fn link_file_with(
+        &self,
+        hash: &Hash,
+        dest_path: &Path,
+        strategy: LinkStrategy,
+    ) -> Result<(), Error> {
         let source_path = self.file_path(hash);
         let (platform, ctx) = Self::create_platform_context();
 
@@ -217,12 +282,42 @@ impl FileStore {
             platform.filesystem().remove_file(&ctx, dest_path).await?;
         }
 
-        // Use APFS clonefile on macOS for copy-on-write semantics
-        // This prevents corruption of the store when files are modified in place
-        platform
-            .filesystem()
-            .clone_file(&ctx, &source_path, dest_path)
-            .await?;
+        // Clones and copies keep in-place edits from reaching the store; hard
+        // links rely on store objects being read-only
+        match strategy {
+            LinkStrategy::Auto | LinkStrategy::Clonefile => {
+                platform
+                    .filesystem()
+                    .clone_file(&ctx, &source_path, dest_path)
+                    .await?;
+            }
+            LinkStrategy::Hardlink => {
+                // A hard link can still be refused (link count limits, EXDEV
+                // under a bind mount); a clone gives the same sharing
+                if platform
+                    .filesystem()
+                    .hard_link(&ctx, &source_path, dest_path)
+                    .await
+                    .is_err()
+                {
+                    platform
+                        .filesystem()
+                        .clone_file(&ctx, &source_path, dest_path)
+                        .await?;
+                }
+            }
+            LinkStrategy::Copy => {
+                fs::copy(&source_path, dest_path)
+                    .await
+                    .map_err(|e| StorageError::IoError {
+                        message: format!(
+                            "failed to copy {} to {}: {e}",
+                            source_path.display(),
+                            dest_path.display()
+                        ),
+                    })?;
+            }
+        }
 
         Ok(())
     }
@@ -234,42 +329,56 @@ impl FileStore {
     /// # Errors
     /// Returns an error if directory traversal or file operations fail
     pub async fn store_directory(&self, dir_path: &Path) -> Result<Vec<FileHashResult>, Error> {
-        // Hash all files in the directory
-        let hash_results = self.file_hasher.hash_directory(dir_path).await?;
-
-        // Filter out manifest.toml and sbom files before storing
-        // Also fix paths by stripping opt/pm/live/ prefix
-        let mut filtered_results = Vec::new();
+        let results = self.hash_package_directory(dir_path).await?;
+        self.store_hashed_files(dir_path, &results).await?;
+        Ok(results)
+    }
 
-        for result in hash_results {
-            // Skip manifest and sbom files - they should only exist in package metadata
-            if result.relative_path == "manifest.toml"
-                || result.relative_path == "sbom.spdx.json"
-                || result.relative_path == "sbom.cdx.json"
-            {
-                continue;
-            }
+    /// Hash the files of an extracted package without storing them
+    ///
+    /// Package metadata (manifest and SBOMs) and the `opt/pm/live` directory
+    /// entries themselves are left out, as they are never linked.
+    ///
+    /// # Errors
+    /// Returns an error if directory traversal or hashing fails
+    pub async fn hash_package_directory(
+        &self,
+        dir_path: &Path,
+    ) -> Result<Vec<FileHashResult>, Error> {
+        let hash_results = self.file_hasher.hash_directory(dir_path).await?;
 
-            // Skip opt/pm/live directory entries themselves
-            if result.relative_path == "opt"
-                || result.relative_path == "opt/pm"
-                || result.relative_path == "opt/pm/live"
-            {
-                continue;
-            }
+        Ok(hash_results
+            .into_iter()
+            .filter(|result| {
+                !matches!(
+                    result.relative_path.as_str(),
+                    "manifest.toml"
+                        | "sbom.spdx.json"
+                        | "sbom.cdx.json"
+                        | "opt"
+                        | "opt/pm"
+                        | "opt/pm/live"
+                )
+            })
+            .collect())
+    }
 
-            // Store the file if it's not a directory or symlink
+    /// Store the regular files listed in `results` from `dir_path`
+    ///
+    /// # Errors
+    /// Returns an error if file operations fail
+    pub async fn store_hashed_files(
+        &self,
+        dir_path: &Path,
+        results: &[FileHashResult],
+    ) -> Result<(), Error> {
+        for result in results {
             if !result.is_directory && !result.is_symlink {
-                // Use original path for file storage
-                let original_path = result.relative_path.clone();
-                let file_path = dir_path.join(&original_path);
-                self.store_file(&file_path, &result.hash).await?;
+                self.store_file(&dir_path.join(&result.relative_path), &result.hash)
+                    .await?;
             }
-
-            filtered_results.push(result);
         }
-
-        Ok(filtered_results)
+        Ok(())
     }
 
     /// Link files from hash results to a destination directory
@@ -283,13 +392,11 @@ impl FileStore {
         dest_base: &Path,
     ) -> Result<(), Error> {
         let (platform, ctx) = Self::create_platform_context();
+        let strategy = self.resolve_link_strategy(dest_base).await;
 
         for result in hash_results {
             // Skip manifest.toml and sbom files - they should only exist in store
-            if result.relative_path == "manifest.toml"
-                || result.relative_path == "sbom.spdx.json"
-                || result.relative_path == "sbom.cdx.json"
-            {
+            if METADATA_FILES.contains(&result.relative_path.as_str()) {
                 continue;
             }
 
@@ -339,7 +446,8 @@ impl FileStore {
                 }
             } else {
                 // Link regular file
-                self.link_file(&result.hash, &dest_path).await?;
+                self.link_file_with(&result.hash, &dest_path, strategy)
+                    .await?;
             }
         }
 
@@ -426,6 +534,84 @@ impl FileStore {
         }
     }
 
+    /// Compare a file outside the store with the object stored under `hash`
+    ///
+    /// `Missing` means `path` does not exist; a mismatch reports what the
+    /// file hashes to with the object's algorithm.
+    ///
+    /// # Errors
+    /// Returns an error if `path` exists but cannot be hashed
+    pub async fn compare_file(
+        &self,
+        hash: &Hash,
+        path: &Path,
+    ) -> Result<FileVerificationResult, Error> {
+        if fs::symlink_metadata(path).await.is_err() {
+            return Ok(FileVerificationResult::Missing);
+        }
+        let actual = Hash::hash_file_with_algorithm(path, hash.algorithm()).await?;
+        if actual == *hash {
+            Ok(FileVerificationResult::Valid)
+        } else {
+            Ok(FileVerificationResult::HashMismatch {
+                expected: hash.clone(),
+                actual,
+            })
+        }
+    }
+
+    /// Count the objects on disk and those missing from `tracked`
+    ///
+    /// `tracked` holds the hex hashes the state database knows about; any
+    /// other object was left behind by an interrupted operation.
+    ///
+    /// # Errors
+    /// Returns an error if the objects directory cannot be read
+    pub async fn stats(&self, tracked: &HashSet<String>) -> Result<FileStoreStats, Error> {
+        let mut stats = FileStoreStats::default();
+        for (path, size) in self.objects().await? {
+            stats.objects += 1;
+            stats.bytes += size;
+            if !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| tracked.contains(name))
+            {
+                stats.untracked_objects += 1;
+                stats.untracked_bytes += size;
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Paths and sizes of the object files on disk
+    ///
+    /// # Errors
+    /// Returns an error if the objects directory cannot be read
+    pub(crate) async fn objects(&self) -> Result<Vec<(PathBuf, u64)>, Error> {
+        let mut objects = Vec::new();
+        if !fs::try_exists(&self.objects_path).await? {
+            return Ok(objects);
+        }
+
+        // Objects live two prefix levels down: objects/ab/cd/<hash>
+        let mut dirs = vec![(self.objects_path.clone(), 0)];
+        while let Some((dir, depth)) = dirs.pop() {
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && depth < 2 {
+                    dirs.push((entry.path(), depth + 1));
+                } else if depth == 2 && !file_type.is_dir() {
+                    objects.push((entry.path(), entry.metadata().await?.len()));
+                }
+            }
+        }
+
+        Ok(objects)
+    }
+
     /// Clean up empty prefix directories
     ///
     /// # Errors
@@ -450,6 +636,27 @@ impl FileStore {
     }
 }
 
+/// Device of the volume holding `path`, or of its closest existing ancestor
+#[must_use]
+pub async fn volume_id(path: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        for ancestor in path.ancestors() {
+            if let Ok(metadata) = fs::metadata(ancestor).await {
+                return Some(metadata.dev());
+            }
+        }
+        None
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -524,4 +731,68 @@ mod tests {
         assert!(dest_dir.join("file1.txt").exists());
         assert!(dest_dir.join("subdir/file2.txt").exists());
     }
+
+    #[tokio::test]
+    async fn test_stats_counts_untracked_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FileStore::new(temp_dir.path());
+
+        let mut tracked = HashSet::new();
+        for (name, content) in [("a.txt", b"tracked".as_slice()), ("b.txt", b"stray")] {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).await.unwrap();
+            let (hash, _) = store.store_file_with_hash(&path).await.unwrap();
+            if name == "a.txt" {
+                tracked.insert(hash.to_hex());
+            }
+        }
+
+        let stats = store.stats(&tracked).await.unwrap();
+        assert_eq!(stats.objects, 2);
+        assert_eq!(stats.bytes, 12);
+        assert_eq!(stats.untracked_objects, 1);
+        assert_eq!(stats.untracked_bytes, 5);
+    }
+
+    #[tokio::test]
+    async fn test_auto_link_strategy_clones_on_the_same_volume() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = FileStore::new(temp_dir.path());
+        assert_eq!(
+            store
+                .resolve_link_strategy(&temp_dir.path().join("live/bin"))
+                .await,
+            LinkStrategy::Clonefile
+        );
+
+        let store = store.with_link_strategy(LinkStrategy::Copy);
+        assert_eq!(
+            store.resolve_link_strategy(temp_dir.path()).await,
+            LinkStrategy::Copy
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_copy_and_hardlink_strategies() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        fs::write(&source, b"linked").await.unwrap();
+
+        for (strategy, shares_inode) in
+            [(LinkStrategy::Copy, false), (LinkStrategy::Hardlink, true)]
+        {
+            let store = FileStore::new(temp_dir.path()).with_link_strategy(strategy);
+            let (hash, _) = store.store_file_with_hash(&source).await.unwrap();
+            let dest = temp_dir.path().join(format!("{strategy}/file.txt"));
+            store.link_file(&hash, &dest).await.unwrap();
+
+            assert_eq!(fs::read(&dest).await.unwrap(), b"linked");
+            let stored = fs::metadata(store.file_path(&hash)).await.unwrap();
+            let linked = fs::metadata(&dest).await.unwrap();
+            assert_eq!(stored.ino() == linked.ino(), shares_inode, "{strategy}");
+        }
+    }
 }
@@ -0,0 +1,287 @@
+//! Publishing package directories in one step, and finding incomplete ones
+//!
+//! A package directory is assembled under a `.ingest-` name next to the
+//! published ones and renamed to its hash once manifest.toml and files.json
+//! are written, so a crash never leaves a directory the store mistakes for
+//! a package. Directories written before that, or damaged since, lack one of
+//! the two files. [`PackageStore::quarantine_incomplete_packages`] moves
+//! them to `incomplete/`, after which the verifier reports their package as
+//! missing and healing adds it again.
+
+use crate::{PackageStore, StoredPackage};
+use sps2_errors::Error;
+use sps2_hash::{FileHashResult, Hash};
+use sps2_platform::filesystem_helpers::set_compression;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+
+const INGEST_PREFIX: &str = ".ingest-";
+const INCOMPLETE_DIR: &str = "incomplete";
+/// Age after which an assembly directory no longer belongs to a running
+/// ingestion
+const ABANDONED_AFTER: Duration = Duration::from_secs(3600);
+
+/// What [`PackageStore::quarantine_incomplete_packages`] cleaned up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IngestScan {
+    /// Assembly directories left behind by interrupted ingestions, removed
+    pub abandoned: usize,
+    /// Packages whose directory was moved to the quarantine
+    pub quarantined: Vec<Hash>,
+}
+
+impl IngestScan {
+    /// Whether the store had nothing to clean up
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.abandoned == 0 && self.quarantined.is_empty()
+    }
+}
+
+impl PackageStore {
+    /// Whether the store holds a complete directory for the package `hash`
+    pub async fn has_complete_package(&self, hash: &Hash) -> bool {
+        is_complete(&self.package_path(hash)).await
+    }
+
+    /// Write the directory of package `hash` from the extracted package in
+    /// `source` and its stored `files`, then publish it
+    ///
+    /// A complete directory published meanwhile by another ingestion wins,
+    /// and an incomplete one is quarantined to make room.
+    pub(crate) async fn publish_package(
+        &self,
+        hash: &Hash,
+        source: &Path,
+        files: &[FileHashResult],
+    ) -> Result<StoredPackage, Error> {
+        let packages = self.base_path.join("packages");
+        fs::create_dir_all(&packages).await?;
+        let assembled = packages.join(format!(
+            "{INGEST_PREFIX}{}-{}",
+            hash.to_hex(),
+            uuid::Uuid::new_v4().simple()
+        ));
+        fs::create_dir(&assembled).await?;
+        if let Err(e) = write_package_dir(&assembled, source, files).await {
+            let _ = fs::remove_dir_all(&assembled).await;
+            return Err(e);
+        }
+
+        let package_path = self.package_path(hash);
+        if fs::symlink_metadata(&package_path).await.is_ok() && !is_complete(&package_path).await {
+            self.quarantine(hash).await?;
+        }
+        if let Err(e) = fs::rename(&assembled, &package_path).await {
+            let _ = fs::remove_dir_all(&assembled).await;
+            if !is_complete(&package_path).await {
+                return Err(e.into());
+            }
+        }
+        StoredPackage::load(&package_path).await
+    }
+
+    /// Remove what interrupted ingestions left behind and quarantine package
+    /// directories that are incomplete
+    ///
+    /// Assembly directories younger than an hour are kept, as another
+    /// process may still be writing them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the packages directory cannot be read or a
+    /// directory cannot be removed or moved.
+    pub async fn quarantine_incomplete_packages(&self) -> Result<IngestScan, Error> {
+        let mut scan = IngestScan::default();
+        let mut entries = match fs::read_dir(self.base_path.join("packages")).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(scan),
+            Err(e) => return Err(e.into()),
+        };
+        let cutoff = SystemTime::now()
+            .checked_sub(ABANDONED_AFTER)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_dir() {
+                continue;
+            }
+            let name = entry.file_name();
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+            if name.starts_with(INGEST_PREFIX) {
+                if entry.metadata().await?.modified()? < cutoff {
+                    fs::remove_dir_all(entry.path()).await?;
+                    scan.abandoned += 1;
+                }
+            } else if let Ok(hash) = Hash::from_hex(name) {
+                if !is_complete(&entry.path()).await {
+                    self.quarantine(&hash).await?;
+                    scan.quarantined.push(hash);
+                }
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Packages whose incomplete directory sits in the quarantine
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the quarantine cannot be read.
+    pub async fn incomplete_packages(&self) -> Result<Vec<Hash>, Error> {
+        let mut hashes = Vec::new();
+        let mut entries = match fs::read_dir(self.base_path.join(INCOMPLETE_DIR)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(hashes),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(hash) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| Hash::from_hex(name).ok())
+            {
+                hashes.push(hash);
+            }
+        }
+        hashes.sort_by_key(Hash::to_hex);
+        Ok(hashes)
+    }
+
+    /// Delete the quarantined directory of package `hash`, if there is one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory cannot be removed.
+    pub async fn discard_incomplete_package(&self, hash: &Hash) -> Result<(), Error> {
+        match fs::remove_dir_all(self.incomplete_path(hash)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn incomplete_path(&self, hash: &Hash) -> PathBuf {
+        self.base_path.join(INCOMPLETE_DIR).join(hash.to_hex())
+    }
+
+    /// Move the directory of package `hash` to the quarantine, replacing an
+    /// earlier one
+    async fn quarantine(&self, hash: &Hash) -> Result<(), Error> {
+        let dest = self.incomplete_path(hash);
+        self.discard_incomplete_package(hash).await?;
+        fs::create_dir_all(self.base_path.join(INCOMPLETE_DIR)).await?;
+        fs::rename(self.package_path(hash), dest).await?;
+        Ok(())
+    }
+}
+
+async fn write_package_dir(
+    dir: &Path,
+    source: &Path,
+    files: &[FileHashResult],
+) -> Result<(), Error> {
+    fs::copy(source.join("manifest.toml"), dir.join("manifest.toml")).await?;
+    for sbom_name in ["sbom.spdx.json", "sbom.cdx.json"] {
+        let sbom_src = source.join(sbom_name);
+        if fs::try_exists(&sbom_src).await? {
+            fs::copy(&sbom_src, dir.join(sbom_name)).await?;
+        }
+    }
+
+    let files_json =
+        serde_json::to_string_pretty(files).map_err(|e| sps2_errors::StorageError::IoError {
+            message: format!("failed to serialize file results: {e}"),
+        })?;
+    fs::write(dir.join("files.json"), files_json).await?;
+
+    set_compression(dir)?;
+    Ok(())
+}
+
+/// Whether `dir` has a readable manifest and file list
+async fn is_complete(dir: &Path) -> bool {
+    if crate::manifest_io::read_manifest(&dir.join("manifest.toml"))
+        .await
+        .is_err()
+    {
+        return false;
+    }
+    fs::read_to_string(dir.join("files.json"))
+        .await
+        .is_ok_and(|content| serde_json::from_str::<Vec<FileHashResult>>(&content).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn incomplete_package_dirs_are_quarantined() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = PackageStore::new(temp_dir.path().join("store"));
+        let source = temp_dir.path().join("source");
+        fs::create_dir_all(&source).await.unwrap();
+        let manifest = sps2_types::Manifest::new(
+            "jq".to_string(),
+            &sps2_types::Version::new(1, 7, 0),
+            1,
+            &sps2_types::Arch::Arm64,
+        );
+        crate::manifest_io::write_manifest(&source.join("manifest.toml"), &manifest)
+            .await
+            .unwrap();
+
+        let complete = Hash::from_data(b"complete");
+        store
+            .publish_package(&complete, &source, &[])
+            .await
+            .unwrap();
+        assert!(store.has_complete_package(&complete).await);
+
+        // A crash between creating the directory and writing files.json
+        let partial = Hash::from_data(b"partial");
+        fs::create_dir_all(store.package_path(&partial))
+            .await
+            .unwrap();
+        fs::copy(
+            source.join("manifest.toml"),
+            store.package_path(&partial).join("manifest.toml"),
+        )
+        .await
+        .unwrap();
+        let abandoned = store.base_path().join("packages/.ingest-old");
+        fs::create_dir_all(&abandoned).await.unwrap();
+        let fresh = store.base_path().join("packages/.ingest-running");
+        fs::create_dir_all(&fresh).await.unwrap();
+        let old = SystemTime::now() - 2 * ABANDONED_AFTER;
+        std::fs::File::open(&abandoned)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let scan = store.quarantine_incomplete_packages().await.unwrap();
+        assert_eq!(scan.abandoned, 1);
+        assert_eq!(scan.quarantined, vec![partial.clone()]);
+        assert!(!abandoned.exists());
+        assert!(fresh.exists());
+        assert!(!store.package_path(&partial).exists());
+        assert!(store.has_complete_package(&complete).await);
+        assert_eq!(
+            store.incomplete_packages().await.unwrap(),
+            vec![partial.clone()]
+        );
+
+        store.publish_package(&partial, &source, &[]).await.unwrap();
+        store.discard_incomplete_package(&partial).await.unwrap();
+        assert!(store.incomplete_packages().await.unwrap().is_empty());
+        assert!(store
+            .quarantine_incomplete_packages()
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
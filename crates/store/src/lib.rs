@@ -8,21 +8,30 @@
 //! can be hard-linked into multiple state directories.
 
 mod archive;
+mod compact;
 mod file_store;
 mod format_detection;
+mod ingest;
 pub mod manifest_io;
 mod package;
+mod relocate;
+mod temp;
 
 pub use archive::{
     create_package, extract_package, extract_package_with_events, list_package_contents,
 };
-pub use file_store::{FileStore, FileVerificationResult};
+pub use compact::{DuplicateObjects, RemovedObjects};
+pub use file_store::{
+    volume_id, FileStore, FileStoreStats, FileVerificationResult, METADATA_FILES,
+};
 pub use format_detection::{PackageFormatDetector, PackageFormatInfo, StoreFormatValidator};
-pub use package::StoredPackage;
+pub use ingest::IngestScan;
+pub use package::{StoredPackage, UnpackedPackage};
+pub use relocate::StoreCopy;
+pub use temp::TempArea;
 
 use sps2_errors::{Error, StorageError};
 use sps2_hash::Hash;
-use sps2_platform::filesystem_helpers::set_compression;
 use sps2_platform::PlatformManager;
 use std::path::{Path, PathBuf};
 
@@ -32,6 +41,7 @@ pub struct PackageStore {
     base_path: PathBuf,
     format_validator: StoreFormatValidator,
     file_store: FileStore,
+    temp: TempArea,
 }
 
 impl PackageStore {
@@ -40,6 +50,7 @@ impl PackageStore {
     pub fn new(base_path: PathBuf) -> Self {
         let file_store = FileStore::new(&base_path);
         Self {
+            temp: TempArea::new(base_path.join("tmp")),
             base_path,
             format_validator: StoreFormatValidator::new(),
             file_store,
@@ -53,6 +64,7 @@ impl PackageStore {
     pub fn new_with_migration_support(base_path: PathBuf) -> Self {
         let file_store = FileStore::new(&base_path);
         Self {
+            temp: TempArea::new(base_path.join("tmp")),
             base_path,
             format_validator: StoreFormatValidator::allow_incompatible(),
             file_store,
@@ -69,6 +81,32 @@ impl PackageStore {
         (platform, context)
     }
 
+    /// Use `strategy` when linking package files out of the store
+    #[must_use]
+    pub fn with_link_strategy(mut self, strategy: sps2_types::LinkStrategy) -> Self {
+        self.file_store = self.file_store.with_link_strategy(strategy);
+        self
+    }
+
+    /// Keep temporary files in `path` instead of `tmp` inside the store
+    #[must_use]
+    pub fn with_temp_path(mut self, path: PathBuf) -> Self {
+        self.temp = TempArea::new(path);
+        self
+    }
+
+    /// Area for temporary files on the store's volume
+    #[must_use]
+    pub fn temp_area(&self) -> &TempArea {
+        &self.temp
+    }
+
+    /// Root directory of the store
+    #[must_use]
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
     /// Get the path for a package hash
     #[must_use]
     pub fn package_path(&self, hash: &Hash) -> PathBuf {
@@ -126,68 +164,66 @@ impl PackageStore {
     /// - Directory creation fails
     /// - Package format is incompatible
     pub async fn add_package(&self, sp_file: &Path) -> Result<StoredPackage, Error> {
+        let unpacked = self.unpack_package(sp_file).await?;
+        self.add_unpacked(&unpacked).await
+    }
+
+    /// Extract and hash a package without adding anything to the store
+    ///
+    /// The result can be inspected to see what adding the package would
+    /// write, then added with [`PackageStore::add_unpacked`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package format is incompatible or the package
+    /// cannot be extracted or hashed.
+    pub async fn unpack_package(&self, sp_file: &Path) -> Result<UnpackedPackage, Error> {
         // Validate package format before processing (no direct printing here)
         self.format_validator
             .validate_before_storage(sp_file)
             .await?;
 
         // Extract to temporary directory first
-        let temp_dir = tempfile::tempdir().map_err(|e| StorageError::IoError {
-            message: e.to_string(),
-        })?;
+        let temp_dir = self.temp.tempdir()?;
 
-        extract_package(sp_file, temp_dir.path()).await?;
+        let flags = archive::extract_package_with_flags(sp_file, temp_dir.path()).await?;
 
         // Compute hash of the extracted contents for package identity
         let package_hash = sps2_hash::Hash::hash_directory(temp_dir.path()).await?;
+        let mut files = self
+            .file_store
+            .hash_package_directory(temp_dir.path())
+            .await?;
+        // Flags come from the archive; the extracted files carry none
+        for file in &mut files {
+            file.flags = flags.get(&file.relative_path).copied();
+        }
 
-        // Check if package already exists
-        let package_path = self.base_path.join("packages").join(package_hash.to_hex());
-        let (platform, ctx) = Self::create_platform_context();
+        Ok(UnpackedPackage::new(temp_dir, package_hash, files))
+    }
 
-        if platform.filesystem().exists(&ctx, &package_path).await {
+    /// Add an unpacked package to the store
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the files or package metadata cannot be written.
+    pub async fn add_unpacked(&self, unpacked: &UnpackedPackage) -> Result<StoredPackage, Error> {
+        // Check if package already exists
+        if self.has_complete_package(unpacked.hash()).await {
             // Package already stored, just return it
-            return StoredPackage::load(&package_path).await;
+            return StoredPackage::load(&self.package_path(unpacked.hash())).await;
         }
 
         // Initialize file store if needed
         self.file_store.initialize().await?;
 
-        // Hash and store all individual files
-        let file_results = self.file_store.store_directory(temp_dir.path()).await?;
-
-        // Create package directory
-        platform
-            .filesystem()
-            .create_dir_all(&ctx, &package_path)
+        // Store all individual files
+        self.file_store
+            .store_hashed_files(unpacked.path(), unpacked.files())
             .await?;
 
-        // Copy manifest to package directory
-        let manifest_src = temp_dir.path().join("manifest.toml");
-        let manifest_dest = package_path.join("manifest.toml");
-        tokio::fs::copy(&manifest_src, &manifest_dest).await?;
-
-        // Copy SBOM if it exists
-        for sbom_name in &["sbom.spdx.json", "sbom.cdx.json"] {
-            let sbom_src = temp_dir.path().join(sbom_name);
-            if platform.filesystem().exists(&ctx, &sbom_src).await {
-                let sbom_dest = package_path.join(sbom_name);
-                tokio::fs::copy(&sbom_src, &sbom_dest).await?;
-            }
-        }
-
-        // Create files.json with all file references
-        let files_json =
-            serde_json::to_string_pretty(&file_results).map_err(|e| StorageError::IoError {
-                message: format!("failed to serialize file results: {e}"),
-            })?;
-        let files_path = package_path.join("files.json");
-        tokio::fs::write(&files_path, files_json).await?;
-
-        // Set compression on macOS
-        set_compression(&package_path)?;
-
-        StoredPackage::load(&package_path).await
+        self.publish_package(unpacked.hash(), unpacked.path(), unpacked.files())
+            .await
     }
 
     /// Remove a package from the store
@@ -230,7 +266,8 @@ impl PackageStore {
     /// - Linking operation fails
     pub async fn link_package(&self, hash: &Hash, dest_root: &Path) -> Result<(), Error> {
         let pkg = StoredPackage::load(&self.package_path(hash)).await?;
-        pkg.link_to(dest_root).await
+        pkg.link_to(dest_root, self.file_store.link_strategy())
+            .await
     }
 
     /// Get SBOM data for a package
@@ -298,7 +335,11 @@ impl PackageStore {
     pub async fn list_packages(&self) -> Result<Vec<Hash>, Error> {
         let mut packages = Vec::new();
 
-        let mut entries = tokio::fs::read_dir(&self.base_path).await?;
+        let mut entries = match tokio::fs::read_dir(self.base_path.join("packages")).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(packages),
+            Err(e) => return Err(e.into()),
+        };
         while let Some(entry) = entries.next_entry().await? {
             if !entry.file_type().await?.is_dir() {
                 continue;
@@ -489,11 +530,8 @@ impl PackageStore {
         let package_hash = self.compute_staging_hash(staging_path).await?;
 
         // Check if package already exists
-        let package_path = self.base_path.join("packages").join(package_hash.to_hex());
-        let (platform, ctx) = Self::create_platform_context();
-
-        if platform.filesystem().exists(&ctx, &package_path).await {
-            return StoredPackage::load(&package_path).await;
+        if self.has_complete_package(&package_hash).await {
+            return StoredPackage::load(&self.package_path(&package_hash)).await;
         }
 
         // Initialize file store if needed
@@ -502,37 +540,8 @@ impl PackageStore {
         // Hash and store all individual files
         let file_results = self.file_store.store_directory(staging_path).await?;
 
-        // Create package directory
-        platform
-            .filesystem()
-            .create_dir_all(&ctx, &package_path)
-            .await?;
-
-        // Copy manifest to package directory
-        let manifest_dest = package_path.join("manifest.toml");
-        tokio::fs::copy(&manifest_path, &manifest_dest).await?;
-
-        // Copy SBOM if it exists
-        for sbom_name in &["sbom.spdx.json", "sbom.cdx.json"] {
-            let sbom_src = staging_path.join(sbom_name);
-            if platform.filesystem().exists(&ctx, &sbom_src).await {
-                let sbom_dest = package_path.join(sbom_name);
-                tokio::fs::copy(&sbom_src, &sbom_dest).await?;
-            }
-        }
-
-        // Create files.json with all file references
-        let files_json =
-            serde_json::to_string_pretty(&file_results).map_err(|e| StorageError::IoError {
-                message: format!("failed to serialize file results: {e}"),
-            })?;
-        let files_path = package_path.join("files.json");
-        tokio::fs::write(&files_path, files_json).await?;
-
-        // Set compression on macOS
-        set_compression(&package_path)?;
-
-        StoredPackage::load(&package_path).await
+        self.publish_package(&package_hash, staging_path, &file_results)
+            .await
     }
 
     /// Compute hash of staging directory contents
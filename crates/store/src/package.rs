@@ -4,10 +4,67 @@ use sps2_errors::{Error, PackageError, StorageError};
 use sps2_hash::FileHashResult;
 use sps2_platform::core::PlatformContext;
 use sps2_platform::PlatformManager;
-use sps2_types::Manifest;
+use sps2_types::{LinkStrategy, Manifest};
+use std::collections::BTreeSet;
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
+/// A package extracted and hashed but not yet added to the store
+///
+/// The extracted files live in a temporary directory removed on drop.
+pub struct UnpackedPackage {
+    temp_dir: tempfile::TempDir,
+    hash: sps2_hash::Hash,
+    files: Vec<FileHashResult>,
+}
+
+impl UnpackedPackage {
+    pub(crate) fn new(
+        temp_dir: tempfile::TempDir,
+        hash: sps2_hash::Hash,
+        files: Vec<FileHashResult>,
+    ) -> Self {
+        Self {
+            temp_dir,
+            hash,
+            files,
+        }
+    }
+
+    /// Package hash the store will file the package under
+    #[must_use]
+    pub fn hash(&self) -> &sps2_hash::Hash {
+        &self.hash
+    }
+
+    /// Directory the package was extracted to
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        self.temp_dir.path()
+    }
+
+    /// Hashes of the package's files, directories and symlinks
+    #[must_use]
+    pub fn files(&self) -> &[FileHashResult] {
+        &self.files
+    }
+
+    /// Total size of the package's regular files
+    #[must_use]
+    pub fn content_bytes(&self) -> u64 {
+        content_bytes(&self.files)
+    }
+}
+
+/// Total size of the regular files among `files`
+fn content_bytes(files: &[FileHashResult]) -> u64 {
+    files
+        .iter()
+        .filter(|file| !file.is_directory && !file.is_symlink)
+        .map(|file| file.size)
+        .sum()
+}
+
 /// A package stored in the content-addressed store
 pub struct StoredPackage {
     path: PathBuf,
@@ -87,6 +144,12 @@ impl StoredPackage {
         self.file_hashes.as_deref()
     }
 
+    /// Total size of the package's regular files, from its file hashes
+    #[must_use]
+    pub fn content_bytes(&self) -> u64 {
+        self.file_hashes.as_deref().map_or(0, content_bytes)
+    }
+
     /// Get the files directory
     #[must_use]
     pub fn files_path(&self) -> PathBuf {
@@ -114,13 +177,30 @@ impl StoredPackage {
         self.path.join("blobs")
     }
 
-    /// Link package contents to a destination
+    /// Link package contents to a destination using `strategy`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if file linking operations fail or the package lacks
+    /// file-level hashes (legacy packages are no longer supported).
+    pub async fn link_to(&self, dest_root: &Path, strategy: LinkStrategy) -> Result<(), Error> {
+        self.link_to_except(dest_root, strategy, &BTreeSet::new())
+            .await
+    }
+
+    /// Link package contents to a destination using `strategy`, leaving out
+    /// the files at the `skipped` relative paths
     ///
     /// # Errors
     ///
     /// Returns an error if file linking operations fail or the package lacks
     /// file-level hashes (legacy packages are no longer supported).
-    pub async fn link_to(&self, dest_root: &Path) -> Result<(), Error> {
+    pub async fn link_to_except(
+        &self,
+        dest_root: &Path,
+        strategy: LinkStrategy,
+        skipped: &BTreeSet<String>,
+    ) -> Result<(), Error> {
         let file_hashes = self
             .file_hashes
             .as_ref()
@@ -135,12 +215,23 @@ impl StoredPackage {
             .ok_or_else(|| StorageError::InvalidPath {
                 path: self.path.display().to_string(),
             })?;
-        let file_store = crate::FileStore::new(store_base);
+        let file_store = crate::FileStore::new(store_base).with_link_strategy(strategy);
 
         // Link all files from the file store
-        file_store
-            .link_files(file_hashes, &PathBuf::new(), dest_root)
-            .await?;
+        if skipped.is_empty() {
+            file_store
+                .link_files(file_hashes, &PathBuf::new(), dest_root)
+                .await?;
+        } else {
+            let kept: Vec<FileHashResult> = file_hashes
+                .iter()
+                .filter(|file| !skipped.contains(&file.relative_path))
+                .cloned()
+                .collect();
+            file_store
+                .link_files(&kept, &PathBuf::new(), dest_root)
+                .await?;
+        }
         Ok(())
     }
 
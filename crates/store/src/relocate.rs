@@ -0,0 +1,224 @@
+//! Copying a whole store to a new location
+
+use crate::file_store::volume_id;
+use crate::PackageStore;
+use sps2_errors::{Error, StorageError};
+use sps2_hash::Hash;
+use sps2_platform::PlatformManager;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Outcome of copying a store with [`PackageStore::copy_to`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreCopy {
+    /// Regular files and symlinks copied
+    pub files: u64,
+    /// Bytes in regular files
+    pub bytes: u64,
+    /// Whether the tree was cloned rather than copied byte for byte
+    pub cloned: bool,
+}
+
+/// What a verified tree entry has to agree on
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    Dir,
+    File(u64),
+    Symlink(PathBuf),
+}
+
+impl PackageStore {
+    /// Copy the entire store to `dest` and verify the copy
+    ///
+    /// `dest` must not exist. On the store's volume the tree is cloned in one
+    /// step; elsewhere it is copied file by file, keeping symlinks and
+    /// permissions. The copy is then checked entry by entry against the
+    /// original, and every object is re-hashed against its name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `dest` exists, copying fails, or the copy does not
+    /// match the original.
+    pub async fn copy_to(&self, dest: &Path) -> Result<StoreCopy, Error> {
+        if fs::symlink_metadata(dest).await.is_ok() {
+            return Err(StorageError::AlreadyExists {
+                path: dest.display().to_string(),
+            }
+            .into());
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let same_volume = matches!(
+            (volume_id(&self.base_path).await, volume_id(dest).await),
+            (Some(store), Some(target)) if store == target
+        );
+        if same_volume {
+            let platform = PlatformManager::instance().platform();
+            let ctx = platform.create_context(None);
+            platform
+                .filesystem()
+                .clone_directory(&ctx, &self.base_path, dest)
+                .await?;
+        } else {
+            copy_tree(&self.base_path, dest).await?;
+        }
+
+        let source = walk(&self.base_path).await?;
+        let copied = walk(dest).await?;
+        if let Some((path, _)) = source
+            .iter()
+            .find(|(path, entry)| copied.get(*path) != Some(*entry))
+            .or_else(|| copied.iter().find(|(path, _)| !source.contains_key(*path)))
+        {
+            return Err(mismatch(dest, path, "differs from the original"));
+        }
+
+        let mut summary = StoreCopy {
+            cloned: same_volume,
+            ..StoreCopy::default()
+        };
+        for (path, entry) in &copied {
+            match entry {
+                Entry::File(size) => {
+                    summary.files += 1;
+                    summary.bytes += size;
+                    if path.starts_with("objects") {
+                        verify_object(dest, path).await?;
+                    }
+                }
+                Entry::Symlink(_) => summary.files += 1,
+                Entry::Dir => {}
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+/// Recursively copy `src` to `dst`, keeping symlinks and permissions
+async fn copy_tree(src: &Path, dst: &Path) -> Result<(), Error> {
+    // Directory permissions are applied last so read-only directories can
+    // still be filled
+    let mut dir_permissions = Vec::new();
+    let mut pending = vec![(src.to_path_buf(), dst.to_path_buf())];
+    while let Some((from, to)) = pending.pop() {
+        fs::create_dir(&to).await?;
+        dir_permissions.push((to.clone(), fs::metadata(&from).await?.permissions()));
+
+        let mut entries = fs::read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let target = to.join(entry.file_name());
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() {
+                pending.push((entry.path(), target));
+            } else if file_type.is_symlink() {
+                let link = fs::read_link(entry.path()).await?;
+                fs::symlink(link, &target).await?;
+            } else {
+                fs::copy(entry.path(), &target).await?;
+            }
+        }
+    }
+
+    for (dir, permissions) in dir_permissions.into_iter().rev() {
+        fs::set_permissions(&dir, permissions).await?;
+    }
+    Ok(())
+}
+
+/// Every entry under `root`, keyed by its path relative to `root`
+async fn walk(root: &Path) -> Result<BTreeMap<PathBuf, Entry>, Error> {
+    let mut entries = BTreeMap::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let mut read_dir = fs::read_dir(&dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+            let metadata = fs::symlink_metadata(&path).await?;
+            let kind = if metadata.is_dir() {
+                dirs.push(path);
+                Entry::Dir
+            } else if metadata.file_type().is_symlink() {
+                Entry::Symlink(fs::read_link(&path).await?)
+            } else {
+                Entry::File(metadata.len())
+            };
+            entries.insert(relative, kind);
+        }
+    }
+    Ok(entries)
+}
+
+/// Check that the object at `relative` under `root` hashes to its file name
+async fn verify_object(root: &Path, relative: &Path) -> Result<(), Error> {
+    let Some(expected) = relative
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| Hash::from_hex(name).ok())
+    else {
+        // Only content-addressed files carry a hash in their name
+        return Ok(());
+    };
+    let actual = Hash::hash_file_with_algorithm(&root.join(relative), expected.algorithm()).await?;
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(mismatch(root, relative, "does not match its hash"))
+    }
+}
+
+fn mismatch(root: &Path, relative: &Path, what: &str) -> Error {
+    StorageError::CorruptedData {
+        message: format!(
+            "{} in the copied store {what}",
+            root.join(relative).display()
+        ),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_copy_to_verifies_objects() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.txt");
+        fs::write(&source, b"relocated").await.unwrap();
+
+        let store = PackageStore::new(temp_dir.path().join("store"));
+        let (hash, _) = store
+            .file_store()
+            .store_file_with_hash(&source)
+            .await
+            .unwrap();
+        fs::create_dir_all(store.base_path().join("packages/abc"))
+            .await
+            .unwrap();
+        fs::symlink("files.json", store.base_path().join("packages/abc/link"))
+            .await
+            .unwrap();
+
+        let dest = temp_dir.path().join("moved/store");
+        let copy = store.copy_to(&dest).await.unwrap();
+        assert_eq!(copy.files, 2);
+        assert_eq!(copy.bytes, 9);
+
+        let moved = PackageStore::new(dest.clone());
+        assert!(moved.file_store().verify_file(&hash).await.unwrap());
+        assert!(fs::symlink_metadata(dest.join("packages/abc/link"))
+            .await
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        // Refuses to overwrite
+        assert!(store.copy_to(&dest).await.is_err());
+    }
+}
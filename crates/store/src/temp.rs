@@ -0,0 +1,133 @@
+//! Managed area for temporary files on the store's volume
+//!
+//! `tempfile` places temporary files under `$TMPDIR`, which is often on a
+//! different volume than the store. Packages extracted or downloaded there
+//! have to be copied into the store instead of linked or renamed, doubling
+//! the I/O. Store ingestion, downloads and builds create their temporary
+//! files in the temp area instead, which defaults to `tmp` inside the store.
+//!
+//! Everything in the area is removed when dropped; entries an interrupted
+//! operation leaves behind are removed by [`TempArea::prune`] once old.
+
+use sps2_errors::{Error, StorageError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+use tokio::fs;
+
+/// Prefix of the entries sps2 creates in the temp area
+const ENTRY_PREFIX: &str = "sps2-";
+
+/// Directory holding the temporary files of sps2 operations
+#[derive(Clone, Debug)]
+pub struct TempArea {
+    root: PathBuf,
+}
+
+impl TempArea {
+    /// Temp area at `root`, created when first used
+    #[must_use]
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Directory of the temp area
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Create a temporary directory in the area, removed when dropped
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the area or the directory cannot be created.
+    pub fn tempdir(&self) -> Result<TempDir, Error> {
+        let io_error = |e: std::io::Error| StorageError::IoError {
+            message: format!(
+                "failed to create temp directory in {}: {e}",
+                self.root.display()
+            ),
+        };
+        std::fs::create_dir_all(&self.root).map_err(io_error)?;
+        tempfile::Builder::new()
+            .prefix(ENTRY_PREFIX)
+            .tempdir_in(&self.root)
+            .map_err(|e| io_error(e).into())
+    }
+
+    /// Bytes the temp area currently occupies
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the area cannot be read.
+    pub async fn usage(&self) -> Result<u64, Error> {
+        if fs::symlink_metadata(&self.root).await.is_err() {
+            return Ok(0);
+        }
+        sps2_platform::fs::size(&self.root).await
+    }
+
+    /// Remove entries last modified more than `max_age` ago
+    ///
+    /// Returns the number of entries removed and the bytes they took.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the area cannot be read or an entry cannot be
+    /// removed.
+    pub async fn prune(&self, max_age: Duration) -> Result<(usize, u64), Error> {
+        let mut entries = match fs::read_dir(&self.root).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(e.into()),
+        };
+        let Some(cutoff) = SystemTime::now().checked_sub(max_age) else {
+            return Ok((0, 0));
+        };
+
+        let (mut removed, mut freed) = (0, 0);
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = entry.metadata().await?;
+            if metadata.modified()? >= cutoff {
+                continue;
+            }
+            let path = entry.path();
+            freed += sps2_platform::fs::size(&path).await?;
+            if metadata.is_dir() {
+                fs::remove_dir_all(&path).await?;
+            } else {
+                fs::remove_file(&path).await?;
+            }
+            removed += 1;
+        }
+        Ok((removed, freed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn temp_dirs_are_created_in_the_area_and_pruned_when_old() {
+        let root = tempfile::tempdir().unwrap();
+        let area = TempArea::new(root.path().join("tmp"));
+        assert_eq!(area.usage().await.unwrap(), 0);
+
+        let dir = area.tempdir().unwrap();
+        assert!(dir.path().starts_with(area.path()));
+        fs::write(dir.path().join("package.sp"), b"contents")
+            .await
+            .unwrap();
+        assert!(area.usage().await.unwrap() >= 8);
+
+        // A recent entry is kept, one left behind for long is removed
+        assert_eq!(area.prune(Duration::from_secs(3600)).await.unwrap().0, 0);
+        let left_behind = dir.keep();
+        let (removed, freed) = area.prune(Duration::ZERO).await.unwrap();
+        assert_eq!(removed, 1);
+        assert!(freed >= 8);
+        assert!(!left_behind.exists());
+    }
+}
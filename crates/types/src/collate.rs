@@ -0,0 +1,110 @@
+//! Ordering of names and versions in listings
+//!
+//! Every user-facing listing sorts through these functions so package
+//! names, state snapshots and store entries come out in the same order
+//! everywhere. Names compare case-insensitively with digit runs compared by
+//! value (`lib2` before `lib10`); versions compare by semantic version.
+//! Both fall back to a plain byte comparison for otherwise equal strings, so
+//! the order is total and does not depend on input order or hash map
+//! iteration.
+//!
+//! Case folding uses Unicode lowercase mapping and is the same in every
+//! locale; no locale-specific tailoring is applied, which keeps output
+//! reproducible across machines.
+
+use semver::Version;
+use std::cmp::Ordering;
+
+/// Compare two names naturally: case-insensitively, with digit runs
+/// compared by numeric value
+#[must_use]
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    folded_cmp(a, b).then_with(|| a.cmp(b))
+}
+
+/// Compare two version strings, oldest first
+///
+/// Strings that do not parse as a version sort before all versions, among
+/// themselves by [`natural_cmp`].
+#[must_use]
+pub fn version_cmp(a: &str, b: &str) -> Ordering {
+    match (Version::parse(a), Version::parse(b)) {
+        (Ok(x), Ok(y)) => x.cmp(&y).then_with(|| natural_cmp(a, b)),
+        (Ok(_), Err(_)) => Ordering::Greater,
+        (Err(_), Ok(_)) => Ordering::Less,
+        (Err(_), Err(_)) => natural_cmp(a, b),
+    }
+}
+
+/// Natural comparison ignoring case and leading zeros
+fn folded_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (x_run, x_rest) = split_digits(a);
+            let (y_run, y_rest) = split_digits(b);
+            (a, b) = (x_rest, y_rest);
+            let x_run = x_run.trim_start_matches('0');
+            let y_run = y_run.trim_start_matches('0');
+            x_run.len().cmp(&y_run.len()).then_with(|| x_run.cmp(y_run))
+        } else {
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+            x.to_lowercase().cmp(y.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut items: Vec<&str>, cmp: fn(&str, &str) -> Ordering) -> Vec<&str> {
+        items.sort_by(|a, b| cmp(a, b));
+        items
+    }
+
+    #[test]
+    fn names_sort_naturally_and_ignore_case() {
+        assert_eq!(
+            sorted(vec!["lib10", "Zlib", "lib2", "curl", "LIB2"], natural_cmp),
+            ["curl", "LIB2", "lib2", "lib10", "Zlib"]
+        );
+    }
+
+    #[test]
+    fn leading_zeros_only_break_ties() {
+        assert_eq!(natural_cmp("v007", "v7"), "v007".cmp("v7"));
+        assert_eq!(natural_cmp("v007", "v8"), Ordering::Less);
+    }
+
+    #[test]
+    fn order_does_not_depend_on_input_order() {
+        let names = vec!["b", "B", "a10", "a9", "a09"];
+        let mut reversed = names.clone();
+        reversed.reverse();
+        assert_eq!(sorted(names, natural_cmp), sorted(reversed, natural_cmp));
+    }
+
+    #[test]
+    fn versions_sort_semantically_with_unparsable_first() {
+        assert_eq!(
+            sorted(
+                vec!["1.10.0", "1.2.0", "nightly", "1.2.0-rc.1", "0.9.0"],
+                version_cmp
+            ),
+            ["nightly", "0.9.0", "1.2.0-rc.1", "1.2.0", "1.10.0"]
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Package archive filenames
+//!
+//! Packages are named `<name>-<version>-<revision>.<arch>.sp`, as produced by
+//! [`Manifest::filename`](crate::Manifest::filename). Both names and versions
+//! may contain `-` (`lib-foo`, `1.0.0-rc.1`), so the split between them is
+//! found by trying each `-` from the left and taking the first one that leaves
+//! a valid package name before it and a semantic version after it. The
+//! package name policy rejects names that contain a version after a `-`,
+//! which makes that split unique: every filename built from a valid name and
+//! version parses back to exactly that name and version.
+
+use crate::name::validate_package_name;
+use semver::Version;
+use sps2_errors::PackageError;
+use std::fmt;
+
+/// Extension of package archives, without the dot
+pub const EXTENSION: &str = "sp";
+
+/// The parts of a package archive filename
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageFilename {
+    pub name: String,
+    pub version: Version,
+    pub revision: u32,
+    pub arch: String,
+}
+
+impl PackageFilename {
+    /// Split `filename` into name, version, revision and architecture
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PackageError::InvalidFormat`] if `filename` is not a package
+    /// archive name or its name or version is invalid.
+    pub fn parse(filename: &str) -> Result<Self, PackageError> {
+        let invalid = |reason: &str| PackageError::InvalidFormat {
+            message: format!("invalid package filename {filename:?}: {reason}"),
+        };
+
+        let stem = filename
+            .strip_suffix(EXTENSION)
+            .and_then(|rest| rest.strip_suffix('.'))
+            .ok_or_else(|| invalid("missing .sp extension"))?;
+        let (rest, arch) = stem
+            .rsplit_once('.')
+            .filter(|(_, arch)| !arch.is_empty() && !arch.contains('-'))
+            .ok_or_else(|| invalid("missing architecture"))?;
+        let (rest, revision) = rest
+            .rsplit_once('-')
+            .ok_or_else(|| invalid("missing revision"))?;
+        let revision = revision
+            .parse()
+            .ok()
+            .filter(|_| revision.bytes().all(|b| b.is_ascii_digit()))
+            .ok_or_else(|| invalid("revision is not a number"))?;
+
+        rest.match_indices('-')
+            .find_map(|(i, _)| {
+                let (name, version) = (&rest[..i], &rest[i + 1..]);
+                let version = Version::parse(version).ok()?;
+                validate_package_name(name).ok()?;
+                Some(Self {
+                    name: name.to_string(),
+                    version,
+                    revision,
+                    arch: arch.to_string(),
+                })
+            })
+            .ok_or_else(|| invalid("no valid package name and version"))
+    }
+}
+
+impl fmt::Display for PackageFilename {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}-{}.{}.{EXTENSION}",
+            self.name, self.version, self.revision, self.arch
+        )
+    }
+}
+
+/// Whether some `-` in `name` is followed by text that reads as the start of
+/// a version, which would make `<name>-<version>` split ambiguously
+pub(crate) fn contains_version(name: &str) -> bool {
+    name.match_indices('-')
+        .any(|(i, _)| Version::parse(&format!("{}-0", &name[i + 1..])).is_ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filename(name: &str, version: &str, revision: u32) -> PackageFilename {
+        PackageFilename {
+            name: name.to_string(),
+            version: Version::parse(version).unwrap(),
+            revision,
+            arch: "arm64".to_string(),
+        }
+    }
+
+    #[test]
+    fn round_trips_names_and_versions_with_dashes() {
+        let names = [
+            "jq",
+            "lib-foo",
+            "python-3",
+            "gtk-2-bin",
+            "x264-2024",
+            "c++",
+            "py3.12-tools",
+        ];
+        let versions = [
+            "1.0.0",
+            "0.10.3",
+            "1.0.0-rc.1",
+            "2.0.0-beta-2",
+            "1.2.3+build.5",
+            "1.2.3-1-2+meta-data",
+        ];
+        for name in names {
+            for version in versions {
+                for revision in [0, 1, 42] {
+                    let expected = filename(name, version, revision);
+                    let text = expected.to_string();
+                    assert_eq!(PackageFilename::parse(&text).unwrap(), expected, "{text}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn matches_manifest_filename() {
+        let manifest = crate::Manifest::new(
+            "lib-foo".to_string(),
+            &Version::parse("1.0.0-rc.1").unwrap(),
+            3,
+            &crate::Arch::Arm64,
+        );
+        let parsed = PackageFilename::parse(&manifest.filename()).unwrap();
+        assert_eq!(parsed.to_string(), manifest.filename());
+        assert_eq!(parsed.name, "lib-foo");
+        assert_eq!(parsed.revision, 3);
+    }
+
+    #[test]
+    fn rejects_malformed_filenames() {
+        for text in [
+            "jq-1.0.0-1.arm64",
+            "jq-1.0.0-1.arm64.tar",
+            "jq-1.0.0-1..sp",
+            "jq-1.0.0.arm64.sp",
+            "jq-1.0.0-x.arm64.sp",
+            "jq-1.0.0-+1.arm64.sp",
+            "jq-1.0-1.arm64.sp",
+            "-1.0.0-1.arm64.sp",
+            "Jq-1.0.0-1.arm64.sp",
+            "jq1.0.0-1.arm64.sp",
+        ] {
+            assert!(PackageFilename::parse(text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn detects_names_containing_versions() {
+        assert!(contains_version("foo-1.2.3"));
+        assert!(contains_version("foo-1.2.3-bar"));
+        assert!(!contains_version("python-3"));
+        assert!(!contains_version("foo-1.2"));
+        assert!(!contains_version("foo"));
+    }
+}
@@ -7,21 +7,26 @@
 //! This crate provides fundamental types used throughout the system,
 //! including version specifications, package information, and common data structures.
 
+pub mod collate;
+pub mod filename;
 pub mod format;
 pub mod manifest;
+pub mod name;
 pub mod package;
 pub mod recipe;
 pub mod reports;
+pub mod schema;
 pub mod state;
 pub mod version;
 
 // Re-export commonly used types
+pub use filename::PackageFilename;
 pub use format::{
     PackageFormatChecker, PackageFormatCompatibility, PackageFormatMigration,
     PackageFormatValidationResult, PackageFormatVersion, PackageFormatVersionError,
 };
 pub use manifest::{
-    Dependencies as ManifestDependencies, Manifest, ManifestBuilder,
+    AppliedPatch, Dependencies as ManifestDependencies, Manifest, ManifestBuilder,
     PackageInfo as ManifestPackageInfo,
 };
 pub use package::{
@@ -33,7 +38,10 @@ pub use recipe::{
     GitSource, Install, IsolationLevel, LocalSource, Metadata, NamedSource, ParsedStep, Post,
     PostOption, Source, SourceMethod, YamlRecipe,
 };
-pub use reports::{BuildReport, InstallReport, PackageChange};
+pub use reports::{
+    BrokenDependency, BuildReport, InstallReport, PackageChange, TestResults, TestStatus,
+};
+pub use schema::{report_schema, ReportSchema, REPORT_SCHEMAS};
 pub use semver::Version;
 pub use state::{ChangeType, OpChange, SlotId, StateId, StateInfo, StateTransition};
 pub use uuid::Uuid;
@@ -44,10 +52,62 @@ pub use version::{VersionConstraint, VersionSpec};
 use serde::{Deserialize, Serialize};
 
 /// Architecture type for packages
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Arch {
     #[serde(rename = "arm64")]
     Arm64,
+    #[serde(rename = "x86_64")]
+    X86_64,
+    /// Fat binaries carrying both `arm64` and `x86_64` slices
+    #[serde(rename = "universal2")]
+    Universal2,
+}
+
+impl Arch {
+    /// Architecture of the machine sps2 runs on
+    ///
+    /// Only Intel Macs report `x86_64`; elsewhere sps2 is being developed
+    /// or tested rather than used, and Apple Silicon is assumed.
+    #[must_use]
+    pub fn host() -> Self {
+        if cfg!(all(target_os = "macos", target_arch = "x86_64")) {
+            Self::X86_64
+        } else {
+            Self::Arm64
+        }
+    }
+
+    /// Whether packages built for this architecture run on `host`
+    #[must_use]
+    pub fn runs_on(self, host: Self) -> bool {
+        self == Self::Universal2 || self == host
+    }
+
+    /// Architecture names passed to the compiler with `-arch`
+    #[must_use]
+    pub fn slices(self) -> &'static [&'static str] {
+        match self {
+            Self::Arm64 => &["arm64"],
+            Self::X86_64 => &["x86_64"],
+            Self::Universal2 => &["arm64", "x86_64"],
+        }
+    }
+}
+
+impl std::str::FromStr for Arch {
+    type Err = sps2_errors::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "arm64" => Ok(Self::Arm64),
+            "x86_64" => Ok(Self::X86_64),
+            "universal2" => Ok(Self::Universal2),
+            _ => Err(sps2_errors::PackageError::InvalidFormat {
+                message: format!("unsupported architecture: {s}"),
+            }
+            .into()),
+        }
+    }
 }
 
 /// `RPath` handling style for dynamic libraries and executables
@@ -95,6 +155,60 @@ pub enum BuildSystemProfile {
     ScriptLight,
 }
 
+/// What a failing recipe test stage does to the build
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestFailurePolicy {
+    /// Fail the build
+    #[default]
+    Fail,
+    /// Report the failure as a warning and keep building
+    Warn,
+    /// Do not run the tests
+    Skip,
+}
+
+impl<'de> serde::Deserialize<'de> for TestFailurePolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct TestFailurePolicyVisitor;
+
+        impl serde::de::Visitor<'_> for TestFailurePolicyVisitor {
+            type Value = TestFailurePolicy;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("a test failure policy (fail, warn, or skip)")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match value.trim().to_ascii_lowercase().as_str() {
+                    "fail" => Ok(TestFailurePolicy::Fail),
+                    "warn" => Ok(TestFailurePolicy::Warn),
+                    "skip" => Ok(TestFailurePolicy::Skip),
+                    other => Err(serde::de::Error::unknown_variant(
+                        other,
+                        &["fail", "warn", "skip"],
+                    )),
+                }
+            }
+
+            fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                self.visit_str(&value)
+            }
+        }
+
+        deserializer.deserialize_any(TestFailurePolicyVisitor)
+    }
+}
+
 /// QA pipeline override for manual recipe control
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -212,6 +326,8 @@ impl std::fmt::Display for Arch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Arm64 => write!(f, "arm64"),
+            Self::X86_64 => write!(f, "x86_64"),
+            Self::Universal2 => write!(f, "universal2"),
         }
     }
 }
@@ -260,3 +376,367 @@ impl Default for ColorChoice {
         Self::Auto
     }
 }
+
+impl ColorChoice {
+    /// Whether to color output written to a stream
+    ///
+    /// `Auto` follows the `NO_COLOR` and `CLICOLOR` conventions: `NO_COLOR`
+    /// set to anything non-empty or `CLICOLOR=0` disables colors,
+    /// `CLICOLOR_FORCE` set to anything but `0` enables them even when
+    /// `is_terminal` is false, and otherwise only terminals get colors.
+    #[must_use]
+    pub fn enabled(self, is_terminal: bool) -> bool {
+        self.enabled_with_env(is_terminal, |name| std::env::var(name).ok())
+    }
+
+    fn enabled_with_env(self, is_terminal: bool, env: impl Fn(&str) -> Option<String>) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                if env("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+                    false
+                } else if env("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+                    true
+                } else {
+                    is_terminal && env("CLICOLOR").as_deref() != Some("0")
+                }
+            }
+        }
+    }
+}
+
+/// How table cells too long for the terminal are shortened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EllipsisPolicy {
+    /// Keep the start: `very-long-na…`
+    End,
+    /// Keep both ends, so version and variant suffixes stay visible: `very-l…name`
+    #[default]
+    Middle,
+    /// Never shorten; tables may exceed the terminal width
+    Off,
+}
+
+/// How files from the store are materialized into a state
+///
+/// Clones and hard links only work when the store and the destination are on
+/// the same volume, so every strategy falls back to copying when a destination
+/// is on another volume. A refused hard link is retried as a clone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStrategy {
+    /// `clonefile` on the store's volume, copies elsewhere
+    #[default]
+    Auto,
+    /// APFS copy-on-write clones
+    Clonefile,
+    /// Hard links sharing the store's read-only inode
+    Hardlink,
+    /// Full copies
+    Copy,
+}
+
+impl std::fmt::Display for LinkStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Clonefile => "clonefile",
+            Self::Hardlink => "hardlink",
+            Self::Copy => "copy",
+        })
+    }
+}
+
+/// What to do when packages in the same state place different files at
+/// the same path
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Refuse the operation
+    #[default]
+    Fail,
+    /// Keep the file of the package installed first: one already installed
+    /// before one being installed, otherwise the first by name
+    PreferFirst,
+    /// Keep the file of the package named on the command line; conflicts
+    /// between two named or two unnamed packages, or that would take a path
+    /// from a package staying installed, still fail
+    PreferExplicit,
+}
+
+impl ConflictPolicy {
+    /// Name used on the command line and in the config file
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Fail => "fail",
+            Self::PreferFirst => "prefer-first",
+            Self::PreferExplicit => "prefer-explicit",
+        }
+    }
+}
+
+impl clap::ValueEnum for ConflictPolicy {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Fail, Self::PreferFirst, Self::PreferExplicit]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()))
+    }
+}
+
+impl std::fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Software bill of materials document format
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SbomFormat {
+    /// SPDX 2.3 JSON
+    #[default]
+    Spdx,
+    /// `CycloneDX` 1.5 JSON
+    CycloneDx,
+}
+
+impl clap::ValueEnum for SbomFormat {
+    fn value_variants<'a>() -> &'a [Self] {
+        &[Self::Spdx, Self::CycloneDx]
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(match self {
+            Self::Spdx => clap::builder::PossibleValue::new("spdx"),
+            Self::CycloneDx => clap::builder::PossibleValue::new("cyclonedx").alias("cdx"),
+        })
+    }
+}
+
+impl std::fmt::Display for SbomFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Spdx => write!(f, "SPDX"),
+            Self::CycloneDx => write!(f, "CycloneDX"),
+        }
+    }
+}
+
+/// Shell that `sps2 env` writes statements for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    /// Z shell, the default login shell on macOS
+    Zsh,
+    /// GNU Bourne-Again shell
+    Bash,
+    /// Friendly interactive shell
+    Fish,
+}
+
+impl Shell {
+    /// Every supported shell
+    pub const ALL: [Self; 3] = [Self::Zsh, Self::Bash, Self::Fish];
+
+    /// Profile snippet, relative to the live prefix, that the installer
+    /// generates for this shell from the `environment` of installed packages
+    #[must_use]
+    pub const fn profile_snippet(self) -> &'static str {
+        match self {
+            Self::Zsh | Self::Bash => "etc/profile.d/sps2.sh",
+            Self::Fish => "etc/profile.d/sps2.fish",
+        }
+    }
+
+    /// Shell whose executable is at `path`, such as the value of `$SHELL`
+    #[must_use]
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.file_name()?.to_str()? {
+            "zsh" => Some(Self::Zsh),
+            "bash" => Some(Self::Bash),
+            "fish" => Some(Self::Fish),
+            _ => None,
+        }
+    }
+}
+
+impl clap::ValueEnum for Shell {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Self::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(match self {
+            Self::Zsh => "zsh",
+            Self::Bash => "bash",
+            Self::Fish => "fish",
+        }))
+    }
+}
+
+/// A directory outside the live prefix that mirrors part of it
+///
+/// Package files below `source` in the live prefix are copied to the same
+/// relative paths below `path`, and verification covers the copies.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManagedDirectory {
+    /// Absolute path of the directory
+    pub path: std::path::PathBuf,
+    /// Directory of the live prefix mirrored into it, such as `share/acme`
+    pub source: String,
+    /// Copy package files into the directory after every state change
+    #[serde(default = "default_populate")]
+    pub populate: bool,
+    /// What verification does with copies that are missing or differ
+    #[serde(default)]
+    pub drift: ManagedDriftPolicy,
+    /// What verification does with files no package provides
+    #[serde(default)]
+    pub untracked: ManagedUntrackedPolicy,
+}
+
+fn default_populate() -> bool {
+    true
+}
+
+/// Handling of missing or modified files in a managed directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManagedDriftPolicy {
+    /// Report them, and restore them from the live prefix when healing
+    #[default]
+    Heal,
+    /// Report them, but leave them alone when healing
+    Report,
+    /// Do not verify the directory
+    Skip,
+}
+
+/// Handling of files in a managed directory that no package provides
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManagedUntrackedPolicy {
+    /// Leave them alone; the directory is shared with other software
+    #[default]
+    Ignore,
+    /// Report them
+    Report,
+    /// Report them, and remove them when healing
+    Remove,
+}
+
+/// Cache that can be listed and cleared on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CacheKind {
+    /// Cached repository index and its `ETag`
+    Index,
+    /// Verified package downloads kept for installing them again
+    Downloads,
+    /// Downloads kept after failing verification
+    Quarantine,
+    /// Build working directories with fetched sources
+    BuildSources,
+    /// Packages kept to skip rebuilding identical inputs
+    BuildArtifacts,
+    /// ccache/sccache directory configured for builds
+    Compiler,
+    /// Discovered platform tool locations
+    PlatformTools,
+    /// Temporary files left in the temp area
+    Temp,
+}
+
+impl CacheKind {
+    /// Every cache, in listing order
+    pub const ALL: [Self; 8] = [
+        Self::Index,
+        Self::Downloads,
+        Self::Quarantine,
+        Self::BuildSources,
+        Self::BuildArtifacts,
+        Self::Compiler,
+        Self::PlatformTools,
+        Self::Temp,
+    ];
+
+    /// Name used on the command line and in reports
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Index => "index",
+            Self::Downloads => "downloads",
+            Self::Quarantine => "quarantine",
+            Self::BuildSources => "build-sources",
+            Self::BuildArtifacts => "build-artifacts",
+            Self::Compiler => "compiler",
+            Self::PlatformTools => "platform-tools",
+            Self::Temp => "temp",
+        }
+    }
+}
+
+impl clap::ValueEnum for CacheKind {
+    fn value_variants<'a>() -> &'a [Self] {
+        &Self::ALL
+    }
+
+    fn to_possible_value(&self) -> Option<clap::builder::PossibleValue> {
+        Some(clap::builder::PossibleValue::new(self.name()))
+    }
+}
+
+impl std::fmt::Display for CacheKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).to_string())
+        }
+    }
+
+    #[test]
+    fn auto_color_follows_no_color_and_clicolor() {
+        let auto = ColorChoice::Auto;
+        assert!(auto.enabled_with_env(true, env(&[])));
+        assert!(!auto.enabled_with_env(false, env(&[])));
+        assert!(!auto.enabled_with_env(true, env(&[("NO_COLOR", "1")])));
+        assert!(auto.enabled_with_env(true, env(&[("NO_COLOR", "")])));
+        assert!(auto.enabled_with_env(false, env(&[("CLICOLOR_FORCE", "1")])));
+        assert!(!auto.enabled_with_env(false, env(&[("CLICOLOR_FORCE", "0")])));
+        assert!(!auto.enabled_with_env(true, env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])));
+        assert!(!auto.enabled_with_env(true, env(&[("CLICOLOR", "0")])));
+
+        assert!(ColorChoice::Always.enabled_with_env(false, env(&[("NO_COLOR", "1")])));
+        assert!(!ColorChoice::Never.enabled_with_env(true, env(&[("CLICOLOR_FORCE", "1")])));
+    }
+
+    #[test]
+    fn universal2_runs_on_every_arch() {
+        for name in ["arm64", "x86_64", "universal2"] {
+            let arch: Arch = name.parse().unwrap();
+            assert_eq!(arch.to_string(), name);
+        }
+        assert!("ppc".parse::<Arch>().is_err());
+
+        assert!(Arch::Universal2.runs_on(Arch::Arm64));
+        assert!(Arch::Universal2.runs_on(Arch::X86_64));
+        assert!(Arch::X86_64.runs_on(Arch::X86_64));
+        assert!(!Arch::X86_64.runs_on(Arch::Arm64));
+        assert_eq!(Arch::Universal2.slices(), ["arm64", "x86_64"]);
+    }
+}
@@ -8,6 +8,28 @@
 use crate::{package::PackageSpec, Arch, PackageFormatVersion, PythonPackageMetadata, Version};
 use serde::{de::IgnoredAny, Deserialize, Serialize};
 use sps2_errors::{Error, PackageError};
+use std::collections::BTreeMap;
+
+/// Search paths `sps2 env` points at the live prefix, which a package's
+/// `environment` cannot set
+pub const RESERVED_ENVIRONMENT: [&str; 5] = [
+    "PATH",
+    "MANPATH",
+    "PKG_CONFIG_PATH",
+    "CPATH",
+    "LIBRARY_PATH",
+];
+
+/// Whether a package's `environment` can set the variable `name`
+#[must_use]
+pub fn is_valid_environment_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && !RESERVED_ENVIRONMENT.contains(&name)
+}
 
 /// Package manifest (manifest.toml contents)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -18,6 +40,15 @@ pub struct Manifest {
     pub package: PackageInfo,
     pub dependencies: Dependencies,
 
+    /// Source patches applied when the package was built
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<AppliedPatch>,
+
+    /// Environment variables users of the package need, such as `JAVA_HOME`;
+    /// `${PREFIX}` in a value stands for the live prefix
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environment: BTreeMap<String, String>,
+
     /// Optional Python-specific metadata for Python packages
     #[serde(skip_serializing_if = "Option::is_none")]
     pub python: Option<PythonPackageMetadata>,
@@ -48,6 +79,20 @@ pub struct Dependencies {
     pub runtime: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub build: Vec<String>,
+    /// Packages installed alongside by default, but not needed to run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recommends: Vec<String>,
+}
+
+/// A source patch applied when the package was built
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppliedPatch {
+    /// Patch file name or URL, as given in the recipe
+    pub source: String,
+    /// BLAKE3 hash of the patch
+    pub blake3: String,
+    /// Leading path components stripped (`patch -p`)
+    pub strip: u32,
 }
 
 impl Manifest {
@@ -67,7 +112,8 @@ impl Manifest {
                 legacy_compression: None,
             },
             dependencies: Dependencies::default(),
-
+            patches: Vec::new(),
+            environment: BTreeMap::new(),
             python: None,
         }
     }
@@ -90,15 +136,9 @@ impl Manifest {
     ///
     /// # Errors
     ///
-    /// Returns an error if the architecture string is not supported (currently only "arm64" is supported).
+    /// Returns an error if the architecture is not `arm64`, `x86_64` or `universal2`.
     pub fn arch(&self) -> Result<Arch, Error> {
-        match self.package.arch.as_str() {
-            "arm64" => Ok(Arch::Arm64),
-            _ => Err(PackageError::InvalidFormat {
-                message: format!("unsupported architecture: {}", self.package.arch),
-            }
-            .into()),
-        }
+        self.package.arch.parse()
     }
 
     /// Get runtime dependencies as `PackageSpec`
@@ -129,6 +169,20 @@ impl Manifest {
             .map_err(Into::into)
     }
 
+    /// Get recommended packages as `PackageSpec`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any recommendation specification string is invalid or cannot be parsed.
+    pub fn recommended_deps(&self) -> Result<Vec<PackageSpec>, Error> {
+        self.dependencies
+            .recommends
+            .iter()
+            .map(|s| PackageSpec::parse(s))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(Into::into)
+    }
+
     /// Add a runtime dependency
     pub fn add_runtime_dep(&mut self, spec: &str) {
         self.dependencies.runtime.push(spec.to_string());
@@ -197,12 +251,7 @@ impl Manifest {
     /// Returns an error if any required field is empty, invalid, or if dependency specifications are malformed.
     pub fn validate(&self) -> Result<(), Error> {
         // Validate name
-        if self.package.name.is_empty() {
-            return Err(PackageError::InvalidManifest {
-                message: "package name cannot be empty".to_string(),
-            }
-            .into());
-        }
+        crate::name::validate_package_name(&self.package.name)?;
 
         // Validate version
         self.version()?;
@@ -213,6 +262,18 @@ impl Manifest {
         // Validate dependencies
         self.runtime_deps()?;
         self.build_deps()?;
+        self.recommended_deps()?;
+
+        if let Some(name) = self
+            .environment
+            .keys()
+            .find(|name| !is_valid_environment_name(name))
+        {
+            return Err(PackageError::InvalidManifest {
+                message: format!("environment cannot set '{name}'"),
+            }
+            .into());
+        }
 
         // Validate format version compatibility
         let current_version = PackageFormatVersion::CURRENT;
@@ -303,6 +364,15 @@ impl ManifestBuilder {
         self
     }
 
+    /// Set an environment variable users of the package need
+    #[must_use]
+    pub fn environment(mut self, name: &str, value: &str) -> Self {
+        self.manifest
+            .environment
+            .insert(name.to_string(), value.to_string());
+        self
+    }
+
     /// Set Python package metadata
     #[must_use]
     pub fn python_metadata(mut self, metadata: PythonPackageMetadata) -> Self {
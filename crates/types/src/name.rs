@@ -0,0 +1,89 @@
+//! Package name policy
+//!
+//! Names end up in `.sp` filenames (`<name>-<version>-<revision>.<arch>.sp`,
+//! parsed back by the repository publisher), in package specs such as
+//! `jq>=1.6`, and in paths on case-insensitive APFS volumes. A valid name is
+//! lowercase ASCII letters, digits, `.`, `_`, `+` and `-`, starts with a
+//! letter or digit, does not end with `.` or `-`, has no version after a
+//! `-` (`foo-1.2.3`), and is at most [`MAX_NAME_LEN`] bytes. Such a name
+//! never contains a version operator, a path separator or whitespace, and
+//! splits unambiguously from the version in a filename, so every one of those
+//! round-trips.
+//!
+//! The same check runs when a recipe is parsed, a package is packed, a
+//! repository is published and a package is installed.
+
+use sps2_errors::PackageError;
+
+/// Longest accepted package name
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Names that belong to sps2 itself
+const RESERVED: &[&str] = &["sbs", "sls", "sps2"];
+
+/// Check `name` against the package name policy
+///
+/// # Errors
+///
+/// Returns [`PackageError::InvalidName`] describing the first rule `name`
+/// breaks.
+pub fn validate_package_name(name: &str) -> Result<(), PackageError> {
+    let reason = if name.is_empty() {
+        Some("name is empty".to_string())
+    } else if name.len() > MAX_NAME_LEN {
+        Some(format!("longer than {MAX_NAME_LEN} characters"))
+    } else if let Some(c) = name.chars().find(char::is_ascii_uppercase) {
+        Some(format!("contains uppercase {c:?}; names are lowercase"))
+    } else if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_lowercase() || c.is_ascii_digit() || "._+-".contains(*c)))
+    {
+        Some(format!("contains {c:?}"))
+    } else if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        Some("must start with a letter or digit".to_string())
+    } else if name.ends_with(['.', '-']) {
+        Some("must not end with `.` or `-`".to_string())
+    } else if crate::filename::contains_version(name) {
+        Some("contains a version after `-`".to_string())
+    } else if RESERVED.contains(&name) {
+        Some("reserved for sps2".to_string())
+    } else {
+        None
+    };
+    match reason {
+        Some(reason) => Err(PackageError::InvalidName {
+            name: name.to_string(),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_names_are_accepted() {
+        for name in [
+            "jq",
+            "libstdc++",
+            "python3.12",
+            "gtk+3",
+            "lib_foo-bar",
+            "7zip",
+        ] {
+            assert!(validate_package_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn names_breaking_filenames_or_specs_are_rejected() {
+        for name in [
+            "", "Jq", "foo bar", "foo/bar", "jq>=1", "-jq", ".jq", "jq-", "jq.", "sps2", "ключ",
+        ] {
+            assert!(validate_package_name(name).is_err(), "{name:?}");
+        }
+        assert!(validate_package_name(&"a".repeat(MAX_NAME_LEN + 1)).is_err());
+    }
+}
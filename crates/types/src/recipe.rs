@@ -228,13 +228,17 @@ pub struct Dependencies {
 
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub build: Vec<String>,
+
+    /// Packages installed alongside by default, but not needed to run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recommends: Vec<String>,
 }
 
 impl Dependencies {
     /// Check if dependencies are empty
     #[must_use]
     pub fn is_empty(&self) -> bool {
-        self.runtime.is_empty() && self.build.is_empty()
+        self.runtime.is_empty() && self.build.is_empty() && self.recommends.is_empty()
     }
 }
 
@@ -1,6 +1,9 @@
 //! Report type definitions for operations
+//!
+//! Reports printed with `--json` carry a `schema_version`; see
+//! [`crate::schema`] for the contract it versions.
 
-use crate::Version;
+use crate::{TestFailurePolicy, Version};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use uuid::Uuid;
@@ -8,12 +11,18 @@ use uuid::Uuid;
 /// Installation report
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct InstallReport {
+    /// Version of the JSON schema the report follows
+    #[serde(default = "first_schema_version")]
+    pub schema_version: u32,
     /// Packages that were installed
     pub installed: Vec<PackageChange>,
     /// Packages that were updated
     pub updated: Vec<PackageChange>,
     /// Packages that were removed
     pub removed: Vec<PackageChange>,
+    /// Installed packages left without a runtime dependency by a forced removal
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub broken_dependencies: Vec<BrokenDependency>,
     /// New state ID
     pub state_id: Uuid,
     /// Total execution time
@@ -23,6 +32,9 @@ pub struct InstallReport {
 /// Build report
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BuildReport {
+    /// Version of the JSON schema the report follows
+    #[serde(default = "first_schema_version")]
+    pub schema_version: u32,
     /// Package that was built
     pub package: String,
     /// Version that was built
@@ -31,6 +43,55 @@ pub struct BuildReport {
     pub output_path: PathBuf,
     /// Build duration
     pub duration_ms: u64,
+    /// Outcome of the recipe's test stage, if it has one
+    #[serde(default)]
+    pub tests: Option<TestResults>,
+}
+
+impl InstallReport {
+    /// Current version of the install report schema
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+impl BuildReport {
+    /// Current version of the build report schema
+    pub const SCHEMA_VERSION: u32 = 1;
+}
+
+/// Reports written before they were versioned follow the first schema
+const fn first_schema_version() -> u32 {
+    1
+}
+
+/// Outcome of a recipe's test stage
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestResults {
+    /// Whether the tests passed, failed or were skipped
+    pub status: TestStatus,
+    /// Policy the tests ran under
+    pub policy: TestFailurePolicy,
+    /// Time spent running the tests
+    pub duration_ms: u64,
+    /// Error from the first failing test command
+    pub failure: Option<String>,
+}
+
+/// Status of a recipe's test stage
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    Skipped,
+}
+
+/// An installed package whose runtime dependencies are being removed
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokenDependency {
+    /// Package that stays installed
+    pub package: String,
+    /// Removed packages it needs at runtime
+    pub needs: Vec<String>,
 }
 
 /// Package change for reports
@@ -0,0 +1,51 @@
+//! JSON Schemas of the reports sps2 prints with `--json`
+//!
+//! `--json` output wraps a report as `{"type": "<kind>", "data": <report>}`;
+//! the schemas describe `data`. Every report carries a `schema_version`,
+//! which is raised when a field is removed, renamed or changes type. New
+//! fields may appear without a new version, so consumers should ignore
+//! fields they do not know.
+
+use crate::{BuildReport, InstallReport};
+
+/// The JSON Schema of one kind of report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReportSchema {
+    /// Name accepted by `sps2 schema dump`
+    pub name: &'static str,
+    /// `type` the report is tagged with in `--json` output
+    pub kind: &'static str,
+    /// `schema_version` of reports following this schema
+    pub version: u32,
+    /// The schema, JSON Schema draft 2020-12
+    pub schema: &'static str,
+}
+
+/// Schemas of every versioned report, by name
+pub const REPORT_SCHEMAS: [ReportSchema; 3] = [
+    ReportSchema {
+        name: "build-report",
+        kind: "BuildReport",
+        version: BuildReport::SCHEMA_VERSION,
+        schema: include_str!("../schemas/build-report.json"),
+    },
+    ReportSchema {
+        name: "install-report",
+        kind: "InstallReport",
+        version: InstallReport::SCHEMA_VERSION,
+        schema: include_str!("../schemas/install-report.json"),
+    },
+    ReportSchema {
+        name: "verification-result",
+        kind: "VerificationResult",
+        // Defined with the verifier, which this crate cannot depend on
+        version: 1,
+        schema: include_str!("../schemas/verification-result.json"),
+    },
+];
+
+/// The schema named `name`
+#[must_use]
+pub fn report_schema(name: &str) -> Option<&'static ReportSchema> {
+    REPORT_SCHEMAS.iter().find(|schema| schema.name == name)
+}
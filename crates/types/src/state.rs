@@ -29,6 +29,9 @@ pub struct StateInfo {
     pub total_size: u64,
     /// Summary of changes from parent (using `ops::OpChange` for change type info)
     pub changes: Vec<OpChange>,
+    /// Snapshot names given to this state
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 /// State transition record